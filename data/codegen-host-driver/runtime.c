@@ -0,0 +1,66 @@
+// A simulated accelerator runtime, for testing host drivers. Buffers live in
+// host memory, and only `compute relu` kernels are supported, which are run as
+// soon as they're enqueued.
+#include <assert.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "glenside_runtime.h"
+
+void *glenside_alloc(size_t bytes) { return malloc(bytes); }
+
+void glenside_free(void *buffer) { free(buffer); }
+
+void glenside_fill(void *buffer, const void *value, size_t value_bytes,
+                   size_t bytes) {
+  size_t i;
+  for (i = 0; i < bytes; i += value_bytes) {
+    memcpy((char *)buffer + i, value, value_bytes);
+  }
+}
+
+void glenside_write(void *buffer, size_t offset, const void *data,
+                    size_t bytes) {
+  memcpy((char *)buffer + offset, data, bytes);
+}
+
+void glenside_read(const void *buffer, size_t offset, void *data,
+                   size_t bytes) {
+  memcpy(data, (const char *)buffer + offset, bytes);
+}
+
+// The offset, in elements, of the `index`th element of `access`, in row-major
+// order.
+static long element_offset(const glenside_access *access, size_t index) {
+  long offset = access->offset;
+  int axis;
+  for (axis = access->ndim - 1; axis >= 0; --axis) {
+    offset += (long)(index % access->shape[axis]) * access->strides[axis];
+    index /= access->shape[axis];
+  }
+  return offset;
+}
+
+void glenside_enqueue(const char *kernel, int node, void **buffers,
+                      const glenside_access *reads, int num_reads,
+                      const glenside_access *output) {
+  fprintf(stderr, "Running %s (node %d)\n", kernel, node);
+  assert(strcmp(kernel, "compute relu") == 0);
+  assert(num_reads == 1);
+
+  size_t elements = 1;
+  int axis;
+  for (axis = 0; axis < output->ndim; ++axis) {
+    elements *= output->shape[axis];
+  }
+  const float *in = buffers[reads[0].buffer];
+  float *out = buffers[output->buffer];
+  size_t i;
+  for (i = 0; i < elements; ++i) {
+    float value = in[element_offset(&reads[0], i)];
+    out[element_offset(output, i)] = value > 0 ? value : 0;
+  }
+}
+
+void glenside_wait(void) {}
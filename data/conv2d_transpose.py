@@ -0,0 +1,43 @@
+import numpy as np
+import tvm
+from tvm import relay
+
+filter_x, filter_y = 3, 3
+i, o = 4, 3
+image_x, image_y = 8, 8
+batch = 1
+strides = (2, 2)
+padding = (1, 1, 1, 1)
+output_padding = (1, 1)
+
+# Transposed convolution weights are in layout IOHW.
+filters = np.random.rand(i, o, filter_x, filter_y).astype('float32')
+activations = np.random.rand(batch, i, image_x, image_y).astype('float32')
+
+filters_var = relay.var('filters',
+                        shape=(i, o, filter_x, filter_y),
+                        dtype='float32')
+activations_var = relay.var('activations',
+                            shape=(batch, i, image_x, image_y),
+                            dtype='float32')
+
+module = tvm.IRModule.from_expr(
+    relay.Function([activations_var, filters_var],
+                   relay.nn.conv2d_transpose(activations_var,
+                                             filters_var,
+                                             channels=o,
+                                             kernel_size=(filter_x, filter_y),
+                                             strides=strides,
+                                             padding=padding,
+                                             output_padding=output_padding)))
+
+ex = relay.create_executor(mod=module)
+
+result = ex.evaluate()(activations, filters).asnumpy()
+
+with open('conv2d_transpose_filters.npy', 'wb') as file:
+    np.save(file, filters)
+with open('conv2d_transpose_activations.npy', 'wb') as file:
+    np.save(file, activations)
+with open('conv2d_transpose_result.npy', 'wb') as file:
+    np.save(file, result)
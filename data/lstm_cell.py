@@ -0,0 +1,28 @@
+import numpy as np
+
+batch = 2
+input_size = 4
+hidden_size = 3
+
+
+def sigmoid(x):
+    return 1 / (1 + np.exp(-x))
+
+
+x = np.random.rand(batch, input_size).astype('float32')
+h = np.random.rand(batch, hidden_size).astype('float32')
+c = np.random.rand(batch, hidden_size).astype('float32')
+# Gates are stacked in the order input, forget, cell, output, as in PyTorch's
+# LSTMCell.
+w_ih = (np.random.rand(4 * hidden_size, input_size) - 0.5).astype('float32')
+w_hh = (np.random.rand(4 * hidden_size, hidden_size) - 0.5).astype('float32')
+
+gates = x @ w_ih.T + h @ w_hh.T
+i, f, g, o = np.split(gates, 4, axis=1)
+c_next = sigmoid(f) * c + sigmoid(i) * np.tanh(g)
+h_next = sigmoid(o) * np.tanh(c_next)
+
+for name, array in [('x', x), ('h', h), ('c', c), ('w_ih', w_ih),
+                    ('w_hh', w_hh), ('h_next', h_next), ('c_next', c_next)]:
+    with open('lstm_cell_{}.npy'.format(name), 'wb') as file:
+        np.save(file, array.astype('float32'))
@@ -0,0 +1,32 @@
+import numpy as np
+import torch
+import torch.nn.functional as F
+
+# Max pools an input whose item axes don't fit a whole number of strides under
+# each window convention (see WindowConvention): PyTorch's ceil_mode=False and
+# ceil_mode=True, and TensorFlow's padding="SAME", which PyTorch's max_pool2d
+# doesn't support, and so is written as explicit padding followed by a pool.
+shape = (1, 2, 9, 7)
+kernel_size = (4, 3)
+stride = (2, 3)
+
+x = torch.rand(*shape)
+
+floor = F.max_pool2d(x, kernel_size, stride, ceil_mode=False)
+ceil = F.max_pool2d(x, kernel_size, stride, ceil_mode=True)
+
+# TensorFlow pads as evenly as possible on both sides, with any odd element of
+# padding at the end. Max pooling ignores padding, which padding with -inf
+# reproduces.
+padding = []
+for n, k, s in zip(shape[2:], kernel_size, stride):
+    needed = max((-(-n // s) - 1) * s + k - n, 0)
+    padding.append((needed // 2, needed - needed // 2))
+# F.pad takes the padding of the last axis first.
+same = F.max_pool2d(F.pad(x, (*padding[1], *padding[0]), value=float('-inf')),
+                    kernel_size, stride)
+
+for name, array in [('input', x), ('floor', floor), ('ceil', ceil),
+                    ('same', same)]:
+    with open('window_conventions_{}.npy'.format(name), 'wb') as file:
+        np.save(file, array.numpy().astype('float32'))
@@ -159,6 +159,11 @@ fn main() {
             ),
     );
 
+    app = app.subcommand(
+        SubCommand::with_name("repl")
+            .about("Analyzes and interprets expressions interactively; type :help for commands"),
+    );
+
     let matches = app.get_matches();
 
     #[allow(unused_variables)]
@@ -204,6 +209,7 @@ fn main() {
 
             let mut egraph = EGraph::new(MyAnalysis {
                 name_to_shape: env.clone(),
+                shape_cache: None,
             });
             let id = egraph.add_expr(&expr);
 
@@ -264,7 +270,7 @@ fn main() {
                     continue;
                 }
                 if value == "glenside" {
-                    rws.push(glenside::language::rewrites::systolic_array());
+                    rws.push(glenside::language::rewrites::systolic_array().rewrite);
                 } else {
                     let parsed = value
                         .chars()
@@ -275,9 +281,12 @@ fn main() {
                         .map(|s| s.parse::<usize>().unwrap())
                         .collect::<Vec<_>>();
                     assert_eq!(parsed.len(), 2);
-                    rws.push(glenside::language::rewrites::systolic_array_with_blocking(
-                        parsed[0], parsed[1],
-                    ));
+                    rws.push(
+                        glenside::language::rewrites::systolic_array_with_blocking(
+                            parsed[0], parsed[1],
+                        )
+                        .rewrite,
+                    );
                 }
                 added.insert(value);
             }
@@ -357,6 +366,7 @@ fn main() {
                 &result.variables,
                 EGraph::new(MyAnalysis {
                     name_to_shape: env.clone(),
+                    shape_cache: None,
                 }),
             );
             // Important! Get the new ID of the root class.
@@ -433,8 +443,9 @@ fn main() {
             .unwrap();
             let code = format!("{}\n{}", kernels, code);
 
-            let json = glenside::hw_design_language::design_to_json(
+            let json = glenside::hw_design_language::design_report(
                 &glenside::hw_design_language::HardwareDesign { atoms: hw_atoms },
+                &glenside::codegen::find_fallbacks(&expr, &classes),
             );
 
             std::fs::File::create(matches.value_of("OUT_CODE_FILEPATH").unwrap())
@@ -478,6 +489,7 @@ fn main() {
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: shapes_map.clone(),
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&glenside_expr);
 
@@ -547,7 +559,7 @@ fn main() {
                 continue;
             }
             if value == "glenside" {
-                rws.push(glenside::language::rewrites::systolic_array());
+                rws.push(glenside::language::rewrites::systolic_array().rewrite);
             } else {
                 let parsed = value
                     .chars()
@@ -558,9 +570,12 @@ fn main() {
                     .map(|s| s.parse::<usize>().unwrap())
                     .collect::<Vec<_>>();
                 assert_eq!(parsed.len(), 2);
-                rws.push(glenside::language::rewrites::systolic_array_with_blocking(
-                    parsed[0], parsed[1],
-                ));
+                rws.push(
+                    glenside::language::rewrites::systolic_array_with_blocking(
+                        parsed[0], parsed[1],
+                    )
+                    .rewrite,
+                );
             }
             added.insert(value);
         }
@@ -606,6 +621,7 @@ fn main() {
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: shapes_map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&extracted_expr);
         let (hw_id_map, hw_atoms) = if let Some(val) = matches.value_of("find-monolithic-designs") {
@@ -627,6 +643,7 @@ fn main() {
         let mut found_vars = glenside::codegen::find_vars(&egraph, id);
         found_vars.sort();
 
+        let worklist = glenside::codegen::generate_worklist_for_codegen(&egraph, id);
         let code = glenside::codegen::codegen(
             &egraph,
             id,
@@ -638,12 +655,13 @@ fn main() {
                 r#"__attribute__ ((section (".uninitialized"))) __attribute__ ((aligned (256)))"#
             },
             &found_vars.iter().map(AsRef::as_ref).collect(),
-            &glenside::codegen::generate_worklist_for_codegen(&egraph, id),
+            &worklist,
             true,
         );
 
-        let json = glenside::hw_design_language::design_to_json(
+        let json = glenside::hw_design_language::design_report(
             &glenside::hw_design_language::HardwareDesign { atoms: hw_atoms },
+            &glenside::codegen::find_fallbacks(&egraph, &worklist),
         );
 
         std::fs::File::create(matches.value_of("OUT_CODE_FILEPATH").unwrap())
@@ -654,6 +672,11 @@ fn main() {
             .unwrap()
             .write_all(serde_json::to_string_pretty(&json).unwrap().as_bytes())
             .unwrap();
+    } else if matches.subcommand_matches("repl").is_some() {
+        let stdin = std::io::stdin();
+        glenside::language::repl::Repl::default()
+            .run(stdin.lock(), std::io::stdout())
+            .unwrap();
     } else {
         todo!()
     }
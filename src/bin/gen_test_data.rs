@@ -0,0 +1,169 @@
+//! Regenerates the `.npy` fixtures under `data/` used by the interpreter
+//! tests, without needing Python, NumPy, or TVM.
+//!
+//! Inputs are drawn uniformly from [0, 1) by a seeded generator, so a given
+//! seed always produces the same fixtures; results are computed with the
+//! naive reference implementations below, which share no code with the
+//! interpreter they're used to test.
+//!
+//! ```sh
+//! cargo run --bin gen_test_data -- [--seed <seed>] [--out <directory>] [<fixture>...]
+//! ```
+//!
+//! With no fixtures named, all of them (`conv2d`, `max_pool2d`, and
+//! `single_matrix_multiply`) are generated. The default seed is 0 and the
+//! default directory is `data`; the fixtures committed there are this tool's
+//! output with the defaults, so running it on a clean tree changes nothing.
+
+use ndarray::{ArrayD, IxDyn};
+use ndarray_npy::{write_npy, WritableElement};
+use std::path::{Path, PathBuf};
+
+/// A SplitMix64 generator: tiny, and stable across versions of Rust and of
+/// any crates, so that fixtures can be regenerated exactly.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1).
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+fn random_f32(rng: &mut SplitMix64, shape: &[usize]) -> ArrayD<f32> {
+    ArrayD::from_shape_simple_fn(shape, || rng.next_f64() as f32)
+}
+
+fn random_f64(rng: &mut SplitMix64, shape: &[usize]) -> ArrayD<f64> {
+    ArrayD::from_shape_simple_fn(shape, || rng.next_f64())
+}
+
+/// Valid (unpadded), stride-1 convolution of CHW `activations` with OIHW
+/// `filters`, producing an OHW result.
+fn conv2d(activations: &ArrayD<f32>, filters: &ArrayD<f32>) -> ArrayD<f32> {
+    let (c, h, w) = match activations.shape() {
+        &[c, h, w] => (c, h, w),
+        _ => panic!("Expected CHW activations"),
+    };
+    let (o, kh, kw) = match filters.shape() {
+        &[o, fc, kh, kw] if fc == c => (o, kh, kw),
+        _ => panic!("Expected OIHW filters with {} input channels", c),
+    };
+    ArrayD::from_shape_fn(IxDyn(&[o, h - kh + 1, w - kw + 1]), |index| {
+        let (o, y, x) = (index[0], index[1], index[2]);
+        let mut sum = 0f32;
+        for c in 0..c {
+            for ky in 0..kh {
+                for kx in 0..kw {
+                    sum += activations[[c, y + ky, x + kx].as_ref()]
+                        * filters[[o, c, ky, kx].as_ref()];
+                }
+            }
+        }
+        sum
+    })
+}
+
+/// Max pooling of CHW `activations` with `size`×`size` windows, `stride`
+/// apart.
+fn max_pool2d(activations: &ArrayD<f32>, size: usize, stride: usize) -> ArrayD<f32> {
+    let (c, h, w) = match activations.shape() {
+        &[c, h, w] => (c, h, w),
+        _ => panic!("Expected CHW activations"),
+    };
+    ArrayD::from_shape_fn(
+        IxDyn(&[c, (h - size) / stride + 1, (w - size) / stride + 1]),
+        |index| {
+            let (c, y, x) = (index[0], index[1] * stride, index[2] * stride);
+            let mut max = std::f32::NEG_INFINITY;
+            for ky in 0..size {
+                for kx in 0..size {
+                    max = max.max(activations[[c, y + ky, x + kx].as_ref()]);
+                }
+            }
+            max
+        },
+    )
+}
+
+fn matmul(a: &ArrayD<f64>, b: &ArrayD<f64>) -> ArrayD<f64> {
+    let (n, k, m) = match (a.shape(), b.shape()) {
+        (&[n, k], &[k2, m]) if k == k2 => (n, k, m),
+        _ => panic!("Expected matrices with matching inner dimensions"),
+    };
+    ArrayD::from_shape_fn(IxDyn(&[n, m]), |index| {
+        (0..k)
+            .map(|i| a[[index[0], i].as_ref()] * b[[i, index[1]].as_ref()])
+            .sum()
+    })
+}
+
+fn save<DataType: WritableElement>(out: &Path, name: &str, array: &ArrayD<DataType>) {
+    let path = out.join(format!("{}.npy", name));
+    write_npy(&path, array)
+        .unwrap_or_else(|e| panic!("Could not write {}: {:?}", path.display(), e));
+    println!("wrote {} {:?}", path.display(), array.shape());
+}
+
+fn main() {
+    let mut seed = 0u64;
+    let mut out = PathBuf::from("data");
+    let mut fixtures = Vec::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => {
+                seed = args
+                    .next()
+                    .and_then(|s| s.parse().ok())
+                    .expect("--seed expects a number")
+            }
+            "--out" => out = PathBuf::from(args.next().expect("--out expects a directory")),
+            _ => fixtures.push(arg),
+        }
+    }
+    if fixtures.is_empty() {
+        fixtures = vec!["conv2d", "max_pool2d", "single_matrix_multiply"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+    }
+    std::fs::create_dir_all(&out)
+        .unwrap_or_else(|e| panic!("Could not create {}: {:?}", out.display(), e));
+
+    for fixture in fixtures {
+        // Each fixture gets its own generator, so that fixtures don't change
+        // depending on which others are generated.
+        let mut rng = SplitMix64(seed);
+        match fixture.as_str() {
+            "conv2d" => {
+                let filters = random_f32(&mut rng, &[8, 4, 3, 3]);
+                let activations = random_f32(&mut rng, &[4, 32, 32]);
+                save(&out, "conv2d_filters", &filters);
+                save(&out, "conv2d_activations", &activations);
+                save(&out, "conv2d_result", &conv2d(&activations, &filters));
+            }
+            "max_pool2d" => {
+                let activations = random_f32(&mut rng, &[4, 32, 32]);
+                save(&out, "max_pool2d_activations", &activations);
+                save(&out, "max_pool2d_result", &max_pool2d(&activations, 2, 2));
+            }
+            "single_matrix_multiply" => {
+                let a = random_f64(&mut rng, &[64, 64]);
+                let b = random_f64(&mut rng, &[64, 64]);
+                save(&out, "single_matrix_multiply_input_a", &a);
+                save(&out, "single_matrix_multiply_input_b", &b);
+                save(&out, "single_matrix_multiply_output", &matmul(&a, &b));
+            }
+            other => panic!("Unknown fixture {}", other),
+        }
+    }
+}
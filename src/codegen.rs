@@ -1,10 +1,14 @@
 use crate::hw_design_language::*;
+use crate::language::materialization::Materialization;
+use crate::language::provenance::Provenance;
+use crate::language::schedule::{AccessDescriptor, Buffer, BufferKind, ExecutionSchedule};
 use crate::language::MyAnalysis;
 use crate::language::MyAnalysisData;
 use crate::language::RelayActivationLayout;
 use crate::language::{Language, PadType, RelayOperator};
 use egg::EGraph;
 use egg::Id;
+use egg::Language as LanguageTrait;
 use itertools::Itertools;
 use log::warn;
 use ndarray::Array;
@@ -28,6 +32,40 @@ extern void rtml_systolic_array_weight_stationary(
   int batch);
 ";
 
+/// The API of the accelerator runtime which host drivers (see
+/// [`codegen_host_driver`]) are written against. The offsets and sizes
+/// passed to `glenside_write`, `glenside_read`, and `glenside_fill` are in
+/// bytes, while a `glenside_access` describes a read or write of a buffer in
+/// elements, as an [`AccessDescriptor`] does.
+pub static HOST_RUNTIME_API: &str = "
+#ifndef GLENSIDE_RUNTIME_API
+#define GLENSIDE_RUNTIME_API
+#include <stddef.h>
+
+typedef struct {
+  int buffer;
+  size_t offset;
+  int ndim;
+  int access_axis;
+  const size_t *shape;
+  const long *strides;
+} glenside_access;
+
+extern void *glenside_alloc(size_t bytes);
+extern void glenside_free(void *buffer);
+extern void glenside_fill(void *buffer, const void *value, size_t value_bytes,
+                          size_t bytes);
+extern void glenside_write(void *buffer, size_t offset, const void *data,
+                           size_t bytes);
+extern void glenside_read(const void *buffer, size_t offset, void *data,
+                          size_t bytes);
+extern void glenside_enqueue(const char *kernel, int node, void **buffers,
+                             const glenside_access *reads, int num_reads,
+                             const glenside_access *output);
+extern void glenside_wait(void);
+#endif
+";
+
 /// Gives the signature of a C array given the datatype, name, and shape. Useful
 /// for declaring arrays and array-type function arguments.
 /// ```
@@ -157,7 +195,7 @@ pub fn c_assignment_string<A: std::fmt::Display>(
 /// map.insert("t2".to_string(), vec![32, 32]);
 /// map.insert("t3".to_string(), vec![32, 32]);
 ///
-/// let mut egraph = EGraph::new(MyAnalysis { name_to_shape: map, name_to_dtype: HashMap::default() });
+/// let mut egraph = EGraph::new(MyAnalysis { name_to_shape: map, name_to_dtype: HashMap::default(), shape_cache: None });
 /// egraph.add_expr(&expr);
 ///
 /// let (hw_map, hw_design) = create_hardware_design_monolithic(&egraph, (32, 32));
@@ -170,6 +208,7 @@ pub fn c_assignment_string<A: std::fmt::Display>(
 ///         config:
 ///             AtomConfig::SystolicArrayWeightStationary(SystolicArrayWeightStationaryParams {
 ///                 dtype: DType::Fp32,
+///                 accumulator_dtype: DType::Fp32,
 ///                 rows: 32,
 ///                 cols: 32,
 ///             }),
@@ -194,6 +233,7 @@ pub fn create_hardware_design_monolithic(
         config: AtomConfig::SystolicArrayWeightStationary(SystolicArrayWeightStationaryParams {
             // TODO(@gussmith23) hardcoded datatype
             dtype: DType::Fp32,
+            accumulator_dtype: DType::Fp32.default_accumulator(),
             rows: row,
             cols: col,
         }),
@@ -280,6 +320,7 @@ pub fn create_hardware_design_no_sharing(expr: &Expr) -> (HashMap<Id, usize>, Ve
                         SystolicArrayWeightStationaryParams {
                             // TODO(@gussmith23) hardcoded datatype
                             dtype: DType::Fp32,
+                            accumulator_dtype: DType::Fp32.default_accumulator(),
                             rows: usize::try_from(*row).unwrap(),
                             cols: usize::try_from(*col).unwrap(),
                         },
@@ -318,7 +359,8 @@ pub fn find_vars(expr: &Expr, id: Id) -> Vec<String> {
             | Language::List(ids)
             | Language::Shape(ids)
             | Language::AcceleratorCall(ids)
-            | Language::ConstructTuple(ids) => {
+            | Language::ConstructTuple(ids)
+            | Language::Outputs(ids) => {
                 for id in ids.iter() {
                     find_vars_recursive_helper(set, expr, *id);
                 }
@@ -331,10 +373,19 @@ pub fn find_vars(expr: &Expr, id: Id) -> Vec<String> {
             }
             // [Id; 2]
             &Language::Access(ids)
+            | &Language::BatchMatmul(ids)
+            | &Language::Dropout(ids)
             | &Language::AccessTranspose(ids)
             | &Language::AccessReshape(ids)
             | &Language::ShapeInsertAxis(ids)
             | &Language::ShapeRemoveAxis(ids)
+            | &Language::ShapeConcat(ids)
+            | &Language::ShapeDim(ids)
+            | &Language::UsizeAdd(ids)
+            | &Language::UsizeSub(ids)
+            | &Language::UsizeMul(ids)
+            | &Language::UsizeDiv(ids)
+            | &Language::TensorDecl(ids)
             | &Language::AccessShape(ids)
             | &Language::AccessSqueeze(ids)
             | &Language::TupleGetItem(ids) => {
@@ -343,38 +394,63 @@ pub fn find_vars(expr: &Expr, id: Id) -> Vec<String> {
                 }
             }
             // [Id; 3]
-            &Language::AccessConcatenate(ids) | &Language::AccessWindows(ids) => {
+            &Language::AccessConcatenate(ids)
+            | &Language::Unroll(ids)
+            | &Language::AccessWindows(ids)
+            | &Language::BiasAdd(ids)
+            | &Language::AdaptivePool2d(ids) => {
                 for id in ids.iter() {
                     find_vars_recursive_helper(set, expr, *id);
                 }
             }
+            &Language::Compute([_compute_type_id, access_id])
+            | &Language::ComputeWithAccumulator([_compute_type_id, _, access_id]) => {
+                find_vars_recursive_helper(set, expr, access_id);
+            }
             // [Id; 4]
             &Language::SystolicArray(ids)
             | &Language::SystolicArrayWithBlocking(ids)
-            | &Language::AccessSlice(ids) => {
+            | &Language::AccessSlice(ids)
+            | &Language::LayerNorm(ids) => {
                 for id in ids.iter() {
                     find_vars_recursive_helper(set, expr, *id);
                 }
             }
             // [Id; 5]
-            &Language::AccessPad(ids) => {
+            &Language::AccessPad(ids)
+            | &Language::AccessWindowsWithConvention(ids)
+            | &Language::Conv1d(ids)
+            | &Language::Conv2d(ids)
+            | &Language::Conv3d(ids)
+            | &Language::Conv2dTranspose(ids)
+            | &Language::GroupNorm(ids) => {
                 for id in ids.iter() {
                     find_vars_recursive_helper(set, expr, *id);
                 }
             }
             &Language::NotNanFloat64(_) => {}
-            &Language::Num(_) | &Language::PadType(_) => (),
+            &Language::Num(_)
+            | &Language::PadType(_)
+            | &Language::WindowConvention(_)
+            | &Language::RoundingMode(_) => (),
             &Language::Literal(_)
             | &Language::SystolicArrayConv2dIm2colNchwOihwWithBlocking(_)
             | &Language::SystolicArrayConv2dIm2colNhwcHwioWithBlocking(_)
             | &Language::SystolicArrayConv2dNchwOihwWithBlocking(_)
             | &Language::SystolicArrayConv2dNhwcHwioWithBlocking(_)
+            | &Language::SystolicArrayWithActivation(_)
+            | &Language::PoolingUnit(_)
+            | &Language::ActivationUnit(_)
+            | &Language::VectorAlu(_)
+            | &Language::SparseSystolicArray(_)
             | &Language::AccessLiteral(_)
             | &Language::AccessBroadcast(_)
             | &Language::AccessInsertAxis(_)
+            | &Language::AccessReverse(_)
             | &Language::AccessPair(_)
             | Language::ComputeType(_)
-            | &Language::Compute(_)
+            | &Language::Cast(_)
+            | &Language::Requantize(_)
             | &Language::AccessCartesianProduct(_)
             | &Language::SliceShape(_)
             | &Language::AccessShiftRight(_) => panic!("{:#?} not implemented", expr[id].nodes[0]),
@@ -421,19 +497,29 @@ pub fn generate_worklist_for_codegen(expr: &Expr, id: Id) -> Vec<Id> {
             | Language::Shape(ids)
             | Language::List(ids)
             | Language::AcceleratorCall(ids)
-            | Language::ConstructTuple(ids) => {
+            | Language::ConstructTuple(ids)
+            | Language::Outputs(ids) => {
                 for id in ids.iter() {
                     helper(worklist, expr, *id);
                 }
             }
             // [Id; 2]
             &Language::Access(ids)
+            | &Language::BatchMatmul(ids)
+            | &Language::Dropout(ids)
             | &Language::AccessTranspose(ids)
             | &Language::AccessShape(ids)
             | &Language::ConstantTensor(ids)
             | &Language::AccessReshape(ids)
             | &Language::ShapeInsertAxis(ids)
             | &Language::ShapeRemoveAxis(ids)
+            | &Language::ShapeConcat(ids)
+            | &Language::ShapeDim(ids)
+            | &Language::UsizeAdd(ids)
+            | &Language::UsizeSub(ids)
+            | &Language::UsizeMul(ids)
+            | &Language::UsizeDiv(ids)
+            | &Language::TensorDecl(ids)
             | &Language::AccessSqueeze(ids)
             | &Language::TupleGetItem(ids) => {
                 for id in ids.iter() {
@@ -441,21 +527,36 @@ pub fn generate_worklist_for_codegen(expr: &Expr, id: Id) -> Vec<Id> {
                 }
             }
             // [Id; 3]
-            &Language::AccessConcatenate(ids) | &Language::AccessWindows(ids) => {
+            &Language::AccessConcatenate(ids)
+            | &Language::Unroll(ids)
+            | &Language::AccessWindows(ids)
+            | &Language::BiasAdd(ids)
+            | &Language::AdaptivePool2d(ids) => {
                 for id in ids.iter() {
                     helper(worklist, expr, *id);
                 }
             }
+            &Language::Compute([_compute_type_id, access_id])
+            | &Language::ComputeWithAccumulator([_compute_type_id, _, access_id]) => {
+                helper(worklist, expr, access_id);
+            }
             // [Id; 4]
             &Language::SystolicArray(ids)
             | &Language::SystolicArrayWithBlocking(ids)
-            | &Language::AccessSlice(ids) => {
+            | &Language::AccessSlice(ids)
+            | &Language::LayerNorm(ids) => {
                 for id in ids.iter() {
                     helper(worklist, expr, *id);
                 }
             }
             // [Id; 5]
-            &Language::AccessPad(ids) => {
+            &Language::AccessPad(ids)
+            | &Language::AccessWindowsWithConvention(ids)
+            | &Language::Conv1d(ids)
+            | &Language::Conv2d(ids)
+            | &Language::Conv3d(ids)
+            | &Language::Conv2dTranspose(ids)
+            | &Language::GroupNorm(ids) => {
                 for id in ids.iter() {
                     helper(worklist, expr, *id);
                 }
@@ -469,19 +570,28 @@ pub fn generate_worklist_for_codegen(expr: &Expr, id: Id) -> Vec<Id> {
             | &Language::NotNanFloat64(_)
             | &Language::Num(_)
             | &Language::DataType(_)
-            | &Language::PadType(_) => (),
+            | &Language::PadType(_)
+            | &Language::WindowConvention(_)
+            | &Language::RoundingMode(_) => (),
 
             &Language::Literal(_)
             | &Language::SystolicArrayConv2dIm2colNchwOihwWithBlocking(_)
             | &Language::SystolicArrayConv2dIm2colNhwcHwioWithBlocking(_)
             | &Language::SystolicArrayConv2dNchwOihwWithBlocking(_)
             | &Language::SystolicArrayConv2dNhwcHwioWithBlocking(_)
+            | &Language::SystolicArrayWithActivation(_)
+            | &Language::PoolingUnit(_)
+            | &Language::ActivationUnit(_)
+            | &Language::VectorAlu(_)
+            | &Language::SparseSystolicArray(_)
             | &Language::AccessLiteral(_)
             | &Language::AccessBroadcast(_)
             | &Language::AccessInsertAxis(_)
+            | &Language::AccessReverse(_)
             | &Language::AccessPair(_)
             | Language::ComputeType(_)
-            | &Language::Compute(_)
+            | &Language::Cast(_)
+            | &Language::Requantize(_)
             | &Language::AccessCartesianProduct(_)
             | &Language::SliceShape(_)
             | &Language::AccessShiftRight(_) => panic!("{:#?} not implemented", expr[id].nodes[0]),
@@ -496,9 +606,33 @@ pub fn generate_worklist_for_codegen(expr: &Expr, id: Id) -> Vec<Id> {
     worklist
 }
 
+/// The eclasses in `worklist` which no atom computes, and which [`codegen`]
+/// thus computes in software, with loop nests: the `compute`s (and
+/// `compute-with-accumulator`s) extraction left unmapped. These are listed in
+/// design reports (see [`design_report`]), as they're likely to dominate the
+/// program's run time.
+pub fn find_fallbacks(expr: &Expr, worklist: &[Id]) -> Vec<Fallback> {
+    worklist
+        .iter()
+        .filter_map(|id| {
+            let node = &expr[*id].nodes[0];
+            match node {
+                &Language::Compute([compute_type_id, _])
+                | &Language::ComputeWithAccumulator([compute_type_id, _, _]) => Some(Fallback {
+                    eclass: usize::from(*id),
+                    op: format!("{} {}", node.display_op(), expr[compute_type_id].nodes[0]),
+                }),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
 /// Returns c code.
 ///
 /// args: The signature will be `void <function_name>(float * out, float * <arg0>...)`
+/// If `id` contains an `outputs` node, there is one output argument per
+/// output, named `out_0`, `out_1`, etc., in place of `out`.
 /// uninitialized_allocations_prefix: The prefix to use for buffer allocations
 /// that do not need to be initialized. In the future, once we have literals in
 /// the program, we will need to also include an initialized_allocations_prefix.
@@ -520,13 +654,306 @@ pub fn codegen(
     args: &Vec<&str>,
     worklist: &Vec<Id>,
     assert_only_one_enode_per_eclass: bool,
+) -> String {
+    codegen_with_provenance(
+        expr,
+        id,
+        hw_map,
+        function_name,
+        uninitialized_allocations_prefix,
+        args,
+        worklist,
+        assert_only_one_enode_per_eclass,
+        &Provenance::default(),
+    )
+}
+
+/// Like [`codegen`], but the code generated for each eclass is preceded by a
+/// `// provenance: <labels>` comment listing the eclass's labels in
+/// `provenance` (see [`Provenance::add_expr`]), if it has any.
+pub fn codegen_with_provenance(
+    expr: &Expr,
+    id: Id,
+    hw_map: &HashMap<Id, usize>,
+    function_name: &str,
+    uninitialized_allocations_prefix: &str,
+    args: &Vec<&str>,
+    worklist: &Vec<Id>,
+    assert_only_one_enode_per_eclass: bool,
+    provenance: &Provenance,
+) -> String {
+    codegen_impl(
+        expr,
+        id,
+        hw_map,
+        function_name,
+        uninitialized_allocations_prefix,
+        args,
+        worklist,
+        assert_only_one_enode_per_eclass,
+        provenance,
+        false,
+    )
+}
+
+/// Like [`codegen`], but emits a "kernel library": eclasses which generate
+/// the same code, up to the names of their input and output buffers (see
+/// [`kernel_key`]), share a single C function, `<function_name>_kernel_<n>`,
+/// which is called once per eclass. Layers which map to the same
+/// shape-specialized access pattern thus don't duplicate its loops. Eclasses
+/// whose code appears only once are still generated inline.
+pub fn codegen_kernel_library(
+    expr: &Expr,
+    id: Id,
+    hw_map: &HashMap<Id, usize>,
+    function_name: &str,
+    uninitialized_allocations_prefix: &str,
+    args: &Vec<&str>,
+    worklist: &Vec<Id>,
+    assert_only_one_enode_per_eclass: bool,
+) -> String {
+    codegen_impl(
+        expr,
+        id,
+        hw_map,
+        function_name,
+        uninitialized_allocations_prefix,
+        args,
+        worklist,
+        assert_only_one_enode_per_eclass,
+        &Provenance::default(),
+        true,
+    )
+}
+
+/// A structural hash of the code generated for eclass `id`, along with the
+/// eclass's tensor-valued children, deduplicated, in order. Two eclasses with
+/// the same key generate the same code, given the variables holding their
+/// tensor-valued children; thus, the code can be outlined into a function
+/// taking those variables as arguments.
+///
+/// Tensor-valued children are described by their shapes, and all other
+/// children (axes, shapes, pad types, etc.) by their full structure. The
+/// hardware id of a systolic array is part of its key.
+pub fn kernel_key(expr: &Expr, id: Id, hw_map: &HashMap<Id, usize>) -> (String, Vec<Id>) {
+    fn structure(expr: &Expr, id: Id) -> String {
+        let node = &expr[id].nodes[0];
+        if node.is_leaf() {
+            node.display_op().to_string()
+        } else {
+            format!(
+                "({} {})",
+                node.display_op(),
+                node.children()
+                    .iter()
+                    .map(|child| structure(expr, *child))
+                    .join(" ")
+            )
+        }
+    }
+
+    let node = &expr[id].nodes[0];
+    let mut tensor_children: Vec<Id> = Vec::default();
+    let children = node
+        .children()
+        .iter()
+        .map(|child| match &expr[*child].data {
+            MyAnalysisData::AccessPattern(a) => {
+                let index = match tensor_children.iter().position(|id| id == child) {
+                    Some(index) => index,
+                    None => {
+                        tensor_children.push(*child);
+                        tensor_children.len() - 1
+                    }
+                };
+                format!(
+                    "arg_{}{:?}{:?}",
+                    index,
+                    a.shape.slice(),
+                    a.item_shape.slice()
+                )
+            }
+            _ => structure(expr, *child),
+        })
+        .collect::<Vec<_>>();
+
+    let key = format!(
+        "({} {}){}",
+        node.display_op(),
+        children.join(" "),
+        hw_map
+            .get(&id)
+            .map(|hw_id| format!(" on hardware {}", hw_id))
+            .unwrap_or_default()
+    );
+    (key, tensor_children)
+}
+
+/// Generates the code for eclass `id` as a function named `kernel_name`, whose
+/// arguments are the output buffer, followed by `inputs` (the eclass's
+/// tensor-valued children; see [`kernel_key`]). Returns the function definition
+/// and any declarations the function needs, or [`None`] if the eclass doesn't
+/// generate code writing to a buffer of its own (e.g. if it just reinterprets
+/// its input), in which case there is nothing to outline.
+fn outline_kernel(
+    expr: &Expr,
+    id: Id,
+    inputs: &[Id],
+    kernel_name: &str,
+    uninitialized_allocations_prefix: &str,
+    hw_map: &HashMap<Id, usize>,
+    assert_only_one_enode_per_eclass: bool,
+) -> Option<(String, String)> {
+    let shape = |id: Id| match &expr[id].data {
+        MyAnalysisData::AccessPattern(a) => a.as_vec(),
+        _ => unreachable!(),
+    };
+
+    let mut declarations = String::default();
+    let mut body = String::default();
+    let out_var_name = codegen_helper(
+        expr,
+        id,
+        uninitialized_allocations_prefix,
+        &mut declarations,
+        &mut body,
+        hw_map,
+        |_, child| {
+            let index = inputs
+                .iter()
+                .position(|input| *input == child)
+                .unwrap_or_else(|| {
+                    panic!("eclass {} is not an input of kernel {}", child, kernel_name)
+                });
+            format!("arg_{}", index)
+        },
+        assert_only_one_enode_per_eclass,
+    )?;
+
+    // The output buffer becomes an argument, so we drop its allocation.
+    let out_allocation = c_allocation_string(
+        uninitialized_allocations_prefix,
+        out_var_name.as_str(),
+        shape(id).as_slice(),
+        DType::Fp32,
+    );
+    if body.is_empty() || !declarations.contains(out_allocation.as_str()) {
+        return None;
+    }
+    let declarations = declarations.replacen(out_allocation.as_str(), "", 1);
+
+    let definition = format!(
+        "
+void {}({}) {{
+{}
+}}
+",
+        kernel_name,
+        std::iter::once(c_array_string("out", shape(id).as_slice(), DType::Fp32))
+            .chain(inputs.iter().enumerate().map(|(i, input)| {
+                c_array_string(
+                    format!("arg_{}", i).as_str(),
+                    shape(*input).as_slice(),
+                    DType::Fp32,
+                )
+            }))
+            .join(", "),
+        body.replace(out_var_name.as_str(), "out")
+    );
+
+    Some((definition, declarations))
+}
+
+fn codegen_impl(
+    expr: &Expr,
+    id: Id,
+    hw_map: &HashMap<Id, usize>,
+    function_name: &str,
+    uninitialized_allocations_prefix: &str,
+    args: &Vec<&str>,
+    worklist: &Vec<Id>,
+    assert_only_one_enode_per_eclass: bool,
+    provenance: &Provenance,
+    outline_kernels: bool,
 ) -> String {
     let mut declarations = String::default();
+    let mut kernel_definitions = String::default();
     let mut code = String::default();
     let mut id_to_variable: HashMap<Id, String> = HashMap::default();
 
+    // Only code which is generated more than once is worth outlining.
+    let mut kernel_key_counts: HashMap<String, usize> = HashMap::default();
+    if outline_kernels {
+        for id in worklist {
+            *kernel_key_counts
+                .entry(kernel_key(expr, *id, hw_map).0)
+                .or_default() += 1;
+        }
+    }
+    // The name of the kernel outlined for each key, or None if the key's code
+    // can't be outlined.
+    let mut kernel_names: HashMap<String, Option<String>> = HashMap::default();
+
     for id in worklist {
-        if let Some(var_name) = codegen_helper(
+        let code_start = code.len();
+        let (key, inputs) = kernel_key(expr, *id, hw_map);
+        if outline_kernels && kernel_key_counts[&key] > 1 && !inputs.is_empty() {
+            if !kernel_names.contains_key(&key) {
+                let kernel_name = format!(
+                    "{}_kernel_{}",
+                    function_name,
+                    kernel_names.values().filter(|name| name.is_some()).count()
+                );
+                let kernel = outline_kernel(
+                    expr,
+                    *id,
+                    &inputs,
+                    kernel_name.as_str(),
+                    uninitialized_allocations_prefix,
+                    hw_map,
+                    assert_only_one_enode_per_eclass,
+                );
+                kernel_names.insert(
+                    key.clone(),
+                    kernel.map(|(definition, kernel_declarations)| {
+                        declarations.push_str(kernel_declarations.as_str());
+                        kernel_definitions.push_str(definition.as_str());
+                        kernel_name
+                    }),
+                );
+            }
+        }
+
+        if let Some(Some(kernel_name)) = kernel_names.get(&key) {
+            let out_var_name = format!("{}_eclass_{}_out", kernel_name, id);
+            declarations.push_str(
+                c_allocation_string(
+                    uninitialized_allocations_prefix,
+                    out_var_name.as_str(),
+                    match &expr[*id].data {
+                        MyAnalysisData::AccessPattern(a) => a.as_vec(),
+                        _ => unreachable!(),
+                    }
+                    .as_slice(),
+                    DType::Fp32,
+                )
+                .as_str(),
+            );
+            // The variables may hold buffers of other shapes (e.g. when they
+            // come from an access-reshape), hence the casts.
+            code.push_str(
+                format!(
+                    "{}({});\n",
+                    kernel_name,
+                    std::iter::once(&out_var_name)
+                        .chain(inputs.iter().map(|input| &id_to_variable[input]))
+                        .map(|var| format!("(void*){}", var))
+                        .join(", ")
+                )
+                .as_str(),
+            );
+            id_to_variable.insert(*id, out_var_name);
+        } else if let Some(var_name) = codegen_helper(
             expr,
             *id,
             uninitialized_allocations_prefix,
@@ -543,9 +970,33 @@ pub fn codegen(
         ) {
             id_to_variable.insert(*id, var_name);
         }
+        let labels = provenance.get_eclass(expr, *id);
+        if code.len() > code_start && !labels.is_empty() {
+            code.insert_str(
+                code_start,
+                format!(
+                    "// provenance: {}\n",
+                    labels.into_iter().collect::<Vec<_>>().join(", ")
+                )
+                .as_str(),
+            );
+        }
     }
 
-    let out_symbol = id_to_variable.get(&id).unwrap();
+    // The output arguments, and the eclasses whose values they receive. A
+    // program rooted at an `outputs` node gets one output argument per output.
+    let outputs: Vec<(String, Id)> = match expr[id]
+        .nodes
+        .iter()
+        .find(|node| matches!(node, Language::Outputs(_)))
+    {
+        Some(Language::Outputs(ids)) => ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (format!("out_{}", i), expr.find(*id)))
+            .collect(),
+        _ => vec![("out".to_string(), id)],
+    };
 
     let found_vars = find_vars(expr, id);
     for found_var in found_vars.iter() {
@@ -555,22 +1006,37 @@ pub fn codegen(
             found_var
         );
     }
+    // Each weight gets a single buffer, however many operators share it (see
+    // [`crate::language::shared_weights`]).
+    for (i, arg) in args.iter().enumerate() {
+        assert!(
+            !args[..i].contains(arg),
+            "Found {} more than once in `args`",
+            arg
+        );
+    }
 
     let mut signature = format!("void {}(", function_name);
 
-    // Output comes first
+    // Outputs come first
     signature.push_str(
-        c_array_string(
-            "out",
-            match &expr[id].data {
-                MyAnalysisData::AccessPattern(a) => a.as_vec(),
-                _ => panic!("Assuming output is a tensor for now"),
-            }
-            .as_slice(),
-            // TODO(@gussmith23) Assuming float32 output.
-            DType::Fp32,
-        )
-        .as_str(),
+        outputs
+            .iter()
+            .map(|(name, id)| {
+                c_array_string(
+                    name,
+                    match &expr[*id].data {
+                        MyAnalysisData::AccessPattern(a) => a.as_vec(),
+                        _ => panic!("Assuming output is a tensor for now"),
+                    }
+                    .as_slice(),
+                    // TODO(@gussmith23) Assuming float32 output.
+                    DType::Fp32,
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+            .as_str(),
     );
 
     signature.push_str(
@@ -598,34 +1064,40 @@ pub fn codegen(
     out.push_str(declarations.as_str());
     out.push_str("\n");
 
+    out.push_str(kernel_definitions.as_str());
+
     out.push_str(signature.as_str());
     out.push_str("{");
     out.push_str("\n");
 
     out.push_str(code.as_str());
 
-    // Copy value into "out" variable
-    // Get length of array
-    let length = match &expr[id].data {
-        MyAnalysisData::AccessPattern(a) => a
-            .shape
-            .slice()
-            .iter()
-            .chain(a.item_shape.slice().iter())
-            .product::<usize>(),
-        _ => panic!(),
-    };
-    out.push_str(
-        format!(
-            "
+    // Copy values into the output variables
+    for (name, id) in outputs.iter() {
+        // Get length of array
+        let length = match &expr[*id].data {
+            MyAnalysisData::AccessPattern(a) => a
+                .shape
+                .slice()
+                .iter()
+                .chain(a.item_shape.slice().iter())
+                .product::<usize>(),
+            _ => panic!(),
+        };
+        out.push_str(
+            format!(
+                "
 for (int i = 0; i < {}; i++) {{
-  ((float*)out)[i] = ((float*){})[i];
+  ((float*){})[i] = ((float*){})[i];
 }}
 ",
-            length, out_symbol
-        )
-        .as_str(),
-    );
+                length,
+                name,
+                id_to_variable.get(id).unwrap()
+            )
+            .as_str(),
+        );
+    }
 
     out.push_str("}");
     out.push_str("\n");
@@ -1235,7 +1707,7 @@ for (int {index_var_name} = 0; {index_var_name} < {dim_len}; {index_var_name}++)
                 MyAnalysisData::AccessPattern(a) => a.as_vec(),
                 _ => panic!(),
             };
-            let axis = MyAnalysis::get_usize(axis_id, expr);
+            let axis = MyAnalysis::get_axis(axis_id, expr, original_shape.len());
             let low = MyAnalysis::get_usize(low_id, expr);
             let _high = MyAnalysis::get_usize(high_id, expr);
             let new_shape = match &expr[id].data {
@@ -1324,6 +1796,13 @@ for (int i{i} = 0; i{i} < {limit}; i{i}++) {{",
             assert_eq!(expr[symbol_id].nodes.len(), 1);
             match &expr[symbol_id].nodes[0] {
                 Language::Symbol(s) => Some(s.clone()),
+                &Language::TensorDecl([name_id, _shape_id]) => {
+                    assert_eq!(expr[name_id].nodes.len(), 1);
+                    match &expr[name_id].nodes[0] {
+                        Language::Symbol(s) => Some(s.clone()),
+                        _ => panic!("expected a symbol!"),
+                    }
+                }
                 _ => panic!("expected a symbol!"),
             }
         }
@@ -1445,47 +1924,31 @@ for (int i{i} = 0; i{i} < {limit}; i{i}++) {{",
 
             Some(out_var_name)
         }
-        &Language::Num(u) => Some(format!("{}", u)),
-        &Language::AccessPad([access_id, pad_type_id, axis_id, pad_before_id, pad_after_id]) => {
+        // Computes which extraction left unmapped (see [`find_fallbacks`]) are
+        // computed in software, with loop nests. The code uses <math.h>.
+        &Language::Compute([compute_type_id, access_id]) => {
+            use crate::language::ComputeType;
+
             let access = match &expr[access_id].data {
                 MyAnalysisData::AccessPattern(a) => a,
                 _ => panic!(),
             };
-            let original_shape = access
-                .shape
-                .slice()
-                .iter()
-                .chain(access.item_shape.slice().iter())
-                .cloned()
-                .collect::<Vec<_>>();
-
-            let pad_type = match &expr[pad_type_id].data {
-                MyAnalysisData::PadType(t) => t,
-                _ => panic!(),
-            };
-
-            let axis = MyAnalysis::get_usize(axis_id, expr);
-            let pad_before = MyAnalysis::get_usize(pad_before_id, expr);
-            let _pad_after = MyAnalysis::get_usize(pad_after_id, expr);
-
-            let new_shape = match &expr[id].data {
-                MyAnalysisData::AccessPattern(a) => a
-                    .shape
-                    .slice()
-                    .iter()
-                    .chain(a.item_shape.slice().iter())
-                    .cloned()
-                    .collect::<Vec<_>>(),
+            let compute_type = match &expr[compute_type_id].data {
+                MyAnalysisData::ComputeType(t) => t.clone(),
                 _ => panic!(),
             };
+            warn!(
+                "No atom computes eclass {} (compute {}); falling back to a loop nest",
+                id, compute_type
+            );
+            let num_outputs: usize = access.shape.slice().iter().product();
+            let item_len: usize = access.item_shape.slice().iter().product();
 
-            let access_var_name = get_c_variable_for_id(expr, access_id);
-
-            let pad_out_var_name: String = {
+            let out_var_name: String = {
                 // TODO(@gussmith23) Find a different way to name intermediates
                 // Currently generating random strings. Not great IMO.
                 let out = format!(
-                    "pad_out_{}",
+                    "compute_out_{}",
                     OsRng
                         .sample_iter(&rand::distributions::Alphanumeric)
                         .take(30)
@@ -1495,7 +1958,11 @@ for (int i{i} = 0; i{i} < {limit}; i{i}++) {{",
                     c_allocation_string(
                         uninitialized_allocations_prefix,
                         out.as_str(),
-                        new_shape.as_slice(),
+                        match &expr[id].data {
+                            MyAnalysisData::AccessPattern(a) => a.as_vec(),
+                            _ => panic!(),
+                        }
+                        .as_slice(),
                         DType::Fp32,
                     )
                     .as_str(),
@@ -1503,54 +1970,345 @@ for (int i{i} = 0; i{i} < {limit}; i{i}++) {{",
                 out
             };
 
-            let index_var_names = (0..new_shape.len())
-                .map(|i| format!("i{}", i))
-                .collect::<Vec<_>>();
-
-            // Create a for loop for every dimension in the result shape.
-            for (dim_index, dim_len) in new_shape.iter().enumerate() {
-                let index_var_name = &index_var_names[dim_index];
-                code.push_str(
+            let access_var_name = get_c_variable_for_id(expr, access_id);
+            // The element at `index` of the access.
+            let x = |index: &str| format!("((float*){})[{}]", access_var_name, index);
+
+            let loop_nest = match compute_type {
+                ComputeType::ReLU
+                | ComputeType::Sqrt
+                | ComputeType::Negative
+                | ComputeType::Sigmoid
+                | ComputeType::Tanh
+                | ComputeType::LogicalNot => {
+                    let x = x("i");
                     format!(
                         "
-for (int {i} = 0; {i} < {limit}; {i}++) {{",
-                        i = index_var_name,
-                        limit = dim_len,
+for (int i = 0; i < {len}; i++) {{
+  ((float*){out})[i] = {value};
+}}
+",
+                        len = num_outputs * item_len,
+                        out = out_var_name,
+                        value = match compute_type {
+                            ComputeType::ReLU => format!("fmaxf({}, 0.0f)", x),
+                            ComputeType::Sqrt => format!("sqrtf({})", x),
+                            ComputeType::Negative => format!("-{}", x),
+                            ComputeType::Sigmoid => format!("1.0f / (1.0f + expf(-{}))", x),
+                            ComputeType::Tanh => format!("tanhf({})", x),
+                            ComputeType::LogicalNot => format!("(float)({} == 0.0f)", x),
+                            _ => unreachable!(),
+                        },
                     )
-                    .as_str(),
-                );
-            }
-
-            // Within the innermost for loop: assign to the output at the
-            // correct location.
-            // We have indices i0..i(n-1), for each of the n axes.
-            code.push_str(
-                format!(
+                }
+                ComputeType::ReduceSum
+                | ComputeType::ReduceMax
+                | ComputeType::ReduceMean
+                | ComputeType::DotProduct => {
+                    // The term reduced at index j of the i-th item.
+                    let (num_terms, term) = match compute_type {
+                        ComputeType::DotProduct => {
+                            let tuple_len = access.item_shape[0];
+                            let vec_len = item_len / tuple_len;
+                            (
+                                vec_len,
+                                (0..tuple_len)
+                                    .map(|k| {
+                                        x(format!("i*{} + {}*{} + j", item_len, k, vec_len)
+                                            .as_str())
+                                    })
+                                    .join(" * "),
+                            )
+                        }
+                        _ => (item_len, x(format!("i*{} + j", item_len).as_str())),
+                    };
+                    let (init, combined) = match compute_type {
+                        ComputeType::ReduceMax => ("-INFINITY", format!("fmaxf(acc, {})", term)),
+                        _ => ("0.0f", format!("acc + {}", term)),
+                    };
+                    format!(
+                        "
+for (int i = 0; i < {num_outputs}; i++) {{
+  float acc = {init};
+  for (int j = 0; j < {num_terms}; j++) {{
+    acc = {combined};
+  }}
+  ((float*){out})[i] = {result};
+}}
+",
+                        num_outputs = num_outputs,
+                        init = init,
+                        num_terms = num_terms,
+                        combined = combined,
+                        out = out_var_name,
+                        result = match compute_type {
+                            ComputeType::ReduceMean => format!("acc / {}", item_len),
+                            _ => "acc".to_string(),
+                        },
+                    )
+                }
+                ComputeType::ElementwiseAdd
+                | ComputeType::ElementwiseMul
+                | ComputeType::ElementwiseDiv
+                | ComputeType::LogicalAnd
+                | ComputeType::LogicalOr => {
+                    let tuple_len = access.item_shape[0];
+                    let vec_len = item_len / tuple_len;
+                    let operands = (0..tuple_len)
+                        .map(|k| x(format!("i*{} + {}*{} + j", item_len, k, vec_len).as_str()));
+                    let value = match compute_type {
+                        ComputeType::ElementwiseAdd => operands.join(" + "),
+                        ComputeType::ElementwiseMul => operands.join(" * "),
+                        ComputeType::ElementwiseDiv => operands.join(" / "),
+                        ComputeType::LogicalAnd => format!(
+                            "(float)({})",
+                            operands.map(|x| format!("{} != 0.0f", x)).join(" && ")
+                        ),
+                        ComputeType::LogicalOr => format!(
+                            "(float)({})",
+                            operands.map(|x| format!("{} != 0.0f", x)).join(" || ")
+                        ),
+                        _ => unreachable!(),
+                    };
+                    format!(
+                        "
+for (int i = 0; i < {num_outputs}; i++) {{
+  for (int j = 0; j < {vec_len}; j++) {{
+    ((float*){out})[i*{vec_len} + j] = {value};
+  }}
+}}
+",
+                        num_outputs = num_outputs,
+                        vec_len = vec_len,
+                        out = out_var_name,
+                        value = value,
+                    )
+                }
+                ComputeType::Softmax => format!(
                     "
-if (i{pad_axis} < {pad_before_index} || i{pad_axis} >= {pad_after_index}) {{
-  {out_name}{out_index} = {pad_value};
-}} else {{
-  {out_name}{out_index} = ((float*){in_name})[{in_index}];
+for (int i = 0; i < {num_outputs}; i++) {{
+  float max = -INFINITY;
+  for (int j = 0; j < {item_len}; j++) {{
+    max = fmaxf(max, {x});
+  }}
+  float sum = 0.0f;
+  for (int j = 0; j < {item_len}; j++) {{
+    ((float*){out})[i*{item_len} + j] = expf({x} - max);
+    sum += ((float*){out})[i*{item_len} + j];
+  }}
+  for (int j = 0; j < {item_len}; j++) {{
+    ((float*){out})[i*{item_len} + j] /= sum;
+  }}
 }}
 ",
-                    pad_axis = axis,
-                    pad_before_index = pad_before,
-                    pad_after_index = pad_before + original_shape[axis],
-                    out_name = pad_out_var_name,
-                    out_index = (0..new_shape.len())
-                        .map(|i| format!("[{}]", index_var_names[i],))
-                        .collect::<Vec<_>>()
-                        .join(""),
-                    pad_value = match pad_type {
-                        PadType::ZeroPadding => "0",
-                        PadType::MinPadding => todo!(),
-                    },
-                    in_name = access_var_name,
-                    in_index = (0..new_shape.len())
-                        .map(|i| if i != axis {
-                            format!(
-                                "{}*({})",
-                                index_var_names[i],
+                    num_outputs = num_outputs,
+                    item_len = item_len,
+                    x = x(format!("i*{} + j", item_len).as_str()),
+                    out = out_var_name,
+                ),
+                ComputeType::ReduceMeanVar | ComputeType::SparseDotProduct => {
+                    panic!("No loop nest fallback for compute {}", compute_type)
+                }
+            };
+            code.push_str(loop_nest.as_str());
+
+            Some(out_var_name)
+        }
+        &Language::ComputeWithAccumulator([compute_type_id, dtype_id, access_id]) => {
+            let access = match &expr[access_id].data {
+                MyAnalysisData::AccessPattern(a) => a,
+                _ => panic!(),
+            };
+            let accumulator_type = match &expr[dtype_id].data {
+                MyAnalysisData::DataType(dtype) => DType::from(dtype).to_c_type_string(),
+                _ => panic!(),
+            };
+            let num_outputs: usize = access.shape.slice().iter().product();
+            let item_len: usize = access.item_shape.slice().iter().product();
+
+            let out_var_name: String = {
+                // TODO(@gussmith23) Find a different way to name intermediates
+                // Currently generating random strings. Not great IMO.
+                let out = format!(
+                    "compute_with_accumulator_out_{}",
+                    OsRng
+                        .sample_iter(&rand::distributions::Alphanumeric)
+                        .take(30)
+                        .collect::<String>()
+                );
+                declarations.push_str(
+                    c_allocation_string(
+                        uninitialized_allocations_prefix,
+                        out.as_str(),
+                        match &expr[id].data {
+                            MyAnalysisData::AccessPattern(a) => a.as_vec(),
+                            _ => panic!(),
+                        }
+                        .as_slice(),
+                        DType::Fp32,
+                    )
+                    .as_str(),
+                );
+                out
+            };
+
+            let access_var_name = get_c_variable_for_id(expr, access_id);
+
+            // The term added to the accumulator at index j of the i-th item.
+            let (num_terms, term) = match &expr[compute_type_id].data {
+                MyAnalysisData::ComputeType(crate::language::ComputeType::ReduceSum) => (
+                    item_len,
+                    format!(
+                        "({})((float*){})[i*{} + j]",
+                        accumulator_type, access_var_name, item_len
+                    ),
+                ),
+                MyAnalysisData::ComputeType(crate::language::ComputeType::DotProduct) => {
+                    let tuple_len = access.item_shape[0];
+                    let vec_len = item_len / tuple_len;
+                    (
+                        vec_len,
+                        (0..tuple_len)
+                            .map(|k| {
+                                format!(
+                                    "({})((float*){})[i*{} + {}*{} + j]",
+                                    accumulator_type, access_var_name, item_len, k, vec_len
+                                )
+                            })
+                            .join(" * "),
+                    )
+                }
+                _ => unreachable!(),
+            };
+
+            code.push_str(
+                format!(
+                    "
+for (int i = 0; i < {num_outputs}; i++) {{
+  {accumulator_type} acc = 0;
+  for (int j = 0; j < {num_terms}; j++) {{
+    acc += ({accumulator_type})({term});
+  }}
+  ((float*){out})[i] = (float)acc;
+}}
+",
+                    num_outputs = num_outputs,
+                    accumulator_type = accumulator_type,
+                    num_terms = num_terms,
+                    term = term,
+                    out = out_var_name,
+                )
+                .as_str(),
+            );
+
+            Some(out_var_name)
+        }
+        &Language::Num(u) => Some(format!("{}", u)),
+        &Language::AccessPad([access_id, pad_type_id, axis_id, pad_before_id, pad_after_id]) => {
+            let access = match &expr[access_id].data {
+                MyAnalysisData::AccessPattern(a) => a,
+                _ => panic!(),
+            };
+            let original_shape = access
+                .shape
+                .slice()
+                .iter()
+                .chain(access.item_shape.slice().iter())
+                .cloned()
+                .collect::<Vec<_>>();
+
+            let pad_type = match &expr[pad_type_id].data {
+                MyAnalysisData::PadType(t) => t,
+                _ => panic!(),
+            };
+
+            let axis = MyAnalysis::get_axis(axis_id, expr, original_shape.len());
+            let pad_before = MyAnalysis::get_usize(pad_before_id, expr);
+            let _pad_after = MyAnalysis::get_usize(pad_after_id, expr);
+
+            let new_shape = match &expr[id].data {
+                MyAnalysisData::AccessPattern(a) => a
+                    .shape
+                    .slice()
+                    .iter()
+                    .chain(a.item_shape.slice().iter())
+                    .cloned()
+                    .collect::<Vec<_>>(),
+                _ => panic!(),
+            };
+
+            let access_var_name = get_c_variable_for_id(expr, access_id);
+
+            let pad_out_var_name: String = {
+                // TODO(@gussmith23) Find a different way to name intermediates
+                // Currently generating random strings. Not great IMO.
+                let out = format!(
+                    "pad_out_{}",
+                    OsRng
+                        .sample_iter(&rand::distributions::Alphanumeric)
+                        .take(30)
+                        .collect::<String>()
+                );
+                declarations.push_str(
+                    c_allocation_string(
+                        uninitialized_allocations_prefix,
+                        out.as_str(),
+                        new_shape.as_slice(),
+                        DType::Fp32,
+                    )
+                    .as_str(),
+                );
+                out
+            };
+
+            let index_var_names = (0..new_shape.len())
+                .map(|i| format!("i{}", i))
+                .collect::<Vec<_>>();
+
+            // Create a for loop for every dimension in the result shape.
+            for (dim_index, dim_len) in new_shape.iter().enumerate() {
+                let index_var_name = &index_var_names[dim_index];
+                code.push_str(
+                    format!(
+                        "
+for (int {i} = 0; {i} < {limit}; {i}++) {{",
+                        i = index_var_name,
+                        limit = dim_len,
+                    )
+                    .as_str(),
+                );
+            }
+
+            // Within the innermost for loop: assign to the output at the
+            // correct location.
+            // We have indices i0..i(n-1), for each of the n axes.
+            code.push_str(
+                format!(
+                    "
+if (i{pad_axis} < {pad_before_index} || i{pad_axis} >= {pad_after_index}) {{
+  {out_name}{out_index} = {pad_value};
+}} else {{
+  {out_name}{out_index} = ((float*){in_name})[{in_index}];
+}}
+",
+                    pad_axis = axis,
+                    pad_before_index = pad_before,
+                    pad_after_index = pad_before + original_shape[axis],
+                    out_name = pad_out_var_name,
+                    out_index = (0..new_shape.len())
+                        .map(|i| format!("[{}]", index_var_names[i],))
+                        .collect::<Vec<_>>()
+                        .join(""),
+                    pad_value = match pad_type {
+                        PadType::ZeroPadding => "0",
+                        PadType::MinPadding => todo!(),
+                    },
+                    in_name = access_var_name,
+                    in_index = (0..new_shape.len())
+                        .map(|i| if i != axis {
+                            format!(
+                                "{}*({})",
+                                index_var_names[i],
                                 original_shape[i + 1..].iter().product::<usize>()
                             )
                         } else {
@@ -1592,10 +2350,7 @@ if (i{pad_axis} < {pad_before_index} || i{pad_axis} >= {pad_after_index}) {{
                 _ => panic!(),
             };
 
-            let new_axis_order = match &expr[list_id].data {
-                MyAnalysisData::List(l) => l,
-                _ => panic!(),
-            };
+            let new_axis_order = MyAnalysis::get_axis_list(list_id, expr, original_shape.len());
 
             assert_eq!(original_shape.len(), new_axis_order.len());
 
@@ -1788,10 +2543,20 @@ if (i{i} < {dim_len}) {{
         | Language::RelayKernelLayout(_)
         | Language::Symbol(_)
         | Language::PadType(_)
+        | Language::WindowConvention(_)
+        | Language::RoundingMode(_)
         | Language::Shape(_)
         | Language::List(_)
         | &Language::ShapeInsertAxis(_)
         | &Language::ShapeRemoveAxis(_)
+        | &Language::ShapeConcat(_)
+        | &Language::ShapeDim(_)
+        | &Language::UsizeAdd(_)
+        | &Language::UsizeSub(_)
+        | &Language::UsizeMul(_)
+        | &Language::UsizeDiv(_)
+        | &Language::TensorDecl(_)
+        | Language::Outputs(_)
         | &Language::ShapeOf(_)
         | &Language::AccessShape(_)
         | Language::RelayOperator(_) => None,
@@ -1803,18 +2568,339 @@ if (i{i} < {dim_len}) {{
         | &Language::SystolicArrayConv2dIm2colNhwcHwioWithBlocking(_)
         | &Language::SystolicArrayConv2dNchwOihwWithBlocking(_)
         | &Language::SystolicArrayConv2dNhwcHwioWithBlocking(_)
+        | &Language::SystolicArrayWithActivation(_)
+        | &Language::PoolingUnit(_)
+        | &Language::ActivationUnit(_)
+        | &Language::VectorAlu(_)
+        | &Language::SparseSystolicArray(_)
         | &Language::AccessBroadcast(_)
         | &Language::AccessInsertAxis(_)
+        | &Language::AccessReverse(_)
         | &Language::AccessPair(_)
         | Language::ComputeType(_)
-        | &Language::Compute(_)
+        | &Language::Cast(_)
+        | &Language::Requantize(_)
         | &Language::AccessCartesianProduct(_)
         | &Language::SliceShape(_)
         | &Language::AccessLiteral(_)
+        | &Language::Conv1d(_)
+        | &Language::Conv2d(_)
+        | &Language::Conv3d(_)
+        | &Language::Conv2dTranspose(_)
+        | &Language::BiasAdd(_)
+        | &Language::AdaptivePool2d(_)
+        | &Language::BatchMatmul(_)
+        | &Language::LayerNorm(_)
+        | &Language::GroupNorm(_)
+        | &Language::Dropout(_)
+        | &Language::Unroll(_)
+        | &Language::AccessWindowsWithConvention(_)
         | &Language::AccessShiftRight(_) => panic!("{:#?} not implemented", expr[id].nodes[0]),
     }
 }
 
+/// The hardware type of `buffer`'s elements, defaulting to float32 where it's
+/// unknown.
+fn buffer_dtype(buffer: &Buffer) -> DType {
+    buffer
+        .dtype
+        .as_ref()
+        .map(DType::from)
+        .unwrap_or(DType::Fp32)
+}
+
+/// `name`, with the characters which can't appear in C identifiers replaced.
+fn c_identifier(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// The C expression for the smallest value of `dtype`, for min-padding.
+fn c_min_value(dtype: DType) -> &'static str {
+    match dtype {
+        DType::Fp32 | DType::Fp64 => "-INFINITY",
+        DType::Int8 => "SCHAR_MIN",
+        DType::Int16 => "SHRT_MIN",
+        DType::Int32 => "INT_MIN",
+        DType::Uint8 | DType::Uint16 | DType::Uint32 => "0",
+        _ => panic!("No C type for {:?}", dtype),
+    }
+}
+
+/// Code copying between the dense, row-major host array `data` and the
+/// elements of `buffer`, held in `buffers`, with `glenside_write` or
+/// `glenside_read`. Padded buffers are copied a row at a time, around the
+/// padding.
+fn c_buffer_copy(buffers: &str, buffer: &Buffer, function: &str) -> String {
+    let c_type = buffer_dtype(buffer).to_c_type_string();
+    let ndim = buffer.shape.len();
+    if buffer.padding.iter().all(|padding| *padding == (0, 0)) {
+        return format!(
+            "  {}({}[{}], 0, data, {} * sizeof({}));\n",
+            function,
+            buffers,
+            buffer.id,
+            buffer.shape.iter().product::<usize>(),
+            c_type
+        );
+    }
+
+    let mut code = String::default();
+    for axis in 0..ndim - 1 {
+        code += format!(
+            "{}for (size_t i{1} = 0; i{1} < {2}; i{1}++)\n",
+            "  ".repeat(axis + 1),
+            axis,
+            buffer.shape[axis]
+        )
+        .as_str();
+    }
+    let offset = (0..ndim - 1)
+        .map(|axis| {
+            format!(
+                "(i{} + {}) * {}",
+                axis, buffer.padding[axis].0, buffer.strides[axis]
+            )
+        })
+        .chain(std::iter::once(buffer.padding[ndim - 1].0.to_string()))
+        .join(" + ");
+    let index = (0..ndim - 1)
+        .map(|axis| {
+            format!(
+                "i{} * {}",
+                axis,
+                buffer.shape[axis + 1..].iter().product::<usize>()
+            )
+        })
+        .chain(std::iter::once("0".to_string()))
+        .join(" + ");
+    code += format!(
+        "{}{}({}[{}], ({}) * sizeof({}), data + {}, {} * sizeof({}));\n",
+        "  ".repeat(ndim),
+        function,
+        buffers,
+        buffer.id,
+        offset,
+        c_type,
+        index,
+        buffer.shape[ndim - 1],
+        c_type
+    )
+    .as_str();
+    code
+}
+
+/// A C initializer for a `glenside_access` describing `descriptor`, whose
+/// shape and strides are the arrays named `shape` and `strides`.
+fn c_access(descriptor: &AccessDescriptor, shape: &str, strides: &str) -> String {
+    format!(
+        "{{{}, {}, {}, {}, {}, {}}}",
+        descriptor.buffer,
+        descriptor.offset,
+        descriptor.shape.len(),
+        descriptor.access_axis,
+        shape,
+        strides
+    )
+}
+
+/// Generates a host driver running `schedule` on an accelerator, written
+/// against the runtime API [`HOST_RUNTIME_API`], which it begins with. For a
+/// `function_name` of `model`, the driver defines:
+///  - `void model_init(void)`, which allocates every buffer, filling the
+///    padding of padded buffers;
+///  - `void model_load_<input>(const <type> *data)` for each input tensor,
+///    which loads the tensor (e.g. weights) into its buffer from a dense,
+///    row-major array;
+///  - `void model_run(void)`, which enqueues the schedule's invocations in
+///    order, each with descriptors of how it reads its inputs and writes its
+///    output, and waits for them to finish. Invocations whose results the
+///    schedule streams into the next invocation (see
+///    [`Materialization::Streamed`]) are marked with a comment, so that
+///    runtimes which can chain kernels know to; their buffers are still
+///    allocated, for runtimes which can't;
+///  - `void model_read_output_<i>(<type> *data)` for each of the program's
+///    results, which reads the result back into a dense, row-major array;
+///    and
+///  - `void model_release(void)`, which frees every buffer.
+///
+/// Buffer ids, layouts, and node indices are those of `schedule`, whose JSON
+/// describes the design the driver runs. Panics if an invocation reads an
+/// access pattern which isn't a strided view of a buffer (see
+/// [`KernelInvocation::reads`](crate::language::schedule::KernelInvocation::reads)).
+/// ```
+/// use egg::RecExpr;
+/// use glenside::codegen::codegen_host_driver;
+/// use glenside::language::schedule::schedule;
+/// use glenside::language::{Language, MyAnalysis};
+///
+/// let expr: RecExpr<Language> = "
+///  (systolic-array 32 64
+///   (access (access-tensor t-32-32) 1)
+///   (access (access-tensor t-32-64) 0))"
+///     .parse()
+///     .unwrap();
+/// let driver = codegen_host_driver(&schedule(&expr, MyAnalysis::default()), "model");
+///
+/// assert!(driver.contains("void model_load_t_32_32(const float *data)"));
+/// assert!(driver.contains("glenside_enqueue(\"systolic-array\", 10, model_buffers"));
+/// assert!(driver.contains("void model_read_output_0(float *data)"));
+/// ```
+pub fn codegen_host_driver(schedule: &ExecutionSchedule, function_name: &str) -> String {
+    let buffers = format!("{}_buffers", function_name);
+    let mut code = format!(
+        "{}\n#include <limits.h>\n#include <math.h>\n\nstatic void *{}[{}];\n",
+        HOST_RUNTIME_API,
+        buffers,
+        schedule.buffers.len()
+    );
+
+    code += format!("\nvoid {}_init(void) {{\n", function_name).as_str();
+    for buffer in &schedule.buffers {
+        let c_type = buffer_dtype(buffer).to_c_type_string();
+        let elements = buffer
+            .shape
+            .iter()
+            .zip(&buffer.padding)
+            .map(|(dim, (before, after))| before + dim + after)
+            .product::<usize>();
+        code += format!(
+            "  {}[{}] = glenside_alloc({} * sizeof({}));\n",
+            buffers, buffer.id, elements, c_type
+        )
+        .as_str();
+        if let Some(pad_type) = buffer.pad_type {
+            let value = match pad_type {
+                PadType::ZeroPadding => "0",
+                PadType::MinPadding => c_min_value(buffer_dtype(buffer)),
+            };
+            code += format!(
+                "  {{\n    const {0} pad = {1};\n    glenside_fill({2}[{3}], &pad, sizeof({0}), {4} * sizeof({0}));\n  }}\n",
+                c_type, value, buffers, buffer.id, elements
+            )
+            .as_str();
+        }
+    }
+    code += "}\n";
+
+    for buffer in &schedule.buffers {
+        if let BufferKind::Input { name } = &buffer.kind {
+            code += format!(
+                "\nvoid {}_load_{}(const {} *data) {{\n{}}}\n",
+                function_name,
+                c_identifier(name),
+                buffer_dtype(buffer).to_c_type_string(),
+                c_buffer_copy(&buffers, buffer, "glenside_write")
+            )
+            .as_str();
+        }
+    }
+
+    code += format!("\nvoid {}_run(void) {{\n", function_name).as_str();
+    for invocation in &schedule.invocations {
+        let output = &schedule.buffers[invocation.output];
+        let output = AccessDescriptor {
+            buffer: output.id,
+            offset: output
+                .padding
+                .iter()
+                .zip(&output.strides)
+                .map(|((before, _), stride)| before * stride)
+                .sum(),
+            shape: output.shape.clone(),
+            access_axis: output.shape.len(),
+            strides: output.strides.iter().map(|stride| *stride as i64).collect(),
+        };
+        let reads = invocation
+            .reads
+            .iter()
+            .map(|read| {
+                read.clone().unwrap_or_else(|| {
+                    panic!(
+                        "Invocation of node {} reads an access pattern which isn't a strided view of a buffer",
+                        invocation.node
+                    )
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if schedule.materialization.get(invocation.node) == Some(&Some(Materialization::Streamed)) {
+            code += format!(
+                "  /* Node {} streams its result into the next invocation. */\n",
+                invocation.node
+            )
+            .as_str();
+        }
+        // C doesn't allow empty arrays, so each array below ends with an
+        // unused element.
+        code += "  {\n";
+        for (name, descriptor) in reads
+            .iter()
+            .enumerate()
+            .map(|(i, read)| (format!("read_{}", i), read))
+            .chain(std::iter::once(("output".to_string(), &output)))
+        {
+            code += format!(
+                "    static const size_t {}_shape[] = {{{}}};\n    static const long {}_strides[] = {{{}}};\n",
+                name,
+                descriptor.shape.iter().chain(std::iter::once(&0)).join(", "),
+                name,
+                descriptor.strides.iter().chain(std::iter::once(&0)).join(", ")
+            )
+            .as_str();
+        }
+        code += format!(
+            "    static const glenside_access reads[] = {{{}}};\n",
+            reads
+                .iter()
+                .enumerate()
+                .map(|(i, read)| c_access(
+                    read,
+                    format!("read_{}_shape", i).as_str(),
+                    format!("read_{}_strides", i).as_str()
+                ))
+                .chain(std::iter::once("{0}".to_string()))
+                .join(", ")
+        )
+        .as_str();
+        code += format!(
+            "    static const glenside_access output = {};\n",
+            c_access(&output, "output_shape", "output_strides")
+        )
+        .as_str();
+        code += format!(
+            "    glenside_enqueue(\"{}\", {}, {}, reads, {}, &output);\n  }}\n",
+            invocation.kernel,
+            invocation.node,
+            buffers,
+            reads.len()
+        )
+        .as_str();
+    }
+    code += "  glenside_wait();\n}\n";
+
+    for (i, output) in schedule.outputs.iter().enumerate() {
+        let buffer = &schedule.buffers[*output];
+        code += format!(
+            "\nvoid {}_read_output_{}({} *data) {{\n{}}}\n",
+            function_name,
+            i,
+            buffer_dtype(buffer).to_c_type_string(),
+            c_buffer_copy(&buffers, buffer, "glenside_read")
+        )
+        .as_str();
+    }
+
+    code += format!("\nvoid {}_release(void) {{\n", function_name).as_str();
+    for buffer in &schedule.buffers {
+        code += format!("  glenside_free({}[{}]);\n", buffers, buffer.id).as_str();
+    }
+    code += "}\n";
+
+    code
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -2015,6 +3101,7 @@ mod tests {
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&expr);
 
@@ -2142,6 +3229,7 @@ int main() {{
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&expr);
 
@@ -2273,6 +3361,7 @@ int main() {{
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&expr);
 
@@ -2430,6 +3519,7 @@ int main() {{
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&expr);
 
@@ -2569,6 +3659,7 @@ int main() {{
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&expr);
 
@@ -2660,6 +3751,421 @@ int main() {{
         );
     }
 
+    #[test]
+    fn kernel_library() {
+        let a =
+            ndarray::Array::from_shape_vec((4, 3), (0..12).map(|v| v as f32).collect()).unwrap();
+        let b =
+            ndarray::Array::from_shape_vec((4, 3), (12..24).map(|v| v as f32).collect()).unwrap();
+        let expected = ndarray::stack(
+            ndarray::Axis(0),
+            &[
+                a.slice(ndarray::s![0..2, ..]),
+                b.slice(ndarray::s![0..2, ..]),
+                a.slice(ndarray::s![1..3, ..]),
+            ],
+        )
+        .unwrap()
+        .into_dyn();
+
+        // The first two slices generate the same code, on different inputs.
+        // The third is sliced differently.
+        let expr = RecExpr::from_str(
+            "
+(access-concatenate
+ (access-concatenate
+  (access-slice (access-tensor a) 0 0 2)
+  (access-slice (access-tensor b) 0 0 2)
+  0)
+ (access-slice (access-tensor a) 0 1 3)
+ 0)",
+        )
+        .unwrap();
+
+        let mut map = HashMap::default();
+        map.insert("a".to_string(), vec![4, 3]);
+        map.insert("b".to_string(), vec![4, 3]);
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&expr);
+
+        // Adding an expression which is already in the egraph just finds its id.
+        let slice_0 =
+            egraph.add_expr(&RecExpr::from_str("(access-slice (access-tensor a) 0 0 2)").unwrap());
+        let slice_1 =
+            egraph.add_expr(&RecExpr::from_str("(access-slice (access-tensor b) 0 0 2)").unwrap());
+        let slice_2 =
+            egraph.add_expr(&RecExpr::from_str("(access-slice (access-tensor a) 0 1 3)").unwrap());
+        let empty_hw_map = HashMap::default();
+        assert_eq!(
+            kernel_key(&egraph, slice_0, &empty_hw_map).0,
+            kernel_key(&egraph, slice_1, &empty_hw_map).0
+        );
+        assert_ne!(
+            kernel_key(&egraph, slice_0, &empty_hw_map).0,
+            kernel_key(&egraph, slice_2, &empty_hw_map).0
+        );
+
+        let code = codegen_kernel_library(
+            &egraph,
+            id,
+            &HashMap::default(),
+            "kernel_library",
+            "",
+            &vec!["a", "b"],
+            &generate_worklist_for_codegen(&egraph, id),
+            true,
+        );
+        // One definition and two calls.
+        assert_eq!(code.matches("kernel_library_kernel_0(").count(), 3);
+        assert!(!code.contains("kernel_library_kernel_1("));
+
+        let main_code = format!(
+            "
+#include <assert.h>
+
+{}
+{}
+{}
+{}
+{}
+
+int main() {{
+  kernel_library(out, a, b);
+
+  for (int i = 0; i < {}; i++) {{
+    assert(((float*)expected)[i] == ((float*)out)[i]);
+  }}
+}}
+",
+            c_assignment_string("", "a", DType::Fp32, &a.into_dyn().view()),
+            c_assignment_string("", "b", DType::Fp32, &b.into_dyn().view()),
+            c_assignment_string("", "expected", DType::Fp32, &expected.view()),
+            c_assignment_string(
+                "",
+                "out",
+                DType::Fp32,
+                &ndarray::ArrayD::<f32>::zeros(expected.shape()).view()
+            ),
+            code,
+            expected.len()
+        );
+
+        let main_c_filepath = std::env::temp_dir().join(format!(
+            "kernel-library-test-{}.c",
+            std::time::SystemTime::now()
+                .duration_since(std::time::SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+
+        let binary_filepath = std::env::temp_dir().join(format!(
+            "kernel-library-test-{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        println!("{}", binary_filepath.to_string_lossy());
+
+        File::create(&main_c_filepath)
+            .unwrap()
+            .write_all(main_code.as_bytes())
+            .unwrap();
+
+        let result = Command::new("gcc")
+            .arg("-Werror")
+            .arg("-g")
+            .arg("-o")
+            .arg(&binary_filepath)
+            .arg(&main_c_filepath)
+            .output()
+            .unwrap();
+
+        assert!(
+            result.status.success(),
+            "{}",
+            std::str::from_utf8(result.stderr.as_slice())
+                .expect("Could not convert stderr to UTF8")
+        );
+
+        let result = Command::new(&binary_filepath).output().unwrap();
+
+        assert!(
+            result.status.success(),
+            "{}",
+            std::str::from_utf8(result.stderr.as_slice())
+                .expect("Could not convert stderr to UTF8")
+        );
+    }
+
+    #[test]
+    fn compute_with_accumulator() {
+        let shape = vec![3, 2, 4];
+        let input = ndarray::ArrayD::from_shape_vec(
+            shape.clone(),
+            (0..shape.iter().product::<usize>())
+                .map(|v| v as f32 - 10.0)
+                .collect(),
+        )
+        .unwrap();
+        let expected = ndarray::ArrayD::from_shape_fn(vec![3], |i| {
+            (0..4)
+                .map(|j| input[[i[0], 0, j]] * input[[i[0], 1, j]])
+                .sum::<f32>()
+        });
+
+        let expr = RecExpr::from_str(
+            "(compute-with-accumulator dot-product int32 (access (access-tensor t) 1))",
+        )
+        .unwrap();
+
+        let mut map = HashMap::default();
+        map.insert("t".to_string(), shape.clone());
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&expr);
+
+        let code = codegen(
+            &egraph,
+            id,
+            &HashMap::default(),
+            "compute_with_accumulator",
+            "",
+            &vec!["t"],
+            &generate_worklist_for_codegen(&egraph, id),
+            true,
+        );
+        assert!(code.contains("int acc = 0;"));
+
+        let main_code = format!(
+            "
+#include <assert.h>
+
+{}
+{}
+{}
+{}
+
+int main() {{
+  compute_with_accumulator(out, a);
+
+  for (int i = 0; i < {}; i++) {{
+    assert(((float*)expected)[i] == ((float*)out)[i]);
+  }}
+}}
+",
+            c_assignment_string("", "a", DType::Fp32, &input.view()),
+            c_assignment_string("", "expected", DType::Fp32, &expected.view()),
+            c_assignment_string(
+                "",
+                "out",
+                DType::Fp32,
+                &ndarray::ArrayD::<f32>::zeros(expected.shape()).view()
+            ),
+            code,
+            expected.len()
+        );
+
+        let main_c_filepath = std::env::temp_dir().join(format!(
+            "compute-with-accumulator-test-{}.c",
+            std::time::SystemTime::now()
+                .duration_since(std::time::SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+
+        let binary_filepath = std::env::temp_dir().join(format!(
+            "compute-with-accumulator-test-{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        println!("{}", binary_filepath.to_string_lossy());
+
+        File::create(&main_c_filepath)
+            .unwrap()
+            .write_all(main_code.as_bytes())
+            .unwrap();
+
+        let result = Command::new("gcc")
+            .arg("-Werror")
+            .arg("-g")
+            .arg("-o")
+            .arg(&binary_filepath)
+            .arg(&main_c_filepath)
+            .output()
+            .unwrap();
+
+        assert!(
+            result.status.success(),
+            "{}",
+            std::str::from_utf8(result.stderr.as_slice())
+                .expect("Could not convert stderr to UTF8")
+        );
+
+        let result = Command::new(&binary_filepath).output().unwrap();
+
+        assert!(
+            result.status.success(),
+            "{}",
+            std::str::from_utf8(result.stderr.as_slice())
+                .expect("Could not convert stderr to UTF8")
+        );
+    }
+
+    #[test]
+    fn fallback_loop_nests() {
+        let shape = vec![3, 2, 4];
+        let input = ndarray::ArrayD::from_shape_vec(
+            shape.clone(),
+            (0..shape.iter().product::<usize>())
+                .map(|v| v as f32 - 10.0)
+                .collect(),
+        )
+        .unwrap();
+        let expected = ndarray::ArrayD::from_shape_fn(vec![3], |i| {
+            let relu = (0..4)
+                .map(|j| (input[[i[0], 0, j]] + input[[i[0], 1, j]]).max(0.0))
+                .collect::<Vec<_>>();
+            let sum = relu.iter().map(|v| v.exp()).sum::<f32>();
+            relu.iter()
+                .map(|v| v.exp() / sum)
+                .fold(f32::NEG_INFINITY, f32::max)
+        });
+
+        let expr = RecExpr::from_str(
+            "(compute reduce-max
+              (access
+               (compute softmax
+                (access
+                 (compute relu
+                  (access (compute elementwise-add (access (access-tensor t) 1)) 1))
+                 1))
+               1))",
+        )
+        .unwrap();
+
+        let mut map = HashMap::default();
+        map.insert("t".to_string(), shape.clone());
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&expr);
+
+        let worklist = generate_worklist_for_codegen(&egraph, id);
+        assert_eq!(
+            find_fallbacks(&egraph, &worklist)
+                .into_iter()
+                .map(|fallback| fallback.op)
+                .collect::<Vec<_>>(),
+            vec![
+                "compute elementwise-add",
+                "compute relu",
+                "compute softmax",
+                "compute reduce-max"
+            ]
+        );
+
+        let code = codegen(
+            &egraph,
+            id,
+            &HashMap::default(),
+            "fallback_loop_nests",
+            "",
+            &vec!["t"],
+            &worklist,
+            true,
+        );
+
+        let main_code = format!(
+            "
+#include <assert.h>
+#include <math.h>
+
+{}
+{}
+{}
+{}
+
+int main() {{
+  fallback_loop_nests(out, a);
+
+  for (int i = 0; i < {}; i++) {{
+    assert(fabsf(((float*)expected)[i] - ((float*)out)[i]) < 1e-5);
+  }}
+}}
+",
+            c_assignment_string("", "a", DType::Fp32, &input.view()),
+            c_assignment_string("", "expected", DType::Fp32, &expected.view()),
+            c_assignment_string(
+                "",
+                "out",
+                DType::Fp32,
+                &ndarray::ArrayD::<f32>::zeros(expected.shape()).view()
+            ),
+            code,
+            expected.len()
+        );
+
+        let main_c_filepath = std::env::temp_dir().join(format!(
+            "fallback-loop-nests-test-{}.c",
+            std::time::SystemTime::now()
+                .duration_since(std::time::SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+
+        let binary_filepath = std::env::temp_dir().join(format!(
+            "fallback-loop-nests-test-{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        println!("{}", binary_filepath.to_string_lossy());
+
+        File::create(&main_c_filepath)
+            .unwrap()
+            .write_all(main_code.as_bytes())
+            .unwrap();
+
+        let result = Command::new("gcc")
+            .arg("-Werror")
+            .arg("-g")
+            .arg("-o")
+            .arg(&binary_filepath)
+            .arg(&main_c_filepath)
+            .arg("-lm")
+            .output()
+            .unwrap();
+
+        assert!(
+            result.status.success(),
+            "{}",
+            std::str::from_utf8(result.stderr.as_slice())
+                .expect("Could not convert stderr to UTF8")
+        );
+
+        let result = Command::new(&binary_filepath).output().unwrap();
+
+        assert!(
+            result.status.success(),
+            "{}",
+            std::str::from_utf8(result.stderr.as_slice())
+                .expect("Could not convert stderr to UTF8")
+        );
+    }
+
     #[test]
     fn access_windows() {
         let shape = vec![3, 50, 27, 4];
@@ -2710,6 +4216,7 @@ int main() {{
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&expr);
 
@@ -2839,6 +4346,7 @@ int main() {{
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&expr);
 
@@ -2963,6 +4471,7 @@ int main() {{
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         egraph.add_expr(&expr);
 
@@ -3006,6 +4515,7 @@ int main() {{
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&expr);
 
@@ -3141,6 +4651,7 @@ def @main(%x: Tensor[(1, 16, 16, 3), float32], %y: Tensor[(1, 1, 3), float32]) {
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: env.clone(),
             name_to_dtype: dtypes_vec.into_iter().collect(),
+            shape_cache: None,
         });
 
         let id = egraph.add_expr(&expr);
@@ -3286,6 +4797,7 @@ def @main(%x: Tensor[(1, 1000), float32], %y: Tensor[(1000), float32]) {
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: env.clone(),
             name_to_dtype: dtypes_vec.into_iter().collect(),
+            shape_cache: None,
         });
 
         let id = egraph.add_expr(&expr);
@@ -3457,6 +4969,7 @@ def @main(%data: Tensor[(1, 2, 2, 16), float32], %bn_gamma: Tensor[(16), float32
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: env.clone(),
             name_to_dtype: dtypes_vec.into_iter().collect(),
+            shape_cache: None,
         });
 
         let id = egraph.add_expr(&expr);
@@ -3632,6 +5145,7 @@ def @main(%data: Tensor[(1,100), float32]) -> Tensor[(1,100), float32] {
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: env.clone(),
             name_to_dtype: dtypes_vec.into_iter().collect(),
+            shape_cache: None,
         });
 
         let id = egraph.add_expr(&expr);
@@ -3783,6 +5297,7 @@ def @main(%x: Tensor[(1, 3, 3, 4), float32]) {
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: env.clone(),
             name_to_dtype: dtypes_vec.into_iter().collect(),
+            shape_cache: None,
         });
 
         let id = egraph.add_expr(&expr);
@@ -3927,6 +5442,7 @@ def @main(%x: Tensor[(1, 112, 112, 64), float32]) -> Tensor[(1, 56, 56, 64), flo
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: env.clone(),
             name_to_dtype: dtypes_vec.into_iter().collect(),
+            shape_cache: None,
         });
 
         let id = egraph.add_expr(&expr);
@@ -4072,6 +5588,7 @@ def @main(%x: Tensor[(1, 512, 1, 1), float32]) {
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: env.clone(),
             name_to_dtype: dtypes_vec.into_iter().collect(),
+            shape_cache: None,
         });
 
         let id = egraph.add_expr(&expr);
@@ -4217,6 +5734,7 @@ def @main(%x: Tensor[(1, 7, 7, 512), float32]) -> Tensor[(1, 1, 1, 512), float32
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: env.clone(),
             name_to_dtype: dtypes_vec.into_iter().collect(),
+            shape_cache: None,
         });
 
         let id = egraph.add_expr(&expr);
@@ -4361,6 +5879,7 @@ def @main(%data: Tensor[(10, 10), float32]) {
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: env.clone(),
             name_to_dtype: dtypes_vec.into_iter().collect(),
+            shape_cache: None,
         });
 
         let id = egraph.add_expr(&expr);
@@ -4509,6 +6028,7 @@ def @main(%data: Tensor[(10, 10), float32]) {
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: env.clone(),
             name_to_dtype: dtypes_vec.into_iter().collect(),
+            shape_cache: None,
         });
 
         let id = egraph.add_expr(&expr);
@@ -4657,6 +6177,7 @@ def @main(%data: Tensor[(1, 1280, 7, 7), float32]) {
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: env.clone(),
             name_to_dtype: dtypes_vec.into_iter().collect(),
+            shape_cache: None,
         });
 
         let id = egraph.add_expr(&expr);
@@ -4805,6 +6326,7 @@ def @main(%data: Tensor[(1, 256, 13, 13), float32]) {
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: env.clone(),
             name_to_dtype: dtypes_vec.into_iter().collect(),
+            shape_cache: None,
         });
 
         let id = egraph.add_expr(&expr);
@@ -4953,6 +6475,7 @@ def @main(%x: Tensor[(1, 256, 13, 13), float32], %y: Tensor[(1, 256), float32])
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: env.clone(),
             name_to_dtype: dtypes_vec.into_iter().collect(),
+            shape_cache: None,
         });
 
         let id = egraph.add_expr(&expr);
@@ -5098,6 +6621,7 @@ def @main(%x: Tensor[(1, 256, 13, 13), float32], %y: Tensor[(1, 256), float32])
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: env.clone(),
             name_to_dtype: dtypes_vec.into_iter().collect(),
+            shape_cache: None,
         });
 
         let id = egraph.add_expr(&expr);
@@ -5252,6 +6776,7 @@ int main() {{
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: env.clone(),
             name_to_dtype: dtypes_vec.into_iter().collect(),
+            shape_cache: None,
         });
 
         let _id = egraph.add_expr(&expr);
@@ -5305,6 +6830,7 @@ int main() {{
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: env.clone(),
             name_to_dtype: dtypes_vec.into_iter().collect(),
+            shape_cache: None,
         });
 
         let _id = egraph.add_expr(&expr);
@@ -0,0 +1,212 @@
+//! Glenside's error type.
+//!
+//! Parsing ([`crate::language::parse`]), the analysis
+//! ([`crate::language::try_add_expr`], [`crate::language::MyAnalysis::try_make`]),
+//! extraction ([`crate::extraction::try_find_best`]), and the interpreter
+//! ([`crate::language::interpreter::try_interpret`]) check their inputs and
+//! report failures as [`GlensideError`]s, so that a tool processing many
+//! programs can report a bad one and move on. [`crate::language::try_add_expr`]
+//! checks every node of an expression before adding any of them, so the
+//! egraph is left untouched when the expression is rejected.
+//!
+//! egg's analyses can't fail, so nodes added some other way (e.g. with
+//! `EGraph::add_expr`, or by a rewrite) panic with the same error when the
+//! analysis rejects them. The rewrites assume the egraph they run on is
+//! well-formed, and assert their own invariants.
+
+use std::fmt::Display;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GlensideError {
+    /// The text of a program, or of a hardware spec, couldn't be parsed.
+    Parse(String),
+    /// The analysis rejected a node, e.g. because its children have the wrong
+    /// kinds or shapes.
+    Analysis(String),
+    /// No program could be extracted, e.g. because every program in an eclass
+    /// contains a node the cost function forbids.
+    Extraction(String),
+    /// The interpreter couldn't evaluate a program.
+    Interpretation(String),
+}
+
+impl Display for GlensideError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GlensideError::Parse(message) => write!(f, "parse error: {}", message),
+            GlensideError::Analysis(message) => write!(f, "analysis error: {}", message),
+            GlensideError::Extraction(message) => write!(f, "extraction error: {}", message),
+            GlensideError::Interpretation(message) => {
+                write!(f, "interpretation error: {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GlensideError {}
+
+pub type Result<T> = std::result::Result<T, GlensideError>;
+
+/// Returns an error of kind `$kind` (e.g. `GlensideError::Analysis`) from the
+/// enclosing function, with a `format!`ted message.
+macro_rules! bail {
+    ($kind:path, $($arg:tt)+) => {
+        return Err($kind(format!($($arg)+)))
+    };
+}
+
+/// Like `assert!`, but returns an error of kind `$kind` from the enclosing
+/// function instead of panicking.
+macro_rules! ensure {
+    ($kind:path, $cond:expr $(,)?) => {
+        if !$cond {
+            return Err($kind(format!("Expected {}", stringify!($cond))));
+        }
+    };
+    ($kind:path, $cond:expr, $($arg:tt)+) => {
+        if !$cond {
+            return Err($kind(format!($($arg)+)));
+        }
+    };
+}
+
+/// Like `assert_eq!`, but returns an error of kind `$kind` from the enclosing
+/// function instead of panicking.
+macro_rules! ensure_eq {
+    ($kind:path, $left:expr, $right:expr $(,)?) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if *left != *right {
+                    return Err($kind(format!(
+                        "Expected {} == {}, found {:?} and {:?}",
+                        stringify!($left),
+                        stringify!($right),
+                        left,
+                        right
+                    )));
+                }
+            }
+        }
+    };
+    ($kind:path, $left:expr, $right:expr, $($arg:tt)+) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if *left != *right {
+                    return Err($kind(format!(
+                        "{} (found {:?} and {:?})",
+                        format_args!($($arg)+),
+                        left,
+                        right
+                    )));
+                }
+            }
+        }
+    };
+}
+
+/// Like `assert_ne!`, but returns an error of kind `$kind` from the enclosing
+/// function instead of panicking.
+macro_rules! ensure_ne {
+    ($kind:path, $left:expr, $right:expr $(,)?) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if *left == *right {
+                    return Err($kind(format!(
+                        "Expected {} != {}, found {:?}",
+                        stringify!($left),
+                        stringify!($right),
+                        left
+                    )));
+                }
+            }
+        }
+    };
+    ($kind:path, $left:expr, $right:expr, $($arg:tt)+) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if *left == *right {
+                    return Err($kind(format!($($arg)+)));
+                }
+            }
+        }
+    };
+}
+
+pub(crate) use bail;
+pub(crate) use ensure;
+pub(crate) use ensure_eq;
+pub(crate) use ensure_ne;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extraction::{try_find_best, MonolithicCostFunction};
+    use crate::language::interpreter::{try_interpret, Environment};
+    use crate::language::{parse, try_add_expr, MyAnalysis};
+    use egg::EGraph;
+    use std::collections::HashMap;
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            GlensideError::Extraction("no program".to_string()).to_string(),
+            "extraction error: no program"
+        );
+    }
+
+    #[test]
+    fn analysis_error() {
+        let mut egraph = EGraph::new(MyAnalysis::default());
+        assert!(matches!(
+            try_add_expr(
+                &mut egraph,
+                &parse("(access (access-tensor t-32-64) 3)").unwrap()
+            ),
+            Err(GlensideError::Analysis(_))
+        ));
+        assert_eq!(egraph.number_of_classes(), 0);
+    }
+
+    #[test]
+    fn unextractable() {
+        let mut egraph = EGraph::new(MyAnalysis::default());
+        let id = try_add_expr(
+            &mut egraph,
+            &parse(
+                "(compute dot-product
+                  (access-cartesian-product
+                   (access (access-tensor t-32-32) 1)
+                   (access (access-tensor t-32-32) 1)))",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        egraph.rebuild();
+
+        // Without the systolic array rewrite, the dot product can't be
+        // extracted.
+        match try_find_best(
+            &egraph,
+            id,
+            MonolithicCostFunction {
+                systolic_array_configuration: (32, 32),
+                egraph: &egraph,
+                prefer_systolic_arrays_with_blocking: false,
+            },
+            MonolithicCostFunction::INFINITY_VALUE,
+        ) {
+            Err(GlensideError::Extraction(_)) => (),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn uninterpretable() {
+        let expr = parse("(access (access-tensor missing) 0)").unwrap();
+        let env: Environment<f64> = HashMap::default();
+        assert!(matches!(
+            try_interpret(&expr, expr.as_ref().len() - 1, &env),
+            Err(GlensideError::Interpretation(_))
+        ));
+    }
+}
@@ -0,0 +1,245 @@
+//! Autotuning: closing the loop between extraction and real hardware.
+//!
+//! Glenside's cost functions are static estimates. [`autotune`] instead
+//! measures extracted candidates using a user-supplied callback (which might,
+//! for example, compile and run generated C, or run a design on an FPGA),
+//! refines a [`LearnedCostFunction`] from the measurements, and re-extracts
+//! with the refined cost function.
+
+use super::sampling;
+use crate::language::{Language, MyAnalysis};
+use egg::{CostFunction, EGraph, Extractor, Id, Language as LanguageTrait, RecExpr};
+use rand::Rng;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// A linear cost model: the cost of a program is the sum, over its enodes, of
+/// a learned weight for each kind of enode. Costs are in seconds.
+///
+/// Enodes are grouped by operator (e.g. all `systolic-array`s share a weight,
+/// as do all `dot-product`s), except for numbers, symbols, and float literals,
+/// which all share the `"literal"` weight.
+#[derive(Clone, Debug)]
+pub struct LearnedCostFunction {
+    pub weights: HashMap<String, f64>,
+    /// The weight of operators not in `weights`.
+    pub default_weight: f64,
+}
+
+impl LearnedCostFunction {
+    /// Weights are kept strictly positive, so that costs strictly increase
+    /// as programs grow; otherwise, extraction may not terminate.
+    const MIN_WEIGHT: f64 = 1e-9;
+
+    pub fn new(default_weight: f64) -> Self {
+        assert!(default_weight >= Self::MIN_WEIGHT);
+        LearnedCostFunction {
+            weights: HashMap::default(),
+            default_weight,
+        }
+    }
+
+    /// The key under which `enode`'s weight is stored.
+    pub fn feature(enode: &Language) -> String {
+        match enode {
+            Language::Num(_) | Language::Symbol(_) | Language::NotNanFloat64(_) => {
+                "literal".to_string()
+            }
+            _ => enode.display_op().to_string(),
+        }
+    }
+
+    fn weight(&self, feature: &str) -> f64 {
+        *self.weights.get(feature).unwrap_or(&self.default_weight)
+    }
+
+    /// Counts the enodes of each kind in `expr`, as if it were a tree (i.e. a
+    /// node used twice is counted twice), matching how costs are computed
+    /// during extraction.
+    fn features(expr: &RecExpr<Language>) -> HashMap<String, f64> {
+        let nodes = expr.as_ref();
+        // How many times each node is used.
+        let mut uses = vec![0f64; nodes.len()];
+        *uses.last_mut().unwrap() = 1.0;
+        for (i, node) in nodes.iter().enumerate().rev() {
+            for child in node.children() {
+                uses[usize::from(*child)] += uses[i];
+            }
+        }
+        let mut features = HashMap::default();
+        for (node, uses) in nodes.iter().zip(uses.iter()) {
+            *features.entry(Self::feature(node)).or_insert(0.0) += uses;
+        }
+        features
+    }
+
+    /// The predicted runtime of `expr`, in seconds.
+    pub fn predict(&self, expr: &RecExpr<Language>) -> f64 {
+        Self::features(expr)
+            .iter()
+            .map(|(feature, count)| self.weight(feature) * count)
+            .sum()
+    }
+
+    /// Moves the weights towards predicting `measured` for `expr`, using a
+    /// normalized least-mean-squares update with step size `learning_rate`
+    /// (in (0, 1]).
+    pub fn update(&mut self, expr: &RecExpr<Language>, measured: Duration, learning_rate: f64) {
+        let features = Self::features(expr);
+        let error = measured.as_secs_f64() - self.predict(expr);
+        let norm: f64 = features.values().map(|count| count * count).sum();
+        for (feature, count) in features.iter() {
+            let weight = self.weight(feature) + learning_rate * error * count / norm;
+            self.weights
+                .insert(feature.clone(), weight.max(Self::MIN_WEIGHT));
+        }
+    }
+}
+
+impl CostFunction<Language> for LearnedCostFunction {
+    type Cost = f64;
+    fn cost<C>(&mut self, enode: &Language, mut costs: C) -> Self::Cost
+    where
+        C: FnMut(Id) -> Self::Cost,
+    {
+        enode.fold(self.weight(&Self::feature(enode)), |sum, id| {
+            sum + costs(id)
+        })
+    }
+}
+
+/// Autotunes the extraction of eclass `root`.
+///
+/// Each of the `num_iterations` iterations extracts the best program under
+/// `cost_function`, plus up to `samples_per_iteration` more programs drawn by
+/// [`sampling::sample`] at the given `temperature`. Each new program is
+/// measured with `measure`, and `cost_function` is then refined against every
+/// measurement so far. Programs are only ever measured once.
+///
+/// Returns every measured program with its measurement, fastest first. After
+/// autotuning, `cost_function` can also be used to extract directly.
+pub fn autotune<R: Rng>(
+    egraph: &EGraph<Language, MyAnalysis>,
+    root: Id,
+    cost_function: &mut LearnedCostFunction,
+    mut measure: impl FnMut(&RecExpr<Language>) -> Duration,
+    num_iterations: usize,
+    samples_per_iteration: usize,
+    temperature: f64,
+    rng: &mut R,
+) -> Vec<(Duration, RecExpr<Language>)> {
+    // Keyed by the program's string, as RecExprs aren't hashable.
+    let mut measurements: HashMap<String, (Duration, RecExpr<Language>)> = HashMap::default();
+
+    for _ in 0..num_iterations {
+        let (_, best) = Extractor::new(egraph, cost_function.clone()).find_best(root);
+        let candidates = std::iter::once(best).chain(
+            sampling::sample(
+                egraph,
+                root,
+                cost_function.clone(),
+                samples_per_iteration,
+                temperature,
+                rng,
+            )
+            .into_iter()
+            .map(|(_, expr)| expr),
+        );
+        for expr in candidates {
+            measurements
+                .entry(expr.to_string())
+                .or_insert_with(|| (measure(&expr), expr));
+        }
+
+        // A few passes over all measurements, so that earlier measurements
+        // aren't forgotten.
+        for _ in 0..100 {
+            for (duration, expr) in measurements.values() {
+                cost_function.update(expr, *duration, 0.5);
+            }
+        }
+    }
+
+    let mut measurements = measurements.into_iter().map(|(_, v)| v).collect::<Vec<_>>();
+    measurements.sort_by_key(|(duration, _)| *duration);
+    measurements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::rewrites;
+    use egg::Runner;
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    #[test]
+    fn learn_faster_mapping() {
+        let program = "
+         (compute dot-product
+          (access-cartesian-product
+           (access (access-tensor t-32-32) 1)
+           (access (access-tensor t-32-32) 1)))"
+            .parse()
+            .unwrap();
+        let mut egraph = EGraph::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program);
+        let egraph = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&vec![rewrites::systolic_array().rewrite])
+            .egraph;
+
+        // With uniform weights, the (smaller) unmapped program is preferred.
+        let mut cost_function = LearnedCostFunction::new(1.0);
+        let (_, best) = Extractor::new(&egraph, cost_function.clone()).find_best(id);
+        assert!(best.to_string().starts_with("(compute"));
+
+        // "Hardware" on which the systolic array is much faster.
+        let measure = |expr: &RecExpr<Language>| {
+            if expr
+                .as_ref()
+                .iter()
+                .any(|node| matches!(node, Language::SystolicArray(_)))
+            {
+                Duration::from_millis(1)
+            } else {
+                Duration::from_secs(1)
+            }
+        };
+        let mut rng = SmallRng::seed_from_u64(23);
+        let results = autotune(
+            &egraph,
+            id,
+            &mut cost_function,
+            measure,
+            3,
+            2,
+            100.0,
+            &mut rng,
+        );
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, Duration::from_millis(1));
+        assert!(results[0].1.to_string().starts_with("(systolic-array"));
+
+        // The refined cost function now prefers the systolic array.
+        let (_, best) = Extractor::new(&egraph, cost_function.clone()).find_best(id);
+        assert!(best.to_string().starts_with("(systolic-array"));
+        assert!(cost_function.predict(&results[0].1) < cost_function.predict(&results[1].1));
+    }
+
+    #[test]
+    fn features_count_shared_nodes() {
+        // (access-pair x x), where x = (access (access-tensor a) 0) is only
+        // represented once.
+        let mut expr = RecExpr::default();
+        let a = expr.add(Language::Symbol("a".to_string()));
+        let access_tensor = expr.add(Language::AccessTensor(a));
+        let zero = expr.add(Language::Num(0));
+        let access = expr.add(Language::Access([access_tensor, zero]));
+        expr.add(Language::AccessPair([access, access]));
+
+        let mut cost_function = LearnedCostFunction::new(1.0);
+        assert_eq!(cost_function.predict(&expr), 9.0);
+        assert_eq!(cost_function.cost_rec(&expr), 9.0);
+    }
+}
@@ -40,11 +40,17 @@ pub fn filter_by_enode_type(enode: &Language, _eclass_id: Id, _egraph: &EGraph)
                     Language::ShapeOf(_)
                     | Language::SliceShape(_)
                     | Language::ShapeInsertAxis(_)
-                    | Language::ShapeRemoveAxis(_) => panic!(),
+                    | Language::ShapeRemoveAxis(_)
+                    | Language::ShapeConcat(_) => panic!(),
 
                 // Things that should always pass through.
                 Language::SystolicArray(_)
                     | Language::SystolicArrayWithBlocking(_)
+                    | Language::SystolicArrayWithActivation(_)
+                    | Language::PoolingUnit(_)
+                    | Language::ActivationUnit(_)
+                    | Language::VectorAlu(_)
+                    | Language::SparseSystolicArray(_)
             | Language::SystolicArrayConv2dNchwOihwWithBlocking(_)
             | Language::SystolicArrayConv2dNhwcHwioWithBlocking(_)
             | Language::SystolicArrayConv2dIm2colNchwOihwWithBlocking(_)
@@ -59,7 +65,9 @@ pub fn filter_by_enode_type(enode: &Language, _eclass_id: Id, _egraph: &EGraph)
                     | Language::NotNanFloat64(_)
                     | Language::RelayOperator(_)
                     | Language::DataType(_)
-                    | Language::Symbol(_) => true,
+                    | Language::Symbol(_)
+                    | Language::TensorDecl(_)
+                    | Language::Outputs(_) => true,
 
                 // Things I'm not sure about.
                 Language::Shape(_) | Language::List(_) | Language::AccessTensor(_) => true,
@@ -79,19 +87,45 @@ pub fn filter_by_enode_type(enode: &Language, _eclass_id: Id, _egraph: &EGraph)
                     | Language::AccessSlice(_)
                     | Language::AccessPad(_)
                     | Language::PadType(_)
+                    | Language::WindowConvention(_)
+                    | Language::RoundingMode(_)
                     | Language::AccessSqueeze(_)
                     | Language::AccessInsertAxis(_)
+                    | Language::AccessReverse(_)
                     | Language::AccessBroadcast(_)
                     | Language::ConstantTensor(_)
-                    | Language::AccessLiteral(_) => true,
+                    | Language::AccessLiteral(_)
+                    | Language::Cast(_)
+                    | Language::Requantize(_) => true,
 
                 // Things that should never pass through.
                 Language::Compute(_)
+                    | Language::ComputeWithAccumulator(_)
+                    | Language::Conv1d(_)
+                    | Language::Conv2d(_)
+                    | Language::Conv3d(_)
+                    | Language::Conv2dTranspose(_)
+                    | Language::BiasAdd(_)
+                    | Language::AdaptivePool2d(_)
+                    | Language::BatchMatmul(_)
+                    | Language::LayerNorm(_)
+                    | Language::GroupNorm(_)
+                    | Language::Dropout(_)
+                    | Language::Unroll(_)
+                    | Language::AccessWindowsWithConvention(_)
                     | Language::ComputeType(_)
                     | Language::AccessCartesianProduct(_)
                     | Language::AccessPair(_)
                     | Language::AccessShiftRight(_) => false,
 
+                // These are always constant-folded, so their eclasses will
+                // also contain the resulting Num.
+                Language::ShapeDim(_)
+                    | Language::UsizeAdd(_)
+                    | Language::UsizeSub(_)
+                    | Language::UsizeMul(_)
+                    | Language::UsizeDiv(_) => false,
+
             }
         == false
     {
@@ -118,7 +152,8 @@ pub fn filter_obviously_less_preferable_nodes(
             Language::ShapeOf(_)
             | Language::SliceShape(_)
             | Language::ShapeInsertAxis(_)
-            | Language::ShapeRemoveAxis(_) => panic!(),
+            | Language::ShapeRemoveAxis(_)
+            | Language::ShapeConcat(_) => panic!(),
 
             Language::SystolicArray(_)
             | Language::SystolicArrayConv2dNchwOihwWithBlocking(_)
@@ -131,7 +166,12 @@ pub fn filter_obviously_less_preferable_nodes(
             | Language::AcceleratorCall(_)
             | Language::AcceleratorFunc(_)
             | Language::DataType(_)
-            | Language::SystolicArrayWithBlocking(_) => true,
+            | Language::SystolicArrayWithBlocking(_)
+            | Language::SystolicArrayWithActivation(_)
+            | Language::PoolingUnit(_)
+            | Language::ActivationUnit(_)
+            | Language::VectorAlu(_)
+            | Language::SparseSystolicArray(_) => true,
 
             Language::Shape(_)
             | Language::List(_)
@@ -142,6 +182,8 @@ pub fn filter_obviously_less_preferable_nodes(
             | Language::NotNanFloat64(_)
             | Language::RelayOperator(_)
             | Language::Symbol(_)
+            | Language::TensorDecl(_)
+            | Language::Outputs(_)
             | Language::Access(_)
             | Language::AccessTranspose(_)
             | Language::AccessReshape(_)
@@ -151,15 +193,38 @@ pub fn filter_obviously_less_preferable_nodes(
             | Language::AccessSlice(_)
             | Language::AccessPad(_)
             | Language::PadType(_)
+            | Language::WindowConvention(_)
+            | Language::RoundingMode(_)
             | Language::AccessSqueeze(_)
             | Language::AccessInsertAxis(_)
+            | Language::AccessReverse(_)
             | Language::AccessBroadcast(_)
             | Language::AccessLiteral(_)
             | Language::Compute(_)
+            | Language::ComputeWithAccumulator(_)
+            | Language::Cast(_)
+            | Language::Requantize(_)
+            | Language::Conv1d(_)
+            | Language::Conv2d(_)
+            | Language::Conv3d(_)
+            | Language::Conv2dTranspose(_)
+            | Language::BiasAdd(_)
+            | Language::AdaptivePool2d(_)
+            | Language::BatchMatmul(_)
+            | Language::LayerNorm(_)
+            | Language::GroupNorm(_)
+            | Language::Dropout(_)
+            | Language::Unroll(_)
+            | Language::AccessWindowsWithConvention(_)
             | Language::ComputeType(_)
             | Language::AccessCartesianProduct(_)
             | Language::AccessPair(_)
             | Language::ConstantTensor(_)
+            | Language::ShapeDim(_)
+            | Language::UsizeAdd(_)
+            | Language::UsizeSub(_)
+            | Language::UsizeMul(_)
+            | Language::UsizeDiv(_)
             | Language::AccessShiftRight(_) => false,
         }
     }
@@ -613,6 +678,7 @@ mod tests {
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: map.clone(),
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&expr);
 
@@ -627,6 +693,7 @@ mod tests {
             EGraph::new(MyAnalysis {
                 name_to_shape: map,
                 name_to_dtype: HashMap::default(),
+                shape_cache: None,
             }),
         );
 
@@ -0,0 +1,251 @@
+//! Multi-objective extraction with lexicographic priorities.
+//!
+//! Sizing an accelerator generally means optimizing several objectives in
+//! order: e.g. first fit every intermediate in SRAM, then minimize latency,
+//! and only then minimize area. [`extract_lexicographic`] extracts the program
+//! which is best under the first [`Objective`], breaking ties with the second,
+//! and so on, and reports which objective actually decided the choice.
+//!
+//! Each objective is additive: the cost of a program is the sum of the costs
+//! of its nodes. This is what lets extraction find the lexicographically best
+//! program bottom-up, as egg's extractor does for a single objective.
+
+use crate::hardware::HardwareSpec;
+use crate::language::{Language, MyAnalysis, MyAnalysisData};
+use egg::{CostFunction, EGraph, Extractor, Id, Language as LanguageTrait, RecExpr};
+use num_traits::{AsPrimitive, Zero};
+
+/// A named, additive objective, given as the cost of each node on its own.
+pub struct Objective<'a> {
+    pub name: String,
+    pub node_cost: Box<dyn FnMut(&Language) -> f64 + 'a>,
+}
+
+impl<'a> Objective<'a> {
+    pub fn new(name: &str, node_cost: impl FnMut(&Language) -> f64 + 'a) -> Self {
+        Objective {
+            name: name.to_string(),
+            node_cost: Box::new(node_cost),
+        }
+    }
+
+    /// An objective from a cost function which sums the costs of each node's
+    /// children into the node's own cost, as all of Glenside's do.
+    pub fn from_cost_function<C>(name: &str, mut cost_function: C) -> Self
+    where
+        C: CostFunction<Language> + 'a,
+        C::Cost: Zero + AsPrimitive<f64>,
+    {
+        Self::new(name, move |enode| {
+            cost_function.cost(enode, |_| C::Cost::zero()).as_()
+        })
+    }
+
+    /// The number of nodes whose results don't fit in `spec`'s SRAM.
+    pub fn sram_violations(
+        spec: &'a HardwareSpec,
+        egraph: &'a EGraph<Language, MyAnalysis>,
+    ) -> Self {
+        Self::new("sram", move |enode| {
+            match egraph.lookup(enode.clone()).map(|id| &egraph[id].data) {
+                Some(MyAnalysisData::AccessPattern(a)) if !spec.fits_in_sram(a) => 1.0,
+                _ => 0.0,
+            }
+        })
+    }
+}
+
+/// A cost function whose costs are the costs under each [`Objective`], which
+/// are compared lexicographically.
+pub struct LexicographicCostFunction<'a> {
+    pub objectives: Vec<Objective<'a>>,
+}
+
+impl LexicographicCostFunction<'_> {
+    fn node_costs(&mut self, enode: &Language) -> Vec<f64> {
+        self.objectives
+            .iter_mut()
+            .map(|objective| (objective.node_cost)(enode))
+            .collect()
+    }
+}
+
+impl CostFunction<Language> for LexicographicCostFunction<'_> {
+    type Cost = Vec<f64>;
+
+    fn cost<C>(&mut self, enode: &Language, mut costs: C) -> Self::Cost
+    where
+        C: FnMut(Id) -> Self::Cost,
+    {
+        enode.fold(self.node_costs(enode), |mut sum, id| {
+            for (sum, cost) in sum.iter_mut().zip(costs(id)) {
+                *sum += cost;
+            }
+            sum
+        })
+    }
+}
+
+/// The result of [`extract_lexicographic`].
+#[derive(Clone, Debug)]
+pub struct LexicographicExtraction {
+    pub expr: RecExpr<Language>,
+    /// The extracted program's cost under each objective, by name.
+    pub costs: Vec<(String, f64)>,
+    /// The objective which decided between the extracted program and the
+    /// runner-up (the best program rooted at a different node of the root
+    /// eclass): the first objective on which they differ. `None` if there's
+    /// no runner-up, or it ties on every objective.
+    pub binding_objective: Option<String>,
+}
+
+/// Extracts the lexicographically best program from `root` in `egraph`:
+/// the best under the first of `objectives`, with ties broken by the second,
+/// and so on.
+pub fn extract_lexicographic(
+    egraph: &EGraph<Language, MyAnalysis>,
+    root: Id,
+    objectives: Vec<Objective>,
+) -> LexicographicExtraction {
+    let names = objectives
+        .iter()
+        .map(|objective| objective.name.clone())
+        .collect::<Vec<_>>();
+    let mut cost_function = LexicographicCostFunction { objectives };
+    // The root's nodes' own costs are computed up front, as the extractor
+    // takes ownership of the cost function.
+    let root_node_costs = egraph[root]
+        .nodes
+        .iter()
+        .map(|enode| cost_function.node_costs(enode))
+        .collect::<Vec<_>>();
+
+    let extractor = Extractor::new(egraph, cost_function);
+    let (cost, expr) = extractor.find_best(root);
+
+    let mut candidates = egraph[root]
+        .nodes
+        .iter()
+        .zip(root_node_costs)
+        .map(|(enode, node_costs)| {
+            enode.fold(node_costs, |mut sum, id| {
+                for (sum, cost) in sum.iter_mut().zip(extractor.find_best_cost(id)) {
+                    *sum += cost;
+                }
+                sum
+            })
+        })
+        .filter(|candidate| *candidate != cost)
+        .collect::<Vec<_>>();
+    candidates.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let binding_objective = candidates.first().and_then(|runner_up| {
+        names
+            .iter()
+            .zip(cost.iter().zip(runner_up.iter()))
+            .find(|(_, (best, other))| best != other)
+            .map(|(name, _)| name.clone())
+    });
+
+    LexicographicExtraction {
+        expr,
+        costs: names.into_iter().zip(cost).collect(),
+        binding_objective,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extraction::MonolithicCostFunction;
+    use crate::language::rewrites;
+    use egg::Runner;
+
+    fn saturate() -> (EGraph<Language, MyAnalysis>, Id) {
+        let expr = "
+         (compute dot-product
+          (access-cartesian-product
+           (access (access-tensor t-32-32) 1)
+           (access (access-tensor t-32-32) 1)))"
+            .parse()
+            .unwrap();
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_expr(&expr)
+            .run(&[rewrites::systolic_array().rewrite]);
+        (runner.egraph, runner.roots[0])
+    }
+
+    fn latency<'a>(egraph: &'a EGraph<Language, MyAnalysis>) -> Objective<'a> {
+        Objective::from_cost_function(
+            "latency",
+            MonolithicCostFunction {
+                systolic_array_configuration: (32, 32),
+                egraph,
+                prefer_systolic_arrays_with_blocking: false,
+            },
+        )
+    }
+
+    #[test]
+    fn tie_broken_by_second_objective() {
+        let (egraph, root) = saturate();
+        let spec = HardwareSpec::from_json(
+            r#"{ "atoms": [], "sram_bytes": 1048576, "dram_bandwidth": 1, "frequency": 1 }"#,
+        )
+        .unwrap();
+
+        let extraction = extract_lexicographic(
+            &egraph,
+            root,
+            vec![Objective::sram_violations(&spec, &egraph), latency(&egraph)],
+        );
+        assert!(extraction.expr.to_string().starts_with("(systolic-array"));
+        assert_eq!(extraction.costs[0], ("sram".to_string(), 0.0));
+        assert_eq!(extraction.binding_objective, Some("latency".to_string()));
+    }
+
+    #[test]
+    fn first_objective_binds() {
+        let (egraph, root) = saturate();
+
+        let extraction = extract_lexicographic(
+            &egraph,
+            root,
+            vec![
+                Objective::new("no-systolic-arrays", |enode| match enode {
+                    Language::SystolicArray(_) => 1.0,
+                    _ => 0.0,
+                }),
+                latency(&egraph),
+            ],
+        );
+        assert!(extraction
+            .expr
+            .to_string()
+            .starts_with("(compute dot-product"));
+        assert_eq!(
+            extraction.costs,
+            vec![
+                ("no-systolic-arrays".to_string(), 0.0),
+                (
+                    "latency".to_string(),
+                    MonolithicCostFunction::INFINITY_VALUE as f64 + 10.0
+                )
+            ]
+        );
+        assert_eq!(
+            extraction.binding_objective,
+            Some("no-systolic-arrays".to_string())
+        );
+    }
+
+    #[test]
+    fn no_runner_up() {
+        let mut egraph = EGraph::new(MyAnalysis::default());
+        let root = egraph.add_expr(&"(access (access-tensor t-32-32) 1)".parse().unwrap());
+        egraph.rebuild();
+
+        let extraction = extract_lexicographic(&egraph, root, vec![latency(&egraph)]);
+        assert_eq!(extraction.costs, vec![("latency".to_string(), 4.0)]);
+        assert_eq!(extraction.binding_objective, None);
+    }
+}
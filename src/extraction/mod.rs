@@ -1,18 +1,24 @@
+pub mod autotuning;
 pub mod ilp;
+pub mod lexicographic;
+pub mod oracle;
+pub mod overrides;
+pub mod sampling;
 
-use crate::language::{ComputeType, Language, MyAnalysis};
+use crate::language::{ComputeType, Language, MyAnalysis, MyAnalysisData};
 use egg::{CostFunction, EGraph, Id, Language as LanguageTrait, Pattern, Searcher};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 pub fn find_all_systolic_array_configurations(
     egraph: &EGraph<Language, MyAnalysis>,
 ) -> HashSet<(usize, usize)> {
     let mut systolic_arrays = HashSet::new();
-    for matches in "(systolic-array ?rows ?cols ?x ?y)"
-        .parse::<Pattern<Language>>()
-        .unwrap()
-        .search(egraph)
-        .iter()
+    for matches in [
+        "(systolic-array ?rows ?cols ?x ?y)",
+        "(systolic-array-with-activation ?activation ?rows ?cols ?x ?y)",
+    ]
+    .iter()
+    .flat_map(|pattern| pattern.parse::<Pattern<Language>>().unwrap().search(egraph))
     {
         for subst in matches.substs.iter() {
             systolic_arrays.insert((
@@ -54,6 +60,10 @@ impl egg::CostFunction<Language> for MonolithicCostFunction<'_> {
         let base_cost = match enode {
             &Language::SystolicArray([rows_id, cols_id, _tensor_0_id, _tensor_1_id])
             | &Language::SystolicArrayWithBlocking([rows_id, cols_id, _tensor_0_id, _tensor_1_id])
+            | &Language::SystolicArrayWithActivation([_, rows_id, cols_id, _tensor_0_id, _tensor_1_id])
+            | &Language::SparseSystolicArray([rows_id, cols_id, _tensor_0_id, _tensor_1_id])
+            | &Language::SystolicArrayConv2dIm2colNchwOihwWithBlocking([rows_id, cols_id, ..])
+            | &Language::SystolicArrayConv2dIm2colNhwcHwioWithBlocking([rows_id, cols_id, ..])
                 if (
                     MyAnalysis::get_usize(rows_id, self.egraph),
                     MyAnalysis::get_usize(cols_id, self.egraph),
@@ -63,25 +73,43 @@ impl egg::CostFunction<Language> for MonolithicCostFunction<'_> {
             }
 
             Language::Symbol(_)
+            | Language::TensorDecl(_)
             | Language::ConstantTensor(_)
             | Language::AccessLiteral(_)
             | Language::Literal(_)
             | Language::NotNanFloat64(_)
             | Language::SystolicArray(_)
             | Language::SystolicArrayWithBlocking(_)
+            | Language::SystolicArrayWithActivation(_)
+            | Language::SystolicArrayConv2dIm2colNchwOihwWithBlocking(_)
+            | Language::SystolicArrayConv2dIm2colNhwcHwioWithBlocking(_)
+            | Language::PoolingUnit(_)
+            | Language::ActivationUnit(_)
+            | Language::VectorAlu(_)
+            | Language::SparseSystolicArray(_)
             | Language::Num(_)
             | Language::ConstructTuple(_)
             | Language::TupleGetItem(_)
+            | Language::Outputs(_)
             | Language::AccessSlice(_)
             | Language::AccessConcatenate(_)
             | Language::AccessPad(_)
             | Language::AccessWindows(_)
+            | Language::AccessWindowsWithConvention(_)
             | Language::PadType(_)
+            | Language::WindowConvention(_)
+            | Language::RoundingMode(_)
             | Language::Access(_)
             | Language::AccessTensor(_)
             | Language::ShapeOf(_)
             | Language::ShapeRemoveAxis(_)
             | Language::ShapeInsertAxis(_)
+            | Language::ShapeConcat(_)
+            | Language::ShapeDim(_)
+            | Language::UsizeAdd(_)
+            | Language::UsizeSub(_)
+            | Language::UsizeMul(_)
+            | Language::UsizeDiv(_)
             | Language::Shape(_)
             | Language::AccessSqueeze(_)
             | Language::AccessCartesianProduct(_)
@@ -89,6 +117,7 @@ impl egg::CostFunction<Language> for MonolithicCostFunction<'_> {
             | Language::AccessReshape(_)
             | Language::AccessShiftRight(_)
             | Language::AccessInsertAxis(_)
+            | Language::AccessReverse(_)
             | Language::AccessBroadcast(_)
             | Language::AccessShape(_)
             | Language::List(_)
@@ -100,6 +129,9 @@ impl egg::CostFunction<Language> for MonolithicCostFunction<'_> {
             // way to handle them.
             // TODO(@gussmith23) We shouldn't have to extract ANY computes!
             | Language::Compute(_)
+            | Language::ComputeWithAccumulator(_)
+            | Language::Cast(_)
+            | Language::Requantize(_)
             | Language::GetAccessShape(_)
             | Language::AccessTranspose(_) => 1,
             | Language::AcceleratorCall(_) => 0,
@@ -110,26 +142,43 @@ impl egg::CostFunction<Language> for MonolithicCostFunction<'_> {
             // TODO(@gussmith23) We shouldn't have to extract ANY computes!
             Language::ComputeType(t) => match t {
                 crate::language::ComputeType::DotProduct => Self::INFINITY_VALUE,
+                crate::language::ComputeType::SparseDotProduct => Self::INFINITY_VALUE,
                 crate::language::ComputeType::ReduceSum => 1,
                 crate::language::ComputeType::ReLU => 1,
                 crate::language::ComputeType::Sqrt => 1,
                 crate::language::ComputeType::Negative => 1,
+                crate::language::ComputeType::Sigmoid => 1,
+                crate::language::ComputeType::Tanh => 1,
                 crate::language::ComputeType::ElementwiseAdd => 1,
                 crate::language::ComputeType::ElementwiseMul => 1,
                 crate::language::ComputeType::ElementwiseDiv => 1,
                 crate::language::ComputeType::ReduceMax => 1,
                 crate::language::ComputeType::Softmax => 1,
                 crate::language::ComputeType::ReduceMean => 1,
+                crate::language::ComputeType::ReduceMeanVar => 1,
+                crate::language::ComputeType::LogicalAnd => 1,
+                crate::language::ComputeType::LogicalOr => 1,
+                crate::language::ComputeType::LogicalNot => 1,
             }
 
-            Language::SystolicArrayConv2dIm2colNchwOihwWithBlocking(_) => todo!(),
-            Language::SystolicArrayConv2dIm2colNhwcHwioWithBlocking(_) => todo!(),
             Language::SystolicArrayConv2dNchwOihwWithBlocking(_) => todo!(),
             Language::SystolicArrayConv2dNhwcHwioWithBlocking(_) => todo!(),
-            Language::DataType(_) => todo!(),
-
-            // Don't extract relay nodes.
-            Language::RelayOperatorCall(_)
+            Language::DataType(_) => 1,
+
+            // Don't extract relay nodes or high-level nodes; these need to be
+            // lowered first.
+            Language::Conv1d(_)
+            | Language::Conv2d(_)
+            | Language::Conv3d(_)
+            | Language::Conv2dTranspose(_)
+            | Language::BiasAdd(_)
+            | Language::AdaptivePool2d(_)
+            | Language::BatchMatmul(_)
+            | Language::LayerNorm(_)
+            | Language::GroupNorm(_)
+            | Language::Dropout(_)
+            | Language::Unroll(_)
+            | Language::RelayOperatorCall(_)
             | Language::RelayOperator(_)
             | Language::RelayActivationLayout(_)
             | Language::RelayKernelLayout(_) => Self::INFINITY_VALUE,
@@ -170,21 +219,33 @@ impl CostFunction<Language> for SimpleCostFunction {
             Language::RelayOperatorCall(_) => todo!(),
             Language::RelayActivationLayout(_) => todo!(),
             Language::RelayKernelLayout(_) => todo!(),
-            Language::DataType(_) => todo!(),
+            Language::DataType(_) => 1,
             Language::SystolicArrayConv2dNchwOihwWithBlocking(_) => todo!(),
             Language::SystolicArrayConv2dNhwcHwioWithBlocking(_) => todo!(),
-            Language::SystolicArrayConv2dIm2colNchwOihwWithBlocking(_) => todo!(),
-            Language::SystolicArrayConv2dIm2colNhwcHwioWithBlocking(_) => todo!(),
             Language::ConstructTuple(_) => todo!(),
             Language::TupleGetItem(_) => todo!(),
 
             // Cannot extract compute: compute must be lowered to an atom.
-            Compute(_) => std::usize::MAX,
+            Compute(_) | ComputeWithAccumulator(_) => std::usize::MAX,
+            // Likewise, high-level nodes must be lowered first.
+            Conv1d(_)
+            | Conv2d(_)
+            | Conv3d(_)
+            | Conv2dTranspose(_)
+            | BiasAdd(_)
+            | AdaptivePool2d(_)
+            | BatchMatmul(_)
+            | LayerNorm(_)
+            | GroupNorm(_)
+            | Dropout(_)
+            | AccessWindowsWithConvention(_) => std::usize::MAX,
+            // Unrolls must be expanded before the egraph is built.
+            Unroll(_) => std::usize::MAX,
             AcceleratorFunc(_) => 1,
             AcceleratorCall(_) => 1,
             ConstantTensor(_) => 1,
             // Extracting hardware atoms is encouraged
-            SystolicArray(_) => {
+            SystolicArray(_) | SystolicArrayWithActivation(_) => {
                 if !self.prefer_systolic_arrays_with_blocking {
                     1
                 } else {
@@ -198,6 +259,12 @@ impl CostFunction<Language> for SimpleCostFunction {
                     usize::MAX
                 }
             }
+            PoolingUnit(_) | ActivationUnit(_) | VectorAlu(_) | SparseSystolicArray(_) => 1,
+            // Both layouts of the im2col convolution are atoms; which one is
+            // cheaper depends on how many transposes its inputs and output
+            // need, given the layout the frontend used.
+            SystolicArrayConv2dIm2colNchwOihwWithBlocking(_)
+            | SystolicArrayConv2dIm2colNhwcHwioWithBlocking(_) => 1,
             // Extracting various access patterns is essential.
             AccessWindows(_)
             | Access(_)
@@ -214,11 +281,17 @@ impl CostFunction<Language> for SimpleCostFunction {
             | AccessSqueeze(_)
             | AccessPad(_)
             | AccessInsertAxis(_)
+            | AccessReverse(_)
             | AccessBroadcast(_) => 1,
+            // Data type conversions are cheap, elementwise, and needed at
+            // quantization boundaries.
+            Cast(_) | Requantize(_) => 1,
             // Other glenside constructs that are necessary.
             Shape(_) | ShapeOf(_) | SliceShape(_) | ShapeInsertAxis(_) | ShapeRemoveAxis(_)
-            | List(_) | AccessShape(_) | Num(_) | PadType(_) | ComputeType(_) | Symbol(_)
-            | Literal(_) | NotNanFloat64(_) => 1,
+            | ShapeConcat(_) | ShapeDim(_) | UsizeAdd(_) | UsizeSub(_) | UsizeMul(_)
+            | UsizeDiv(_) | List(_) | AccessShape(_) | Num(_) | PadType(_)
+            | WindowConvention(_) | RoundingMode(_) | ComputeType(_) | Symbol(_)
+            | TensorDecl(_) | Outputs(_) | Literal(_) | NotNanFloat64(_) => 1,
         };
 
         enode.fold(base_cost, |sum, id| sum.saturating_add(costs(id)))
@@ -250,14 +323,29 @@ impl CostFunction<Language> for AcceleratorCostFunction {
             | Language::AccessShape(_)
             | Language::AcceleratorFunc(_)
             | Language::Symbol(_)
+            | Language::TensorDecl(_)
             | Language::RelayOperator(_)
             | Language::PadType(_)
+            | Language::WindowConvention(_)
+            | Language::RoundingMode(_)
             | Language::ConstructTuple(_)
+            | Language::Outputs(_)
             | Language::ConstantTensor(_)
             | Language::TupleGetItem(_)
             | Language::DataType(_)
             | Language::AccessTensor(_) => 0.0,
-            Language::RelayOperatorCall(_) => self.0 / 2.0,
+            Language::RelayOperatorCall(_)
+            | Language::Conv1d(_)
+            | Language::Conv2d(_)
+            | Language::Conv3d(_)
+            | Language::Conv2dTranspose(_)
+            | Language::BiasAdd(_)
+            | Language::AdaptivePool2d(_)
+            | Language::BatchMatmul(_)
+            | Language::LayerNorm(_)
+            | Language::GroupNorm(_)
+            | Language::Dropout(_)
+            | Language::Unroll(_) => self.0 / 2.0,
             Language::AccessTranspose(_)
             | Language::RelayKernelLayout(_)
             | Language::RelayActivationLayout(_)
@@ -265,21 +353,33 @@ impl CostFunction<Language> for AcceleratorCostFunction {
             | Language::AccessPad(_)
             | Language::AccessFlatten(_)
             | Language::AccessWindows(_)
+            | Language::AccessWindowsWithConvention(_)
             | Language::AccessInsertAxis(_)
+            | Language::AccessReverse(_)
             | Language::AccessSqueeze(_) => 1.0,
 
-            Language::Compute(_) => 1.0,
+            Language::Compute(_)
+            | Language::ComputeWithAccumulator(_)
+            | Language::Cast(_)
+            | Language::Requantize(_) => 1.0,
             Language::AccessReshape(_) => self.0,
             Language::ComputeType(compute_type) => match compute_type {
                 ComputeType::DotProduct
+                | ComputeType::SparseDotProduct
                 | ComputeType::Softmax
                 | ComputeType::ReLU
                 | ComputeType::ReduceSum
-                | ComputeType::ReduceMean => self.0,
+                | ComputeType::ReduceMean
+                | ComputeType::ReduceMeanVar => self.0,
                 _ => 1.0,
             },
             Language::AccessCartesianProduct(_)
             | Language::SystolicArray(_)
+            | Language::SystolicArrayWithActivation(_)
+            | Language::PoolingUnit(_)
+            | Language::ActivationUnit(_)
+            | Language::VectorAlu(_)
+            | Language::SparseSystolicArray(_)
             | Language::AccessBroadcast(_)
             | Language::SystolicArrayConv2dIm2colNchwOihwWithBlocking(_)
             | Language::SystolicArrayConv2dIm2colNhwcHwioWithBlocking(_)
@@ -290,6 +390,12 @@ impl CostFunction<Language> for AcceleratorCostFunction {
             | Language::SliceShape(_)
             | Language::ShapeInsertAxis(_)
             | Language::ShapeRemoveAxis(_)
+            | Language::ShapeConcat(_)
+            | Language::ShapeDim(_)
+            | Language::UsizeAdd(_)
+            | Language::UsizeSub(_)
+            | Language::UsizeMul(_)
+            | Language::UsizeDiv(_)
             | Language::AccessSlice(_)
             | Language::AccessConcatenate(_)
             | Language::AccessShiftRight(_)
@@ -299,12 +405,193 @@ impl CostFunction<Language> for AcceleratorCostFunction {
     }
 }
 
+/// Per-operator penalties on data reorganization (transposes, reshapes,
+/// padding, etc.), which the other cost functions treat as nearly free.
+/// Each node of an operator named in `weights` (by its name in the language,
+/// e.g. `access-transpose`) costs its weight times the number of bytes it
+/// moves: the total size of its access pattern arguments.
+///
+/// A chain of views such as `access-pad`, `access-transpose` and then
+/// `access-windows` doesn't need each of its steps materialized, though: the
+/// consumer of the chain can read through all of them at once, with a single
+/// strided access (see [`crate::language::schedule::AccessDescriptor`]). With
+/// `compose_views` set, these views cost nothing themselves. Instead, the
+/// node consuming a chain of them is charged once for the data it reads
+/// through the chain, at the weight of the heaviest operator in it.
+#[derive(Clone, Debug)]
+pub struct LayoutPenalties {
+    pub weights: HashMap<String, f64>,
+    /// The size of each tensor element, in bytes.
+    pub element_bytes: usize,
+    /// Whether to charge chains of views to their consumers, rather than
+    /// charging each view in the chain.
+    pub compose_views: bool,
+}
+impl Default for LayoutPenalties {
+    fn default() -> Self {
+        LayoutPenalties {
+            weights: HashMap::default(),
+            element_bytes: 4,
+            compose_views: false,
+        }
+    }
+}
+impl LayoutPenalties {
+    /// Whether `enode` is a view which a strided access can read through,
+    /// rather than one which must be materialized.
+    fn is_view(enode: &Language) -> bool {
+        matches!(
+            enode,
+            Language::Access(_)
+                | Language::AccessTranspose(_)
+                | Language::AccessSlice(_)
+                | Language::AccessReverse(_)
+                | Language::AccessSqueeze(_)
+                | Language::AccessInsertAxis(_)
+                | Language::AccessBroadcast(_)
+                | Language::AccessWindows(_)
+                | Language::AccessWindowsWithConvention(_)
+                | Language::AccessPad(_)
+        )
+    }
+
+    fn weight(&self, enode: &Language) -> f64 {
+        self.weights.get(&enode.to_string()).copied().unwrap_or(0.0)
+    }
+
+    /// The weight of the heaviest operator in the chains of views which
+    /// eclass `id` may be built with. As extraction may choose any of them,
+    /// the heaviest is charged.
+    fn chain_weight(&self, id: Id, egraph: &EGraph<Language, MyAnalysis>) -> f64 {
+        fn helper(
+            penalties: &LayoutPenalties,
+            id: Id,
+            egraph: &EGraph<Language, MyAnalysis>,
+            visited: &mut HashSet<Id>,
+        ) -> f64 {
+            let id = egraph.find(id);
+            if !visited.insert(id) {
+                return 0.0;
+            }
+            egraph[id]
+                .nodes
+                .iter()
+                .filter(|enode| LayoutPenalties::is_view(enode))
+                .map(|enode| {
+                    enode
+                        .children()
+                        .iter()
+                        .filter(|child| {
+                            matches!(egraph[**child].data, MyAnalysisData::AccessPattern(_))
+                        })
+                        .map(|child| helper(penalties, *child, egraph, visited))
+                        .fold(penalties.weight(enode), f64::max)
+                })
+                .fold(0.0, f64::max)
+        }
+        helper(self, id, egraph, &mut HashSet::default())
+    }
+
+    /// The penalty of `enode`, in `egraph`.
+    pub fn penalty(&self, enode: &Language, egraph: &EGraph<Language, MyAnalysis>) -> f64 {
+        if self.compose_views && Self::is_view(enode) {
+            return 0.0;
+        }
+        let weight = self.weight(enode);
+        let bytes = |id: Id| match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => {
+                (a.as_vec().iter().product::<usize>() * self.element_bytes) as f64
+            }
+            _ => 0.0,
+        };
+        enode
+            .children()
+            .iter()
+            .map(|id| {
+                if self.compose_views {
+                    weight.max(self.chain_weight(*id, egraph)) * bytes(*id)
+                } else {
+                    weight * bytes(*id)
+                }
+            })
+            .sum()
+    }
+}
+
+/// Adds [`LayoutPenalties`] to another cost function, so that extracted
+/// designs avoid gratuitous data reorganization:
+/// ```
+/// use egg::{EGraph, Extractor};
+/// use glenside::extraction::{LayoutPenalties, MonolithicCostFunction, WithLayoutPenalties};
+/// use glenside::language::MyAnalysis;
+///
+/// let mut egraph = EGraph::new(MyAnalysis::default());
+/// let id = egraph.add_expr(&"(access (access-tensor t-32-32) 1)".parse().unwrap());
+///
+/// let mut penalties = LayoutPenalties::default();
+/// penalties.weights.insert("access-transpose".to_string(), 1.0);
+/// let (cost, _) = Extractor::new(
+///     &egraph,
+///     WithLayoutPenalties {
+///         inner: MonolithicCostFunction {
+///             systolic_array_configuration: (32, 32),
+///             egraph: &egraph,
+///             prefer_systolic_arrays_with_blocking: false,
+///         },
+///         penalties,
+///         egraph: &egraph,
+///     },
+/// )
+/// .find_best(id);
+/// assert_eq!(cost, 4);
+/// ```
+pub struct WithLayoutPenalties<'a, C> {
+    pub inner: C,
+    pub penalties: LayoutPenalties,
+    pub egraph: &'a EGraph<Language, MyAnalysis>,
+}
+impl<C: CostFunction<Language, Cost = usize>> CostFunction<Language>
+    for WithLayoutPenalties<'_, C>
+{
+    type Cost = usize;
+
+    fn cost<F>(&mut self, enode: &Language, mut costs: F) -> Self::Cost
+    where
+        F: FnMut(Id) -> Self::Cost,
+    {
+        self.inner.cost(enode, &mut costs)
+            + self.penalties.penalty(enode, self.egraph).round() as usize
+    }
+}
+
+/// Extracts the best program from eclass `id`, like
+/// [`egg::Extractor::find_best`], but reports it as an error if its cost
+/// reaches `unextractable`, the cost which `cost_function` gives programs
+/// which shouldn't be extracted (e.g.
+/// [`MonolithicCostFunction::INFINITY_VALUE`]).
+pub fn try_find_best<C: CostFunction<Language>>(
+    egraph: &EGraph<Language, MyAnalysis>,
+    id: Id,
+    cost_function: C,
+    unextractable: C::Cost,
+) -> crate::error::Result<(C::Cost, egg::RecExpr<Language>)> {
+    let (cost, expr) = egg::Extractor::new(egraph, cost_function).find_best(id);
+    if cost >= unextractable {
+        return Err(crate::error::GlensideError::Extraction(format!(
+            "The best program in eclass {} has cost {:?}, which is unextractable: {}",
+            id,
+            cost,
+            expr.pretty(80)
+        )));
+    }
+    Ok((cost, expr))
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::language::MyAnalysis;
     use super::*;
     use egg::{EGraph, Extractor};
-    use std::collections::HashMap;
 
     #[test]
     fn find_systolic_array_configs_0() {
@@ -509,6 +796,7 @@ mod tests {
         let mut egraph = EGraph::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         egraph.rebuild();
@@ -543,4 +831,147 @@ mod tests {
   (access (access-tensor weight2) 0))"
         );
     }
+
+    #[test]
+    fn layout_penalties() {
+        let transposed = "
+         (access-transpose
+          (access-transpose (access (access-tensor t-32-32) 1) (list 1 0))
+          (list 1 0))"
+            .parse()
+            .unwrap();
+        let reshaped = "
+         (access-squeeze
+          (access-insert-axis
+           (access-squeeze
+            (access-insert-axis
+             (access-squeeze
+              (access-insert-axis (access (access-tensor t-32-32) 1) 0)
+              0)
+             0)
+            0)
+           0)
+          0)"
+        .parse()
+        .unwrap();
+        let mut egraph = EGraph::new(MyAnalysis::default());
+        let id = egraph.add_expr(&transposed);
+        let reshaped_id = egraph.add_expr(&reshaped);
+        egraph.union(id, reshaped_id);
+        egraph.rebuild();
+        let list_id = egraph.add_expr(&"(list 1 0)".parse().unwrap());
+
+        let extract = |penalties| {
+            Extractor::new(
+                &egraph,
+                WithLayoutPenalties {
+                    inner: MonolithicCostFunction {
+                        systolic_array_configuration: (32, 32),
+                        egraph: &egraph,
+                        prefer_systolic_arrays_with_blocking: false,
+                    },
+                    penalties,
+                    egraph: &egraph,
+                },
+            )
+            .find_best(id)
+        };
+
+        // Without penalties, the transposes are smaller.
+        let (cost, expr) = extract(LayoutPenalties::default());
+        assert_eq!(cost, 12);
+        assert_eq!(expr.pretty(80), transposed.pretty(80));
+
+        let mut penalties = LayoutPenalties::default();
+        penalties
+            .weights
+            .insert("access-transpose".to_string(), 0.5);
+        let (cost, expr) = extract(penalties.clone());
+        assert_eq!(cost, 16);
+        assert_eq!(expr.pretty(80), reshaped.pretty(80));
+
+        // Each transpose moves 32 * 32 4-byte elements.
+        let transpose = Language::AccessTranspose([id, list_id]);
+        assert_eq!(penalties.penalty(&transpose, &egraph), 2048.0);
+    }
+
+    #[test]
+    fn composed_layout_penalties() {
+        // x, padded with two rows of zeros and transposed, through a chain of
+        // views...
+        let chained = "
+         (access-transpose
+          (access-transpose
+           (access-transpose
+            (access-pad (access (access-tensor x) 0) zero-padding 0 0 2)
+            (list 1 0))
+           (list 1 0))
+          (list 1 0))"
+            .parse()
+            .unwrap();
+        // ...or by materializing a concatenation with zeros.
+        let concatenated = "
+         (access-transpose
+          (access-concatenate (access (access-tensor x) 0) (access (access-tensor zeros) 0) 0)
+          (list 1 0))"
+            .parse()
+            .unwrap();
+        let mut name_to_shape = HashMap::default();
+        name_to_shape.insert("x".to_string(), vec![30, 32]);
+        name_to_shape.insert("zeros".to_string(), vec![2, 32]);
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let chained_id = egraph.add_expr(&chained);
+        let concatenated_id = egraph.add_expr(&concatenated);
+        egraph.union(chained_id, concatenated_id);
+        let relu_id = egraph.add(Language::ComputeType(ComputeType::ReLU));
+        let id = egraph.add(Language::Compute([relu_id, chained_id]));
+        egraph.rebuild();
+
+        let mut penalties = LayoutPenalties::default();
+        for op in &["access-transpose", "access-pad", "access-concatenate"] {
+            penalties.weights.insert(op.to_string(), 1.0);
+        }
+        let extract = |penalties| {
+            Extractor::new(
+                &egraph,
+                WithLayoutPenalties {
+                    inner: MonolithicCostFunction {
+                        systolic_array_configuration: (32, 32),
+                        egraph: &egraph,
+                        prefer_systolic_arrays_with_blocking: false,
+                    },
+                    penalties,
+                    egraph: &egraph,
+                },
+            )
+            .find_best(id)
+        };
+
+        // Charging each view, the pad and three transposes move 3840 + 3 *
+        // 4096 bytes, while the concatenation and transpose move only 2 *
+        // 4096.
+        let (_, expr) = extract(penalties.clone());
+        assert!(expr.pretty(80).contains("access-concatenate"));
+
+        // Composed, the chain of views is only read once, by the relu, while
+        // the concatenation must still be materialized before the relu reads
+        // its transpose.
+        penalties.compose_views = true;
+        let (_, expr) = extract(penalties.clone());
+        assert!(!expr.pretty(80).contains("access-concatenate"));
+        assert!(expr.pretty(80).contains("access-pad"));
+
+        let compute = &egraph[egraph.find(id)].nodes[0];
+        assert_eq!(penalties.penalty(compute, &egraph), 4096.0);
+        let transpose = egraph[egraph.find(chained_id)]
+            .nodes
+            .iter()
+            .find(|enode| matches!(enode, Language::AccessTranspose(_)))
+            .unwrap();
+        assert_eq!(penalties.penalty(transpose, &egraph), 0.0);
+    }
 }
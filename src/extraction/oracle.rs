@@ -0,0 +1,274 @@
+//! Externally-provided costs.
+//!
+//! The analytical cost models in [`extraction`](super) are coarse. When
+//! better numbers are available for some enodes, e.g. measured on the
+//! hardware or predicted by a learned cost model, [`OracleCosts`] lets an
+//! oracle supply them: for each enode, the oracle is asked about its operator
+//! and the shapes of its arguments and result, and may answer with a cost,
+//! which is used in place of (or blended with) the analytical cost. Enodes
+//! the oracle doesn't answer for keep their analytical cost.
+
+use crate::language::{Language, MyAnalysis, MyAnalysisData};
+use egg::{CostFunction, EGraph, Id, Language as LanguageTrait};
+use num_traits::{AsPrimitive, Zero};
+use std::collections::HashMap;
+
+/// What an oracle is asked about an enode.
+#[derive(Clone, Debug)]
+pub struct OracleQuery<'a> {
+    /// The enode's operator, e.g. `systolic-array`.
+    pub operator: String,
+    pub enode: &'a Language,
+    /// The shapes of the enode's tensor and access pattern arguments, in
+    /// order. Access patterns' shapes are their full shapes, i.e. their
+    /// shapes followed by their item shapes.
+    pub argument_shapes: Vec<Vec<usize>>,
+    /// The shape of the enode's result, if it's a tensor or an access
+    /// pattern.
+    pub shape: Option<Vec<usize>>,
+}
+
+/// How an oracle's cost for an enode is combined with its analytical cost.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Blend {
+    /// The oracle's cost replaces the analytical cost.
+    Replace,
+    /// `weight` times the oracle's cost, plus `1 - weight` times the
+    /// analytical cost. Both costs should be in comparable units.
+    Weighted(f64),
+}
+
+impl Blend {
+    fn apply(self, oracle: f64, analytical: f64) -> f64 {
+        match self {
+            Blend::Replace => oracle,
+            Blend::Weighted(weight) => weight * oracle + (1.0 - weight) * analytical,
+        }
+    }
+}
+
+/// The shape of the value in `eclass`, if it's a tensor or an access pattern.
+fn shape(egraph: &EGraph<Language, MyAnalysis>, eclass: Id) -> Option<Vec<usize>> {
+    match &egraph[eclass].data {
+        MyAnalysisData::AccessPattern(a) => Some(a.as_vec()),
+        MyAnalysisData::Shape(s) => Some(s.shape.slice().to_vec()),
+        _ => None,
+    }
+}
+
+/// Wraps another cost function, using costs from `oracle` for the enodes it
+/// answers for. The cost of each enode is its own (possibly blended) cost,
+/// plus the costs of its children.
+///
+/// Each enode of `egraph` is only asked about once, so the oracle may be
+/// expensive. It should only answer for enodes which should be extractable:
+/// its answers replace (or are blended with) whatever cost the inner cost
+/// function would have given, including costs marking enodes unextractable.
+/// ```
+/// use egg::{EGraph, Extractor};
+/// use glenside::extraction::oracle::{Blend, OracleCosts};
+/// use glenside::extraction::SimpleCostFunction;
+/// use glenside::language::MyAnalysis;
+///
+/// let mut egraph = EGraph::new(MyAnalysis::default());
+/// let id = egraph.add_expr(
+///     &"(systolic-array 32 32
+///        (access (access-tensor t-32-32) 1)
+///        (access (access-tensor t-32-32) 0))"
+///         .parse()
+///         .unwrap(),
+/// );
+/// egraph.rebuild();
+///
+/// let costs = OracleCosts::new(
+///     SimpleCostFunction::default(),
+///     |query| match (query.operator.as_str(), query.shape.as_deref()) {
+///         // Measured: 1000 for a 32x32 systolic array producing a 32x32 matrix.
+///         ("systolic-array", Some([32, 32])) => Some(1000.0),
+///         _ => None,
+///     },
+///     Blend::Replace,
+///     &egraph,
+/// );
+/// let (cost, _) = Extractor::new(&egraph, costs).find_best(id);
+/// // The systolic array, plus the analytical costs of the other 10 enodes.
+/// assert_eq!(cost, 1010.0);
+/// ```
+pub struct OracleCosts<'a, C, F> {
+    pub inner: C,
+    pub oracle: F,
+    pub blend: Blend,
+    pub egraph: &'a EGraph<Language, MyAnalysis>,
+    /// The oracle's answers so far.
+    answers: HashMap<Language, Option<f64>>,
+}
+
+impl<'a, C, F> OracleCosts<'a, C, F>
+where
+    C: CostFunction<Language>,
+    F: FnMut(&OracleQuery) -> Option<f64>,
+{
+    pub fn new(
+        inner: C,
+        oracle: F,
+        blend: Blend,
+        egraph: &'a EGraph<Language, MyAnalysis>,
+    ) -> Self {
+        OracleCosts {
+            inner,
+            oracle,
+            blend,
+            egraph,
+            answers: HashMap::default(),
+        }
+    }
+
+    /// The oracle's answer for `enode`, asking it if it hasn't been asked
+    /// yet.
+    fn answer(&mut self, enode: &Language) -> Option<f64> {
+        if let Some(answer) = self.answers.get(enode) {
+            return *answer;
+        }
+        let query = OracleQuery {
+            operator: enode.display_op().to_string(),
+            enode,
+            argument_shapes: enode
+                .children()
+                .iter()
+                .filter_map(|&child| shape(self.egraph, child))
+                .collect(),
+            shape: self
+                .egraph
+                .lookup(enode.clone())
+                .and_then(|eclass| shape(self.egraph, eclass)),
+        };
+        let answer = (self.oracle)(&query);
+        self.answers.insert(enode.clone(), answer);
+        answer
+    }
+}
+
+impl<C, F> CostFunction<Language> for OracleCosts<'_, C, F>
+where
+    C: CostFunction<Language>,
+    C::Cost: AsPrimitive<f64> + Zero,
+    F: FnMut(&OracleQuery) -> Option<f64>,
+{
+    type Cost = f64;
+
+    fn cost<G>(&mut self, enode: &Language, mut costs: G) -> Self::Cost
+    where
+        G: FnMut(Id) -> Self::Cost,
+    {
+        // The inner cost function's cost for the enode alone.
+        let analytical: f64 = self.inner.cost(enode, |_| C::Cost::zero()).as_();
+        let cost = match self.answer(enode) {
+            Some(oracle) => self.blend.apply(oracle, analytical),
+            None => analytical,
+        };
+        enode.fold(cost, |sum, id| sum + costs(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extraction::MonolithicCostFunction;
+    use egg::{Extractor, RecExpr};
+
+    /// An egraph in which a 64x64 matrix multiplication is both a single
+    /// 64x64 systolic array and two 64x32 ones.
+    fn egraph() -> (EGraph<Language, MyAnalysis>, Id) {
+        let mut egraph = EGraph::new(MyAnalysis::default());
+        let whole: RecExpr<Language> = "
+         (systolic-array 64 64
+          (access (access-tensor t-64-64) 1)
+          (access (access-tensor t-64-64) 0))"
+            .parse()
+            .unwrap();
+        let split: RecExpr<Language> = "
+         (access-concatenate
+          (systolic-array 64 32
+           (access (access-tensor t-64-64) 1)
+           (access-slice (access (access-tensor t-64-64) 0) 1 0 32))
+          (systolic-array 64 32
+           (access (access-tensor t-64-64) 1)
+           (access-slice (access (access-tensor t-64-64) 0) 1 32 64))
+          1)"
+        .parse()
+        .unwrap();
+        let id = egraph.add_expr(&whole);
+        let split_id = egraph.add_expr(&split);
+        egraph.union(id, split_id);
+        egraph.rebuild();
+        (egraph, id)
+    }
+
+    /// Whether `expr` uses the two 64x32 systolic arrays.
+    fn is_split(expr: &RecExpr<Language>) -> bool {
+        matches!(expr.as_ref().last(), Some(Language::AccessConcatenate(_)))
+    }
+
+    #[test]
+    fn oracle_overrides_analytical_costs() {
+        let (egraph, id) = egraph();
+        let monolithic = || MonolithicCostFunction {
+            systolic_array_configuration: (64, 64),
+            egraph: &egraph,
+            prefer_systolic_arrays_with_blocking: false,
+        };
+
+        // Analytically, only 64x64 systolic arrays are extractable.
+        let (_, expr) = Extractor::new(&egraph, monolithic()).find_best(id);
+        assert!(!is_split(&expr));
+
+        // The oracle has measured both kinds of systolic array: two 64x32
+        // arrays beat one 64x64 array.
+        let mut queries = Vec::default();
+        let oracle = |query: &OracleQuery| {
+            queries.push((query.operator.clone(), query.argument_shapes.clone()));
+            match (query.operator.as_str(), query.shape.as_deref()) {
+                ("systolic-array", Some([64, 64])) => Some(100.0),
+                ("systolic-array", Some([64, 32])) => Some(10.0),
+                _ => None,
+            }
+        };
+        let (cost, expr) = Extractor::new(
+            &egraph,
+            OracleCosts::new(monolithic(), oracle, Blend::Replace, &egraph),
+        )
+        .find_best(id);
+        assert!(is_split(&expr));
+        assert!(cost < 100.0);
+
+        // Each enode was only asked about once.
+        assert_eq!(queries.len(), egraph.total_number_of_nodes());
+        assert!(queries.contains(&(
+            "systolic-array".to_string(),
+            vec![vec![64, 64], vec![64, 32]]
+        )));
+    }
+
+    #[test]
+    fn blended_costs() {
+        let (egraph, id) = egraph();
+        let oracle = |query: &OracleQuery| match query.operator.as_str() {
+            "systolic-array" => Some(101.0),
+            _ => None,
+        };
+        // Half of the oracle's 101, plus half of the analytical 1.
+        let (cost, expr) = Extractor::new(
+            &egraph,
+            OracleCosts::new(
+                crate::extraction::SimpleCostFunction::default(),
+                oracle,
+                Blend::Weighted(0.5),
+                &egraph,
+            ),
+        )
+        .find_best(id);
+        assert!(!is_split(&expr));
+        // Plus the other 10 enodes of the unsplit program, at 1 each.
+        assert_eq!(cost, 51.0 + 10.0);
+    }
+}
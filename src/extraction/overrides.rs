@@ -0,0 +1,322 @@
+//! Per-layer mapping overrides.
+//!
+//! Automatic extraction picks each layer's mapping by cost alone. Experts
+//! often know better for a few layers: "run conv1 on the 16x16 array", "leave
+//! softmax in software". Overrides pin the layers, identified by their
+//! [`Provenance`] labels (e.g. `nn.conv2d#0`), to a [`Mapping`], and leave
+//! the rest of the program to the cost function. They're given as JSON, e.g.
+//! ```json
+//! {
+//!   "nn.conv2d#0": { "mapping": "systolic-array", "rows": 16, "cols": 16 },
+//!   "nn.max_pool2d#0": { "mapping": "atom", "atom": "pooling-unit" },
+//!   "nn.softmax#0": { "mapping": "unmapped" }
+//! }
+//! ```
+//! and enforced as extraction constraints by [`WithMappingOverrides`], which
+//! makes the enodes violating them unextractable.
+
+use super::MonolithicCostFunction;
+use crate::error::{GlensideError, Result};
+use crate::hardware::ATOM_NAMES;
+use crate::language::provenance::Provenance;
+use crate::language::{Language, MyAnalysis};
+use egg::{CostFunction, EGraph, Id, Language as LanguageTrait};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// How a layer must be mapped.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mapping", rename_all = "kebab-case")]
+pub enum Mapping {
+    /// Onto a `rows` by `cols` systolic array.
+    SystolicArray { rows: usize, cols: usize },
+    /// Onto the atom named `atom`, e.g. `pooling-unit`, of whatever size.
+    Atom { atom: String },
+    /// Onto no atom at all.
+    Unmapped,
+}
+
+/// Mappings, keyed by the provenance label of the layer they're for.
+pub type MappingOverrides = HashMap<String, Mapping>;
+
+/// Parses overrides from JSON.
+pub fn overrides_from_json(json: &str) -> Result<MappingOverrides> {
+    let overrides: MappingOverrides = serde_json::from_str(json)
+        .map_err(|e| GlensideError::Parse(format!("Invalid mapping overrides: {}", e)))?;
+    for (label, mapping) in &overrides {
+        if let Mapping::Atom { atom } = mapping {
+            if !ATOM_NAMES.contains(&atom.as_str()) {
+                return Err(GlensideError::Parse(format!(
+                    "{} is mapped to unknown atom {}",
+                    label, atom
+                )));
+            }
+        }
+    }
+    Ok(overrides)
+}
+
+/// The name of the atom `enode` invokes, as in hardware specs, if it's an
+/// atom invocation. Int8 systolic arrays are plain `systolic-array`s in the
+/// egraph, so they're named as such.
+fn atom_name(enode: &Language) -> Option<&'static str> {
+    match enode {
+        Language::SystolicArray(_)
+        | Language::SystolicArrayWithBlocking(_)
+        | Language::SystolicArrayWithActivation(_)
+        | Language::SystolicArrayConv2dNchwOihwWithBlocking(_)
+        | Language::SystolicArrayConv2dNhwcHwioWithBlocking(_)
+        | Language::SystolicArrayConv2dIm2colNchwOihwWithBlocking(_)
+        | Language::SystolicArrayConv2dIm2colNhwcHwioWithBlocking(_) => Some("systolic-array"),
+        Language::SparseSystolicArray(_) => Some("sparse-systolic-array"),
+        Language::PoolingUnit(_) => Some("pooling-unit"),
+        Language::ActivationUnit(_) => Some("activation-unit"),
+        Language::VectorAlu(_) => Some("vector-alu"),
+        Language::AcceleratorCall(_) => Some("accelerator-call"),
+        _ => None,
+    }
+}
+
+/// The `(rows, cols)` of the systolic array `enode` invokes.
+fn systolic_array_size(enode: &Language, egraph: &EGraph<Language, MyAnalysis>) -> (usize, usize) {
+    let (rows, cols) = match enode {
+        &Language::SystolicArray([rows, cols, ..])
+        | &Language::SystolicArrayWithBlocking([rows, cols, ..])
+        | &Language::SystolicArrayWithActivation([_, rows, cols, ..])
+        | &Language::SystolicArrayConv2dNchwOihwWithBlocking([rows, cols, ..])
+        | &Language::SystolicArrayConv2dNhwcHwioWithBlocking([rows, cols, ..])
+        | &Language::SystolicArrayConv2dIm2colNchwOihwWithBlocking([rows, cols, ..])
+        | &Language::SystolicArrayConv2dIm2colNhwcHwioWithBlocking([rows, cols, ..]) => {
+            (rows, cols)
+        }
+        _ => panic!("{:?} is not a systolic array", enode),
+    };
+    (
+        MyAnalysis::get_usize(rows, egraph),
+        MyAnalysis::get_usize(cols, egraph),
+    )
+}
+
+impl Mapping {
+    /// Whether `enode`, part of a layer which must be mapped as `self`, breaks
+    /// the mapping. Layers mapped onto an atom may only invoke that atom, and
+    /// may not leave any of their computations unmapped; unmapped layers may
+    /// not invoke any atom.
+    fn forbids(&self, enode: &Language, egraph: &EGraph<Language, MyAnalysis>) -> bool {
+        match (self, atom_name(enode)) {
+            (Mapping::Unmapped, name) => name.is_some(),
+            (_, None) => matches!(
+                enode,
+                Language::Compute(_) | Language::ComputeWithAccumulator(_)
+            ),
+            (Mapping::Atom { atom }, Some(name)) => atom != name,
+            (Mapping::SystolicArray { rows, cols }, Some("systolic-array")) => {
+                systolic_array_size(enode, egraph) != (*rows, *cols)
+            }
+            (Mapping::SystolicArray { .. }, Some(_)) => true,
+        }
+    }
+}
+
+/// Wraps another cost function, adding
+/// [`MonolithicCostFunction::INFINITY_VALUE`] to the cost of each enode which
+/// breaks an override, so that [`super::try_find_best`] reports programs
+/// which can't honor the overrides.
+///
+/// A layer is the eclass carrying its label, along with the unlabeled
+/// eclasses below it: rewrites introduce new eclasses (e.g. the transposes
+/// and reshapes around a systolic array), which are part of the layer they
+/// were introduced for. This is how
+/// [`Provenance::for_extracted_expr`] labels them too. The layer stops at
+/// labeled eclasses, which belong to other layers (e.g. the layer producing
+/// its input).
+pub struct WithMappingOverrides<'a, C> {
+    pub inner: C,
+    pub egraph: &'a EGraph<Language, MyAnalysis>,
+    /// The overrides each eclass is subject to.
+    constraints: HashMap<Id, Vec<Mapping>>,
+}
+
+impl<'a, C> WithMappingOverrides<'a, C> {
+    /// Given `provenance`, the provenance of `egraph`'s eclasses (see
+    /// [`Provenance::add_expr`]), constrains the layers `overrides` names.
+    /// Fails if an override names a label no eclass carries.
+    pub fn new(
+        inner: C,
+        overrides: &MappingOverrides,
+        provenance: &Provenance,
+        egraph: &'a EGraph<Language, MyAnalysis>,
+    ) -> Result<Self> {
+        let eclass_labels = provenance.by_eclass(egraph);
+        let mut constraints: HashMap<Id, Vec<Mapping>> = HashMap::default();
+        for (label, mapping) in overrides {
+            let mut to_visit = eclass_labels
+                .iter()
+                .filter(|(_, labels)| labels.contains(label))
+                .map(|(id, _)| *id)
+                .collect::<Vec<_>>();
+            if to_visit.is_empty() {
+                return Err(GlensideError::Extraction(format!(
+                    "No eclass is labeled {}, so it can't be mapped",
+                    label
+                )));
+            }
+            let mut visited = HashSet::new();
+            while let Some(id) = to_visit.pop() {
+                if !visited.insert(id) {
+                    continue;
+                }
+                constraints.entry(id).or_default().push(mapping.clone());
+                to_visit.extend(
+                    egraph[id]
+                        .nodes
+                        .iter()
+                        .flat_map(|enode| enode.children())
+                        .map(|child| egraph.find(*child))
+                        .filter(|child| !eclass_labels.contains_key(child)),
+                );
+            }
+        }
+        Ok(WithMappingOverrides {
+            inner,
+            egraph,
+            constraints,
+        })
+    }
+}
+
+impl<C: CostFunction<Language, Cost = usize>> CostFunction<Language>
+    for WithMappingOverrides<'_, C>
+{
+    type Cost = usize;
+
+    fn cost<F>(&mut self, enode: &Language, mut costs: F) -> Self::Cost
+    where
+        F: FnMut(Id) -> Self::Cost,
+    {
+        let egraph = self.egraph;
+        let forbidden = egraph
+            .lookup(enode.clone())
+            .and_then(|id| self.constraints.get(&id))
+            .map_or(false, |mappings| {
+                mappings
+                    .iter()
+                    .any(|mapping| mapping.forbids(enode, egraph))
+            });
+        let cost = self.inner.cost(enode, &mut costs);
+        if forbidden {
+            cost.saturating_add(MonolithicCostFunction::INFINITY_VALUE)
+        } else {
+            cost
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extraction::try_find_best;
+    use egg::{AstSize, RecExpr};
+
+    /// An egraph in which a 64x64 matrix multiplication, labeled `dense`, is
+    /// a dot product, a single 64x64 systolic array, and two 64x32 ones.
+    fn egraph() -> (EGraph<Language, MyAnalysis>, Id, Provenance) {
+        let mut egraph = EGraph::new(MyAnalysis::default());
+        let mut id = None;
+        for expr in &[
+            "(systolic-array 64 64
+              (access (access-tensor t-64-64) 1)
+              (access (access-tensor t-64-64) 0))",
+            "(access-concatenate
+              (systolic-array 64 32
+               (access (access-tensor t-64-64) 1)
+               (access-slice (access (access-tensor t-64-64) 0) 1 0 32))
+              (systolic-array 64 32
+               (access (access-tensor t-64-64) 1)
+               (access-slice (access (access-tensor t-64-64) 0) 1 32 64))
+              1)",
+            "(compute dot-product
+              (access-cartesian-product
+               (access (access-tensor t-64-64) 1)
+               (access (access-transpose (access-tensor t-64-64) (list 1 0)) 1)))",
+        ] {
+            let expr: RecExpr<Language> = expr.parse().unwrap();
+            let new_id = egraph.add_expr(&expr);
+            if let Some(id) = id {
+                egraph.union(id, new_id);
+            }
+            id = Some(new_id);
+        }
+        egraph.rebuild();
+        let id = egraph.find(id.unwrap());
+
+        let mut provenance = Provenance::default();
+        provenance.add(id, "dense");
+        (egraph, id, provenance)
+    }
+
+    fn extract(overrides: &str) -> Result<RecExpr<Language>> {
+        let (egraph, id, provenance) = egraph();
+        let overrides = overrides_from_json(overrides)?;
+        let cost_function = WithMappingOverrides::new(AstSize, &overrides, &provenance, &egraph)?;
+        try_find_best(
+            &egraph,
+            id,
+            cost_function,
+            MonolithicCostFunction::INFINITY_VALUE,
+        )
+        .map(|(_, expr)| expr)
+    }
+
+    #[test]
+    fn no_overrides() {
+        assert!(extract("{}")
+            .unwrap()
+            .pretty(1000)
+            .starts_with("(systolic-array 64 64"));
+    }
+
+    #[test]
+    fn systolic_array() {
+        assert!(
+            extract(r#"{ "dense": { "mapping": "systolic-array", "rows": 64, "cols": 32 } }"#)
+                .unwrap()
+                .pretty(1000)
+                .starts_with("(access-concatenate (systolic-array 64 32")
+        );
+    }
+
+    #[test]
+    fn unmapped() {
+        let expr = extract(r#"{ "dense": { "mapping": "unmapped" } }"#).unwrap();
+        assert!(expr.pretty(1000).starts_with("(compute dot-product"));
+    }
+
+    #[test]
+    fn impossible() {
+        assert!(matches!(
+            extract(r#"{ "dense": { "mapping": "atom", "atom": "pooling-unit" } }"#),
+            Err(GlensideError::Extraction(_))
+        ));
+        assert!(matches!(
+            extract(r#"{ "dense": { "mapping": "systolic-array", "rows": 16, "cols": 16 } }"#),
+            Err(GlensideError::Extraction(_))
+        ));
+    }
+
+    #[test]
+    fn unknown_label() {
+        match extract(r#"{ "conv": { "mapping": "unmapped" } }"#) {
+            Err(GlensideError::Extraction(message)) => assert!(message.contains("conv")),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn unknown_atom() {
+        assert!(matches!(
+            overrides_from_json(r#"{ "dense": { "mapping": "atom", "atom": "tpu" } }"#),
+            Err(GlensideError::Parse(_))
+        ));
+    }
+}
@@ -0,0 +1,281 @@
+//! Sampling-based extraction.
+//!
+//! Greedy extraction (via [`egg::Extractor`]) and ILP extraction each return a
+//! single program. When populating a pool of candidates to measure on actual
+//! hardware (e.g. for autotuning), we instead want many different programs,
+//! biased towards cheap ones. [`sample`] draws such programs from an egraph.
+
+use crate::language::{Language, MyAnalysis};
+use egg::{CostFunction, EGraph, Id, Language as LanguageTrait, RecExpr};
+use num_traits::ToPrimitive;
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+
+/// Computes the cost of the cheapest program in each eclass, as
+/// [`egg::Extractor`] does. Eclasses with no finite program are omitted.
+fn find_best_costs<CF>(
+    egraph: &EGraph<Language, MyAnalysis>,
+    cost_function: &mut CF,
+) -> HashMap<Id, CF::Cost>
+where
+    CF: CostFunction<Language>,
+{
+    let mut costs: HashMap<Id, CF::Cost> = HashMap::default();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for eclass in egraph.classes() {
+            let best = eclass
+                .nodes
+                .iter()
+                .filter(|node| {
+                    node.children()
+                        .iter()
+                        .all(|id| costs.contains_key(&egraph.find(*id)))
+                })
+                .map(|node| cost_function.cost(node, |id| costs[&egraph.find(id)].clone()))
+                .min_by(|a, b| a.partial_cmp(b).unwrap());
+            if let Some(best) = best {
+                if costs.get(&eclass.id).map_or(true, |old| best < *old) {
+                    costs.insert(eclass.id, best);
+                    changed = true;
+                }
+            }
+        }
+    }
+    costs
+}
+
+/// Draws up to `num_samples` distinct programs from eclass `root`.
+///
+/// Programs are built top-down. In each eclass, an enode is picked at random
+/// with probability proportional to `exp(-(c - c_min) / temperature)`, where
+/// `c` is the cost of the cheapest program rooted at that enode, and `c_min` is
+/// the cost of the cheapest program in the eclass. Thus a `temperature` of 0
+/// always picks the cheapest enodes (breaking ties at random), while higher
+/// temperatures produce more diverse, more expensive programs. Enodes which
+/// would create a cycle are never picked.
+///
+/// As duplicate draws are discarded, fewer than `num_samples` programs are
+/// returned if the egraph doesn't contain enough programs, or if too many draws
+/// are duplicates: we give up after `10 * num_samples` draws.
+///
+/// Returns the programs and their costs, cheapest first.
+pub fn sample<CF, R>(
+    egraph: &EGraph<Language, MyAnalysis>,
+    root: Id,
+    mut cost_function: CF,
+    num_samples: usize,
+    temperature: f64,
+    rng: &mut R,
+) -> Vec<(CF::Cost, RecExpr<Language>)>
+where
+    CF: CostFunction<Language>,
+    CF::Cost: ToPrimitive,
+    R: Rng,
+{
+    assert!(temperature >= 0.0, "Temperature must be non-negative");
+    let root = egraph.find(root);
+    let best_costs = find_best_costs(egraph, &mut cost_function);
+    assert!(
+        best_costs.contains_key(&root),
+        "No program can be extracted from eclass {}",
+        root
+    );
+
+    // For each eclass, its extractable enodes and their weights.
+    let mut choices: HashMap<Id, Vec<(&Language, f64)>> = HashMap::default();
+    for eclass in egraph.classes() {
+        let best = match best_costs.get(&eclass.id) {
+            Some(best) => best.to_f64().unwrap(),
+            None => continue,
+        };
+        let nodes = eclass
+            .nodes
+            .iter()
+            .filter(|node| {
+                node.children()
+                    .iter()
+                    .all(|id| best_costs.contains_key(&egraph.find(*id)))
+            })
+            .map(|node| {
+                let cost = cost_function
+                    .cost(node, |id| best_costs[&egraph.find(id)].clone())
+                    .to_f64()
+                    .unwrap();
+                let weight = if temperature == 0.0 {
+                    if cost == best {
+                        1.0
+                    } else {
+                        0.0
+                    }
+                } else {
+                    (-(cost - best) / temperature).exp()
+                };
+                (node, weight)
+            })
+            .collect();
+        choices.insert(eclass.id, nodes);
+    }
+
+    /// Adds a program for `eclass` to `expr`, returning its id, or returns
+    /// [`None`] if every choice would create a cycle.
+    fn draw<R: Rng>(
+        egraph: &EGraph<Language, MyAnalysis>,
+        choices: &HashMap<Id, Vec<(&Language, f64)>>,
+        eclass: Id,
+        expr: &mut RecExpr<Language>,
+        added: &mut HashMap<Id, Id>,
+        path: &mut HashSet<Id>,
+        rng: &mut R,
+    ) -> Option<Id> {
+        if let Some(id) = added.get(&eclass) {
+            return Some(*id);
+        }
+
+        let candidates = choices[&eclass]
+            .iter()
+            .filter(|(node, weight)| {
+                *weight > 0.0
+                    && node
+                        .children()
+                        .iter()
+                        .all(|id| !path.contains(&egraph.find(*id)))
+            })
+            .collect::<Vec<_>>();
+        let total: f64 = candidates.iter().map(|(_, weight)| weight).sum();
+        if candidates.is_empty() {
+            return None;
+        }
+        let mut r = rng.gen::<f64>() * total;
+        let mut node = candidates.last().unwrap().0;
+        for (candidate, weight) in candidates.iter() {
+            if r < *weight {
+                node = *candidate;
+                break;
+            }
+            r -= weight;
+        }
+
+        path.insert(eclass);
+        let mut children = Vec::default();
+        for child in node.children() {
+            children.push(draw(
+                egraph,
+                choices,
+                egraph.find(*child),
+                expr,
+                added,
+                path,
+                rng,
+            )?);
+        }
+        path.remove(&eclass);
+
+        let mut children = children.into_iter();
+        let id = expr.add(node.clone().map_children(|_| children.next().unwrap()));
+        added.insert(eclass, id);
+        Some(id)
+    }
+
+    let mut samples: Vec<(CF::Cost, RecExpr<Language>)> = Vec::default();
+    let mut seen: HashSet<String> = HashSet::default();
+    for _ in 0..10 * num_samples {
+        if samples.len() == num_samples {
+            break;
+        }
+        let mut expr = RecExpr::default();
+        if draw(
+            egraph,
+            &choices,
+            root,
+            &mut expr,
+            &mut HashMap::default(),
+            &mut HashSet::default(),
+            rng,
+        )
+        .is_none()
+        {
+            continue;
+        }
+        if seen.insert(expr.to_string()) {
+            samples.push((cost_function.cost_rec(&expr), expr));
+        }
+    }
+
+    samples.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap());
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extraction::MonolithicCostFunction;
+    use crate::language::rewrites;
+    use egg::{AstSize, Runner};
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    /// A matrix multiplication, and the egraph after mapping it to a systolic
+    /// array.
+    fn matmul_egraph() -> (EGraph<Language, MyAnalysis>, Id) {
+        let program = "
+         (compute dot-product
+          (access-cartesian-product
+           (access (access-tensor t-32-32) 1)
+           (access (access-tensor t-32-32) 1)))"
+            .parse()
+            .unwrap();
+        let mut egraph = EGraph::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program);
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&vec![rewrites::systolic_array().rewrite]);
+        (runner.egraph, id)
+    }
+
+    #[test]
+    fn diverse_samples() {
+        let (egraph, id) = matmul_egraph();
+        let mut rng = SmallRng::seed_from_u64(23);
+        let samples = sample(&egraph, id, AstSize, 5, 1000.0, &mut rng);
+
+        // Both the original program and the systolic array are drawn.
+        assert_eq!(samples.len(), 2);
+        assert!(samples[0].0 <= samples[1].0);
+        let programs = samples
+            .iter()
+            .map(|(_, expr)| expr.to_string())
+            .collect::<Vec<_>>();
+        assert!(programs.iter().any(|p| p.starts_with("(systolic-array")));
+        assert!(programs.iter().any(|p| p.starts_with("(compute")));
+        for (cost, expr) in samples.iter() {
+            assert_eq!(*cost, AstSize.cost_rec(expr));
+        }
+    }
+
+    #[test]
+    fn zero_temperature() {
+        let (egraph, id) = matmul_egraph();
+        let mut rng = SmallRng::seed_from_u64(23);
+        let samples = sample(
+            &egraph,
+            id,
+            MonolithicCostFunction {
+                egraph: &egraph,
+                systolic_array_configuration: (32, 32),
+                prefer_systolic_arrays_with_blocking: false,
+            },
+            5,
+            0.0,
+            &mut rng,
+        );
+
+        // Only the cheapest program (which uses the systolic array) is drawn.
+        assert_eq!(samples.len(), 1);
+        assert!(samples[0].0 < MonolithicCostFunction::INFINITY_VALUE);
+        assert!(samples[0]
+            .1
+            .to_string()
+            .starts_with("(systolic-array 32 32"));
+    }
+}
@@ -0,0 +1,370 @@
+//! Gantt charts of execution schedules.
+//!
+//! An [`ExecutionSchedule`] says in which order a mapped program's kernels
+//! run, but not when: whether the systolic array sits idle waiting on DRAM,
+//! or a software fallback serializes everything behind it, only shows up once
+//! each invocation is given a latency. [`timeline`] does so, using a simple
+//! latency model of the [`HardwareSpec`], and lays the invocations and the
+//! buffer transfers out on one lane per atom, as a [`Timeline`] which can be
+//! exported as JSON ([`Timeline::to_json`]) or as a standalone HTML page
+//! ([`Timeline::to_html`]).
+//!
+//! The latency model, in clock cycles:
+//!  - a `rows` by `cols` systolic array performs `rows × cols` operations
+//!    each cycle, after `rows + cols` cycles to fill its pipeline;
+//!  - a vector ALU performs one operation per lane each cycle;
+//!  - every other atom, and the host (which runs the invocations mapped to no
+//!    atom), performs one operation each cycle;
+//!  - loading an input from DRAM, or storing an output to it, takes
+//!    [`HardwareSpec::dram_cycles`] of its size.
+//!
+//! Each lane runs its events in schedule order, one at a time, and an
+//! invocation starts once its inputs are loaded or computed. Intermediates
+//! stay on chip.
+
+use crate::hardware::HardwareSpec;
+use crate::language::op_count::OpCounts;
+use crate::language::schedule::{BufferKind, ExecutionSchedule};
+use crate::language::stats::operator_kind;
+use crate::language::timing::is_hardware_atom;
+use crate::language::Language;
+use egg::{Id, RecExpr};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// What happens during an [`Event`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum EventKind {
+    /// The invocation computing node `node` of the scheduled program.
+    Invocation { node: usize },
+    /// Loading input buffer `buffer` from DRAM.
+    Load { buffer: usize },
+    /// Storing output buffer `buffer` to DRAM.
+    Store { buffer: usize },
+}
+
+/// An interval of time on a lane, in clock cycles.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Event {
+    /// A description of the event, e.g. `systolic-array (node 5)`.
+    pub label: String,
+    pub start: f64,
+    pub end: f64,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+/// The events run by one atom, by the host, or by the DRAM interface.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Lane {
+    /// The atom's name, as in hardware specs, or `host` or `dram`.
+    pub name: String,
+    /// The lane's events, in the order they run.
+    pub events: Vec<Event>,
+}
+
+impl Lane {
+    /// The number of cycles the lane spends running events.
+    pub fn busy(&self) -> f64 {
+        self.events
+            .iter()
+            .map(|event| event.end - event.start)
+            .sum()
+    }
+}
+
+/// A schedule laid out in time; see [`timeline`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Timeline {
+    /// The lanes, in the order their first events run.
+    pub lanes: Vec<Lane>,
+    /// The number of cycles from the first event's start to the last event's
+    /// end.
+    pub makespan: f64,
+}
+
+/// The value of `id`, a number, in `expr`.
+fn num(expr: &RecExpr<Language>, id: Id) -> usize {
+    match expr.as_ref()[usize::from(id)] {
+        Language::Num(n) => n as usize,
+        ref node => panic!("Expected a number, found {:?}", node),
+    }
+}
+
+/// The lane which runs `node`, and the number of cycles it takes to perform
+/// `ops` operations there.
+fn latency(expr: &RecExpr<Language>, node: &Language, ops: u64) -> (String, f64) {
+    let ops = ops as f64;
+    match node {
+        &Language::SystolicArray([rows, cols, ..])
+        | &Language::SystolicArrayWithBlocking([rows, cols, ..])
+        | &Language::SystolicArrayWithActivation([_, rows, cols, ..])
+        | &Language::SparseSystolicArray([rows, cols, ..])
+        | &Language::SystolicArrayConv2dNchwOihwWithBlocking([rows, cols, ..])
+        | &Language::SystolicArrayConv2dNhwcHwioWithBlocking([rows, cols, ..])
+        | &Language::SystolicArrayConv2dIm2colNchwOihwWithBlocking([rows, cols, ..])
+        | &Language::SystolicArrayConv2dIm2colNhwcHwioWithBlocking([rows, cols, ..]) => {
+            let (rows, cols) = (num(expr, rows), num(expr, cols));
+            let name = match node {
+                Language::SparseSystolicArray(_) => "sparse-systolic-array",
+                _ => "systolic-array",
+            };
+            (
+                name.to_string(),
+                (ops / (rows * cols) as f64).ceil() + (rows + cols) as f64,
+            )
+        }
+        &Language::VectorAlu([_, lanes, _]) => {
+            (operator_kind(node), (ops / num(expr, lanes) as f64).ceil())
+        }
+        node if is_hardware_atom(node) => (operator_kind(node), ops),
+        _ => ("host".to_string(), ops),
+    }
+}
+
+/// Lays `schedule`, the schedule of `expr`, out in time on `spec`, given the
+/// operations each of `expr`'s nodes performs (see
+/// [`count_ops`](crate::language::op_count::count_ops)).
+pub fn timeline(
+    expr: &RecExpr<Language>,
+    schedule: &ExecutionSchedule,
+    op_counts: &OpCounts,
+    spec: &HardwareSpec,
+) -> Timeline {
+    let mut lanes: Vec<Lane> = Vec::default();
+    // The time at which each lane is next free, by name.
+    let mut free: HashMap<String, f64> = HashMap::default();
+    // The time at which each buffer is ready to be read.
+    let mut ready: HashMap<usize, f64> = HashMap::default();
+
+    let mut run = |lane: &str, earliest: f64, cycles: f64, label: String, kind: EventKind| {
+        let start = earliest.max(free.get(lane).cloned().unwrap_or(0.0));
+        let end = start + cycles;
+        free.insert(lane.to_string(), end);
+        let event = Event {
+            label,
+            start,
+            end,
+            kind,
+        };
+        match lanes.iter_mut().find(|other| other.name == lane) {
+            Some(lane) => lane.events.push(event),
+            None => lanes.push(Lane {
+                name: lane.to_string(),
+                events: vec![event],
+            }),
+        }
+        end
+    };
+    let bytes = |buffer: usize| {
+        schedule.buffers[buffer].shape.iter().product::<usize>() * spec.element_bytes
+    };
+
+    for invocation in &schedule.invocations {
+        for &input in &invocation.inputs {
+            if let BufferKind::Input { name } = &schedule.buffers[input].kind {
+                if !ready.contains_key(&input) {
+                    let end = run(
+                        "dram",
+                        0.0,
+                        spec.dram_cycles(bytes(input)),
+                        format!("load {}", name),
+                        EventKind::Load { buffer: input },
+                    );
+                    ready.insert(input, end);
+                }
+            }
+        }
+
+        let node = &expr.as_ref()[invocation.node];
+        let counts = op_counts.per_node[invocation.node];
+        let (lane, cycles) = latency(expr, node, counts.macs + counts.elementwise_ops);
+        let inputs_ready = invocation
+            .inputs
+            .iter()
+            .map(|input| ready.get(input).cloned().unwrap_or(0.0))
+            .fold(0.0, f64::max);
+        let end = run(
+            lane.as_str(),
+            inputs_ready,
+            cycles,
+            format!("{} (node {})", invocation.kernel, invocation.node),
+            EventKind::Invocation {
+                node: invocation.node,
+            },
+        );
+        ready.insert(invocation.output, end);
+
+        if schedule.buffers[invocation.output].kind == BufferKind::Output {
+            run(
+                "dram",
+                end,
+                spec.dram_cycles(bytes(invocation.output)),
+                format!("store buffer {}", invocation.output),
+                EventKind::Store {
+                    buffer: invocation.output,
+                },
+            );
+        }
+    }
+
+    let makespan = lanes
+        .iter()
+        .flat_map(|lane| lane.events.iter().map(|event| event.end))
+        .fold(0.0, f64::max);
+    Timeline { lanes, makespan }
+}
+
+impl Timeline {
+    /// The timeline as JSON, with fields named as [`Timeline`]'s are.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap()
+    }
+
+    /// The timeline as a standalone HTML page, drawing each lane as a row of
+    /// bars, one per event, labeled with the lane's utilization. Hovering
+    /// over a bar shows the event's label and times.
+    pub fn to_html(&self) -> String {
+        const LABEL_WIDTH: f64 = 200.0;
+        const CHART_WIDTH: f64 = 1000.0;
+        const ROW_HEIGHT: f64 = 30.0;
+        let scale = if self.makespan > 0.0 {
+            CHART_WIDTH / self.makespan
+        } else {
+            0.0
+        };
+
+        let mut svg = String::default();
+        for (row, lane) in self.lanes.iter().enumerate() {
+            let y = row as f64 * ROW_HEIGHT;
+            svg.push_str(&format!(
+                "<text x=\"0\" y=\"{}\">{} ({:.0}% busy)</text>\n",
+                y + ROW_HEIGHT * 0.65,
+                lane.name,
+                if self.makespan > 0.0 {
+                    lane.busy() / self.makespan * 100.0
+                } else {
+                    0.0
+                }
+            ));
+            for event in &lane.events {
+                svg.push_str(&format!(
+                    "<rect x=\"{:.2}\" y=\"{}\" width=\"{:.2}\" height=\"{}\" fill=\"{}\" \
+                     stroke=\"white\"><title>{}: {} to {}</title></rect>\n",
+                    LABEL_WIDTH + event.start * scale,
+                    y + ROW_HEIGHT * 0.1,
+                    (event.end - event.start) * scale,
+                    ROW_HEIGHT * 0.8,
+                    match event.kind {
+                        EventKind::Invocation { .. } => "#4e79a7",
+                        EventKind::Load { .. } | EventKind::Store { .. } => "#f28e2b",
+                    },
+                    event.label,
+                    event.start,
+                    event.end
+                ));
+            }
+        }
+
+        format!(
+            "<!DOCTYPE html>
+<html>
+<head><meta charset=\"utf-8\"><title>Schedule</title></head>
+<body>
+<p>Makespan: {} cycles</p>
+<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"sans-serif\" font-size=\"12\">
+{}</svg>
+</body>
+</html>
+",
+            self.makespan,
+            LABEL_WIDTH + CHART_WIDTH,
+            self.lanes.len() as f64 * ROW_HEIGHT,
+            svg
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::op_count::count_ops;
+    use crate::language::schedule::schedule;
+    use crate::language::MyAnalysis;
+
+    fn example() -> Timeline {
+        let expr: RecExpr<Language> = "
+         (compute relu
+          (systolic-array 32 64
+           (access (access-tensor t-32-32) 1)
+           (access (access-tensor t-32-64) 0)))"
+            .parse()
+            .unwrap();
+        let spec = HardwareSpec::from_json(
+            r#"
+            {
+              "atoms": [{ "atom": "systolic-array", "rows": 32, "cols": 64 }],
+              "sram_bytes": 1000000,
+              "dram_bandwidth": 1e9,
+              "frequency": 1e9
+            }"#,
+        )
+        .unwrap();
+        timeline(
+            &expr,
+            &schedule(&expr, MyAnalysis::default()),
+            &count_ops(&expr, MyAnalysis::default()),
+            &spec,
+        )
+    }
+
+    #[test]
+    fn lanes() {
+        let timeline = example();
+        let spans = |name: &str| {
+            timeline
+                .lanes
+                .iter()
+                .find(|lane| lane.name == name)
+                .unwrap()
+                .events
+                .iter()
+                .map(|event| (event.start, event.end))
+                .collect::<Vec<_>>()
+        };
+        // Each byte takes a cycle to transfer.
+        assert_eq!(
+            spans("dram"),
+            vec![
+                (0.0, 4096.0),
+                (4096.0, 12288.0),
+                (14464.0, 14464.0 + 8192.0)
+            ]
+        );
+        // 32 × 64 × 32 MACs on 32 × 64 processing elements, plus filling the
+        // array's pipeline.
+        assert_eq!(
+            spans("systolic-array"),
+            vec![(12288.0, 12288.0 + 32.0 + 96.0)]
+        );
+        // The ReLU isn't mapped, so the host applies it to each element.
+        assert_eq!(spans("host"), vec![(12416.0, 12416.0 + 2048.0)]);
+        assert_eq!(timeline.makespan, 22656.0);
+    }
+
+    #[test]
+    fn export() {
+        let timeline = example();
+        let json = timeline.to_json();
+        assert_eq!(json["lanes"][0]["name"], "dram");
+        assert_eq!(json["lanes"][0]["events"][0]["kind"], "load");
+        assert_eq!(json["lanes"][0]["events"][0]["label"], "load t-32-32");
+
+        let html = timeline.to_html();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        // The systolic array is the second-to-last node.
+        assert!(html.contains("<title>systolic-array (node 11): 12288 to 12416</title>"));
+        assert_eq!(html.matches("<rect").count(), 5);
+    }
+}
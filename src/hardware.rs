@@ -0,0 +1,357 @@
+//! Hardware specifications.
+//!
+//! A [`HardwareSpec`] describes the hardware a program is being mapped to:
+//! which atoms it provides and how large they are, how much on-chip SRAM it
+//! has, and how fast it runs and moves data. Specs are read from JSON files,
+//! e.g.
+//! ```json
+//! {
+//!   "atoms": [
+//!     { "atom": "systolic-array", "rows": 16, "cols": 16, "blocking": true },
+//!     { "atom": "pooling-unit" },
+//!     { "atom": "vector-alu", "lanes": 8 }
+//!   ],
+//!   "sram_bytes": 262144,
+//!   "dram_bandwidth": 1.6e10,
+//!   "frequency": 2e8
+//! }
+//! ```
+//! and then parameterize the mapping rewrites ([`HardwareSpec::rewrites`]),
+//! which only map computations the atoms support (see [`crate::legality`]),
+//! the cost model ([`HardwareSpec::cost_function`]), and the constraints on
+//! what can be extracted ([`HardwareSpec::fits_in_sram`]).
+
+use crate::error::{GlensideError, Result};
+use crate::extraction::{LayoutPenalties, MonolithicCostFunction, WithLayoutPenalties};
+use crate::language::{rewrites, AccessPatternData, Language, MyAnalysis};
+use crate::legality::{legality, Legality};
+use egg::{EGraph, Rewrite};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// A hardware atom.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "atom", rename_all = "kebab-case")]
+pub enum Atom {
+    /// A `rows` by `cols` systolic array. If `blocking` is set, larger matrix
+    /// multiplications are blocked to fit the array (see
+    /// [`rewrites::systolic_array_with_blocking`]); otherwise, the array is
+    /// assumed to be generated at whatever size each multiplication needs.
+    SystolicArray {
+        rows: usize,
+        cols: usize,
+        #[serde(default)]
+        blocking: bool,
+    },
+    /// See [`rewrites::pooling_unit`].
+    PoolingUnit,
+    /// See [`rewrites::activation_unit`].
+    ActivationUnit,
+    /// See [`rewrites::vector_alu`].
+    VectorAlu { lanes: usize },
+    /// A systolic array which skips the zero blocks of sparse weights, sized
+    /// like an unblocked systolic array. See
+    /// [`rewrites::sparse_systolic_array`]; dot products are only mapped to
+    /// it once they're marked sparse by [`rewrites::sparse_dot_product`].
+    SparseSystolicArray,
+    /// A systolic array multiplying int8 matrices, accumulating in int32,
+    /// sized like an unblocked systolic array. See
+    /// [`rewrites::int8_systolic_array`]; only the int8 regions of
+    /// mixed-precision programs are mapped to it.
+    Int8SystolicArray,
+}
+
+impl Atom {
+    /// The atom's name, as in specs, e.g. `systolic-array`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Atom::SystolicArray { .. } => "systolic-array",
+            Atom::PoolingUnit => "pooling-unit",
+            Atom::ActivationUnit => "activation-unit",
+            Atom::VectorAlu { .. } => "vector-alu",
+            Atom::SparseSystolicArray => "sparse-systolic-array",
+            Atom::Int8SystolicArray => "int8-systolic-array",
+        }
+    }
+}
+
+/// The names of every kind of atom.
+pub(crate) const ATOM_NAMES: [&str; 6] = [
+    "systolic-array",
+    "pooling-unit",
+    "activation-unit",
+    "vector-alu",
+    "sparse-systolic-array",
+    "int8-systolic-array",
+];
+
+fn default_element_bytes() -> usize {
+    4
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HardwareSpec {
+    pub atoms: Vec<Atom>,
+    /// The size of the on-chip SRAM, in bytes.
+    pub sram_bytes: usize,
+    /// The bandwidth between DRAM and the chip, in bytes per second.
+    pub dram_bandwidth: f64,
+    /// The clock frequency, in Hz.
+    pub frequency: f64,
+    /// The size of each tensor element, in bytes. Defaults to 4.
+    #[serde(default = "default_element_bytes")]
+    pub element_bytes: usize,
+    /// Limits on the computations each kind of atom supports, keyed by the
+    /// atom's name. Atoms without limits support whatever their rewrites
+    /// match.
+    #[serde(default)]
+    pub legality: HashMap<String, Legality>,
+}
+
+impl HardwareSpec {
+    /// Parses a spec from JSON.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let spec: HardwareSpec = serde_json::from_str(json)
+            .map_err(|e| GlensideError::Parse(format!("Invalid hardware spec: {}", e)))?;
+        if spec.dram_bandwidth <= 0.0 {
+            return Err(GlensideError::Parse(
+                "DRAM bandwidth must be positive".to_string(),
+            ));
+        }
+        if spec.frequency <= 0.0 {
+            return Err(GlensideError::Parse(
+                "Frequency must be positive".to_string(),
+            ));
+        }
+        if let Some(name) = spec
+            .legality
+            .keys()
+            .find(|name| !ATOM_NAMES.contains(&name.as_str()))
+        {
+            return Err(GlensideError::Parse(format!(
+                "Legality given for unknown atom {}",
+                name
+            )));
+        }
+        Ok(spec)
+    }
+
+    /// Reads a spec from a JSON file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        Self::from_json(&std::fs::read_to_string(path).map_err(|e| {
+            GlensideError::Parse(format!("Could not read {}: {}", path.display(), e))
+        })?)
+    }
+
+    /// The rewrites mapping computations onto this hardware's atoms, each
+    /// restricted to the computations its atom supports.
+    pub fn rewrites(&self) -> Vec<Rewrite<Language, MyAnalysis>> {
+        let mut rws = Vec::default();
+        let mut unblocked_systolic_array = None;
+        for atom in &self.atoms {
+            let legality = legality(self, atom);
+            match atom {
+                &Atom::SystolicArray {
+                    rows,
+                    cols,
+                    blocking: true,
+                } => {
+                    for rewrite in [
+                        rewrites::systolic_array_with_blocking(rows, cols),
+                        rewrites::systolic_array_conv2d_nchw_oihw_with_blocking(rows, cols),
+                        rewrites::systolic_array_conv2d_nhwc_hwio_with_blocking(rows, cols),
+                        rewrites::systolic_array_conv2d_im2col_nchw_oihw_with_blocking(rows, cols),
+                        rewrites::systolic_array_conv2d_im2col_nhwc_hwio_with_blocking(rows, cols),
+                        rewrites::systolic_array_conv2d_im2col_nchw_oihw_to_nhwc_hwio(),
+                    ] {
+                        rws.push(legality.restrict(rewrite));
+                    }
+                }
+                Atom::SystolicArray {
+                    blocking: false, ..
+                } => unblocked_systolic_array = Some(atom),
+                Atom::PoolingUnit => rws.push(legality.restrict(rewrites::pooling_unit())),
+                Atom::ActivationUnit => rws.push(legality.restrict(rewrites::activation_unit())),
+                &Atom::VectorAlu { lanes } => {
+                    rws.push(legality.restrict(rewrites::vector_alu(lanes)));
+                    rws.push(rewrites::split_elementwise_for_vector_alu(lanes));
+                }
+                Atom::SparseSystolicArray => {
+                    rws.push(legality.restrict(rewrites::sparse_systolic_array()))
+                }
+                Atom::Int8SystolicArray => {
+                    rws.push(legality.restrict(rewrites::int8_systolic_array()))
+                }
+            }
+        }
+        if let Some(atom) = unblocked_systolic_array {
+            let legality = legality(self, atom);
+            rws.push(legality.restrict(rewrites::systolic_array()));
+            rws.push(legality.restrict(rewrites::batch_matmul_to_systolic_arrays()));
+            rws.push(legality.restrict(rewrites::fuse_bias_add_into_systolic_array()));
+            // The fused activation is applied as results leave the array, so
+            // it needs the activation unit as well.
+            if self.atoms.contains(&Atom::ActivationUnit) {
+                rws.push(legality.restrict(rewrites::systolic_array_with_activation()));
+            }
+        }
+        rws
+    }
+
+    /// The hardware's systolic array, as `(rows, cols, blocking)`. Panics
+    /// unless there's exactly one, as the cost model only supports one.
+    pub fn systolic_array(&self) -> (usize, usize, bool) {
+        let mut systolic_arrays = self.atoms.iter().filter_map(|atom| match atom {
+            &Atom::SystolicArray {
+                rows,
+                cols,
+                blocking,
+            } => Some((rows, cols, blocking)),
+            _ => None,
+        });
+        match (systolic_arrays.next(), systolic_arrays.next()) {
+            (Some(systolic_array), None) => systolic_array,
+            _ => panic!("Expected exactly one systolic array in the hardware spec"),
+        }
+    }
+
+    /// The number of clock cycles spent moving `bytes` bytes to or from DRAM.
+    pub fn dram_cycles(&self, bytes: usize) -> f64 {
+        bytes as f64 / self.dram_bandwidth * self.frequency
+    }
+
+    /// Whether `access`'s tensor fits in SRAM.
+    pub fn fits_in_sram(&self, access: &AccessPatternData) -> bool {
+        access.as_vec().iter().product::<usize>() * self.element_bytes <= self.sram_bytes
+    }
+
+    /// A cost function for extracting designs for this hardware from `egraph`:
+    /// a [`MonolithicCostFunction`] for the hardware's systolic array, in
+    /// which data reorganization (transposes, padding, and concatenation) is
+    /// penalized by the number of cycles it takes to move the data through
+    /// DRAM. Chains of views are charged once, to the node reading through
+    /// them (see [`LayoutPenalties::compose_views`]).
+    pub fn cost_function<'a>(
+        &self,
+        egraph: &'a EGraph<Language, MyAnalysis>,
+    ) -> WithLayoutPenalties<'a, MonolithicCostFunction<'a>> {
+        let (rows, cols, blocking) = self.systolic_array();
+        let cycles_per_byte = self.dram_cycles(1);
+        WithLayoutPenalties {
+            inner: MonolithicCostFunction {
+                systolic_array_configuration: (rows, cols),
+                egraph,
+                prefer_systolic_arrays_with_blocking: blocking,
+            },
+            penalties: LayoutPenalties {
+                weights: ["access-transpose", "access-pad", "access-concatenate"]
+                    .iter()
+                    .map(|op| (op.to_string(), cycles_per_byte))
+                    .collect(),
+                element_bytes: self.element_bytes,
+                compose_views: true,
+            },
+            egraph,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egg::{Extractor, Runner};
+
+    const SPEC: &str = r#"
+        {
+          "atoms": [
+            { "atom": "systolic-array", "rows": 32, "cols": 32 },
+            { "atom": "activation-unit" },
+            { "atom": "vector-alu", "lanes": 8 }
+          ],
+          "sram_bytes": 4000,
+          "dram_bandwidth": 1e9,
+          "frequency": 5e8
+        }"#;
+
+    #[test]
+    fn parse() {
+        let spec = HardwareSpec::from_json(SPEC).unwrap();
+        assert_eq!(
+            spec.atoms,
+            vec![
+                Atom::SystolicArray {
+                    rows: 32,
+                    cols: 32,
+                    blocking: false
+                },
+                Atom::ActivationUnit,
+                Atom::VectorAlu { lanes: 8 }
+            ]
+        );
+        assert_eq!(spec.element_bytes, 4);
+        assert_eq!(spec.systolic_array(), (32, 32, false));
+        assert_eq!(spec.dram_cycles(10), 5.0);
+        assert_eq!(
+            spec.rewrites()
+                .iter()
+                .map(|rw| rw.name.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "activation-unit",
+                "vector-alu-8",
+                "split-elementwise-for-vector-alu-8",
+                "systolic-array",
+                "batch-matmul-to-systolic-arrays",
+                "fuse-bias-add-into-systolic-array",
+                "systolic-array-with-activation"
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_unknown_atom() {
+        match HardwareSpec::from_json(
+            r#"{ "atoms": [ { "atom": "tpu" } ], "sram_bytes": 1,
+                 "dram_bandwidth": 1, "frequency": 1 }"#,
+        ) {
+            Err(GlensideError::Parse(message)) => {
+                assert!(message.starts_with("Invalid hardware spec"))
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn map_and_extract() {
+        let spec = HardwareSpec::from_json(SPEC).unwrap();
+        let expr = "
+         (compute relu
+          (compute dot-product
+           (access-cartesian-product
+            (access (access-tensor t-32-32) 1)
+            (access (access-tensor t-32-32) 1))))"
+            .parse()
+            .unwrap();
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_expr(&expr)
+            .run(&spec.rewrites());
+        let (cost, extracted) = Extractor::new(&runner.egraph, spec.cost_function(&runner.egraph))
+            .find_best(runner.roots[0]);
+
+        assert!(cost < MonolithicCostFunction::INFINITY_VALUE);
+        // The activation is fused into the systolic array.
+        assert!(extracted
+            .to_string()
+            .contains("(systolic-array-with-activation relu 32 32"));
+
+        // A 32x32 matrix of 4-byte elements doesn't fit in 4000 bytes.
+        match &runner.egraph[runner.roots[0]].data {
+            crate::language::MyAnalysisData::AccessPattern(a) => {
+                assert!(!spec.fits_in_sram(a))
+            }
+            _ => panic!(),
+        }
+    }
+}
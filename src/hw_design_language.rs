@@ -2,7 +2,7 @@ use serde::Serialize;
 use serde_json::map::Map;
 use serde_json::{json, Value};
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum DType {
     Int8 = 0,
@@ -15,6 +15,8 @@ pub enum DType {
     Fp16 = 7,
     Fp32 = 8,
     Fp64 = 9,
+    Int4 = 10,
+    Uint4 = 11,
 }
 
 impl DType {
@@ -26,23 +28,279 @@ impl DType {
     pub fn to_c_type_string(&self) -> String {
         match &self {
             &DType::Fp32 => "float",
+            &DType::Fp64 => "double",
+            &DType::Int8 => "signed char",
+            &DType::Int16 => "short",
             &DType::Int32 => "int",
-            _ => panic!(),
+            &DType::Uint8 => "unsigned char",
+            &DType::Uint16 => "unsigned short",
+            &DType::Uint32 => "unsigned int",
+            _ => panic!("No C type for {:?}", self),
         }
         .to_string()
     }
+
+    /// The type in which dot products and sums of values of this type are
+    /// accumulated by default: narrow integers accumulate in 32-bit integers,
+    /// and narrow floats in 32-bit floats, so that sums don't overflow or
+    /// lose precision as quickly.
+    /// ```
+    /// use glenside::hw_design_language::*;
+    /// assert_eq!(DType::Int8.default_accumulator(), DType::Int32);
+    /// assert_eq!(DType::Fp16.default_accumulator(), DType::Fp32);
+    /// assert_eq!(DType::Fp64.default_accumulator(), DType::Fp64);
+    /// ```
+    pub fn default_accumulator(&self) -> DType {
+        match self {
+            DType::Int4 | DType::Int8 | DType::Int16 => DType::Int32,
+            DType::Uint4 | DType::Uint8 | DType::Uint16 => DType::Uint32,
+            DType::Bf16 | DType::Fp16 => DType::Fp32,
+            _ => *self,
+        }
+    }
+
+    /// The number of bits in a value of this type.
+    /// ```
+    /// use glenside::hw_design_language::*;
+    /// assert_eq!(DType::Int4.bits(), 4);
+    /// assert_eq!(DType::Bf16.bits(), 16);
+    /// ```
+    pub fn bits(&self) -> usize {
+        match self {
+            DType::Int4 | DType::Uint4 => 4,
+            DType::Int8 | DType::Uint8 => 8,
+            DType::Int16 | DType::Uint16 | DType::Bf16 | DType::Fp16 => 16,
+            DType::Int32 | DType::Uint32 | DType::Fp32 => 32,
+            DType::Fp64 => 64,
+        }
+    }
+
+    /// The area of a multiply-accumulate unit multiplying values of this
+    /// type, relative to that of one multiplying int8s. A multiplier's area
+    /// grows with the square of the width of the values it multiplies (for
+    /// floats, of their significands), and dominates the area of the unit.
+    /// ```
+    /// use glenside::hw_design_language::*;
+    /// assert_eq!(DType::Int4.mac_area(), 0.25);
+    /// assert_eq!(DType::Int16.mac_area(), 4.0);
+    /// assert_eq!(DType::Bf16.mac_area(), 1.0);
+    /// ```
+    pub fn mac_area(&self) -> f64 {
+        let significand_bits = match self {
+            DType::Bf16 => 8,
+            DType::Fp16 => 11,
+            DType::Fp32 => 24,
+            DType::Fp64 => 53,
+            _ => self.bits(),
+        };
+        (significand_bits as f64 / 8.0).powi(2)
+    }
+}
+
+impl From<&crate::language::DataType> for DType {
+    /// ```
+    /// use glenside::hw_design_language::DType;
+    /// use glenside::language::DataType;
+    /// assert_eq!(DType::from(&DataType::Int(32)), DType::Int32);
+    /// assert_eq!(DType::from(&DataType::Float(32)), DType::Fp32);
+    /// ```
+    fn from(dtype: &crate::language::DataType) -> Self {
+        use crate::language::DataType;
+        match dtype {
+            DataType::Int(4) => DType::Int4,
+            DataType::Int(8) => DType::Int8,
+            DataType::Int(16) => DType::Int16,
+            DataType::Int(32) => DType::Int32,
+            DataType::Uint(4) => DType::Uint4,
+            DataType::Uint(8) => DType::Uint8,
+            DataType::Uint(16) => DType::Uint16,
+            DataType::Uint(32) => DType::Uint32,
+            DataType::Float(16) => DType::Fp16,
+            DataType::Float(32) => DType::Fp32,
+            DataType::Float(64) => DType::Fp64,
+            _ => panic!("No hardware dtype for {}", dtype),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct SystolicArrayWeightStationaryParams {
     pub dtype: DType,
+    /// The type of the partial sums accumulated along each column.
+    pub accumulator_dtype: DType,
     pub rows: usize,
     pub cols: usize,
 }
 
+impl SystolicArrayWeightStationaryParams {
+    /// The area of the array, relative to that of a single int8
+    /// multiply-accumulate unit (see [`DType::mac_area`]), so that arrays of
+    /// different precisions can be compared.
+    /// ```
+    /// use glenside::hw_design_language::*;
+    /// let params = SystolicArrayWeightStationaryParams {
+    ///     dtype: DType::Int4,
+    ///     accumulator_dtype: DType::Int32,
+    ///     rows: 16,
+    ///     cols: 16,
+    /// };
+    /// assert_eq!(params.area(), 64.0);
+    /// ```
+    pub fn area(&self) -> f64 {
+        (self.rows * self.cols) as f64 * self.dtype.mac_area()
+    }
+}
+
+/// A unit which reduces windows of its input, for max and average pooling.
+/// See the `pooling-unit` construct.
+#[derive(Debug)]
+pub struct PoolingUnitParams {
+    pub dtype: DType,
+    /// The number of window elements reduced each cycle.
+    pub lanes: usize,
+    /// The number of cycles between a window entering the unit and its result
+    /// leaving it.
+    pub latency: usize,
+    /// The area of the unit, relative to that of a single multiply-accumulate
+    /// unit.
+    pub area: f64,
+}
+
+impl PoolingUnitParams {
+    /// The number of cycles taken to reduce `num_windows` windows of
+    /// `window_len` elements each. Windows are pipelined, so the latency is
+    /// only paid once.
+    /// ```
+    /// use glenside::hw_design_language::*;
+    /// let params = PoolingUnitParams {
+    ///     dtype: DType::Fp32,
+    ///     lanes: 4,
+    ///     latency: 3,
+    ///     area: 4.0,
+    /// };
+    /// // 2x2 max pooling of a 4x32x32 tensor.
+    /// assert_eq!(params.cycles(4 * 16 * 16, 4), 4 * 16 * 16 + 3);
+    /// // 3x3 windows take three passes through four lanes.
+    /// assert_eq!(params.cycles(10, 9), 10 * 3 + 3);
+    /// ```
+    pub fn cycles(&self, num_windows: usize, window_len: usize) -> usize {
+        num_windows * ((window_len + self.lanes - 1) / self.lanes) + self.latency
+    }
+}
+
+/// A unit which applies an activation function elementwise, or a softmax to
+/// vectors. ReLU is computed directly; sigmoid, tanh, and softmax's
+/// exponentials are looked up in tables. See the
+/// `activation-unit` and `systolic-array-with-activation` constructs.
+#[derive(Debug)]
+pub struct ActivationUnitParams {
+    pub dtype: DType,
+    /// The number of elements processed each cycle.
+    pub lanes: usize,
+    /// The number of entries in each of the sigmoid and tanh lookup tables.
+    pub lookup_table_entries: usize,
+    /// The number of cycles between an element entering the unit and its
+    /// result leaving it.
+    pub latency: usize,
+    /// The area of the unit, relative to that of a single multiply-accumulate
+    /// unit.
+    pub area: f64,
+}
+
+impl ActivationUnitParams {
+    /// The number of cycles taken to apply an activation function to
+    /// `num_elements` elements.
+    /// ```
+    /// use glenside::hw_design_language::*;
+    /// let params = ActivationUnitParams {
+    ///     dtype: DType::Int8,
+    ///     lanes: 16,
+    ///     lookup_table_entries: 256,
+    ///     latency: 2,
+    ///     area: 8.0,
+    /// };
+    /// assert_eq!(params.cycles(1024), 64 + 2);
+    /// assert_eq!(params.cycles(1025), 65 + 2);
+    /// ```
+    pub fn cycles(&self, num_elements: usize) -> usize {
+        (num_elements + self.lanes - 1) / self.lanes + self.latency
+    }
+}
+
+/// A unit which adds or multiplies two vectors elementwise, e.g. for the
+/// residual additions in ResNets. See the `vector-alu` construct.
+#[derive(Debug)]
+pub struct VectorAluParams {
+    pub dtype: DType,
+    /// The number of elements in each vector operand.
+    pub lanes: usize,
+    /// The number of cycles between an invocation's operands entering the
+    /// unit and its result leaving it.
+    pub latency: usize,
+    /// The area of the unit, relative to that of a single multiply-accumulate
+    /// unit.
+    pub area: f64,
+}
+
+impl VectorAluParams {
+    /// The number of cycles taken by `invocations` pipelined invocations.
+    /// ```
+    /// use glenside::hw_design_language::*;
+    /// let params = VectorAluParams {
+    ///     dtype: DType::Int32,
+    ///     lanes: 16,
+    ///     latency: 1,
+    ///     area: 16.0,
+    /// };
+    /// assert_eq!(params.cycles(64), 65);
+    /// ```
+    pub fn cycles(&self, invocations: usize) -> usize {
+        invocations + self.latency
+    }
+}
+
+/// A weight-stationary systolic array which skips the zero blocks of a
+/// sparse weight matrix, loading only the stored blocks. See the
+/// `sparse-systolic-array` construct and [`crate::language::sparse`].
+#[derive(Debug)]
+pub struct SparseSystolicArrayParams {
+    pub dtype: DType,
+    /// The type of the partial sums accumulated along each column.
+    pub accumulator_dtype: DType,
+    pub rows: usize,
+    pub cols: usize,
+    /// The shape of the weight blocks the array skips or loads.
+    pub block_shape: [usize; 2],
+}
+
+impl SparseSystolicArrayParams {
+    /// The number of cycles taken to multiply `batch` input vectors by a
+    /// weight matrix with `stored_blocks` nonzero blocks. Each stored block is
+    /// loaded into the array and then streamed through by the whole batch;
+    /// the array's pipeline is filled once.
+    /// ```
+    /// use glenside::hw_design_language::*;
+    /// let params = SparseSystolicArrayParams {
+    ///     dtype: DType::Int8,
+    ///     accumulator_dtype: DType::Int32,
+    ///     rows: 16,
+    ///     cols: 16,
+    ///     block_shape: [16, 16],
+    /// };
+    /// assert_eq!(params.cycles(64, 3), 3 * (16 + 64) + 16 + 16);
+    /// ```
+    pub fn cycles(&self, batch: usize, stored_blocks: usize) -> usize {
+        stored_blocks * (self.block_shape[0] + batch) + self.rows + self.cols
+    }
+}
+
 #[derive(Debug)]
 pub enum AtomConfig {
     SystolicArrayWeightStationary(SystolicArrayWeightStationaryParams),
+    PoolingUnit(PoolingUnitParams),
+    ActivationUnit(ActivationUnitParams),
+    VectorAlu(VectorAluParams),
+    SparseSystolicArray(SparseSystolicArrayParams),
 }
 
 #[derive(Debug)]
@@ -56,6 +314,16 @@ pub struct HardwareDesign {
     pub atoms: Vec<Atom>,
 }
 
+/// A computation which no atom computes, and which the generated code thus
+/// computes in software. See [`crate::codegen::find_fallbacks`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Fallback {
+    /// The eclass of the computation.
+    pub eclass: usize,
+    /// The computation, e.g. `compute softmax`.
+    pub op: String,
+}
+
 pub fn design_to_json(design: &HardwareDesign) -> Value {
     Value::Array(
         design
@@ -66,6 +334,31 @@ pub fn design_to_json(design: &HardwareDesign) -> Value {
     )
 }
 
+/// A report on a design: its atoms, as in [`design_to_json`], and the
+/// computations which fell back to software.
+/// ```
+/// use glenside::hw_design_language::*;
+/// use serde_json::json;
+///
+/// let report = design_report(
+///     &HardwareDesign { atoms: vec![] },
+///     &[Fallback { eclass: 4, op: "compute softmax".to_string() }],
+/// );
+/// assert_eq!(
+///     report,
+///     json!({
+///         "atoms": [],
+///         "fallbacks": [{ "eclass": 4, "op": "compute softmax" }]
+///     })
+/// );
+/// ```
+pub fn design_report(design: &HardwareDesign, fallbacks: &[Fallback]) -> Value {
+    json!({
+        "atoms": design_to_json(design),
+        "fallbacks": fallbacks,
+    })
+}
+
 pub fn atom_to_json(atom: &Atom) -> Value {
     let mut map = Map::default();
     map.append(&mut match &atom.config {
@@ -76,10 +369,58 @@ pub fn atom_to_json(atom: &Atom) -> Value {
                 json!("bsg_systolic_array_weight_stationary"),
             );
             map.insert("dtype".to_string(), json!(params.dtype));
+            map.insert(
+                "accumulator_dtype".to_string(),
+                json!(params.accumulator_dtype),
+            );
             map.insert("rows".to_string(), json!(params.rows));
             map.insert("cols".to_string(), json!(params.cols));
             map
         }
+        AtomConfig::PoolingUnit(params) => {
+            let mut map = Map::default();
+            map.insert("atom".to_string(), json!("pooling_unit"));
+            map.insert("dtype".to_string(), json!(params.dtype));
+            map.insert("lanes".to_string(), json!(params.lanes));
+            map.insert("latency".to_string(), json!(params.latency));
+            map.insert("area".to_string(), json!(params.area));
+            map
+        }
+        AtomConfig::ActivationUnit(params) => {
+            let mut map = Map::default();
+            map.insert("atom".to_string(), json!("activation_unit"));
+            map.insert("dtype".to_string(), json!(params.dtype));
+            map.insert("lanes".to_string(), json!(params.lanes));
+            map.insert(
+                "lookup_table_entries".to_string(),
+                json!(params.lookup_table_entries),
+            );
+            map.insert("latency".to_string(), json!(params.latency));
+            map.insert("area".to_string(), json!(params.area));
+            map
+        }
+        AtomConfig::VectorAlu(params) => {
+            let mut map = Map::default();
+            map.insert("atom".to_string(), json!("vector_alu"));
+            map.insert("dtype".to_string(), json!(params.dtype));
+            map.insert("lanes".to_string(), json!(params.lanes));
+            map.insert("latency".to_string(), json!(params.latency));
+            map.insert("area".to_string(), json!(params.area));
+            map
+        }
+        AtomConfig::SparseSystolicArray(params) => {
+            let mut map = Map::default();
+            map.insert("atom".to_string(), json!("sparse_systolic_array"));
+            map.insert("dtype".to_string(), json!(params.dtype));
+            map.insert(
+                "accumulator_dtype".to_string(),
+                json!(params.accumulator_dtype),
+            );
+            map.insert("rows".to_string(), json!(params.rows));
+            map.insert("cols".to_string(), json!(params.cols));
+            map.insert("block_shape".to_string(), json!(params.block_shape));
+            map
+        }
     });
     map.insert("name".to_string(), json!(atom.name));
     map.insert("id".to_string(), json!(atom.id));
@@ -101,6 +442,7 @@ mod tests {
                     config: AtomConfig::SystolicArrayWeightStationary(
                         SystolicArrayWeightStationaryParams {
                             dtype: DType::Int8,
+                            accumulator_dtype: DType::Int32,
                             rows: 16,
                             cols: 16,
                         },
@@ -112,6 +454,7 @@ mod tests {
                     config: AtomConfig::SystolicArrayWeightStationary(
                         SystolicArrayWeightStationaryParams {
                             dtype: DType::Int8,
+                            accumulator_dtype: DType::Int32,
                             rows: 16,
                             cols: 16,
                         },
@@ -123,6 +466,7 @@ mod tests {
                     config: AtomConfig::SystolicArrayWeightStationary(
                         SystolicArrayWeightStationaryParams {
                             dtype: DType::Int8,
+                            accumulator_dtype: DType::Int32,
                             rows: 16,
                             cols: 16,
                         },
@@ -139,6 +483,7 @@ mod tests {
                        "atom" : "bsg_systolic_array_weight_stationary",
                        "id" : 1,
                        "dtype" : "int8",
+                       "accumulator_dtype" : "int32",
                        "cols" : 16,
                        "rows" : 16,
                     },
@@ -146,6 +491,7 @@ mod tests {
                        "atom" : "bsg_systolic_array_weight_stationary",
                        "id" : 2,
                        "dtype" : "int8",
+                       "accumulator_dtype" : "int32",
                        "cols" : 16,
                        "rows" : 16,
                     },
@@ -153,6 +499,7 @@ mod tests {
                        "atom" : "bsg_systolic_array_weight_stationary",
                        "id" : 3,
                        "dtype" : "int8",
+                       "accumulator_dtype" : "int32",
                        "cols" : 16,
                        "rows" : 16,
                     },
@@ -160,4 +507,83 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn serialize_pooling_unit() {
+        assert_eq!(
+            atom_to_json(&Atom {
+                name: "pool1".to_string(),
+                id: 4,
+                config: AtomConfig::PoolingUnit(PoolingUnitParams {
+                    dtype: DType::Int8,
+                    lanes: 4,
+                    latency: 2,
+                    area: 1.5,
+                }),
+            }),
+            json!({
+                "name" : "pool1",
+                "atom" : "pooling_unit",
+                "id" : 4,
+                "dtype" : "int8",
+                "lanes" : 4,
+                "latency" : 2,
+                "area" : 1.5,
+            })
+        );
+    }
+
+    #[test]
+    fn serialize_activation_unit() {
+        assert_eq!(
+            atom_to_json(&Atom {
+                name: "act0".to_string(),
+                id: 5,
+                config: AtomConfig::ActivationUnit(ActivationUnitParams {
+                    dtype: DType::Fp16,
+                    lanes: 16,
+                    lookup_table_entries: 256,
+                    latency: 2,
+                    area: 8.0,
+                }),
+            }),
+            json!({
+                "name" : "act0",
+                "atom" : "activation_unit",
+                "id" : 5,
+                "dtype" : "fp16",
+                "lanes" : 16,
+                "lookup_table_entries" : 256,
+                "latency" : 2,
+                "area" : 8.0,
+            })
+        );
+    }
+
+    #[test]
+    fn serialize_sparse_systolic_array() {
+        assert_eq!(
+            atom_to_json(&Atom {
+                name: "sparse0".to_string(),
+                id: 6,
+                config: AtomConfig::SparseSystolicArray(SparseSystolicArrayParams {
+                    dtype: DType::Int8,
+                    accumulator_dtype: DType::Int32,
+                    rows: 16,
+                    cols: 16,
+                    block_shape: [4, 16],
+                }),
+            }),
+            json!({
+                "name" : "sparse0",
+                "atom" : "sparse_systolic_array",
+                "id" : 6,
+                "dtype" : "int8",
+                "accumulator_dtype" : "int32",
+                "rows" : 16,
+                "cols" : 16,
+                "block_shape" : [4, 16],
+            })
+        );
+    }
 }
@@ -0,0 +1,220 @@
+//! Shapes of access patterns.
+//!
+//! Both the analysis ([`MyAnalysis`](super::MyAnalysis)) and the interpreter
+//! need the shape of the access pattern each operator produces: the analysis
+//! computes it from the shapes of the operator's arguments, and the
+//! interpreter to lay out the operator's result. Computed separately, the two
+//! drift apart (e.g. the interpreter once left flattened accesses accessed at
+//! their old access axis). [`AccessShape`]'s methods compute these shapes for
+//! both, for the operators whose resulting shapes aren't just their
+//! arguments', such as windowing and flattening.
+
+use super::{access_windows_resulting_shape, AccessPatternData};
+use ndarray::{Dimension, IxDyn};
+
+/// The shape of an access pattern: the shape of the access (the dimensions
+/// before the access axis) and the shape of each item (the dimensions after
+/// it).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccessShape {
+    pub shape: IxDyn,
+    pub item_shape: IxDyn,
+}
+
+impl From<&AccessPatternData> for AccessShape {
+    fn from(access: &AccessPatternData) -> Self {
+        AccessShape {
+            shape: access.shape.clone(),
+            item_shape: access.item_shape.clone(),
+        }
+    }
+}
+
+impl AccessShape {
+    pub fn new(shape: &[usize], item_shape: &[usize]) -> Self {
+        AccessShape {
+            shape: IxDyn(shape),
+            item_shape: IxDyn(item_shape),
+        }
+    }
+
+    /// The shape of a tensor of shape `tensor_shape`, accessed at
+    /// `access_axis`.
+    pub fn of_tensor(tensor_shape: &[usize], access_axis: usize) -> Self {
+        assert!(
+            access_axis <= tensor_shape.len(),
+            "Access axis {} is out of bounds for a tensor with {} axes",
+            access_axis,
+            tensor_shape.len()
+        );
+        AccessShape::new(&tensor_shape[..access_axis], &tensor_shape[access_axis..])
+    }
+
+    /// The index of the first item axis.
+    pub fn access_axis(&self) -> usize {
+        self.shape.ndim()
+    }
+
+    /// The shape of the underlying tensor.
+    pub fn as_vec(&self) -> Vec<usize> {
+        self.shape
+            .slice()
+            .iter()
+            .chain(self.item_shape.slice())
+            .cloned()
+            .collect()
+    }
+
+    /// The shape of `access-transpose`, permuting the tensor's axes so that
+    /// axis `i` is the old axis `list[i]`. The access axis doesn't move.
+    pub fn transpose(&self, list: &[usize]) -> Self {
+        let tensor_shape = self.as_vec();
+        assert_eq!(
+            tensor_shape.len(),
+            list.len(),
+            "Number of items in list should equal the number of axes in the first argument"
+        );
+        let permuted = list
+            .iter()
+            .map(|axis| tensor_shape[*axis])
+            .collect::<Vec<_>>();
+        AccessShape::of_tensor(&permuted, self.access_axis())
+    }
+
+    /// The shape of `access-flatten`, which flattens the access's shape and
+    /// its item shape each into at most one axis. Empty shapes stay empty,
+    /// rather than becoming a single axis of length 1.
+    pub fn flatten(&self) -> Self {
+        let flatten = |shape: &IxDyn| -> Vec<usize> {
+            if shape.ndim() == 0 {
+                vec![]
+            } else {
+                vec![shape.slice().iter().product()]
+            }
+        };
+        AccessShape::new(&flatten(&self.shape), &flatten(&self.item_shape))
+    }
+
+    /// The shape of `access-cartesian-product` of accesses of shapes `self`
+    /// and `other`, whose item shapes must match: the concatenation of both
+    /// shapes, with items pairing an item of each.
+    pub fn cartesian_product(&self, other: &AccessShape) -> Self {
+        assert_eq!(
+            self.item_shape, other.item_shape,
+            "Cartesian product argument shapes must match"
+        );
+        AccessShape::new(
+            &self
+                .shape
+                .slice()
+                .iter()
+                .chain(other.shape.slice())
+                .cloned()
+                .collect::<Vec<_>>(),
+            &std::iter::once(2)
+                .chain(self.item_shape.slice().iter().cloned())
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// The shape of `access-windows`, which places windows of shape
+    /// `filters_shape`, spaced `stride_shape` apart, over each item (see
+    /// [`access_windows_resulting_shape`]).
+    pub fn windows(&self, filters_shape: &IxDyn, stride_shape: &IxDyn) -> Self {
+        AccessShape::new(
+            &self
+                .shape
+                .slice()
+                .iter()
+                .cloned()
+                .chain(access_windows_resulting_shape(
+                    &self.item_shape,
+                    filters_shape,
+                    stride_shape,
+                ))
+                .collect::<Vec<_>>(),
+            filters_shape.slice(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::interpreter::{interpret, Value};
+    use super::super::{Language, MyAnalysis, MyAnalysisData};
+    use super::*;
+    use egg::{EGraph, RecExpr};
+    use std::collections::HashMap;
+
+    /// Checks that the shape the analysis gives each access pattern in `expr`
+    /// is the shape of the access the interpreter produces for it.
+    fn check_consistency(expr: &str) {
+        let expr: RecExpr<Language> = expr.parse().unwrap();
+        let mut env = HashMap::default();
+        env.insert(
+            "t",
+            ndarray::ArrayD::from_shape_fn(IxDyn(&[2, 3, 6, 8]), |index| {
+                index.slice().iter().sum::<usize>() as i64
+            }),
+        );
+        let mut name_to_shape = HashMap::default();
+        name_to_shape.insert("t".to_string(), vec![2, 3, 6, 8]);
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&expr);
+        let analyzed = match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => AccessShape::from(a),
+            other => panic!("Expected an access pattern, found {:?}", other),
+        };
+        let interpreted = match interpret(&expr, expr.as_ref().len() - 1, &env) {
+            Value::Access(a) => AccessShape::of_tensor(a.tensor.shape(), a.access_axis),
+            _ => panic!(),
+        };
+        assert_eq!(analyzed, interpreted, "{}", expr.pretty(80));
+    }
+
+    #[test]
+    fn shapes() {
+        let access = AccessShape::of_tensor(&[2, 3, 6, 8], 1);
+        assert_eq!(access, AccessShape::new(&[2], &[3, 6, 8]));
+        assert_eq!(access.as_vec(), vec![2, 3, 6, 8]);
+        assert_eq!(
+            access.transpose(&[3, 0, 1, 2]),
+            AccessShape::new(&[8], &[2, 3, 6])
+        );
+        assert_eq!(access.flatten(), AccessShape::new(&[2], &[144]));
+        assert_eq!(
+            AccessShape::of_tensor(&[2, 3], 0).flatten(),
+            AccessShape::new(&[], &[6])
+        );
+        assert_eq!(
+            AccessShape::new(&[4], &[8]).cartesian_product(&AccessShape::new(&[5, 6], &[8])),
+            AccessShape::new(&[4, 5, 6], &[2, 8])
+        );
+        assert_eq!(
+            access.windows(&IxDyn(&[3, 3, 3]), &IxDyn(&[1, 2, 2])),
+            AccessShape::new(&[2, 1, 2, 3], &[3, 3, 3])
+        );
+    }
+
+    #[test]
+    fn consistent_with_interpreter() {
+        for expr in &[
+            "(access (access-tensor t) 2)",
+            "(access-transpose (access (access-tensor t) 1) (list 3 1 0 2))",
+            "(access-flatten (access (access-tensor t) 0))",
+            "(access-flatten (access (access-tensor t) 2))",
+            "(access-flatten (access (access-tensor t) 4))",
+            "(access-cartesian-product
+              (access (access-tensor t) 2)
+              (access (access-transpose (access-tensor t) (list 1 0 2 3)) 2))",
+            "(access-windows (access (access-tensor t) 2) (shape 3 3) (shape 1 2))",
+            "(access-windows (access (access-tensor t) 0) (shape 2 3 3 4) (shape 1 1 3 2))",
+        ] {
+            check_consistency(expr);
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! Bit-accurate simulation of systolic arrays.
+//!
+//! By default, the interpreter simulates a systolic array by multiplying
+//! matrices in whatever arithmetic `DataType` has, in whatever order is
+//! fastest (e.g. with BLAS, under the `fast-matmul` feature). That's right
+//! up to rounding, but hardware bring-up needs results which match the RTL
+//! exactly. Interpreting in a [`BitAccurateEnvironment`] simulates
+//! `systolic-array`, `systolic-array-with-blocking`, and
+//! `systolic-array-with-activation` atoms as the hardware computes them
+//! instead: each output is accumulated from integer products in a fixed order,
+//! one multiply-accumulate at a time, in an accumulator of a configurable
+//! width, which saturates or wraps around on overflow.
+
+use super::interpreter::Lookup;
+use super::sparse::SparseMatrix;
+use ndarray::{Array2, ArrayD, ArrayView2, ArrayViewD};
+
+/// What an accumulator does when a sum doesn't fit in it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    /// Clamps the sum to the largest or smallest representable value.
+    Saturate,
+    /// Keeps the low bits of the sum, as two's complement addition does.
+    Wrap,
+}
+
+/// The arithmetic of a systolic array's processing elements.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SystolicArrayArithmetic {
+    /// The width of each accumulator, in bits, between 1 and 64. Accumulators
+    /// hold signed values.
+    pub accumulator_bits: u32,
+    pub overflow: Overflow,
+}
+
+impl SystolicArrayArithmetic {
+    /// The smallest and largest values an accumulator holds.
+    fn range(&self) -> (i128, i128) {
+        assert!(
+            self.accumulator_bits >= 1 && self.accumulator_bits <= 64,
+            "Accumulators must be between 1 and 64 bits wide"
+        );
+        let max = (1i128 << (self.accumulator_bits - 1)) - 1;
+        (-max - 1, max)
+    }
+
+    /// Adds `product` to the accumulator holding `acc`.
+    /// ```
+    /// use glenside::language::bit_accurate::{Overflow, SystolicArrayArithmetic};
+    ///
+    /// let saturating = SystolicArrayArithmetic { accumulator_bits: 8, overflow: Overflow::Saturate };
+    /// assert_eq!(saturating.accumulate(100, 50), 127);
+    /// assert_eq!(saturating.accumulate(-100, -50), -128);
+    /// let wrapping = SystolicArrayArithmetic { accumulator_bits: 8, overflow: Overflow::Wrap };
+    /// assert_eq!(wrapping.accumulate(100, 50), -106);
+    /// assert_eq!(wrapping.accumulate(-100, -50), 106);
+    /// ```
+    pub fn accumulate(&self, acc: i64, product: i64) -> i64 {
+        let (min, max) = self.range();
+        let sum = acc as i128 + product as i128;
+        let sum = match self.overflow {
+            Overflow::Saturate => sum.max(min).min(max),
+            Overflow::Wrap => {
+                let modulus = 1i128 << self.accumulator_bits;
+                (sum - min).rem_euclid(modulus) + min
+            }
+        };
+        sum as i64
+    }
+
+    /// Multiplies the `m` by `k` matrix `a` by the `k` by `n` matrix `b`, as
+    /// a weight-stationary systolic array does: each output starts at zero,
+    /// and the products of its dot product are accumulated in order of
+    /// increasing `k`, as partial sums flow down the array's rows. A blocked
+    /// array carries its partial sums from one block of rows to the next, so
+    /// it accumulates in the same order.
+    pub fn matmul(&self, a: ArrayView2<i64>, b: ArrayView2<i64>) -> Array2<i64> {
+        assert_eq!(a.shape()[1], b.shape()[0]);
+        Array2::from_shape_fn((a.shape()[0], b.shape()[1]), |(row, col)| {
+            a.row(row)
+                .iter()
+                .zip(b.column(col).iter())
+                .fold(0, |acc, (a, b)| {
+                    self.accumulate(acc, (*a as i128 * *b as i128) as i64)
+                })
+        })
+    }
+}
+
+/// Wraps another environment, so that systolic arrays are simulated with
+/// `arithmetic` when interpreting in it. Every tensor the systolic arrays
+/// multiply must hold integers; the interpreter panics otherwise.
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::bit_accurate::{BitAccurateEnvironment, Overflow, SystolicArrayArithmetic};
+/// use glenside::language::interpreter::{interpret, Environment, Value};
+/// use glenside::language::Language;
+/// use ndarray::array;
+///
+/// let expr: RecExpr<Language> = "
+///  (systolic-array 2 1 (access (access-tensor a) 1) (access (access-tensor b) 0))"
+///     .parse()
+///     .unwrap();
+/// let mut env: Environment<i64> = Environment::new();
+/// env.insert("a", array![[100, 100]].into_dyn());
+/// env.insert("b", array![[1], [1]].into_dyn());
+///
+/// let env = BitAccurateEnvironment {
+///     env: &env,
+///     arithmetic: SystolicArrayArithmetic { accumulator_bits: 8, overflow: Overflow::Saturate },
+/// };
+/// match interpret(&expr, expr.as_ref().len() - 1, &env) {
+///     Value::Access(a) => assert_eq!(a.tensor, array![[127]].into_dyn()),
+///     _ => panic!(),
+/// }
+/// ```
+pub struct BitAccurateEnvironment<'a, DataType> {
+    pub env: &'a dyn Lookup<DataType>,
+    pub arithmetic: SystolicArrayArithmetic,
+}
+
+impl<'a, DataType> Lookup<DataType> for BitAccurateEnvironment<'a, DataType> {
+    fn lookup(&self, name: &str) -> Option<ArrayViewD<'_, DataType>> {
+        self.env.lookup(name)
+    }
+
+    fn lookup_sparse(&self, name: &str) -> Option<&SparseMatrix<DataType>> {
+        self.env.lookup_sparse(name)
+    }
+
+    fn systolic_array_arithmetic(&self) -> Option<SystolicArrayArithmetic> {
+        Some(self.arithmetic)
+    }
+
+    fn unpack(&self, name: &str) -> Option<ArrayD<DataType>> {
+        self.env.unpack(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::interpreter::{interpret, Environment, Value};
+    use crate::language::Language;
+    use egg::RecExpr;
+    use ndarray::{array, ArrayD};
+
+    fn interpret_matmul(
+        expr: &str,
+        env: &Environment<f64>,
+        arithmetic: Option<SystolicArrayArithmetic>,
+    ) -> ArrayD<f64> {
+        let expr: RecExpr<Language> = expr.parse().unwrap();
+        let bit_accurate;
+        let env: &dyn Lookup<f64> = match arithmetic {
+            Some(arithmetic) => {
+                bit_accurate = BitAccurateEnvironment { env, arithmetic };
+                &bit_accurate
+            }
+            None => env,
+        };
+        match interpret(&expr, expr.as_ref().len() - 1, env) {
+            Value::Access(a) => a.tensor,
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn wide_accumulators_match_float_simulation() {
+        let mut env = Environment::new();
+        env.insert(
+            "a",
+            array![[1., -2., 3., 4.], [-5., 6., 7., -8.]].into_dyn(),
+        );
+        env.insert(
+            "b",
+            array![[1., 2.], [3., 4.], [-5., 6.], [7., -8.]].into_dyn(),
+        );
+        for expr in &[
+            "(systolic-array 4 2 (access (access-tensor a) 1) (access (access-tensor b) 0))",
+            "(systolic-array-with-blocking 2 2
+              (access (access-tensor a) 1) (access (access-tensor b) 0))",
+            "(systolic-array-with-activation relu 4 2
+              (access (access-tensor a) 1) (access (access-tensor b) 0))",
+        ] {
+            let arithmetic = SystolicArrayArithmetic {
+                accumulator_bits: 32,
+                overflow: Overflow::Saturate,
+            };
+            assert_eq!(
+                interpret_matmul(expr, &env, Some(arithmetic)),
+                interpret_matmul(expr, &env, None)
+            );
+        }
+    }
+
+    #[test]
+    fn accumulation_order() {
+        // With 8-bit saturating accumulators, 100 + 100 - 100 saturates to
+        // 127 before the subtraction, giving 27, while the reverse order,
+        // -100 + 100 + 100, gives 100.
+        let mut env = Environment::new();
+        env.insert(
+            "a",
+            array![[100., 100., -100.], [-100., 100., 100.]].into_dyn(),
+        );
+        env.insert("b", array![[1.], [1.], [1.]].into_dyn());
+        let expr = "(systolic-array 3 1 (access (access-tensor a) 1) (access (access-tensor b) 0))";
+
+        let saturating = SystolicArrayArithmetic {
+            accumulator_bits: 8,
+            overflow: Overflow::Saturate,
+        };
+        assert_eq!(
+            interpret_matmul(expr, &env, Some(saturating)),
+            array![[27.], [100.]].into_dyn()
+        );
+
+        // Wrapping around is associative, so the order doesn't matter.
+        let wrapping = SystolicArrayArithmetic {
+            accumulator_bits: 8,
+            overflow: Overflow::Wrap,
+        };
+        assert_eq!(
+            interpret_matmul(expr, &env, Some(wrapping)),
+            array![[100.], [100.]].into_dyn()
+        );
+
+        // The float simulation doesn't overflow.
+        assert_eq!(
+            interpret_matmul(expr, &env, None),
+            array![[100.], [100.]].into_dyn()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected an integer")]
+    fn non_integer_inputs() {
+        let mut env = Environment::new();
+        env.insert("a", array![[0.5]].into_dyn());
+        env.insert("b", array![[1.]].into_dyn());
+        interpret_matmul(
+            "(systolic-array 1 1 (access (access-tensor a) 1) (access (access-tensor b) 0))",
+            &env,
+            Some(SystolicArrayArithmetic {
+                accumulator_bits: 32,
+                overflow: Overflow::Saturate,
+            }),
+        );
+    }
+}
@@ -0,0 +1,208 @@
+//! Canonical forms of expressions, for comparing against golden files.
+//!
+//! Small changes to rewrites or cost functions can change which of several
+//! equivalent programs extraction picks, e.g. `(compute elementwise-add
+//! (access-pair a b))` rather than `(compute elementwise-add (access-pair b
+//! a))`. [`canonicalize`] maps such programs to the same expression, so that
+//! tests comparing extracted programs against expected ones only fail when a
+//! program actually changes.
+
+use super::hash_cons::HashConsBuilder;
+use super::{ComputeType, Language};
+use egg::{Id, Language as LanguageTrait, RecExpr};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Returns the canonical form of `expr`:
+///
+/// - the operands of commutative computations (`elementwise-add`,
+///   `elementwise-mul`, `logical-and` and `logical-or`) over an
+///   `access-pair` are ordered,
+/// - each distinct subexpression (e.g. each literal) appears once, and
+/// - the nodes are in the order a depth-first traversal from the root visits
+///   them, leaving out nodes unreachable from the root.
+///
+/// Two expressions with the same canonical form compute the same value.
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::canonical::canonicalize;
+/// use glenside::language::Language;
+///
+/// let a: RecExpr<Language> =
+///     "(compute elementwise-add (access-pair (access-tensor b) (access-tensor a)))"
+///         .parse()
+///         .unwrap();
+/// let b: RecExpr<Language> =
+///     "(compute elementwise-add (access-pair (access-tensor a) (access-tensor b)))"
+///         .parse()
+///         .unwrap();
+/// assert_eq!(canonicalize(&a), canonicalize(&b));
+/// ```
+pub fn canonicalize(expr: &RecExpr<Language>) -> RecExpr<Language> {
+    let mut builder = HashConsBuilder::default();
+    add(
+        expr,
+        Id::from(expr.as_ref().len() - 1),
+        &mut builder,
+        &mut HashMap::default(),
+    );
+    builder.build()
+}
+
+/// Whether swapping the operands of an `access-pair` computed over with
+/// `compute_type` leaves the result unchanged.
+fn is_commutative(compute_type: &ComputeType) -> bool {
+    match compute_type {
+        ComputeType::ElementwiseAdd
+        | ComputeType::ElementwiseMul
+        | ComputeType::LogicalAnd
+        | ComputeType::LogicalOr => true,
+        _ => false,
+    }
+}
+
+/// Orders canonical subexpressions: by operator, then by their children, in
+/// order. Equal subexpressions have equal ids in `builder`.
+fn compare(builder: &HashConsBuilder, a: Id, b: Id) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+    let (a, b) = (builder.get(a), builder.get(b));
+    a.to_string()
+        .cmp(&b.to_string())
+        .then_with(|| a.children().len().cmp(&b.children().len()))
+        .then_with(|| {
+            a.children()
+                .iter()
+                .zip(b.children())
+                .map(|(a, b)| compare(builder, *a, *b))
+                .find(|ordering| *ordering != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        })
+}
+
+/// Adds the canonical form of node `id` of `expr` to `builder`.
+fn add(
+    expr: &RecExpr<Language>,
+    id: Id,
+    builder: &mut HashConsBuilder,
+    memo: &mut HashMap<Id, Id>,
+) -> Id {
+    if let Some(new_id) = memo.get(&id) {
+        return *new_id;
+    }
+
+    let node = &expr.as_ref()[usize::from(id)];
+    let new_node = match node {
+        &Language::Compute([compute_type_id, pair_id]) => {
+            match (
+                &expr.as_ref()[usize::from(compute_type_id)],
+                &expr.as_ref()[usize::from(pair_id)],
+            ) {
+                (Language::ComputeType(compute_type), &Language::AccessPair([a_id, b_id]))
+                    if is_commutative(compute_type) =>
+                {
+                    let compute_type_id = add(expr, compute_type_id, builder, memo);
+                    let mut operands = [
+                        add(expr, a_id, builder, memo),
+                        add(expr, b_id, builder, memo),
+                    ];
+                    if compare(builder, operands[0], operands[1]) == Ordering::Greater {
+                        operands.swap(0, 1);
+                    }
+                    let pair_id = builder.add(Language::AccessPair(operands));
+                    Language::Compute([compute_type_id, pair_id])
+                }
+                _ => canonical_children(expr, node, builder, memo),
+            }
+        }
+        _ => canonical_children(expr, node, builder, memo),
+    };
+
+    let new_id = builder.add(new_node);
+    memo.insert(id, new_id);
+    new_id
+}
+
+/// `node`, with its children replaced by their canonical forms.
+fn canonical_children(
+    expr: &RecExpr<Language>,
+    node: &Language,
+    builder: &mut HashConsBuilder,
+    memo: &mut HashMap<Id, Id>,
+) -> Language {
+    let mut children = Vec::with_capacity(node.children().len());
+    for child in node.children() {
+        children.push(add(expr, *child, builder, memo));
+    }
+    let mut children = children.into_iter();
+    node.clone().map_children(|_| children.next().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canonical(expr: &str) -> RecExpr<Language> {
+        canonicalize(&expr.parse().unwrap())
+    }
+
+    #[test]
+    fn commutative_operands() {
+        assert_eq!(
+            canonical(
+                "(compute elementwise-mul
+                  (access-pair
+                   (compute relu (access (access-tensor x) 0))
+                   (access (access-tensor x) 0)))"
+            )
+            .pretty(200),
+            "(compute elementwise-mul (access-pair (access (access-tensor x) 0) (compute relu (access (access-tensor x) 0))))"
+        );
+        // Division doesn't commute.
+        assert_eq!(
+            canonical(
+                "(compute elementwise-div (access-pair (access-tensor b) (access-tensor a)))"
+            )
+            .pretty(200),
+            "(compute elementwise-div (access-pair (access-tensor b) (access-tensor a)))"
+        );
+    }
+
+    #[test]
+    fn shared_pair() {
+        // The pair is also used by a non-commutative computation, which must
+        // keep its operand order.
+        let expr = canonical(
+            "(access-concatenate
+              (compute elementwise-add (access-pair (access-tensor b) (access-tensor a)))
+              (compute elementwise-div (access-pair (access-tensor b) (access-tensor a)))
+              0)",
+        );
+        assert_eq!(
+            expr.pretty(200),
+            "(access-concatenate (compute elementwise-add (access-pair (access-tensor a) (access-tensor b))) (compute elementwise-div (access-pair (access-tensor b) (access-tensor a))) 0)"
+        );
+    }
+
+    #[test]
+    fn node_order() {
+        let mut expr = RecExpr::default();
+        let zero = expr.add(Language::Num(0));
+        let x = expr.add(Language::Symbol("x".to_string()));
+        let unused = expr.add(Language::Symbol("unused".to_string()));
+        expr.add(Language::AccessTensor(unused));
+        let x = expr.add(Language::AccessTensor(x));
+        let also_zero = expr.add(Language::Num(0));
+        let a = expr.add(Language::Access([x, zero]));
+        let b = expr.add(Language::Access([x, also_zero]));
+        expr.add(Language::AccessPair([a, b]));
+
+        assert_eq!(
+            canonicalize(&expr),
+            canonical("(access-pair (access (access-tensor x) 0) (access (access-tensor x) 0))")
+        );
+        // x, access-tensor, 0, access, access-pair.
+        assert_eq!(canonicalize(&expr).as_ref().len(), 5);
+    }
+}
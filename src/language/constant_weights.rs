@@ -0,0 +1,207 @@
+//! Known values of constant tensors, for rewrites which exploit the structure
+//! of a model's weights.
+//!
+//! Most rewrites only need shapes. Some simplifications depend on the values
+//! of the weights, though: an input channel whose filters are all zero can be
+//! dropped, and a separable kernel can be applied as two one-dimensional
+//! convolutions (see [`crate::language::rewrites::prune_zero_input_channels`]
+//! and [`crate::language::rewrites::separable_conv2d`]). [`ConstantWeights`]
+//! holds the values of a model's weights, and finds the value of an eclass by
+//! interpreting a term in it which refers only to those weights.
+
+use super::interpreter::{interpret, Environment, Value};
+use super::{Language, MyAnalysis};
+use egg::{EGraph, Id, Language as LanguageTrait, RecExpr};
+use ndarray::ArrayD;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+/// The values of a model's weights, shared between the rewrites which use
+/// them. Rewrites may add weights derived from the originals, e.g. the
+/// factors of a separable kernel; after running them, use [`Self::values`] as
+/// the environment for interpreting extracted programs.
+#[derive(Clone, Default)]
+pub struct ConstantWeights {
+    values: Arc<Mutex<HashMap<String, ArrayD<f64>>>>,
+}
+
+/// Whether the interpreter can evaluate `node`.
+pub(crate) fn is_interpretable(node: &Language) -> bool {
+    match node {
+        Language::GetAccessShape(_)
+        | Language::SystolicArrayConv2dIm2colNchwOihwWithBlocking(_)
+        | Language::SystolicArrayConv2dIm2colNhwcHwioWithBlocking(_)
+        | Language::SystolicArrayConv2dNchwOihwWithBlocking(_)
+        | Language::SystolicArrayConv2dNhwcHwioWithBlocking(_)
+        | Language::RelayOperatorCall(_)
+        | Language::RelayOperator(_)
+        | Language::DataType(_)
+        | Language::RelayActivationLayout(_)
+        | Language::RelayKernelLayout(_)
+        | Language::ConstructTuple(_)
+        | Language::TupleGetItem(_)
+        | Language::AcceleratorCall(_)
+        | Language::AcceleratorFunc(_)
+        | Language::ConstantTensor(_)
+        | Language::Unroll(_)
+        | Language::AccessShiftRight(_) => false,
+        _ => true,
+    }
+}
+
+impl ConstantWeights {
+    pub fn new(values: HashMap<String, ArrayD<f64>>) -> Self {
+        ConstantWeights {
+            values: Arc::new(Mutex::new(values)),
+        }
+    }
+
+    /// The values of all of the weights, including those added by rewrites.
+    pub fn values(&self) -> HashMap<String, ArrayD<f64>> {
+        self.values.lock().unwrap().clone()
+    }
+
+    /// Adds a weight named `name`, derived from the original weights by a
+    /// rewrite, and adds its shape to `egraph`'s analysis so that it can be
+    /// referred to in `egraph`.
+    pub fn add(&self, egraph: &mut EGraph<Language, MyAnalysis>, name: &str, value: ArrayD<f64>) {
+        egraph
+            .analysis
+            .name_to_shape
+            .insert(name.to_string(), value.shape().to_vec());
+        self.values.lock().unwrap().insert(name.to_string(), value);
+    }
+
+    /// A name for a weight derived from the original weights, made unique by
+    /// hashing `value`. Deriving the same weight twice (e.g. when a rewrite
+    /// is applied to the same match in two iterations) gives the same name.
+    pub fn name_for(prefix: &str, value: &ArrayD<f64>) -> String {
+        let mut hasher = DefaultHasher::new();
+        value.shape().hash(&mut hasher);
+        for v in value.iter() {
+            v.to_bits().hash(&mut hasher);
+        }
+        format!("{}-{:016x}", prefix, hasher.finish())
+    }
+
+    /// The value of eclass `id`, if it contains a term which refers only to
+    /// these weights.
+    pub fn value_of(&self, egraph: &EGraph<Language, MyAnalysis>, id: Id) -> Option<ArrayD<f64>> {
+        let values = self.values.lock().unwrap();
+        let mut expr = RecExpr::default();
+        let root = add_constant_term(egraph, id, &values, &mut expr, &mut HashMap::default())?;
+
+        let env: Environment<f64> = expr
+            .as_ref()
+            .iter()
+            .filter_map(|node| match node {
+                Language::Symbol(name) => Some((name.as_str(), values[name].clone())),
+                _ => None,
+            })
+            .collect();
+        match interpret(&expr, usize::from(root), &env) {
+            Value::Access(a) => Some(a.tensor),
+            Value::Tensor(t) => Some(t.into_owned()),
+            _ => None,
+        }
+    }
+}
+
+/// Adds to `expr` a term from eclass `id` whose only symbols are in `values`,
+/// returning its root, or `None` if there is no such term. `added` holds the
+/// results for the eclasses visited so far; an eclass is marked as having no
+/// such term while its own term is being searched for, which stops the search
+/// from going around cycles.
+fn add_constant_term(
+    egraph: &EGraph<Language, MyAnalysis>,
+    id: Id,
+    values: &HashMap<String, ArrayD<f64>>,
+    expr: &mut RecExpr<Language>,
+    added: &mut HashMap<Id, Option<Id>>,
+) -> Option<Id> {
+    let id = egraph.find(id);
+    if let Some(result) = added.get(&id) {
+        return *result;
+    }
+    added.insert(id, None);
+
+    for node in egraph[id]
+        .nodes
+        .iter()
+        .filter(|node| is_interpretable(node))
+    {
+        if let Language::Symbol(name) = node {
+            if !values.contains_key(name) {
+                continue;
+            }
+        }
+        let children = node
+            .children()
+            .iter()
+            .map(|child| add_constant_term(egraph, *child, values, expr, added))
+            .collect::<Option<Vec<_>>>();
+        if let Some(children) = children {
+            let mut children = children.into_iter();
+            let result = expr.add(node.clone().map_children(|_| children.next().unwrap()));
+            added.insert(id, Some(result));
+            return Some(result);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    fn value_of() {
+        let mut map = HashMap::default();
+        map.insert("w".to_string(), vec![2, 3]);
+        map.insert("x".to_string(), vec![3, 2]);
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let transposed = egraph.add_expr(
+            &"(access-transpose (access (access-tensor w) 1) (list 1 0))"
+                .parse()
+                .unwrap(),
+        );
+        let input = egraph.add_expr(&"(access-tensor x)".parse().unwrap());
+        egraph.rebuild();
+
+        let weights = ConstantWeights::new(
+            vec![(
+                "w".to_string(),
+                array![[1., 2., 3.], [4., 5., 6.]].into_dyn(),
+            )]
+            .into_iter()
+            .collect(),
+        );
+        assert_eq!(
+            weights.value_of(&egraph, transposed),
+            Some(array![[1., 4.], [2., 5.], [3., 6.]].into_dyn())
+        );
+        assert_eq!(weights.value_of(&egraph, input), None);
+    }
+
+    #[test]
+    fn name_for() {
+        let a = array![[1., 2.], [3., 4.]].into_dyn();
+        let b = array![1., 2., 3., 4.].into_dyn();
+        assert_eq!(
+            ConstantWeights::name_for("w", &a),
+            ConstantWeights::name_for("w", &a.clone())
+        );
+        assert_ne!(
+            ConstantWeights::name_for("w", &a),
+            ConstantWeights::name_for("w", &b)
+        );
+        assert!(ConstantWeights::name_for("w", &a).starts_with("w-"));
+    }
+}
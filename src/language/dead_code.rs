@@ -0,0 +1,417 @@
+//! Cleanup passes over [`RecExpr`]s.
+//!
+//! Expressions coming out of the Relay importer, or built by hand, can contain
+//! nodes which the root never uses, and tuples which are only constructed so
+//! that a single element can be pulled back out of them. Extracted expressions
+//! can also stop referencing some of the tensors in the environment, once
+//! rewrites have simplified them away. [`eliminate_dead_code`] removes the
+//! former, and [`unused_symbols`] reports the latter.
+//!
+//! When debugging, [`subexpr`] and [`environment_subset`] cut a program down
+//! to the part computing a single node, along with just the tensors that part
+//! needs, so that e.g. a node the interpreter fails on can be reproduced on
+//! its own.
+
+use super::Language;
+use egg::{Id, Language as LanguageTrait, RecExpr};
+use std::collections::{HashMap, HashSet};
+
+/// Returns a copy of `expr` containing only the nodes reachable from its root
+/// (the last node). Along the way, `(tuple-get-item (construct-tuple ...) i)`
+/// is replaced by the `i`th element of the tuple, so that unused tuple
+/// elements are pruned too.
+///
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::dead_code::eliminate_dead_code;
+/// use glenside::language::Language;
+///
+/// let expr: RecExpr<Language> = "
+///     (tuple-get-item
+///      (construct-tuple (access-tensor t-32-32) (access-tensor t-32-64))
+///      1)"
+///     .parse()
+///     .unwrap();
+/// assert_eq!(
+///     eliminate_dead_code(&expr).pretty(80),
+///     "(access-tensor t-32-64)"
+/// );
+/// ```
+pub fn eliminate_dead_code(expr: &RecExpr<Language>) -> RecExpr<Language> {
+    let nodes = expr.as_ref();
+    assert!(!nodes.is_empty(), "Expected a non-empty expression");
+
+    /// Follows `(tuple-get-item (construct-tuple ...) i)` chains down to the
+    /// tuple element they select.
+    fn resolve(nodes: &[Language], id: Id) -> Id {
+        // The tuple-get-items wrapping `id`, and their indices, outermost
+        // first.
+        let mut pending: Vec<(Id, Id)> = Vec::default();
+        let mut id = id;
+        loop {
+            if let Language::TupleGetItem([tuple_id, index_id]) = &nodes[usize::from(id)] {
+                pending.push((id, *index_id));
+                id = *tuple_id;
+                continue;
+            }
+            let (get_item_id, index_id) = match pending.pop() {
+                Some(get_item) => get_item,
+                None => return id,
+            };
+            match (&nodes[usize::from(id)], &nodes[usize::from(index_id)]) {
+                (Language::ConstructTuple(ids), Language::Num(i)) => {
+                    assert!(
+                        *i >= 0 && (*i as usize) < ids.len(),
+                        "Tuple index {} out of bounds for tuple of length {}",
+                        i,
+                        ids.len()
+                    );
+                    id = ids[*i as usize];
+                }
+                // The element can't be selected here, and so neither can
+                // those of the tuple-get-items wrapping this one.
+                _ => return pending.first().map_or(get_item_id, |(id, _)| *id),
+            }
+        }
+    }
+
+    copy_reachable(nodes, Id::from(nodes.len() - 1), |id| resolve(nodes, id))
+}
+
+/// Copies the nodes of `nodes` reachable from `root` into a new expression,
+/// replacing each child (and the root) with `resolve(child)` first. Nodes are
+/// copied depth-first, without recursing, so that deep expressions (e.g. long
+/// chains of layers) don't overflow the stack. Children are visited in order,
+/// so that nodes are copied in the same order a recursive traversal would copy
+/// them.
+fn copy_reachable(nodes: &[Language], root: Id, resolve: impl Fn(Id) -> Id) -> RecExpr<Language> {
+    let mut new_expr = RecExpr::default();
+    let mut old_to_new: HashMap<Id, Id> = HashMap::default();
+    let mut stack = vec![resolve(root)];
+    while let Some(&id) = stack.last() {
+        if old_to_new.contains_key(&id) {
+            stack.pop();
+            continue;
+        }
+        let node = &nodes[usize::from(id)];
+        let children: Vec<Id> = node
+            .children()
+            .iter()
+            .map(|child| resolve(*child))
+            .collect();
+        let uncopied: Vec<Id> = children
+            .iter()
+            .filter(|child| !old_to_new.contains_key(child))
+            .cloned()
+            .collect();
+        if uncopied.is_empty() {
+            let mut children = children.into_iter();
+            let new_node = node
+                .clone()
+                .map_children(|_| old_to_new[&children.next().unwrap()]);
+            old_to_new.insert(id, new_expr.add(new_node));
+            stack.pop();
+        } else {
+            stack.extend(uncopied.into_iter().rev());
+        }
+    }
+    new_expr
+}
+
+/// Returns the names in `names` (generally, the names of the tensors in an
+/// environment) which are never referenced by a symbol reachable from the root
+/// of `expr`. Names are returned in the order they are given.
+///
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::dead_code::unused_symbols;
+/// use glenside::language::Language;
+///
+/// let expr: RecExpr<Language> = "(access (access-tensor weights) 0)".parse().unwrap();
+/// assert_eq!(
+///     unused_symbols(&expr, vec!["data", "weights", "bias"]),
+///     vec!["data", "bias"]
+/// );
+/// ```
+pub fn unused_symbols<'a>(
+    expr: &RecExpr<Language>,
+    names: impl IntoIterator<Item = &'a str>,
+) -> Vec<&'a str> {
+    let used = match expr.as_ref().len().checked_sub(1) {
+        Some(root) => used_symbols(expr.as_ref(), Id::from(root)),
+        None => HashSet::default(),
+    };
+    names
+        .into_iter()
+        .filter(|name| !used.contains(name))
+        .collect()
+}
+
+/// The names of the symbols reachable from node `root` of `nodes`.
+fn used_symbols(nodes: &[Language], root: Id) -> HashSet<&str> {
+    let mut used: HashSet<&str> = HashSet::default();
+    let mut visited: HashSet<Id> = HashSet::default();
+    let mut stack = vec![root];
+    while let Some(id) = stack.pop() {
+        if !visited.insert(id) {
+            continue;
+        }
+        let node = &nodes[usize::from(id)];
+        if let Language::Symbol(name) = node {
+            used.insert(name.as_str());
+        }
+        stack.extend(node.children().iter().cloned());
+    }
+    used
+}
+
+/// Returns the subexpression of `expr` rooted at node `index`, as a program of
+/// its own: node `index` becomes the root, and only the nodes it depends on
+/// are kept. Unlike [`eliminate_dead_code`], the nodes are copied as they are,
+/// so that the subexpression computes exactly what node `index` did.
+///
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::dead_code::subexpr;
+/// use glenside::language::Language;
+///
+/// let expr: RecExpr<Language> = "
+///     (compute relu
+///      (access-cartesian-product
+///       (access (access-tensor a) 0)
+///       (access (access-tensor b) 0)))"
+///     .parse()
+///     .unwrap();
+/// // Node 8 is the second access.
+/// assert_eq!(subexpr(&expr, 8).pretty(80), "(access (access-tensor b) 0)");
+/// ```
+pub fn subexpr(expr: &RecExpr<Language>, index: usize) -> RecExpr<Language> {
+    let nodes = expr.as_ref();
+    assert!(
+        index < nodes.len(),
+        "Node {} out of bounds for an expression of {} nodes",
+        index,
+        nodes.len()
+    );
+
+    copy_reachable(nodes, Id::from(index), |id| id)
+}
+
+/// Returns the entries of `env` (generally, an
+/// [`Environment`](super::interpreter::Environment)) which `expr` uses, i.e.
+/// which are named by symbols reachable from its root. Together with
+/// [`subexpr`], this isolates a single node of a program, along with its
+/// inputs, as a standalone reproducer.
+///
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::dead_code::{environment_subset, subexpr};
+/// use glenside::language::interpreter::{interpret, Environment, Value};
+/// use glenside::language::Language;
+/// use ndarray::array;
+///
+/// let expr: RecExpr<Language> = "
+///     (access-cartesian-product
+///      (access (compute relu (access (access-tensor a) 0)) 0)
+///      (access (access-tensor b) 0))"
+///     .parse()
+///     .unwrap();
+/// let mut env: Environment<f64> = Environment::new();
+/// env.insert("a", array![-1., 1.].into_dyn());
+/// env.insert("b", array![2., 3.].into_dyn());
+///
+/// // Isolate the ReLU.
+/// let relu = subexpr(&expr, 5);
+/// let relu_env = environment_subset(&relu, &env);
+/// assert_eq!(relu_env.len(), 1);
+/// match interpret(&relu, relu.as_ref().len() - 1, &relu_env) {
+///     Value::Access(a) => assert_eq!(a.tensor, array![0., 1.].into_dyn()),
+///     _ => panic!(),
+/// }
+/// ```
+pub fn environment_subset<'a, T: Clone>(
+    expr: &RecExpr<Language>,
+    env: &HashMap<&'a str, T>,
+) -> HashMap<&'a str, T> {
+    let used = match expr.as_ref().len().checked_sub(1) {
+        Some(root) => used_symbols(expr.as_ref(), Id::from(root)),
+        None => HashSet::default(),
+    };
+    env.iter()
+        .filter(|(name, _)| used.contains(*name))
+        .map(|(name, value)| (*name, value.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::interpreter::{interpret, Value};
+    use ndarray::array;
+
+    #[test]
+    fn prune_unreachable() {
+        // Build an expression by hand which contains nodes the root never
+        // uses.
+        let mut expr = RecExpr::default();
+        let unused = expr.add(Language::Symbol("t-32-64".to_string()));
+        expr.add(Language::AccessTensor(unused));
+        let used = expr.add(Language::Symbol("t-32-32".to_string()));
+        let access_tensor = expr.add(Language::AccessTensor(used));
+        let zero = expr.add(Language::Num(0));
+        expr.add(Language::Access([access_tensor, zero]));
+        assert_eq!(expr.as_ref().len(), 6);
+
+        let pruned = eliminate_dead_code(&expr);
+        assert_eq!(pruned.as_ref().len(), 4);
+        assert_eq!(
+            pruned,
+            "(access (access-tensor t-32-32) 0)"
+                .parse::<RecExpr<Language>>()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn nested_tuples() {
+        let expr: RecExpr<Language> = "
+         (compute relu
+          (tuple-get-item
+           (tuple-get-item
+            (construct-tuple
+             (access (access-tensor a) 0)
+             (construct-tuple
+              (access (access-tensor b) 0)
+              (access (access-tensor c) 0)))
+            1)
+           0))"
+        .parse()
+        .unwrap();
+        let pruned = eliminate_dead_code(&expr);
+        assert_eq!(
+            pruned,
+            "(compute relu (access (access-tensor b) 0))"
+                .parse::<RecExpr<Language>>()
+                .unwrap()
+        );
+        assert_eq!(unused_symbols(&pruned, vec!["a", "b", "c"]), vec!["a", "c"]);
+
+        // The pruned program computes the same thing, without needing a or c.
+        let mut env = HashMap::default();
+        env.insert("b", array![-1., 1.].into_dyn());
+        match interpret(&pruned, pruned.as_ref().len() - 1, &env) {
+            Value::Access(a) => assert_eq!(a.tensor, array![0., 1.].into_dyn()),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn deep_expression() {
+        // Deep enough to overflow the stack if nodes were copied recursively.
+        let mut expr = RecExpr::default();
+        let t = expr.add(Language::Symbol("t".to_string()));
+        expr.add(Language::AccessTensor(t));
+        let relu = expr.add(Language::ComputeType(crate::language::ComputeType::ReLU));
+        let mut id = expr.add(Language::AccessTensor(t));
+        for _ in 0..1_000_000 {
+            id = expr.add(Language::Compute([relu, id]));
+        }
+        assert_eq!(expr.as_ref().len(), 1_000_004);
+
+        let pruned = eliminate_dead_code(&expr);
+        assert_eq!(pruned.as_ref().len(), 1_000_003);
+        assert_eq!(subexpr(&expr, 1_000_003).as_ref().len(), 1_000_003);
+    }
+
+    #[test]
+    fn tuple_not_constructed_in_expr() {
+        // When the tuple doesn't come from a construct-tuple (e.g. it comes
+        // from a Relay operator which returns a tuple), tuple-get-item is kept.
+        let expr: RecExpr<Language> = "(tuple-get-item (access-tensor t-32-32) 0)"
+            .parse()
+            .unwrap();
+        assert_eq!(eliminate_dead_code(&expr), expr);
+    }
+
+    #[test]
+    #[should_panic(expected = "Tuple index 2 out of bounds for tuple of length 2")]
+    fn tuple_index_out_of_bounds() {
+        let expr: RecExpr<Language> =
+            "(tuple-get-item (construct-tuple (access-tensor a) (access-tensor b)) 2)"
+                .parse()
+                .unwrap();
+        eliminate_dead_code(&expr);
+    }
+
+    #[test]
+    fn unused_symbols_all_used() {
+        let expr: RecExpr<Language> =
+            "(access-cartesian-product (access (access-tensor a) 0) (access (access-tensor b) 0))"
+                .parse()
+                .unwrap();
+        assert!(unused_symbols(&expr, vec!["b", "a"]).is_empty());
+    }
+
+    #[test]
+    fn unused_symbols_ignores_unreachable() {
+        let mut expr = RecExpr::default();
+        expr.add(Language::Symbol("a".to_string()));
+        let b = expr.add(Language::Symbol("b".to_string()));
+        expr.add(Language::AccessTensor(b));
+        assert_eq!(unused_symbols(&expr, vec!["a", "b"]), vec!["a"]);
+    }
+
+    #[test]
+    fn subexpr_of_shared_node() {
+        // b's access is shared by both operands of the cartesian product.
+        let mut expr = RecExpr::default();
+        let a = expr.add(Language::Symbol("a".to_string()));
+        let b = expr.add(Language::Symbol("b".to_string()));
+        let b = expr.add(Language::AccessTensor(b));
+        let zero = expr.add(Language::Num(0));
+        let b = expr.add(Language::Access([b, zero]));
+        let pair = expr.add(Language::AccessCartesianProduct([b, b]));
+        expr.add(Language::AccessTensor(a));
+
+        let pair = subexpr(&expr, usize::from(pair));
+        // The shared node is still shared, and a is gone.
+        assert_eq!(pair.as_ref().len(), 5);
+        assert_eq!(
+            pair.pretty(80),
+            "(access-cartesian-product (access (access-tensor b) 0) (access (access-tensor b) 0))"
+        );
+
+        // The root's subexpression is the whole (reachable) program.
+        let root = subexpr(&expr, expr.as_ref().len() - 1);
+        assert_eq!(root.pretty(80), "(access-tensor a)");
+    }
+
+    #[test]
+    #[should_panic(expected = "Node 3 out of bounds for an expression of 3 nodes")]
+    fn subexpr_out_of_bounds() {
+        let expr: RecExpr<Language> = "(access (access-tensor a) 0)".parse().unwrap();
+        subexpr(&expr, 3);
+    }
+
+    #[test]
+    fn environment_subset_of_subexpr() {
+        let expr: RecExpr<Language> = "
+         (access-cartesian-product
+          (access (access-tensor a) 0)
+          (access (access-tensor b) 0))"
+            .parse()
+            .unwrap();
+        let mut env = HashMap::default();
+        env.insert("a", array![1.].into_dyn());
+        env.insert("b", array![2.].into_dyn());
+        env.insert("c", array![3.].into_dyn());
+
+        let whole = environment_subset(&expr, &env);
+        assert_eq!(whole.len(), 2);
+        assert!(!whole.contains_key("c"));
+
+        // Node 3 is a's access.
+        let a = environment_subset(&subexpr(&expr, 3), &env);
+        assert_eq!(a.keys().collect::<Vec<_>>(), vec![&"a"]);
+    }
+}
@@ -0,0 +1,722 @@
+//! Converting expressions to einsum notation.
+//!
+//! Many Glenside computations are tensor contractions in disguise: a
+//! `dot-product` over an `access-cartesian-product`, a `reduce-sum`, or a
+//! `systolic-array` just multiplies and sums tensors along some axes, after
+//! transposing them. [`einsums`] recognizes these structures and writes them
+//! in the einsum notation of NumPy's `numpy.einsum`, e.g. `ab,bc->ac` for a
+//! matrix multiplication. This documents what a (possibly heavily rewritten)
+//! subexpression computes, and lets its results be cross-checked against
+//! NumPy.
+//!
+//! An expression has an einsum if it's built from tensors with only:
+//!  - `access`, `access-transpose`, and `access-squeeze` (which sums over an
+//!    axis of length 1),
+//!  - `compute dot-product` of an `access-cartesian-product`,
+//!  - `compute reduce-sum`, and
+//!  - `systolic-array` and `systolic-array-with-blocking`.
+//!
+//! Nested contractions are merged into a single einsum.
+//!
+//! Conversely, [`from_einsum`] builds the expression computing an einsum,
+//! which is a compact way to define custom kernels for the design search.
+
+use super::{ComputeType, Language, MyAnalysis, MyAnalysisData};
+use crate::error::{GlensideError, Result};
+use egg::{EGraph, Id, Language as LanguageTrait, RecExpr};
+use std::collections::HashMap;
+use std::fmt::Display;
+
+/// A computation in einsum notation: `numpy.einsum(equation, *operands)`,
+/// where `operands` are the names of the tensors read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Einsum {
+    pub equation: String,
+    pub operands: Vec<String>,
+}
+
+impl Display for Einsum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "einsum(\"{}\"", self.equation)?;
+        for operand in &self.operands {
+            write!(f, ", {}", operand)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// The value of a node, with each axis labelled by a number. Axes of
+/// different tensors which are multiplied together share a label.
+#[derive(Clone, Debug)]
+enum Term {
+    /// An access pattern whose axes (those of its shape, then of its item
+    /// shape) are labelled `labels`, computed from the tensors `operands`.
+    Access {
+        operands: Vec<(String, Vec<usize>)>,
+        labels: Vec<usize>,
+    },
+    /// An `access-cartesian-product` of two such access patterns, whose shape
+    /// is labelled `shape`, and whose pairs of items are labelled `items`.
+    Product {
+        operands: Vec<(String, Vec<usize>)>,
+        shape: Vec<usize>,
+        items: [Vec<usize>; 2],
+    },
+}
+
+/// Unifies label `from` with label `to` in `operands`.
+fn unify(operands: &mut [(String, Vec<usize>)], from: usize, to: usize) {
+    for label in operands
+        .iter_mut()
+        .flat_map(|(_, labels)| labels.iter_mut())
+    {
+        if *label == from {
+            *label = to;
+        }
+    }
+}
+
+/// `operands` and `labels` with every label replaced by a fresh one, so that
+/// they can be multiplied with a term sharing their labels (e.g. when a
+/// tensor is multiplied by itself).
+fn relabel(
+    operands: &[(String, Vec<usize>)],
+    labels: &[usize],
+    next_label: &mut usize,
+) -> (Vec<(String, Vec<usize>)>, Vec<usize>) {
+    let mut fresh = HashMap::new();
+    let mut fresh_label = |label: &usize| {
+        *fresh.entry(*label).or_insert_with(|| {
+            *next_label += 1;
+            *next_label - 1
+        })
+    };
+    let operands = operands
+        .iter()
+        .map(|(name, labels)| (name.clone(), labels.iter().map(&mut fresh_label).collect()))
+        .collect();
+    let labels = labels.iter().map(&mut fresh_label).collect();
+    (operands, labels)
+}
+
+/// Writes an access pattern term as an einsum, naming labels with letters in
+/// the order they first appear. `None` if it has more labels than letters.
+fn to_einsum(operands: &[(String, Vec<usize>)], labels: &[usize]) -> Option<Einsum> {
+    let letters = ('a'..='z').chain('A'..='Z').collect::<Vec<_>>();
+    let mut names: HashMap<usize, char> = HashMap::new();
+    let mut name = |label: &usize| -> Option<char> {
+        if !names.contains_key(label) {
+            let letter = *letters.get(names.len())?;
+            names.insert(*label, letter);
+        }
+        Some(names[label])
+    };
+    let inputs = operands
+        .iter()
+        .map(|(_, labels)| labels.iter().map(&mut name).collect::<Option<String>>())
+        .collect::<Option<Vec<_>>>()?;
+    let output = labels.iter().map(&mut name).collect::<Option<String>>()?;
+    Some(Einsum {
+        equation: format!("{}->{}", inputs.join(","), output),
+        operands: operands.iter().map(|(name, _)| name.clone()).collect(),
+    })
+}
+
+/// The einsum of each node of `expr`, indexed as `expr`'s nodes are, where
+/// the node has one: its value as an access pattern (all axes of its shape,
+/// then of its item shape) is the result of the einsum. `analysis` must know
+/// the shapes of every tensor `expr` reads.
+///
+/// An operand's labels are those of its axes as stored, so a transposed
+/// weight matrix is labelled `bc`, not `cb`:
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::einsum::einsums;
+/// use glenside::language::{Language, MyAnalysis};
+///
+/// let expr: RecExpr<Language> = "
+///  (compute dot-product
+///   (access-cartesian-product
+///    (access (access-tensor t-32-64) 1)
+///    (access (access-transpose (access-tensor t-64-16) (list 1 0)) 1)))"
+///     .parse()
+///     .unwrap();
+/// let einsums = einsums(&expr, MyAnalysis::default());
+///
+/// let matmul = einsums.last().unwrap().as_ref().unwrap();
+/// assert_eq!(matmul.equation, "ab,bc->ac");
+/// assert_eq!(matmul.to_string(), "einsum(\"ab,bc->ac\", t-32-64, t-64-16)");
+/// ```
+pub fn einsums(expr: &RecExpr<Language>, analysis: MyAnalysis) -> Vec<Option<Einsum>> {
+    let nodes = expr.as_ref();
+    let mut egraph = EGraph::new(analysis);
+    let mut ids: Vec<Id> = Vec::with_capacity(nodes.len());
+    let mut terms: Vec<Option<Term>> = Vec::with_capacity(nodes.len());
+    let mut next_label = 0;
+
+    for node in nodes {
+        let id = egraph.add(node.clone().map_children(|child| ids[usize::from(child)]));
+        ids.push(id);
+        let access = |index: Id| match &terms[usize::from(index)] {
+            Some(Term::Access { operands, labels }) => Some((operands.clone(), labels.clone())),
+            _ => None,
+        };
+        let shape_ndim = |index: Id| match &egraph[ids[usize::from(index)]].data {
+            MyAnalysisData::AccessPattern(a) => a.shape.ndim(),
+            _ => panic!(),
+        };
+
+        let term = match node {
+            &Language::AccessTensor(tensor) => {
+                let name = match &nodes[usize::from(tensor)] {
+                    &Language::TensorDecl([name, _]) => &nodes[usize::from(name)],
+                    other => other,
+                };
+                match (name, &egraph[id].data) {
+                    (Language::Symbol(name), MyAnalysisData::AccessPattern(a)) => {
+                        let labels =
+                            (next_label..next_label + a.as_vec().len()).collect::<Vec<_>>();
+                        next_label += labels.len();
+                        Some(Term::Access {
+                            operands: vec![(name.clone(), labels.clone())],
+                            labels,
+                        })
+                    }
+                    _ => None,
+                }
+            }
+            &Language::Access([access_id, _]) => {
+                access(access_id).map(|(operands, labels)| Term::Access { operands, labels })
+            }
+            &Language::AccessTranspose([access_id, list_id]) => {
+                access(access_id).map(|(operands, labels)| {
+                    let list =
+                        MyAnalysis::get_axis_list(ids[usize::from(list_id)], &egraph, labels.len());
+                    Term::Access {
+                        operands,
+                        labels: list.iter().map(|axis| labels[*axis]).collect(),
+                    }
+                })
+            }
+            &Language::AccessSqueeze([access_id, axis_id]) => {
+                access(access_id).map(|(operands, mut labels)| {
+                    labels.remove(MyAnalysis::get_axis(
+                        ids[usize::from(axis_id)],
+                        &egraph,
+                        labels.len(),
+                    ));
+                    Term::Access { operands, labels }
+                })
+            }
+            &Language::AccessCartesianProduct([a0_id, a1_id]) => {
+                match (access(a0_id), access(a1_id)) {
+                    (Some((mut operands, mut labels0)), Some((operands1, labels1))) => {
+                        let (operands1, mut labels1) =
+                            relabel(&operands1, &labels1, &mut next_label);
+                        operands.extend(operands1);
+                        let items1 = labels1.split_off(shape_ndim(a1_id));
+                        let items0 = labels0.split_off(shape_ndim(a0_id));
+                        labels0.extend(labels1);
+                        Some(Term::Product {
+                            operands,
+                            shape: labels0,
+                            items: [items0, items1],
+                        })
+                    }
+                    _ => None,
+                }
+            }
+            &Language::Compute([compute_type_id, access_id]) => {
+                match (
+                    &nodes[usize::from(compute_type_id)],
+                    &terms[usize::from(access_id)],
+                ) {
+                    (
+                        Language::ComputeType(ComputeType::DotProduct),
+                        Some(Term::Product {
+                            operands,
+                            shape,
+                            items,
+                        }),
+                    ) => {
+                        let mut operands = operands.clone();
+                        for (from, to) in items[1].iter().zip(&items[0]) {
+                            unify(&mut operands, *from, *to);
+                        }
+                        Some(Term::Access {
+                            operands,
+                            labels: shape.clone(),
+                        })
+                    }
+                    (
+                        Language::ComputeType(ComputeType::ReduceSum),
+                        Some(Term::Access { operands, labels }),
+                    ) => Some(Term::Access {
+                        operands: operands.clone(),
+                        labels: labels[..shape_ndim(access_id)].to_vec(),
+                    }),
+                    _ => None,
+                }
+            }
+            &Language::SystolicArray([_, _, a0_id, a1_id])
+            | &Language::SystolicArrayWithBlocking([_, _, a0_id, a1_id]) => {
+                match (access(a0_id), access(a1_id)) {
+                    (Some((mut operands, mut labels)), Some((operands1, labels1))) => {
+                        // Multiplies a0's items, of length K, by a1's K by N
+                        // matrix.
+                        let (mut operands1, labels1) =
+                            relabel(&operands1, &labels1, &mut next_label);
+                        let k = labels.pop().unwrap();
+                        unify(&mut operands1, labels1[0], k);
+                        operands.extend(operands1);
+                        labels.push(labels1[1]);
+                        Some(Term::Access { operands, labels })
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        terms.push(term);
+    }
+
+    terms
+        .iter()
+        .map(|term| match term {
+            Some(Term::Access { operands, labels }) => to_einsum(operands, labels),
+            _ => None,
+        })
+        .collect()
+}
+
+/// A node of an expression being built by [`from_einsum`], whose axes (those
+/// of its shape, then of its item shape) are labelled `labels`.
+struct Labelled {
+    id: Id,
+    labels: Vec<char>,
+}
+
+/// Adds `(list <values>...)` to `expr`.
+fn add_list(expr: &mut RecExpr<Language>, values: impl Iterator<Item = usize>) -> Id {
+    let values = values
+        .map(|value| expr.add(Language::Num(value as i64)))
+        .collect::<Vec<_>>();
+    expr.add(Language::List(values.into_boxed_slice()))
+}
+
+/// Adds `(access <access> <axis>)` to `expr`.
+fn add_access(expr: &mut RecExpr<Language>, access: Id, axis: usize) -> Id {
+    let axis = expr.add(Language::Num(axis as i64));
+    expr.add(Language::Access([access, axis]))
+}
+
+/// Transposes `x` so that its axes are labelled `labels`, if they aren't
+/// already.
+fn transpose(expr: &mut RecExpr<Language>, x: Labelled, labels: Vec<char>) -> Labelled {
+    if x.labels == labels {
+        return x;
+    }
+    let list = add_list(
+        expr,
+        labels
+            .iter()
+            .map(|label| x.labels.iter().position(|l| l == label).unwrap()),
+    );
+    Labelled {
+        id: expr.add(Language::AccessTranspose([x.id, list])),
+        labels,
+    }
+}
+
+/// Sums `x` over the axes whose labels aren't in `keep`, if there are any.
+fn sum_all_but(expr: &mut RecExpr<Language>, x: Labelled, keep: &[char]) -> Labelled {
+    let (kept, summed): (Vec<char>, Vec<char>) =
+        x.labels.iter().partition(|label| keep.contains(*label));
+    if summed.is_empty() {
+        return x;
+    }
+    let x = transpose(expr, x, kept.iter().chain(&summed).cloned().collect());
+    let access = add_access(expr, x.id, kept.len());
+    let reduce_sum = expr.add(Language::ComputeType(ComputeType::ReduceSum));
+    Labelled {
+        id: expr.add(Language::Compute([reduce_sum, access])),
+        labels: kept,
+    }
+}
+
+/// Multiplies `a` by `b`, summing over the labels they share, none of which
+/// may be in `keep`.
+fn contract(
+    expr: &mut RecExpr<Language>,
+    a: Labelled,
+    b: Labelled,
+    keep: &[char],
+) -> Result<Labelled> {
+    let shared = a
+        .labels
+        .iter()
+        .filter(|label| b.labels.contains(*label))
+        .cloned()
+        .collect::<Vec<_>>();
+    if let Some(label) = shared.iter().find(|label| keep.contains(*label)) {
+        return Err(GlensideError::Parse(format!(
+            "Label {} is shared by two operands and kept in the result, which isn't supported",
+            label
+        )));
+    }
+    let (a_kept, b_kept) = (
+        a.labels
+            .iter()
+            .filter(|label| !shared.contains(*label))
+            .cloned()
+            .collect::<Vec<_>>(),
+        b.labels
+            .iter()
+            .filter(|label| !shared.contains(*label))
+            .cloned()
+            .collect::<Vec<_>>(),
+    );
+    let a = transpose(expr, a, a_kept.iter().chain(&shared).cloned().collect());
+    let b = transpose(expr, b, b_kept.iter().chain(&shared).cloned().collect());
+    let a = add_access(expr, a.id, a_kept.len());
+    let b = add_access(expr, b.id, b_kept.len());
+    let product = expr.add(Language::AccessCartesianProduct([a, b]));
+    let dot_product = expr.add(Language::ComputeType(ComputeType::DotProduct));
+    Ok(Labelled {
+        id: expr.add(Language::Compute([dot_product, product])),
+        labels: a_kept.into_iter().chain(b_kept).collect(),
+    })
+}
+
+/// Builds the expression computing the einsum `equation` (in the notation of
+/// `numpy.einsum`, with an explicit output) of `operands`, given by name and
+/// shape. The tensors are declared with `tensor-decl`, so the expression
+/// describes their shapes itself (see [`MyAnalysis::from_tensor_decls`]).
+///
+/// Operands are multiplied from left to right, each product summing over the
+/// labels its factors share. Labels which appear in only one operand and not
+/// in the output are summed with `reduce-sum` first. Equations with labels
+/// repeated within an operand (e.g. `ii->i`), or shared by two operands and
+/// kept in the output (e.g. the batch label of `bij,bjk->bik`), aren't
+/// supported, and give a [`GlensideError::Parse`], as do malformed equations.
+/// ```
+/// use glenside::language::einsum::{einsums, from_einsum};
+/// use glenside::language::MyAnalysis;
+///
+/// let expr = from_einsum("ij,jk->ik", &[("x", &[32, 64]), ("w", &[64, 16])]).unwrap();
+/// assert_eq!(
+///     einsums(&expr, MyAnalysis::from_tensor_decls(&expr)).last().unwrap().as_ref().unwrap().equation,
+///     "ab,bc->ac"
+/// );
+/// ```
+pub fn from_einsum(equation: &str, operands: &[(&str, &[usize])]) -> Result<RecExpr<Language>> {
+    let equation = equation
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>();
+    let (inputs, output) = match equation.split("->").collect::<Vec<_>>()[..] {
+        [inputs, output] => (inputs.split(',').collect::<Vec<_>>(), output),
+        _ => {
+            return Err(GlensideError::Parse(format!(
+                "Expected an einsum equation of the form <inputs>-><output>, found {}",
+                equation
+            )))
+        }
+    };
+    if inputs.len() != operands.len() {
+        return Err(GlensideError::Parse(format!(
+            "Einsum {} has {} inputs, but {} operands were given",
+            equation,
+            inputs.len(),
+            operands.len()
+        )));
+    }
+
+    let mut sizes: HashMap<char, usize> = HashMap::new();
+    for (input, (name, shape)) in inputs.iter().zip(operands) {
+        let labels = input.chars().collect::<Vec<_>>();
+        if labels.len() != shape.len() {
+            return Err(GlensideError::Parse(format!(
+                "Operand {} has {} axes, but is labelled {}",
+                name,
+                shape.len(),
+                input
+            )));
+        }
+        for (label, size) in labels.iter().zip(shape.iter()) {
+            if *sizes.entry(*label).or_insert(*size) != *size {
+                return Err(GlensideError::Parse(format!(
+                    "Label {} has size {} in operand {}, but size {} elsewhere",
+                    label, size, name, sizes[label]
+                )));
+            }
+        }
+    }
+    for term in inputs.iter().chain(std::iter::once(&output)) {
+        if let Some(label) = term
+            .chars()
+            .find(|label| !label.is_ascii_alphabetic() || term.matches(*label).count() > 1)
+        {
+            return Err(GlensideError::Parse(format!(
+                "Label {} in {} is invalid or repeated",
+                label, term
+            )));
+        }
+    }
+    if let Some(label) = output.chars().find(|label| !sizes.contains_key(label)) {
+        return Err(GlensideError::Parse(format!(
+            "Output label {} doesn't label any input",
+            label
+        )));
+    }
+
+    let mut expr = RecExpr::default();
+    let mut tensors = inputs
+        .iter()
+        .zip(operands)
+        .map(|(input, (name, shape))| {
+            let name = expr.add(Language::Symbol(name.to_string()));
+            let dims = shape
+                .iter()
+                .map(|dim| expr.add(Language::Num(*dim as i64)))
+                .collect::<Vec<_>>();
+            let shape = expr.add(Language::Shape(dims.into_boxed_slice()));
+            let tensor = expr.add(Language::TensorDecl([name, shape]));
+            Labelled {
+                id: expr.add(Language::AccessTensor(tensor)),
+                labels: input.chars().collect(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .into_iter();
+
+    let mut result = match tensors.next() {
+        Some(tensor) => tensor,
+        None => {
+            return Err(GlensideError::Parse(
+                "Expected at least one operand".to_string(),
+            ))
+        }
+    };
+    for (i, tensor) in tensors.enumerate() {
+        // The labels still needed after this product.
+        let keep = output
+            .chars()
+            .chain(inputs[i + 2..].iter().flat_map(|input| input.chars()))
+            .collect::<Vec<_>>();
+        let a = sum_all_but(
+            &mut expr,
+            result,
+            &keep
+                .iter()
+                .chain(&tensor.labels)
+                .cloned()
+                .collect::<Vec<_>>(),
+        );
+        let b = sum_all_but(
+            &mut expr,
+            tensor,
+            &keep.iter().chain(&a.labels).cloned().collect::<Vec<_>>(),
+        );
+        result = contract(&mut expr, a, b, &keep)?;
+    }
+    let result = sum_all_but(&mut expr, result, &output.chars().collect::<Vec<_>>());
+    transpose(&mut expr, result, output.chars().collect());
+
+    Ok(expr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn einsum(expr: &str) -> Option<String> {
+        let expr: RecExpr<Language> = expr.parse().unwrap();
+        einsums(&expr, MyAnalysis::default())
+            .pop()
+            .unwrap()
+            .map(|einsum| einsum.to_string())
+    }
+
+    #[test]
+    fn views() {
+        assert_eq!(
+            einsum("(access-transpose (access-tensor t-32-64) (list 1 0))"),
+            Some("einsum(\"ab->ba\", t-32-64)".to_string())
+        );
+        assert_eq!(
+            einsum("(access-squeeze (access (access-tensor t-1-64) 1) 0)"),
+            Some("einsum(\"ab->b\", t-1-64)".to_string())
+        );
+        assert_eq!(
+            einsum("(access-slice (access (access-tensor t-32-64) 1) 1 0 16)"),
+            None
+        );
+    }
+
+    #[test]
+    fn reduce_sum() {
+        assert_eq!(
+            einsum("(compute reduce-sum (access (access-tensor t-32-64-16) 1))"),
+            Some("einsum(\"abc->a\", t-32-64-16)".to_string())
+        );
+        // Other reductions aren't sums of products.
+        assert_eq!(
+            einsum("(compute reduce-max (access (access-tensor t-32-64-16) 1))"),
+            None
+        );
+    }
+
+    #[test]
+    fn self_product() {
+        // A Gram matrix: each row's dot product with every other.
+        assert_eq!(
+            einsum(
+                "(compute dot-product
+                  (access-cartesian-product
+                   (access (access-tensor t-32-64) 1)
+                   (access (access-tensor t-32-64) 1)))"
+            ),
+            Some("einsum(\"ab,cb->ac\", t-32-64, t-32-64)".to_string())
+        );
+    }
+
+    #[test]
+    fn nested() {
+        // Two layers, the second on a systolic array, merge into one einsum.
+        assert_eq!(
+            einsum(
+                "(systolic-array 16 8
+                  (access
+                   (compute dot-product
+                    (access-cartesian-product
+                     (access (access-tensor t-4-32) 1)
+                     (access (access-transpose (access-tensor t-32-16) (list 1 0)) 1)))
+                   1)
+                  (access (access-tensor t-16-8) 0))"
+            ),
+            Some("einsum(\"ab,bc,cd->ad\", t-4-32, t-32-16, t-16-8)".to_string())
+        );
+    }
+
+    #[test]
+    fn batched() {
+        // Every item of one access pattern is multiplied with every item of
+        // the other, so the axes of their shapes are never shared, even when
+        // they're both batch axes.
+        let einsums = einsums(
+            &"(compute dot-product
+               (access-cartesian-product
+                (access (access-tensor t-8-4-32) 2)
+                (access (access-tensor t-8-16-32) 2)))"
+                .parse()
+                .unwrap(),
+            MyAnalysis::default(),
+        );
+        assert_eq!(
+            einsums.last().unwrap().as_ref().unwrap().equation,
+            "abc,dec->abde"
+        );
+    }
+
+    fn interpret_einsum(
+        equation: &str,
+        operands: &[(&str, ndarray::ArrayD<f64>)],
+    ) -> ndarray::ArrayD<f64> {
+        use crate::language::interpreter::{interpret, Environment, Value};
+        let shapes = operands
+            .iter()
+            .map(|(name, value)| (*name, value.shape()))
+            .collect::<Vec<_>>();
+        let expr = from_einsum(equation, &shapes).unwrap();
+        let mut env = Environment::new();
+        for (name, value) in operands {
+            env.insert(*name, value.clone());
+        }
+        match interpret(&expr, expr.as_ref().len() - 1, &env) {
+            Value::Access(a) => a.tensor,
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn import_matmul() {
+        let x = ndarray::array![[1., 2., 3.], [4., 5., 6.]];
+        let w = ndarray::array![[1., 0.], [0., 1.], [1., 1.]];
+        assert_eq!(
+            interpret_einsum(
+                "ij,jk->ik",
+                &[("x", x.clone().into_dyn()), ("w", w.clone().into_dyn())]
+            ),
+            x.dot(&w).into_dyn()
+        );
+        // The transposed result.
+        assert_eq!(
+            interpret_einsum(
+                "ij,jk->ki",
+                &[("x", x.clone().into_dyn()), ("w", w.clone().into_dyn())]
+            ),
+            x.dot(&w).reversed_axes().into_dyn()
+        );
+    }
+
+    #[test]
+    fn import_reductions() {
+        let x = ndarray::array![[1., 2., 3.], [4., 5., 6.]].into_dyn();
+        assert_eq!(
+            interpret_einsum("ij->j", &[("x", x.clone())]),
+            ndarray::array![5., 7., 9.].into_dyn()
+        );
+        // i is summed out of x before it's multiplied by y.
+        assert_eq!(
+            interpret_einsum(
+                "ij,j->",
+                &[
+                    ("x", x.clone()),
+                    ("y", ndarray::array![1., 0., 2.].into_dyn())
+                ]
+            ),
+            ndarray::arr0(5. + 2. * 9.).into_dyn()
+        );
+    }
+
+    #[test]
+    fn import_chain() {
+        // Three operands, with j contracted first, then k.
+        let expr = from_einsum(
+            "ij,jk,kl->il",
+            &[("a", &[4, 8]), ("b", &[8, 16]), ("c", &[16, 2])],
+        )
+        .unwrap();
+        assert_eq!(
+            einsums(&expr, MyAnalysis::from_tensor_decls(&expr))
+                .pop()
+                .unwrap()
+                .unwrap(),
+            Einsum {
+                equation: "ab,bc,cd->ad".to_string(),
+                operands: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn import_errors() {
+        for (equation, operands) in &[
+            ("ij,jk", &[("x", &[2, 3][..]), ("w", &[3, 4][..])][..]),
+            ("ij,jk->ik", &[("x", &[2, 3][..])][..]),
+            ("ij,jk->ik", &[("x", &[2, 3][..]), ("w", &[4, 4][..])][..]),
+            ("ii->i", &[("x", &[3, 3][..])][..]),
+            (
+                "bij,bjk->bik",
+                &[("x", &[2, 3, 4][..]), ("w", &[2, 4, 5][..])][..],
+            ),
+            ("ij->iz", &[("x", &[2, 3][..])][..]),
+        ] {
+            assert!(matches!(
+                from_einsum(equation, operands),
+                Err(GlensideError::Parse(_))
+            ));
+        }
+    }
+}
@@ -16,6 +16,8 @@ use tvm::ir::{module::*, PrimExpr};
 use tvm::runtime::array::Array;
 use tvm::runtime::IsObjectRef;
 
+use super::hash_cons::hash_cons;
+use super::provenance::Provenance;
 use super::ComputeType;
 use super::PadType;
 use super::RelayOperator;
@@ -391,6 +393,451 @@ pub fn conv2d(
     }
 }
 
+/// Builds a 3D convolution in the access-windows formulation. Like
+/// [`conv2d`], but with data in layout NCDHW and weights in layout OIDHW;
+/// other layouts, dilation, and groups are not yet supported. `padding` is
+/// (front top left back bottom right).
+pub fn conv3d(
+    expr: &mut RecExpr<Language>,
+    data_id: Id,
+    data_shape: &[usize],
+    weights_id: Id,
+    weights_shape: &[usize],
+    strides: &[usize],
+    padding: &[usize],
+    dilation: &[usize],
+    groups: usize,
+    data_layout: &str,
+    kernel_layout: &str,
+    out_layout: &str,
+) -> Id {
+    assert_eq!(data_shape.len(), 5);
+    assert_eq!(weights_shape.len(), 5);
+    assert_eq!(strides.len(), 3);
+    assert_eq!(padding.len(), 6);
+    assert_eq!(dilation.len(), 3);
+    assert_eq!(groups, 1);
+
+    assert_eq!(data_layout, "NCDHW", "NCDHW is the only layout supported");
+    assert_eq!(kernel_layout, "OIDHW", "OIDHW is the only layout supported");
+    assert_eq!(dilation, [1, 1, 1]);
+    assert_eq!(out_layout, "");
+
+    let mut data_id = data_id;
+    for i in 0..3 {
+        data_id = access_pad(
+            expr,
+            data_id,
+            PadType::ZeroPadding,
+            2 + i,
+            padding[i],
+            padding[3 + i],
+        );
+    }
+
+    let data_id = access(expr, data_id, 1);
+    let weights_shape_id = shape(expr, weights_shape[1..].to_vec());
+    let stride_shape_id = shape(
+        expr,
+        std::iter::once(1).chain(strides.iter().cloned()).collect(),
+    );
+    let data_id = expr.add(Language::AccessWindows([
+        data_id,
+        weights_shape_id,
+        stride_shape_id,
+    ]));
+    // Result is [batch 1 new_d new_h new_w] [in_channel kd kh kw]
+
+    // Squeeze extraneous 1st dimension
+    let squeeze_axis_id = expr.add(Language::Num(1));
+    let data_id = expr.add(Language::AccessSqueeze([data_id, squeeze_axis_id]));
+    let data_id = access(expr, data_id, 4);
+    // Result is [batch new_d new_h new_w] [in_channel kd kh kw]
+
+    let weights_id = access(expr, weights_id, 1);
+    let data_id = expr.add(Language::AccessCartesianProduct([weights_id, data_id]));
+    let data_id = compute(expr, ComputeType::DotProduct, data_id);
+
+    access_transpose(expr, data_id, &[1, 0, 2, 3, 4])
+}
+
+/// Builds a 2D transposed convolution (see the `conv2d-transpose` node) as a
+/// regular convolution, which can then be mapped to hardware like any other.
+/// The data is upsampled by inserting `stride - 1` zeros between neighboring
+/// elements, padded by `kernel size - 1 - padding` on each side (or cropped,
+/// where that's negative), and convolved, with stride 1, with the kernel
+/// flipped spatially and with its channel axes swapped. Data must be in layout
+/// NCHW and weights in layout IOHW; dilation and groups are not yet supported.
+pub fn conv2d_transpose(
+    expr: &mut RecExpr<Language>,
+    data_id: Id,
+    data_shape: &[usize],
+    weights_id: Id,
+    weights_shape: &[usize],
+    strides: &[usize],
+    padding: &[usize],
+    output_padding: &[usize],
+    dilation: &[usize],
+    groups: usize,
+    data_layout: &str,
+    kernel_layout: &str,
+    out_layout: &str,
+) -> Id {
+    assert_eq!(data_shape.len(), 4);
+    assert_eq!(weights_shape.len(), 4);
+    assert_eq!(strides.len(), 2);
+    assert_eq!(padding.len(), 4);
+    assert_eq!(output_padding.len(), 2);
+    assert_eq!(dilation.len(), 2);
+    assert_eq!(groups, 1);
+
+    assert_eq!(data_layout, "NCHW", "NCHW is the only layout supported");
+    assert_eq!(kernel_layout, "IOHW", "IOHW is the only layout supported");
+    assert_eq!(dilation, [1, 1]);
+    assert_eq!(out_layout, "");
+
+    let mut data_id = data_id;
+    let mut data_shape = Vec::from(data_shape);
+    for i in 0..2 {
+        let axis = 2 + i;
+
+        // Insert zeros: [.., H, ..] -> [.., H, 1, ..] -> [.., H, stride, ..]
+        // -> [.., H * stride, ..], then drop the trailing zeros.
+        if strides[i] > 1 {
+            data_id = access_insert_axis(expr, data_id, axis + 1);
+            data_id = access_pad(
+                expr,
+                data_id,
+                PadType::ZeroPadding,
+                axis + 1,
+                0,
+                strides[i] - 1,
+            );
+            let mut reshaped_shape = data_shape.clone();
+            reshaped_shape[axis] *= strides[i];
+            let access_shape_id = access_shape(expr, &[], &reshaped_shape);
+            data_id = expr.add(Language::AccessReshape([data_id, access_shape_id]));
+            data_shape[axis] = (data_shape[axis] - 1) * strides[i] + 1;
+            data_id = access_slice(expr, data_id, axis, 0, data_shape[axis]);
+        }
+
+        let before = weights_shape[axis] as i64 - 1 - padding[i] as i64;
+        let after =
+            weights_shape[axis] as i64 - 1 - padding[2 + i] as i64 + output_padding[i] as i64;
+        let (crop_before, crop_after) = (before.min(0).abs() as usize, after.min(0).abs() as usize);
+        if crop_before > 0 || crop_after > 0 {
+            data_id = access_slice(
+                expr,
+                data_id,
+                axis,
+                crop_before,
+                data_shape[axis] - crop_after,
+            );
+            data_shape[axis] -= crop_before + crop_after;
+        }
+        let (pad_before, pad_after) = (before.max(0) as usize, after.max(0) as usize);
+        if pad_before > 0 || pad_after > 0 {
+            data_id = access_pad(
+                expr,
+                data_id,
+                PadType::ZeroPadding,
+                axis,
+                pad_before,
+                pad_after,
+            );
+            data_shape[axis] += pad_before + pad_after;
+        }
+    }
+
+    // IOHW -> OIHW, flipping the kernel's rows and columns.
+    let weights_id = access_transpose(expr, weights_id, &[1, 0, 2, 3]);
+    let weights_id = access_reverse(expr, weights_id, 2);
+    let weights_id = access_reverse(expr, weights_id, 3);
+    let weights_shape = [
+        weights_shape[1],
+        weights_shape[0],
+        weights_shape[2],
+        weights_shape[3],
+    ];
+
+    conv2d(
+        expr,
+        data_id,
+        &data_shape,
+        weights_id,
+        &weights_shape,
+        &[1, 1],
+        &[0, 0, 0, 0],
+        &[1, 1],
+        1,
+        "NCHW",
+        "OIHW",
+        "",
+        false,
+    )
+}
+
+/// Builds a batched matrix multiplication (see the `batch-matmul` node) of `a`,
+/// with shape [B, M, K], and `b`, with shape [B, K, N]. Both are broadcast to
+/// [B, M, N, K] (`b` after being transposed to [B, N, K]), paired, and reduced
+/// with a dot product.
+///
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::from_relay::batch_matmul;
+/// use glenside::language::Language;
+///
+/// let mut expr = RecExpr::default();
+/// let a_id = expr.add(Language::Symbol("a".to_string()));
+/// let a_id = expr.add(Language::AccessTensor(a_id));
+/// let b_id = expr.add(Language::Symbol("b".to_string()));
+/// let b_id = expr.add(Language::AccessTensor(b_id));
+/// batch_matmul(&mut expr, a_id, &[2, 3, 4], b_id, &[2, 4, 5]);
+/// assert!(expr.pretty(80).starts_with("(compute dot-product"));
+/// ```
+pub fn batch_matmul(
+    expr: &mut RecExpr<Language>,
+    a_id: Id,
+    a_shape: &[usize],
+    b_id: Id,
+    b_shape: &[usize],
+) -> Id {
+    assert_eq!(a_shape.len(), 3);
+    assert_eq!(b_shape.len(), 3);
+    assert_eq!(a_shape[0], b_shape[0]);
+    assert_eq!(a_shape[2], b_shape[1]);
+    let broadcast_shape = [a_shape[0], a_shape[1], b_shape[2], a_shape[2]];
+
+    let a_id = access_insert_axis(expr, a_id, 2);
+    let access_shape_id = access_shape(expr, &broadcast_shape, &[]);
+    let a_id = expr.add(Language::AccessBroadcast([a_id, access_shape_id]));
+
+    let b_id = access_transpose(expr, b_id, &[0, 2, 1]);
+    let b_id = access_insert_axis(expr, b_id, 1);
+    let access_shape_id = access_shape(expr, &broadcast_shape, &[]);
+    let b_id = expr.add(Language::AccessBroadcast([b_id, access_shape_id]));
+
+    let pair_id = access_pair(expr, a_id, b_id, 3);
+    compute(expr, ComputeType::DotProduct, pair_id)
+}
+
+/// Multiplies every element of `data`, with shape `data_shape`, by the
+/// constant `factor`, as in the scaling of attention scores.
+///
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::from_relay::scale;
+/// use glenside::language::Language;
+///
+/// let mut expr = RecExpr::default();
+/// let data_id = expr.add(Language::Symbol("data".to_string()));
+/// let data_id = expr.add(Language::AccessTensor(data_id));
+/// scale(&mut expr, data_id, &[2, 3], 0.5);
+/// assert!(expr.pretty(80).starts_with("(compute elementwise-mul"));
+/// ```
+pub fn scale(expr: &mut RecExpr<Language>, data_id: Id, data_shape: &[usize], factor: f64) -> Id {
+    let factor_id = expr.add(Language::NotNanFloat64(NotNan::new(factor).unwrap()));
+    let factor_id = expr.add(Language::Literal(factor_id));
+    let mut factor_id = expr.add(Language::AccessLiteral(factor_id));
+    for _ in 0..data_shape.len() {
+        factor_id = access_insert_axis(expr, factor_id, 0);
+    }
+    let access_shape_id = access_shape(expr, data_shape, &[]);
+    let factor_id = expr.add(Language::AccessBroadcast([factor_id, access_shape_id]));
+
+    let pair_id = access_pair(expr, data_id, factor_id, data_shape.len());
+    compute(expr, ComputeType::ElementwiseMul, pair_id)
+}
+
+/// Broadcasts `id`, which has shape `shape` and no item shape, to shape
+/// `shape` followed by `item_shape`, by repeating it along new trailing axes.
+fn broadcast_over_items(
+    expr: &mut RecExpr<Language>,
+    mut id: Id,
+    shape: &[usize],
+    item_shape: &[usize],
+) -> Id {
+    for axis in shape.len()..shape.len() + item_shape.len() {
+        id = access_insert_axis(expr, id, axis);
+    }
+    let full_shape = shape
+        .iter()
+        .chain(item_shape.iter())
+        .cloned()
+        .collect::<Vec<_>>();
+    let access_shape_id = access_shape(expr, &full_shape, &[]);
+    expr.add(Language::AccessBroadcast([id, access_shape_id]))
+}
+
+/// Normalizes each item of `data`, with shape `shape` and item shape
+/// `item_shape`, to zero mean and unit variance, using only reductions and
+/// elementwise operations:
+///
+/// `(x - mean(x)) / sqrt(var(x) + epsilon)`
+///
+/// The mean and variance are computed together, by a single
+/// [`ComputeType::ReduceMeanVar`]. `epsilon_id` is a scalar `literal`. The
+/// result has shape `shape` and item shape `item_shape`.
+fn normalize(
+    expr: &mut RecExpr<Language>,
+    data_id: Id,
+    shape: &[usize],
+    item_shape: &[usize],
+    epsilon_id: Id,
+) -> Id {
+    let statistics_id = compute(expr, ComputeType::ReduceMeanVar, data_id);
+    let zero_id = expr.add(Language::Num(0));
+    let mean_id = expr.add(Language::TupleGetItem([statistics_id, zero_id]));
+    let one_id = expr.add(Language::Num(1));
+    let variance_id = expr.add(Language::TupleGetItem([statistics_id, one_id]));
+
+    let mean_id = broadcast_over_items(expr, mean_id, shape, item_shape);
+    let negated_mean_id = compute(expr, ComputeType::Negative, mean_id);
+    let pair_id = access_pair(expr, data_id, negated_mean_id, shape.len());
+    let centered_id = compute(expr, ComputeType::ElementwiseAdd, pair_id);
+
+    let mut epsilon_id = expr.add(Language::AccessLiteral(epsilon_id));
+    for _ in 0..shape.len() {
+        epsilon_id = access_insert_axis(expr, epsilon_id, 0);
+    }
+    let access_shape_id = access_shape(expr, shape, &[]);
+    let epsilon_id = expr.add(Language::AccessBroadcast([epsilon_id, access_shape_id]));
+    let pair_id = access_pair(expr, variance_id, epsilon_id, shape.len());
+    let variance_id = compute(expr, ComputeType::ElementwiseAdd, pair_id);
+    let std_id = compute(expr, ComputeType::Sqrt, variance_id);
+    let std_id = broadcast_over_items(expr, std_id, shape, item_shape);
+
+    let pair_id = access_pair(expr, centered_id, std_id, shape.len());
+    compute(expr, ComputeType::ElementwiseDiv, pair_id)
+}
+
+/// Layer normalization of `data`, with shape `shape` and item shape
+/// `item_shape`, lowered to reductions and elementwise operations: each item
+/// is normalized by [`normalize`], then multiplied by `gamma` and offset by
+/// `beta`, which have the item shape of `data`.
+///
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::from_relay::layer_norm;
+/// use glenside::language::Language;
+/// use ordered_float::NotNan;
+///
+/// let mut expr = RecExpr::default();
+/// let data_id = expr.add(Language::Symbol("data".to_string()));
+/// let data_id = expr.add(Language::AccessTensor(data_id));
+/// let gamma_id = expr.add(Language::Symbol("gamma".to_string()));
+/// let gamma_id = expr.add(Language::AccessTensor(gamma_id));
+/// let beta_id = expr.add(Language::Symbol("beta".to_string()));
+/// let beta_id = expr.add(Language::AccessTensor(beta_id));
+/// let epsilon_id = expr.add(Language::NotNanFloat64(NotNan::new(1e-5).unwrap()));
+/// let epsilon_id = expr.add(Language::Literal(epsilon_id));
+/// layer_norm(&mut expr, data_id, &[2], &[3], gamma_id, beta_id, epsilon_id);
+/// assert!(expr.pretty(80).starts_with("(compute elementwise-add"));
+/// ```
+pub fn layer_norm(
+    expr: &mut RecExpr<Language>,
+    data_id: Id,
+    shape: &[usize],
+    item_shape: &[usize],
+    gamma_id: Id,
+    beta_id: Id,
+    epsilon_id: Id,
+) -> Id {
+    let normalized_id = normalize(expr, data_id, shape, item_shape, epsilon_id);
+
+    let full_shape = shape
+        .iter()
+        .chain(item_shape.iter())
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut out_id = normalized_id;
+    for (param_id, compute_type) in [
+        (gamma_id, ComputeType::ElementwiseMul),
+        (beta_id, ComputeType::ElementwiseAdd),
+    ]
+    .iter()
+    {
+        let mut param_id = *param_id;
+        for _ in 0..shape.len() {
+            param_id = access_insert_axis(expr, param_id, 0);
+        }
+        let access_shape_id = access_shape(expr, &full_shape, &[]);
+        let param_id = expr.add(Language::AccessBroadcast([param_id, access_shape_id]));
+        let pair_id = access_pair(expr, out_id, param_id, shape.len());
+        out_id = compute(expr, compute_type.clone(), pair_id);
+    }
+    out_id
+}
+
+/// Group normalization of `data`, with shape `data_shape` in layout NC...,
+/// lowered to reductions and elementwise operations: `data` is reshaped so
+/// that each of the `groups` groups of channels of each batch element is one
+/// item, normalized by [`normalize`], reshaped back, and then each channel is
+/// multiplied by its value in `gamma` and offset by its value in `beta`.
+///
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::from_relay::group_norm;
+/// use glenside::language::Language;
+/// use ordered_float::NotNan;
+///
+/// let mut expr = RecExpr::default();
+/// let data_id = expr.add(Language::Symbol("data".to_string()));
+/// let data_id = expr.add(Language::AccessTensor(data_id));
+/// let gamma_id = expr.add(Language::Symbol("gamma".to_string()));
+/// let gamma_id = expr.add(Language::AccessTensor(gamma_id));
+/// let beta_id = expr.add(Language::Symbol("beta".to_string()));
+/// let beta_id = expr.add(Language::AccessTensor(beta_id));
+/// let epsilon_id = expr.add(Language::NotNanFloat64(NotNan::new(1e-5).unwrap()));
+/// let epsilon_id = expr.add(Language::Literal(epsilon_id));
+/// group_norm(&mut expr, data_id, &[1, 4, 3, 3], gamma_id, beta_id, 2, epsilon_id);
+/// assert!(expr.pretty(80).starts_with("(compute elementwise-add"));
+/// ```
+pub fn group_norm(
+    expr: &mut RecExpr<Language>,
+    data_id: Id,
+    data_shape: &[usize],
+    gamma_id: Id,
+    beta_id: Id,
+    groups: usize,
+    epsilon_id: Id,
+) -> Id {
+    assert!(data_shape.len() >= 2);
+    assert_eq!(data_shape[1] % groups, 0);
+    let group_len = data_shape[1..].iter().product::<usize>() / groups;
+
+    let access_shape_id = access_shape(expr, &[data_shape[0], groups], &[group_len]);
+    let data_id = expr.add(Language::AccessReshape([data_id, access_shape_id]));
+    let normalized_id = normalize(
+        expr,
+        data_id,
+        &[data_shape[0], groups],
+        &[group_len],
+        epsilon_id,
+    );
+    let access_shape_id = access_shape(expr, data_shape, &[]);
+    let mut out_id = expr.add(Language::AccessReshape([normalized_id, access_shape_id]));
+
+    for (param_id, compute_type) in [
+        (gamma_id, ComputeType::ElementwiseMul),
+        (beta_id, ComputeType::ElementwiseAdd),
+    ]
+    .iter()
+    {
+        // Broadcast the per-channel parameter along every other axis, as in
+        // bias addition.
+        let mut param_id = access_insert_axis(expr, *param_id, 0);
+        for axis in 2..data_shape.len() {
+            param_id = access_insert_axis(expr, param_id, axis);
+        }
+        let access_shape_id = access_shape(expr, data_shape, &[]);
+        let param_id = expr.add(Language::AccessBroadcast([param_id, access_shape_id]));
+        let pair_id = access_pair(expr, out_id, param_id, data_shape.len());
+        out_id = compute(expr, compute_type.clone(), pair_id);
+    }
+    out_id
+}
+
 /// Create access shape literal
 ///
 /// ```
@@ -565,6 +1012,22 @@ pub fn access_insert_axis(expr: &mut RecExpr<Language>, id: Id, axis: usize) ->
     expr.add(Language::AccessInsertAxis([id, axis_id]))
 }
 
+/// Reverse an access along an axis
+///
+/// ```
+/// use std::str::FromStr;
+/// use glenside::language::from_relay::access_reverse;
+/// use egg::RecExpr;
+///
+/// let mut expr = RecExpr::from_str("(access-tensor a)").unwrap();
+/// let id = access_reverse(&mut expr, 1.into(), 2);
+/// assert_eq!(expr.pretty(80), "(access-reverse (access-tensor a) 2)");
+/// ```
+pub fn access_reverse(expr: &mut RecExpr<Language>, id: Id, axis: usize) -> Id {
+    let axis_id = expr.add(Language::Num(axis.try_into().unwrap()));
+    expr.add(Language::AccessReverse([id, axis_id]))
+}
+
 /// Given the input access and compute type, add compute expression
 ///
 /// ```
@@ -632,6 +1095,8 @@ pub fn dtype_from_type(t: tvm::ir::ty::Type) -> crate::language::DataType {
         crate::language::DataType::Int(32)
     } else if dtype == "uint8".parse().unwrap() {
         crate::language::DataType::Uint(8)
+    } else if dtype == "bool".parse().unwrap() {
+        crate::language::DataType::Bool
     } else {
         panic!("Unsupported data type: {:?}", dtype)
     }
@@ -650,8 +1115,9 @@ pub fn shape_from_type(t: tvm::ir::ty::Type) -> Vec<usize> {
         });
     assert!(
         tensor_type.dtype.clone() == "float32".parse().unwrap()
-            || tensor_type.dtype.clone() == "int32".parse().unwrap(),
-        "only supporting float32x1 and int32x1 at the moment"
+            || tensor_type.dtype.clone() == "int32".parse().unwrap()
+            || tensor_type.dtype.clone() == "bool".parse().unwrap(),
+        "only supporting float32x1, int32x1, and bool at the moment"
     );
     let mut shape = Vec::<usize>::default();
     for j in 0..tensor_type.shape.len() {
@@ -694,6 +1160,31 @@ pub fn from_relay(
     RecExpr<Language>,
     Vec<(String, Vec<usize>)>,
     Vec<(String, crate::language::DataType)>,
+) {
+    let (glenside_expr, names_and_shapes, names_to_dtype, _) = from_relay_with_provenance(
+        module,
+        simplify_batch_norm_for_inference_hack,
+        use_opaque_operators_for,
+    );
+    (glenside_expr, names_and_shapes, names_to_dtype)
+}
+
+/// Like [`from_relay`], but also returns the provenance of the Glenside nodes
+/// (see [`crate::language::provenance`]). Every node generated for a Relay
+/// operator call is labeled `<op name>#<n>`, where the call is the `n`th call
+/// to that operator (counting from 0) in the order calls are compiled, e.g.
+/// `nn.conv2d#3`. Identical nodes are only added once (see
+/// [`hash_cons`](crate::language::hash_cons)), so a node shared by several calls
+/// carries each call's label.
+pub fn from_relay_with_provenance(
+    module: &IRModule,
+    simplify_batch_norm_for_inference_hack: bool,
+    use_opaque_operators_for: &Vec<RelayOperator>,
+) -> (
+    RecExpr<Language>,
+    Vec<(String, Vec<usize>)>,
+    Vec<(String, crate::language::DataType)>,
+    Provenance,
 ) {
     let main = module
         .lookup(module.get_global_var("main").unwrap())
@@ -715,7 +1206,10 @@ pub fn from_relay(
     let mut visited = HashSet::new();
     create_worklist(func.body.clone(), &mut worklist, &mut visited);
     let mut map = HashMap::new();
+    let mut provenance = Provenance::default();
+    let mut op_counts: HashMap<String, usize> = HashMap::new();
     for expr in worklist {
+        let first_new_node = glenside_expr.as_ref().len();
         map.insert(
             expr.clone(),
             compile_expression(
@@ -730,9 +1224,35 @@ pub fn from_relay(
                 use_opaque_operators_for,
             ),
         );
+
+        let op_name = expr
+            .clone()
+            .downcast::<tvm::ir::relay::Call>()
+            .ok()
+            .and_then(|call| {
+                call.op
+                    .clone()
+                    .upcast::<tvm::ir::expr::BaseExpr>()
+                    .downcast::<tvm::ir::op::Op>()
+                    .ok()
+            })
+            .map(|op| op.name.as_str().unwrap().to_string());
+        if let Some(op_name) = op_name {
+            let count = op_counts.entry(op_name.clone()).or_insert(0);
+            let label = format!("{}#{}", op_name, count);
+            *count += 1;
+            for i in first_new_node..glenside_expr.as_ref().len() {
+                provenance.add(Id::from(i), label.clone());
+            }
+        }
     }
 
-    (glenside_expr, names_and_shapes, names_to_dtype)
+    // Operators compile independently, so e.g. every convolution adds its own
+    // copies of the same lists and shapes.
+    let (glenside_expr, ids) = hash_cons(&glenside_expr);
+    let provenance = provenance.remap(&ids);
+
+    (glenside_expr, names_and_shapes, names_to_dtype, provenance)
 }
 
 /// Generates an ordered list of Relay expressions to compile.
@@ -1070,13 +1590,21 @@ fn compile_expression(
                     assert_eq!(a_shape.len(), 3);
                     assert_eq!(b_shape.len(), 3);
 
-                    let relay_op_id = glenside_expr.add(Language::RelayOperator(
-                        crate::language::RelayOperator::RelayBatchMatmul,
-                    ));
+                    if use_opaque_operators_for
+                        .contains(&crate::language::RelayOperator::RelayBatchMatmul)
+                    {
+                        let relay_op_id = glenside_expr.add(Language::RelayOperator(
+                            crate::language::RelayOperator::RelayBatchMatmul,
+                        ));
 
-                    glenside_expr.add(Language::RelayOperatorCall(
-                        vec![relay_op_id, a_id, b_id].into_boxed_slice(),
-                    ))
+                        return glenside_expr.add(Language::RelayOperatorCall(
+                            vec![relay_op_id, a_id, b_id].into_boxed_slice(),
+                        ));
+                    }
+
+                    // Relay's second argument is [B, N, K].
+                    let b_id = access_transpose(glenside_expr, b_id, &[0, 2, 1]);
+                    glenside_expr.add(Language::BatchMatmul([a_id, b_id]))
                 }
                 "strided_slice" => {
                     let data_id = get_compiled_expression(call.args.get(0).unwrap());
@@ -1331,6 +1859,11 @@ fn compile_expression(
                         data_id,
                     )
                 }
+                "logical_not" => {
+                    assert_eq!(call.args.len(), 1);
+                    let data_id = get_compiled_expression(call.args.get(0).unwrap());
+                    compute(glenside_expr, ComputeType::LogicalNot, data_id)
+                }
                 "nn.max_pool2d" => {
                     assert_eq!(call.args.len(), 1);
                     let data_shape =
@@ -1748,7 +2281,8 @@ fn compile_expression(
 
                     compute(glenside_expr, ComputeType::DotProduct, data_id)
                 }
-                "add" | "multiply" | "divide" | "maximum" | "minimum" => {
+                "add" | "multiply" | "divide" | "maximum" | "minimum" | "logical_and"
+                | "logical_or" => {
                     assert_eq!(call.args.len(), 2);
                     let mut a_id = get_compiled_expression(call.args.get(0).unwrap());
                     let mut a_shape =
@@ -1876,6 +2410,8 @@ fn compile_expression(
                             compute(glenside_expr, ComputeType::ElementwiseMul, pair_id)
                         }
                         "divide" => compute(glenside_expr, ComputeType::ElementwiseDiv, pair_id),
+                        "logical_and" => compute(glenside_expr, ComputeType::LogicalAnd, pair_id),
+                        "logical_or" => compute(glenside_expr, ComputeType::LogicalOr, pair_id),
                         _ => unreachable!(),
                     }
                 }
@@ -2844,7 +3380,7 @@ fn compile_expression(
 mod tests {
     use crate::language::interpreter::interpret;
     use crate::language::{Language, MyAnalysis};
-    use approx::AbsDiffEq;
+    use crate::testing::{assert_close, Tolerance};
     use egg::{EGraph, Pattern, Searcher};
     use ndarray_npy::{read_npy, write_npy};
     use ndarray_rand::{rand_distr::Uniform, RandomExt};
@@ -2914,6 +3450,7 @@ mod tests {
                 let mut egraph = EGraph::new(MyAnalysis {
                     name_to_shape: env.clone(),
                     name_to_dtype: dtypes_vec.into_iter().collect(),
+                    shape_cache: None,
                 });
                 let id = egraph.add_expr(&expr);
                 egraph.rebuild();
@@ -2997,11 +3534,10 @@ mod tests {
                         crate::language::interpreter::Value::Access(a) => a.tensor,
                         _ => panic!(),
                     };
-                    assert!(
-                        relay_output.abs_diff_eq(&interpreter_output, $tol),
-                        "{:?}\nvs.\n{:?}",
-                        relay_output,
-                        interpreter_output
+                    assert_close(
+                        &interpreter_output,
+                        &relay_output,
+                        Tolerance::absolute($tol),
                     );
                 }
             }
@@ -3227,6 +3763,23 @@ def @main(%x: Tensor[(1, 3, 32, 32), float32]) -> Tensor[(1, 3, 1, 1), float32]
 "#
     );
 
+    test!(
+        batch_matmul,
+        1e-5,
+        r#"
+#[version = "0.0.5"]
+def @main(%x: Tensor[(2, 3, 4), float32], %y: Tensor[(2, 5, 4), float32]) -> Tensor[(2, 3, 5), float32] {
+  nn.batch_matmul(%x, %y) /* ty=Tensor[(2, 3, 5), float32] */
+}
+"#,
+        r#"
+(batch-matmul
+ (access-tensor x)
+ (access-transpose (access-tensor y) (list 0 2 1))
+)
+"#
+    );
+
     test!(
         batch_flatten,
         1e-60,
@@ -3561,4 +4114,32 @@ def @main(%x: Tensor[(1, 100, 1, 1), float32]) {
     // relay-activation-layout-nchw)
     //         "#
     //     );
+
+    #[test]
+    fn provenance() {
+        let relay = r#"
+#[version = "0.0.5"]
+def @main(%x: Tensor[(2, 2), float32], %y: Tensor[(2, 2), float32]) {
+    add(nn.relu(%x), nn.relu(%y))
+}
+"#;
+        let module = tvm::ir::module::IRModule::parse("", relay).unwrap();
+        let (expr, _, _, provenance) = super::from_relay_with_provenance(&module, false, &vec![]);
+
+        let root = egg::Id::from(expr.as_ref().len() - 1);
+        assert_eq!(
+            provenance.get(root),
+            vec!["add#0".to_string()].into_iter().collect()
+        );
+        let report = provenance.report(&expr);
+        assert!(report.contains("nn.relu#0"));
+        assert!(report.contains("nn.relu#1"));
+        // Variables aren't operator calls, so the nodes they compile to are
+        // unlabeled.
+        for (i, node) in expr.as_ref().iter().enumerate() {
+            if let Language::AccessTensor(_) = node {
+                assert!(provenance.get(egg::Id::from(i)).is_empty());
+            }
+        }
+    }
 }
@@ -0,0 +1,137 @@
+//! Hash-consed construction of expressions.
+//!
+//! Frontends build expressions node by node, and emit the same subtree over
+//! and over: every layer of a model adds its own `(list 0 1)`, its own
+//! `(shape 3 3)`, and its own copy of any constant reshape. A
+//! [`HashConsBuilder`] adds each distinct node once, returning the existing
+//! node's id when an equal node is added again, so the expression (and the
+//! egraph built from it) only holds each subtree once. [`hash_cons`] does the
+//! same to an already built expression.
+
+use super::Language;
+use egg::{Id, Language as LanguageTrait, RecExpr};
+use std::collections::HashMap;
+
+/// Builds a [`RecExpr`] in which no two nodes are equal.
+/// ```
+/// use glenside::language::hash_cons::HashConsBuilder;
+/// use glenside::language::Language;
+///
+/// let mut builder = HashConsBuilder::default();
+/// let a = builder.add(Language::Num(1));
+/// let b = builder.add(Language::Num(1));
+/// assert_eq!(a, b);
+/// builder.add(Language::List(vec![a, b].into_boxed_slice()));
+/// assert_eq!(builder.build().pretty(80), "(list 1 1)");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct HashConsBuilder {
+    expr: RecExpr<Language>,
+    ids: HashMap<Language, Id>,
+}
+
+impl HashConsBuilder {
+    /// Adds `node`, whose children must have been added already, unless an
+    /// equal node has been. Returns the id of the node in the expression.
+    pub fn add(&mut self, node: Language) -> Id {
+        if let Some(id) = self.ids.get(&node) {
+            return *id;
+        }
+        let id = self.expr.add(node.clone());
+        self.ids.insert(node, id);
+        id
+    }
+
+    /// Adds each node of `expr`, returning the id each node now has.
+    pub fn add_expr(&mut self, expr: &RecExpr<Language>) -> Vec<Id> {
+        let mut ids: Vec<Id> = Vec::with_capacity(expr.as_ref().len());
+        for node in expr.as_ref() {
+            let id = self.add(node.clone().map_children(|child| ids[usize::from(child)]));
+            ids.push(id);
+        }
+        ids
+    }
+
+    /// The node with id `id`.
+    pub fn get(&self, id: Id) -> &Language {
+        &self.expr.as_ref()[usize::from(id)]
+    }
+
+    /// The number of distinct nodes added.
+    pub fn len(&self) -> usize {
+        self.expr.as_ref().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The expression built, whose root is the last node added, unless it was
+    /// equal to a node added before.
+    pub fn build(self) -> RecExpr<Language> {
+        self.expr
+    }
+}
+
+/// Hash-conses `expr`, returning an equivalent expression in which no two
+/// nodes are equal, and the id each of `expr`'s nodes has in it. As usual, the
+/// new expression's root is its last node.
+pub fn hash_cons(expr: &RecExpr<Language>) -> (RecExpr<Language>, Vec<Id>) {
+    let mut builder = HashConsBuilder::default();
+    let mut ids = builder.add_expr(expr);
+    let mut new_expr = builder.build();
+    // The root can only have been merged into an earlier node if that node is
+    // unreachable from it, in which case the root is added again at the end.
+    if let Some(root) = ids.last_mut() {
+        if usize::from(*root) != new_expr.as_ref().len() - 1 {
+            let node = new_expr.as_ref()[usize::from(*root)].clone();
+            *root = new_expr.add(node);
+        }
+    }
+    (new_expr, ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicate_subtrees() {
+        let expr: RecExpr<Language> = "
+         (access-pair
+          (access-transpose (access (access-tensor a) 1) (list 1 0))
+          (access-transpose (access (access-tensor a) 1) (list 1 0))
+          0)"
+        .parse()
+        .unwrap();
+        let (new_expr, ids) = hash_cons(&expr);
+        // a, access-tensor, 1, access, 0, list, access-transpose, access-pair.
+        assert_eq!(new_expr.as_ref().len(), 8);
+        assert_eq!(new_expr.pretty(80), expr.pretty(80));
+        assert_eq!(ids.len(), expr.as_ref().len());
+        for (i, id) in ids.iter().enumerate() {
+            assert_eq!(
+                new_expr.as_ref()[usize::from(*id)]
+                    .clone()
+                    .map_children(|_| Id::from(0)),
+                expr.as_ref()[i].clone().map_children(|_| Id::from(0))
+            );
+        }
+    }
+
+    #[test]
+    fn unreachable_copy_of_root() {
+        let mut expr = RecExpr::default();
+        let a = expr.add(Language::Symbol("a".to_string()));
+        expr.add(Language::AccessTensor(a));
+        let b = expr.add(Language::Symbol("b".to_string()));
+        expr.add(Language::AccessTensor(b));
+        expr.add(Language::AccessTensor(a));
+
+        let (new_expr, ids) = hash_cons(&expr);
+        assert_eq!(ids[1], Id::from(1));
+        assert_eq!(ids[4], Id::from(4));
+        assert_eq!(new_expr.as_ref().len(), 5);
+        assert_eq!(new_expr.pretty(80), "(access-tensor a)");
+    }
+}
@@ -1,17 +1,25 @@
-use super::language::{ComputeType, Language, PadType};
-use egg::{Id, RecExpr};
+use super::access_shape::AccessShape;
+use super::bit_accurate::SystolicArrayArithmetic;
+use super::language::{
+    try_resolve_axis, ComputeType, Language, PadType, RoundingMode, WindowConvention,
+};
+use super::sparse::SparseMatrix;
+use crate::error::{bail, ensure, ensure_eq, ensure_ne, GlensideError, Result};
+use egg::{Id, Language as LanguageTrait, RecExpr};
 use itertools::Itertools;
-use ndarray::{s, Array, ArrayD, Dimension, IxDyn, Zip};
+use ndarray::{s, Array2, ArrayD, ArrayView1, ArrayViewD, CowArray, Dimension, Ix2, IxDyn};
 use num_traits::cast::AsPrimitive;
 use num_traits::Pow;
 use std::collections::hash_map::HashMap;
 use std::convert::TryInto;
-use std::iter::FromIterator;
 use std::ops::Div;
 use std::str::FromStr;
 
-pub enum Value<DataType> {
-    Tensor(ArrayD<DataType>),
+pub enum Value<'e, DataType> {
+    /// A tensor. The value of a symbol borrows the symbol's tensor from the
+    /// environment; it's only copied if an operator needs to own it (e.g.
+    /// `access-tensor`), and never if only its shape is needed.
+    Tensor(CowArray<'e, DataType, IxDyn>),
     Access(Access<DataType>),
     Num(usize),
     Int32(i32),
@@ -21,8 +29,44 @@ pub enum Value<DataType> {
     Shape(IxDyn),
     ComputeType(ComputeType),
     PadType(PadType),
+    WindowConvention(WindowConvention),
+    RoundingMode(RoundingMode),
     AccessShape(IxDyn, usize),
     List(Vec<usize>),
+    /// The values of each output of an `outputs` node, in order.
+    Outputs(Vec<Value<'e, DataType>>),
+    /// The values of a `construct-tuple` node, or the statistics computed by
+    /// [`ComputeType::ReduceMeanVar`].
+    Tuple(Vec<Value<'e, DataType>>),
+}
+
+impl<'e, DataType: Clone + 'static> Value<'e, DataType> {
+    /// Copies any tensors this value borrows from the environment, so that it
+    /// can outlive the environment.
+    pub fn into_owned(self) -> Value<'static, DataType> {
+        match self {
+            Value::Tensor(t) => Value::Tensor(CowArray::from(t.into_owned())),
+            Value::Access(a) => Value::Access(a),
+            Value::Num(n) => Value::Num(n),
+            Value::Int32(n) => Value::Int32(n),
+            Value::Int64(n) => Value::Int64(n),
+            Value::Int8(n) => Value::Int8(n),
+            Value::Uint8(n) => Value::Uint8(n),
+            Value::Shape(s) => Value::Shape(s),
+            Value::ComputeType(t) => Value::ComputeType(t),
+            Value::PadType(t) => Value::PadType(t),
+            Value::WindowConvention(c) => Value::WindowConvention(c),
+            Value::RoundingMode(m) => Value::RoundingMode(m),
+            Value::AccessShape(s, access_axis) => Value::AccessShape(s, access_axis),
+            Value::List(l) => Value::List(l),
+            Value::Outputs(values) => {
+                Value::Outputs(values.into_iter().map(Value::into_owned).collect())
+            }
+            Value::Tuple(values) => {
+                Value::Tuple(values.into_iter().map(Value::into_owned).collect())
+            }
+        }
+    }
 }
 
 pub struct Access<DataType> {
@@ -32,6 +76,498 @@ pub struct Access<DataType> {
 
 pub type Environment<'a, DataType> = HashMap<&'a str, ArrayD<DataType>>;
 
+/// An environment holding views of tensors owned elsewhere, e.g. of weights
+/// which are too large to copy into an [`Environment`].
+pub type ViewEnvironment<'a, DataType> = HashMap<&'a str, ArrayViewD<'a, DataType>>;
+
+/// Where the interpreter looks up the tensors named by symbols. Lookups
+/// return views, so the environment itself is never copied; a symbol's own
+/// tensor is only copied when an operator needs to own it (see
+/// [`Value::Tensor`]).
+pub trait Lookup<DataType> {
+    fn lookup(&self, name: &str) -> Option<ArrayViewD<'_, DataType>>;
+
+    /// The sparse form of the weight `name`, if it's sparse. See
+    /// [`SparseEnvironment`](super::sparse::SparseEnvironment).
+    fn lookup_sparse(&self, _name: &str) -> Option<&SparseMatrix<DataType>> {
+        None
+    }
+
+    /// The arithmetic to simulate systolic arrays with bit-accurately, if
+    /// any. See
+    /// [`BitAccurateEnvironment`](super::bit_accurate::BitAccurateEnvironment).
+    fn systolic_array_arithmetic(&self) -> Option<SystolicArrayArithmetic> {
+        None
+    }
+
+    /// The tensor `name`, unpacked, if it's stored packed. See
+    /// [`PackedEnvironment`](super::packed::PackedEnvironment).
+    fn unpack(&self, _name: &str) -> Option<ArrayD<DataType>> {
+        None
+    }
+}
+
+impl<'a, DataType> Lookup<DataType> for Environment<'a, DataType> {
+    fn lookup(&self, name: &str) -> Option<ArrayViewD<'_, DataType>> {
+        self.get(name).map(|tensor| tensor.view())
+    }
+}
+
+impl<'a, DataType> Lookup<DataType> for ViewEnvironment<'a, DataType> {
+    fn lookup(&self, name: &str) -> Option<ArrayViewD<'_, DataType>> {
+        self.get(name).map(|tensor| tensor.view())
+    }
+}
+
+/// Looks names up in each environment in turn, so that environments can be
+/// combined without copying any of them.
+struct Layered<'e, DataType>(Vec<&'e dyn Lookup<DataType>>);
+
+impl<'e, DataType> Lookup<DataType> for Layered<'e, DataType> {
+    fn lookup(&self, name: &str) -> Option<ArrayViewD<'_, DataType>> {
+        self.0.iter().find_map(|env| env.lookup(name))
+    }
+
+    fn lookup_sparse(&self, name: &str) -> Option<&SparseMatrix<DataType>> {
+        self.0.iter().find_map(|env| env.lookup_sparse(name))
+    }
+
+    fn systolic_array_arithmetic(&self) -> Option<SystolicArrayArithmetic> {
+        self.0
+            .iter()
+            .find_map(|env| env.systolic_array_arithmetic())
+    }
+
+    fn unpack(&self, name: &str) -> Option<ArrayD<DataType>> {
+        self.0.iter().find_map(|env| env.unpack(name))
+    }
+}
+
+/// The windows formed by `access-windows`, which can be consumed one at a time
+/// with [`LazyWindows::windows`], rather than materialized all at once. For a
+/// large activation, the materialized windows take up
+/// O(window count × window size) memory.
+///
+/// The interpreter uses this when a reduction (`reduce-sum`, `reduce-max`,
+/// `reduce-mean`, or a `dot-product` over an `access-cartesian-product` with
+/// the windows) consumes the windows directly, optionally through
+/// `access-squeeze`s, as in pooling and convolution.
+pub struct LazyWindows<DataType> {
+    /// The access being windowed.
+    access: Access<DataType>,
+    filters_shape: IxDyn,
+    stride_shape: IxDyn,
+    /// The shape of the materialized windows, up to the access axis (that is,
+    /// the shape of the access being windowed, up to its access axis,
+    /// followed by the number of windows along each of its item axes), minus
+    /// any squeezed axes.
+    shape: Vec<usize>,
+}
+
+impl<DataType: Copy> LazyWindows<DataType> {
+    /// Fails if the windows don't fit in `access`'s items (see
+    /// [`access_windows_resulting_shape`](super::access_windows_resulting_shape)).
+    pub fn new(
+        access: Access<DataType>,
+        filters_shape: IxDyn,
+        stride_shape: IxDyn,
+    ) -> Result<Self> {
+        super::try_access_windows_resulting_shape(
+            &IxDyn(&access.tensor.shape()[access.access_axis..]),
+            &filters_shape,
+            &stride_shape,
+        )
+        .map_err(GlensideError::Interpretation)?;
+
+        let shape = Self::unsqueezed_shape(&access, &filters_shape, &stride_shape);
+        Ok(LazyWindows {
+            access,
+            filters_shape,
+            stride_shape,
+            shape,
+        })
+    }
+
+    fn unsqueezed_shape(
+        access: &Access<DataType>,
+        filters_shape: &IxDyn,
+        stride_shape: &IxDyn,
+    ) -> Vec<usize> {
+        AccessShape::of_tensor(access.tensor.shape(), access.access_axis)
+            .windows(filters_shape, stride_shape)
+            .shape
+            .slice()
+            .to_vec()
+    }
+
+    /// The shape of the materialized windows, up to the access axis.
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    /// The shape of each window.
+    pub fn window_shape(&self) -> &[usize] {
+        self.filters_shape.slice()
+    }
+
+    /// Removes `axis`, which must be of length 1 and come before the access
+    /// axis, as `access-squeeze` does.
+    pub fn squeeze(&mut self, axis: usize) -> Result<()> {
+        ensure!(GlensideError::Interpretation, axis < self.shape.len());
+        ensure_eq!(
+            GlensideError::Interpretation,
+            self.shape[axis],
+            1,
+            "Cannot squeeze an axis which is not equal to 1"
+        );
+        self.shape.remove(axis);
+        Ok(())
+    }
+
+    /// Iterates over the windows, in the (row-major) order they appear in the
+    /// materialized windows.
+    pub fn windows(&self) -> impl Iterator<Item = ndarray::ArrayViewD<'_, DataType>> + '_ {
+        let access_axis = self.access.access_axis;
+        ndarray::indices(Self::unsqueezed_shape(
+            &self.access,
+            &self.filters_shape,
+            &self.stride_shape,
+        ))
+        .into_iter()
+        .map(move |index| {
+            self.access.tensor.slice(
+                ndarray::SliceInfo::<_, IxDyn>::new(
+                    (0..access_axis)
+                        .map(|axis| ndarray::SliceOrIndex::from(index[axis]))
+                        .chain(
+                            self.filters_shape
+                                .slice()
+                                .iter()
+                                .zip(self.stride_shape.slice().iter())
+                                .enumerate()
+                                .map(|(i, (&filter, &stride))| {
+                                    let start = index[access_axis + i] * stride;
+                                    ndarray::SliceOrIndex::from(start..start + filter)
+                                }),
+                        )
+                        .collect::<Vec<_>>(),
+                )
+                .unwrap()
+                .as_ref(),
+            )
+        })
+    }
+
+    /// Reduces each window to a single value with `f`, producing an access of
+    /// shape [`LazyWindows::shape`], accessed at its last axis.
+    pub fn reduce(
+        &self,
+        f: impl FnMut(ndarray::ArrayViewD<DataType>) -> DataType,
+    ) -> Access<DataType> {
+        Access {
+            tensor: ArrayD::from_shape_vec(self.shape.clone(), self.windows().map(f).collect())
+                .unwrap(),
+            access_axis: self.shape.len(),
+        }
+    }
+
+    /// Materializes the windows, producing the access `access-windows` (and
+    /// any `access-squeeze`s) would.
+    pub fn materialize(&self) -> Access<DataType> {
+        Access {
+            tensor: ArrayD::from_shape_vec(
+                self.shape
+                    .iter()
+                    .chain(self.filters_shape.slice().iter())
+                    .cloned()
+                    .collect::<Vec<_>>(),
+                self.windows()
+                    .flat_map(|window| window.iter().cloned().collect::<Vec<_>>())
+                    .collect(),
+            )
+            .unwrap(),
+            access_axis: self.shape.len(),
+        }
+    }
+}
+
+/// Gets an interpreted axis argument as an axis into something with `ndim`
+/// axes, resolving negative axes (which are interpreted as [`Value::Int64`]s).
+fn get_axis<DataType>(value: Value<DataType>, ndim: usize) -> Result<usize> {
+    match value {
+        Value::Num(u) => Ok(u),
+        Value::Int64(i) => try_resolve_axis(i, ndim).map_err(GlensideError::Interpretation),
+        _ => bail!(GlensideError::Interpretation, "Expected an axis"),
+    }
+}
+
+/// Copies `tensor` into standard (row-major) layout, unless it's already in
+/// standard layout. Tensors produced by e.g. `access-transpose` are views onto
+/// their original data, and so generally aren't.
+fn into_standard_layout<DataType: Clone>(tensor: ArrayD<DataType>) -> ArrayD<DataType> {
+    if tensor.is_standard_layout() {
+        tensor
+    } else {
+        tensor.as_standard_layout().into_owned()
+    }
+}
+
+/// Pads `tensor` along `axis` with `pad_before` elements before and
+/// `pad_after` elements after, picking the elements as `pad_type` says.
+fn pad<DataType: GlensideScalar>(
+    tensor: &ArrayD<DataType>,
+    pad_type: PadType,
+    axis: usize,
+    pad_before: usize,
+    pad_after: usize,
+) -> ArrayD<DataType> {
+    let value = match pad_type {
+        PadType::ZeroPadding => DataType::zero(),
+        PadType::MinPadding => DataType::min_value(),
+    };
+    let mut before_shape = tensor.shape().to_vec();
+    before_shape[axis] = pad_before;
+    let mut after_shape = tensor.shape().to_vec();
+    after_shape[axis] = pad_after;
+
+    ndarray::stack(
+        ndarray::Axis(axis),
+        &[
+            ArrayD::from_elem(before_shape, value).view(),
+            tensor.view(),
+            ArrayD::from_elem(after_shape, value).view(),
+        ],
+    )
+    .unwrap()
+}
+
+/// Reshapes `tensor` to `shape`, reading its elements in row-major order.
+/// [`ndarray::ArrayBase::into_shape`] fails on tensors which aren't
+/// contiguous, and reads column-major tensors in column-major order, so this
+/// should be used for all reshaping in the interpreter.
+fn reshape<DataType: Clone>(tensor: ArrayD<DataType>, shape: &[usize]) -> ArrayD<DataType> {
+    let from = tensor.shape().to_vec();
+    into_standard_layout(tensor)
+        .into_shape(shape)
+        .unwrap_or_else(|e| panic!("Cannot reshape {:?} to {:?}: {}", from, shape, e))
+}
+
+/// Reshapes `tensor` to a `rows` by `cols` matrix, as by [`reshape`].
+fn to_matrix<DataType: Clone>(
+    tensor: ArrayD<DataType>,
+    rows: usize,
+    cols: usize,
+) -> Array2<DataType> {
+    reshape(tensor, &[rows, cols])
+        .into_dimensionality::<Ix2>()
+        .unwrap()
+}
+
+/// The mean and (biased) variance of `values`, computed in one pass by
+/// Welford's algorithm. Updating the mean and the sum of squared deviations
+/// from it with each value avoids the cancellation in computing
+/// E[x^2] - E[x]^2.
+fn mean_and_variance<DataType: 'static>(values: ArrayView1<DataType>) -> (DataType, DataType)
+where
+    DataType: GlensideScalar,
+{
+    let (mut mean, mut squared_deviations) = (DataType::zero(), DataType::zero());
+    for (i, x) in values.iter().enumerate() {
+        let delta = *x + -mean;
+        mean = mean + delta / DataType::from_count(i + 1);
+        squared_deviations = squared_deviations + delta * (*x + -mean);
+    }
+    (
+        mean,
+        squared_deviations / DataType::from_count(values.len()),
+    )
+}
+
+/// Normalizes each row of `rows` to zero mean and unit variance, adding
+/// `epsilon` to the variance, as layer and group normalization do.
+fn normalize_rows<DataType: 'static>(
+    mut rows: Array2<DataType>,
+    epsilon: DataType,
+) -> Array2<DataType>
+where
+    DataType: GlensideScalar,
+{
+    for mut row in rows.outer_iter_mut() {
+        let (mean, variance) = mean_and_variance(row.view());
+        let std = (variance + epsilon).sqrt();
+        row.mapv_inplace(|v| (v + -mean) / std);
+    }
+    rows
+}
+
+/// The sparse weight accessed by `id`, if `id` is
+/// `(access (access-tensor <weight>) 1)` and `<weight>` is sparse in `env`.
+fn sparse_weight<'e, DataType, E: Lookup<DataType> + ?Sized>(
+    expr: &RecExpr<Language>,
+    id: Id,
+    env: &'e E,
+) -> Option<&'e SparseMatrix<DataType>> {
+    match &expr.as_ref()[usize::from(id)] {
+        &Language::Access([tensor_id, axis_id]) => match (
+            &expr.as_ref()[usize::from(tensor_id)],
+            &expr.as_ref()[usize::from(axis_id)],
+        ) {
+            (&Language::AccessTensor(symbol_id), Language::Num(1)) => {
+                match &expr.as_ref()[usize::from(symbol_id)] {
+                    Language::Symbol(name) => env.lookup_sparse(name),
+                    _ => None,
+                }
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The dot products of each item of `a0` with each row of `weight`, i.e. the
+/// value of a `dot-product` over the cartesian product of `a0` and `weight`.
+fn sparse_dot_product<DataType>(
+    a0: Access<DataType>,
+    weight: &SparseMatrix<DataType>,
+) -> Result<Access<DataType>>
+where
+    DataType: Copy + num_traits::Zero + PartialEq + std::ops::Mul<Output = DataType>,
+{
+    let a0_shape = a0.tensor.shape()[..a0.access_axis].to_vec();
+    let item_len = a0.tensor.shape()[a0.access_axis..].iter().product();
+    ensure_eq!(
+        GlensideError::Interpretation,
+        item_len,
+        weight.shape[1],
+        "Expected item shapes to match"
+    );
+    let product = weight.dot_rows(to_matrix(a0.tensor, a0_shape.iter().product(), item_len).view());
+    let shape = a0_shape
+        .into_iter()
+        .chain(std::iter::once(weight.shape[0]))
+        .collect::<Vec<_>>();
+    Ok(Access {
+        access_axis: shape.len(),
+        tensor: reshape(product.into_dyn(), &shape),
+    })
+}
+
+/// Whether [`fast_matmul`] can multiply matrices of `DataType`. With the
+/// `fast-matmul` feature, products of `f32` and `f64` matrices are computed by
+/// ndarray's optimized routines (or by BLAS, with the `blas` feature) rather
+/// than one element at a time, which makes interpreting full networks
+/// practical.
+fn has_fast_matmul<DataType: 'static>() -> bool {
+    use std::any::TypeId;
+    cfg!(feature = "fast-matmul")
+        && (TypeId::of::<DataType>() == TypeId::of::<f32>()
+            || TypeId::of::<DataType>() == TypeId::of::<f64>())
+}
+
+/// Multiplies `a` (of shape `[m, k]`) by `b` (of shape `[k, n]`). Should only
+/// be called when [`has_fast_matmul`] holds.
+#[cfg(feature = "fast-matmul")]
+fn fast_matmul<DataType: 'static>(a: Array2<DataType>, b: Array2<DataType>) -> Array2<DataType> {
+    use std::any::Any;
+    fn dot<T: ndarray::LinalgScalar, DataType: 'static>(
+        a: &dyn Any,
+        b: &dyn Any,
+    ) -> Option<Array2<DataType>> {
+        let product: Box<dyn Any> = Box::new(
+            a.downcast_ref::<Array2<T>>()?
+                .dot(b.downcast_ref::<Array2<T>>()?),
+        );
+        product.downcast::<Array2<DataType>>().ok().map(|p| *p)
+    }
+    dot::<f32, DataType>(&a, &b)
+        .or_else(|| dot::<f64, DataType>(&a, &b))
+        .expect("Fast matrix multiplication is only supported for f32 and f64")
+}
+
+#[cfg(not(feature = "fast-matmul"))]
+fn fast_matmul<DataType>(_a: Array2<DataType>, _b: Array2<DataType>) -> Array2<DataType> {
+    unreachable!("Fast matrix multiplication requires the fast-matmul feature")
+}
+
+/// Applies an activation function (see [`ComputeType::is_activation`])
+/// elementwise.
+fn activation<DataType>(
+    compute_type: &ComputeType,
+    tensor: &ArrayD<DataType>,
+) -> Result<ArrayD<DataType>>
+where
+    DataType: Copy
+        + std::ops::Add<Output = DataType>
+        + std::ops::Div<Output = DataType>
+        + std::ops::Neg<Output = DataType>
+        + num_traits::identities::One
+        + num_traits::identities::Zero
+        + std::cmp::PartialOrd
+        + Exp,
+{
+    Ok(match compute_type {
+        ComputeType::Sigmoid => {
+            tensor.mapv(|v| DataType::one() / (DataType::one() + v.neg().exp()))
+        }
+        // tanh(x) = (1 - e^-2x) / (1 + e^-2x), computed on |x| so that the
+        // exponential can't overflow.
+        ComputeType::Tanh => tensor.mapv(|v| {
+            let abs = if v < DataType::zero() { v.neg() } else { v };
+            let e = (abs + abs).neg().exp();
+            let tanh = (DataType::one() + e.neg()) / (DataType::one() + e);
+            if v < DataType::zero() {
+                tanh.neg()
+            } else {
+                tanh
+            }
+        }),
+        ComputeType::ReLU => tensor.mapv(|v| {
+            if v >= DataType::zero() {
+                v
+            } else {
+                DataType::zero()
+            }
+        }),
+        _ => bail!(
+            GlensideError::Interpretation,
+            "{} is not an activation function",
+            compute_type
+        ),
+    })
+}
+
+/// Applies softmax to each item of `access`, which must be a vector.
+fn softmax<DataType>(access: &Access<DataType>) -> Result<ArrayD<DataType>>
+where
+    DataType: Copy + std::ops::Div<Output = DataType> + num_traits::identities::Zero + Exp,
+{
+    ensure_eq!(
+        GlensideError::Interpretation,
+        access.access_axis,
+        access.tensor.ndim() - 1,
+        "Softmax over any axis other than the last is not implemented"
+    );
+
+    let shape = access.tensor.shape();
+    let mut exps = ndarray::Zip::from(&access.tensor).apply_collect(|v| v.exp());
+    let denominators = exps
+        .sum_axis(ndarray::Axis(access.tensor.ndim() - 1))
+        .insert_axis(ndarray::Axis(access.tensor.ndim() - 1));
+    ndarray::Zip::from(&mut exps)
+        .and(&denominators.broadcast(shape).unwrap())
+        .apply(|v, denom| *v = *v / *denom);
+    Ok(exps)
+}
+
+/// The mask value representing `b`: `1` if true, `0` if false.
+fn mask<DataType: num_traits::identities::Zero + num_traits::identities::One>(b: bool) -> DataType {
+    if b {
+        DataType::one()
+    } else {
+        DataType::zero()
+    }
+}
+
 /// Simple wrapper over [`interpret`].
 ///
 /// This was created for the web demo. Specifically, this lets us avoid having
@@ -41,11 +577,12 @@ pub type Environment<'a, DataType> = HashMap<&'a str, ArrayD<DataType>>;
 ///
 /// ```
 /// use glenside::language::interpreter::interpret_from_str;
-/// use glenside::language::interpreter::Value;
+/// use glenside::language::interpreter::{Environment, Value};
 /// use std::collections::HashMap;
 /// use ndarray::Dimension;
 ///
-/// match interpret_from_str::<i64>("(access-shape (shape 1 2) (shape 3 4))", &HashMap::default()) {
+/// let env: Environment<i64> = HashMap::default();
+/// match interpret_from_str::<i64>("(access-shape (shape 1 2) (shape 3 4))", &env) {
 ///     Value::AccessShape(shape, access_axis) => {
 ///         assert_eq!(shape.slice(), &[1, 2, 3, 4]);
 ///         assert_eq!(access_axis, 2);
@@ -53,51 +590,199 @@ pub type Environment<'a, DataType> = HashMap<&'a str, ArrayD<DataType>>;
 ///     _ => panic!(),
 /// }
 /// ```
-pub fn interpret_from_str<DataType: 'static>(
+pub fn interpret_from_str<'e, DataType: 'static>(
     program: &str,
-    env: &Environment<DataType>,
-) -> Value<DataType>
+    env: &'e (impl Lookup<DataType> + ?Sized),
+) -> Value<'e, DataType>
 where
-    DataType: Copy
-        + std::ops::Mul<Output = DataType>
-        + std::ops::Div<Output = DataType>
-        + std::ops::Neg<Output = DataType>
-        + std::iter::Sum
-        + num_traits::identities::One
-        + num_traits::identities::Zero
-        + std::cmp::PartialOrd
-        + num_traits::Bounded
-        + Exp
-        + Sqrt
-        + FromNotNanFloat64Literal
-        + ndarray::ScalarOperand,
-    usize: num_traits::cast::AsPrimitive<DataType>,
+    DataType: GlensideScalar,
 {
     let expr = RecExpr::<Language>::from_str(program).unwrap();
 
     interpret(&expr, expr.as_ref().len() - 1, env)
 }
 
+/// Interprets `expr` once for each of the environments in `inputs` (e.g. for
+/// each image in a batch), returning the value of node `index` in each.
+///
+/// `weights` holds the tensors shared by all of the environments, e.g. a
+/// model's weights. Subexpressions which depend only on `weights`, such as
+/// transposed or padded weights, are evaluated once and reused across the
+/// batch, rather than being evaluated for every environment. No name may
+/// appear in both `weights` and an environment in `inputs`.
+///
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::interpreter::{interpret_batch, Value};
+/// use glenside::language::Language;
+/// use ndarray::array;
+/// use std::collections::HashMap;
+///
+/// let expr: RecExpr<Language> = "
+///     (compute dot-product
+///      (access-cartesian-product
+///       (access (access-tensor x) 1)
+///       (access (access-transpose (access (access-tensor w) 1) (list 1 0)) 1)))"
+///     .parse()
+///     .unwrap();
+///
+/// let mut weights = HashMap::default();
+/// weights.insert("w", array![[1., 2.], [3., 4.]].into_dyn());
+/// let inputs = vec![
+///     vec![("x", array![[1., 0.]].into_dyn())].into_iter().collect(),
+///     vec![("x", array![[0., 1.]].into_dyn())].into_iter().collect(),
+/// ];
+///
+/// let values = interpret_batch(&expr, expr.as_ref().len() - 1, &weights, &inputs);
+/// match (&values[0], &values[1]) {
+///     (Value::Access(a0), Value::Access(a1)) => {
+///         assert_eq!(a0.tensor, array![[1., 2.]].into_dyn());
+///         assert_eq!(a1.tensor, array![[3., 4.]].into_dyn());
+///     }
+///     _ => panic!(),
+/// }
+/// ```
+pub fn interpret_batch<'a, DataType: 'static>(
+    expr: &RecExpr<Language>,
+    index: usize,
+    weights: &Environment<'a, DataType>,
+    inputs: &[Environment<'a, DataType>],
+) -> Vec<Value<'static, DataType>>
+where
+    DataType: GlensideScalar,
+{
+    let (expr, memoized_tensors) = memoize_weight_subexpressions(expr, index, weights);
+    let memoized: ViewEnvironment<DataType> = memoized_tensors
+        .iter()
+        .map(|(name, tensor)| (name.as_str(), tensor.view()))
+        .collect();
+
+    inputs
+        .iter()
+        .map(|input| {
+            for name in input.keys() {
+                assert!(
+                    !weights.contains_key(name),
+                    "{} is both a weight and an input",
+                    name
+                );
+            }
+            // Neither the weights nor the inputs are copied.
+            let env = Layered(vec![input as &dyn Lookup<DataType>, &memoized, weights]);
+            interpret(&expr, expr.as_ref().len() - 1, &env).into_owned()
+        })
+        .collect()
+}
+
+/// Replaces each of the largest subexpressions of the expression rooted at
+/// `index` which depend only on `weights` (and which evaluate to tensors or
+/// accesses) with a new symbol. Returns the new expression, rooted at its last
+/// node, and the values of the new symbols.
+fn memoize_weight_subexpressions<DataType: 'static>(
+    expr: &RecExpr<Language>,
+    index: usize,
+    weights: &Environment<DataType>,
+) -> (RecExpr<Language>, Vec<(String, ArrayD<DataType>)>)
+where
+    DataType: GlensideScalar,
+{
+    let nodes = expr.as_ref();
+
+    // Whether each node depends on anything other than the weights.
+    let mut depends_on_inputs = Vec::with_capacity(index + 1);
+    for node in &nodes[..=index] {
+        let depends = match node {
+            Language::Symbol(name) => !weights.contains_key(name.as_str()),
+            _ => node
+                .children()
+                .iter()
+                .any(|id| depends_on_inputs[usize::from(*id)]),
+        };
+        depends_on_inputs.push(depends);
+    }
+
+    // Find the nodes to keep, evaluating the largest weight-only
+    // subexpressions along the way. Leaves are cheap, and are left alone.
+    let mut keep = vec![false; index + 1];
+    let mut values = HashMap::new();
+    let mut to_visit = vec![index];
+    while let Some(i) = to_visit.pop() {
+        if keep[i] {
+            continue;
+        }
+        keep[i] = true;
+        if !depends_on_inputs[i] && !nodes[i].is_leaf() {
+            match interpret(expr, i, weights) {
+                value @ Value::Access(_) | value @ Value::Tensor(_) => {
+                    values.insert(i, value);
+                    continue;
+                }
+                _ => (),
+            }
+        }
+        to_visit.extend(nodes[i].children().iter().map(|id| usize::from(*id)));
+    }
+
+    let mut new_expr = RecExpr::default();
+    let mut new_ids = HashMap::new();
+    let mut memoized = Vec::default();
+    for i in (0..=index).filter(|i| keep[*i]) {
+        let new_id = match values.remove(&i) {
+            Some(value) => {
+                let name = format!("__memoized_{}", i);
+                let symbol_id = new_expr.add(Language::Symbol(name.clone()));
+                match value {
+                    Value::Tensor(tensor) => {
+                        memoized.push((name, tensor.into_owned()));
+                        symbol_id
+                    }
+                    Value::Access(access) => {
+                        memoized.push((name, access.tensor));
+                        let access_tensor_id = new_expr.add(Language::AccessTensor(symbol_id));
+                        let axis_id = new_expr.add(Language::Num(access.access_axis as i64));
+                        new_expr.add(Language::Access([access_tensor_id, axis_id]))
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            None => new_expr.add(
+                nodes[i]
+                    .clone()
+                    .map_children(|id| new_ids[&usize::from(id)]),
+            ),
+        };
+        new_ids.insert(i, new_id);
+    }
+
+    (new_expr, memoized)
+}
+
 // TODO(@gussmith23) Interpreter stack overflows on large programs
 // If I want to interpret something like a full resnet, then I will have to
 // figure out a way around the stack overflows.
 /// Interpret a Glenside expression
 ///
+/// `env` can be anything which implements [`Lookup`], e.g. an
+/// [`Environment`] or a [`ViewEnvironment`]. The result borrows the tensors of
+/// any symbols it's made of from `env` (see [`Value::Tensor`]);
+/// [`Value::into_owned`] copies them.
+///
 /// Generally, `DataType` can be inferred from the environment passed in. If
 /// your expression doesn't actually use any tensor values, and the environment
-/// is empty, you can choose an arbitrary type: e.g. `interpret::<i64>(...)`:
+/// is empty, you can choose an arbitrary type: e.g. `interpret::<i64>(...)`.
+/// An empty environment still needs its own type spelled out, as
+/// `HashMap::default()` could be either kind of environment:
 ///
 /// ```
 /// use egg::RecExpr;
 /// use glenside::language::Language;
 /// use glenside::language::interpreter::interpret;
-/// use glenside::language::interpreter::Value;
+/// use glenside::language::interpreter::{Environment, Value};
 /// use std::str::FromStr;
-/// use std::collections::HashMap;
 /// use ndarray::Dimension;
 ///
 /// let expr = RecExpr::<Language>::from_str("(access-shape (shape 1 2) (shape 3 4))").unwrap();
-/// match interpret::<i64>(&expr, expr.as_ref().len() - 1, &HashMap::default()) {
+/// match interpret::<i64>(&expr, expr.as_ref().len() - 1, &Environment::new()) {
 ///     Value::AccessShape(shape, access_axis) => {
 ///         assert_eq!(shape.slice(), &[1, 2, 3, 4]);
 ///         assert_eq!(access_axis, 2);
@@ -105,102 +790,390 @@ where
 ///     _ => panic!(),
 /// }
 /// ```
-pub fn interpret<DataType: 'static>(
+pub fn interpret<'e, DataType: 'static>(
     expr: &RecExpr<Language>,
     index: usize,
-    env: &Environment<DataType>,
-) -> Value<DataType>
+    env: &'e (impl Lookup<DataType> + ?Sized),
+) -> Value<'e, DataType>
 where
-    DataType: Copy
-        + std::ops::Mul<Output = DataType>
-        + std::ops::Div<Output = DataType>
-        + std::ops::Neg<Output = DataType>
-        + std::iter::Sum
-        + num_traits::identities::One
-        + num_traits::identities::Zero
-        + std::cmp::PartialOrd
-        + num_traits::Bounded
-        + Exp
-        + Sqrt
-        + FromNotNanFloat64Literal
-        + ndarray::ScalarOperand,
-    usize: num_traits::cast::AsPrimitive<DataType>,
+    DataType: GlensideScalar,
 {
-    match &expr.as_ref()[index] {
-        &Language::GetAccessShape(_) => todo!(),
-        &Language::SystolicArrayConv2dIm2colNchwOihwWithBlocking(_) => todo!(),
-        &Language::SystolicArrayConv2dIm2colNhwcHwioWithBlocking(_) => todo!(),
-        &Language::SystolicArrayConv2dNchwOihwWithBlocking(_) => todo!(),
-        &Language::SystolicArrayConv2dNhwcHwioWithBlocking(_) => todo!(),
-        &Language::RelayOperatorCall(_) => todo!(),
-        &Language::RelayOperator(_) => todo!(),
-        &Language::DataType(_) => todo!(),
-        &Language::RelayActivationLayout(_) => todo!(),
-        &Language::RelayKernelLayout(_) => todo!(),
-        &Language::ConstructTuple(_) => todo!(),
-        &Language::TupleGetItem(_) => todo!(),
-        &Language::AcceleratorCall(_) => todo!(),
-        &Language::AcceleratorFunc(_) => todo!(),
-        &Language::ConstantTensor(_) => todo!(),
-        &Language::AccessReshape([data_id, shape_id]) => {
-            let mut a = match interpret(expr, data_id.into(), env) {
-                Value::Access(a) => a,
-                _ => panic!(),
-            };
-            let (s, access_dim) = match interpret(expr, shape_id.into(), env) {
-                Value::AccessShape(s, access_dim) => (s, access_dim),
-                _ => panic!(),
-            };
+    try_interpret(expr, index, env).unwrap_or_else(|e| panic!("{}", e))
+}
 
-            a.tensor = a.tensor.into_shape(s).unwrap();
-            a.access_axis = access_dim;
+/// Like [`interpret`], but returns an
+/// [`Interpretation`](GlensideError::Interpretation) error, rather than
+/// panicking, if the program can't be evaluated (e.g. because a symbol isn't
+/// in `env`, or a node's arguments have the wrong kinds or shapes).
+/// ```
+/// use glenside::error::GlensideError;
+/// use glenside::language::interpreter::{try_interpret, Environment};
+/// use glenside::language::parse;
+///
+/// let expr = parse("(access (access-tensor missing) 0)").unwrap();
+/// let env: Environment<f64> = Environment::default();
+/// assert!(matches!(
+///     try_interpret(&expr, expr.as_ref().len() - 1, &env),
+///     Err(GlensideError::Interpretation(_))
+/// ));
+/// ```
+pub fn try_interpret<'e, DataType: 'static>(
+    expr: &RecExpr<Language>,
+    index: usize,
+    env: &'e (impl Lookup<DataType> + ?Sized),
+) -> Result<Value<'e, DataType>>
+where
+    DataType: GlensideScalar,
+{
+    interpret_with_checks(expr, index, env, None)
+}
 
-            Value::Access(a)
-        }
-        &Language::AccessShape([shape_id, item_shape_id]) => {
-            let shape = match interpret(expr, shape_id.into(), env) {
-                Value::Shape(s) => s,
-                _ => panic!(),
-            };
-            let item_shape = match interpret(expr, item_shape_id.into(), env) {
-                Value::Shape(s) => s,
-                _ => panic!(),
-            };
-            Value::AccessShape(
-                IxDyn(
-                    shape
-                        .slice()
-                        .iter()
-                        .chain(item_shape.slice().iter())
-                        .cloned()
-                        .collect::<Vec<_>>()
-                        .as_slice(),
-                ),
-                shape.ndim(),
-            )
-        }
-        &Language::AccessSlice([access_id, axis_id, low_id, high_id]) => {
-            let mut access = match interpret(expr, access_id.into(), env) {
-                Value::Access(a) => a,
-                _ => panic!(),
-            };
-            let axis = match interpret(expr, axis_id.into(), env) {
-                Value::Num(u) => u,
-                _ => panic!(),
-            };
-            let low = match interpret(expr, low_id.into(), env) {
-                Value::Num(u) => u,
-                _ => panic!(),
-            };
-            let high = match interpret(expr, high_id.into(), env) {
-                Value::Num(u) => u,
-                _ => panic!(),
-            };
+/// The access pattern shapes the analysis predicts for the nodes of the
+/// expression being interpreted by [`interpret_checked`].
+struct AccessChecks {
+    /// The (shape, item shape) of each node of access pattern type.
+    predicted: Vec<Option<(IxDyn, IxDyn)>>,
+}
 
-            let mut slice_info: Vec<ndarray::SliceOrIndex> =
-                std::iter::repeat(ndarray::SliceOrIndex::from(..))
-                    .take(access.tensor.ndim())
-                    .collect();
+/// Interprets node `index` of `expr`, checking each access produced along the
+/// way against `checks`, if given.
+fn interpret_with_checks<'e, DataType: 'static, E: Lookup<DataType> + ?Sized>(
+    expr: &RecExpr<Language>,
+    index: usize,
+    env: &'e E,
+    checks: Option<&AccessChecks>,
+) -> Result<Value<'e, DataType>>
+where
+    DataType: GlensideScalar,
+{
+    let value = interpret_node(expr, index, env, checks)?;
+    if let Some(checks) = checks {
+        check_access(expr, index, &value, checks);
+    }
+    Ok(value)
+}
+
+/// Like [`interpret`], but checks the access axis of every access the
+/// interpreter produces along the way: that it's at most the access's number
+/// of dimensions, and that it splits the access's tensor into the shape and
+/// item shape the analysis predicts for the node. Panics at the first node
+/// which fails a check, naming it. Access axis bookkeeping bugs otherwise
+/// often only show up as wrong results much further up the expression.
+///
+/// The shapes of the tensors in `env` are taken as the shapes of the
+/// expression's symbols.
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::interpreter::{interpret_checked, Environment, Value};
+/// use glenside::language::Language;
+/// use ndarray::array;
+///
+/// let expr: RecExpr<Language> = "(access-windows (access (access-tensor t) 1) (shape 2) (shape 1))"
+///     .parse()
+///     .unwrap();
+/// let mut env = Environment::new();
+/// env.insert("t", array![[1., 2., 3.], [4., 5., 6.]].into_dyn());
+/// match interpret_checked(&expr, expr.as_ref().len() - 1, &env) {
+///     Value::Access(a) => assert_eq!(a.tensor.shape(), &[2, 2, 2]),
+///     _ => panic!(),
+/// }
+/// ```
+pub fn interpret_checked<'e, DataType: 'static>(
+    expr: &RecExpr<Language>,
+    index: usize,
+    env: &'e (impl Lookup<DataType> + ?Sized),
+) -> Value<'e, DataType>
+where
+    DataType: GlensideScalar,
+{
+    let mut analysis = super::MyAnalysis::default();
+    for node in expr.as_ref() {
+        if let Language::Symbol(name) = node {
+            if let Some(tensor) = env.lookup(name) {
+                analysis
+                    .name_to_shape
+                    .insert(name.clone(), tensor.shape().to_vec());
+            }
+        }
+    }
+    let mut egraph = egg::EGraph::new(analysis);
+    let mut ids: Vec<Id> = Vec::with_capacity(expr.as_ref().len());
+    for node in expr.as_ref() {
+        let id = egraph.add(node.clone().map_children(|child| ids[usize::from(child)]));
+        ids.push(id);
+    }
+    let predicted = ids
+        .iter()
+        .map(|id| match &egraph[*id].data {
+            super::MyAnalysisData::AccessPattern(a) => {
+                Some((a.shape.clone(), a.item_shape.clone()))
+            }
+            _ => None,
+        })
+        .collect();
+
+    interpret_with_checks(expr, index, env, Some(&AccessChecks { predicted }))
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Checks `value`, the value of node `index` of `expr`, against `checks`, if
+/// it's an access.
+fn check_access<DataType>(
+    expr: &RecExpr<Language>,
+    index: usize,
+    value: &Value<DataType>,
+    checks: &AccessChecks,
+) {
+    let access = match value {
+        Value::Access(access) => access,
+        _ => return,
+    };
+    let shape = access.tensor.shape();
+    assert!(
+        access.access_axis <= shape.len(),
+        "Node {} ({}) has access axis {}, but only {} dimensions",
+        index,
+        expr.as_ref()[index],
+        access.access_axis,
+        shape.len()
+    );
+    if let Some((predicted_shape, predicted_item_shape)) = &checks.predicted[index] {
+        assert_eq!(
+            (&shape[..access.access_axis], &shape[access.access_axis..]),
+            (predicted_shape.slice(), predicted_item_shape.slice()),
+            "Node {} ({}) doesn't have the access pattern shape the analysis predicts",
+            index,
+            expr.as_ref()[index]
+        );
+    }
+}
+
+/// Interprets node `index` of `expr`, interpreting its children with
+/// [`interpret_with_checks`].
+fn interpret_node<'e, DataType: 'static, E: Lookup<DataType> + ?Sized>(
+    expr: &RecExpr<Language>,
+    index: usize,
+    env: &'e E,
+    checks: Option<&AccessChecks>,
+) -> Result<Value<'e, DataType>>
+where
+    DataType: GlensideScalar,
+{
+    let node = &expr.as_ref()[index];
+    Ok(match node {
+        &Language::GetAccessShape([access_id]) => {
+            match interpret_with_checks(expr, access_id.into(), env, checks)? {
+                Value::Access(a) => Value::AccessShape(IxDyn(a.tensor.shape()), a.access_axis),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Expected the argument of get-access-shape to be an access"
+                ),
+            }
+        }
+        &Language::SystolicArrayConv2dIm2colNchwOihwWithBlocking(_) => bail!(
+            GlensideError::Interpretation,
+            "Interpreting {} is not supported",
+            node
+        ),
+        &Language::SystolicArrayConv2dIm2colNhwcHwioWithBlocking(_) => bail!(
+            GlensideError::Interpretation,
+            "Interpreting {} is not supported",
+            node
+        ),
+        &Language::SystolicArrayConv2dNchwOihwWithBlocking(_) => bail!(
+            GlensideError::Interpretation,
+            "Interpreting {} is not supported",
+            node
+        ),
+        &Language::SystolicArrayConv2dNhwcHwioWithBlocking(_) => bail!(
+            GlensideError::Interpretation,
+            "Interpreting {} is not supported",
+            node
+        ),
+        &Language::RelayOperatorCall(_) => bail!(
+            GlensideError::Interpretation,
+            "Interpreting {} is not supported",
+            node
+        ),
+        &Language::RelayOperator(_) => bail!(
+            GlensideError::Interpretation,
+            "Interpreting {} is not supported",
+            node
+        ),
+        &Language::DataType(_) => bail!(
+            GlensideError::Interpretation,
+            "Interpreting {} is not supported",
+            node
+        ),
+        &Language::RelayActivationLayout(_) => bail!(
+            GlensideError::Interpretation,
+            "Interpreting {} is not supported",
+            node
+        ),
+        &Language::RelayKernelLayout(_) => bail!(
+            GlensideError::Interpretation,
+            "Interpreting {} is not supported",
+            node
+        ),
+        Language::ConstructTuple(ids) => Value::Tuple(
+            ids.iter()
+                .map(|id| interpret_with_checks(expr, (*id).into(), env, checks))
+                .collect::<Result<Vec<_>>>()?,
+        ),
+        Language::Outputs(ids) => Value::Outputs(
+            ids.iter()
+                .map(|id| interpret_with_checks(expr, (*id).into(), env, checks))
+                .collect::<Result<Vec<_>>>()?,
+        ),
+        &Language::TupleGetItem([tuple_id, index_id]) => {
+            let mut values = match interpret_with_checks(expr, tuple_id.into(), env, checks)? {
+                Value::Tuple(values) => values,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Expected the first argument of tuple-get-item to be a tuple"
+                ),
+            };
+            let index = match interpret_with_checks(expr, index_id.into(), env, checks)? {
+                Value::Num(u) => u,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+            ensure!(
+                GlensideError::Interpretation,
+                index < values.len(),
+                "Tuple index {} out of range",
+                index
+            );
+            values.swap_remove(index)
+        }
+        &Language::AcceleratorCall(_) => bail!(
+            GlensideError::Interpretation,
+            "Interpreting {} is not supported",
+            node
+        ),
+        &Language::AcceleratorFunc(_) => bail!(
+            GlensideError::Interpretation,
+            "Interpreting {} is not supported",
+            node
+        ),
+        &Language::ConstantTensor(_) => bail!(
+            GlensideError::Interpretation,
+            "Interpreting {} is not supported",
+            node
+        ),
+        &Language::AccessReshape([data_id, shape_id]) => {
+            let mut a = match interpret_with_checks(expr, data_id.into(), env, checks)? {
+                Value::Access(a) => a,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+            let (s, access_dim) = match interpret_with_checks(expr, shape_id.into(), env, checks)? {
+                Value::AccessShape(s, access_dim) => (s, access_dim),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+            // The shape and the item shape are reshaped separately: items
+            // can't move across the access axis.
+            let old_shape = a.tensor.shape();
+            ensure!(GlensideError::Interpretation,
+                old_shape[..a.access_axis].iter().product::<usize>()
+                    == s.slice()[..access_dim].iter().product::<usize>()
+                    && old_shape[a.access_axis..].iter().product::<usize>()
+                        == s.slice()[access_dim..].iter().product::<usize>(),
+                "Cannot reshape access of shape {:?} and item shape {:?} to shape {:?} and item shape {:?}",
+                &old_shape[..a.access_axis],
+                &old_shape[a.access_axis..],
+                &s.slice()[..access_dim],
+                &s.slice()[access_dim..]
+            );
+
+            a.tensor = reshape(a.tensor, s.slice());
+            a.access_axis = access_dim;
+
+            Value::Access(a)
+        }
+        &Language::AccessShape([shape_id, item_shape_id]) => {
+            let shape = match interpret_with_checks(expr, shape_id.into(), env, checks)? {
+                Value::Shape(s) => s,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+            let item_shape = match interpret_with_checks(expr, item_shape_id.into(), env, checks)? {
+                Value::Shape(s) => s,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+            Value::AccessShape(
+                IxDyn(
+                    shape
+                        .slice()
+                        .iter()
+                        .chain(item_shape.slice().iter())
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .as_slice(),
+                ),
+                shape.ndim(),
+            )
+        }
+        &Language::AccessSlice([access_id, axis_id, low_id, high_id]) => {
+            let mut access = match interpret_with_checks(expr, access_id.into(), env, checks)? {
+                Value::Access(a) => a,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+            let axis = get_axis(
+                interpret_with_checks(expr, axis_id.into(), env, checks)?,
+                access.tensor.ndim(),
+            )?;
+            let low = match interpret_with_checks(expr, low_id.into(), env, checks)? {
+                Value::Num(u) => u,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+            let high = match interpret_with_checks(expr, high_id.into(), env, checks)? {
+                Value::Num(u) => u,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+            ensure!(
+                GlensideError::Interpretation,
+                low <= high && high <= access.tensor.shape()[axis],
+                "Slice {}..{} out of bounds of axis {} of shape {:?}",
+                low,
+                high,
+                axis,
+                access.tensor.shape()
+            );
+
+            let mut slice_info: Vec<ndarray::SliceOrIndex> =
+                std::iter::repeat(ndarray::SliceOrIndex::from(..))
+                    .take(access.tensor.ndim())
+                    .collect();
             slice_info[axis] = ndarray::SliceOrIndex::from(low..high);
             let slice_info = ndarray::SliceInfo::new(slice_info).unwrap();
             access.tensor = access
@@ -212,71 +1185,115 @@ where
             Value::Access(access)
         }
         &Language::AccessConcatenate([a_id, b_id, axis_id]) => {
-            let a = match interpret(expr, a_id.into(), env) {
+            let a = match interpret_with_checks(expr, a_id.into(), env, checks)? {
                 Value::Access(a) => a,
-                _ => panic!(),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
             };
-            let b = match interpret(expr, b_id.into(), env) {
+            let b = match interpret_with_checks(expr, b_id.into(), env, checks)? {
                 Value::Access(a) => a,
-                _ => panic!(),
-            };
-            let axis = match interpret(expr, axis_id.into(), env) {
-                Value::Num(u) => u,
-                _ => panic!(),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
             };
-
-            assert_eq!(a.access_axis, b.access_axis);
+            let axis = get_axis(
+                interpret_with_checks(expr, axis_id.into(), env, checks)?,
+                a.tensor.ndim(),
+            )?;
+
+            ensure_eq!(GlensideError::Interpretation, a.access_axis, b.access_axis);
+            ensure!(
+                GlensideError::Interpretation,
+                axis < a.tensor.ndim()
+                    && a.tensor.ndim() == b.tensor.ndim()
+                    && (0..a.tensor.ndim())
+                        .all(|i| i == axis || a.tensor.shape()[i] == b.tensor.shape()[i]),
+                "Can't concatenate accesses of shapes {:?} and {:?} along axis {}",
+                a.tensor.shape(),
+                b.tensor.shape(),
+                axis
+            );
 
             Value::Access(Access {
                 tensor: ndarray::stack![ndarray::Axis(axis), a.tensor, b.tensor].into_dyn(),
                 access_axis: a.access_axis,
             })
         }
-        &Language::AccessLiteral(id) => match interpret(expr, id.into(), env) {
-            Value::Tensor(t) => Value::Access(Access {
-                tensor: t,
-                access_axis: 0,
-            }),
-            _ => panic!(),
-        },
-        &Language::Literal(id) => match interpret(expr, id.into(), env) {
+        &Language::AccessLiteral(id) => {
+            match interpret_with_checks(expr, id.into(), env, checks)? {
+                Value::Tensor(t) => Value::Access(Access {
+                    tensor: t.into_owned(),
+                    access_axis: 0,
+                }),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            }
+        }
+        &Language::Literal(id) => match interpret_with_checks(expr, id.into(), env, checks)? {
             t @ Value::Tensor(_) => t,
-            _ => panic!(),
+            _ => bail!(
+                GlensideError::Interpretation,
+                "Unexpected argument to {}",
+                node
+            ),
         },
-        &Language::NotNanFloat64(v) => Value::Tensor(
+        &Language::NotNanFloat64(v) => Value::Tensor(CowArray::from(
             ndarray::arr0(DataType::from_not_nan_float_64_literal(v.into())).into_dyn(),
-        ),
+        )),
         &Language::AccessFlatten(access_id) => {
-            let mut access = match interpret(expr, access_id.into(), env) {
+            let mut access = match interpret_with_checks(expr, access_id.into(), env, checks)? {
                 Value::Access(a) => a,
-                _ => panic!(),
-            };
-
-            let shape = if access.access_axis == 0 || access.access_axis >= access.tensor.ndim() {
-                vec![access.tensor.shape().iter().product::<usize>()]
-            } else {
-                vec![
-                    access.tensor.shape()[..access.access_axis]
-                        .iter()
-                        .product::<usize>(),
-                    access.tensor.shape()[access.access_axis..]
-                        .iter()
-                        .product::<usize>(),
-                ]
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
             };
 
-            access.tensor = access.tensor.into_shape(shape).unwrap().into_dyn();
+            let shape = AccessShape::of_tensor(access.tensor.shape(), access.access_axis).flatten();
+            access.tensor = reshape(access.tensor, &shape.as_vec());
+            access.access_axis = shape.access_axis();
 
             Value::Access(access)
         }
         &Language::AccessTranspose([access_id, list_id]) => {
-            let mut access = match interpret(expr, access_id.into(), env) {
+            let mut access = match interpret_with_checks(expr, access_id.into(), env, checks)? {
                 Value::Access(a) => a,
-                _ => panic!(),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
             };
-            let list = match interpret(expr, list_id.into(), env) {
-                Value::List(l) => l,
-                _ => panic!(),
+            let ndim = access.tensor.ndim();
+            let list = match &expr.as_ref()[usize::from(list_id)] {
+                // Lists may contain negative axes, which we can only resolve
+                // here, once we know the rank of the access.
+                Language::List(list) => list
+                    .iter()
+                    .map(|id| -> Result<usize> {
+                        get_axis(
+                            interpret_with_checks(expr, (*id).into(), env, checks)?,
+                            ndim,
+                        )
+                    })
+                    .collect::<Result<Vec<_>>>()?,
+                _ => match interpret_with_checks(expr, list_id.into(), env, checks)? {
+                    Value::List(l) => l,
+                    _ => bail!(
+                        GlensideError::Interpretation,
+                        "Unexpected argument to {}",
+                        node
+                    ),
+                },
             };
 
             access.tensor = access.tensor.permuted_axes(list);
@@ -284,44 +1301,102 @@ where
         }
         Language::List(list) => Value::List(
             list.iter()
-                .map(|id: &Id| match interpret(expr, (*id).into(), env) {
-                    Value::Num(u) => u,
-                    _ => panic!(),
+                .map(|id: &Id| -> Result<usize> {
+                    match interpret_with_checks(expr, (*id).into(), env, checks)? {
+                        Value::Num(u) => Ok(u),
+                        _ => bail!(
+                            GlensideError::Interpretation,
+                            "Unexpected argument to {}",
+                            node
+                        ),
+                    }
                 })
-                .collect::<Vec<_>>(),
+                .collect::<Result<Vec<_>>>()?,
         ),
         &Language::AccessBroadcast([access_id, shape_id]) => {
-            let mut access = match interpret(expr, access_id.into(), env) {
+            let mut access = match interpret_with_checks(expr, access_id.into(), env, checks)? {
                 Value::Access(a) => a,
-                _ => panic!(),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
             };
-            let shape = match interpret(expr, shape_id.into(), env) {
+            let shape = match interpret_with_checks(expr, shape_id.into(), env, checks)? {
                 Value::AccessShape(s, _) => s,
-                _ => panic!("Expected access shape as second argument to access-broadcast"),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Expected access shape as second argument to access-broadcast"
+                ),
             };
 
-            assert_eq!(access.tensor.ndim(), shape.ndim());
+            ensure_eq!(
+                GlensideError::Interpretation,
+                access.tensor.ndim(),
+                shape.ndim()
+            );
             for (broadcast_from_dim, broadcast_to_dim) in
                 access.tensor.shape().iter().zip(shape.slice().iter())
             {
-                assert!(*broadcast_from_dim == 1 || broadcast_from_dim == broadcast_to_dim);
+                ensure!(
+                    GlensideError::Interpretation,
+                    *broadcast_from_dim == 1 || broadcast_from_dim == broadcast_to_dim
+                );
             }
 
             access.tensor = access.tensor.broadcast(shape).unwrap().to_owned();
 
             Value::Access(access)
         }
+        &Language::AccessReverse([access_id, axis_id]) => {
+            let mut access = match interpret_with_checks(expr, access_id.into(), env, checks)? {
+                Value::Access(a) => a,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+            let axis = match interpret_with_checks(expr, axis_id.into(), env, checks)? {
+                Value::Num(u) => u,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+            ensure!(GlensideError::Interpretation, axis < access.tensor.ndim());
+
+            // Copy into a new array, rather than just negating the axis'
+            // stride, so that the result is in standard layout.
+            let reversed = access
+                .tensor
+                .slice_axis(ndarray::Axis(axis), ndarray::Slice::new(0, None, -1));
+            access.tensor =
+                ArrayD::from_shape_vec(reversed.shape(), reversed.iter().cloned().collect())
+                    .unwrap();
+
+            Value::Access(access)
+        }
         &Language::AccessInsertAxis([access_id, axis_id]) => {
-            let mut access = match interpret(expr, access_id.into(), env) {
+            let mut access = match interpret_with_checks(expr, access_id.into(), env, checks)? {
                 Value::Access(a) => a,
-                _ => panic!(),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
             };
-            let axis = match interpret(expr, axis_id.into(), env) {
+            let axis = match interpret_with_checks(expr, axis_id.into(), env, checks)? {
                 Value::Num(u) => u,
-                _ => panic!(),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
             };
 
-            assert!(axis <= access.tensor.ndim());
+            ensure!(GlensideError::Interpretation, axis <= access.tensor.ndim());
 
             access.tensor = access.tensor.insert_axis(ndarray::Axis(axis));
             if axis <= access.access_axis {
@@ -332,18 +1407,27 @@ where
         }
         &Language::AccessPair([a0_id, a1_id]) => {
             let (a0, a1) = match (
-                interpret(expr, a0_id.into(), env),
-                interpret(expr, a1_id.into(), env),
+                interpret_with_checks(expr, a0_id.into(), env, checks)?,
+                interpret_with_checks(expr, a1_id.into(), env, checks)?,
             ) {
                 (Value::Access(a0), Value::Access(a1)) => (a0, a1),
-                _ => panic!("Expected both arguments to access-pair to be accesses"),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Expected both arguments to access-pair to be accesses"
+                ),
             };
 
-            assert_eq!(a0.tensor.shape(), a1.tensor.shape());
+            ensure_eq!(
+                GlensideError::Interpretation,
+                a0.tensor.shape(),
+                a1.tensor.shape()
+            );
             // TODO(@gussmith23) Trying out some new syntax...
             let access_axis = {
-                assert_eq!(
-                    a0.access_axis, a1.access_axis,
+                ensure_eq!(
+                    GlensideError::Interpretation,
+                    a0.access_axis,
+                    a1.access_axis,
                     "Expected access axes to match in access-pair"
                 );
                 a0.access_axis
@@ -364,16 +1448,21 @@ where
             })
         }
         &Language::AccessSqueeze([access_id, axis_id]) => {
-            let mut access = match interpret(expr, access_id.into(), env) {
+            let mut access = match interpret_with_checks(expr, access_id.into(), env, checks)? {
                 Value::Access(a) => a,
-                _ => panic!(),
-            };
-            let axis = match interpret(expr, axis_id.into(), env) {
-                Value::Num(u) => u,
-                _ => panic!(),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
             };
+            let axis = get_axis(
+                interpret_with_checks(expr, axis_id.into(), env, checks)?,
+                access.tensor.ndim(),
+            )?;
 
-            assert_eq!(
+            ensure_eq!(
+                GlensideError::Interpretation,
                 access.tensor.shape()[axis],
                 1,
                 "Cannot squeeze an axis which is not equal to 1"
@@ -387,130 +1476,465 @@ where
             Value::Access(access)
         }
         Language::PadType(t) => Value::PadType(*t),
+        Language::WindowConvention(c) => Value::WindowConvention(*c),
+        Language::RoundingMode(m) => Value::RoundingMode(*m),
         &Language::AccessPad([access_id, pad_type_id, axis_id, pad_before_id, pad_after_id]) => {
-            let access = match interpret(expr, access_id.into(), env) {
+            let access = match interpret_with_checks(expr, access_id.into(), env, checks)? {
                 Value::Access(a) => a,
-                _ => panic!(),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
             };
-            let pad_type = match interpret(expr, pad_type_id.into(), env) {
+            let pad_type = match interpret_with_checks(expr, pad_type_id.into(), env, checks)? {
                 Value::PadType(t) => t,
-                _ => panic!(),
-            };
-            let axis = match interpret(expr, axis_id.into(), env) {
-                Value::Num(u) => u,
-                _ => panic!(),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
             };
-            let pad_before = match interpret(expr, pad_before_id.into(), env) {
+            let axis = get_axis(
+                interpret_with_checks(expr, axis_id.into(), env, checks)?,
+                access.tensor.ndim(),
+            )?;
+            let pad_before = match interpret_with_checks(expr, pad_before_id.into(), env, checks)? {
                 Value::Num(u) => u,
-                _ => panic!(),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
             };
-            let pad_after = match interpret(expr, pad_after_id.into(), env) {
+            let pad_after = match interpret_with_checks(expr, pad_after_id.into(), env, checks)? {
                 Value::Num(u) => u,
-                _ => panic!(),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
             };
 
-            let mut before_shape = access.tensor.shape().to_vec();
-            before_shape[axis] = pad_before;
-            let mut after_shape = access.tensor.shape().to_vec();
-            after_shape[axis] = pad_after;
-
             Value::Access(Access {
-                tensor: ndarray::stack(
-                    ndarray::Axis(axis),
-                    &[
-                        // TODO(@gussmith) What's going on here...
-                        ndarray::ArrayD::from_elem(
-                            before_shape,
-                            match &pad_type {
-                                PadType::ZeroPadding => DataType::zero(),
-                                PadType::MinPadding => DataType::min_value(),
-                            },
-                        )
-                        .to_owned()
-                        .view(),
-                        access.tensor.clone().view(),
-                        ndarray::ArrayD::from_elem(
-                            after_shape,
-                            match &pad_type {
-                                PadType::ZeroPadding => DataType::zero(),
-                                PadType::MinPadding => DataType::min_value(),
-                            },
-                        )
-                        .to_owned()
-                        .view(),
-                    ],
-                )
-                .unwrap(),
+                tensor: pad(&access.tensor, pad_type, axis, pad_before, pad_after),
                 access_axis: access.access_axis,
             })
         }
         Language::ComputeType(t) => Value::ComputeType(t.clone()),
-        &Language::Compute([compute_type_id, access_id]) => {
-            let compute_type = match interpret(expr, compute_type_id.into(), env) {
-                Value::ComputeType(t) => t,
-                _ => panic!(),
+        &Language::Cast([dtype_id, access_id]) => {
+            let dtype = match &expr.as_ref()[usize::from(dtype_id)] {
+                Language::DataType(dtype) => *dtype,
+                _ => bail!(GlensideError::Interpretation, "Expected a DataType"),
             };
-            let access = match interpret(expr, access_id.into(), env) {
+            let mut access = match interpret_with_checks(expr, access_id.into(), env, checks)? {
                 Value::Access(a) => a,
-                _ => panic!(),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+            access.tensor.mapv_inplace(|v| v.cast(dtype));
+            Value::Access(access)
+        }
+        &Language::ComputeWithAccumulator([compute_type_id, dtype_id, access_id]) => {
+            let compute_type =
+                match interpret_with_checks(expr, compute_type_id.into(), env, checks)? {
+                    Value::ComputeType(t) => t,
+                    _ => bail!(
+                        GlensideError::Interpretation,
+                        "Unexpected argument to {}",
+                        node
+                    ),
+                };
+            let dtype = match &expr.as_ref()[usize::from(dtype_id)] {
+                Language::DataType(dtype) => *dtype,
+                _ => bail!(GlensideError::Interpretation, "Expected a DataType"),
+            };
+            let access = match interpret_with_checks(expr, access_id.into(), env, checks)? {
+                Value::Access(a) => a,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
             };
 
-            match compute_type {
-                ComputeType::ReduceMean => Value::Access(Access {
-                    tensor: access
-                        .tensor
-                        .clone()
-                        .into_shape(
-                            access.tensor.shape()[..access.access_axis]
-                                .iter()
-                                .cloned()
-                                .chain(std::iter::once(
-                                    access.tensor.shape()[access.access_axis..]
-                                        .iter()
-                                        .cloned()
-                                        .product(),
-                                ))
-                                .collect::<Vec<_>>()
-                                .as_slice(),
-                        )
-                        .unwrap()
-                        .sum_axis(ndarray::Axis(access.access_axis))
-                        .div(
-                            access.tensor.shape()[access.access_axis..]
-                                .iter()
-                                .product::<usize>()
-                                .as_(),
-                        ),
-                    access_axis: access.access_axis,
-                }),
-                ComputeType::Softmax => {
-                    assert_eq!(
-                        access.access_axis,
-                        access.tensor.ndim() - 1,
-                        "Softmax over any axis other than the last is not implemented",
-                    );
+            let outer_shape = access.tensor.shape()[..access.access_axis].to_vec();
+            let item_shape = access.tensor.shape()[access.access_axis..].to_vec();
+            let item_len: usize = item_shape.iter().product();
+            // Each item is reduced as `tuple_len` vectors of length `vec_len`:
+            // a reduce-sum sums a single vector, while a dot-product sums the
+            // products of corresponding elements of each vector.
+            let (tuple_len, vec_len) = match compute_type {
+                ComputeType::ReduceSum => (1, item_len),
+                ComputeType::DotProduct => (item_shape[0], item_len / item_shape[0]),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Accumulator dtypes are only supported for dot-product and reduce-sum"
+                ),
+            };
 
-                    let shape = access.tensor.shape();
-                    let mut exps = ndarray::Zip::from(&access.tensor).apply_collect(|v| v.exp());
-                    let denominators = exps
-                        .sum_axis(ndarray::Axis(access.tensor.ndim() - 1))
-                        .insert_axis(ndarray::Axis(access.tensor.ndim() - 1));
-                    ndarray::Zip::from(&mut exps)
-                        .and(&denominators.broadcast(shape).unwrap())
-                        .apply(|v, denom| *v = *v / *denom);
+            let values = access.tensor.iter().cloned().collect::<Vec<_>>();
+            let results = (0..outer_shape.iter().product::<usize>())
+                .map(|i| {
+                    let item = &values[i * item_len..(i + 1) * item_len];
+                    (0..vec_len).fold(DataType::zero(), |acc, j| {
+                        let term = (0..tuple_len).fold(DataType::one(), |product, k| {
+                            (product * item[k * vec_len + j]).cast(dtype)
+                        });
+                        (acc + term).cast(dtype)
+                    })
+                })
+                .collect::<Vec<_>>();
 
-                    Value::Access(Access {
-                        access_axis: access.access_axis,
-                        tensor: exps,
+            Value::Access(Access {
+                access_axis: outer_shape.len(),
+                tensor: ArrayD::from_shape_vec(outer_shape, results).unwrap(),
+            })
+        }
+        &Language::Requantize(
+            [dtype_id, access_id, input_scale_id, input_zero_point_id, output_scale_id, output_zero_point_id, rounding_id],
+        ) => {
+            // Scales and zero points are read straight from the expression, as
+            // the scales can't be represented in integer DataTypes.
+            let get_scale = |id: Id| -> Result<f64> {
+                match &expr.as_ref()[usize::from(id)] {
+                    &Language::NotNanFloat64(v) => Ok(v.into_inner()),
+                    &Language::Num(n) => Ok(n as f64),
+                    _ => bail!(GlensideError::Interpretation, "Expected a scale"),
+                }
+            };
+            let get_zero_point = |id: Id| -> Result<i64> {
+                match &expr.as_ref()[usize::from(id)] {
+                    &Language::Num(n) => Ok(n),
+                    _ => bail!(GlensideError::Interpretation, "Expected a zero point"),
+                }
+            };
+            let (min, max) = match &expr.as_ref()[usize::from(dtype_id)] {
+                Language::DataType(crate::language::DataType::Int(bits)) if *bits <= 32 => {
+                    (-(1i64 << (bits - 1)), (1i64 << (bits - 1)) - 1)
+                }
+                Language::DataType(crate::language::DataType::Uint(bits)) if *bits <= 32 => {
+                    (0, (1i64 << bits) - 1)
+                }
+                other => bail!(
+                    GlensideError::Interpretation,
+                    "Unsupported requantize output type {:?}",
+                    other
+                ),
+            };
+            let rounding = match interpret_with_checks(expr, rounding_id.into(), env, checks)? {
+                Value::RoundingMode(m) => m,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+            let (multiplier, shift) =
+                quantize_multiplier(get_scale(input_scale_id)? / get_scale(output_scale_id)?);
+            let input_zero_point = get_zero_point(input_zero_point_id)?;
+            let output_zero_point = get_zero_point(output_zero_point_id)?;
+
+            let mut access = match interpret_with_checks(expr, access_id.into(), env, checks)? {
+                Value::Access(a) => a,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+            access.tensor.mapv_inplace(|v| {
+                let input = (v.to_quantized() - input_zero_point) as i32;
+                let output = multiply_by_quantized_multiplier(input, multiplier, shift, rounding)
+                    as i64
+                    + output_zero_point;
+                DataType::from_quantized(output.max(min).min(max))
+            });
+            Value::Access(access)
+        }
+        &Language::Compute([compute_type_id, access_id]) => {
+            let compute_type =
+                match interpret_with_checks(expr, compute_type_id.into(), env, checks)? {
+                    Value::ComputeType(t) => t,
+                    _ => bail!(
+                        GlensideError::Interpretation,
+                        "Unexpected argument to {}",
+                        node
+                    ),
+                };
+
+            // Reductions directly over windows consume them one at a time.
+            let lazy_windows = |id: Id| -> Result<Option<LazyWindows<DataType>>> {
+                let mut squeezes = Vec::default();
+                let mut id = id;
+                loop {
+                    match &expr.as_ref()[usize::from(id)] {
+                        &Language::AccessSqueeze([access_id, axis_id]) => {
+                            squeezes.push(axis_id);
+                            id = access_id;
+                        }
+                        &Language::AccessWindows(
+                            [access_id, filters_shape_id, stride_shape_id],
+                        ) => {
+                            let access =
+                                match interpret_with_checks(expr, access_id.into(), env, checks)? {
+                                    Value::Access(a) => a,
+                                    _ => bail!(
+                                        GlensideError::Interpretation,
+                                        "Unexpected argument to {}",
+                                        node
+                                    ),
+                                };
+                            let filters_shape = match interpret_with_checks(
+                                expr,
+                                filters_shape_id.into(),
+                                env,
+                                checks,
+                            )? {
+                                Value::Shape(s) => s,
+                                _ => bail!(
+                                    GlensideError::Interpretation,
+                                    "Unexpected argument to {}",
+                                    node
+                                ),
+                            };
+                            let stride_shape = match interpret_with_checks(
+                                expr,
+                                stride_shape_id.into(),
+                                env,
+                                checks,
+                            )? {
+                                Value::Shape(s) => s,
+                                _ => bail!(
+                                    GlensideError::Interpretation,
+                                    "Unexpected argument to {}",
+                                    node
+                                ),
+                            };
+                            let mut windows =
+                                LazyWindows::new(access, filters_shape, stride_shape)?;
+                            for axis_id in squeezes.into_iter().rev() {
+                                let ndim = windows.shape().len() + windows.window_shape().len();
+                                let axis = get_axis(
+                                    interpret_with_checks(expr, axis_id.into(), env, checks)?,
+                                    ndim,
+                                )?;
+                                // Squeezing a window axis would change the
+                                // windows themselves.
+                                if axis >= windows.shape().len() {
+                                    return Ok(None);
+                                }
+                                windows.squeeze(axis)?;
+                            }
+                            return Ok(Some(windows));
+                        }
+                        _ => return Ok(None),
+                    }
+                }
+            };
+            let lazy_result = match compute_type {
+                ComputeType::ReduceSum | ComputeType::ReduceMax | ComputeType::ReduceMean => {
+                    lazy_windows(access_id)?.map(|windows| {
+                        windows.reduce(|window| {
+                            let len = window.len();
+                            match compute_type {
+                                ComputeType::ReduceSum => {
+                                    window.iter().fold(DataType::zero(), |acc, v| acc + *v)
+                                }
+                                ComputeType::ReduceMax => {
+                                    window.iter().fold(DataType::min_value(), |acc, v| {
+                                        if *v > acc {
+                                            *v
+                                        } else {
+                                            acc
+                                        }
+                                    })
+                                }
+                                ComputeType::ReduceMean => {
+                                    window.iter().fold(DataType::zero(), |acc, v| acc + *v)
+                                        / DataType::from_count(len)
+                                }
+                                _ => unreachable!(),
+                            }
+                        })
                     })
                 }
-                ComputeType::ElementwiseDiv => Value::Access(Access {
-                    access_axis: access.access_axis,
-                    tensor: access
-                        .tensor
-                        .axis_iter(ndarray::Axis(access.access_axis))
-                        // This is like a hacky version of fold_first. Struggled
-                        // to use fold_first because it will expect the function
+                // The dot products of each item of the first access with each
+                // window, as in convolution, or with each item of the second
+                // access, as a single matrix multiplication.
+                ComputeType::DotProduct => match &expr.as_ref()[usize::from(access_id)] {
+                    &Language::AccessCartesianProduct([a0_id, a1_id]) => match lazy_windows(a1_id)?
+                    {
+                        Some(windows) => {
+                            let a0 = match interpret_with_checks(expr, a0_id.into(), env, checks)? {
+                                Value::Access(a) => a,
+                                _ => bail!(
+                                    GlensideError::Interpretation,
+                                    "Unexpected argument to {}",
+                                    node
+                                ),
+                            };
+                            ensure_eq!(
+                                GlensideError::Interpretation,
+                                &a0.tensor.shape()[a0.access_axis..],
+                                windows.window_shape(),
+                                "Expected item shapes to match"
+                            );
+                            let a0_shape = a0.tensor.shape()[..a0.access_axis].to_vec();
+                            let a0_items = reshape(
+                                a0.tensor,
+                                &[
+                                    a0_shape.iter().product(),
+                                    windows.window_shape().iter().product(),
+                                ],
+                            );
+                            let shape = a0_shape
+                                .iter()
+                                .chain(windows.shape().iter())
+                                .cloned()
+                                .collect::<Vec<_>>();
+                            let results = a0_items
+                                .outer_iter()
+                                .flat_map(|item| {
+                                    windows
+                                        .windows()
+                                        .map(|window| {
+                                            item.iter()
+                                                .zip(window.iter())
+                                                .fold(DataType::zero(), |acc, (a, b)| acc + *a * *b)
+                                        })
+                                        .collect::<Vec<_>>()
+                                })
+                                .collect::<Vec<_>>();
+                            Some(Access {
+                                access_axis: shape.len(),
+                                tensor: ArrayD::from_shape_vec(shape, results).unwrap(),
+                            })
+                        }
+                        None if has_fast_matmul::<DataType>() => {
+                            let (a0, a1) = match (
+                                interpret_with_checks(expr, a0_id.into(), env, checks)?,
+                                interpret_with_checks(expr, a1_id.into(), env, checks)?,
+                            ) {
+                                (Value::Access(a0), Value::Access(a1)) => (a0, a1),
+                                _ => bail!(
+                                    GlensideError::Interpretation,
+                                    "Unexpected argument to {}",
+                                    node
+                                ),
+                            };
+                            ensure_eq!(
+                                GlensideError::Interpretation,
+                                &a0.tensor.shape()[a0.access_axis..],
+                                &a1.tensor.shape()[a1.access_axis..],
+                                "Expected item shapes to match"
+                            );
+                            let a0_shape = a0.tensor.shape()[..a0.access_axis].to_vec();
+                            let a1_shape = a1.tensor.shape()[..a1.access_axis].to_vec();
+                            let item_len = a0.tensor.shape()[a0.access_axis..].iter().product();
+                            let product = fast_matmul(
+                                to_matrix(a0.tensor, a0_shape.iter().product(), item_len),
+                                to_matrix(a1.tensor, a1_shape.iter().product(), item_len)
+                                    .reversed_axes(),
+                            );
+                            let shape = a0_shape.into_iter().chain(a1_shape).collect::<Vec<_>>();
+                            Some(Access {
+                                access_axis: shape.len(),
+                                tensor: reshape(product.into_dyn(), &shape),
+                            })
+                        }
+                        None => None,
+                    },
+                    _ => None,
+                },
+                // Sparse weights are multiplied by their stored blocks only.
+                ComputeType::SparseDotProduct => match &expr.as_ref()[usize::from(access_id)] {
+                    &Language::AccessCartesianProduct([a0_id, a1_id]) => {
+                        match sparse_weight(expr, a1_id, env) {
+                            Some(weight) => {
+                                match interpret_with_checks(expr, a0_id.into(), env, checks)? {
+                                    Value::Access(a0) => Some(sparse_dot_product(a0, weight)?),
+                                    _ => bail!(
+                                        GlensideError::Interpretation,
+                                        "Unexpected argument to {}",
+                                        node
+                                    ),
+                                }
+                            }
+                            None => None,
+                        }
+                    }
+                    _ => None,
+                },
+                _ => None,
+            };
+            if let Some(access) = lazy_result {
+                return Ok(Value::Access(access));
+            }
+
+            let access = match interpret_with_checks(expr, access_id.into(), env, checks)? {
+                Value::Access(a) => a,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+
+            match compute_type {
+                ComputeType::ReduceMean => Value::Access(Access {
+                    tensor: reshape(
+                        access.tensor.clone(),
+                        access.tensor.shape()[..access.access_axis]
+                            .iter()
+                            .cloned()
+                            .chain(std::iter::once(
+                                access.tensor.shape()[access.access_axis..]
+                                    .iter()
+                                    .cloned()
+                                    .product(),
+                            ))
+                            .collect::<Vec<_>>()
+                            .as_slice(),
+                    )
+                    .sum_axis(ndarray::Axis(access.access_axis))
+                    .div(DataType::from_count(
+                        access.tensor.shape()[access.access_axis..]
+                            .iter()
+                            .product::<usize>(),
+                    )),
+                    access_axis: access.access_axis,
+                }),
+                ComputeType::ReduceMeanVar => {
+                    let shape = access.tensor.shape()[..access.access_axis].to_vec();
+                    let item_len: usize =
+                        access.tensor.shape()[access.access_axis..].iter().product();
+                    let items = to_matrix(access.tensor, shape.iter().product(), item_len);
+                    let (means, variances): (Vec<_>, Vec<_>) =
+                        items.outer_iter().map(mean_and_variance).unzip();
+
+                    let statistic = |values: Vec<DataType>| {
+                        Value::Access(Access {
+                            tensor: ArrayD::from_shape_vec(shape.clone(), values).unwrap(),
+                            access_axis: shape.len(),
+                        })
+                    };
+                    Value::Tuple(vec![statistic(means), statistic(variances)])
+                }
+                ComputeType::Softmax => Value::Access(Access {
+                    tensor: softmax(&access)?,
+                    access_axis: access.access_axis,
+                }),
+                ComputeType::ElementwiseDiv => Value::Access(Access {
+                    access_axis: access.access_axis,
+                    tensor: access
+                        .tensor
+                        .axis_iter(ndarray::Axis(access.access_axis))
+                        // This is like a hacky version of fold_first. Struggled
+                        // to use fold_first because it will expect the function
                         // to return an ArrayView, which is not what you want
                         // (or not even possible?)
                         .skip(1)
@@ -520,7 +1944,11 @@ where
                                 .tensor
                                 .axis_iter(ndarray::Axis(access.access_axis))
                                 .next()
-                                .expect("Cannot divide 0 arguments")
+                                .ok_or_else(|| {
+                                    GlensideError::Interpretation(
+                                        "Cannot divide 0 arguments".to_string(),
+                                    )
+                                })?
                                 .into_owned(),
                             |acc, t| acc / t,
                         ),
@@ -567,21 +1995,18 @@ where
                             |acc, t| acc + t,
                         ),
                 }),
-                ComputeType::DotProduct => {
-                    let reshaped = access
-                        .tensor
-                        .clone()
-                        .into_shape(
-                            std::iter::once(
-                                access.tensor.shape()[..access.access_axis]
-                                    .iter()
-                                    .cloned()
-                                    .product(),
-                            )
-                            .chain(access.tensor.shape()[access.access_axis..].iter().cloned())
-                            .collect::<Vec<_>>(),
+                ComputeType::DotProduct | ComputeType::SparseDotProduct => {
+                    let reshaped = reshape(
+                        access.tensor.clone(),
+                        &std::iter::once(
+                            access.tensor.shape()[..access.access_axis]
+                                .iter()
+                                .cloned()
+                                .product(),
                         )
-                        .unwrap();
+                        .chain(access.tensor.shape()[access.access_axis..].iter().cloned())
+                        .collect::<Vec<_>>(),
+                    );
 
                     let num_elements_per_vec: usize = access.tensor.shape()
                         [access.access_axis + 1..]
@@ -628,6 +2053,31 @@ where
                         tensor: reshaped,
                     })
                 }
+                ComputeType::LogicalAnd | ComputeType::LogicalOr => {
+                    let is_and = compute_type == ComputeType::LogicalAnd;
+                    let mut items = access.tensor.axis_iter(ndarray::Axis(access.access_axis));
+                    let first = items
+                        .next()
+                        .ok_or_else(|| {
+                            GlensideError::Interpretation(
+                                "Cannot compute logical and/or of 0 arguments".to_string(),
+                            )
+                        })?
+                        .mapv(|v| mask(v != DataType::zero()));
+                    Value::Access(Access {
+                        access_axis: access.access_axis,
+                        tensor: items.fold(first, |acc, t| {
+                            ndarray::Zip::from(&acc).and(&t).apply_collect(|a, b| {
+                                let (a, b) = (*a != DataType::zero(), *b != DataType::zero());
+                                mask(if is_and { a && b } else { a || b })
+                            })
+                        }),
+                    })
+                }
+                ComputeType::LogicalNot => Value::Access(Access {
+                    tensor: access.tensor.mapv(|v| mask(v == DataType::zero())),
+                    access_axis: access.access_axis,
+                }),
                 ComputeType::Negative => Value::Access(Access {
                     tensor: access.tensor.mapv(|v| v.neg()),
                     access_axis: access.access_axis,
@@ -636,108 +2086,239 @@ where
                     tensor: access.tensor.mapv(|v| v.sqrt()),
                     access_axis: access.access_axis,
                 }),
-                ComputeType::ReLU => Value::Access(Access {
-                    tensor: access.tensor.mapv(|v| {
-                        if v >= DataType::zero() {
-                            v
-                        } else {
-                            DataType::zero()
-                        }
-                    }),
-                    access_axis: access.access_axis,
-                }),
+                ComputeType::Sigmoid | ComputeType::Tanh | ComputeType::ReLU => {
+                    Value::Access(Access {
+                        tensor: activation(&compute_type, &access.tensor)?,
+                        access_axis: access.access_axis,
+                    })
+                }
                 ComputeType::ReduceSum => Value::Access(Access {
-                    tensor: access
-                        .tensor
-                        .clone()
-                        .into_shape(
-                            access.tensor.shape()[..access.access_axis]
-                                .iter()
-                                .cloned()
-                                .chain(std::iter::once(
-                                    access.tensor.shape()[access.access_axis..]
-                                        .iter()
-                                        .cloned()
-                                        .product(),
-                                ))
-                                .collect::<Vec<_>>()
-                                .as_slice(),
-                        )
-                        .unwrap()
-                        .sum_axis(ndarray::Axis(access.access_axis)),
+                    tensor: reshape(
+                        access.tensor.clone(),
+                        access.tensor.shape()[..access.access_axis]
+                            .iter()
+                            .cloned()
+                            .chain(std::iter::once(
+                                access.tensor.shape()[access.access_axis..]
+                                    .iter()
+                                    .cloned()
+                                    .product(),
+                            ))
+                            .collect::<Vec<_>>()
+                            .as_slice(),
+                    )
+                    .sum_axis(ndarray::Axis(access.access_axis)),
                     access_axis: access.access_axis,
                 }),
                 ComputeType::ReduceMax => Value::Access(Access {
-                    tensor: access
-                        .tensor
-                        .clone()
-                        .into_shape(
-                            access.tensor.shape()[..access.access_axis]
-                                .iter()
-                                .cloned()
-                                .chain(std::iter::once(
-                                    access.tensor.shape()[access.access_axis..]
-                                        .iter()
-                                        .cloned()
-                                        .product(),
-                                ))
-                                .collect::<Vec<_>>()
-                                .as_slice(),
+                    tensor: reshape(
+                        access.tensor.clone(),
+                        access.tensor.shape()[..access.access_axis]
+                            .iter()
+                            .cloned()
+                            .chain(std::iter::once(
+                                access.tensor.shape()[access.access_axis..]
+                                    .iter()
+                                    .cloned()
+                                    .product(),
+                            ))
+                            .collect::<Vec<_>>()
+                            .as_slice(),
+                    )
+                    .map_axis(ndarray::Axis(access.access_axis), |t| {
+                        t.iter().fold(
+                            DataType::min_value(),
+                            |acc, v| if *v > acc { *v } else { acc },
                         )
-                        .unwrap()
-                        .map_axis(ndarray::Axis(access.access_axis), |t| {
-                            t.iter().fold(
-                                DataType::min_value(),
-                                |acc, v| if *v > acc { *v } else { acc },
-                            )
-                        }),
+                    }),
                     access_axis: access.access_axis,
                 }),
             }
         }
+        &Language::ActivationUnit([activation_id, access_id]) => {
+            let activation_type =
+                match interpret_with_checks(expr, activation_id.into(), env, checks)? {
+                    Value::ComputeType(t) => t,
+                    _ => bail!(
+                        GlensideError::Interpretation,
+                        "Unexpected argument to {}",
+                        node
+                    ),
+                };
+            let access = match interpret_with_checks(expr, access_id.into(), env, checks)? {
+                Value::Access(a) => a,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+            Value::Access(Access {
+                tensor: match activation_type {
+                    ComputeType::Softmax => softmax(&access)?,
+                    _ => activation(&activation_type, &access.tensor)?,
+                },
+                access_axis: access.access_axis,
+            })
+        }
+        // Simulates the vector ALU one invocation at a time: each invocation
+        // reads two vectors of `lanes` elements and writes one.
+        &Language::VectorAlu([op_id, lanes_id, access_id]) => {
+            let op = match interpret_with_checks(expr, op_id.into(), env, checks)? {
+                Value::ComputeType(t) => t,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+            let lanes = match interpret_with_checks(expr, lanes_id.into(), env, checks)? {
+                Value::Num(u) => u,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+            let access = match interpret_with_checks(expr, access_id.into(), env, checks)? {
+                Value::Access(a) => a,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+            let (shape, item_shape) = access.tensor.shape().split_at(access.access_axis);
+            ensure_eq!(GlensideError::Interpretation, item_shape, &[2, lanes]);
+            let shape = shape.to_vec();
+            let invocations = shape.iter().product::<usize>();
+
+            let operands = reshape(access.tensor, &[invocations, 2, lanes]);
+            let mut results = ArrayD::zeros(vec![invocations, lanes]);
+            for invocation in 0..invocations {
+                for lane in 0..lanes {
+                    let (a, b) = (
+                        operands[&[invocation, 0, lane][..]],
+                        operands[&[invocation, 1, lane][..]],
+                    );
+                    results[&[invocation, lane][..]] = match op {
+                        ComputeType::ElementwiseAdd => a + b,
+                        ComputeType::ElementwiseMul => a * b,
+                        _ => bail!(
+                            GlensideError::Interpretation,
+                            "Vector ALU must be elementwise-add or elementwise-mul"
+                        ),
+                    };
+                }
+            }
+
+            let access_axis = shape.len();
+            Value::Access(Access {
+                tensor: reshape(results, &[shape, vec![lanes]].concat()),
+                access_axis,
+            })
+        }
+        &Language::PoolingUnit([pool_type_id, access_id, window_shape_id, stride_shape_id]) => {
+            let pool_type = match interpret_with_checks(expr, pool_type_id.into(), env, checks)? {
+                Value::ComputeType(t) => t,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+            let access = match interpret_with_checks(expr, access_id.into(), env, checks)? {
+                Value::Access(a) => a,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+            let window_shape =
+                match interpret_with_checks(expr, window_shape_id.into(), env, checks)? {
+                    Value::Shape(s) => s,
+                    _ => bail!(
+                        GlensideError::Interpretation,
+                        "Unexpected argument to {}",
+                        node
+                    ),
+                };
+            let stride_shape =
+                match interpret_with_checks(expr, stride_shape_id.into(), env, checks)? {
+                    Value::Shape(s) => s,
+                    _ => bail!(
+                        GlensideError::Interpretation,
+                        "Unexpected argument to {}",
+                        node
+                    ),
+                };
+
+            ensure!(
+                GlensideError::Interpretation,
+                matches!(pool_type, ComputeType::ReduceMax | ComputeType::ReduceMean),
+                "Pooling unit must be reduce-max or reduce-mean"
+            );
+
+            Value::Access(
+                LazyWindows::new(access, window_shape, stride_shape)?.reduce(|window| {
+                    match pool_type {
+                        ComputeType::ReduceMax => {
+                            window.iter().fold(DataType::min_value(), |acc, v| {
+                                if *v > acc {
+                                    *v
+                                } else {
+                                    acc
+                                }
+                            })
+                        }
+                        ComputeType::ReduceMean => {
+                            window.iter().fold(DataType::zero(), |acc, v| acc + *v)
+                                / DataType::from_count(window.len())
+                        }
+                        _ => unreachable!(),
+                    }
+                }),
+            )
+        }
         &Language::AccessCartesianProduct([a0_id, a1_id]) => {
             let (a0, a1) = match (
-                interpret(expr, a0_id.into(), env),
-                interpret(expr, a1_id.into(), env),
+                interpret_with_checks(expr, a0_id.into(), env, checks)?,
+                interpret_with_checks(expr, a1_id.into(), env, checks)?,
             ) {
                 (Value::Access(a0), Value::Access(a1)) => (a0, a1),
-                _ => panic!(),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
             };
 
-            assert_eq!(
-                a0.tensor.shape()[a0.access_axis..],
-                a1.tensor.shape()[a1.access_axis..],
-                "Expected item shapes to match"
-            );
+            let shape = AccessShape::of_tensor(a0.tensor.shape(), a0.access_axis)
+                .cartesian_product(&AccessShape::of_tensor(a1.tensor.shape(), a1.access_axis));
 
-            let reshaped_0 = a0
-                .tensor
-                .as_standard_layout()
-                .into_shape(
-                    std::iter::once(
-                        a0.tensor.shape()[..a0.access_axis]
-                            .iter()
-                            .cloned()
-                            .product(),
-                    )
-                    .chain(a0.tensor.shape()[a0.access_axis..].iter().cloned())
-                    .collect::<Vec<_>>(),
+            let reshaped_0 = reshape(
+                a0.tensor.clone(),
+                &std::iter::once(
+                    a0.tensor.shape()[..a0.access_axis]
+                        .iter()
+                        .cloned()
+                        .product(),
                 )
-                .unwrap();
-            let reshaped_1 = a1
-                .tensor
-                .clone()
-                .into_shape(
-                    std::iter::once(
-                        a1.tensor.shape()[..a1.access_axis]
-                            .iter()
-                            .cloned()
-                            .product(),
-                    )
-                    .chain(a1.tensor.shape()[a1.access_axis..].iter().cloned())
-                    .collect::<Vec<_>>(),
+                .chain(a0.tensor.shape()[a0.access_axis..].iter().cloned())
+                .collect::<Vec<_>>(),
+            );
+            let reshaped_1 = reshape(
+                a1.tensor.clone(),
+                &std::iter::once(
+                    a1.tensor.shape()[..a1.access_axis]
+                        .iter()
+                        .cloned()
+                        .product(),
                 )
-                .unwrap();
+                .chain(a1.tensor.shape()[a1.access_axis..].iter().cloned())
+                .collect::<Vec<_>>(),
+            );
 
             let to_stack = reshaped_0
                 .axis_iter(ndarray::Axis(0))
@@ -765,34 +2346,28 @@ where
             )
             .unwrap();
 
-            let reshaped = unreshaped
-                .into_shape(
-                    a0.tensor.shape()[..a0.access_axis]
-                        .iter()
-                        .cloned()
-                        .chain(a1.tensor.shape()[..a1.access_axis].iter().cloned())
-                        .chain(std::iter::once(2))
-                        .chain(a0.tensor.shape()[a0.access_axis..].iter().cloned())
-                        .collect::<Vec<_>>(),
-                )
-                .unwrap();
+            let reshaped = unreshaped.into_shape(shape.as_vec()).unwrap();
 
             Value::Access(Access {
                 tensor: reshaped.into_dyn(),
-                access_axis: a0.access_axis + a1.access_axis,
+                access_axis: shape.access_axis(),
             })
         }
         &Language::Access([access_id, dim_id]) => {
-            let access = match interpret(expr, access_id.into(), env) {
+            let access = match interpret_with_checks(expr, access_id.into(), env, checks)? {
                 Value::Access(a) => a,
-                _ => panic!(),
-            };
-            let dim = match interpret(expr, dim_id.into(), env) {
-                Value::Num(u) => u,
-                _ => panic!(),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
             };
+            let dim = get_axis(
+                interpret_with_checks(expr, dim_id.into(), env, checks)?,
+                access.tensor.ndim(),
+            )?;
 
-            assert!(dim <= access.tensor.ndim());
+            ensure!(GlensideError::Interpretation, dim <= access.tensor.ndim());
 
             Value::Access(Access {
                 tensor: access.tensor,
@@ -801,136 +2376,579 @@ where
             })
         }
         &Language::AccessWindows([access_id, filters_shape_id, stride_shape_id]) => {
-            let access = match interpret(expr, access_id.into(), env) {
+            let access = match interpret_with_checks(expr, access_id.into(), env, checks)? {
                 Value::Access(a) => a,
-                _ => panic!(),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+            let filters_shape =
+                match interpret_with_checks(expr, filters_shape_id.into(), env, checks)? {
+                    Value::Shape(s) => s,
+                    _ => bail!(
+                        GlensideError::Interpretation,
+                        "Unexpected argument to {}",
+                        node
+                    ),
+                };
+            let stride_shape =
+                match interpret_with_checks(expr, stride_shape_id.into(), env, checks)? {
+                    Value::Shape(s) => s,
+                    _ => bail!(
+                        GlensideError::Interpretation,
+                        "Unexpected argument to {}",
+                        node
+                    ),
+                };
+
+            Value::Access(LazyWindows::new(access, filters_shape, stride_shape)?.materialize())
+        }
+        &Language::AccessWindowsWithConvention(
+            [access_id, filters_shape_id, stride_shape_id, convention_id, pad_type_id],
+        ) => {
+            let (mut access, filters_shape, stride_shape, convention, pad_type) = match (
+                interpret_with_checks(expr, access_id.into(), env, checks)?,
+                interpret_with_checks(expr, filters_shape_id.into(), env, checks)?,
+                interpret_with_checks(expr, stride_shape_id.into(), env, checks)?,
+                interpret_with_checks(expr, convention_id.into(), env, checks)?,
+                interpret_with_checks(expr, pad_type_id.into(), env, checks)?,
+            ) {
+                (
+                    Value::Access(a),
+                    Value::Shape(f),
+                    Value::Shape(s),
+                    Value::WindowConvention(c),
+                    Value::PadType(t),
+                ) => (a, f, s, c, t),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+
+            let padding = super::windows::padding(
+                &access.tensor.shape()[access.access_axis..],
+                filters_shape.slice(),
+                stride_shape.slice(),
+                convention,
+            )
+            .map_err(GlensideError::Interpretation)?;
+            for (item_axis, (pad_before, pad_after)) in padding.into_iter().enumerate() {
+                if pad_before > 0 || pad_after > 0 {
+                    access.tensor = pad(
+                        &access.tensor,
+                        pad_type,
+                        access.access_axis + item_axis,
+                        pad_before,
+                        pad_after,
+                    );
+                }
+            }
+
+            Value::Access(LazyWindows::new(access, filters_shape, stride_shape)?.materialize())
+        }
+        &Language::Conv2dTranspose(
+            [data_id, weights_id, strides_id, padding_id, output_padding_id],
+        ) => {
+            let (data, weights) = match (
+                interpret_with_checks(expr, data_id.into(), env, checks)?,
+                interpret_with_checks(expr, weights_id.into(), env, checks)?,
+            ) {
+                (Value::Access(data), Value::Access(weights)) => (data.tensor, weights.tensor),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Expected data and weights of a transposed convolution to be accesses"
+                ),
             };
-            let filters_shape = match interpret(expr, filters_shape_id.into(), env) {
+            let (strides, padding, output_padding) = match (
+                interpret_with_checks(expr, strides_id.into(), env, checks)?,
+                interpret_with_checks(expr, padding_id.into(), env, checks)?,
+                interpret_with_checks(expr, output_padding_id.into(), env, checks)?,
+            ) {
+                (Value::Shape(strides), Value::Shape(padding), Value::Shape(output_padding)) => {
+                    (strides, padding, output_padding)
+                }
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+            ensure_eq!(GlensideError::Interpretation, data.ndim(), 4);
+            ensure_eq!(GlensideError::Interpretation, weights.ndim(), 4);
+            ensure_eq!(
+                GlensideError::Interpretation,
+                weights.shape()[0],
+                data.shape()[1]
+            );
+
+            let out_shape = [data.shape()[0], weights.shape()[1]]
+                .iter()
+                .cloned()
+                .chain((0..2).map(|i| {
+                    (data.shape()[2 + i] - 1) * strides[i]
+                        + weights.shape()[2 + i]
+                        + output_padding[i]
+                        - padding[i]
+                        - padding[2 + i]
+                }))
+                .collect::<Vec<_>>();
+
+            // Rather than scattering each input element into the output, we
+            // gather, for each output element, the input elements whose
+            // scattered kernels cover it.
+            let tensor = ArrayD::from_shape_fn(out_shape, |index| {
+                let (batch, out_channel) = (index[0], index[1]);
+                let mut sum = DataType::zero();
+                for in_channel in 0..data.shape()[1] {
+                    for ky in 0..weights.shape()[2] {
+                        for kx in 0..weights.shape()[3] {
+                            // Position in the uncropped output, relative to
+                            // where the kernel copy starts.
+                            let (y, x) = (index[2] + padding[0], index[3] + padding[1]);
+                            if y < ky
+                                || x < kx
+                                || (y - ky) % strides[0] != 0
+                                || (x - kx) % strides[1] != 0
+                            {
+                                continue;
+                            }
+                            let (i, j) = ((y - ky) / strides[0], (x - kx) / strides[1]);
+                            if i >= data.shape()[2] || j >= data.shape()[3] {
+                                continue;
+                            }
+                            sum = sum
+                                + data[&[batch, in_channel, i, j][..]]
+                                    * weights[&[in_channel, out_channel, ky, kx][..]];
+                        }
+                    }
+                }
+                sum
+            });
+
+            Value::Access(Access {
+                access_axis: tensor.ndim(),
+                tensor,
+            })
+        }
+        &Language::Conv1d([data_id, weights_id, strides_id, padding_id, groups_id])
+        | &Language::Conv2d([data_id, weights_id, strides_id, padding_id, groups_id])
+        | &Language::Conv3d([data_id, weights_id, strides_id, padding_id, groups_id]) => {
+            let (data, weights) = match (
+                interpret_with_checks(expr, data_id.into(), env, checks)?,
+                interpret_with_checks(expr, weights_id.into(), env, checks)?,
+            ) {
+                (Value::Access(data), Value::Access(weights)) => (data.tensor, weights.tensor),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Expected data and weights of a convolution to be accesses"
+                ),
+            };
+            let strides = match interpret_with_checks(expr, strides_id.into(), env, checks)? {
                 Value::Shape(s) => s,
-                _ => panic!(),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
             };
-            let stride_shape = match interpret(expr, stride_shape_id.into(), env) {
+            let padding = match interpret_with_checks(expr, padding_id.into(), env, checks)? {
                 Value::Shape(s) => s,
-                _ => panic!(),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+            let groups = match interpret_with_checks(expr, groups_id.into(), env, checks)? {
+                Value::Num(u) => u,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
             };
 
-            // assert_eq!(
-            //     access.access_axis,
-            //     access.tensor.ndim(),
-            //     "access-windows access should be accessed at its last dimension"
-            // );
-            assert_eq!(
-                access.tensor.ndim() - access.access_axis,
-                stride_shape.ndim(),
-                "access-windows item shape ndims should match stride ndims"
+            let num_spatial_dims = strides.ndim();
+            ensure_eq!(
+                GlensideError::Interpretation,
+                data.ndim(),
+                num_spatial_dims + 2
+            );
+            ensure_eq!(
+                GlensideError::Interpretation,
+                weights.ndim(),
+                num_spatial_dims + 2
             );
-            assert_eq!(
-                filters_shape.ndim(),
-                stride_shape.ndim(),
-                "access-windows filters ndims should match stride ndims"
+            ensure_eq!(
+                GlensideError::Interpretation,
+                padding.ndim(),
+                2 * num_spatial_dims
+            );
+            ensure_eq!(GlensideError::Interpretation, data.shape()[1] % groups, 0);
+            ensure_eq!(
+                GlensideError::Interpretation,
+                weights.shape()[0] % groups,
+                0
+            );
+            ensure_eq!(
+                GlensideError::Interpretation,
+                weights.shape()[1],
+                data.shape()[1] / groups
             );
 
-            let out_shape = access.tensor.shape()[..access.access_axis]
+            // Zero-pad the spatial dimensions.
+            let padded_shape = data.shape()[..2]
                 .iter()
+                .cloned()
                 .chain(
-                    super::access_windows_resulting_shape(
-                        &IxDyn(&access.tensor.shape()[access.access_axis..]),
-                        &filters_shape,
-                        // Ignore striding for now; we will stride after we get the result
-                        // TODO(@gussmith23) More efficient striding?
-                        &IxDyn(
-                            std::iter::repeat(1)
-                                .take(filters_shape.ndim())
-                                .collect::<Vec<_>>()
-                                .as_slice(),
-                        ),
-                    )
-                    .iter(),
+                    (0..num_spatial_dims)
+                        .map(|i| padding[i] + data.shape()[2 + i] + padding[num_spatial_dims + i]),
                 )
-                .cloned()
                 .collect::<Vec<_>>();
-
-            let mut result = ArrayD::<DataType>::zeros(
-                out_shape
-                    .iter()
-                    .cloned()
-                    .chain(std::iter::once(filters_shape.slice().iter().product()))
-                    .collect::<Vec<_>>(),
-            );
-
-            Zip::from(result.genrows_mut())
-                .and(
-                    access.tensor.windows(
-                        std::iter::repeat(&1)
-                            .take(access.access_axis)
-                            .chain(filters_shape.slice().iter())
-                            .cloned()
+            let mut padded = ArrayD::<DataType>::zeros(padded_shape.clone());
+            padded
+                .slice_mut(
+                    ndarray::SliceInfo::<_, IxDyn>::new(
+                        std::iter::repeat(ndarray::SliceOrIndex::from(..))
+                            .take(2)
+                            .chain((0..num_spatial_dims).map(|i| {
+                                ndarray::SliceOrIndex::from(
+                                    padding[i]..padding[i] + data.shape()[2 + i],
+                                )
+                            }))
                             .collect::<Vec<_>>(),
-                    ),
+                    )
+                    .unwrap()
+                    .as_ref(),
                 )
-                .apply(|mut result, windows| {
-                    result.assign(&Array::from_iter(windows.iter().cloned()))
-                });
+                .assign(&data);
 
-            let mut result = result
-                .into_shape(
-                    out_shape
-                        .iter()
-                        .cloned()
-                        .chain(filters_shape.slice().iter().cloned())
-                        .collect::<Vec<_>>(),
-                )
-                .unwrap();
+            let out_channels_per_group = weights.shape()[0] / groups;
+            let in_channels_per_group = weights.shape()[1];
+            let out_shape = [data.shape()[0], weights.shape()[0]]
+                .iter()
+                .cloned()
+                .chain(super::access_windows_resulting_shape(
+                    &IxDyn(&padded_shape[2..]),
+                    &IxDyn(&weights.shape()[2..]),
+                    &strides,
+                ))
+                .collect::<Vec<_>>();
 
-            for (axis, &stride) in (access.access_axis..(access.access_axis + stride_shape.ndim()))
-                .zip(stride_shape.slice().iter())
-            {
-                // TODO(@gussmith23) Interpreter window striding is inefficient
-                // I wish we could do this in the windows() method of ndarray,
-                // but they don't seem to support it.
-                result = ndarray::stack(
-                    ndarray::Axis(axis),
-                    result
-                        .axis_iter(ndarray::Axis(axis))
-                        .step_by(stride)
-                        // TODO(@gussmith23) This seems dumb
-                        // Can we do an axis_iter that doesn't remove the axis?
-                        .map(|t| t.insert_axis(ndarray::Axis(axis)))
-                        .collect::<Vec<_>>()
-                        .as_slice(),
-                )
-                .unwrap();
-            }
+            let tensor = ArrayD::from_shape_fn(out_shape, |index| {
+                let (batch, out_channel) = (index[0], index[1]);
+                let group = out_channel / out_channels_per_group;
+                let window = padded.slice(
+                    ndarray::SliceInfo::<_, IxDyn>::new(
+                        std::iter::once(ndarray::SliceOrIndex::from(batch))
+                            .chain(std::iter::once(ndarray::SliceOrIndex::from(
+                                group * in_channels_per_group..(group + 1) * in_channels_per_group,
+                            )))
+                            .chain((0..num_spatial_dims).map(|i| {
+                                let start = index[2 + i] * strides[i];
+                                ndarray::SliceOrIndex::from(start..start + weights.shape()[2 + i])
+                            }))
+                            .collect::<Vec<_>>(),
+                    )
+                    .unwrap()
+                    .as_ref(),
+                );
+                window
+                    .iter()
+                    .zip(weights.index_axis(ndarray::Axis(0), out_channel).iter())
+                    .fold(DataType::zero(), |acc, (d, w)| acc + *d * *w)
+            });
 
             Value::Access(Access {
-                tensor: result,
-                access_axis: access.access_axis + stride_shape.ndim(),
+                access_axis: tensor.ndim(),
+                tensor,
             })
         }
+        &Language::BatchMatmul([a_id, b_id]) => {
+            let (a, b) = match (
+                interpret_with_checks(expr, a_id.into(), env, checks)?,
+                interpret_with_checks(expr, b_id.into(), env, checks)?,
+            ) {
+                (Value::Access(a), Value::Access(b)) => (a.tensor, b.tensor),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Expected both arguments of batch-matmul to be accesses"
+                ),
+            };
+            ensure_eq!(GlensideError::Interpretation, a.ndim(), 3);
+            ensure_eq!(GlensideError::Interpretation, b.ndim(), 3);
+            ensure_eq!(GlensideError::Interpretation, a.shape()[0], b.shape()[0]);
+            ensure_eq!(GlensideError::Interpretation, a.shape()[2], b.shape()[1]);
+
+            let tensor =
+                ArrayD::from_shape_fn(vec![a.shape()[0], a.shape()[1], b.shape()[2]], |index| {
+                    (0..a.shape()[2])
+                        .map(|k| a[&[index[0], index[1], k][..]] * b[&[index[0], k, index[2]][..]])
+                        .sum()
+                });
+
+            Value::Access(Access {
+                access_axis: tensor.ndim(),
+                tensor,
+            })
+        }
+        // Inference mode: nothing is dropped.
+        &Language::Dropout([data_id, _rate_id]) => {
+            match interpret_with_checks(expr, data_id.into(), env, checks)? {
+                a @ Value::Access(_) => a,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Expected data of dropout to be an access"
+                ),
+            }
+        }
+        &Language::LayerNorm([data_id, gamma_id, beta_id, epsilon_id]) => {
+            let (mut data, gamma, beta) = match (
+                interpret_with_checks(expr, data_id.into(), env, checks)?,
+                interpret_with_checks(expr, gamma_id.into(), env, checks)?,
+                interpret_with_checks(expr, beta_id.into(), env, checks)?,
+            ) {
+                (Value::Access(data), Value::Access(gamma), Value::Access(beta)) => {
+                    (data, gamma.tensor, beta.tensor)
+                }
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Expected data, gamma, and beta of layer-norm to be accesses"
+                ),
+            };
+            let epsilon = match interpret_with_checks(expr, epsilon_id.into(), env, checks)? {
+                Value::Tensor(t) if t.ndim() == 0 => *t.first().unwrap(),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Epsilon of layer-norm should be a scalar literal"
+                ),
+            };
+
+            let shape = data.tensor.shape().to_vec();
+            let item_shape = &shape[data.access_axis..];
+            ensure_eq!(GlensideError::Interpretation, gamma.shape(), item_shape);
+            ensure_eq!(GlensideError::Interpretation, beta.shape(), item_shape);
+
+            let item_len = item_shape.iter().product::<usize>();
+            let items = shape[..data.access_axis].iter().product::<usize>();
+            let normalized = normalize_rows(to_matrix(data.tensor, items, item_len), epsilon);
+            // Gamma and beta broadcast along the leading (non-item) axes.
+            data.tensor = &(&reshape(normalized.into_dyn(), &shape) * &gamma) + &beta;
+
+            Value::Access(data)
+        }
+        &Language::GroupNorm([data_id, gamma_id, beta_id, groups_id, epsilon_id]) => {
+            let (data, gamma, beta) = match (
+                interpret_with_checks(expr, data_id.into(), env, checks)?,
+                interpret_with_checks(expr, gamma_id.into(), env, checks)?,
+                interpret_with_checks(expr, beta_id.into(), env, checks)?,
+            ) {
+                (Value::Access(data), Value::Access(gamma), Value::Access(beta)) => {
+                    (data.tensor, gamma.tensor, beta.tensor)
+                }
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Expected data, gamma, and beta of group-norm to be accesses"
+                ),
+            };
+            let groups = match interpret_with_checks(expr, groups_id.into(), env, checks)? {
+                Value::Num(u) => u,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+            let epsilon = match interpret_with_checks(expr, epsilon_id.into(), env, checks)? {
+                Value::Tensor(t) if t.ndim() == 0 => *t.first().unwrap(),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Epsilon of group-norm should be a scalar literal"
+                ),
+            };
+
+            let shape = data.shape().to_vec();
+            ensure!(GlensideError::Interpretation, shape.len() >= 2);
+            ensure!(
+                GlensideError::Interpretation,
+                groups > 0 && shape[1] % groups == 0
+            );
+            ensure_eq!(GlensideError::Interpretation, gamma.shape(), &[shape[1]]);
+            ensure_eq!(GlensideError::Interpretation, beta.shape(), &[shape[1]]);
+
+            // Each row holds one group of one batch element.
+            let rows = shape[0] * groups;
+            let cols = data.len() / rows;
+            let normalized = normalize_rows(to_matrix(data, rows, cols), epsilon);
+            let normalized = reshape(normalized.into_dyn(), &shape);
+
+            // Reshape gamma and beta so that they broadcast along every axis
+            // but the channel axis.
+            let mut channel_shape = vec![1; shape.len() - 1];
+            channel_shape[0] = shape[1];
+            let gamma = reshape(gamma, &channel_shape);
+            let beta = reshape(beta, &channel_shape);
+            let tensor = &(&normalized * &gamma) + &beta;
+
+            Value::Access(Access {
+                access_axis: tensor.ndim(),
+                tensor,
+            })
+        }
+        &Language::AdaptivePool2d([pool_type_id, data_id, output_size_id]) => {
+            let pool_type = match interpret_with_checks(expr, pool_type_id.into(), env, checks)? {
+                Value::ComputeType(t) => t,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+            let data = match interpret_with_checks(expr, data_id.into(), env, checks)? {
+                Value::Access(a) => a.tensor,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Expected data of adaptive-pool2d to be an access"
+                ),
+            };
+            let output_size = match interpret_with_checks(expr, output_size_id.into(), env, checks)?
+            {
+                Value::Shape(s) => s,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+            ensure_eq!(GlensideError::Interpretation, data.ndim(), 4);
+            ensure_eq!(GlensideError::Interpretation, output_size.ndim(), 2);
+            ensure!(
+                GlensideError::Interpretation,
+                matches!(pool_type, ComputeType::ReduceMean | ComputeType::ReduceMax),
+                "Adaptive pooling must be reduce-mean or reduce-max"
+            );
+
+            // The range of input indices pooled into output index `i` along
+            // an axis of length `len`, which is pooled down to `out_len`.
+            fn window(i: usize, len: usize, out_len: usize) -> std::ops::Range<usize> {
+                (i * len / out_len)..(((i + 1) * len + out_len - 1) / out_len)
+            }
+
+            let (height, width) = (data.shape()[2], data.shape()[3]);
+            let tensor = ArrayD::from_shape_fn(
+                vec![
+                    data.shape()[0],
+                    data.shape()[1],
+                    output_size[0],
+                    output_size[1],
+                ],
+                |index| {
+                    let mut values = Vec::default();
+                    for y in window(index[2], height, output_size[0]) {
+                        for x in window(index[3], width, output_size[1]) {
+                            values.push(data[&[index[0], index[1], y, x][..]]);
+                        }
+                    }
+                    match pool_type {
+                        ComputeType::ReduceMean => {
+                            let count = values.len();
+                            values.into_iter().fold(DataType::zero(), |sum, v| sum + v)
+                                / DataType::from_count(count)
+                        }
+                        ComputeType::ReduceMax => {
+                            values.into_iter().fold(DataType::min_value(), |acc, v| {
+                                if v > acc {
+                                    v
+                                } else {
+                                    acc
+                                }
+                            })
+                        }
+                        _ => unreachable!(),
+                    }
+                },
+            );
+
+            Value::Access(Access {
+                access_axis: tensor.ndim(),
+                tensor,
+            })
+        }
+        &Language::BiasAdd([data_id, bias_id, axis_id]) => {
+            let (mut data, bias) = match (
+                interpret_with_checks(expr, data_id.into(), env, checks)?,
+                interpret_with_checks(expr, bias_id.into(), env, checks)?,
+            ) {
+                (Value::Access(data), Value::Access(bias)) => (data, bias),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Expected data and bias of bias-add to be accesses"
+                ),
+            };
+            let axis = match interpret_with_checks(expr, axis_id.into(), env, checks)? {
+                Value::Num(u) => u,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            };
+
+            ensure!(GlensideError::Interpretation, axis < data.tensor.ndim());
+            ensure_eq!(
+                GlensideError::Interpretation,
+                bias.tensor.shape(),
+                &[data.tensor.shape()[axis]]
+            );
+
+            // Reshape the bias so that it broadcasts along every axis but
+            // `axis`.
+            let mut bias_shape = vec![1; data.tensor.ndim()];
+            bias_shape[axis] = data.tensor.shape()[axis];
+            let bias = reshape(bias.tensor, &bias_shape);
+
+            data.tensor = &data.tensor + &bias;
+
+            Value::Access(data)
+        }
         Language::Shape(list) => Value::Shape(IxDyn(
             list.iter()
-                .map(|id: &Id| match interpret(expr, (*id).into(), env) {
-                    Value::Num(u) => u,
-                    _ => panic!(),
+                .map(|id: &Id| -> Result<usize> {
+                    match interpret_with_checks(expr, (*id).into(), env, checks)? {
+                        Value::Num(u) => Ok(u),
+                        _ => bail!(
+                            GlensideError::Interpretation,
+                            "Unexpected argument to {}",
+                            node
+                        ),
+                    }
                 })
-                .collect::<Vec<_>>()
+                .collect::<Result<Vec<_>>>()?
                 .as_slice(),
         )),
         &Language::SliceShape([shape_id, slice_axis_id]) => match (
-            interpret(expr, shape_id.into(), env),
-            interpret(expr, slice_axis_id.into(), env),
+            interpret_with_checks(expr, shape_id.into(), env, checks)?,
+            interpret_with_checks(expr, slice_axis_id.into(), env, checks)?,
         ) {
             (Value::Shape(s), Value::Num(u)) => {
                 Value::Shape(IxDyn(s.as_array_view().slice(s![u..]).to_slice().unwrap()))
             }
-            _ => panic!(),
+            _ => bail!(
+                GlensideError::Interpretation,
+                "Unexpected argument to {}",
+                node
+            ),
         },
         &Language::ShapeInsertAxis([shape_id, axis_id]) => match (
-            interpret(expr, shape_id.into(), env),
-            interpret(expr, axis_id.into(), env),
+            interpret_with_checks(expr, shape_id.into(), env, checks)?,
+            interpret_with_checks(expr, axis_id.into(), env, checks)?,
         ) {
             (Value::Shape(s), Value::Num(u)) => {
-                assert!(u <= s.ndim());
+                ensure!(GlensideError::Interpretation, u <= s.ndim());
                 Value::Shape(IxDyn(
                     s.slice()[..u]
                         .iter()
@@ -941,14 +2959,22 @@ where
                         .as_slice(),
                 ))
             }
-            _ => panic!(),
+            _ => bail!(
+                GlensideError::Interpretation,
+                "Unexpected argument to {}",
+                node
+            ),
         },
         &Language::ShapeRemoveAxis([shape_id, axis_id]) => match (
-            interpret(expr, shape_id.into(), env),
-            interpret(expr, axis_id.into(), env),
+            interpret_with_checks(expr, shape_id.into(), env, checks)?,
+            interpret_with_checks(expr, axis_id.into(), env, checks)?,
         ) {
             (Value::Shape(s), Value::Num(u)) => {
-                assert!(u < s.ndim(), "Invalid axis in shape-remove-axis");
+                ensure!(
+                    GlensideError::Interpretation,
+                    u < s.ndim(),
+                    "Invalid axis in shape-remove-axis"
+                );
                 Value::Shape(IxDyn(
                     s.slice()[..u]
                         .iter()
@@ -958,31 +2984,257 @@ where
                         .as_slice(),
                 ))
             }
-            _ => panic!(),
+            _ => bail!(
+                GlensideError::Interpretation,
+                "Unexpected argument to {}",
+                node
+            ),
         },
-        &Language::ShapeOf([tensor_id]) => match interpret(expr, tensor_id.into(), env) {
-            Value::Tensor(t) => Value::Shape(IxDyn(t.shape())),
-            _ => panic!(),
+        &Language::ShapeConcat([shape0_id, shape1_id]) => match (
+            interpret_with_checks(expr, shape0_id.into(), env, checks)?,
+            interpret_with_checks(expr, shape1_id.into(), env, checks)?,
+        ) {
+            (Value::Shape(s0), Value::Shape(s1)) => Value::Shape(IxDyn(
+                s0.slice()
+                    .iter()
+                    .chain(s1.slice().iter())
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .as_slice(),
+            )),
+            _ => bail!(
+                GlensideError::Interpretation,
+                "Unexpected argument to {}",
+                node
+            ),
+        },
+        &Language::ShapeDim([shape_id, axis_id]) => match (
+            interpret_with_checks(expr, shape_id.into(), env, checks)?,
+            interpret_with_checks(expr, axis_id.into(), env, checks)?,
+        ) {
+            (Value::Shape(s), Value::Num(u)) => {
+                ensure!(
+                    GlensideError::Interpretation,
+                    u < s.ndim(),
+                    "Invalid axis in shape-dim"
+                );
+                Value::Num(s[u])
+            }
+            _ => bail!(
+                GlensideError::Interpretation,
+                "Unexpected argument to {}",
+                node
+            ),
         },
-        &Language::AccessTensor(tensor_id) => match interpret(expr, tensor_id.into(), env) {
-            Value::Tensor(t) => Value::Access(Access {
-                tensor: t,
-                // TODO(@gussmith) Arbitrarily picked default access axis
-                access_axis: 0,
+        &Language::UsizeAdd([a_id, b_id])
+        | &Language::UsizeSub([a_id, b_id])
+        | &Language::UsizeMul([a_id, b_id])
+        | &Language::UsizeDiv([a_id, b_id]) => match (
+            interpret_with_checks(expr, a_id.into(), env, checks)?,
+            interpret_with_checks(expr, b_id.into(), env, checks)?,
+        ) {
+            (Value::Num(a), Value::Num(b)) => Value::Num(match &expr.as_ref()[index] {
+                Language::UsizeAdd(_) => a + b,
+                Language::UsizeSub(_) => {
+                    ensure!(
+                        GlensideError::Interpretation,
+                        a >= b,
+                        "Cannot subtract {} from {}",
+                        b,
+                        a
+                    );
+                    a - b
+                }
+                Language::UsizeMul(_) => a * b,
+                Language::UsizeDiv(_) => {
+                    ensure_ne!(GlensideError::Interpretation, b, 0, "Division by zero");
+                    a / b
+                }
+                _ => unreachable!(),
             }),
-            _ => panic!(),
+            _ => bail!(
+                GlensideError::Interpretation,
+                "Unexpected argument to {}",
+                node
+            ),
         },
-        Language::Symbol(s) => Value::Tensor(
-            env.get(s.as_str())
-                .unwrap_or_else(|| panic!("Symbol {} not in environment", s))
-                .clone(),
-        ),
+        &Language::TensorDecl([name_id, shape_id]) => match (
+            interpret_with_checks(expr, name_id.into(), env, checks)?,
+            interpret_with_checks(expr, shape_id.into(), env, checks)?,
+        ) {
+            (Value::Tensor(t), Value::Shape(s)) => {
+                ensure_eq!(
+                    GlensideError::Interpretation,
+                    t.shape(),
+                    s.slice(),
+                    "Declared shape does not match the shape of the tensor"
+                );
+                Value::Tensor(t)
+            }
+            _ => bail!(
+                GlensideError::Interpretation,
+                "Unexpected argument to {}",
+                node
+            ),
+        },
+        &Language::ShapeOf([tensor_id]) => {
+            match interpret_with_checks(expr, tensor_id.into(), env, checks)? {
+                Value::Tensor(t) => Value::Shape(IxDyn(t.shape())),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            }
+        }
+        &Language::AccessTensor(tensor_id) => {
+            match interpret_with_checks(expr, tensor_id.into(), env, checks)? {
+                Value::Tensor(t) => Value::Access(Access {
+                    tensor: t.into_owned(),
+                    // TODO(@gussmith) Arbitrarily picked default access axis
+                    access_axis: 0,
+                }),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Unexpected argument to {}",
+                    node
+                ),
+            }
+        }
+        Language::Symbol(s) => Value::Tensor(match env.unpack(s.as_str()) {
+            Some(tensor) => CowArray::from(tensor),
+            None => CowArray::from(env.lookup(s.as_str()).ok_or_else(|| {
+                GlensideError::Interpretation(format!("Symbol {} not in environment", s))
+            })?),
+        }),
+        // Negative Nums only make sense as axes, which are resolved against
+        // the rank of whatever they index into; see [`get_axis`].
+        &Language::Num(u) if u < 0 => Value::Int64(u),
         &Language::Num(u) => Value::Num(u.try_into().unwrap()),
 
-        &Language::SystolicArray(_)
-        | &Language::SystolicArrayWithBlocking(_)
-        | &Language::AccessShiftRight(_) => todo!("{:?}", &expr.as_ref()[index]),
-    }
+        // With or without blocking, a systolic array computes a
+        // vector-matrix or matrix-matrix multiplication; blocking only
+        // changes how the hardware schedules it. A fused activation is applied
+        // to the result.
+        &Language::SystolicArray([_rows_id, _cols_id, a0_id, a1_id])
+        | &Language::SystolicArrayWithBlocking([_rows_id, _cols_id, a0_id, a1_id])
+        | &Language::SystolicArrayWithActivation([_, _rows_id, _cols_id, a0_id, a1_id]) => {
+            let (a0, a1) = match (
+                interpret_with_checks(expr, a0_id.into(), env, checks)?,
+                interpret_with_checks(expr, a1_id.into(), env, checks)?,
+            ) {
+                (Value::Access(a0), Value::Access(a1)) => (a0, a1),
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Expected access patterns as third and fourth arguments"
+                ),
+            };
+            ensure_eq!(GlensideError::Interpretation, a1.access_axis, 0);
+            ensure_eq!(GlensideError::Interpretation, a1.tensor.ndim(), 2);
+            ensure!(GlensideError::Interpretation, a0.access_axis <= 1);
+            ensure_eq!(
+                GlensideError::Interpretation,
+                a0.tensor.ndim(),
+                a0.access_axis + 1
+            );
+            let (k, m) = (a1.tensor.shape()[0], a1.tensor.shape()[1]);
+            ensure_eq!(
+                GlensideError::Interpretation,
+                a0.tensor.shape()[a0.access_axis],
+                k
+            );
+
+            let shape = a0.tensor.shape()[..a0.access_axis]
+                .iter()
+                .cloned()
+                .chain(std::iter::once(m))
+                .collect::<Vec<_>>();
+            let tensor = if let Some(arithmetic) = env.systolic_array_arithmetic() {
+                let rows = shape[..shape.len() - 1].iter().product();
+                let quantized = |tensor: ArrayD<DataType>, rows, cols| {
+                    to_matrix(tensor, rows, cols).mapv(QuantizedValue::to_quantized)
+                };
+                let product = arithmetic.matmul(
+                    quantized(a0.tensor, rows, k).view(),
+                    quantized(a1.tensor, k, m).view(),
+                );
+                reshape(product.mapv(DataType::from_quantized).into_dyn(), &shape)
+            } else if has_fast_matmul::<DataType>() {
+                let rows = shape[..shape.len() - 1].iter().product();
+                let product =
+                    fast_matmul(to_matrix(a0.tensor, rows, k), to_matrix(a1.tensor, k, m));
+                reshape(product.into_dyn(), &shape)
+            } else {
+                ArrayD::from_shape_fn(shape, |index| {
+                    let index = index.slice();
+                    let (row, col) = index.split_at(index.len() - 1);
+                    (0..k)
+                        .map(|i| {
+                            a0.tensor[[row, &[i][..]].concat().as_slice()]
+                                * a1.tensor[&[i, col[0]][..]]
+                        })
+                        .sum()
+                })
+            };
+            let tensor = match &expr.as_ref()[index] {
+                &Language::SystolicArrayWithActivation([activation_id, ..]) => {
+                    match interpret_with_checks(expr, activation_id.into(), env, checks)? {
+                        Value::ComputeType(t) => activation(&t, &tensor)?,
+                        _ => bail!(
+                            GlensideError::Interpretation,
+                            "Unexpected argument to {}",
+                            node
+                        ),
+                    }
+                }
+                _ => tensor,
+            };
+            let access_axis = tensor.ndim();
+            Value::Access(Access {
+                tensor,
+                access_axis,
+            })
+        }
+        &Language::SparseSystolicArray([_rows_id, _cols_id, a0_id, a1_id]) => {
+            let a0 = match interpret_with_checks(expr, a0_id.into(), env, checks)? {
+                Value::Access(a0) => a0,
+                _ => bail!(
+                    GlensideError::Interpretation,
+                    "Expected an access pattern as the third argument"
+                ),
+            };
+            let access = match sparse_weight(expr, a1_id, env) {
+                Some(weight) => sparse_dot_product(a0, weight)?,
+                // Other weights are converted to CSR as they're used.
+                None => match interpret_with_checks(expr, a1_id.into(), env, checks)? {
+                    Value::Access(a1) => {
+                        ensure_eq!(GlensideError::Interpretation, a1.access_axis, 1);
+                        ensure_eq!(GlensideError::Interpretation, a1.tensor.ndim(), 2);
+                        let (rows, cols) = (a1.tensor.shape()[0], a1.tensor.shape()[1]);
+                        let weight = to_matrix(a1.tensor, rows, cols);
+                        sparse_dot_product(a0, &SparseMatrix::from_dense(weight.view(), [1, 1]))?
+                    }
+                    _ => bail!(
+                        GlensideError::Interpretation,
+                        "Expected an access pattern as the fourth argument"
+                    ),
+                },
+            };
+            Value::Access(access)
+        }
+        &Language::AccessShiftRight(_) => {
+            bail!(
+                GlensideError::Interpretation,
+                "Interpreting {} is not supported",
+                node
+            )
+        }
+        &Language::Unroll(_) => {
+            bail!(GlensideError::Interpretation,
+            "unroll must be expanded before interpretation; see language::unroll::expand_unrolls"
+        )
+        }
+    })
 }
 
 /// Trait for types which can be converted to from Glenside literals.
@@ -1033,6 +3285,251 @@ impl FromNotNanFloat64Literal for i64 {
     }
 }
 
+impl FromNotNanFloat64Literal for i32 {
+    fn from_not_nan_float_64_literal(_value: ordered_float::NotNan<f64>) -> Self {
+        unreachable!()
+    }
+}
+
+/// Trait for types which can hold values of Glenside's data types.
+///
+/// The interpreter computes over a single Rust type, so a `cast` doesn't change
+/// the type of the values; instead, it replaces each value with the nearest
+/// value (under the casting rules documented on the `cast` node) representable
+/// in the target data type.
+pub trait Cast {
+    /// Convert this value to `dtype`, represented as `Self`.
+    fn cast(self, dtype: crate::language::DataType) -> Self;
+}
+
+/// Wraps an integer around to the range of the integer (or bool) data type
+/// `dtype`.
+fn wrap_integer(value: i128, dtype: crate::language::DataType) -> i128 {
+    match dtype {
+        crate::language::DataType::Bool => (value != 0) as i128,
+        crate::language::DataType::Int(bits) | crate::language::DataType::Uint(bits) => {
+            assert!(bits > 0 && bits <= 64, "Unsupported integer width {}", bits);
+            let modulus = 1i128 << bits;
+            let value = value.rem_euclid(modulus);
+            match dtype {
+                crate::language::DataType::Int(_) if value >= modulus / 2 => value - modulus,
+                _ => value,
+            }
+        }
+        crate::language::DataType::Float(_) => unreachable!(),
+    }
+}
+
+impl Cast for f64 {
+    /// ```
+    /// use glenside::language::interpreter::Cast;
+    /// use glenside::language::DataType;
+    /// assert_eq!((-1.7f64).cast(DataType::Int(8)), -1.0);
+    /// assert_eq!((-1.7f64).cast(DataType::Uint(8)), 255.0);
+    /// assert_eq!(300.2f64.cast(DataType::Uint(8)), 44.0);
+    /// assert_eq!(0.1f64.cast(DataType::Float(32)), 0.1f32 as f64);
+    /// assert_eq!(0.1f64.cast(DataType::Bool), 1.0);
+    /// ```
+    fn cast(self, dtype: crate::language::DataType) -> Self {
+        match dtype {
+            crate::language::DataType::Float(64) => self,
+            crate::language::DataType::Float(32) => self as f32 as f64,
+            crate::language::DataType::Float(bits) => panic!("Unsupported float width {}", bits),
+            crate::language::DataType::Bool => (self != 0.0) as i64 as f64,
+            _ => wrap_integer(self.trunc() as i128, dtype) as f64,
+        }
+    }
+}
+
+impl Cast for f32 {
+    /// ```
+    /// use glenside::language::interpreter::Cast;
+    /// use glenside::language::DataType;
+    /// assert_eq!(129.9f32.cast(DataType::Int(8)), -127.0);
+    /// assert_eq!(0.1f32.cast(DataType::Float(64)), 0.1f32);
+    /// ```
+    fn cast(self, dtype: crate::language::DataType) -> Self {
+        match dtype {
+            crate::language::DataType::Float(32) | crate::language::DataType::Float(64) => self,
+            crate::language::DataType::Float(bits) => panic!("Unsupported float width {}", bits),
+            crate::language::DataType::Bool => (self != 0.0) as i64 as f32,
+            _ => wrap_integer(self.trunc() as i128, dtype) as f32,
+        }
+    }
+}
+
+impl Cast for i64 {
+    /// ```
+    /// use glenside::language::interpreter::Cast;
+    /// use glenside::language::DataType;
+    /// assert_eq!(128i64.cast(DataType::Int(8)), -128);
+    /// assert_eq!((-1i64).cast(DataType::Uint(16)), 65535);
+    /// assert_eq!(16777217i64.cast(DataType::Float(32)), 16777216);
+    /// ```
+    fn cast(self, dtype: crate::language::DataType) -> Self {
+        match dtype {
+            crate::language::DataType::Float(64) => self as f64 as i64,
+            crate::language::DataType::Float(32) => self as f32 as i64,
+            crate::language::DataType::Float(bits) => panic!("Unsupported float width {}", bits),
+            _ => wrap_integer(self as i128, dtype) as i64,
+        }
+    }
+}
+
+impl Cast for i32 {
+    /// ```
+    /// use glenside::language::interpreter::Cast;
+    /// use glenside::language::DataType;
+    /// assert_eq!(300i32.cast(DataType::Uint(8)), 44);
+    /// ```
+    fn cast(self, dtype: crate::language::DataType) -> Self {
+        match dtype {
+            crate::language::DataType::Float(64) => self,
+            crate::language::DataType::Float(32) => self as f32 as i32,
+            crate::language::DataType::Float(bits) => panic!("Unsupported float width {}", bits),
+            _ => wrap_integer(self as i128, dtype) as i32,
+        }
+    }
+}
+
+/// Trait for types which can hold quantized (i.e. integer) values.
+pub trait QuantizedValue {
+    /// Get the integer value. Panics if the value isn't an integer.
+    fn to_quantized(self) -> i64;
+    /// Convert from an integer value.
+    fn from_quantized(value: i64) -> Self;
+}
+
+impl QuantizedValue for f64 {
+    /// ```
+    /// use glenside::language::interpreter::QuantizedValue;
+    /// assert_eq!((-3f64).to_quantized(), -3);
+    /// assert_eq!(f64::from_quantized(-3), -3.0);
+    /// ```
+    fn to_quantized(self) -> i64 {
+        assert_eq!(self.fract(), 0.0, "Expected an integer, found {}", self);
+        self as i64
+    }
+    fn from_quantized(value: i64) -> Self {
+        value as f64
+    }
+}
+
+impl QuantizedValue for f32 {
+    /// ```should_panic
+    /// use glenside::language::interpreter::QuantizedValue;
+    /// 0.5f32.to_quantized();
+    /// ```
+    fn to_quantized(self) -> i64 {
+        assert_eq!(self.fract(), 0.0, "Expected an integer, found {}", self);
+        self as i64
+    }
+    fn from_quantized(value: i64) -> Self {
+        value as f32
+    }
+}
+
+impl QuantizedValue for i64 {
+    fn to_quantized(self) -> i64 {
+        self
+    }
+    fn from_quantized(value: i64) -> Self {
+        value
+    }
+}
+
+impl QuantizedValue for i32 {
+    fn to_quantized(self) -> i64 {
+        self as i64
+    }
+    fn from_quantized(value: i64) -> Self {
+        value.try_into().unwrap()
+    }
+}
+
+/// Converts a real multiplier into a fixed-point multiplier (a Q31 value in
+/// [0.5, 1)) and a power-of-two shift, as TFLite's `QuantizeMultiplier` does.
+fn quantize_multiplier(real_multiplier: f64) -> (i32, i32) {
+    if real_multiplier == 0.0 {
+        return (0, 0);
+    }
+    // Decompose into a fraction in [0.5, 1) and an exponent (i.e. frexp).
+    let mut shift = real_multiplier.log2().floor() as i32 + 1;
+    let mut fraction = real_multiplier / 2f64.powi(shift);
+    // log2 can be off by one due to floating point error.
+    if fraction >= 1.0 {
+        fraction /= 2.0;
+        shift += 1;
+    } else if fraction < 0.5 {
+        fraction *= 2.0;
+        shift -= 1;
+    }
+    // f64::round rounds ties away from zero, as TfLiteRound does.
+    let mut q_fixed = (fraction * (1i64 << 31) as f64).round() as i64;
+    assert!(q_fixed <= 1i64 << 31);
+    if q_fixed == 1i64 << 31 {
+        q_fixed /= 2;
+        shift += 1;
+    }
+    if shift < -31 {
+        return (0, 0);
+    }
+    (q_fixed as i32, shift)
+}
+
+/// gemmlowp's `SaturatingRoundingDoublingHighMul`: the high 32 bits of
+/// `2 * a * b`, rounded.
+fn saturating_rounding_doubling_high_mul(a: i32, b: i32, rounding: RoundingMode) -> i32 {
+    if a == b && a == std::i32::MIN {
+        return std::i32::MAX;
+    }
+    let ab = a as i64 * b as i64;
+    match rounding {
+        RoundingMode::ToNearest => {
+            let nudge = if ab >= 0 {
+                1i64 << 30
+            } else {
+                1 - (1i64 << 30)
+            };
+            // Division truncates toward zero, as in C++.
+            ((ab + nudge) / (1i64 << 31)) as i32
+        }
+        RoundingMode::Upward => (ab + (1i64 << 30)).div_euclid(1i64 << 31) as i32,
+    }
+}
+
+/// gemmlowp's `RoundingDivideByPOT`: `x / 2^exponent`, rounded.
+fn rounding_divide_by_pot(x: i32, exponent: i32, rounding: RoundingMode) -> i32 {
+    let mask = ((1i64 << exponent) - 1) as i32;
+    let remainder = x & mask;
+    let threshold = (mask >> 1)
+        + match rounding {
+            RoundingMode::ToNearest if x < 0 => 1,
+            _ => 0,
+        };
+    (x >> exponent) + if remainder > threshold { 1 } else { 0 }
+}
+
+/// TFLite's `MultiplyByQuantizedMultiplier`.
+fn multiply_by_quantized_multiplier(
+    x: i32,
+    multiplier: i32,
+    shift: i32,
+    rounding: RoundingMode,
+) -> i32 {
+    let left_shift = if shift > 0 { shift } else { 0 };
+    let right_shift = if shift > 0 { 0 } else { -shift };
+    rounding_divide_by_pot(
+        saturating_rounding_doubling_high_mul(
+            x.wrapping_mul(1 << left_shift),
+            multiplier,
+            rounding,
+        ),
+        right_shift,
+        rounding,
+    )
+}
+
 /// Trait for types which implement the exponential function.
 pub trait Exp {
     /// Calculate exponential function
@@ -1069,6 +3566,12 @@ impl Exp for i64 {
     }
 }
 
+impl Exp for i32 {
+    fn exp(self) -> Self {
+        unreachable!()
+    }
+}
+
 /// Trait for types which implement square root.
 /// TODO(@gussmith23) Does this already exist somewhere?
 pub trait Sqrt {
@@ -1106,20 +3609,97 @@ impl Sqrt for i64 {
     }
 }
 
-#[cfg(test)]
-mod tests {
+impl Sqrt for i32 {
+    fn sqrt(self) -> Self {
+        panic!()
+    }
+}
 
-    use super::*;
-    use approx::AbsDiffEq;
-    use ndarray::array;
-    use std::str::FromStr;
+/// The element types the interpreter computes over: everything it needs of a
+/// scalar, bundled so that functions generic over the element type need only
+/// one bound. Implemented for every type meeting the bounds, which includes
+/// `f64`, `f32`, `i64` and `i32`.
+/// ```
+/// use glenside::language::interpreter::{interpret_from_str, GlensideScalar, Value};
+/// use ndarray::ArrayD;
+/// use std::collections::HashMap;
+///
+/// fn relu_sum<DataType: GlensideScalar>(values: Vec<DataType>) -> DataType {
+///     let mut env = HashMap::default();
+///     env.insert(
+///         "t",
+///         ArrayD::from_shape_vec(vec![values.len()], values).unwrap(),
+///     );
+///     match interpret_from_str("(compute reduce-sum (compute relu (access-tensor t)))", &env) {
+///         Value::Access(a) => *a.tensor.iter().next().unwrap(),
+///         _ => panic!(),
+///     }
+/// }
+///
+/// assert_eq!(relu_sum(vec![1i32, -2, 3]), 4);
+/// assert_eq!(relu_sum(vec![1.5f64, -2.0, 3.0]), 4.5);
+/// ```
+pub trait GlensideScalar:
+    'static
+    + Copy
+    + std::ops::Mul<Output = Self>
+    + std::ops::Div<Output = Self>
+    + std::ops::Neg<Output = Self>
+    + std::iter::Sum
+    + num_traits::identities::One
+    + num_traits::identities::Zero
+    + std::cmp::PartialOrd
+    + num_traits::Bounded
+    + Exp
+    + Sqrt
+    + Cast
+    + QuantizedValue
+    + FromNotNanFloat64Literal
+    + ndarray::ScalarOperand
+{
+    /// Converts a count (e.g. the number of values averaged) to this type.
+    fn from_count(count: usize) -> Self;
+}
 
-    /// Creates a benchmark and a test for the interpreter
-    /// The test does the following:
-    ///  1. Parses $glenside_str as glenside expression
-    ///  2. Creates a new Environment from the vector of (key, value) pairs if
-    ///     present
-    ///  3. Calls the interpreter with the glenside expression and env, and
+impl<T> GlensideScalar for T
+where
+    T: 'static
+        + Copy
+        + std::ops::Mul<Output = T>
+        + std::ops::Div<Output = T>
+        + std::ops::Neg<Output = T>
+        + std::iter::Sum
+        + num_traits::identities::One
+        + num_traits::identities::Zero
+        + std::cmp::PartialOrd
+        + num_traits::Bounded
+        + Exp
+        + Sqrt
+        + Cast
+        + QuantizedValue
+        + FromNotNanFloat64Literal
+        + ndarray::ScalarOperand,
+    usize: AsPrimitive<T>,
+{
+    fn from_count(count: usize) -> Self {
+        count.as_()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::testing::{assert_close, Tolerance};
+    use ndarray::array;
+    use std::str::FromStr;
+
+    /// Creates a benchmark and a test for the interpreter
+    /// The test does the following:
+    ///  1. Parses $glenside_str as glenside expression
+    ///  2. Creates a new Environment from the vector of (key, value) pairs if
+    ///     present
+    ///  3. Calls the interpreter with the glenside expression and env, and
     ///     passes the value to check_correct
     /// $test_name: the name of the created benchmark test
     /// $glenside_str: A string containing the Glenside program
@@ -1162,27 +3742,1112 @@ mod tests {
                 for (key, value) in $env.into_iter() {
                     env.insert(key, value);
                 }
-
-                let expr = RecExpr::<Language>::from_str($glenside_str).unwrap();
-
-                let value = interpret(&expr, expr.as_ref().len() - 1, &env);
-                $check_correct(value);
+
+                let expr = RecExpr::<Language>::from_str($glenside_str).unwrap();
+
+                let value = interpret(&expr, expr.as_ref().len() - 1, &env);
+                $check_correct(value);
+            }
+        };
+        ($(#[$meta:meta])* $test_name: ident, $bench_name:ident, $glenside_str: expr, $check_correct: expr) => {
+            benchmark_and_test!($(#[$meta])* $test_name, $bench_name, $glenside_str, Vec::<(&str, ArrayD<f32>)>::new(), $check_correct);
+        };
+    }
+
+    benchmark_and_test!(
+        compute_elementwise_add_0,
+        bench_compute_elementwise_add_0,
+        "(compute elementwise-add
+        (access (access-tensor t) 0)
+        )",
+        vec![(
+            "t",
+            array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],].into_dyn(),
+        )],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(access_axis, 0);
+                    assert_eq!(
+                        tensor,
+                        array![[1 + -5 + -9, -2 + 6 + 10], [3 + 0 + 11, 0 + 8 + 12]].into_dyn()
+                    );
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        compute_elementwise_mul_0,
+        bench_compute_elementwise_mul_0,
+        "(compute elementwise-mul
+        (access (access-tensor t) 0)
+        )",
+        vec![(
+            "t",
+            array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],].into_dyn(),
+        )],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(access_axis, 0);
+                    assert_eq!(
+                        tensor,
+                        array![[1 * -5 * -9, -2 * 6 * 10], [3 * 0 * 11, 0 * 8 * 12]].into_dyn()
+                    );
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        compute_reduce_sum_0,
+        bench_compute_reduce_sum_0,
+        "(compute reduce-sum
+        (access (access-tensor t) 0)
+        )",
+        vec![(
+            "t",
+            array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],].into_dyn(),
+        )],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(access_axis, 0);
+                    assert_eq!(
+                        tensor,
+                        ndarray::arr0(1 + -2 + 3 + 0 + -5 + 6 + 0 + 8 + -9 + 10 + 11 + 12)
+                            .into_dyn()
+                    );
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        compute_reduce_sum_1,
+        bench_compute_reduce_sum_1,
+        "(compute reduce-sum
+        (access (access-tensor t) 1)
+        )",
+        vec![(
+            "t",
+            array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],].into_dyn(),
+        )],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(access_axis, 1);
+                    assert_eq!(
+                        tensor,
+                        array![1 + -2 + 3 + 0, -5 + 6 + 0 + 8, -9 + 10 + 11 + 12].into_dyn()
+                    );
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        compute_reduce_sum_2,
+        bench_compute_reduce_sum_2,
+        "(compute reduce-sum
+        (access (access-tensor t) 2)
+        )",
+        vec![(
+            "t",
+            array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],].into_dyn(),
+        )],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(access_axis, 2);
+                    assert_eq!(
+                        tensor,
+                        array![[1 + -2, 3 + 0], [-5 + 6, 0 + 8], [-9 + 10, 11 + 12]].into_dyn()
+                    );
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        compute_reduce_sum_3,
+        bench_compute_reduce_sum_3,
+        "(compute reduce-sum
+        (access (access-tensor t) 3)
+        )",
+        vec![(
+            "t",
+            array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],].into_dyn(),
+        )],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(access_axis, 3);
+                    assert_eq!(
+                        tensor,
+                        array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],]
+                            .into_dyn(),
+                    );
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        compute_relu_0,
+        bench_compute_relu_0,
+        "(compute relu
+        (access (access-tensor t) 0)
+        )",
+        vec![(
+            "t",
+            array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],].into_dyn(),
+        )],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(access_axis, 0);
+                    assert_eq!(
+                        tensor,
+                        array![[[1, 0], [3, 0]], [[0, 6], [0, 8]], [[0, 10], [11, 12]],].into_dyn(),
+                    );
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        compute_relu_1,
+        bench_compute_relu_1,
+        "(compute relu
+        (access (access-tensor t) 2)
+        )",
+        vec![(
+            "t",
+            array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],].into_dyn(),
+        )],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(access_axis, 2);
+                    assert_eq!(
+                        tensor,
+                        array![[[1, 0], [3, 0]], [[0, 6], [0, 8]], [[0, 10], [11, 12]],].into_dyn(),
+                    );
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        compute_dot_product_0,
+        bench_compute_dot_product_0,
+        "(compute dot-product
+        (access (access-tensor t) 0)
+        )",
+        vec![(
+            "t",
+            // 3 x 2 x 2
+            array![[[1, 2], [3, 4]], [[5, 6], [7, 8]], [[9, 10], [11, 12]],].into_dyn(),
+        )],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(tensor.shape(), &[] as &[usize]);
+                    assert_eq!(access_axis, 0);
+                    assert_eq!(
+                        tensor,
+                        ndarray::arr0(1 * 5 * 9 + 2 * 6 * 10 + 3 * 7 * 11 + 4 * 8 * 12).into_dyn()
+                    );
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        compute_dot_product_1,
+        bench_compute_dot_product_1,
+        "(compute dot-product
+        (access (access-tensor t) 1)
+        )",
+        vec![(
+            "t",
+            // 3 x 2 x 2
+            array![[[1, 2], [3, 4]], [[5, 6], [7, 8]], [[9, 10], [11, 12]],].into_dyn(),
+        )],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(tensor.shape(), &[3]);
+                    assert_eq!(access_axis, 1);
+                    assert_eq!(
+                        tensor,
+                        array![11, 5 * 7 + 8 * 6, 9 * 11 + 10 * 12].into_dyn()
+                    );
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        compute_dot_product_2,
+        bench_compute_dot_product_2,
+        "(compute dot-product
+            (access (access-tensor t) 2)
+           )",
+        vec![(
+            "t",
+            // 3 x 2 x 2
+            array![[[1, 2], [3, 4]], [[5, 6], [7, 8]], [[9, 10], [11, 12]],].into_dyn(),
+        )],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(tensor.shape(), &[3, 2]);
+                    assert_eq!(access_axis, 2);
+                    assert_eq!(
+                        tensor,
+                        array![[1 * 2, 3 * 4], [5 * 6, 7 * 8], [9 * 10, 11 * 12]].into_dyn()
+                    );
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        access_cartesian_product,
+        bench_access_cartesian_product,
+        "(access-cartesian-product
+        (access (access-tensor t0) 2)
+        (access (access-tensor t1) 2)
+        )",
+        vec![
+            (
+                "t0",
+                // 3 x 2 x 2
+                array![[[1, 2], [3, 4]], [[5, 6], [7, 8]], [[9, 10], [11, 12]],].into_dyn(),
+            ),
+            (
+                "t1",
+                // 2 x 2 x 2
+                array![[[13, 14], [15, 16]], [[17, 18], [19, 20]]].into_dyn(),
+            )
+        ],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(tensor.shape(), &[3, 2, 2, 2, 2, 2]);
+                    assert_eq!(access_axis, 4);
+                    assert_eq!(
+                        tensor.slice(s![0, 0, 0, 0, .., ..]),
+                        array![[1, 2], [13, 14]]
+                    );
+                    assert_eq!(
+                        tensor.slice(s![2, 0, 1, 0, .., ..]),
+                        array![[9, 10], [17, 18]]
+                    );
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        access,
+        bench_access,
+        "(access (access-tensor t) 1)",
+        vec![("t", array![[1., 2.], [3., 4.]].into_dyn())],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(tensor, array![[1., 2.], [3., 4.]].into_dyn());
+                    assert_eq!(access_axis, 1);
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        #[should_panic]
+        access_panic,
+        bench_access_panic,
+        "access (access-tensor t) 3)",
+        vec![("t", array![[1., 2.], [3., 4.]].into_dyn())],
+        |value| value
+    );
+
+    benchmark_and_test!(
+        access_windows,
+        bench_access_windows,
+        "(access-windows
+            (access (access-tensor t) 0)
+            (shape 3 2 2)
+            (shape 1 1 1)
+        )",
+        vec![(
+            "t",
+            array![
+                [[1., 2., 3.], [4., 5., 6.], [7., 8., 9.]],
+                [[10., 11., 12.], [13., 14., 15.], [16., 17., 18.]],
+                [[19., 20., 21.], [22., 23., 24.], [25., 26., 27.]],
+            ]
+            .into_dyn()
+        )],
+        |value| {
+            match value {
+                Value::Access(a) => {
+                    assert_eq!(a.access_axis, 3);
+                    assert_eq!(a.tensor.shape(), &[1, 2, 2, 3, 2, 2]);
+                    assert_eq!(
+                        a.tensor.slice(s![0, 0, 0, .., .., ..]),
+                        array![
+                            [[1., 2.], [4., 5.]],
+                            [[10., 11.], [13., 14.]],
+                            [[19., 20.], [22., 23.]],
+                        ]
+                    );
+                    assert_eq!(
+                        a.tensor.slice(s![0, 1, 0, .., .., ..]),
+                        array![
+                            [[4., 5.], [7., 8.]],
+                            [[13., 14.], [16., 17.]],
+                            [[22., 23.], [25., 26.]],
+                        ]
+                    );
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        access_windows_1,
+        bench_access_windows_1,
+        "(access-windows
+            (access (access-tensor t) 1)
+            (shape 2 2)
+            (shape 1 1)
+        )",
+        vec![(
+            "t",
+            array![
+                [[1., 2., 3.], [4., 5., 6.], [7., 8., 9.]],
+                [[10., 11., 12.], [13., 14., 15.], [16., 17., 18.]],
+                [[19., 20., 21.], [22., 23., 24.], [25., 26., 27.]],
+            ]
+            .into_dyn()
+        )],
+        |value| {
+            match value {
+                Value::Access(a) => {
+                    assert_eq!(a.access_axis, 3);
+                    assert_eq!(a.tensor.shape(), &[3, 2, 2, 2, 2]);
+                    assert_eq!(
+                        a.tensor.slice(s![0, 0, 0, .., ..]),
+                        array![[1., 2.], [4., 5.]]
+                    );
+                    assert_eq!(
+                        a.tensor.slice(s![0, 1, 0, .., ..]),
+                        array![[4., 5.], [7., 8.]]
+                    );
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        access_windows_2,
+        bench_access_windows_2,
+        "(access-windows
+            (access (access-tensor t) 2)
+            (shape 2)
+            (shape 1)
+        )",
+        vec![(
+            "t",
+            array![
+                [[1., 2., 3.], [4., 5., 6.], [7., 8., 9.]],
+                [[10., 11., 12.], [13., 14., 15.], [16., 17., 18.]],
+                [[19., 20., 21.], [22., 23., 24.], [25., 26., 27.]],
+            ]
+            .into_dyn()
+        )],
+        |value| {
+            match value {
+                Value::Access(a) => {
+                    assert_eq!(a.access_axis, 3);
+                    assert_eq!(a.tensor.shape(), &[3, 3, 2, 2]);
+                    assert_eq!(a.tensor.slice(s![0, 0, 0, ..]), array![1., 2.]);
+                    assert_eq!(a.tensor.slice(s![0, 1, 0, ..]), array![4., 5.]);
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        reduce_max_access_windows,
+        bench_reduce_max_access_windows,
+        "(compute reduce-max
+          (access-windows (access (access-tensor t) 1) (shape 2 2) (shape 2 2)))",
+        vec![(
+            "t",
+            array![[
+                [1., 2., 3., 4.],
+                [5., 6., 7., 8.],
+                [9., 10., 11., 12.],
+                [13., 14., 15., 16.]
+            ]]
+            .into_dyn()
+        )],
+        |value| {
+            match value {
+                Value::Access(a) => {
+                    assert_eq!(a.tensor, array![[[6., 8.], [14., 16.]]].into_dyn());
+                    assert_eq!(a.access_axis, 3);
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        dot_product_access_windows,
+        bench_dot_product_access_windows,
+        "(compute dot-product
+          (access-cartesian-product
+           (access (access-tensor weights) 1)
+           (access-squeeze
+            (access-windows (access (access-tensor data) 0) (shape 1 2 2) (shape 1 1 1))
+            0)))",
+        vec![
+            ("data", array![[[1, 2, 3], [4, 5, 6], [7, 8, 9]]].into_dyn()),
+            ("weights", array![[[[1, 0], [0, 1]]]].into_dyn())
+        ],
+        |value| {
+            match value {
+                Value::Access(a) => {
+                    assert_eq!(a.tensor, array![[[6, 8], [12, 14]]].into_dyn());
+                    assert_eq!(a.access_axis, 3);
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    #[test]
+    fn lazy_access_windows_match_materialized() {
+        let mut env = Environment::new();
+        env.insert(
+            "t",
+            ArrayD::from_shape_fn(vec![2, 3, 7, 6], |index| {
+                ((index[0] * 7 + index[1] * 5 + index[2] * 3 + index[3]) % 11) as f64 - 5.
+            }),
+        );
+        env.insert(
+            "w",
+            ArrayD::from_shape_fn(vec![4, 3, 3, 2], |index| {
+                ((index[0] + index[1] * 2 + index[2] * 3 + index[3]) % 5) as f64 - 2.
+            }),
+        );
+        let windows = "(access-windows (access (access-tensor t) 1) (shape 3 3 2) (shape 1 2 3))";
+
+        // Wrapping the windows in an access forces them to be materialized.
+        for (lazy, materialized) in vec![
+            (
+                format!("(compute reduce-sum {})", windows),
+                format!("(compute reduce-sum (access {} 4))", windows),
+            ),
+            (
+                format!("(compute reduce-max {})", windows),
+                format!("(compute reduce-max (access {} 4))", windows),
+            ),
+            (
+                format!("(compute reduce-mean {})", windows),
+                format!("(compute reduce-mean (access {} 4))", windows),
+            ),
+            (
+                format!(
+                    "(compute dot-product
+                      (access-cartesian-product
+                       (access (access-tensor w) 1)
+                       (access-squeeze {} 1)))",
+                    windows
+                ),
+                format!(
+                    "(compute dot-product
+                      (access-cartesian-product
+                       (access (access-tensor w) 1)
+                       (access (access-squeeze {} 1) 3)))",
+                    windows
+                ),
+            ),
+        ] {
+            let lazy = RecExpr::<Language>::from_str(&lazy).unwrap();
+            let materialized = RecExpr::<Language>::from_str(&materialized).unwrap();
+            match (
+                interpret(&lazy, lazy.as_ref().len() - 1, &env),
+                interpret(&materialized, materialized.as_ref().len() - 1, &env),
+            ) {
+                (Value::Access(lazy), Value::Access(materialized)) => {
+                    assert_eq!(lazy.tensor.shape(), materialized.tensor.shape());
+                    assert_eq!(lazy.access_axis, materialized.access_axis);
+                    assert_close(
+                        &lazy.tensor,
+                        &materialized.tensor,
+                        Tolerance::absolute(1e-12),
+                    );
+                }
+                _ => panic!(),
+            }
+        }
+    }
+
+    benchmark_and_test!(shape, bench_shape, "(shape 1 2 3)", |value| {
+        match value {
+            Value::Shape(s) => assert_eq!(s, IxDyn(&[1, 2, 3])),
+            _ => panic!(),
+        }
+    });
+
+    benchmark_and_test!(
+        slice_shape_0,
+        bench_slice_shape_0,
+        "(slice-shape (shape-of t) 0)",
+        vec![("t", array![[1., 2.], [3., 4.]].into_dyn())],
+        |value| {
+            match value {
+                Value::Shape(s) => assert_eq!(s, IxDyn(&[2, 2])),
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        slice_shape_1,
+        bench_slice_shape_1,
+        "(slice-shape (shape-of t) 1)",
+        vec![("t", array![[1., 2.], [3., 4.]].into_dyn())],
+        |value| {
+            match value {
+                Value::Shape(s) => assert_eq!(s, IxDyn(&[2])),
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        slice_shape_2,
+        bench_slice_shape_2,
+        "(slice-shape (shape-of t) 2)",
+        vec![("t", array![[1., 2.], [3., 4.]].into_dyn())],
+        |value| {
+            match value {
+                Value::Shape(s) => assert_eq!(s, IxDyn(&[])),
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        shape_insert_axis_0,
+        bench_shape_insert_axis_0,
+        "(shape-insert-axis (shape 2 3) 0)",
+        |value| {
+            match value {
+                Value::Shape(s) => assert_eq!(s, IxDyn(&[1, 2, 3])),
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        shape_insert_axis_1,
+        bench_shape_insert_axis_1,
+        "(shape-insert-axis (shape 2 3) 1)",
+        |value| {
+            match value {
+                Value::Shape(s) => assert_eq!(s, IxDyn(&[2, 1, 3])),
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        shape_insert_axis_2,
+        bench_shape_insert_axis_2,
+        "(shape-insert-axis (shape 2 3) 2)",
+        |value| {
+            match value {
+                Value::Shape(s) => assert_eq!(s, IxDyn(&[2, 3, 1])),
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        #[should_panic]
+        shape_insert_axis_panic,
+        bench_shape_insert_axis_panic,
+        "(shape-insert-axis (shape 2 3) 3)",
+        |value| { value }
+    );
+
+    benchmark_and_test!(
+        shape_remove_axis_0,
+        bench_shape_remove_axis_0,
+        "(shape-remove-axis (shape 1 2 3) 0)",
+        |value| {
+            match value {
+                Value::Shape(s) => assert_eq!(s, IxDyn(&[2, 3])),
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        shape_remove_axis_1,
+        bench_shape_remove_axis_1,
+        "(shape-remove-axis (shape 1 2 3) 1)",
+        |value| {
+            match value {
+                Value::Shape(s) => assert_eq!(s, IxDyn(&[1, 3])),
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        shape_remove_axis_2,
+        bench_shape_remove_axis_2,
+        "(shape-remove-axis (shape 1 2 3) 2)",
+        |value| {
+            match value {
+                Value::Shape(s) => assert_eq!(s, IxDyn(&[1, 2])),
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        #[should_panic]
+        shape_remove_axis_panic,
+        bench_shape_remove_axis_panic,
+        "(shape-remove-axis (shape 1 2 3) 3)",
+        |value| { value }
+    );
+
+    benchmark_and_test!(
+        shape_concat,
+        bench_shape_concat,
+        "(shape-concat (shape 1 2) (shape-of t))",
+        vec![("t", array![[1., 2.], [3., 4.]].into_dyn())],
+        |value| {
+            match value {
+                Value::Shape(s) => assert_eq!(s, IxDyn(&[1, 2, 2, 2])),
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        shape_dim,
+        bench_shape_dim,
+        "(shape (shape-dim (shape 1 2 3) 2) (shape-dim (shape 1 2 3) 0))",
+        |value| {
+            match value {
+                Value::Shape(s) => assert_eq!(s, IxDyn(&[3, 1])),
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        #[should_panic]
+        shape_dim_panic,
+        bench_shape_dim_panic,
+        "(shape-dim (shape 1 2 3) 3)",
+        |value| { value }
+    );
+
+    benchmark_and_test!(
+        tensor_decl,
+        bench_tensor_decl,
+        "(access (access-tensor (tensor-decl t (shape 2 2))) 1)",
+        vec![("t", array![[1, 2], [3, 4]].into_dyn())],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(tensor, array![[1, 2], [3, 4]].into_dyn());
+                    assert_eq!(access_axis, 1);
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        #[should_panic(expected = "Declared shape does not match the shape of the tensor")]
+        tensor_decl_panic,
+        bench_tensor_decl_panic,
+        "(access-tensor (tensor-decl t (shape 2 3)))",
+        vec![("t", array![[1, 2], [3, 4]].into_dyn())],
+        |value| { value }
+    );
+
+    benchmark_and_test!(
+        usize_arithmetic,
+        bench_usize_arithmetic,
+        "(shape (+ 2 3) (- 7 3) (* 2 4) (/ 9 2))",
+        |value| {
+            match value {
+                Value::Shape(s) => assert_eq!(s, IxDyn(&[5, 4, 8, 4])),
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        usize_arithmetic_padded_size,
+        bench_usize_arithmetic_padded_size,
+        // Round 30 up to the closest multiple of 16.
+        "(* (/ (+ (shape-dim (shape-of t) 1) 15) 16) 16)",
+        vec![("t", ndarray::ArrayD::<f64>::zeros(vec![2, 30]))],
+        |value| {
+            match value {
+                Value::Num(n) => assert_eq!(n, 32),
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        #[should_panic(expected = "Cannot subtract 3 from 2")]
+        usize_sub_panic,
+        bench_usize_sub_panic,
+        "(- 2 3)",
+        |value| { value }
+    );
+
+    benchmark_and_test!(
+        shape_of,
+        bench_shape_of,
+        "(shape-of t)",
+        vec![("t", array![[1., 2.], [3., 4.]].into_dyn())],
+        |value| {
+            match value {
+                Value::Shape(s) => assert_eq!(s, IxDyn(&[2, 2])),
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(usize, bench_usize, "23", |value| {
+        match value {
+            Value::Num(23) => (),
+            _ => panic!(),
+        }
+    });
+
+    benchmark_and_test!(
+        symbol,
+        bench_symbol,
+        "t",
+        vec![("t", array![[1., 2.], [3., 4.]].into_dyn())],
+        |value| {
+            match value {
+                Value::Tensor(t) => assert_eq!(t, array![[1., 2.], [3., 4.]].into_dyn()),
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        access_tensor,
+        bench_access_tensor,
+        "(access-tensor t)",
+        vec![("t", array![[1., 2.], [3., 4.]].into_dyn())],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(tensor, array![[1., 2.], [3., 4.]].into_dyn());
+                    assert_eq!(access_axis, 0);
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(pad_type, bench_pad_type, "zero-padding", |value| {
+        match value {
+            Value::PadType(PadType::ZeroPadding) => (),
+            _ => panic!(),
+        }
+    });
+
+    benchmark_and_test!(
+        access_pad,
+        bench_access_pad,
+        "(access-pad (access-tensor t) zero-padding 0 2 4)",
+        vec![("t", array![[1., 2.], [3., 4.]].into_dyn())],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(
+                        tensor,
+                        array![
+                            [0., 0.],
+                            [0., 0.],
+                            [1., 2.],
+                            [3., 4.],
+                            [0., 0.],
+                            [0., 0.],
+                            [0., 0.],
+                            [0., 0.]
+                        ]
+                        .into_dyn()
+                    );
+                    assert_eq!(access_axis, 0);
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        compute_reduce_max_0,
+        bench_compute_reduce_max_0,
+        "(compute reduce-max
+            (access (access-tensor t) 0)
+           )",
+        vec![(
+            "t",
+            array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],].into_dyn()
+        )],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(access_axis, 0);
+                    assert_eq!(tensor, ndarray::arr0(12).into_dyn());
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        compute_reduce_max_1,
+        bench_compute_reduce_max_1,
+        "(compute reduce-max
+            (access (access-tensor t) 1)
+           )",
+        vec![(
+            "t",
+            array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],].into_dyn()
+        )],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(access_axis, 1);
+                    assert_eq!(tensor, array![3, 8, 12].into_dyn());
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        compute_reduce_max_2,
+        bench_compute_reduce_max_2,
+        "(compute reduce-max
+            (access (access-tensor t) 2)
+           )",
+        vec![(
+            "t",
+            array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],].into_dyn()
+        )],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(access_axis, 2);
+                    assert_eq!(tensor, array![[1, 3], [6, 8], [10, 12]].into_dyn());
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        compute_reduce_max_3,
+        bench_compute_reduce_max_3,
+        "(compute reduce-max
+            (access (access-tensor t) 3)
+            )",
+        vec![(
+            "t",
+            array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],].into_dyn()
+        )],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(access_axis, 3);
+                    assert_eq!(
+                        tensor,
+                        array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],]
+                            .into_dyn(),
+                    );
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        access_squeeze_0,
+        bench_access_squeeze_0,
+        "(access-squeeze (access-tensor t) 0)",
+        vec![("t", array![[1., 2.]].into_dyn())],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(tensor, array![1., 2.].into_dyn());
+                    assert_eq!(access_axis, 0);
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        access_squeeze_1,
+        bench_access_squeeze_1,
+        "(access-squeeze (access (access-tensor t) 1) 0)",
+        vec![("t", array![[1., 2.]].into_dyn())],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(tensor, array![1., 2.].into_dyn());
+                    assert_eq!(access_axis, 0);
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        #[should_panic]
+        access_squeeze_panic,
+        bench_access_squeeze_panic,
+        "(access-squeeze (access (access-tensor t) 1) 1)",
+        vec![("t", array![[1., 2.]].into_dyn())],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(tensor, array![1., 2.].into_dyn());
+                    assert_eq!(access_axis, 0);
+                }
+                _ => panic!(),
             }
-        };
-        ($(#[$meta:meta])* $test_name: ident, $bench_name:ident, $glenside_str: expr, $check_correct: expr) => {
-            benchmark_and_test!($(#[$meta])* $test_name, $bench_name, $glenside_str, Vec::<(&str, ArrayD<f32>)>::new(), $check_correct);
-        };
-    }
+        }
+    );
 
     benchmark_and_test!(
-        compute_elementwise_add_0,
-        bench_compute_elementwise_add_0,
-        "(compute elementwise-add
-        (access (access-tensor t) 0)
-        )",
+        max_pool2d,
+        bench_max_pool2d,
+        "(compute reduce-max
+            (access-windows (access (access-tensor t) 0) (shape 1 2 2) (shape 1 2 2))
+           )",
         vec![(
             "t",
-            array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],].into_dyn(),
+            array![
+                [[1, -2, -4, 5], [3, 6, -8, 0]],
+                [[-5, 6, -8, -10], [0, 0, 0, 8]],
+                [[-9, -20, -15, 10], [-1, 2, 11, 12]],
+            ]
+            .into_dyn()
         )],
         |value| {
             match value {
@@ -1190,11 +4855,36 @@ mod tests {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(access_axis, 0);
+                    assert_eq!(access_axis, 3);
+                    // Checking that output is CHW.
+                    assert_eq!(tensor.shape(), [3, 1, 2]);
+                    assert_eq!(tensor, array![[[6, 5]], [[6, 8]], [[2, 12]]].into_dyn(),);
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        access_pair_0,
+        bench_access_pair_0,
+        "(access-pair (access (access-tensor a) 0) (access (access-tensor b) 0))",
+        vec![
+            ("a", array![[1, 2], [3, 4]].into_dyn()),
+            ("b", array![[5, 6], [7, 8]].into_dyn())
+        ],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(tensor.shape(), [2, 2, 2]);
                     assert_eq!(
                         tensor,
-                        array![[1 + -5 + -9, -2 + 6 + 10], [3 + 0 + 11, 0 + 8 + 12]].into_dyn()
+                        array![[[1, 2], [3, 4]], [[5, 6], [7, 8]]].into_dyn()
                     );
+                    assert_eq!(access_axis, 0);
                 }
                 _ => panic!(),
             }
@@ -1202,26 +4892,25 @@ mod tests {
     );
 
     benchmark_and_test!(
-        compute_elementwise_mul_0,
-        bench_compute_elementwise_mul_0,
-        "(compute elementwise-mul
-        (access (access-tensor t) 0)
-        )",
-        vec![(
-            "t",
-            array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],].into_dyn(),
-        )],
+        access_pair_1,
+        bench_access_pair_1,
+        "(access-pair (access (access-tensor a) 1) (access (access-tensor b) 1))",
+        vec![
+            ("a", array![[1, 2], [3, 4]].into_dyn()),
+            ("b", array![[5, 6], [7, 8]].into_dyn())
+        ],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(access_axis, 0);
+                    assert_eq!(tensor.shape(), [2, 2, 2]);
                     assert_eq!(
                         tensor,
-                        array![[1 * -5 * -9, -2 * 6 * 10], [3 * 0 * 11, 0 * 8 * 12]].into_dyn()
+                        array![[[1, 2], [5, 6]], [[3, 4], [7, 8]]].into_dyn()
                     );
+                    assert_eq!(access_axis, 1);
                 }
                 _ => panic!(),
             }
@@ -1229,27 +4918,25 @@ mod tests {
     );
 
     benchmark_and_test!(
-        compute_reduce_sum_0,
-        bench_compute_reduce_sum_0,
-        "(compute reduce-sum
-        (access (access-tensor t) 0)
-        )",
-        vec![(
-            "t",
-            array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],].into_dyn(),
-        )],
+        access_pair_2,
+        bench_access_pair_2,
+        "(access-pair (access (access-tensor a) 2) (access (access-tensor b) 2))",
+        vec![
+            ("a", array![[1, 2], [3, 4]].into_dyn()),
+            ("b", array![[5, 6], [7, 8]].into_dyn())
+        ],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(access_axis, 0);
+                    assert_eq!(tensor.shape(), [2, 2, 2]);
                     assert_eq!(
                         tensor,
-                        ndarray::arr0(1 + -2 + 3 + 0 + -5 + 6 + 0 + 8 + -9 + 10 + 11 + 12)
-                            .into_dyn()
+                        array![[[1, 5], [2, 6]], [[3, 7], [4, 8]]].into_dyn()
                     );
+                    assert_eq!(access_axis, 2);
                 }
                 _ => panic!(),
             }
@@ -1257,26 +4944,160 @@ mod tests {
     );
 
     benchmark_and_test!(
-        compute_reduce_sum_1,
-        bench_compute_reduce_sum_1,
-        "(compute reduce-sum
-        (access (access-tensor t) 1)
-        )",
-        vec![(
-            "t",
-            array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],].into_dyn(),
-        )],
+        #[should_panic]
+        access_pair_panic,
+        bench_access_pair_panic,
+        "(access-pair (access (access-tensor a) 2) (access (access-tensor b) 2))",
+        vec![
+            ("a", array![[1], [3]].into_dyn()),
+            ("b", array![[5, 6], [7, 8]].into_dyn())
+        ],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(access_axis, 1);
+                    assert_eq!(tensor.shape(), [2, 2, 2]);
                     assert_eq!(
                         tensor,
-                        array![1 + -2 + 3 + 0, -5 + 6 + 0 + 8, -9 + 10 + 11 + 12].into_dyn()
+                        array![[[1, 5], [2, 6]], [[3, 7], [4, 8]]].into_dyn()
                     );
+                    assert_eq!(access_axis, 2);
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        access_insert_axis_0,
+        bench_access_insert_axis_0,
+        "(access-insert-axis (access (access-tensor t) 0) 0)",
+        vec![("t", array![1, 2].into_dyn())],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(tensor, array![[1, 2]].into_dyn());
+                    assert_eq!(access_axis, 1);
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        access_insert_axis_1,
+        bench_access_insert_axis_1,
+        "(access-insert-axis (access (access-tensor t) 0) 1)",
+        vec![("t", array![1, 2].into_dyn())],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(tensor, array![[1], [2]].into_dyn());
+                    assert_eq!(access_axis, 0);
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        access_reverse,
+        bench_access_reverse,
+        "(access-reverse (access (access-tensor t) 1) 1)",
+        vec![("t", array![[1, 2, 3], [4, 5, 6]].into_dyn())],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(tensor, array![[3, 2, 1], [6, 5, 4]].into_dyn());
+                    assert_eq!(access_axis, 1);
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        access_broadcast,
+        bench_access_broadcast,
+        "(access-broadcast (access (access-tensor t) 0) (access-shape (shape 2 2) (shape)))",
+        vec![("t", array![[1, 2]].into_dyn())],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(tensor, array![[1, 2], [1, 2]].into_dyn());
+                    assert_eq!(access_axis, 0);
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        #[should_panic]
+        access_broadcast_panic,
+        bench_access_broadcast_panic,
+        "(access-broadcast (access (access-tensor t) 0) (shape 2 2 2))",
+        vec![("t", array![[1, 2]].into_dyn())],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(tensor, array![[1, 2], [1, 2]].into_dyn());
+                    assert_eq!(access_axis, 0);
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        access_transpose_0,
+        bench_access_transpose_0,
+        "(access-transpose (access (access-tensor t) 0) (list 1 0))",
+        vec![("t", array![[1, 2]].into_dyn())],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(tensor, array![[1], [2]].into_dyn());
+                    assert_eq!(access_axis, 0);
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        access_transpose_1,
+        bench_access_transpose_1,
+        "(access-transpose (access (access-tensor t) 0) (list 1 0))",
+        vec![("t", array![[1, 2]].into_dyn())],
+        |value| {
+            match value {
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(tensor, array![[1], [2]].into_dyn());
+                    assert_eq!(access_axis, 0);
                 }
                 _ => panic!(),
             }
@@ -1284,26 +5105,18 @@ mod tests {
     );
 
     benchmark_and_test!(
-        compute_reduce_sum_2,
-        bench_compute_reduce_sum_2,
-        "(compute reduce-sum
-        (access (access-tensor t) 2)
-        )",
-        vec![(
-            "t",
-            array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],].into_dyn(),
-        )],
+        access_transpose_2,
+        bench_access_transpose_2,
+        "(access-transpose (access (access-tensor t) 0) (list 1 0))",
+        vec![("t", array![[2, 3], [1, 2]].into_dyn())],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(access_axis, 2);
-                    assert_eq!(
-                        tensor,
-                        array![[1 + -2, 3 + 0], [-5 + 6, 0 + 8], [-9 + 10, 11 + 12]].into_dyn()
-                    );
+                    assert_eq!(tensor, array![[2, 1], [3, 2]].into_dyn());
+                    assert_eq!(access_axis, 0);
                 }
                 _ => panic!(),
             }
@@ -1311,27 +5124,36 @@ mod tests {
     );
 
     benchmark_and_test!(
-        compute_reduce_sum_3,
-        bench_compute_reduce_sum_3,
-        "(compute reduce-sum
-        (access (access-tensor t) 3)
-        )",
-        vec![(
-            "t",
-            array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],].into_dyn(),
-        )],
+        #[should_panic]
+        access_transpose_panic_0,
+        bench_access_transpose_panic_0,
+        "(access-transpose (access (access-tensor t) 0) (list 1 0 2))",
+        vec![("t", array![[2, 3], [1, 2]].into_dyn())],
+        |value| { value }
+    );
+
+    benchmark_and_test!(
+        #[should_panic]
+        access_transpose_panic_1,
+        bench_access_transpose_panic_1,
+        "(access-transpose (access (access-tensor t) 0) (list 1 1))",
+        vec![("t", array![[2, 3], [1, 2]].into_dyn())],
+        |value| { value }
+    );
+
+    benchmark_and_test!(
+        access_transpose_negative_axes,
+        bench_access_transpose_negative_axes,
+        "(access-transpose (access (access-tensor t) 0) (list -1 0))",
+        vec![("t", array![[2, 3], [1, 2]].into_dyn())],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(access_axis, 3);
-                    assert_eq!(
-                        tensor,
-                        array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],]
-                            .into_dyn(),
-                    );
+                    assert_eq!(tensor, array![[2, 1], [3, 2]].into_dyn());
+                    assert_eq!(access_axis, 0);
                 }
                 _ => panic!(),
             }
@@ -1339,14 +5161,12 @@ mod tests {
     );
 
     benchmark_and_test!(
-        compute_relu_0,
-        bench_compute_relu_0,
-        "(compute relu
-        (access (access-tensor t) 0)
-        )",
+        compute_softmax,
+        bench_compute_softmax,
+        "(compute softmax (access (access-tensor t) 1))",
         vec![(
             "t",
-            array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],].into_dyn(),
+            array![[0.4597965, 0.8250755], [0.14535584, 0.16271448]].into_dyn()
         )],
         |value| {
             match value {
@@ -1354,11 +5174,12 @@ mod tests {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(access_axis, 0);
-                    assert_eq!(
-                        tensor,
-                        array![[[1, 0], [3, 0]], [[0, 6], [0, 8]], [[0, 10], [11, 12]],].into_dyn(),
+                    assert_close(
+                        &tensor,
+                        &array![[0.40968227, 0.5903177], [0.49566042, 0.5043395]].into_dyn(),
+                        Tolerance::absolute(1e-7),
                     );
+                    assert_eq!(access_axis, 1);
                 }
                 _ => panic!(),
             }
@@ -1366,25 +5187,30 @@ mod tests {
     );
 
     benchmark_and_test!(
-        compute_relu_1,
-        bench_compute_relu_1,
-        "(compute relu
-        (access (access-tensor t) 2)
-        )",
+        access_flatten_0,
+        bench_access_flatten_0,
+        "(access-flatten (access (access-tensor t) 0))",
         vec![(
             "t",
-            array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],].into_dyn(),
+            ndarray::ArrayD::from_shape_vec(
+                vec![2, 2, 4, 6, 10],
+                (0..2 * 2 * 4 * 6 * 10).collect(),
+            )
+            .unwrap(),
         )],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
-                    access_axis,
+                    access_axis: _,
                 }) => {
-                    assert_eq!(access_axis, 2);
                     assert_eq!(
                         tensor,
-                        array![[[1, 0], [3, 0]], [[0, 6], [0, 8]], [[0, 10], [11, 12]],].into_dyn(),
+                        ndarray::ArrayD::from_shape_vec(
+                            vec![2 * 2 * 4 * 6 * 10],
+                            (0..2 * 2 * 4 * 6 * 10).collect(),
+                        )
+                        .unwrap(),
                     );
                 }
                 _ => panic!(),
@@ -1393,27 +5219,30 @@ mod tests {
     );
 
     benchmark_and_test!(
-        compute_dot_product_0,
-        bench_compute_dot_product_0,
-        "(compute dot-product
-        (access (access-tensor t) 0)
-        )",
+        access_flatten_1,
+        bench_access_flatten_1,
+        "(access-flatten (access (access-tensor t) 1))",
         vec![(
             "t",
-            // 3 x 2 x 2
-            array![[[1, 2], [3, 4]], [[5, 6], [7, 8]], [[9, 10], [11, 12]],].into_dyn(),
+            ndarray::ArrayD::from_shape_vec(
+                vec![2, 2, 4, 6, 10],
+                (0..2 * 2 * 4 * 6 * 10).collect(),
+            )
+            .unwrap(),
         )],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
-                    access_axis,
+                    access_axis: _,
                 }) => {
-                    assert_eq!(tensor.shape(), &[] as &[usize]);
-                    assert_eq!(access_axis, 0);
                     assert_eq!(
                         tensor,
-                        ndarray::arr0(1 * 5 * 9 + 2 * 6 * 10 + 3 * 7 * 11 + 4 * 8 * 12).into_dyn()
+                        ndarray::ArrayD::from_shape_vec(
+                            vec![2, 2 * 4 * 6 * 10],
+                            (0..2 * 2 * 4 * 6 * 10).collect(),
+                        )
+                        .unwrap(),
                     );
                 }
                 _ => panic!(),
@@ -1422,27 +5251,30 @@ mod tests {
     );
 
     benchmark_and_test!(
-        compute_dot_product_1,
-        bench_compute_dot_product_1,
-        "(compute dot-product
-        (access (access-tensor t) 1)
-        )",
+        access_flatten_2,
+        bench_access_flatten_2,
+        "(access-flatten (access (access-tensor t) 2))",
         vec![(
             "t",
-            // 3 x 2 x 2
-            array![[[1, 2], [3, 4]], [[5, 6], [7, 8]], [[9, 10], [11, 12]],].into_dyn(),
+            ndarray::ArrayD::from_shape_vec(
+                vec![2, 2, 4, 6, 10],
+                (0..2 * 2 * 4 * 6 * 10).collect(),
+            )
+            .unwrap(),
         )],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
-                    access_axis,
+                    access_axis: _,
                 }) => {
-                    assert_eq!(tensor.shape(), &[3]);
-                    assert_eq!(access_axis, 1);
                     assert_eq!(
                         tensor,
-                        array![11, 5 * 7 + 8 * 6, 9 * 11 + 10 * 12].into_dyn()
+                        ndarray::ArrayD::from_shape_vec(
+                            vec![2 * 2, 4 * 6 * 10],
+                            (0..2 * 2 * 4 * 6 * 10).collect(),
+                        )
+                        .unwrap(),
                     );
                 }
                 _ => panic!(),
@@ -1451,27 +5283,30 @@ mod tests {
     );
 
     benchmark_and_test!(
-        compute_dot_product_2,
-        bench_compute_dot_product_2,
-        "(compute dot-product
-            (access (access-tensor t) 2)
-           )",
+        access_flatten_3,
+        bench_access_flatten_3,
+        "(access-flatten (access (access-tensor t) 5))",
         vec![(
             "t",
-            // 3 x 2 x 2
-            array![[[1, 2], [3, 4]], [[5, 6], [7, 8]], [[9, 10], [11, 12]],].into_dyn(),
+            ndarray::ArrayD::from_shape_vec(
+                vec![2, 2, 4, 6, 10],
+                (0..2 * 2 * 4 * 6 * 10).collect(),
+            )
+            .unwrap(),
         )],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
-                    access_axis,
+                    access_axis: _,
                 }) => {
-                    assert_eq!(tensor.shape(), &[3, 2]);
-                    assert_eq!(access_axis, 2);
                     assert_eq!(
                         tensor,
-                        array![[1 * 2, 3 * 4], [5 * 6, 7 * 8], [9 * 10, 11 * 12]].into_dyn()
+                        ndarray::ArrayD::from_shape_vec(
+                            vec![2 * 2 * 4 * 6 * 10],
+                            (0..2 * 2 * 4 * 6 * 10).collect(),
+                        )
+                        .unwrap(),
                     );
                 }
                 _ => panic!(),
@@ -1479,23 +5314,22 @@ mod tests {
         }
     );
 
+    // A 2x2 convolution of a 3x3 image with two filters, as an im2col-style
+    // matrix multiplication: the flattened windows are multiplied with the
+    // flattened filters.
     benchmark_and_test!(
-        access_cartesian_product,
-        bench_access_cartesian_product,
-        "(access-cartesian-product
-        (access (access-tensor t0) 2)
-        (access (access-tensor t1) 2)
-        )",
+        access_flatten_im2col,
+        bench_access_flatten_im2col,
+        "(compute dot-product
+          (access-cartesian-product
+           (access-flatten
+            (access-windows (access (access-tensor t) 0) (shape 1 2 2) (shape 1 1 1)))
+           (access-flatten (access (access-tensor w) 1))))",
         vec![
+            ("t", array![[[1, 2, 3], [4, 5, 6], [7, 8, 9]]].into_dyn()),
             (
-                "t0",
-                // 3 x 2 x 2
-                array![[[1, 2], [3, 4]], [[5, 6], [7, 8]], [[9, 10], [11, 12]],].into_dyn(),
-            ),
-            (
-                "t1",
-                // 2 x 2 x 2
-                array![[[13, 14], [15, 16]], [[17, 18], [19, 20]]].into_dyn(),
+                "w",
+                array![[[[1, 0], [0, 0]]], [[[1, 1], [1, 1]]]].into_dyn()
             )
         ],
         |value| {
@@ -1504,16 +5338,11 @@ mod tests {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(tensor.shape(), &[3, 2, 2, 2, 2, 2]);
-                    assert_eq!(access_axis, 4);
-                    assert_eq!(
-                        tensor.slice(s![0, 0, 0, 0, .., ..]),
-                        array![[1, 2], [13, 14]]
-                    );
                     assert_eq!(
-                        tensor.slice(s![2, 0, 1, 0, .., ..]),
-                        array![[9, 10], [17, 18]]
+                        tensor,
+                        array![[1, 12], [2, 16], [4, 24], [5, 28]].into_dyn()
                     );
+                    assert_eq!(access_axis, 2);
                 }
                 _ => panic!(),
             }
@@ -1521,107 +5350,58 @@ mod tests {
     );
 
     benchmark_and_test!(
-        access,
-        bench_access,
-        "(access (access-tensor t) 1)",
-        vec![("t", array![[1., 2.], [3., 4.]].into_dyn())],
+        access_flatten_transposed,
+        bench_access_flatten_transposed,
+        "(access-flatten (access (access-transpose (access-tensor t) (list 1 0)) 0))",
+        vec![("t", array![[1, 2, 3], [4, 5, 6]].into_dyn())],
         |value| {
             match value {
-                Value::Access(Access {
-                    tensor,
-                    access_axis,
-                }) => {
-                    assert_eq!(tensor, array![[1., 2.], [3., 4.]].into_dyn());
-                    assert_eq!(access_axis, 1);
-                }
+                // Flattened in row-major order, although the transposed tensor
+                // is column-major.
+                Value::Access(a) => assert_eq!(a.tensor, array![1, 4, 2, 5, 3, 6].into_dyn()),
                 _ => panic!(),
             }
         }
     );
 
     benchmark_and_test!(
-        #[should_panic]
-        access_panic,
-        bench_access_panic,
-        "access (access-tensor t) 3)",
-        vec![("t", array![[1., 2.], [3., 4.]].into_dyn())],
-        |value| value
-    );
-
-    benchmark_and_test!(
-        access_windows,
-        bench_access_windows,
-        "(access-windows
-            (access (access-tensor t) 0)
-            (shape 3 2 2)
-            (shape 1 1 1)
-        )",
+        compute_reduce_sum_transposed,
+        bench_compute_reduce_sum_transposed,
+        "(compute reduce-sum (access (access-transpose (access-tensor t) (list 1 0 2)) 1))",
         vec![(
             "t",
-            array![
-                [[1., 2., 3.], [4., 5., 6.], [7., 8., 9.]],
-                [[10., 11., 12.], [13., 14., 15.], [16., 17., 18.]],
-                [[19., 20., 21.], [22., 23., 24.], [25., 26., 27.]],
-            ]
-            .into_dyn()
+            array![[[1, 2, 3], [4, 5, 6]], [[7, 8, 9], [10, 11, 12]]].into_dyn()
         )],
-        |value| {
-            match value {
-                Value::Access(a) => {
-                    assert_eq!(a.access_axis, 3);
-                    assert_eq!(a.tensor.shape(), &[1, 2, 2, 3, 2, 2]);
-                    assert_eq!(
-                        a.tensor.slice(s![0, 0, 0, .., .., ..]),
-                        array![
-                            [[1., 2.], [4., 5.]],
-                            [[10., 11.], [13., 14.]],
-                            [[19., 20.], [22., 23.]],
-                        ]
-                    );
-                    assert_eq!(
-                        a.tensor.slice(s![0, 1, 0, .., .., ..]),
-                        array![
-                            [[4., 5.], [7., 8.]],
-                            [[13., 14.], [16., 17.]],
-                            [[22., 23.], [25., 26.]],
-                        ]
-                    );
-                }
+        |value| {
+            match value {
+                Value::Access(a) => assert_eq!(a.tensor, array![6 + 24, 15 + 33].into_dyn()),
                 _ => panic!(),
             }
         }
     );
 
     benchmark_and_test!(
-        access_windows_1,
-        bench_access_windows_1,
-        "(access-windows
-            (access (access-tensor t) 1)
-            (shape 2 2)
-            (shape 1 1)
-        )",
+        compute_reduce_mean_0,
+        bench_compute_reduce_mean_0,
+        "(compute reduce-mean (access (access-tensor t) 0))",
         vec![(
             "t",
-            array![
-                [[1., 2., 3.], [4., 5., 6.], [7., 8., 9.]],
-                [[10., 11., 12.], [13., 14., 15.], [16., 17., 18.]],
-                [[19., 20., 21.], [22., 23., 24.], [25., 26., 27.]],
-            ]
-            .into_dyn()
+            array![[[1f64, 2f64], [3f64, 4f64]], [[5f64, 6f64], [7f64, 8f64]]].into_dyn()
         )],
         |value| {
             match value {
-                Value::Access(a) => {
-                    assert_eq!(a.access_axis, 3);
-                    assert_eq!(a.tensor.shape(), &[3, 2, 2, 2, 2]);
-                    assert_eq!(
-                        a.tensor.slice(s![0, 0, 0, .., ..]),
-                        array![[1., 2.], [4., 5.]]
-                    );
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
                     assert_eq!(
-                        a.tensor.slice(s![0, 1, 0, .., ..]),
-                        array![[4., 5.], [7., 8.]]
+                        tensor,
+                        ndarray::arr0(
+                            (1f64 + 2f64 + 3f64 + 4f64 + 5f64 + 6f64 + 7f64 + 8f64) / 8f64
+                        )
+                        .into_dyn(),
                     );
+                    assert_eq!(access_axis, 0);
                 }
                 _ => panic!(),
             }
@@ -1629,254 +5409,367 @@ mod tests {
     );
 
     benchmark_and_test!(
-        access_windows_2,
-        bench_access_windows_2,
-        "(access-windows
-            (access (access-tensor t) 2)
-            (shape 2)
-            (shape 1)
-        )",
+        compute_reduce_mean_1,
+        bench_compute_reduce_mean_1,
+        "(compute reduce-mean (access (access-tensor t) 1))",
         vec![(
             "t",
-            array![
-                [[1., 2., 3.], [4., 5., 6.], [7., 8., 9.]],
-                [[10., 11., 12.], [13., 14., 15.], [16., 17., 18.]],
-                [[19., 20., 21.], [22., 23., 24.], [25., 26., 27.]],
-            ]
-            .into_dyn()
+            array![[[1f64, 2f64], [3f64, 4f64]], [[5f64, 6f64], [7f64, 8f64]]].into_dyn()
         )],
         |value| {
             match value {
-                Value::Access(a) => {
-                    assert_eq!(a.access_axis, 3);
-                    assert_eq!(a.tensor.shape(), &[3, 3, 2, 2]);
-                    assert_eq!(a.tensor.slice(s![0, 0, 0, ..]), array![1., 2.]);
-                    assert_eq!(a.tensor.slice(s![0, 1, 0, ..]), array![4., 5.]);
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(
+                        tensor,
+                        array![
+                            (1f64 + 2f64 + 3f64 + 4f64) / 4f64,
+                            (5f64 + 6f64 + 7f64 + 8f64) / 4f64
+                        ]
+                        .into_dyn(),
+                    );
+                    assert_eq!(access_axis, 1);
                 }
                 _ => panic!(),
             }
         }
     );
 
-    benchmark_and_test!(shape, bench_shape, "(shape 1 2 3)", |value| {
-        match value {
-            Value::Shape(s) => assert_eq!(s, IxDyn(&[1, 2, 3])),
-            _ => panic!(),
-        }
-    });
-
     benchmark_and_test!(
-        slice_shape_0,
-        bench_slice_shape_0,
-        "(slice-shape (shape-of t) 0)",
-        vec![("t", array![[1., 2.], [3., 4.]].into_dyn())],
+        compute_reduce_mean_2,
+        bench_compute_reduce_mean_2,
+        "(compute reduce-mean (access (access-tensor t) 2))",
+        vec![(
+            "t",
+            array![[[1f64, 2f64], [3f64, 4f64]], [[5f64, 6f64], [7f64, 8f64]]].into_dyn()
+        )],
         |value| {
             match value {
-                Value::Shape(s) => assert_eq!(s, IxDyn(&[2, 2])),
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(
+                        tensor,
+                        array![
+                            [(1f64 + 2f64) / 2f64, (3f64 + 4f64) / 2f64],
+                            [(5f64 + 6f64) / 2f64, (7f64 + 8f64) / 2f64]
+                        ]
+                        .into_dyn(),
+                    );
+                    assert_eq!(access_axis, 2);
+                }
                 _ => panic!(),
             }
         }
     );
 
     benchmark_and_test!(
-        slice_shape_1,
-        bench_slice_shape_1,
-        "(slice-shape (shape-of t) 1)",
-        vec![("t", array![[1., 2.], [3., 4.]].into_dyn())],
+        compute_reduce_mean_3,
+        bench_compute_reduce_mean_3,
+        "(compute reduce-mean (access (access-tensor t) 3))",
+        vec![(
+            "t",
+            array![[[1f64, 2f64], [3f64, 4f64]], [[5f64, 6f64], [7f64, 8f64]]].into_dyn()
+        )],
         |value| {
             match value {
-                Value::Shape(s) => assert_eq!(s, IxDyn(&[2])),
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(
+                        tensor,
+                        array![[[1f64, 2f64], [3f64, 4f64]], [[5f64, 6f64], [7f64, 8f64]]]
+                            .into_dyn(),
+                    );
+                    assert_eq!(access_axis, 3);
+                }
                 _ => panic!(),
             }
         }
     );
 
     benchmark_and_test!(
-        slice_shape_2,
-        bench_slice_shape_2,
-        "(slice-shape (shape-of t) 2)",
+        access_pad_min_padding,
+        bench_access_pad_min_padding,
+        "(access-pad (access-tensor t) min-padding 0 2 4)",
         vec![("t", array![[1., 2.], [3., 4.]].into_dyn())],
         |value| {
             match value {
-                Value::Shape(s) => assert_eq!(s, IxDyn(&[])),
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(
+                        tensor,
+                        array![
+                            [std::f64::MIN, std::f64::MIN],
+                            [std::f64::MIN, std::f64::MIN],
+                            [1., 2.],
+                            [3., 4.],
+                            [std::f64::MIN, std::f64::MIN],
+                            [std::f64::MIN, std::f64::MIN],
+                            [std::f64::MIN, std::f64::MIN],
+                            [std::f64::MIN, std::f64::MIN]
+                        ]
+                        .into_dyn()
+                    );
+                    assert_eq!(access_axis, 0);
+                }
                 _ => panic!(),
             }
         }
     );
 
     benchmark_and_test!(
-        shape_insert_axis_0,
-        bench_shape_insert_axis_0,
-        "(shape-insert-axis (shape 2 3) 0)",
+        compute_elementwise_div,
+        bench_compute_elementwise_div,
+        "(compute elementwise-div
+            (access (access-tensor t) 0)
+           )",
+        vec![(
+            "t",
+            array![
+                [[1f32, -2f32], [3f32, 1f32]],
+                [[-5f32, 6f32], [1f32, 8f32]],
+                [[-9f32, 10f32], [11f32, 12f32]],
+            ]
+            .into_dyn()
+        )],
         |value| {
             match value {
-                Value::Shape(s) => assert_eq!(s, IxDyn(&[1, 2, 3])),
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(access_axis, 0);
+                    assert_eq!(
+                        tensor,
+                        array![
+                            [1f32 / -5f32 / -9f32, -2f32 / 6f32 / 10f32],
+                            [3f32 / 1f32 / 11f32, 1f32 / 8f32 / 12f32]
+                        ]
+                        .into_dyn()
+                    );
+                }
                 _ => panic!(),
             }
         }
     );
 
-    benchmark_and_test!(
-        shape_insert_axis_1,
-        bench_shape_insert_axis_1,
-        "(shape-insert-axis (shape 2 3) 1)",
-        |value| {
-            match value {
-                Value::Shape(s) => assert_eq!(s, IxDyn(&[2, 1, 3])),
-                _ => panic!(),
+    benchmark_and_test!(literal_0, bench_literal_0, "(literal 0.1234)", |value| {
+        match value {
+            Value::Tensor(t) => {
+                assert_eq!(t, ndarray::arr0(0.1234).into_dyn());
             }
+            _ => panic!(),
         }
-    );
+    });
 
     benchmark_and_test!(
-        shape_insert_axis_2,
-        bench_shape_insert_axis_2,
-        "(shape-insert-axis (shape 2 3) 2)",
+        access_literal,
+        bench_access_literal,
+        "(access-literal (literal 0.1234))",
         |value| {
             match value {
-                Value::Shape(s) => assert_eq!(s, IxDyn(&[2, 3, 1])),
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(tensor, ndarray::arr0(0.1234).into_dyn());
+                    assert_eq!(access_axis, 0);
+                }
                 _ => panic!(),
             }
         }
     );
 
     benchmark_and_test!(
-        #[should_panic]
-        shape_insert_axis_panic,
-        bench_shape_insert_axis_panic,
-        "(shape-insert-axis (shape 2 3) 3)",
-        |value| { value }
-    );
-
-    benchmark_and_test!(
-        shape_remove_axis_0,
-        bench_shape_remove_axis_0,
-        "(shape-remove-axis (shape 1 2 3) 0)",
+        compute_with_accumulator_dot_product_int8,
+        bench_compute_with_accumulator_dot_product_int8,
+        "(compute-with-accumulator dot-product int8 (access (access-tensor t) 1))",
+        vec![(
+            "t",
+            array![[[100., 100.], [2., 2.]], [[-3., 4.], [5., 6.]]].into_dyn()
+        )],
         |value| {
             match value {
-                Value::Shape(s) => assert_eq!(s, IxDyn(&[2, 3])),
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(access_axis, 1);
+                    // Each 100 * 2 wraps around to -56.
+                    assert_eq!(tensor, array![-112., 9.].into_dyn());
+                }
                 _ => panic!(),
             }
         }
     );
 
     benchmark_and_test!(
-        shape_remove_axis_1,
-        bench_shape_remove_axis_1,
-        "(shape-remove-axis (shape 1 2 3) 1)",
+        compute_with_accumulator_dot_product_int32,
+        bench_compute_with_accumulator_dot_product_int32,
+        "(compute-with-accumulator dot-product int32 (access (access-tensor t) 1))",
+        vec![(
+            "t",
+            array![[[100., 100.], [2., 2.]], [[-3., 4.], [5., 6.]]].into_dyn()
+        )],
         |value| {
             match value {
-                Value::Shape(s) => assert_eq!(s, IxDyn(&[1, 3])),
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(access_axis, 1);
+                    assert_eq!(tensor, array![400., 9.].into_dyn());
+                }
                 _ => panic!(),
             }
         }
     );
 
     benchmark_and_test!(
-        shape_remove_axis_2,
-        bench_shape_remove_axis_2,
-        "(shape-remove-axis (shape 1 2 3) 2)",
+        compute_with_accumulator_reduce_sum_float32,
+        bench_compute_with_accumulator_reduce_sum_float32,
+        "(compute-with-accumulator reduce-sum float32 (access (access-tensor t) 1))",
+        vec![("t", array![[1., 1e-8, 1e-8], [0.5, 0.25, 0.125]].into_dyn())],
         |value| {
             match value {
-                Value::Shape(s) => assert_eq!(s, IxDyn(&[1, 2])),
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(access_axis, 1);
+                    // In float32, 1 + 1e-8 rounds back down to 1.
+                    assert_eq!(tensor, array![1., 0.875].into_dyn());
+                }
                 _ => panic!(),
             }
         }
     );
 
     benchmark_and_test!(
-        #[should_panic]
-        shape_remove_axis_panic,
-        bench_shape_remove_axis_panic,
-        "(shape-remove-axis (shape 1 2 3) 3)",
-        |value| { value }
-    );
-
-    benchmark_and_test!(
-        shape_of,
-        bench_shape_of,
-        "(shape-of t)",
-        vec![("t", array![[1., 2.], [3., 4.]].into_dyn())],
+        compute_sigmoid,
+        bench_compute_sigmoid,
+        "(compute sigmoid (access (access-tensor t) 0))",
+        vec![("t", array![[0f64, 2f64], [-2f64, -1000f64]].into_dyn())],
         |value| {
             match value {
-                Value::Shape(s) => assert_eq!(s, IxDyn(&[2, 2])),
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(access_axis, 0);
+                    assert_close(
+                        &tensor,
+                        &array![
+                            [0.5, 1. / (1. + (-2f64).exp())],
+                            [1. / (1. + 2f64.exp()), 0.]
+                        ]
+                        .into_dyn(),
+                        Tolerance::absolute(1e-12),
+                    );
+                }
                 _ => panic!(),
             }
         }
     );
 
-    benchmark_and_test!(usize, bench_usize, "23", |value| {
-        match value {
-            Value::Num(23) => (),
-            _ => panic!(),
-        }
-    });
-
     benchmark_and_test!(
-        symbol,
-        bench_symbol,
-        "t",
-        vec![("t", array![[1., 2.], [3., 4.]].into_dyn())],
+        compute_tanh,
+        bench_compute_tanh,
+        "(compute tanh (access (access-tensor t) 0))",
+        vec![("t", array![[0f64, 0.5f64], [-0.5f64, -1000f64]].into_dyn())],
         |value| {
             match value {
-                Value::Tensor(t) => assert_eq!(t, array![[1., 2.], [3., 4.]].into_dyn()),
+                Value::Access(Access {
+                    tensor,
+                    access_axis,
+                }) => {
+                    assert_eq!(access_axis, 0);
+                    assert_close(
+                        &tensor,
+                        &array![[0., 0.5f64.tanh()], [(-0.5f64).tanh(), -1.]].into_dyn(),
+                        Tolerance::absolute(1e-12),
+                    );
+                }
                 _ => panic!(),
             }
         }
     );
 
     benchmark_and_test!(
-        access_tensor,
-        bench_access_tensor,
-        "(access-tensor t)",
-        vec![("t", array![[1., 2.], [3., 4.]].into_dyn())],
+        compute_sqrt,
+        bench_compute_sqrt,
+        "(compute sqrt
+            (access (access-tensor t) 0)
+           )",
+        vec![(
+            "t",
+            array![
+                [[1f64, 2f64], [3f64, 0f64]],
+                [[5f64, 6f64], [0f64, 8f64]],
+                [[9f64, 10f64], [11f64, 12f64]],
+            ]
+            .into_dyn()
+        )],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(tensor, array![[1., 2.], [3., 4.]].into_dyn());
                     assert_eq!(access_axis, 0);
+                    assert_eq!(
+                        tensor,
+                        array![
+                            [[1f64.sqrt(), 2f64.sqrt()], [3f64.sqrt(), 0f64.sqrt()]],
+                            [[5f64.sqrt(), 6f64.sqrt()], [0f64.sqrt(), 8f64.sqrt()]],
+                            [[9f64.sqrt(), 10f64.sqrt()], [11f64.sqrt(), 12f64.sqrt()]],
+                        ]
+                        .into_dyn(),
+                    );
                 }
                 _ => panic!(),
             }
         }
     );
 
-    benchmark_and_test!(pad_type, bench_pad_type, "zero-padding", |value| {
-        match value {
-            Value::PadType(PadType::ZeroPadding) => (),
-            _ => panic!(),
-        }
-    });
-
     benchmark_and_test!(
-        access_pad,
-        bench_access_pad,
-        "(access-pad (access-tensor t) zero-padding 0 2 4)",
-        vec![("t", array![[1., 2.], [3., 4.]].into_dyn())],
+        compute_negative,
+        bench_compute_negative,
+        "(compute negative
+            (access (access-tensor t) 0)
+           )",
+        vec![(
+            "t",
+            array![
+                [[1f32, -2f32], [3f32, 1f32]],
+                [[-5f32, 6f32], [1f32, 8f32]],
+                [[-9f32, 10f32], [11f32, 12f32]],
+            ]
+            .into_dyn()
+        )],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
                     access_axis,
                 }) => {
+                    assert_eq!(access_axis, 0);
                     assert_eq!(
                         tensor,
                         array![
-                            [0., 0.],
-                            [0., 0.],
-                            [1., 2.],
-                            [3., 4.],
-                            [0., 0.],
-                            [0., 0.],
-                            [0., 0.],
-                            [0., 0.]
+                            [[-1f32, 2f32], [-3f32, -1f32]],
+                            [[5f32, -6f32], [-1f32, -8f32]],
+                            [[9f32, -10f32], [-11f32, -12f32]],
                         ]
                         .into_dyn()
                     );
-                    assert_eq!(access_axis, 0);
                 }
                 _ => panic!(),
             }
@@ -1884,14 +5777,14 @@ mod tests {
     );
 
     benchmark_and_test!(
-        compute_reduce_max_0,
-        bench_compute_reduce_max_0,
-        "(compute reduce-max
+        compute_logical_and,
+        bench_compute_logical_and,
+        "(compute logical-and
             (access (access-tensor t) 0)
            )",
         vec![(
             "t",
-            array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],].into_dyn()
+            array![[[1f32, 0.], [-2., 0.5]], [[3f32, 1.], [0., 0.]]].into_dyn()
         )],
         |value| {
             match value {
@@ -1900,7 +5793,7 @@ mod tests {
                     access_axis,
                 }) => {
                     assert_eq!(access_axis, 0);
-                    assert_eq!(tensor, ndarray::arr0(12).into_dyn());
+                    assert_eq!(tensor, array![[1f32, 0.], [0., 0.]].into_dyn());
                 }
                 _ => panic!(),
             }
@@ -1908,14 +5801,14 @@ mod tests {
     );
 
     benchmark_and_test!(
-        compute_reduce_max_1,
-        bench_compute_reduce_max_1,
-        "(compute reduce-max
+        compute_logical_or,
+        bench_compute_logical_or,
+        "(compute logical-or
             (access (access-tensor t) 1)
            )",
         vec![(
             "t",
-            array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],].into_dyn()
+            array![[[1f32, 0.], [-2., 0.]], [[0f32, 0.], [0., 0.5]]].into_dyn()
         )],
         |value| {
             match value {
@@ -1924,7 +5817,7 @@ mod tests {
                     access_axis,
                 }) => {
                     assert_eq!(access_axis, 1);
-                    assert_eq!(tensor, array![3, 8, 12].into_dyn());
+                    assert_eq!(tensor, array![[1f32, 0.], [0., 1.]].into_dyn());
                 }
                 _ => panic!(),
             }
@@ -1932,23 +5825,20 @@ mod tests {
     );
 
     benchmark_and_test!(
-        compute_reduce_max_2,
-        bench_compute_reduce_max_2,
-        "(compute reduce-max
-            (access (access-tensor t) 2)
+        compute_logical_not,
+        bench_compute_logical_not,
+        "(compute logical-not
+            (access (access-tensor t) 0)
            )",
-        vec![(
-            "t",
-            array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],].into_dyn()
-        )],
+        vec![("t", array![[1f32, 0.], [-0.5, 0.]].into_dyn())],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(access_axis, 2);
-                    assert_eq!(tensor, array![[1, 3], [6, 8], [10, 12]].into_dyn());
+                    assert_eq!(access_axis, 0);
+                    assert_eq!(tensor, array![[0f32, 1.], [0., 1.]].into_dyn());
                 }
                 _ => panic!(),
             }
@@ -1956,27 +5846,23 @@ mod tests {
     );
 
     benchmark_and_test!(
-        compute_reduce_max_3,
-        bench_compute_reduce_max_3,
-        "(compute reduce-max
-            (access (access-tensor t) 3)
-            )",
-        vec![(
-            "t",
-            array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],].into_dyn()
-        )],
+        vector_alu_add,
+        bench_vector_alu_add,
+        "(vector-alu elementwise-add 2
+          (access-pair (access (access-tensor t) 1) (access (access-tensor n) 1))
+         )",
+        vec![
+            ("t", array![[1, 2], [3, 4], [5, 6]].into_dyn()),
+            ("n", array![[10, 20], [30, 40], [50, 60]].into_dyn())
+        ],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(access_axis, 3);
-                    assert_eq!(
-                        tensor,
-                        array![[[1, -2], [3, 0]], [[-5, 6], [0, 8]], [[-9, 10], [11, 12]],]
-                            .into_dyn(),
-                    );
+                    assert_eq!(access_axis, 1);
+                    assert_eq!(tensor, array![[11, 22], [33, 44], [55, 66]].into_dyn());
                 }
                 _ => panic!(),
             }
@@ -1984,18 +5870,23 @@ mod tests {
     );
 
     benchmark_and_test!(
-        access_squeeze_0,
-        bench_access_squeeze_0,
-        "(access-squeeze (access-tensor t) 0)",
-        vec![("t", array![[1., 2.]].into_dyn())],
+        vector_alu_mul,
+        bench_vector_alu_mul,
+        "(vector-alu elementwise-mul 3
+          (access-pair (access (access-tensor t) 0) (access (access-tensor n) 0))
+         )",
+        vec![
+            ("t", array![1, 2, 3].into_dyn()),
+            ("n", array![4, 5, -6].into_dyn())
+        ],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(tensor, array![1., 2.].into_dyn());
                     assert_eq!(access_axis, 0);
+                    assert_eq!(tensor, array![4, 10, -18].into_dyn());
                 }
                 _ => panic!(),
             }
@@ -2003,17 +5894,20 @@ mod tests {
     );
 
     benchmark_and_test!(
-        access_squeeze_1,
-        bench_access_squeeze_1,
-        "(access-squeeze (access (access-tensor t) 1) 0)",
-        vec![("t", array![[1., 2.]].into_dyn())],
+        access_concatenate_0,
+        bench_access_concatenate_0,
+        "(access-concatenate (access (access-tensor t) 0) (access (access-tensor n) 0) 0)",
+        vec![
+            ("t", array![[1, 2]].into_dyn()),
+            ("n", array![[1, 2], [3, 4]].into_dyn())
+        ],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(tensor, array![1., 2.].into_dyn());
+                    assert_eq!(tensor, array![[1, 2], [1, 2], [3, 4]].into_dyn());
                     assert_eq!(access_axis, 0);
                 }
                 _ => panic!(),
@@ -2022,18 +5916,20 @@ mod tests {
     );
 
     benchmark_and_test!(
-        #[should_panic]
-        access_squeeze_panic,
-        bench_access_squeeze_panic,
-        "(access-squeeze (access (access-tensor t) 1) 1)",
-        vec![("t", array![[1., 2.]].into_dyn())],
+        access_concatenate_1,
+        bench_access_concatenate_1,
+        "(access-concatenate (access (access-tensor t) 0) (access (access-tensor n) 0) 1)",
+        vec![
+            ("t", array![[1], [2]].into_dyn()),
+            ("n", array![[1, 2], [3, 4]].into_dyn())
+        ],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(tensor, array![1., 2.].into_dyn());
+                    assert_eq!(tensor, array![[1, 1, 2], [2, 3, 4]].into_dyn());
                     assert_eq!(access_axis, 0);
                 }
                 _ => panic!(),
@@ -2042,30 +5938,51 @@ mod tests {
     );
 
     benchmark_and_test!(
-        max_pool2d,
-        bench_max_pool2d,
-        "(compute reduce-max
-            (access-windows (access (access-tensor t) 0) (shape 1 2 2) (shape 1 2 2))
-           )",
-        vec![(
-            "t",
-            array![
-                [[1, -2, -4, 5], [3, 6, -8, 0]],
-                [[-5, 6, -8, -10], [0, 0, 0, 8]],
-                [[-9, -20, -15, 10], [-1, 2, 11, 12]],
-            ]
-            .into_dyn()
-        )],
+        #[should_panic]
+        access_concatenate_panic_0,
+        bench_access_concatenate_panic_0,
+        "(access-concatenate (access (access-tensor t) 0) (access (access-tensor n) 1) 1)",
+        vec![
+            ("t", array![[1], [2]].into_dyn()),
+            ("n", array![[1, 2], [3, 4]].into_dyn())
+        ],
+        |value| { value }
+    );
+
+    benchmark_and_test!(
+        #[should_panic]
+        access_concatenate_panic_1,
+        bench_access_concatenate_panic_1,
+        "(access-concatenate (access (access-tensor t) 0) (access (access-tensor n) 0) 0)",
+        vec![
+            ("t", array![[1], [2]].into_dyn()),
+            ("n", array![[1, 2], [3, 4]].into_dyn())
+        ],
+        |value| { value }
+    );
+
+    benchmark_and_test!(
+        access_concatenate_item_axis,
+        bench_access_concatenate_item_axis,
+        "(access-concatenate (access (access-tensor t) 1) (access (access-tensor n) 1) 2)",
+        vec![
+            ("t", array![[[1], [2]], [[3], [4]]].into_dyn()),
+            (
+                "n",
+                array![[[5, 6], [7, 8]], [[9, 10], [11, 12]]].into_dyn()
+            )
+        ],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(access_axis, 3);
-                    // Checking that output is CHW.
-                    assert_eq!(tensor.shape(), [3, 1, 2]);
-                    assert_eq!(tensor, array![[[6, 5]], [[6, 8]], [[2, 12]]].into_dyn(),);
+                    assert_eq!(
+                        tensor,
+                        array![[[1, 5, 6], [2, 7, 8]], [[3, 9, 10], [4, 11, 12]]].into_dyn()
+                    );
+                    assert_eq!(access_axis, 1);
                 }
                 _ => panic!(),
             }
@@ -2073,12 +5990,12 @@ mod tests {
     );
 
     benchmark_and_test!(
-        access_pair_0,
-        bench_access_pair_0,
-        "(access-pair (access (access-tensor a) 0) (access (access-tensor b) 0))",
+        access_concatenate_negative_axis,
+        bench_access_concatenate_negative_axis,
+        "(access-concatenate (access (access-tensor t) 1) (access (access-tensor n) 1) -2)",
         vec![
-            ("a", array![[1, 2], [3, 4]].into_dyn()),
-            ("b", array![[5, 6], [7, 8]].into_dyn())
+            ("t", array![[1, 2]].into_dyn()),
+            ("n", array![[3, 4]].into_dyn())
         ],
         |value| {
             match value {
@@ -2086,12 +6003,8 @@ mod tests {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(tensor.shape(), [2, 2, 2]);
-                    assert_eq!(
-                        tensor,
-                        array![[[1, 2], [3, 4]], [[5, 6], [7, 8]]].into_dyn()
-                    );
-                    assert_eq!(access_axis, 0);
+                    assert_eq!(tensor, array![[1, 2], [3, 4]].into_dyn());
+                    assert_eq!(access_axis, 1);
                 }
                 _ => panic!(),
             }
@@ -2099,25 +6012,32 @@ mod tests {
     );
 
     benchmark_and_test!(
-        access_pair_1,
-        bench_access_pair_1,
-        "(access-pair (access (access-tensor a) 1) (access (access-tensor b) 1))",
-        vec![
-            ("a", array![[1, 2], [3, 4]].into_dyn()),
-            ("b", array![[5, 6], [7, 8]].into_dyn())
-        ],
+        #[should_panic(
+            expected = "Can't concatenate accesses of shapes [2, 1] and [2, 2] along axis 0"
+        )]
+        access_concatenate_panic_2,
+        bench_access_concatenate_panic_2,
+        "(access-concatenate (access (access-tensor t) 0) (access (access-tensor n) 0) 0)",
+        vec![
+            ("t", array![[1], [2]].into_dyn()),
+            ("n", array![[1, 2], [3, 4]].into_dyn())
+        ],
+        |value| { value }
+    );
+
+    benchmark_and_test!(
+        access_reshape,
+        bench_access_reshape,
+        "(access-reshape (access (access-tensor t) 1) (access-shape (shape 1 2) (shape 2 1)))",
+        vec![("t", array![[1, 2], [3, 4]].into_dyn())],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(tensor.shape(), [2, 2, 2]);
-                    assert_eq!(
-                        tensor,
-                        array![[[1, 2], [5, 6]], [[3, 4], [7, 8]]].into_dyn()
-                    );
-                    assert_eq!(access_axis, 1);
+                    assert_eq!(tensor, array![[[[1], [2]], [[3], [4]]]].into_dyn());
+                    assert_eq!(access_axis, 2);
                 }
                 _ => panic!(),
             }
@@ -2125,25 +6045,32 @@ mod tests {
     );
 
     benchmark_and_test!(
-        access_pair_2,
-        bench_access_pair_2,
-        "(access-pair (access (access-tensor a) 2) (access (access-tensor b) 2))",
-        vec![
-            ("a", array![[1, 2], [3, 4]].into_dyn()),
-            ("b", array![[5, 6], [7, 8]].into_dyn())
-        ],
+        #[should_panic(expected = "Cannot reshape access of shape [2] and item shape [2]")]
+        access_reshape_across_access_axis,
+        bench_access_reshape_across_access_axis,
+        "(access-reshape (access (access-tensor t) 1) (access-shape (shape 4) (shape)))",
+        vec![("t", array![[1, 2], [3, 4]].into_dyn())],
+        |value| { value }
+    );
+
+    benchmark_and_test!(
+        flatten_unflatten,
+        bench_flatten_unflatten,
+        "(access-reshape
+          (access-flatten (access (access-tensor t) 1))
+          (get-access-shape (access (access-tensor t) 1)))",
+        vec![("t", array![[[1, 2], [3, 4]], [[5, 6], [7, 8]]].into_dyn())],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(tensor.shape(), [2, 2, 2]);
                     assert_eq!(
                         tensor,
-                        array![[[1, 5], [2, 6]], [[3, 7], [4, 8]]].into_dyn()
+                        array![[[1, 2], [3, 4]], [[5, 6], [7, 8]]].into_dyn()
                     );
-                    assert_eq!(access_axis, 2);
+                    assert_eq!(access_axis, 1);
                 }
                 _ => panic!(),
             }
@@ -2151,26 +6078,18 @@ mod tests {
     );
 
     benchmark_and_test!(
-        #[should_panic]
-        access_pair_panic,
-        bench_access_pair_panic,
-        "(access-pair (access (access-tensor a) 2) (access (access-tensor b) 2))",
-        vec![
-            ("a", array![[1], [3]].into_dyn()),
-            ("b", array![[5, 6], [7, 8]].into_dyn())
-        ],
+        access_slice_0,
+        bench_access_slice_0,
+        "(access-slice (access (access-tensor t) 0) 0 0 1)",
+        vec![("t", array![[1, 2], [3, 4]].into_dyn())],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(tensor.shape(), [2, 2, 2]);
-                    assert_eq!(
-                        tensor,
-                        array![[[1, 5], [2, 6]], [[3, 7], [4, 8]]].into_dyn()
-                    );
-                    assert_eq!(access_axis, 2);
+                    assert_eq!(tensor, array![[1, 2]].into_dyn());
+                    assert_eq!(access_axis, 0);
                 }
                 _ => panic!(),
             }
@@ -2178,18 +6097,18 @@ mod tests {
     );
 
     benchmark_and_test!(
-        access_insert_axis_0,
-        bench_access_insert_axis_0,
-        "(access-insert-axis (access (access-tensor t) 0) 0)",
-        vec![("t", array![1, 2].into_dyn())],
+        access_slice_1,
+        bench_access_slice_1,
+        "(access-slice (access (access-tensor t) 0) 0 0 2)",
+        vec![("t", array![[1, 2], [3, 4]].into_dyn())],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(tensor, array![[1, 2]].into_dyn());
-                    assert_eq!(access_axis, 1);
+                    assert_eq!(tensor, array![[1, 2], [3, 4]].into_dyn());
+                    assert_eq!(access_axis, 0);
                 }
                 _ => panic!(),
             }
@@ -2197,17 +6116,17 @@ mod tests {
     );
 
     benchmark_and_test!(
-        access_insert_axis_1,
-        bench_access_insert_axis_1,
-        "(access-insert-axis (access (access-tensor t) 0) 1)",
-        vec![("t", array![1, 2].into_dyn())],
+        access_slice_2,
+        bench_access_slice_2,
+        "(access-slice (access (access-tensor t) 0) 1 1 2)",
+        vec![("t", array![[1, 2], [3, 4]].into_dyn())],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(tensor, array![[1], [2]].into_dyn());
+                    assert_eq!(tensor, array![[2], [4]].into_dyn());
                     assert_eq!(access_axis, 0);
                 }
                 _ => panic!(),
@@ -2216,18 +6135,18 @@ mod tests {
     );
 
     benchmark_and_test!(
-        access_broadcast,
-        bench_access_broadcast,
-        "(access-broadcast (access (access-tensor t) 0) (access-shape (shape 2 2) (shape)))",
-        vec![("t", array![[1, 2]].into_dyn())],
+        access_slice_shape_dimension,
+        bench_access_slice_shape_dimension,
+        "(access-slice (access (access-tensor t) 1) 0 1 3)",
+        vec![("t", array![[1, 2], [3, 4], [5, 6]].into_dyn())],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(tensor, array![[1, 2], [1, 2]].into_dyn());
-                    assert_eq!(access_axis, 0);
+                    assert_eq!(tensor, array![[3, 4], [5, 6]].into_dyn());
+                    assert_eq!(access_axis, 1);
                 }
                 _ => panic!(),
             }
@@ -2235,19 +6154,18 @@ mod tests {
     );
 
     benchmark_and_test!(
-        #[should_panic]
-        access_broadcast_panic,
-        bench_access_broadcast_panic,
-        "(access-broadcast (access (access-tensor t) 0) (shape 2 2 2))",
-        vec![("t", array![[1, 2]].into_dyn())],
+        access_slice_item_dimension,
+        bench_access_slice_item_dimension,
+        "(access-slice (access (access-tensor t) 1) 2 0 1)",
+        vec![("t", array![[[1, 2], [3, 4]], [[5, 6], [7, 8]]].into_dyn())],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(tensor, array![[1, 2], [1, 2]].into_dyn());
-                    assert_eq!(access_axis, 0);
+                    assert_eq!(tensor, array![[[1], [3]], [[5], [7]]].into_dyn());
+                    assert_eq!(access_axis, 1);
                 }
                 _ => panic!(),
             }
@@ -2255,18 +6173,18 @@ mod tests {
     );
 
     benchmark_and_test!(
-        access_transpose_0,
-        bench_access_transpose_0,
-        "(access-transpose (access (access-tensor t) 0) (list 1 0))",
-        vec![("t", array![[1, 2]].into_dyn())],
+        access_slice_negative_axis,
+        bench_access_slice_negative_axis,
+        "(access-slice (access (access-tensor t) -1) -1 1 2)",
+        vec![("t", array![[1, 2], [3, 4]].into_dyn())],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(tensor, array![[1], [2]].into_dyn());
-                    assert_eq!(access_axis, 0);
+                    assert_eq!(tensor, array![[2], [4]].into_dyn());
+                    assert_eq!(access_axis, 1);
                 }
                 _ => panic!(),
             }
@@ -2274,18 +6192,27 @@ mod tests {
     );
 
     benchmark_and_test!(
-        access_transpose_1,
-        bench_access_transpose_1,
-        "(access-transpose (access (access-tensor t) 0) (list 1 0))",
-        vec![("t", array![[1, 2]].into_dyn())],
+        #[should_panic]
+        access_slice_negative_axis_panic,
+        bench_access_slice_negative_axis_panic,
+        "(access-slice (access (access-tensor t) 0) -3 0 1)",
+        vec![("t", array![[1, 2], [3, 4]].into_dyn())],
+        |value| { value }
+    );
+
+    benchmark_and_test!(
+        access_squeeze_negative_axis,
+        bench_access_squeeze_negative_axis,
+        "(access-squeeze (access (access-tensor t) 1) -1)",
+        vec![("t", array![[1], [2]].into_dyn())],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(tensor, array![[1], [2]].into_dyn());
-                    assert_eq!(access_axis, 0);
+                    assert_eq!(tensor, array![1, 2].into_dyn());
+                    assert_eq!(access_axis, 1);
                 }
                 _ => panic!(),
             }
@@ -2293,18 +6220,18 @@ mod tests {
     );
 
     benchmark_and_test!(
-        access_transpose_2,
-        bench_access_transpose_2,
-        "(access-transpose (access (access-tensor t) 0) (list 1 0))",
-        vec![("t", array![[2, 3], [1, 2]].into_dyn())],
+        access_pad_negative_axis,
+        bench_access_pad_negative_axis,
+        "(access-pad (access (access-tensor t) 1) zero-padding -1 1 0)",
+        vec![("t", array![[1, 2], [3, 4]].into_dyn())],
         |value| {
             match value {
                 Value::Access(Access {
                     tensor,
                     access_axis,
                 }) => {
-                    assert_eq!(tensor, array![[2, 1], [3, 2]].into_dyn());
-                    assert_eq!(access_axis, 0);
+                    assert_eq!(tensor, array![[0, 1, 2], [0, 3, 4]].into_dyn());
+                    assert_eq!(access_axis, 1);
                 }
                 _ => panic!(),
             }
@@ -2313,41 +6240,40 @@ mod tests {
 
     benchmark_and_test!(
         #[should_panic]
-        access_transpose_panic_0,
-        bench_access_transpose_panic_0,
-        "(access-transpose (access (access-tensor t) 0) (list 1 0 2))",
-        vec![("t", array![[2, 3], [1, 2]].into_dyn())],
+        access_slice_panic_0,
+        bench_access_slice_panic_0,
+        "(access-slice (access (access-tensor t) 0) 0 0 3)",
+        vec![("t", array![[1, 2], [3, 4]].into_dyn())],
         |value| { value }
     );
 
     benchmark_and_test!(
         #[should_panic]
-        access_transpose_panic_1,
-        bench_access_transpose_panic_1,
-        "(access-transpose (access (access-tensor t) 0) (list 1 1))",
-        vec![("t", array![[2, 3], [1, 2]].into_dyn())],
+        access_slice_panic_1,
+        bench_access_slice_panic_1,
+        "(access-slice (access (access-tensor t) 0) 2 0 1)",
+        vec![("t", array![[1, 2], [3, 4]].into_dyn())],
         |value| { value }
     );
 
     benchmark_and_test!(
-        compute_softmax,
-        bench_compute_softmax,
-        "(compute softmax (access (access-tensor t) 1))",
-        vec![(
-            "t",
-            array![[0.4597965, 0.8250755], [0.14535584, 0.16271448]].into_dyn()
-        )],
+        #[should_panic(expected = "Slice 2..1 out of bounds")]
+        access_slice_panic_2,
+        bench_access_slice_panic_2,
+        "(access-slice (access (access-tensor t) 0) 0 2 1)",
+        vec![("t", array![[1, 2], [3, 4]].into_dyn())],
+        |value| { value }
+    );
+
+    benchmark_and_test!(
+        access_shape,
+        bench_access_shape,
+        "(access-shape (shape 1 2) (shape 3 4))",
         |value| {
             match value {
-                Value::Access(Access {
-                    tensor,
-                    access_axis,
-                }) => {
-                    assert!(tensor.abs_diff_eq(
-                        &array![[0.40968227, 0.5903177], [0.49566042, 0.5043395]].into_dyn(),
-                        1e-7
-                    ));
-                    assert_eq!(access_axis, 1);
+                Value::AccessShape(shape, access_axis) => {
+                    assert_eq!(shape.slice(), &[1, 2, 3, 4]);
+                    assert_eq!(access_axis, 2);
                 }
                 _ => panic!(),
             }
@@ -2355,31 +6281,131 @@ mod tests {
     );
 
     benchmark_and_test!(
-        access_flatten_0,
-        bench_access_flatten_0,
-        "(access-flatten (access (access-tensor t) 0))",
-        vec![(
-            "t",
-            ndarray::ArrayD::from_shape_vec(
-                vec![2, 2, 4, 6, 10],
-                (0..2 * 2 * 4 * 6 * 10).collect(),
+        access_windows_from_max_pool,
+        bench_access_windows_from_max_pool,
+        "(access-windows
+            (access
+             (access-pad
+              (access-pad
+               (access-tensor data)
+               min-padding
+               2 1 3
+              )
+              min-padding
+              3 2 4
+             )
+             2
             )
-            .unwrap(),
-        )],
+            (shape 3 4)
+            (shape 2 3)
+           )
+          ",
+        vec![("data", ArrayD::<f64>::zeros(IxDyn(&[1, 3, 32, 32])))],
         |value| {
             match value {
-                Value::Access(Access {
-                    tensor,
-                    access_axis: _,
-                }) => {
+                Value::Access(a) => {
+                    assert_eq!(a.tensor.shape(), &[1, 3, 17, 12, 3, 4]);
+                    assert_eq!(a.access_axis, 4);
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        conv2d_0,
+        bench_conv2d_0,
+        "(conv2d (access-tensor data) (access-tensor weights) (shape 1 1) (shape 0 0 0 0) 1)",
+        vec![
+            (
+                "data",
+                array![[[[1, 2, 3], [4, 5, 6], [7, 8, 9]]]].into_dyn()
+            ),
+            ("weights", array![[[[1, 0], [0, 1]]]].into_dyn())
+        ],
+        |value| {
+            match value {
+                Value::Access(a) => {
+                    assert_eq!(a.tensor, array![[[[6, 8], [12, 14]]]].into_dyn());
+                    assert_eq!(a.access_axis, 4);
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        conv2d_padding_and_strides,
+        bench_conv2d_padding_and_strides,
+        "(conv2d (access-tensor data) (access-tensor weights) (shape 2 2) (shape 1 1 1 1) 1)",
+        vec![
+            (
+                "data",
+                array![[[[1, 2, 3], [4, 5, 6], [7, 8, 9]]]].into_dyn()
+            ),
+            ("weights", array![[[[1, 1], [1, 1]]]].into_dyn())
+        ],
+        |value| {
+            match value {
+                Value::Access(a) => {
+                    assert_eq!(a.tensor, array![[[[1, 5], [11, 28]]]].into_dyn());
+                    assert_eq!(a.access_axis, 4);
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        conv2d_depthwise,
+        bench_conv2d_depthwise,
+        "(conv2d (access-tensor data) (access-tensor weights) (shape 1 1) (shape 0 0 0 0) 2)",
+        vec![
+            (
+                "data",
+                array![[[[1, 2], [3, 4]], [[5, 6], [7, 8]]]].into_dyn()
+            ),
+            ("weights", array![[[[2]]], [[[3]]]].into_dyn())
+        ],
+        |value| {
+            match value {
+                Value::Access(a) => {
                     assert_eq!(
-                        tensor,
-                        ndarray::ArrayD::from_shape_vec(
-                            vec![2 * 2 * 4 * 6 * 10],
-                            (0..2 * 2 * 4 * 6 * 10).collect(),
-                        )
-                        .unwrap(),
+                        a.tensor,
+                        array![[[[2, 4], [6, 8]], [[15, 18], [21, 24]]]].into_dyn()
                     );
+                    assert_eq!(a.access_axis, 4);
+                }
+                _ => panic!(),
+            }
+        }
+    );
+
+    benchmark_and_test!(
+        #[should_panic]
+        conv2d_panic_channel_mismatch,
+        bench_conv2d_panic_channel_mismatch,
+        "(conv2d (access-tensor data) (access-tensor weights) (shape 1 1) (shape 0 0 0 0) 1)",
+        vec![
+            ("data", array![[[[1, 2], [3, 4]]]].into_dyn()),
+            ("weights", array![[[[1]], [[1]]]].into_dyn())
+        ],
+        |value| { value }
+    );
+
+    benchmark_and_test!(
+        conv1d_0,
+        bench_conv1d_0,
+        "(conv1d (access-tensor data) (access-tensor weights) (shape 1) (shape 0 1) 1)",
+        vec![
+            ("data", array![[[1, 2, 3, 4]]].into_dyn()),
+            ("weights", array![[[1, -1]], [[2, 0]]].into_dyn())
+        ],
+        |value| {
+            match value {
+                Value::Access(a) => {
+                    assert_eq!(a.tensor, array![[[-1, -1, -1, 4], [2, 4, 6, 8]]].into_dyn());
+                    assert_eq!(a.access_axis, 3);
                 }
                 _ => panic!(),
             }
@@ -2387,31 +6413,24 @@ mod tests {
     );
 
     benchmark_and_test!(
-        access_flatten_1,
-        bench_access_flatten_1,
-        "(access-flatten (access (access-tensor t) 1))",
-        vec![(
-            "t",
-            ndarray::ArrayD::from_shape_vec(
-                vec![2, 2, 4, 6, 10],
-                (0..2 * 2 * 4 * 6 * 10).collect(),
+        conv3d_0,
+        bench_conv3d_0,
+        "(conv3d (access-tensor data) (access-tensor weights) (shape 1 1 1) (shape 0 0 0 1 0 0) 1)",
+        vec![
+            (
+                "data",
+                array![[[[[1, 2], [3, 4]], [[5, 6], [7, 8]]]]].into_dyn()
+            ),
+            (
+                "weights",
+                array![[[[[1, 1], [1, 1]], [[1, 1], [1, 1]]]]].into_dyn()
             )
-            .unwrap(),
-        )],
+        ],
         |value| {
             match value {
-                Value::Access(Access {
-                    tensor,
-                    access_axis: _,
-                }) => {
-                    assert_eq!(
-                        tensor,
-                        ndarray::ArrayD::from_shape_vec(
-                            vec![2, 2 * 4 * 6 * 10],
-                            (0..2 * 2 * 4 * 6 * 10).collect(),
-                        )
-                        .unwrap(),
-                    );
+                Value::Access(a) => {
+                    assert_eq!(a.tensor, array![[[[[36]], [[26]]]]].into_dyn());
+                    assert_eq!(a.access_axis, 5);
                 }
                 _ => panic!(),
             }
@@ -2419,31 +6438,27 @@ mod tests {
     );
 
     benchmark_and_test!(
-        access_flatten_2,
-        bench_access_flatten_2,
-        "(access-flatten (access (access-tensor t) 2))",
-        vec![(
-            "t",
-            ndarray::ArrayD::from_shape_vec(
-                vec![2, 2, 4, 6, 10],
-                (0..2 * 2 * 4 * 6 * 10).collect(),
-            )
-            .unwrap(),
-        )],
+        conv2d_transpose_0,
+        bench_conv2d_transpose_0,
+        "(conv2d-transpose (access-tensor data) (access-tensor weights) (shape 2 2) (shape 0 0 0 0) (shape 0 0))",
+        vec![
+            ("data", array![[[[1, 2], [3, 4]]]].into_dyn()),
+            ("weights", array![[[[1, 1], [1, 1]]]].into_dyn())
+        ],
         |value| {
             match value {
-                Value::Access(Access {
-                    tensor,
-                    access_axis: _,
-                }) => {
+                Value::Access(a) => {
                     assert_eq!(
-                        tensor,
-                        ndarray::ArrayD::from_shape_vec(
-                            vec![2 * 2, 4 * 6 * 10],
-                            (0..2 * 2 * 4 * 6 * 10).collect(),
-                        )
-                        .unwrap(),
+                        a.tensor,
+                        array![[[
+                            [1, 1, 2, 2],
+                            [1, 1, 2, 2],
+                            [3, 3, 4, 4],
+                            [3, 3, 4, 4]
+                        ]]]
+                        .into_dyn()
                     );
+                    assert_eq!(a.access_axis, 4);
                 }
                 _ => panic!(),
             }
@@ -2451,31 +6466,20 @@ mod tests {
     );
 
     benchmark_and_test!(
-        access_flatten_3,
-        bench_access_flatten_3,
-        "(access-flatten (access (access-tensor t) 5))",
-        vec![(
-            "t",
-            ndarray::ArrayD::from_shape_vec(
-                vec![2, 2, 4, 6, 10],
-                (0..2 * 2 * 4 * 6 * 10).collect(),
-            )
-            .unwrap(),
-        )],
+        conv2d_transpose_1,
+        bench_conv2d_transpose_1,
+        "(conv2d-transpose (access-tensor data) (access-tensor weights) (shape 1 1) (shape 1 1 0 0) (shape 0 0))",
+        vec![
+            ("data", array![[[[1, 2], [3, 4]]]].into_dyn()),
+            ("weights", array![[[[1, 1], [1, 1]]]].into_dyn())
+        ],
         |value| {
             match value {
-                Value::Access(Access {
-                    tensor,
-                    access_axis: _,
-                }) => {
-                    assert_eq!(
-                        tensor,
-                        ndarray::ArrayD::from_shape_vec(
-                            vec![2 * 2 * 4 * 6 * 10],
-                            (0..2 * 2 * 4 * 6 * 10).collect(),
-                        )
-                        .unwrap(),
-                    );
+                Value::Access(a) => {
+                    // The full output is [[1, 3, 2], [4, 10, 6], [3, 7, 4]];
+                    // padding crops its first row and column.
+                    assert_eq!(a.tensor, array![[[[10, 6], [7, 4]]]].into_dyn());
+                    assert_eq!(a.access_axis, 4);
                 }
                 _ => panic!(),
             }
@@ -2483,27 +6487,24 @@ mod tests {
     );
 
     benchmark_and_test!(
-        compute_reduce_mean_0,
-        bench_compute_reduce_mean_0,
-        "(compute reduce-mean (access (access-tensor t) 0))",
-        vec![(
-            "t",
-            array![[[1f64, 2f64], [3f64, 4f64]], [[5f64, 6f64], [7f64, 8f64]]].into_dyn()
-        )],
+        batch_matmul,
+        bench_batch_matmul,
+        "(batch-matmul (access-tensor a) (access-tensor b))",
+        vec![
+            ("a", array![[[1, 2], [3, 4]], [[1, 0], [0, -1]]].into_dyn()),
+            (
+                "b",
+                array![[[1, 0, 1], [0, 1, 1]], [[2, 3, 4], [5, 6, 7]]].into_dyn()
+            )
+        ],
         |value| {
             match value {
-                Value::Access(Access {
-                    tensor,
-                    access_axis,
-                }) => {
+                Value::Access(a) => {
                     assert_eq!(
-                        tensor,
-                        ndarray::arr0(
-                            (1f64 + 2f64 + 3f64 + 4f64 + 5f64 + 6f64 + 7f64 + 8f64) / 8f64
-                        )
-                        .into_dyn(),
+                        a.tensor,
+                        array![[[1, 2, 3], [3, 4, 7]], [[2, 3, 4], [-5, -6, -7]]].into_dyn()
                     );
-                    assert_eq!(access_axis, 0);
+                    assert_eq!(a.access_axis, 3);
                 }
                 _ => panic!(),
             }
@@ -2511,28 +6512,28 @@ mod tests {
     );
 
     benchmark_and_test!(
-        compute_reduce_mean_1,
-        bench_compute_reduce_mean_1,
-        "(compute reduce-mean (access (access-tensor t) 1))",
+        adaptive_pool2d_mean,
+        bench_adaptive_pool2d_mean,
+        "(adaptive-pool2d reduce-mean (access-tensor data) (shape 2 3))",
         vec![(
-            "t",
-            array![[[1f64, 2f64], [3f64, 4f64]], [[5f64, 6f64], [7f64, 8f64]]].into_dyn()
+            "data",
+            array![[[
+                [1., 2., 3., 4., 5.],
+                [6., 7., 8., 9., 10.],
+                [11., 12., 13., 14., 15.]
+            ]]]
+            .into_dyn()
         )],
         |value| {
             match value {
-                Value::Access(Access {
-                    tensor,
-                    access_axis,
-                }) => {
+                Value::Access(a) => {
+                    // Rows are pooled in the (overlapping) windows 0..2 and
+                    // 1..3, and columns in the windows 0..2, 1..4, and 3..5.
                     assert_eq!(
-                        tensor,
-                        array![
-                            (1f64 + 2f64 + 3f64 + 4f64) / 4f64,
-                            (5f64 + 6f64 + 7f64 + 8f64) / 4f64
-                        ]
-                        .into_dyn(),
+                        a.tensor,
+                        array![[[[4., 5.5, 7.], [9., 10.5, 12.]]]].into_dyn()
                     );
-                    assert_eq!(access_axis, 1);
+                    assert_eq!(a.access_axis, 4);
                 }
                 _ => panic!(),
             }
@@ -2540,28 +6541,18 @@ mod tests {
     );
 
     benchmark_and_test!(
-        compute_reduce_mean_2,
-        bench_compute_reduce_mean_2,
-        "(compute reduce-mean (access (access-tensor t) 2))",
+        adaptive_pool2d_global_max,
+        bench_adaptive_pool2d_global_max,
+        "(adaptive-pool2d reduce-max (access-tensor data) (shape 1 1))",
         vec![(
-            "t",
-            array![[[1f64, 2f64], [3f64, 4f64]], [[5f64, 6f64], [7f64, 8f64]]].into_dyn()
+            "data",
+            array![[[[1, 5], [3, 2]], [[-4, -1], [-3, -2]]]].into_dyn()
         )],
         |value| {
             match value {
-                Value::Access(Access {
-                    tensor,
-                    access_axis,
-                }) => {
-                    assert_eq!(
-                        tensor,
-                        array![
-                            [(1f64 + 2f64) / 2f64, (3f64 + 4f64) / 2f64],
-                            [(5f64 + 6f64) / 2f64, (7f64 + 8f64) / 2f64]
-                        ]
-                        .into_dyn(),
-                    );
-                    assert_eq!(access_axis, 2);
+                Value::Access(a) => {
+                    assert_eq!(a.tensor, array![[[[5]], [[-1]]]].into_dyn());
+                    assert_eq!(a.access_axis, 4);
                 }
                 _ => panic!(),
             }
@@ -2569,25 +6560,18 @@ mod tests {
     );
 
     benchmark_and_test!(
-        compute_reduce_mean_3,
-        bench_compute_reduce_mean_3,
-        "(compute reduce-mean (access (access-tensor t) 3))",
-        vec![(
-            "t",
-            array![[[1f64, 2f64], [3f64, 4f64]], [[5f64, 6f64], [7f64, 8f64]]].into_dyn()
-        )],
+        bias_add_0,
+        bench_bias_add_0,
+        "(bias-add (access (access-tensor data) 1) (access-tensor bias) 1)",
+        vec![
+            ("data", array![[1, 2, 3], [4, 5, 6]].into_dyn()),
+            ("bias", array![10, 20, 30].into_dyn())
+        ],
         |value| {
             match value {
-                Value::Access(Access {
-                    tensor,
-                    access_axis,
-                }) => {
-                    assert_eq!(
-                        tensor,
-                        array![[[1f64, 2f64], [3f64, 4f64]], [[5f64, 6f64], [7f64, 8f64]]]
-                            .into_dyn(),
-                    );
-                    assert_eq!(access_axis, 3);
+                Value::Access(a) => {
+                    assert_eq!(a.tensor, array![[11, 22, 33], [14, 25, 36]].into_dyn());
+                    assert_eq!(a.access_axis, 1);
                 }
                 _ => panic!(),
             }
@@ -2595,31 +6579,18 @@ mod tests {
     );
 
     benchmark_and_test!(
-        access_pad_min_padding,
-        bench_access_pad_min_padding,
-        "(access-pad (access-tensor t) min-padding 0 2 4)",
-        vec![("t", array![[1., 2.], [3., 4.]].into_dyn())],
+        bias_add_1,
+        bench_bias_add_1,
+        "(bias-add (access-tensor data) (access-tensor bias) 0)",
+        vec![
+            ("data", array![[1, 2, 3], [4, 5, 6]].into_dyn()),
+            ("bias", array![10, 20].into_dyn())
+        ],
         |value| {
             match value {
-                Value::Access(Access {
-                    tensor,
-                    access_axis,
-                }) => {
-                    assert_eq!(
-                        tensor,
-                        array![
-                            [std::f64::MIN, std::f64::MIN],
-                            [std::f64::MIN, std::f64::MIN],
-                            [1., 2.],
-                            [3., 4.],
-                            [std::f64::MIN, std::f64::MIN],
-                            [std::f64::MIN, std::f64::MIN],
-                            [std::f64::MIN, std::f64::MIN],
-                            [std::f64::MIN, std::f64::MIN]
-                        ]
-                        .into_dyn()
-                    );
-                    assert_eq!(access_axis, 0);
+                Value::Access(a) => {
+                    assert_eq!(a.tensor, array![[11, 12, 13], [24, 25, 26]].into_dyn());
+                    assert_eq!(a.access_axis, 0);
                 }
                 _ => panic!(),
             }
@@ -2627,99 +6598,67 @@ mod tests {
     );
 
     benchmark_and_test!(
-        compute_elementwise_div,
-        bench_compute_elementwise_div,
-        "(compute elementwise-div
-            (access (access-tensor t) 0)
-           )",
-        vec![(
-            "t",
-            array![
-                [[1f32, -2f32], [3f32, 1f32]],
-                [[-5f32, 6f32], [1f32, 8f32]],
-                [[-9f32, 10f32], [11f32, 12f32]],
-            ]
-            .into_dyn()
-        )],
+        #[should_panic]
+        bias_add_panic,
+        bench_bias_add_panic,
+        "(bias-add (access-tensor data) (access-tensor bias) 1)",
+        vec![
+            ("data", array![[1, 2, 3], [4, 5, 6]].into_dyn()),
+            ("bias", array![10, 20].into_dyn())
+        ],
+        |value| { value }
+    );
+
+    benchmark_and_test!(
+        dropout,
+        bench_dropout,
+        "(dropout (access (access-tensor t) 1) 0.5)",
+        vec![("t", array![[1., -2.], [3., 4.]].into_dyn())],
         |value| {
             match value {
-                Value::Access(Access {
-                    tensor,
-                    access_axis,
-                }) => {
-                    assert_eq!(access_axis, 0);
-                    assert_eq!(
-                        tensor,
-                        array![
-                            [1f32 / -5f32 / -9f32, -2f32 / 6f32 / 10f32],
-                            [3f32 / 1f32 / 11f32, 1f32 / 8f32 / 12f32]
-                        ]
-                        .into_dyn()
-                    );
+                Value::Access(a) => {
+                    assert_eq!(a.tensor, array![[1., -2.], [3., 4.]].into_dyn());
+                    assert_eq!(a.access_axis, 1);
                 }
                 _ => panic!(),
             }
         }
     );
 
-    benchmark_and_test!(literal_0, bench_literal_0, "(literal 0.1234)", |value| {
-        match value {
-            Value::Tensor(t) => {
-                assert_eq!(t, ndarray::arr0(0.1234).into_dyn());
-            }
-            _ => panic!(),
-        }
-    });
-
     benchmark_and_test!(
-        access_literal,
-        bench_access_literal,
-        "(access-literal (literal 0.1234))",
+        reduce_mean_var,
+        bench_reduce_mean_var,
+        "(compute reduce-mean-var (access (access-tensor t) 1))",
+        vec![("t", array![[1., 3.], [0., 4.], [2., 2.]].into_dyn())],
         |value| {
             match value {
-                Value::Access(Access {
-                    tensor,
-                    access_axis,
-                }) => {
-                    assert_eq!(tensor, ndarray::arr0(0.1234).into_dyn());
-                    assert_eq!(access_axis, 0);
-                }
+                Value::Tuple(values) => match &values[..] {
+                    [Value::Access(means), Value::Access(variances)] => {
+                        assert_eq!(means.tensor, array![2., 2., 2.].into_dyn());
+                        assert_eq!(variances.tensor, array![1., 4., 0.].into_dyn());
+                        assert_eq!(means.access_axis, 1);
+                    }
+                    _ => panic!(),
+                },
                 _ => panic!(),
             }
         }
     );
 
     benchmark_and_test!(
-        compute_sqrt,
-        bench_compute_sqrt,
-        "(compute sqrt
-            (access (access-tensor t) 0)
-           )",
+        reduce_mean_var_variance,
+        bench_reduce_mean_var_variance,
+        "(tuple-get-item (compute reduce-mean-var (access (access-tensor t) 0)) 1)",
+        // Computing E[x^2] - E[x]^2 would lose the variance to cancellation.
         vec![(
             "t",
-            array![
-                [[1f64, 2f64], [3f64, 0f64]],
-                [[5f64, 6f64], [0f64, 8f64]],
-                [[9f64, 10f64], [11f64, 12f64]],
-            ]
-            .into_dyn()
+            array![1e8 + 4., 1e8 + 7., 1e8 + 13., 1e8 + 16.].into_dyn()
         )],
         |value| {
             match value {
-                Value::Access(Access {
-                    tensor,
-                    access_axis,
-                }) => {
-                    assert_eq!(access_axis, 0);
-                    assert_eq!(
-                        tensor,
-                        array![
-                            [[1f64.sqrt(), 2f64.sqrt()], [3f64.sqrt(), 0f64.sqrt()]],
-                            [[5f64.sqrt(), 6f64.sqrt()], [0f64.sqrt(), 8f64.sqrt()]],
-                            [[9f64.sqrt(), 10f64.sqrt()], [11f64.sqrt(), 12f64.sqrt()]],
-                        ]
-                        .into_dyn(),
-                    );
+                Value::Access(a) => {
+                    assert_eq!(a.tensor.shape(), &[] as &[usize]);
+                    assert!((a.tensor.first().unwrap() - 22.5f64).abs() < 1e-6);
                 }
                 _ => panic!(),
             }
@@ -2727,36 +6666,20 @@ mod tests {
     );
 
     benchmark_and_test!(
-        compute_negative,
-        bench_compute_negative,
-        "(compute negative
-            (access (access-tensor t) 0)
-           )",
-        vec![(
-            "t",
-            array![
-                [[1f32, -2f32], [3f32, 1f32]],
-                [[-5f32, 6f32], [1f32, 8f32]],
-                [[-9f32, 10f32], [11f32, 12f32]],
-            ]
-            .into_dyn()
-        )],
+        layer_norm,
+        bench_layer_norm,
+        "(layer-norm (access (access-tensor data) 1) (access-tensor gamma) (access-tensor beta)
+          (literal 0.0))",
+        vec![
+            ("data", array![[1., 3.], [0., 4.]].into_dyn()),
+            ("gamma", array![2., 3.].into_dyn()),
+            ("beta", array![10., 20.].into_dyn())
+        ],
         |value| {
             match value {
-                Value::Access(Access {
-                    tensor,
-                    access_axis,
-                }) => {
-                    assert_eq!(access_axis, 0);
-                    assert_eq!(
-                        tensor,
-                        array![
-                            [[-1f32, 2f32], [-3f32, -1f32]],
-                            [[5f32, -6f32], [-1f32, -8f32]],
-                            [[9f32, -10f32], [-11f32, -12f32]],
-                        ]
-                        .into_dyn()
-                    );
+                Value::Access(a) => {
+                    assert_eq!(a.tensor, array![[8., 23.], [8., 23.]].into_dyn());
+                    assert_eq!(a.access_axis, 1);
                 }
                 _ => panic!(),
             }
@@ -2764,21 +6687,26 @@ mod tests {
     );
 
     benchmark_and_test!(
-        access_concatenate_0,
-        bench_access_concatenate_0,
-        "(access-concatenate (access (access-tensor t) 0) (access (access-tensor n) 0) 0)",
+        group_norm,
+        bench_group_norm,
+        "(group-norm (access (access-tensor data) 0) (access-tensor gamma) (access-tensor beta)
+          2 (literal 0.0))",
         vec![
-            ("t", array![[1, 2]].into_dyn()),
-            ("n", array![[1, 2], [3, 4]].into_dyn())
+            (
+                "data",
+                array![[[1., 3.], [1., 3.], [0., 4.], [4., 0.]]].into_dyn()
+            ),
+            ("gamma", array![1., 2., 3., 4.].into_dyn()),
+            ("beta", array![0., 0., 0., 10.].into_dyn())
         ],
         |value| {
             match value {
-                Value::Access(Access {
-                    tensor,
-                    access_axis,
-                }) => {
-                    assert_eq!(tensor, array![[1, 2], [1, 2], [3, 4]].into_dyn());
-                    assert_eq!(access_axis, 0);
+                Value::Access(a) => {
+                    assert_eq!(
+                        a.tensor,
+                        array![[[-1., 1.], [-2., 2.], [-3., 3.], [14., 6.]]].into_dyn()
+                    );
+                    assert_eq!(a.access_axis, 3);
                 }
                 _ => panic!(),
             }
@@ -2786,21 +6714,18 @@ mod tests {
     );
 
     benchmark_and_test!(
-        access_concatenate_1,
-        bench_access_concatenate_1,
-        "(access-concatenate (access (access-tensor t) 0) (access (access-tensor n) 0) 1)",
-        vec![
-            ("t", array![[1], [2]].into_dyn()),
-            ("n", array![[1, 2], [3, 4]].into_dyn())
-        ],
+        cast_quantize_dequantize,
+        bench_cast_quantize_dequantize,
+        "(cast float32 (cast uint8 (access (access-tensor t) 1)))",
+        vec![(
+            "t",
+            array![[0.5, 1.9, -1.5], [255.7, 256.2, 0.1]].into_dyn()
+        )],
         |value| {
             match value {
-                Value::Access(Access {
-                    tensor,
-                    access_axis,
-                }) => {
-                    assert_eq!(tensor, array![[1, 1, 2], [2, 3, 4]].into_dyn());
-                    assert_eq!(access_axis, 0);
+                Value::Access(a) => {
+                    assert_eq!(a.tensor, array![[0., 1., 255.], [255., 0., 0.]].into_dyn());
+                    assert_eq!(a.access_axis, 1);
                 }
                 _ => panic!(),
             }
@@ -2808,42 +6733,53 @@ mod tests {
     );
 
     benchmark_and_test!(
-        #[should_panic]
-        access_concatenate_panic_0,
-        bench_access_concatenate_panic_0,
-        "(access-concatenate (access (access-tensor t) 0) (access (access-tensor n) 1) 1)",
-        vec![
-            ("t", array![[1], [2]].into_dyn()),
-            ("n", array![[1, 2], [3, 4]].into_dyn())
-        ],
-        |value| { value }
+        cast_integer_widths,
+        bench_cast_integer_widths,
+        "(cast int8 (access (access-tensor t) 0))",
+        vec![("t", array![127i64, 128, -129, 1000, 0].into_dyn())],
+        |value| {
+            match value {
+                Value::Access(a) => {
+                    assert_eq!(a.tensor, array![127, -128, 127, -24, 0].into_dyn());
+                    assert_eq!(a.access_axis, 0);
+                }
+                _ => panic!(),
+            }
+        }
     );
 
     benchmark_and_test!(
-        #[should_panic]
-        access_concatenate_panic_1,
-        bench_access_concatenate_panic_1,
-        "(access-concatenate (access (access-tensor t) 0) (access (access-tensor n) 0) 0)",
-        vec![
-            ("t", array![[1], [2]].into_dyn()),
-            ("n", array![[1, 2], [3, 4]].into_dyn())
-        ],
-        |value| { value }
+        cast_bool,
+        bench_cast_bool,
+        "(cast bool (access (access-tensor t) 0))",
+        vec![("t", array![0., -0.25, 3.].into_dyn())],
+        |value| {
+            match value {
+                Value::Access(a) => assert_eq!(a.tensor, array![0., 1., 1.].into_dyn()),
+                _ => panic!(),
+            }
+        }
     );
 
+    #[test]
+    fn quantize_multiplier_0() {
+        assert_eq!(quantize_multiplier(0.0), (0, 0));
+        assert_eq!(quantize_multiplier(0.5), (1 << 30, 0));
+        assert_eq!(quantize_multiplier(1.0), (1 << 30, 1));
+        assert_eq!(quantize_multiplier(0.4), (1717986918, -1));
+        assert_eq!(quantize_multiplier(1.0 / 3.0), (1431655765, -1));
+    }
+
     benchmark_and_test!(
-        access_slice_0,
-        bench_access_slice_0,
-        "(access-slice (access (access-tensor t) 0) 0 0 1)",
-        vec![("t", array![[1, 2], [3, 4]].into_dyn())],
+        requantize_tonearest,
+        bench_requantize_tonearest,
+        "(requantize int8 (access (access-tensor t) 0) 0.25 0 1 0 tonearest)",
+        vec![("t", array![-6i64, -2, 2, 6, -7, 7].into_dyn())],
         |value| {
             match value {
-                Value::Access(Access {
-                    tensor,
-                    access_axis,
-                }) => {
-                    assert_eq!(tensor, array![[1, 2]].into_dyn());
-                    assert_eq!(access_axis, 0);
+                Value::Access(a) => {
+                    assert_eq!(a.tensor, array![-2, -1, 1, 2, -2, 2].into_dyn());
+                    assert_eq!(a.access_axis, 0);
                 }
                 _ => panic!(),
             }
@@ -2851,37 +6787,27 @@ mod tests {
     );
 
     benchmark_and_test!(
-        access_slice_1,
-        bench_access_slice_1,
-        "(access-slice (access (access-tensor t) 0) 0 0 2)",
-        vec![("t", array![[1, 2], [3, 4]].into_dyn())],
+        requantize_upward,
+        bench_requantize_upward,
+        "(requantize int8 (access (access-tensor t) 0) 0.25 0 1 0 upward)",
+        vec![("t", array![-6i64, -2, 2, 6, -7, 7].into_dyn())],
         |value| {
             match value {
-                Value::Access(Access {
-                    tensor,
-                    access_axis,
-                }) => {
-                    assert_eq!(tensor, array![[1, 2], [3, 4]].into_dyn());
-                    assert_eq!(access_axis, 0);
-                }
+                Value::Access(a) => assert_eq!(a.tensor, array![-1, 0, 1, 2, -1, 2].into_dyn()),
                 _ => panic!(),
             }
         }
     );
 
     benchmark_and_test!(
-        access_slice_2,
-        bench_access_slice_2,
-        "(access-slice (access (access-tensor t) 0) 1 1 2)",
-        vec![("t", array![[1, 2], [3, 4]].into_dyn())],
+        requantize_zero_points,
+        bench_requantize_zero_points,
+        "(requantize int8 (access (access-tensor t) 0) 0.02 128 0.05 -10 tonearest)",
+        vec![("t", array![0., 10., 100., 200., 255.].into_dyn())],
         |value| {
             match value {
-                Value::Access(Access {
-                    tensor,
-                    access_axis,
-                }) => {
-                    assert_eq!(tensor, array![[2], [4]].into_dyn());
-                    assert_eq!(access_axis, 0);
+                Value::Access(a) => {
+                    assert_eq!(a.tensor, array![-61., -57., -21., 19., 41.].into_dyn())
                 }
                 _ => panic!(),
             }
@@ -2889,32 +6815,40 @@ mod tests {
     );
 
     benchmark_and_test!(
-        #[should_panic]
-        access_slice_panic_0,
-        bench_access_slice_panic_0,
-        "(access-slice (access (access-tensor t) 0) 0 0 3)",
-        vec![("t", array![[1, 2], [3, 4]].into_dyn())],
-        |value| { value }
+        requantize_clamp,
+        bench_requantize_clamp,
+        "(requantize uint8 (access (access-tensor t) 0) 2.0 0 1 3 tonearest)",
+        vec![("t", array![100i64, -100, 50, 0].into_dyn())],
+        |value| {
+            match value {
+                Value::Access(a) => assert_eq!(a.tensor, array![203, 0, 103, 3].into_dyn()),
+                _ => panic!(),
+            }
+        }
     );
 
     benchmark_and_test!(
-        #[should_panic]
-        access_slice_panic_1,
-        bench_access_slice_panic_1,
-        "(access-slice (access (access-tensor t) 0) 2 0 1)",
-        vec![("t", array![[1, 2], [3, 4]].into_dyn())],
+        #[should_panic(expected = "Expected an integer")]
+        requantize_non_integer_panic,
+        bench_requantize_non_integer_panic,
+        "(requantize int8 (access (access-tensor t) 0) 0.5 0 1 0 tonearest)",
+        vec![("t", array![0.5].into_dyn())],
         |value| { value }
     );
 
     benchmark_and_test!(
-        access_shape,
-        bench_access_shape,
-        "(access-shape (shape 1 2) (shape 3 4))",
+        systolic_array,
+        bench_systolic_array,
+        "(systolic-array 2 3 (access (access-tensor a) 1) (access (access-tensor b) 0))",
+        vec![
+            ("a", array![[1., 2.], [3., 4.]].into_dyn()),
+            ("b", array![[1., 2., 3.], [4., 5., 6.]].into_dyn())
+        ],
         |value| {
             match value {
-                Value::AccessShape(shape, access_axis) => {
-                    assert_eq!(shape.slice(), &[1, 2, 3, 4]);
-                    assert_eq!(access_axis, 2);
+                Value::Access(a) => {
+                    assert_eq!(a.access_axis, 2);
+                    assert_eq!(a.tensor, array![[9., 12., 15.], [19., 26., 33.]].into_dyn());
                 }
                 _ => panic!(),
             }
@@ -2922,34 +6856,267 @@ mod tests {
     );
 
     benchmark_and_test!(
-        access_windows_from_max_pool,
-        bench_access_windows_from_max_pool,
-        "(access-windows
-            (access
-             (access-pad
-              (access-pad
-               (access-tensor data)
-               min-padding
-               2 1 3
-              )
-              min-padding
-              3 2 4
-             )
-             2
-            )
-            (shape 3 4)
-            (shape 2 3)
-           )
-          ",
-        vec![("data", ArrayD::<f64>::zeros(IxDyn(&[1, 3, 32, 32])))],
+        systolic_array_with_blocking_vector,
+        bench_systolic_array_with_blocking_vector,
+        "(systolic-array-with-blocking 1 3 (access (access-tensor a) 0) (access (access-tensor b) 0))",
+        vec![
+            ("a", array![1., 2.].into_dyn()),
+            ("b", array![[1., 2., 3.], [4., 5., 6.]].into_dyn())
+        ],
         |value| {
             match value {
                 Value::Access(a) => {
-                    assert_eq!(a.tensor.shape(), &[1, 3, 17, 12, 3, 4]);
-                    assert_eq!(a.access_axis, 4);
+                    assert_eq!(a.access_axis, 1);
+                    assert_eq!(a.tensor, array![9., 12., 15.].into_dyn());
                 }
                 _ => panic!(),
             }
         }
     );
+
+    /// A two-layer perceptron over a batch of two-row inputs.
+    const BATCHED_MLP: &str = "
+        (compute relu
+         (compute dot-product
+          (access-cartesian-product
+           (access
+            (compute relu
+             (compute dot-product
+              (access-cartesian-product
+               (access (access-tensor x) 1)
+               (access (access-transpose (access (access-tensor w1) 1) (list 1 0)) 1))))
+            1)
+           (access (access-transpose (access (access-tensor w2) 1) (list 1 0)) 1))))";
+
+    #[test]
+    fn interpret_checked_network() {
+        let expr = RecExpr::<Language>::from_str(
+            "
+         (access-transpose
+          (compute reduce-max
+           (access-windows
+            (access
+             (compute relu
+              (compute dot-product
+               (access-cartesian-product
+                (access (access-tensor x) 1)
+                (access (access-tensor w) 1))))
+             1)
+            (shape 2)
+            (shape 2)))
+          (list 1 0))",
+        )
+        .unwrap();
+        let mut env = Environment::new();
+        env.insert("x", array![[1., -2.], [3., 4.], [-5., 6.]].into_dyn());
+        env.insert(
+            "w",
+            array![[1., 0.], [0., 1.], [1., 1.], [-1., 1.]].into_dyn(),
+        );
+
+        match (
+            interpret_checked(&expr, expr.as_ref().len() - 1, &env),
+            interpret(&expr, expr.as_ref().len() - 1, &env),
+        ) {
+            (Value::Access(checked), Value::Access(unchecked)) => {
+                assert_eq!(checked.tensor, unchecked.tensor);
+                assert_eq!(checked.access_axis, unchecked.access_axis);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Node 4 (access) doesn't have the access pattern shape")]
+    fn interpret_checked_wrong_access_axis() {
+        let expr =
+            RecExpr::<Language>::from_str("(compute relu (access (access-tensor t) 1))").unwrap();
+        let mut env = Environment::new();
+        env.insert("t", array![[1., -2.], [3., 4.]].into_dyn());
+
+        // Predict the shape an access at axis 0 would have, as if the
+        // interpreter had dropped the access axis.
+        let checks = AccessChecks {
+            predicted: vec![
+                None,
+                None,
+                Some((IxDyn(&[]), IxDyn(&[2, 2]))),
+                None,
+                Some((IxDyn(&[]), IxDyn(&[2, 2]))),
+                None,
+            ],
+        };
+        interpret_with_checks(&expr, expr.as_ref().len() - 1, &env, Some(&checks));
+    }
+
+    #[test]
+    fn interpret_batch_matches_interpret() {
+        let expr = RecExpr::<Language>::from_str(BATCHED_MLP).unwrap();
+        let mut weights = Environment::new();
+        weights.insert(
+            "w1",
+            ArrayD::from_shape_fn(vec![4, 3], |i| (i[0] as f64 - 1.5) * (i[1] as f64 + 1.)),
+        );
+        weights.insert(
+            "w2",
+            ArrayD::from_shape_fn(vec![3, 2], |i| i[0] as f64 - i[1] as f64 * 0.5),
+        );
+        let inputs = (0..3)
+            .map(|sample| {
+                let mut env = Environment::new();
+                env.insert(
+                    "x",
+                    ArrayD::from_shape_fn(vec![2, 4], |i| {
+                        (sample * 8 + i[0] * 4 + i[1]) as f64 * 0.25 - 3.
+                    }),
+                );
+                env
+            })
+            .collect::<Vec<_>>();
+
+        let values = interpret_batch(&expr, expr.as_ref().len() - 1, &weights, &inputs);
+        assert_eq!(values.len(), inputs.len());
+        for (value, input) in values.into_iter().zip(inputs.iter()) {
+            let mut env = weights.clone();
+            env.extend(input.iter().map(|(name, tensor)| (*name, tensor.clone())));
+            match (value, interpret(&expr, expr.as_ref().len() - 1, &env)) {
+                (Value::Access(batched), Value::Access(expected)) => {
+                    assert_eq!(batched.access_axis, expected.access_axis);
+                    assert_eq!(batched.tensor, expected.tensor);
+                }
+                _ => panic!(),
+            }
+        }
+    }
+
+    #[test]
+    fn memoize_transposed_weights() {
+        let expr = RecExpr::<Language>::from_str(BATCHED_MLP).unwrap();
+        let mut weights = Environment::new();
+        weights.insert("w1", ArrayD::<f64>::zeros(vec![4, 3]));
+        weights.insert("w2", ArrayD::<f64>::zeros(vec![3, 2]));
+
+        let (memoized_expr, memoized) =
+            super::memoize_weight_subexpressions(&expr, expr.as_ref().len() - 1, &weights);
+
+        // Both transposed weights are evaluated ahead of time.
+        assert_eq!(memoized.len(), 2);
+        assert_eq!(memoized[0].1.shape(), &[3, 4]);
+        assert_eq!(memoized[1].1.shape(), &[2, 3]);
+        assert!(memoized_expr.as_ref().iter().all(|node| match node {
+            Language::AccessTranspose(_) => false,
+            Language::Symbol(name) => name != "w1" && name != "w2",
+            _ => true,
+        }));
+    }
+
+    #[test]
+    fn interpret_view_environment() {
+        let expr = RecExpr::<Language>::from_str(BATCHED_MLP).unwrap();
+        let w1 = ArrayD::from_shape_fn(vec![4, 3], |i| (i[0] * 3 + i[1]) as f64 - 5.);
+        let w2 = ArrayD::from_shape_fn(vec![3, 2], |i| (i[0] + i[1]) as f64 * 0.5);
+        let x = ArrayD::from_shape_fn(vec![2, 4], |i| (i[0] * 4 + i[1]) as f64 * 0.25 - 1.);
+
+        let mut views = ViewEnvironment::new();
+        views.insert("w1", w1.view());
+        views.insert("w2", w2.view());
+        views.insert("x", x.view());
+        let mut env = Environment::new();
+        env.insert("w1", w1.clone());
+        env.insert("w2", w2.clone());
+        env.insert("x", x.clone());
+
+        match (
+            interpret(&expr, expr.as_ref().len() - 1, &views),
+            interpret(&expr, expr.as_ref().len() - 1, &env),
+        ) {
+            (Value::Access(from_views), Value::Access(expected)) => {
+                assert_eq!(from_views.access_axis, expected.access_axis);
+                assert_eq!(from_views.tensor, expected.tensor);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn symbols_borrow_environment() {
+        let expr = RecExpr::<Language>::from_str("(shape-of t)").unwrap();
+        let mut env = Environment::new();
+        env.insert("t", array![[1., 2.], [3., 4.]].into_dyn());
+
+        match interpret(&expr, 0, &env) {
+            Value::Tensor(t) => assert_eq!(t.as_ptr(), env["t"].as_ptr()),
+            _ => panic!(),
+        }
+        match interpret(&expr, expr.as_ref().len() - 1, &env).into_owned() {
+            Value::Shape(s) => assert_eq!(s, IxDyn(&[2, 2])),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn matmuls_match_across_data_types() {
+        // Small integers are exact in every DataType, so however the float
+        // matrix multiplications are computed, they must match the integer
+        // ones.
+        let expr = RecExpr::<Language>::from_str(
+            "(compute dot-product
+              (access-cartesian-product
+               (access (access-tensor a) 2)
+               (systolic-array 5 4
+                (access (access-tensor b) 1)
+                (access (access-tensor c) 0))))",
+        )
+        .unwrap();
+        let a = ArrayD::from_shape_fn(vec![2, 3, 4], |i| (i[0] * 12 + i[1] * 4 + i[2]) as i64 - 9);
+        let b = ArrayD::from_shape_fn(vec![4, 5], |i| (i[0] * 5 + i[1]) as i64 % 7 - 3);
+        let c = ArrayD::from_shape_fn(vec![5, 4], |i| (i[0] + 2 * i[1]) as i64 % 5 - 2);
+
+        let mut env_i64 = Environment::new();
+        env_i64.insert("a", a.clone());
+        env_i64.insert("b", b.clone());
+        env_i64.insert("c", c.clone());
+        let mut env_f32 = Environment::new();
+        let mut env_f64 = Environment::new();
+        for (name, tensor) in &[("a", &a), ("b", &b), ("c", &c)] {
+            env_f32.insert(*name, tensor.mapv(|v| v as f32));
+            env_f64.insert(*name, tensor.mapv(|v| v as f64));
+        }
+
+        let expected = match interpret(&expr, expr.as_ref().len() - 1, &env_i64) {
+            Value::Access(a) => a,
+            _ => panic!(),
+        };
+        assert_eq!(expected.tensor.shape(), &[2, 3, 4]);
+        match interpret(&expr, expr.as_ref().len() - 1, &env_f32) {
+            Value::Access(a) => {
+                assert_eq!(a.access_axis, expected.access_axis);
+                assert_eq!(a.tensor, expected.tensor.mapv(|v| v as f32));
+            }
+            _ => panic!(),
+        }
+        match interpret(&expr, expr.as_ref().len() - 1, &env_f64) {
+            Value::Access(a) => {
+                assert_eq!(a.access_axis, expected.access_axis);
+                assert_eq!(a.tensor, expected.tensor.mapv(|v| v as f64));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn layered_lookup() {
+        let mut top = Environment::new();
+        top.insert("a", array![1.].into_dyn());
+        let bottom_a = array![2.].into_dyn();
+        let bottom_b = array![3.].into_dyn();
+        let mut bottom = ViewEnvironment::new();
+        bottom.insert("a", bottom_a.view());
+        bottom.insert("b", bottom_b.view());
+
+        let env = Layered(vec![&top as &dyn Lookup<f64>, &bottom]);
+        assert_eq!(env.lookup("a").unwrap(), array![1.].into_dyn().view());
+        assert_eq!(env.lookup("b").unwrap(), array![3.].into_dyn().view());
+        assert!(env.lookup("c").is_none());
+    }
 }
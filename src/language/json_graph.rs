@@ -0,0 +1,313 @@
+//! A JSON interchange format for Glenside programs.
+//!
+//! Tools outside of Rust (e.g. Python scripts consuming extracted programs)
+//! shouldn't need an s-expression parser to read a program. A [`JsonGraph`]
+//! lays a program out like a Relay JSON graph: a flat list of nodes, each
+//! naming its inputs by index, along with the indices of the input tensors
+//! (`arg_nodes`) and of the result (`heads`). For example, `(compute relu
+//! (access (access-tensor x) 1))` is exported as
+//! ```json
+//! {
+//!   "version": 1,
+//!   "nodes": [
+//!     { "op": "relu" },
+//!     { "op": "x" },
+//!     { "op": "access-tensor", "inputs": [1], "shape": [], "item_shape": [2, 4] },
+//!     { "op": "1" },
+//!     { "op": "access", "inputs": [2, 3], "shape": [2], "item_shape": [4] },
+//!     { "op": "compute", "inputs": [0, 4], "shape": [2], "item_shape": [4] }
+//!   ],
+//!   "arg_nodes": [1],
+//!   "heads": [5],
+//!   "tensors": { "x": { "shape": [2, 4], "dtype": "float32" } }
+//! }
+//! ```
+//!
+//! Each node's `op` is its operator (e.g. `access-windows`) or, for a leaf,
+//! its value as written in an s-expression (e.g. `relu`, `1`, or a tensor's
+//! name). A node's inputs always come before it. Nodes of access pattern type
+//! carry the `shape` and `item_shape` the analysis computes for them, and
+//! hardware atoms (see [`is_hardware_atom`]) carry the name of the `atom`
+//! they run on. Those annotations are written for the benefit of other tools,
+//! and are ignored when a graph is read back in.
+
+use super::dead_code::subexpr;
+use super::stats::operator_kind;
+use super::timing::is_hardware_atom;
+use super::{check_tensor_shape, DataType, Language, MyAnalysis, MyAnalysisData};
+use crate::error::{GlensideError, Result};
+use egg::{EGraph, Id, Language as LanguageTrait, RecExpr};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// The version of the format written by [`to_json_graph`]. Graphs of other
+/// versions are rejected when read.
+pub const VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JsonGraph {
+    pub version: u32,
+    pub nodes: Vec<JsonNode>,
+    /// The indices of the nodes naming input tensors.
+    pub arg_nodes: Vec<usize>,
+    /// The index of the node computing the program's result. Programs with
+    /// several results have an `outputs` node as their single head.
+    pub heads: Vec<usize>,
+    /// The input tensors, by name.
+    #[serde(default)]
+    pub tensors: BTreeMap<String, JsonTensor>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JsonNode {
+    pub op: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inputs: Vec<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shape: Option<Vec<usize>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item_shape: Option<Vec<usize>>,
+    /// The kind of hardware atom the node runs on, e.g. `systolic-array`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub atom: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JsonTensor {
+    pub shape: Vec<usize>,
+    /// The tensor's data type, e.g. `float32`, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dtype: Option<String>,
+}
+
+/// Exports `expr`, rooted at its last node. `analysis` must know the shapes of
+/// all of the tensors in `expr`.
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::json_graph::{to_json_graph, JsonGraph};
+/// use glenside::language::{Language, MyAnalysis};
+///
+/// let expr: RecExpr<Language> = "(compute relu (access (access-tensor x) 1))"
+///     .parse()
+///     .unwrap();
+/// let mut analysis = MyAnalysis::default();
+/// analysis.name_to_shape.insert("x".to_string(), vec![2, 4]);
+///
+/// let json = to_json_graph(&expr, analysis).to_json().to_string();
+/// let (imported, analysis) = JsonGraph::from_json(&json).unwrap().to_expr().unwrap();
+/// assert_eq!(imported, expr);
+/// assert_eq!(analysis.name_to_shape["x"], vec![2, 4]);
+/// ```
+pub fn to_json_graph(expr: &RecExpr<Language>, analysis: MyAnalysis) -> JsonGraph {
+    let mut tensors = BTreeMap::default();
+    let mut arg_nodes = Vec::default();
+    for (index, node) in expr.as_ref().iter().enumerate() {
+        if let Language::Symbol(name) = node {
+            if let Some(shape) = analysis.name_to_shape.get(name) {
+                arg_nodes.push(index);
+                tensors.insert(
+                    name.clone(),
+                    JsonTensor {
+                        shape: shape.clone(),
+                        dtype: analysis.name_to_dtype.get(name).map(|d| d.to_string()),
+                    },
+                );
+            }
+        }
+    }
+
+    let mut egraph = EGraph::new(analysis);
+    let mut ids: Vec<Id> = Vec::with_capacity(expr.as_ref().len());
+    let mut nodes = Vec::with_capacity(expr.as_ref().len());
+    for node in expr.as_ref() {
+        let id = egraph.add(node.clone().map_children(|child| ids[usize::from(child)]));
+        ids.push(id);
+        let (shape, item_shape) = match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => (
+                Some(a.shape.slice().to_vec()),
+                Some(a.item_shape.slice().to_vec()),
+            ),
+            _ => (None, None),
+        };
+        nodes.push(JsonNode {
+            op: node.to_string(),
+            inputs: node.children().iter().map(|id| usize::from(*id)).collect(),
+            shape,
+            item_shape,
+            atom: if is_hardware_atom(node) {
+                Some(operator_kind(node))
+            } else {
+                None
+            },
+        });
+    }
+
+    JsonGraph {
+        version: VERSION,
+        nodes,
+        arg_nodes,
+        heads: vec![expr.as_ref().len() - 1],
+        tensors,
+    }
+}
+
+impl JsonGraph {
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap()
+    }
+
+    /// Parses a graph from JSON.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let graph: JsonGraph = serde_json::from_str(json)
+            .map_err(|e| GlensideError::Parse(format!("Invalid JSON graph: {}", e)))?;
+        if graph.version != VERSION {
+            return Err(GlensideError::Parse(format!(
+                "Unsupported JSON graph version {}; expected {}",
+                graph.version, VERSION
+            )));
+        }
+        Ok(graph)
+    }
+
+    /// The program the graph holds, rooted at its last node, along with an
+    /// analysis knowing the shapes (and data types) of its tensors. Nodes the
+    /// head doesn't use are dropped.
+    pub fn to_expr(&self) -> Result<(RecExpr<Language>, MyAnalysis)> {
+        let error = |index: usize, message: String| {
+            GlensideError::Parse(format!("Node {} of JSON graph: {}", index, message))
+        };
+
+        let mut expr = RecExpr::default();
+        for (index, node) in self.nodes.iter().enumerate() {
+            if let Some(input) = node.inputs.iter().find(|input| **input >= index) {
+                return Err(error(
+                    index,
+                    format!("input {} doesn't come before the node", input),
+                ));
+            }
+            let children = node.inputs.iter().map(|input| Id::from(*input)).collect();
+            let node = Language::from_op_str(&node.op, children).map_err(|e| error(index, e))?;
+            expr.add(node);
+        }
+
+        let head = match self.heads[..] {
+            [head] if head < self.nodes.len() => head,
+            [head] => {
+                return Err(GlensideError::Parse(format!(
+                    "Head {} of JSON graph is out of range",
+                    head
+                )))
+            }
+            _ => {
+                return Err(GlensideError::Parse(format!(
+                    "Expected a JSON graph with one head, found {}",
+                    self.heads.len()
+                )))
+            }
+        };
+
+        let mut analysis = MyAnalysis::default();
+        for (name, tensor) in &self.tensors {
+            check_tensor_shape(name, &tensor.shape).map_err(GlensideError::Parse)?;
+            analysis
+                .name_to_shape
+                .insert(name.clone(), tensor.shape.clone());
+            if let Some(dtype) = &tensor.dtype {
+                analysis.name_to_dtype.insert(
+                    name.clone(),
+                    DataType::from_str(dtype).map_err(|e| {
+                        GlensideError::Parse(format!("Data type of tensor {}: {}", name, e))
+                    })?,
+                );
+            }
+        }
+
+        Ok((subexpr(&expr, head), analysis))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let expr: RecExpr<Language> = "
+         (systolic-array 4 4
+          (access (access-tensor x) 1)
+          (access (access-transpose (access-tensor w) (list 1 0)) 0))"
+            .parse()
+            .unwrap();
+        let mut analysis = MyAnalysis::default();
+        analysis.name_to_shape.insert("x".to_string(), vec![2, 4]);
+        analysis.name_to_shape.insert("w".to_string(), vec![4, 4]);
+        analysis
+            .name_to_dtype
+            .insert("x".to_string(), DataType::Float(32));
+
+        let graph = to_json_graph(&expr, analysis);
+        let root = graph.nodes.last().unwrap();
+        assert_eq!(root.atom, Some("systolic-array".to_string()));
+        assert_eq!(root.shape, Some(vec![2]));
+        assert_eq!(root.item_shape, Some(vec![4]));
+        assert_eq!(graph.arg_nodes.len(), 2);
+        assert_eq!(graph.tensors["x"].dtype, Some("float32".to_string()));
+        assert_eq!(graph.tensors["w"].dtype, None);
+
+        let graph = JsonGraph::from_json(&graph.to_json().to_string()).unwrap();
+        let (imported, analysis) = graph.to_expr().unwrap();
+        assert_eq!(imported, expr);
+        assert_eq!(analysis.name_to_shape["w"], vec![4, 4]);
+        assert_eq!(analysis.name_to_dtype["x"], DataType::Float(32));
+    }
+
+    #[test]
+    fn head_before_end() {
+        let graph = JsonGraph::from_json(
+            r#"{
+                "version": 1,
+                "nodes": [
+                    { "op": "x" },
+                    { "op": "access-tensor", "inputs": [0] },
+                    { "op": "y" }
+                ],
+                "arg_nodes": [0],
+                "heads": [1],
+                "tensors": { "x": { "shape": [3] } }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(graph.to_expr().unwrap().0.pretty(80), "(access-tensor x)");
+    }
+
+    #[test]
+    fn errors() {
+        let graph = |nodes: &str, heads: &str| {
+            JsonGraph::from_json(&format!(
+                r#"{{ "version": 1, "nodes": {}, "arg_nodes": [], "heads": {} }}"#,
+                nodes, heads
+            ))
+            .and_then(|graph| graph.to_expr())
+            .map(|(expr, _)| expr)
+        };
+
+        assert_eq!(
+            graph(r#"[{ "op": "access-tensor", "inputs": [0] }]"#, "[0]"),
+            Err(GlensideError::Parse(
+                "Node 0 of JSON graph: input 0 doesn't come before the node".to_string()
+            ))
+        );
+        assert!(graph(
+            r#"[{ "op": "x" }, { "op": "access", "inputs": [0] }]"#,
+            "[1]"
+        )
+        .is_err());
+        assert!(graph(r#"[{ "op": "x" }]"#, "[]").is_err());
+        assert!(graph(r#"[{ "op": "x" }]"#, "[1]").is_err());
+        assert!(JsonGraph::from_json(
+            r#"{ "version": 2, "nodes": [], "arg_nodes": [], "heads": [] }"#
+        )
+        .is_err());
+    }
+}
@@ -1,3 +1,6 @@
+use crate::error::{bail, ensure, ensure_eq, ensure_ne, GlensideError};
+use crate::language::access_shape::AccessShape;
+use crate::language::shape_cache::ShapeCache;
 use crate::language::RelayOperator::*;
 use egg::{define_language, DidMerge, EGraph, Id, Language as LanguageTrait};
 use itertools::{any, multizip};
@@ -40,6 +43,27 @@ define_language! {
         // smaller systolic array.
         "systolic-array-with-blocking" = SystolicArrayWithBlocking([Id; 4]),
 
+        // (systolic-array-with-activation <activation: ComputeType>
+        //                                 <rows: Num> <cols: Num>
+        //                                 <access-0> <access-1>)
+        // A systolic array whose outputs pass through an activation unit on
+        // their way out of the array, fusing the activation into the matrix
+        // multiply. Equivalent to
+        // (compute <activation> (systolic-array <rows> <cols> <access-0> <access-1>)).
+        // Discovered by rewrites::systolic_array_with_activation().
+        "systolic-array-with-activation" = SystolicArrayWithActivation([Id; 5]),
+
+        // (sparse-systolic-array <rows: Num> <cols: Num> <access-0> <access-1>)
+        // A systolic array which skips the zero blocks of a sparse weight
+        // matrix. Equivalent to
+        // (compute sparse-dot-product (access-cartesian-product <access-0> <access-1>)).
+        // Unlike the dense systolic array, the weights are taken in the layout
+        // they're stored in: <access-0> has shape [M] [N] (or [] [N]), and
+        // <access-1>, the weights, has shape [O] [N], where <rows> is N and
+        // <cols> is O. The result has shape [M, O] [] (or [O] []).
+        // Discovered by rewrites::sparse_systolic_array().
+        "sparse-systolic-array" = SparseSystolicArray([Id; 4]),
+
         // (systolic-array-conv2d-nchw-oihw-with-blocking
         //  <rows: Num> <cols: Num>
         //  <weights: Access> <data: Access>
@@ -73,12 +97,49 @@ define_language! {
         "systolic-array-conv2d-im2col-nchw-oihw-with-blocking" = SystolicArrayConv2dIm2colNchwOihwWithBlocking([Id; 8]),
         "systolic-array-conv2d-im2col-nhwc-hwio-with-blocking" = SystolicArrayConv2dIm2colNhwcHwioWithBlocking([Id; 8]),
 
+        // (pooling-unit <pool-type: ComputeType> <access: Access>
+        //               <window-shape: Shape> <stride-shape: Shape>)
+        // A pooling unit: a hardware atom which reduces windows of its input,
+        // so that pooling layers needn't be mapped to the systolic arrays.
+        // Equivalent to
+        // (compute <pool-type> (access-windows <access> <window-shape> <stride-shape>)),
+        // where <pool-type> is reduce-max (max pooling) or reduce-mean
+        // (average pooling). Discovered by rewrites::pooling_unit().
+        "pooling-unit" = PoolingUnit([Id; 4]),
+
+        // (activation-unit <activation: ComputeType> <access: Access>)
+        // An activation unit: a hardware atom which applies an activation
+        // function elementwise, using a lookup table for the nonlinear
+        // functions. Equivalent to (compute <activation> <access>), where
+        // <activation> is relu, sigmoid, or tanh (see
+        // ComputeType::is_activation()), or softmax, in which case <access>
+        // must have vector items. Discovered by rewrites::activation_unit().
+        "activation-unit" = ActivationUnit([Id; 2]),
+
+        // (vector-alu <op: ComputeType> <lanes: Num> <access: Access>)
+        // A vector ALU: a hardware atom which adds or multiplies two vectors of
+        // <lanes> elements each time it's invoked. <access> must have item
+        // shape [2, <lanes>]; each item is one invocation. Equivalent to
+        // (compute <op> <access>), where <op> is elementwise-add or
+        // elementwise-mul. Larger elementwise operations are split across
+        // invocations by rewrites::split_elementwise_for_vector_alu(), and
+        // then discovered by rewrites::vector_alu().
+        "vector-alu" = VectorAlu([Id; 3]),
+
         // (access-windows <access> <filters-shape: Shape> <stride-shape: Shape>)
         // Form the windows which will be convolved over.
         // TODO(@gussmith23) AccessWindows shouldn't be specific to filters.
         // AccessWindows is used in other contexts too, i.e. pooling.
         "access-windows" = AccessWindows([Id; 3]),
 
+        // (access-windows-with-convention <access> <filters-shape: Shape>
+        //  <stride-shape: Shape> <convention: WindowConvention>
+        //  <pad-type: PadType>)
+        // Like access-windows, but places as many windows along each item
+        // axis as <convention> calls for, padding the axis with <pad-type>
+        // where they don't fit. See WindowConvention.
+        "access-windows-with-convention" = AccessWindowsWithConvention([Id; 5]),
+
         // (shape-of <tensor>)
         // Returns the shape of the tensor.
         // TODO(@gussmith) Choose between ([Id; 1]) and (Id) and be consistent
@@ -99,6 +160,42 @@ define_language! {
         // Removes axis from shape.
         "shape-remove-axis" = ShapeRemoveAxis([Id; 2]),
 
+        // (shape-concat <shape: Shape> <shape: Shape>)
+        // Concatenates two shapes.
+        "shape-concat" = ShapeConcat([Id; 2]),
+
+        // (shape-dim <shape: Shape> <axis: usize>)
+        // Gets the value of a single dimension of a shape, as a usize.
+        "shape-dim" = ShapeDim([Id; 2]),
+
+        // (+ <a: usize> <b: usize>), (- <a: usize> <b: usize>),
+        // (* <a: usize> <b: usize>), (/ <a: usize> <b: usize>)
+        // Arithmetic over usizes, for computing things like tile counts and
+        // padded sizes in the IR. Division rounds down. These are
+        // constant-folded by the analysis: any eclass with a known usize value
+        // also contains that value as a literal.
+        "+" = UsizeAdd([Id; 2]),
+        "-" = UsizeSub([Id; 2]),
+        "*" = UsizeMul([Id; 2]),
+        "/" = UsizeDiv([Id; 2]),
+
+        // (tensor-decl <name: Symbol> <shape: Shape>)
+        // Declares that the tensor <name> has shape <shape>, and evaluates to
+        // that tensor; e.g. (access-tensor (tensor-decl x (shape 32 64))).
+        // This makes expressions self-describing: see
+        // MyAnalysis::from_tensor_decls, which builds the name-to-shape map
+        // from the declarations in an expression.
+        "tensor-decl" = TensorDecl([Id; 2]),
+
+        // (unroll <i: Symbol> <n: usize> <body: Access>)
+        // <n> copies of <body>, concatenated along axis 0, where the symbol
+        // <i> stands for 0 in the first copy, 1 in the second, and so on; e.g.
+        // (unroll t 4 (compute relu (access-slice x 0 t (+ t 1)))) for a
+        // sequence x of length 4. Lets frontends import fixed-length sequence
+        // models without writing the unrolled graph themselves. Unrolls must
+        // be expanded (see crate::language::unroll) before analysis.
+        "unroll" = Unroll([Id; 3]),
+
         // (access <tensor> <dim>)
         // The most basic access pattern.
         // Let <tensor> have dims d0, .., dn.
@@ -136,6 +233,144 @@ define_language! {
         // shape of the tensors to be dot-producted with one another.
         "compute" = Compute([Id; 2]),
 
+        // (compute-with-accumulator <compute-type> <accumulator-dtype: DataType>
+        //                           <access>)
+        // Like compute, but for the reductions dot-product and reduce-sum,
+        // which accumulate in <accumulator-dtype>: each product, and each
+        // partial sum, is converted to <accumulator-dtype> as with cast. For
+        // example, int8 dot products should accumulate in int32. Plain compute
+        // accumulates in whatever type the tensors are represented in.
+        "compute-with-accumulator" = ComputeWithAccumulator([Id; 3]),
+
+        // (cast <dtype: DataType> <access>)
+        // Converts each element of <access> to <dtype>, e.g. at the
+        // quantize/dequantize boundaries of imported quantized models. Casting
+        // to an integer type truncates toward zero and then wraps around to
+        // the type's range; casting to bool maps nonzero values to 1. The
+        // result has the same shape as <access>. See
+        // interpreter::Cast for how the interpreter represents the converted
+        // values.
+        "cast" = Cast([Id; 2]),
+
+        // (requantize <out-dtype: DataType> <access>
+        //             <input-scale: float> <input-zero-point: int>
+        //             <output-scale: float> <output-zero-point: int>
+        //             <rounding: RoundingMode>)
+        // Requantizes each (integer) element of <access> from the input
+        // quantization parameters to the output quantization parameters,
+        // clamping to the range of <out-dtype>. The interpreter matches the
+        // fixed-point arithmetic of TFLite's reference kernels bit-for-bit
+        // (when <rounding> is tonearest). The result has the same shape as
+        // <access>.
+        "requantize" = Requantize([Id; 7]),
+
+        // (conv2d <data: Access> <weights: Access>
+        //         <strides: Shape> <padding: Shape> <groups: usize>)
+        // High-level 2D convolution, as emitted by frontends. <data> is in
+        // layout NCHW and <weights> in layout OIHW. <strides> is (stride-h
+        // stride-w) and <padding> follows Relay's (top left bottom right)
+        // convention. The result has shape [N, O, new-H, new-W] and an empty
+        // item shape.
+        // The interpreter evaluates this node directly; for hardware mapping,
+        // it is lowered into the access-windows formulation by
+        // rewrites::conv2d_to_access_windows().
+        "conv2d" = Conv2d([Id; 5]),
+
+        // (conv1d <data: Access> <weights: Access>
+        //         <strides: Shape> <padding: Shape> <groups: usize>)
+        // High-level 1D convolution. Same as conv2d, but with <data> in layout
+        // NCW, <weights> in layout OIW, <strides> of the form (stride-w) and
+        // <padding> of the form (before after).
+        "conv1d" = Conv1d([Id; 5]),
+
+        // (conv3d <data: Access> <weights: Access>
+        //         <strides: Shape> <padding: Shape> <groups: usize>)
+        // High-level 3D convolution. Same as conv2d, but with <data> in layout
+        // NCDHW, <weights> in layout OIDHW, <strides> of the form
+        // (stride-d stride-h stride-w) and <padding> of the form
+        // (front top left back bottom right).
+        "conv3d" = Conv3d([Id; 5]),
+
+        // (conv2d-transpose <data: Access> <weights: Access>
+        //                   <strides: Shape> <padding: Shape>
+        //                   <output-padding: Shape>)
+        // High-level 2D transposed convolution (sometimes called
+        // deconvolution), as used in decoders to upsample. <data> is in layout
+        // NCHW and <weights> in layout IOHW. Each input element scatters a
+        // copy of the kernel, scaled by the element, into the output, with
+        // copies <strides> (stride-h stride-w) apart. <padding> (top left
+        // bottom right) is then cropped from the output, and <output-padding>
+        // (h w) adds rows and columns at the bottom and right. Thus the result
+        // has shape [N, O, new-H, new-W], where
+        //   new-H = (H - 1) * stride-h + KH - top - bottom + output-padding-h.
+        // Only ungrouped transposed convolutions are supported. Lowered by
+        // rewrites::conv2d_transpose_to_access_windows().
+        "conv2d-transpose" = Conv2dTranspose([Id; 5]),
+
+        // (bias-add <data: Access> <bias: Access> <axis: usize>)
+        // High-level bias addition. <bias> is one-dimensional, with length
+        // equal to the length of <data> at <axis>; it is broadcast along all
+        // other axes of <data> and added. Typically, <axis> is the channel
+        // axis of a convolution or dense layer's output.
+        "bias-add" = BiasAdd([Id; 3]),
+
+        // (adaptive-pool2d <pool type: ComputeType> <data: Access>
+        //                  <output size: Shape>)
+        // High-level 2D adaptive pooling. <data> is in layout NCHW, and
+        // <output size> is (out-H out-W). Output element (i, j) reduces rows
+        // floor(i * H / out-H) up to (but not including)
+        // ceil((i + 1) * H / out-H) of <data>, and similarly for columns.
+        // <pool type> is reduce-mean (average pooling) or reduce-max (max
+        // pooling). Global pooling is adaptive pooling with an output size of
+        // (1 1). The result has shape [N, C, out-H, out-W] and an empty item
+        // shape. When the output size evenly divides the input size, the
+        // windows don't overlap and all have the same size; such pools are
+        // lowered by rewrites::adaptive_pool2d_to_access_windows().
+        "adaptive-pool2d" = AdaptivePool2d([Id; 3]),
+
+        // (batch-matmul <a: Access> <b: Access>)
+        // High-level batched matrix multiplication, as found in attention
+        // blocks: <a> has shape [B, M, K] and <b> has shape [B, K, N], and the
+        // result, with shape [B, M, N] and an empty item shape, holds the B
+        // products of corresponding matrices. (Relay's batch_matmul instead
+        // takes <b> as [B, N, K].) Lowered by
+        // rewrites::batch_matmul_to_access_pattern() and
+        // rewrites::batch_matmul_to_systolic_arrays().
+        "batch-matmul" = BatchMatmul([Id; 2]),
+
+        // (layer-norm <data: Access> <gamma: Access> <beta: Access>
+        //             <epsilon: Literal>)
+        // High-level layer normalization, as found in transformers. Each item
+        // of <data> is normalized to zero mean and unit variance, with the
+        // scalar <epsilon> added to the variance to keep the division stable.
+        // The result is then scaled elementwise by <gamma> and shifted by
+        // <beta>, which both have the item shape of <data>. Normalizing over
+        // the last axis, as transformers do, means accessing <data> at its
+        // last axis. The result has the shape and item shape of <data>.
+        // Lowered by rewrites::layer_norm_to_mean_variance().
+        "layer-norm" = LayerNorm([Id; 4]),
+
+        // (group-norm <data: Access> <gamma: Access> <beta: Access>
+        //             <groups: usize> <epsilon: Literal>)
+        // High-level group normalization, as found in vision models. <data> is
+        // in layout NC... (e.g. NCHW). Its C channels are divided into
+        // <groups> groups of consecutive channels, and each group of each
+        // batch element is normalized as by layer-norm, over all of its
+        // values. <gamma> and <beta> are vectors of length C, which scale and
+        // shift each channel. With one group, this is layer normalization
+        // over everything but the batch axis; with C groups, it's instance
+        // normalization. The result has the shape of <data> and an empty item
+        // shape. Lowered by rewrites::group_norm_to_mean_variance().
+        "group-norm" = GroupNorm([Id; 5]),
+
+        // (dropout <data: Access> <rate: Float64>)
+        // Dropout, as found in imported training graphs. Glenside compiles for
+        // inference, where dropout is the identity, so the result is <data>
+        // itself. <rate> is the probability of dropping an element in
+        // training, and is kept only for reference. Removed by
+        // rewrites::remove_dropout().
+        "dropout" = Dropout([Id; 2]),
+
         // (get-access-shape <access>)
         // Returns the shape of the access.
         "get-access-shape" = GetAccessShape([Id;1]),
@@ -176,6 +411,13 @@ define_language! {
         // Get the item at the ith index of tuple
         "tuple-get-item" = TupleGetItem([Id;2]),
 
+        // (outputs <value> <value> ...)
+        // The root of a program with multiple outputs, e.g. a detection model
+        // with separate box and class heads. The outputs may share
+        // subexpressions. Output names are tracked alongside the expression;
+        // see crate::language::multi_output.
+        "outputs" = Outputs(Box<[Id]>),
+
         // (access-shape <shape: shape> <item-shape: shape>)
         // Access shape literal.
         "access-shape" = AccessShape([Id;2]),
@@ -220,6 +462,11 @@ define_language! {
         // (access-insert-axis <a> <axis (usize)>)
         "access-insert-axis" = AccessInsertAxis([Id; 2]),
 
+        // (access-reverse <a> <axis (usize)>)
+        // Reverses the order of the elements of <a> along <axis>, e.g. to flip
+        // a convolution kernel spatially. The shape is unchanged.
+        "access-reverse" = AccessReverse([Id; 2]),
+
         // (access-broadcast <a> <shape: shape>)
         // Simple broadcasting. <a> and <shape> must have the same total number
         // of dimensions. All dimensions in <a> must either match the
@@ -246,6 +493,9 @@ define_language! {
         // (constant-tensor <value> <shape>)
         "constant-tensor" = ConstantTensor([Id; 2]),
 
+        // Axis arguments to access, access-slice, access-pad, access-squeeze,
+        // and access-transpose's list may be negative, in which case they
+        // count back from the last axis (so -1 is the last axis).
         Num(i64),
 
         DataType(DataType),
@@ -262,6 +512,12 @@ define_language! {
         // (No other options right now)
         PadType(PadType),
 
+        // window convention: floor-windows, ceil-windows, or same-windows
+        WindowConvention(WindowConvention),
+
+        // rounding mode: upward or tonearest
+        RoundingMode(RoundingMode),
+
         ComputeType(ComputeType),
 
         AcceleratorFunc(AcceleratorFunc),
@@ -640,13 +896,55 @@ impl Display for RelayKernelLayout {
     }
 }
 
+/// Specifies how ties are rounded when requantizing. Named after the rounding
+/// modes of Relay's `qnn.requantize`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, PartialOrd, Ord, Copy)]
+pub enum RoundingMode {
+    /// Round ties toward positive infinity.
+    Upward,
+    /// Round ties away from zero. Requantizing with this mode matches TFLite's
+    /// reference kernels exactly, including their intermediate rounding.
+    ToNearest,
+}
+impl FromStr for RoundingMode {
+    type Err = ();
+    fn from_str(input: &str) -> Result<RoundingMode, Self::Err> {
+        match input {
+            "upward" => Ok(RoundingMode::Upward),
+            "tonearest" => Ok(RoundingMode::ToNearest),
+            _ => Err(()),
+        }
+    }
+}
+impl Display for RoundingMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                RoundingMode::Upward => "upward",
+                RoundingMode::ToNearest => "tonearest",
+            }
+        )
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum ComputeType {
     DotProduct,
+    /// A dot product whose second operand is a sparse weight. Computes the
+    /// same thing as [`ComputeType::DotProduct`], but marks the computation as
+    /// one which a sparsity-aware atom should perform (see
+    /// [`crate::language::sparse`]).
+    SparseDotProduct,
     ReduceSum,
     ReLU,
     Sqrt,
     Negative,
+    /// Elementwise logistic function, `1 / (1 + e^-x)`.
+    Sigmoid,
+    /// Elementwise hyperbolic tangent.
+    Tanh,
     /// Expects item shape of `a x b1 x .. x bn`. Performs an elementwise
     /// addition of the `a` tensors of size `b1 x .. x bn`.
     /// TODO(@gussmith) Multiple-arg compute feels clunky and ad-hoc.
@@ -666,22 +964,45 @@ pub enum ComputeType {
     /// For an item shape of `a1 x a2 x ...`, returns an item shape of `1` where
     /// the returned scalar is the mean of the `a1 x a2 x ...`-shaped tensor.
     ReduceMean,
+    /// Computes both the mean and the (biased) variance of each item, in one
+    /// pass. Produces a tuple of two accesses, of the means and of the
+    /// variances, each shaped like the result of [`ComputeType::ReduceMean`];
+    /// take them apart with `tuple-get-item`.
+    ReduceMeanVar,
+    /// Expects item shape of `a x b1 x .. x bn`. Performs an elementwise
+    /// logical and of the `a` tensors of size `b1 x .. x bn`.
+    ///
+    /// Glenside has no boolean tensors; truth values are represented as masks
+    /// in whatever datatype is being computed on. Any nonzero value is true,
+    /// and logical computes produce `1` for true and `0` for false.
+    LogicalAnd,
+    /// Like [`ComputeType::LogicalAnd`], but performs a logical or.
+    LogicalOr,
+    /// Elementwise logical not: `1` where the input is zero, `0` elsewhere.
+    LogicalNot,
 }
 impl FromStr for ComputeType {
     type Err = ();
     fn from_str(input: &str) -> Result<ComputeType, Self::Err> {
         match input {
             "dot-product" => Ok(ComputeType::DotProduct),
+            "sparse-dot-product" => Ok(ComputeType::SparseDotProduct),
             "reduce-sum" => Ok(ComputeType::ReduceSum),
             "reduce-max" => Ok(ComputeType::ReduceMax),
             "relu" => Ok(ComputeType::ReLU),
             "sqrt" => Ok(ComputeType::Sqrt),
             "negative" => Ok(ComputeType::Negative),
+            "sigmoid" => Ok(ComputeType::Sigmoid),
+            "tanh" => Ok(ComputeType::Tanh),
             "elementwise-add" => Ok(ComputeType::ElementwiseAdd),
             "elementwise-mul" => Ok(ComputeType::ElementwiseMul),
             "elementwise-div" => Ok(ComputeType::ElementwiseDiv),
             "softmax" => Ok(ComputeType::Softmax),
             "reduce-mean" => Ok(ComputeType::ReduceMean),
+            "reduce-mean-var" => Ok(ComputeType::ReduceMeanVar),
+            "logical-and" => Ok(ComputeType::LogicalAnd),
+            "logical-or" => Ok(ComputeType::LogicalOr),
+            "logical-not" => Ok(ComputeType::LogicalNot),
             _ => Err(()),
         }
     }
@@ -693,20 +1014,45 @@ impl Display for ComputeType {
             "{}",
             match self {
                 ComputeType::DotProduct => "dot-product",
+                ComputeType::SparseDotProduct => "sparse-dot-product",
                 ComputeType::ReduceSum => "reduce-sum",
                 ComputeType::ReduceMax => "reduce-max",
                 ComputeType::ReLU => "relu",
                 ComputeType::Sqrt => "sqrt",
                 ComputeType::Negative => "negative",
+                ComputeType::Sigmoid => "sigmoid",
+                ComputeType::Tanh => "tanh",
                 ComputeType::ElementwiseAdd => "elementwise-add",
                 ComputeType::ElementwiseMul => "elementwise-mul",
                 ComputeType::ElementwiseDiv => "elementwise-div",
                 ComputeType::Softmax => "softmax",
                 ComputeType::ReduceMean => "reduce-mean",
+                ComputeType::ReduceMeanVar => "reduce-mean-var",
+                ComputeType::LogicalAnd => "logical-and",
+                ComputeType::LogicalOr => "logical-or",
+                ComputeType::LogicalNot => "logical-not",
             }
         )
     }
 }
+impl ComputeType {
+    /// Whether this compute type is an elementwise activation function,
+    /// which can be run on an activation unit or fused into a systolic array.
+    pub fn is_activation(&self) -> bool {
+        match self {
+            ComputeType::ReLU | ComputeType::Sigmoid | ComputeType::Tanh => true,
+            _ => false,
+        }
+    }
+
+    /// Whether this compute type can be run on an activation unit: the
+    /// elementwise activation functions, plus softmax, whose exponentials
+    /// are looked up like sigmoid's. Softmax is only supported over vector
+    /// items.
+    pub fn runs_on_activation_unit(&self) -> bool {
+        self.is_activation() || *self == ComputeType::Softmax
+    }
+}
 
 /// Specifies how to pick the values we pad with.
 #[derive(Debug, PartialEq, Eq, Hash, Clone, PartialOrd, Ord, Copy)]
@@ -810,13 +1156,41 @@ pub enum MyAnalysisData {
     //Tensor(TensorData),
     ComputeType(ComputeType),
     PadType(PadType),
+    WindowConvention(WindowConvention),
+    RoundingMode(RoundingMode),
     List(Vec<usize>),
+    // A list containing negative axes, which can only be resolved once we know
+    // the rank of the access it's applied to.
+    AxisList(Vec<i64>),
     RelayOperator(RelayOperator),
     RelayActivationLayout(RelayActivationLayout),
     RelayKernelLayout(RelayKernelLayout),
     AcceleratorFunc(AcceleratorFuncData),
 }
 
+impl MyAnalysisData {
+    /// The name of the kind of data this is, for error messages.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            MyAnalysisData::Literal(_) => "a literal",
+            MyAnalysisData::Num(_) => "a number",
+            MyAnalysisData::DataType(_) => "a data type",
+            MyAnalysisData::AccessPattern(_) => "an access pattern",
+            MyAnalysisData::Shape(_) => "a shape",
+            MyAnalysisData::Tuple(_) => "a tuple",
+            MyAnalysisData::ComputeType(_) => "a compute type",
+            MyAnalysisData::PadType(_) => "a pad type",
+            MyAnalysisData::WindowConvention(_) => "a window convention",
+            MyAnalysisData::RoundingMode(_) => "a rounding mode",
+            MyAnalysisData::List(_) | MyAnalysisData::AxisList(_) => "a list",
+            MyAnalysisData::RelayOperator(_) => "a Relay operator",
+            MyAnalysisData::RelayActivationLayout(_) => "a Relay activation layout",
+            MyAnalysisData::RelayKernelLayout(_) => "a Relay kernel layout",
+            MyAnalysisData::AcceleratorFunc(_) => "an accelerator function",
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone, PartialOrd, Ord, Copy)]
 pub enum DataType {
     Bool,
@@ -1314,40 +1688,351 @@ impl std::ops::IndexMut<usize> for AccessPatternData {
     }
 }
 
+/// The number of windows along each axis when windowing a tensor of shape
+/// `access_shape` with windows of shape `filters_shape`, spaced `stride_shape`
+/// apart. Only windows which fit entirely within the tensor are placed, i.e.
+/// the count follows [`WindowConvention::Floor`].
+///
+/// Panics, naming the offending axis, if a window doesn't fit in the tensor
+/// along some axis, or if a window or stride is empty. Unchecked, these would
+/// wrap around to absurdly large window counts. See
+/// [`try_access_windows_resulting_shape`] for a version which returns the
+/// error instead.
+///
+/// ```
+/// use glenside::language::access_windows_resulting_shape;
+/// use ndarray::IxDyn;
+///
+/// assert_eq!(
+///     access_windows_resulting_shape(&IxDyn(&[3, 32, 32]), &IxDyn(&[3, 3, 3]), &IxDyn(&[1, 2, 2])),
+///     vec![1, 15, 15]
+/// );
+/// ```
 pub fn access_windows_resulting_shape(
     access_shape: &IxDyn,
     filters_shape: &IxDyn,
     stride_shape: &IxDyn,
 ) -> Vec<usize> {
-    assert_eq!(access_shape.ndim(), stride_shape.ndim());
-    assert_eq!(filters_shape.ndim(), stride_shape.ndim());
+    try_access_windows_resulting_shape(access_shape, filters_shape, stride_shape)
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Like [`access_windows_resulting_shape`], but returns a description of the
+/// problem rather than panicking.
+///
+/// ```
+/// use glenside::language::try_access_windows_resulting_shape;
+/// use ndarray::IxDyn;
+///
+/// assert_eq!(
+///     try_access_windows_resulting_shape(&IxDyn(&[2]), &IxDyn(&[3]), &IxDyn(&[1])),
+///     Err("access-windows axis 0 (input size 2, filter size 3, stride 1): \
+///          filter is larger than input"
+///         .to_string())
+/// );
+/// ```
+pub fn try_access_windows_resulting_shape(
+    access_shape: &IxDyn,
+    filters_shape: &IxDyn,
+    stride_shape: &IxDyn,
+) -> std::result::Result<Vec<usize>, String> {
+    if access_shape.ndim() != stride_shape.ndim() {
+        return Err(format!(
+            "access-windows input has {} axes, but strides have {}",
+            access_shape.ndim(),
+            stride_shape.ndim()
+        ));
+    }
+    if filters_shape.ndim() != stride_shape.ndim() {
+        return Err(format!(
+            "access-windows filters have {} axes, but strides have {}",
+            filters_shape.ndim(),
+            stride_shape.ndim()
+        ));
+    }
 
     multizip((
         access_shape.slice().iter(),
         filters_shape.slice().iter(),
         stride_shape.slice().iter(),
     ))
+    .enumerate()
     .map(
-        |(&dim_len, &kernel_dim_len, &stride): (&usize, &usize, &usize)| {
-            let total_dim_len = dim_len;
-            assert!(
-                total_dim_len >= kernel_dim_len,
-                "{} !>= {}",
-                total_dim_len,
-                kernel_dim_len
-            );
-            let num_spots = total_dim_len - (kernel_dim_len - 1);
-            (num_spots + stride - 1) / stride
+        |(axis, (&dim_len, &kernel_dim_len, &stride)): (usize, (&usize, &usize, &usize))| {
+            let error = |problem: &str| {
+                Err(format!(
+                    "access-windows axis {} (input size {}, filter size {}, stride {}): {}",
+                    axis, dim_len, kernel_dim_len, stride, problem
+                ))
+            };
+            if kernel_dim_len == 0 {
+                return error("filter is empty");
+            }
+            if stride == 0 {
+                return error("stride must be positive");
+            }
+            if kernel_dim_len > dim_len {
+                return error("filter is larger than input");
+            }
+            Ok(WindowConvention::Floor.num_windows(dim_len, kernel_dim_len, stride))
         },
     )
     .collect()
 }
 
+/// How many windows are placed along an axis whose length doesn't fit a whole
+/// number of strides, and how the axis is padded to fit them. The
+/// `access-windows` node only places windows which fit within its input, i.e.
+/// it follows [`WindowConvention::Floor`];
+/// `access-windows-with-convention` takes the convention as an argument
+/// (written `floor-windows`, `ceil-windows`, or `same-windows`), and both the
+/// analysis and the interpreter pad its input as the convention requires (see
+/// [`windows`](super::windows)).
+///
+/// For an axis of length `n`, windows of length `k`, and a stride of `s`:
+/// ```
+/// use glenside::language::WindowConvention;
+///
+/// // n = 5, k = 2, s = 2.
+/// assert_eq!(WindowConvention::Floor.num_windows(5, 2, 2), 2);
+/// assert_eq!(WindowConvention::Ceil.num_windows(5, 2, 2), 3);
+/// assert_eq!(WindowConvention::Ceil.padding(5, 2, 2), (0, 1));
+/// // n = 5, k = 3, s = 1.
+/// assert_eq!(WindowConvention::Same.num_windows(5, 3, 1), 5);
+/// assert_eq!(WindowConvention::Same.padding(5, 3, 1), (1, 1));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum WindowConvention {
+    /// floor((n - k) / s) + 1 windows, each entirely within the axis. This is
+    /// PyTorch's default (`ceil_mode=False`) and TensorFlow's `VALID` padding.
+    Floor,
+    /// ceil((n - k) / s) + 1 windows, dropping the last if it would start
+    /// past the end of the axis, with the axis padded at the end. This is
+    /// PyTorch's `ceil_mode=True`.
+    Ceil,
+    /// ceil(n / s) windows, with the axis padded as evenly as possible on both
+    /// sides, and any odd element of padding at the end. This is
+    /// TensorFlow's `SAME` padding.
+    Same,
+}
+
+impl FromStr for WindowConvention {
+    type Err = ();
+    fn from_str(input: &str) -> Result<WindowConvention, Self::Err> {
+        match input {
+            "floor-windows" => Ok(WindowConvention::Floor),
+            "ceil-windows" => Ok(WindowConvention::Ceil),
+            "same-windows" => Ok(WindowConvention::Same),
+            _ => Err(()),
+        }
+    }
+}
+impl Display for WindowConvention {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                WindowConvention::Floor => "floor-windows",
+                WindowConvention::Ceil => "ceil-windows",
+                WindowConvention::Same => "same-windows",
+            }
+        )
+    }
+}
+
+impl WindowConvention {
+    /// The number of windows along an axis of length `dim_len`.
+    pub fn num_windows(self, dim_len: usize, kernel_dim_len: usize, stride: usize) -> usize {
+        assert!(kernel_dim_len > 0, "Windows must not be empty");
+        assert!(stride > 0, "Strides must be positive");
+        match self {
+            WindowConvention::Floor | WindowConvention::Ceil => {
+                assert!(
+                    kernel_dim_len <= dim_len,
+                    "Window of length {} doesn't fit in an axis of length {}",
+                    kernel_dim_len,
+                    dim_len
+                );
+                let spare = dim_len - kernel_dim_len;
+                match self {
+                    WindowConvention::Floor => spare / stride + 1,
+                    _ => {
+                        let num_windows = (spare + stride - 1) / stride + 1;
+                        if (num_windows - 1) * stride >= dim_len {
+                            num_windows - 1
+                        } else {
+                            num_windows
+                        }
+                    }
+                }
+            }
+            WindowConvention::Same => (dim_len + stride - 1) / stride,
+        }
+    }
+
+    /// The padding before and after an axis of length `dim_len` needed to
+    /// place [`WindowConvention::num_windows`] windows.
+    pub fn padding(self, dim_len: usize, kernel_dim_len: usize, stride: usize) -> (usize, usize) {
+        let needed = ((self.num_windows(dim_len, kernel_dim_len, stride) - 1) * stride
+            + kernel_dim_len)
+            .saturating_sub(dim_len);
+        match self {
+            WindowConvention::Floor => (0, 0),
+            WindowConvention::Ceil => (0, needed),
+            WindowConvention::Same => (needed / 2, needed - needed / 2),
+        }
+    }
+}
+
 // #[derive(Debug, Clone, PartialEq)]
 // pub struct TensorData {
 //     shape: IxDyn,
 // }
 
+/// Resolves a possibly-negative axis against a rank of `ndim`, Python-style:
+/// -1 is the last axis, -2 the second-to-last, and so on.
+pub fn resolve_axis(axis: i64, ndim: usize) -> usize {
+    try_resolve_axis(axis, ndim).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Like [`resolve_axis`], but returns an error if the axis is out of bounds.
+pub fn try_resolve_axis(axis: i64, ndim: usize) -> std::result::Result<usize, String> {
+    let resolved = if axis < 0 { ndim as i64 + axis } else { axis };
+    if resolved < 0 || resolved as usize >= ndim {
+        return Err(format!("Axis {} out of bounds for {} axes", axis, ndim));
+    }
+    Ok(resolved as usize)
+}
+
+/// The error for data of the wrong kind, where `expected` was expected.
+fn unexpected_kind(expected: &str, found: &MyAnalysisData) -> GlensideError {
+    GlensideError::Analysis(format!("Expected {}, found {}", expected, found.kind()))
+}
+
+/// The kinds of the data of `enode`'s children, for error messages.
+fn child_kinds(egraph: &EGraph<Language, MyAnalysis>, enode: &Language) -> String {
+    format!(
+        "({})",
+        enode
+            .children()
+            .iter()
+            .map(|id| egraph[*id].data.kind())
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+/// Checks the dimensions of a shape: none may be negative, and the number of
+/// elements (their product) must fit in a `usize`, so that computing it later
+/// can't overflow. Dimensions of zero are allowed: shapes also give amounts
+/// of padding, window strides, and the like, and an access pattern may be
+/// empty (e.g. `(access-slice a 0 2 2)`). Only tensors' shapes must be
+/// nonempty; see [`check_tensor_shape`].
+/// ```
+/// use glenside::language::check_shape;
+///
+/// assert_eq!(check_shape(&[3, 0, 2]), Ok(vec![3, 0, 2]));
+/// assert!(check_shape(&[3, -1]).is_err());
+/// assert!(check_shape(&[1 << 32, 1 << 32, 1 << 32]).is_err());
+/// ```
+pub fn check_shape(dims: &[i64]) -> std::result::Result<Vec<usize>, String> {
+    let shape = dims
+        .iter()
+        .map(|dim| {
+            usize::try_from(*dim).map_err(|_| format!("Negative dimension {} in shape", dim))
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    num_elements(&shape).map(|_| shape)
+}
+
+/// The number of elements of a tensor of shape `shape`, or an error if it
+/// doesn't fit in a `usize`.
+pub fn num_elements(shape: &[usize]) -> std::result::Result<usize, String> {
+    if shape.contains(&0) {
+        return Ok(0);
+    }
+    shape
+        .iter()
+        .try_fold(1usize, |n, dim| n.checked_mul(*dim))
+        .ok_or_else(|| format!("Shape {:?} has more than {} elements", shape, usize::MAX))
+}
+
+/// Checks the shape of tensor `name`: as for [`check_shape`], except that
+/// no dimension may be zero. An input tensor without elements is almost
+/// always a mistake in a shape file or a frontend, and left unchecked, only
+/// shows up much later as an empty kernel or a division by zero.
+pub fn check_tensor_shape(name: &str, shape: &[usize]) -> std::result::Result<(), String> {
+    if shape.contains(&0) {
+        return Err(format!(
+            "Tensor {} has a dimension of size 0 in shape {:?}",
+            name, shape
+        ));
+    }
+    num_elements(shape).map(|_| ())
+}
+
+/// Parses a program, reporting malformed text as an error, with the line and
+/// column of the problem. See [`super::spans::parse_with_spans`].
+/// ```
+/// use glenside::error::GlensideError;
+/// use glenside::language::parse;
+///
+/// assert!(parse("(access (access-tensor t) 0)").is_ok());
+/// assert!(matches!(parse("(access (access-tensor t) 0"), Err(GlensideError::Parse(_))));
+/// ```
+pub fn parse(text: &str) -> crate::error::Result<egg::RecExpr<Language>> {
+    super::spans::parse_with_spans(text).map(|spanned| spanned.expr)
+}
+
+/// Adds `expr` to `egraph`, like [`EGraph::add_expr`], but returns an error
+/// if the analysis rejects one of its nodes (e.g. because its children have
+/// the wrong kinds or shapes). The whole expression is checked before any of
+/// it is added, so after an error, `egraph` is unchanged.
+/// ```
+/// use egg::EGraph;
+/// use glenside::error::GlensideError;
+/// use glenside::language::{parse, try_add_expr, MyAnalysis};
+///
+/// let mut egraph = EGraph::new(MyAnalysis::default());
+/// assert!(try_add_expr(&mut egraph, &parse("(access (access-tensor t-32-32) 1)").unwrap()).is_ok());
+/// let classes = egraph.number_of_classes();
+/// assert!(matches!(
+///     try_add_expr(&mut egraph, &parse("(access (access-tensor t-32-64) 3)").unwrap()),
+///     Err(GlensideError::Analysis(_))
+/// ));
+/// assert_eq!(egraph.number_of_classes(), classes);
+/// ```
+pub fn try_add_expr(
+    egraph: &mut EGraph<Language, MyAnalysis>,
+    expr: &egg::RecExpr<Language>,
+) -> crate::error::Result<Id> {
+    check_nodes(&egraph.analysis, expr.as_ref()).map_err(|(_, e)| e)?;
+    Ok(egraph.add_expr(expr))
+}
+
+/// Checks that the analysis accepts each of `nodes` (the nodes of a
+/// [`RecExpr`](egg::RecExpr)), by adding them to an egraph of their own
+/// which knows the same tensors as `analysis`. Returns the index of the first
+/// node rejected, along with the error.
+pub(crate) fn check_nodes(
+    analysis: &MyAnalysis,
+    nodes: &[Language],
+) -> std::result::Result<(), (usize, GlensideError)> {
+    let mut egraph = EGraph::new(MyAnalysis {
+        name_to_shape: analysis.name_to_shape.clone(),
+        name_to_dtype: analysis.name_to_dtype.clone(),
+        shape_cache: analysis.shape_cache.clone(),
+    });
+    let mut ids: Vec<Id> = Vec::with_capacity(nodes.len());
+    for (index, node) in nodes.iter().enumerate() {
+        let node = node.clone().map_children(|id| ids[usize::from(id)]);
+        MyAnalysis::try_make(&egraph, &node).map_err(|e| (index, e))?;
+        ids.push(egraph.add(node));
+    }
+    Ok(())
+}
+
 // TODO(@gussmith23) Pick a better analysis name.
 #[derive(Debug, Clone, PartialEq)]
 pub struct MyAnalysisDataLegacyData {
@@ -1358,32 +2043,161 @@ pub struct MyAnalysisDataLegacyData {
 pub struct MyAnalysis {
     pub name_to_shape: HashMap<String, Vec<usize>>,
     pub name_to_dtype: HashMap<String, DataType>,
+    /// Where to look up the data of enodes analyzed before, possibly in other
+    /// egraphs. `None` analyzes every enode afresh.
+    pub shape_cache: Option<ShapeCache>,
 }
 impl MyAnalysis {
+    /// Creates an analysis whose name-to-shape map is populated from the
+    /// `tensor-decl` nodes in `expr`, so that a self-describing expression
+    /// can be analyzed without passing in shapes separately.
+    /// ```
+    /// use egg::{EGraph, RecExpr};
+    /// use glenside::language::{Language, MyAnalysis, MyAnalysisData};
+    /// use ndarray::IxDyn;
+    ///
+    /// let expr: RecExpr<Language> = "(access-tensor (tensor-decl x (shape 32 64)))"
+    ///     .parse()
+    ///     .unwrap();
+    /// let mut egraph = EGraph::new(MyAnalysis::from_tensor_decls(&expr));
+    /// let id = egraph.add_expr(&expr);
+    /// match &egraph[id].data {
+    ///     MyAnalysisData::AccessPattern(a) => assert_eq!(a.shape, IxDyn(&[32, 64])),
+    ///     _ => panic!(),
+    /// }
+    /// ```
+    pub fn from_tensor_decls(expr: &egg::RecExpr<Language>) -> Self {
+        let mut analysis = MyAnalysis::default();
+        analysis.add_tensor_decls(expr);
+        analysis
+    }
+
+    /// Adds the shapes declared by the `tensor-decl` nodes in `expr` to the
+    /// name-to-shape map. Declarations must agree with any shapes already in
+    /// the map.
+    pub fn add_tensor_decls(&mut self, expr: &egg::RecExpr<Language>) {
+        for node in expr.as_ref() {
+            if let &Language::TensorDecl([name_id, shape_id]) = node {
+                let name = match &expr.as_ref()[usize::from(name_id)] {
+                    Language::Symbol(name) => name.clone(),
+                    _ => panic!("Expected a symbol as the first argument to tensor-decl"),
+                };
+                let shape = match &expr.as_ref()[usize::from(shape_id)] {
+                    Language::Shape(dims) => check_shape(
+                        &dims
+                            .iter()
+                            .map(|id| match &expr.as_ref()[usize::from(*id)] {
+                                &Language::Num(n) => n,
+                                _ => panic!("Shape of tensor {} must be a literal", name),
+                            })
+                            .collect::<Vec<_>>(),
+                    )
+                    .unwrap_or_else(|e| panic!("{}", e)),
+                    _ => panic!("Expected a shape as the second argument to tensor-decl"),
+                };
+                check_tensor_shape(&name, &shape).unwrap_or_else(|e| panic!("{}", e));
+                if let Some(existing) = self.name_to_shape.get(&name) {
+                    assert_eq!(
+                        existing, &shape,
+                        "Conflicting shapes declared for tensor {}",
+                        name
+                    );
+                }
+                self.name_to_shape.insert(name, shape);
+            }
+        }
+    }
+
     /// Legacy function: gets Num value as a usize. Before Num, we instead had a
-    /// Num construct.
+    /// Num construct. Panics if `id` isn't a non-negative number; see
+    /// [`MyAnalysis::try_get_usize`].
     pub fn get_usize(id: Id, egraph: &EGraph<Language, MyAnalysis>) -> usize {
+        Self::try_get_usize(id, egraph).unwrap_or_else(|e| panic!("{}", e))
+    }
+    /// Gets Num value as a usize, or an error if it isn't a non-negative
+    /// number.
+    pub fn try_get_usize(
+        id: Id,
+        egraph: &EGraph<Language, MyAnalysis>,
+    ) -> crate::error::Result<usize> {
         match &egraph[id].data {
-            &MyAnalysisData::Num(s) => s.try_into().unwrap(),
-            _ => panic!(),
+            &MyAnalysisData::Num(s) => s.try_into().map_err(|_| {
+                GlensideError::Analysis(format!("Expected a non-negative number, found {}", s))
+            }),
+            other => Err(unexpected_kind("a number", other)),
+        }
+    }
+    /// Gets a Num value used as an axis into something with `ndim` axes.
+    /// Negative axes count backwards from the end, so -1 is the last axis.
+    pub fn get_axis(id: Id, egraph: &EGraph<Language, MyAnalysis>, ndim: usize) -> usize {
+        Self::try_get_axis(id, egraph, ndim).unwrap_or_else(|e| panic!("{}", e))
+    }
+    /// Like [`MyAnalysis::get_axis`], but returns an error if `id` isn't a
+    /// number.
+    pub fn try_get_axis(
+        id: Id,
+        egraph: &EGraph<Language, MyAnalysis>,
+        ndim: usize,
+    ) -> crate::error::Result<usize> {
+        match &egraph[id].data {
+            &MyAnalysisData::Num(axis) => {
+                try_resolve_axis(axis, ndim).map_err(GlensideError::Analysis)
+            }
+            other => Err(unexpected_kind("an axis", other)),
+        }
+    }
+    /// Gets a list of axes into something with `ndim` axes, resolving any
+    /// negative axes. See [`MyAnalysis::get_axis`].
+    pub fn get_axis_list(id: Id, egraph: &EGraph<Language, MyAnalysis>, ndim: usize) -> Vec<usize> {
+        Self::try_get_axis_list(id, egraph, ndim).unwrap_or_else(|e| panic!("{}", e))
+    }
+    /// Like [`MyAnalysis::get_axis_list`], but returns an error if `id` isn't
+    /// a list.
+    pub fn try_get_axis_list(
+        id: Id,
+        egraph: &EGraph<Language, MyAnalysis>,
+        ndim: usize,
+    ) -> crate::error::Result<Vec<usize>> {
+        match &egraph[id].data {
+            MyAnalysisData::List(l) => Ok(l.clone()),
+            MyAnalysisData::AxisList(l) => l
+                .iter()
+                .map(|axis| try_resolve_axis(*axis, ndim).map_err(GlensideError::Analysis))
+                .collect(),
+            other => Err(unexpected_kind("a list of axes", other)),
         }
     }
     pub(crate) fn get_shape(id: Id, egraph: &EGraph<Language, MyAnalysis>) -> &IxDyn {
+        Self::try_get_shape(id, egraph).unwrap_or_else(|e| panic!("{}", e))
+    }
+    pub(crate) fn try_get_shape(
+        id: Id,
+        egraph: &EGraph<Language, MyAnalysis>,
+    ) -> crate::error::Result<&IxDyn> {
         match &egraph[id].data {
-            MyAnalysisData::Shape(s) => &s.shape,
-            _ => panic!(),
+            MyAnalysisData::Shape(s) => Ok(&s.shape),
+            other => Err(unexpected_kind("a tensor", other)),
         }
     }
-    pub(crate) fn get_dtype(id: Id, egraph: &EGraph<Language, MyAnalysis>) -> &DataType {
+    pub(crate) fn try_get_dtype(
+        id: Id,
+        egraph: &EGraph<Language, MyAnalysis>,
+    ) -> crate::error::Result<&DataType> {
         match &egraph[id].data {
-            MyAnalysisData::Shape(s) => &s.dtype,
-            _ => panic!(),
+            MyAnalysisData::Shape(s) => Ok(&s.dtype),
+            other => Err(unexpected_kind("a tensor", other)),
         }
     }
     pub(crate) fn get_shape_of_value(id: Id, egraph: &EGraph<Language, MyAnalysis>) -> &IxDyn {
+        Self::try_get_shape_of_value(id, egraph).unwrap_or_else(|e| panic!("{}", e))
+    }
+    pub(crate) fn try_get_shape_of_value(
+        id: Id,
+        egraph: &EGraph<Language, MyAnalysis>,
+    ) -> crate::error::Result<&IxDyn> {
         match &egraph[id].data {
-            MyAnalysisData::Shape(s) => &s.shape,
-            _ => panic!(),
+            MyAnalysisData::Shape(s) => Ok(&s.shape),
+            other => Err(unexpected_kind("a shape", other)),
         }
     }
 }
@@ -1555,7 +2369,53 @@ impl egg::Analysis<Language> for MyAnalysis {
         }
     }
 
+    fn modify(egraph: &mut EGraph<Language, Self>, id: Id) {
+        // Constant folding: if we know an eclass's usize value (e.g. because
+        // it's computed by usize arithmetic or shape-dim), add the literal.
+        if let MyAnalysisData::Num(n) = egraph[id].data {
+            if !egraph[id]
+                .nodes
+                .iter()
+                .any(|node| matches!(node, Language::Num(_)))
+            {
+                let num_id = egraph.add(Language::Num(n));
+                egraph.union(id, num_id);
+            }
+        }
+    }
+
+    /// Panics if the analysis rejects `enode`, as egg's analyses can't fail.
+    /// [`try_add_expr`] checks a whole expression with
+    /// [`MyAnalysis::try_make`] before adding it.
     fn make(egraph: &EGraph<Language, Self>, enode: &Language) -> Self::Data {
+        MyAnalysis::try_make(egraph, enode).unwrap_or_else(|e| panic!("{}", e))
+    }
+}
+
+impl MyAnalysis {
+    /// Computes the data of `enode`, whose children are in `egraph`, or an
+    /// [`Analysis`](GlensideError::Analysis) error if `enode` is ill-formed
+    /// (e.g. because its children have the wrong kinds or shapes). Looks the
+    /// data up in the analysis's [`ShapeCache`] first, if it has one.
+    pub fn try_make(
+        egraph: &EGraph<Language, Self>,
+        enode: &Language,
+    ) -> crate::error::Result<MyAnalysisData> {
+        crate::language::shape_cache::cached(egraph, enode, || {
+            MyAnalysis::try_make_uncached(egraph, enode)
+        })
+        .map_err(|e| match e {
+            GlensideError::Analysis(message) => {
+                GlensideError::Analysis(format!("{}: {}", enode.display_op(), message))
+            }
+            e => e,
+        })
+    }
+
+    fn try_make_uncached(
+        egraph: &EGraph<Language, Self>,
+        enode: &Language,
+    ) -> crate::error::Result<MyAnalysisData> {
         fn all_children_are_settled(
             egraph: &EGraph<Language, MyAnalysis>,
             enode: &Language,
@@ -1574,7 +2434,7 @@ impl egg::Analysis<Language> for MyAnalysis {
         }
 
         use Language::*;
-        match enode {
+        Ok(match enode {
             &GetAccessShape([id]) => match egraph[id].data.clone() {
                 MyAnalysisData::AccessPattern(mut a) => {
                     a.zero_regions = HashMap::default();
@@ -1582,7 +2442,11 @@ impl egg::Analysis<Language> for MyAnalysis {
                     // settled should remain the same!
                     MyAnalysisData::AccessPattern(a)
                 }
-                _ => panic!(),
+                _ => bail!(
+                    GlensideError::Analysis,
+                    "children of the wrong kinds: {}",
+                    child_kinds(egraph, enode)
+                ),
             },
             &SystolicArrayConv2dIm2colNhwcHwioWithBlocking(
                 [rows_id, cols_id, weights_id, data_id, kh_id, kw_id, stride_h_id, stride_w_id],
@@ -1607,16 +2471,24 @@ impl egg::Analysis<Language> for MyAnalysis {
                         MyAnalysisData::Num(stride_h),
                         MyAnalysisData::Num(stride_w),
                     ) => (*rows, *cols, weights, data, *kh, *kw, *stride_h, *stride_w),
-                    _ => panic!("Does not type check"),
+                    _ => bail!(GlensideError::Analysis, "Does not type check"),
                 };
-                assert_eq!(weights.shape.ndim() + weights.item_shape.ndim(), 4);
-                assert_eq!(data.shape.ndim() + data.item_shape.ndim(), 4);
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    weights.shape.ndim() + weights.item_shape.ndim(),
+                    4
+                );
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    data.shape.ndim() + data.item_shape.ndim(),
+                    4
+                );
 
                 let (n, h, w, c) = (data[0], data[1], data[2], data[3]);
                 let (_kh, _kw, _c, o) = (weights[0], weights[1], weights[2], weights[3]);
-                assert_eq!(c, _c);
-                assert_eq!(usize::try_from(kh).unwrap(), _kh);
-                assert_eq!(usize::try_from(kw).unwrap(), _kw);
+                ensure_eq!(GlensideError::Analysis, c, _c);
+                ensure_eq!(GlensideError::Analysis, usize::try_from(kh).unwrap(), _kh);
+                ensure_eq!(GlensideError::Analysis, usize::try_from(kw).unwrap(), _kw);
 
                 // These aren't actually requirements at the moment.
                 //assert_eq!(o % cols, 0);
@@ -1665,19 +2537,35 @@ impl egg::Analysis<Language> for MyAnalysis {
                         MyAnalysisData::Num(stride_h),
                         MyAnalysisData::Num(stride_w),
                     ) => (*rows, *cols, weights, data, *kh, *kw, *stride_h, *stride_w),
-                    _ => panic!("Does not type check"),
+                    _ => bail!(GlensideError::Analysis, "Does not type check"),
                 };
-                assert_eq!(weights.shape.ndim() + weights.item_shape.ndim(), 4);
-                assert_eq!(data.shape.ndim() + data.item_shape.ndim(), 4);
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    weights.shape.ndim() + weights.item_shape.ndim(),
+                    4
+                );
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    data.shape.ndim() + data.item_shape.ndim(),
+                    4
+                );
 
                 let (n, h, w, c) = (data[0], data[1], data[2], data[3]);
                 let (_kh, _kw, _c, o) = (weights[0], weights[1], weights[2], weights[3]);
-                assert_eq!(c, _c);
-                assert_eq!(usize::try_from(kh).unwrap(), _kh);
-                assert_eq!(usize::try_from(kw).unwrap(), _kw);
-
-                assert_eq!(o % usize::try_from(cols).unwrap(), 0);
-                assert_eq!(c % usize::try_from(rows).unwrap(), 0);
+                ensure_eq!(GlensideError::Analysis, c, _c);
+                ensure_eq!(GlensideError::Analysis, usize::try_from(kh).unwrap(), _kh);
+                ensure_eq!(GlensideError::Analysis, usize::try_from(kw).unwrap(), _kw);
+
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    o % usize::try_from(cols).unwrap(),
+                    0
+                );
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    c % usize::try_from(rows).unwrap(),
+                    0
+                );
 
                 let new_h =
                     (h - usize::try_from(kh - 1).unwrap() + usize::try_from(stride_h).unwrap() - 1)
@@ -1721,16 +2609,24 @@ impl egg::Analysis<Language> for MyAnalysis {
                         MyAnalysisData::Num(stride_h),
                         MyAnalysisData::Num(stride_w),
                     ) => (*rows, *cols, weights, data, *kh, *kw, *stride_h, *stride_w),
-                    _ => panic!("Does not type check"),
+                    _ => bail!(GlensideError::Analysis, "Does not type check"),
                 };
-                assert_eq!(weights.shape.ndim() + weights.item_shape.ndim(), 4);
-                assert_eq!(data.shape.ndim() + data.item_shape.ndim(), 4);
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    weights.shape.ndim() + weights.item_shape.ndim(),
+                    4
+                );
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    data.shape.ndim() + data.item_shape.ndim(),
+                    4
+                );
 
                 let (n, c, h, w) = (data[0], data[1], data[2], data[3]);
                 let (o, _c, _kh, _kw) = (weights[0], weights[1], weights[2], weights[3]);
-                assert_eq!(c, _c);
-                assert_eq!(usize::try_from(kh).unwrap(), _kh);
-                assert_eq!(usize::try_from(kw).unwrap(), _kw);
+                ensure_eq!(GlensideError::Analysis, c, _c);
+                ensure_eq!(GlensideError::Analysis, usize::try_from(kh).unwrap(), _kh);
+                ensure_eq!(GlensideError::Analysis, usize::try_from(kw).unwrap(), _kw);
 
                 // These aren't actually requirements for the moment.
                 //assert_eq!(o % cols, 0);
@@ -1778,19 +2674,35 @@ impl egg::Analysis<Language> for MyAnalysis {
                         MyAnalysisData::Num(stride_h),
                         MyAnalysisData::Num(stride_w),
                     ) => (*rows, *cols, weights, data, *kh, *kw, *stride_h, *stride_w),
-                    _ => panic!("Does not type check"),
+                    _ => bail!(GlensideError::Analysis, "Does not type check"),
                 };
-                assert_eq!(weights.shape.ndim() + weights.item_shape.ndim(), 4);
-                assert_eq!(data.shape.ndim() + data.item_shape.ndim(), 4);
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    weights.shape.ndim() + weights.item_shape.ndim(),
+                    4
+                );
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    data.shape.ndim() + data.item_shape.ndim(),
+                    4
+                );
 
                 let (n, c, h, w) = (data[0], data[1], data[2], data[3]);
                 let (o, _c, _kh, _kw) = (weights[0], weights[1], weights[2], weights[3]);
-                assert_eq!(c, _c);
-                assert_eq!(usize::try_from(kh).unwrap(), _kh);
-                assert_eq!(usize::try_from(kw).unwrap(), _kw);
-
-                assert_eq!(o % usize::try_from(cols).unwrap(), 0);
-                assert_eq!(c % usize::try_from(rows).unwrap(), 0);
+                ensure_eq!(GlensideError::Analysis, c, _c);
+                ensure_eq!(GlensideError::Analysis, usize::try_from(kh).unwrap(), _kh);
+                ensure_eq!(GlensideError::Analysis, usize::try_from(kw).unwrap(), _kw);
+
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    o % usize::try_from(cols).unwrap(),
+                    0
+                );
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    c % usize::try_from(rows).unwrap(),
+                    0
+                );
 
                 let new_h =
                     (h - usize::try_from(kh - 1).unwrap() + usize::try_from(stride_h).unwrap() - 1)
@@ -1815,7 +2727,8 @@ impl egg::Analysis<Language> for MyAnalysis {
                 let accelerator_call = &egraph[ids[0]].data;
                 let accelerator_func_data = match accelerator_call {
                     MyAnalysisData::AcceleratorFunc(data) => data,
-                    _ => panic!(
+                    _ => bail!(
+                        GlensideError::Analysis,
                         "Invalid data for accelerator function: {:?}",
                         accelerator_call
                     ),
@@ -1824,7 +2737,10 @@ impl egg::Analysis<Language> for MyAnalysis {
                     crate::language::AcceleratorFunc::FlexLSTM => {
                         let out_shape = match &egraph[ids[ids.len() - 1]].data {
                             MyAnalysisData::Shape(shape) => shape.shape.slice().to_vec(),
-                            _ => panic!("no shape data appended for FlexLSTM"),
+                            _ => bail!(
+                                GlensideError::Analysis,
+                                "no shape data appended for FlexLSTM"
+                            ),
                         };
 
                         MyAnalysisData::AccessPattern(AccessPatternData {
@@ -1844,8 +2760,8 @@ impl egg::Analysis<Language> for MyAnalysis {
                                 MyAnalysisData::AccessPattern(activations),
                                 MyAnalysisData::AccessPattern(weights),
                             ) => {
-                                assert_eq!(activations.as_vec().len(), 2);
-                                assert_eq!(weights.as_vec().len(), 2);
+                                ensure_eq!(GlensideError::Analysis, activations.as_vec().len(), 2);
+                                ensure_eq!(GlensideError::Analysis, weights.as_vec().len(), 2);
                                 MyAnalysisData::AccessPattern(AccessPatternData {
                                     zero_regions: HashMap::default(),
                                     shape: IxDyn(&[activations.as_vec()[0], weights.as_vec()[0]]),
@@ -1856,7 +2772,11 @@ impl egg::Analysis<Language> for MyAnalysis {
                                     contains_accelerator_calls: true,
                                 })
                             }
-                            _ => panic!(),
+                            _ => bail!(
+                                GlensideError::Analysis,
+                                "children of the wrong kinds: {}",
+                                child_kinds(egraph, enode)
+                            ),
                         }
                     }
                     crate::language::AcceleratorFunc::VTAConv1D => {
@@ -1874,15 +2794,19 @@ impl egg::Analysis<Language> for MyAnalysis {
                     crate::language::AcceleratorFunc::FlexASRMaxPool => {
                         let mut access = match &egraph[ids[1]].data {
                             MyAnalysisData::AccessPattern(a) => a.clone(),
-                            _ => panic!(),
+                            _ => bail!(
+                                GlensideError::Analysis,
+                                "children of the wrong kinds: {}",
+                                child_kinds(egraph, enode)
+                            ),
                         };
 
-                        assert_eq!(access.item_shape.ndim(), 2);
-                        assert_eq!(access.shape.ndim(), 0);
+                        ensure_eq!(GlensideError::Analysis, access.item_shape.ndim(), 2);
+                        ensure_eq!(GlensideError::Analysis, access.shape.ndim(), 0);
                         let t = access.item_shape[0];
                         let h = access.item_shape[1];
-                        assert_eq!(t % 2, 0);
-                        assert_eq!(h % 16, 0);
+                        ensure_eq!(GlensideError::Analysis, t % 2, 0);
+                        ensure_eq!(GlensideError::Analysis, h % 16, 0);
                         access.item_shape[0] = access.item_shape[0] / 2;
                         access.contains_accelerator_calls = true;
                         // Setting this to false for now b/c their shapes are all messed up.
@@ -1911,11 +2835,12 @@ impl egg::Analysis<Language> for MyAnalysis {
                                 data_shape[3] += padding.shape[1] + padding.shape[3];
                                 let n = data_shape[0].clone();
                                 let c = channels.clone();
-                                let access_window_shape = access_windows_resulting_shape(
+                                let access_window_shape = try_access_windows_resulting_shape(
                                     &IxDyn(&data_shape[1..]),
                                     &kernel_size.shape,
                                     &strides.shape,
-                                );
+                                )
+                                .map_err(GlensideError::Analysis)?;
                                 let h = access_window_shape[1];
                                 let w = access_window_shape[2];
                                 AccessPatternData {
@@ -1928,7 +2853,9 @@ impl egg::Analysis<Language> for MyAnalysis {
                                     contains_accelerator_calls: true,
                                 }
                             }
-                            _ => panic!("Cannot parse arguments for Conv2D"),
+                            _ => {
+                                bail!(GlensideError::Analysis, "Cannot parse arguments for Conv2D")
+                            }
                         };
                         MyAnalysisData::AccessPattern(access)
                     }
@@ -1957,50 +2884,78 @@ impl egg::Analysis<Language> for MyAnalysis {
                     .collect::<Vec<_>>();
                 MyAnalysisData::Tuple(tuple_shape)
             }
+            Outputs(ids) => {
+                ensure!(
+                    GlensideError::Analysis,
+                    !ids.is_empty(),
+                    "outputs should have at least one output"
+                );
+                MyAnalysisData::Tuple(
+                    ids.iter()
+                        .map(|id| (&egraph[*id].data).clone())
+                        .collect::<Vec<_>>(),
+                )
+            }
             TupleGetItem(ids) => {
-                let index = MyAnalysis::get_usize(ids[1], egraph);
+                let index = MyAnalysis::try_get_usize(ids[1], egraph)?;
                 let data = match &egraph[ids[0]].data {
                     MyAnalysisData::Tuple(x) => x,
-                    _ => panic!("Expected {:?} to be a Tuple.", &egraph[ids[0]]),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Expected {:?} to be a Tuple.",
+                        &egraph[ids[0]]
+                    ),
                 };
                 data[index].clone()
             }
             RelayOperator(op) => MyAnalysisData::RelayOperator(op.clone()),
             RelayOperatorCall(params) => {
-                assert!(params.len() > 0);
+                ensure!(GlensideError::Analysis, params.len() > 0);
 
                 let op_type = match &egraph[params[0]].data {
                     MyAnalysisData::RelayOperator(op_type) => op_type,
-                    _ => panic!(),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "children of the wrong kinds: {}",
+                        child_kinds(egraph, enode)
+                    ),
                 };
 
                 match op_type {
                     crate::language::RelayOperator::RelaySqueeze => {
-                        assert_eq!(params.len(), 3);
+                        ensure_eq!(GlensideError::Analysis, params.len(), 3);
                         let a = match &egraph[params[1]].data {
                             MyAnalysisData::AccessPattern(a) => a,
-                            _ => panic!(),
+                            _ => bail!(
+                                GlensideError::Analysis,
+                                "children of the wrong kinds: {}",
+                                child_kinds(egraph, enode)
+                            ),
                         };
                         let axes = match &egraph[params[2]].data {
                             MyAnalysisData::List(v) => v,
-                            _ => panic!(),
+                            _ => bail!(
+                                GlensideError::Analysis,
+                                "children of the wrong kinds: {}",
+                                child_kinds(egraph, enode)
+                            ),
                         };
 
+                        for (i, v) in a.as_vec().iter().enumerate() {
+                            if axes.contains(&i) {
+                                ensure_eq!(
+                                    GlensideError::Analysis,
+                                    *v,
+                                    1,
+                                    "Cannot squeeze an axis unless its value is 1."
+                                );
+                            }
+                        }
                         let new_shape = a
                             .as_vec()
                             .iter()
                             .enumerate()
-                            .filter_map(|(i, v)| {
-                                if axes.contains(&i) {
-                                    assert_eq!(
-                                        *v, 1,
-                                        "Cannot squeeze an axis unless its value is 1."
-                                    );
-                                    None
-                                } else {
-                                    Some(*v)
-                                }
-                            })
+                            .filter_map(|(i, v)| if axes.contains(&i) { None } else { Some(*v) })
                             .collect::<Vec<_>>();
 
                         if any(&[a], |a| !a.zero_regions.is_empty()) {
@@ -2021,22 +2976,34 @@ impl egg::Analysis<Language> for MyAnalysis {
                     crate::language::RelayOperator::RelayCopy => {
                         let mut out = match &egraph[params[1]].data {
                             MyAnalysisData::AccessPattern(a) => a.clone(),
-                            _ => panic!(),
+                            _ => bail!(
+                                GlensideError::Analysis,
+                                "children of the wrong kinds: {}",
+                                child_kinds(egraph, enode)
+                            ),
                         };
                         out.access_pattern_shape_settled = false;
                         MyAnalysisData::AccessPattern(out)
                     }
                     crate::language::RelayOperator::RelayTranspose => {
-                        assert_eq!(params.len(), 3);
+                        ensure_eq!(GlensideError::Analysis, params.len(), 3);
                         let a = match &egraph[params[1]].data {
                             MyAnalysisData::AccessPattern(a) => a,
-                            _ => panic!(),
+                            _ => bail!(
+                                GlensideError::Analysis,
+                                "children of the wrong kinds: {}",
+                                child_kinds(egraph, enode)
+                            ),
                         };
                         let axes = match &egraph[params[2]].data {
                             MyAnalysisData::List(v) => v,
-                            _ => panic!(),
+                            _ => bail!(
+                                GlensideError::Analysis,
+                                "children of the wrong kinds: {}",
+                                child_kinds(egraph, enode)
+                            ),
                         };
-                        assert_eq!(a.as_vec().len(), axes.len());
+                        ensure_eq!(GlensideError::Analysis, a.as_vec().len(), axes.len());
 
                         let new_shape: Vec<_> = axes.iter().map(|&i| a.as_vec()[i]).collect();
 
@@ -2056,34 +3023,55 @@ impl egg::Analysis<Language> for MyAnalysis {
                         })
                     }
                     crate::language::RelayOperator::RelayConcatenate => {
-                        assert_eq!(params.len(), 3);
+                        ensure_eq!(GlensideError::Analysis, params.len(), 3);
 
                         let axis = match &egraph[params[1]].data {
                             MyAnalysisData::Num(v) => *v,
-                            _ => panic!(),
+                            _ => bail!(
+                                GlensideError::Analysis,
+                                "children of the wrong kinds: {}",
+                                child_kinds(egraph, enode)
+                            ),
                         };
 
                         // In the future we need to handle negative axis, but
                         // for now I think they'll always be positive.
-                        assert!(axis >= 0);
+                        ensure!(GlensideError::Analysis, axis >= 0);
                         let axis = axis as usize;
 
-                        let access_pattern_iter = match &egraph[params[2]].data {
+                        let access_patterns = match &egraph[params[2]].data {
                             MyAnalysisData::Tuple(t) => t,
-                            _ => panic!(),
+                            _ => bail!(
+                                GlensideError::Analysis,
+                                "children of the wrong kinds: {}",
+                                child_kinds(egraph, enode)
+                            ),
                         }
                         .iter()
                         .map(|a| match a {
-                            MyAnalysisData::AccessPattern(a) => a,
-                            _ => panic!(),
-                        });
+                            MyAnalysisData::AccessPattern(a) => Ok(a),
+                            _ => Err(GlensideError::Analysis(
+                                "Expected a tuple of access patterns".to_string(),
+                            )),
+                        })
+                        .collect::<crate::error::Result<Vec<_>>>()?;
+                        let access_pattern_iter = access_patterns.iter().copied();
 
                         let mut shapes = access_pattern_iter
                             .clone()
                             .map(AccessPatternData::as_vec)
                             .collect::<Vec<_>>();
 
-                        assert!(shapes.len() > 0);
+                        ensure!(GlensideError::Analysis, shapes.len() > 0);
+                        for shape in &shapes[1..] {
+                            for (i, (first_val, this_val)) in
+                                shapes[0].iter().zip(shape.iter()).enumerate()
+                            {
+                                if i != axis {
+                                    ensure_eq!(GlensideError::Analysis, first_val, this_val);
+                                }
+                            }
+                        }
 
                         let new_shape = shapes
                             .drain(..)
@@ -2095,7 +3083,6 @@ impl egg::Analysis<Language> for MyAnalysis {
                                         if i == axis {
                                             acc_val + this_val
                                         } else {
-                                            assert_eq!(acc_val, this_val);
                                             *acc_val
                                         }
                                     })
@@ -2121,17 +3108,26 @@ impl egg::Analysis<Language> for MyAnalysis {
                         })
                     }
                     crate::language::RelayOperator::RelayPad => {
-                        assert_eq!(params.len(), 3);
+                        ensure_eq!(GlensideError::Analysis, params.len(), 3);
 
                         let a = match &egraph[params[1]].data {
                             MyAnalysisData::AccessPattern(a) => a.clone(),
-                            _ => panic!(),
+                            _ => bail!(
+                                GlensideError::Analysis,
+                                "children of the wrong kinds: {}",
+                                child_kinds(egraph, enode)
+                            ),
                         };
                         let pad_width = match &egraph[params[2]].data {
                             MyAnalysisData::Shape(ShapeData { shape, .. }) => shape,
-                            _ => panic!(),
+                            _ => bail!(
+                                GlensideError::Analysis,
+                                "children of the wrong kinds: {}",
+                                child_kinds(egraph, enode)
+                            ),
                         };
-                        assert_eq!(
+                        ensure_eq!(
+                            GlensideError::Analysis,
                             pad_width.ndim(),
                             2 * a.as_vec().len(),
                             "There should be two padding values per tensor dimension."
@@ -2153,25 +3149,40 @@ impl egg::Analysis<Language> for MyAnalysis {
                         })
                     }
                     crate::language::RelayOperator::RelayExpandDims => {
-                        assert_eq!(params.len(), 4);
+                        ensure_eq!(GlensideError::Analysis, params.len(), 4);
 
                         let mut a = match &egraph[params[1]].data {
                             MyAnalysisData::AccessPattern(a) => a.clone(),
-                            _ => panic!(),
+                            _ => bail!(
+                                GlensideError::Analysis,
+                                "children of the wrong kinds: {}",
+                                child_kinds(egraph, enode)
+                            ),
                         };
                         // TODO(@gussmith23) This pattern appears a lot, and it's annoying.
                         let axis: i64 = match &egraph[params[2]].data {
                             MyAnalysisData::Num(v) => *v,
-                            _ => panic!(),
+                            _ => bail!(
+                                GlensideError::Analysis,
+                                "children of the wrong kinds: {}",
+                                child_kinds(egraph, enode)
+                            ),
                         };
                         let num_axis: i64 = match &egraph[params[3]].data {
                             MyAnalysisData::Num(v) => *v,
-                            _ => panic!(),
+                            _ => bail!(
+                                GlensideError::Analysis,
+                                "children of the wrong kinds: {}",
+                                child_kinds(egraph, enode)
+                            ),
                         };
 
                         // From the TVM docs.
-                        assert!(-(a.as_vec().len() as i64) - 1 <= axis);
-                        assert!(axis <= a.as_vec().len() as i64);
+                        ensure!(
+                            GlensideError::Analysis,
+                            -(a.as_vec().len() as i64) - 1 <= axis
+                        );
+                        ensure!(GlensideError::Analysis, axis <= a.as_vec().len() as i64);
 
                         // Convert negative axis.
                         let axis: usize = if axis < 0 {
@@ -2217,7 +3228,11 @@ impl egg::Analysis<Language> for MyAnalysis {
                         {
                             [MyAnalysisData::Shape(s)] => s.shape.clone(),
 
-                            _ => panic!(),
+                            _ => bail!(
+                                GlensideError::Analysis,
+                                "children of the wrong kinds: {}",
+                                child_kinds(egraph, enode)
+                            ),
                         };
                         MyAnalysisData::AccessPattern(AccessPatternData {
                             shape: s.clone(),
@@ -2235,16 +3250,20 @@ impl egg::Analysis<Language> for MyAnalysis {
                         {
                             [MyAnalysisData::AccessPattern(a0), MyAnalysisData::AccessPattern(a1)] =>
                             {
-                                assert_eq!(a0.as_vec().len(), 3);
-                                assert_eq!(a1.as_vec().len(), 3);
+                                ensure_eq!(GlensideError::Analysis, a0.as_vec().len(), 3);
+                                ensure_eq!(GlensideError::Analysis, a1.as_vec().len(), 3);
                                 (a0, a1)
                             }
-                            _ => panic!(),
+                            _ => bail!(
+                                GlensideError::Analysis,
+                                "children of the wrong kinds: {}",
+                                child_kinds(egraph, enode)
+                            ),
                         };
 
                         let (s0, s1) = (a0.as_vec(), a1.as_vec());
-                        assert_eq!(s0[0], s1[0]);
-                        assert_eq!(s0[2], s1[2]);
+                        ensure_eq!(GlensideError::Analysis, s0[0], s1[0]);
+                        ensure_eq!(GlensideError::Analysis, s0[2], s1[2]);
                         let out_shape = vec![s0[0], s0[1], s1[1]];
 
                         if any(&[a0, a1], |a| !a.zero_regions.is_empty()) {
@@ -2268,7 +3287,11 @@ impl egg::Analysis<Language> for MyAnalysis {
                     crate::language::RelayOperator::RelayLayerNorm => {
                         let mut out = match &egraph[params[1]].data {
                             MyAnalysisData::AccessPattern(a) => a.clone(),
-                            _ => panic!(),
+                            _ => bail!(
+                                GlensideError::Analysis,
+                                "children of the wrong kinds: {}",
+                                child_kinds(egraph, enode)
+                            ),
                         };
                         out.access_pattern_shape_settled = false;
                         MyAnalysisData::AccessPattern(out)
@@ -2284,7 +3307,7 @@ impl egg::Analysis<Language> for MyAnalysis {
                                 contains_accelerator_calls: false,
                             })
                         }
-                        _ => panic!("Invalid rounding"),
+                        _ => bail!(GlensideError::Analysis, "Invalid rounding"),
                     },
                     crate::language::RelayOperator::RelayLeftShift
                     | crate::language::RelayOperator::RelayRightShift => {
@@ -2305,32 +3328,43 @@ impl egg::Analysis<Language> for MyAnalysis {
                                     contains_accelerator_calls: false,
                                 })
                             }
-                            _ => panic!("Invalid bit-shifting"),
+                            _ => bail!(GlensideError::Analysis, "Invalid bit-shifting"),
                         }
                     }
                     crate::language::RelayOperator::RelayStack => {
                         let accesses = params[1..params.len() - 1]
                             .iter()
                             .map(|id| match &egraph[*id].data {
-                                MyAnalysisData::AccessPattern(a) => a.clone(),
-                                _ => panic!(),
+                                MyAnalysisData::AccessPattern(a) => Ok(a.clone()),
+                                _ => Err(GlensideError::Analysis(format!(
+                                    "children of the wrong kinds: {}",
+                                    child_kinds(egraph, enode)
+                                ))),
                             })
-                            .collect::<Vec<_>>();
+                            .collect::<crate::error::Result<Vec<_>>>()?;
 
-                        assert!(accesses.len() > 0);
+                        ensure!(GlensideError::Analysis, accesses.len() > 0);
                         let shape = accesses[0].as_vec();
                         for access in &accesses {
                             if access.as_vec() != shape {
-                                todo!("Stack inputs of different shapes not yet supported");
+                                bail!(
+                                    GlensideError::Analysis,
+                                    "Stack inputs of different shapes not yet supported"
+                                );
                             }
                         }
 
                         let axis = match egraph[params[params.len() - 1]].data {
                             MyAnalysisData::Num(v) => i32::try_from(v).unwrap(),
-                            _ => panic!(),
+                            _ => bail!(
+                                GlensideError::Analysis,
+                                "children of the wrong kinds: {}",
+                                child_kinds(egraph, enode)
+                            ),
                         };
                         // This comes right from the Relay impl.
-                        assert!(
+                        ensure!(
+                            GlensideError::Analysis,
                             axis >= -(i32::try_from(shape.len()).unwrap() + 1)
                                 && axis < i32::try_from(shape.len()).unwrap() + 1
                         );
@@ -2380,7 +3414,7 @@ impl egg::Analysis<Language> for MyAnalysis {
                                     .expect("Rate argument must be a scalar")
                                     .into_scalar(),
                             ),
-                            _ => panic!("Parameters do not type check"),
+                            _ => bail!(GlensideError::Analysis, "Parameters do not type check"),
                         };
 
                         access.access_pattern_shape_settled = false;
@@ -2400,12 +3434,19 @@ impl egg::Analysis<Language> for MyAnalysis {
                             [MyAnalysisData::AccessPattern(data), MyAnalysisData::AccessPattern(indices), MyAnalysisData::Num(axis)] => {
                                 (data.clone(), indices.clone(), axis.clone())
                             }
-                            _ => panic!(),
+                            _ => bail!(
+                                GlensideError::Analysis,
+                                "children of the wrong kinds: {}",
+                                child_kinds(egraph, enode)
+                            ),
                         };
 
                         let data_shape = data.as_vec();
                         let indices_shape = indices.as_vec();
-                        assert!(usize::try_from(axis).unwrap() < data_shape.len());
+                        ensure!(
+                            GlensideError::Analysis,
+                            usize::try_from(axis).unwrap() < data_shape.len()
+                        );
 
                         let out_shape: Vec<_> = data_shape[..axis.try_into().unwrap()]
                             .iter()
@@ -2445,13 +3486,13 @@ impl egg::Analysis<Language> for MyAnalysis {
                                     strides.shape.slice(),
                                 )
                             }
-                            _ => panic!("Parameters do not type check",),
+                            _ => bail!(GlensideError::Analysis, "Parameters do not type check",),
                         };
 
-                        assert!(strides.iter().all(|i| *i == 1));
-                        assert_eq!(begin.len(), end.len());
-                        assert_eq!(begin.len(), strides.len());
-                        assert_eq!(begin.len(), data.as_vec().len());
+                        ensure!(GlensideError::Analysis, strides.iter().all(|i| *i == 1));
+                        ensure_eq!(GlensideError::Analysis, begin.len(), end.len());
+                        ensure_eq!(GlensideError::Analysis, begin.len(), strides.len());
+                        ensure_eq!(GlensideError::Analysis, begin.len(), data.as_vec().len());
 
                         let new_shape: Vec<_> = begin
                             .iter()
@@ -2488,9 +3529,11 @@ impl egg::Analysis<Language> for MyAnalysis {
                             [MyAnalysisData::AccessPattern(a), MyAnalysisData::AccessPattern(b)] => {
                                 (a.clone(), b.clone())
                             }
-                            _ => panic!(
+                            _ => bail!(
+                                GlensideError::Analysis,
                                 "Parameters do not type check: {:?} {:?}",
-                                egraph[params[1]].data, egraph[params[2]].data
+                                egraph[params[1]].data,
+                                egraph[params[2]].data
                             ),
                         };
 
@@ -2516,14 +3559,15 @@ impl egg::Analysis<Language> for MyAnalysis {
                                     .chain(b.item_shape.slice().iter()),
                             )
                             .map(|(a, b): (&usize, &usize)| {
-                                assert!(
-                                    a == b || (*a == 1 || *b == 1),
-                                    "Shapes can't be broadcast"
-                                );
-                                std::cmp::max(a, b)
+                                if a == b || (*a == 1 || *b == 1) {
+                                    Ok(*std::cmp::max(a, b))
+                                } else {
+                                    Err(GlensideError::Analysis(
+                                        "Shapes can't be broadcast".to_string(),
+                                    ))
+                                }
                             })
-                            .cloned()
-                            .collect::<Vec<_>>();
+                            .collect::<crate::error::Result<Vec<_>>>()?;
 
                         MyAnalysisData::AccessPattern(AccessPatternData {
                             shape: IxDyn(new_shape.as_slice()),
@@ -2547,7 +3591,10 @@ impl egg::Analysis<Language> for MyAnalysis {
                                 access_pattern_shape_settled: false,
                                 contains_accelerator_calls: a.contains_accelerator_calls,
                             },
-                            _ => panic!("Erf only supports accepting 1 input tensor"),
+                            _ => bail!(
+                                GlensideError::Analysis,
+                                "Erf only supports accepting 1 input tensor"
+                            ),
                         };
                         MyAnalysisData::AccessPattern(access)
                     }
@@ -2601,7 +3648,8 @@ impl egg::Analysis<Language> for MyAnalysis {
                                 let mut begin = 0;
                                 let mut access_vec = Vec::default();
                                 for index in sections.iter() {
-                                    assert!(
+                                    ensure!(
+                                        GlensideError::Analysis,
                                         *index > begin,
                                         "`index` of the sections must be greater than `begin`"
                                     );
@@ -2620,7 +3668,7 @@ impl egg::Analysis<Language> for MyAnalysis {
                                         },
                                     ));
                                 }
-                                assert!(relay_shape[axis] > begin);
+                                ensure!(GlensideError::Analysis, relay_shape[axis] > begin);
                                 let mut oshape: Vec<_> =
                                     relay_shape.slice().iter().cloned().collect();
                                 oshape[axis] = relay_shape[axis] - begin;
@@ -2633,7 +3681,7 @@ impl egg::Analysis<Language> for MyAnalysis {
                                 }));
                                 MyAnalysisData::Tuple(access_vec)
                             }
-                            _ => panic!("Invalid call to RelaySplit"),
+                            _ => bail!(GlensideError::Analysis, "Invalid call to RelaySplit"),
                         }
                     }
                     crate::language::RelayOperator::RelayMean => {
@@ -2653,7 +3701,10 @@ impl egg::Analysis<Language> for MyAnalysis {
                                     .cloned()
                                     .collect::<Vec<_>>();
                                 let axis = *usize_data;
-                                assert!(usize::try_from(axis).unwrap() < shape_length);
+                                ensure!(
+                                    GlensideError::Analysis,
+                                    usize::try_from(axis).unwrap() < shape_length
+                                );
                                 if usize::try_from(axis).unwrap() == shape_length - 1 {
                                     AccessPatternData {
                                         shape: IxDyn(&[]),
@@ -2678,7 +3729,10 @@ impl egg::Analysis<Language> for MyAnalysis {
                                     }
                                 }
                             }
-                            _ => panic!("Erf only supports accepting 1 input tensor"),
+                            _ => bail!(
+                                GlensideError::Analysis,
+                                "Erf only supports accepting 1 input tensor"
+                            ),
                         };
                         MyAnalysisData::AccessPattern(access)
                     }
@@ -2704,11 +3758,11 @@ impl egg::Analysis<Language> for MyAnalysis {
                                     .chain(weight.item_shape.slice().iter())
                                     .cloned()
                                     .collect::<Vec<_>>();
-                                assert_eq!(data_shape.len(), 3);
-                                assert_eq!(weight_shape.len(), 3);
-                                assert_eq!(data_shape[1], weight_shape[1]);
-                                assert_eq!(strides.ndim(), 1);
-                                assert_eq!(padding.ndim(), 2);
+                                ensure_eq!(GlensideError::Analysis, data_shape.len(), 3);
+                                ensure_eq!(GlensideError::Analysis, weight_shape.len(), 3);
+                                ensure_eq!(GlensideError::Analysis, data_shape[1], weight_shape[1]);
+                                ensure_eq!(GlensideError::Analysis, strides.ndim(), 1);
+                                ensure_eq!(GlensideError::Analysis, padding.ndim(), 2);
                                 let output_shape = IxDyn(&[
                                     data_shape[0],
                                     weight_shape[0],
@@ -2721,14 +3775,18 @@ impl egg::Analysis<Language> for MyAnalysis {
                                     // where we implement the calculation. I
                                     // match on the result just to make sure
                                     // it's the expected length.
-                                    match access_windows_resulting_shape(
+                                    match try_access_windows_resulting_shape(
                                         &IxDyn(&[padding[0] + data_shape[2] + padding[1]]),
                                         &IxDyn(&[weight_shape[2]]),
                                         strides,
-                                    )[..]
+                                    )
+                                    .map_err(GlensideError::Analysis)?[..]
                                     {
                                         [result] => result,
-                                        _ => panic!("unexpected length result"),
+                                        _ => bail!(
+                                            GlensideError::Analysis,
+                                            "unexpected length result"
+                                        ),
                                     },
                                 ]);
                                 AccessPatternData {
@@ -2740,7 +3798,7 @@ impl egg::Analysis<Language> for MyAnalysis {
                                         || weight.contains_accelerator_calls,
                                 }
                             }
-                            _ => panic!("Incorrect conv1d arguments"),
+                            _ => bail!(GlensideError::Analysis, "Incorrect conv1d arguments"),
                         };
                         MyAnalysisData::AccessPattern(access)
                     }
@@ -2761,7 +3819,11 @@ impl egg::Analysis<Language> for MyAnalysis {
                                         crate::language::RelayActivationLayout::NHWC,
                                         &[n, h, w, c],
                                     ) => (n, c, h, w),
-                                    _ => panic!(),
+                                    _ => bail!(
+                                        GlensideError::Analysis,
+                                        "children of the wrong kinds: {}",
+                                        child_kinds(egraph, enode)
+                                    ),
                                 };
                                 let (o, i, kh, kw) = match (ker_layout, &weight.as_vec()[..]) {
                                     (crate::language::RelayKernelLayout::OIHW, &[o, i, h, w]) => {
@@ -2770,20 +3832,30 @@ impl egg::Analysis<Language> for MyAnalysis {
                                     (crate::language::RelayKernelLayout::HWIO, &[h, w, i, o]) => {
                                         (o, i, h, w)
                                     }
-                                    _ => panic!(),
+                                    _ => bail!(
+                                        GlensideError::Analysis,
+                                        "children of the wrong kinds: {}",
+                                        child_kinds(egraph, enode)
+                                    ),
                                 };
                                 let h = padding.shape[0] + h + padding.shape[2];
                                 let w = padding.shape[1] + w + padding.shape[3];
-                                assert_eq!(strides.shape.ndim(), 2);
+                                ensure_eq!(GlensideError::Analysis, strides.shape.ndim(), 2);
                                 match *group {
                                     1 => {
-                                        assert_eq!(i, c);
-                                        let access_window_shape = access_windows_resulting_shape(
-                                            &IxDyn(&[h, w]),
-                                            &IxDyn(&[kh, kw]),
-                                            &strides.shape,
+                                        ensure_eq!(GlensideError::Analysis, i, c);
+                                        let access_window_shape =
+                                            try_access_windows_resulting_shape(
+                                                &IxDyn(&[h, w]),
+                                                &IxDyn(&[kh, kw]),
+                                                &strides.shape,
+                                            )
+                                            .map_err(GlensideError::Analysis)?;
+                                        ensure_eq!(
+                                            GlensideError::Analysis,
+                                            access_window_shape.len(),
+                                            2
                                         );
-                                        assert_eq!(access_window_shape.len(), 2);
                                         let h = access_window_shape[0];
                                         let w = access_window_shape[1];
                                         let out_shape = match act_layout {
@@ -2808,25 +3880,32 @@ impl egg::Analysis<Language> for MyAnalysis {
                                     c => {
                                         match act_layout {
                                             crate::language::RelayActivationLayout::NCHW => (),
-                                            crate::language::RelayActivationLayout::NHWC => todo!("Not currently supported, supporting only NCHW for PLDI push.")
+                                            crate::language::RelayActivationLayout::NHWC => bail!(GlensideError::Analysis, "Not currently supported, supporting only NCHW for PLDI push.")
                                         }
                                         match ker_layout {
                                             crate::language::RelayKernelLayout::OIHW => (),
-                                            crate::language::RelayKernelLayout::HWIO => todo!("Not currently supported, supporting only OIHW for PLDI push.")
+                                            crate::language::RelayKernelLayout::HWIO => bail!(GlensideError::Analysis, "Not currently supported, supporting only OIHW for PLDI push.")
                                         }
 
-                                        assert_eq!(
+                                        ensure_eq!(
+                                            GlensideError::Analysis,
                                             i,
                                             usize::try_from(c).unwrap()
                                                 / usize::try_from(*group).unwrap()
                                         );
 
-                                        let access_window_shape = access_windows_resulting_shape(
-                                            &IxDyn(&[h, w]),
-                                            &IxDyn(&[kh, kw]),
-                                            &IxDyn(&strides.shape.slice()),
+                                        let access_window_shape =
+                                            try_access_windows_resulting_shape(
+                                                &IxDyn(&[h, w]),
+                                                &IxDyn(&[kh, kw]),
+                                                &IxDyn(&strides.shape.slice()),
+                                            )
+                                            .map_err(GlensideError::Analysis)?;
+                                        ensure_eq!(
+                                            GlensideError::Analysis,
+                                            access_window_shape.len(),
+                                            2
                                         );
-                                        assert_eq!(access_window_shape.len(), 2);
                                         let h = access_window_shape[0];
                                         let w = access_window_shape[1];
 
@@ -2842,7 +3921,9 @@ impl egg::Analysis<Language> for MyAnalysis {
                                     }
                                 }
                             }
-                            _ => panic!("Cannot parse arguments for Conv2D"),
+                            _ => {
+                                bail!(GlensideError::Analysis, "Cannot parse arguments for Conv2D")
+                            }
                         };
                         MyAnalysisData::AccessPattern(access)
                     }
@@ -2871,7 +3952,7 @@ impl egg::Analysis<Language> for MyAnalysis {
                                 let batch = lhs_relay_shape[0];
                                 let in_feat = lhs_relay_shape[1];
                                 let out_feat = rhs_relay_shape[0];
-                                assert_eq!(rhs_relay_shape[1], in_feat);
+                                ensure_eq!(GlensideError::Analysis, rhs_relay_shape[1], in_feat);
                                 let new_shape = [batch, out_feat];
                                 AccessPatternData {
                                     shape: IxDyn(&new_shape),
@@ -2882,7 +3963,10 @@ impl egg::Analysis<Language> for MyAnalysis {
                                         || b.contains_accelerator_calls,
                                 }
                             }
-                            _ => panic!("Dense current only support 2 parameters"),
+                            _ => bail!(
+                                GlensideError::Analysis,
+                                "Dense current only support 2 parameters"
+                            ),
                         };
                         MyAnalysisData::AccessPattern(access)
                     }
@@ -2903,7 +3987,7 @@ impl egg::Analysis<Language> for MyAnalysis {
                                     dtype: dtype.clone(),
                                 })
                             }
-                            _ => panic!("Invalid cast"),
+                            _ => bail!(GlensideError::Analysis, "Invalid cast"),
                         }
                     }
                     crate::language::RelayOperator::RelayClip => {
@@ -2926,7 +4010,7 @@ impl egg::Analysis<Language> for MyAnalysis {
                                     contains_accelerator_calls: false,
                                 })
                             }
-                            _ => panic!("Invalid Clip"),
+                            _ => bail!(GlensideError::Analysis, "Invalid Clip"),
                         }
                     }
                     crate::language::RelayOperator::RelayReshape => {
@@ -2945,7 +4029,10 @@ impl egg::Analysis<Language> for MyAnalysis {
                                     contains_accelerator_calls: access.contains_accelerator_calls,
                                 }
                             }
-                            _ => panic!("Cannot match parameters for Reshape operator"),
+                            _ => bail!(
+                                GlensideError::Analysis,
+                                "Cannot match parameters for Reshape operator"
+                            ),
                         };
                         MyAnalysisData::AccessPattern(access)
                     }
@@ -2958,7 +4045,7 @@ impl egg::Analysis<Language> for MyAnalysis {
                             [MyAnalysisData::AccessPattern(a), MyAnalysisData::AccessPattern(_), MyAnalysisData::Num(_) | MyAnalysisData::Shape(_)] => {
                                 a.clone()
                             }
-                            _ => panic!("Parameters do not type check"),
+                            _ => bail!(GlensideError::Analysis, "Parameters do not type check"),
                         };
 
                         if !access.zero_regions.is_empty() {
@@ -2980,7 +4067,7 @@ impl egg::Analysis<Language> for MyAnalysis {
                             .collect::<Vec<_>>()[..]
                         {
                             [MyAnalysisData::AccessPattern(a)] => a.clone(),
-                            _ => panic!("Parameters do not type check"),
+                            _ => bail!(GlensideError::Analysis, "Parameters do not type check"),
                         };
 
                         if !access.zero_regions.is_empty() {
@@ -2991,7 +4078,10 @@ impl egg::Analysis<Language> for MyAnalysis {
                         }
                         access.zero_regions = HashMap::default();
 
-                        assert!(access.shape.ndim() + access.item_shape.ndim() > 0);
+                        ensure!(
+                            GlensideError::Analysis,
+                            access.shape.ndim() + access.item_shape.ndim() > 0
+                        );
 
                         // TODO(@gussmith23) Assuming NCHW layout
                         // TODO(@gussmith23) I'm just doing something arbitrary
@@ -3025,7 +4115,7 @@ impl egg::Analysis<Language> for MyAnalysis {
                             [MyAnalysisData::AccessPattern(a), MyAnalysisData::RelayActivationLayout(l)] => {
                                 (a.clone(), l.clone())
                             }
-                            _ => panic!("Parameters do not type check"),
+                            _ => bail!(GlensideError::Analysis, "Parameters do not type check"),
                         };
 
                         if !access.zero_regions.is_empty() {
@@ -3036,7 +4126,11 @@ impl egg::Analysis<Language> for MyAnalysis {
                         }
                         access.zero_regions = HashMap::default();
 
-                        assert_eq!(access.shape.ndim() + access.item_shape.ndim(), 4);
+                        ensure_eq!(
+                            GlensideError::Analysis,
+                            access.shape.ndim() + access.item_shape.ndim(),
+                            4
+                        );
 
                         match layout {
                             crate::language::RelayActivationLayout::NCHW => {
@@ -3062,7 +4156,7 @@ impl egg::Analysis<Language> for MyAnalysis {
                             [MyAnalysisData::AccessPattern(a), MyAnalysisData::Shape(pool_size), MyAnalysisData::Shape(strides), MyAnalysisData::Shape(padding), MyAnalysisData::RelayActivationLayout(l)] => {
                                 (a.clone(), pool_size, strides, padding, l)
                             }
-                            _ => panic!("Parameters do not type check"),
+                            _ => bail!(GlensideError::Analysis, "Parameters do not type check"),
                         };
 
                         if !access.zero_regions.is_empty() {
@@ -3073,55 +4167,30 @@ impl egg::Analysis<Language> for MyAnalysis {
                         }
                         access.zero_regions = HashMap::default();
 
-                        assert_eq!(access.shape.ndim() + access.item_shape.ndim(), 4);
-                        assert_eq!(pool_size.shape.ndim(), 2);
-                        assert_eq!(strides.shape.ndim(), 2);
-                        assert_eq!(padding.shape.ndim(), 4);
+                        ensure_eq!(
+                            GlensideError::Analysis,
+                            access.shape.ndim() + access.item_shape.ndim(),
+                            4
+                        );
+                        ensure_eq!(GlensideError::Analysis, pool_size.shape.ndim(), 2);
+                        ensure_eq!(GlensideError::Analysis, strides.shape.ndim(), 2);
+                        ensure_eq!(GlensideError::Analysis, padding.shape.ndim(), 4);
 
-                        match layout {
-                            crate::language::RelayActivationLayout::NCHW => {
-                                // Sorry for the horrific indentation...
-                                access[2] =
-                                // The dimension plus padding
-                                    (((padding.shape[0] + access[2] + padding.shape[2])
-                                      // Get the number of spots where we could pool
-                                      - (pool_size.shape[0] - 1))
-                                     // Then calculate the spots we actually pool at
-                                     // using the stride
-                                     + strides.shape[0]
-                                     - 1)
-                                    / strides.shape[0];
-                                access[3] = (((padding.shape[1] + access[3] + padding.shape[3])
-                                              // Get the number of spots where we could pool
-                                              - (pool_size.shape[1] - 1))
-                                             // Then calculate the spots we actually pool at
-                                             // using the stride
-                                             + strides.shape[1]
-                                    - 1)
-                                    / strides.shape[1];
-                            }
-                            crate::language::RelayActivationLayout::NHWC => {
-                                // Sorry for the horrific indentation...
-                                access[1] =
-                                // The dimension plus padding
-                                    (((padding.shape[0] + access[1] + padding.shape[2])
-                                      // Get the number of spots where we could pool
-                                      - (pool_size.shape[0] - 1))
-                                     // Then calculate the spots we actually pool at
-                                     // using the stride
-                                     + strides.shape[0]
-                                     - 1)
-                                    / strides.shape[0];
-                                access[2] = (((padding.shape[1] + access[2] + padding.shape[3])
-                                              // Get the number of spots where we could pool
-                                              - (pool_size.shape[1] - 1))
-                                             // Then calculate the spots we actually pool at
-                                             // using the stride
-                                             + strides.shape[1]
-                                    - 1)
-                                    / strides.shape[1];
-                            }
-                        }
+                        let (h, w) = match layout {
+                            crate::language::RelayActivationLayout::NCHW => (2, 3),
+                            crate::language::RelayActivationLayout::NHWC => (1, 2),
+                        };
+                        let pooled_shape = try_access_windows_resulting_shape(
+                            &IxDyn(&[
+                                padding.shape[0] + access[h] + padding.shape[2],
+                                padding.shape[1] + access[w] + padding.shape[3],
+                            ]),
+                            &pool_size.shape,
+                            &strides.shape,
+                        )
+                        .map_err(GlensideError::Analysis)?;
+                        access[h] = pooled_shape[0];
+                        access[w] = pooled_shape[1];
 
                         access.access_pattern_shape_settled = false;
 
@@ -3134,7 +4203,7 @@ impl egg::Analysis<Language> for MyAnalysis {
                             .collect::<Vec<_>>()[..]
                         {
                             [MyAnalysisData::AccessPattern(a)] => a.clone(),
-                            _ => panic!("Parameters do not type check"),
+                            _ => bail!(GlensideError::Analysis, "Parameters do not type check"),
                         };
 
                         if !access.zero_regions.is_empty() {
@@ -3158,7 +4227,7 @@ impl egg::Analysis<Language> for MyAnalysis {
                             [MyAnalysisData::AccessPattern(a), MyAnalysisData::Literal(_)] => {
                                 a.clone()
                             }
-                            _ => panic!("Parameters do not type check"),
+                            _ => bail!(GlensideError::Analysis, "Parameters do not type check"),
                         };
 
                         if !access.zero_regions.is_empty() {
@@ -3181,7 +4250,7 @@ impl egg::Analysis<Language> for MyAnalysis {
                             .collect::<Vec<_>>()[..]
                         {
                             [MyAnalysisData::AccessPattern(a)] => a.clone(),
-                            _ => panic!("Parameters do not type check"),
+                            _ => bail!(GlensideError::Analysis, "Parameters do not type check"),
                         };
 
                         if !access.zero_regions.is_empty() {
@@ -3206,7 +4275,7 @@ impl egg::Analysis<Language> for MyAnalysis {
                             [MyAnalysisData::AccessPattern(a), MyAnalysisData::Num(_) | MyAnalysisData::Shape(_)] => {
                                 a.clone()
                             }
-                            _ => panic!("Parameters do not type check"),
+                            _ => bail!(GlensideError::Analysis, "Parameters do not type check"),
                         };
 
                         if !access.zero_regions.is_empty() {
@@ -3230,7 +4299,7 @@ impl egg::Analysis<Language> for MyAnalysis {
                             [MyAnalysisData::AccessPattern(a), MyAnalysisData::AccessPattern(_), MyAnalysisData::AccessPattern(_), MyAnalysisData::AccessPattern(_), MyAnalysisData::AccessPattern(_), MyAnalysisData::Num(_) | MyAnalysisData::Shape(_), MyAnalysisData::Literal(_)] => {
                                 a.clone()
                             }
-                            _ => panic!("Parameters do not type check"),
+                            _ => bail!(GlensideError::Analysis, "Parameters do not type check"),
                         };
 
                         if !access.zero_regions.is_empty() {
@@ -3254,7 +4323,7 @@ impl egg::Analysis<Language> for MyAnalysis {
                             [MyAnalysisData::AccessPattern(a), MyAnalysisData::Shape(pool_size), MyAnalysisData::Shape(strides), MyAnalysisData::Shape(padding), MyAnalysisData::RelayActivationLayout(l)] => {
                                 (a.clone(), pool_size, strides, padding, l)
                             }
-                            _ => panic!("Parameters do not type check"),
+                            _ => bail!(GlensideError::Analysis, "Parameters do not type check"),
                         };
 
                         if !access.zero_regions.is_empty() {
@@ -3265,55 +4334,30 @@ impl egg::Analysis<Language> for MyAnalysis {
                         }
                         access.zero_regions = HashMap::default();
 
-                        assert_eq!(access.shape.ndim() + access.item_shape.ndim(), 4);
-                        assert_eq!(pool_size.shape.ndim(), 2);
-                        assert_eq!(strides.shape.ndim(), 2);
-                        assert_eq!(padding.shape.ndim(), 4);
+                        ensure_eq!(
+                            GlensideError::Analysis,
+                            access.shape.ndim() + access.item_shape.ndim(),
+                            4
+                        );
+                        ensure_eq!(GlensideError::Analysis, pool_size.shape.ndim(), 2);
+                        ensure_eq!(GlensideError::Analysis, strides.shape.ndim(), 2);
+                        ensure_eq!(GlensideError::Analysis, padding.shape.ndim(), 4);
 
-                        match layout {
-                            crate::language::RelayActivationLayout::NCHW => {
-                                // Sorry for the horrific indentation...
-                                access[2] =
-                                // The dimension plus padding
-                                    (((padding.shape[0] + access[2] + padding.shape[2])
-                                      // Get the number of spots where we could pool
-                                      - (pool_size.shape[0] - 1))
-                                     // Then calculate the spots we actually pool at
-                                     // using the stride
-                                     + strides.shape[0]
-                                     - 1)
-                                    / strides.shape[0];
-                                access[3] = (((padding.shape[1] + access[3] + padding.shape[3])
-                                              // Get the number of spots where we could pool
-                                              - (pool_size.shape[1] - 1))
-                                             // Then calculate the spots we actually pool at
-                                             // using the stride
-                                             + strides.shape[1]
-                                    - 1)
-                                    / strides.shape[1];
-                            }
-                            crate::language::RelayActivationLayout::NHWC => {
-                                // Sorry for the horrific indentation...
-                                access[1] =
-                                // The dimension plus padding
-                                    (((padding.shape[0] + access[1] + padding.shape[2])
-                                      // Get the number of spots where we could pool
-                                      - (pool_size.shape[0] - 1))
-                                     // Then calculate the spots we actually pool at
-                                     // using the stride
-                                     + strides.shape[0]
-                                     - 1)
-                                    / strides.shape[0];
-                                access[2] = (((padding.shape[1] + access[2] + padding.shape[3])
-                                              // Get the number of spots where we could pool
-                                              - (pool_size.shape[1] - 1))
-                                             // Then calculate the spots we actually pool at
-                                             // using the stride
-                                             + strides.shape[1]
-                                    - 1)
-                                    / strides.shape[1];
-                            }
-                        }
+                        let (h, w) = match layout {
+                            crate::language::RelayActivationLayout::NCHW => (2, 3),
+                            crate::language::RelayActivationLayout::NHWC => (1, 2),
+                        };
+                        let pooled_shape = try_access_windows_resulting_shape(
+                            &IxDyn(&[
+                                padding.shape[0] + access[h] + padding.shape[2],
+                                padding.shape[1] + access[w] + padding.shape[3],
+                            ]),
+                            &pool_size.shape,
+                            &strides.shape,
+                        )
+                        .map_err(GlensideError::Analysis)?;
+                        access[h] = pooled_shape[0];
+                        access[w] = pooled_shape[1];
 
                         access.access_pattern_shape_settled = false;
 
@@ -3327,15 +4371,16 @@ impl egg::Analysis<Language> for MyAnalysis {
                         {
                             [MyAnalysisData::AccessPattern(a), MyAnalysisData::Literal(scale_h), MyAnalysisData::Literal(scale_w), MyAnalysisData::RelayActivationLayout(layout)] =>
                             {
-                                assert_eq!(
+                                ensure_eq!(
+                                    GlensideError::Analysis,
                                     layout.clone(),
                                     crate::language::RelayActivationLayout::NCHW,
                                     "upsampling only supports NCHW"
                                 );
                                 // let mut shape = array![a.shape[0], a.shape[1], scale_h.into() * shape[2], scale_w.into() * shape[w]];
                                 let mut shape = a.shape.clone();
-                                assert_eq!(scale_h.ndim(), 0);
-                                assert_eq!(scale_w.ndim(), 0);
+                                ensure_eq!(GlensideError::Analysis, scale_h.ndim(), 0);
+                                ensure_eq!(GlensideError::Analysis, scale_w.ndim(), 0);
                                 shape[2] =
                                     (scale_h.first().unwrap() * (shape[2] as f64)).round() as usize;
                                 shape[3] =
@@ -3349,7 +4394,7 @@ impl egg::Analysis<Language> for MyAnalysis {
                                     contains_accelerator_calls: a.contains_accelerator_calls,
                                 }
                             }
-                            _ => panic!("Parameters do not type check"),
+                            _ => bail!(GlensideError::Analysis, "Parameters do not type check"),
                         };
 
                         if !access.zero_regions.is_empty() {
@@ -3377,38 +4422,44 @@ impl egg::Analysis<Language> for MyAnalysis {
                     access_pattern_shape_settled: all_children_are_settled(egraph, enode),
                     contains_accelerator_calls: false,
                 }),
-                _ => panic!(),
+                _ => bail!(
+                    GlensideError::Analysis,
+                    "children of the wrong kinds: {}",
+                    child_kinds(egraph, enode)
+                ),
             },
             &ConstantTensor([_value, shape]) => match &egraph[shape].data {
                 MyAnalysisData::Shape(s) => MyAnalysisData::Shape(s.clone()),
-                _ => panic!(),
+                _ => bail!(
+                    GlensideError::Analysis,
+                    "children of the wrong kinds: {}",
+                    child_kinds(egraph, enode)
+                ),
             },
             &NotNanFloat64(v) => MyAnalysisData::Literal(ndarray::arr0(v.into_inner()).into_dyn()),
             &Literal(id) => match &egraph[id].data {
                 t @ MyAnalysisData::Literal(_) => t.clone(),
-                _ => panic!(),
+                _ => bail!(
+                    GlensideError::Analysis,
+                    "children of the wrong kinds: {}",
+                    child_kinds(egraph, enode)
+                ),
             },
             &AccessTranspose([access_id, list_id]) => {
                 let access = match &egraph[access_id].data {
                     MyAnalysisData::AccessPattern(a) => a,
-                    _ => panic!(),
-                };
-                let list = match &egraph[list_id].data {
-                    MyAnalysisData::List(l) => l,
-                    _ => panic!(),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "children of the wrong kinds: {}",
+                        child_kinds(egraph, enode)
+                    ),
                 };
-                assert_eq!(
+                let list = MyAnalysis::try_get_axis_list(
+                    list_id,
+                    egraph,
                     access.shape.ndim() + access.item_shape.ndim(),
-                    list.len(),
-                    "Number of items in list should equal the number of axes in the first argument"
-                );
-                let tmp = access
-                    .shape
-                    .slice()
-                    .iter()
-                    .chain(access.item_shape.slice().iter())
-                    .collect::<Vec<_>>();
-                let new_shape = list.iter().map(|i| *tmp[*i]).collect::<Vec<_>>();
+                )?;
+                let new_shape = AccessShape::from(access).transpose(&list);
 
                 // Re-sort zero regions.
                 let mut new_zero_regions = HashMap::default();
@@ -3419,8 +4470,8 @@ impl egg::Analysis<Language> for MyAnalysis {
                 }
 
                 MyAnalysisData::AccessPattern(AccessPatternData {
-                    shape: IxDyn(&new_shape[..access.shape.ndim()]),
-                    item_shape: IxDyn(&new_shape[access.shape.ndim()..]),
+                    shape: new_shape.shape,
+                    item_shape: new_shape.item_shape,
                     zero_regions: new_zero_regions,
                     access_pattern_shape_settled: all_children_are_settled(egraph, enode),
                     contains_accelerator_calls: access.contains_accelerator_calls,
@@ -3429,24 +4480,39 @@ impl egg::Analysis<Language> for MyAnalysis {
             List(list) => {
                 let list = list
                     .iter()
-                    .map(|id| MyAnalysis::get_usize(*id, egraph))
-                    .collect::<Vec<_>>();
-                MyAnalysisData::List(list)
+                    .map(|id| match &egraph[*id].data {
+                        &MyAnalysisData::Num(n) => Ok(n),
+                        _ => Err(GlensideError::Analysis(format!(
+                            "children of the wrong kinds: {}",
+                            child_kinds(egraph, enode)
+                        ))),
+                    })
+                    .collect::<crate::error::Result<Vec<_>>>()?;
+                if list.iter().all(|n| *n >= 0) {
+                    MyAnalysisData::List(list.iter().map(|n| *n as usize).collect())
+                } else {
+                    MyAnalysisData::AxisList(list)
+                }
             }
             &AccessBroadcast([access_id, shape_id]) => {
                 let access = match &egraph[access_id].data {
                     MyAnalysisData::AccessPattern(a) => a,
-                    _ => panic!(),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "children of the wrong kinds: {}",
+                        child_kinds(egraph, enode)
+                    ),
                 };
-                let shape = match &egraph[shape_id].data {
-                    MyAnalysisData::AccessPattern(a) => a,
-                    _ => panic!(
+                let shape =
+                    match &egraph[shape_id].data {
+                        MyAnalysisData::AccessPattern(a) => a,
+                        _ => bail!(GlensideError::Analysis,
                         "Expected access shape as second argument of access-broadcast, got {:?}",
                         egraph[shape_id]
                     ),
-                };
+                    };
 
-                assert_eq!(
+                ensure_eq!(GlensideError::Analysis,
                     access.shape.ndim() + access.item_shape.ndim(),
                     shape.shape.ndim() + shape.item_shape.ndim(),
                     "Shape we're broadcasting to should have the same number of dimensions as the shape we're broadcasting from"
@@ -3465,15 +4531,16 @@ impl egg::Analysis<Language> for MyAnalysis {
                             .chain(shape.item_shape.slice().iter()),
                     )
                     .map(|(broadcast_from_dim, broadcast_to_dim): (&usize, &usize)| {
-                        assert!(
-                            *broadcast_from_dim == 1 || broadcast_from_dim == broadcast_to_dim,
-                            "Expected broadcast_from_dim to be 1 or {}, got {}",
-                            *broadcast_to_dim,
-                            *broadcast_from_dim
-                        );
-                        *broadcast_to_dim
+                        if *broadcast_from_dim == 1 || broadcast_from_dim == broadcast_to_dim {
+                            Ok(*broadcast_to_dim)
+                        } else {
+                            Err(GlensideError::Analysis(format!(
+                                "Expected broadcast_from_dim to be 1 or {}, got {}",
+                                *broadcast_to_dim, *broadcast_from_dim
+                            )))
+                        }
                     })
-                    .collect::<Vec<_>>();
+                    .collect::<crate::error::Result<Vec<_>>>()?;
 
                 if !access.zero_regions.is_empty() {
                     debug!(
@@ -3482,7 +4549,8 @@ impl egg::Analysis<Language> for MyAnalysis {
                     );
                 }
 
-                assert_eq!(
+                ensure_eq!(
+                    GlensideError::Analysis,
                     new_shape.len(),
                     access.shape.ndim() + access.item_shape.ndim()
                 );
@@ -3507,10 +4575,40 @@ impl egg::Analysis<Language> for MyAnalysis {
                     contains_accelerator_calls: access.contains_accelerator_calls,
                 })
             }
+            &AccessReverse([access_id, axis_id]) => {
+                let mut access = match &egraph[access_id].data {
+                    MyAnalysisData::AccessPattern(a) => a.clone(),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Expected an access as the first argument to access-reverse"
+                    ),
+                };
+                let axis = MyAnalysis::try_get_usize(axis_id, egraph)?;
+                ensure!(
+                    GlensideError::Analysis,
+                    axis < access.shape.ndim() + access.item_shape.ndim(),
+                    "Invalid axis {} for access-reverse",
+                    axis
+                );
+                // TODO(@gussmith23) Implement zero_regions
+                // Zero regions could be mirrored along the axis instead.
+                if !access.zero_regions.is_empty() {
+                    debug!(
+                        "Throwing away zero region analysis data on line {}",
+                        std::line!()
+                    );
+                    access.zero_regions = HashMap::default();
+                }
+                MyAnalysisData::AccessPattern(access)
+            }
             &AccessInsertAxis([access_id, axis_id]) => {
                 let mut access = match &egraph[access_id].data {
                     MyAnalysisData::AccessPattern(a) => a.clone(),
-                    _ => panic!(),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "children of the wrong kinds: {}",
+                        child_kinds(egraph, enode)
+                    ),
                 };
                 // TODO(@gussmith23) Implement zero_regions
                 if !access.zero_regions.is_empty() {
@@ -3522,10 +4620,15 @@ impl egg::Analysis<Language> for MyAnalysis {
                 }
                 let axis = match egraph[axis_id].data {
                     MyAnalysisData::Num(v) => v,
-                    _ => panic!(),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "children of the wrong kinds: {}",
+                        child_kinds(egraph, enode)
+                    ),
                 };
 
-                assert!(
+                ensure!(
+                    GlensideError::Analysis,
                     usize::try_from(axis).unwrap()
                         <= access.shape.ndim() + access.item_shape.ndim()
                 );
@@ -3568,7 +4671,11 @@ impl egg::Analysis<Language> for MyAnalysis {
             &AccessSqueeze([access_id, axis_id]) => {
                 let mut access = match &egraph[access_id].data {
                     MyAnalysisData::AccessPattern(a) => a.clone(),
-                    _ => panic!(),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "children of the wrong kinds: {}",
+                        child_kinds(egraph, enode)
+                    ),
                 };
                 // TODO(@gussmith23) Implement zero_regions
                 if !access.zero_regions.is_empty() {
@@ -3578,17 +4685,28 @@ impl egg::Analysis<Language> for MyAnalysis {
                     );
                     access.zero_regions = HashMap::default();
                 }
-                let axis = MyAnalysis::get_usize(axis_id, egraph);
+                let axis = MyAnalysis::try_get_axis(
+                    axis_id,
+                    egraph,
+                    access.shape.ndim() + access.item_shape.ndim(),
+                )?;
                 use ndarray::RemoveAxis;
                 if axis < access.shape.ndim() {
-                    assert_eq!(
-                        access.shape[axis], 1,
+                    ensure_eq!(
+                        GlensideError::Analysis,
+                        access.shape[axis],
+                        1,
                         "Expected axis {} of {:?} to be 1",
-                        axis, access.shape
+                        axis,
+                        access.shape
                     );
                     access.shape = access.shape.remove_axis(ndarray::Axis(axis));
                 } else {
-                    assert_eq!(access.item_shape[axis - access.shape.ndim()], 1);
+                    ensure_eq!(
+                        GlensideError::Analysis,
+                        access.item_shape[axis - access.shape.ndim()],
+                        1
+                    );
                     access.item_shape = access
                         .item_shape
                         .remove_axis(ndarray::Axis(axis - access.shape.ndim()));
@@ -3601,17 +4719,28 @@ impl egg::Analysis<Language> for MyAnalysis {
             &AccessPad([access_id, pad_type_id, axis_id, pad_before_id, pad_after_id]) => {
                 let mut access = match &egraph[access_id].data {
                     MyAnalysisData::AccessPattern(a) => a.clone(),
-                    _ => panic!("Expected AccessPattern, got {:#?}", &egraph[access_id].data),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Expected AccessPattern, got {:#?}",
+                        &egraph[access_id].data
+                    ),
                 };
                 let pad_type = match &egraph[pad_type_id].data {
                     MyAnalysisData::PadType(t) => t,
-                    _ => panic!(),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "children of the wrong kinds: {}",
+                        child_kinds(egraph, enode)
+                    ),
                 };
-                let axis = MyAnalysis::get_usize(axis_id, egraph);
-                assert!(axis < access.shape.ndim() + access.item_shape.ndim());
+                let axis = MyAnalysis::try_get_axis(
+                    axis_id,
+                    egraph,
+                    access.shape.ndim() + access.item_shape.ndim(),
+                )?;
                 let orig_axis_val = access[axis];
-                let pad_before = MyAnalysis::get_usize(pad_before_id, egraph);
-                let pad_after = MyAnalysis::get_usize(pad_after_id, egraph);
+                let pad_before = MyAnalysis::try_get_usize(pad_before_id, egraph)?;
+                let pad_after = MyAnalysis::try_get_usize(pad_after_id, egraph)?;
                 if axis < access.shape.ndim() {
                     access.shape[axis] += pad_before + pad_after;
                 } else {
@@ -3620,7 +4749,8 @@ impl egg::Analysis<Language> for MyAnalysis {
 
                 // TODO(@gussmith23) Remove this after figuring out padding issues
                 for (axis, val) in &access.zero_regions {
-                    assert!(
+                    ensure!(
+                        GlensideError::Analysis,
                         val.len() <= access[*axis],
                         "{} > {}",
                         val.len(),
@@ -3671,7 +4801,7 @@ impl egg::Analysis<Language> for MyAnalysis {
 
                 // TODO(@gussmith23) Remove this after figuring out padding issues
                 for (axis, val) in &access.zero_regions {
-                    assert!(val.len() <= access[*axis]);
+                    ensure!(GlensideError::Analysis, val.len() <= access[*axis]);
                 }
 
                 access.access_pattern_shape_settled = all_children_are_settled(egraph, enode);
@@ -3681,7 +4811,11 @@ impl egg::Analysis<Language> for MyAnalysis {
             &AccessTensor(t_id) => {
                 let shape = match &egraph[t_id].data {
                     MyAnalysisData::Shape(l) => l.shape.clone(),
-                    _ => panic!(),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "children of the wrong kinds: {}",
+                        child_kinds(egraph, enode)
+                    ),
                 };
                 MyAnalysisData::AccessPattern(AccessPatternData {
                     // TODO(@gussmith23) Implement zero regions
@@ -3698,7 +4832,11 @@ impl egg::Analysis<Language> for MyAnalysis {
             &AccessShiftRight(a_id) => {
                 let a = match &egraph[a_id].data {
                     MyAnalysisData::AccessPattern(a) => a,
-                    _ => panic!(),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "children of the wrong kinds: {}",
+                        child_kinds(egraph, enode)
+                    ),
                 };
 
                 let combined = a
@@ -3733,7 +4871,11 @@ impl egg::Analysis<Language> for MyAnalysis {
                     (MyAnalysisData::AccessPattern(a0), MyAnalysisData::AccessPattern(a1)) => {
                         (a0, a1)
                     }
-                    _ => panic!(),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "children of the wrong kinds: {}",
+                        child_kinds(egraph, enode)
+                    ),
                 };
 
                 // assert_eq!(a0.shape, a1.shape);
@@ -3774,21 +4916,34 @@ impl egg::Analysis<Language> for MyAnalysis {
             &AccessSlice([access_id, axis_id, low_id, high_id]) => {
                 let mut new_access = match &egraph[access_id].data {
                     MyAnalysisData::AccessPattern(a) => a.clone(),
-                    _ => panic!(),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "children of the wrong kinds: {}",
+                        child_kinds(egraph, enode)
+                    ),
                 };
-                let axis: usize = Self::get_usize(axis_id, egraph);
-                let low: usize = Self::get_usize(low_id, egraph);
-                let high: usize = Self::get_usize(high_id, egraph);
+                let axis: usize = MyAnalysis::try_get_axis(
+                    axis_id,
+                    egraph,
+                    new_access.shape.ndim() + new_access.item_shape.ndim(),
+                )?;
+                let low: usize = MyAnalysis::try_get_usize(low_id, egraph)?;
+                let high: usize = MyAnalysis::try_get_usize(high_id, egraph)?;
                 let original_axis_value = new_access[axis];
 
-                assert!(new_access.shape.ndim() + new_access.item_shape.ndim() > axis);
                 if axis < new_access.shape.ndim() {
-                    assert!(low < new_access.shape[axis]);
-                    assert!(high <= new_access.shape[axis]);
+                    ensure!(GlensideError::Analysis, low < new_access.shape[axis]);
+                    ensure!(GlensideError::Analysis, high <= new_access.shape[axis]);
                     new_access.shape[axis] = high - low;
                 } else {
-                    assert!(low < new_access.item_shape[axis - new_access.shape.ndim()]);
-                    assert!(high <= new_access.item_shape[axis - new_access.shape.ndim()]);
+                    ensure!(
+                        GlensideError::Analysis,
+                        low < new_access.item_shape[axis - new_access.shape.ndim()]
+                    );
+                    ensure!(
+                        GlensideError::Analysis,
+                        high <= new_access.item_shape[axis - new_access.shape.ndim()]
+                    );
                     new_access.item_shape[axis - new_access.shape.ndim()] = high - low;
                 }
 
@@ -3804,11 +4959,19 @@ impl egg::Analysis<Language> for MyAnalysis {
                 MyAnalysisData::AccessPattern(new_access)
             }
             &AccessConcatenate([a0_id, a1_id, axis_id]) => {
-                let axis = Self::get_usize(axis_id, egraph);
                 let mut new_access = match &egraph[a0_id].data {
                     MyAnalysisData::AccessPattern(a) => a.clone(),
-                    _ => panic!(),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "children of the wrong kinds: {}",
+                        child_kinds(egraph, enode)
+                    ),
                 };
+                let axis = MyAnalysis::try_get_axis(
+                    axis_id,
+                    egraph,
+                    new_access.shape.ndim() + new_access.item_shape.ndim(),
+                )?;
                 let a1 = match &egraph[a1_id].data {
                     MyAnalysisData::AccessPattern(a) => {
                         if egraph[a1_id].nodes.iter().all(|n| match n {
@@ -3817,7 +4980,7 @@ impl egg::Analysis<Language> for MyAnalysis {
                         }) {
                             let relay_shape = IxDyn(&a.as_vec());
                             let new_axis = new_access.shape.ndim();
-                            assert!(new_axis <= relay_shape.ndim());
+                            ensure!(GlensideError::Analysis, new_axis <= relay_shape.ndim());
                             AccessPatternData {
                                 zero_regions: HashMap::default(),
                                 shape: IxDyn(&relay_shape.slice()[..new_axis]),
@@ -3831,7 +4994,11 @@ impl egg::Analysis<Language> for MyAnalysis {
                             a.clone()
                         }
                     }
-                    _ => panic!(),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "children of the wrong kinds: {}",
+                        child_kinds(egraph, enode)
+                    ),
                 };
                 // TODO(@gussmith23) Implement zero_regions
                 if !new_access.zero_regions.is_empty() {
@@ -3847,9 +5014,20 @@ impl egg::Analysis<Language> for MyAnalysis {
                         std::line!()
                     );
                 }
-                assert_eq!(new_access.shape.ndim(), a1.shape.ndim(),);
-                assert_eq!(new_access.item_shape.ndim(), a1.item_shape.ndim(),);
-                assert!(axis < a1.shape.ndim() + a1.item_shape.ndim());
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    new_access.shape.ndim(),
+                    a1.shape.ndim(),
+                );
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    new_access.item_shape.ndim(),
+                    a1.item_shape.ndim(),
+                );
+                ensure!(
+                    GlensideError::Analysis,
+                    axis < a1.shape.ndim() + a1.item_shape.ndim()
+                );
                 if axis < new_access.shape.ndim() {
                     new_access.shape[axis] += a1.shape[axis];
                 } else {
@@ -3867,25 +5045,35 @@ impl egg::Analysis<Language> for MyAnalysis {
                     zero_regions: { HashMap::default() },
                     shape: match &egraph[shape_id].data {
                         MyAnalysisData::Shape(s) => s.shape.clone(),
-                        _ => panic!(),
+                        _ => bail!(
+                            GlensideError::Analysis,
+                            "children of the wrong kinds: {}",
+                            child_kinds(egraph, enode)
+                        ),
                     },
                     access_pattern_shape_settled: all_children_are_settled(egraph, enode),
                     item_shape: match &egraph[item_shape_id].data {
                         MyAnalysisData::Shape(s) => s.shape.clone(),
-                        _ => panic!(),
+                        _ => bail!(
+                            GlensideError::Analysis,
+                            "children of the wrong kinds: {}",
+                            child_kinds(egraph, enode)
+                        ),
                     },
                     contains_accelerator_calls: false,
                 })
             }
-            Shape(list) => MyAnalysisData::Shape(ShapeData {
-                shape: IxDyn(
-                    list.iter()
-                        .map(|id: &Id| MyAnalysis::get_usize(*id, egraph))
-                        .collect::<Vec<_>>()
-                        .as_slice(),
-                ),
-                dtype: crate::language::DataType::Uint(64),
-            }),
+            Shape(list) => {
+                let shape = list
+                    .iter()
+                    .map(|id: &Id| MyAnalysis::try_get_usize(*id, egraph))
+                    .collect::<crate::error::Result<Vec<_>>>()?;
+                num_elements(&shape).map_err(GlensideError::Analysis)?;
+                MyAnalysisData::Shape(ShapeData {
+                    shape: IxDyn(&shape),
+                    dtype: crate::language::DataType::Uint(64),
+                })
+            }
             &AccessReshape([access_id, access_shape_id]) => {
                 let a = match &egraph[access_id].data {
                     MyAnalysisData::AccessPattern(a) => a.clone(),
@@ -3896,11 +5084,18 @@ impl egg::Analysis<Language> for MyAnalysis {
                         access_pattern_shape_settled: all_children_are_settled(egraph, enode),
                         contains_accelerator_calls: false,
                     },
-                    _ => panic!("Expected an access as the first argument to access-reshape"),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Expected an access as the first argument to access-reshape"
+                    ),
                 };
                 let mut new_shape = match &egraph[access_shape_id].data {
                     MyAnalysisData::AccessPattern(a) => a.clone(),
-                    _ => panic!(),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "children of the wrong kinds: {}",
+                        child_kinds(egraph, enode)
+                    ),
                 };
                 // TODO(@gussmith23) Implement zero_regions
                 new_shape.zero_regions = HashMap::default();
@@ -3926,8 +5121,13 @@ impl egg::Analysis<Language> for MyAnalysis {
             &AccessFlatten(access_id) => {
                 let a = match &egraph[access_id].data {
                     MyAnalysisData::AccessPattern(a) => a,
-                    _ => panic!(),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "children of the wrong kinds: {}",
+                        child_kinds(egraph, enode)
+                    ),
                 };
+                let flattened = AccessShape::from(a).flatten();
                 MyAnalysisData::AccessPattern(AccessPatternData {
                     // TODO(@gussmith23) Implement zero regions
                     // It's harmless (I think) if `zero_regions` defaults to
@@ -3942,40 +5142,129 @@ impl egg::Analysis<Language> for MyAnalysis {
                         }
                         HashMap::default()
                     },
-                    shape: IxDyn(&{
-                        // This helps us avoid magically inventing a dimension, e.g.
-                        // flatten ((), (256, 2)) should not become ((1), (512)).
-                        if a.shape.ndim() == 0 {
-                            vec![]
-                        } else {
-                            let flattened_shape = a.shape.as_array_view().iter().product();
-                            vec![flattened_shape]
-                        }
-                    }),
-                    item_shape: IxDyn(&{
-                        // This helps us avoid magically inventing a dimension, e.g.
-                        // flatten ((256, 2), ()) should not become ((512), (1)).
-                        if a.item_shape.ndim() == 0 {
-                            vec![]
-                        } else {
-                            let flattened_item_shape =
-                                a.item_shape.as_array_view().iter().product();
-                            vec![flattened_item_shape]
-                        }
-                    }),
+                    shape: flattened.shape,
+                    item_shape: flattened.item_shape,
                     access_pattern_shape_settled: all_children_are_settled(egraph, enode),
                     contains_accelerator_calls: a.contains_accelerator_calls,
                 })
             }
             ComputeType(t) => MyAnalysisData::ComputeType(t.clone()),
+            &ComputeWithAccumulator([compute_type_id, dtype_id, access_id]) => {
+                match &egraph[compute_type_id].data {
+                    MyAnalysisData::ComputeType(self::ComputeType::DotProduct)
+                    | MyAnalysisData::ComputeType(self::ComputeType::ReduceSum) => (),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Accumulator dtypes are only supported for dot-product and reduce-sum"
+                    ),
+                };
+                match &egraph[dtype_id].data {
+                    MyAnalysisData::DataType(_) => (),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Argument 1 of {:?} should be a DataType",
+                        enode
+                    ),
+                };
+                // Otherwise, the result is that of the plain compute.
+                Self::try_make_uncached(egraph, &Compute([compute_type_id, access_id]))?
+            }
+            &Cast([dtype_id, access_id]) => {
+                match &egraph[dtype_id].data {
+                    MyAnalysisData::DataType(_) => (),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Argument 0 of {:?} should be a DataType",
+                        enode
+                    ),
+                };
+                let mut a = match &egraph[access_id].data {
+                    MyAnalysisData::AccessPattern(a) => a.clone(),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Argument 1 of {:?} should be an access pattern",
+                        enode
+                    ),
+                };
+                // Zeros are cast to zeros, so the zero regions carry over.
+                a.access_pattern_shape_settled = all_children_are_settled(egraph, enode);
+                MyAnalysisData::AccessPattern(a)
+            }
+            &Requantize(
+                [dtype_id, access_id, input_scale_id, input_zero_point_id, output_scale_id, output_zero_point_id, rounding_id],
+            ) => {
+                match &egraph[dtype_id].data {
+                    MyAnalysisData::DataType(crate::language::DataType::Int(_))
+                    | MyAnalysisData::DataType(crate::language::DataType::Uint(_)) => (),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Argument 0 of {:?} should be an integer DataType",
+                        enode
+                    ),
+                };
+                for scale_id in [input_scale_id, output_scale_id].iter() {
+                    let scale = match &egraph[*scale_id].data {
+                        MyAnalysisData::Literal(t) if t.ndim() == 0 => *t.iter().next().unwrap(),
+                        MyAnalysisData::Num(n) => *n as f64,
+                        _ => bail!(
+                            GlensideError::Analysis,
+                            "Scales of {:?} should be float literals",
+                            enode
+                        ),
+                    };
+                    ensure!(
+                        GlensideError::Analysis,
+                        scale > 0.0,
+                        "Quantization scales must be positive"
+                    );
+                }
+                for zero_point_id in [input_zero_point_id, output_zero_point_id].iter() {
+                    match &egraph[*zero_point_id].data {
+                        MyAnalysisData::Num(_) => (),
+                        _ => bail!(
+                            GlensideError::Analysis,
+                            "Zero points of {:?} should be integers",
+                            enode
+                        ),
+                    };
+                }
+                match &egraph[rounding_id].data {
+                    MyAnalysisData::RoundingMode(_) => (),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Argument 6 of {:?} should be a RoundingMode",
+                        enode
+                    ),
+                };
+                let mut a = match &egraph[access_id].data {
+                    MyAnalysisData::AccessPattern(a) => a.clone(),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Argument 1 of {:?} should be an access pattern",
+                        enode
+                    ),
+                };
+                // Zeros generally don't requantize to zeros.
+                a.zero_regions = HashMap::default();
+                a.access_pattern_shape_settled = all_children_are_settled(egraph, enode);
+                MyAnalysisData::AccessPattern(a)
+            }
             &Compute([compute_type_id, access_id]) => {
                 let compute_type = match &egraph[compute_type_id].data {
                     MyAnalysisData::ComputeType(t) => t,
-                    _ => panic!("Argument 0 of {:?} should be a ComputeType", enode),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Argument 0 of {:?} should be a ComputeType",
+                        enode
+                    ),
                 };
                 let a0 = match &egraph[access_id].data {
                     MyAnalysisData::AccessPattern(a0) => a0,
-                    _ => panic!(),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "children of the wrong kinds: {}",
+                        child_kinds(egraph, enode)
+                    ),
                 };
                 // TODO(@gussmith23) Implement zero_regions
                 if !a0.zero_regions.is_empty() {
@@ -4007,8 +5296,19 @@ impl egg::Analysis<Language> for MyAnalysis {
                             contains_accelerator_calls: a0.contains_accelerator_calls,
                         })
                     }
+                    self::ComputeType::ReduceMeanVar => {
+                        let statistic = MyAnalysisData::AccessPattern(AccessPatternData {
+                            zero_regions: HashMap::default(),
+                            shape: a0.shape.clone(),
+                            item_shape: ndarray::IxDyn(&[]),
+                            access_pattern_shape_settled: all_children_are_settled(egraph, enode),
+                            contains_accelerator_calls: a0.contains_accelerator_calls,
+                        });
+                        MyAnalysisData::Tuple(vec![statistic.clone(), statistic])
+                    }
                     self::ComputeType::Softmax => {
-                        assert_eq!(
+                        ensure_eq!(
+                            GlensideError::Analysis,
                             a0.item_shape.ndim(),
                             1,
                             "Softmax is only implemented for axis=-1"
@@ -4035,8 +5335,10 @@ impl egg::Analysis<Language> for MyAnalysis {
                     }
                     self::ComputeType::ElementwiseAdd
                     | self::ComputeType::ElementwiseMul
-                    | self::ComputeType::ElementwiseDiv => {
-                        assert!(a0.item_shape.ndim() >= 1);
+                    | self::ComputeType::ElementwiseDiv
+                    | self::ComputeType::LogicalAnd
+                    | self::ComputeType::LogicalOr => {
+                        ensure!(GlensideError::Analysis, a0.item_shape.ndim() >= 1);
                         MyAnalysisData::AccessPattern(AccessPatternData {
                             // TODO(@gussmith23) Implement zero regions
                             // It's harmless (I think) if `zero_regions` defaults to
@@ -4057,14 +5359,14 @@ impl egg::Analysis<Language> for MyAnalysis {
                             contains_accelerator_calls: a0.contains_accelerator_calls,
                         })
                     }
-                    self::ComputeType::DotProduct => {
+                    self::ComputeType::DotProduct | self::ComputeType::SparseDotProduct => {
                         // If it's =1, that's just a "dot product" of scalars,
                         // which is just a sum.
                         //
                         // Honestly, it could also be 0. It doesn't make much
                         // sense but it's not wrong. Can remove this later if we
                         // want those semantics.
-                        assert!(a0.item_shape.ndim() >= 1);
+                        ensure!(GlensideError::Analysis, a0.item_shape.ndim() >= 1);
 
                         // MyAnalysisData::Tensor(TensorData {
                         //     shape: a0.shape.clone(),
@@ -4112,7 +5414,10 @@ impl egg::Analysis<Language> for MyAnalysis {
                     }
                     self::ComputeType::ReLU
                     | self::ComputeType::Sqrt
-                    | self::ComputeType::Negative => {
+                    | self::ComputeType::Negative
+                    | self::ComputeType::Sigmoid
+                    | self::ComputeType::Tanh
+                    | self::ComputeType::LogicalNot => {
                         // TODO(@gussmith23) Implement zero_regions
                         if !a0.zero_regions.is_empty() {
                             debug!(
@@ -4133,37 +5438,13 @@ impl egg::Analysis<Language> for MyAnalysis {
                     (MyAnalysisData::AccessPattern(a0), MyAnalysisData::AccessPattern(a1)) => {
                         (a0, a1)
                     }
-                    _ => panic!(),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "children of the wrong kinds: {}",
+                        child_kinds(egraph, enode)
+                    ),
                 };
-                assert_eq!(
-                    a0.item_shape, a1.item_shape,
-                    "Cartesian product argument shapes must match"
-                );
-
-                let new_shape = IxDyn(
-                    a0.shape
-                        .as_array_view()
-                        .iter()
-                        .cloned()
-                        .chain(a1.shape.as_array_view().iter().cloned())
-                        .collect::<Vec<usize>>()
-                        .as_slice(),
-                );
-                let new_item_shape = IxDyn(
-                    std::iter::once(2)
-                        .chain(a0.item_shape.as_array_view().iter().cloned())
-                        .collect::<Vec<usize>>()
-                        .as_slice(),
-                );
-
-                assert_eq!(
-                    new_shape.as_array_view().iter().product::<usize>()
-                        * new_item_shape.as_array_view().iter().product::<usize>(),
-                    a0.shape.as_array_view().iter().product::<usize>()
-                        * a1.shape.as_array_view().iter().product::<usize>()
-                        * 2
-                        * a0.item_shape.as_array_view().iter().product::<usize>()
-                );
+                let new_shape = AccessShape::from(a0).cartesian_product(&AccessShape::from(a1));
 
                 MyAnalysisData::AccessPattern(AccessPatternData {
                     zero_regions: {
@@ -4196,28 +5477,413 @@ impl egg::Analysis<Language> for MyAnalysis {
 
                         zero_regions
                     },
-                    shape: new_shape.clone(),
-                    item_shape: new_item_shape.clone(),
+                    shape: new_shape.shape,
+                    item_shape: new_shape.item_shape,
                     access_pattern_shape_settled: all_children_are_settled(egraph, enode),
                     contains_accelerator_calls: a0.contains_accelerator_calls
                         || a1.contains_accelerator_calls,
                 })
             }
+            &Conv1d([data_id, weights_id, strides_id, padding_id, groups_id])
+            | &Conv2d([data_id, weights_id, strides_id, padding_id, groups_id])
+            | &Conv3d([data_id, weights_id, strides_id, padding_id, groups_id]) => {
+                let num_spatial_dims = match enode {
+                    Conv1d(_) => 1,
+                    Conv2d(_) => 2,
+                    Conv3d(_) => 3,
+                    _ => unreachable!(),
+                };
+                let (data, weights) = match (&egraph[data_id].data, &egraph[weights_id].data) {
+                    (
+                        MyAnalysisData::AccessPattern(data),
+                        MyAnalysisData::AccessPattern(weights),
+                    ) => (data, weights),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Expected data and weights of a convolution to be accesses"
+                    ),
+                };
+                let strides = MyAnalysis::try_get_shape_of_value(strides_id, egraph)?;
+                let padding = MyAnalysis::try_get_shape_of_value(padding_id, egraph)?;
+                let groups = MyAnalysis::try_get_usize(groups_id, egraph)?;
+
+                let data_shape = data.as_vec();
+                let weights_shape = weights.as_vec();
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    data_shape.len(),
+                    num_spatial_dims + 2
+                );
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    weights_shape.len(),
+                    num_spatial_dims + 2
+                );
+                ensure_eq!(GlensideError::Analysis, strides.ndim(), num_spatial_dims);
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    padding.ndim(),
+                    2 * num_spatial_dims
+                );
+                ensure!(
+                    GlensideError::Analysis,
+                    groups > 0 && data_shape[1] % groups == 0 && weights_shape[0] % groups == 0,
+                    "Invalid number of groups {} for {} input and {} output channels",
+                    groups,
+                    data_shape[1],
+                    weights_shape[0]
+                );
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    weights_shape[1],
+                    data_shape[1] / groups,
+                    "Weights' input channels must equal data's channels divided by groups"
+                );
+
+                let padded_spatial_shape = (0..num_spatial_dims)
+                    .map(|i| padding[i] + data_shape[2 + i] + padding[num_spatial_dims + i])
+                    .collect::<Vec<_>>();
+                let out_spatial_shape = try_access_windows_resulting_shape(
+                    &IxDyn(&padded_spatial_shape),
+                    &IxDyn(&weights_shape[2..]),
+                    strides,
+                )
+                .map_err(GlensideError::Analysis)?;
+
+                MyAnalysisData::AccessPattern(AccessPatternData {
+                    shape: IxDyn(
+                        &[data_shape[0], weights_shape[0]]
+                            .iter()
+                            .chain(out_spatial_shape.iter())
+                            .cloned()
+                            .collect::<Vec<_>>(),
+                    ),
+                    item_shape: IxDyn(&[]),
+                    zero_regions: HashMap::default(),
+                    // Like the Relay operators, the high-level convolutions
+                    // don't commit to a shape; their lowered forms do.
+                    access_pattern_shape_settled: false,
+                    contains_accelerator_calls: data.contains_accelerator_calls
+                        || weights.contains_accelerator_calls,
+                })
+            }
+            &Conv2dTranspose([data_id, weights_id, strides_id, padding_id, output_padding_id]) => {
+                let (data, weights) = match (&egraph[data_id].data, &egraph[weights_id].data) {
+                    (
+                        MyAnalysisData::AccessPattern(data),
+                        MyAnalysisData::AccessPattern(weights),
+                    ) => (data, weights),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Expected data and weights of a transposed convolution to be accesses"
+                    ),
+                };
+                let strides = MyAnalysis::try_get_shape_of_value(strides_id, egraph)?;
+                let padding = MyAnalysis::try_get_shape_of_value(padding_id, egraph)?;
+                let output_padding = MyAnalysis::try_get_shape_of_value(output_padding_id, egraph)?;
+
+                let data_shape = data.as_vec();
+                let weights_shape = weights.as_vec();
+                ensure_eq!(GlensideError::Analysis, data_shape.len(), 4);
+                ensure_eq!(GlensideError::Analysis, weights_shape.len(), 4);
+                ensure_eq!(GlensideError::Analysis, strides.ndim(), 2);
+                ensure_eq!(GlensideError::Analysis, padding.ndim(), 4);
+                ensure_eq!(GlensideError::Analysis, output_padding.ndim(), 2);
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    weights_shape[0],
+                    data_shape[1],
+                    "Weights' input channels must equal data's channels"
+                );
+
+                let mut out_spatial_shape = Vec::with_capacity(2);
+                for i in 0..2 {
+                    ensure!(
+                        GlensideError::Analysis,
+                        output_padding[i] < strides[i],
+                        "Output padding must be smaller than the stride"
+                    );
+                    let full = (data_shape[2 + i] - 1) * strides[i] + weights_shape[2 + i];
+                    ensure!(
+                        GlensideError::Analysis,
+                        padding[i] + padding[2 + i] < full + output_padding[i],
+                        "Padding removes the entire output"
+                    );
+                    out_spatial_shape.push(full + output_padding[i] - padding[i] - padding[2 + i]);
+                }
+
+                MyAnalysisData::AccessPattern(AccessPatternData {
+                    shape: IxDyn(
+                        &[data_shape[0], weights_shape[1]]
+                            .iter()
+                            .chain(out_spatial_shape.iter())
+                            .cloned()
+                            .collect::<Vec<_>>(),
+                    ),
+                    item_shape: IxDyn(&[]),
+                    zero_regions: HashMap::default(),
+                    access_pattern_shape_settled: false,
+                    contains_accelerator_calls: data.contains_accelerator_calls
+                        || weights.contains_accelerator_calls,
+                })
+            }
+            &BatchMatmul([a_id, b_id]) => {
+                let (a, b) = match (&egraph[a_id].data, &egraph[b_id].data) {
+                    (MyAnalysisData::AccessPattern(a), MyAnalysisData::AccessPattern(b)) => (a, b),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Expected both arguments of batch-matmul to be accesses"
+                    ),
+                };
+                let (a_shape, b_shape) = (a.as_vec(), b.as_vec());
+                ensure_eq!(GlensideError::Analysis, a_shape.len(), 3);
+                ensure_eq!(GlensideError::Analysis, b_shape.len(), 3);
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    a_shape[0],
+                    b_shape[0],
+                    "Batch sizes must match"
+                );
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    a_shape[2],
+                    b_shape[1],
+                    "Inner dimensions of batch-matmul must match"
+                );
+
+                MyAnalysisData::AccessPattern(AccessPatternData {
+                    shape: IxDyn(&[a_shape[0], a_shape[1], b_shape[2]]),
+                    item_shape: IxDyn(&[]),
+                    zero_regions: HashMap::default(),
+                    access_pattern_shape_settled: false,
+                    contains_accelerator_calls: a.contains_accelerator_calls
+                        || b.contains_accelerator_calls,
+                })
+            }
+            &Dropout([data_id, rate_id]) => {
+                let data = match &egraph[data_id].data {
+                    MyAnalysisData::AccessPattern(data) => data,
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Expected data of dropout to be an access"
+                    ),
+                };
+                match &egraph[rate_id].data {
+                    MyAnalysisData::Literal(t)
+                        if t.ndim() == 0 && (0.0..1.0).contains(t.iter().next().unwrap()) => {}
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Rate of dropout should be a scalar in [0, 1)"
+                    ),
+                };
+
+                MyAnalysisData::AccessPattern(data.clone())
+            }
+            &LayerNorm([data_id, gamma_id, beta_id, epsilon_id]) => {
+                let (data, gamma, beta) = match (
+                    &egraph[data_id].data,
+                    &egraph[gamma_id].data,
+                    &egraph[beta_id].data,
+                ) {
+                    (
+                        MyAnalysisData::AccessPattern(data),
+                        MyAnalysisData::AccessPattern(gamma),
+                        MyAnalysisData::AccessPattern(beta),
+                    ) => (data, gamma, beta),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Expected data, gamma, and beta of layer-norm to be accesses"
+                    ),
+                };
+                match &egraph[epsilon_id].data {
+                    MyAnalysisData::Literal(t) if t.ndim() == 0 => (),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Epsilon of layer-norm should be a scalar literal"
+                    ),
+                };
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    gamma.as_vec(),
+                    data.item_shape.slice(),
+                    "Gamma of layer-norm should have the item shape of data"
+                );
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    beta.as_vec(),
+                    data.item_shape.slice(),
+                    "Beta of layer-norm should have the item shape of data"
+                );
+
+                MyAnalysisData::AccessPattern(AccessPatternData {
+                    shape: data.shape.clone(),
+                    item_shape: data.item_shape.clone(),
+                    zero_regions: HashMap::default(),
+                    access_pattern_shape_settled: false,
+                    contains_accelerator_calls: data.contains_accelerator_calls
+                        || gamma.contains_accelerator_calls
+                        || beta.contains_accelerator_calls,
+                })
+            }
+            &GroupNorm([data_id, gamma_id, beta_id, groups_id, epsilon_id]) => {
+                let (data, gamma, beta) = match (
+                    &egraph[data_id].data,
+                    &egraph[gamma_id].data,
+                    &egraph[beta_id].data,
+                ) {
+                    (
+                        MyAnalysisData::AccessPattern(data),
+                        MyAnalysisData::AccessPattern(gamma),
+                        MyAnalysisData::AccessPattern(beta),
+                    ) => (data, gamma, beta),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Expected data, gamma, and beta of group-norm to be accesses"
+                    ),
+                };
+                let groups = MyAnalysis::try_get_usize(groups_id, egraph)?;
+                match &egraph[epsilon_id].data {
+                    MyAnalysisData::Literal(t) if t.ndim() == 0 => (),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Epsilon of group-norm should be a scalar literal"
+                    ),
+                };
+
+                let data_shape = data.as_vec();
+                ensure!(
+                    GlensideError::Analysis,
+                    data_shape.len() >= 2,
+                    "Data of group-norm should have batch and channel axes"
+                );
+                ensure!(
+                    GlensideError::Analysis,
+                    groups > 0 && data_shape[1] % groups == 0,
+                    "{} groups don't evenly divide {} channels",
+                    groups,
+                    data_shape[1]
+                );
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    gamma.as_vec(),
+                    vec![data_shape[1]],
+                    "Gamma of group-norm should be a vector with one value per channel"
+                );
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    beta.as_vec(),
+                    vec![data_shape[1]],
+                    "Beta of group-norm should be a vector with one value per channel"
+                );
+
+                MyAnalysisData::AccessPattern(AccessPatternData {
+                    shape: IxDyn(&data_shape),
+                    item_shape: IxDyn(&[]),
+                    zero_regions: HashMap::default(),
+                    access_pattern_shape_settled: false,
+                    contains_accelerator_calls: data.contains_accelerator_calls
+                        || gamma.contains_accelerator_calls
+                        || beta.contains_accelerator_calls,
+                })
+            }
+            &AdaptivePool2d([pool_type_id, data_id, output_size_id]) => {
+                match &egraph[pool_type_id].data {
+                    MyAnalysisData::ComputeType(self::ComputeType::ReduceMean)
+                    | MyAnalysisData::ComputeType(self::ComputeType::ReduceMax) => (),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Adaptive pooling must be reduce-mean or reduce-max"
+                    ),
+                };
+                let data = match &egraph[data_id].data {
+                    MyAnalysisData::AccessPattern(data) => data,
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Expected data of adaptive-pool2d to be an access"
+                    ),
+                };
+                let output_size = MyAnalysis::try_get_shape_of_value(output_size_id, egraph)?;
+
+                let data_shape = data.as_vec();
+                ensure_eq!(GlensideError::Analysis, data_shape.len(), 4);
+                ensure_eq!(GlensideError::Analysis, output_size.ndim(), 2);
+                ensure!(
+                    GlensideError::Analysis,
+                    output_size.slice().iter().all(|s| *s > 0),
+                    "Output size of adaptive-pool2d must be positive"
+                );
+
+                MyAnalysisData::AccessPattern(AccessPatternData {
+                    shape: IxDyn(&[data_shape[0], data_shape[1], output_size[0], output_size[1]]),
+                    item_shape: IxDyn(&[]),
+                    zero_regions: HashMap::default(),
+                    access_pattern_shape_settled: false,
+                    contains_accelerator_calls: data.contains_accelerator_calls,
+                })
+            }
+            &BiasAdd([data_id, bias_id, axis_id]) => {
+                let (data, bias) = match (&egraph[data_id].data, &egraph[bias_id].data) {
+                    (MyAnalysisData::AccessPattern(data), MyAnalysisData::AccessPattern(bias)) => {
+                        (data, bias)
+                    }
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Expected data and bias of bias-add to be accesses"
+                    ),
+                };
+                let axis = MyAnalysis::try_get_usize(axis_id, egraph)?;
+                ensure!(
+                    GlensideError::Analysis,
+                    axis < data.shape.ndim() + data.item_shape.ndim(),
+                    "Invalid axis {} for bias-add",
+                    axis
+                );
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    bias.as_vec(),
+                    vec![data[axis]],
+                    "Bias should be a vector with length equal to data's length at axis {}",
+                    axis
+                );
+
+                if !data.zero_regions.is_empty() {
+                    debug!(
+                        "Throwing away zero region analysis data on line {}",
+                        std::line!()
+                    );
+                }
+
+                MyAnalysisData::AccessPattern(AccessPatternData {
+                    shape: data.shape.clone(),
+                    item_shape: data.item_shape.clone(),
+                    zero_regions: HashMap::default(),
+                    // The lowered form accesses the data differently, so we
+                    // leave it to decide the shape.
+                    access_pattern_shape_settled: false,
+                    contains_accelerator_calls: data.contains_accelerator_calls
+                        || bias.contains_accelerator_calls,
+                })
+            }
             &SliceShape([shape_id, dim_id]) => {
                 let shape = match &egraph[shape_id].data {
                     MyAnalysisData::Shape(s) => &s.shape,
-                    _ => panic!(),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "children of the wrong kinds: {}",
+                        child_kinds(egraph, enode)
+                    ),
                 };
-                let dim = MyAnalysis::get_usize(dim_id, egraph);
+                let dim = MyAnalysis::try_get_usize(dim_id, egraph)?;
                 MyAnalysisData::Shape(ShapeData {
                     shape: IxDyn(shape.as_array_view().slice(s![dim..]).to_slice().unwrap()),
                     dtype: crate::language::DataType::Uint(64),
                 })
             }
             &ShapeInsertAxis([shape_id, dim_id]) => {
-                let shape = MyAnalysis::get_shape_of_value(shape_id, egraph);
-                let dim = MyAnalysis::get_usize(dim_id, egraph);
-                assert!(
+                let shape = MyAnalysis::try_get_shape_of_value(shape_id, egraph)?;
+                let dim = MyAnalysis::try_get_usize(dim_id, egraph)?;
+                ensure!(
+                    GlensideError::Analysis,
                     dim <= shape.ndim(),
                     "Invalid dimension {} for shape {:?}",
                     dim,
@@ -4237,9 +5903,10 @@ impl egg::Analysis<Language> for MyAnalysis {
                 })
             }
             &ShapeRemoveAxis([shape_id, dim_id]) => {
-                let shape = MyAnalysis::get_shape_of_value(shape_id, egraph);
-                let dim = MyAnalysis::get_usize(dim_id, egraph);
-                assert!(
+                let shape = MyAnalysis::try_get_shape_of_value(shape_id, egraph)?;
+                let dim = MyAnalysis::try_get_usize(dim_id, egraph)?;
+                ensure!(
+                    GlensideError::Analysis,
                     dim < shape.ndim(),
                     "Invalid dimension {} for shape {:?}",
                     dim,
@@ -4257,13 +5924,97 @@ impl egg::Analysis<Language> for MyAnalysis {
                     dtype: crate::language::DataType::Uint(64),
                 })
             }
+            &ShapeConcat([shape0_id, shape1_id]) => {
+                let shape0 = MyAnalysis::try_get_shape_of_value(shape0_id, egraph)?;
+                let shape1 = MyAnalysis::try_get_shape_of_value(shape1_id, egraph)?;
+                MyAnalysisData::Shape(ShapeData {
+                    shape: IxDyn(
+                        shape0
+                            .slice()
+                            .iter()
+                            .chain(shape1.slice().iter())
+                            .cloned()
+                            .collect::<Vec<_>>()
+                            .as_slice(),
+                    ),
+                    dtype: crate::language::DataType::Uint(64),
+                })
+            }
+            &ShapeDim([shape_id, dim_id]) => {
+                let shape = MyAnalysis::try_get_shape_of_value(shape_id, egraph)?;
+                let dim = MyAnalysis::try_get_usize(dim_id, egraph)?;
+                ensure!(
+                    GlensideError::Analysis,
+                    dim < shape.ndim(),
+                    "Invalid dimension {} for shape {:?}",
+                    dim,
+                    shape
+                );
+                MyAnalysisData::Num(shape[dim] as i64)
+            }
+            &UsizeAdd([a_id, b_id])
+            | &UsizeSub([a_id, b_id])
+            | &UsizeMul([a_id, b_id])
+            | &UsizeDiv([a_id, b_id]) => {
+                let a = MyAnalysis::try_get_usize(a_id, egraph)?;
+                let b = MyAnalysis::try_get_usize(b_id, egraph)?;
+                let result = match enode {
+                    UsizeAdd(_) => a.checked_add(b),
+                    UsizeSub(_) => {
+                        ensure!(
+                            GlensideError::Analysis,
+                            a >= b,
+                            "Cannot subtract {} from {}",
+                            b,
+                            a
+                        );
+                        Some(a - b)
+                    }
+                    UsizeMul(_) => a.checked_mul(b),
+                    UsizeDiv(_) => {
+                        ensure_ne!(GlensideError::Analysis, b, 0, "Division by zero");
+                        Some(a / b)
+                    }
+                    _ => unreachable!(),
+                }
+                .and_then(|result| i64::try_from(result).ok())
+                .ok_or_else(|| {
+                    GlensideError::Analysis(format!(
+                        "({} {} {}) overflows",
+                        enode.display_op(),
+                        a,
+                        b
+                    ))
+                })?;
+                MyAnalysisData::Num(result)
+            }
+            Unroll(_) => bail!(
+                GlensideError::Analysis,
+                "unroll must be expanded before analysis; see language::unroll::expand_unrolls"
+            ),
+            &TensorDecl([name_id, shape_id]) => {
+                let declared_shape = MyAnalysis::try_get_shape_of_value(shape_id, egraph)?;
+                ensure_eq!(
+                    GlensideError::Analysis,
+                    MyAnalysis::try_get_shape(name_id, egraph)?,
+                    declared_shape,
+                    "Declared shape does not match the shape of the tensor"
+                );
+                MyAnalysisData::Shape(ShapeData {
+                    shape: declared_shape.clone(),
+                    dtype: MyAnalysis::try_get_dtype(name_id, egraph)?.clone(),
+                })
+            }
             &DataType(dtype) => MyAnalysisData::DataType(dtype.clone()),
             &Access([tensor_or_access_id, dim_id]) => {
                 // TODO(@gussmith23) How to access tensor literals?
-                let dim = MyAnalysis::get_usize(dim_id, egraph);
                 let access = match &egraph[tensor_or_access_id].data {
                     MyAnalysisData::AccessPattern(a) => a,
-                    _ => panic!(),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "children of the wrong kinds: {}",
+                        child_kinds(egraph, enode)
+                    ),
                 };
                 let shape = access
                     .shape
@@ -4272,6 +6023,14 @@ impl egg::Analysis<Language> for MyAnalysis {
                     .chain(access.item_shape.as_array_view().iter())
                     .cloned()
                     .collect::<Vec<_>>();
+                // A negative dimension counts back from the end, so that
+                // (access t -1) accesses the last axis as the item.
+                let dim = match &egraph[dim_id].data {
+                    &MyAnalysisData::Num(dim) if dim < 0 => {
+                        try_resolve_axis(dim, shape.len()).map_err(GlensideError::Analysis)?
+                    }
+                    _ => MyAnalysis::try_get_usize(dim_id, egraph)?,
+                };
                 MyAnalysisData::AccessPattern(AccessPatternData {
                     // TODO(@gussmith23) Implement zero regions
                     // It's harmless (I think) if `zero_regions` defaults to
@@ -4292,36 +6051,112 @@ impl egg::Analysis<Language> for MyAnalysis {
                     contains_accelerator_calls: access.contains_accelerator_calls,
                 })
             }
+            &SparseSystolicArray([rows_id, cols_id, a0_id, a1_id]) => {
+                let rows = MyAnalysis::try_get_usize(rows_id, egraph)?;
+                let cols = MyAnalysis::try_get_usize(cols_id, egraph)?;
+
+                let (a0, a1) = match (&egraph[a0_id].data, &egraph[a1_id].data) {
+                    (MyAnalysisData::AccessPattern(a0), MyAnalysisData::AccessPattern(a1)) => {
+                        (a0, a1)
+                    }
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Expected access patterns as third and fourth arguments"
+                    ),
+                };
+
+                ensure!(
+                    GlensideError::Analysis,
+                    a0.shape.ndim() == 0 || a0.shape.ndim() == 1
+                );
+                ensure_eq!(GlensideError::Analysis, a0.item_shape, IxDyn(&[rows]));
+                ensure_eq!(GlensideError::Analysis, a1.shape, IxDyn(&[cols]));
+                ensure_eq!(GlensideError::Analysis, a1.item_shape, IxDyn(&[rows]));
+
+                MyAnalysisData::AccessPattern(AccessPatternData {
+                    // TODO(@gussmith23) Implement zero regions
+                    zero_regions: {
+                        if !a0.zero_regions.is_empty() || !a1.zero_regions.is_empty() {
+                            debug!(
+                                "Throwing away zero region analysis data on line {}",
+                                std::line!()
+                            );
+                        }
+                        HashMap::default()
+                    },
+                    shape: IxDyn(
+                        a0.shape
+                            .slice()
+                            .iter()
+                            .chain(std::iter::once(&cols))
+                            .cloned()
+                            .collect::<Vec<_>>()
+                            .as_slice(),
+                    ),
+                    item_shape: IxDyn(&[]),
+                    access_pattern_shape_settled: all_children_are_settled(egraph, enode),
+                    contains_accelerator_calls: a0.contains_accelerator_calls
+                        || a1.contains_accelerator_calls,
+                })
+            }
             &SystolicArray([rows_id, cols_id, a0_id, a1_id])
-            | &SystolicArrayWithBlocking([rows_id, cols_id, a0_id, a1_id]) => {
-                let rows = Self::get_usize(rows_id, egraph);
-                let cols = Self::get_usize(cols_id, egraph);
+            | &SystolicArrayWithBlocking([rows_id, cols_id, a0_id, a1_id])
+            | &SystolicArrayWithActivation([_, rows_id, cols_id, a0_id, a1_id]) => {
+                if let &SystolicArrayWithActivation([activation_id, ..]) = enode {
+                    match &egraph[activation_id].data {
+                        MyAnalysisData::ComputeType(t) if t.is_activation() => (),
+                        other => bail!(
+                            GlensideError::Analysis,
+                            "Expected an activation function, found {:?}",
+                            other
+                        ),
+                    }
+                }
+
+                let rows = MyAnalysis::try_get_usize(rows_id, egraph)?;
+                let cols = MyAnalysis::try_get_usize(cols_id, egraph)?;
 
                 let (a0, a1) = match (&egraph[a0_id].data, &egraph[a1_id].data) {
                     (MyAnalysisData::AccessPattern(a0), MyAnalysisData::AccessPattern(a1)) => {
                         (a0, a1)
                     }
-                    _ => panic!("Expected access patterns as third and fourth arguments"),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Expected access patterns as third and fourth arguments"
+                    ),
                 };
 
-                assert_eq!(a1.shape, IxDyn(&[]));
-                assert!(a0.shape.ndim() == 0 || a0.shape.ndim() == 1);
+                ensure_eq!(GlensideError::Analysis, a1.shape, IxDyn(&[]));
+                ensure!(
+                    GlensideError::Analysis,
+                    a0.shape.ndim() == 0 || a0.shape.ndim() == 1
+                );
 
                 match &enode {
-                    &SystolicArray(_) => {
-                        assert_eq!(a1.item_shape, IxDyn(&[rows, cols]));
-                        assert_eq!(a0.item_shape, IxDyn(&[rows]));
+                    &SystolicArray(_) | &SystolicArrayWithActivation(_) => {
+                        ensure_eq!(GlensideError::Analysis, a1.item_shape, IxDyn(&[rows, cols]));
+                        ensure_eq!(GlensideError::Analysis, a0.item_shape, IxDyn(&[rows]));
                     }
                     &SystolicArrayWithBlocking(_) => {
                         // Scott: The input vector size should be a multiple of
                         // the systolic array's height and the output vector
                         // size should be a multiple of the systolic array's
                         // width.
-                        assert_eq!(a0.item_shape.ndim(), 1);
-                        assert!(a0.item_shape.slice()[0] % rows == 0);
-                        assert_eq!(a1.item_shape.ndim(), 2);
-                        assert_eq!(a0.item_shape.slice()[0], a1.item_shape.slice()[0]);
-                        assert!(a1.item_shape.slice()[1] % cols == 0);
+                        ensure_eq!(GlensideError::Analysis, a0.item_shape.ndim(), 1);
+                        ensure!(
+                            GlensideError::Analysis,
+                            a0.item_shape.slice()[0] % rows == 0
+                        );
+                        ensure_eq!(GlensideError::Analysis, a1.item_shape.ndim(), 2);
+                        ensure_eq!(
+                            GlensideError::Analysis,
+                            a0.item_shape.slice()[0],
+                            a1.item_shape.slice()[0]
+                        );
+                        ensure!(
+                            GlensideError::Analysis,
+                            a1.item_shape.slice()[1] % cols == 0
+                        );
                     }
                     _ => unreachable!(),
                 }
@@ -4363,44 +6198,46 @@ impl egg::Analysis<Language> for MyAnalysis {
             }
             Num(u) => MyAnalysisData::Num(*u),
             Symbol(name) => {
+                let shape = match &name[..] {
+                    "in" => vec![1, 784],
+                    "w1" => vec![784, 512],
+                    "w2" => vec![512, 512],
+                    "w3" => vec![512, 10],
+                    // TODO(@gussmith23) have to figure out a way around this.
+                    // Max seems to think the tensors should just go
+                    // into the egraph. I was hoping to have some kind
+                    // of environment that we could wrap the egraph in
+                    // (would have to be accessible from here), but Max
+                    // doesn't have that nor does he plan to implement
+                    // it.
+                    //
+                    // Update, Max is implementing something that will
+                    // allow for this.
+                    "single-matrix-multiply-input-a" => vec![32, 32],
+                    "single-matrix-multiply-input-b" => vec![32, 32],
+                    "v-32" => vec![32],
+                    "t-32-32" => vec![32, 32],
+                    "t-32-64" => vec![32, 64],
+                    "t-64-128" => vec![64, 128],
+                    "t-128-16" => vec![128, 16],
+                    // A 3-channel "image" in CHW format.
+                    "t-3-32-32" => vec![3, 32, 32],
+                    // An OIHW set of convolution filters.
+                    "t-8-3-3-3" => vec![8, 3, 3, 3],
+                    "t-1024-2-256" => vec![1024, 2, 256],
+                    "t-1-2-3-4" => vec![1, 2, 3, 4],
+                    _ => egraph
+                        .analysis
+                        .name_to_shape
+                        .get(name)
+                        .ok_or_else(|| {
+                            GlensideError::Analysis(format!("No shape defined for {}", name))
+                        })?
+                        .clone(),
+                };
+                check_tensor_shape(name, &shape).map_err(GlensideError::Analysis)?;
                 MyAnalysisData::Shape(ShapeData {
-                    shape: ndarray::IxDyn(
-                        &(match &name[..] {
-                            "in" => vec![1, 784],
-                            "w1" => vec![784, 512],
-                            "w2" => vec![512, 512],
-                            "w3" => vec![512, 10],
-                            // TODO(@gussmith23) have to figure out a way around this.
-                            // Max seems to think the tensors should just go
-                            // into the egraph. I was hoping to have some kind
-                            // of environment that we could wrap the egraph in
-                            // (would have to be accessible from here), but Max
-                            // doesn't have that nor does he plan to implement
-                            // it.
-                            //
-                            // Update, Max is implementing something that will
-                            // allow for this.
-                            "single-matrix-multiply-input-a" => vec![32, 32],
-                            "single-matrix-multiply-input-b" => vec![32, 32],
-                            "v-32" => vec![32],
-                            "t-32-32" => vec![32, 32],
-                            "t-32-64" => vec![32, 64],
-                            "t-64-128" => vec![64, 128],
-                            "t-128-16" => vec![128, 16],
-                            // A 3-channel "image" in CHW format.
-                            "t-3-32-32" => vec![3, 32, 32],
-                            // An OIHW set of convolution filters.
-                            "t-8-3-3-3" => vec![8, 3, 3, 3],
-                            "t-1024-2-256" => vec![1024, 2, 256],
-                            "t-1-2-3-4" => vec![1, 2, 3, 4],
-                            _ => egraph
-                                .analysis
-                                .name_to_shape
-                                .get(name)
-                                .unwrap_or_else(|| panic!("No shape defined for {}", name))
-                                .clone(),
-                        })[..],
-                    ),
+                    shape: ndarray::IxDyn(&shape),
                     dtype: egraph
                         .analysis
                         .name_to_dtype
@@ -4410,21 +6247,27 @@ impl egg::Analysis<Language> for MyAnalysis {
                 })
             }
             PadType(t) => MyAnalysisData::PadType(*t),
+            WindowConvention(c) => MyAnalysisData::WindowConvention(*c),
+            RoundingMode(m) => MyAnalysisData::RoundingMode(*m),
             &AccessWindows([access_id, filters_shape_id, stride_shape_id]) => {
                 let access = match &egraph[access_id].data {
                     MyAnalysisData::AccessPattern(a) => a,
                     _ => {
-                        panic!("Expected an access pattern as the first argument to access-windows")
+                        bail!(
+                            GlensideError::Analysis,
+                            "Expected an access pattern as the first argument to access-windows"
+                        )
                     }
                 };
-                let filters_shape = MyAnalysis::get_shape_of_value(filters_shape_id, egraph);
-                let stride_shape = MyAnalysis::get_shape_of_value(stride_shape_id, egraph);
+                let filters_shape = MyAnalysis::try_get_shape_of_value(filters_shape_id, egraph)?;
+                let stride_shape = MyAnalysis::try_get_shape_of_value(stride_shape_id, egraph)?;
 
                 // TODO(@gussmith23) Generalize AccessWindows to other accesses
                 // Right now we expect item shape to be a scalar.
                 // I don't think we need this to be true.
                 //assert_eq!(access.item_shape.ndim(), 0);
 
+                let windows = AccessShape::from(access).windows(&filters_shape, &stride_shape);
                 MyAnalysisData::AccessPattern(AccessPatternData {
                     // TODO(@gussmith23) Implement zero regions
                     // It's harmless (I think) if `zero_regions` defaults to
@@ -4439,6 +6282,178 @@ impl egg::Analysis<Language> for MyAnalysis {
                         }
                         HashMap::default()
                     },
+                    shape: windows.shape,
+                    item_shape: windows.item_shape,
+                    access_pattern_shape_settled: all_children_are_settled(egraph, enode),
+                    contains_accelerator_calls: access.contains_accelerator_calls,
+                })
+            }
+
+            &AccessWindowsWithConvention(
+                [access_id, filters_shape_id, stride_shape_id, convention_id, pad_type_id],
+            ) => {
+                let (access, convention) = match (
+                    &egraph[access_id].data,
+                    &egraph[convention_id].data,
+                    &egraph[pad_type_id].data,
+                ) {
+                    (
+                        MyAnalysisData::AccessPattern(a),
+                        MyAnalysisData::WindowConvention(c),
+                        MyAnalysisData::PadType(_),
+                    ) => (a, *c),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "children of the wrong kinds: {}",
+                        child_kinds(egraph, enode)
+                    ),
+                };
+                let filters_shape = MyAnalysis::try_get_shape_of_value(filters_shape_id, egraph)?;
+                let stride_shape = MyAnalysis::try_get_shape_of_value(stride_shape_id, egraph)?;
+
+                let padding = super::windows::padding(
+                    access.item_shape.slice(),
+                    filters_shape.slice(),
+                    stride_shape.slice(),
+                    convention,
+                )
+                .map_err(GlensideError::Analysis)?;
+                let padded_item_shape: Vec<usize> = access
+                    .item_shape
+                    .slice()
+                    .iter()
+                    .zip(padding.iter())
+                    .map(|(dim_len, (before, after))| before + dim_len + after)
+                    .collect();
+                let windows_shape = try_access_windows_resulting_shape(
+                    &IxDyn(&padded_item_shape),
+                    &filters_shape,
+                    &stride_shape,
+                )
+                .map_err(GlensideError::Analysis)?;
+
+                MyAnalysisData::AccessPattern(AccessPatternData {
+                    // TODO(@gussmith23) Implement zero regions
+                    zero_regions: {
+                        if !access.zero_regions.is_empty() {
+                            debug!(
+                                "Throwing away zero region analysis data on line {}",
+                                std::line!()
+                            );
+                        }
+                        HashMap::default()
+                    },
+                    shape: IxDyn(
+                        &access
+                            .shape
+                            .slice()
+                            .iter()
+                            .cloned()
+                            .chain(windows_shape)
+                            .collect::<Vec<_>>(),
+                    ),
+                    item_shape: filters_shape.clone(),
+                    access_pattern_shape_settled: all_children_are_settled(egraph, enode),
+                    contains_accelerator_calls: access.contains_accelerator_calls,
+                })
+            }
+
+            &ShapeOf([tensor_id]) => MyAnalysisData::Shape(ShapeData {
+                shape: MyAnalysis::try_get_shape(tensor_id, egraph)?.clone(),
+                dtype: MyAnalysis::try_get_dtype(tensor_id, egraph)?.clone(),
+            }),
+            &ActivationUnit([activation_id, access_id]) => {
+                let activation = match &egraph[activation_id].data {
+                    MyAnalysisData::ComputeType(t) if t.runs_on_activation_unit() => t,
+                    other => bail!(
+                        GlensideError::Analysis,
+                        "Expected an activation function, found {:?}",
+                        other
+                    ),
+                };
+                let mut a = match &egraph[access_id].data {
+                    MyAnalysisData::AccessPattern(a) => a.clone(),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Expected an access pattern as the input to activation-unit"
+                    ),
+                };
+                if *activation == self::ComputeType::Softmax {
+                    ensure_eq!(
+                        GlensideError::Analysis,
+                        a.item_shape.ndim(),
+                        1,
+                        "The activation unit only computes softmax over vectors"
+                    );
+                }
+                // TODO(@gussmith23) Implement zero_regions
+                if !a.zero_regions.is_empty() {
+                    debug!(
+                        "Throwing away zero region analysis data on line {}",
+                        std::line!()
+                    );
+                }
+                a.zero_regions = HashMap::default();
+                a.access_pattern_shape_settled = all_children_are_settled(egraph, enode);
+                MyAnalysisData::AccessPattern(a)
+            }
+            &VectorAlu([op_id, lanes_id, access_id]) => {
+                match &egraph[op_id].data {
+                    MyAnalysisData::ComputeType(self::ComputeType::ElementwiseAdd)
+                    | MyAnalysisData::ComputeType(self::ComputeType::ElementwiseMul) => (),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Vector ALU must be elementwise-add or elementwise-mul"
+                    ),
+                };
+                let lanes = MyAnalysis::try_get_usize(lanes_id, egraph)?;
+                let a = match &egraph[access_id].data {
+                    MyAnalysisData::AccessPattern(a) => a,
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Expected an access pattern as the input to vector-alu"
+                    ),
+                };
+                ensure_eq!(GlensideError::Analysis, a.item_shape, IxDyn(&[2, lanes]));
+
+                MyAnalysisData::AccessPattern(AccessPatternData {
+                    // TODO(@gussmith23) Implement zero regions
+                    zero_regions: {
+                        if !a.zero_regions.is_empty() {
+                            debug!(
+                                "Throwing away zero region analysis data on line {}",
+                                std::line!()
+                            );
+                        }
+                        HashMap::default()
+                    },
+                    shape: a.shape.clone(),
+                    item_shape: IxDyn(&[lanes]),
+                    access_pattern_shape_settled: all_children_are_settled(egraph, enode),
+                    contains_accelerator_calls: a.contains_accelerator_calls,
+                })
+            }
+            &PoolingUnit([pool_type_id, access_id, window_shape_id, stride_shape_id]) => {
+                match &egraph[pool_type_id].data {
+                    MyAnalysisData::ComputeType(self::ComputeType::ReduceMax)
+                    | MyAnalysisData::ComputeType(self::ComputeType::ReduceMean) => (),
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Pooling unit must be reduce-max or reduce-mean"
+                    ),
+                };
+                let access = match &egraph[access_id].data {
+                    MyAnalysisData::AccessPattern(a) => a,
+                    _ => bail!(
+                        GlensideError::Analysis,
+                        "Expected an access pattern as the input to pooling-unit"
+                    ),
+                };
+                let window_shape = MyAnalysis::try_get_shape_of_value(window_shape_id, egraph)?;
+                let stride_shape = MyAnalysis::try_get_shape_of_value(stride_shape_id, egraph)?;
+
+                MyAnalysisData::AccessPattern(AccessPatternData {
+                    zero_regions: HashMap::default(),
                     shape: IxDyn(
                         access
                             .shape
@@ -4446,29 +6461,23 @@ impl egg::Analysis<Language> for MyAnalysis {
                             .iter()
                             .cloned()
                             .chain(
-                                access_windows_resulting_shape(
+                                try_access_windows_resulting_shape(
                                     &access.item_shape,
-                                    &filters_shape,
-                                    &stride_shape,
+                                    window_shape,
+                                    stride_shape,
                                 )
-                                .as_slice()
-                                .iter()
-                                .cloned(),
+                                .map_err(GlensideError::Analysis)?
+                                .into_iter(),
                             )
                             .collect::<Vec<_>>()
                             .as_slice(),
                     ),
-                    item_shape: filters_shape.clone(),
+                    item_shape: IxDyn(&[]),
                     access_pattern_shape_settled: all_children_are_settled(egraph, enode),
                     contains_accelerator_calls: access.contains_accelerator_calls,
                 })
             }
-
-            &ShapeOf([tensor_id]) => MyAnalysisData::Shape(ShapeData {
-                shape: Self::get_shape(tensor_id, egraph).clone(),
-                dtype: Self::get_dtype(tensor_id, egraph).clone(),
-            }),
-        }
+        })
     }
 }
 
@@ -4511,6 +6520,34 @@ mod tests {
         }
     }
 
+    #[test]
+    #[should_panic(
+        expected = "access-windows axis 2 (input size 32, filter size 33, stride 1): filter is larger than input"
+    )]
+    fn access_windows_filter_too_large() {
+        let program = "
+         (access-windows (access (access-tensor t-3-32-32) 0) (shape 3 3 33) (shape 1 1 1))
+         "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        egraph.add_expr(&program);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "access-windows axis 1 (input size 32, filter size 3, stride 0): stride must be positive"
+    )]
+    fn access_windows_zero_stride() {
+        let program = "
+         (access-windows (access (access-tensor t-3-32-32) 0) (shape 3 3 3) (shape 1 0 1))
+         "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        egraph.add_expr(&program);
+    }
+
     #[test]
     fn shape_of() {
         let program = "
@@ -4670,6 +6707,279 @@ mod tests {
         assert_eq!(MyAnalysis::get_shape_of_value(id, &egraph), &IxDyn(&[1, 2]));
     }
 
+    #[test]
+    fn shape_concat() {
+        let program = "
+         (shape-concat (shape 1 2) (shape-of t-32-64))
+         "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program);
+        assert_eq!(
+            MyAnalysis::get_shape_of_value(id, &egraph),
+            &IxDyn(&[1, 2, 32, 64])
+        );
+    }
+
+    #[test]
+    fn shape_dim() {
+        let program = "
+         (shape-dim (shape 1 2 3) 1)
+         "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program);
+        assert_eq!(MyAnalysis::get_usize(id, &egraph), 2);
+    }
+
+    #[test]
+    fn shape_dim_in_shape() {
+        // A window shape built from the dimensions of another tensor.
+        let program = "
+         (access-windows
+          (access (access-tensor t-3-32-32) 0)
+          (shape-insert-axis
+           (shape (shape-dim (shape-of t-8-3-3-3) 2) (shape-dim (shape-of t-8-3-3-3) 3))
+           0)
+          (shape-concat (shape 1) (shape 1 1)))
+         "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program);
+        match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => {
+                assert_eq!(a.shape, IxDyn(&[3, 30, 30]));
+                assert_eq!(a.item_shape, IxDyn(&[1, 3, 3]));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn usize_arithmetic_constant_folding() {
+        use egg::Searcher;
+        let program = "
+         (shape (+ 2 3) (- 7 3) (* 2 4) (/ 9 2))
+         "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+        assert_eq!(
+            MyAnalysis::get_shape_of_value(id, &egraph),
+            &IxDyn(&[5, 4, 8, 4])
+        );
+        assert!("(shape 5 4 8 4)"
+            .parse::<egg::Pattern<_>>()
+            .unwrap()
+            .search_eclass(&egraph, id)
+            .is_some());
+    }
+
+    #[test]
+    fn usize_arithmetic_in_access_pad() {
+        // Pad 30 up to the closest multiple of 16.
+        let program = "
+         (access-pad (access (access-tensor t) 1) zero-padding 1 0
+          (- (* (/ (+ (shape-dim (shape-of t) 1) 15) 16) 16) 30))
+         "
+        .parse()
+        .unwrap();
+        let mut map = HashMap::default();
+        map.insert("t".to_string(), vec![2, 30]);
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => {
+                assert_eq!(a.shape, IxDyn(&[2]));
+                assert_eq!(a.item_shape, IxDyn(&[32]));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Division by zero")]
+    fn usize_div_panic() {
+        let program = "(/ 2 0)".parse().unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        egraph.add_expr(&program);
+    }
+
+    #[test]
+    fn tensor_decl() {
+        let program = "
+         (compute dot-product
+          (access-cartesian-product
+           (access (access-tensor (tensor-decl a (shape 16 32))) 1)
+           (access (access-tensor (tensor-decl b (shape 8 32))) 1)))
+         "
+        .parse()
+        .unwrap();
+        let mut egraph =
+            egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::from_tensor_decls(&program));
+        let id = egraph.add_expr(&program);
+        match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => {
+                assert_eq!(a.shape, IxDyn(&[16, 8]));
+                assert_eq!(a.item_shape, IxDyn(&[]));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn tensor_decl_with_existing_map() {
+        let program = "(access-tensor (tensor-decl a (shape 16 32)))"
+            .parse()
+            .unwrap();
+        let mut map = HashMap::default();
+        map.insert("a".to_string(), vec![16, 32]);
+        let mut analysis = MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        };
+        analysis.add_tensor_decls(&program);
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(analysis);
+        let id = egraph.add_expr(&program);
+        match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => assert_eq!(a.shape, IxDyn(&[16, 32])),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Conflicting shapes declared for tensor a")]
+    fn tensor_decl_conflicting_map_panic() {
+        let program = "(access-tensor (tensor-decl a (shape 16 32)))"
+            .parse()
+            .unwrap();
+        let mut map = HashMap::default();
+        map.insert("a".to_string(), vec![32, 16]);
+        let mut analysis = MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        };
+        analysis.add_tensor_decls(&program);
+    }
+
+    #[test]
+    #[should_panic(expected = "Declared shape does not match the shape of the tensor")]
+    fn tensor_decl_wrong_shape_panic() {
+        // t-32-32 is one of the built-in test tensors.
+        let program = "(access-tensor (tensor-decl t-32-32 (shape 32 64)))"
+            .parse()
+            .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        egraph.add_expr(&program);
+    }
+
+    #[test]
+    fn cast() {
+        let program = "(cast uint8 (access (access-tensor t-32-64) 1))"
+            .parse()
+            .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program);
+        match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => {
+                assert_eq!(a.shape, IxDyn(&[32]));
+                assert_eq!(a.item_shape, IxDyn(&[64]));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "should be a DataType")]
+    fn cast_panic() {
+        let program = "(cast 8 (access (access-tensor t-32-64) 1))"
+            .parse()
+            .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        egraph.add_expr(&program);
+    }
+
+    #[test]
+    fn compute_with_accumulator() {
+        let program = "
+         (compute-with-accumulator dot-product int32
+          (access (access-tensor t-32-2-64) 1))"
+            .parse()
+            .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program);
+        match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => {
+                assert_eq!(a.shape, IxDyn(&[32]));
+                assert_eq!(a.item_shape, IxDyn(&[]));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "only supported for dot-product and reduce-sum")]
+    fn compute_with_accumulator_panic() {
+        let program = "(compute-with-accumulator relu int32 (access (access-tensor t-32-64) 1))"
+            .parse()
+            .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        egraph.add_expr(&program);
+    }
+
+    #[test]
+    fn requantize() {
+        let program = "
+         (requantize int8 (access (access-tensor t-32-64) 1) 0.02 128 0.05 -10 tonearest)
+         "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program);
+        match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => {
+                assert_eq!(a.shape, IxDyn(&[32]));
+                assert_eq!(a.item_shape, IxDyn(&[64]));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Quantization scales must be positive")]
+    fn requantize_negative_scale_panic() {
+        let program = "
+         (requantize int8 (access (access-tensor t-32-64) 1) -0.5 0 1 0 upward)
+         "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        egraph.add_expr(&program);
+    }
+
+    #[test]
+    #[should_panic]
+    fn shape_dim_panic() {
+        let program = "
+         (shape-dim (shape 1 2 3) 3)
+         "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        egraph.add_expr(&program);
+    }
+
     #[test]
     #[should_panic]
     fn shape_remove_axis_panic() {
@@ -5021,24 +7331,93 @@ mod tests {
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
             MyAnalysisData::AccessPattern(a) => {
-                assert_eq!(a.shape, IxDyn(&[3]));
-                assert_eq!(a.item_shape, IxDyn(&[16, 32]));
+                assert_eq!(a.shape, IxDyn(&[3]));
+                assert_eq!(a.item_shape, IxDyn(&[16, 32]));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn access_slice_2() {
+        let program = "(access-slice (access (access-tensor t-3-32-32) 2) 2 16 32)"
+            .parse()
+            .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program);
+        match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => {
+                assert_eq!(a.shape, IxDyn(&[3, 32]));
+                assert_eq!(a.item_shape, IxDyn(&[16]));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn access_slice_negative_axis() {
+        let program = "(access-slice (access (access-tensor t-3-32-32) -1) -1 16 32)"
+            .parse()
+            .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program);
+        match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => {
+                assert_eq!(a.shape, IxDyn(&[3, 32]));
+                assert_eq!(a.item_shape, IxDyn(&[16]));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Axis -4 out of bounds for 3 axes")]
+    fn access_slice_negative_axis_panic() {
+        let program = "(access-slice (access (access-tensor t-3-32-32) 1) -4 0 1)"
+            .parse()
+            .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        egraph.add_expr(&program);
+    }
+
+    #[test]
+    fn access_squeeze_and_pad_negative_axes() {
+        let program = "
+         (access-pad
+          (access-squeeze
+           (access (access-tensor t) 1)
+           -2)
+          zero-padding -2 1 1)"
+            .parse()
+            .unwrap();
+        let mut map = HashMap::default();
+        map.insert("t".to_string(), vec![3, 1, 32]);
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => {
+                assert_eq!(a.shape, IxDyn(&[5]));
+                assert_eq!(a.item_shape, IxDyn(&[32]));
             }
             _ => panic!(),
         }
     }
 
     #[test]
-    fn access_slice_2() {
-        let program = "(access-slice (access (access-tensor t-3-32-32) 2) 2 16 32)"
+    fn access_transpose_negative_axes() {
+        let program = "(access-transpose (access (access-tensor t-1-2-3-4) 1) (list -1 0 -2 1))"
             .parse()
             .unwrap();
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
             MyAnalysisData::AccessPattern(a) => {
-                assert_eq!(a.shape, IxDyn(&[3, 32]));
-                assert_eq!(a.item_shape, IxDyn(&[16]));
+                assert_eq!(a.shape, IxDyn(&[4]));
+                assert_eq!(a.item_shape, IxDyn(&[1, 3, 2]));
             }
             _ => panic!(),
         }
@@ -5145,6 +7524,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn access_concatenate_negative_axis() {
+        let program = "(access-concatenate (access (access-tensor t-3-32-32) 1) (access (access-tensor t-3-32-32) 1) -1)"
+            .parse()
+            .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program);
+        match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => {
+                assert_eq!(a.shape, IxDyn(&[3]));
+                assert_eq!(a.item_shape, IxDyn(&[32, 64]));
+            }
+            _ => panic!(),
+        }
+    }
+
     #[should_panic]
     #[test]
     fn access_concatenate_panic_0() {
@@ -5608,76 +8003,348 @@ mod tests {
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
             MyAnalysisData::AccessPattern(a) => {
-                assert_eq!(a.shape, IxDyn(&[36]));
-                assert_eq!(a.item_shape, IxDyn(&[34]));
-                assert_eq!(a.zero_regions.len(), 2);
-                assert_eq!(a.zero_regions[&1].len(), 34);
-                assert_eq!(a.zero_regions[&0].len(), 36);
-                assert!(a.zero_regions[&1].covered((32, 34)));
-                assert!(!a.zero_regions[&1].covered((0, 32)));
-                assert!(a.zero_regions[&0].covered((0, 1)));
-                assert!(a.zero_regions[&0].covered((33, 36)));
-                assert!(!a.zero_regions[&0].covered((1, 33)));
+                assert_eq!(a.shape, IxDyn(&[36]));
+                assert_eq!(a.item_shape, IxDyn(&[34]));
+                assert_eq!(a.zero_regions.len(), 2);
+                assert_eq!(a.zero_regions[&1].len(), 34);
+                assert_eq!(a.zero_regions[&0].len(), 36);
+                assert!(a.zero_regions[&1].covered((32, 34)));
+                assert!(!a.zero_regions[&1].covered((0, 32)));
+                assert!(a.zero_regions[&0].covered((0, 1)));
+                assert!(a.zero_regions[&0].covered((33, 36)));
+                assert!(!a.zero_regions[&0].covered((1, 33)));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn access_pad_zero_padding_3() {
+        let program = "
+(access-pad
+ (access-pad (access (access-tensor t-32-32) 1) zero-padding 0 0 2)
+ zero-padding 0 1 3
+)
+         "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program);
+        match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => {
+                assert_eq!(a.shape, IxDyn(&[38]));
+                assert_eq!(a.item_shape, IxDyn(&[32]));
+                assert_eq!(a.zero_regions.len(), 1);
+                assert_eq!(a.zero_regions[&0].len(), 38);
+                assert!(a.zero_regions[&0].covered((0, 1)));
+                assert!(a.zero_regions[&0].covered((35, 38)));
+                assert!(!a.zero_regions[&0].covered((1, 35)));
+                // This one is key: this makes sure that the first pad's zero
+                // region was shifted appropriately by the second pad (was (32,
+                // 34), but should get shifted by 1)
+                assert!(a.zero_regions[&0].covered((33, 35)));
+                assert!(!a.zero_regions[&0].covered((0, 33)));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn access_pad_zero_padding_panic() {
+        let program = "
+         (access-pad (access (access-tensor t-32-32) 1) zero-padding 2 3 2)
+         "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        egraph.add_expr(&program);
+    }
+
+    #[test]
+    fn compute_reduce_max_0() {
+        let program = "
+         (compute reduce-max (access (access-tensor t-3-32-32) 0))
+         "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program);
+        match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => {
+                assert_eq!(a.shape, IxDyn(&[]));
+                assert_eq!(a.item_shape, IxDyn(&[]));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn compute_reduce_max_1() {
+        let program = "
+         (compute reduce-max (access (access-tensor t-3-32-32) 2))
+         "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program);
+        match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => {
+                assert_eq!(a.shape, IxDyn(&[3, 32]));
+                assert_eq!(a.item_shape, IxDyn(&[]));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn access_squeeze() {
+        let program = "
+         (access-squeeze (access (access-tensor t-1-2-3-4) 1) 0)
+         "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program);
+        match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => {
+                assert_eq!(a.shape, IxDyn(&[]));
+                assert_eq!(a.item_shape, IxDyn(&[2, 3, 4]));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn access_squeeze_panic() {
+        let program = "
+         (access-squeeze (access (access-tensor t-1-2-3-4) 1) 2)
+         "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        egraph.add_expr(&program);
+    }
+
+    #[test]
+    fn access_insert_axis() {
+        let program = "
+         (access-insert-axis (access (access-tensor t-1-2-3-4) 1) 0)
+         "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program);
+        match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => {
+                assert_eq!(a.shape, IxDyn(&[1, 1]));
+                assert_eq!(a.item_shape, IxDyn(&[2, 3, 4]));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    // TODO(@gussmith) More access-insert-axis tests
+    fn access_insert_axis_panic() {
+        let program = "
+         (access-squeeze (access (access-tensor t-1-2-3-4) 1) 5)
+         "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        egraph.add_expr(&program);
+    }
+
+    #[test]
+    // TODO(@gussmith) More access-broadcast tests
+    fn access_broadcast() {
+        let program = "
+         (access-broadcast (access (access-tensor t-1-2-3-4) 1) (access-shape (shape 2 2 3 4) (shape)))
+         "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: HashMap::default(),
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => {
+                assert_eq!(a.shape, IxDyn(&[2]));
+                assert_eq!(a.item_shape, IxDyn(&[2, 3, 4]));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn systolic_array() {
+        let mut map = HashMap::default();
+        map.insert("a".to_string(), vec![32, 64]);
+        let program = "
+         (systolic-array 64 32
+          (access (access-tensor a) 1)
+          (access (access-transpose (access-tensor a) (list 1 0)) 0)
+         )
+         "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => {
+                assert_eq!(a.shape, IxDyn(&[32, 32]));
+                assert_eq!(a.item_shape, IxDyn(&[]));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn systolic_array_panic_0() {
+        let mut map = HashMap::default();
+        map.insert("a".to_string(), vec![32, 64]);
+        // Because the second argument is not the right shape.
+        let program = "
+         (systolic-array 64 32
+          (access (access-tensor a) 1)
+          (access (access-tensor a) 0)
+         )
+         "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        egraph.add_expr(&program);
+    }
+
+    #[test]
+    fn conv2d() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![1, 3, 32, 32]);
+        map.insert("weights".to_string(), vec![8, 3, 3, 3]);
+        let program = "
+         (conv2d (access-tensor data) (access-tensor weights)
+          (shape 2 1) (shape 1 0 1 0) 1)
+         "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => {
+                assert_eq!(a.shape, IxDyn(&[1, 8, 16, 30]));
+                assert_eq!(a.item_shape, IxDyn(&[]));
             }
             _ => panic!(),
         }
     }
 
     #[test]
-    fn access_pad_zero_padding_3() {
+    #[should_panic]
+    fn conv2d_panic_groups() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![1, 3, 32, 32]);
+        map.insert("weights".to_string(), vec![8, 3, 3, 3]);
+        // Because 3 channels can't be split into 2 groups.
         let program = "
-(access-pad
- (access-pad (access (access-tensor t-32-32) 1) zero-padding 0 0 2)
- zero-padding 0 1 3
-)
+         (conv2d (access-tensor data) (access-tensor weights)
+          (shape 1 1) (shape 0 0 0 0) 2)
          "
         .parse()
         .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        egraph.add_expr(&program);
+    }
+
+    #[test]
+    fn conv3d() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![1, 2, 8, 8, 8]);
+        map.insert("weights".to_string(), vec![4, 2, 3, 3, 3]);
+        let program = "
+         (conv3d (access-tensor data) (access-tensor weights)
+          (shape 2 1 1) (shape 1 0 1 1 0 1) 1)
+         "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
             MyAnalysisData::AccessPattern(a) => {
-                assert_eq!(a.shape, IxDyn(&[38]));
-                assert_eq!(a.item_shape, IxDyn(&[32]));
-                assert_eq!(a.zero_regions.len(), 1);
-                assert_eq!(a.zero_regions[&0].len(), 38);
-                assert!(a.zero_regions[&0].covered((0, 1)));
-                assert!(a.zero_regions[&0].covered((35, 38)));
-                assert!(!a.zero_regions[&0].covered((1, 35)));
-                // This one is key: this makes sure that the first pad's zero
-                // region was shifted appropriately by the second pad (was (32,
-                // 34), but should get shifted by 1)
-                assert!(a.zero_regions[&0].covered((33, 35)));
-                assert!(!a.zero_regions[&0].covered((0, 33)));
+                assert_eq!(a.shape, IxDyn(&[1, 4, 4, 6, 8]));
+                assert_eq!(a.item_shape, IxDyn(&[]));
             }
             _ => panic!(),
         }
     }
 
     #[test]
-    #[should_panic]
-    fn access_pad_zero_padding_panic() {
+    #[should_panic(
+        expected = "Weights' input channels must equal data's channels divided by groups"
+    )]
+    fn conv3d_panic_channels() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![1, 2, 8, 8, 8]);
+        map.insert("weights".to_string(), vec![4, 3, 3, 3, 3]);
         let program = "
-         (access-pad (access (access-tensor t-32-32) 1) zero-padding 2 3 2)
+         (conv3d (access-tensor data) (access-tensor weights)
+          (shape 1 1 1) (shape 0 0 0 0 0 0) 1)
          "
         .parse()
         .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
         egraph.add_expr(&program);
     }
 
     #[test]
-    fn compute_reduce_max_0() {
+    fn conv2d_transpose() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![1, 4, 8, 5]);
+        map.insert("weights".to_string(), vec![4, 3, 3, 3]);
         let program = "
-         (compute reduce-max (access (access-tensor t-3-32-32) 0))
+         (conv2d-transpose (access-tensor data) (access-tensor weights)
+          (shape 2 3) (shape 1 0 1 2) (shape 1 0))
          "
         .parse()
         .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
             MyAnalysisData::AccessPattern(a) => {
-                assert_eq!(a.shape, IxDyn(&[]));
+                assert_eq!(a.shape, IxDyn(&[1, 3, 16, 13]));
                 assert_eq!(a.item_shape, IxDyn(&[]));
             }
             _ => panic!(),
@@ -5685,17 +8352,42 @@ mod tests {
     }
 
     #[test]
-    fn compute_reduce_max_1() {
+    #[should_panic(expected = "Output padding must be smaller than the stride")]
+    fn conv2d_transpose_panic_output_padding() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![1, 4, 8, 8]);
+        map.insert("weights".to_string(), vec![4, 3, 3, 3]);
         let program = "
-         (compute reduce-max (access (access-tensor t-3-32-32) 2))
+         (conv2d-transpose (access-tensor data) (access-tensor weights)
+          (shape 2 2) (shape 0 0 0 0) (shape 2 0))
          "
         .parse()
         .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        egraph.add_expr(&program);
+    }
+
+    #[test]
+    fn batch_matmul() {
+        let mut map = HashMap::default();
+        map.insert("a".to_string(), vec![4, 8, 16]);
+        map.insert("b".to_string(), vec![4, 16, 32]);
+        let program = "(batch-matmul (access-tensor a) (access-tensor b))"
+            .parse()
+            .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
             MyAnalysisData::AccessPattern(a) => {
-                assert_eq!(a.shape, IxDyn(&[3, 32]));
+                assert_eq!(a.shape, IxDyn(&[4, 8, 32]));
                 assert_eq!(a.item_shape, IxDyn(&[]));
             }
             _ => panic!(),
@@ -5703,109 +8395,198 @@ mod tests {
     }
 
     #[test]
-    fn access_squeeze() {
-        let program = "
-         (access-squeeze (access (access-tensor t-1-2-3-4) 1) 0)
-         "
-        .parse()
-        .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+    #[should_panic(expected = "Inner dimensions of batch-matmul must match")]
+    fn batch_matmul_panic_inner_dimensions() {
+        let mut map = HashMap::default();
+        map.insert("a".to_string(), vec![4, 8, 16]);
+        map.insert("b".to_string(), vec![4, 32, 16]);
+        let program = "(batch-matmul (access-tensor a) (access-tensor b))"
+            .parse()
+            .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        egraph.add_expr(&program);
+    }
+
+    #[test]
+    fn compute_reduce_mean_var() {
+        let mut map = HashMap::default();
+        map.insert("t".to_string(), vec![2, 16, 64]);
+        let program = "(compute reduce-mean-var (access (access-tensor t) 2))"
+            .parse()
+            .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        match &egraph[id].data {
+            MyAnalysisData::Tuple(statistics) => {
+                assert_eq!(statistics.len(), 2);
+                for statistic in statistics {
+                    match statistic {
+                        MyAnalysisData::AccessPattern(a) => {
+                            assert_eq!(a.shape, IxDyn(&[2, 16]));
+                            assert_eq!(a.item_shape, IxDyn(&[]));
+                        }
+                        _ => panic!(),
+                    }
+                }
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn layer_norm() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![2, 16, 64]);
+        map.insert("gamma".to_string(), vec![64]);
+        map.insert("beta".to_string(), vec![64]);
+        let program = "(layer-norm (access (access-tensor data) 2) (access-tensor gamma)
+                        (access-tensor beta) (literal 0.00001))"
+            .parse()
+            .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
             MyAnalysisData::AccessPattern(a) => {
-                assert_eq!(a.shape, IxDyn(&[]));
-                assert_eq!(a.item_shape, IxDyn(&[2, 3, 4]));
+                assert_eq!(a.shape, IxDyn(&[2, 16]));
+                assert_eq!(a.item_shape, IxDyn(&[64]));
             }
             _ => panic!(),
         }
     }
 
     #[test]
-    #[should_panic]
-    fn access_squeeze_panic() {
-        let program = "
-         (access-squeeze (access (access-tensor t-1-2-3-4) 1) 2)
-         "
-        .parse()
-        .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+    #[should_panic(expected = "Gamma of layer-norm should have the item shape of data")]
+    fn layer_norm_panic_gamma() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![2, 16, 64]);
+        map.insert("gamma".to_string(), vec![16]);
+        map.insert("beta".to_string(), vec![64]);
+        let program = "(layer-norm (access (access-tensor data) 2) (access-tensor gamma)
+                        (access-tensor beta) (literal 0.00001))"
+            .parse()
+            .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
         egraph.add_expr(&program);
     }
 
     #[test]
-    fn access_insert_axis() {
-        let program = "
-         (access-insert-axis (access (access-tensor t-1-2-3-4) 1) 0)
-         "
-        .parse()
-        .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+    fn group_norm() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![2, 32, 7, 7]);
+        map.insert("gamma".to_string(), vec![32]);
+        map.insert("beta".to_string(), vec![32]);
+        let program = "(group-norm (access (access-tensor data) 1) (access-tensor gamma)
+                        (access-tensor beta) 8 (literal 0.00001))"
+            .parse()
+            .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
             MyAnalysisData::AccessPattern(a) => {
-                assert_eq!(a.shape, IxDyn(&[1, 1]));
-                assert_eq!(a.item_shape, IxDyn(&[2, 3, 4]));
+                assert_eq!(a.shape, IxDyn(&[2, 32, 7, 7]));
+                assert_eq!(a.item_shape, IxDyn(&[]));
             }
             _ => panic!(),
         }
     }
 
     #[test]
-    #[should_panic]
-    // TODO(@gussmith) More access-insert-axis tests
-    fn access_insert_axis_panic() {
-        let program = "
-         (access-squeeze (access (access-tensor t-1-2-3-4) 1) 5)
-         "
-        .parse()
-        .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+    #[should_panic(expected = "5 groups don't evenly divide 32 channels")]
+    fn group_norm_panic_groups() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![2, 32, 7, 7]);
+        map.insert("gamma".to_string(), vec![32]);
+        map.insert("beta".to_string(), vec![32]);
+        let program = "(group-norm (access-tensor data) (access-tensor gamma)
+                        (access-tensor beta) 5 (literal 0.00001))"
+            .parse()
+            .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
         egraph.add_expr(&program);
     }
 
     #[test]
-    // TODO(@gussmith) More access-broadcast tests
-    fn access_broadcast() {
-        let program = "
-         (access-broadcast (access (access-tensor t-1-2-3-4) 1) (access-shape (shape 2 2 3 4) (shape)))
-         "
-        .parse()
-        .unwrap();
+    fn adaptive_pool2d() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![2, 16, 7, 9]);
+        let program = "(adaptive-pool2d reduce-mean (access-tensor data) (shape 3 4))"
+            .parse()
+            .unwrap();
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
-            name_to_shape: HashMap::default(),
+            name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
             MyAnalysisData::AccessPattern(a) => {
-                assert_eq!(a.shape, IxDyn(&[2]));
-                assert_eq!(a.item_shape, IxDyn(&[2, 3, 4]));
+                assert_eq!(a.shape, IxDyn(&[2, 16, 3, 4]));
+                assert_eq!(a.item_shape, IxDyn(&[]));
             }
             _ => panic!(),
         }
     }
 
     #[test]
-    fn systolic_array() {
+    #[should_panic(expected = "Adaptive pooling must be reduce-mean or reduce-max")]
+    fn adaptive_pool2d_panic_pool_type() {
         let mut map = HashMap::default();
-        map.insert("a".to_string(), vec![32, 64]);
+        map.insert("data".to_string(), vec![2, 16, 7, 9]);
+        let program = "(adaptive-pool2d relu (access-tensor data) (shape 1 1))"
+            .parse()
+            .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        egraph.add_expr(&program);
+    }
+
+    #[test]
+    fn bias_add() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![1, 8, 16, 16]);
+        map.insert("bias".to_string(), vec![8]);
         let program = "
-         (systolic-array 64 32
-          (access (access-tensor a) 1)
-          (access (access-transpose (access-tensor a) (list 1 0)) 0)
-         )
+         (bias-add (access (access-tensor data) 2) (access-tensor bias) 1)
          "
         .parse()
         .unwrap();
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
             MyAnalysisData::AccessPattern(a) => {
-                assert_eq!(a.shape, IxDyn(&[32, 32]));
-                assert_eq!(a.item_shape, IxDyn(&[]));
+                assert_eq!(a.shape, IxDyn(&[1, 8]));
+                assert_eq!(a.item_shape, IxDyn(&[16, 16]));
             }
             _ => panic!(),
         }
@@ -5813,25 +8594,50 @@ mod tests {
 
     #[test]
     #[should_panic]
-    fn systolic_array_panic_0() {
+    fn bias_add_panic() {
         let mut map = HashMap::default();
-        map.insert("a".to_string(), vec![32, 64]);
-        // Because the second argument is not the right shape.
+        map.insert("data".to_string(), vec![1, 8, 16, 16]);
+        map.insert("bias".to_string(), vec![8]);
+        // Because the bias doesn't match the length of axis 2.
         let program = "
-         (systolic-array 64 32
-          (access (access-tensor a) 1)
-          (access (access-tensor a) 0)
-         )
+         (bias-add (access (access-tensor data) 2) (access-tensor bias) 2)
          "
         .parse()
         .unwrap();
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         egraph.add_expr(&program);
     }
 
+    #[test]
+    fn conv1d() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![2, 4, 10]);
+        map.insert("weights".to_string(), vec![6, 2, 3]);
+        let program = "
+         (conv1d (access-tensor data) (access-tensor weights)
+          (shape 1) (shape 1 1) 2)
+         "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => {
+                assert_eq!(a.shape, IxDyn(&[2, 6, 10]));
+                assert_eq!(a.item_shape, IxDyn(&[]));
+            }
+            _ => panic!(),
+        }
+    }
+
     #[test]
     fn list() {
         let program = "
@@ -5863,6 +8669,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype,
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -5895,6 +8702,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype,
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -5921,6 +8729,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -5947,6 +8756,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -5975,6 +8785,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         egraph.add_expr(&program);
     }
@@ -5992,6 +8803,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         egraph.add_expr(&program);
     }
@@ -6197,6 +9009,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6224,6 +9037,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6251,6 +9065,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6263,7 +9078,7 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "assertion failed: a1.item_shape.slice()[1] % cols == 0")]
+    #[should_panic(expected = "Expected a1.item_shape.slice()[1] % cols == 0")]
     fn systolic_array_with_blocking_panic() {
         let mut map = HashMap::default();
         map.insert("a".to_string(), vec![32, 64]);
@@ -6279,6 +9094,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let _id = egraph.add_expr(&program);
     }
@@ -6306,6 +9122,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6341,6 +9158,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6366,6 +9184,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6394,6 +9213,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6418,6 +9238,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6440,6 +9261,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: HashMap::default(),
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6469,6 +9291,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6498,6 +9321,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6528,6 +9352,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6554,6 +9379,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6580,6 +9406,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6607,6 +9434,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6632,6 +9460,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6659,6 +9488,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6687,6 +9517,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6714,6 +9545,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6741,6 +9573,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6769,6 +9602,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6796,6 +9630,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6820,6 +9655,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6848,6 +9684,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6874,6 +9711,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6898,6 +9736,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6927,6 +9766,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: names_to_shapes,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         let section_data = MyAnalysisData::AccessPattern(AccessPatternData {
@@ -6959,6 +9799,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -6984,6 +9825,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -7014,6 +9856,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -7044,6 +9887,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -7076,6 +9920,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -7108,6 +9953,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -7132,6 +9978,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -7162,6 +10009,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -7207,6 +10055,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -7252,6 +10101,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         match &egraph[id].data {
@@ -7273,6 +10123,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: dtypes,
+            shape_cache: None,
         });
         let program = "
     (relay-operator-call relay-cast data float32)
@@ -7300,6 +10151,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: dtypes,
+            shape_cache: None,
         });
         let program = "
     (relay-operator-call relay-take (access-tensor data) (access-tensor indices) 0)
@@ -7332,6 +10184,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: dtypes,
+            shape_cache: None,
         });
         let program = "
     (relay-operator-call relay-take (access-tensor data) (access-tensor indices) 1)
@@ -7364,6 +10217,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: dtypes,
+            shape_cache: None,
         });
         let program = "
     (relay-operator-call relay-take (access-tensor data) (access-tensor indices) 2)
@@ -7395,6 +10249,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: dtypes,
+            shape_cache: None,
         });
         let program = "
     (relay-operator-call relay-stack (access-tensor a) (access-tensor b) (access-tensor c) 0)
@@ -7426,6 +10281,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: dtypes,
+            shape_cache: None,
         });
         let program = "
     (relay-operator-call relay-stack (access-tensor a) (access-tensor b) (access-tensor c) 1)
@@ -7456,6 +10312,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: dtypes,
+            shape_cache: None,
         });
         let program = "
     (relay-operator-call relay-stack (access-tensor a) (access-tensor b) (access-tensor c) 2)
@@ -7486,6 +10343,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: dtypes,
+            shape_cache: None,
         });
         let program = "
     (relay-operator-call relay-stack (access-tensor a) (access-tensor b) (access-tensor c) 3)
@@ -7516,6 +10374,7 @@ mod tests {
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: dtypes,
+            shape_cache: None,
         });
         let program = "
     (relay-operator-call relay-stack (access-tensor a) (access-tensor b) (access-tensor c) -1)
@@ -7529,4 +10388,24 @@ mod tests {
             _ => panic!(),
         }
     }
+
+    #[test]
+    #[should_panic(expected = "Tensor x has a dimension of size 0 in shape [4, 0]")]
+    fn zero_sized_tensor() {
+        let mut map = HashMap::default();
+        map.insert("x".to_string(), vec![4, 0]);
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        egraph.add_expr(&"(access (access-tensor x) 1)".parse().unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected a non-negative number, found -1")]
+    fn negative_dimension() {
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        egraph.add_expr(&"(shape 2 -1)".parse().unwrap());
+    }
 }
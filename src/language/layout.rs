@@ -0,0 +1,237 @@
+//! Row-major and column-major layout checks.
+//!
+//! The interpreter indexes tensors logically, so it computes the same values
+//! whatever the memory layout of its inputs, and its results may come back in
+//! either layout (e.g. `access-transpose` only permutes strides). Hardware
+//! backends, however, consume tensors as flat buffers in some fixed layout,
+//! and a tensor handed over in the other layout is silently transposed.
+//! [`interpret_with_layout`] catches this: it checks that the environment's
+//! tensors are in the declared [`Layout`] before interpreting, and that the
+//! result is in it afterwards, converting them instead if asked to.
+
+use super::interpreter::{try_interpret, Environment, GlensideScalar, Value};
+use super::Language;
+use crate::error::{GlensideError, Result};
+use egg::RecExpr;
+use ndarray::{ArrayD, ArrayViewD, CowArray, ShapeBuilder};
+use std::fmt::Display;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// C order: the last axis is contiguous.
+    RowMajor,
+    /// Fortran order: the first axis is contiguous.
+    ColumnMajor,
+}
+
+impl Display for Layout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Layout::RowMajor => "row-major",
+                Layout::ColumnMajor => "column-major",
+            }
+        )
+    }
+}
+
+impl Layout {
+    /// Whether `tensor` is contiguous and in this layout. Tensors with at
+    /// most one axis longer than 1 are in both layouts.
+    pub fn holds<DataType>(self, tensor: &ArrayViewD<DataType>) -> bool {
+        match self {
+            Layout::RowMajor => tensor.is_standard_layout(),
+            Layout::ColumnMajor => tensor.t().is_standard_layout(),
+        }
+    }
+
+    /// A copy of `tensor` in this layout.
+    pub fn convert<DataType: Clone>(self, tensor: &ArrayViewD<DataType>) -> ArrayD<DataType> {
+        match self {
+            Layout::RowMajor => {
+                ArrayD::from_shape_vec(tensor.raw_dim(), tensor.iter().cloned().collect())
+            }
+            Layout::ColumnMajor => {
+                ArrayD::from_shape_vec(tensor.raw_dim().f(), tensor.t().iter().cloned().collect())
+            }
+        }
+        .unwrap()
+    }
+}
+
+/// How [`interpret_with_layout`] treats tensors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LayoutChecks {
+    /// The layout the backend expects its inputs and results in.
+    pub layout: Layout,
+    /// Whether tensors in the wrong layout are converted, rather than
+    /// reported as errors.
+    pub convert: bool,
+}
+
+impl LayoutChecks {
+    /// Checks (or converts) `tensor`, named `name` in errors.
+    fn check<DataType: Clone>(&self, name: &str, tensor: &mut ArrayD<DataType>) -> Result<()> {
+        if self.layout.holds(&tensor.view()) {
+            Ok(())
+        } else if self.convert {
+            *tensor = self.layout.convert(&tensor.view());
+            Ok(())
+        } else {
+            Err(GlensideError::Interpretation(format!(
+                "{} of shape {:?} isn't {}",
+                name,
+                tensor.shape(),
+                self.layout
+            )))
+        }
+    }
+
+    /// Checks (or converts) every tensor in `env`.
+    pub fn check_environment<DataType: Clone>(
+        &self,
+        env: &mut Environment<DataType>,
+    ) -> Result<()> {
+        let mut names = env.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        for name in names {
+            self.check(name, env.get_mut(name).unwrap())?;
+        }
+        Ok(())
+    }
+
+    /// Checks (or converts) the tensors in `value`.
+    pub fn check_value<DataType: Clone>(&self, value: &mut Value<DataType>) -> Result<()> {
+        match value {
+            Value::Tensor(tensor) => {
+                // Only copy a tensor borrowed from the environment if it needs
+                // converting.
+                if !self.layout.holds(&tensor.view()) {
+                    let mut owned = tensor.to_owned();
+                    self.check("The result", &mut owned)?;
+                    *tensor = CowArray::from(owned);
+                }
+                Ok(())
+            }
+            Value::Access(access) => self.check("The result", &mut access.tensor),
+            Value::Outputs(values) | Value::Tuple(values) => values
+                .iter_mut()
+                .try_for_each(|value| self.check_value(value)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Interprets `expr` at `index`, as [`try_interpret`] does, checking (or
+/// converting) the layout of the tensors in `env` beforehand and of the
+/// result afterwards, per `checks`.
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::interpreter::Environment;
+/// use glenside::language::layout::{interpret_with_layout, Layout, LayoutChecks};
+/// use glenside::language::Language;
+/// use ndarray::array;
+/// use std::collections::HashMap;
+///
+/// let expr: RecExpr<Language> = "(access (access-tensor w) 1)".parse().unwrap();
+/// let mut env: Environment<f64> = HashMap::default();
+/// env.insert("w", array![[1., 2.], [3., 4.]].into_dyn().reversed_axes());
+///
+/// let checks = LayoutChecks { layout: Layout::RowMajor, convert: false };
+/// assert!(interpret_with_layout(&expr, expr.as_ref().len() - 1, &mut env, checks).is_err());
+/// ```
+pub fn interpret_with_layout<'e, DataType: 'static>(
+    expr: &RecExpr<Language>,
+    index: usize,
+    env: &'e mut Environment<DataType>,
+    checks: LayoutChecks,
+) -> Result<Value<'e, DataType>>
+where
+    DataType: GlensideScalar,
+{
+    checks.check_environment(env)?;
+    let mut value = try_interpret(expr, index, &*env)?;
+    checks.check_value(&mut value)?;
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+    use std::collections::HashMap;
+
+    #[test]
+    fn layouts() {
+        let row_major = array![[1, 2, 3], [4, 5, 6]].into_dyn();
+        let column_major = Layout::ColumnMajor.convert(&row_major.view());
+        assert_eq!(column_major, row_major);
+        assert!(Layout::RowMajor.holds(&row_major.view()));
+        assert!(!Layout::ColumnMajor.holds(&row_major.view()));
+        assert!(Layout::ColumnMajor.holds(&column_major.view()));
+        assert!(!Layout::RowMajor.holds(&column_major.view()));
+        assert_eq!(
+            column_major.as_slice_memory_order(),
+            Some(&[1, 4, 2, 5, 3, 6][..])
+        );
+
+        // Vectors are in both layouts.
+        let vector = array![1, 2, 3].into_dyn();
+        assert!(
+            Layout::RowMajor.holds(&vector.view()) && Layout::ColumnMajor.holds(&vector.view())
+        );
+    }
+
+    #[test]
+    fn transposed_result() {
+        let expr: RecExpr<Language> = "(access-transpose (access (access-tensor w) 1) (list 1 0))"
+            .parse()
+            .unwrap();
+        let mut env: Environment<i64> = HashMap::default();
+        env.insert("w", array![[1, 2, 3], [4, 5, 6]].into_dyn());
+
+        let checks = LayoutChecks {
+            layout: Layout::RowMajor,
+            convert: false,
+        };
+        assert_eq!(
+            interpret_with_layout(&expr, expr.as_ref().len() - 1, &mut env, checks).err(),
+            Some(GlensideError::Interpretation(
+                "The result of shape [3, 2] isn't row-major".to_string()
+            ))
+        );
+
+        let checks = LayoutChecks {
+            convert: true,
+            ..checks
+        };
+        match interpret_with_layout(&expr, expr.as_ref().len() - 1, &mut env, checks).unwrap() {
+            Value::Access(access) => {
+                assert_eq!(access.tensor.as_slice(), Some(&[1, 4, 2, 5, 3, 6][..]));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn column_major_inputs() {
+        let expr: RecExpr<Language> = "(access (access-tensor w) 1)".parse().unwrap();
+        let mut env: Environment<i64> = HashMap::default();
+        env.insert("w", array![[1, 2, 3], [4, 5, 6]].into_dyn());
+
+        let checks = LayoutChecks {
+            layout: Layout::ColumnMajor,
+            convert: true,
+        };
+        match interpret_with_layout(&expr, expr.as_ref().len() - 1, &mut env, checks).unwrap() {
+            Value::Access(access) => {
+                assert_eq!(access.tensor, array![[1, 2, 3], [4, 5, 6]].into_dyn());
+                assert!(Layout::ColumnMajor.holds(&access.tensor.view()));
+            }
+            _ => panic!(),
+        }
+        assert!(Layout::ColumnMajor.holds(&env["w"].view()));
+    }
+}
@@ -0,0 +1,227 @@
+//! Lowering of high-level nodes ahead of equality saturation.
+//!
+//! The Relay importer produces opaque `relay-operator-call`s and high-level
+//! Glenside nodes (`conv2d`, `bias-add`, `adaptive-pool2d`, etc.), which are
+//! generally lowered to access patterns by rewrites inside the egraph. This
+//! forces every search to carry both forms of every operator. [`lower`]
+//! instead lowers them once, deterministically, before the egraph is built.
+//! The high-level Glenside nodes can optionally be kept, for the rewrites which
+//! map them to fused hardware operations (e.g.
+//! [`conv2d_1x1_to_matmul`](super::rewrites::conv2d_1x1_to_matmul)).
+
+use super::rewrites;
+use super::unroll::expand_unrolls;
+use super::{Language, MyAnalysis};
+use egg::{CostFunction, Extractor, Id, Language as LanguageTrait, RecExpr, Runner};
+
+/// The cost of a node which should have been lowered. Finite, so that nodes
+/// which can't be lowered (e.g. operators without lowering rewrites) are still
+/// extracted as they are.
+const UNLOWERED_COST: f64 = 1e9;
+
+/// Whether `node` is one of Glenside's high-level operator nodes.
+fn is_high_level(node: &Language) -> bool {
+    match node {
+        Language::Conv1d(_)
+        | Language::Conv2d(_)
+        | Language::Conv3d(_)
+        | Language::Conv2dTranspose(_)
+        | Language::BiasAdd(_)
+        | Language::AdaptivePool2d(_)
+        | Language::BatchMatmul(_)
+        | Language::LayerNorm(_)
+        | Language::GroupNorm(_)
+        | Language::AccessWindowsWithConvention(_) => true,
+        _ => false,
+    }
+}
+
+/// Prefers lowered programs, and then smaller programs.
+struct LoweringCostFunction {
+    keep_high_level_nodes: bool,
+}
+
+impl CostFunction<Language> for LoweringCostFunction {
+    type Cost = f64;
+
+    fn cost<C>(&mut self, enode: &Language, mut costs: C) -> Self::Cost
+    where
+        C: FnMut(Id) -> Self::Cost,
+    {
+        let base_cost = match enode {
+            Language::RelayOperatorCall(_) => UNLOWERED_COST,
+            node if is_high_level(node) && !self.keep_high_level_nodes => UNLOWERED_COST,
+            _ => 1.0,
+        };
+        enode.fold(base_cost, |sum, id| sum + costs(id))
+    }
+}
+
+/// Lowers the `relay-operator-call`s in `expr` to Glenside and, unless
+/// `keep_high_level_nodes` is set, lowers Glenside's high-level nodes to
+/// access patterns. `analysis` must know the shapes of all of the tensors in
+/// `expr`. Operators which can't be lowered are left as they are.
+///
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::lowering::lower;
+/// use glenside::language::{Language, MyAnalysis};
+/// use std::collections::HashMap;
+///
+/// let expr: RecExpr<Language> = "
+///  (relay-operator-call relay-relu
+///   (relay-operator-call relay-dense (access-tensor x) (access-tensor w)))"
+///     .parse()
+///     .unwrap();
+/// let mut name_to_shape = HashMap::default();
+/// name_to_shape.insert("x".to_string(), vec![2, 4]);
+/// name_to_shape.insert("w".to_string(), vec![3, 4]);
+/// let analysis = MyAnalysis {
+///     name_to_shape,
+///     name_to_dtype: HashMap::default(),
+///     shape_cache: None,
+/// };
+///
+/// assert_eq!(
+///     lower(&expr, analysis, false).pretty(200),
+///     "(compute relu (compute dot-product (access-cartesian-product (access (access-tensor x) 1) (access (access-tensor w) 1))))"
+/// );
+/// ```
+pub fn lower(
+    expr: &RecExpr<Language>,
+    analysis: MyAnalysis,
+    keep_high_level_nodes: bool,
+) -> RecExpr<Language> {
+    // Unrolls can't be analyzed, and so can't be added to the egraph.
+    let expr = &expand_unrolls(expr);
+
+    let mut rws = rewrites::relay_to_glenside_rewrites();
+    rws.push(rewrites::remove_dropout());
+    if !keep_high_level_nodes {
+        rws.extend(vec![
+            rewrites::conv1d_to_access_windows(),
+            rewrites::conv2d_to_access_windows(),
+            rewrites::conv3d_to_access_windows(),
+            rewrites::conv2d_transpose_to_access_windows(),
+            rewrites::bias_add_to_glenside(),
+            rewrites::adaptive_pool2d_to_access_windows(),
+            rewrites::access_windows_with_convention_to_access_windows(),
+            rewrites::batch_matmul_to_access_pattern(),
+            rewrites::layer_norm_to_mean_variance(),
+            rewrites::group_norm_to_mean_variance(),
+        ]);
+    }
+
+    // The lowering rewrites only ever lower, and so always saturate. The
+    // simple scheduler applies every rewrite in every iteration, rather than
+    // backing off from rewrites which match many times, which would be most
+    // of them on a large network.
+    let runner = Runner::<_, _, ()>::new(analysis)
+        .with_scheduler(egg::SimpleScheduler)
+        .with_node_limit(usize::MAX)
+        .with_iter_limit(usize::MAX)
+        .with_time_limit(std::time::Duration::from_secs(u64::MAX))
+        .with_expr(expr)
+        .run(&rws);
+
+    let (_, lowered) = Extractor::new(
+        &runner.egraph,
+        LoweringCostFunction {
+            keep_high_level_nodes,
+        },
+    )
+    .find_best(runner.roots[0]);
+    lowered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::verify;
+    use egg::EGraph;
+    use std::collections::HashMap;
+
+    fn analysis(shapes: &[(&str, &[usize])]) -> MyAnalysis {
+        MyAnalysis {
+            name_to_shape: shapes
+                .iter()
+                .map(|(name, shape)| (name.to_string(), shape.to_vec()))
+                .collect(),
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        }
+    }
+
+    #[test]
+    fn lower_conv2d() {
+        let expr: RecExpr<Language> = "
+         (bias-add
+          (conv2d (access-tensor data) (access-tensor weights)
+           (shape 1 1) (shape 1 1 1 1) 1)
+          (access-tensor bias) 1)"
+            .parse()
+            .unwrap();
+        let shapes: &[(&str, &[usize])] = &[
+            ("data", &[1, 3, 8, 8]),
+            ("weights", &[4, 3, 3, 3]),
+            ("bias", &[4]),
+        ];
+
+        let lowered = lower(&expr, analysis(shapes), false);
+        assert!(!lowered.as_ref().iter().any(is_high_level));
+
+        let mut egraph = EGraph::new(analysis(shapes));
+        egraph.add_expr(&expr);
+        assert!(verify(&egraph, &expr, &lowered, 1e-10, 0).passed);
+    }
+
+    #[test]
+    fn lower_conv1d() {
+        let expr: RecExpr<Language> = "
+         (conv1d (access-tensor data) (access-tensor weights)
+          (shape 2) (shape 1 2) 1)"
+            .parse()
+            .unwrap();
+        let shapes: &[(&str, &[usize])] = &[("data", &[2, 3, 9]), ("weights", &[4, 3, 3])];
+
+        let lowered = lower(&expr, analysis(shapes), false);
+        assert!(!lowered.as_ref().iter().any(is_high_level));
+
+        let mut egraph = EGraph::new(analysis(shapes));
+        egraph.add_expr(&expr);
+        assert!(verify(&egraph, &expr, &lowered, 1e-10, 0).passed);
+    }
+
+    #[test]
+    fn remove_dropout() {
+        let expr: RecExpr<Language> = "
+         (relay-operator-call relay-relu
+          (tuple-get-item
+           (relay-operator-call relay-dropout (access-tensor x) 0.5)
+           0))"
+        .parse()
+        .unwrap();
+        let shapes: &[(&str, &[usize])] = &[("x", &[2, 4])];
+
+        assert_eq!(
+            lower(&expr, analysis(shapes), true).pretty(200),
+            "(compute relu (access-tensor x))"
+        );
+    }
+
+    #[test]
+    fn keep_high_level_nodes() {
+        let expr: RecExpr<Language> = "
+         (relay-operator-call relay-relu
+          (conv2d (access-tensor data) (access-tensor weights)
+           (shape 1 1) (shape 0 0 0 0) 1))"
+            .parse()
+            .unwrap();
+        let shapes: &[(&str, &[usize])] = &[("data", &[1, 3, 8, 8]), ("weights", &[4, 3, 1, 1])];
+
+        assert_eq!(
+            lower(&expr, analysis(shapes), true).pretty(200),
+            "(compute relu (conv2d (access-tensor data) (access-tensor weights) (shape 1 1) (shape 0 0 0 0) 1))"
+        );
+    }
+}
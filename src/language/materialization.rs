@@ -0,0 +1,467 @@
+//! Materialization points.
+//!
+//! Not every intermediate value of an extracted program is written to
+//! memory. [`infer_materialization`] decides, for each node of a scheduled
+//! program (see [`schedule`](super::schedule::schedule)) whose value is an
+//! access pattern, how that value comes to exist:
+//!  - [`Materialization::Buffer`]: it's written to a buffer of its own, like
+//!    the program's inputs, most kernels' results, and reorganizations (e.g.
+//!    flattening a transpose) which can't be read in place;
+//!  - [`Materialization::Fused`]: it's never written, but read in place, as a
+//!    strided view of some buffer, by each kernel consuming it; or
+//!  - [`Materialization::Streamed`]: it's a kernel's result which is passed
+//!    element by element to the elementwise kernel consuming it, e.g. a matrix
+//!    multiplication followed by a ReLU, so that it never needs a buffer.
+//!
+//! The schedule records these decisions (see
+//! [`ExecutionSchedule::materialization`]), which the host driver generator
+//! ([`crate::codegen::codegen_host_driver`]) and the memory planner
+//! ([`peak_bytes`]) use. [`report`] lists them alongside the program.
+
+use super::schedule::{BufferKind, ExecutionSchedule};
+use super::{ComputeType, DataType, Language};
+use egg::{Id, Language as LanguageTrait, RecExpr};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt::Display;
+
+/// How the value of a node is stored; see the [module](self) documentation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Materialization {
+    Buffer,
+    Fused,
+    Streamed,
+}
+
+impl Display for Materialization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Materialization::Buffer => "buffer",
+                Materialization::Fused => "fused",
+                Materialization::Streamed => "streamed",
+            }
+        )
+    }
+}
+
+/// Whether `node` is an access pattern manipulation which can be read in
+/// place, as a strided view of its argument.
+fn is_view(node: &Language) -> bool {
+    matches!(
+        node,
+        Language::Access(_)
+            | Language::AccessTranspose(_)
+            | Language::AccessSlice(_)
+            | Language::AccessReverse(_)
+            | Language::AccessSqueeze(_)
+            | Language::AccessInsertAxis(_)
+            | Language::AccessBroadcast(_)
+            | Language::AccessWindows(_)
+            | Language::AccessWindowsWithConvention(_)
+            | Language::AccessPad(_)
+    )
+}
+
+/// Whether `node` combines access patterns, each of which its consumers read
+/// separately.
+fn is_combination(node: &Language) -> bool {
+    matches!(
+        node,
+        Language::AccessCartesianProduct(_)
+            | Language::AccessPair(_)
+            | Language::AccessConcatenate(_)
+    )
+}
+
+/// Whether `node` reorganizes an access pattern in a way which can't be read
+/// in place.
+fn is_reorganization(node: &Language) -> bool {
+    matches!(
+        node,
+        Language::AccessFlatten(_) | Language::AccessReshape(_) | Language::AccessShiftRight(_)
+    )
+}
+
+/// Whether `node` computes each element of its result from the element of
+/// its argument at the same index, in the same order.
+fn is_elementwise(expr: &RecExpr<Language>, node: &Language) -> bool {
+    match node {
+        &Language::Compute([compute_type_id, _]) => matches!(
+            expr.as_ref()[usize::from(compute_type_id)],
+            Language::ComputeType(ComputeType::ReLU)
+                | Language::ComputeType(ComputeType::Sqrt)
+                | Language::ComputeType(ComputeType::Negative)
+                | Language::ComputeType(ComputeType::Sigmoid)
+                | Language::ComputeType(ComputeType::Tanh)
+                | Language::ComputeType(ComputeType::LogicalNot)
+        ),
+        Language::ActivationUnit(_) | Language::Cast(_) | Language::Requantize(_) => true,
+        _ => false,
+    }
+}
+
+/// Marks node `index`, which some kernel (or the host) reads, and the nodes
+/// it reads in turn, up to the nodes written to buffers.
+fn mark_read(expr: &RecExpr<Language>, index: usize, marks: &mut Vec<Option<Materialization>>) {
+    if marks[index].is_some() {
+        return;
+    }
+    let node = &expr.as_ref()[index];
+    let (mark, children) = match node {
+        Language::AccessTensor(_) => (Materialization::Buffer, &[] as &[Id]),
+        _ if is_view(node) => (Materialization::Fused, &node.children()[..1]),
+        _ if is_combination(node) => (Materialization::Fused, node.children()),
+        _ if is_reorganization(node) => (Materialization::Buffer, &node.children()[..1]),
+        _ => return,
+    };
+    marks[index] = Some(mark);
+    for child in children {
+        mark_read(expr, usize::from(*child), marks);
+    }
+}
+
+/// The nodes which `index`, a kernel, reads through each of its reads (see
+/// [`KernelInvocation::reads`](super::schedule::KernelInvocation::reads)),
+/// given `marks` for the nodes it reads.
+fn read_nodes(
+    expr: &RecExpr<Language>,
+    index: usize,
+    marks: &[Option<Materialization>],
+) -> Vec<usize> {
+    expr.as_ref()[index]
+        .children()
+        .iter()
+        .map(|child| usize::from(*child))
+        .filter(|child| marks[*child].is_some())
+        .flat_map(|child| {
+            if is_combination(&expr.as_ref()[child]) {
+                read_nodes(expr, child, marks)
+            } else {
+                vec![child]
+            }
+        })
+        .collect()
+}
+
+/// Decides how the value of each node of `expr`, whose schedule is
+/// `schedule`, is stored, as described in the [module](self) documentation.
+/// The result is indexed by node, and is `None` for nodes which aren't
+/// access patterns, or which aren't scheduled.
+///
+/// A kernel's result is streamed when the next kernel run is elementwise,
+/// and reads it, in order, and nothing else reads it. Views are fused, unless
+/// a kernel can't read them in place (e.g. because it reads its buffer with
+/// conflicting padding), in which case they're written to a buffer.
+pub fn infer_materialization(
+    expr: &RecExpr<Language>,
+    schedule: &ExecutionSchedule,
+) -> Vec<Option<Materialization>> {
+    let nodes = expr.as_ref();
+    let mut marks = vec![None; nodes.len()];
+    for invocation in &schedule.invocations {
+        marks[invocation.node] = Some(Materialization::Buffer);
+    }
+
+    // Kernels read their arguments, and the host reads the results.
+    for invocation in &schedule.invocations {
+        for child in nodes[invocation.node].children() {
+            mark_read(expr, usize::from(*child), &mut marks);
+        }
+    }
+    let root = nodes.len() - 1;
+    match &nodes[root] {
+        Language::Outputs(outputs) => {
+            for output in outputs.iter() {
+                mark_read(expr, usize::from(*output), &mut marks);
+            }
+        }
+        _ => mark_read(expr, root, &mut marks),
+    }
+
+    for (i, invocation) in schedule.invocations.iter().enumerate() {
+        let read_nodes = read_nodes(expr, invocation.node, &marks);
+        assert_eq!(read_nodes.len(), invocation.reads.len());
+        for (read_node, read) in read_nodes.into_iter().zip(&invocation.reads) {
+            // A chain of views which can't be read in place must be written
+            // out. (A chain which can't be read in place because it views a
+            // reorganization reads the reorganization's buffer in place.)
+            let mut node = read_node;
+            while marks[node] == Some(Materialization::Fused) && is_view(&nodes[node]) {
+                node = usize::from(nodes[node].children()[0]);
+            }
+            if read.is_none() && !is_reorganization(&nodes[node]) {
+                marks[read_node] = Some(Materialization::Buffer);
+            }
+        }
+
+        let output = &schedule.buffers[invocation.output];
+        let streamed = output.kind == BufferKind::Intermediate
+            && schedule.invocations.get(i + 1).map_or(false, |next| {
+                next.inputs == vec![output.id]
+                    && is_elementwise(expr, &nodes[next.node])
+                    && match &next.reads[..] {
+                        [Some(read)] => {
+                            read.offset == 0
+                                && read.shape == output.shape
+                                && read
+                                    .strides
+                                    .iter()
+                                    .zip(&output.strides)
+                                    .all(|(read, stride)| *read == *stride as i64)
+                        }
+                        _ => false,
+                    }
+            })
+            && schedule
+                .invocations
+                .iter()
+                .filter(|other| other.inputs.contains(&output.id))
+                .count()
+                == 1;
+        if streamed {
+            marks[invocation.node] = Some(Materialization::Streamed);
+        }
+    }
+
+    marks
+}
+
+/// The number of bytes each element of type `dtype` takes up; 4 if unknown.
+fn element_bytes(dtype: Option<DataType>) -> usize {
+    match dtype {
+        Some(DataType::Bool) => 1,
+        Some(DataType::Int(bits)) | Some(DataType::Float(bits)) | Some(DataType::Uint(bits)) => {
+            (bits + 7) / 8
+        }
+        None => 4,
+    }
+}
+
+/// The most memory, in bytes, that `schedule`'s buffers take up at once, as
+/// its invocations run in order. Inputs and outputs take up memory
+/// throughout, intermediates from when they're written until they're freed,
+/// and the results of streamed kernels none at all.
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::materialization::peak_bytes;
+/// use glenside::language::schedule::schedule;
+/// use glenside::language::{Language, MyAnalysis};
+///
+/// let expr: RecExpr<Language> = "
+///  (compute relu
+///   (systolic-array 32 32
+///    (access (access-tensor t-32-32) 1)
+///    (access (access-tensor t-32-32) 0)))"
+///     .parse()
+///     .unwrap();
+/// // The input and the output, each of 32 * 32 4-byte elements. The product
+/// // is streamed into the relu.
+/// assert_eq!(peak_bytes(&schedule(&expr, MyAnalysis::default())), 2 * 4096);
+/// ```
+pub fn peak_bytes(schedule: &ExecutionSchedule) -> usize {
+    let bytes = |buffer: usize| {
+        let buffer = &schedule.buffers[buffer];
+        buffer
+            .shape
+            .iter()
+            .zip(&buffer.padding)
+            .map(|(dim, (before, after))| before + dim + after)
+            .product::<usize>()
+            * element_bytes(buffer.dtype)
+    };
+    let streamed = schedule
+        .invocations
+        .iter()
+        .filter(|invocation| {
+            schedule.materialization[invocation.node] == Some(Materialization::Streamed)
+        })
+        .map(|invocation| invocation.output)
+        .collect::<HashSet<_>>();
+
+    let mut live = schedule
+        .buffers
+        .iter()
+        .filter(|buffer| buffer.kind != BufferKind::Intermediate)
+        .map(|buffer| bytes(buffer.id))
+        .sum::<usize>();
+    let mut peak = live;
+    for invocation in &schedule.invocations {
+        if schedule.buffers[invocation.output].kind == BufferKind::Intermediate
+            && !streamed.contains(&invocation.output)
+        {
+            live += bytes(invocation.output);
+        }
+        peak = peak.max(live);
+        for freed in &invocation.frees {
+            if !streamed.contains(freed) {
+                live -= bytes(*freed);
+            }
+        }
+    }
+    peak
+}
+
+/// Lists how the value of each node of `expr` is stored, given
+/// `materialization`, as computed by [`infer_materialization`]: one node per
+/// line, e.g. `5: systolic-array: streamed`.
+pub fn report(expr: &RecExpr<Language>, materialization: &[Option<Materialization>]) -> String {
+    expr.as_ref()
+        .iter()
+        .zip(materialization)
+        .enumerate()
+        .filter_map(|(i, (node, materialization))| {
+            materialization.map(|materialization| {
+                format!("{}: {}: {}\n", i, node.display_op(), materialization)
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::dead_code::subexpr;
+    use super::super::schedule::schedule;
+    use super::super::MyAnalysis;
+    use super::*;
+
+    fn analysis() -> MyAnalysis {
+        MyAnalysis {
+            name_to_shape: vec![
+                ("x".to_string(), vec![4, 16]),
+                ("w".to_string(), vec![8, 16]),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// The materialization of the first node of `expr` printed as `node`.
+    fn of(expr: &RecExpr<Language>, node: &str) -> Option<Materialization> {
+        let index = (0..expr.as_ref().len())
+            .find(|index| subexpr(expr, *index).pretty(1000) == node)
+            .unwrap();
+        schedule(expr, analysis()).materialization[index]
+    }
+
+    const PRODUCT: &str = "(systolic-array 16 8 (access (access-tensor x) 1) (access (access-transpose (access-tensor w) (list 1 0)) 0))";
+
+    #[test]
+    fn streamed_into_activation() {
+        let expr: RecExpr<Language> = format!("(compute relu (activation-unit relu {}))", PRODUCT)
+            .parse()
+            .unwrap();
+
+        // Both the product and the first relu are streamed into the kernels
+        // after them. The last relu is the output.
+        assert_eq!(of(&expr, PRODUCT), Some(Materialization::Streamed));
+        assert_eq!(
+            of(&expr, &format!("(activation-unit relu {})", PRODUCT)),
+            Some(Materialization::Streamed)
+        );
+        assert_eq!(of(&expr, &expr.pretty(1000)), Some(Materialization::Buffer));
+        // w is read transposed, in place.
+        assert_eq!(
+            of(&expr, "(access-transpose (access-tensor w) (list 1 0))"),
+            Some(Materialization::Fused)
+        );
+        assert_eq!(
+            of(&expr, "(access-tensor w)"),
+            Some(Materialization::Buffer)
+        );
+        assert_eq!(of(&expr, "(list 1 0)"), None);
+
+        let schedule = schedule(&expr, analysis());
+        // Only x, w, and the output take up memory, each of 4-byte elements.
+        assert_eq!(peak_bytes(&schedule), (64 + 128 + 32) * 4);
+        assert!(report(&expr, &schedule.materialization)
+            .lines()
+            .any(|line| line.ends_with("systolic-array: streamed")));
+        assert_eq!(
+            schedule.to_json()["materialization"]
+                [usize::from(*expr.as_ref().last().unwrap().children().last().unwrap())],
+            "streamed"
+        );
+        assert_eq!(
+            crate::codegen::codegen_host_driver(&schedule, "model")
+                .matches("streams its result into the next invocation")
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn not_streamed() {
+        // The product is read by both the relu and the host, and the relu by
+        // a reduction, which isn't elementwise.
+        let mut expr: RecExpr<Language> =
+            format!("(compute reduce-sum (compute relu {}))", PRODUCT)
+                .parse()
+                .unwrap();
+        let reduced = Id::from(expr.as_ref().len() - 1);
+        let product = Id::from(
+            expr.as_ref()
+                .iter()
+                .position(|node| matches!(node, Language::SystolicArray(_)))
+                .unwrap(),
+        );
+        expr.add(Language::Outputs(vec![reduced, product].into_boxed_slice()));
+
+        assert_eq!(of(&expr, PRODUCT), Some(Materialization::Buffer));
+        assert_eq!(
+            of(&expr, &format!("(compute relu {})", PRODUCT)),
+            Some(Materialization::Buffer)
+        );
+        // The product and the relu are both live while the relu runs.
+        assert_eq!(
+            peak_bytes(&schedule(&expr, analysis())),
+            (64 + 128 + 32 + 32 + 4) * 4
+        );
+    }
+
+    #[test]
+    fn reorganizations() {
+        // The flattened transpose must be written out before the relu can
+        // read it, but the transpose itself is read in place to do so. The
+        // second, conflicting, padding of x must be written out too.
+        let expr: RecExpr<Language> = "
+         (outputs
+          (compute relu (access-flatten (access-transpose (access (access-tensor w) 1) (list 1 0))))
+          (compute reduce-max (access-pad (access (access-tensor x) 1) min-padding 1 0 1))
+          (compute relu (access-pad (access (access-tensor x) 1) zero-padding 1 1 0)))"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            of(
+                &expr,
+                "(access-flatten (access-transpose (access (access-tensor w) 1) (list 1 0)))"
+            ),
+            Some(Materialization::Buffer)
+        );
+        assert_eq!(
+            of(
+                &expr,
+                "(access-transpose (access (access-tensor w) 1) (list 1 0))"
+            ),
+            Some(Materialization::Fused)
+        );
+        assert_eq!(
+            of(
+                &expr,
+                "(access-pad (access (access-tensor x) 1) min-padding 1 0 1)"
+            ),
+            Some(Materialization::Fused)
+        );
+        assert_eq!(
+            of(
+                &expr,
+                "(access-pad (access (access-tensor x) 1) zero-padding 1 1 0)"
+            ),
+            Some(Materialization::Buffer)
+        );
+    }
+}
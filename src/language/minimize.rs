@@ -0,0 +1,179 @@
+//! Minimizing failing expressions.
+//!
+//! When the interpreter, the analysis, or a comparison against a reference
+//! fails on an imported model, the failure usually only involves a handful of
+//! its thousands of nodes. [`minimize`] shrinks such an expression, in the
+//! style of delta debugging, into a small one which still fails in the same
+//! way, which makes for a much better bug report.
+
+use super::dead_code::subexpr;
+use super::Language;
+use egg::{Id, Language as LanguageTrait, RecExpr};
+use std::collections::HashMap;
+
+/// Returns a copy of `expr` in which references to node `from` are replaced
+/// with references to node `to`, keeping only the nodes the (possibly new)
+/// root depends on.
+fn redirect(expr: &RecExpr<Language>, from: Id, to: Id) -> RecExpr<Language> {
+    fn helper(
+        nodes: &[Language],
+        id: Id,
+        (from, to): (Id, Id),
+        new_expr: &mut RecExpr<Language>,
+        old_to_new: &mut HashMap<Id, Id>,
+    ) -> Id {
+        let id = if id == from { to } else { id };
+        if let Some(new_id) = old_to_new.get(&id) {
+            return *new_id;
+        }
+        let node = nodes[usize::from(id)]
+            .clone()
+            .map_children(|child| helper(nodes, child, (from, to), new_expr, old_to_new));
+        let new_id = new_expr.add(node);
+        old_to_new.insert(id, new_id);
+        new_id
+    }
+
+    let nodes = expr.as_ref();
+    let mut new_expr = RecExpr::default();
+    helper(
+        nodes,
+        Id::from(nodes.len() - 1),
+        (from, to),
+        &mut new_expr,
+        &mut HashMap::default(),
+    );
+    new_expr
+}
+
+/// Shrinks `expr`, which `fails`, into a smaller expression which still
+/// `fails`. Smaller expressions are tried until none of them fail:
+///  - each of the expression's subexpressions (see [`subexpr`]), and then
+///  - the expression with a node replaced by one of its own children.
+///
+/// The result is thus minimal in the sense that no single one of these steps
+/// keeps it failing, although a smaller failing expression may exist.
+///
+/// Many of the expressions tried are malformed, so `fails` should check for
+/// the specific failure being minimized (e.g. a panic with a particular
+/// message, or a mismatch against a reference implementation), rather than
+/// for any failure at all. Panics if `expr` doesn't fail to begin with.
+///
+/// ```
+/// use egg::RecExpr;
+/// use glenside::error::GlensideError;
+/// use glenside::language::interpreter::{try_interpret, Environment};
+/// use glenside::language::minimize::minimize;
+/// use glenside::language::Language;
+/// use ndarray::array;
+///
+/// let expr: RecExpr<Language> = "
+///     (compute relu
+///      (access
+///       (compute reduce-max
+///        (access-windows (access (access-tensor a) 0) (shape 5) (shape 1)))
+///       0))"
+///     .parse()
+///     .unwrap();
+/// let mut env: Environment<f64> = Environment::new();
+/// env.insert("a", array![1., 2., 3.].into_dyn());
+///
+/// // The windows are larger than a.
+/// let minimized = minimize(&expr, |expr| {
+///     match try_interpret(expr, expr.as_ref().len() - 1, &env) {
+///         Err(GlensideError::Interpretation(message)) => {
+///             message.contains("filter is larger than input")
+///         }
+///         _ => false,
+///     }
+/// });
+/// assert!(minimized.pretty(80).starts_with("(access-windows"));
+/// ```
+pub fn minimize(
+    expr: &RecExpr<Language>,
+    mut fails: impl FnMut(&RecExpr<Language>) -> bool,
+) -> RecExpr<Language> {
+    assert!(!expr.as_ref().is_empty(), "Expected a non-empty expression");
+    assert!(fails(expr), "Expected the expression to fail");
+
+    let mut expr = subexpr(expr, expr.as_ref().len() - 1);
+    loop {
+        let len = expr.as_ref().len();
+        let subexprs = (0..len - 1).map(|index| subexpr(&expr, index));
+        let hoists = expr.as_ref().iter().enumerate().flat_map(|(index, node)| {
+            let mut children = node.children().to_vec();
+            children.dedup();
+            let expr = &expr;
+            children
+                .into_iter()
+                .map(move |child| redirect(expr, Id::from(index), child))
+        });
+        match subexprs
+            .chain(hoists)
+            .find(|candidate| candidate.as_ref().len() < len && fails(candidate))
+        {
+            Some(candidate) => expr = candidate,
+            None => return expr,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether some node of `expr` is the root of `target`.
+    fn contains(expr: &RecExpr<Language>, target: &str) -> bool {
+        (0..expr.as_ref().len()).any(|index| subexpr(expr, index).pretty(80) == target)
+    }
+
+    #[test]
+    fn minimize_to_subexpression() {
+        let expr: RecExpr<Language> = "
+         (compute dot-product
+          (access-cartesian-product
+           (access (access-tensor a) 1)
+           (access (access-transpose (access (access-tensor b) 0) (list 1 0)) 1)))"
+            .parse()
+            .unwrap();
+        let target = "(access-transpose (access (access-tensor b) 0) (list 1 0))";
+        assert_eq!(
+            minimize(&expr, |expr| contains(expr, target)).pretty(80),
+            target
+        );
+    }
+
+    #[test]
+    fn minimize_by_hoisting() {
+        // The relu of a transpose is needed, but the accesses around the
+        // transpose aren't.
+        let expr: RecExpr<Language> = "
+         (compute relu
+          (access
+           (access-transpose (access (access-tensor b) 0) (list 1 0))
+           1))"
+        .parse()
+        .unwrap();
+        let is_relu_of_transpose = |expr: &RecExpr<Language>| {
+            let transposed = match expr.as_ref().last() {
+                Some(Language::Compute([_, access])) => matches!(
+                    expr.as_ref()[usize::from(*access)],
+                    Language::AccessTranspose(_)
+                ),
+                _ => false,
+            };
+            transposed && contains(expr, "(access-tensor b)") && contains(expr, "(list 1 0)")
+        };
+        assert_eq!(
+            minimize(&expr, is_relu_of_transpose).pretty(80),
+            "(compute relu (access-transpose (access-tensor b) (list 1 0)))"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected the expression to fail")]
+    fn not_failing() {
+        let expr: RecExpr<Language> = "(access-tensor a)".parse().unwrap();
+        minimize(&expr, |_| false);
+    }
+}
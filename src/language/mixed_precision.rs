@@ -0,0 +1,333 @@
+//! Mixed-precision programs.
+//!
+//! Quantizing a whole network to int8 often costs too much accuracy, so only
+//! some of its layers are quantized, while the rest stay in float. Marking
+//! subtrees of a program as [`Precision::Int8`] or [`Precision::Float32`] and
+//! running [`insert_precision_boundaries`] makes the precision of each
+//! computation explicit: in int8 regions, tensors are cast to int8 and dot
+//! products accumulate in int32 (with `compute-with-accumulator`), and
+//! `cast`/`requantize` nodes convert values wherever they cross from one
+//! region to another. The result can be run in the interpreter, and mapped
+//! with [`rewrites::int8_systolic_array`](super::rewrites::int8_systolic_array)
+//! for the int8 regions and the usual float atoms elsewhere.
+
+use super::{ComputeType, DataType, Language, RoundingMode};
+use egg::{Id, Language as LanguageTrait, RecExpr};
+use ordered_float::NotNan;
+use std::collections::HashMap;
+
+/// The precision a subtree is computed in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Precision {
+    /// Values are int8; dot products accumulate in int32.
+    Int8,
+    Float32,
+}
+
+/// Returns a copy of `expr` in which each subtree rooted at a node in `marks`
+/// is computed in the marked precision, and everything else in float32.
+/// Marks on nodes within marked subtrees take precedence over the marks
+/// above them.
+///
+/// In int8 subtrees, the tensors accessed are cast to int8, so they should
+/// already hold values on the int8 grid (e.g. quantized weights stored as
+/// floats), and dot products become int32-accumulating dot products. Values
+/// are converted where they cross into a subtree of another precision:
+///  - int32 accumulators are requantized to int8 with an input scale of 1 and
+///    an output scale of `requantize_scale`, i.e. they're divided by
+///    `requantize_scale` and rounded,
+///  - float values are cast to int8, and
+///  - int8 and int32 values are cast to float32.
+///
+/// The root's value is cast to float32 if it's quantized. Other computations
+/// in int8 subtrees (e.g. activations) compute on the quantized values as
+/// they are.
+/// ```
+/// use egg::{Id, RecExpr};
+/// use glenside::language::mixed_precision::{insert_precision_boundaries, Precision};
+/// use glenside::language::Language;
+///
+/// let expr: RecExpr<Language> = "
+///  (compute dot-product
+///   (access-cartesian-product (access (access-tensor a) 1) (access (access-tensor b) 1)))"
+///     .parse()
+///     .unwrap();
+/// let root = Id::from(expr.as_ref().len() - 1);
+/// let marks = vec![(root, Precision::Int8)].into_iter().collect();
+/// assert_eq!(
+///     insert_precision_boundaries(&expr, &marks, 1.0).pretty(1000),
+///     "(cast float32 \
+///       (compute-with-accumulator dot-product int32 \
+///        (access-cartesian-product \
+///         (access (cast int8 (access-tensor a)) 1) \
+///         (access (cast int8 (access-tensor b)) 1))))"
+/// );
+/// ```
+pub fn insert_precision_boundaries(
+    expr: &RecExpr<Language>,
+    marks: &HashMap<Id, Precision>,
+    requantize_scale: f64,
+) -> RecExpr<Language> {
+    let nodes = expr.as_ref();
+    assert!(!nodes.is_empty(), "Expected a non-empty expression");
+    let requantize_scale =
+        NotNan::new(requantize_scale).expect("Expected the requantize scale to be a number");
+
+    struct Context<'a> {
+        nodes: &'a [Language],
+        marks: &'a HashMap<Id, Precision>,
+        requantize_scale: NotNan<f64>,
+        new_expr: RecExpr<Language>,
+        /// The translation of each node in each precision, along with the
+        /// type of its elements, if it's a tensor or access pattern.
+        translated: HashMap<(Id, Precision), (Id, Option<DataType>)>,
+    }
+
+    impl Context<'_> {
+        /// Converts the value in `id`, whose elements are of type `dtype`, for
+        /// use in a computation of precision `precision`.
+        fn convert(&mut self, id: Id, dtype: DataType, precision: Precision) -> (Id, DataType) {
+            match (precision, dtype) {
+                (Precision::Float32, DataType::Int(_)) => {
+                    (self.add_cast(DataType::Float(32), id), DataType::Float(32))
+                }
+                (Precision::Int8, DataType::Float(_)) => {
+                    (self.add_cast(DataType::Int(8), id), DataType::Int(8))
+                }
+                (Precision::Int8, DataType::Int(bits)) if bits > 8 => {
+                    let int8 = self.new_expr.add(Language::DataType(DataType::Int(8)));
+                    let one = self
+                        .new_expr
+                        .add(Language::NotNanFloat64(NotNan::new(1.0).unwrap()));
+                    let zero = self.new_expr.add(Language::Num(0));
+                    let scale = self
+                        .new_expr
+                        .add(Language::NotNanFloat64(self.requantize_scale));
+                    let rounding = self
+                        .new_expr
+                        .add(Language::RoundingMode(RoundingMode::ToNearest));
+                    (
+                        self.new_expr.add(Language::Requantize([
+                            int8, id, one, zero, scale, zero, rounding,
+                        ])),
+                        DataType::Int(8),
+                    )
+                }
+                _ => (id, dtype),
+            }
+        }
+
+        fn add_cast(&mut self, dtype: DataType, id: Id) -> Id {
+            let dtype = self.new_expr.add(Language::DataType(dtype));
+            self.new_expr.add(Language::Cast([dtype, id]))
+        }
+
+        fn translate(&mut self, id: Id, precision: Precision) -> (Id, Option<DataType>) {
+            let precision = self.marks.get(&id).copied().unwrap_or(precision);
+            if let Some(translated) = self.translated.get(&(id, precision)) {
+                return *translated;
+            }
+
+            let mut dtype = None;
+            let node = self.nodes[usize::from(id)].clone().map_children(|child| {
+                let (child, child_dtype) = self.translate(child, precision);
+                match child_dtype {
+                    Some(child_dtype) => {
+                        let (child, child_dtype) = self.convert(child, child_dtype, precision);
+                        dtype = dtype.or(Some(child_dtype));
+                        child
+                    }
+                    None => child,
+                }
+            });
+            let translated = match (precision, node) {
+                (_, node @ Language::AccessTensor(_)) => {
+                    let access_tensor = self.new_expr.add(node);
+                    match precision {
+                        Precision::Int8 => (
+                            self.add_cast(DataType::Int(8), access_tensor),
+                            Some(DataType::Int(8)),
+                        ),
+                        Precision::Float32 => (access_tensor, Some(DataType::Float(32))),
+                    }
+                }
+                (Precision::Int8, Language::Compute([compute_type, access]))
+                    if self.new_expr.as_ref()[usize::from(compute_type)]
+                        == Language::ComputeType(ComputeType::DotProduct) =>
+                {
+                    let int32 = self.new_expr.add(Language::DataType(DataType::Int(32)));
+                    (
+                        self.new_expr.add(Language::ComputeWithAccumulator([
+                            compute_type,
+                            int32,
+                            access,
+                        ])),
+                        Some(DataType::Int(32)),
+                    )
+                }
+                (_, node) => (self.new_expr.add(node), dtype),
+            };
+            self.translated.insert((id, precision), translated);
+            translated
+        }
+    }
+
+    let mut context = Context {
+        nodes,
+        marks,
+        requantize_scale,
+        new_expr: RecExpr::default(),
+        translated: HashMap::default(),
+    };
+    let root = Id::from(nodes.len() - 1);
+    match context.translate(root, Precision::Float32) {
+        (root, Some(dtype)) => {
+            context.convert(root, dtype, Precision::Float32);
+        }
+        (_, None) => (),
+    }
+    context.new_expr
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extraction::SimpleCostFunction;
+    use crate::hardware::HardwareSpec;
+    use crate::language::interpreter::{interpret, Environment, Value};
+    use crate::language::MyAnalysis;
+    use egg::{EGraph, Extractor, Runner};
+    use ndarray::{array, ArrayD};
+
+    /// A dense layer, a ReLU, and another dense layer. Returns the program
+    /// and the id of the ReLU.
+    fn program() -> (RecExpr<Language>, Id) {
+        let program: RecExpr<Language> = "
+         (compute dot-product
+          (access-cartesian-product
+           (access
+            (compute relu
+             (compute dot-product
+              (access-cartesian-product
+               (access (access-tensor x) 1)
+               (access (access-tensor w1) 1))))
+            1)
+           (access (access-tensor w2) 1)))"
+            .parse()
+            .unwrap();
+        let relu = program
+            .as_ref()
+            .iter()
+            .position(|node| match node {
+                Language::Compute([compute_type, _]) => {
+                    program.as_ref()[usize::from(*compute_type)]
+                        == Language::ComputeType(ComputeType::ReLU)
+                }
+                _ => false,
+            })
+            .unwrap();
+        (program, Id::from(relu))
+    }
+
+    fn env() -> Environment<'static, f64> {
+        let mut env = Environment::new();
+        env.insert("x", array![[1., -2., 3., 0.], [2., 2., -1., 4.]].into_dyn());
+        env.insert(
+            "w1",
+            array![[1., 1., 1., 1.], [-1., 2., 0., 3.], [4., -3., 2., 1.]].into_dyn(),
+        );
+        env.insert("w2", array![[0.5, -1.25, 2.], [1.5, 0.25, -0.5]].into_dyn());
+        env
+    }
+
+    fn run(expr: &RecExpr<Language>) -> ArrayD<f64> {
+        match interpret(expr, expr.as_ref().len() - 1, &env()) {
+            Value::Access(a) => a.tensor,
+            _ => panic!(),
+        }
+    }
+
+    fn marks(relu: Id) -> HashMap<Id, Precision> {
+        vec![(relu, Precision::Int8)].into_iter().collect()
+    }
+
+    #[test]
+    fn boundaries() {
+        let (program, relu) = program();
+        let quantized = insert_precision_boundaries(&program, &marks(relu), 1.0);
+        let printed = quantized.pretty(1000);
+        assert!(printed.contains("(compute-with-accumulator dot-product int32"));
+        assert!(printed.contains("(cast int8 (access-tensor x))"));
+        assert!(printed.contains("(cast int8 (access-tensor w1))"));
+        // The second dense layer stays in float.
+        assert!(printed.starts_with("(compute dot-product"));
+        assert!(!printed.contains("(cast int8 (access-tensor w2))"));
+        // The accumulators are requantized for the ReLU, whose output is cast
+        // back to float.
+        assert!(printed.contains("(cast float32 (compute relu (requantize int8"));
+
+        // The inputs are small integers, so nothing is lost.
+        assert_eq!(run(&quantized), run(&program));
+    }
+
+    #[test]
+    fn requantize_scale() {
+        let expr: RecExpr<Language> = "
+         (compute relu
+          (compute dot-product
+           (access-cartesian-product
+            (access (access-tensor x) 1)
+            (access (access-tensor x) 1))))"
+            .parse()
+            .unwrap();
+        let root = Id::from(expr.as_ref().len() - 1);
+        let quantized = insert_precision_boundaries(&expr, &marks(root), 5.0);
+        // x times its transpose is [[14, -5], [-5, 25]]; divided by 5 and
+        // rounded, [[3, -1], [-1, 5]].
+        assert_eq!(run(&quantized), array![[3., 0.], [0., 5.]].into_dyn());
+    }
+
+    #[test]
+    fn map_mixed_precision() {
+        let (program, relu) = program();
+        let quantized = insert_precision_boundaries(&program, &marks(relu), 1.0);
+
+        let spec = HardwareSpec::from_json(
+            r#"{
+              "atoms": [
+                { "atom": "systolic-array", "rows": 16, "cols": 16 },
+                { "atom": "int8-systolic-array" },
+                { "atom": "activation-unit" }
+              ],
+              "legality": { "systolic-array": { "dtypes": ["float32"] } },
+              "sram_bytes": 262144,
+              "dram_bandwidth": 1e9,
+              "frequency": 5e8
+            }"#,
+        )
+        .unwrap();
+        let mut name_to_shape = HashMap::default();
+        name_to_shape.insert("x".to_string(), vec![2, 4]);
+        name_to_shape.insert("w1".to_string(), vec![3, 4]);
+        name_to_shape.insert("w2".to_string(), vec![2, 3]);
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&quantized);
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&spec.rewrites());
+        let (cost, extracted) = Extractor::new(&runner.egraph, SimpleCostFunction::default())
+            .find_best(runner.egraph.find(id));
+
+        // Everything was mapped: the first dense layer onto the int8 array,
+        // and the second onto the float one.
+        assert!(cost < usize::MAX);
+        let printed = extracted.pretty(1000);
+        assert!(printed.contains("(systolic-array 4 3 (access (cast int8 (access-tensor x)) 1)"));
+        assert!(printed.starts_with("(systolic-array 3 2"));
+        assert_eq!(run(&extracted), run(&quantized));
+    }
+}
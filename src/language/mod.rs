@@ -8,3 +8,67 @@ pub mod interpreter;
 pub mod rewrites;
 
 pub mod from_relay;
+
+pub mod multi_output;
+
+pub mod dead_code;
+
+pub mod provenance;
+
+pub mod op_count;
+
+pub mod constant_weights;
+
+pub mod out_of_core;
+
+pub mod visualize;
+
+pub mod stats;
+
+pub mod workload;
+
+pub mod lowering;
+
+pub mod shared_weights;
+
+pub mod sparse;
+
+pub mod spans;
+
+pub mod repl;
+
+pub mod npy_shapes;
+
+pub mod layout;
+
+pub mod weight_preprocessing;
+
+pub mod timing;
+
+pub mod schedule;
+
+pub mod bit_accurate;
+
+pub mod mixed_precision;
+
+pub mod minimize;
+
+pub mod einsum;
+
+pub mod materialization;
+
+pub mod access_shape;
+
+pub mod packed;
+
+pub mod shape_cache;
+
+pub mod hash_cons;
+
+pub mod unroll;
+
+pub mod canonical;
+
+pub mod json_graph;
+
+pub mod windows;
@@ -0,0 +1,246 @@
+//! Support for programs with multiple named outputs.
+//!
+//! Models with auxiliary outputs (e.g. detection models, with separate box and
+//! class heads) are represented as a single expression rooted at an `outputs`
+//! node, so that search, extraction, interpretation and codegen all operate
+//! over every output at once, and subexpressions shared between outputs are
+//! only represented once. [`MultiOutputExpr`] pairs such an expression with the
+//! names of its outputs.
+//!
+//! - Search: add [`MultiOutputExpr::expr`] to an egraph as usual, and use
+//!   [`MultiOutputExpr::output_eclasses`] to find each output's eclass.
+//! - Extraction: extract from the eclass of the root, then rebuild a
+//!   [`MultiOutputExpr`] with [`MultiOutputExpr::from_parts`].
+//! - Interpretation: interpreting the root returns a
+//!   [`Value::Outputs`](crate::language::interpreter::Value::Outputs), with one
+//!   value per output, in order.
+//! - Codegen: [`crate::codegen::codegen`] generates one output argument per
+//!   output, named `out_0`, `out_1`, etc., in order.
+
+use super::Language;
+use super::MyAnalysis;
+use egg::{EGraph, Id, Language as LanguageTrait, RecExpr};
+use std::collections::HashMap;
+
+pub struct MultiOutputExpr {
+    /// The expression, rooted at an `outputs` node.
+    pub expr: RecExpr<Language>,
+    /// The names of the outputs, in the same order as the children of the
+    /// root `outputs` node.
+    pub names: Vec<String>,
+}
+
+impl MultiOutputExpr {
+    /// Combines several named expressions into a single expression rooted at
+    /// an `outputs` node. Identical subexpressions are shared.
+    ///
+    /// ```
+    /// use egg::RecExpr;
+    /// use glenside::language::multi_output::MultiOutputExpr;
+    /// use glenside::language::Language;
+    ///
+    /// let boxes: RecExpr<Language> = "(access (access-tensor t-32-32) 0)".parse().unwrap();
+    /// let scores: RecExpr<Language> = "(access (access-tensor t-32-32) 1)".parse().unwrap();
+    /// let multi = MultiOutputExpr::new(&[("boxes", &boxes), ("scores", &scores)]);
+    /// // (access-tensor t-32-32) is shared between the two outputs.
+    /// assert_eq!(multi.expr.as_ref().len(), 7);
+    /// assert_eq!(multi.output_id("scores"), Some(multi.output_ids()[1]));
+    /// ```
+    pub fn new(outputs: &[(&str, &RecExpr<Language>)]) -> Self {
+        assert!(!outputs.is_empty(), "Expected at least one output");
+
+        let mut expr = RecExpr::default();
+        let mut memo: HashMap<Language, Id> = HashMap::default();
+        let mut roots = Vec::default();
+        let mut names = Vec::default();
+        for (name, output) in outputs.iter() {
+            assert!(
+                !names.iter().any(|n| n == name),
+                "Output {} defined more than once",
+                name
+            );
+            // Maps ids in `output` to ids in `expr`.
+            let mut ids: Vec<Id> = Vec::with_capacity(output.as_ref().len());
+            for node in output.as_ref() {
+                let node = node.clone().map_children(|id| ids[usize::from(id)]);
+                let id = match memo.get(&node) {
+                    Some(id) => *id,
+                    None => {
+                        let id = expr.add(node.clone());
+                        memo.insert(node, id);
+                        id
+                    }
+                };
+                ids.push(id);
+            }
+            roots.push(*ids.last().unwrap());
+            names.push(name.to_string());
+        }
+        expr.add(Language::Outputs(roots.into_boxed_slice()));
+
+        MultiOutputExpr { expr, names }
+    }
+
+    /// Pairs an expression rooted at an `outputs` node (e.g. one extracted
+    /// from an egraph) with the names of its outputs.
+    pub fn from_parts(expr: RecExpr<Language>, names: Vec<String>) -> Self {
+        let num_outputs = match expr.as_ref().last() {
+            Some(Language::Outputs(ids)) => ids.len(),
+            _ => panic!("Expected expression to be rooted at an outputs node"),
+        };
+        assert_eq!(
+            num_outputs,
+            names.len(),
+            "Expected one name for each output"
+        );
+        MultiOutputExpr { expr, names }
+    }
+
+    /// The id of the root `outputs` node.
+    pub fn root(&self) -> Id {
+        (self.expr.as_ref().len() - 1).into()
+    }
+
+    /// The ids of each output within [`MultiOutputExpr::expr`], in order.
+    pub fn output_ids(&self) -> &[Id] {
+        match &self.expr.as_ref()[usize::from(self.root())] {
+            Language::Outputs(ids) => ids,
+            _ => unreachable!(),
+        }
+    }
+
+    /// The id of the output called `name`, if there is one.
+    pub fn output_id(&self, name: &str) -> Option<Id> {
+        self.names
+            .iter()
+            .position(|n| n == name)
+            .map(|i| self.output_ids()[i])
+    }
+
+    /// Given the eclass `root` containing this expression's `outputs` node,
+    /// finds the eclass of each named output.
+    pub fn output_eclasses(
+        &self,
+        egraph: &EGraph<Language, MyAnalysis>,
+        root: Id,
+    ) -> HashMap<String, Id> {
+        let ids = egraph[root]
+            .nodes
+            .iter()
+            .find_map(|node| match node {
+                Language::Outputs(ids) if ids.len() == self.names.len() => Some(ids),
+                _ => None,
+            })
+            .expect("Expected an outputs node in the root eclass");
+        self.names
+            .iter()
+            .cloned()
+            .zip(ids.iter().map(|id| egraph.find(*id)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::interpreter::{interpret, Value};
+    use crate::language::MyAnalysisData;
+    use egg::{Pattern, Runner, Searcher};
+    use ndarray::array;
+
+    #[test]
+    fn shared_subexpressions() {
+        let a: RecExpr<Language> = "(compute relu (access (access-tensor t-32-32) 1))"
+            .parse()
+            .unwrap();
+        let b: RecExpr<Language> =
+            "(compute reduce-sum (access (compute relu (access (access-tensor t-32-32) 1)) 1))"
+                .parse()
+                .unwrap();
+        let multi = MultiOutputExpr::new(&[("a", &a), ("b", &b)]);
+
+        // The relu (and everything under it) is only represented once: a's six
+        // nodes, plus reduce-sum, the outer access and compute, and the root.
+        assert_eq!(multi.expr.as_ref().len(), 10);
+        assert_eq!(multi.names, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(multi.output_id("a"), Some(Id::from(a.as_ref().len() - 1)));
+        assert_eq!(multi.output_id("c"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Output a defined more than once")]
+    fn duplicate_names_panic() {
+        let a: RecExpr<Language> = "(access-tensor t-32-32)".parse().unwrap();
+        MultiOutputExpr::new(&[("a", &a), ("a", &a)]);
+    }
+
+    #[test]
+    fn search_and_extract() {
+        let a: RecExpr<Language> = "
+         (compute dot-product
+          (access-cartesian-product
+           (access (access-tensor t-32-32) 1)
+           (access (access-tensor t-32-32) 1)))"
+            .parse()
+            .unwrap();
+        let b: RecExpr<Language> = "(compute relu (access (access-tensor t-32-32) 1))"
+            .parse()
+            .unwrap();
+        let multi = MultiOutputExpr::new(&[("matmul", &a), ("relu", &b)]);
+
+        let mut egraph = EGraph::new(MyAnalysis::default());
+        let root = egraph.add_expr(&multi.expr);
+        match &egraph[root].data {
+            MyAnalysisData::Tuple(outputs) => assert_eq!(outputs.len(), 2),
+            _ => panic!(),
+        }
+
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&vec![crate::language::rewrites::systolic_array().rewrite]);
+        let outputs = multi.output_eclasses(&runner.egraph, root);
+        assert!("(systolic-array 32 32 ?a ?b)"
+            .parse::<Pattern<_>>()
+            .unwrap()
+            .search_eclass(&runner.egraph, outputs["matmul"])
+            .is_some());
+
+        let (cost, expr) = egg::Extractor::new(
+            &runner.egraph,
+            crate::extraction::MonolithicCostFunction {
+                egraph: &runner.egraph,
+                systolic_array_configuration: (32, 32),
+                prefer_systolic_arrays_with_blocking: false,
+            },
+        )
+        .find_best(root);
+        assert!(cost < crate::extraction::MonolithicCostFunction::INFINITY_VALUE);
+        let extracted = MultiOutputExpr::from_parts(expr, multi.names.clone());
+        assert_eq!(extracted.output_ids().len(), 2);
+    }
+
+    #[test]
+    fn interpret_outputs() {
+        let a: RecExpr<Language> = "(access (access-tensor t) 0)".parse().unwrap();
+        let b: RecExpr<Language> = "(compute relu (access (access-tensor t) 0))"
+            .parse()
+            .unwrap();
+        let multi = MultiOutputExpr::new(&[("a", &a), ("b", &b)]);
+
+        let mut env = HashMap::default();
+        env.insert("t", array![-1., 2.].into_dyn());
+        match interpret(&multi.expr, usize::from(multi.root()), &env) {
+            Value::Outputs(values) => {
+                assert_eq!(values.len(), 2);
+                match (&values[0], &values[1]) {
+                    (Value::Access(a), Value::Access(b)) => {
+                        assert_eq!(a.tensor, array![-1., 2.].into_dyn());
+                        assert_eq!(b.tensor, array![0., 2.].into_dyn());
+                    }
+                    _ => panic!(),
+                }
+            }
+            _ => panic!(),
+        }
+    }
+}
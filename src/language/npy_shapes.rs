@@ -0,0 +1,254 @@
+//! Shapes of environment tensors, from `.npy` headers.
+//!
+//! Shape analysis needs the shape of each tensor a program reads, which
+//! otherwise has to be written out by hand alongside the `.npy` files holding
+//! the tensors, and can drift from them. [`read_npy_headers`] reads just the
+//! header of each `.npy` file in a directory (not the data, so it's fast even
+//! for large weights), and [`analysis_from_npy_directory`] builds a
+//! [`MyAnalysis`] from them, naming each tensor by its file's stem, e.g.
+//! `conv2d_filters` for `conv2d_filters.npy`.
+
+use super::{DataType, MyAnalysis};
+use crate::error::{GlensideError, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use std::path::Path;
+
+/// What the header of a `.npy` file says about its tensor.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NpyHeader {
+    pub shape: Vec<usize>,
+    pub dtype: DataType,
+    pub fortran_order: bool,
+}
+
+/// The value of `key` in the header's Python dict literal, up to the next
+/// `terminator`.
+fn header_value<'a>(header: &'a str, key: &str, terminator: char) -> Option<&'a str> {
+    let start = header.find(&format!("'{}':", key))? + key.len() + 3;
+    let value = header[start..].trim_start();
+    let end = value[1..].find(terminator)? + 1;
+    Some(&value[..=end])
+}
+
+/// The [`DataType`] of a NumPy type descriptor, e.g. `<f4`.
+fn dtype(descr: &str) -> Option<DataType> {
+    let descr = descr.trim_start_matches(|c| c == '<' || c == '>' || c == '|' || c == '=');
+    let bits = descr.get(1..)?.parse::<usize>().ok()? * 8;
+    match &descr[..1] {
+        "b" if bits == 8 => Some(DataType::Bool),
+        "f" => Some(DataType::Float(bits)),
+        "i" => Some(DataType::Int(bits)),
+        "u" => Some(DataType::Uint(bits)),
+        _ => None,
+    }
+}
+
+/// Parses the header of `.npy` file `path`, without reading its data.
+pub fn read_npy_header(path: impl AsRef<Path>) -> Result<NpyHeader> {
+    let path = path.as_ref();
+    let error = |message: &str| GlensideError::Parse(format!("{}: {}", path.display(), message));
+    let mut file = std::fs::File::open(path).map_err(|e| error(&e.to_string()))?;
+
+    let mut preamble = [0u8; 8];
+    file.read_exact(&mut preamble)
+        .map_err(|_| error("not a .npy file"))?;
+    if &preamble[..6] != b"\x93NUMPY" {
+        return Err(error("not a .npy file"));
+    }
+    // Version 1 headers have a 2-byte length; later versions, a 4-byte one.
+    let header_len = if preamble[6] == 1 {
+        let mut len = [0u8; 2];
+        file.read_exact(&mut len)
+            .map_err(|_| error("truncated header"))?;
+        u16::from_le_bytes(len) as usize
+    } else {
+        let mut len = [0u8; 4];
+        file.read_exact(&mut len)
+            .map_err(|_| error("truncated header"))?;
+        u32::from_le_bytes(len) as usize
+    };
+    let mut header = vec![0u8; header_len];
+    file.read_exact(&mut header)
+        .map_err(|_| error("truncated header"))?;
+    let header = String::from_utf8(header).map_err(|_| error("header isn't text"))?;
+
+    let shape = header_value(&header, "shape", ')')
+        .ok_or_else(|| error("header has no shape"))?
+        .trim_matches(|c| c == '(' || c == ')')
+        .split(',')
+        .map(str::trim)
+        .filter(|dim| !dim.is_empty())
+        .map(|dim| {
+            dim.parse::<usize>()
+                .map_err(|_| error(&format!("bad dimension {}", dim)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let descr = header_value(&header, "descr", '\'').ok_or_else(|| error("header has no descr"))?;
+    let dtype = dtype(descr.trim_matches('\''))
+        .ok_or_else(|| error(&format!("unsupported dtype {}", descr)))?;
+    let fortran_order = header_value(&header, "fortran_order", ',')
+        .ok_or_else(|| error("header has no fortran_order"))?
+        .trim_end_matches(',')
+        .trim()
+        == "True";
+
+    Ok(NpyHeader {
+        shape,
+        dtype,
+        fortran_order,
+    })
+}
+
+/// Reads the header of each `.npy` file directly in `directory`, by file
+/// stem.
+pub fn read_npy_headers(directory: impl AsRef<Path>) -> Result<BTreeMap<String, NpyHeader>> {
+    let directory = directory.as_ref();
+    let entries = std::fs::read_dir(directory)
+        .map_err(|e| GlensideError::Parse(format!("{}: {}", directory.display(), e)))?;
+    let mut headers = BTreeMap::default();
+    for entry in entries {
+        let path = entry
+            .map_err(|e| GlensideError::Parse(format!("{}: {}", directory.display(), e)))?
+            .path();
+        if path
+            .extension()
+            .map_or(false, |extension| extension == "npy")
+        {
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            headers.insert(name, read_npy_header(&path)?);
+        }
+    }
+    Ok(headers)
+}
+
+/// Checks that every tensor in `given`, a hand-written shape map, has the
+/// shape its header says it has. Tensors without a header aren't checked.
+/// Reports every disagreement at once.
+pub fn check_shapes(
+    headers: &BTreeMap<String, NpyHeader>,
+    given: &HashMap<String, Vec<usize>>,
+) -> Result<()> {
+    let mut names = given.keys().collect::<Vec<_>>();
+    names.sort();
+    let mismatches = names
+        .into_iter()
+        .filter_map(|name| {
+            headers
+                .get(name)
+                .filter(|header| header.shape != given[name])
+                .map(|header| {
+                    format!(
+                        "{} has shape {:?}, but its .npy file has shape {:?}",
+                        name, given[name], header.shape
+                    )
+                })
+        })
+        .collect::<Vec<_>>();
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(GlensideError::Analysis(mismatches.join("; ")))
+    }
+}
+
+/// Builds an analysis whose shapes and dtypes are those of the `.npy` files
+/// in `directory`, plus the shapes in `given` (e.g. of tensors which aren't
+/// stored in files), after checking `given` against the files with
+/// [`check_shapes`].
+/// ```
+/// use glenside::language::npy_shapes::analysis_from_npy_directory;
+/// use std::collections::HashMap;
+///
+/// let analysis =
+///     analysis_from_npy_directory(concat!(env!("CARGO_MANIFEST_DIR"), "/data"), &HashMap::default())
+///         .unwrap();
+/// assert_eq!(analysis.name_to_shape["conv2d_filters"], vec![8, 4, 3, 3]);
+/// ```
+pub fn analysis_from_npy_directory(
+    directory: impl AsRef<Path>,
+    given: &HashMap<String, Vec<usize>>,
+) -> Result<MyAnalysis> {
+    let headers = read_npy_headers(directory)?;
+    check_shapes(&headers, given)?;
+    let mut name_to_shape = given.clone();
+    let mut name_to_dtype = HashMap::default();
+    for (name, header) in headers {
+        name_to_shape.insert(name.clone(), header.shape);
+        name_to_dtype.insert(name, header.dtype);
+    }
+    Ok(MyAnalysis {
+        name_to_shape,
+        name_to_dtype,
+        shape_cache: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{Array, ArrayD};
+    use ndarray_npy::write_npy;
+
+    fn data() -> String {
+        format!("{}/data", env!("CARGO_MANIFEST_DIR"))
+    }
+
+    #[test]
+    fn headers_match_data() {
+        let headers = read_npy_headers(data()).unwrap();
+        assert_eq!(
+            headers["conv2d_activations"],
+            NpyHeader {
+                shape: vec![4, 32, 32],
+                dtype: DataType::Float(32),
+                fortran_order: false,
+            }
+        );
+        assert_eq!(headers["w1"].shape, vec![784, 512]);
+        assert_eq!(headers["w1"].dtype, DataType::Float(64));
+
+        // Headers agree with the data.
+        let filters: ArrayD<f32> =
+            ndarray_npy::read_npy(format!("{}/conv2d_filters.npy", data())).unwrap();
+        assert_eq!(headers["conv2d_filters"].shape, filters.shape());
+    }
+
+    #[test]
+    fn scalar_and_vector() {
+        let directory = std::env::temp_dir().join(format!(
+            "glenside-npy-shapes-{}",
+            rand::Rng::gen::<u64>(&mut rand::rngs::OsRng)
+        ));
+        std::fs::create_dir(&directory).unwrap();
+        write_npy(directory.join("v.npy"), &Array::<i64, _>::zeros(5)).unwrap();
+        write_npy(directory.join("s.npy"), &ArrayD::<u8>::zeros(vec![])).unwrap();
+        let headers = read_npy_headers(&directory);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let headers = headers.unwrap();
+        assert_eq!(headers["v"].shape, vec![5]);
+        assert_eq!(headers["v"].dtype, DataType::Int(64));
+        assert_eq!(headers["s"].shape, Vec::<usize>::new());
+        assert_eq!(headers["s"].dtype, DataType::Uint(8));
+    }
+
+    #[test]
+    fn drifted_shapes() {
+        let mut given = HashMap::default();
+        given.insert("w1".to_string(), vec![784, 512]);
+        given.insert("w2".to_string(), vec![512, 256]);
+        given.insert("not-a-file".to_string(), vec![1]);
+        assert_eq!(
+            analysis_from_npy_directory(data(), &given).err(),
+            Some(GlensideError::Analysis(
+                "w2 has shape [512, 256], but its .npy file has shape [512, 512]".to_string()
+            ))
+        );
+
+        given.insert("w2".to_string(), vec![512, 512]);
+        let analysis = analysis_from_npy_directory(data(), &given).unwrap();
+        assert_eq!(analysis.name_to_shape["not-a-file"], vec![1]);
+        assert_eq!(analysis.name_to_dtype["in"], DataType::Float(64));
+    }
+}
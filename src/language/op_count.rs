@@ -0,0 +1,446 @@
+//! Counting the arithmetic an expression performs.
+//!
+//! [`op_count`] counts the multiply-accumulates (MACs) and other elementwise
+//! operations performed by a single node, using the shape analysis of its
+//! children; [`count_ops`] does so for every node of an expression and totals
+//! them. The counts are useful for sanity-checking imported models (a
+//! ResNet-50 should come to about 4 billion MACs) and as a quick input to cost
+//! models.
+//!
+//! The counts are of the arithmetic the node describes, not of any particular
+//! implementation of it: a convolution performs the same number of MACs
+//! whether it's a high-level `conv2d`, an `access-windows` formulation, or a
+//! call to a systolic array. Access pattern manipulation is free. Opaque
+//! Relay operator calls and accelerator calls aren't counted.
+
+use super::{ComputeType, Language, MyAnalysis, MyAnalysisData};
+use egg::{Analysis, EGraph, Id, Language as LanguageTrait, RecExpr};
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// The arithmetic performed by a node or expression.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize)]
+pub struct OpCount {
+    /// Multiply-accumulates, i.e. the multiplications in dot products,
+    /// matrix multiplications, and convolutions.
+    pub macs: u64,
+    /// All other arithmetic, e.g. additions, reductions, and activation
+    /// functions, counted once per element they're applied to.
+    pub elementwise_ops: u64,
+}
+
+impl std::ops::Add for OpCount {
+    type Output = OpCount;
+    fn add(self, other: OpCount) -> OpCount {
+        OpCount {
+            macs: self.macs + other.macs,
+            elementwise_ops: self.elementwise_ops + other.elementwise_ops,
+        }
+    }
+}
+
+impl std::ops::AddAssign for OpCount {
+    fn add_assign(&mut self, other: OpCount) {
+        *self = *self + other;
+    }
+}
+
+impl std::iter::Sum for OpCount {
+    fn sum<I: Iterator<Item = OpCount>>(iter: I) -> OpCount {
+        iter.fold(OpCount::default(), |acc, count| acc + count)
+    }
+}
+
+/// The result of [`count_ops`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpCounts {
+    /// The arithmetic performed by each node, indexed as the nodes of the
+    /// expression are.
+    pub per_node: Vec<OpCount>,
+    /// The arithmetic performed by the nodes reachable from the root, each
+    /// counted once, even if it's used more than once.
+    pub total: OpCount,
+}
+
+fn product(dims: &[usize]) -> u64 {
+    dims.iter().map(|d| *d as u64).product()
+}
+
+fn access_dims(egraph: &EGraph<Language, MyAnalysis>, id: Id) -> (Vec<usize>, Vec<usize>) {
+    match &egraph[id].data {
+        MyAnalysisData::AccessPattern(a) => {
+            (a.shape.slice().to_vec(), a.item_shape.slice().to_vec())
+        }
+        other => panic!("Expected an access pattern, found {:?}", other),
+    }
+}
+
+fn num_elements(egraph: &EGraph<Language, MyAnalysis>, id: Id) -> u64 {
+    let (shape, item_shape) = access_dims(egraph, id);
+    product(&shape) * product(&item_shape)
+}
+
+/// The number of elements in the result of `enode`.
+fn num_output_elements(egraph: &EGraph<Language, MyAnalysis>, enode: &Language) -> u64 {
+    match MyAnalysis::make(egraph, enode) {
+        MyAnalysisData::AccessPattern(a) => product(&a.as_vec()),
+        other => panic!("Expected an access pattern, found {:?}", other),
+    }
+}
+
+fn compute_op_count(
+    egraph: &EGraph<Language, MyAnalysis>,
+    compute_type_id: Id,
+    access_id: Id,
+) -> OpCount {
+    let compute_type = match &egraph[compute_type_id].data {
+        MyAnalysisData::ComputeType(t) => t.clone(),
+        other => panic!("Expected a compute type, found {:?}", other),
+    };
+    let (shape, item_shape) = access_dims(egraph, access_id);
+    let num_items = product(&shape);
+    let item_len = product(&item_shape);
+    // For computes over tuples of tensors, the tuple multiplicity and the
+    // number of elements in each tensor.
+    let (tuple_len, tensor_len) = match item_shape.split_first() {
+        Some((n, rest)) => (*n as u64, product(rest)),
+        None => (1, 1),
+    };
+    let elementwise = |ops| OpCount {
+        macs: 0,
+        elementwise_ops: ops,
+    };
+
+    match compute_type {
+        // A dot product of a single tensor is just a sum.
+        ComputeType::DotProduct | ComputeType::SparseDotProduct if tuple_len < 2 => {
+            elementwise(num_items * item_len)
+        }
+        ComputeType::DotProduct | ComputeType::SparseDotProduct => OpCount {
+            macs: num_items * (tuple_len - 1) * tensor_len,
+            elementwise_ops: 0,
+        },
+        ComputeType::ElementwiseAdd
+        | ComputeType::ElementwiseMul
+        | ComputeType::ElementwiseDiv
+        | ComputeType::LogicalAnd
+        | ComputeType::LogicalOr => {
+            elementwise(num_items * tuple_len.saturating_sub(1) * tensor_len)
+        }
+        ComputeType::ReduceSum
+        | ComputeType::ReduceMax
+        | ComputeType::ReduceMean
+        | ComputeType::ReLU
+        | ComputeType::Sqrt
+        | ComputeType::Negative
+        | ComputeType::Sigmoid
+        | ComputeType::Tanh
+        | ComputeType::LogicalNot => elementwise(num_items * item_len),
+        // Exponentiation, summation, and division.
+        ComputeType::Softmax => elementwise(3 * num_items * item_len),
+        // Updating both the mean and the squared deviations.
+        ComputeType::ReduceMeanVar => elementwise(2 * num_items * item_len),
+    }
+}
+
+/// Counts the arithmetic performed by `enode`, whose children must be in
+/// `egraph`.
+///
+/// ```
+/// use egg::{EGraph, RecExpr};
+/// use glenside::language::op_count::op_count;
+/// use glenside::language::{Language, MyAnalysis};
+///
+/// // A 32x32 by 32x64 matrix multiplication.
+/// let expr: RecExpr<Language> = "
+///     (compute dot-product
+///      (access-cartesian-product
+///       (access (access-tensor t-32-32) 1)
+///       (access (access-transpose (access (access-tensor t-32-64) 1) (list 1 0)) 1)))"
+///     .parse()
+///     .unwrap();
+/// let mut egraph = EGraph::new(MyAnalysis::default());
+/// let id = egraph.add_expr(&expr);
+/// assert_eq!(op_count(&egraph, &egraph[id].nodes[0]).macs, 32 * 64 * 32);
+/// ```
+pub fn op_count(egraph: &EGraph<Language, MyAnalysis>, enode: &Language) -> OpCount {
+    match enode {
+        &Language::Compute([compute_type_id, access_id])
+        | &Language::ComputeWithAccumulator([compute_type_id, _, access_id]) => {
+            compute_op_count(egraph, compute_type_id, access_id)
+        }
+        // <access-0> has shape [M] [N] and <access-1> has shape [] [N, O].
+        // A fused activation adds one operation per output element.
+        &Language::SystolicArrayWithActivation([_, rows_id, cols_id, a0_id, a1_id]) => {
+            op_count(
+                egraph,
+                &Language::SystolicArray([rows_id, cols_id, a0_id, a1_id]),
+            ) + OpCount {
+                macs: 0,
+                elementwise_ops: num_output_elements(egraph, enode),
+            }
+        }
+        &Language::ActivationUnit(_) | &Language::VectorAlu(_) => OpCount {
+            macs: 0,
+            elementwise_ops: num_output_elements(egraph, enode),
+        },
+        &Language::SystolicArray([_, _, a0_id, a1_id])
+        | &Language::SystolicArrayWithBlocking([_, _, a0_id, a1_id]) => {
+            let (a0_shape, _) = access_dims(egraph, a0_id);
+            let (_, a1_item_shape) = access_dims(egraph, a1_id);
+            OpCount {
+                macs: product(&a0_shape) * product(&a1_item_shape),
+                elementwise_ops: 0,
+            }
+        }
+        // <access-0> has shape [M] [N] and <access-1> has shape [O] [N]. This
+        // counts the MACs of the dense multiplication; the array only performs
+        // those of the weights' stored blocks.
+        &Language::SparseSystolicArray([_, _, a0_id, a1_id]) => {
+            let (a0_shape, a0_item_shape) = access_dims(egraph, a0_id);
+            let (a1_shape, _) = access_dims(egraph, a1_id);
+            OpCount {
+                macs: product(&a0_shape) * product(&a0_item_shape) * product(&a1_shape),
+                elementwise_ops: 0,
+            }
+        }
+        // Each output element is a dot product over one output channel's
+        // weights. With weights in layout OIHW (or HWIO), that's all of the
+        // weights divided by the number of output channels.
+        &Language::SystolicArrayConv2dNchwOihwWithBlocking([_, _, weights_id, ..])
+        | &Language::SystolicArrayConv2dIm2colNchwOihwWithBlocking([_, _, weights_id, ..])
+        | &Language::Conv1d([_, weights_id, ..])
+        | &Language::Conv2d([_, weights_id, ..])
+        | &Language::Conv3d([_, weights_id, ..]) => {
+            let (shape, item_shape) = access_dims(egraph, weights_id);
+            let weights = shape
+                .iter()
+                .chain(item_shape.iter())
+                .cloned()
+                .collect::<Vec<_>>();
+            OpCount {
+                macs: num_output_elements(egraph, enode) * product(&weights[1..]),
+                elementwise_ops: 0,
+            }
+        }
+        &Language::SystolicArrayConv2dNhwcHwioWithBlocking([_, _, weights_id, ..])
+        | &Language::SystolicArrayConv2dIm2colNhwcHwioWithBlocking([_, _, weights_id, ..]) => {
+            let (shape, item_shape) = access_dims(egraph, weights_id);
+            let weights = shape
+                .iter()
+                .chain(item_shape.iter())
+                .cloned()
+                .collect::<Vec<_>>();
+            OpCount {
+                macs: num_output_elements(egraph, enode) * product(&weights[..weights.len() - 1]),
+                elementwise_ops: 0,
+            }
+        }
+        // Each input element scatters a copy of its input channel's weights,
+        // which are in layout IOHW.
+        &Language::Conv2dTranspose([data_id, weights_id, ..]) => {
+            let (shape, item_shape) = access_dims(egraph, weights_id);
+            let weights = shape
+                .iter()
+                .chain(item_shape.iter())
+                .cloned()
+                .collect::<Vec<_>>();
+            OpCount {
+                macs: num_elements(egraph, data_id) * product(&weights[1..]),
+                elementwise_ops: 0,
+            }
+        }
+        // One operation per element of each window.
+        &Language::PoolingUnit([_, _, window_shape_id, _]) => OpCount {
+            macs: 0,
+            elementwise_ops: num_output_elements(egraph, enode)
+                * product(MyAnalysis::get_shape_of_value(window_shape_id, egraph).slice()),
+        },
+        // <a> has shape [B, M, K] and <b> has shape [B, K, N].
+        &Language::BatchMatmul([a_id, b_id]) => {
+            let (shape, item_shape) = access_dims(egraph, b_id);
+            let b = shape
+                .iter()
+                .chain(item_shape.iter())
+                .cloned()
+                .collect::<Vec<_>>();
+            OpCount {
+                macs: num_elements(egraph, a_id) * b[2] as u64,
+                elementwise_ops: 0,
+            }
+        }
+        // The mean, centering, squaring, variance, division, scaling, and
+        // shifting each take one operation per element.
+        &Language::LayerNorm([data_id, ..]) | &Language::GroupNorm([data_id, ..]) => OpCount {
+            macs: 0,
+            elementwise_ops: 7 * num_elements(egraph, data_id),
+        },
+        // Every input element is reduced into (at least) one window.
+        &Language::BiasAdd([data_id, _, _])
+        | &Language::AdaptivePool2d([_, data_id, _])
+        | &Language::Cast([_, data_id])
+        | &Language::Requantize([_, data_id, ..]) => OpCount {
+            macs: 0,
+            elementwise_ops: num_elements(egraph, data_id),
+        },
+        _ => OpCount::default(),
+    }
+}
+
+/// Counts the arithmetic performed by each node of `expr`, and in total.
+/// `analysis` provides the shapes of the tensors `expr` refers to.
+///
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::op_count::count_ops;
+/// use glenside::language::{Language, MyAnalysis};
+///
+/// // A convolution followed by a ReLU.
+/// let expr: RecExpr<Language> = "
+///     (compute relu
+///      (conv2d (access-tensor data) (access-tensor weights)
+///              (shape 1 1) (shape 1 1 1 1) 1))"
+///     .parse()
+///     .unwrap();
+/// let analysis = MyAnalysis {
+///     name_to_shape: vec![
+///         ("data".to_string(), vec![1, 3, 32, 32]),
+///         ("weights".to_string(), vec![8, 3, 3, 3]),
+///     ]
+///     .into_iter()
+///     .collect(),
+///     ..Default::default()
+/// };
+/// let counts = count_ops(&expr, analysis);
+/// assert_eq!(counts.total.macs, 8 * 32 * 32 * 3 * 3 * 3);
+/// assert_eq!(counts.total.elementwise_ops, 8 * 32 * 32);
+/// ```
+pub fn count_ops(expr: &RecExpr<Language>, analysis: MyAnalysis) -> OpCounts {
+    let nodes = expr.as_ref();
+    assert!(!nodes.is_empty(), "Expected a non-empty expression");
+
+    let mut egraph = EGraph::new(analysis);
+    let mut ids = Vec::with_capacity(nodes.len());
+    let mut per_node = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        let node = node.clone().map_children(|child| ids[usize::from(child)]);
+        per_node.push(op_count(&egraph, &node));
+        ids.push(egraph.add(node));
+    }
+
+    let mut reachable = HashSet::new();
+    let mut stack = vec![nodes.len() - 1];
+    while let Some(index) = stack.pop() {
+        if reachable.insert(index) {
+            stack.extend(nodes[index].children().iter().map(|id| usize::from(*id)));
+        }
+    }
+    let total = reachable.iter().map(|index| per_node[*index]).sum();
+
+    OpCounts { per_node, total }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn total(expr: &str) -> OpCount {
+        let analysis = MyAnalysis {
+            name_to_shape: vec![
+                ("a".to_string(), vec![16, 32]),
+                ("b".to_string(), vec![32, 8]),
+                ("data".to_string(), vec![1, 3, 8, 8]),
+                ("weights".to_string(), vec![4, 3, 3, 3]),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        count_ops(&expr.parse().unwrap(), analysis).total
+    }
+
+    #[test]
+    fn matmul_formulations_agree() {
+        let dot_product = total(
+            "(compute dot-product
+              (access-cartesian-product
+               (access (access-tensor a) 1)
+               (access (access-transpose (access (access-tensor b) 1) (list 1 0)) 1)))",
+        );
+        let systolic_array = total(
+            "(systolic-array 32 8 (access (access-tensor a) 1) (access (access-tensor b) 0))",
+        );
+        assert_eq!(dot_product, systolic_array);
+        assert_eq!(
+            dot_product,
+            OpCount {
+                macs: 16 * 32 * 8,
+                elementwise_ops: 0
+            }
+        );
+    }
+
+    #[test]
+    fn conv2d_formulations_agree() {
+        let conv2d = total(
+            "(conv2d (access-tensor data) (access-tensor weights) (shape 1 1) (shape 0 0 0 0) 1)",
+        );
+        let access_windows = total(
+            "(compute dot-product
+              (access-cartesian-product
+               (access (access-tensor weights) 1)
+               (access-squeeze
+                (access-windows (access (access-tensor data) 1) (shape 3 3 3) (shape 1 1 1))
+                1)))",
+        );
+        assert_eq!(conv2d, access_windows);
+        assert_eq!(conv2d.macs, 4 * 6 * 6 * 3 * 3 * 3);
+    }
+
+    #[test]
+    fn elementwise() {
+        assert_eq!(
+            total(
+                "(compute elementwise-add
+                  (access-pair (access (access-tensor a) 1) (access (access-tensor a) 1)))"
+            ),
+            OpCount {
+                macs: 0,
+                elementwise_ops: 16 * 32
+            }
+        );
+        assert_eq!(
+            total("(compute reduce-sum (access (access-tensor a) 1))").elementwise_ops,
+            16 * 32
+        );
+        assert_eq!(
+            total("(access-transpose (access (access-tensor a) 1) (list 1 0))"),
+            OpCount::default()
+        );
+    }
+
+    #[test]
+    fn unreachable_and_shared_nodes() {
+        let mut expr = RecExpr::default();
+        let a = expr.add(Language::Symbol("t-32-32".to_string()));
+        let a = expr.add(Language::AccessTensor(a));
+        let one = expr.add(Language::Num(1));
+        let a = expr.add(Language::Access([a, one]));
+        let relu = expr.add(Language::ComputeType(ComputeType::ReLU));
+        let shared = expr.add(Language::Compute([relu, a]));
+        let sqrt = expr.add(Language::ComputeType(ComputeType::Sqrt));
+        let unused = expr.add(Language::Compute([sqrt, a]));
+        let pair = expr.add(Language::AccessPair([shared, shared]));
+        let add = expr.add(Language::ComputeType(ComputeType::ElementwiseAdd));
+        expr.add(Language::Compute([add, pair]));
+
+        let counts = count_ops(&expr, MyAnalysis::default());
+        assert_eq!(counts.per_node.len(), expr.as_ref().len());
+        assert_eq!(
+            counts.per_node[usize::from(unused)].elementwise_ops,
+            32 * 32
+        );
+        // The ReLU is counted once, and the square root not at all.
+        assert_eq!(counts.total.elementwise_ops, 2 * 32 * 32);
+    }
+}
@@ -0,0 +1,543 @@
+//! Out-of-core interpretation.
+//!
+//! [`interpret`](super::interpreter::interpret) holds every intermediate
+//! value of a program in memory at once, which makes it unusable for
+//! validating large (e.g. ImageNet-scale) models. [`interpret_out_of_core`]
+//! instead evaluates a program one operator at a time, keeping each
+//! intermediate access only until its last use, and spilling accesses larger
+//! than a threshold to `.npy` files on disk until they're needed. Only the
+//! inputs and output of the operator currently being evaluated need to fit in
+//! memory.
+//!
+//! Each operator is evaluated by the interpreter itself, by building a small
+//! expression in which the operator's (already-evaluated) access arguments
+//! are replaced by `(access (access-tensor <name>) <axis>)`, with `<name>`
+//! bound to a view of the argument's tensor. Arguments which are access
+//! shapes or tuples (e.g. of `get-access-shape` or `construct-tuple`) are
+//! likewise replaced by `access-shape` and `construct-tuple` nodes over their
+//! stored values. Thus, this mode supports exactly the operators the
+//! interpreter supports, and produces exactly the same results.
+
+use super::interpreter::{interpret, Access, Environment, GlensideScalar, Value, ViewEnvironment};
+use super::Language;
+use egg::{Id, Language as LanguageTrait, RecExpr};
+use ndarray::{ArrayD, ArrayViewD, Dimension, IxDyn};
+use ndarray_npy::{read_npy, write_npy, ReadableElement, WritableElement};
+use rand::{rngs::OsRng, Rng};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Options for [`interpret_out_of_core`].
+#[derive(Clone, Debug)]
+pub struct OutOfCoreOptions {
+    /// Intermediate accesses whose tensors take up more than this many bytes
+    /// are spilled to disk.
+    pub spill_threshold_bytes: usize,
+    /// The directory spilled tensors are written to.
+    pub spill_directory: PathBuf,
+}
+
+impl Default for OutOfCoreOptions {
+    /// Spills tensors larger than 256MiB to the system's temporary directory.
+    fn default() -> Self {
+        OutOfCoreOptions {
+            spill_threshold_bytes: 256 << 20,
+            spill_directory: std::env::temp_dir(),
+        }
+    }
+}
+
+/// A spilled tensor, which is deleted when dropped (including when
+/// interpretation panics).
+struct SpillFile(PathBuf);
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// An evaluated result, kept until its last use. Accesses are bound to a
+/// name in the expressions of the operators which use them.
+enum Stored<'e, DataType> {
+    /// The tensor named `.0` in the environment, accessed at axis 0, i.e. the
+    /// value of `(access-tensor <.0>)`. Used in place, rather than copied out
+    /// of the environment.
+    Input(&'e str),
+    InMemory(String, Access<DataType>),
+    Spilled(String, SpillFile, usize),
+    AccessShape(IxDyn, usize),
+    Tuple(Vec<Stored<'e, DataType>>),
+}
+
+/// Interprets `expr` at `index`, as [`interpret`] does, but evaluating one
+/// operator at a time and spilling large intermediate accesses to disk, as
+/// configured by `options`. Unlike [`interpret`], shared subexpressions are
+/// only evaluated once.
+///
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::interpreter::Value;
+/// use glenside::language::out_of_core::{interpret_out_of_core, OutOfCoreOptions};
+/// use glenside::language::Language;
+/// use ndarray::array;
+/// use std::collections::HashMap;
+///
+/// let expr: RecExpr<Language> =
+///     "(compute relu (access-flatten (access (access-tensor t) 0)))".parse().unwrap();
+/// let mut env = HashMap::default();
+/// env.insert("t", array![[-1f64, 2.], [3., -4.]].into_dyn());
+/// let options = OutOfCoreOptions {
+///     // Spill everything.
+///     spill_threshold_bytes: 0,
+///     ..Default::default()
+/// };
+/// match interpret_out_of_core(&expr, expr.as_ref().len() - 1, &env, &options) {
+///     Value::Access(a) => assert_eq!(a.tensor, array![0., 2., 3., 0.].into_dyn()),
+///     _ => panic!(),
+/// }
+/// ```
+pub fn interpret_out_of_core<DataType: 'static>(
+    expr: &RecExpr<Language>,
+    index: usize,
+    env: &Environment<DataType>,
+    options: &OutOfCoreOptions,
+) -> Value<'static, DataType>
+where
+    DataType: GlensideScalar + WritableElement + ReadableElement,
+{
+    interpret_each_operator(expr, index, env, options, &mut |_, _| ())
+}
+
+/// Interprets `expr` at `index` as [`interpret_out_of_core`] does, calling
+/// `on_evaluated` with the index of each operator evaluated and the time its
+/// evaluation took. The time doesn't include reading the operator's arguments
+/// back in, or spilling its result.
+pub(crate) fn interpret_each_operator<DataType: 'static>(
+    expr: &RecExpr<Language>,
+    index: usize,
+    env: &Environment<DataType>,
+    options: &OutOfCoreOptions,
+    on_evaluated: &mut dyn FnMut(usize, Duration),
+) -> Value<'static, DataType>
+where
+    DataType: GlensideScalar + WritableElement + ReadableElement,
+{
+    let nodes = expr.as_ref();
+
+    // Which nodes the root depends on, and how many of those use each node.
+    let mut reachable = vec![false; index + 1];
+    reachable[index] = true;
+    let mut uses = vec![0usize; index + 1];
+    for i in (0..=index).rev() {
+        if reachable[i] {
+            for child in nodes[i].children() {
+                reachable[usize::from(*child)] = true;
+                uses[usize::from(*child)] += 1;
+            }
+        }
+    }
+
+    let spill_prefix = format!("glenside-{}-{:x}", std::process::id(), OsRng.gen::<u64>());
+
+    // The evaluated results: accesses, access shapes, and tuples of them.
+    // Other nodes are never stored; instead, their subexpressions are copied
+    // into the expressions of the nodes which use them, and reevaluated
+    // there. These are cheap (e.g. numbers, shapes, and compute types), as
+    // all tensor computation happens through accesses.
+    let mut stored: Vec<Option<Stored<DataType>>> = (0..=index).map(|_| None).collect();
+
+    for i in 0..=index {
+        if !reachable[i] || (nodes[i].is_leaf() && i != index) {
+            continue;
+        }
+
+        // Inputs are used where they are in the environment.
+        if let Language::AccessTensor(symbol_id) = &nodes[i] {
+            if let Language::Symbol(name) = &nodes[usize::from(*symbol_id)] {
+                if i != index {
+                    let start = Instant::now();
+                    assert!(
+                        env.contains_key(name.as_str()),
+                        "Symbol {} not in environment",
+                        name
+                    );
+                    stored[i] = Some(Stored::Input(name.as_str()));
+                    on_evaluated(i, start.elapsed());
+                    release(nodes, *symbol_id, &mut uses, &mut stored);
+                    continue;
+                }
+            }
+        }
+
+        let (value, time) = evaluate(expr, i, env, &stored);
+        on_evaluated(i, time);
+
+        if i == index {
+            return value;
+        }
+
+        stored[i] = store(
+            value,
+            format!("__out_of_core_{}", i),
+            options,
+            &spill_prefix,
+        );
+        if stored[i].is_some() {
+            // Stored results don't need their arguments after they're
+            // evaluated.
+            for child in nodes[i].children() {
+                release(nodes, *child, &mut uses, &mut stored);
+            }
+        }
+    }
+
+    unreachable!()
+}
+
+/// Stores `value`, binding its accesses to `name` (or, within tuples, to
+/// names derived from it), and spilling those larger than configured by
+/// `options`. Returns `None` if `value` isn't stored.
+fn store<'e, DataType: 'static + WritableElement>(
+    value: Value<'static, DataType>,
+    name: String,
+    options: &OutOfCoreOptions,
+    spill_prefix: &str,
+) -> Option<Stored<'e, DataType>> {
+    match value {
+        Value::Access(access) => {
+            let bytes = access.tensor.len() * std::mem::size_of::<DataType>();
+            Some(if bytes > options.spill_threshold_bytes {
+                let path = options
+                    .spill_directory
+                    .join(format!("{}-{}.npy", spill_prefix, name));
+                write_npy(&path, &access.tensor)
+                    .unwrap_or_else(|e| panic!("Could not spill to {}: {:?}", path.display(), e));
+                Stored::Spilled(name, SpillFile(path), access.access_axis)
+            } else {
+                Stored::InMemory(name, access)
+            })
+        }
+        Value::AccessShape(shape, access_axis) => Some(Stored::AccessShape(shape, access_axis)),
+        Value::Tuple(values) => values
+            .into_iter()
+            .enumerate()
+            .map(|(j, value)| store(value, format!("{}_{}", name, j), options, spill_prefix))
+            .collect::<Option<Vec<_>>>()
+            .map(Stored::Tuple),
+        _ => None,
+    }
+}
+
+/// Records that one user of `id` no longer needs it. When `id` has no users
+/// left, it's dropped, along with anything only it was holding onto.
+fn release<DataType>(
+    nodes: &[Language],
+    id: Id,
+    uses: &mut Vec<usize>,
+    stored: &mut Vec<Option<Stored<DataType>>>,
+) {
+    let i = usize::from(id);
+    uses[i] -= 1;
+    if uses[i] > 0 {
+        return;
+    }
+    match stored[i].take() {
+        Some(_) => (),
+        // Nodes which aren't stored are copied into their users, so their
+        // arguments are needed until they themselves aren't.
+        None => {
+            for child in nodes[i].children() {
+                release(nodes, *child, uses, stored);
+            }
+        }
+    }
+}
+
+/// The tensors bound in the expression [`evaluate`] builds: views of inputs
+/// and of results held in memory, and the files of spilled results, which
+/// are only read in once the expression is built.
+struct Bindings<'a, DataType> {
+    views: Vec<(&'a str, ArrayViewD<'a, DataType>)>,
+    spilled: Vec<(&'a str, &'a Path)>,
+}
+
+/// Evaluates node `index` of `expr`, given the results stored so far.
+/// Returns its value, and the time taken by the interpreter.
+fn evaluate<DataType: 'static>(
+    expr: &RecExpr<Language>,
+    index: usize,
+    env: &Environment<DataType>,
+    stored: &[Option<Stored<DataType>>],
+) -> (Value<'static, DataType>, Duration)
+where
+    DataType: GlensideScalar + ReadableElement,
+{
+    fn add_access(new_expr: &mut RecExpr<Language>, name: &str, access_axis: usize) -> Id {
+        let symbol = new_expr.add(Language::Symbol(name.to_string()));
+        let access_tensor = new_expr.add(Language::AccessTensor(symbol));
+        let axis = new_expr.add(Language::Num(access_axis as i64));
+        new_expr.add(Language::Access([access_tensor, axis]))
+    }
+
+    fn add_shape(new_expr: &mut RecExpr<Language>, shape: &[usize]) -> Id {
+        let ids: Vec<Id> = shape
+            .iter()
+            .map(|dim| new_expr.add(Language::Num(*dim as i64)))
+            .collect();
+        new_expr.add(Language::Shape(ids.into_boxed_slice()))
+    }
+
+    /// Adds an expression evaluating to the stored result `stored`.
+    fn add_stored<'a, DataType>(
+        stored: &'a Stored<DataType>,
+        env: &'a Environment<DataType>,
+        new_expr: &mut RecExpr<Language>,
+        bindings: &mut Bindings<'a, DataType>,
+    ) -> Id {
+        match stored {
+            Stored::Input(name) => {
+                bindings.views.push((*name, env[name].view()));
+                add_access(new_expr, name, 0)
+            }
+            Stored::InMemory(name, access) => {
+                bindings.views.push((name.as_str(), access.tensor.view()));
+                add_access(new_expr, name, access.access_axis)
+            }
+            Stored::Spilled(name, SpillFile(path), access_axis) => {
+                bindings.spilled.push((name.as_str(), path.as_path()));
+                add_access(new_expr, name, *access_axis)
+            }
+            Stored::AccessShape(shape, access_axis) => {
+                let shape_id = add_shape(new_expr, &shape.slice()[..*access_axis]);
+                let item_shape_id = add_shape(new_expr, &shape.slice()[*access_axis..]);
+                new_expr.add(Language::AccessShape([shape_id, item_shape_id]))
+            }
+            Stored::Tuple(elements) => {
+                let ids: Vec<Id> = elements
+                    .iter()
+                    .map(|element| add_stored(element, env, new_expr, bindings))
+                    .collect();
+                new_expr.add(Language::ConstructTuple(ids.into_boxed_slice()))
+            }
+        }
+    }
+
+    fn add<'a, DataType>(
+        nodes: &'a [Language],
+        id: Id,
+        env: &'a Environment<DataType>,
+        stored: &'a [Option<Stored<DataType>>],
+        new_expr: &mut RecExpr<Language>,
+        bindings: &mut Bindings<'a, DataType>,
+        old_to_new: &mut HashMap<Id, Id>,
+    ) -> Id {
+        if let Some(new_id) = old_to_new.get(&id) {
+            return *new_id;
+        }
+        let i = usize::from(id);
+        let new_id = match &stored[i] {
+            Some(stored) => add_stored(stored, env, new_expr, bindings),
+            None => {
+                if let Language::Symbol(name) = &nodes[i] {
+                    if let Some(tensor) = env.get(name.as_str()) {
+                        bindings.views.push((name.as_str(), tensor.view()));
+                    }
+                }
+                let node = nodes[i].clone().map_children(|child| {
+                    add(nodes, child, env, stored, new_expr, bindings, old_to_new)
+                });
+                new_expr.add(node)
+            }
+        };
+        old_to_new.insert(id, new_id);
+        new_id
+    }
+
+    let nodes = expr.as_ref();
+    let mut new_expr = RecExpr::default();
+    let mut bindings = Bindings {
+        views: Vec::default(),
+        spilled: Vec::default(),
+    };
+    let mut old_to_new = HashMap::default();
+    let node = nodes[index].clone().map_children(|child| {
+        add(
+            nodes,
+            child,
+            env,
+            stored,
+            &mut new_expr,
+            &mut bindings,
+            &mut old_to_new,
+        )
+    });
+    if let Language::Symbol(name) = &nodes[index] {
+        if let Some(tensor) = env.get(name.as_str()) {
+            bindings.views.push((name.as_str(), tensor.view()));
+        }
+    }
+    new_expr.add(node);
+
+    let spilled: Vec<(&str, ArrayD<DataType>)> = bindings
+        .spilled
+        .into_iter()
+        .map(|(name, path)| {
+            (
+                name,
+                read_npy(path).unwrap_or_else(|e| {
+                    panic!("Could not read spilled {}: {:?}", path.display(), e)
+                }),
+            )
+        })
+        .collect();
+    let mut new_env: ViewEnvironment<DataType> = HashMap::default();
+    for (name, view) in bindings.views {
+        new_env.insert(name, view);
+    }
+    for (name, tensor) in &spilled {
+        new_env.insert(*name, tensor.view());
+    }
+
+    let start = Instant::now();
+    let value = interpret(&new_expr, new_expr.as_ref().len() - 1, &new_env).into_owned();
+    (value, start.elapsed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::ComputeType;
+    use ndarray_rand::{rand_distr::Uniform, RandomExt};
+
+    /// A fresh, empty directory to spill into.
+    fn spill_directory(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "{}-{}",
+            name,
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        std::fs::create_dir(&dir).unwrap();
+        dir
+    }
+
+    fn check(expr: &RecExpr<Language>, env: &Environment<f64>, name: &str) {
+        let root = expr.as_ref().len() - 1;
+        let dir = spill_directory(name);
+
+        for threshold in vec![0, std::usize::MAX] {
+            let options = OutOfCoreOptions {
+                spill_threshold_bytes: threshold,
+                spill_directory: dir.clone(),
+            };
+            match (
+                interpret(expr, root, env),
+                interpret_out_of_core(expr, root, env, &options),
+            ) {
+                (Value::Access(expected), Value::Access(actual)) => {
+                    assert_eq!(expected.tensor, actual.tensor);
+                    assert_eq!(expected.access_axis, actual.access_axis);
+                }
+                (Value::Outputs(expected), Value::Outputs(actual)) => {
+                    assert_eq!(expected.len(), actual.len());
+                    for (expected, actual) in expected.into_iter().zip(actual.into_iter()) {
+                        match (expected, actual) {
+                            (Value::Access(expected), Value::Access(actual)) => {
+                                assert_eq!(expected.tensor, actual.tensor)
+                            }
+                            _ => panic!(),
+                        }
+                    }
+                }
+                _ => panic!(),
+            }
+            // Every spilled tensor has been cleaned up.
+            assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        }
+
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn conv2d() {
+        let mut env = HashMap::default();
+        env.insert(
+            "data",
+            ArrayD::random(vec![1, 3, 8, 8], Uniform::new(-1.0, 1.0)),
+        );
+        env.insert(
+            "weights",
+            ArrayD::random(vec![4, 3, 3, 3], Uniform::new(-1.0, 1.0)),
+        );
+        check(
+            &"
+            (compute relu
+             (access-transpose
+              (compute dot-product
+               (access-cartesian-product
+                (access (access-tensor weights) 1)
+                (access-squeeze
+                 (access-windows
+                  (access
+                   (access-pad
+                    (access-pad (access-tensor data) zero-padding 2 1 1)
+                    zero-padding 3 1 1)
+                   1)
+                  (shape 3 3 3)
+                  (shape 1 1 1))
+                 1)))
+              (list 1 0 2 3)))"
+                .parse()
+                .unwrap(),
+            &env,
+            "glenside-out-of-core-conv2d",
+        );
+    }
+
+    #[test]
+    fn tuples_and_access_shapes() {
+        let mut env = HashMap::default();
+        env.insert("a", ArrayD::random(vec![4, 6], Uniform::new(-1.0, 1.0)));
+        env.insert("b", ArrayD::random(vec![2, 12], Uniform::new(-1.0, 1.0)));
+        check(
+            &"
+            (access-pair
+             (tuple-get-item
+              (construct-tuple
+               (compute relu (access (access-tensor a) 1))
+               (compute relu (access (access-tensor b) 1)))
+              1)
+             (access-reshape
+              (compute relu (access (access-tensor a) 1))
+              (get-access-shape (compute relu (access (access-tensor b) 1)))))"
+                .parse()
+                .unwrap(),
+            &env,
+            "glenside-out-of-core-tuples-and-access-shapes",
+        );
+    }
+
+    #[test]
+    fn shared_subexpressions() {
+        let mut env = HashMap::default();
+        env.insert("a", ArrayD::random(vec![4, 6], Uniform::new(-1.0, 1.0)));
+        // x is used by both outputs, and twice by the second. Parsing doesn't
+        // share subexpressions, so the expression is built by hand.
+        let mut expr = RecExpr::default();
+        let relu = expr.add(Language::ComputeType(ComputeType::ReLU));
+        let a = expr.add(Language::Symbol("a".to_string()));
+        let access_tensor = expr.add(Language::AccessTensor(a));
+        let one = expr.add(Language::Num(1));
+        let access = expr.add(Language::Access([access_tensor, one]));
+        let x = expr.add(Language::Compute([relu, access]));
+        let add = expr.add(Language::ComputeType(ComputeType::ElementwiseAdd));
+        let pair = expr.add(Language::AccessPair([x, x]));
+        let sum = expr.add(Language::Compute([add, pair]));
+        expr.add(Language::Outputs(vec![x, sum]));
+        check(&expr, &env, "glenside-out-of-core-shared-subexpressions");
+    }
+}
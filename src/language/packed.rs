@@ -0,0 +1,249 @@
+//! Packed low-precision tensors.
+//!
+//! Studying how narrow an accelerator's datapath can be (e.g. int4 or int16
+//! weights rather than int8) means evaluating models whose tensors hold
+//! narrow integers. A [`PackedTensor`] stores such a tensor as it would be
+//! stored in the accelerator's memory: each element takes only its type's
+//! bits, so two int4 elements share a byte.
+//!
+//! Tensors are packed by putting them in a [`PackedEnvironment`]. The
+//! interpreter unpacks a packed tensor into the environment's element type
+//! whenever its symbol is interpreted, so the rest of the program computes
+//! on ordinary tensors; casts to the narrow type (see `cast`) keep
+//! intermediate results in range.
+
+use super::interpreter::{Environment, Lookup};
+use super::DataType;
+use ndarray::{ArrayD, ArrayViewD, IxDyn};
+use num_traits::{FromPrimitive, ToPrimitive};
+use std::collections::HashMap;
+
+/// A tensor of integers, packed into `bits(dtype)` bits per element, in
+/// row-major order. Elements are stored least significant bit first, so an
+/// int4 tensor's first element is the low nibble of its first byte.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PackedTensor {
+    pub shape: Vec<usize>,
+    pub dtype: DataType,
+    pub bytes: Vec<u8>,
+}
+
+/// The number of bits each element of type `dtype` is packed into. Panics
+/// unless `dtype` is a signed or unsigned integer of at most 32 bits.
+pub fn bits(dtype: DataType) -> usize {
+    match dtype {
+        DataType::Int(bits) | DataType::Uint(bits) if bits > 0 && bits <= 32 => bits,
+        _ => panic!("Can't pack elements of type {}", dtype),
+    }
+}
+
+/// The smallest and largest values of type `dtype`.
+fn range(dtype: DataType) -> (i64, i64) {
+    let bits = bits(dtype);
+    match dtype {
+        DataType::Int(_) => (-(1 << (bits - 1)), (1 << (bits - 1)) - 1),
+        _ => (0, (1 << bits) - 1),
+    }
+}
+
+impl PackedTensor {
+    /// Packs `tensor` into elements of type `dtype`. Panics if an element
+    /// isn't an integer in `dtype`'s range.
+    pub fn pack<T: ToPrimitive>(tensor: ArrayViewD<T>, dtype: DataType) -> Self {
+        let bits = bits(dtype);
+        let (min, max) = range(dtype);
+        let mut bytes = vec![0u8; (tensor.len() * bits + 7) / 8];
+        for (index, value) in tensor.iter().enumerate() {
+            let value = value
+                .to_i64()
+                .filter(|v| value.to_f64() == Some(*v as f64) && *v >= min && *v <= max)
+                .unwrap_or_else(|| {
+                    panic!(
+                        "Element {} ({:?}) doesn't fit in {}",
+                        index,
+                        value.to_f64(),
+                        dtype
+                    )
+                });
+            let start = index * bits;
+            for bit in 0..bits {
+                if (value >> bit) & 1 == 1 {
+                    bytes[(start + bit) / 8] |= 1 << ((start + bit) % 8);
+                }
+            }
+        }
+        PackedTensor {
+            shape: tensor.shape().to_vec(),
+            dtype,
+            bytes,
+        }
+    }
+
+    /// The number of elements.
+    pub fn len(&self) -> usize {
+        self.shape.iter().product()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The element at `index`, counting in row-major order.
+    pub fn get(&self, index: usize) -> i64 {
+        assert!(
+            index < self.len(),
+            "Index {} out of bounds for a packed tensor of {} elements",
+            index,
+            self.len()
+        );
+        let bits = bits(self.dtype);
+        let start = index * bits;
+        let raw = (0..bits)
+            .map(|bit| {
+                let byte = self.bytes[(start + bit) / 8];
+                (((byte >> ((start + bit) % 8)) & 1) as i64) << bit
+            })
+            .sum::<i64>();
+        match self.dtype {
+            // Sign-extend.
+            DataType::Int(_) if raw >> (bits - 1) == 1 => raw - (1 << bits),
+            _ => raw,
+        }
+    }
+
+    /// The tensor, with its elements converted to `T`.
+    pub fn unpack<T: FromPrimitive>(&self) -> ArrayD<T> {
+        ArrayD::from_shape_vec(
+            IxDyn(&self.shape),
+            (0..self.len())
+                .map(|index| {
+                    let value = self.get(index);
+                    T::from_i64(value)
+                        .unwrap_or_else(|| panic!("Can't unpack {} from {}", value, self.dtype))
+                })
+                .collect(),
+        )
+        .unwrap()
+    }
+}
+
+/// An environment in which some tensors are packed. Packed tensors are only
+/// stored packed; they're unpacked each time they're looked up.
+#[derive(Default)]
+pub struct PackedEnvironment<'a, DataType> {
+    pub dense: Environment<'a, DataType>,
+    pub packed: HashMap<&'a str, PackedTensor>,
+}
+
+impl<'a, DataType: ToPrimitive> PackedEnvironment<'a, DataType> {
+    /// Adds the tensor `name`, packed into elements of type `dtype`.
+    pub fn insert_packed(
+        &mut self,
+        name: &'a str,
+        tensor: ArrayViewD<DataType>,
+        dtype: super::DataType,
+    ) {
+        self.packed.insert(name, PackedTensor::pack(tensor, dtype));
+    }
+
+    /// The number of bytes the environment's tensors take up, with each dense
+    /// element taking `dense_element_bytes` bytes.
+    pub fn bytes(&self, dense_element_bytes: usize) -> usize {
+        self.dense
+            .values()
+            .map(|tensor| tensor.len() * dense_element_bytes)
+            .chain(self.packed.values().map(|tensor| tensor.bytes.len()))
+            .sum()
+    }
+}
+
+impl<'a, DataType: FromPrimitive> Lookup<DataType> for PackedEnvironment<'a, DataType> {
+    fn lookup(&self, name: &str) -> Option<ArrayViewD<'_, DataType>> {
+        self.dense.lookup(name)
+    }
+
+    fn unpack(&self, name: &str) -> Option<ArrayD<DataType>> {
+        self.packed.get(name).map(|tensor| tensor.unpack())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::interpreter::{interpret, Value};
+    use crate::language::Language;
+    use egg::RecExpr;
+    use ndarray::array;
+
+    #[test]
+    fn pack_int4() {
+        let packed =
+            PackedTensor::pack(array![[-8, 7], [3, -1]].into_dyn().view(), DataType::Int(4));
+        assert_eq!(packed.bytes, vec![0x78, 0xf3]);
+        assert_eq!(packed.unpack::<i64>(), array![[-8, 7], [3, -1]].into_dyn());
+    }
+
+    #[test]
+    fn pack_16_bits() {
+        let tensor = array![-32768., 32767., 0., -2.].into_dyn();
+        let packed = PackedTensor::pack(tensor.view(), DataType::Int(16));
+        assert_eq!(packed.bytes.len(), 8);
+        assert_eq!(packed.unpack::<f64>(), tensor);
+
+        let tensor = array![65535, 1, 256].into_dyn();
+        let packed = PackedTensor::pack(tensor.view(), DataType::Uint(16));
+        assert_eq!(packed.bytes, vec![0xff, 0xff, 0x01, 0x00, 0x00, 0x01]);
+        assert_eq!(packed.unpack::<i32>(), tensor);
+    }
+
+    #[test]
+    fn odd_length() {
+        let packed = PackedTensor::pack(array![1, 2, 15].into_dyn().view(), DataType::Uint(4));
+        assert_eq!(packed.bytes, vec![0x21, 0x0f]);
+        assert_eq!(packed.get(2), 15);
+    }
+
+    #[test]
+    #[should_panic(expected = "doesn't fit in int4")]
+    fn out_of_range() {
+        PackedTensor::pack(array![8].into_dyn().view(), DataType::Int(4));
+    }
+
+    #[test]
+    #[should_panic(expected = "doesn't fit in uint4")]
+    fn not_an_integer() {
+        PackedTensor::pack(array![0.5].into_dyn().view(), DataType::Uint(4));
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't pack elements of type float32")]
+    fn unsupported_dtype() {
+        PackedTensor::pack(array![1.].into_dyn().view(), DataType::Float(32));
+    }
+
+    #[test]
+    fn interpret_packed_weights() {
+        let expr: RecExpr<Language> = "
+         (compute dot-product
+          (access-cartesian-product
+           (access (access-tensor a) 1)
+           (access (access-tensor w) 1)))"
+            .parse()
+            .unwrap();
+        let mut env = PackedEnvironment::default();
+        env.dense.insert("a", array![[1., 2.], [3., 4.]].into_dyn());
+        env.insert_packed(
+            "w",
+            array![[-8., 7.], [1., -1.]].into_dyn().view(),
+            DataType::Int(4),
+        );
+        assert_eq!(env.bytes(4), 4 * 4 + 2);
+
+        match interpret(&expr, expr.as_ref().len() - 1, &env) {
+            Value::Access(a) => {
+                assert_eq!(a.tensor, array![[6., -1.], [4., -1.]].into_dyn())
+            }
+            _ => panic!(),
+        }
+    }
+}
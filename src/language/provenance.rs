@@ -0,0 +1,324 @@
+//! Provenance tracking.
+//!
+//! When a mapped program misbehaves, we want to know which part of the source
+//! model each piece of it came from: e.g. that a given `systolic-array` call
+//! is the third `nn.dense` in the Relay program. [`Provenance`] attaches such
+//! labels to the nodes of a [`RecExpr`] (see
+//! [`crate::language::from_relay::from_relay_with_provenance`]), and carries
+//! them through the egraph:
+//!
+//! 1. [`Provenance::add_expr`] adds an expression to an egraph, moving its
+//!    labels onto eclasses. Rewrites add enodes to existing eclasses, so the
+//!    labels are preserved across rewrites, and eclasses which are merged
+//!    share their labels.
+//! 2. [`Provenance::for_extracted_expr`] moves the labels back onto the nodes
+//!    of an extracted expression. Nodes introduced by rewrites into new
+//!    eclasses inherit the labels of the node that uses them.
+//! 3. [`Provenance::report`] summarizes the labels, and
+//!    [`crate::codegen::codegen_with_provenance`] emits them as comments.
+
+use super::{Language, MyAnalysis};
+use egg::{EGraph, Id, Language as LanguageTrait, RecExpr};
+use std::collections::{BTreeSet, HashMap};
+
+/// Labels attached to ids, which are either the ids of nodes in a [`RecExpr`]
+/// or the ids of eclasses, depending on context.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Provenance {
+    labels: HashMap<Id, BTreeSet<String>>,
+}
+
+impl Provenance {
+    /// Attaches `label` to `id`.
+    pub fn add(&mut self, id: Id, label: impl Into<String>) {
+        self.labels.entry(id).or_default().insert(label.into());
+    }
+
+    /// The provenance of the nodes of an expression whose node `i` became node
+    /// `ids[i]` of another (e.g. by [`hash_cons`](super::hash_cons::hash_cons)).
+    /// Nodes which were merged share their labels.
+    pub fn remap(&self, ids: &[Id]) -> Provenance {
+        let mut remapped = Provenance::default();
+        for (id, labels) in &self.labels {
+            for label in labels {
+                remapped.add(ids[usize::from(*id)], label.clone());
+            }
+        }
+        remapped
+    }
+
+    /// The labels attached to `id`. When `id` is an eclass, use
+    /// [`Provenance::get_eclass`] instead, so that merged eclasses are handled.
+    pub fn get(&self, id: Id) -> BTreeSet<String> {
+        self.labels.get(&id).cloned().unwrap_or_default()
+    }
+
+    /// The labels attached to eclass `id`, or to any eclass since merged into
+    /// it.
+    pub fn get_eclass(&self, egraph: &EGraph<Language, MyAnalysis>, id: Id) -> BTreeSet<String> {
+        let id = egraph.find(id);
+        self.labels
+            .iter()
+            .filter(|(other, _)| egraph.find(**other) == id)
+            .flat_map(|(_, labels)| labels.iter().cloned())
+            .collect()
+    }
+
+    /// The labels of each labeled eclass of `egraph`, with the labels of
+    /// merged eclasses combined.
+    pub fn by_eclass(
+        &self,
+        egraph: &EGraph<Language, MyAnalysis>,
+    ) -> HashMap<Id, BTreeSet<String>> {
+        let mut by_eclass: HashMap<Id, BTreeSet<String>> = HashMap::default();
+        for (id, labels) in &self.labels {
+            by_eclass
+                .entry(egraph.find(*id))
+                .or_default()
+                .extend(labels.iter().cloned());
+        }
+        by_eclass
+    }
+
+    /// Adds `expr` to `egraph`, as [`EGraph::add_expr`] does, given `self`,
+    /// the provenance of `expr`'s nodes. Returns the id of the root eclass and
+    /// the provenance of the eclasses.
+    pub fn add_expr(
+        &self,
+        egraph: &mut EGraph<Language, MyAnalysis>,
+        expr: &RecExpr<Language>,
+    ) -> (Id, Provenance) {
+        let mut eclass_provenance = Provenance::default();
+        let mut ids: Vec<Id> = Vec::with_capacity(expr.as_ref().len());
+        for (i, node) in expr.as_ref().iter().enumerate() {
+            let id = egraph.add(node.clone().map_children(|child| ids[usize::from(child)]));
+            for label in self.get(Id::from(i)) {
+                eclass_provenance.add(id, label);
+            }
+            ids.push(id);
+        }
+        (*ids.last().unwrap(), eclass_provenance)
+    }
+
+    /// Given `self`, the provenance of `egraph`'s eclasses, computes the
+    /// provenance of the nodes of `expr`, which must be represented in
+    /// `egraph` (e.g. because it was extracted from it).
+    pub fn for_extracted_expr(
+        &self,
+        egraph: &EGraph<Language, MyAnalysis>,
+        expr: &RecExpr<Language>,
+    ) -> Provenance {
+        let nodes = expr.as_ref();
+        let mut node_labels: Vec<BTreeSet<String>> = Vec::with_capacity(nodes.len());
+        let mut eclasses: Vec<Id> = Vec::with_capacity(nodes.len());
+        for node in nodes {
+            let node = node
+                .clone()
+                .map_children(|child| eclasses[usize::from(child)]);
+            let eclass = egraph
+                .lookup(node.clone())
+                .unwrap_or_else(|| panic!("{:?} is not in the egraph", node));
+            node_labels.push(self.get_eclass(egraph, eclass));
+            eclasses.push(eclass);
+        }
+
+        // Nodes without labels inherit their parents' labels. Parents come
+        // after their children, so we go in reverse.
+        for i in (0..nodes.len()).rev() {
+            if node_labels[i].is_empty() {
+                continue;
+            }
+            for child in nodes[i].children() {
+                let child = usize::from(*child);
+                if node_labels[child].is_empty()
+                    && self.get_eclass(egraph, eclasses[child]).is_empty()
+                {
+                    node_labels[child] = node_labels[i].clone();
+                }
+            }
+        }
+
+        let mut provenance = Provenance::default();
+        for (i, labels) in node_labels.into_iter().enumerate() {
+            for label in labels {
+                provenance.add(Id::from(i), label);
+            }
+        }
+        provenance
+    }
+
+    /// Given `self`, the provenance of `expr`'s nodes, lists the labels of each
+    /// labeled operator in `expr` (that is, each node with children), one per
+    /// line, e.g. `5: systolic-array <- nn.dense#0`.
+    pub fn report(&self, expr: &RecExpr<Language>) -> String {
+        expr.as_ref()
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| !node.is_leaf())
+            .filter_map(|(i, node)| {
+                let labels = self.get(Id::from(i));
+                if labels.is_empty() {
+                    None
+                } else {
+                    Some(format!(
+                        "{}: {} <- {}\n",
+                        i,
+                        node.display_op(),
+                        labels.into_iter().collect::<Vec<_>>().join(", ")
+                    ))
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::{codegen, codegen_with_provenance, generate_worklist_for_codegen};
+    use crate::extraction::MonolithicCostFunction;
+    use crate::language::rewrites;
+    use egg::{Extractor, Runner};
+
+    #[test]
+    fn through_rewrites_and_extraction() {
+        let expr: RecExpr<Language> = "
+         (compute relu
+          (compute dot-product
+           (access-cartesian-product
+            (access (access-tensor t-32-32) 1)
+            (access (access-tensor t-32-32) 1))))"
+            .parse()
+            .unwrap();
+        let mut provenance = Provenance::default();
+        let n = expr.as_ref().len();
+        provenance.add(Id::from(n - 1), "nn.relu#0");
+        provenance.add(Id::from(n - 2), "nn.dense#0");
+
+        let mut egraph = EGraph::new(MyAnalysis::default());
+        let (root, eclass_provenance) = provenance.add_expr(&mut egraph, &expr);
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&vec![rewrites::systolic_array().rewrite]);
+
+        let (_, extracted) = Extractor::new(
+            &runner.egraph,
+            MonolithicCostFunction {
+                egraph: &runner.egraph,
+                systolic_array_configuration: (32, 32),
+                prefer_systolic_arrays_with_blocking: false,
+            },
+        )
+        .find_best(root);
+        let extracted_provenance = eclass_provenance.for_extracted_expr(&runner.egraph, &extracted);
+
+        let nodes = extracted.as_ref();
+        let systolic_array = nodes
+            .iter()
+            .position(|node| matches!(node, Language::SystolicArray(_)))
+            .unwrap();
+        assert_eq!(
+            extracted_provenance.get(Id::from(systolic_array)),
+            vec!["nn.dense#0".to_string()].into_iter().collect()
+        );
+        assert_eq!(
+            extracted_provenance.get(Id::from(nodes.len() - 1)),
+            vec!["nn.relu#0".to_string()].into_iter().collect()
+        );
+        // The transpose introduced by the rewrite inherits the dense's label.
+        let transpose = nodes
+            .iter()
+            .position(|node| matches!(node, Language::AccessTranspose(_)))
+            .unwrap();
+        assert_eq!(
+            extracted_provenance.get(Id::from(transpose)),
+            vec!["nn.dense#0".to_string()].into_iter().collect()
+        );
+
+        let report = extracted_provenance.report(&extracted);
+        assert!(report.contains(&format!(
+            "{}: systolic-array <- nn.dense#0\n",
+            systolic_array
+        )));
+        assert!(report.contains(&format!("{}: compute <- nn.relu#0\n", nodes.len() - 1)));
+    }
+
+    #[test]
+    fn merged_eclasses() {
+        let mut egraph = EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let a: RecExpr<Language> = "(access (access-tensor t-32-32) 0)".parse().unwrap();
+        let b: RecExpr<Language> = "(access-flatten (access (access-tensor t-32-32) 0))"
+            .parse()
+            .unwrap();
+        let mut provenance_a = Provenance::default();
+        provenance_a.add(Id::from(a.as_ref().len() - 1), "a");
+        let mut provenance_b = Provenance::default();
+        provenance_b.add(Id::from(b.as_ref().len() - 1), "b");
+
+        let (a_id, a_eclass_provenance) = provenance_a.add_expr(&mut egraph, &a);
+        let (b_id, b_eclass_provenance) = provenance_b.add_expr(&mut egraph, &b);
+        let mut eclass_provenance = a_eclass_provenance;
+        for label in b_eclass_provenance.get(b_id) {
+            eclass_provenance.add(b_id, label);
+        }
+        assert_eq!(eclass_provenance.get_eclass(&egraph, a_id).len(), 1);
+
+        egraph.union(a_id, b_id);
+        egraph.rebuild();
+        assert_eq!(
+            eclass_provenance.get_eclass(&egraph, a_id),
+            vec!["a".to_string(), "b".to_string()].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn codegen_comments() {
+        let expr: RecExpr<Language> = "
+         (systolic-array 10 15
+          (access (access-tensor t0) 1)
+          (access (access-tensor t1) 0))"
+            .parse()
+            .unwrap();
+        let mut provenance = Provenance::default();
+        provenance.add(Id::from(expr.as_ref().len() - 1), "nn.dense#0");
+
+        let mut map = HashMap::default();
+        map.insert("t0".to_string(), vec![2, 10]);
+        map.insert("t1".to_string(), vec![10, 15]);
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let (id, eclass_provenance) = provenance.add_expr(&mut egraph, &expr);
+        let mut hw_map = HashMap::default();
+        hw_map.insert(id, 0);
+        let worklist = generate_worklist_for_codegen(&egraph, id);
+
+        let code = codegen_with_provenance(
+            &egraph,
+            id,
+            &hw_map,
+            "systolic_array",
+            "",
+            &vec!["t0", "t1"],
+            &worklist,
+            true,
+            &eclass_provenance,
+        );
+        let comment = code.find("// provenance: nn.dense#0\n").unwrap();
+        assert!(code[comment..].contains("rtml_systolic_array"));
+
+        let code = codegen(
+            &egraph,
+            id,
+            &hw_map,
+            "systolic_array",
+            "",
+            &vec!["t0", "t1"],
+            &worklist,
+            true,
+        );
+        assert!(!code.contains("provenance"));
+    }
+}
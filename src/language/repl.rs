@@ -0,0 +1,313 @@
+//! An interactive read-eval-print loop for Glenside expressions.
+//!
+//! Designing a new access pattern usually means writing an expression,
+//! checking the shape the analysis infers for it, and interpreting it on small
+//! inputs to see that it computes what was intended. A [`Repl`] makes each of
+//! these a single line:
+//!
+//! ```text
+//! glenside> :random a 2 3
+//! a: tensor of shape [2, 3]
+//! glenside> (access (access-tensor a) 1)
+//! access of shape (2, 3) viewed as 2 vectors of length 3
+//! glenside> :eval (compute reduce-sum (access (access-tensor a) 1))
+//! access of shape (2) viewed as 2 scalars
+//! [1.3208497059393506, 1.5770370297420327]
+//! ```
+//!
+//! Expressions may span several lines; input is read until its parentheses
+//! balance. Run the REPL with
+//! `cargo run --example glenside-cli -- repl`, and type `:help` for the list
+//! of commands.
+
+use super::interpreter::{try_interpret, Value, ViewEnvironment};
+use super::spans::{parse_with_spans, SpannedExpr};
+use super::visualize::{describe_eclass, describe_value};
+use super::{Language, MyAnalysis};
+use egg::{EGraph, Id};
+use ndarray::ArrayD;
+use rand::{rngs::OsRng, Rng};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, Write};
+
+const HELP: &str = "\
+<expr>                   show the shape of <expr>
+:nodes <expr>            show the shape of each subexpression of <expr>
+:eval [@<node>] <expr>   interpret <expr>, or its subexpression <node>
+:random <name> <dim>...  define <name> as a tensor of random values in [0, 1)
+:load <name> <path>      define <name> as the tensor in the .npy file <path>
+:env                     list the defined tensors
+:help                    show this message
+:quit                    exit";
+
+/// A REPL session: the tensors defined so far, which expressions are analyzed
+/// and interpreted with.
+#[derive(Default)]
+pub struct Repl {
+    pub tensors: BTreeMap<String, ArrayD<f64>>,
+}
+
+impl Repl {
+    fn analysis(&self) -> MyAnalysis {
+        MyAnalysis {
+            name_to_shape: self
+                .tensors
+                .iter()
+                .map(|(name, tensor)| (name.clone(), tensor.shape().to_vec()))
+                .collect(),
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        }
+    }
+
+    /// Parses `text` and adds it to a new egraph, returning the eclass of
+    /// each of its nodes.
+    fn analyze(
+        &self,
+        text: &str,
+    ) -> Result<(SpannedExpr, EGraph<Language, MyAnalysis>, Vec<Id>), String> {
+        let spanned = parse_with_spans(text).map_err(|e| e.to_string())?;
+        let mut egraph = EGraph::new(self.analysis());
+        let ids = spanned
+            .add_nodes_to_egraph(&mut egraph)
+            .map_err(|e| e.to_string())?;
+        Ok((spanned, egraph, ids))
+    }
+
+    fn shape(&self, text: &str) -> Result<String, String> {
+        let (_, egraph, ids) = self.analyze(text)?;
+        Ok(describe_eclass(&egraph, *ids.last().unwrap()))
+    }
+
+    fn nodes(&self, text: &str) -> Result<String, String> {
+        let (spanned, egraph, ids) = self.analyze(text)?;
+        Ok(ids
+            .iter()
+            .enumerate()
+            .map(|(index, &id)| {
+                format!(
+                    "@{} {}: {}",
+                    index,
+                    spanned.snippet(index),
+                    describe_eclass(&egraph, id)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    fn eval(&self, text: &str) -> Result<String, String> {
+        let (node, text) = if let Some(text) = text.strip_prefix('@') {
+            let mut split = text.splitn(2, char::is_whitespace);
+            let node = split.next().unwrap();
+            (
+                Some(
+                    node.parse::<usize>()
+                        .map_err(|_| format!("Expected a node index, found @{}", node))?,
+                ),
+                split.next().unwrap_or(""),
+            )
+        } else {
+            (None, text)
+        };
+        let (spanned, _, ids) = self.analyze(text)?;
+        let index = node.unwrap_or(ids.len() - 1);
+        if index >= ids.len() {
+            return Err(format!("No node @{}; try :nodes", index));
+        }
+
+        let env: ViewEnvironment<f64> = self
+            .tensors
+            .iter()
+            .map(|(name, tensor)| (name.as_str(), tensor.view()))
+            .collect();
+        let value = try_interpret(&spanned.expr, index, &env).map_err(|e| e.to_string())?;
+        Ok(match &value {
+            Value::Access(access) => format!("{}\n{}", describe_value(&value), access.tensor),
+            Value::Tensor(tensor) => format!("{}\n{}", describe_value(&value), tensor),
+            _ => describe_value(&value),
+        })
+    }
+
+    fn define(&mut self, name: &str, tensor: ArrayD<f64>) -> String {
+        let description = format!("{}: tensor of shape {:?}", name, tensor.shape());
+        self.tensors.insert(name.to_string(), tensor);
+        description
+    }
+
+    fn random(&mut self, args: &str) -> Result<String, String> {
+        let mut args = args.split_whitespace();
+        let name = args.next().ok_or("Usage: :random <name> <dim>...")?;
+        let shape = args
+            .map(|dim| {
+                dim.parse::<usize>()
+                    .map_err(|_| format!("Expected a dimension, found {}", dim))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let tensor = ArrayD::from_shape_simple_fn(shape, || OsRng.gen::<f64>());
+        Ok(self.define(name, tensor))
+    }
+
+    fn load(&mut self, args: &str) -> Result<String, String> {
+        let args = args.split_whitespace().collect::<Vec<_>>();
+        let (name, path) = match args[..] {
+            [name, path] => (name, path),
+            _ => return Err("Usage: :load <name> <path>".to_string()),
+        };
+        // Fixtures are often saved as float32; they're widened to float64.
+        let tensor = ndarray_npy::read_npy::<_, ArrayD<f64>>(path)
+            .or_else(|_| ndarray_npy::read_npy::<_, ArrayD<f32>>(path).map(|t| t.mapv(f64::from)))
+            .map_err(|e| format!("Couldn't read {}: {}", path, e))?;
+        Ok(self.define(name, tensor))
+    }
+
+    fn env(&self) -> String {
+        if self.tensors.is_empty() {
+            return "No tensors defined; see :random and :load".to_string();
+        }
+        self.tensors
+            .iter()
+            .map(|(name, tensor)| format!("{}: tensor of shape {:?}", name, tensor.shape()))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Evaluates one command or expression, returning what to print. Errors
+    /// are printed too, rather than ending the session.
+    pub fn eval_line(&mut self, line: &str) -> String {
+        let line = line.trim();
+        let (command, rest) = if line.starts_with(':') {
+            let mut split = line.splitn(2, char::is_whitespace);
+            (split.next().unwrap(), split.next().unwrap_or("").trim())
+        } else {
+            ("", line)
+        };
+        match command {
+            "" if rest.is_empty() => Ok(String::default()),
+            "" => self.shape(rest),
+            ":nodes" => self.nodes(rest),
+            ":eval" => self.eval(rest),
+            ":random" => self.random(rest),
+            ":load" => self.load(rest),
+            ":env" => Ok(self.env()),
+            ":help" => Ok(HELP.to_string()),
+            _ => Err(format!("Unknown command {}; try :help", command)),
+        }
+        .unwrap_or_else(|error| error)
+    }
+
+    /// Reads commands from `input` and prints their results to `output`,
+    /// until `:quit` or the end of the input.
+    pub fn run(&mut self, mut input: impl BufRead, mut output: impl Write) -> std::io::Result<()> {
+        loop {
+            let mut text = String::default();
+            write!(output, "glenside> ")?;
+            loop {
+                output.flush()?;
+                if input.read_line(&mut text)? == 0 {
+                    return Ok(());
+                }
+                if text.matches('(').count() <= text.matches(')').count() {
+                    break;
+                }
+                write!(output, "      ... ")?;
+            }
+            if text.trim() == ":quit" {
+                return Ok(());
+            }
+            let result = self.eval_line(&text);
+            if !result.is_empty() {
+                writeln!(output, "{}", result)?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    fn repl() -> Repl {
+        let mut repl = Repl::default();
+        repl.tensors.insert(
+            "a".to_string(),
+            array![[1., 2., 3.], [4., 5., 6.]].into_dyn(),
+        );
+        repl
+    }
+
+    #[test]
+    fn shapes() {
+        let mut repl = repl();
+        assert_eq!(
+            repl.eval_line("(access (access-tensor a) 1)"),
+            "access of shape (2, 3) viewed as 2 vectors of length 3"
+        );
+
+        let nodes = repl.eval_line(":nodes (access (access-tensor a) 1)");
+        assert_eq!(nodes.lines().count(), 4);
+        assert_eq!(
+            nodes.lines().last().unwrap(),
+            "@3 (access (access-tensor a) 1): access of shape (2, 3) viewed as 2 vectors of length 3"
+        );
+
+        assert_eq!(
+            repl.eval_line("(access (access-tensor b) 1)"),
+            "analysis error: No shape defined for b at line 1, column 24: b"
+        );
+    }
+
+    #[test]
+    fn eval() {
+        let mut repl = repl();
+        let result = repl.eval_line(":eval (compute reduce-sum (access (access-tensor a) 1))");
+        assert!(result.starts_with("access of shape (2) viewed as 2 scalars\n"));
+        assert!(result.contains("15"));
+
+        assert!(repl
+            .eval_line(":eval @4 (compute reduce-sum (access (access-tensor a) 1))")
+            .starts_with("access of shape (2, 3) viewed as 2 vectors of length 3\n"));
+        assert_eq!(
+            repl.eval_line(":eval @9 (access (access-tensor a) 1)"),
+            "No node @9; try :nodes"
+        );
+    }
+
+    #[test]
+    fn define_tensors() {
+        let mut repl = Repl::default();
+        assert_eq!(
+            repl.eval_line(":env"),
+            "No tensors defined; see :random and :load"
+        );
+        assert_eq!(repl.eval_line(":random w 4 3"), "w: tensor of shape [4, 3]");
+        assert!(repl.tensors["w"].iter().all(|v| (0. ..1.).contains(v)));
+        assert!(repl
+            .eval_line(":load x does-not-exist.npy")
+            .starts_with("Couldn't read does-not-exist.npy"));
+        assert_eq!(repl.eval_line(":env"), "w: tensor of shape [4, 3]");
+    }
+
+    #[test]
+    fn session() {
+        let input = "\
+:random w 4 3
+(compute dot-product
+ (access-cartesian-product
+  (access (access-tensor a) 1)
+  (access (access-tensor w) 1)))
+:quit
+(access (access-tensor a) 1)
+";
+        let mut output = Vec::default();
+        repl().run(input.as_bytes(), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "\
+glenside> w: tensor of shape [4, 3]
+glenside>       ...       ...       ... access of shape (2, 4) viewed as 2×4 scalars
+glenside> "
+        );
+    }
+}
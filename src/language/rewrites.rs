@@ -2,6 +2,7 @@ use std::convert::TryFrom;
 use std::convert::TryInto;
 use std::str::FromStr;
 
+use crate::language::constant_weights::ConstantWeights;
 use crate::language::from_relay::{
     access_concatenate, access_insert_axis, access_pair, access_shape, compute,
 };
@@ -21,6 +22,93 @@ use ndarray::IxDyn;
 type EG = EGraph<Language, MyAnalysis>;
 type RW = Rewrite<Language, MyAnalysis>;
 
+/// A rewrite which maps computations onto a hardware atom, along with what
+/// [`Legality::restrict`](crate::legality::Legality::restrict) needs to
+/// check the atom supports each computation it matches: the variables its
+/// searcher binds to the atom's inputs, and the number of elements the atom
+/// reduces into each output. Use [`AtomRewrite::rewrite`] directly to map
+/// computations regardless of the atom's limits.
+pub struct AtomRewrite {
+    pub rewrite: RW,
+    /// The variables bound to the eclasses of the atom's inputs.
+    pub inputs: Vec<Var>,
+    /// The number of elements the atom reduces into each output of a match,
+    /// if it reduces. `None` from the function if it can't be determined.
+    pub depth: Option<Depth>,
+}
+
+/// Finds the depth of a reduction from a match. See [`AtomRewrite::depth`].
+pub type Depth = Box<dyn Fn(&EG, &Subst) -> Option<usize> + Send + Sync>;
+
+impl AtomRewrite {
+    /// Panics if `rewrite`'s searcher doesn't bind each of `inputs`.
+    fn new(rewrite: RW, inputs: &[&str], depth: Option<Depth>) -> Self {
+        let inputs: Vec<Var> = inputs.iter().map(|var| var.parse().unwrap()).collect();
+        let bound = rewrite.searcher.vars();
+        for var in &inputs {
+            assert!(
+                bound.contains(var),
+                "{} doesn't bind atom input {}",
+                rewrite.name,
+                var
+            );
+        }
+        AtomRewrite {
+            rewrite,
+            inputs,
+            depth,
+        }
+    }
+}
+
+/// The length of the last item axis of the access bound to `var`, e.g. the K
+/// of an MxK by KxN multiplication whose left-hand side is `var`.
+fn last_item_axis(var: &str) -> Depth {
+    let var: Var = var.parse().unwrap();
+    Box::new(move |egraph, subst| match &egraph[*subst.get(var)?].data {
+        MyAnalysisData::AccessPattern(a) => a.item_shape.slice().last().copied(),
+        _ => None,
+    })
+}
+
+/// The length of the last axis of the access bound to `var`.
+fn last_access_axis(var: &str) -> Depth {
+    let var: Var = var.parse().unwrap();
+    Box::new(move |egraph, subst| match &egraph[*subst.get(var)?].data {
+        MyAnalysisData::AccessPattern(a) => a.as_vec().last().copied(),
+        _ => None,
+    })
+}
+
+/// The product of the lengths of `axes` of the access bound to `var`, e.g. of
+/// the input channel and kernel axes of convolution weights.
+fn access_axes(var: &str, axes: std::ops::Range<usize>) -> Depth {
+    let var: Var = var.parse().unwrap();
+    Box::new(move |egraph, subst| match &egraph[*subst.get(var)?].data {
+        MyAnalysisData::AccessPattern(a) => Some(a.as_vec().get(axes.clone())?.iter().product()),
+        _ => None,
+    })
+}
+
+/// The number of elements in the shape bound to `var`, e.g. of a pooling
+/// window.
+fn shape_size(var: &str) -> Depth {
+    let var: Var = var.parse().unwrap();
+    Box::new(move |egraph, subst| match &egraph[*subst.get(var)?].data {
+        MyAnalysisData::Shape(s) => Some(s.shape.slice().iter().product()),
+        _ => None,
+    })
+}
+
+/// The number bound to `var`, plus `extra`.
+fn num_plus(var: &str, extra: usize) -> Depth {
+    let var: Var = var.parse().unwrap();
+    Box::new(move |egraph, subst| match &egraph[*subst.get(var)?].data {
+        MyAnalysisData::Num(n) => Some(usize::try_from(*n).ok()? + extra),
+        _ => None,
+    })
+}
+
 fn constrain_vars(
     vars: Vec<Var>,
     constraint: impl Fn(Vec<MyAnalysisData>) -> bool,
@@ -962,7 +1050,7 @@ pub fn add_bias_add_to_dense() -> RW {
 /// array as long as:
 ///  - N is a multiple of `rows`, and
 ///  - O is a multiple of `columns`.
-pub fn systolic_array_with_blocking(rows: usize, cols: usize) -> Rewrite<Language, MyAnalysis> {
+pub fn systolic_array_with_blocking(rows: usize, cols: usize) -> AtomRewrite {
     struct ApplierImpl {
         rows: usize,
         cols: usize,
@@ -1000,26 +1088,30 @@ pub fn systolic_array_with_blocking(rows: usize, cols: usize) -> Rewrite<Languag
         }
     }
 
-    rewrite!(format!("systolic-array-with-blocking-{}-{}", rows, cols);
-             "(compute dot-product
-               (access-cartesian-product
-                ?access-1
-                ?access-2
-               )
-              )
-             " =>
-             { ApplierImpl{rows, cols, a: "?access-1".parse().unwrap(), b: "?access-2".parse().unwrap(),}}
-             // Remember: the accesses look like [M] [N] and [O] [N] for an
-             // MxN X NxO multiplication.
-             if constrain_access("?access-1".parse().unwrap(),
-                                 move |a| a.shape.ndim() <= 1 && a.item_shape.ndim() == 1
-                                          && a.item_shape.slice()[0] % rows == 0)
-             if constrain_access("?access-2".parse().unwrap(),
-                                 move |a| a.shape.ndim() == 1 && a.item_shape.ndim() == 1
-                                          && a.shape.slice()[0] % cols == 0))
+    AtomRewrite::new(
+        rewrite!(format!("systolic-array-with-blocking-{}-{}", rows, cols);
+                 "(compute dot-product
+                   (access-cartesian-product
+                    ?access-1
+                    ?access-2
+                   )
+                  )
+                 " =>
+                 { ApplierImpl{rows, cols, a: "?access-1".parse().unwrap(), b: "?access-2".parse().unwrap(),}}
+                 // Remember: the accesses look like [M] [N] and [O] [N] for an
+                 // MxN X NxO multiplication.
+                 if constrain_access("?access-1".parse().unwrap(),
+                                     move |a| a.shape.ndim() <= 1 && a.item_shape.ndim() == 1
+                                              && a.item_shape.slice()[0] % rows == 0)
+                 if constrain_access("?access-2".parse().unwrap(),
+                                     move |a| a.shape.ndim() == 1 && a.item_shape.ndim() == 1
+                                              && a.shape.slice()[0] % cols == 0)),
+        &["?access-1", "?access-2"],
+        Some(last_item_axis("?access-1")),
+    )
 }
 
-pub fn systolic_array() -> Rewrite<Language, MyAnalysis> {
+pub fn systolic_array() -> AtomRewrite {
     struct ApplierImpl {
         a: Var,
         b: Var,
@@ -1057,7 +1149,227 @@ pub fn systolic_array() -> Rewrite<Language, MyAnalysis> {
         }
     }
 
-    rewrite!("systolic-array";
+    AtomRewrite::new(
+        rewrite!("systolic-array";
+                 "(compute dot-product
+                   (access-cartesian-product
+                    ?access-1
+                    ?access-2
+                   )
+                  )
+                 " =>
+                 { ApplierImpl{a: "?access-1".parse().unwrap(), b: "?access-2".parse().unwrap(),}}
+                 if constrain_access("?access-1".parse().unwrap(),
+                                     |a| a.shape.ndim() <= 1 && a.item_shape.ndim() == 1)
+                 if constrain_access("?access-2".parse().unwrap(),
+                                     |a| a.shape.ndim() == 1 && a.item_shape.ndim() == 1)),
+        &["?access-1", "?access-2"],
+        Some(last_item_axis("?access-1")),
+    )
+}
+
+/// Maps int32-accumulating dot products of int8 values, i.e. the matrix
+/// multiplications of the int8 regions of a mixed-precision program (see
+/// [`mixed_precision`](super::mixed_precision)), onto an int8 systolic array.
+/// Like [`systolic_array()`], the array is sized to fit the multiplication.
+pub fn int8_systolic_array() -> AtomRewrite {
+    struct ApplierImpl {
+        b: Var,
+    }
+    impl Applier<Language, MyAnalysis> for ApplierImpl {
+        fn apply_one(
+            &self,
+            egraph: &mut EG,
+            eclass: Id,
+            subst: &Subst,
+            searcher_ast: Option<&PatternAst<Language>>,
+            rule_name: Symbol,
+        ) -> Vec<Id> {
+            let b = match &egraph[subst[self.b]].data {
+                MyAnalysisData::AccessPattern(b) => b,
+                _ => panic!(),
+            };
+            let rows: usize = b.item_shape.slice()[0];
+            let cols: usize = b.shape.slice()[0];
+
+            let pattern: Pattern<Language> = format!(
+                "(systolic-array {} {}
+                  ?access-1
+                  (access (access-transpose ?access-2 (list 1 0)) 0))",
+                rows, cols
+            )
+            .parse()
+            .unwrap();
+
+            pattern.apply_one(egraph, eclass, subst, searcher_ast, rule_name)
+        }
+    }
+
+    fn is_int8(var: &'static str) -> impl Fn(&mut EG, egg::Id, &egg::Subst) -> bool {
+        let var = var.parse().unwrap();
+        move |egraph, _, subst| {
+            crate::legality::element_dtype(egraph, subst[var])
+                == Some(crate::language::DataType::Int(8))
+        }
+    }
+
+    AtomRewrite::new(
+        rewrite!("int8-systolic-array";
+                 "(compute-with-accumulator dot-product int32
+                   (access-cartesian-product ?access-1 ?access-2))" =>
+                 { ApplierImpl{ b: "?access-2".parse().unwrap() } }
+                 if constrain_access("?access-1".parse().unwrap(),
+                                     |a| a.shape.ndim() <= 1 && a.item_shape.ndim() == 1)
+                 if constrain_access("?access-2".parse().unwrap(),
+                                     |a| a.shape.ndim() == 1 && a.item_shape.ndim() == 1)
+                 if is_int8("?access-1")
+                 if is_int8("?access-2")),
+        &["?access-1", "?access-2"],
+        Some(last_item_axis("?access-1")),
+    )
+}
+
+/// Marks dot products with the weights named in `sparse_weights` as sparse
+/// dot products, which can then be mapped to a sparsity-aware atom (see
+/// [`sparse_systolic_array()`]). Generally, `sparse_weights` are the weights
+/// which are sparse in the interpreter's
+/// [`SparseEnvironment`](crate::language::sparse::SparseEnvironment).
+pub fn sparse_dot_product(sparse_weights: std::collections::HashSet<String>) -> RW {
+    let weight: Var = "?weight".parse().unwrap();
+    rewrite!("sparse-dot-product";
+             "(compute dot-product
+               (access-cartesian-product ?a (access (access-tensor ?weight) 1)))" =>
+             "(compute sparse-dot-product
+               (access-cartesian-product ?a (access (access-tensor ?weight) 1)))"
+             if move |egraph: &mut EG, _, subst: &Subst| egraph[subst[weight]]
+                 .nodes
+                 .iter()
+                 .any(|node| matches!(node, Language::Symbol(name) if sparse_weights.contains(name))))
+}
+
+/// Maps sparse dot products onto a sparse systolic array. Unlike
+/// [`systolic_array()`], the weights aren't transposed, as the array reads the
+/// weights' rows as they're stored.
+pub fn sparse_systolic_array() -> AtomRewrite {
+    struct ApplierImpl {
+        weights: Var,
+    }
+    impl Applier<Language, MyAnalysis> for ApplierImpl {
+        fn apply_one(
+            &self,
+            egraph: &mut EG,
+            eclass: Id,
+            subst: &Subst,
+            searcher_ast: Option<&PatternAst<Language>>,
+            rule_name: Symbol,
+        ) -> Vec<Id> {
+            let weights = match &egraph[subst[self.weights]].data {
+                MyAnalysisData::AccessPattern(a) => a,
+                _ => panic!(),
+            };
+            let rows: usize = weights.item_shape.slice()[0];
+            let cols: usize = weights.shape.slice()[0];
+
+            let pattern: Pattern<Language> = format!(
+                "(sparse-systolic-array {} {} ?access-1 ?access-2)",
+                rows, cols
+            )
+            .parse()
+            .unwrap();
+
+            pattern.apply_one(egraph, eclass, subst, searcher_ast, rule_name)
+        }
+    }
+
+    AtomRewrite::new(
+        rewrite!("sparse-systolic-array";
+                 "(compute sparse-dot-product (access-cartesian-product ?access-1 ?access-2))" =>
+                 { ApplierImpl{ weights: "?access-2".parse().unwrap() } }
+                 if constrain_access("?access-1".parse().unwrap(),
+                                     |a| a.shape.ndim() <= 1 && a.item_shape.ndim() == 1)
+                 if constrain_access("?access-2".parse().unwrap(),
+                                     |a| a.shape.ndim() == 1 && a.item_shape.ndim() == 1)),
+        &["?access-1", "?access-2"],
+        Some(last_item_axis("?access-1")),
+    )
+}
+
+/// Zero-pads a dense (M,K)x(K,N) multiplication so that it fits on a
+/// `rows`x`cols` systolic array with blocking, slicing the extra output
+/// columns back off afterwards. The reduction axis (K) is padded up to a
+/// multiple of `rows`, which doesn't change the result, as the padding
+/// contributes only zeros to each dot product. The output axis (N) is padded
+/// up to a multiple of `cols`. Combined with [`systolic_array_with_blocking`],
+/// this lets us map dense layers whose dimensions don't divide evenly into the
+/// array size.
+pub fn pad_dense_for_systolic_array_with_blocking(
+    rows: usize,
+    cols: usize,
+) -> Rewrite<Language, MyAnalysis> {
+    struct ApplierImpl {
+        rows: usize,
+        cols: usize,
+        a: Var,
+        b: Var,
+    }
+    impl Applier<Language, MyAnalysis> for ApplierImpl {
+        fn apply_one(
+            &self,
+            egraph: &mut EG,
+            eclass: Id,
+            subst: &Subst,
+            _searcher_ast: Option<&PatternAst<Language>>,
+            _rule_name: Symbol,
+        ) -> Vec<Id> {
+            let (a, b) = match (&egraph[subst[self.a]].data, &egraph[subst[self.b]].data) {
+                (MyAnalysisData::AccessPattern(a), MyAnalysisData::AccessPattern(b)) => (a, b),
+                _ => panic!(),
+            };
+            assert_eq!(a.item_shape.ndim(), 1);
+            assert_eq!(b.shape.ndim(), 1);
+            assert_eq!(b.item_shape.ndim(), 1);
+            assert_eq!(a.item_shape[0], b.item_shape[0]);
+
+            let k = b.item_shape[0];
+            let n = b.shape[0];
+            let k_pad = (self.rows - (k % self.rows)) % self.rows;
+            let n_pad = (self.cols - (n % self.cols)) % self.cols;
+            // The reduction axis is the first (only) item axis of each access.
+            let a_k_axis = a.shape.ndim();
+            // The output axis corresponding to N comes after all of the shape
+            // axes of ?access-1.
+            let out_n_axis = a.shape.ndim();
+
+            let mut new_a = "?access-1".to_string();
+            let mut new_b = "?access-2".to_string();
+            if k_pad > 0 {
+                new_a = format!(
+                    "(access-pad {} zero-padding {} 0 {})",
+                    new_a, a_k_axis, k_pad
+                );
+                new_b = format!("(access-pad {} zero-padding 1 0 {})", new_b, k_pad);
+            }
+            if n_pad > 0 {
+                new_b = format!("(access-pad {} zero-padding 0 0 {})", new_b, n_pad);
+            }
+
+            let pattern: Pattern<Language> = format!(
+                "(access-slice
+                  (compute dot-product
+                   (access-cartesian-product {} {})
+                  )
+                  {} 0 {}
+                 )",
+                new_a, new_b, out_n_axis, n
+            )
+            .parse()
+            .unwrap();
+
+            pattern.apply_one(egraph, eclass, subst, _searcher_ast, _rule_name)
+        }
+    }
+
+    rewrite!(format!("pad-dense-for-systolic-array-with-blocking-{}-{}", rows, cols);
              "(compute dot-product
                (access-cartesian-product
                 ?access-1
@@ -1065,11 +1377,15 @@ pub fn systolic_array() -> Rewrite<Language, MyAnalysis> {
                )
               )
              " =>
-             { ApplierImpl{a: "?access-1".parse().unwrap(), b: "?access-2".parse().unwrap(),}}
+             { ApplierImpl{rows, cols, a: "?access-1".parse().unwrap(), b: "?access-2".parse().unwrap(),}}
              if constrain_access("?access-1".parse().unwrap(),
                                  |a| a.shape.ndim() <= 1 && a.item_shape.ndim() == 1)
+             // Only fire when padding is actually needed; otherwise
+             // systolic_array_with_blocking already applies.
              if constrain_access("?access-2".parse().unwrap(),
-                                 |a| a.shape.ndim() == 1 && a.item_shape.ndim() == 1))
+                                 move |a| a.shape.ndim() == 1 && a.item_shape.ndim() == 1
+                                          && (a.item_shape[0] % rows != 0
+                                              || a.shape[0] % cols != 0)))
 }
 
 pub enum SliceConcatenateStrategy {
@@ -1088,6 +1404,54 @@ pub enum SliceConcatenateStrategy {
     SliceOnce {
         segment_size: usize,
     },
+    /// Divides the axis into segments of each size given by `factors` which
+    /// evenly divides it, all in one rewrite.
+    Tile {
+        factors: TilingFactors,
+    },
+}
+
+/// Strategies for choosing the candidate tile sizes (i.e. segment sizes) when
+/// tiling an axis. Each candidate multiplies the number of ways an axis can be
+/// tiled, so the choice of strategy controls the size of the design space.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TilingFactors {
+    /// Powers of two, up to and including `max` (generally the size of the
+    /// systolic array).
+    PowersOfTwo { max: usize },
+    /// Every divisor of the axis' length.
+    Divisors,
+    /// The given sizes.
+    List(Vec<usize>),
+}
+
+impl TilingFactors {
+    /// The tile sizes to try on an axis of length `dim`, in increasing order.
+    /// Only sizes which evenly divide `dim` are included, and tiles of size 1
+    /// or of the whole axis are never included.
+    pub fn factors(&self, dim: usize) -> Vec<usize> {
+        let candidates: Vec<usize> = match self {
+            TilingFactors::PowersOfTwo { max } => std::iter::successors(Some(2), |f| Some(f * 2))
+                .take_while(|f| f <= max)
+                .collect(),
+            TilingFactors::Divisors => (2..dim).collect(),
+            TilingFactors::List(factors) => factors.iter().cloned().sorted().dedup().collect(),
+        };
+        candidates
+            .into_iter()
+            .filter(|f| *f > 1 && *f < dim && dim % f == 0)
+            .collect()
+    }
+}
+
+impl std::fmt::Display for TilingFactors {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TilingFactors::PowersOfTwo { max } => write!(f, "powers-of-two-up-to-{}", max),
+            TilingFactors::Divisors => write!(f, "divisors"),
+            TilingFactors::List(factors) => write!(f, "factors-{}", factors.iter().join("-")),
+        }
+    }
 }
 
 pub fn slice_concatenate_accesses(
@@ -1178,13 +1542,48 @@ pub fn slice_concatenate_accesses(
         }
     }
 
-    // TODO(@gussmith23) This could be combined with the applier above.
-    // Their implementations are nearly identical.
-    struct DivideIntoApplier {
+    /// Slices the access `id` into segments of length `segment_size` along
+    /// `axis`, and concatenates them back together.
+    fn divide_into(egraph: &mut EG, id: Id, axis: usize, segment_size: usize) -> Id {
+        let dim_value = match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => a[axis],
+            _ => panic!(),
+        };
+        assert_eq!(dim_value % segment_size, 0);
+
+        let axis_id = egraph.add(Language::Num(axis.try_into().unwrap()));
+
+        (0..(dim_value / segment_size))
+            .map(|segment_index| {
+                let low_bound = segment_index * segment_size;
+                let high_bound = low_bound + segment_size;
+                let low_bound_id = egraph.add(Language::Num(low_bound.try_into().unwrap()));
+                let high_bound_id = egraph.add(Language::Num(high_bound.try_into().unwrap()));
+                egraph.add(Language::AccessSlice([
+                    id,
+                    axis_id,
+                    low_bound_id,
+                    high_bound_id,
+                ]))
+            })
+            .collect::<Vec<_>>()
+            .iter()
+            .fold(None, |prev_concat_id, this_slice_id| match prev_concat_id {
+                None => Some(*this_slice_id),
+                Some(prev_concat_id) => Some(egraph.add(Language::AccessConcatenate([
+                    prev_concat_id,
+                    *this_slice_id,
+                    axis_id,
+                ]))),
+            })
+            .unwrap()
+    }
+
+    struct TileApplier {
         axis: usize,
-        segment_size: usize,
+        factors: TilingFactors,
     }
-    impl Applier<Language, MyAnalysis> for DivideIntoApplier {
+    impl Applier<Language, MyAnalysis> for TileApplier {
         fn apply_one(
             &self,
             egraph: &mut EG,
@@ -1193,44 +1592,36 @@ pub fn slice_concatenate_accesses(
             _searcher_ast: Option<&PatternAst<Language>>,
             _rule_name: Symbol,
         ) -> std::vec::Vec<egg::Id> {
-            let shape = match &egraph[id].data {
-                MyAnalysisData::AccessPattern(a) => a,
+            let dim_value = match &egraph[id].data {
+                MyAnalysisData::AccessPattern(a) => a[self.axis],
                 _ => panic!(),
             };
-            assert!(self.axis < shape.shape.ndim() + shape.item_shape.ndim());
-            let dim_value = if self.axis < shape.shape.ndim() {
-                shape.shape[self.axis]
-            } else {
-                shape.item_shape[self.axis - shape.shape.ndim()]
-            };
-            assert_eq!(dim_value % self.segment_size, 0);
-
-            let axis_id = egraph.add(Language::Num(self.axis.try_into().unwrap()));
-
-            let top_concat_id = (0..(dim_value / self.segment_size))
-                .map(|segment_index| {
-                    let low_bound = segment_index * self.segment_size;
-                    let high_bound = low_bound + self.segment_size;
-                    let low_bound_id = egraph.add(Language::Num(low_bound.try_into().unwrap()));
-                    let high_bound_id = egraph.add(Language::Num(high_bound.try_into().unwrap()));
-                    egraph.add(Language::AccessSlice([
-                        id,
-                        axis_id,
-                        low_bound_id,
-                        high_bound_id,
-                    ]))
-                })
-                .collect::<Vec<_>>()
-                .iter()
-                .fold(None, |prev_concat_id, this_slice_id| match prev_concat_id {
-                    None => Some(*this_slice_id),
-                    Some(prev_concat_id) => Some(egraph.add(Language::AccessConcatenate([
-                        prev_concat_id,
-                        *this_slice_id,
-                        axis_id,
-                    ]))),
+            self.factors
+                .factors(dim_value)
+                .into_iter()
+                .map(|segment_size| {
+                    let concat_id = divide_into(egraph, id, self.axis, segment_size);
+                    egraph.union(id, concat_id);
+                    concat_id
                 })
-                .unwrap();
+                .collect()
+        }
+    }
+
+    struct DivideIntoApplier {
+        axis: usize,
+        segment_size: usize,
+    }
+    impl Applier<Language, MyAnalysis> for DivideIntoApplier {
+        fn apply_one(
+            &self,
+            egraph: &mut EG,
+            id: egg::Id,
+            _subst: &Subst,
+            _searcher_ast: Option<&PatternAst<Language>>,
+            _rule_name: Symbol,
+        ) -> std::vec::Vec<egg::Id> {
+            let top_concat_id = divide_into(egraph, id, self.axis, self.segment_size);
 
             egraph.union(id, top_concat_id);
 
@@ -1308,6 +1699,16 @@ pub fn slice_concatenate_accesses(
                      // Only slice if there's  at least `segment_size` to slice off.
                      if constrain_access("?a".parse().unwrap(), move |access| access[axis] > segment_size))
         }
+        SliceConcatenateStrategy::Tile { factors } => {
+            let name = format!("slice-concatenate-access-axis-{}-tile-{}", axis, factors);
+            let condition_factors = factors.clone();
+            rewrite!(name;
+                     "?a" => { TileApplier {axis, factors} }
+                     if is_access()
+                     if access_has_axis(axis)
+                     if constrain_access("?a".parse().unwrap(),
+                                         move |access| !condition_factors.factors(access[axis]).is_empty()))
+        }
     }
 }
 
@@ -2294,10 +2695,7 @@ pub fn bubble_access_transpose_through_access_pad() -> Rewrite<Language, MyAnaly
      })
 }
 
-pub fn systolic_array_conv2d_nchw_oihw_with_blocking(
-    rows: usize,
-    cols: usize,
-) -> Rewrite<Language, MyAnalysis> {
+pub fn systolic_array_conv2d_nchw_oihw_with_blocking(rows: usize, cols: usize) -> AtomRewrite {
     struct ApplierImpl {
         rows: usize,
         cols: usize,
@@ -2319,45 +2717,46 @@ pub fn systolic_array_conv2d_nchw_oihw_with_blocking(
                 .apply_one(egraph,matched_id, subst,_searcher_ast,_rule_name)
         }
     }
-    rewrite!(format!("systolic-array-conv2d-nchw-oihw-with-blocking-{}-{}", rows, cols);
-    "
-             (access-transpose
-              (compute dot-product
-               (access-cartesian-product
-                (access ?weights 1)
-                (access
-                 (access-squeeze
-                   (access-windows
-                    (access ?data 1)
-                    (shape ?c ?kh ?kw)
-                    (shape 1 ?stride-h ?stride-w)
+    AtomRewrite::new(
+        rewrite!(format!("systolic-array-conv2d-nchw-oihw-with-blocking-{}-{}", rows, cols);
+        "
+                 (access-transpose
+                  (compute dot-product
+                   (access-cartesian-product
+                    (access ?weights 1)
+                    (access
+                     (access-squeeze
+                       (access-windows
+                        (access ?data 1)
+                        (shape ?c ?kh ?kw)
+                        (shape 1 ?stride-h ?stride-w)
+                       )
+                      1
+                     )
+                     3
+                    )
                    )
-                  1
+                  )
+                  (list 1 0 2 3)
                  )
-                 3
-                )
-               )
-              )
-              (list 1 0 2 3)
-             )
-" => {
-                  ApplierImpl {rows, cols}
-              }
-             if constrain_access("?weights".parse().unwrap(),
-                                 move |a| a.shape.ndim() + a.item_shape.ndim() == 4
-                                 // Input channels divisible by rows, output
-                                 // channels divisible by columns.
-                                 && a[1] % rows == 0 && a[0] % cols == 0)
-             if constrain_access("?data".parse().unwrap(),
-                                 move |a| a.shape.ndim() + a.item_shape.ndim() == 4
-                                 // Input channels divisible by rows
-                                 && a[1] % rows == 0))
-}
-
-pub fn systolic_array_conv2d_nhwc_hwio_with_blocking(
-    rows: usize,
-    cols: usize,
-) -> Rewrite<Language, MyAnalysis> {
+    " => {
+                      ApplierImpl {rows, cols}
+                  }
+                 if constrain_access("?weights".parse().unwrap(),
+                                     move |a| a.shape.ndim() + a.item_shape.ndim() == 4
+                                     // Input channels divisible by rows, output
+                                     // channels divisible by columns.
+                                     && a[1] % rows == 0 && a[0] % cols == 0)
+                 if constrain_access("?data".parse().unwrap(),
+                                     move |a| a.shape.ndim() + a.item_shape.ndim() == 4
+                                     // Input channels divisible by rows
+                                     && a[1] % rows == 0)),
+        &["?weights", "?data"],
+        Some(access_axes("?weights", 1..4)),
+    )
+}
+
+pub fn systolic_array_conv2d_nhwc_hwio_with_blocking(rows: usize, cols: usize) -> AtomRewrite {
     struct ApplierImpl {
         rows: usize,
         cols: usize,
@@ -2379,41 +2778,45 @@ pub fn systolic_array_conv2d_nhwc_hwio_with_blocking(
                 .apply_one(egraph,matched_id, subst,_searcher_ast,_rule_name)
         }
     }
-    rewrite!(format!("systolic-array-conv2d-nhwc-hwio-with-blocking-{}-{}", rows, cols);
-    "       (access-transpose
-             (systolic-array-conv2d-nchw-oihw-with-blocking
-              ?rows ?cols
-              (access-transpose ?weights (list 3 2 0 1))
-              (access-transpose ?data (list 0 3 1 2))
-              ?kh ?kw ?stride-h ?stride-w
-             )
-             (list 0 2 3 1)
-            )" => {
-                  ApplierImpl {rows, cols}
-              }
-             if move |egraph: &mut EGraph<Language, MyAnalysis>, _, subst: &Subst| match &egraph[subst["?rows".parse().unwrap()]].data {
-                 MyAnalysisData::Num(l) => usize::try_from(*l).unwrap() == rows,
-                 _ => panic!(),
-             }
-             if move |egraph: &mut EGraph<Language, MyAnalysis>, _, subst: &Subst| match &egraph[subst["?cols".parse().unwrap()]].data {
-                 MyAnalysisData::Num(l) => usize::try_from(*l).unwrap() == cols,
-                 _ => panic!(),
-             }
-             if constrain_access("?weights".parse().unwrap(),
-                                 move |a| a.shape.ndim() + a.item_shape.ndim() == 4
-                                 // Input channels divisible by rows, output
-                                 // channels divisible by columns.
-                                 && a[2] % rows == 0 && a[3] % cols == 0)
-             if constrain_access("?data".parse().unwrap(),
-                                 move |a| a.shape.ndim() + a.item_shape.ndim() == 4
-                                 // Input channels divisible by rows
-                                 && a[3] % rows == 0))
+    AtomRewrite::new(
+        rewrite!(format!("systolic-array-conv2d-nhwc-hwio-with-blocking-{}-{}", rows, cols);
+        "       (access-transpose
+                 (systolic-array-conv2d-nchw-oihw-with-blocking
+                  ?rows ?cols
+                  (access-transpose ?weights (list 3 2 0 1))
+                  (access-transpose ?data (list 0 3 1 2))
+                  ?kh ?kw ?stride-h ?stride-w
+                 )
+                 (list 0 2 3 1)
+                )" => {
+                      ApplierImpl {rows, cols}
+                  }
+                 if move |egraph: &mut EGraph<Language, MyAnalysis>, _, subst: &Subst| match &egraph[subst["?rows".parse().unwrap()]].data {
+                     MyAnalysisData::Num(l) => usize::try_from(*l).unwrap() == rows,
+                     _ => panic!(),
+                 }
+                 if move |egraph: &mut EGraph<Language, MyAnalysis>, _, subst: &Subst| match &egraph[subst["?cols".parse().unwrap()]].data {
+                     MyAnalysisData::Num(l) => usize::try_from(*l).unwrap() == cols,
+                     _ => panic!(),
+                 }
+                 if constrain_access("?weights".parse().unwrap(),
+                                     move |a| a.shape.ndim() + a.item_shape.ndim() == 4
+                                     // Input channels divisible by rows, output
+                                     // channels divisible by columns.
+                                     && a[2] % rows == 0 && a[3] % cols == 0)
+                 if constrain_access("?data".parse().unwrap(),
+                                     move |a| a.shape.ndim() + a.item_shape.ndim() == 4
+                                     // Input channels divisible by rows
+                                     && a[3] % rows == 0)),
+        &["?weights", "?data"],
+        Some(access_axes("?weights", 0..3)),
+    )
 }
 
 pub fn systolic_array_conv2d_im2col_nchw_oihw_with_blocking(
     rows: usize,
     cols: usize,
-) -> Rewrite<Language, MyAnalysis> {
+) -> AtomRewrite {
     struct ApplierImpl {
         rows: usize,
         cols: usize,
@@ -2435,44 +2838,48 @@ pub fn systolic_array_conv2d_im2col_nchw_oihw_with_blocking(
                 .apply_one(egraph,matched_id, subst,_searcher_ast,_rule_name)
         }
     }
-    rewrite!(format!("systolic-array-conv2d-im2col-nchw-oihw-with-blocking-{}-{}", rows, cols);
-    "
-            (access-transpose
-             (access-reshape
-              (compute dot-product
-               (access-cartesian-product
-                (access-flatten (access ?weights 1))
-                (access-flatten
-                 (access
-                  (access-squeeze
-                    (access-windows
-                     (access ?data 1)
-                     (shape ?c ?kh ?kw)
-                     (shape 1 ?stride-h ?stride-w)
-                    )
-                   1
-                  )
-                  3
+    AtomRewrite::new(
+        rewrite!(format!("systolic-array-conv2d-im2col-nchw-oihw-with-blocking-{}-{}", rows, cols);
+        "
+                (access-transpose
+                 (access-reshape
+                  (compute dot-product
+                   (access-cartesian-product
+                    (access-flatten (access ?weights 1))
+                    (access-flatten
+                     (access
+                      (access-squeeze
+                        (access-windows
+                         (access ?data 1)
+                         (shape ?c ?kh ?kw)
+                         (shape 1 ?stride-h ?stride-w)
+                        )
+                       1
+                      )
+                      3
+                     )
+                    )
+                   )
+                  )
+                  ?reshape-shape
                  )
-                )
-               )
-              )
-              ?reshape-shape
-             )
-             (list 1 0 2 3)
-            )" => {
-                  ApplierImpl {rows, cols}
-              }
-             // TODO(@gussmith23) Any constraints on these?
-             // There may not be many constraints here, because Scott's
-             // implementing the tail padding himself.
-             )
+                 (list 1 0 2 3)
+                )" => {
+                      ApplierImpl {rows, cols}
+                  }
+                 // TODO(@gussmith23) Any constraints on these?
+                 // There may not be many constraints here, because Scott's
+                 // implementing the tail padding himself.
+                 ),
+        &["?weights", "?data"],
+        Some(access_axes("?weights", 1..4)),
+    )
 }
 
 pub fn systolic_array_conv2d_im2col_nhwc_hwio_with_blocking(
     rows: usize,
     cols: usize,
-) -> Rewrite<Language, MyAnalysis> {
+) -> AtomRewrite {
     struct ApplierImpl {
         rows: usize,
         cols: usize,
@@ -2494,21 +2901,51 @@ pub fn systolic_array_conv2d_im2col_nhwc_hwio_with_blocking(
                 .apply_one(egraph,matched_id, subst,_searcher_ast,_rule_name)
         }
     }
-    rewrite!(format!("systolic-array-conv2d-im2col-nhwc-hwio-with-blocking-{}-{}", rows, cols);
-    "       (access-transpose
-             (systolic-array-conv2d-im2col-nchw-oihw-with-blocking
-              ?rows ?cols
-              (access-transpose ?weights (list 3 2 0 1))
-              (access-transpose ?data (list 0 3 1 2))
-              ?kh ?kw ?stride-h ?stride-w
-             )
-             (list 0 2 3 1)
-            )" => {
-                  ApplierImpl {rows, cols}
-              }
-             // TODO(@gussmith23) Any constraints on these?
-             // There may not be many constraints here, because Scott's
-             // implementing the tail padding himself.
+    AtomRewrite::new(
+        rewrite!(format!("systolic-array-conv2d-im2col-nhwc-hwio-with-blocking-{}-{}", rows, cols);
+        "       (access-transpose
+                 (systolic-array-conv2d-im2col-nchw-oihw-with-blocking
+                  ?rows ?cols
+                  (access-transpose ?weights (list 3 2 0 1))
+                  (access-transpose ?data (list 0 3 1 2))
+                  ?kh ?kw ?stride-h ?stride-w
+                 )
+                 (list 0 2 3 1)
+                )" => {
+                      ApplierImpl {rows, cols}
+                  }
+                 // TODO(@gussmith23) Any constraints on these?
+                 // There may not be many constraints here, because Scott's
+                 // implementing the tail padding himself.
+        ),
+        &["?weights", "?data"],
+        Some(access_axes("?weights", 0..3)),
+    )
+}
+
+/// The inverse of [`systolic_array_conv2d_im2col_nhwc_hwio_with_blocking`]:
+/// rewrites an NCHW/OIHW im2col convolution into the channels-last NHWC/HWIO
+/// form, with its inputs and output transposed. Whichever layout the frontend
+/// used, this puts both layouts in the egraph; combined with
+/// [`collapse_nested_transposes`] and [`remove_trivial_transpose`], the
+/// transposes cancel against the frontend's own layout changes, and the cost
+/// function can pick the layout needing fewer transposes.
+pub fn systolic_array_conv2d_im2col_nchw_oihw_to_nhwc_hwio() -> AtomRewrite {
+    AtomRewrite::new(
+        rewrite!("systolic-array-conv2d-im2col-nchw-oihw-to-nhwc-hwio";
+                 "(systolic-array-conv2d-im2col-nchw-oihw-with-blocking
+                   ?rows ?cols ?weights ?data ?kh ?kw ?stride-h ?stride-w)" =>
+                 "(access-transpose
+                   (systolic-array-conv2d-im2col-nhwc-hwio-with-blocking
+                    ?rows ?cols
+                    (access-transpose ?weights (list 2 3 1 0))
+                    (access-transpose ?data (list 0 2 3 1))
+                    ?kh ?kw ?stride-h ?stride-w
+                   )
+                   (list 0 3 1 2)
+                  )"),
+        &["?weights", "?data"],
+        Some(access_axes("?weights", 1..4)),
     )
 }
 
@@ -2569,6 +3006,182 @@ pub fn flexasr_maxpool() -> Rewrite<Language, MyAnalysis> {
     }))
 }
 
+/// Whether `var` is a compute type which the pooling unit supports.
+fn is_pooling_compute_type(var: &'static str) -> impl Fn(&mut EG, egg::Id, &egg::Subst) -> bool {
+    let var = var.parse().unwrap();
+    move |egraph, _, subst| {
+        matches!(
+            &egraph[subst[var]].data,
+            MyAnalysisData::ComputeType(ComputeType::ReduceMax)
+                | MyAnalysisData::ComputeType(ComputeType::ReduceMean)
+        )
+    }
+}
+
+/// Maps max and average pooling, i.e. reduce-max and reduce-mean over
+/// windows, onto a pooling unit, so that they needn't be run on the systolic
+/// arrays.
+pub fn pooling_unit() -> AtomRewrite {
+    AtomRewrite::new(
+        rewrite!("pooling-unit";
+            "(compute ?pool-type (access-windows ?a ?window-shape ?stride-shape))" =>
+            "(pooling-unit ?pool-type ?a ?window-shape ?stride-shape)"
+            if is_pooling_compute_type("?pool-type")),
+        &["?a"],
+        Some(shape_size("?window-shape")),
+    )
+}
+
+/// Whether `var` is an activation function which the activation unit supports.
+fn is_activation_compute_type(var: &'static str) -> impl Fn(&mut EG, egg::Id, &egg::Subst) -> bool {
+    let var = var.parse().unwrap();
+    move |egraph, _, subst| match &egraph[subst[var]].data {
+        MyAnalysisData::ComputeType(t) => t.is_activation(),
+        _ => false,
+    }
+}
+
+/// Whether `activation` can be applied to `access` by the activation unit
+/// (see [`ComputeType::runs_on_activation_unit`]).
+fn runs_on_activation_unit(
+    activation: &'static str,
+    access: &'static str,
+) -> impl Fn(&mut EG, egg::Id, &egg::Subst) -> bool {
+    let activation = activation.parse().unwrap();
+    let access = access.parse().unwrap();
+    move |egraph, _, subst| match (&egraph[subst[activation]].data, &egraph[subst[access]].data) {
+        (MyAnalysisData::ComputeType(ComputeType::Softmax), MyAnalysisData::AccessPattern(a)) => {
+            a.item_shape.ndim() == 1
+        }
+        (MyAnalysisData::ComputeType(t), _) => t.is_activation(),
+        _ => false,
+    }
+}
+
+/// Maps activation functions, and softmaxes over vectors, onto a standalone
+/// activation unit.
+pub fn activation_unit() -> AtomRewrite {
+    AtomRewrite::new(
+        rewrite!("activation-unit";
+            "(compute ?activation ?a)" =>
+            "(activation-unit ?activation ?a)"
+            if runs_on_activation_unit("?activation", "?a")),
+        &["?a"],
+        None,
+    )
+}
+
+/// Fuses an activation function into the systolic array which produces its
+/// input, so that the activation is applied as results leave the array. The
+/// extractor then chooses between this and a standalone activation unit (see
+/// [`activation_unit()`]).
+pub fn systolic_array_with_activation() -> AtomRewrite {
+    AtomRewrite::new(
+        rewrite!("systolic-array-with-activation";
+            "(compute ?activation (systolic-array ?rows ?cols ?a0 ?a1))" =>
+            "(systolic-array-with-activation ?activation ?rows ?cols ?a0 ?a1)"
+            if is_activation_compute_type("?activation")),
+        &["?a0", "?a1"],
+        Some(last_item_axis("?a0")),
+    )
+}
+
+/// Whether `var` is an elementwise compute type which the vector ALU supports.
+fn is_vector_alu_compute_type(var: &'static str) -> impl Fn(&mut EG, egg::Id, &egg::Subst) -> bool {
+    let var = var.parse().unwrap();
+    move |egraph, _, subst| {
+        matches!(
+            &egraph[subst[var]].data,
+            MyAnalysisData::ComputeType(ComputeType::ElementwiseAdd)
+                | MyAnalysisData::ComputeType(ComputeType::ElementwiseMul)
+        )
+    }
+}
+
+/// Maps elementwise additions and multiplications of pairs of `lanes`-element
+/// vectors onto a vector ALU with `lanes` lanes. Larger elementwise operations
+/// must first be split up by [`split_elementwise_for_vector_alu()`].
+pub fn vector_alu(lanes: usize) -> AtomRewrite {
+    AtomRewrite::new(
+        rewrite!(format!("vector-alu-{}", lanes);
+                 "(compute ?op ?a)" =>
+                 { format!("(vector-alu ?op {} ?a)", lanes).parse::<Pattern<Language>>().unwrap() }
+                 if is_vector_alu_compute_type("?op")
+                 if constrain_access("?a".parse().unwrap(),
+                                     move |a| a.item_shape.slice() == [2, lanes])),
+        &["?a"],
+        None,
+    )
+}
+
+/// Splits an elementwise addition or multiplication of two accesses into
+/// elementwise operations over pairs of `lanes`-element vectors, each of which
+/// is one invocation of a vector ALU (see [`vector_alu()`]). Both accesses are
+/// flattened into vectors of `lanes` elements, and the result is reshaped
+/// back. The accesses must have the same shape, and their number of elements
+/// must be a multiple of `lanes`.
+pub fn split_elementwise_for_vector_alu(lanes: usize) -> RW {
+    fn accesses_match(a0: Var, a1: Var) -> impl Fn(&mut EG, egg::Id, &egg::Subst) -> bool {
+        move |egraph, _, subst| match (&egraph[subst[a0]].data, &egraph[subst[a1]].data) {
+            (MyAnalysisData::AccessPattern(a0), MyAnalysisData::AccessPattern(a1)) => {
+                a0.shape == a1.shape && a0.item_shape == a1.item_shape
+            }
+            _ => false,
+        }
+    }
+    struct ApplierImpl {
+        lanes: usize,
+        a0: Var,
+    }
+    impl Applier<Language, MyAnalysis> for ApplierImpl {
+        fn apply_one(
+            &self,
+            egraph: &mut EG,
+            eclass: Id,
+            subst: &Subst,
+            _searcher_ast: Option<&PatternAst<Language>>,
+            _rule_name: Symbol,
+        ) -> Vec<Id> {
+            let a0 = match &egraph[subst[self.a0]].data {
+                MyAnalysisData::AccessPattern(a) => a,
+                _ => panic!(),
+            };
+            let to_string = |dims: &[usize]| {
+                dims.iter()
+                    .map(|d| d.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            };
+            let invocations = a0.as_vec().iter().product::<usize>() / self.lanes;
+
+            format!(
+                "(access-reshape
+                  (compute ?op
+                   (access-pair
+                    (access-reshape ?a0 (access-shape (shape {invocations}) (shape {lanes})))
+                    (access-reshape ?a1 (access-shape (shape {invocations}) (shape {lanes})))))
+                  (access-shape (shape {shape}) (shape {item_shape})))",
+                invocations = invocations,
+                lanes = self.lanes,
+                shape = to_string(a0.shape.slice()),
+                item_shape = to_string(a0.item_shape.slice()),
+            )
+            .parse::<Pattern<Language>>()
+            .unwrap()
+            .apply_one(egraph, eclass, subst, _searcher_ast, _rule_name)
+        }
+    }
+    rewrite!(format!("split-elementwise-for-vector-alu-{}", lanes);
+             "(compute ?op (access-pair ?a0 ?a1))" =>
+             { ApplierImpl { lanes, a0: "?a0".parse().unwrap() } }
+             if is_vector_alu_compute_type("?op")
+             if accesses_match("?a0".parse().unwrap(), "?a1".parse().unwrap())
+             // Don't split accesses which are already split.
+             if constrain_access("?a0".parse().unwrap(),
+                                 move |a| a.item_shape.slice() != [lanes]
+                                          && a.as_vec().iter().product::<usize>() % lanes == 0))
+}
+
 /// Breaks a large reduce-max into smaller reduce-maxes which are then reduced
 /// by the original reduce-max.
 pub fn reassociate_max(window_len: usize, strides: usize) -> RW {
@@ -2956,183 +3569,247 @@ pub fn conv1d_relay_to_glenside() -> RW {
             .parse::<Pattern<Language>>().unwrap() } => { i })
 }
 
-pub fn softmax_relay_to_glenside() -> RW {
+/// Lowers the high-level `conv2d` node into the access-windows formulation
+/// built by [`from_relay::conv2d`]. Only ungrouped and depthwise convolutions
+/// can be lowered; other group counts are left as they are.
+pub fn conv2d_to_access_windows() -> RW {
     struct Impl {
         data: Var,
-        axis: Var,
+        weights: Var,
+        strides: Var,
+        padding: Var,
+        groups: Var,
     }
     impl Applier<Language, MyAnalysis> for Impl {
         fn apply_one(
             &self,
             egraph: &mut EGraph<Language, MyAnalysis>,
-            _eclass: Id,
+            eclass: Id,
             subst: &Subst,
             _searcher_ast: Option<&PatternAst<Language>>,
             _rule_name: Symbol,
         ) -> Vec<Id> {
-            let data = match &egraph[subst[self.data]].data {
-                MyAnalysisData::AccessPattern(data) => data,
-                _ => panic!(),
-            };
-            let axis: i64 = match &egraph[subst[self.axis]].data {
-                MyAnalysisData::Num(u) => *u,
-                _ => panic!(),
+            let (data, weights, strides, padding, groups) = match vec![
+                self.data,
+                self.weights,
+                self.strides,
+                self.padding,
+                self.groups,
+            ]
+            .drain(..)
+            .map(|v| &egraph[subst[v]].data)
+            .collect::<Vec<_>>()[..]
+            {
+                [MyAnalysisData::AccessPattern(data), MyAnalysisData::AccessPattern(weights), MyAnalysisData::Shape(ShapeData { shape: strides, .. }), MyAnalysisData::Shape(ShapeData { shape: padding, .. }), MyAnalysisData::Num(groups)] => {
+                    (data, weights, strides, padding, *groups)
+                }
+                _ => panic!("Cannot parse arguments for conv2d"),
             };
 
-            assert_eq!(
-                axis, -1,
-                "We only support an axis value of -1 at the moment"
+            let mut expr = RecExpr::default();
+            let data_id = expr.add(Language::Symbol("data_PLACEHOLDER".to_string()));
+            let weights_id = expr.add(Language::Symbol("weights_PLACEHOLDER".to_string()));
+            from_relay::conv2d(
+                &mut expr,
+                data_id,
+                data.as_vec().as_slice(),
+                weights_id,
+                weights.as_vec().as_slice(),
+                strides.slice(),
+                padding.slice(),
+                &[1, 1],
+                groups.try_into().unwrap(),
+                "NCHW",
+                "OIHW",
+                "",
+                false,
             );
 
-            let axis: i64 = if axis < 0 {
-                data.as_vec().len() as i64 + axis
-            } else {
-                axis
-            };
-            assert!(axis >= 0 && axis < data.as_vec().len() as i64);
+            let pattern_ast = PatternAst::from(
+                expr.as_ref()
+                    .iter()
+                    .map(|n| match n {
+                        Language::Symbol(s) if s == "data_PLACEHOLDER" => {
+                            ENodeOrVar::Var(self.data)
+                        }
+                        Language::Symbol(s) if s == "weights_PLACEHOLDER" => {
+                            ENodeOrVar::Var(self.weights)
+                        }
+                        _ => ENodeOrVar::ENode(n.clone()),
+                    })
+                    .collect::<Vec<_>>(),
+            );
 
-            format!("(compute softmax (access {} {}))", self.data, axis)
-                .parse::<Pattern<Language>>()
-                .unwrap()
-                .apply_one(egraph, _eclass, subst, _searcher_ast, _rule_name)
+            let out_id = egraph.add_instantiation(&pattern_ast, subst);
+            egraph.union(eclass, out_id);
+            vec![out_id]
         }
     }
     let i = Impl {
         data: "?data".parse().unwrap(),
-        axis: "?axis".parse().unwrap(),
+        weights: "?weights".parse().unwrap(),
+        strides: "?strides".parse().unwrap(),
+        padding: "?padding".parse().unwrap(),
+        groups: "?groups".parse().unwrap(),
     };
-    rewrite!("softmax-relay-to-glenside";
-        { format!("(relay-operator-call relay-softmax {} {})",
-                    i.data, i.axis)
-            .parse::<Pattern<Language>>().unwrap() } => { i })
-}
-
-pub fn max_pool2d_relay_to_glenside_nchw() -> RW {
-    rewrite!("max-pool2d-relay-to-glenside-nchw";
-        "(relay-operator-call relay-max-pool2d
-            ?data
-            ?pool-size
-            ?strides
-            (shape ?padding0 ?padding1 ?padding2 ?padding3)
-            relay-activation-layout-nchw)" =>
-        "(compute reduce-max
-          (access-windows
-           (access
-            (access-pad
-             (access-pad
-              (access-tensor data)
-              min-padding
-              2 ?padding0 ?padding2
-             )
-             min-padding
-             3 ?padding1 ?padding3
-            )
-            2
-           )
-           ?pool-size
-           ?strides
-          )
-         )")
-}
-
-pub fn global_avg_pool2d_relay_to_glenside_nchw() -> RW {
-    rewrite!("global-avg-pool2d-relay-to-glenside-nchw";
-        "(relay-operator-call relay-global-avg-pool2d
-            ?data
-            relay-activation-layout-nchw)" =>
-        "(access
-          (access-insert-axis
-           (access-insert-axis
-            (compute reduce-mean (access ?data 2))
-            2
-           )
-           3
-          )
-          2
-         )")
-}
-
-// TODO(gussmith23) on second thought, remove this; doesn't really do anything.
-macro_rules! relay_to_glenside_simple {
-    ($fn_name: ident, $rw_name: expr, $from: expr, $to: expr) => {
-        pub fn $fn_name() -> RW {
-            rewrite!($rw_name; $from => $to)
+    let (data, weights, groups) = (i.data, i.weights, i.groups);
+    rewrite!("conv2d-to-access-windows";
+        { format!("(conv2d {} {} {} {} {})",
+                    i.data, i.weights, i.strides, i.padding, i.groups)
+            .parse::<Pattern<Language>>().unwrap() } => {
+        ConditionalApplier {
+            applier: i,
+            condition: move |egraph: &mut EG, _id: Id, subst: &Subst| {
+                let groups = MyAnalysis::get_usize(subst[groups], egraph);
+                match (&egraph[subst[data]].data, &egraph[subst[weights]].data) {
+                    (MyAnalysisData::AccessPattern(data), MyAnalysisData::AccessPattern(weights)) => {
+                        // Depthwise convolutions are lowered one channel at
+                        // a time, which requires one output channel per group.
+                        groups == 1 || (groups == data[1] && weights[0] == data[1])
+                    }
+                    _ => false,
+                }
+            },
         }
-    }
+    })
 }
 
-relay_to_glenside_simple!(
-    relu_relay_to_glenside,
-    "relu-relay-to-glenside",
-    "(relay-operator-call relay-relu ?data)",
-    "(compute relu ?data)"
-);
-
-relay_to_glenside_simple!(
-    negative_relay_to_glenside,
-    "negative-relay-to-glenside",
-    "(relay-operator-call relay-negative ?data)",
-    "(compute negative ?data)"
-);
-
-relay_to_glenside_simple!(
-    sqrt_relay_to_glenside,
-    "sqrt-relay-to-glenside",
-    "(relay-operator-call relay-sqrt ?data)",
-    "(compute sqrt ?data)"
-);
-
-pub fn expand_dims_relay_to_glenside() -> RW {
-    #[derive(Clone)]
-    struct Impl {
-        num_newaxis: Var,
-    }
-    impl Applier<Language, MyAnalysis> for Impl {
+/// Lowers an ungrouped `conv2d` with a 1x1 kernel directly to a matrix
+/// multiplication. Such a convolution multiplies the `[C]` vector at each
+/// pixel by the `[O, C]` weights, so there's no need to form windows over the
+/// channels: the data is flattened to an `[N*H*W, C]` matrix, multiplied
+/// against the weights in the form [`systolic_array`] expects, and reshaped
+/// back to `[N, O, H, W]`. Padding becomes `access-pad`s of the data, which
+/// are only added when the padding is nonzero. Strided convolutions (e.g.
+/// ResNet's downsampling shortcuts) first pick out every stride'th pixel with
+/// 1x1 `access-windows`.
+pub fn conv2d_1x1_to_matmul() -> RW {
+    struct ApplierImpl;
+    impl Applier<Language, MyAnalysis> for ApplierImpl {
         fn apply_one(
             &self,
-            egraph: &mut EGraph<Language, MyAnalysis>,
-            _eclass: Id,
+            egraph: &mut EG,
+            eclass: Id,
             subst: &Subst,
             _searcher_ast: Option<&PatternAst<Language>>,
             _rule_name: Symbol,
         ) -> Vec<Id> {
-            let num_newaxis: i64 = match &egraph[subst[self.num_newaxis]].data {
-                MyAnalysisData::Num(u) => *u as i64,
-                _ => panic!(),
+            let (data, weights) = match (
+                &egraph[subst["?data".parse().unwrap()]].data,
+                &egraph[subst["?weights".parse().unwrap()]].data,
+            ) {
+                (MyAnalysisData::AccessPattern(data), MyAnalysisData::AccessPattern(weights)) => {
+                    (data.as_vec(), weights.as_vec())
+                }
+                _ => panic!("Cannot parse arguments for conv2d"),
             };
+            let strides =
+                MyAnalysis::get_shape_of_value(subst["?strides".parse().unwrap()], egraph).clone();
+            let padding =
+                MyAnalysis::get_shape_of_value(subst["?padding".parse().unwrap()], egraph).clone();
+            let (n, c, o) = (data[0], data[1], weights[0]);
+            let (h, w) = match super::access_windows_resulting_shape(
+                &IxDyn(&[
+                    padding[0] + data[2] + padding[2],
+                    padding[1] + data[3] + padding[3],
+                ]),
+                &IxDyn(&[1, 1]),
+                &strides,
+            )[..]
+            {
+                [h, w] => (h, w),
+                _ => unreachable!(),
+            };
+
+            let mut padded = "?data".to_string();
+            for (axis, before, after) in &[(2, padding[0], padding[2]), (3, padding[1], padding[3])]
+            {
+                if before + after > 0 {
+                    padded = format!(
+                        "(access-pad {} zero-padding {} {} {})",
+                        padded, axis, before, after
+                    );
+                }
+            }
+            if strides.slice() != [1, 1] {
+                padded = format!(
+                    "(access-squeeze
+                      (access-squeeze
+                       (access-windows (access {} 2) (shape 1 1) (shape {} {}))
+                       5)
+                      4)",
+                    padded, strides[0], strides[1]
+                );
+            }
 
             format!(
-                "(relay-operator-call relay-expand-dims (access-insert-axis ?data ?axis) ?axis {})",
-                num_newaxis - 1
+                "(access-transpose
+                  (access-reshape
+                   (compute dot-product
+                    (access-cartesian-product
+                     (access-reshape
+                      (access (access-transpose {padded} (list 0 2 3 1)) 3)
+                      (access-shape (shape {pixels}) (shape {c})))
+                     (access-reshape
+                      (access ?weights 1)
+                      (access-shape (shape {o}) (shape {c})))))
+                   (access-shape (shape {n} {h} {w} {o}) (shape)))
+                  (list 0 3 1 2))",
+                padded = padded,
+                pixels = n * h * w,
+                n = n,
+                c = c,
+                h = h,
+                w = w,
+                o = o,
             )
             .parse::<Pattern<Language>>()
             .unwrap()
-            .apply_one(egraph, _eclass, subst, _searcher_ast, _rule_name)
+            .apply_one(egraph, eclass, subst, _searcher_ast, _rule_name)
         }
     }
-    let i = Impl {
-        num_newaxis: "?num-newaxis".parse().unwrap(),
-    };
-    rewrite!("expand-dims-relay-to-glenside";
-    { format!("(relay-operator-call relay-expand-dims ?data ?axis {})",
-                i.num_newaxis)
-        .parse::<Pattern<Language>>().unwrap() } => { i.clone() }
-    if move |egraph: &mut EGraph<Language, MyAnalysis>, _, subst: &Subst| match &egraph[subst[i.num_newaxis]].data {
-        MyAnalysisData::Num(v) => v > &0,
-        _ => panic!(),
-    })
+    rewrite!("conv2d-1x1-to-matmul";
+    "(conv2d ?data ?weights ?strides ?padding ?groups)" =>
+    { ApplierImpl }
+    if is_one("?groups")
+    if constrain_access("?weights".parse().unwrap(),
+                        |a| a.as_vec()[2..] == [1, 1]))
 }
 
-pub fn eliminate_expand_dims_zero_num_newaxis() -> RW {
-    rewrite!("eliminate-expand-dims-zero-num-newaxis";
-             "(relay-operator-call relay-expand-dims ?data ?axis 0)" => "?data")
+/// Fuses two convolutions of the same data whose outputs are concatenated
+/// along the channel axis, as in the branches of an Inception block, into one
+/// convolution whose weights are the two sets of weights concatenated along
+/// their output-channel axis. The wider convolution makes better use of a
+/// systolic array than two narrow ones. Applied repeatedly, this fuses any
+/// number of branches. Only applies to ungrouped convolutions with the same
+/// kernel size, strides, and padding.
+pub fn fuse_concatenated_conv2ds() -> RW {
+    rewrite!("fuse-concatenated-conv2ds";
+    "(access-concatenate
+      (conv2d ?data ?weights0 ?strides ?padding ?groups)
+      (conv2d ?data ?weights1 ?strides ?padding ?groups)
+      1)" =>
+    "(conv2d ?data (access-concatenate ?weights0 ?weights1 0) ?strides ?padding ?groups)"
+    if is_one("?groups")
+    if constrain_vars(
+        vec!["?weights0".parse().unwrap(), "?weights1".parse().unwrap()],
+        |data| match &data[..] {
+            [MyAnalysisData::AccessPattern(weights0), MyAnalysisData::AccessPattern(weights1)] => {
+                weights0.shape.ndim() == weights1.shape.ndim()
+                    && weights0.as_vec()[1..] == weights1.as_vec()[1..]
+            }
+            _ => false,
+        }))
 }
 
-pub fn pad_relay_to_glenside() -> RW {
-    #[derive(Clone)]
+/// Lowers the high-level `conv1d` node into the access-windows formulation
+/// built by [`from_relay::conv1d`]. Only ungrouped convolutions are lowered.
+pub fn conv1d_to_access_windows() -> RW {
     struct Impl {
         data: Var,
-        pad_widths: Var,
+        weights: Var,
+        strides: Var,
+        padding: Var,
     }
     impl Applier<Language, MyAnalysis> for Impl {
         fn apply_one(
@@ -3143,183 +3820,386 @@ pub fn pad_relay_to_glenside() -> RW {
             _searcher_ast: Option<&PatternAst<Language>>,
             _rule_name: Symbol,
         ) -> Vec<Id> {
-            let pad_widths = match &egraph[subst[self.pad_widths]].data {
-                MyAnalysisData::Shape(ShapeData { shape, .. }) => shape.clone(),
-                _ => panic!(),
-            };
-            let ndim = match &egraph[subst[self.data]].data {
-                MyAnalysisData::AccessPattern(a) => a.as_vec().len(),
-                _ => panic!(),
+            let (data, weights, strides, padding) = match vec![
+                self.data,
+                self.weights,
+                self.strides,
+                self.padding,
+            ]
+            .drain(..)
+            .map(|v| &egraph[subst[v]].data)
+            .collect::<Vec<_>>()[..]
+            {
+                [MyAnalysisData::AccessPattern(data), MyAnalysisData::AccessPattern(weights), MyAnalysisData::Shape(ShapeData { shape: strides, .. }), MyAnalysisData::Shape(ShapeData { shape: padding, .. })] => {
+                    (data, weights, strides, padding)
+                }
+                _ => panic!("Cannot parse arguments for conv1d"),
             };
-            assert_eq!(pad_widths.ndim(), ndim * 2,);
 
-            let zero_pad_id = egraph.add(Language::PadType(crate::language::PadType::ZeroPadding));
+            let mut expr = RecExpr::default();
+            let data_id = expr.add(Language::Symbol("data_PLACEHOLDER".to_string()));
+            let weights_id = expr.add(Language::Symbol("weights_PLACEHOLDER".to_string()));
+            from_relay::conv1d(
+                &mut expr,
+                data_id,
+                data.as_vec().as_slice(),
+                weights_id,
+                weights.as_vec().as_slice(),
+                strides.slice(),
+                padding.slice(),
+                &[1],
+                1,
+                "NCW",
+                "OIW",
+                "",
+            );
 
-            let mut id = subst[self.data];
-            for i in 0..ndim {
-                let axis_id = egraph.add(Language::Num(i.try_into().unwrap()));
-                let pad_before_id =
-                    egraph.add(Language::Num(pad_widths[2 * i].try_into().unwrap()));
-                let pad_after_id =
-                    egraph.add(Language::Num(pad_widths[2 * i + 1].try_into().unwrap()));
-                id = egraph.add(Language::AccessPad([
-                    id,
-                    zero_pad_id,
-                    axis_id,
-                    pad_before_id,
-                    pad_after_id,
-                ]));
-            }
+            let pattern_ast = PatternAst::from(
+                expr.as_ref()
+                    .iter()
+                    .map(|n| match n {
+                        Language::Symbol(s) if s == "data_PLACEHOLDER" => {
+                            ENodeOrVar::Var(self.data)
+                        }
+                        Language::Symbol(s) if s == "weights_PLACEHOLDER" => {
+                            ENodeOrVar::Var(self.weights)
+                        }
+                        _ => ENodeOrVar::ENode(n.clone()),
+                    })
+                    .collect::<Vec<_>>(),
+            );
 
-            egraph.union(eclass, id);
-            vec![id]
+            let out_id = egraph.add_instantiation(&pattern_ast, subst);
+            egraph.union(eclass, out_id);
+            vec![out_id]
         }
     }
     let i = Impl {
         data: "?data".parse().unwrap(),
-        pad_widths: "?pad-widths".parse().unwrap(),
+        weights: "?weights".parse().unwrap(),
+        strides: "?strides".parse().unwrap(),
+        padding: "?padding".parse().unwrap(),
     };
-    rewrite!("pad-relay-to-glenside";
-    { format!("(relay-operator-call relay-pad {} {})",
-                i.data, i.pad_widths)
-        .parse::<Pattern<Language>>().unwrap() } => { i.clone() })
+    rewrite!("conv1d-to-access-windows";
+        { format!("(conv1d {} {} {} {} 1)",
+                    i.data, i.weights, i.strides, i.padding)
+            .parse::<Pattern<Language>>().unwrap() } => { i })
 }
 
-pub fn dense_relay_to_glenside() -> RW {
-    rewrite!("dense-relay-to-glenside";
-                "(relay-operator-call relay-dense ?data ?weights)" =>
-                "(compute dot-product (access-cartesian-product (access ?data 1) (access ?weights 1)))")
-}
+/// Lowers the high-level `conv3d` node into the access-windows formulation
+/// built by [`from_relay::conv3d`]. Only ungrouped convolutions are lowered.
+/// The lowered form can then be mapped to a systolic array in the same way as
+/// a lowered `conv2d`, by flattening the windows and weights into matrices
+/// (im2col) with [`flatten_unflatten_any_access`] and the reshape-bubbling
+/// rewrites.
+pub fn conv3d_to_access_windows() -> RW {
+    struct Impl {
+        data: Var,
+        weights: Var,
+        strides: Var,
+        padding: Var,
+    }
+    impl Applier<Language, MyAnalysis> for Impl {
+        fn apply_one(
+            &self,
+            egraph: &mut EGraph<Language, MyAnalysis>,
+            eclass: Id,
+            subst: &Subst,
+            _searcher_ast: Option<&PatternAst<Language>>,
+            _rule_name: Symbol,
+        ) -> Vec<Id> {
+            let (data, weights, strides, padding) = match vec![
+                self.data,
+                self.weights,
+                self.strides,
+                self.padding,
+            ]
+            .drain(..)
+            .map(|v| &egraph[subst[v]].data)
+            .collect::<Vec<_>>()[..]
+            {
+                [MyAnalysisData::AccessPattern(data), MyAnalysisData::AccessPattern(weights), MyAnalysisData::Shape(ShapeData { shape: strides, .. }), MyAnalysisData::Shape(ShapeData { shape: padding, .. })] => {
+                    (data, weights, strides, padding)
+                }
+                _ => panic!("Cannot parse arguments for conv3d"),
+            };
 
-struct BroadcastedRelayOpApplier {
-    op: ComputeType,
-    a: Var,
-    b: Var,
-}
-impl Applier<Language, MyAnalysis> for BroadcastedRelayOpApplier {
-    fn apply_one(
-        &self,
-        egraph: &mut EGraph<Language, MyAnalysis>,
-        eclass: Id,
-        subst: &Subst,
-        _searcher_ast: Option<&PatternAst<Language>>,
-        _rule_name: Symbol,
-    ) -> Vec<Id> {
-        let a_id = subst[self.a];
-        let b_id = subst[self.b];
-        let (mut a_shape, mut b_shape) = match (&egraph[a_id].data, &egraph[b_id].data) {
-            (MyAnalysisData::AccessPattern(a), MyAnalysisData::AccessPattern(b)) => {
-                (a.as_vec().clone(), b.as_vec().clone())
-            }
-            _ => panic!(),
-        };
+            let mut expr = RecExpr::default();
+            let data_id = expr.add(Language::Symbol("data_PLACEHOLDER".to_string()));
+            let weights_id = expr.add(Language::Symbol("weights_PLACEHOLDER".to_string()));
+            from_relay::conv3d(
+                &mut expr,
+                data_id,
+                data.as_vec().as_slice(),
+                weights_id,
+                weights.as_vec().as_slice(),
+                strides.slice(),
+                padding.slice(),
+                &[1, 1, 1],
+                1,
+                "NCDHW",
+                "OIDHW",
+                "",
+            );
 
-        let mut expr = RecExpr::default();
-        let mut a_id = expr.add(Language::Symbol("a_PLACEHOLDER".to_string()));
-        let mut b_id = expr.add(Language::Symbol("b_PLACEHOLDER".to_string()));
-        while a_shape.len() < b_shape.len() {
-            a_id = access_insert_axis(&mut expr, a_id, 0);
-            a_shape.insert(0, 1);
-        }
+            let pattern_ast = PatternAst::from(
+                expr.as_ref()
+                    .iter()
+                    .map(|n| match n {
+                        Language::Symbol(s) if s == "data_PLACEHOLDER" => {
+                            ENodeOrVar::Var(self.data)
+                        }
+                        Language::Symbol(s) if s == "weights_PLACEHOLDER" => {
+                            ENodeOrVar::Var(self.weights)
+                        }
+                        _ => ENodeOrVar::ENode(n.clone()),
+                    })
+                    .collect::<Vec<_>>(),
+            );
 
-        while b_shape.len() < a_shape.len() {
-            b_id = access_insert_axis(&mut expr, b_id, 0);
-            b_shape.insert(0, 1);
+            let out_id = egraph.add_instantiation(&pattern_ast, subst);
+            egraph.union(eclass, out_id);
+            vec![out_id]
         }
+    }
+    let i = Impl {
+        data: "?data".parse().unwrap(),
+        weights: "?weights".parse().unwrap(),
+        strides: "?strides".parse().unwrap(),
+        padding: "?padding".parse().unwrap(),
+    };
+    rewrite!("conv3d-to-access-windows";
+        { format!("(conv3d {} {} {} {} 1)",
+                    i.data, i.weights, i.strides, i.padding)
+            .parse::<Pattern<Language>>().unwrap() } => { i })
+}
 
-        assert_eq!(a_shape.len(), b_shape.len());
+/// Whether `var` is the number 1, e.g. an ungrouped convolution's groups.
+fn is_one(var: &'static str) -> impl Fn(&mut EG, egg::Id, &egg::Subst) -> bool {
+    let var = var.parse().unwrap();
+    move |egraph, _, subst| match &egraph[subst[var]].data {
+        MyAnalysisData::Num(n) => *n == 1,
+        _ => false,
+    }
+}
 
-        assert!(a_shape.iter().zip(b_shape.iter()).map(|(a, b)| a <= b).all(|v| v) ||
-                a_shape.iter().zip(b_shape.iter()).map(|(a, b)| a >= b).all(|v| v),
-                "Can only handle simple broadcasts; all dims of a must be <= all dims of b (or vice-versa)");
-        if a_shape
-            .iter()
-            .zip(b_shape.iter())
-            .map(|(a, b)| a < b)
-            .any(|v| v)
-        {
-            let access_shape_id = access_shape(&mut expr, &b_shape, &[]);
-            a_id = expr.add(Language::AccessBroadcast([a_id, access_shape_id]));
-        } else if a_shape
+/// The runs of input channels of convolution weights `weights` (in layout
+/// OIHW, or any layout with input channels on axis 1) which have nonzero
+/// filters, as half-open ranges.
+fn nonzero_input_channel_runs(weights: &ndarray::ArrayD<f64>) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = Vec::default();
+    for channel in 0..weights.shape()[1] {
+        if weights
+            .index_axis(ndarray::Axis(1), channel)
             .iter()
-            .zip(b_shape.iter())
-            .map(|(a, b)| a > b)
-            .any(|v| v)
+            .all(|v| *v == 0.)
         {
-            let access_shape_id = access_shape(&mut expr, &a_shape, &[]);
-            b_id = expr.add(Language::AccessBroadcast([b_id, access_shape_id]));
+            continue;
         }
+        match runs.last_mut() {
+            Some((_, high)) if *high == channel => *high = channel + 1,
+            _ => runs.push((channel, channel + 1)),
+        }
+    }
+    runs
+}
 
-        let pair_id = access_pair(&mut expr, a_id, b_id, 0);
-
-        let _compute_id = compute(&mut expr, self.op.clone(), pair_id);
-
-        let pattern_ast = PatternAst::from(
-            expr.as_ref()
-                .iter()
-                .map(|n| match n {
-                    Language::Symbol(s) if s == "a_PLACEHOLDER" => ENodeOrVar::Var(self.a),
-                    Language::Symbol(s) if s == "b_PLACEHOLDER" => ENodeOrVar::Var(self.b),
-                    _ => ENodeOrVar::ENode(n.clone()),
+/// Removes the input channels of an ungrouped convolution whose filters are
+/// all zero (e.g. because they were pruned), slicing them out of both the
+/// data and the weights. The values of the weights come from `weights`; see
+/// [`ConstantWeights`].
+pub fn prune_zero_input_channels(weights: ConstantWeights) -> RW {
+    fn has_zero_input_channels(
+        weights: ConstantWeights,
+    ) -> impl Fn(&mut EG, egg::Id, &egg::Subst) -> bool {
+        let var = "?weights".parse().unwrap();
+        move |egraph, _, subst| match weights.value_of(egraph, subst[var]) {
+            Some(w) => {
+                let runs = nonzero_input_channel_runs(&w);
+                !runs.is_empty() && runs != [(0, w.shape()[1])]
+            }
+            None => false,
+        }
+    }
+    struct ApplierImpl(ConstantWeights);
+    impl Applier<Language, MyAnalysis> for ApplierImpl {
+        fn apply_one(
+            &self,
+            egraph: &mut EG,
+            eclass: Id,
+            subst: &Subst,
+            _searcher_ast: Option<&PatternAst<Language>>,
+            _rule_name: Symbol,
+        ) -> Vec<Id> {
+            let weights = self
+                .0
+                .value_of(egraph, subst["?weights".parse().unwrap()])
+                .unwrap();
+            let runs = nonzero_input_channel_runs(&weights);
+            let slice_runs = |access: &str| {
+                let mut slices = runs
+                    .iter()
+                    .map(|(low, high)| format!("(access-slice {} 1 {} {})", access, low, high));
+                let first = slices.next().unwrap();
+                slices.fold(first, |concatenated, slice| {
+                    format!("(access-concatenate {} {} 1)", concatenated, slice)
                 })
-                .collect::<Vec<_>>(),
-        );
-
-        let out_id = egraph.add_instantiation(&pattern_ast, subst);
+            };
 
-        let out_id = out_id;
-        egraph.union(eclass, out_id);
-        vec![out_id]
+            format!(
+                "(conv2d {} {} ?strides ?padding ?groups)",
+                slice_runs("?data"),
+                slice_runs("?weights")
+            )
+            .parse::<Pattern<Language>>()
+            .unwrap()
+            .apply_one(egraph, eclass, subst, _searcher_ast, _rule_name)
+        }
     }
+    rewrite!("prune-zero-input-channels";
+             "(conv2d ?data ?weights ?strides ?padding ?groups)" =>
+             { ApplierImpl(weights.clone()) }
+             if is_one("?groups")
+             if has_zero_input_channels(weights))
 }
 
-pub fn add_relay_to_glenside() -> RW {
-    rewrite!("add-relay-to-glenside";
-    "(relay-operator-call relay-add ?a ?b)" =>
-    {
-        BroadcastedRelayOpApplier {
-            op: ComputeType::ElementwiseAdd,
-            a: "?a".parse().unwrap(),
-            b: "?b".parse().unwrap(),
+/// Factors convolution weights `weights` of shape `[O, I, KH, KW]` into `U` of
+/// shape `[O, I, KH, 1]` and `V` of shape `[I, 1, 1, KW]` such that
+/// `weights[o, i, h, w] = U[o, i, h, 0] * V[i, 0, 0, w]`, if possible.
+fn separable_factors(
+    weights: &ndarray::ArrayD<f64>,
+) -> Option<(ndarray::ArrayD<f64>, ndarray::ArrayD<f64>)> {
+    let (o, i, kh, kw) = match weights.shape() {
+        &[o, i, kh, kw] => (o, i, kh, kw),
+        _ => return None,
+    };
+    let mut u = ndarray::ArrayD::<f64>::zeros(vec![o, i, kh, 1]);
+    let mut v = ndarray::ArrayD::<f64>::zeros(vec![i, 1, 1, kw]);
+    for channel in 0..i {
+        // Every row of every filter applied to this channel must be a
+        // multiple of the largest such row.
+        let rows = (0..o)
+            .cartesian_product(0..kh)
+            .map(|(out, h)| {
+                (
+                    (out, h),
+                    weights.slice(ndarray::s![out, channel, h, ..]).to_owned(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let norm = |row: &ndarray::Array1<f64>| row.dot(row);
+        let largest = rows
+            .iter()
+            .map(|(_, row)| row)
+            .max_by(|a, b| norm(a).partial_cmp(&norm(b)).unwrap())
+            .unwrap()
+            .clone();
+        if norm(&largest) == 0. {
+            continue;
         }
-    })
-}
-
-pub fn multiply_relay_to_glenside() -> RW {
-    rewrite!("multipy-relay-to-glenside";
-    "(relay-operator-call relay-multiply ?a ?b)" =>
-    {
-        BroadcastedRelayOpApplier {
-            op: ComputeType::ElementwiseMul,
-            a: "?a".parse().unwrap(),
-            b: "?b".parse().unwrap(),
+        let tolerance = 1e-9 * largest.iter().fold(0f64, |max, x| max.max(x.abs()));
+        for ((out, h), row) in &rows {
+            let scale = row.dot(&largest) / norm(&largest);
+            if row
+                .iter()
+                .zip(largest.iter())
+                .any(|(x, y)| (x - scale * y).abs() > tolerance)
+            {
+                return None;
+            }
+            u[&[*out, channel, *h, 0][..]] = scale;
         }
-    })
+        v.slice_mut(ndarray::s![channel, 0, 0, ..]).assign(&largest);
+    }
+    Some((u, v))
 }
 
-pub fn divide_relay_to_glenside() -> RW {
-    rewrite!("divide-relay-to-glenside";
-    "(relay-operator-call relay-divide ?a ?b)" =>
-    {
-        BroadcastedRelayOpApplier {
-            op: ComputeType::ElementwiseDiv,
-            a: "?a".parse().unwrap(),
-            b: "?b".parse().unwrap(),
+/// Applies an ungrouped convolution with a spatially separable kernel as two
+/// convolutions: a depthwise `1 x KW` convolution with each input channel's
+/// horizontal profile, followed by a `KH x 1` convolution. A kernel of shape
+/// `[O, I, KH, KW]` is separable if the filters applied to each input channel
+/// all share the same horizontal profile, up to scale; see
+/// [`separable_factors`]. This takes `I * KW + O * I * KH` multiply-accumulates
+/// per output pixel, rather than `O * I * KH * KW`.
+///
+/// The values of the weights come from `weights`; see [`ConstantWeights`].
+/// The two factors of the kernel are added to `weights`.
+pub fn separable_conv2d(weights: ConstantWeights) -> RW {
+    fn is_separable(weights: ConstantWeights) -> impl Fn(&mut EG, egg::Id, &egg::Subst) -> bool {
+        let var = "?weights".parse().unwrap();
+        move |egraph, _, subst| match weights.value_of(egraph, subst[var]) {
+            Some(w) => {
+                w.ndim() == 4
+                    && w.shape()[2] > 1
+                    && w.shape()[3] > 1
+                    && separable_factors(&w).is_some()
+            }
+            None => false,
         }
-    })
-}
+    }
+    struct ApplierImpl(ConstantWeights);
+    impl Applier<Language, MyAnalysis> for ApplierImpl {
+        fn apply_one(
+            &self,
+            egraph: &mut EG,
+            eclass: Id,
+            subst: &Subst,
+            _searcher_ast: Option<&PatternAst<Language>>,
+            _rule_name: Symbol,
+        ) -> Vec<Id> {
+            let weights = self
+                .0
+                .value_of(egraph, subst["?weights".parse().unwrap()])
+                .unwrap();
+            let (u, v) = separable_factors(&weights).unwrap();
+            let strides =
+                MyAnalysis::get_shape_of_value(subst["?strides".parse().unwrap()], egraph).clone();
+            let padding =
+                MyAnalysis::get_shape_of_value(subst["?padding".parse().unwrap()], egraph).clone();
+            let channels = weights.shape()[1];
+
+            let u_name = ConstantWeights::name_for("separable-vertical", &u);
+            let v_name = ConstantWeights::name_for("separable-horizontal", &v);
+            self.0.add(egraph, &u_name, u);
+            self.0.add(egraph, &v_name, v);
 
-pub fn batch_flatten_relay_to_glenside() -> RW {
-    rewrite!("batch-flatten-relay-to-glenside";
-        "(relay-operator-call relay-batch-flatten ?a)" =>
-        "(access-flatten (access ?a 1))")
+            format!(
+                "(conv2d
+                  (conv2d ?data (access-tensor {v}) (shape 1 {sw}) (shape 0 {pl} 0 {pr}) {channels})
+                  (access-tensor {u}) (shape {sh} 1) (shape {pt} 0 {pb} 0) 1)",
+                u = u_name,
+                v = v_name,
+                sh = strides[0],
+                sw = strides[1],
+                pt = padding[0],
+                pl = padding[1],
+                pb = padding[2],
+                pr = padding[3],
+                channels = channels,
+            )
+            .parse::<Pattern<Language>>()
+            .unwrap()
+            .apply_one(egraph, eclass, subst, _searcher_ast, _rule_name)
+        }
+    }
+    rewrite!("separable-conv2d";
+             "(conv2d ?data ?weights ?strides ?padding ?groups)" =>
+             { ApplierImpl(weights.clone()) }
+             if is_one("?groups")
+             if is_separable(weights))
 }
 
-pub fn bias_add_relay_to_glenside() -> RW {
+/// Lowers the high-level `conv2d-transpose` node into a regular convolution
+/// over zero-upsampled data, as built by [`from_relay::conv2d_transpose`]. The
+/// convolution is then lowered further, and mapped to a systolic array, just
+/// like any other lowered `conv2d`.
+pub fn conv2d_transpose_to_access_windows() -> RW {
     struct Impl {
-        data_var: Var,
-        bias_var: Var,
-        axis_var: Var,
+        data: Var,
+        weights: Var,
+        strides: Var,
+        padding: Var,
+        output_padding: Var,
     }
     impl Applier<Language, MyAnalysis> for Impl {
         fn apply_one(
@@ -3330,51 +4210,52 @@ pub fn bias_add_relay_to_glenside() -> RW {
             _searcher_ast: Option<&PatternAst<Language>>,
             _rule_name: Symbol,
         ) -> Vec<Id> {
-            let axis = match &egraph[subst[self.axis_var]].data {
-                MyAnalysisData::Num(v) => *v,
-                _ => panic!(),
-            };
-            let data_shape = match &egraph[subst[self.data_var]].data {
-                MyAnalysisData::AccessPattern(v) => v.clone(),
-                _ => panic!(),
-            };
-            let axis = if axis < 0 {
-                axis + i64::try_from(data_shape.as_vec().len()).unwrap()
-            } else {
-                axis
+            let (data, weights, strides, padding, output_padding) = match vec![
+                self.data,
+                self.weights,
+                self.strides,
+                self.padding,
+                self.output_padding,
+            ]
+            .drain(..)
+            .map(|v| &egraph[subst[v]].data)
+            .collect::<Vec<_>>()[..]
+            {
+                [MyAnalysisData::AccessPattern(data), MyAnalysisData::AccessPattern(weights), MyAnalysisData::Shape(ShapeData { shape: strides, .. }), MyAnalysisData::Shape(ShapeData { shape: padding, .. }), MyAnalysisData::Shape(ShapeData {
+                    shape: output_padding,
+                    ..
+                })] => (data, weights, strides, padding, output_padding),
+                _ => panic!("Cannot parse arguments for conv2d-transpose"),
             };
 
             let mut expr = RecExpr::default();
             let data_id = expr.add(Language::Symbol("data_PLACEHOLDER".to_string()));
-            let mut bias_id = expr.add(Language::Symbol("bias_PLACEHOLDER".to_string()));
-
-            // Insert axes before
-            for _ in 0..axis {
-                let zero_id = expr.add(Language::Num(0));
-                bias_id = expr.add(Language::AccessInsertAxis([bias_id, zero_id]));
-            }
-
-            // Insert axes after
-            for axis in usize::try_from(axis + 1).unwrap()..data_shape.as_vec().len() {
-                let axis_id = expr.add(Language::Num(axis.try_into().unwrap()));
-                bias_id = expr.add(Language::AccessInsertAxis([bias_id, axis_id]));
-            }
-
-            let access_shape_id = access_shape(&mut expr, &data_shape.as_vec(), &[]);
-            let bias_id = expr.add(Language::AccessBroadcast([bias_id, access_shape_id]));
-
-            let data_id = access_pair(&mut expr, data_id, bias_id, 0);
-            let _data_id = compute(&mut expr, ComputeType::ElementwiseAdd, data_id);
+            let weights_id = expr.add(Language::Symbol("weights_PLACEHOLDER".to_string()));
+            from_relay::conv2d_transpose(
+                &mut expr,
+                data_id,
+                data.as_vec().as_slice(),
+                weights_id,
+                weights.as_vec().as_slice(),
+                strides.slice(),
+                padding.slice(),
+                output_padding.slice(),
+                &[1, 1],
+                1,
+                "NCHW",
+                "IOHW",
+                "",
+            );
 
             let pattern_ast = PatternAst::from(
                 expr.as_ref()
                     .iter()
                     .map(|n| match n {
                         Language::Symbol(s) if s == "data_PLACEHOLDER" => {
-                            ENodeOrVar::Var(self.data_var)
+                            ENodeOrVar::Var(self.data)
                         }
-                        Language::Symbol(s) if s == "bias_PLACEHOLDER" => {
-                            ENodeOrVar::Var(self.bias_var)
+                        Language::Symbol(s) if s == "weights_PLACEHOLDER" => {
+                            ENodeOrVar::Var(self.weights)
                         }
                         _ => ENodeOrVar::ENode(n.clone()),
                     })
@@ -3382,21 +4263,30 @@ pub fn bias_add_relay_to_glenside() -> RW {
             );
 
             let out_id = egraph.add_instantiation(&pattern_ast, subst);
-
-            let out_id = out_id;
             egraph.union(eclass, out_id);
             vec![out_id]
         }
     }
-    rewrite!("bias-add-relay-to-glenside";
-                "(relay-operator-call relay-bias-add ?data ?bias ?axis)" =>
-                { Impl{data_var:"?data".parse().unwrap(), bias_var:"?bias".parse().unwrap(), axis_var:"?axis".parse().unwrap()} })
+    let i = Impl {
+        data: "?data".parse().unwrap(),
+        weights: "?weights".parse().unwrap(),
+        strides: "?strides".parse().unwrap(),
+        padding: "?padding".parse().unwrap(),
+        output_padding: "?output-padding".parse().unwrap(),
+    };
+    rewrite!("conv2d-transpose-to-access-windows";
+        { format!("(conv2d-transpose {} {} {} {} {})",
+                    i.data, i.weights, i.strides, i.padding, i.output_padding)
+            .parse::<Pattern<Language>>().unwrap() } => { i })
 }
 
-pub fn concatenate_relay_to_glenside() -> RW {
+/// Lowers the high-level `adaptive-pool2d` node into a reduction over
+/// non-overlapping windows, when the output size evenly divides the input size.
+/// Other adaptive pools have windows of differing sizes, and are left as-is.
+pub fn adaptive_pool2d_to_access_windows() -> RW {
     struct Impl {
-        axis_var: Var,
-        tuple_var: Var,
+        data: Var,
+        output_size: Var,
     }
     impl Applier<Language, MyAnalysis> for Impl {
         fn apply_one(
@@ -3407,123 +4297,347 @@ pub fn concatenate_relay_to_glenside() -> RW {
             _searcher_ast: Option<&PatternAst<Language>>,
             _rule_name: Symbol,
         ) -> Vec<Id> {
-            let tuple_len = match &egraph[subst[self.tuple_var]].data {
-                MyAnalysisData::Tuple(t) => t.len(),
-                _ => panic!(),
-            };
-            let axis = match &egraph[subst[self.axis_var]].data {
-                MyAnalysisData::Num(v) => *v,
-                _ => panic!(),
+            let data_shape = match &egraph[subst[self.data]].data {
+                MyAnalysisData::AccessPattern(a) => a.as_vec(),
+                _ => panic!("Cannot parse arguments for adaptive-pool2d"),
             };
+            let output_size = MyAnalysis::get_shape_of_value(subst[self.output_size], egraph);
+            if data_shape[2] % output_size[0] != 0 || data_shape[3] % output_size[1] != 0 {
+                return vec![];
+            }
+            let (window_h, window_w) = (
+                data_shape[2] / output_size[0],
+                data_shape[3] / output_size[1],
+            );
 
-            let mut expr = RecExpr::default();
-
-            let tuple_id = expr.add(Language::Symbol("tuple_PLACEHOLDER".to_string()));
+            let pattern: Pattern<Language> = format!(
+                "(compute ?pool-type
+                  (access-windows
+                   (access ?data 2)
+                   (shape {} {})
+                   (shape {} {})
+                  )
+                 )",
+                window_h, window_w, window_h, window_w
+            )
+            .parse()
+            .unwrap();
 
-            let tensor_ids: Vec<Id> = (0..tuple_len)
-                .map(|i| {
-                    let i_id = expr.add(Language::Num(i.try_into().unwrap()));
-                    expr.add(Language::TupleGetItem([tuple_id, i_id]))
-                })
-                .collect();
+            pattern.apply_one(egraph, eclass, subst, _searcher_ast, _rule_name)
+        }
+    }
+    rewrite!("adaptive-pool2d-to-access-windows";
+             "(adaptive-pool2d ?pool-type ?data ?output-size)" =>
+             { Impl {
+                 data: "?data".parse().unwrap(),
+                 output_size: "?output-size".parse().unwrap(),
+             } })
+}
 
-            assert!(tensor_ids.len() > 0);
+/// Lowers `access-windows-with-convention` into an `access-windows` over its
+/// input, padded (see [`windows::padding`](super::windows::padding)) so that
+/// `access-windows` places as many windows as the convention calls for.
+pub fn access_windows_with_convention_to_access_windows() -> RW {
+    struct Impl {
+        access: Var,
+        filters_shape: Var,
+        stride_shape: Var,
+        convention: Var,
+    }
+    impl Applier<Language, MyAnalysis> for Impl {
+        fn apply_one(
+            &self,
+            egraph: &mut EGraph<Language, MyAnalysis>,
+            eclass: Id,
+            subst: &Subst,
+            _searcher_ast: Option<&PatternAst<Language>>,
+            _rule_name: Symbol,
+        ) -> Vec<Id> {
+            let (access_axis, item_shape) = match &egraph[subst[self.access]].data {
+                MyAnalysisData::AccessPattern(a) => (a.shape.ndim(), a.item_shape.clone()),
+                _ => panic!("Cannot parse arguments for access-windows-with-convention"),
+            };
+            let convention = match &egraph[subst[self.convention]].data {
+                MyAnalysisData::WindowConvention(c) => *c,
+                _ => panic!("Cannot parse arguments for access-windows-with-convention"),
+            };
+            let padding = super::windows::padding(
+                item_shape.slice(),
+                MyAnalysis::get_shape_of_value(subst[self.filters_shape], egraph).slice(),
+                MyAnalysis::get_shape_of_value(subst[self.stride_shape], egraph).slice(),
+                convention,
+            )
+            .unwrap();
 
-            let mut concatted_id = tensor_ids[0];
-            for id in tensor_ids[1..].iter() {
-                // TODO(@gussmith23) Layout assumption
-                concatted_id =
-                    access_concatenate(&mut expr, concatted_id, *id, axis.try_into().unwrap());
+            let mut access = "?access".to_string();
+            for (item_axis, (before, after)) in padding.into_iter().enumerate() {
+                if before > 0 || after > 0 {
+                    access = format!(
+                        "(access-pad {} ?pad-type {} {} {})",
+                        access,
+                        access_axis + item_axis,
+                        before,
+                        after
+                    );
+                }
             }
+            let pattern: Pattern<Language> =
+                format!("(access-windows {} ?filters-shape ?stride-shape)", access)
+                    .parse()
+                    .unwrap();
+
+            pattern.apply_one(egraph, eclass, subst, _searcher_ast, _rule_name)
+        }
+    }
+    rewrite!("access-windows-with-convention-to-access-windows";
+             "(access-windows-with-convention ?access ?filters-shape ?stride-shape ?convention ?pad-type)" =>
+             { Impl {
+                 access: "?access".parse().unwrap(),
+                 filters_shape: "?filters-shape".parse().unwrap(),
+                 stride_shape: "?stride-shape".parse().unwrap(),
+                 convention: "?convention".parse().unwrap(),
+             } })
+}
+
+/// Lowers the high-level `batch-matmul` node into the access-pattern
+/// formulation built by [`from_relay::batch_matmul`].
+pub fn batch_matmul_to_access_pattern() -> RW {
+    struct Impl {
+        a: Var,
+        b: Var,
+    }
+    impl Applier<Language, MyAnalysis> for Impl {
+        fn apply_one(
+            &self,
+            egraph: &mut EGraph<Language, MyAnalysis>,
+            eclass: Id,
+            subst: &Subst,
+            _searcher_ast: Option<&PatternAst<Language>>,
+            _rule_name: Symbol,
+        ) -> Vec<Id> {
+            let (a, b) = match (&egraph[subst[self.a]].data, &egraph[subst[self.b]].data) {
+                (MyAnalysisData::AccessPattern(a), MyAnalysisData::AccessPattern(b)) => (a, b),
+                _ => panic!("Cannot parse arguments for batch-matmul"),
+            };
+
+            let mut expr = RecExpr::default();
+            let a_id = expr.add(Language::Symbol("a_PLACEHOLDER".to_string()));
+            let b_id = expr.add(Language::Symbol("b_PLACEHOLDER".to_string()));
+            from_relay::batch_matmul(
+                &mut expr,
+                a_id,
+                a.as_vec().as_slice(),
+                b_id,
+                b.as_vec().as_slice(),
+            );
 
             let pattern_ast = PatternAst::from(
                 expr.as_ref()
                     .iter()
                     .map(|n| match n {
-                        Language::Symbol(s) if s == "tuple_PLACEHOLDER" => {
-                            ENodeOrVar::Var(self.tuple_var)
-                        }
+                        Language::Symbol(s) if s == "a_PLACEHOLDER" => ENodeOrVar::Var(self.a),
+                        Language::Symbol(s) if s == "b_PLACEHOLDER" => ENodeOrVar::Var(self.b),
                         _ => ENodeOrVar::ENode(n.clone()),
                     })
                     .collect::<Vec<_>>(),
             );
 
             let out_id = egraph.add_instantiation(&pattern_ast, subst);
-
-            let out_id = out_id;
             egraph.union(eclass, out_id);
             vec![out_id]
         }
     }
-    rewrite!("concatenate-relay-to-glenside";
-                "(relay-operator-call relay-concatenate ?axis ?tuple)" =>
-                { Impl{axis_var:"?axis".parse().unwrap(), tuple_var: "?tuple".parse().unwrap()} })
+    rewrite!("batch-matmul-to-access-pattern";
+             "(batch-matmul ?a ?b)" =>
+             { Impl {
+                 a: "?a".parse().unwrap(),
+                 b: "?b".parse().unwrap(),
+             } })
 }
 
-pub fn simplify_tuple_get_item() -> RW {
-    struct SearcherImpl(Var);
-    impl Searcher<Language, MyAnalysis> for SearcherImpl {
-        fn search_eclass(
+/// Maps the high-level `batch-matmul` node onto one `systolic-array`
+/// invocation per batch, each multiplying an [M, K] matrix by a [K, N] one.
+/// The per-batch results are concatenated back together.
+pub fn batch_matmul_to_systolic_arrays() -> AtomRewrite {
+    struct Impl {
+        a: Var,
+        b: Var,
+    }
+    impl Applier<Language, MyAnalysis> for Impl {
+        fn apply_one(
             &self,
-            egraph: &EGraph<Language, MyAnalysis>,
+            egraph: &mut EGraph<Language, MyAnalysis>,
             eclass: Id,
-        ) -> Option<egg::SearchMatches<Language>> {
-            let substs: Vec<Subst> = egraph[eclass]
-                .nodes
-                .iter()
-                .filter_map(|v| match v {
-                    Language::TupleGetItem([t_id, idx_id]) => Some((*t_id, *idx_id)),
-                    _ => None,
-                })
-                .flat_map(|(tuple_id, idx_id)| {
-                    let idx = match egraph[idx_id].data {
-                        MyAnalysisData::Num(v) => v,
-                        _ => panic!(),
-                    };
-                    egraph[tuple_id].nodes.iter().filter_map(move |v| match v {
-                        Language::ConstructTuple(ids) => {
-                            let mut subst = Subst::default();
-                            subst.insert(self.0, ids[usize::try_from(idx).unwrap()]);
-                            Some(subst)
-                        }
-                        _ => None,
-                    })
-                })
-                .collect();
-            if substs.is_empty() {
-                None
-            } else {
-                Some(SearchMatches {
-                    eclass,
-                    substs,
-                    ast: None,
+            subst: &Subst,
+            _searcher_ast: Option<&PatternAst<Language>>,
+            _rule_name: Symbol,
+        ) -> Vec<Id> {
+            let (a, b) = match (&egraph[subst[self.a]].data, &egraph[subst[self.b]].data) {
+                (MyAnalysisData::AccessPattern(a), MyAnalysisData::AccessPattern(b)) => {
+                    (a.as_vec(), b.as_vec())
+                }
+                _ => panic!("Cannot parse arguments for batch-matmul"),
+            };
+            let (batch, rows, cols) = (a[0], a[2], b[2]);
+
+            let per_batch = |i: usize| {
+                format!(
+                    "(access-insert-axis
+                      (systolic-array {rows} {cols}
+                       (access (access-squeeze (access-slice ?a 0 {i} {j}) 0) 1)
+                       (access (access-squeeze (access-slice ?b 0 {i} {j}) 0) 0)
+                      )
+                      0
+                     )",
+                    rows = rows,
+                    cols = cols,
+                    i = i,
+                    j = i + 1
+                )
+            };
+            let pattern: Pattern<Language> = (1..batch)
+                .fold(per_batch(0), |acc, i| {
+                    format!("(access-concatenate {} {} 0)", acc, per_batch(i))
                 })
-            }
+                .parse()
+                .unwrap();
+
+            pattern.apply_one(egraph, eclass, subst, _searcher_ast, _rule_name)
         }
+    }
+    AtomRewrite::new(
+        rewrite!("batch-matmul-to-systolic-arrays";
+                 "(batch-matmul ?a ?b)" =>
+                 { Impl {
+                     a: "?a".parse().unwrap(),
+                     b: "?b".parse().unwrap(),
+                 } }),
+        &["?a", "?b"],
+        Some(last_access_axis("?a")),
+    )
+}
 
-        fn vars(&self) -> Vec<Var> {
-            vec![self.0]
+pub fn softmax_relay_to_glenside() -> RW {
+    struct Impl {
+        data: Var,
+        axis: Var,
+    }
+    impl Applier<Language, MyAnalysis> for Impl {
+        fn apply_one(
+            &self,
+            egraph: &mut EGraph<Language, MyAnalysis>,
+            _eclass: Id,
+            subst: &Subst,
+            _searcher_ast: Option<&PatternAst<Language>>,
+            _rule_name: Symbol,
+        ) -> Vec<Id> {
+            let data = match &egraph[subst[self.data]].data {
+                MyAnalysisData::AccessPattern(data) => data,
+                _ => panic!(),
+            };
+            let axis: i64 = match &egraph[subst[self.axis]].data {
+                MyAnalysisData::Num(u) => *u,
+                _ => panic!(),
+            };
+
+            assert_eq!(
+                axis, -1,
+                "We only support an axis value of -1 at the moment"
+            );
+
+            let axis: i64 = if axis < 0 {
+                data.as_vec().len() as i64 + axis
+            } else {
+                axis
+            };
+            assert!(axis >= 0 && axis < data.as_vec().len() as i64);
+
+            format!("(compute softmax (access {} {}))", self.data, axis)
+                .parse::<Pattern<Language>>()
+                .unwrap()
+                .apply_one(egraph, _eclass, subst, _searcher_ast, _rule_name)
         }
     }
+    let i = Impl {
+        data: "?data".parse().unwrap(),
+        axis: "?axis".parse().unwrap(),
+    };
+    rewrite!("softmax-relay-to-glenside";
+        { format!("(relay-operator-call relay-softmax {} {})",
+                    i.data, i.axis)
+            .parse::<Pattern<Language>>().unwrap() } => { i })
+}
 
-    rewrite!("simplify-tuple-get-item";
-                { SearcherImpl("?val".parse().unwrap()) } => "?val")
+pub fn max_pool2d_relay_to_glenside_nchw() -> RW {
+    rewrite!("max-pool2d-relay-to-glenside-nchw";
+        "(relay-operator-call relay-max-pool2d
+            ?data
+            ?pool-size
+            ?strides
+            (shape ?padding0 ?padding1 ?padding2 ?padding3)
+            relay-activation-layout-nchw)" =>
+        "(compute reduce-max
+          (access-windows
+           (access
+            (access-pad
+             (access-pad
+              (access-tensor data)
+              min-padding
+              2 ?padding0 ?padding2
+             )
+             min-padding
+             3 ?padding1 ?padding3
+            )
+            2
+           )
+           ?pool-size
+           ?strides
+          )
+         )")
 }
 
-pub fn transpose_relay_to_glenside() -> RW {
-    rewrite!("transpose-relay-to-glenside";
-                "(relay-operator-call relay-transpose ?data ?axes)" => "(access-transpose ?data ?axes)")
+pub fn global_avg_pool2d_relay_to_glenside_nchw() -> RW {
+    rewrite!("global-avg-pool2d-relay-to-glenside-nchw";
+        "(relay-operator-call relay-global-avg-pool2d
+            ?data
+            relay-activation-layout-nchw)" =>
+        "(access
+          (access-insert-axis
+           (access-insert-axis
+            (compute reduce-mean (access ?data 2))
+            2
+           )
+           3
+          )
+          2
+         )")
 }
 
-pub fn reshape_relay_to_glenside() -> RW {
-    rewrite!("reshape-relay-to-glenside";
-                "(relay-operator-call relay-reshape ?data ?shape)" => "(access-reshape ?data (access-shape (shape) ?shape))")
+/// Relay's `nn.batch_matmul` takes its second argument as [B, N, K].
+pub fn batch_matmul_relay_to_glenside() -> RW {
+    rewrite!("batch-matmul-relay-to-glenside";
+        "(relay-operator-call relay-batch-matmul ?a ?b)" =>
+        "(batch-matmul ?a (access-transpose ?b (list 0 2 1)))")
 }
 
-pub fn squeeze_relay_to_glenside() -> RW {
+/// Relay's `nn.dropout` returns a tuple of its result and its mask; only the
+/// result is used in inference.
+pub fn dropout_relay_to_glenside() -> RW {
+    rewrite!("dropout-relay-to-glenside";
+        "(tuple-get-item (relay-operator-call relay-dropout ?data ?rate) 0)" =>
+        "(dropout ?data ?rate)")
+}
+
+/// Removes `dropout`, which is the identity in inference.
+pub fn remove_dropout() -> RW {
+    rewrite!("remove-dropout"; "(dropout ?data ?rate)" => "?data")
+}
+
+/// Maps Relay's `nn.layer_norm`, which the importer only supports over the
+/// last axis with an epsilon of 1e-5, to the high-level `layer-norm` node.
+pub fn layer_norm_relay_to_glenside() -> RW {
     struct Impl {
-        axis_var: Var,
-        data_var: Var,
+        data: Var,
     }
     impl Applier<Language, MyAnalysis> for Impl {
         fn apply_one(
@@ -3534,27 +4648,77 @@ pub fn squeeze_relay_to_glenside() -> RW {
             _searcher_ast: Option<&PatternAst<Language>>,
             _rule_name: Symbol,
         ) -> Vec<Id> {
-            let mut axes = match &egraph[subst[self.axis_var]].data {
-                MyAnalysisData::List(v) => v.clone(),
-                _ => panic!(),
+            let ndim = match &egraph[subst[self.data]].data {
+                MyAnalysisData::AccessPattern(a) => a.as_vec().len(),
+                _ => panic!("Cannot parse data of layer-norm"),
             };
-            axes.sort();
-
-            let mut expr = RecExpr::default();
+            let pattern: Pattern<Language> = format!(
+                "(layer-norm (access ?data {}) ?gamma ?beta (literal 0.00001))",
+                ndim - 1
+            )
+            .parse()
+            .unwrap();
+            pattern.apply_one(egraph, eclass, subst, _searcher_ast, _rule_name)
+        }
+    }
+    rewrite!("layer-norm-relay-to-glenside";
+             "(relay-operator-call relay-layer-norm ?data ?gamma ?beta)" =>
+             { Impl { data: "?data".parse().unwrap() } })
+}
 
-            let mut data_id = expr.add(Language::Symbol("data_PLACEHOLDER".to_string()));
+/// Lowers the high-level `layer-norm` node to reductions and elementwise
+/// operations, as built by [`from_relay::layer_norm`].
+pub fn layer_norm_to_mean_variance() -> RW {
+    struct Impl {
+        data: Var,
+        gamma: Var,
+        beta: Var,
+        epsilon: Var,
+    }
+    impl Applier<Language, MyAnalysis> for Impl {
+        fn apply_one(
+            &self,
+            egraph: &mut EGraph<Language, MyAnalysis>,
+            eclass: Id,
+            subst: &Subst,
+            _searcher_ast: Option<&PatternAst<Language>>,
+            _rule_name: Symbol,
+        ) -> Vec<Id> {
+            let data = match &egraph[subst[self.data]].data {
+                MyAnalysisData::AccessPattern(a) => a.clone(),
+                _ => panic!("Cannot parse data of layer-norm"),
+            };
 
-            for axis in axes.iter().rev() {
-                let usize_id = expr.add(Language::Num((*axis).try_into().unwrap()));
-                data_id = expr.add(Language::AccessSqueeze([data_id, usize_id]));
-            }
+            let mut expr = RecExpr::default();
+            let data_id = expr.add(Language::Symbol("data_PLACEHOLDER".to_string()));
+            let gamma_id = expr.add(Language::Symbol("gamma_PLACEHOLDER".to_string()));
+            let beta_id = expr.add(Language::Symbol("beta_PLACEHOLDER".to_string()));
+            let epsilon_id = expr.add(Language::Symbol("epsilon_PLACEHOLDER".to_string()));
+            from_relay::layer_norm(
+                &mut expr,
+                data_id,
+                data.shape.slice(),
+                data.item_shape.slice(),
+                gamma_id,
+                beta_id,
+                epsilon_id,
+            );
 
             let pattern_ast = PatternAst::from(
                 expr.as_ref()
                     .iter()
                     .map(|n| match n {
                         Language::Symbol(s) if s == "data_PLACEHOLDER" => {
-                            ENodeOrVar::Var(self.data_var)
+                            ENodeOrVar::Var(self.data)
+                        }
+                        Language::Symbol(s) if s == "gamma_PLACEHOLDER" => {
+                            ENodeOrVar::Var(self.gamma)
+                        }
+                        Language::Symbol(s) if s == "beta_PLACEHOLDER" => {
+                            ENodeOrVar::Var(self.beta)
+                        }
+                        Language::Symbol(s) if s == "epsilon_PLACEHOLDER" => {
+                            ENodeOrVar::Var(self.epsilon)
                         }
                         _ => ENodeOrVar::ENode(n.clone()),
                     })
@@ -3562,800 +4726,838 @@ pub fn squeeze_relay_to_glenside() -> RW {
             );
 
             let out_id = egraph.add_instantiation(&pattern_ast, subst);
-
-            let out_id = out_id;
             egraph.union(eclass, out_id);
             vec![out_id]
         }
     }
-    rewrite!("squeeze-relay-to-glenside";
-                "(relay-operator-call relay-squeeze ?data ?axis)" =>
-                { Impl{axis_var:"?axis".parse().unwrap(), data_var: "?data".parse().unwrap()} })
+    rewrite!("layer-norm-to-mean-variance";
+             "(layer-norm ?data ?gamma ?beta ?epsilon)" =>
+             { Impl {
+                 data: "?data".parse().unwrap(),
+                 gamma: "?gamma".parse().unwrap(),
+                 beta: "?beta".parse().unwrap(),
+                 epsilon: "?epsilon".parse().unwrap(),
+             } })
 }
 
-pub fn relay_to_glenside_rewrites() -> Vec<RW> {
-    vec![
-        conv2d_relay_to_glenside(),
-        conv1d_relay_to_glenside(),
-        softmax_relay_to_glenside(),
-        relu_relay_to_glenside(),
-        negative_relay_to_glenside(),
-        sqrt_relay_to_glenside(),
-        max_pool2d_relay_to_glenside_nchw(),
-        global_avg_pool2d_relay_to_glenside_nchw(),
-        expand_dims_relay_to_glenside(),
-        eliminate_expand_dims_zero_num_newaxis(),
-        pad_relay_to_glenside(),
-        dense_relay_to_glenside(),
-        add_relay_to_glenside(),
-        multiply_relay_to_glenside(),
-        divide_relay_to_glenside(),
-        batch_flatten_relay_to_glenside(),
-        bias_add_relay_to_glenside(),
-        concatenate_relay_to_glenside(),
-        simplify_tuple_get_item(),
-        transpose_relay_to_glenside(),
-        reshape_relay_to_glenside(),
-        squeeze_relay_to_glenside(),
-    ]
-}
+/// Lowers the high-level `group-norm` node to reshapes, reductions, and
+/// elementwise operations, as built by [`from_relay::group_norm`].
+pub fn group_norm_to_mean_variance() -> RW {
+    struct Impl {
+        data: Var,
+        gamma: Var,
+        beta: Var,
+        groups: Var,
+        epsilon: Var,
+    }
+    impl Applier<Language, MyAnalysis> for Impl {
+        fn apply_one(
+            &self,
+            egraph: &mut EGraph<Language, MyAnalysis>,
+            eclass: Id,
+            subst: &Subst,
+            _searcher_ast: Option<&PatternAst<Language>>,
+            _rule_name: Symbol,
+        ) -> Vec<Id> {
+            let data = match &egraph[subst[self.data]].data {
+                MyAnalysisData::AccessPattern(a) => a.clone(),
+                _ => panic!("Cannot parse data of group-norm"),
+            };
+            let groups = MyAnalysis::get_usize(subst[self.groups], egraph);
 
-#[cfg(test)]
-mod tests {
+            let mut expr = RecExpr::default();
+            let data_id = expr.add(Language::Symbol("data_PLACEHOLDER".to_string()));
+            let gamma_id = expr.add(Language::Symbol("gamma_PLACEHOLDER".to_string()));
+            let beta_id = expr.add(Language::Symbol("beta_PLACEHOLDER".to_string()));
+            let epsilon_id = expr.add(Language::Symbol("epsilon_PLACEHOLDER".to_string()));
+            from_relay::group_norm(
+                &mut expr,
+                data_id,
+                data.as_vec().as_slice(),
+                gamma_id,
+                beta_id,
+                groups,
+                epsilon_id,
+            );
 
-    use super::*;
-    use crate::language::interpreter::interpret;
-    use crate::language::RelayOperator;
-    use crate::language::{Language, MyAnalysis};
-    use approx::AbsDiffEq;
-    use egg::{EGraph, Pattern, RecExpr, Runner, Searcher};
-    use ndarray::IxDyn;
-    use ndarray_npy::{read_npy, write_npy};
-    use ndarray_rand::{rand_distr::Uniform, RandomExt};
-    use rand::{rngs::SmallRng, Rng, SeedableRng};
-    use std::collections::HashMap;
-    use std::io::Write;
-    use std::process::Command;
-    use std::str::FromStr;
-    use test_logger::test;
-
-    #[test]
-    fn flexasr_maxpool() {
-        let mut map = HashMap::default();
-        map.insert("a".to_string(), vec![32, 32]);
-        let program = "
-         (compute reduce-max (access-windows (access (access-tensor a) 1) (shape 2) (shape 2)))
-        "
-        .parse()
-        .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
-            name_to_shape: map,
-            name_to_dtype: HashMap::default(),
-        });
-        let id = egraph.add_expr(&program);
-        egraph.rebuild();
-
-        let rws = vec![super::flexasr_maxpool()];
+            let pattern_ast = PatternAst::from(
+                expr.as_ref()
+                    .iter()
+                    .map(|n| match n {
+                        Language::Symbol(s) if s == "data_PLACEHOLDER" => {
+                            ENodeOrVar::Var(self.data)
+                        }
+                        Language::Symbol(s) if s == "gamma_PLACEHOLDER" => {
+                            ENodeOrVar::Var(self.gamma)
+                        }
+                        Language::Symbol(s) if s == "beta_PLACEHOLDER" => {
+                            ENodeOrVar::Var(self.beta)
+                        }
+                        Language::Symbol(s) if s == "epsilon_PLACEHOLDER" => {
+                            ENodeOrVar::Var(self.epsilon)
+                        }
+                        _ => ENodeOrVar::ENode(n.clone()),
+                    })
+                    .collect::<Vec<_>>(),
+            );
 
-        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
-            .with_egraph(egraph)
-            .run(&rws);
-        match runner.stop_reason.unwrap() {
-            egg::StopReason::Saturated => (),
-            _ => panic!(),
-        };
+            let out_id = egraph.add_instantiation(&pattern_ast, subst);
+            egraph.union(eclass, out_id);
+            vec![out_id]
+        }
+    }
+    rewrite!("group-norm-to-mean-variance";
+             "(group-norm ?data ?gamma ?beta ?groups ?epsilon)" =>
+             { Impl {
+                 data: "?data".parse().unwrap(),
+                 gamma: "?gamma".parse().unwrap(),
+                 beta: "?beta".parse().unwrap(),
+                 groups: "?groups".parse().unwrap(),
+                 epsilon: "?epsilon".parse().unwrap(),
+             } })
+}
 
-        let test_pattern = "
-            (access
-             (access-transpose
-              (accelerator-call flex-maxpool
-               (access 
-                (access-transpose 
-                 (access (access-tensor a) 1)
-                 (list 1 0))
-                 0) ?shape)
-              (list 1 0))
-             2)"
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        assert_eq!(matches.substs.len(), 1);
+// TODO(gussmith23) on second thought, remove this; doesn't really do anything.// TODO(gussmith23) on second thought, remove this; doesn't really do anything.
+macro_rules! relay_to_glenside_simple {
+    ($fn_name: ident, $rw_name: expr, $from: expr, $to: expr) => {
+        pub fn $fn_name() -> RW {
+            rewrite!($rw_name; $from => $to)
+        }
     }
+}
 
-    #[test]
-    fn flatten_unflatten_access_windows() {
-        test_logger::ensure_env_logger_initialized();
+relay_to_glenside_simple!(
+    relu_relay_to_glenside,
+    "relu-relay-to-glenside",
+    "(relay-operator-call relay-relu ?data)",
+    "(compute relu ?data)"
+);
 
-        let program = "
-         (access-windows
-          (access (access-tensor t-3-32-32) 0)
-          (shape 3 3 3)
-          (shape 1 1 2)
-         )
-         "
-        .parse()
-        .unwrap();
+relay_to_glenside_simple!(
+    negative_relay_to_glenside,
+    "negative-relay-to-glenside",
+    "(relay-operator-call relay-negative ?data)",
+    "(compute negative ?data)"
+);
 
-        let rws = vec![super::flatten_unflatten_any_access()];
-        let mut egraph = EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
-        egraph.add_expr(&program);
-        egraph.rebuild();
-        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
-            .with_egraph(egraph)
-            .run(&rws);
+relay_to_glenside_simple!(
+    sqrt_relay_to_glenside,
+    "sqrt-relay-to-glenside",
+    "(relay-operator-call relay-sqrt ?data)",
+    "(compute sqrt ?data)"
+);
 
-        assert_eq!(
-            "
-            (access-reshape
-             (access-flatten
-              (access-windows
-               (access (access-tensor t-3-32-32) 0)
-               (shape 3 3 3)
-               (shape 1 1 2)
-              )
-             )
-             (get-access-shape
-              (access-windows
-               (access (access-tensor t-3-32-32) 0)
-               (shape 3 3 3)
-               (shape 1 1 2)
-              )
-             )
+pub fn expand_dims_relay_to_glenside() -> RW {
+    #[derive(Clone)]
+    struct Impl {
+        num_newaxis: Var,
+    }
+    impl Applier<Language, MyAnalysis> for Impl {
+        fn apply_one(
+            &self,
+            egraph: &mut EGraph<Language, MyAnalysis>,
+            _eclass: Id,
+            subst: &Subst,
+            _searcher_ast: Option<&PatternAst<Language>>,
+            _rule_name: Symbol,
+        ) -> Vec<Id> {
+            let num_newaxis: i64 = match &egraph[subst[self.num_newaxis]].data {
+                MyAnalysisData::Num(u) => *u as i64,
+                _ => panic!(),
+            };
+
+            format!(
+                "(relay-operator-call relay-expand-dims (access-insert-axis ?data ?axis) ?axis {})",
+                num_newaxis - 1
             )
-            "
             .parse::<Pattern<Language>>()
             .unwrap()
-            .search(&runner.egraph)
-            .len(),
-            1
-        );
+            .apply_one(egraph, _eclass, subst, _searcher_ast, _rule_name)
+        }
     }
+    let i = Impl {
+        num_newaxis: "?num-newaxis".parse().unwrap(),
+    };
+    rewrite!("expand-dims-relay-to-glenside";
+    { format!("(relay-operator-call relay-expand-dims ?data ?axis {})",
+                i.num_newaxis)
+        .parse::<Pattern<Language>>().unwrap() } => { i.clone() }
+    if move |egraph: &mut EGraph<Language, MyAnalysis>, _, subst: &Subst| match &egraph[subst[i.num_newaxis]].data {
+        MyAnalysisData::Num(v) => v > &0,
+        _ => panic!(),
+    })
+}
 
-    #[test]
-    fn bubble_reshape_through_cartesian_product() {
-        test_logger::ensure_env_logger_initialized();
+pub fn eliminate_expand_dims_zero_num_newaxis() -> RW {
+    rewrite!("eliminate-expand-dims-zero-num-newaxis";
+             "(relay-operator-call relay-expand-dims ?data ?axis 0)" => "?data")
+}
 
-        let program = "
-         (access-cartesian-product
-          (access (access-tensor t-8-3-3-3) 1)
-          (access-squeeze
-           (access-windows
-            (access (access-tensor t-3-32-32) 0)
-            (shape 3 3 3)
-            (shape 1 1 1)
-           )
-           0
-          )
-         )
-        "
-        .parse()
-        .unwrap();
-        let rws = vec![
-            super::flatten_unflatten_any_access(),
-            super::bubble_reshape_through_cartesian_product(),
-        ];
-        let mut egraph = EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
-        let id = egraph.add_expr(&program);
-        egraph.rebuild();
-        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
-            .with_egraph(egraph)
-            .run(&rws);
+pub fn pad_relay_to_glenside() -> RW {
+    #[derive(Clone)]
+    struct Impl {
+        data: Var,
+        pad_widths: Var,
+    }
+    impl Applier<Language, MyAnalysis> for Impl {
+        fn apply_one(
+            &self,
+            egraph: &mut EGraph<Language, MyAnalysis>,
+            eclass: Id,
+            subst: &Subst,
+            _searcher_ast: Option<&PatternAst<Language>>,
+            _rule_name: Symbol,
+        ) -> Vec<Id> {
+            let pad_widths = match &egraph[subst[self.pad_widths]].data {
+                MyAnalysisData::Shape(ShapeData { shape, .. }) => shape.clone(),
+                _ => panic!(),
+            };
+            let ndim = match &egraph[subst[self.data]].data {
+                MyAnalysisData::AccessPattern(a) => a.as_vec().len(),
+                _ => panic!(),
+            };
+            assert_eq!(pad_widths.ndim(), ndim * 2,);
 
-        let test_pattern = "
-            (access-reshape
-             (access-cartesian-product
-              (access-flatten
-               (access (access-tensor t-8-3-3-3) 1)
-              )
-              (access-flatten
-               (access-squeeze
-                (access-windows
-                 (access (access-tensor t-3-32-32) 0)
-                 (shape 3 3 3)
-                 (shape 1 1 1)
-                )
-                0
-               )
-              )
-             )
-             ?shape
-            )
-            "
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        assert_eq!(matches.substs.len(), 1);
+            let zero_pad_id = egraph.add(Language::PadType(crate::language::PadType::ZeroPadding));
 
-        // ?shape should be what we expect. This is kind of over-testing, in the
-        // sense that, if we find the above pattern, then the analysis data is
-        // guaranteed (for now) to match, and so the shapes should be the same,
-        // but I figure over-testing is better than under-testing (given that
-        // "for now").
-        match &runner.egraph[matches.substs[0]["?shape".parse().unwrap()]].data {
-            MyAnalysisData::AccessPattern(a) => {
-                assert_eq!(a.item_shape, IxDyn(&[2, 3, 3, 3]));
-                assert_eq!(a.shape, IxDyn(&[8, 30, 30]));
+            let mut id = subst[self.data];
+            for i in 0..ndim {
+                let axis_id = egraph.add(Language::Num(i.try_into().unwrap()));
+                let pad_before_id =
+                    egraph.add(Language::Num(pad_widths[2 * i].try_into().unwrap()));
+                let pad_after_id =
+                    egraph.add(Language::Num(pad_widths[2 * i + 1].try_into().unwrap()));
+                id = egraph.add(Language::AccessPad([
+                    id,
+                    zero_pad_id,
+                    axis_id,
+                    pad_before_id,
+                    pad_after_id,
+                ]));
             }
-            _ => panic!(),
+
+            egraph.union(eclass, id);
+            vec![id]
         }
     }
+    let i = Impl {
+        data: "?data".parse().unwrap(),
+        pad_widths: "?pad-widths".parse().unwrap(),
+    };
+    rewrite!("pad-relay-to-glenside";
+    { format!("(relay-operator-call relay-pad {} {})",
+                i.data, i.pad_widths)
+        .parse::<Pattern<Language>>().unwrap() } => { i.clone() })
+}
 
-    #[test]
-    fn bubble_reshape_through_dot_product() {
-        test_logger::ensure_env_logger_initialized();
-
-        let program = "
-         (compute dot-product
-          (access-reshape (access (access-tensor t-1024-2-256) 1) (access-shape (shape 32 32) (shape 2 16 16)))
-         )
-        "
-        .parse()
-        .unwrap();
-        let rws = vec![super::bubble_reshape_through_compute_dot_product()];
-        let mut egraph = EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
-        let id = egraph.add_expr(&program);
-        egraph.rebuild();
-        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
-            .with_egraph(egraph)
-            .run(&rws);
-
-        let test_pattern = "
-            (access-reshape
-             (compute dot-product
-              (access (access-tensor t-1024-2-256) 1)
-             )
-             ?shape
-            )
-            "
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        assert_eq!(matches.substs.len(), 1);
+pub fn dense_relay_to_glenside() -> RW {
+    rewrite!("dense-relay-to-glenside";
+                "(relay-operator-call relay-dense ?data ?weights)" =>
+                "(compute dot-product (access-cartesian-product (access ?data 1) (access ?weights 1)))")
+}
 
-        match &runner.egraph[matches.substs[0]["?shape".parse().unwrap()]].data {
-            MyAnalysisData::AccessPattern(a) => {
-                assert_eq!(a.item_shape, IxDyn(&[]));
-                assert_eq!(a.shape, IxDyn(&[32, 32]));
+struct BroadcastedRelayOpApplier {
+    op: ComputeType,
+    a: Var,
+    b: Var,
+}
+impl Applier<Language, MyAnalysis> for BroadcastedRelayOpApplier {
+    fn apply_one(
+        &self,
+        egraph: &mut EGraph<Language, MyAnalysis>,
+        eclass: Id,
+        subst: &Subst,
+        _searcher_ast: Option<&PatternAst<Language>>,
+        _rule_name: Symbol,
+    ) -> Vec<Id> {
+        let a_id = subst[self.a];
+        let b_id = subst[self.b];
+        let (mut a_shape, mut b_shape) = match (&egraph[a_id].data, &egraph[b_id].data) {
+            (MyAnalysisData::AccessPattern(a), MyAnalysisData::AccessPattern(b)) => {
+                (a.as_vec().clone(), b.as_vec().clone())
             }
             _ => panic!(),
+        };
+
+        let mut expr = RecExpr::default();
+        let mut a_id = expr.add(Language::Symbol("a_PLACEHOLDER".to_string()));
+        let mut b_id = expr.add(Language::Symbol("b_PLACEHOLDER".to_string()));
+        while a_shape.len() < b_shape.len() {
+            a_id = access_insert_axis(&mut expr, a_id, 0);
+            a_shape.insert(0, 1);
         }
-    }
-    #[test]
-    fn conv1d_im2col_systolic_array() {
-        let program = "
-        (access-transpose
-            (compute dot-product
-              (access-cartesian-product
-               (access (access-tensor weights) 1)
-               (access-squeeze
-                (access-windows
-                 (access
-                  (access-pad
-                   (access-tensor data)
-                   zero-padding
-                   2 3 4
-                  )
-                  1
-                 )
-                 (shape 3 3)
-                 (shape 1 2)
-                )
-                1
-               )
-              )
-            )
-            (list 1 0 2)
-           )
-        "
-        .parse()
-        .unwrap();
 
-        let mut map = HashMap::new();
-        map.insert("data".to_string(), vec![1, 3, 32]);
-        map.insert("weights".to_string(), vec![8, 3, 3]);
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
-            name_to_shape: map,
-            name_to_dtype: HashMap::default(),
-        });
-        let id = egraph.add_expr(&program);
-        egraph.rebuild();
+        while b_shape.len() < a_shape.len() {
+            b_id = access_insert_axis(&mut expr, b_id, 0);
+            b_shape.insert(0, 1);
+        }
 
-        let rws = vec![
-            super::flatten_unflatten_any_access(),
-            super::bubble_reshape_through_cartesian_product(),
-            super::bubble_reshape_through_compute_dot_product(),
-            super::systolic_array(),
-        ];
+        assert_eq!(a_shape.len(), b_shape.len());
 
-        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
-            .with_egraph(egraph)
-            .run(&rws);
+        assert!(a_shape.iter().zip(b_shape.iter()).map(|(a, b)| a <= b).all(|v| v) ||
+                a_shape.iter().zip(b_shape.iter()).map(|(a, b)| a >= b).all(|v| v),
+                "Can only handle simple broadcasts; all dims of a must be <= all dims of b (or vice-versa)");
+        if a_shape
+            .iter()
+            .zip(b_shape.iter())
+            .map(|(a, b)| a < b)
+            .any(|v| v)
+        {
+            let access_shape_id = access_shape(&mut expr, &b_shape, &[]);
+            a_id = expr.add(Language::AccessBroadcast([a_id, access_shape_id]));
+        } else if a_shape
+            .iter()
+            .zip(b_shape.iter())
+            .map(|(a, b)| a > b)
+            .any(|v| v)
+        {
+            let access_shape_id = access_shape(&mut expr, &a_shape, &[]);
+            b_id = expr.add(Language::AccessBroadcast([b_id, access_shape_id]));
+        }
 
-        let test_pattern = "
-        (access-transpose
-         (access-reshape
-          (systolic-array ?rows ?cols
-            ?a
-            ?b
-          )
-          ?shape
-         )
-         ?transpose-list
-        )
-            "
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        assert_eq!(matches.substs.len(), 1);
-    }
+        let pair_id = access_pair(&mut expr, a_id, b_id, 0);
 
-    #[test]
-    fn conv2d_im2col_systolic_array() {
-        let program = "
-         (compute dot-product
-          (access-cartesian-product
-           (access (access-tensor t-8-3-3-3) 1)
-           (access-squeeze
-            (access-windows
-             (access (access-tensor t-3-32-32) 0)
-             (shape 3 3 3)
-             (shape 1 1 1)
-            )
-            0
-           )
-          )
-         )
-        "
-        .parse()
-        .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
-        let id = egraph.add_expr(&program);
-        egraph.rebuild();
+        let _compute_id = compute(&mut expr, self.op.clone(), pair_id);
 
-        let rws = vec![
-            super::flatten_unflatten_any_access(),
-            super::bubble_reshape_through_cartesian_product(),
-            super::bubble_reshape_through_compute_dot_product(),
-            super::systolic_array(),
-        ];
+        let pattern_ast = PatternAst::from(
+            expr.as_ref()
+                .iter()
+                .map(|n| match n {
+                    Language::Symbol(s) if s == "a_PLACEHOLDER" => ENodeOrVar::Var(self.a),
+                    Language::Symbol(s) if s == "b_PLACEHOLDER" => ENodeOrVar::Var(self.b),
+                    _ => ENodeOrVar::ENode(n.clone()),
+                })
+                .collect::<Vec<_>>(),
+        );
 
-        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
-            .with_egraph(egraph)
-            .run(&rws);
+        let out_id = egraph.add_instantiation(&pattern_ast, subst);
 
-        let test_pattern = "
-            (access-reshape
-             (systolic-array 27 900
-              (access-flatten (access (access-tensor t-8-3-3-3) 1))
-              (access
-               (access-transpose
-                (access-flatten
-                 (access-squeeze
-                  (access-windows
-                   (access (access-tensor t-3-32-32) 0)
-                   (shape 3 3 3)
-                   (shape 1 1 1)
-                  )
-                  0
-                 )
-                )
-                (list 1 0)
-               )
-               0
-              )
-             )
-             ?shape
-            )
-            "
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        assert_eq!(matches.substs.len(), 1);
+        let out_id = out_id;
+        egraph.union(eclass, out_id);
+        vec![out_id]
     }
+}
 
-    #[test]
-    fn slice_concatenate_accesses_divide_into_0() {
-        test_logger::ensure_env_logger_initialized();
+pub fn add_relay_to_glenside() -> RW {
+    rewrite!("add-relay-to-glenside";
+    "(relay-operator-call relay-add ?a ?b)" =>
+    {
+        BroadcastedRelayOpApplier {
+            op: ComputeType::ElementwiseAdd,
+            a: "?a".parse().unwrap(),
+            b: "?b".parse().unwrap(),
+        }
+    })
+}
 
-        let program = "(access (access-tensor t-32-32) 1)".parse().unwrap();
+pub fn multiply_relay_to_glenside() -> RW {
+    rewrite!("multipy-relay-to-glenside";
+    "(relay-operator-call relay-multiply ?a ?b)" =>
+    {
+        BroadcastedRelayOpApplier {
+            op: ComputeType::ElementwiseMul,
+            a: "?a".parse().unwrap(),
+            b: "?b".parse().unwrap(),
+        }
+    })
+}
 
-        let rws = vec![
-            super::slice_concatenate_accesses(
-                0,
-                SliceConcatenateStrategy::DivideInto { segment_size: 8 },
-            ),
-            super::slice_concatenate_accesses(
-                1,
-                SliceConcatenateStrategy::DivideInto { segment_size: 8 },
-            ),
-            super::collapse_nested_access_slices(),
-        ];
+pub fn divide_relay_to_glenside() -> RW {
+    rewrite!("divide-relay-to-glenside";
+    "(relay-operator-call relay-divide ?a ?b)" =>
+    {
+        BroadcastedRelayOpApplier {
+            op: ComputeType::ElementwiseDiv,
+            a: "?a".parse().unwrap(),
+            b: "?b".parse().unwrap(),
+        }
+    })
+}
 
-        let mut egraph = EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
-        egraph.add_expr(&program);
-        egraph.rebuild();
-        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
-            .with_egraph(egraph)
-            .run(&rws);
+pub fn batch_flatten_relay_to_glenside() -> RW {
+    rewrite!("batch-flatten-relay-to-glenside";
+        "(relay-operator-call relay-batch-flatten ?a)" =>
+        "(access-flatten (access ?a 1))")
+}
 
-        for dim0 in &[0, 8, 16, 24] {
-            for dim1 in &[0, 8, 16, 24] {
-                assert_eq!(
-                    format!("(access-slice (access-slice (access (access-tensor t-32-32) 1) 1 {} {}) 0 {} {})", dim1, dim1+8, dim0, dim0+8)
-                        .parse::<Pattern<Language>>()
-                        .unwrap()
-                        .search(&runner.egraph)
-                        .len(),
-                    1
-                );
-            }
-        }
-    }
+/// Lowers a bias addition to a broadcast [`ComputeType::ElementwiseAdd`].
+/// Shared by [`bias_add_relay_to_glenside`] and [`bias_add_to_glenside`].
+struct BiasAddApplier {
+    data_var: Var,
+    bias_var: Var,
+    axis_var: Var,
+}
+impl Applier<Language, MyAnalysis> for BiasAddApplier {
+    fn apply_one(
+        &self,
+        egraph: &mut EGraph<Language, MyAnalysis>,
+        eclass: Id,
+        subst: &Subst,
+        _searcher_ast: Option<&PatternAst<Language>>,
+        _rule_name: Symbol,
+    ) -> Vec<Id> {
+        let axis = match &egraph[subst[self.axis_var]].data {
+            MyAnalysisData::Num(v) => *v,
+            _ => panic!(),
+        };
+        let data_shape = match &egraph[subst[self.data_var]].data {
+            MyAnalysisData::AccessPattern(v) => v.clone(),
+            _ => panic!(),
+        };
+        let axis = if axis < 0 {
+            axis + i64::try_from(data_shape.as_vec().len()).unwrap()
+        } else {
+            axis
+        };
 
-    #[test]
-    fn slice_concatenate_accesses() {
-        test_logger::ensure_env_logger_initialized();
+        let mut expr = RecExpr::default();
+        let data_id = expr.add(Language::Symbol("data_PLACEHOLDER".to_string()));
+        let mut bias_id = expr.add(Language::Symbol("bias_PLACEHOLDER".to_string()));
 
-        let program = "(access (access-tensor t-32-32) 1)".parse().unwrap();
+        // Insert axes before
+        for _ in 0..axis {
+            let zero_id = expr.add(Language::Num(0));
+            bias_id = expr.add(Language::AccessInsertAxis([bias_id, zero_id]));
+        }
 
-        let rws = vec![
-            super::slice_concatenate_accesses(
-                0,
-                SliceConcatenateStrategy::DivideBy {
-                    divisor: 2,
-                    limit: 16,
-                },
-            ),
-            super::slice_concatenate_accesses(
-                1,
-                SliceConcatenateStrategy::DivideBy {
-                    divisor: 2,
-                    limit: 16,
-                },
-            ),
-            super::collapse_nested_access_slices(),
-        ];
+        // Insert axes after
+        for axis in usize::try_from(axis + 1).unwrap()..data_shape.as_vec().len() {
+            let axis_id = expr.add(Language::Num(axis.try_into().unwrap()));
+            bias_id = expr.add(Language::AccessInsertAxis([bias_id, axis_id]));
+        }
 
-        let mut egraph = EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
-        egraph.add_expr(&program);
-        egraph.rebuild();
-        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
-            .with_egraph(egraph)
-            .run(&rws);
+        let access_shape_id = access_shape(&mut expr, &data_shape.as_vec(), &[]);
+        let bias_id = expr.add(Language::AccessBroadcast([bias_id, access_shape_id]));
 
-        assert_eq!(
-            "(access-slice (access-slice (access (access-tensor t-32-32) 1) 1 0 16) 0 0 16)"
-                .parse::<Pattern<Language>>()
-                .unwrap()
-                .search(&runner.egraph)
-                .len(),
-            1
-        );
+        let data_id = access_pair(&mut expr, data_id, bias_id, 0);
+        let _data_id = compute(&mut expr, ComputeType::ElementwiseAdd, data_id);
 
-        assert_eq!(
-            "(access-slice (access-slice (access (access-tensor t-32-32) 1) 1 16 32) 0 0 16)"
-                .parse::<Pattern<Language>>()
-                .unwrap()
-                .search(&runner.egraph)
-                .len(),
-            1
+        let pattern_ast = PatternAst::from(
+            expr.as_ref()
+                .iter()
+                .map(|n| match n {
+                    Language::Symbol(s) if s == "data_PLACEHOLDER" => {
+                        ENodeOrVar::Var(self.data_var)
+                    }
+                    Language::Symbol(s) if s == "bias_PLACEHOLDER" => {
+                        ENodeOrVar::Var(self.bias_var)
+                    }
+                    _ => ENodeOrVar::ENode(n.clone()),
+                })
+                .collect::<Vec<_>>(),
         );
 
-        assert_eq!(
-            "(access-slice (access-slice (access (access-tensor t-32-32) 1) 1 0 16) 0 16 32)"
-                .parse::<Pattern<Language>>()
-                .unwrap()
-                .search(&runner.egraph)
-                .len(),
-            1
-        );
+        let out_id = egraph.add_instantiation(&pattern_ast, subst);
 
-        assert_eq!(
-            "(access-slice (access-slice (access (access-tensor t-32-32) 1) 1 16 32) 0 16 32)"
-                .parse::<Pattern<Language>>()
-                .unwrap()
-                .search(&runner.egraph)
-                .len(),
-            1
-        );
+        let out_id = out_id;
+        egraph.union(eclass, out_id);
+        vec![out_id]
     }
+}
 
-    #[test]
-    fn access_slice_access_transpose_composition_commutative_0() {
-        let program = "
-(access-slice
- (access-transpose
-  (access (access-tensor t-3-32-32) 1)
-  (list 2 0 1)
- )
- 0 16 32
-)
-        "
-        .parse()
-        .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
-        let id = egraph.add_expr(&program);
-        egraph.rebuild();
-        let rws = vec![super::access_slice_access_transpose_composition_commutative()];
-        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
-            .with_egraph(egraph)
-            .run(&rws);
+pub fn bias_add_relay_to_glenside() -> RW {
+    rewrite!("bias-add-relay-to-glenside";
+                "(relay-operator-call relay-bias-add ?data ?bias ?axis)" =>
+                { BiasAddApplier{data_var:"?data".parse().unwrap(), bias_var:"?bias".parse().unwrap(), axis_var:"?axis".parse().unwrap()} })
+}
 
-        let test_pattern = "
-(access-transpose
- (access-slice
-  (access (access-tensor t-3-32-32) 1)
-  2 16 32
- )
- (list 2 0 1)
-)"
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        assert_eq!(matches.substs.len(), 1);
+/// Lowers the high-level `bias-add` node to the access formulation.
+pub fn bias_add_to_glenside() -> RW {
+    rewrite!("bias-add-to-glenside";
+                "(bias-add ?data ?bias ?axis)" =>
+                { BiasAddApplier{data_var:"?data".parse().unwrap(), bias_var:"?bias".parse().unwrap(), axis_var:"?axis".parse().unwrap()} })
+}
+
+/// Fuses a bias addition into the systolic array computing its input, by
+/// folding the bias into the weights as an extra row and appending a column
+/// of ones to the input:
+///
+/// `x W + b = [x 1] [W; b]`
+///
+/// The resulting systolic array has one more row than the original. Only
+/// applies when the bias is added along the systolic array's output-column
+/// axis.
+pub fn fuse_bias_add_into_systolic_array() -> AtomRewrite {
+    struct Impl {
+        rows: Var,
+        cols: Var,
+        a0: Var,
+        a1: Var,
+        bias: Var,
     }
+    impl Applier<Language, MyAnalysis> for Impl {
+        fn apply_one(
+            &self,
+            egraph: &mut EGraph<Language, MyAnalysis>,
+            eclass: Id,
+            subst: &Subst,
+            _searcher_ast: Option<&PatternAst<Language>>,
+            _rule_name: Symbol,
+        ) -> Vec<Id> {
+            let rows = MyAnalysis::get_usize(subst[self.rows], egraph);
+            let a0 = match &egraph[subst[self.a0]].data {
+                MyAnalysisData::AccessPattern(a) => a.clone(),
+                _ => panic!(),
+            };
 
-    #[test]
-    fn access_slice_access_transpose_composition_commutative_1() {
-        let program = "
-(access-slice
- (access-transpose
-  (access (access-tensor t-3-32-32) 1)
-  (list 2 0 1)
- )
- 1 0 2
-)
-        "
-        .parse()
-        .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
-        let id = egraph.add_expr(&program);
-        egraph.rebuild();
-        let rws = vec![super::access_slice_access_transpose_composition_commutative()];
-        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
-            .with_egraph(egraph)
-            .run(&rws);
+            let mut expr = RecExpr::default();
+            let a0_id = expr.add(Language::Symbol("a0_PLACEHOLDER".to_string()));
+            let a1_id = expr.add(Language::Symbol("a1_PLACEHOLDER".to_string()));
+            let bias_id = expr.add(Language::Symbol("bias_PLACEHOLDER".to_string()));
+            let cols_id = expr.add(Language::Symbol("cols_PLACEHOLDER".to_string()));
+
+            // Build a column of ones with shape [a0.shape..., 1] and access it
+            // the same way as a0.
+            let one_id = expr.add(Language::NotNanFloat64(
+                ordered_float::NotNan::new(1.0).unwrap(),
+            ));
+            let one_id = expr.add(Language::Literal(one_id));
+            let mut ones_id = expr.add(Language::AccessLiteral(one_id));
+            for _ in 0..a0.shape.ndim() + 1 {
+                ones_id = access_insert_axis(&mut expr, ones_id, 0);
+            }
+            let ones_shape_id = access_shape(
+                &mut expr,
+                &a0.shape
+                    .slice()
+                    .iter()
+                    .cloned()
+                    .chain(std::iter::once(1))
+                    .collect::<Vec<_>>(),
+                &[],
+            );
+            let ones_id = expr.add(Language::AccessBroadcast([ones_id, ones_shape_id]));
+            let ones_id = from_relay::access(&mut expr, ones_id, a0.shape.ndim());
+            let a0_id = access_concatenate(&mut expr, a0_id, ones_id, a0.shape.ndim());
 
-        let test_pattern = "
-(access-transpose
- (access-slice
-  (access (access-tensor t-3-32-32) 1)
-  0 0 2
- )
- (list 2 0 1)
-)"
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        assert_eq!(matches.substs.len(), 1);
-    }
-
-    #[test]
-    fn access_slice_access_transpose_composition_commutative_2() {
-        let program = "
-(access-slice
- (access-transpose
-  (access (access-tensor t-3-32-32) 1)
-  (list 1 0 2)
- )
- 2 0 16
-)
-        "
-        .parse()
-        .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
-        let id = egraph.add_expr(&program);
-        egraph.rebuild();
-        let rws = vec![super::access_slice_access_transpose_composition_commutative()];
-        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
-            .with_egraph(egraph)
-            .run(&rws);
+            // Append the bias to the weights as a final row.
+            let bias_id = access_insert_axis(&mut expr, bias_id, 0);
+            let bias_id = from_relay::access(&mut expr, bias_id, 0);
+            let a1_id = access_concatenate(&mut expr, a1_id, bias_id, 0);
 
-        let test_pattern = "
-(access-transpose
- (access-slice (access (access-tensor t-3-32-32) 1) 2 0 16)
- (list 1 0 2)
-)"
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        assert_eq!(matches.substs.len(), 1);
-    }
+            let rows_id = expr.add(Language::Num((rows + 1).try_into().unwrap()));
+            expr.add(Language::SystolicArray([rows_id, cols_id, a0_id, a1_id]));
 
-    #[test]
-    fn access_slice_access_transpose_composition_commutative_3() {
-        let program = "
-(access-slice
- (access-transpose
-  (access (access-tensor t-3-32-32) 1)
-  (list 1 0 2)
- )
- 1 0 2
-)"
-        .parse()
-        .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
-        let id = egraph.add_expr(&program);
-        egraph.rebuild();
-        let rws = vec![super::access_slice_access_transpose_composition_commutative()];
-        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
-            .with_egraph(egraph)
-            .run(&rws);
+            let pattern_ast = PatternAst::from(
+                expr.as_ref()
+                    .iter()
+                    .map(|n| match n {
+                        Language::Symbol(s) if s == "a0_PLACEHOLDER" => ENodeOrVar::Var(self.a0),
+                        Language::Symbol(s) if s == "a1_PLACEHOLDER" => ENodeOrVar::Var(self.a1),
+                        Language::Symbol(s) if s == "bias_PLACEHOLDER" => {
+                            ENodeOrVar::Var(self.bias)
+                        }
+                        Language::Symbol(s) if s == "cols_PLACEHOLDER" => {
+                            ENodeOrVar::Var(self.cols)
+                        }
+                        _ => ENodeOrVar::ENode(n.clone()),
+                    })
+                    .collect::<Vec<_>>(),
+            );
 
-        let test_pattern = "
-(access-transpose
- (access-slice (access (access-tensor t-3-32-32) 1) 0 0 2)
- (list 1 0 2)
-)"
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        assert_eq!(matches.substs.len(), 1);
+            let out_id = egraph.add_instantiation(&pattern_ast, subst);
+            egraph.union(eclass, out_id);
+            vec![out_id]
+        }
     }
 
-    #[test]
-    fn bubble_access_concatenate_through_access_transpose_0() {
-        let program = "
-(access-transpose
- (access-concatenate
-  (access (access-tensor t-3-32-32) 1)
-  (access (access-tensor t-3-32-32) 1)
-  0
- )
- (list 1 2 0)
-)"
-        .parse()
-        .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
-        let id = egraph.add_expr(&program);
-        egraph.rebuild();
-        let rws = vec![super::bubble_access_concatenate_through_access_transpose()];
-        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
-            .with_egraph(egraph)
-            .run(&rws);
-
-        let test_pattern = "
-(access-concatenate
- (access-transpose (access (access-tensor t-3-32-32) 1) (list 1 2 0))
- (access-transpose (access (access-tensor t-3-32-32) 1) (list 1 2 0))
- 2
-)"
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        assert_eq!(matches.substs.len(), 1);
+    fn bias_is_on_output_column_axis(
+        a0: Var,
+        axis: Var,
+    ) -> impl Fn(&mut EG, egg::Id, &egg::Subst) -> bool {
+        move |egraph, _id, subst| match &egraph[subst[a0]].data {
+            MyAnalysisData::AccessPattern(a0) => {
+                MyAnalysis::get_usize(subst[axis], egraph) == a0.shape.ndim()
+            }
+            _ => false,
+        }
     }
 
-    #[test]
-    fn bubble_access_concatenate_through_access_transpose_1() {
-        let program = "
-(access-transpose
- (access-concatenate
-  (access (access-tensor t-3-32-32) 1)
-  (access (access-tensor t-3-32-32) 1)
-  1
- )
- (list 1 2 0)
-)"
-        .parse()
-        .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
-        let id = egraph.add_expr(&program);
-        egraph.rebuild();
-        let rws = vec![super::bubble_access_concatenate_through_access_transpose()];
-        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
-            .with_egraph(egraph)
-            .run(&rws);
+    AtomRewrite::new(
+        rewrite!("fuse-bias-add-into-systolic-array";
+                 "(bias-add (systolic-array ?rows ?cols ?a0 ?a1) ?bias ?axis)" =>
+                 { Impl {
+                     rows: "?rows".parse().unwrap(),
+                     cols: "?cols".parse().unwrap(),
+                     a0: "?a0".parse().unwrap(),
+                     a1: "?a1".parse().unwrap(),
+                     bias: "?bias".parse().unwrap(),
+                 } }
+                 if bias_is_on_output_column_axis(
+                     "?a0".parse().unwrap(),
+                     "?axis".parse().unwrap())),
+        &["?a0", "?a1", "?bias"],
+        Some(num_plus("?rows", 1)),
+    )
+}
 
-        let test_pattern = "
-(access-concatenate
- (access-transpose (access (access-tensor t-3-32-32) 1) (list 1 2 0))
- (access-transpose (access (access-tensor t-3-32-32) 1) (list 1 2 0))
- 0
-)"
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        assert_eq!(matches.substs.len(), 1);
+pub fn concatenate_relay_to_glenside() -> RW {
+    struct Impl {
+        axis_var: Var,
+        tuple_var: Var,
     }
+    impl Applier<Language, MyAnalysis> for Impl {
+        fn apply_one(
+            &self,
+            egraph: &mut EGraph<Language, MyAnalysis>,
+            eclass: Id,
+            subst: &Subst,
+            _searcher_ast: Option<&PatternAst<Language>>,
+            _rule_name: Symbol,
+        ) -> Vec<Id> {
+            let tuple_len = match &egraph[subst[self.tuple_var]].data {
+                MyAnalysisData::Tuple(t) => t.len(),
+                _ => panic!(),
+            };
+            let axis = match &egraph[subst[self.axis_var]].data {
+                MyAnalysisData::Num(v) => *v,
+                _ => panic!(),
+            };
 
-    #[test]
-    fn bubble_access_concatenate_through_access_transpose_2() {
-        let program = "
-(access-transpose
- (access-concatenate
-  (access (access-tensor t-3-32-32) 1)
-  (access (access-tensor t-3-32-32) 1)
-  2
- )
- (list 1 0 2)
-)"
-        .parse()
-        .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
-        let id = egraph.add_expr(&program);
-        egraph.rebuild();
-        let rws = vec![super::bubble_access_concatenate_through_access_transpose()];
-        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
-            .with_egraph(egraph)
-            .run(&rws);
+            let mut expr = RecExpr::default();
 
-        let test_pattern = "
-(access-concatenate
- (access-transpose (access (access-tensor t-3-32-32) 1) (list 1 0 2))
- (access-transpose (access (access-tensor t-3-32-32) 1) (list 1 0 2))
- 2
-)"
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        assert_eq!(matches.substs.len(), 1);
-    }
+            let tuple_id = expr.add(Language::Symbol("tuple_PLACEHOLDER".to_string()));
 
-    #[test]
-    fn bubble_access_concatenate_through_access_transpose_3() {
-        let program = "
-(access-transpose
- (access-concatenate
-  (access (access-tensor t-3-32-32) 1)
-  (access (access-tensor t-3-32-32) 1)
-  1
- )
- (list 2 0 1)
-)"
-        .parse()
-        .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
-        let id = egraph.add_expr(&program);
-        egraph.rebuild();
-        let rws = vec![super::bubble_access_concatenate_through_access_transpose()];
-        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
-            .with_egraph(egraph)
-            .run(&rws);
+            let tensor_ids: Vec<Id> = (0..tuple_len)
+                .map(|i| {
+                    let i_id = expr.add(Language::Num(i.try_into().unwrap()));
+                    expr.add(Language::TupleGetItem([tuple_id, i_id]))
+                })
+                .collect();
 
-        let test_pattern = "
-(access-concatenate
- (access-transpose (access (access-tensor t-3-32-32) 1) (list 2 0 1))
- (access-transpose (access (access-tensor t-3-32-32) 1) (list 2 0 1))
- 2
-)"
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        assert_eq!(matches.substs.len(), 1);
-    }
+            assert!(tensor_ids.len() > 0);
 
-    #[test]
-    fn bubble_access_concatenate_through_access_cartesian_product_left() {
-        let program = "
-             (access-cartesian-product
-              (access-concatenate (access (access-tensor t-3-32-32) 1) (access (access-tensor t-3-32-32) 1) 0)
-              (access-concatenate (access (access-tensor t-3-32-32) 1) (access (access-tensor t-3-32-32) 1) 0)
-             )"
-        .parse()
-        .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+            let mut concatted_id = tensor_ids[0];
+            for id in tensor_ids[1..].iter() {
+                // TODO(@gussmith23) Layout assumption
+                concatted_id =
+                    access_concatenate(&mut expr, concatted_id, *id, axis.try_into().unwrap());
+            }
+
+            let pattern_ast = PatternAst::from(
+                expr.as_ref()
+                    .iter()
+                    .map(|n| match n {
+                        Language::Symbol(s) if s == "tuple_PLACEHOLDER" => {
+                            ENodeOrVar::Var(self.tuple_var)
+                        }
+                        _ => ENodeOrVar::ENode(n.clone()),
+                    })
+                    .collect::<Vec<_>>(),
+            );
+
+            let out_id = egraph.add_instantiation(&pattern_ast, subst);
+
+            let out_id = out_id;
+            egraph.union(eclass, out_id);
+            vec![out_id]
+        }
+    }
+    rewrite!("concatenate-relay-to-glenside";
+                "(relay-operator-call relay-concatenate ?axis ?tuple)" =>
+                { Impl{axis_var:"?axis".parse().unwrap(), tuple_var: "?tuple".parse().unwrap()} })
+}
+
+pub fn simplify_tuple_get_item() -> RW {
+    struct SearcherImpl(Var);
+    impl Searcher<Language, MyAnalysis> for SearcherImpl {
+        fn search_eclass(
+            &self,
+            egraph: &EGraph<Language, MyAnalysis>,
+            eclass: Id,
+        ) -> Option<egg::SearchMatches<Language>> {
+            let substs: Vec<Subst> = egraph[eclass]
+                .nodes
+                .iter()
+                .filter_map(|v| match v {
+                    Language::TupleGetItem([t_id, idx_id]) => Some((*t_id, *idx_id)),
+                    _ => None,
+                })
+                .flat_map(|(tuple_id, idx_id)| {
+                    let idx = match egraph[idx_id].data {
+                        MyAnalysisData::Num(v) => v,
+                        _ => panic!(),
+                    };
+                    egraph[tuple_id].nodes.iter().filter_map(move |v| match v {
+                        Language::ConstructTuple(ids) => {
+                            let mut subst = Subst::default();
+                            subst.insert(self.0, ids[usize::try_from(idx).unwrap()]);
+                            Some(subst)
+                        }
+                        _ => None,
+                    })
+                })
+                .collect();
+            if substs.is_empty() {
+                None
+            } else {
+                Some(SearchMatches {
+                    eclass,
+                    substs,
+                    ast: None,
+                })
+            }
+        }
+
+        fn vars(&self) -> Vec<Var> {
+            vec![self.0]
+        }
+    }
+
+    rewrite!("simplify-tuple-get-item";
+                { SearcherImpl("?val".parse().unwrap()) } => "?val")
+}
+
+pub fn transpose_relay_to_glenside() -> RW {
+    rewrite!("transpose-relay-to-glenside";
+                "(relay-operator-call relay-transpose ?data ?axes)" => "(access-transpose ?data ?axes)")
+}
+
+pub fn reshape_relay_to_glenside() -> RW {
+    rewrite!("reshape-relay-to-glenside";
+                "(relay-operator-call relay-reshape ?data ?shape)" => "(access-reshape ?data (access-shape (shape) ?shape))")
+}
+
+pub fn squeeze_relay_to_glenside() -> RW {
+    struct Impl {
+        axis_var: Var,
+        data_var: Var,
+    }
+    impl Applier<Language, MyAnalysis> for Impl {
+        fn apply_one(
+            &self,
+            egraph: &mut EGraph<Language, MyAnalysis>,
+            eclass: Id,
+            subst: &Subst,
+            _searcher_ast: Option<&PatternAst<Language>>,
+            _rule_name: Symbol,
+        ) -> Vec<Id> {
+            let mut axes = match &egraph[subst[self.axis_var]].data {
+                MyAnalysisData::List(v) => v.clone(),
+                _ => panic!(),
+            };
+            axes.sort();
+
+            let mut expr = RecExpr::default();
+
+            let mut data_id = expr.add(Language::Symbol("data_PLACEHOLDER".to_string()));
+
+            for axis in axes.iter().rev() {
+                let usize_id = expr.add(Language::Num((*axis).try_into().unwrap()));
+                data_id = expr.add(Language::AccessSqueeze([data_id, usize_id]));
+            }
+
+            let pattern_ast = PatternAst::from(
+                expr.as_ref()
+                    .iter()
+                    .map(|n| match n {
+                        Language::Symbol(s) if s == "data_PLACEHOLDER" => {
+                            ENodeOrVar::Var(self.data_var)
+                        }
+                        _ => ENodeOrVar::ENode(n.clone()),
+                    })
+                    .collect::<Vec<_>>(),
+            );
+
+            let out_id = egraph.add_instantiation(&pattern_ast, subst);
+
+            let out_id = out_id;
+            egraph.union(eclass, out_id);
+            vec![out_id]
+        }
+    }
+    rewrite!("squeeze-relay-to-glenside";
+                "(relay-operator-call relay-squeeze ?data ?axis)" =>
+                { Impl{axis_var:"?axis".parse().unwrap(), data_var: "?data".parse().unwrap()} })
+}
+
+pub fn relay_to_glenside_rewrites() -> Vec<RW> {
+    vec![
+        conv2d_relay_to_glenside(),
+        conv1d_relay_to_glenside(),
+        softmax_relay_to_glenside(),
+        relu_relay_to_glenside(),
+        negative_relay_to_glenside(),
+        sqrt_relay_to_glenside(),
+        max_pool2d_relay_to_glenside_nchw(),
+        global_avg_pool2d_relay_to_glenside_nchw(),
+        batch_matmul_relay_to_glenside(),
+        layer_norm_relay_to_glenside(),
+        dropout_relay_to_glenside(),
+        expand_dims_relay_to_glenside(),
+        eliminate_expand_dims_zero_num_newaxis(),
+        pad_relay_to_glenside(),
+        dense_relay_to_glenside(),
+        add_relay_to_glenside(),
+        multiply_relay_to_glenside(),
+        divide_relay_to_glenside(),
+        batch_flatten_relay_to_glenside(),
+        bias_add_relay_to_glenside(),
+        concatenate_relay_to_glenside(),
+        simplify_tuple_get_item(),
+        transpose_relay_to_glenside(),
+        reshape_relay_to_glenside(),
+        squeeze_relay_to_glenside(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::language::interpreter::interpret;
+    use crate::language::RelayOperator;
+    use crate::language::{Language, MyAnalysis};
+    use crate::testing::{assert_close, Tolerance};
+    use egg::{EGraph, Pattern, RecExpr, Runner, Searcher};
+    use ndarray::IxDyn;
+    use ndarray_npy::{read_npy, write_npy};
+    use ndarray_rand::{rand_distr::Uniform, RandomExt};
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+    use std::collections::HashMap;
+    use std::io::Write;
+    use std::process::Command;
+    use std::str::FromStr;
+    use test_logger::test;
+
+    #[test]
+    fn flexasr_maxpool() {
+        let mut map = HashMap::default();
+        map.insert("a".to_string(), vec![32, 32]);
+        let program = "
+         (compute reduce-max (access-windows (access (access-tensor a) 1) (shape 2) (shape 2)))
+        "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
         let id = egraph.add_expr(&program);
         egraph.rebuild();
-        let rws = vec![
-            super::bubble_access_concatenate_through_access_cartesian_product_not_item_axis_left(),
-        ];
+
+        let rws = vec![super::flexasr_maxpool()];
+
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
             .run(&rws);
+        match runner.stop_reason.unwrap() {
+            egg::StopReason::Saturated => (),
+            _ => panic!(),
+        };
 
         let test_pattern = "
-             (access-concatenate
-              (access-cartesian-product
-               (access (access-tensor t-3-32-32) 1)
-               (access-concatenate (access (access-tensor t-3-32-32) 1) (access (access-tensor t-3-32-32) 1) 0)
-              )
-              (access-cartesian-product
-               (access (access-tensor t-3-32-32) 1)
-               (access-concatenate (access (access-tensor t-3-32-32) 1) (access (access-tensor t-3-32-32) 1) 0)
-              )
-              0
-             )"
+            (access
+             (access-transpose
+              (accelerator-call flex-maxpool
+               (access 
+                (access-transpose 
+                 (access (access-tensor a) 1)
+                 (list 1 0))
+                 0) ?shape)
+              (list 1 0))
+             2)"
         .parse::<Pattern<Language>>()
         .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
@@ -4363,144 +5565,227 @@ mod tests {
     }
 
     #[test]
-    fn bubble_access_concatenate_through_access_cartesian_product_right() {
+    fn flatten_unflatten_access_windows() {
+        test_logger::ensure_env_logger_initialized();
+
         let program = "
-             (access-cartesian-product
-              (access-concatenate (access (access-tensor t-3-32-32) 1) (access (access-tensor t-3-32-32) 1) 0)
-              (access-concatenate (access (access-tensor t-3-32-32) 1) (access (access-tensor t-3-32-32) 1) 0)
-             )"
+         (access-windows
+          (access (access-tensor t-3-32-32) 0)
+          (shape 3 3 3)
+          (shape 1 1 2)
+         )
+         "
         .parse()
         .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
-        let id = egraph.add_expr(&program);
+
+        let rws = vec![super::flatten_unflatten_any_access()];
+        let mut egraph = EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        egraph.add_expr(&program);
         egraph.rebuild();
-        let rws = vec![
-            super::bubble_access_concatenate_through_access_cartesian_product_not_item_axis_right(),
-        ];
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
             .run(&rws);
 
-        let test_pattern = "
-             (access-concatenate
-              (access-cartesian-product
-               (access-concatenate (access (access-tensor t-3-32-32) 1) (access (access-tensor t-3-32-32) 1) 0)
-               (access (access-tensor t-3-32-32) 1)
+        assert_eq!(
+            "
+            (access-reshape
+             (access-flatten
+              (access-windows
+               (access (access-tensor t-3-32-32) 0)
+               (shape 3 3 3)
+               (shape 1 1 2)
               )
-              (access-cartesian-product
-               (access-concatenate (access (access-tensor t-3-32-32) 1) (access (access-tensor t-3-32-32) 1) 0)
-               (access (access-tensor t-3-32-32) 1)
+             )
+             (get-access-shape
+              (access-windows
+               (access (access-tensor t-3-32-32) 0)
+               (shape 3 3 3)
+               (shape 1 1 2)
               )
-              1
-             )"
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        assert_eq!(matches.substs.len(), 1);
+             )
+            )
+            "
+            .parse::<Pattern<Language>>()
+            .unwrap()
+            .search(&runner.egraph)
+            .len(),
+            1
+        );
     }
 
     #[test]
-    fn bubble_access_concatenate_through_access_cartesian_product_same_item_axis_0() {
+    fn bubble_reshape_through_cartesian_product() {
+        test_logger::ensure_env_logger_initialized();
+
         let program = "
-             (access-cartesian-product
-              (access-concatenate (access (access-tensor t-3-32-32) 1) (access (access-tensor t-3-32-32) 1) 1)
-              (access-concatenate (access (access-tensor t-3-32-32) 1) (access (access-tensor t-3-32-32) 1) 1)
-             )"
+         (access-cartesian-product
+          (access (access-tensor t-8-3-3-3) 1)
+          (access-squeeze
+           (access-windows
+            (access (access-tensor t-3-32-32) 0)
+            (shape 3 3 3)
+            (shape 1 1 1)
+           )
+           0
+          )
+         )
+        "
         .parse()
         .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
-        let id = egraph.add_expr(&program);
-        egraph.rebuild();
         let rws = vec![
-            super::bubble_access_concatenate_through_access_cartesian_product_same_item_axis(),
+            super::flatten_unflatten_any_access(),
+            super::bubble_reshape_through_cartesian_product(),
         ];
+        let mut egraph = EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
             .run(&rws);
 
         let test_pattern = "
-             (access-concatenate
-              (access-cartesian-product
-               (access (access-tensor t-3-32-32) 1)
-               (access (access-tensor t-3-32-32) 1)
+            (access-reshape
+             (access-cartesian-product
+              (access-flatten
+               (access (access-tensor t-8-3-3-3) 1)
               )
-              (access-cartesian-product
-               (access (access-tensor t-3-32-32) 1)
-               (access (access-tensor t-3-32-32) 1)
+              (access-flatten
+               (access-squeeze
+                (access-windows
+                 (access (access-tensor t-3-32-32) 0)
+                 (shape 3 3 3)
+                 (shape 1 1 1)
+                )
+                0
+               )
               )
-              3
-             )"
+             )
+             ?shape
+            )
+            "
         .parse::<Pattern<Language>>()
         .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
         assert_eq!(matches.substs.len(), 1);
+
+        // ?shape should be what we expect. This is kind of over-testing, in the
+        // sense that, if we find the above pattern, then the analysis data is
+        // guaranteed (for now) to match, and so the shapes should be the same,
+        // but I figure over-testing is better than under-testing (given that
+        // "for now").
+        match &runner.egraph[matches.substs[0]["?shape".parse().unwrap()]].data {
+            MyAnalysisData::AccessPattern(a) => {
+                assert_eq!(a.item_shape, IxDyn(&[2, 3, 3, 3]));
+                assert_eq!(a.shape, IxDyn(&[8, 30, 30]));
+            }
+            _ => panic!(),
+        }
     }
 
     #[test]
-    fn bubble_access_concatenate_through_access_cartesian_product_same_item_axis_1() {
+    fn bubble_reshape_through_dot_product() {
+        test_logger::ensure_env_logger_initialized();
+
         let program = "
-             (access-cartesian-product
-              (access-concatenate (access (access-tensor t-3-32-32) 0) (access (access-tensor t-3-32-32) 0) 2)
-              (access-concatenate (access (access-tensor t-3-32-32) 0) (access (access-tensor t-3-32-32) 0) 2)
-             )"
+         (compute dot-product
+          (access-reshape (access (access-tensor t-1024-2-256) 1) (access-shape (shape 32 32) (shape 2 16 16)))
+         )
+        "
         .parse()
         .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let rws = vec![super::bubble_reshape_through_compute_dot_product()];
+        let mut egraph = EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
         let id = egraph.add_expr(&program);
         egraph.rebuild();
-        let rws = vec![
-            super::bubble_access_concatenate_through_access_cartesian_product_same_item_axis(),
-        ];
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
             .run(&rws);
 
         let test_pattern = "
-             (access-concatenate
-              (access-cartesian-product
-               (access (access-tensor t-3-32-32) 0)
-               (access (access-tensor t-3-32-32) 0)
-              )
-              (access-cartesian-product
-               (access (access-tensor t-3-32-32) 0)
-               (access (access-tensor t-3-32-32) 0)
-              )
-              3
-             )"
+            (access-reshape
+             (compute dot-product
+              (access (access-tensor t-1024-2-256) 1)
+             )
+             ?shape
+            )
+            "
         .parse::<Pattern<Language>>()
         .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
         assert_eq!(matches.substs.len(), 1);
-    }
 
+        match &runner.egraph[matches.substs[0]["?shape".parse().unwrap()]].data {
+            MyAnalysisData::AccessPattern(a) => {
+                assert_eq!(a.item_shape, IxDyn(&[]));
+                assert_eq!(a.shape, IxDyn(&[32, 32]));
+            }
+            _ => panic!(),
+        }
+    }
     #[test]
-    fn bubble_access_concatenate_through_compute_dot_product_not_item_axis() {
+    fn conv1d_im2col_systolic_array() {
         let program = "
-             (compute dot-product
-              (access-concatenate
-               (access (access-tensor t-3-32-32) 1)
-               (access (access-tensor t-3-32-32) 1)
-               0
+        (access-transpose
+            (compute dot-product
+              (access-cartesian-product
+               (access (access-tensor weights) 1)
+               (access-squeeze
+                (access-windows
+                 (access
+                  (access-pad
+                   (access-tensor data)
+                   zero-padding
+                   2 3 4
+                  )
+                  1
+                 )
+                 (shape 3 3)
+                 (shape 1 2)
+                )
+                1
+               )
               )
-             )"
+            )
+            (list 1 0 2)
+           )
+        "
         .parse()
         .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+
+        let mut map = HashMap::new();
+        map.insert("data".to_string(), vec![1, 3, 32]);
+        map.insert("weights".to_string(), vec![8, 3, 3]);
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
         let id = egraph.add_expr(&program);
         egraph.rebuild();
-        let rws =
-            vec![super::bubble_access_concatenate_through_compute_dot_product_not_item_axis()];
+
+        let rws = vec![
+            super::flatten_unflatten_any_access(),
+            super::bubble_reshape_through_cartesian_product(),
+            super::bubble_reshape_through_compute_dot_product(),
+            super::systolic_array().rewrite,
+        ];
+
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
             .run(&rws);
 
         let test_pattern = "
-             (access-concatenate
-              (compute dot-product (access (access-tensor t-3-32-32) 1))
-              (compute dot-product (access (access-tensor t-3-32-32) 1))
-              0
-             )
-             "
+        (access-transpose
+         (access-reshape
+          (systolic-array ?rows ?cols
+            ?a
+            ?b
+          )
+          ?shape
+         )
+         ?transpose-list
+        )
+            "
         .parse::<Pattern<Language>>()
         .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
@@ -4508,202 +5793,307 @@ mod tests {
     }
 
     #[test]
-    fn bubble_access_concatenate_through_compute_dot_product_item_access_0() {
+    fn conv2d_im2col_systolic_array() {
         let program = "
-             (compute dot-product
-              (access-concatenate
-               (access (access-tensor t-3-32-32) 1)
-               (access (access-tensor t-3-32-32) 1)
-               1
-              )
-             )"
+         (compute dot-product
+          (access-cartesian-product
+           (access (access-tensor t-8-3-3-3) 1)
+           (access-squeeze
+            (access-windows
+             (access (access-tensor t-3-32-32) 0)
+             (shape 3 3 3)
+             (shape 1 1 1)
+            )
+            0
+           )
+          )
+         )
+        "
         .parse()
         .unwrap();
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
         let id = egraph.add_expr(&program);
         egraph.rebuild();
-        let rws = vec![super::bubble_access_concatenate_through_compute_dot_product_item_axis()];
+
+        let rws = vec![
+            super::flatten_unflatten_any_access(),
+            super::bubble_reshape_through_cartesian_product(),
+            super::bubble_reshape_through_compute_dot_product(),
+            super::systolic_array().rewrite,
+        ];
+
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
             .run(&rws);
 
         let test_pattern = "
-             (compute reduce-sum
-              (access-pair
-               (compute dot-product (access (access-tensor t-3-32-32) 1))
-               (compute dot-product (access (access-tensor t-3-32-32) 1))
-              )
-             )
-             "
-        .parse::<Pattern<Language>>()
-        .unwrap();
+            (access-reshape
+             (systolic-array 27 900
+              (access-flatten (access (access-tensor t-8-3-3-3) 1))
+              (access
+               (access-transpose
+                (access-flatten
+                 (access-squeeze
+                  (access-windows
+                   (access (access-tensor t-3-32-32) 0)
+                   (shape 3 3 3)
+                   (shape 1 1 1)
+                  )
+                  0
+                 )
+                )
+                (list 1 0)
+               )
+               0
+              )
+             )
+             ?shape
+            )
+            "
+        .parse::<Pattern<Language>>()
+        .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
         assert_eq!(matches.substs.len(), 1);
     }
 
     #[test]
-    fn bubble_access_concatenate_through_compute_dot_product_item_access_1() {
-        let program = "
-             (compute dot-product
-              (access-concatenate
-               (access (access-tensor t-3-32-32) 1)
-               (access (access-tensor t-3-32-32) 1)
-               2
-              )
-             )"
-        .parse()
-        .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
-        let id = egraph.add_expr(&program);
+    fn slice_concatenate_accesses_divide_into_0() {
+        test_logger::ensure_env_logger_initialized();
+
+        let program = "(access (access-tensor t-32-32) 1)".parse().unwrap();
+
+        let rws = vec![
+            super::slice_concatenate_accesses(
+                0,
+                SliceConcatenateStrategy::DivideInto { segment_size: 8 },
+            ),
+            super::slice_concatenate_accesses(
+                1,
+                SliceConcatenateStrategy::DivideInto { segment_size: 8 },
+            ),
+            super::collapse_nested_access_slices(),
+        ];
+
+        let mut egraph = EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        egraph.add_expr(&program);
         egraph.rebuild();
-        let rws = vec![super::bubble_access_concatenate_through_compute_dot_product_item_axis()];
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
             .run(&rws);
 
-        let test_pattern = "
-             (compute reduce-sum
-              (access-pair
-               (compute dot-product (access (access-tensor t-3-32-32) 1))
-               (compute dot-product (access (access-tensor t-3-32-32) 1))
-              )
-             )
-             "
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        assert_eq!(matches.substs.len(), 1);
+        for dim0 in &[0, 8, 16, 24] {
+            for dim1 in &[0, 8, 16, 24] {
+                assert_eq!(
+                    format!("(access-slice (access-slice (access (access-tensor t-32-32) 1) 1 {} {}) 0 {} {})", dim1, dim1+8, dim0, dim0+8)
+                        .parse::<Pattern<Language>>()
+                        .unwrap()
+                        .search(&runner.egraph)
+                        .len(),
+                    1
+                );
+            }
+        }
     }
 
     #[test]
-    fn bubble_access_concatenate_through_access() {
-        let program = "
-             (access
-              (access-concatenate
-               (access (access-tensor t-3-32-32) 1)
-               (access (access-tensor t-3-32-32) 1)
-               2
-              )
-              0
-             )"
-        .parse()
-        .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+    fn tiling_factors() {
+        assert_eq!(
+            TilingFactors::PowersOfTwo { max: 16 }.factors(48),
+            vec![2, 4, 8, 16]
+        );
+        assert_eq!(
+            TilingFactors::PowersOfTwo { max: 16 }.factors(16),
+            vec![2, 4, 8]
+        );
+        assert_eq!(TilingFactors::PowersOfTwo { max: 16 }.factors(7), vec![]);
+        assert_eq!(TilingFactors::Divisors.factors(12), vec![2, 3, 4, 6]);
+        assert_eq!(TilingFactors::Divisors.factors(13), vec![]);
+        assert_eq!(
+            TilingFactors::List(vec![32, 3, 1, 3, 5]).factors(30),
+            vec![3, 5]
+        );
+        assert_eq!(TilingFactors::List(vec![8, 3]).to_string(), "factors-8-3");
+    }
+
+    #[test]
+    fn slice_concatenate_accesses_tile() {
+        test_logger::ensure_env_logger_initialized();
+
+        let program = "(access (access-tensor t-32-32) 1)".parse().unwrap();
+
+        let rws = vec![super::slice_concatenate_accesses(
+            0,
+            SliceConcatenateStrategy::Tile {
+                factors: TilingFactors::List(vec![8, 16, 24]),
+            },
+        )];
+
+        let mut egraph = EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
         let id = egraph.add_expr(&program);
         egraph.rebuild();
-        let rws = vec![super::bubble_access_concatenate_through_access()];
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
+            .with_iter_limit(1)
             .run(&rws);
 
-        let test_pattern = "
-             (access-concatenate
-              (access
-               (access (access-tensor t-3-32-32) 1)
-               0
-              )
-              (access
-               (access (access-tensor t-3-32-32) 1)
-               0
-              )
-              2
-             )
-             "
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        assert_eq!(matches.substs.len(), 1);
+        // Tiles of 8 and of 16, but not of 24, which doesn't divide 32.
+        for segment_size in &[8usize, 16] {
+            let concat = (*segment_size..32).step_by(*segment_size).fold(
+                format!("(access-slice ?a 0 0 {})", segment_size),
+                |concat, low| {
+                    format!(
+                        "(access-concatenate {} (access-slice ?a 0 {} {}) 0)",
+                        concat,
+                        low,
+                        low + segment_size
+                    )
+                },
+            );
+            assert!(concat
+                .parse::<Pattern<Language>>()
+                .unwrap()
+                .search_eclass(&runner.egraph, id)
+                .is_some());
+        }
+        assert!("(access-slice ?a 0 0 24)"
+            .parse::<Pattern<Language>>()
+            .unwrap()
+            .search(&runner.egraph)
+            .is_empty());
     }
 
     #[test]
-    fn bubble_access_concatenate_through_access_slice_0() {
-        let program = "
-             (access-slice
-              (access-concatenate
-               (access (access-tensor t-32-32) 1)
-               (access (access-tensor t-32-64) 1)
-               1
-              )
-              1 0 16
-             )"
-        .parse()
-        .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
-        let id = egraph.add_expr(&program);
+    fn slice_concatenate_accesses() {
+        test_logger::ensure_env_logger_initialized();
+
+        let program = "(access (access-tensor t-32-32) 1)".parse().unwrap();
+
+        let rws = vec![
+            super::slice_concatenate_accesses(
+                0,
+                SliceConcatenateStrategy::DivideBy {
+                    divisor: 2,
+                    limit: 16,
+                },
+            ),
+            super::slice_concatenate_accesses(
+                1,
+                SliceConcatenateStrategy::DivideBy {
+                    divisor: 2,
+                    limit: 16,
+                },
+            ),
+            super::collapse_nested_access_slices(),
+        ];
+
+        let mut egraph = EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        egraph.add_expr(&program);
         egraph.rebuild();
-        let rws = vec![super::bubble_access_concatenate_through_access_slice()];
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
             .run(&rws);
 
-        let test_pattern = "(access-slice (access (access-tensor t-32-32) 1) 1 0 16)"
-            .parse::<Pattern<Language>>()
-            .unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        assert_eq!(matches.substs.len(), 1);
+        assert_eq!(
+            "(access-slice (access-slice (access (access-tensor t-32-32) 1) 1 0 16) 0 0 16)"
+                .parse::<Pattern<Language>>()
+                .unwrap()
+                .search(&runner.egraph)
+                .len(),
+            1
+        );
+
+        assert_eq!(
+            "(access-slice (access-slice (access (access-tensor t-32-32) 1) 1 16 32) 0 0 16)"
+                .parse::<Pattern<Language>>()
+                .unwrap()
+                .search(&runner.egraph)
+                .len(),
+            1
+        );
+
+        assert_eq!(
+            "(access-slice (access-slice (access (access-tensor t-32-32) 1) 1 0 16) 0 16 32)"
+                .parse::<Pattern<Language>>()
+                .unwrap()
+                .search(&runner.egraph)
+                .len(),
+            1
+        );
+
+        assert_eq!(
+            "(access-slice (access-slice (access (access-tensor t-32-32) 1) 1 16 32) 0 16 32)"
+                .parse::<Pattern<Language>>()
+                .unwrap()
+                .search(&runner.egraph)
+                .len(),
+            1
+        );
     }
 
     #[test]
-    fn bubble_access_concatenate_through_access_slice_1() {
+    fn access_slice_access_transpose_composition_commutative_0() {
         let program = "
-             (access-slice
-              (access-concatenate
-               (access (access-tensor t-32-32) 1)
-               (access (access-tensor t-32-64) 1)
-               1
-              )
-              1 48 64
-             )"
+(access-slice
+ (access-transpose
+  (access (access-tensor t-3-32-32) 1)
+  (list 2 0 1)
+ )
+ 0 16 32
+)
+        "
         .parse()
         .unwrap();
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
         let id = egraph.add_expr(&program);
         egraph.rebuild();
-        let rws = vec![super::bubble_access_concatenate_through_access_slice()];
+        let rws = vec![super::access_slice_access_transpose_composition_commutative()];
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
             .run(&rws);
 
-        let test_pattern = "(access-slice (access (access-tensor t-32-64) 1) 1 16 32)"
-            .parse::<Pattern<Language>>()
-            .unwrap();
+        let test_pattern = "
+(access-transpose
+ (access-slice
+  (access (access-tensor t-3-32-32) 1)
+  2 16 32
+ )
+ (list 2 0 1)
+)"
+        .parse::<Pattern<Language>>()
+        .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
         assert_eq!(matches.substs.len(), 1);
     }
 
     #[test]
-    fn bubble_access_concatenate_through_access_slice_2() {
+    fn access_slice_access_transpose_composition_commutative_1() {
         let program = "
-             (access-slice
-              (access-concatenate
-               (access (access-tensor t-32-32) 1)
-               (access (access-tensor t-32-64) 1)
-               1
-              )
-              1 16 48
-             )"
+(access-slice
+ (access-transpose
+  (access (access-tensor t-3-32-32) 1)
+  (list 2 0 1)
+ )
+ 1 0 2
+)
+        "
         .parse()
         .unwrap();
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
         let id = egraph.add_expr(&program);
         egraph.rebuild();
-        let rws = vec![super::bubble_access_concatenate_through_access_slice()];
+        let rws = vec![super::access_slice_access_transpose_composition_commutative()];
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
             .run(&rws);
 
         let test_pattern = "
-            (access-concatenate
-             (access-slice
-              (access (access-tensor t-32-32) 1)
-              1 16 32
-             )
-             (access-slice
-              (access (access-tensor t-32-64) 1)
-              1 0 16
-             )
-             1
-            )"
+(access-transpose
+ (access-slice
+  (access (access-tensor t-3-32-32) 1)
+  0 0 2
+ )
+ (list 2 0 1)
+)"
         .parse::<Pattern<Language>>()
         .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
@@ -4711,75 +6101,62 @@ mod tests {
     }
 
     #[test]
-    fn slice_concatenate_tensor_accesses() {
-        let program = "(access-tensor t-32-32)".parse().unwrap();
+    fn access_slice_access_transpose_composition_commutative_2() {
+        let program = "
+(access-slice
+ (access-transpose
+  (access (access-tensor t-3-32-32) 1)
+  (list 1 0 2)
+ )
+ 2 0 16
+)
+        "
+        .parse()
+        .unwrap();
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
         let id = egraph.add_expr(&program);
         egraph.rebuild();
-        let rws = vec![
-            super::slice_concatenate_tensor_accesses(0, 1),
-            super::slice_concatenate_tensor_accesses(1, 1),
-        ];
+        let rws = vec![super::access_slice_access_transpose_composition_commutative()];
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
             .run(&rws);
 
         let test_pattern = "
-            (access-concatenate
-             (access-slice
-              (access-tensor t-32-32)
-              0 0 16
-             )
-             (access-slice
-              (access-tensor t-32-32)
-              0 16 32
-             )
-             0
-            )"
+(access-transpose
+ (access-slice (access (access-tensor t-3-32-32) 1) 2 0 16)
+ (list 1 0 2)
+)"
         .parse::<Pattern<Language>>()
         .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
         assert_eq!(matches.substs.len(), 1);
+    }
 
-        let test_pattern = "
-            (access-concatenate
-             (access-slice
-              (access-tensor t-32-32)
-              1 0 16
-             )
-             (access-slice
-              (access-tensor t-32-32)
-              1 16 32
-             )
-             1
-            )"
-        .parse::<Pattern<Language>>()
+    #[test]
+    fn access_slice_access_transpose_composition_commutative_3() {
+        let program = "
+(access-slice
+ (access-transpose
+  (access (access-tensor t-3-32-32) 1)
+  (list 1 0 2)
+ )
+ 1 0 2
+)"
+        .parse()
         .unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        assert_eq!(matches.substs.len(), 1);
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+        let rws = vec![super::access_slice_access_transpose_composition_commutative()];
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&rws);
 
         let test_pattern = "
-            (access-concatenate
-             (access-slice
-              (access-concatenate
-               (access-slice
-                (access-tensor t-32-32)
-                1 0 16
-               )
-               (access-slice
-                (access-tensor t-32-32)
-                1 16 32
-               )
-               1
-              )
-              0 0 16
-             )
-             (access-slice
-              (access-tensor t-32-32)
-              0 16 32
-             )
-             0
-            )"
+(access-transpose
+ (access-slice (access (access-tensor t-3-32-32) 1) 0 0 2)
+ (list 1 0 2)
+)"
         .parse::<Pattern<Language>>()
         .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
@@ -4787,365 +6164,242 @@ mod tests {
     }
 
     #[test]
-    fn collapse_nested_transposes() {
+    fn bubble_access_concatenate_through_access_transpose_0() {
         let program = "
-             (access-transpose
-              (access-transpose
-               (access (access-tensor t) 1)
-               (list 1 3 2 0)
-              )
-              (list 3 2 1 0)
-             )"
+(access-transpose
+ (access-concatenate
+  (access (access-tensor t-3-32-32) 1)
+  (access (access-tensor t-3-32-32) 1)
+  0
+ )
+ (list 1 2 0)
+)"
         .parse()
         .unwrap();
-        let mut map = HashMap::default();
-        map.insert("t".to_string(), vec![1, 2, 3, 4]);
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
-            name_to_shape: map,
-            name_to_dtype: HashMap::default(),
-        });
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
         let id = egraph.add_expr(&program);
         egraph.rebuild();
-        let rws = vec![super::collapse_nested_transposes()];
-        let runner = Runner::<_, _, ()>::default().with_egraph(egraph).run(&rws);
+        let rws = vec![super::bubble_access_concatenate_through_access_transpose()];
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&rws);
 
-        let test_pattern = "(access-transpose (access (access-tensor t) 1) (list 0 2 3 1))"
-            .parse::<Pattern<Language>>()
-            .unwrap();
+        let test_pattern = "
+(access-concatenate
+ (access-transpose (access (access-tensor t-3-32-32) 1) (list 1 2 0))
+ (access-transpose (access (access-tensor t-3-32-32) 1) (list 1 2 0))
+ 2
+)"
+        .parse::<Pattern<Language>>()
+        .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
         assert_eq!(matches.substs.len(), 1);
     }
 
     #[test]
-    fn remove_trivial_transpose() {
+    fn bubble_access_concatenate_through_access_transpose_1() {
         let program = "
-              (access-transpose
-               (access (access-tensor t) 1)
-               (list 0 1 2 3)
-              )
-             "
+(access-transpose
+ (access-concatenate
+  (access (access-tensor t-3-32-32) 1)
+  (access (access-tensor t-3-32-32) 1)
+  1
+ )
+ (list 1 2 0)
+)"
         .parse()
         .unwrap();
-        let mut map = HashMap::default();
-        map.insert("t".to_string(), vec![1, 2, 3, 4]);
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
-            name_to_shape: map,
-            name_to_dtype: HashMap::default(),
-        });
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
         let id = egraph.add_expr(&program);
         egraph.rebuild();
-        let rws = vec![super::remove_trivial_transpose()];
-        let runner = Runner::<_, _, ()>::default().with_egraph(egraph).run(&rws);
+        let rws = vec![super::bubble_access_concatenate_through_access_transpose()];
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&rws);
 
-        let test_pattern = "(access (access-tensor t) 1)"
-            .parse::<Pattern<Language>>()
-            .unwrap();
+        let test_pattern = "
+(access-concatenate
+ (access-transpose (access (access-tensor t-3-32-32) 1) (list 1 2 0))
+ (access-transpose (access (access-tensor t-3-32-32) 1) (list 1 2 0))
+ 0
+)"
+        .parse::<Pattern<Language>>()
+        .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
         assert_eq!(matches.substs.len(), 1);
     }
 
     #[test]
-    fn collapse_nested_accesses() {
-        let program = "(access (access (access-tensor t) 0) 1)".parse().unwrap();
-        let mut map = HashMap::default();
-        map.insert("t".to_string(), vec![1, 2, 3, 4]);
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
-            name_to_shape: map,
-            name_to_dtype: HashMap::default(),
-        });
+    fn bubble_access_concatenate_through_access_transpose_2() {
+        let program = "
+(access-transpose
+ (access-concatenate
+  (access (access-tensor t-3-32-32) 1)
+  (access (access-tensor t-3-32-32) 1)
+  2
+ )
+ (list 1 0 2)
+)"
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
         let id = egraph.add_expr(&program);
         egraph.rebuild();
-        let rws = vec![super::collapse_nested_accesses()];
-        let runner = Runner::<_, _, ()>::default().with_egraph(egraph).run(&rws);
+        let rws = vec![super::bubble_access_concatenate_through_access_transpose()];
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&rws);
 
-        let test_pattern = "(access (access-tensor t) 1)"
-            .parse::<Pattern<Language>>()
-            .unwrap();
+        let test_pattern = "
+(access-concatenate
+ (access-transpose (access (access-tensor t-3-32-32) 1) (list 1 0 2))
+ (access-transpose (access (access-tensor t-3-32-32) 1) (list 1 0 2))
+ 2
+)"
+        .parse::<Pattern<Language>>()
+        .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
         assert_eq!(matches.substs.len(), 1);
     }
 
     #[test]
-    fn pad_slice_accesses_pad_to_multiples() {
-        let program = "(access (access-tensor t) 0)".parse().unwrap();
-        let mut map = HashMap::default();
-        map.insert("t".to_string(), vec![1, 2, 3, 4]);
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
-            name_to_shape: map,
-            name_to_dtype: HashMap::default(),
-        });
-        let id = egraph.add_expr(&program);
+    fn bubble_access_concatenate_through_access_transpose_3() {
+        let program = "
+(access-transpose
+ (access-concatenate
+  (access (access-tensor t-3-32-32) 1)
+  (access (access-tensor t-3-32-32) 1)
+  1
+ )
+ (list 2 0 1)
+)"
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program);
         egraph.rebuild();
-        let rws = vec![super::pad_slice_accesses(
-            0,
-            PadSliceStrategy::PadToMultiplesOf {
-                limit: 16,
-                multiples_of: 4,
-                pad_location: PadLocation::End,
-                pad_type: PadType::ZeroPadding,
-            },
-        )];
-        let runner = Runner::<_, _, ()>::default().with_egraph(egraph).run(&rws);
+        let rws = vec![super::bubble_access_concatenate_through_access_transpose()];
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&rws);
 
-        for dim_val in &[4, 8, 12, 16] {
-            let pattern = format!(
-                "(access-slice
-                  (access-pad
-                   (access (access-tensor t) 0)
-                   zero-padding
-                   0 0 {}
-                  )
-                  0 0 1
-                 )",
-                dim_val - 1
-            )
-            .parse::<Pattern<Language>>()
-            .unwrap();
-            let matches = pattern.search_eclass(&runner.egraph, id).unwrap();
-            assert_eq!(matches.substs.len(), 1);
-        }
+        let test_pattern = "
+(access-concatenate
+ (access-transpose (access (access-tensor t-3-32-32) 1) (list 2 0 1))
+ (access-transpose (access (access-tensor t-3-32-32) 1) (list 2 0 1))
+ 2
+)"
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        assert_eq!(matches.substs.len(), 1);
     }
 
-    /// This test tests the newer conv2d syntax, on a more realistically-shaped
-    /// set of inputs.
     #[test]
-    fn conv2d_im2col_systolic_array_1() {
-        test_logger::ensure_env_logger_initialized();
-
-        pub const IMAGE_SHAPE: &[usize] = &[1, 32, 32, 3];
-        pub const KERNEL_SHAPE: &[usize] = &[3, 3, 3, 8];
-
-        let mut expr = RecExpr::from_str("(access-tensor image)").unwrap();
-        let data_id = expr.as_ref().len() - 1;
-        let weights_id = expr.add(Language::Symbol("weights".to_string()));
-        let weights_id = expr.add(Language::AccessTensor(weights_id));
-
-        let _conv2d_id = crate::language::from_relay::conv2d(
-            &mut expr,
-            data_id.into(),
-            IMAGE_SHAPE,
-            weights_id,
-            KERNEL_SHAPE,
-            &[1, 1],
-            &[1, 1, 1, 1],
-            &[1, 1],
-            1,
-            "NHWC",
-            "HWIO",
-            "",
-            false,
-        );
-
-        let mut map = HashMap::default();
-        // batch, height, width, channels
-        map.insert("image".to_string(), vec![1, 32, 32, 3]);
-        // kernel height, kernel width, in channels, out channels
-        map.insert("weights".to_string(), vec![3, 3, 3, 8]);
-
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
-            name_to_shape: map,
-            name_to_dtype: HashMap::default(),
-        });
-        let id = egraph.add_expr(&expr);
+    fn bubble_access_concatenate_through_access_cartesian_product_left() {
+        let program = "
+             (access-cartesian-product
+              (access-concatenate (access (access-tensor t-3-32-32) 1) (access (access-tensor t-3-32-32) 1) 0)
+              (access-concatenate (access (access-tensor t-3-32-32) 1) (access (access-tensor t-3-32-32) 1) 0)
+             )"
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program);
         egraph.rebuild();
-
         let rws = vec![
-            super::flatten_unflatten_any_access(),
-            super::bubble_reshape_through_cartesian_product(),
-            super::bubble_reshape_through_compute_dot_product(),
-            super::systolic_array(),
+            super::bubble_access_concatenate_through_access_cartesian_product_not_item_axis_left(),
         ];
-
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
             .run(&rws);
 
-        // Expected original program.
         let test_pattern = "
-(access-transpose
- (access-transpose
-  (compute dot-product
-   (access-cartesian-product
-    (access
-     (access-transpose (access-tensor weights) (list 3 2 0 1))
-     1
-    )
-    (access
-     (access-squeeze
-       (access-windows
-        (access
-         (access-pad
-          (access-pad
-           (access-transpose (access-tensor image) (list 0 3 1 2))
-           zero-padding
-           2 1 1
-          )
-          zero-padding
-          3 1 1
-         )
-         1
-        )
-        (shape 3 3 3)
-        (shape 1 1 1)
-       )
-      1
-     )
-     3
-    )
-   )
-  )
-  (list 1 0 2 3)
- )
- (list 0 2 3 1)
-)
-            "
+             (access-concatenate
+              (access-cartesian-product
+               (access (access-tensor t-3-32-32) 1)
+               (access-concatenate (access (access-tensor t-3-32-32) 1) (access (access-tensor t-3-32-32) 1) 0)
+              )
+              (access-cartesian-product
+               (access (access-tensor t-3-32-32) 1)
+               (access-concatenate (access (access-tensor t-3-32-32) 1) (access (access-tensor t-3-32-32) 1) 0)
+              )
+              0
+             )"
         .parse::<Pattern<Language>>()
         .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
         assert_eq!(matches.substs.len(), 1);
+    }
+
+    #[test]
+    fn bubble_access_concatenate_through_access_cartesian_product_right() {
+        let program = "
+             (access-cartesian-product
+              (access-concatenate (access (access-tensor t-3-32-32) 1) (access (access-tensor t-3-32-32) 1) 0)
+              (access-concatenate (access (access-tensor t-3-32-32) 1) (access (access-tensor t-3-32-32) 1) 0)
+             )"
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+        let rws = vec![
+            super::bubble_access_concatenate_through_access_cartesian_product_not_item_axis_right(),
+        ];
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&rws);
 
-        // Find the version with flattened and reshaped weight/image arguments
         let test_pattern = "
-(access-transpose
- (access-transpose
-  (compute dot-product
-   (access-cartesian-product
-    (access-reshape
-     (access-flatten
-      (access
-       (access-transpose (access-tensor weights) (list 3 2 0 1))
-       1
-      )
-     )
-     ?shape1
-    )
-    (access-reshape
-     (access-flatten
-      (access
-       (access-squeeze
-         (access-windows
-          (access
-           (access-pad
-            (access-pad
-             (access-transpose (access-tensor image) (list 0 3 1 2))
-             zero-padding
-             2 1 1
-            )
-            zero-padding
-            3 1 1
-           )
-           1
-          )
-          (shape 3 3 3)
-          (shape 1 1 1)
-         )
-        1
-       )
-       3
-      )
-     )
-     ?shape0
-    )
-   )
-  )
-  (list 1 0 2 3)
- )
- (list 0 2 3 1)
-)
-            "
+             (access-concatenate
+              (access-cartesian-product
+               (access-concatenate (access (access-tensor t-3-32-32) 1) (access (access-tensor t-3-32-32) 1) 0)
+               (access (access-tensor t-3-32-32) 1)
+              )
+              (access-cartesian-product
+               (access-concatenate (access (access-tensor t-3-32-32) 1) (access (access-tensor t-3-32-32) 1) 0)
+               (access (access-tensor t-3-32-32) 1)
+              )
+              1
+             )"
         .parse::<Pattern<Language>>()
         .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
         assert_eq!(matches.substs.len(), 1);
-
-        let test_pattern = "
-(access-transpose
- (access-transpose
-  (access-reshape
-   (systolic-array 27 1024
-    (access-flatten
-     (access
-      (access-transpose (access-tensor weights) (list 3 2 0 1))
-      1
-     )
-    )
-    (access
-     (access-transpose
-      (access-flatten
-       (access
-        (access-squeeze
-          (access-windows
-           (access
-            (access-pad
-             (access-pad
-              (access-transpose (access-tensor image) (list 0 3 1 2))
-              zero-padding
-              2 1 1
-             )
-             zero-padding
-             3 1 1
-            )
-            1
-           )
-           (shape 3 3 3)
-           (shape 1 1 1)
-          )
-         1
-        )
-        3
-       )
-      )
-      (list 1 0)
-     )
-     0
-    )
-   )
-   (access-shape (shape 8 1 32 32) (shape))
-  )
-  (list 1 0 2 3)
- )
- (list 0 2 3 1)
-)
-            "
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        assert_eq!(matches.substs.len(), 1);
-    }
+    }
 
     #[test]
-    fn bubble_access_slice_through_access_pad_inequal_axes() {
+    fn bubble_access_concatenate_through_access_cartesian_product_same_item_axis_0() {
         let program = "
-              (access-pad
-               (access-slice
-                (access-tensor t)
-                0 0 8
-               )
-               zero-padding 1 2 2
-              )
-             "
+             (access-cartesian-product
+              (access-concatenate (access (access-tensor t-3-32-32) 1) (access (access-tensor t-3-32-32) 1) 1)
+              (access-concatenate (access (access-tensor t-3-32-32) 1) (access (access-tensor t-3-32-32) 1) 1)
+             )"
         .parse()
         .unwrap();
-        let mut map = HashMap::default();
-        map.insert("t".to_string(), vec![8, 10]);
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
-            name_to_shape: map,
-            name_to_dtype: HashMap::default(),
-        });
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
         let id = egraph.add_expr(&program);
         egraph.rebuild();
-        let rws = vec![super::bubble_access_slice_through_access_pad_inequal_axes()];
-        let runner = Runner::<_, _, ()>::default().with_egraph(egraph).run(&rws);
+        let rws = vec![
+            super::bubble_access_concatenate_through_access_cartesian_product_same_item_axis(),
+        ];
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&rws);
 
         let test_pattern = "
-              (access-slice
-               (access-pad
-                (access-tensor t)
-                zero-padding 1 2 2
-               )
-               0 0 8
+             (access-concatenate
+              (access-cartesian-product
+               (access (access-tensor t-3-32-32) 1)
+               (access (access-tensor t-3-32-32) 1)
               )
-"
+              (access-cartesian-product
+               (access (access-tensor t-3-32-32) 1)
+               (access (access-tensor t-3-32-32) 1)
+              )
+              3
+             )"
         .parse::<Pattern<Language>>()
         .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
@@ -5153,173 +6407,70 @@ mod tests {
     }
 
     #[test]
-    fn pad_slice_accesses_pad_to_closest_multiple() {
-        let program = "(access (access-tensor t) 0)".parse().unwrap();
-        let mut map = HashMap::default();
-        map.insert("t".to_string(), vec![1, 2, 3, 4]);
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
-            name_to_shape: map,
-            name_to_dtype: HashMap::default(),
-        });
+    fn bubble_access_concatenate_through_access_cartesian_product_same_item_axis_1() {
+        let program = "
+             (access-cartesian-product
+              (access-concatenate (access (access-tensor t-3-32-32) 0) (access (access-tensor t-3-32-32) 0) 2)
+              (access-concatenate (access (access-tensor t-3-32-32) 0) (access (access-tensor t-3-32-32) 0) 2)
+             )"
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
         let id = egraph.add_expr(&program);
         egraph.rebuild();
         let rws = vec![
-            super::pad_slice_accesses(
-                0,
-                PadSliceStrategy::PadToClosestMultipleOf {
-                    multiple_of: 3,
-                    pad_location: PadLocation::End,
-                    pad_type: PadType::ZeroPadding,
-                },
-            ),
-            super::pad_slice_accesses(
-                1,
-                PadSliceStrategy::PadToClosestMultipleOf {
-                    multiple_of: 3,
-                    pad_location: PadLocation::End,
-                    pad_type: PadType::ZeroPadding,
-                },
-            ),
-            super::pad_slice_accesses(
-                2,
-                PadSliceStrategy::PadToClosestMultipleOf {
-                    multiple_of: 3,
-                    pad_location: PadLocation::End,
-                    pad_type: PadType::ZeroPadding,
-                },
-            ),
-            super::pad_slice_accesses(
-                3,
-                PadSliceStrategy::PadToClosestMultipleOf {
-                    multiple_of: 3,
-                    pad_location: PadLocation::End,
-                    pad_type: PadType::ZeroPadding,
-                },
-            ),
+            super::bubble_access_concatenate_through_access_cartesian_product_same_item_axis(),
         ];
-        let runner = Runner::<_, _, ()>::default().with_egraph(egraph).run(&rws);
-
-        for class in runner.egraph.classes() {
-            match &class.data {
-                MyAnalysisData::AccessPattern(a) => {
-                    for &shape_dim_val in a.shape.slice() {
-                        assert!(shape_dim_val <= 6);
-                    }
-                    for &item_shape_dim_val in a.item_shape.slice() {
-                        assert!(item_shape_dim_val <= 6);
-                    }
-                }
-                _ => (),
-            }
-        }
-
-        runner.print_report();
-
-        let test_pattern = "
-                 (access-slice
-                  (access-pad
-                   (access (access-tensor t) 0)
-                   zero-padding
-                   0 0 2
-                  )
-                  0 0 1
-                 )"
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        assert_eq!(matches.substs.len(), 1);
-
-        let test_pattern = "
-                 (access-slice
-                  (access-pad
-                   (access (access-tensor t) 0)
-                   zero-padding
-                   1 0 1
-                  )
-                  1 0 2
-                 )"
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        assert_eq!(matches.substs.len(), 1);
-
-        let test_pattern = "
-                 (access-slice
-                  (access-pad
-                   (access (access-tensor t) 0)
-                   zero-padding
-                   2 0 0
-                  )
-                  2 0 3
-                 )"
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id);
-        assert!(matches.is_none());
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&rws);
 
         let test_pattern = "
-                 (access-slice
-                  (access-pad
-                   (access (access-tensor t) 0)
-                   zero-padding
-                   3 0 2
-                  )
-                  3 0 4
-                 )"
+             (access-concatenate
+              (access-cartesian-product
+               (access (access-tensor t-3-32-32) 0)
+               (access (access-tensor t-3-32-32) 0)
+              )
+              (access-cartesian-product
+               (access (access-tensor t-3-32-32) 0)
+               (access (access-tensor t-3-32-32) 0)
+              )
+              3
+             )"
         .parse::<Pattern<Language>>()
         .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
         assert_eq!(matches.substs.len(), 1);
-
-        // Shouldn't pad up to the next highest multiple. I.e. length 1
-        // shouldn't go to 6, just to 3.
-        let test_pattern = "
-                 (access-slice
-                  (access-pad
-                   (access (access-tensor t) 0)
-                   zero-padding
-                   0 0 5
-                  )
-                  0 0 1
-                 )"
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id);
-        assert!(matches.is_none());
     }
 
     #[test]
-    fn bubble_access_slice_through_access_cartesian_product_left() {
+    fn bubble_access_concatenate_through_compute_dot_product_not_item_axis() {
         let program = "
-             (access-cartesian-product
-              (access-slice (access (access-tensor a) 1) 0 0 2)
-              (access (access-tensor b) 1)
+             (compute dot-product
+              (access-concatenate
+               (access (access-tensor t-3-32-32) 1)
+               (access (access-tensor t-3-32-32) 1)
+               0
+              )
              )"
         .parse()
         .unwrap();
-        let mut map = HashMap::default();
-        map.insert("a".to_string(), vec![4, 3, 3, 4]);
-        map.insert("b".to_string(), vec![10, 3, 3, 4]);
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
-            name_to_shape: map,
-            name_to_dtype: HashMap::default(),
-        });
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
         let id = egraph.add_expr(&program);
         egraph.rebuild();
         let rws =
-            vec![super::bubble_access_slice_through_access_cartesian_product_not_item_axis_left()];
+            vec![super::bubble_access_concatenate_through_compute_dot_product_not_item_axis()];
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
             .run(&rws);
 
         let test_pattern = "
-             (access-slice
-              (access-cartesian-product
-               (access (access-tensor a) 1)
-               (access (access-tensor b) 1)
-              )
-              0 0 2
-             )"
+             (access-concatenate
+              (compute dot-product (access (access-tensor t-3-32-32) 1))
+              (compute dot-product (access (access-tensor t-3-32-32) 1))
+              0
+             )
+             "
         .parse::<Pattern<Language>>()
         .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
@@ -5327,37 +6478,33 @@ mod tests {
     }
 
     #[test]
-    fn bubble_access_slice_through_access_cartesian_product_right() {
+    fn bubble_access_concatenate_through_compute_dot_product_item_access_0() {
         let program = "
-             (access-cartesian-product
-              (access (access-tensor b) 1)
-              (access-slice (access (access-tensor a) 1) 0 0 2)
+             (compute dot-product
+              (access-concatenate
+               (access (access-tensor t-3-32-32) 1)
+               (access (access-tensor t-3-32-32) 1)
+               1
+              )
              )"
         .parse()
         .unwrap();
-        let mut map = HashMap::default();
-        map.insert("a".to_string(), vec![4, 3, 3, 4]);
-        map.insert("b".to_string(), vec![10, 3, 3, 4]);
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
-            name_to_shape: map,
-            name_to_dtype: HashMap::default(),
-        });
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
         let id = egraph.add_expr(&program);
         egraph.rebuild();
-        let rws =
-            vec![super::bubble_access_slice_through_access_cartesian_product_not_item_axis_right()];
+        let rws = vec![super::bubble_access_concatenate_through_compute_dot_product_item_axis()];
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
             .run(&rws);
 
         let test_pattern = "
-             (access-slice
-              (access-cartesian-product
-               (access (access-tensor b) 1)
-               (access (access-tensor a) 1)
+             (compute reduce-sum
+              (access-pair
+               (compute dot-product (access (access-tensor t-3-32-32) 1))
+               (compute dot-product (access (access-tensor t-3-32-32) 1))
               )
-              1 0 2
-             )"
+             )
+             "
         .parse::<Pattern<Language>>()
         .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
@@ -5365,37 +6512,33 @@ mod tests {
     }
 
     #[test]
-    fn bubble_access_slice_through_access_cartesian_product_same_item_axis() {
+    fn bubble_access_concatenate_through_compute_dot_product_item_access_1() {
         let program = "
-             (access-cartesian-product
-              (access-slice (access (access-tensor a) 2) 3 1 2)
-              (access-slice (access (access-tensor b) 1) 2 1 2)
+             (compute dot-product
+              (access-concatenate
+               (access (access-tensor t-3-32-32) 1)
+               (access (access-tensor t-3-32-32) 1)
+               2
+              )
              )"
         .parse()
         .unwrap();
-        let mut map = HashMap::default();
-        map.insert("a".to_string(), vec![4, 16, 3, 3, 4]);
-        map.insert("b".to_string(), vec![10, 3, 3, 4]);
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
-            name_to_shape: map,
-            name_to_dtype: HashMap::default(),
-        });
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
         let id = egraph.add_expr(&program);
         egraph.rebuild();
-        let rws =
-            vec![super::bubble_access_slice_through_access_cartesian_product_same_item_axis()];
+        let rws = vec![super::bubble_access_concatenate_through_compute_dot_product_item_axis()];
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
             .run(&rws);
 
         let test_pattern = "
-             (access-slice
-              (access-cartesian-product
-               (access (access-tensor a) 2)
-               (access (access-tensor b) 1)
+             (compute reduce-sum
+              (access-pair
+               (compute dot-product (access (access-tensor t-3-32-32) 1))
+               (compute dot-product (access (access-tensor t-3-32-32) 1))
               )
-              5 1 2
-             )"
+             )
+             "
         .parse::<Pattern<Language>>()
         .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
@@ -5403,33 +6546,37 @@ mod tests {
     }
 
     #[test]
-    fn bubble_access_slice_through_compute_dot_product_not_item_axis() {
+    fn bubble_access_concatenate_through_access() {
         let program = "
-             (compute dot-product
-              (access-slice
-               (access (access-tensor a) 1)
-               0 2 3
+             (access
+              (access-concatenate
+               (access (access-tensor t-3-32-32) 1)
+               (access (access-tensor t-3-32-32) 1)
+               2
               )
+              0
              )"
         .parse()
         .unwrap();
-        let mut map = HashMap::default();
-        map.insert("a".to_string(), vec![4, 16, 3, 3, 4]);
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
-            name_to_shape: map,
-            name_to_dtype: HashMap::default(),
-        });
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
         let id = egraph.add_expr(&program);
         egraph.rebuild();
-        let rws = vec![super::bubble_access_slice_through_compute_dot_product_not_item_axis()];
+        let rws = vec![super::bubble_access_concatenate_through_access()];
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
             .run(&rws);
 
         let test_pattern = "
-             (access-slice
-              (compute dot-product (access (access-tensor a) 1))
-              0 2 3
+             (access-concatenate
+              (access
+               (access (access-tensor t-3-32-32) 1)
+               0
+              )
+              (access
+               (access (access-tensor t-3-32-32) 1)
+               0
+              )
+              2
              )
              "
         .parse::<Pattern<Language>>()
@@ -5439,176 +6586,320 @@ mod tests {
     }
 
     #[test]
-    fn bubble_access_slice_through_compute_dot_product_item_axis_not_tuple_axis() {
+    fn bubble_access_concatenate_through_access_slice_0() {
         let program = "
-             (compute dot-product
-              (access-slice
-               (access-pad
-                (access (access-tensor a) 1)
-                zero-padding
-                3 2 3
-               )
-               3 2 5
+             (access-slice
+              (access-concatenate
+               (access (access-tensor t-32-32) 1)
+               (access (access-tensor t-32-64) 1)
+               1
               )
+              1 0 16
              )"
         .parse()
         .unwrap();
-        let mut map = HashMap::default();
-        map.insert("a".to_string(), vec![4, 16, 3, 3, 4]);
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
-            name_to_shape: map,
-            name_to_dtype: HashMap::default(),
-        });
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
         let id = egraph.add_expr(&program);
         egraph.rebuild();
-        let rws =
-            vec![super::bubble_access_slice_through_compute_dot_product_item_axis_not_tuple_axis()];
+        let rws = vec![super::bubble_access_concatenate_through_access_slice()];
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
             .run(&rws);
 
-        let test_pattern = "
-              (compute dot-product
-               (access-pad
-                (access (access-tensor a) 1)
-                zero-padding
-                3 2 3
-               )
+        let test_pattern = "(access-slice (access (access-tensor t-32-32) 1) 1 0 16)"
+            .parse::<Pattern<Language>>()
+            .unwrap();
+        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        assert_eq!(matches.substs.len(), 1);
+    }
+
+    #[test]
+    fn bubble_access_concatenate_through_access_slice_1() {
+        let program = "
+             (access-slice
+              (access-concatenate
+               (access (access-tensor t-32-32) 1)
+               (access (access-tensor t-32-64) 1)
+               1
               )
-             "
-        .parse::<Pattern<Language>>()
+              1 48 64
+             )"
+        .parse()
         .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+        let rws = vec![super::bubble_access_concatenate_through_access_slice()];
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&rws);
+
+        let test_pattern = "(access-slice (access (access-tensor t-32-64) 1) 1 16 32)"
+            .parse::<Pattern<Language>>()
+            .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
         assert_eq!(matches.substs.len(), 1);
     }
 
     #[test]
-    fn systolic_array_with_blocking() {
-        let mut map = HashMap::default();
-        map.insert("a".to_string(), vec![32, 64]);
-        map.insert("b".to_string(), vec![32, 64]);
+    fn bubble_access_concatenate_through_access_slice_2() {
         let program = "
-         (compute dot-product
-          (access-cartesian-product
-           (access (access-tensor a) 1)
-           (access (access-tensor b) 1)
-          )
-         )
-        "
+             (access-slice
+              (access-concatenate
+               (access (access-tensor t-32-32) 1)
+               (access (access-tensor t-32-64) 1)
+               1
+              )
+              1 16 48
+             )"
         .parse()
         .unwrap();
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
-            name_to_shape: map,
-            name_to_dtype: HashMap::default(),
-        });
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
         let id = egraph.add_expr(&program);
         egraph.rebuild();
+        let rws = vec![super::bubble_access_concatenate_through_access_slice()];
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&rws);
+
+        let test_pattern = "
+            (access-concatenate
+             (access-slice
+              (access (access-tensor t-32-32) 1)
+              1 16 32
+             )
+             (access-slice
+              (access (access-tensor t-32-64) 1)
+              1 0 16
+             )
+             1
+            )"
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        assert_eq!(matches.substs.len(), 1);
+    }
 
+    #[test]
+    fn slice_concatenate_tensor_accesses() {
+        let program = "(access-tensor t-32-32)".parse().unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
         let rws = vec![
-            super::systolic_array_with_blocking(64, 32),
-            super::systolic_array_with_blocking(32, 32),
-            super::systolic_array_with_blocking(16, 32),
-            super::systolic_array_with_blocking(32, 16),
-            super::systolic_array_with_blocking(2, 2),
-            // Shouldn't tensorize to this one.
-            super::systolic_array_with_blocking(32, 3),
+            super::slice_concatenate_tensor_accesses(0, 1),
+            super::slice_concatenate_tensor_accesses(1, 1),
         ];
-
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
             .run(&rws);
-        match runner.stop_reason.unwrap() {
-            egg::StopReason::Saturated => (),
-            _ => panic!(),
-        };
 
         let test_pattern = "
-          (systolic-array-with-blocking 64 32
-           (access (access-tensor a) 1)
-           (access (access-transpose  (access (access-tensor b) 1) (list 1 0)) 0)
-          )
-            "
+            (access-concatenate
+             (access-slice
+              (access-tensor t-32-32)
+              0 0 16
+             )
+             (access-slice
+              (access-tensor t-32-32)
+              0 16 32
+             )
+             0
+            )"
         .parse::<Pattern<Language>>()
         .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
         assert_eq!(matches.substs.len(), 1);
 
         let test_pattern = "
-          (systolic-array-with-blocking 32 32
-           (access (access-tensor a) 1)
-           (access (access-transpose  (access (access-tensor b) 1) (list 1 0)) 0)
-          )
-            "
+            (access-concatenate
+             (access-slice
+              (access-tensor t-32-32)
+              1 0 16
+             )
+             (access-slice
+              (access-tensor t-32-32)
+              1 16 32
+             )
+             1
+            )"
         .parse::<Pattern<Language>>()
         .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
         assert_eq!(matches.substs.len(), 1);
 
         let test_pattern = "
-          (systolic-array-with-blocking 16 32
-           (access (access-tensor a) 1)
-           (access (access-transpose  (access (access-tensor b) 1) (list 1 0)) 0)
-          )
-            "
+            (access-concatenate
+             (access-slice
+              (access-concatenate
+               (access-slice
+                (access-tensor t-32-32)
+                1 0 16
+               )
+               (access-slice
+                (access-tensor t-32-32)
+                1 16 32
+               )
+               1
+              )
+              0 0 16
+             )
+             (access-slice
+              (access-tensor t-32-32)
+              0 16 32
+             )
+             0
+            )"
         .parse::<Pattern<Language>>()
         .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
         assert_eq!(matches.substs.len(), 1);
+    }
 
-        let test_pattern = "
-          (systolic-array-with-blocking 32 16
-           (access (access-tensor a) 1)
-           (access (access-transpose  (access (access-tensor b) 1) (list 1 0)) 0)
-          )
-            "
-        .parse::<Pattern<Language>>()
+    #[test]
+    fn collapse_nested_transposes() {
+        let program = "
+             (access-transpose
+              (access-transpose
+               (access (access-tensor t) 1)
+               (list 1 3 2 0)
+              )
+              (list 3 2 1 0)
+             )"
+        .parse()
         .unwrap();
+        let mut map = HashMap::default();
+        map.insert("t".to_string(), vec![1, 2, 3, 4]);
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+        let rws = vec![super::collapse_nested_transposes()];
+        let runner = Runner::<_, _, ()>::default().with_egraph(egraph).run(&rws);
+
+        let test_pattern = "(access-transpose (access (access-tensor t) 1) (list 0 2 3 1))"
+            .parse::<Pattern<Language>>()
+            .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
         assert_eq!(matches.substs.len(), 1);
+    }
 
-        let test_pattern = "
-          (systolic-array-with-blocking 2 2
-           (access (access-tensor a) 1)
-           (access (access-transpose  (access (access-tensor b) 1) (list 1 0)) 0)
-          )
-            "
-        .parse::<Pattern<Language>>()
+    #[test]
+    fn remove_trivial_transpose() {
+        let program = "
+              (access-transpose
+               (access (access-tensor t) 1)
+               (list 0 1 2 3)
+              )
+             "
+        .parse()
         .unwrap();
+        let mut map = HashMap::default();
+        map.insert("t".to_string(), vec![1, 2, 3, 4]);
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+        let rws = vec![super::remove_trivial_transpose()];
+        let runner = Runner::<_, _, ()>::default().with_egraph(egraph).run(&rws);
+
+        let test_pattern = "(access (access-tensor t) 1)"
+            .parse::<Pattern<Language>>()
+            .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
         assert_eq!(matches.substs.len(), 1);
+    }
 
-        let test_pattern = "
-          (systolic-array-with-blocking 32 3
-           (access (access-tensor a) 1)
-           (access (access-transpose  (access (access-tensor b) 1) (list 1 0)) 0)
-          )
-            "
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id);
-        assert!(matches.is_none());
+    #[test]
+    fn collapse_nested_accesses() {
+        let program = "(access (access (access-tensor t) 0) 1)".parse().unwrap();
+        let mut map = HashMap::default();
+        map.insert("t".to_string(), vec![1, 2, 3, 4]);
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+        let rws = vec![super::collapse_nested_accesses()];
+        let runner = Runner::<_, _, ()>::default().with_egraph(egraph).run(&rws);
+
+        let test_pattern = "(access (access-tensor t) 1)"
+            .parse::<Pattern<Language>>()
+            .unwrap();
+        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        assert_eq!(matches.substs.len(), 1);
     }
 
     #[test]
-    #[should_panic]
-    fn conv2d_im2col_fc_systolic_array() {
-        let data_shape = vec![1, 32, 32, 3];
-        let kernel_shape = vec![3, 3, 3, 8];
+    fn pad_slice_accesses_pad_to_multiples() {
+        let program = "(access (access-tensor t) 0)".parse().unwrap();
+        let mut map = HashMap::default();
+        map.insert("t".to_string(), vec![1, 2, 3, 4]);
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+        let rws = vec![super::pad_slice_accesses(
+            0,
+            PadSliceStrategy::PadToMultiplesOf {
+                limit: 16,
+                multiples_of: 4,
+                pad_location: PadLocation::End,
+                pad_type: PadType::ZeroPadding,
+            },
+        )];
+        let runner = Runner::<_, _, ()>::default().with_egraph(egraph).run(&rws);
 
-        let mut expr = RecExpr::default();
+        for dim_val in &[4, 8, 12, 16] {
+            let pattern = format!(
+                "(access-slice
+                  (access-pad
+                   (access (access-tensor t) 0)
+                   zero-padding
+                   0 0 {}
+                  )
+                  0 0 1
+                 )",
+                dim_val - 1
+            )
+            .parse::<Pattern<Language>>()
+            .unwrap();
+            let matches = pattern.search_eclass(&runner.egraph, id).unwrap();
+            assert_eq!(matches.substs.len(), 1);
+        }
+    }
 
-        let data_id = expr.add(Language::Symbol("data".to_string()));
-        let data_id = expr.add(Language::AccessTensor(data_id));
+    /// This test tests the newer conv2d syntax, on a more realistically-shaped
+    /// set of inputs.
+    #[test]
+    fn conv2d_im2col_systolic_array_1() {
+        test_logger::ensure_env_logger_initialized();
 
-        let kernel_id = expr.add(Language::Symbol("kernel".to_string()));
-        let kernel_id = expr.add(Language::AccessTensor(kernel_id));
+        pub const IMAGE_SHAPE: &[usize] = &[1, 32, 32, 3];
+        pub const KERNEL_SHAPE: &[usize] = &[3, 3, 3, 8];
+
+        let mut expr = RecExpr::from_str("(access-tensor image)").unwrap();
+        let data_id = expr.as_ref().len() - 1;
+        let weights_id = expr.add(Language::Symbol("weights".to_string()));
+        let weights_id = expr.add(Language::AccessTensor(weights_id));
 
         let _conv2d_id = crate::language::from_relay::conv2d(
             &mut expr,
-            data_id,
-            &data_shape,
-            kernel_id,
-            &kernel_shape,
+            data_id.into(),
+            IMAGE_SHAPE,
+            weights_id,
+            KERNEL_SHAPE,
             &[1, 1],
             &[1, 1, 1, 1],
             &[1, 1],
@@ -5620,276 +6911,354 @@ mod tests {
         );
 
         let mut map = HashMap::default();
-        map.insert("data".to_string(), data_shape);
-        map.insert("kernel".to_string(), kernel_shape);
+        // batch, height, width, channels
+        map.insert("image".to_string(), vec![1, 32, 32, 3]);
+        // kernel height, kernel width, in channels, out channels
+        map.insert("weights".to_string(), vec![3, 3, 3, 8]);
+
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&expr);
         egraph.rebuild();
 
         let rws = vec![
-            // This rewrite enables im2col to be found.
             super::flatten_unflatten_any_access(),
-            // These rewrites move the reshape into the right place.
             super::bubble_reshape_through_cartesian_product(),
             super::bubble_reshape_through_compute_dot_product(),
-            // This rewrite tensorizes.
-            super::systolic_array_conv2d_im2col_fc_with_blocking(32, 32),
+            super::systolic_array().rewrite,
         ];
 
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
             .run(&rws);
-        match runner.stop_reason.unwrap() {
-            egg::StopReason::Saturated => (),
-            _ => panic!(),
-        };
 
+        // Expected original program.
         let test_pattern = "
-          (systolic-array-conv2d-im2col-fc-with-blocking 32 32
-           (access (access-tensor data) 1)
-           (access (access-tensor kernel) 0)
+(access-transpose
+ (access-transpose
+  (compute dot-product
+   (access-cartesian-product
+    (access
+     (access-transpose (access-tensor weights) (list 3 2 0 1))
+     1
+    )
+    (access
+     (access-squeeze
+       (access-windows
+        (access
+         (access-pad
+          (access-pad
+           (access-transpose (access-tensor image) (list 0 3 1 2))
+           zero-padding
+           2 1 1
           )
+          zero-padding
+          3 1 1
+         )
+         1
+        )
+        (shape 3 3 3)
+        (shape 1 1 1)
+       )
+      1
+     )
+     3
+    )
+   )
+  )
+  (list 1 0 2 3)
+ )
+ (list 0 2 3 1)
+)
             "
         .parse::<Pattern<Language>>()
         .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
         assert_eq!(matches.substs.len(), 1);
-    }
-
-    #[test]
-    #[ignore = "ignored b/c broken during pldi push"]
-    fn systolic_array_conv2d_nchw_oihw_with_blocking() {
-        let data_shape = vec![1, 64, 32, 32]; // NCHW
-        let kernel_shape = vec![128, 64, 3, 3]; // OIHW
-
-        let mut expr = RecExpr::default();
-
-        let data_id = expr.add(Language::Symbol("data".to_string()));
-        let data_id = expr.add(Language::AccessTensor(data_id));
-
-        let kernel_id = expr.add(Language::Symbol("kernel".to_string()));
-        let kernel_id = expr.add(Language::AccessTensor(kernel_id));
-
-        let _conv2d_id = crate::language::from_relay::conv2d(
-            &mut expr,
-            data_id,
-            &data_shape,
-            kernel_id,
-            &kernel_shape,
-            &[1, 1],
-            &[1, 1, 1, 1],
-            &[1, 1],
-            1,
-            "NCHW",
-            "OIHW",
-            "",
-            false,
-        );
-
-        let mut map = HashMap::default();
-        map.insert("data".to_string(), data_shape);
-        map.insert("kernel".to_string(), kernel_shape);
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
-            name_to_shape: map,
-            name_to_dtype: HashMap::default(),
-        });
-        let id = egraph.add_expr(&expr);
-        egraph.rebuild();
-
-        let rws = vec![
-            super::systolic_array_conv2d_nchw_oihw_with_blocking(64, 32),
-            super::systolic_array_conv2d_nchw_oihw_with_blocking(32, 32),
-            super::systolic_array_conv2d_nchw_oihw_with_blocking(2, 2),
-            super::systolic_array_conv2d_nchw_oihw_with_blocking(3, 2),
-        ];
-
-        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
-            .with_egraph(egraph)
-            .run(&rws);
-        match runner.stop_reason.unwrap() {
-            egg::StopReason::Saturated => (),
-            _ => panic!(),
-        };
 
+        // Find the version with flattened and reshaped weight/image arguments
         let test_pattern = "
-          (systolic-array-conv2d-nchw-oihw-with-blocking
-           64 32
-           (access-tensor kernel)
-           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
-           3 3
-           1 1
+(access-transpose
+ (access-transpose
+  (compute dot-product
+   (access-cartesian-product
+    (access-reshape
+     (access-flatten
+      (access
+       (access-transpose (access-tensor weights) (list 3 2 0 1))
+       1
+      )
+     )
+     ?shape1
+    )
+    (access-reshape
+     (access-flatten
+      (access
+       (access-squeeze
+         (access-windows
+          (access
+           (access-pad
+            (access-pad
+             (access-transpose (access-tensor image) (list 0 3 1 2))
+             zero-padding
+             2 1 1
+            )
+            zero-padding
+            3 1 1
+           )
+           1
           )
+          (shape 3 3 3)
+          (shape 1 1 1)
+         )
+        1
+       )
+       3
+      )
+     )
+     ?shape0
+    )
+   )
+  )
+  (list 1 0 2 3)
+ )
+ (list 0 2 3 1)
+)
             "
         .parse::<Pattern<Language>>()
         .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
         assert_eq!(matches.substs.len(), 1);
 
-        let _matches = "
-          (systolic-array-conv2d-nchw-oihw-with-blocking
-           32 32
-           (access-tensor kernel)
-           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
-           3 3
-           1 1
+        let test_pattern = "
+(access-transpose
+ (access-transpose
+  (access-reshape
+   (systolic-array 27 1024
+    (access-flatten
+     (access
+      (access-transpose (access-tensor weights) (list 3 2 0 1))
+      1
+     )
+    )
+    (access
+     (access-transpose
+      (access-flatten
+       (access
+        (access-squeeze
+          (access-windows
+           (access
+            (access-pad
+             (access-pad
+              (access-transpose (access-tensor image) (list 0 3 1 2))
+              zero-padding
+              2 1 1
+             )
+             zero-padding
+             3 1 1
+            )
+            1
+           )
+           (shape 3 3 3)
+           (shape 1 1 1)
           )
+         1
+        )
+        3
+       )
+      )
+      (list 1 0)
+     )
+     0
+    )
+   )
+   (access-shape (shape 8 1 32 32) (shape))
+  )
+  (list 1 0 2 3)
+ )
+ (list 0 2 3 1)
+)
             "
         .parse::<Pattern<Language>>()
         .unwrap();
-        let _matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        // I don't think this check makes sense.
-        //assert_eq!(matches.substs.len(), 1);
+        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        assert_eq!(matches.substs.len(), 1);
+    }
 
-        let _matches = "
-          (systolic-array-conv2d-nchw-oihw-with-blocking
-          2 2
-           (access-tensor kernel)
-           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
-           3 3
-           1 1
-          )
-            "
-        .parse::<Pattern<Language>>()
+    #[test]
+    fn bubble_access_slice_through_access_pad_inequal_axes() {
+        let program = "
+              (access-pad
+               (access-slice
+                (access-tensor t)
+                0 0 8
+               )
+               zero-padding 1 2 2
+              )
+             "
+        .parse()
         .unwrap();
-        let _matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        // I don't think this check makes sense.
-        //assert_eq!(matches.substs.len(), 1);
+        let mut map = HashMap::default();
+        map.insert("t".to_string(), vec![8, 10]);
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+        let rws = vec![super::bubble_access_slice_through_access_pad_inequal_axes()];
+        let runner = Runner::<_, _, ()>::default().with_egraph(egraph).run(&rws);
 
         let test_pattern = "
-          (systolic-array-conv2d-nchw-oihw-with-blocking
-          3 2
-           (access-tensor kernel)
-           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
-           3 3
-           1 1
-          )
-            "
+              (access-slice
+               (access-pad
+                (access-tensor t)
+                zero-padding 1 2 2
+               )
+               0 0 8
+              )
+"
         .parse::<Pattern<Language>>()
         .unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id);
-        assert!(matches.is_none());
+        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        assert_eq!(matches.substs.len(), 1);
     }
 
     #[test]
-    #[ignore = "ignored b/c broken during pldi push"]
-    fn systolic_array_conv2d_nhwc_hwio_with_blocking() {
-        let data_shape = vec![1, 32, 32, 64]; // NHWC
-        let kernel_shape = vec![3, 3, 64, 128]; // HWIO
-
-        let mut expr = RecExpr::default();
-
-        let data_id = expr.add(Language::Symbol("data".to_string()));
-        let data_id = expr.add(Language::AccessTensor(data_id));
-
-        let kernel_id = expr.add(Language::Symbol("kernel".to_string()));
-        let kernel_id = expr.add(Language::AccessTensor(kernel_id));
-
-        let _conv2d_id = crate::language::from_relay::conv2d(
-            &mut expr,
-            data_id,
-            &data_shape,
-            kernel_id,
-            &kernel_shape,
-            &[1, 1],
-            &[1, 1, 1, 1],
-            &[1, 1],
-            1,
-            "NHWC",
-            "HWIO",
-            "",
-            false,
-        );
-
+    fn pad_slice_accesses_pad_to_closest_multiple() {
+        let program = "(access (access-tensor t) 0)".parse().unwrap();
         let mut map = HashMap::default();
-        map.insert("data".to_string(), data_shape);
-        map.insert("kernel".to_string(), kernel_shape);
+        map.insert("t".to_string(), vec![1, 2, 3, 4]);
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
-        let id = egraph.add_expr(&expr);
+        let id = egraph.add_expr(&program);
         egraph.rebuild();
-
         let rws = vec![
-            // This rewrite is needed to move the padding further "out" and the
-            // transposes further "in", so that we can match on the layout
-            // changes on the inputs.
-            super::bubble_access_transpose_through_access_pad(),
-            // These rewrites are needed to find the initial systolic array.
-            super::systolic_array_conv2d_nchw_oihw_with_blocking(64, 32),
-            super::systolic_array_conv2d_nchw_oihw_with_blocking(32, 32),
-            super::systolic_array_conv2d_nchw_oihw_with_blocking(2, 2),
-            super::systolic_array_conv2d_nchw_oihw_with_blocking(3, 2),
-            super::systolic_array_conv2d_nhwc_hwio_with_blocking(64, 32),
-            super::systolic_array_conv2d_nhwc_hwio_with_blocking(32, 32),
-            super::systolic_array_conv2d_nhwc_hwio_with_blocking(2, 2),
-            super::systolic_array_conv2d_nhwc_hwio_with_blocking(3, 2),
+            super::pad_slice_accesses(
+                0,
+                PadSliceStrategy::PadToClosestMultipleOf {
+                    multiple_of: 3,
+                    pad_location: PadLocation::End,
+                    pad_type: PadType::ZeroPadding,
+                },
+            ),
+            super::pad_slice_accesses(
+                1,
+                PadSliceStrategy::PadToClosestMultipleOf {
+                    multiple_of: 3,
+                    pad_location: PadLocation::End,
+                    pad_type: PadType::ZeroPadding,
+                },
+            ),
+            super::pad_slice_accesses(
+                2,
+                PadSliceStrategy::PadToClosestMultipleOf {
+                    multiple_of: 3,
+                    pad_location: PadLocation::End,
+                    pad_type: PadType::ZeroPadding,
+                },
+            ),
+            super::pad_slice_accesses(
+                3,
+                PadSliceStrategy::PadToClosestMultipleOf {
+                    multiple_of: 3,
+                    pad_location: PadLocation::End,
+                    pad_type: PadType::ZeroPadding,
+                },
+            ),
         ];
+        let runner = Runner::<_, _, ()>::default().with_egraph(egraph).run(&rws);
 
-        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
-            .with_egraph(egraph)
-            .run(&rws);
-        match runner.stop_reason.unwrap() {
-            egg::StopReason::Saturated => (),
-            _ => panic!(),
-        };
+        for class in runner.egraph.classes() {
+            match &class.data {
+                MyAnalysisData::AccessPattern(a) => {
+                    for &shape_dim_val in a.shape.slice() {
+                        assert!(shape_dim_val <= 6);
+                    }
+                    for &item_shape_dim_val in a.item_shape.slice() {
+                        assert!(item_shape_dim_val <= 6);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        runner.print_report();
 
         let test_pattern = "
-          (systolic-array-conv2d-nhwc-hwio-with-blocking
-           64 32
-           (access-tensor kernel)
-           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
-           3 3
-           1 1
-          )
-            "
+                 (access-slice
+                  (access-pad
+                   (access (access-tensor t) 0)
+                   zero-padding
+                   0 0 2
+                  )
+                  0 0 1
+                 )"
         .parse::<Pattern<Language>>()
         .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
         assert_eq!(matches.substs.len(), 1);
 
-        let _matches = "
-          (systolic-array-conv2d-nhwc-hwio-with-blocking
-           32 32
-           (access-tensor kernel)
-           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
-           3 3
-           1 1
-          )
-            "
+        let test_pattern = "
+                 (access-slice
+                  (access-pad
+                   (access (access-tensor t) 0)
+                   zero-padding
+                   1 0 1
+                  )
+                  1 0 2
+                 )"
         .parse::<Pattern<Language>>()
         .unwrap();
-        let _matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        // I don't think this check makes sense.
-        //assert_eq!(matches.substs.len(), 1);
+        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        assert_eq!(matches.substs.len(), 1);
 
-        let _matches = "
-          (systolic-array-conv2d-nhwc-hwio-with-blocking
-          2 2
-           (access-tensor kernel)
-           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
-           3 3
-           1 1
-          )
-            "
+        let test_pattern = "
+                 (access-slice
+                  (access-pad
+                   (access (access-tensor t) 0)
+                   zero-padding
+                   2 0 0
+                  )
+                  2 0 3
+                 )"
         .parse::<Pattern<Language>>()
         .unwrap();
-        let _matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        // I don't think this check makes sense.
-        //assert_eq!(matches.substs.len(), 1);
+        let matches = test_pattern.search_eclass(&runner.egraph, id);
+        assert!(matches.is_none());
 
         let test_pattern = "
-          (systolic-array-conv2d-nhwc-hwio-with-blocking
-          3 2
-           (access-tensor kernel)
-           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
-           3 3
-           1 1
-          )
-            "
+                 (access-slice
+                  (access-pad
+                   (access (access-tensor t) 0)
+                   zero-padding
+                   3 0 2
+                  )
+                  3 0 4
+                 )"
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        assert_eq!(matches.substs.len(), 1);
+
+        // Shouldn't pad up to the next highest multiple. I.e. length 1
+        // shouldn't go to 6, just to 3.
+        let test_pattern = "
+                 (access-slice
+                  (access-pad
+                   (access (access-tensor t) 0)
+                   zero-padding
+                   0 0 5
+                  )
+                  0 0 1
+                 )"
         .parse::<Pattern<Language>>()
         .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id);
@@ -5897,48 +7266,38 @@ mod tests {
     }
 
     #[test]
-    fn bubble_access_transpose_through_access_pad() {
-        let mut map = HashMap::default();
-        map.insert("a".to_string(), vec![32, 64, 3, 2]);
+    fn bubble_access_slice_through_access_cartesian_product_left() {
         let program = "
-         (access-pad
-          (access-transpose
-           (access (access-tensor a) 1)
-           (list 3 1 2 0)
-          )
-          zero-padding
-          0 2 3
-         )
-        "
+             (access-cartesian-product
+              (access-slice (access (access-tensor a) 1) 0 0 2)
+              (access (access-tensor b) 1)
+             )"
         .parse()
         .unwrap();
+        let mut map = HashMap::default();
+        map.insert("a".to_string(), vec![4, 3, 3, 4]);
+        map.insert("b".to_string(), vec![10, 3, 3, 4]);
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         egraph.rebuild();
-
-        let rws = vec![super::bubble_access_transpose_through_access_pad()];
-
+        let rws =
+            vec![super::bubble_access_slice_through_access_cartesian_product_not_item_axis_left()];
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
             .run(&rws);
-        match runner.stop_reason.unwrap() {
-            egg::StopReason::Saturated => (),
-            _ => panic!(),
-        };
 
         let test_pattern = "
-          (access-transpose
-           (access-pad
-            (access (access-tensor a) 1)
-            zero-padding
-            3 2 3
-           )
-           (list 3 1 2 0)
-          )
-            "
+             (access-slice
+              (access-cartesian-product
+               (access (access-tensor a) 1)
+               (access (access-tensor b) 1)
+              )
+              0 0 2
+             )"
         .parse::<Pattern<Language>>()
         .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
@@ -5946,276 +7305,113 @@ mod tests {
     }
 
     #[test]
-    fn systolic_array_conv2d_im2col_nchw_oihw_with_blocking() {
-        let data_shape = vec![1, 64, 32, 32]; // NCHW
-        let kernel_shape = vec![128, 64, 3, 3]; // OIHW
-
-        let mut expr = RecExpr::default();
-
-        let data_id = expr.add(Language::Symbol("data".to_string()));
-        let data_id = expr.add(Language::AccessTensor(data_id));
-
-        let kernel_id = expr.add(Language::Symbol("kernel".to_string()));
-        let kernel_id = expr.add(Language::AccessTensor(kernel_id));
-
-        let _conv2d_id = crate::language::from_relay::conv2d(
-            &mut expr,
-            data_id,
-            &data_shape,
-            kernel_id,
-            &kernel_shape,
-            &[1, 1],
-            &[1, 1, 1, 1],
-            &[1, 1],
-            1,
-            "NCHW",
-            "OIHW",
-            "",
-            false,
-        );
-
+    fn bubble_access_slice_through_access_cartesian_product_right() {
+        let program = "
+             (access-cartesian-product
+              (access (access-tensor b) 1)
+              (access-slice (access (access-tensor a) 1) 0 0 2)
+             )"
+        .parse()
+        .unwrap();
         let mut map = HashMap::default();
-        map.insert("data".to_string(), data_shape);
-        map.insert("kernel".to_string(), kernel_shape);
+        map.insert("a".to_string(), vec![4, 3, 3, 4]);
+        map.insert("b".to_string(), vec![10, 3, 3, 4]);
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
-        let id = egraph.add_expr(&expr);
+        let id = egraph.add_expr(&program);
         egraph.rebuild();
-
-        let rws = vec![
-            // These are needed to find the im2col convolution
-            super::flatten_unflatten_any_access(),
-            super::bubble_reshape_through_cartesian_product(),
-            super::bubble_reshape_through_compute_dot_product(),
-            super::systolic_array_conv2d_im2col_nchw_oihw_with_blocking(64, 32),
-            super::systolic_array_conv2d_im2col_nchw_oihw_with_blocking(32, 32),
-            super::systolic_array_conv2d_im2col_nchw_oihw_with_blocking(2, 2),
-            super::systolic_array_conv2d_im2col_nchw_oihw_with_blocking(3, 2),
-        ];
-
+        let rws =
+            vec![super::bubble_access_slice_through_access_cartesian_product_not_item_axis_right()];
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
             .run(&rws);
 
         let test_pattern = "
-          (systolic-array-conv2d-im2col-nchw-oihw-with-blocking
-           64 32
-           (access-tensor kernel)
-           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
-           3 3
-           1 1
-          )
-            "
+             (access-slice
+              (access-cartesian-product
+               (access (access-tensor b) 1)
+               (access (access-tensor a) 1)
+              )
+              1 0 2
+             )"
         .parse::<Pattern<Language>>()
         .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
         assert_eq!(matches.substs.len(), 1);
-
-        let _matches = "
-          (systolic-array-conv2d-im2col-nchw-oihw-with-blocking
-           32 32
-           (access-tensor kernel)
-           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
-           3 3
-           1 1
-          )
-            "
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let _matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        // I don't think this check makes sense.
-        //assert_eq!(matches.substs.len(), 1);
-
-        let _matches = "
-          (systolic-array-conv2d-im2col-nchw-oihw-with-blocking
-          2 2
-           (access-tensor kernel)
-           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
-           3 3
-           1 1
-          )
-            "
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let _matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        // I don't think this check makes sense.
-        //assert_eq!(matches.substs.len(), 1);
-
-        let _matches = "
-          (systolic-array-conv2d-im2col-nchw-oihw-with-blocking
-          3 2
-           (access-tensor kernel)
-           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
-           3 3
-           1 1
-          )
-            "
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let _matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        // I don't think this check makes sense.
-        //assert_eq!(matches.substs.len(), 1);
     }
 
     #[test]
-    fn systolic_array_conv2d_im2col_nhwc_hwio_with_blocking() {
-        let data_shape = vec![1, 32, 32, 64]; // NHWC
-        let kernel_shape = vec![3, 3, 64, 128]; // HWIO
-
-        let mut expr = RecExpr::default();
-
-        let data_id = expr.add(Language::Symbol("data".to_string()));
-        let data_id = expr.add(Language::AccessTensor(data_id));
-
-        let kernel_id = expr.add(Language::Symbol("kernel".to_string()));
-        let kernel_id = expr.add(Language::AccessTensor(kernel_id));
-
-        let _conv2d_id = crate::language::from_relay::conv2d(
-            &mut expr,
-            data_id,
-            &data_shape,
-            kernel_id,
-            &kernel_shape,
-            &[1, 1],
-            &[1, 1, 1, 1],
-            &[1, 1],
-            1,
-            "NHWC",
-            "HWIO",
-            "",
-            false,
-        );
-
+    fn bubble_access_slice_through_access_cartesian_product_same_item_axis() {
+        let program = "
+             (access-cartesian-product
+              (access-slice (access (access-tensor a) 2) 3 1 2)
+              (access-slice (access (access-tensor b) 1) 2 1 2)
+             )"
+        .parse()
+        .unwrap();
         let mut map = HashMap::default();
-        map.insert("data".to_string(), data_shape);
-        map.insert("kernel".to_string(), kernel_shape);
+        map.insert("a".to_string(), vec![4, 16, 3, 3, 4]);
+        map.insert("b".to_string(), vec![10, 3, 3, 4]);
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
-        let id = egraph.add_expr(&expr);
+        let id = egraph.add_expr(&program);
         egraph.rebuild();
-
-        let rws = vec![
-            // These are needed to find the im2col convolution
-            super::flatten_unflatten_any_access(),
-            super::bubble_reshape_through_cartesian_product(),
-            super::bubble_reshape_through_compute_dot_product(),
-            // These tensorize to nchw systolic arrays, which is needed to find
-            // nhwc
-            super::systolic_array_conv2d_im2col_nchw_oihw_with_blocking(64, 32),
-            super::systolic_array_conv2d_im2col_nchw_oihw_with_blocking(32, 32),
-            super::systolic_array_conv2d_im2col_nchw_oihw_with_blocking(2, 2),
-            super::systolic_array_conv2d_im2col_nchw_oihw_with_blocking(3, 2),
-            // This rewrite is needed to move the padding further "out" and the
-            // transposes further "in", so that we can match on the layout
-            // changes on the inputs.
-            super::bubble_access_transpose_through_access_pad(),
-            // These rewrites tensorize.
-            super::systolic_array_conv2d_im2col_nhwc_hwio_with_blocking(64, 32),
-            super::systolic_array_conv2d_im2col_nhwc_hwio_with_blocking(32, 32),
-            super::systolic_array_conv2d_im2col_nhwc_hwio_with_blocking(2, 2),
-            super::systolic_array_conv2d_im2col_nhwc_hwio_with_blocking(3, 2),
-        ];
-
+        let rws =
+            vec![super::bubble_access_slice_through_access_cartesian_product_same_item_axis()];
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
             .run(&rws);
-        runner.print_report();
 
         let test_pattern = "
-          (systolic-array-conv2d-im2col-nhwc-hwio-with-blocking
-           64 32
-           (access-tensor kernel)
-           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
-           3 3
-           1 1
-          )
-            "
+             (access-slice
+              (access-cartesian-product
+               (access (access-tensor a) 2)
+               (access (access-tensor b) 1)
+              )
+              5 1 2
+             )"
         .parse::<Pattern<Language>>()
         .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
         assert_eq!(matches.substs.len(), 1);
-
-        let _matches = "
-          (systolic-array-conv2d-im2col-nhwc-hwio-with-blocking
-           32 32
-           (access-tensor kernel)
-           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
-           3 3
-           1 1
-          )
-            "
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let _matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        // I don't think this check makes sense.
-        //assert_eq!(matches.substs.len(), 1);
-
-        let _matches = "
-          (systolic-array-conv2d-im2col-nhwc-hwio-with-blocking
-          2 2
-           (access-tensor kernel)
-           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
-           3 3
-           1 1
-          )
-            "
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let _matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        // I don't think this check makes sense.
-        //assert_eq!(matches.substs.len(), 1);
-
-        let _matches = "
-          (systolic-array-conv2d-im2col-nhwc-hwio-with-blocking
-          3 2
-           (access-tensor kernel)
-           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
-           3 3
-           1 1
-          )
-            "
-        .parse::<Pattern<Language>>()
-        .unwrap();
-        let _matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
-        // I don't think this check makes sense.
-        //assert_eq!(matches.substs.len(), 1);
     }
 
     #[test]
-    fn reassociate_max() {
-        let mut map = HashMap::default();
-        map.insert("a".to_string(), vec![16, 4]);
+    fn bubble_access_slice_through_compute_dot_product_not_item_axis() {
         let program = "
-         (compute reduce-max (access (access-tensor a) 1))
-        "
+             (compute dot-product
+              (access-slice
+               (access (access-tensor a) 1)
+               0 2 3
+              )
+             )"
         .parse()
         .unwrap();
+        let mut map = HashMap::default();
+        map.insert("a".to_string(), vec![4, 16, 3, 3, 4]);
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         egraph.rebuild();
-
-        let rws = vec![super::reassociate_max(2, 2)];
-
+        let rws = vec![super::bubble_access_slice_through_compute_dot_product_not_item_axis()];
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
             .run(&rws);
 
         let test_pattern = "
-            (compute reduce-max
-             (access
-              (compute reduce-max
-               (access-windows
-                (access (access-tensor a) 1)
-                (shape 2)
-                (shape 2)))
-              1))
+             (access-slice
+              (compute dot-product (access (access-tensor a) 1))
+              0 2 3
+             )
              "
         .parse::<Pattern<Language>>()
         .unwrap();
@@ -6224,41 +7420,43 @@ mod tests {
     }
 
     #[test]
-    fn reassociate_max_maxpool_2d() {
-        let mut map = HashMap::default();
-        map.insert("data".to_string(), vec![16, 4]);
+    fn bubble_access_slice_through_compute_dot_product_item_axis_not_tuple_axis() {
         let program = "
-         (compute reduce-max 
-          (access-windows 
-           (access (access-tensor data) 1) 
-           (shape 4) (shape 4)))
-        "
+             (compute dot-product
+              (access-slice
+               (access-pad
+                (access (access-tensor a) 1)
+                zero-padding
+                3 2 3
+               )
+               3 2 5
+              )
+             )"
         .parse()
         .unwrap();
+        let mut map = HashMap::default();
+        map.insert("a".to_string(), vec![4, 16, 3, 3, 4]);
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         egraph.rebuild();
-
-        let rws = vec![super::reassociate_max(2, 2)];
-
+        let rws =
+            vec![super::bubble_access_slice_through_compute_dot_product_item_axis_not_tuple_axis()];
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
             .run(&rws);
 
         let test_pattern = "
-            (compute reduce-max
-             (access
-              (compute reduce-max
-               (access-windows
-                (access-windows 
-                 (access (access-tensor data) 1) 
-                 (shape 4) (shape 4))
-                (shape 2)
-                (shape 2)))
-              2))
+              (compute dot-product
+               (access-pad
+                (access (access-tensor a) 1)
+                zero-padding
+                3 2 3
+               )
+              )
              "
         .parse::<Pattern<Language>>()
         .unwrap();
@@ -6266,327 +7464,1203 @@ mod tests {
         assert_eq!(matches.substs.len(), 1);
     }
 
-    /// Test in which we map a small 2D max pool layer to FlexASR. This is done
-    /// by:
-    ///
-    /// 1. Flattening the input windows to vectors,
-    /// 2. Converting the reduce-max computation to reduce in windows of two
-    ///    (FlexASR-style) rather than reducing the entire vector all at once,
-    /// 3. Mapping the new reduce-max computations to FlexASR.
-    ///
-    /// There are also various rewrites used for cleanup and exploration.
     #[test]
-    fn flexasr_maxpool_split_tensorize() {
-        // Very simple 2D max pool layer. We use small shapes here so that we
-        // can write out the final expression by hand; the larger the reduction,
-        // the larger the final expression. Note that this is the max pool
-        // described in our original paper.
+    fn systolic_array_with_blocking() {
+        let mut map = HashMap::default();
+        map.insert("a".to_string(), vec![32, 64]);
+        map.insert("b".to_string(), vec![32, 64]);
         let program = "
-         (compute reduce-max 
-          (access-windows 
-           (access (access-tensor data) 2) 
-           (shape 2 2) (shape 2 2)))
-         "
+         (compute dot-product
+          (access-cartesian-product
+           (access (access-tensor a) 1)
+           (access (access-tensor b) 1)
+          )
+         )
+        "
         .parse()
         .unwrap();
-
-        // Define the shape of the input data: batch, channels, height, width.
-        let mut map = HashMap::default();
-        map.insert("data".to_string(), vec![3, 16, 4, 4]);
-
-        // Insert the expression into the egraph.
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
         let id = egraph.add_expr(&program);
         egraph.rebuild();
 
-        // Define our rewrites. These rewrites are what map the max pool to
-        // FlexASR.
         let rws = vec![
-            // Performs initial flattening of 2D pooling windows into vectors.
-            super::flatten_unflatten_any_access(),
-            // Splits a reduce-max into two reduce-maxes: the first reduce max
-            // reduces the data in windows of size 2, striding by 2 (FlexASR
-            // style) while the second reduce max just reduces the rest all at
-            // once. This is the core rewrite for transforming max pools to a
-            // format which can be mapped to FlexASR.
-            super::reassociate_max(2, 2),
-            // Tensorize.
-            super::flexasr_maxpool(),
-            //
-            // The rest of the rewrites are needed for cleanup.
-            //
-            // Moves the access-reshape which results from the flatten-unflatten
-            // rewrite up through the program.
-            super::bubble_access_reshape_through_compute_reduce_max(),
-            // Collapses adjacent operators.
-            super::simplify_multiple_accesses(),
-            super::simplify_multiple_transposes(),
-            super::simplify_multiple_access_reshapes(),
-            // Move access through access-transpose, to enable more collapsing.
-            super::bubble_access_through_access_transpose(),
-            // Remove the topmost reduce-max which becomes "trivial" (i.e. a max
-            // over a single element) after we rewrite it multiple times to
-            // FlexASR invocations.
-            super::simplify_reduce_max(),
+            super::systolic_array_with_blocking(64, 32).rewrite,
+            super::systolic_array_with_blocking(32, 32).rewrite,
+            super::systolic_array_with_blocking(16, 32).rewrite,
+            super::systolic_array_with_blocking(32, 16).rewrite,
+            super::systolic_array_with_blocking(2, 2).rewrite,
+            // Shouldn't tensorize to this one.
+            super::systolic_array_with_blocking(32, 3).rewrite,
         ];
 
-        // Run the rewrites.
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
-            .with_iter_limit(7)
             .run(&rws);
+        match runner.stop_reason.unwrap() {
+            egg::StopReason::Saturated => (),
+            _ => panic!(),
+        };
 
-        // Assert that we find the expected program in the egraph.
-        //
-        // The final program computes the original max pool with two invocations
-        // of FlexASR.
-        //
-        // Reading from inside to out:
-        // 1. We first form 2x2 windows over the data. These are the windows we
-        //    want to reduce via the max operator.
-        // 2. We then flatten those 2x2 windows into vectors of length 4.
-        // 3. We transpose the data so that it's in the format expected by
-        //    FlexASR.
-        // 4. We compute multiple max pools on FlexASR.
-        // 5. We transpose the data back to its original layout, and reshape it
-        //    to its final reshape.
-        //
-        // Note that operators like access-reshape, access-flatten, and access
-        // are operators which exist purely to keep the types in check in
-        // Glenside. They do not involve actual data movement.
         let test_pattern = "
-         (access-reshape
-          (access
-           (access-transpose
-            (accelerator-call flex-maxpool
-             (access
-              (accelerator-call flex-maxpool
-               (access
-                (access-transpose
-                 (access-flatten
-                  (access-windows
-                   (access (access-tensor data) 2)
-                   (shape 2 2)
-                   (shape 2 2)))
-                 (list 1 0))
-                0) ?shape0)
-              0) ?shape1)
-            (list 1 0))
-           1)
-          (access-shape (shape 3 16 2 2) (shape)))
-        "
+          (systolic-array-with-blocking 64 32
+           (access (access-tensor a) 1)
+           (access (access-transpose  (access (access-tensor b) 1) (list 1 0)) 0)
+          )
+            "
         .parse::<Pattern<Language>>()
         .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
         assert_eq!(matches.substs.len(), 1);
-    }
-
-    #[test]
-    fn bubble_access_reshape_through_compute_reduce_max() {
-        let mut map = HashMap::default();
-        map.insert("data".to_string(), vec![16, 4]);
-
-        let program = "
-         (compute reduce-max
-          (access-reshape 
-           (access (access-tensor data) 1)
-           (access-shape (shape 4 4) (shape 1 2 2))))"
-            .parse()
-            .unwrap();
 
-        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
-            name_to_shape: map,
-            name_to_dtype: HashMap::default(),
-        });
-        let id = egraph.add_expr(&program);
-        egraph.rebuild();
+        let test_pattern = "
+          (systolic-array-with-blocking 32 32
+           (access (access-tensor a) 1)
+           (access (access-transpose  (access (access-tensor b) 1) (list 1 0)) 0)
+          )
+            "
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        assert_eq!(matches.substs.len(), 1);
 
-        let rws = vec![super::bubble_access_reshape_through_compute_reduce_max()];
+        let test_pattern = "
+          (systolic-array-with-blocking 16 32
+           (access (access-tensor a) 1)
+           (access (access-transpose  (access (access-tensor b) 1) (list 1 0)) 0)
+          )
+            "
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        assert_eq!(matches.substs.len(), 1);
 
-        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
-            .with_egraph(egraph)
-            .run(&rws);
+        let test_pattern = "
+          (systolic-array-with-blocking 32 16
+           (access (access-tensor a) 1)
+           (access (access-transpose  (access (access-tensor b) 1) (list 1 0)) 0)
+          )
+            "
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        assert_eq!(matches.substs.len(), 1);
 
         let test_pattern = "
-         (access-reshape
-          (compute reduce-max 
-           (access (access-tensor data) 1))
-          (access-shape (shape 4 4) (shape)))"
-            .parse::<Pattern<Language>>()
-            .unwrap();
+          (systolic-array-with-blocking 2 2
+           (access (access-tensor a) 1)
+           (access (access-transpose  (access (access-tensor b) 1) (list 1 0)) 0)
+          )
+            "
+        .parse::<Pattern<Language>>()
+        .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
         assert_eq!(matches.substs.len(), 1);
+
+        let test_pattern = "
+          (systolic-array-with-blocking 32 3
+           (access (access-tensor a) 1)
+           (access (access-transpose  (access (access-tensor b) 1) (list 1 0)) 0)
+          )
+            "
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let matches = test_pattern.search_eclass(&runner.egraph, id);
+        assert!(matches.is_none());
     }
 
     #[test]
-    fn simplify_multiple_transposes_0() {
-        test_logger::ensure_env_logger_initialized();
+    #[should_panic]
+    fn conv2d_im2col_fc_systolic_array() {
+        let data_shape = vec![1, 32, 32, 3];
+        let kernel_shape = vec![3, 3, 3, 8];
 
-        let mut map = HashMap::default();
-        map.insert("data".to_string(), vec![2, 3, 4, 5]);
+        let mut expr = RecExpr::default();
 
-        let program = "
-         (access-transpose (access-transpose (access-tensor data) (list 3 1 0 2)) (list 2 1 3 0))
-         "
-        .parse()
-        .unwrap();
+        let data_id = expr.add(Language::Symbol("data".to_string()));
+        let data_id = expr.add(Language::AccessTensor(data_id));
+
+        let kernel_id = expr.add(Language::Symbol("kernel".to_string()));
+        let kernel_id = expr.add(Language::AccessTensor(kernel_id));
+
+        let _conv2d_id = crate::language::from_relay::conv2d(
+            &mut expr,
+            data_id,
+            &data_shape,
+            kernel_id,
+            &kernel_shape,
+            &[1, 1],
+            &[1, 1, 1, 1],
+            &[1, 1],
+            1,
+            "NHWC",
+            "HWIO",
+            "",
+            false,
+        );
 
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), data_shape);
+        map.insert("kernel".to_string(), kernel_shape);
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
-        let id = egraph.add_expr(&program);
+        let id = egraph.add_expr(&expr);
         egraph.rebuild();
 
-        let rws = vec![super::simplify_multiple_transposes()];
+        let rws = vec![
+            // This rewrite enables im2col to be found.
+            super::flatten_unflatten_any_access(),
+            // These rewrites move the reshape into the right place.
+            super::bubble_reshape_through_cartesian_product(),
+            super::bubble_reshape_through_compute_dot_product(),
+            // This rewrite tensorizes.
+            super::systolic_array_conv2d_im2col_fc_with_blocking(32, 32),
+        ];
 
-        let runner = Runner::default().with_egraph(egraph).run(&rws);
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&rws);
+        match runner.stop_reason.unwrap() {
+            egg::StopReason::Saturated => (),
+            _ => panic!(),
+        };
 
-        let test_pattern = "(access-tensor data)".parse::<Pattern<Language>>().unwrap();
+        let test_pattern = "
+          (systolic-array-conv2d-im2col-fc-with-blocking 32 32
+           (access (access-tensor data) 1)
+           (access (access-tensor kernel) 0)
+          )
+            "
+        .parse::<Pattern<Language>>()
+        .unwrap();
         let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
         assert_eq!(matches.substs.len(), 1);
     }
 
     #[test]
-    fn simplify_multiple_transposes_1() {
-        let mut map = HashMap::default();
-        map.insert("data".to_string(), vec![2, 3, 4, 5]);
+    #[ignore = "ignored b/c broken during pldi push"]
+    fn systolic_array_conv2d_nchw_oihw_with_blocking() {
+        let data_shape = vec![1, 64, 32, 32]; // NCHW
+        let kernel_shape = vec![128, 64, 3, 3]; // OIHW
 
-        let program = "
-         (access-transpose (access-transpose (access-tensor data) (list 3 1 0 2)) (list 2 3 1 0))
-         "
-        .parse()
-        .unwrap();
+        let mut expr = RecExpr::default();
+
+        let data_id = expr.add(Language::Symbol("data".to_string()));
+        let data_id = expr.add(Language::AccessTensor(data_id));
+
+        let kernel_id = expr.add(Language::Symbol("kernel".to_string()));
+        let kernel_id = expr.add(Language::AccessTensor(kernel_id));
+
+        let _conv2d_id = crate::language::from_relay::conv2d(
+            &mut expr,
+            data_id,
+            &data_shape,
+            kernel_id,
+            &kernel_shape,
+            &[1, 1],
+            &[1, 1, 1, 1],
+            &[1, 1],
+            1,
+            "NCHW",
+            "OIHW",
+            "",
+            false,
+        );
 
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), data_shape);
+        map.insert("kernel".to_string(), kernel_shape);
         let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
             name_to_shape: map,
             name_to_dtype: HashMap::default(),
+            shape_cache: None,
         });
-        let id = egraph.add_expr(&program);
+        let id = egraph.add_expr(&expr);
         egraph.rebuild();
 
-        let rws = vec![super::simplify_multiple_transposes()];
+        let rws = vec![
+            super::systolic_array_conv2d_nchw_oihw_with_blocking(64, 32).rewrite,
+            super::systolic_array_conv2d_nchw_oihw_with_blocking(32, 32).rewrite,
+            super::systolic_array_conv2d_nchw_oihw_with_blocking(2, 2).rewrite,
+            super::systolic_array_conv2d_nchw_oihw_with_blocking(3, 2).rewrite,
+        ];
 
         let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
             .with_egraph(egraph)
             .run(&rws);
+        match runner.stop_reason.unwrap() {
+            egg::StopReason::Saturated => (),
+            _ => panic!(),
+        };
 
-        let test_pattern = "(access-tensor data)".parse::<Pattern<Language>>().unwrap();
-        let matches = test_pattern.search_eclass(&runner.egraph, id);
+        let test_pattern = "
+          (systolic-array-conv2d-nchw-oihw-with-blocking
+           64 32
+           (access-tensor kernel)
+           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
+           3 3
+           1 1
+          )
+            "
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        assert_eq!(matches.substs.len(), 1);
 
-        assert!(matches.is_none());
-    }
+        let _matches = "
+          (systolic-array-conv2d-nchw-oihw-with-blocking
+           32 32
+           (access-tensor kernel)
+           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
+           3 3
+           1 1
+          )
+            "
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let _matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        // I don't think this check makes sense.
+        //assert_eq!(matches.substs.len(), 1);
 
-    /// Creates a Relay-to-Glenside test
-    /// The test does the following:
-    ///  1. Converts $relay_str to glenside by running the from_relay.py script
-    ///  2. Inserts the resulting Glenside code into an egraph
-    ///  3. Searches the egraph for $glenside_str to ensure the expected program
-    ///     exists
-    /// $test_name: the name of the created test
-    /// $relay_str: A string containing the Relay program to be converted
-    /// $glenside_str: A string containing the expected resulting Glenside
-    ///     expression
-    /// $tol: Tolerance value.
-    /// $rws: Expression evaluating to a vector of rewrites.
-    macro_rules! test {
-        ($(#[$meta:meta])* $test_name:ident, $tol:literal, $relay_str:expr, $glenside_str:expr, $rws: expr) => {
-            // TODO(@gussmith23) Hardcoding to f32
-            test!(
-                $(#[$meta])*
-                $test_name,
-                $tol,
-                $relay_str,
-                $glenside_str,
-                "",
-                Uniform::new(-1f32, 1f32),
-                $rws,
-                &vec![]
-            );
-        };
-        ($(#[$meta:meta])* $test_name:ident, $tol:literal, $relay_str:expr, $glenside_str:expr, $rws: expr, $use_opaque_operators_for: expr) => {
-            // TODO(@gussmith23) Hardcoding to f32
-            test!(
-                $(#[$meta])*
-                $test_name,
-                $tol,
-                $relay_str,
-                $glenside_str,
-                "",
-                Uniform::new(-1f32, 1f32),
-                $rws,
-                $use_opaque_operators_for
-            );
-        };
-        ($(#[$meta:meta])* $test_name:ident, $tol:literal, $relay_str:expr, $glenside_str:expr, $optional_arg:literal, $rws: expr, $use_opaque_operators_for: expr) => {
-            // TODO(@gussmith23) Hardcoding to f32
-            test!(
-                $(#[$meta])*
-                $test_name,
-                $tol,
-                $relay_str,
-                $glenside_str,
-                $optional_arg,
-                Uniform::new(-1f32, 1f32),
-                $rws,
-                $use_opaque_operators_for
-            );
-        };
-        ($(#[$meta:meta])* $test_name:ident, $tol:literal, $relay_str:expr, $glenside_str:expr, $optional_arg:literal, $distribution:expr, $rws: expr, $use_opaque_operators_for: expr) => {
-            $(#[$meta])*
-            #[test]
-            fn $test_name() {
-                test_logger::ensure_env_logger_initialized();
+        let _matches = "
+          (systolic-array-conv2d-nchw-oihw-with-blocking
+          2 2
+           (access-tensor kernel)
+           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
+           3 3
+           1 1
+          )
+            "
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let _matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        // I don't think this check makes sense.
+        //assert_eq!(matches.substs.len(), 1);
 
-                // The number of times to run each program and compare their
-                // outputs.
-                // TODO(@gussmith23) # random samples chosen arbitrarily
-                const SAMPLES: usize = 3;
+        let test_pattern = "
+          (systolic-array-conv2d-nchw-oihw-with-blocking
+          3 2
+           (access-tensor kernel)
+           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
+           3 3
+           1 1
+          )
+            "
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let matches = test_pattern.search_eclass(&runner.egraph, id);
+        assert!(matches.is_none());
+    }
 
-                // Random number generator for generating random tensors.
-                const SEED: u64 = 23;
-                let mut tensor_rng = SmallRng::seed_from_u64(SEED);
+    #[test]
+    #[ignore = "ignored b/c broken during pldi push"]
+    fn systolic_array_conv2d_nhwc_hwio_with_blocking() {
+        let data_shape = vec![1, 32, 32, 64]; // NHWC
+        let kernel_shape = vec![3, 3, 64, 128]; // HWIO
 
-                let module = tvm::ir::module::IRModule::parse("", $relay_str).unwrap();
+        let mut expr = RecExpr::default();
 
-                let (expr, shapes_vec, dtypes_vec) =
-                    from_relay::from_relay(&module, false, $use_opaque_operators_for);
+        let data_id = expr.add(Language::Symbol("data".to_string()));
+        let data_id = expr.add(Language::AccessTensor(data_id));
 
-                let mut env = HashMap::default();
-                for (k, v) in &shapes_vec {
-                    env.insert(k.clone(), v.clone());
-                }
+        let kernel_id = expr.add(Language::Symbol("kernel".to_string()));
+        let kernel_id = expr.add(Language::AccessTensor(kernel_id));
 
-                // TODO(@gussmith23) Include some simple simplifying rewrites
-                // If we add some very basic rewrites here, then $glenside_str
-                // won't need to exactly match what's actually produced by
-                // from_relay.py. It can be simpler (e.g. collapsing accesses).
-                let mut egraph = EGraph::new(MyAnalysis {
-                    name_to_shape: env.clone(),
-                    name_to_dtype: dtypes_vec.into_iter().collect(),
-                });
-                let id = egraph.add_expr(&expr);egraph.rebuild();
+        let _conv2d_id = crate::language::from_relay::conv2d(
+            &mut expr,
+            data_id,
+            &data_shape,
+            kernel_id,
+            &kernel_shape,
+            &[1, 1],
+            &[1, 1, 1, 1],
+            &[1, 1],
+            1,
+            "NHWC",
+            "HWIO",
+            "",
+            false,
+        );
 
-                let pattern = $glenside_str.parse::<Pattern<Language>>().unwrap();
-                // The program should not be found at first.
-                assert!(pattern.search_eclass(&egraph, id).is_none());
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), data_shape);
+        map.insert("kernel".to_string(), kernel_shape);
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&expr);
+        egraph.rebuild();
 
-                // Run compilation rewrites.
-                let runner = Runner::default().with_egraph(egraph).run($rws);
+        let rws = vec![
+            // This rewrite is needed to move the padding further "out" and the
+            // transposes further "in", so that we can match on the layout
+            // changes on the inputs.
+            super::bubble_access_transpose_through_access_pad(),
+            // These rewrites are needed to find the initial systolic array.
+            super::systolic_array_conv2d_nchw_oihw_with_blocking(64, 32).rewrite,
+            super::systolic_array_conv2d_nchw_oihw_with_blocking(32, 32).rewrite,
+            super::systolic_array_conv2d_nchw_oihw_with_blocking(2, 2).rewrite,
+            super::systolic_array_conv2d_nchw_oihw_with_blocking(3, 2).rewrite,
+            super::systolic_array_conv2d_nhwc_hwio_with_blocking(64, 32).rewrite,
+            super::systolic_array_conv2d_nhwc_hwio_with_blocking(32, 32).rewrite,
+            super::systolic_array_conv2d_nhwc_hwio_with_blocking(2, 2).rewrite,
+            super::systolic_array_conv2d_nhwc_hwio_with_blocking(3, 2).rewrite,
+        ];
 
-                // Program should now be found.
-                assert!(pattern.search_eclass(&runner.egraph, id).is_some());
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&rws);
+        match runner.stop_reason.unwrap() {
+            egg::StopReason::Saturated => (),
+            _ => panic!(),
+        };
 
-                let expr_to_interpret = RecExpr::from_str($glenside_str).unwrap();
+        let test_pattern = "
+          (systolic-array-conv2d-nhwc-hwio-with-blocking
+           64 32
+           (access-tensor kernel)
+           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
+           3 3
+           1 1
+          )
+            "
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        assert_eq!(matches.substs.len(), 1);
 
-                for _ in (0..SAMPLES) {
-                    // Run interpreters and compare output.
-                    let script_filepath = format!(
-                        "{}/src/language/from_relay/run_relay.py",
-                        env!("CARGO_MANIFEST_DIR")
+        let _matches = "
+          (systolic-array-conv2d-nhwc-hwio-with-blocking
+           32 32
+           (access-tensor kernel)
+           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
+           3 3
+           1 1
+          )
+            "
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let _matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        // I don't think this check makes sense.
+        //assert_eq!(matches.substs.len(), 1);
+
+        let _matches = "
+          (systolic-array-conv2d-nhwc-hwio-with-blocking
+          2 2
+           (access-tensor kernel)
+           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
+           3 3
+           1 1
+          )
+            "
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let _matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        // I don't think this check makes sense.
+        //assert_eq!(matches.substs.len(), 1);
+
+        let test_pattern = "
+          (systolic-array-conv2d-nhwc-hwio-with-blocking
+          3 2
+           (access-tensor kernel)
+           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
+           3 3
+           1 1
+          )
+            "
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let matches = test_pattern.search_eclass(&runner.egraph, id);
+        assert!(matches.is_none());
+    }
+
+    #[test]
+    fn bubble_access_transpose_through_access_pad() {
+        let mut map = HashMap::default();
+        map.insert("a".to_string(), vec![32, 64, 3, 2]);
+        let program = "
+         (access-pad
+          (access-transpose
+           (access (access-tensor a) 1)
+           (list 3 1 2 0)
+          )
+          zero-padding
+          0 2 3
+         )
+        "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+
+        let rws = vec![super::bubble_access_transpose_through_access_pad()];
+
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&rws);
+        match runner.stop_reason.unwrap() {
+            egg::StopReason::Saturated => (),
+            _ => panic!(),
+        };
+
+        let test_pattern = "
+          (access-transpose
+           (access-pad
+            (access (access-tensor a) 1)
+            zero-padding
+            3 2 3
+           )
+           (list 3 1 2 0)
+          )
+            "
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        assert_eq!(matches.substs.len(), 1);
+    }
+
+    #[test]
+    fn systolic_array_conv2d_im2col_nchw_oihw_with_blocking() {
+        let data_shape = vec![1, 64, 32, 32]; // NCHW
+        let kernel_shape = vec![128, 64, 3, 3]; // OIHW
+
+        let mut expr = RecExpr::default();
+
+        let data_id = expr.add(Language::Symbol("data".to_string()));
+        let data_id = expr.add(Language::AccessTensor(data_id));
+
+        let kernel_id = expr.add(Language::Symbol("kernel".to_string()));
+        let kernel_id = expr.add(Language::AccessTensor(kernel_id));
+
+        let _conv2d_id = crate::language::from_relay::conv2d(
+            &mut expr,
+            data_id,
+            &data_shape,
+            kernel_id,
+            &kernel_shape,
+            &[1, 1],
+            &[1, 1, 1, 1],
+            &[1, 1],
+            1,
+            "NCHW",
+            "OIHW",
+            "",
+            false,
+        );
+
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), data_shape);
+        map.insert("kernel".to_string(), kernel_shape);
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&expr);
+        egraph.rebuild();
+
+        let rws = vec![
+            // These are needed to find the im2col convolution
+            super::flatten_unflatten_any_access(),
+            super::bubble_reshape_through_cartesian_product(),
+            super::bubble_reshape_through_compute_dot_product(),
+            super::systolic_array_conv2d_im2col_nchw_oihw_with_blocking(64, 32).rewrite,
+            super::systolic_array_conv2d_im2col_nchw_oihw_with_blocking(32, 32).rewrite,
+            super::systolic_array_conv2d_im2col_nchw_oihw_with_blocking(2, 2).rewrite,
+            super::systolic_array_conv2d_im2col_nchw_oihw_with_blocking(3, 2).rewrite,
+        ];
+
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&rws);
+
+        let test_pattern = "
+          (systolic-array-conv2d-im2col-nchw-oihw-with-blocking
+           64 32
+           (access-tensor kernel)
+           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
+           3 3
+           1 1
+          )
+            "
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        assert_eq!(matches.substs.len(), 1);
+
+        let _matches = "
+          (systolic-array-conv2d-im2col-nchw-oihw-with-blocking
+           32 32
+           (access-tensor kernel)
+           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
+           3 3
+           1 1
+          )
+            "
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let _matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        // I don't think this check makes sense.
+        //assert_eq!(matches.substs.len(), 1);
+
+        let _matches = "
+          (systolic-array-conv2d-im2col-nchw-oihw-with-blocking
+          2 2
+           (access-tensor kernel)
+           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
+           3 3
+           1 1
+          )
+            "
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let _matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        // I don't think this check makes sense.
+        //assert_eq!(matches.substs.len(), 1);
+
+        let _matches = "
+          (systolic-array-conv2d-im2col-nchw-oihw-with-blocking
+          3 2
+           (access-tensor kernel)
+           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
+           3 3
+           1 1
+          )
+            "
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let _matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        // I don't think this check makes sense.
+        //assert_eq!(matches.substs.len(), 1);
+    }
+
+    #[test]
+    fn systolic_array_conv2d_im2col_nhwc_hwio_with_blocking() {
+        let data_shape = vec![1, 32, 32, 64]; // NHWC
+        let kernel_shape = vec![3, 3, 64, 128]; // HWIO
+
+        let mut expr = RecExpr::default();
+
+        let data_id = expr.add(Language::Symbol("data".to_string()));
+        let data_id = expr.add(Language::AccessTensor(data_id));
+
+        let kernel_id = expr.add(Language::Symbol("kernel".to_string()));
+        let kernel_id = expr.add(Language::AccessTensor(kernel_id));
+
+        let _conv2d_id = crate::language::from_relay::conv2d(
+            &mut expr,
+            data_id,
+            &data_shape,
+            kernel_id,
+            &kernel_shape,
+            &[1, 1],
+            &[1, 1, 1, 1],
+            &[1, 1],
+            1,
+            "NHWC",
+            "HWIO",
+            "",
+            false,
+        );
+
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), data_shape);
+        map.insert("kernel".to_string(), kernel_shape);
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&expr);
+        egraph.rebuild();
+
+        let rws = vec![
+            // These are needed to find the im2col convolution
+            super::flatten_unflatten_any_access(),
+            super::bubble_reshape_through_cartesian_product(),
+            super::bubble_reshape_through_compute_dot_product(),
+            // These tensorize to nchw systolic arrays, which is needed to find
+            // nhwc
+            super::systolic_array_conv2d_im2col_nchw_oihw_with_blocking(64, 32).rewrite,
+            super::systolic_array_conv2d_im2col_nchw_oihw_with_blocking(32, 32).rewrite,
+            super::systolic_array_conv2d_im2col_nchw_oihw_with_blocking(2, 2).rewrite,
+            super::systolic_array_conv2d_im2col_nchw_oihw_with_blocking(3, 2).rewrite,
+            // This rewrite is needed to move the padding further "out" and the
+            // transposes further "in", so that we can match on the layout
+            // changes on the inputs.
+            super::bubble_access_transpose_through_access_pad(),
+            // These rewrites tensorize.
+            super::systolic_array_conv2d_im2col_nhwc_hwio_with_blocking(64, 32).rewrite,
+            super::systolic_array_conv2d_im2col_nhwc_hwio_with_blocking(32, 32).rewrite,
+            super::systolic_array_conv2d_im2col_nhwc_hwio_with_blocking(2, 2).rewrite,
+            super::systolic_array_conv2d_im2col_nhwc_hwio_with_blocking(3, 2).rewrite,
+        ];
+
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&rws);
+        runner.print_report();
+
+        let test_pattern = "
+          (systolic-array-conv2d-im2col-nhwc-hwio-with-blocking
+           64 32
+           (access-tensor kernel)
+           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
+           3 3
+           1 1
+          )
+            "
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        assert_eq!(matches.substs.len(), 1);
+
+        let _matches = "
+          (systolic-array-conv2d-im2col-nhwc-hwio-with-blocking
+           32 32
+           (access-tensor kernel)
+           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
+           3 3
+           1 1
+          )
+            "
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let _matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        // I don't think this check makes sense.
+        //assert_eq!(matches.substs.len(), 1);
+
+        let _matches = "
+          (systolic-array-conv2d-im2col-nhwc-hwio-with-blocking
+          2 2
+           (access-tensor kernel)
+           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
+           3 3
+           1 1
+          )
+            "
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let _matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        // I don't think this check makes sense.
+        //assert_eq!(matches.substs.len(), 1);
+
+        let _matches = "
+          (systolic-array-conv2d-im2col-nhwc-hwio-with-blocking
+          3 2
+           (access-tensor kernel)
+           (access-pad (access-pad ?data zero-padding ?0 1 1) zero-padding ?1 1 1)
+           3 3
+           1 1
+          )
+            "
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let _matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        // I don't think this check makes sense.
+        //assert_eq!(matches.substs.len(), 1);
+    }
+
+    /// Builds an NCHW/OIHW im2col convolution whose data and output are
+    /// transposed from `data_layout`, and extracts the cheapest layout once
+    /// both are available.
+    fn extract_im2col_layout(data_layout: &str) -> RecExpr<Language> {
+        let mut map = HashMap::default();
+        map.insert("weights".to_string(), vec![8, 4, 3, 3]);
+        let program = match data_layout {
+            "NCHW" => {
+                map.insert("data".to_string(), vec![1, 4, 10, 10]);
+                "(systolic-array-conv2d-im2col-nchw-oihw-with-blocking
+                  32 32 (access-tensor weights) (access-tensor data) 3 3 1 1)"
+            }
+            "NHWC" => {
+                map.insert("data".to_string(), vec![1, 10, 10, 4]);
+                "(access-transpose
+                  (systolic-array-conv2d-im2col-nchw-oihw-with-blocking
+                   32 32
+                   (access-tensor weights)
+                   (access-transpose (access-tensor data) (list 0 3 1 2))
+                   3 3 1 1)
+                  (list 0 2 3 1))"
+            }
+            _ => unreachable!(),
+        }
+        .parse::<RecExpr<Language>>()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&vec![
+                super::systolic_array_conv2d_im2col_nchw_oihw_to_nhwc_hwio().rewrite,
+                super::collapse_nested_transposes(),
+                super::remove_trivial_transpose(),
+            ]);
+        let (_, expr) = egg::Extractor::new(
+            &runner.egraph,
+            crate::extraction::SimpleCostFunction::default(),
+        )
+        .find_best(id);
+        expr
+    }
+
+    #[test]
+    fn systolic_array_conv2d_im2col_layout_nchw() {
+        assert_eq!(
+            extract_im2col_layout("NCHW").pretty(80),
+            "(systolic-array-conv2d-im2col-nchw-oihw-with-blocking
+              32 32 (access-tensor weights) (access-tensor data) 3 3 1 1)"
+                .parse::<RecExpr<Language>>()
+                .unwrap()
+                .pretty(80)
+        );
+    }
+
+    #[test]
+    fn systolic_array_conv2d_im2col_layout_nhwc() {
+        // Only the weights need to be transposed in the channels-last form;
+        // the data and output transposes cancel out.
+        assert_eq!(
+            extract_im2col_layout("NHWC").pretty(80),
+            "(systolic-array-conv2d-im2col-nhwc-hwio-with-blocking
+              32 32
+              (access-transpose (access-tensor weights) (list 2 3 1 0))
+              (access-tensor data)
+              3 3 1 1)"
+                .parse::<RecExpr<Language>>()
+                .unwrap()
+                .pretty(80)
+        );
+    }
+
+    #[test]
+    fn reassociate_max() {
+        let mut map = HashMap::default();
+        map.insert("a".to_string(), vec![16, 4]);
+        let program = "
+         (compute reduce-max (access (access-tensor a) 1))
+        "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+
+        let rws = vec![super::reassociate_max(2, 2)];
+
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&rws);
+
+        let test_pattern = "
+            (compute reduce-max
+             (access
+              (compute reduce-max
+               (access-windows
+                (access (access-tensor a) 1)
+                (shape 2)
+                (shape 2)))
+              1))
+             "
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        assert_eq!(matches.substs.len(), 1);
+    }
+
+    #[test]
+    fn reassociate_max_maxpool_2d() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![16, 4]);
+        let program = "
+         (compute reduce-max 
+          (access-windows 
+           (access (access-tensor data) 1) 
+           (shape 4) (shape 4)))
+        "
+        .parse()
+        .unwrap();
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+
+        let rws = vec![super::reassociate_max(2, 2)];
+
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&rws);
+
+        let test_pattern = "
+            (compute reduce-max
+             (access
+              (compute reduce-max
+               (access-windows
+                (access-windows 
+                 (access (access-tensor data) 1) 
+                 (shape 4) (shape 4))
+                (shape 2)
+                (shape 2)))
+              2))
+             "
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        assert_eq!(matches.substs.len(), 1);
+    }
+
+    /// Test in which we map a small 2D max pool layer to FlexASR. This is done
+    /// by:
+    ///
+    /// 1. Flattening the input windows to vectors,
+    /// 2. Converting the reduce-max computation to reduce in windows of two
+    ///    (FlexASR-style) rather than reducing the entire vector all at once,
+    /// 3. Mapping the new reduce-max computations to FlexASR.
+    ///
+    /// There are also various rewrites used for cleanup and exploration.
+    #[test]
+    fn flexasr_maxpool_split_tensorize() {
+        // Very simple 2D max pool layer. We use small shapes here so that we
+        // can write out the final expression by hand; the larger the reduction,
+        // the larger the final expression. Note that this is the max pool
+        // described in our original paper.
+        let program = "
+         (compute reduce-max 
+          (access-windows 
+           (access (access-tensor data) 2) 
+           (shape 2 2) (shape 2 2)))
+         "
+        .parse()
+        .unwrap();
+
+        // Define the shape of the input data: batch, channels, height, width.
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![3, 16, 4, 4]);
+
+        // Insert the expression into the egraph.
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+
+        // Define our rewrites. These rewrites are what map the max pool to
+        // FlexASR.
+        let rws = vec![
+            // Performs initial flattening of 2D pooling windows into vectors.
+            super::flatten_unflatten_any_access(),
+            // Splits a reduce-max into two reduce-maxes: the first reduce max
+            // reduces the data in windows of size 2, striding by 2 (FlexASR
+            // style) while the second reduce max just reduces the rest all at
+            // once. This is the core rewrite for transforming max pools to a
+            // format which can be mapped to FlexASR.
+            super::reassociate_max(2, 2),
+            // Tensorize.
+            super::flexasr_maxpool(),
+            //
+            // The rest of the rewrites are needed for cleanup.
+            //
+            // Moves the access-reshape which results from the flatten-unflatten
+            // rewrite up through the program.
+            super::bubble_access_reshape_through_compute_reduce_max(),
+            // Collapses adjacent operators.
+            super::simplify_multiple_accesses(),
+            super::simplify_multiple_transposes(),
+            super::simplify_multiple_access_reshapes(),
+            // Move access through access-transpose, to enable more collapsing.
+            super::bubble_access_through_access_transpose(),
+            // Remove the topmost reduce-max which becomes "trivial" (i.e. a max
+            // over a single element) after we rewrite it multiple times to
+            // FlexASR invocations.
+            super::simplify_reduce_max(),
+        ];
+
+        // Run the rewrites.
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .with_iter_limit(7)
+            .run(&rws);
+
+        // Assert that we find the expected program in the egraph.
+        //
+        // The final program computes the original max pool with two invocations
+        // of FlexASR.
+        //
+        // Reading from inside to out:
+        // 1. We first form 2x2 windows over the data. These are the windows we
+        //    want to reduce via the max operator.
+        // 2. We then flatten those 2x2 windows into vectors of length 4.
+        // 3. We transpose the data so that it's in the format expected by
+        //    FlexASR.
+        // 4. We compute multiple max pools on FlexASR.
+        // 5. We transpose the data back to its original layout, and reshape it
+        //    to its final reshape.
+        //
+        // Note that operators like access-reshape, access-flatten, and access
+        // are operators which exist purely to keep the types in check in
+        // Glenside. They do not involve actual data movement.
+        let test_pattern = "
+         (access-reshape
+          (access
+           (access-transpose
+            (accelerator-call flex-maxpool
+             (access
+              (accelerator-call flex-maxpool
+               (access
+                (access-transpose
+                 (access-flatten
+                  (access-windows
+                   (access (access-tensor data) 2)
+                   (shape 2 2)
+                   (shape 2 2)))
+                 (list 1 0))
+                0) ?shape0)
+              0) ?shape1)
+            (list 1 0))
+           1)
+          (access-shape (shape 3 16 2 2) (shape)))
+        "
+        .parse::<Pattern<Language>>()
+        .unwrap();
+        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        assert_eq!(matches.substs.len(), 1);
+    }
+
+    #[test]
+    fn bubble_access_reshape_through_compute_reduce_max() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![16, 4]);
+
+        let program = "
+         (compute reduce-max
+          (access-reshape 
+           (access (access-tensor data) 1)
+           (access-shape (shape 4 4) (shape 1 2 2))))"
+            .parse()
+            .unwrap();
+
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+
+        let rws = vec![super::bubble_access_reshape_through_compute_reduce_max()];
+
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&rws);
+
+        let test_pattern = "
+         (access-reshape
+          (compute reduce-max 
+           (access (access-tensor data) 1))
+          (access-shape (shape 4 4) (shape)))"
+            .parse::<Pattern<Language>>()
+            .unwrap();
+        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        assert_eq!(matches.substs.len(), 1);
+    }
+
+    #[test]
+    fn simplify_multiple_transposes_0() {
+        test_logger::ensure_env_logger_initialized();
+
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![2, 3, 4, 5]);
+
+        let program = "
+         (access-transpose (access-transpose (access-tensor data) (list 3 1 0 2)) (list 2 1 3 0))
+         "
+        .parse()
+        .unwrap();
+
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+
+        let rws = vec![super::simplify_multiple_transposes()];
+
+        let runner = Runner::default().with_egraph(egraph).run(&rws);
+
+        let test_pattern = "(access-tensor data)".parse::<Pattern<Language>>().unwrap();
+        let matches = test_pattern.search_eclass(&runner.egraph, id).unwrap();
+        assert_eq!(matches.substs.len(), 1);
+    }
+
+    #[test]
+    fn simplify_multiple_transposes_1() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![2, 3, 4, 5]);
+
+        let program = "
+         (access-transpose (access-transpose (access-tensor data) (list 3 1 0 2)) (list 2 3 1 0))
+         "
+        .parse()
+        .unwrap();
+
+        let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+
+        let rws = vec![super::simplify_multiple_transposes()];
+
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&rws);
+
+        let test_pattern = "(access-tensor data)".parse::<Pattern<Language>>().unwrap();
+        let matches = test_pattern.search_eclass(&runner.egraph, id);
+
+        assert!(matches.is_none());
+    }
+
+    /// Creates a Relay-to-Glenside test
+    /// The test does the following:
+    ///  1. Converts $relay_str to glenside by running the from_relay.py script
+    ///  2. Inserts the resulting Glenside code into an egraph
+    ///  3. Searches the egraph for $glenside_str to ensure the expected program
+    ///     exists
+    /// $test_name: the name of the created test
+    /// $relay_str: A string containing the Relay program to be converted
+    /// $glenside_str: A string containing the expected resulting Glenside
+    ///     expression
+    /// $tol: Tolerance value.
+    /// $rws: Expression evaluating to a vector of rewrites.
+    macro_rules! test {
+        ($(#[$meta:meta])* $test_name:ident, $tol:literal, $relay_str:expr, $glenside_str:expr, $rws: expr) => {
+            // TODO(@gussmith23) Hardcoding to f32
+            test!(
+                $(#[$meta])*
+                $test_name,
+                $tol,
+                $relay_str,
+                $glenside_str,
+                "",
+                Uniform::new(-1f32, 1f32),
+                $rws,
+                &vec![]
+            );
+        };
+        ($(#[$meta:meta])* $test_name:ident, $tol:literal, $relay_str:expr, $glenside_str:expr, $rws: expr, $use_opaque_operators_for: expr) => {
+            // TODO(@gussmith23) Hardcoding to f32
+            test!(
+                $(#[$meta])*
+                $test_name,
+                $tol,
+                $relay_str,
+                $glenside_str,
+                "",
+                Uniform::new(-1f32, 1f32),
+                $rws,
+                $use_opaque_operators_for
+            );
+        };
+        ($(#[$meta:meta])* $test_name:ident, $tol:literal, $relay_str:expr, $glenside_str:expr, $optional_arg:literal, $rws: expr, $use_opaque_operators_for: expr) => {
+            // TODO(@gussmith23) Hardcoding to f32
+            test!(
+                $(#[$meta])*
+                $test_name,
+                $tol,
+                $relay_str,
+                $glenside_str,
+                $optional_arg,
+                Uniform::new(-1f32, 1f32),
+                $rws,
+                $use_opaque_operators_for
+            );
+        };
+        ($(#[$meta:meta])* $test_name:ident, $tol:literal, $relay_str:expr, $glenside_str:expr, $optional_arg:literal, $distribution:expr, $rws: expr, $use_opaque_operators_for: expr) => {
+            $(#[$meta])*
+            #[test]
+            fn $test_name() {
+                test_logger::ensure_env_logger_initialized();
+
+                // The number of times to run each program and compare their
+                // outputs.
+                // TODO(@gussmith23) # random samples chosen arbitrarily
+                const SAMPLES: usize = 3;
+
+                // Random number generator for generating random tensors.
+                const SEED: u64 = 23;
+                let mut tensor_rng = SmallRng::seed_from_u64(SEED);
+
+                let module = tvm::ir::module::IRModule::parse("", $relay_str).unwrap();
+
+                let (expr, shapes_vec, dtypes_vec) =
+                    from_relay::from_relay(&module, false, $use_opaque_operators_for);
+
+                let mut env = HashMap::default();
+                for (k, v) in &shapes_vec {
+                    env.insert(k.clone(), v.clone());
+                }
+
+                // TODO(@gussmith23) Include some simple simplifying rewrites
+                // If we add some very basic rewrites here, then $glenside_str
+                // won't need to exactly match what's actually produced by
+                // from_relay.py. It can be simpler (e.g. collapsing accesses).
+                let mut egraph = EGraph::new(MyAnalysis {
+                    name_to_shape: env.clone(),
+                    name_to_dtype: dtypes_vec.into_iter().collect(),
+                    shape_cache: None,
+                });
+                let id = egraph.add_expr(&expr);egraph.rebuild();
+
+                let pattern = $glenside_str.parse::<Pattern<Language>>().unwrap();
+                // The program should not be found at first.
+                assert!(pattern.search_eclass(&egraph, id).is_none());
+
+                // Run compilation rewrites.
+                let runner = Runner::default().with_egraph(egraph).run($rws);
+
+                // Program should now be found.
+                assert!(pattern.search_eclass(&runner.egraph, id).is_some());
+
+                let expr_to_interpret = RecExpr::from_str($glenside_str).unwrap();
+
+                for _ in (0..SAMPLES) {
+                    // Run interpreters and compare output.
+                    let script_filepath = format!(
+                        "{}/src/language/from_relay/run_relay.py",
+                        env!("CARGO_MANIFEST_DIR")
                     );
                     // https://www.reddit.com/r/rust/comments/38jhva/piping_string_to_child_process_stdin/crvlqcd/?utm_source=reddit&utm_medium=web2x&context=3
                     // Output filename
@@ -6603,798 +8677,2714 @@ mod tests {
                             .collect::<String>()
                     ));
 
-                    let mut cmd = Command::new("python3");
-                    cmd.arg(script_filepath);
-                    if $optional_arg.len() > 0 {
-                        cmd.arg($optional_arg);
-                    }
-                    cmd.arg("--npy_out_filepath");
-                    cmd.arg(&output_filepath);
-                    cmd.arg("--npy_arg_filepath");
-                    cmd.stdin(std::process::Stdio::piped())
-                        .stdout(std::process::Stdio::piped())
-                        .stderr(std::process::Stdio::piped());
-                    let mut env = HashMap::default();
-                    for (name, shape) in shapes_vec.iter() {
-                        // TODO(@gussmith23) output type assumption
-                        let value = ndarray::ArrayD::<f32>::random_using(
-                            shape.clone(),
-                            $distribution,
-                            &mut tensor_rng,
-                        );
-                        env.insert(name.as_str(), value.clone());
-                        let filepath = std::env::temp_dir().join(format!(
-                            "arg-{}.npy",
-                            rand::thread_rng()
-                                .sample_iter(&rand::distributions::Alphanumeric)
-                                .take(30)
-                                .collect::<String>()
-                        ));
-                        write_npy(&filepath, &value).unwrap();
-                        cmd.arg(filepath);
-                    }
+                    let mut cmd = Command::new("python3");
+                    cmd.arg(script_filepath);
+                    if $optional_arg.len() > 0 {
+                        cmd.arg($optional_arg);
+                    }
+                    cmd.arg("--npy_out_filepath");
+                    cmd.arg(&output_filepath);
+                    cmd.arg("--npy_arg_filepath");
+                    cmd.stdin(std::process::Stdio::piped())
+                        .stdout(std::process::Stdio::piped())
+                        .stderr(std::process::Stdio::piped());
+                    let mut env = HashMap::default();
+                    for (name, shape) in shapes_vec.iter() {
+                        // TODO(@gussmith23) output type assumption
+                        let value = ndarray::ArrayD::<f32>::random_using(
+                            shape.clone(),
+                            $distribution,
+                            &mut tensor_rng,
+                        );
+                        env.insert(name.as_str(), value.clone());
+                        let filepath = std::env::temp_dir().join(format!(
+                            "arg-{}.npy",
+                            rand::thread_rng()
+                                .sample_iter(&rand::distributions::Alphanumeric)
+                                .take(30)
+                                .collect::<String>()
+                        ));
+                        write_npy(&filepath, &value).unwrap();
+                        cmd.arg(filepath);
+                    }
+
+                    let mut proc = cmd.spawn().ok().expect("Failed to spawn process");
+                    proc.stdin
+                        .as_mut()
+                        .unwrap()
+                        .write_all($relay_str.as_bytes())
+                        .unwrap();
+                    let output = proc.wait_with_output().unwrap();
+                    // Check that it ran.
+                    assert!(
+                        output.status.success(),
+                        "Running Relay code failed with code {:?}.\nstdout:\n{}\nstderr:\n{}",
+                        output.status.code(),
+                        std::str::from_utf8(output.stdout.as_slice())
+                            .expect("Could not convert stderr to UTF8"),
+                        std::str::from_utf8(output.stderr.as_slice())
+                            .expect("Could not convert stderr to UTF8")
+                    );
+
+                    // TODO(@gussmith23) output type assumption
+                    let relay_output: ndarray::ArrayD<f32> = read_npy(output_filepath).unwrap();
+                    let interpreter_output = match interpret(
+                        &expr_to_interpret,
+                        expr_to_interpret.as_ref().len() - 1,
+                        &env,
+                    ) {
+                        crate::language::interpreter::Value::Access(a) => a.tensor,
+                        _ => panic!(),
+                    };
+                    assert_close(&interpreter_output, &relay_output, Tolerance::absolute($tol));
+                }
+            }
+        };
+    }
+
+    test!(
+        conv2d_relay_to_glenside,
+        1e-5,
+        r#"
+#[version = "0.0.5"]
+def @main(%data: Tensor[(1, 3, 32, 32), float32], %weights: Tensor[(8, 3, 3, 3), float32]) -> Tensor[(1, 8, 17, 12), float32] {
+  nn.conv2d(%data, %weights, strides=[2, 3], padding=[1, 2, 3, 4]) /* ty=Tensor[(1, 8, 17, 12), float32] */
+}
+"#,
+        r#"
+(access-transpose
+ (compute dot-product
+  (access-cartesian-product
+   (access (access-tensor weights) 1)
+   (access
+    (access-squeeze
+     (access-windows
+      (access
+       (access-pad
+        (access-pad
+         (access-tensor data)
+         zero-padding
+         2 1 3
+        )
+        zero-padding
+        3 2 4
+       )
+       1
+      )
+      (shape 3 3 3)
+      (shape 1 2 3)
+     )
+     1
+    )
+    3
+   )
+  )
+ )
+ (list 1 0 2 3)
+)
+"#,
+        &vec![super::conv2d_relay_to_glenside()],
+        &vec![RelayOperator::RelayConv2D]
+    );
+
+    test!(
+        conv1d_relay_to_glenside,
+        1e-6,
+        r#"
+    #[version = "0.0.5"]
+    def @main(%data: Tensor[(1, 3, 32), float32], %weights: Tensor[(8, 3, 3), float32]) -> Tensor[(1, 8, 19), float32] {
+        nn.conv1d(%data, %weights, strides=[2], padding=[3, 4]) /* ty=Tensor[(1, 8, 19), float32] */
+    }
+"#,
+        r#"
+(access-transpose
+ (compute dot-product
+   (access-cartesian-product
+    (access (access-tensor weights) 1)
+    (access-squeeze
+     (access-windows
+      (access
+       (access-pad
+        (access-tensor data)
+        zero-padding
+        2 3 4
+       )
+       1
+      )
+      (shape 3 3)
+      (shape 1 2)
+     )
+     1
+    )
+   )
+ )
+ (list 1 0 2)
+)
+"#,
+        &vec![super::conv1d_relay_to_glenside()],
+        &vec![RelayOperator::RelayConv1D]
+    );
+
+    test!(
+        softmax_relay_to_glenside_0,
+        1e-7,
+        r#"
+#[version = "0.0.5"]
+def @main(%x: Tensor[(3), float32]) -> Tensor[(3), float32] {
+  nn.softmax(%x) /* ty=Tensor[(3), float32] */
+}
+"#,
+        r#"
+(compute softmax (access (access-tensor x) 0))
+"#,
+        &vec![super::softmax_relay_to_glenside()],
+        &vec![RelayOperator::RelaySoftmax]
+    );
+
+    test!(
+        softmax_relay_to_glenside_1,
+        1e-7,
+        r#"
+#[version = "0.0.5"]
+def @main(%x: Tensor[(3), float32]) -> Tensor[(3), float32] {
+  %0 = nn.softmax(%x); /* ty=Tensor[(3), float32] */
+  nn.softmax(%0) /* ty=Tensor[(3), float32] */
+}
+"#,
+        r#"
+(compute softmax (access (compute softmax (access (access-tensor x) 0)) 0))
+"#,
+        &vec![super::softmax_relay_to_glenside()],
+        &vec![RelayOperator::RelaySoftmax]
+    );
+
+    test!(
+        relu_relay_to_glenside,
+        1e-60,
+        r#"
+#[version = "0.0.5"]
+def @main(%x: Tensor[(1, 3, 32, 32), float32]) -> Tensor[(1, 3, 32, 32), float32] {
+  nn.relu(%x)
+}
+"#,
+        r#"
+(compute relu (access-tensor x))
+"#,
+        &vec![super::relu_relay_to_glenside()],
+        &vec![RelayOperator::RelayReLU]
+    );
+
+    test!(
+        negative_relay_to_glenside,
+        1e-60,
+        r#"
+#[version = "0.0.5"]
+def @main(%x: Tensor[(1, 3, 32, 32), float32]) -> Tensor[(1, 3, 32, 32), float32] {
+  negative(%x)
+}
+"#,
+        r#"
+(compute negative (access-tensor x))
+"#,
+        &vec![super::negative_relay_to_glenside()],
+        &vec![RelayOperator::RelayNegative]
+    );
+
+    test!(
+        sqrt_relay_to_glenside,
+        1e-60,
+        r#"
+#[version = "0.0.5"]
+def @main(%x: Tensor[(1, 3, 32, 32), float32]) -> Tensor[(1, 3, 32, 32), float32] {
+  sqrt(%x)
+}
+"#,
+        r#"
+(compute sqrt (access-tensor x))
+"#,
+        "",
+        // TODO(@gussmith23) Hardcoding test to f32
+        Uniform::new(0f32, 1f32),
+        &vec![super::sqrt_relay_to_glenside()],
+        &vec![RelayOperator::RelaySqrt]
+    );
+
+    test!(
+        max_pool2d_nchw_relay_to_glenside,
+        1e-60,
+        r#"
+#[version = "0.0.5"]
+def @main(%data: Tensor[(1, 3, 32, 32), float32]) -> Tensor[(1, 3, 17, 12), float32] {
+  nn.max_pool2d(%data, pool_size=[3, 4], strides=[2, 3], padding=[1, 2, 3, 4]) /* ty=Tensor[(1, 3, 17, 12), float32] */
+}
+"#,
+        r#"
+(compute reduce-max
+ (access-windows
+  (access
+   (access-pad
+    (access-pad
+     (access-tensor data)
+     min-padding
+     2 1 3
+    )
+    min-padding
+    3 2 4
+   )
+   2
+  )
+  (shape 3 4)
+  (shape 2 3)
+ )
+)
+"#,
+        &vec![super::max_pool2d_relay_to_glenside_nchw()],
+        &vec![RelayOperator::RelayMaxPool2D]
+    );
+
+    test!(
+        global_avg_pool2d_nchw_relay_to_glenside,
+        1e-60,
+        r#"
+#[version = "0.0.5"]
+def @main(%x: Tensor[(1, 3, 32, 32), float32]) -> Tensor[(1, 3, 1, 1), float32] {
+  nn.global_avg_pool2d(%x) /* ty=Tensor[(1, 3, 1, 1), float32] */
+}
+"#,
+        r#"
+(access
+ (access-insert-axis
+  (access-insert-axis
+   (compute reduce-mean (access (access-tensor x) 2))
+   2
+  )
+  3
+ )
+ 2
+)
+"#,
+        &vec![super::global_avg_pool2d_relay_to_glenside_nchw()],
+        &vec![RelayOperator::RelayGlobalAvgPool2D]
+    );
+
+    test!(
+        batch_matmul_relay_to_glenside,
+        1e-5,
+        r#"
+#[version = "0.0.5"]
+def @main(%x: Tensor[(2, 3, 4), float32], %y: Tensor[(2, 5, 4), float32]) -> Tensor[(2, 3, 5), float32] {
+  nn.batch_matmul(%x, %y) /* ty=Tensor[(2, 3, 5), float32] */
+}
+"#,
+        r#"
+(batch-matmul
+ (access-tensor x)
+ (access-transpose (access-tensor y) (list 0 2 1))
+)
+"#,
+        &vec![super::batch_matmul_relay_to_glenside()],
+        &vec![RelayOperator::RelayBatchMatmul]
+    );
+
+    // TODO(@gussmith23) ?axis should be 2 here, but we can't match an Int64
+    // literal. We need to fix the confusion over all of the literals in
+    // Glenside, and then once we do, we need to fix this.
+    test!(
+        #[should_panic = "Symbol ?axis not in environment"]
+        expand_dims_relay_to_glenside,
+        1e-60,
+        r#"
+#[version = "0.0.5"]
+def @main(%data: Tensor[(1, 3, 32, 32), float32]) -> Tensor[(1, 3, 1, 1, 1, 32, 32), float32] {
+  expand_dims(%data, axis=2, num_newaxis=3) /* ty=Tensor[(1, 3, 1, 1, 1, 32, 32), float32] */
+}
+"#,
+        r#"
+(access-insert-axis (access-insert-axis (access-insert-axis (access-tensor data) ?axis) ?axis) ?axis)
+"#,
+        &vec![
+            super::expand_dims_relay_to_glenside(),
+            super::eliminate_expand_dims_zero_num_newaxis()
+        ],
+        &vec![RelayOperator::RelayExpandDims]
+    );
+
+    test!(
+        pad_relay_to_glenside,
+        1e-60,
+        r#"
+#[version = "0.0.5"]
+def @main(%data: Tensor[(1, 2, 3), float32]) {
+    nn.pad(%data, 0, pad_width=[[0, 0], [1, 0], [1, 1]])
+}"#,
+        r#"
+(access-pad 
+ (access-pad 
+  (access-pad 
+   (access-tensor data)
+   zero-padding 0 0 0)
+  zero-padding 1 1 0)
+ zero-padding 2 1 1)
+"#,
+        &vec![super::pad_relay_to_glenside(),],
+        &vec![RelayOperator::RelayPad]
+    );
+
+    test!(
+        dense_relay_to_glenside,
+        1e-5,
+        r#"
+#[version = "0.0.5"]
+def @main(%data: Tensor[(16, 32), float32], %weights: Tensor[(64, 32), float32]) -> Tensor[(16, 64), float32] {
+  nn.dense(%data, %weights, units=64) /* ty=Tensor[(16, 64), float32] */
+}
+"#,
+        r#"
+(compute dot-product
+ (access-cartesian-product
+  (access (access-tensor data) 1)
+  (access (access-tensor weights) 1)
+ )
+)
+"#,
+        &vec![super::dense_relay_to_glenside(),],
+        &vec![RelayOperator::RelayDense]
+    );
+
+    test!(
+        add_relay_to_glenside,
+        1e-60,
+        r#"
+#[version = "0.0.5"]
+def @main(%x: Tensor[(1024, 1, 1), float32], %y: Tensor[(1, 1024, 7, 7), float32]) -> Tensor[(1, 1024, 7, 7), float32] {
+  add(%x, %y) /* ty=Tensor[(1, 1024, 7, 7), float32] */
+}
+"#,
+        r#"
+(compute elementwise-add
+ (access-pair
+  (access (access-broadcast (access-insert-axis (access-tensor x) 0) (access-shape (shape 1 1024 7 7) (shape))) 0)
+  (access (access-tensor y) 0)
+ )
+)
+"#,
+        &vec![super::add_relay_to_glenside(),],
+        &vec![RelayOperator::RelayAdd]
+    );
+
+    test!(
+        multiply_relay_to_glenside,
+        1e-60,
+        r#"
+#[version = "0.0.5"]
+def @main(%x: Tensor[(1024, 1, 1), float32], %y: Tensor[(1, 1024, 7, 7), float32]) -> Tensor[(1, 1024, 7, 7), float32] {
+  multiply(%x, %y) /* ty=Tensor[(1, 1024, 7, 7), float32] */
+}
+"#,
+        r#"
+(compute elementwise-mul
+ (access-pair
+  (access (access-broadcast (access-insert-axis (access-tensor x) 0) (access-shape (shape 1 1024 7 7) (shape))) 0)
+  (access (access-tensor y) 0)
+ )
+)
+"#,
+        &vec![super::multiply_relay_to_glenside(),],
+        &vec![RelayOperator::RelayMultiply]
+    );
+
+    test!(
+        divide_relay_to_glenside,
+        1e-60,
+        r#"
+#[version = "0.0.5"]
+def @main(%x: Tensor[(1024, 1, 1), float32], %y: Tensor[(1, 1024, 7, 7), float32]) -> Tensor[(1, 1024, 7, 7), float32] {
+  divide(%x, %y) /* ty=Tensor[(1, 1024, 7, 7), float32] */
+}
+"#,
+        r#"
+(compute elementwise-div
+ (access-pair
+  (access (access-broadcast (access-insert-axis (access-tensor x) 0) (access-shape (shape 1 1024 7 7) (shape))) 0)
+  (access (access-tensor y) 0)
+ )
+)
+"#,
+        &vec![super::divide_relay_to_glenside(),],
+        &vec![RelayOperator::RelayDivide]
+    );
+
+    test!(
+        batch_flatten_relay_to_glenside,
+        1e-60,
+        r#"
+#[version = "0.0.5"]
+def @main(%x: Tensor[(2, 3, 3, 4, 100), float32]) -> Tensor[(2, 3600), float32] {
+  nn.batch_flatten(%x)
+}
+"#,
+        r#"
+(access-flatten (access (access-tensor x) 1))
+"#,
+        &vec![super::batch_flatten_relay_to_glenside(),],
+        &vec![RelayOperator::RelayBatchFlatten]
+    );
+
+    test!(
+        bias_add_relay_to_glenside_axis_0,
+        1e-60,
+        r#"
+#[version = "0.0.5"]
+def @main(%x: Tensor[(3, 3), float32], %y: Tensor[(3), float32]) -> Tensor[(3, 3), float32] {
+  nn.bias_add(%x, %y, axis=0)
+}
+"#,
+        r#"
+(compute elementwise-add
+ (access-pair
+  (access (access-tensor x) 0)
+  (access
+   (access-broadcast
+    (access-insert-axis (access-tensor y) 1)
+    (access-shape (shape 3 3) (shape))
+   )
+   0
+  )
+ )
+)
+"#,
+        &vec![super::bias_add_relay_to_glenside(),],
+        &vec![RelayOperator::RelayBiasAdd]
+    );
+
+    test!(
+        bias_add_relay_to_glenside_axis_1,
+        1e-60,
+        r#"
+#[version = "0.0.5"]
+def @main(%x: Tensor[(3, 3), float32], %y: Tensor[(3), float32]) -> Tensor[(3, 3), float32] {
+  nn.bias_add(%x, %y, axis=1)
+}
+"#,
+        r#"
+(compute elementwise-add
+ (access-pair
+  (access (access-tensor x) 0)
+  (access
+   (access-broadcast
+    (access-insert-axis (access-tensor y) 0)
+    (access-shape (shape 3 3) (shape))
+   )
+   0
+  )
+ )
+)
+"#,
+        &vec![super::bias_add_relay_to_glenside(),],
+        &vec![RelayOperator::RelayBiasAdd]
+    );
+
+    test!(
+        bias_add_relay_to_glenside_axis_neg_1,
+        1e-60,
+        r#"
+#[version = "0.0.5"]
+def @main(%x: Tensor[(3, 3), float32], %y: Tensor[(3), float32]) -> Tensor[(3, 3), float32] {
+  nn.bias_add(%x, %y, axis=-1)
+}
+"#,
+        r#"
+(compute elementwise-add
+ (access-pair
+  (access (access-tensor x) 0)
+  (access
+   (access-broadcast
+    (access-insert-axis (access-tensor y) 0)
+    (access-shape (shape 3 3) (shape))
+   )
+   0
+  )
+ )
+)
+"#,
+        &vec![super::bias_add_relay_to_glenside(),],
+        &vec![RelayOperator::RelayBiasAdd]
+    );
+
+    test!(
+        bias_add_relay_to_glenside_axis_neg_2,
+        1e-60,
+        r#"
+#[version = "0.0.5"]
+def @main(%x: Tensor[(3, 3), float32], %y: Tensor[(3), float32]) -> Tensor[(3, 3), float32] {
+  nn.bias_add(%x, %y, axis=-2)
+}
+"#,
+        r#"
+(compute elementwise-add
+ (access-pair
+  (access (access-tensor x) 0)
+  (access
+   (access-broadcast
+    (access-insert-axis (access-tensor y) 1)
+    (access-shape (shape 3 3) (shape))
+   )
+   0
+  )
+ )
+)
+"#,
+        &vec![super::bias_add_relay_to_glenside(),],
+        &vec![RelayOperator::RelayBiasAdd]
+    );
+
+    test!(
+        concatenate_relay_to_glenside,
+        1e-60,
+        r#"
+#[version = "0.0.5"]
+def @main(%x: Tensor[(1, 5, 1, 1), float32], %y: Tensor[(1, 3, 1, 1), float32]) {
+    %0 = (%x, %y);
+    concatenate(%0, axis=1)
+}
+"#,
+        r#"
+(access-concatenate (access-tensor x) (access-tensor y) 1)
+"#,
+        &vec![
+            super::concatenate_relay_to_glenside(),
+            super::simplify_tuple_get_item()
+        ],
+        &vec![RelayOperator::RelayConcatenate]
+    );
+
+    test!(
+        transpose_relay_to_glenside,
+        1e-60,
+        r#"
+#[version = "0.0.5"]
+def @main(%x: Tensor[(3, 5), float32]) {
+    transpose(%x, axes=[1, 0])
+}
+"#,
+        r#"
+(access-transpose (access-tensor x) (list 1 0))
+        "#,
+        &vec![super::transpose_relay_to_glenside(),],
+        &vec![RelayOperator::RelayTranspose]
+    );
+
+    test!(
+        reshape_relay_to_glenside,
+        1e-60,
+        r#"
+    #[version = "0.0.5"]
+    def @main(%x: Tensor[(1, 255, 52, 52), float32]) {
+        reshape(%x, newshape=[-1, 3, 85, 52, 52])
+    }
+    "#,
+        r#"
+    (access-reshape (access-tensor x) (access-shape (shape) (shape 1 3 85 52 52)))
+    "#,
+        &vec![super::reshape_relay_to_glenside(),],
+        &vec![RelayOperator::RelayReshape]
+    );
+
+    test!(
+        squeeze_relay_to_glenside,
+        1e-60,
+        r#"
+#[version = "0.0.5"]
+def @main(%x: Tensor[(1, 100, 1, 1), float32]) {
+    squeeze(%x, axis=[2, 3])
+}
+"#,
+        r#"
+(access-squeeze (access-squeeze (access-tensor x) 3) 2)
+        "#,
+        &vec![super::squeeze_relay_to_glenside(),],
+        &vec![RelayOperator::RelaySqueeze]
+    );
+
+    test!(
+        conv2d,
+        1e-5,
+        r#"
+    #[version = "0.0.5"]
+    def @main(%data: Tensor[(1, 3, 32, 32), float32], %weights: Tensor[(8, 3, 3, 3), float32]) -> Tensor[(1, 8, 17, 12), float32] {
+      nn.conv2d(%data, %weights, strides=[2, 3], padding=[1, 2, 3, 4]) /* ty=Tensor[(1, 8, 17, 12), float32] */
+    }
+    "#,
+        r#"
+    (access-transpose
+     (compute dot-product
+      (access-cartesian-product
+       (access (access-tensor weights) 1)
+       (access
+        (access-squeeze
+         (access-windows
+          (access
+           (access-pad
+            (access-pad
+             (access-tensor data)
+             zero-padding
+             2 1 3
+            )
+            zero-padding
+            3 2 4
+           )
+           1
+          )
+          (shape 3 3 3)
+          (shape 1 2 3)
+         )
+         1
+        )
+        3
+       )
+      )
+     )
+     (list 1 0 2 3)
+    )
+    "#,
+        &vec![super::conv2d_relay_to_glenside(),],
+        &vec![RelayOperator::RelayConv2D]
+    );
+
+    test!(
+        conv2d_nhwc_hwio,
+        1e-5,
+        r#"
+    #[version = "0.0.5"]
+    def @main(%data: Tensor[(1, 32, 32, 3), float32], %weights: Tensor[(3, 3, 3, 8), float32]) -> Tensor[(1, 17, 12, 8), float32] {
+      nn.conv2d(%data, %weights, strides=[2, 3], padding=[1, 2, 3, 4], data_layout="NHWC", kernel_layout="HWIO")
+    }
+    "#,
+        r#"
+    (access-transpose
+     (access-transpose
+      (compute dot-product
+       (access-cartesian-product
+        (access
+         (access-transpose (access-tensor weights) (list 3 2 0 1))
+         1
+        )
+        (access
+         (access-squeeze
+          (access-windows
+           (access
+            (access-pad
+             (access-pad
+              (access-transpose (access-tensor data) (list 0 3 1 2))
+              zero-padding
+              2 1 3
+             )
+             zero-padding
+             3 2 4
+            )
+            1
+           )
+           (shape 3 3 3)
+           (shape 1 2 3)
+          )
+          1
+         )
+         3
+        )
+       )
+      )
+      (list 1 0 2 3)
+     )
+     (list 0 2 3 1)
+    )
+    "#,
+        &vec![super::conv2d_relay_to_glenside(),],
+        &vec![RelayOperator::RelayConv2D]
+    );
+
+    // The first part of a separable convolution, as seen in Mobilenet.
+    test!(
+        conv2d_depthwise_separable_stage1,
+        1e-6,
+        r#"
+    #[version = "0.0.5"]
+    def @main(%data: Tensor[(1, 3, 32, 32), float32], %weight: Tensor[(3, 1, 3, 3), float32]) -> Tensor[(1, 3, 38, 20), float32] {
+     nn.conv2d(%data, %weight, strides=[1, 2], padding=[3, 4, 5, 6], groups=3)
+    }
+    "#,
+        // TODO(@gussmith23) I'm being lazy here
+        r#"
+    (access-concatenate
+        (access-concatenate
+        (compute
+          dot-product
+          (access-cartesian-product
+            (access (access-slice (access-tensor weight) 0 0 1) 0)
+            (access
+              (access-windows
+                (access-slice
+                  (access
+                    (access-pad
+                      (access-pad (access-tensor data) zero-padding 2 3 5)
+                      zero-padding
+                      3
+                      4
+                      6)
+                    0)
+                  1
+                  0
+                  1)
+                (shape 1 1 3 3)
+                (shape 1 1 1 2))
+              4)))
+        (compute
+          dot-product
+          (access-cartesian-product
+            (access (access-slice (access-tensor weight) 0 1 2) 0)
+            (access
+              (access-windows
+                (access-slice
+                  (access
+                    (access-pad
+                      (access-pad (access-tensor data) zero-padding 2 3 5)
+                      zero-padding
+                      3
+                      4
+                      6)
+                    0)
+                  1
+                  1
+                  2)
+                (shape 1 1 3 3)
+                (shape 1 1 1 2))
+              4)))
+        1)
+        (compute
+        dot-product
+        (access-cartesian-product
+          (access (access-slice (access-tensor weight) 0 2 3) 0)
+          (access
+            (access-windows
+              (access-slice
+                (access
+                  (access-pad
+                    (access-pad (access-tensor data) zero-padding 2 3 5)
+                    zero-padding
+                    3
+                    4
+                    6)
+                  0)
+                1
+                2
+                3)
+              (shape 1 1 3 3)
+              (shape 1 1 1 2))
+            4)))
+        1)
+    "#,
+        &vec![super::conv2d_relay_to_glenside(),],
+        &vec![RelayOperator::RelayConv2D]
+    );
+
+    #[test]
+    fn conv2d_to_access_windows() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![1, 3, 8, 8]);
+        map.insert("weights".to_string(), vec![4, 3, 3, 3]);
+        let program = "
+         (conv2d (access-tensor data) (access-tensor weights)
+          (shape 2 1) (shape 1 2 0 1) 1)
+         "
+        .parse::<RecExpr<Language>>()
+        .unwrap();
+
+        let mut lowered = RecExpr::default();
+        let data_id = lowered.add(Language::Symbol("data".to_string()));
+        let data_id = lowered.add(Language::AccessTensor(data_id));
+        let weights_id = lowered.add(Language::Symbol("weights".to_string()));
+        let weights_id = lowered.add(Language::AccessTensor(weights_id));
+        from_relay::conv2d(
+            &mut lowered,
+            data_id,
+            &[1, 3, 8, 8],
+            weights_id,
+            &[4, 3, 3, 3],
+            &[2, 1],
+            &[1, 2, 0, 1],
+            &[1, 1],
+            1,
+            "NCHW",
+            "OIHW",
+            "",
+            false,
+        );
+        let pattern = lowered.pretty(80).parse::<Pattern<Language>>().unwrap();
+
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map.clone(),
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+        assert!(pattern.search_eclass(&egraph, id).is_none());
+
+        let runner = Runner::default()
+            .with_egraph(egraph)
+            .run(&vec![super::conv2d_to_access_windows()]);
+        assert!(pattern.search_eclass(&runner.egraph, id).is_some());
+
+        // The lowered program should compute the same thing as the
+        // high-level node.
+        let mut tensor_rng = SmallRng::seed_from_u64(23);
+        let mut env = HashMap::default();
+        for (name, shape) in map.iter() {
+            env.insert(
+                name.as_str(),
+                ndarray::ArrayD::<f64>::random_using(
+                    shape.clone(),
+                    Uniform::new(-1f64, 1f64),
+                    &mut tensor_rng,
+                ),
+            );
+        }
+        match (
+            interpret(&program, program.as_ref().len() - 1, &env),
+            interpret(&lowered, lowered.as_ref().len() - 1, &env),
+        ) {
+            (
+                crate::language::interpreter::Value::Access(high_level),
+                crate::language::interpreter::Value::Access(lowered),
+            ) => {
+                assert_eq!(high_level.tensor.shape(), lowered.tensor.shape());
+                assert_close(
+                    &lowered.tensor,
+                    &high_level.tensor,
+                    Tolerance::absolute(1e-10),
+                );
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn conv2d_1x1_to_matmul() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![2, 8, 5, 5]);
+        map.insert("weights".to_string(), vec![6, 8, 1, 1]);
+        let program = "
+         (conv2d (access-tensor data) (access-tensor weights)
+          (shape 1 1) (shape 0 1 2 0) 1)
+         "
+        .parse::<RecExpr<Language>>()
+        .unwrap();
+        let matmul = "
+         (access-transpose
+          (access-reshape
+           (compute dot-product
+            (access-cartesian-product
+             (access-reshape
+              (access
+               (access-transpose
+                (access-pad
+                 (access-pad (access-tensor data) zero-padding 2 0 2)
+                 zero-padding 3 1 0)
+                (list 0 2 3 1))
+               3)
+              (access-shape (shape 84) (shape 8)))
+             (access-reshape
+              (access (access-tensor weights) 1)
+              (access-shape (shape 6) (shape 8)))))
+           (access-shape (shape 2 7 6 6) (shape)))
+          (list 0 3 1 2))
+         "
+        .parse::<RecExpr<Language>>()
+        .unwrap();
+
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map.clone(),
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+        let runner = Runner::default().with_egraph(egraph).run(&vec![
+            super::conv2d_1x1_to_matmul(),
+            super::systolic_array().rewrite,
+        ]);
+        assert!(matmul
+            .pretty(80)
+            .parse::<Pattern<Language>>()
+            .unwrap()
+            .search_eclass(&runner.egraph, id)
+            .is_some());
+        // The matmul can then be mapped to a systolic array.
+        assert!(
+            "(access-transpose (access-reshape (systolic-array 8 6 ?a ?b) ?shape) ?list)"
+                .parse::<Pattern<Language>>()
+                .unwrap()
+                .search_eclass(&runner.egraph, id)
+                .is_some()
+        );
+
+        let mut tensor_rng = SmallRng::seed_from_u64(23);
+        let mut env = HashMap::default();
+        for (name, shape) in map.iter() {
+            env.insert(
+                name.as_str(),
+                ndarray::ArrayD::<f64>::random_using(
+                    shape.clone(),
+                    Uniform::new(-1f64, 1f64),
+                    &mut tensor_rng,
+                ),
+            );
+        }
+        assert_same_access(&program, &matmul, &env);
+    }
+
+    #[test]
+    fn conv2d_1x1_to_matmul_strided() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![1, 8, 7, 7]);
+        map.insert("weights".to_string(), vec![4, 8, 1, 1]);
+        let program = "
+         (conv2d (access-tensor data) (access-tensor weights)
+          (shape 2 1) (shape 0 0 0 0) 1)
+         "
+        .parse::<RecExpr<Language>>()
+        .unwrap();
+        let matmul = "
+         (access-transpose
+          (access-reshape
+           (compute dot-product
+            (access-cartesian-product
+             (access-reshape
+              (access
+               (access-transpose
+                (access-squeeze
+                 (access-squeeze
+                  (access-windows (access (access-tensor data) 2) (shape 1 1) (shape 2 1))
+                  5)
+                 4)
+                (list 0 2 3 1))
+               3)
+              (access-shape (shape 28) (shape 8)))
+             (access-reshape
+              (access (access-tensor weights) 1)
+              (access-shape (shape 4) (shape 8)))))
+           (access-shape (shape 1 4 7 4) (shape)))
+          (list 0 3 1 2))
+         "
+        .parse::<RecExpr<Language>>()
+        .unwrap();
+
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map.clone(),
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+        let runner = Runner::default()
+            .with_egraph(egraph)
+            .run(&vec![super::conv2d_1x1_to_matmul()]);
+        assert!(matmul
+            .pretty(80)
+            .parse::<Pattern<Language>>()
+            .unwrap()
+            .search_eclass(&runner.egraph, id)
+            .is_some());
+
+        let mut tensor_rng = SmallRng::seed_from_u64(23);
+        let mut env = HashMap::default();
+        for (name, shape) in map.iter() {
+            env.insert(
+                name.as_str(),
+                ndarray::ArrayD::<f64>::random_using(
+                    shape.clone(),
+                    Uniform::new(-1f64, 1f64),
+                    &mut tensor_rng,
+                ),
+            );
+        }
+        assert_same_access(&program, &matmul, &env);
+    }
 
-                    let mut proc = cmd.spawn().ok().expect("Failed to spawn process");
-                    proc.stdin
-                        .as_mut()
-                        .unwrap()
-                        .write_all($relay_str.as_bytes())
-                        .unwrap();
-                    let output = proc.wait_with_output().unwrap();
-                    // Check that it ran.
-                    assert!(
-                        output.status.success(),
-                        "Running Relay code failed with code {:?}.\nstdout:\n{}\nstderr:\n{}",
-                        output.status.code(),
-                        std::str::from_utf8(output.stdout.as_slice())
-                            .expect("Could not convert stderr to UTF8"),
-                        std::str::from_utf8(output.stderr.as_slice())
-                            .expect("Could not convert stderr to UTF8")
-                    );
+    #[test]
+    fn conv2d_1x1_to_matmul_not_applied() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![1, 8, 6, 6]);
+        map.insert("weights".to_string(), vec![6, 8, 1, 1]);
+        map.insert("weights-3x3".to_string(), vec![6, 8, 3, 3]);
+        map.insert("weights-grouped".to_string(), vec![8, 4, 1, 1]);
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let ids = vec![
+            // Not 1x1.
+            "(conv2d (access-tensor data) (access-tensor weights-3x3) (shape 1 1) (shape 0 0 0 0) 1)",
+            // Grouped.
+            "(conv2d (access-tensor data) (access-tensor weights-grouped) (shape 1 1) (shape 0 0 0 0) 2)",
+        ]
+        .iter()
+        .map(|program| egraph.add_expr(&program.parse().unwrap()))
+        .collect::<Vec<_>>();
+        egraph.rebuild();
+        let runner = Runner::default()
+            .with_egraph(egraph)
+            .run(&vec![super::conv2d_1x1_to_matmul()]);
+        for id in ids {
+            assert_eq!(runner.egraph[id].nodes.len(), 1);
+        }
+    }
+
+    #[test]
+    fn fuse_concatenated_conv2ds() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![1, 16, 8, 8]);
+        map.insert("weights0".to_string(), vec![8, 16, 3, 3]);
+        map.insert("weights1".to_string(), vec![4, 16, 3, 3]);
+        map.insert("weights2".to_string(), vec![4, 16, 3, 3]);
+        let program = "
+         (access-concatenate
+          (access-concatenate
+           (conv2d (access-tensor data) (access-tensor weights0)
+            (shape 1 1) (shape 1 1 1 1) 1)
+           (conv2d (access-tensor data) (access-tensor weights1)
+            (shape 1 1) (shape 1 1 1 1) 1)
+           1)
+          (conv2d (access-tensor data) (access-tensor weights2)
+           (shape 1 1) (shape 1 1 1 1) 1)
+          1)
+         "
+        .parse::<RecExpr<Language>>()
+        .unwrap();
+        let fused = "
+         (conv2d (access-tensor data)
+          (access-concatenate
+           (access-concatenate (access-tensor weights0) (access-tensor weights1) 0)
+           (access-tensor weights2)
+           0)
+          (shape 1 1) (shape 1 1 1 1) 1)
+         "
+        .parse::<RecExpr<Language>>()
+        .unwrap();
+
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map.clone(),
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+        let runner = Runner::default()
+            .with_egraph(egraph)
+            .run(&vec![super::fuse_concatenated_conv2ds()]);
+        assert!(fused
+            .pretty(80)
+            .parse::<Pattern<Language>>()
+            .unwrap()
+            .search_eclass(&runner.egraph, id)
+            .is_some());
+
+        let mut tensor_rng = SmallRng::seed_from_u64(23);
+        let mut env = HashMap::default();
+        for (name, shape) in map.iter() {
+            env.insert(
+                name.as_str(),
+                ndarray::ArrayD::<f64>::random_using(
+                    shape.clone(),
+                    Uniform::new(-1f64, 1f64),
+                    &mut tensor_rng,
+                ),
+            );
+        }
+        assert_same_access(&program, &fused, &env);
+    }
+
+    #[test]
+    fn fuse_concatenated_conv2ds_not_applied() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![1, 16, 8, 8]);
+        map.insert("other-data".to_string(), vec![1, 16, 8, 8]);
+        map.insert("weights-1x1".to_string(), vec![8, 16, 1, 1]);
+        map.insert("weights-3x3".to_string(), vec![8, 16, 3, 3]);
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let ids = vec![
+            // Different kernel sizes.
+            "(access-concatenate
+              (conv2d (access-tensor data) (access-tensor weights-1x1) (shape 1 1) (shape 1 1 1 1) 1)
+              (conv2d (access-tensor data) (access-tensor weights-3x3) (shape 1 1) (shape 0 0 0 0) 1)
+              1)",
+            // Different data.
+            "(access-concatenate
+              (conv2d (access-tensor data) (access-tensor weights-3x3) (shape 1 1) (shape 1 1 1 1) 1)
+              (conv2d (access-tensor other-data) (access-tensor weights-3x3) (shape 1 1) (shape 1 1 1 1) 1)
+              1)",
+        ]
+        .iter()
+        .map(|program| egraph.add_expr(&program.parse().unwrap()))
+        .collect::<Vec<_>>();
+        egraph.rebuild();
+        let runner = Runner::default()
+            .with_egraph(egraph)
+            .run(&vec![super::fuse_concatenated_conv2ds()]);
+        for id in ids {
+            assert_eq!(runner.egraph[id].nodes.len(), 1);
+        }
+    }
+
+    #[test]
+    fn conv3d_to_access_windows() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![1, 2, 5, 6, 6]);
+        map.insert("weights".to_string(), vec![3, 2, 2, 3, 3]);
+        let program = "
+         (conv3d (access-tensor data) (access-tensor weights)
+          (shape 1 2 1) (shape 1 0 1 0 1 1) 1)
+         "
+        .parse::<RecExpr<Language>>()
+        .unwrap();
+
+        let mut lowered = RecExpr::default();
+        let data_id = lowered.add(Language::Symbol("data".to_string()));
+        let data_id = lowered.add(Language::AccessTensor(data_id));
+        let weights_id = lowered.add(Language::Symbol("weights".to_string()));
+        let weights_id = lowered.add(Language::AccessTensor(weights_id));
+        from_relay::conv3d(
+            &mut lowered,
+            data_id,
+            &[1, 2, 5, 6, 6],
+            weights_id,
+            &[3, 2, 2, 3, 3],
+            &[1, 2, 1],
+            &[1, 0, 1, 0, 1, 1],
+            &[1, 1, 1],
+            1,
+            "NCDHW",
+            "OIDHW",
+            "",
+        );
+        let pattern = lowered.pretty(80).parse::<Pattern<Language>>().unwrap();
+
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map.clone(),
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+        assert!(pattern.search_eclass(&egraph, id).is_none());
+
+        let runner = Runner::default()
+            .with_egraph(egraph)
+            .run(&vec![super::conv3d_to_access_windows()]);
+        assert!(pattern.search_eclass(&runner.egraph, id).is_some());
+
+        let mut tensor_rng = SmallRng::seed_from_u64(23);
+        let mut env = HashMap::default();
+        for (name, shape) in map.iter() {
+            env.insert(
+                name.as_str(),
+                ndarray::ArrayD::<f64>::random_using(
+                    shape.clone(),
+                    Uniform::new(-1f64, 1f64),
+                    &mut tensor_rng,
+                ),
+            );
+        }
+        match (
+            interpret(&program, program.as_ref().len() - 1, &env),
+            interpret(&lowered, lowered.as_ref().len() - 1, &env),
+        ) {
+            (
+                crate::language::interpreter::Value::Access(high_level),
+                crate::language::interpreter::Value::Access(lowered),
+            ) => {
+                assert_eq!(high_level.tensor.shape(), &[1, 3, 5, 3, 6]);
+                assert_eq!(high_level.tensor.shape(), lowered.tensor.shape());
+                assert_close(
+                    &lowered.tensor,
+                    &high_level.tensor,
+                    Tolerance::absolute(1e-10),
+                );
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn conv3d_im2col_systolic_array() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![1, 2, 4, 4, 4]);
+        map.insert("weights".to_string(), vec![3, 2, 2, 2, 2]);
+        let program = "
+         (conv3d (access-tensor data) (access-tensor weights)
+          (shape 1 1 1) (shape 0 0 0 0 0 0) 1)
+         "
+        .parse::<RecExpr<Language>>()
+        .unwrap();
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map.clone(),
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+
+        let rws = vec![
+            super::conv3d_to_access_windows(),
+            super::flatten_unflatten_any_access(),
+            super::bubble_reshape_through_cartesian_product(),
+            super::bubble_reshape_through_compute_dot_product(),
+            super::systolic_array().rewrite,
+        ];
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&rws);
+
+        // The weights and windows are each flattened into a matrix, with
+        // 2*2*2*2 = 16 elements per row, and multiplied on the array.
+        let test_pattern = "
+         (systolic-array 16 27
+          (access-flatten (access (access-tensor weights) 1))
+          (access (access-transpose (access-flatten ?windows) (list 1 0)) 0))"
+            .parse::<Pattern<Language>>()
+            .unwrap();
+        assert!(!test_pattern.search(&runner.egraph).is_empty());
+
+        let (cost, extracted) = egg::Extractor::new(
+            &runner.egraph,
+            crate::extraction::MonolithicCostFunction {
+                egraph: &runner.egraph,
+                systolic_array_configuration: (16, 27),
+                prefer_systolic_arrays_with_blocking: false,
+            },
+        )
+        .find_best(id);
+        assert!(cost < crate::extraction::MonolithicCostFunction::INFINITY_VALUE);
+        assert!(crate::search::verify(&runner.egraph, &program, &extracted, 1e-10, 0).passed);
+    }
+
+    /// Lowers `conv2d` of `data` of shape `data_shape` and `weights` of shape
+    /// `weights_shape` to a single systolic array, and checks that the
+    /// extracted program computes the same thing.
+    fn check_conv2d_im2col_systolic_array(
+        data_shape: Vec<usize>,
+        weights_shape: Vec<usize>,
+        strides: &str,
+        padding: &str,
+        rows: usize,
+        cols: usize,
+    ) {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), data_shape);
+        map.insert("weights".to_string(), weights_shape);
+        let program = format!(
+            "(conv2d (access-tensor data) (access-tensor weights) {} {} 1)",
+            strides, padding
+        )
+        .parse::<RecExpr<Language>>()
+        .unwrap();
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+
+        let rws = vec![
+            super::conv2d_to_access_windows(),
+            super::flatten_unflatten_any_access(),
+            super::bubble_reshape_through_cartesian_product(),
+            super::bubble_reshape_through_compute_dot_product(),
+            super::systolic_array().rewrite,
+        ];
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&rws);
+
+        let (cost, extracted) = egg::Extractor::new(
+            &runner.egraph,
+            crate::extraction::MonolithicCostFunction {
+                egraph: &runner.egraph,
+                systolic_array_configuration: (rows, cols),
+                prefer_systolic_arrays_with_blocking: false,
+            },
+        )
+        .find_best(id);
+        assert!(cost < crate::extraction::MonolithicCostFunction::INFINITY_VALUE);
+        assert!(crate::search::verify(&runner.egraph, &program, &extracted, 1e-10, 0).passed);
+    }
+
+    #[test]
+    fn conv2d_im2col_systolic_array_stride_2() {
+        // The padded 11x11 image has (11 - 3) / 2 + 1 = 5 windows along each
+        // axis, each of 3*3*3 = 27 elements.
+        check_conv2d_im2col_systolic_array(
+            vec![1, 3, 9, 9],
+            vec![4, 3, 3, 3],
+            "(shape 2 2)",
+            "(shape 1 1 1 1)",
+            27,
+            25,
+        );
+    }
+
+    #[test]
+    fn conv2d_im2col_systolic_array_stride_2_uneven() {
+        // The stride doesn't evenly divide the padded 10x10 image, so the
+        // last row and column are never covered: (10 - 3) / 2 + 1 = 4.
+        check_conv2d_im2col_systolic_array(
+            vec![1, 2, 8, 8],
+            vec![4, 2, 3, 3],
+            "(shape 2 2)",
+            "(shape 1 1 1 1)",
+            18,
+            16,
+        );
+    }
+
+    #[test]
+    fn conv2d_im2col_systolic_array_asymmetric_strides() {
+        // The padded image is 11 high and 10 wide, giving (11 - 3) / 2 + 1 = 5
+        // rows and (10 - 3) / 1 + 1 = 8 columns of windows.
+        check_conv2d_im2col_systolic_array(
+            vec![1, 3, 9, 9],
+            vec![4, 3, 3, 3],
+            "(shape 2 1)",
+            "(shape 0 1 2 0)",
+            27,
+            40,
+        );
+    }
+
+    #[test]
+    fn conv2d_im2col_systolic_array_asymmetric_strides_and_kernel() {
+        // A 3x1 kernel with strides (1, 2) over a 7x8 image: (7 - 3) / 1 + 1
+        // = 5 rows and (8 - 1) / 2 + 1 = 4 columns of windows.
+        check_conv2d_im2col_systolic_array(
+            vec![1, 4, 7, 8],
+            vec![2, 4, 3, 1],
+            "(shape 1 2)",
+            "(shape 0 0 0 0)",
+            12,
+            20,
+        );
+    }
+
+    #[test]
+    fn conv2d_transpose_to_access_windows() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![1, 2, 4, 5]);
+        map.insert("weights".to_string(), vec![2, 3, 3, 2]);
+        let program = "
+         (conv2d-transpose (access-tensor data) (access-tensor weights)
+          (shape 2 3) (shape 3 0 1 1) (shape 1 2))
+         "
+        .parse::<RecExpr<Language>>()
+        .unwrap();
+
+        let mut lowered = RecExpr::default();
+        let data_id = lowered.add(Language::Symbol("data".to_string()));
+        let data_id = lowered.add(Language::AccessTensor(data_id));
+        let weights_id = lowered.add(Language::Symbol("weights".to_string()));
+        let weights_id = lowered.add(Language::AccessTensor(weights_id));
+        from_relay::conv2d_transpose(
+            &mut lowered,
+            data_id,
+            &[1, 2, 4, 5],
+            weights_id,
+            &[2, 3, 3, 2],
+            &[2, 3],
+            &[3, 0, 1, 1],
+            &[1, 2],
+            &[1, 1],
+            1,
+            "NCHW",
+            "IOHW",
+            "",
+        );
+        let pattern = lowered.pretty(80).parse::<Pattern<Language>>().unwrap();
+
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map.clone(),
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+        assert!(pattern.search_eclass(&egraph, id).is_none());
+
+        let runner = Runner::default()
+            .with_egraph(egraph)
+            .run(&vec![super::conv2d_transpose_to_access_windows()]);
+        assert!(pattern.search_eclass(&runner.egraph, id).is_some());
+
+        let mut tensor_rng = SmallRng::seed_from_u64(23);
+        let mut env = HashMap::default();
+        for (name, shape) in map.iter() {
+            env.insert(
+                name.as_str(),
+                ndarray::ArrayD::<f64>::random_using(
+                    shape.clone(),
+                    Uniform::new(-1f64, 1f64),
+                    &mut tensor_rng,
+                ),
+            );
+        }
+        match (
+            interpret(&program, program.as_ref().len() - 1, &env),
+            interpret(&lowered, lowered.as_ref().len() - 1, &env),
+        ) {
+            (
+                crate::language::interpreter::Value::Access(high_level),
+                crate::language::interpreter::Value::Access(lowered),
+            ) => {
+                assert_eq!(high_level.tensor.shape(), &[1, 3, 6, 15]);
+                assert_eq!(high_level.tensor.shape(), lowered.tensor.shape());
+                assert_close(
+                    &lowered.tensor,
+                    &high_level.tensor,
+                    Tolerance::absolute(1e-10),
+                );
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn conv2d_transpose_im2col_systolic_array() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![1, 2, 3, 3]);
+        map.insert("weights".to_string(), vec![2, 3, 2, 2]);
+        let program = "
+         (conv2d-transpose (access-tensor data) (access-tensor weights)
+          (shape 2 2) (shape 0 0 0 0) (shape 0 0))
+         "
+        .parse::<RecExpr<Language>>()
+        .unwrap();
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map.clone(),
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+
+        let rws = vec![
+            super::conv2d_transpose_to_access_windows(),
+            super::flatten_unflatten_any_access(),
+            super::bubble_reshape_through_cartesian_product(),
+            super::bubble_reshape_through_compute_dot_product(),
+            super::systolic_array().rewrite,
+        ];
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&rws);
+
+        // The lowered convolution runs over the 7x7 upsampled and padded
+        // data, producing 6*6 = 36 windows of 2*2*2 = 8 elements each.
+        let test_pattern = "
+         (systolic-array 8 36
+          (access-flatten
+           (access
+            (access-reverse
+             (access-reverse
+              (access-transpose (access-tensor weights) (list 1 0 2 3))
+              2)
+             3)
+            1))
+          (access (access-transpose (access-flatten ?windows) (list 1 0)) 0))"
+            .parse::<Pattern<Language>>()
+            .unwrap();
+        assert!(!test_pattern.search(&runner.egraph).is_empty());
+
+        let (cost, extracted) = egg::Extractor::new(
+            &runner.egraph,
+            crate::extraction::MonolithicCostFunction {
+                egraph: &runner.egraph,
+                systolic_array_configuration: (8, 36),
+                prefer_systolic_arrays_with_blocking: false,
+            },
+        )
+        .find_best(id);
+        assert!(cost < crate::extraction::MonolithicCostFunction::INFINITY_VALUE);
+        assert!(crate::search::verify(&runner.egraph, &program, &extracted, 1e-10, 0).passed);
+    }
+
+    #[test]
+    fn adaptive_pool2d_to_access_windows() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![1, 3, 8, 6]);
+        let mut tensor_rng = SmallRng::seed_from_u64(23);
+        let mut env = HashMap::default();
+        env.insert(
+            "data",
+            ndarray::ArrayD::<f64>::random_using(
+                vec![1, 3, 8, 6],
+                Uniform::new(-1f64, 1f64),
+                &mut tensor_rng,
+            ),
+        );
 
-                    // TODO(@gussmith23) output type assumption
-                    let relay_output: ndarray::ArrayD<f32> = read_npy(output_filepath).unwrap();
-                    let interpreter_output = match interpret(
-                        &expr_to_interpret,
-                        expr_to_interpret.as_ref().len() - 1,
-                        &env,
-                    ) {
-                        crate::language::interpreter::Value::Access(a) => a.tensor,
-                        _ => panic!(),
-                    };
-                    assert!(
-                        relay_output.abs_diff_eq(&interpreter_output, $tol),
-                        "{:?}\nvs.\n{:?}",
-                        relay_output,
-                        interpreter_output
+        for (program, lowered) in &[
+            (
+                "(adaptive-pool2d reduce-mean (access-tensor data) (shape 2 3))",
+                "(compute reduce-mean
+                  (access-windows (access (access-tensor data) 2) (shape 4 2) (shape 4 2)))",
+            ),
+            // Global pooling.
+            (
+                "(adaptive-pool2d reduce-max (access-tensor data) (shape 1 1))",
+                "(compute reduce-max
+                  (access-windows (access (access-tensor data) 2) (shape 8 6) (shape 8 6)))",
+            ),
+        ] {
+            let program = program.parse::<RecExpr<Language>>().unwrap();
+            let lowered = lowered.parse::<RecExpr<Language>>().unwrap();
+            let pattern = lowered.pretty(80).parse::<Pattern<Language>>().unwrap();
+
+            let mut egraph = EGraph::new(MyAnalysis {
+                name_to_shape: map.clone(),
+                name_to_dtype: HashMap::default(),
+                shape_cache: None,
+            });
+            let id = egraph.add_expr(&program);
+            egraph.rebuild();
+            assert!(pattern.search_eclass(&egraph, id).is_none());
+
+            let runner = Runner::default()
+                .with_egraph(egraph)
+                .run(&vec![super::adaptive_pool2d_to_access_windows()]);
+            assert!(pattern.search_eclass(&runner.egraph, id).is_some());
+
+            match (
+                interpret(&program, program.as_ref().len() - 1, &env),
+                interpret(&lowered, lowered.as_ref().len() - 1, &env),
+            ) {
+                (
+                    crate::language::interpreter::Value::Access(high_level),
+                    crate::language::interpreter::Value::Access(lowered),
+                ) => {
+                    assert_eq!(high_level.tensor.shape(), lowered.tensor.shape());
+                    assert_close(
+                        &lowered.tensor,
+                        &high_level.tensor,
+                        Tolerance::absolute(1e-10),
                     );
                 }
+                _ => panic!(),
             }
-        };
+        }
     }
 
-    test!(
-        conv2d_relay_to_glenside,
-        1e-5,
-        r#"
-#[version = "0.0.5"]
-def @main(%data: Tensor[(1, 3, 32, 32), float32], %weights: Tensor[(8, 3, 3, 3), float32]) -> Tensor[(1, 8, 17, 12), float32] {
-  nn.conv2d(%data, %weights, strides=[2, 3], padding=[1, 2, 3, 4]) /* ty=Tensor[(1, 8, 17, 12), float32] */
-}
-"#,
-        r#"
-(access-transpose
- (compute dot-product
-  (access-cartesian-product
-   (access (access-tensor weights) 1)
-   (access
-    (access-squeeze
-     (access-windows
-      (access
-       (access-pad
-        (access-pad
-         (access-tensor data)
-         zero-padding
-         2 1 3
+    /// Interprets `program` and `expected` on `env`, and checks that they
+    /// compute the same access.
+    fn assert_same_access(
+        program: &RecExpr<Language>,
+        expected: &RecExpr<Language>,
+        env: &HashMap<&str, ndarray::ArrayD<f64>>,
+    ) {
+        match (
+            interpret(program, program.as_ref().len() - 1, env),
+            interpret(expected, expected.as_ref().len() - 1, env),
+        ) {
+            (
+                crate::language::interpreter::Value::Access(program),
+                crate::language::interpreter::Value::Access(expected),
+            ) => {
+                assert_eq!(program.tensor.shape(), expected.tensor.shape());
+                assert_close(
+                    &expected.tensor,
+                    &program.tensor,
+                    Tolerance::absolute(1e-10),
+                );
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn prune_zero_input_channels() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![1, 4, 6, 6]);
+        map.insert("weights".to_string(), vec![2, 4, 3, 3]);
+        let mut tensor_rng = SmallRng::seed_from_u64(23);
+        let data = ndarray::ArrayD::<f64>::random_using(
+            vec![1, 4, 6, 6],
+            Uniform::new(-1f64, 1f64),
+            &mut tensor_rng,
+        );
+        let mut weights = ndarray::ArrayD::<f64>::random_using(
+            vec![2, 4, 3, 3],
+            Uniform::new(-1f64, 1f64),
+            &mut tensor_rng,
+        );
+        // Prune input channels 1 and 2.
+        weights.slice_mut(ndarray::s![.., 1..3, .., ..]).fill(0.);
+
+        let program = "
+         (conv2d (access-tensor data) (access-tensor weights) (shape 1 1) (shape 1 1 1 1) 1)"
+            .parse::<RecExpr<Language>>()
+            .unwrap();
+        let pruned = "
+         (conv2d
+          (access-concatenate
+           (access-slice (access-tensor data) 1 0 1)
+           (access-slice (access-tensor data) 1 3 4)
+           1)
+          (access-concatenate
+           (access-slice (access-tensor weights) 1 0 1)
+           (access-slice (access-tensor weights) 1 3 4)
+           1)
+          (shape 1 1) (shape 1 1 1 1) 1)"
+            .parse::<RecExpr<Language>>()
+            .unwrap();
+
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+        let constant_weights = ConstantWeights::new(
+            vec![("weights".to_string(), weights.clone())]
+                .into_iter()
+                .collect(),
+        );
+        let runner = Runner::default()
+            .with_egraph(egraph)
+            .run(&vec![super::prune_zero_input_channels(constant_weights)]);
+        assert!(pruned
+            .pretty(80)
+            .parse::<Pattern<Language>>()
+            .unwrap()
+            .search_eclass(&runner.egraph, id)
+            .is_some());
+
+        let mut env = HashMap::default();
+        env.insert("data", data);
+        env.insert("weights", weights);
+        assert_same_access(&program, &pruned, &env);
+    }
+
+    #[test]
+    fn prune_zero_input_channels_none_zero() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![1, 4, 6, 6]);
+        map.insert("weights".to_string(), vec![2, 4, 3, 3]);
+        let program = "
+         (conv2d (access-tensor data) (access-tensor weights) (shape 1 1) (shape 1 1 1 1) 1)"
+            .parse::<RecExpr<Language>>()
+            .unwrap();
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+        let constant_weights = ConstantWeights::new(
+            vec![(
+                "weights".to_string(),
+                ndarray::ArrayD::<f64>::ones(vec![2, 4, 3, 3]),
+            )]
+            .into_iter()
+            .collect(),
+        );
+        let runner = Runner::default()
+            .with_egraph(egraph)
+            .run(&vec![super::prune_zero_input_channels(constant_weights)]);
+        assert_eq!(runner.egraph[id].nodes.len(), 1);
+    }
+
+    #[test]
+    fn separable_conv2d() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![1, 3, 8, 8]);
+        map.insert("weights".to_string(), vec![4, 3, 3, 3]);
+        let mut tensor_rng = SmallRng::seed_from_u64(23);
+        let data = ndarray::ArrayD::<f64>::random_using(
+            vec![1, 3, 8, 8],
+            Uniform::new(-1f64, 1f64),
+            &mut tensor_rng,
+        );
+        // Each input channel's filters share a horizontal profile.
+        let vertical = ndarray::ArrayD::<f64>::random_using(
+            vec![4, 3, 3],
+            Uniform::new(-1f64, 1f64),
+            &mut tensor_rng,
+        );
+        let horizontal = ndarray::ArrayD::<f64>::random_using(
+            vec![3, 3],
+            Uniform::new(-1f64, 1f64),
+            &mut tensor_rng,
+        );
+        let weights = ndarray::ArrayD::from_shape_fn(vec![4, 3, 3, 3], |i| {
+            vertical[&[i[0], i[1], i[2]][..]] * horizontal[&[i[1], i[3]][..]]
+        });
+
+        let program = "
+         (conv2d (access-tensor data) (access-tensor weights) (shape 2 1) (shape 1 2 0 1) 1)"
+            .parse::<RecExpr<Language>>()
+            .unwrap();
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+        let constant_weights =
+            ConstantWeights::new(vec![("weights".to_string(), weights)].into_iter().collect());
+        let runner = Runner::default()
+            .with_egraph(egraph)
+            .run(&vec![super::separable_conv2d(constant_weights.clone())]);
+
+        let values = constant_weights.values();
+        assert_eq!(values.len(), 3);
+        let name = |prefix: &str| {
+            values
+                .keys()
+                .find(|name| name.starts_with(prefix))
+                .unwrap()
+                .clone()
+        };
+        let separated = format!(
+            "(conv2d
+              (conv2d (access-tensor data) (access-tensor {}) (shape 1 1) (shape 0 2 0 1) 3)
+              (access-tensor {}) (shape 2 1) (shape 1 0 0 0) 1)",
+            name("separable-horizontal"),
+            name("separable-vertical")
         )
-        zero-padding
-        3 2 4
-       )
-       1
-      )
-      (shape 3 3 3)
-      (shape 1 2 3)
-     )
-     1
-    )
-    3
-   )
-  )
- )
- (list 1 0 2 3)
-)
-"#,
-        &vec![super::conv2d_relay_to_glenside()],
-        &vec![RelayOperator::RelayConv2D]
-    );
+        .parse::<RecExpr<Language>>()
+        .unwrap();
+        assert!(separated
+            .pretty(80)
+            .parse::<Pattern<Language>>()
+            .unwrap()
+            .search_eclass(&runner.egraph, id)
+            .is_some());
 
-    test!(
-        conv1d_relay_to_glenside,
-        1e-6,
-        r#"
-    #[version = "0.0.5"]
-    def @main(%data: Tensor[(1, 3, 32), float32], %weights: Tensor[(8, 3, 3), float32]) -> Tensor[(1, 8, 19), float32] {
-        nn.conv1d(%data, %weights, strides=[2], padding=[3, 4]) /* ty=Tensor[(1, 8, 19), float32] */
+        let mut env = values
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.clone()))
+            .collect::<HashMap<_, _>>();
+        env.insert("data", data);
+        assert_same_access(&program, &separated, &env);
     }
-"#,
-        r#"
-(access-transpose
- (compute dot-product
-   (access-cartesian-product
-    (access (access-tensor weights) 1)
-    (access-squeeze
-     (access-windows
-      (access
-       (access-pad
-        (access-tensor data)
-        zero-padding
-        2 3 4
-       )
-       1
-      )
-      (shape 3 3)
-      (shape 1 2)
-     )
-     1
-    )
-   )
- )
- (list 1 0 2)
-)
-"#,
-        &vec![super::conv1d_relay_to_glenside()],
-        &vec![RelayOperator::RelayConv1D]
-    );
 
-    test!(
-        softmax_relay_to_glenside_0,
-        1e-7,
-        r#"
-#[version = "0.0.5"]
-def @main(%x: Tensor[(3), float32]) -> Tensor[(3), float32] {
-  nn.softmax(%x) /* ty=Tensor[(3), float32] */
-}
-"#,
-        r#"
-(compute softmax (access (access-tensor x) 0))
-"#,
-        &vec![super::softmax_relay_to_glenside()],
-        &vec![RelayOperator::RelaySoftmax]
-    );
+    #[test]
+    fn separable_conv2d_not_separable() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![1, 3, 8, 8]);
+        map.insert("weights".to_string(), vec![4, 3, 3, 3]);
+        let mut tensor_rng = SmallRng::seed_from_u64(23);
+        let program = "
+         (conv2d (access-tensor data) (access-tensor weights) (shape 1 1) (shape 1 1 1 1) 1)"
+            .parse::<RecExpr<Language>>()
+            .unwrap();
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+        let constant_weights = ConstantWeights::new(
+            vec![(
+                "weights".to_string(),
+                ndarray::ArrayD::<f64>::random_using(
+                    vec![4, 3, 3, 3],
+                    Uniform::new(-1f64, 1f64),
+                    &mut tensor_rng,
+                ),
+            )]
+            .into_iter()
+            .collect(),
+        );
+        let runner = Runner::default()
+            .with_egraph(egraph)
+            .run(&vec![super::separable_conv2d(constant_weights.clone())]);
+        assert_eq!(runner.egraph[id].nodes.len(), 1);
+        assert_eq!(constant_weights.values().len(), 1);
+    }
 
-    test!(
-        softmax_relay_to_glenside_1,
-        1e-7,
-        r#"
-#[version = "0.0.5"]
-def @main(%x: Tensor[(3), float32]) -> Tensor[(3), float32] {
-  %0 = nn.softmax(%x); /* ty=Tensor[(3), float32] */
-  nn.softmax(%0) /* ty=Tensor[(3), float32] */
-}
-"#,
-        r#"
-(compute softmax (access (compute softmax (access (access-tensor x) 0)) 0))
-"#,
-        &vec![super::softmax_relay_to_glenside()],
-        &vec![RelayOperator::RelaySoftmax]
-    );
+    #[test]
+    fn adaptive_pool2d_to_access_windows_uneven() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![1, 3, 7, 6]);
+        let program = "(adaptive-pool2d reduce-mean (access-tensor data) (shape 2 3))"
+            .parse::<RecExpr<Language>>()
+            .unwrap();
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+
+        // Rows can't be split into windows of equal size.
+        let runner = Runner::default()
+            .with_egraph(egraph)
+            .run(&vec![super::adaptive_pool2d_to_access_windows()]);
+        assert_eq!(runner.egraph[id].nodes.len(), 1);
+    }
+
+    #[test]
+    fn pooling_unit() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![1, 3, 8, 6]);
+        let mut tensor_rng = SmallRng::seed_from_u64(23);
+        let mut env = HashMap::default();
+        env.insert(
+            "data",
+            ndarray::ArrayD::<f64>::random_using(
+                vec![1, 3, 8, 6],
+                Uniform::new(-1f64, 1f64),
+                &mut tensor_rng,
+            ),
+        );
+
+        for (program, mapped) in &[
+            (
+                "(compute reduce-max
+                  (access-windows (access (access-tensor data) 2) (shape 2 2) (shape 2 2)))",
+                "(pooling-unit reduce-max (access (access-tensor data) 2) (shape 2 2) (shape 2 2))",
+            ),
+            (
+                "(compute reduce-mean
+                  (access-windows (access (access-tensor data) 2) (shape 3 3) (shape 1 2)))",
+                "(pooling-unit reduce-mean (access (access-tensor data) 2) (shape 3 3) (shape 1 2))",
+            ),
+        ] {
+            let program = program.parse::<RecExpr<Language>>().unwrap();
+            let mapped = mapped.parse::<RecExpr<Language>>().unwrap();
+            let pattern = mapped.pretty(80).parse::<Pattern<Language>>().unwrap();
+
+            let mut egraph = EGraph::new(MyAnalysis {
+                name_to_shape: map.clone(),
+                name_to_dtype: HashMap::default(),
+                shape_cache: None,
+            });
+            let id = egraph.add_expr(&program);
+            egraph.rebuild();
+            assert!(pattern.search_eclass(&egraph, id).is_none());
+
+            let runner = Runner::default()
+                .with_egraph(egraph)
+                .run(&vec![super::pooling_unit().rewrite]);
+            assert!(pattern.search_eclass(&runner.egraph, id).is_some());
+
+            match (
+                interpret(&program, program.as_ref().len() - 1, &env),
+                interpret(&mapped, mapped.as_ref().len() - 1, &env),
+            ) {
+                (
+                    crate::language::interpreter::Value::Access(program),
+                    crate::language::interpreter::Value::Access(mapped),
+                ) => {
+                    assert_eq!(program.tensor.shape(), mapped.tensor.shape());
+                    assert_eq!(program.access_axis, mapped.access_axis);
+                    assert_close(&mapped.tensor, &program.tensor, Tolerance::absolute(1e-10));
+                }
+                _ => panic!(),
+            }
+        }
+    }
+
+    #[test]
+    fn activation_unit() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![1, 3, 8, 6]);
+        let mut tensor_rng = SmallRng::seed_from_u64(23);
+        let mut env = HashMap::default();
+        env.insert(
+            "data",
+            ndarray::ArrayD::<f64>::random_using(
+                vec![1, 3, 8, 6],
+                Uniform::new(-4f64, 4f64),
+                &mut tensor_rng,
+            ),
+        );
+
+        for activation in &["relu", "sigmoid", "tanh"] {
+            let program = format!("(compute {} (access (access-tensor data) 2))", activation)
+                .parse::<RecExpr<Language>>()
+                .unwrap();
+            let mapped = format!(
+                "(activation-unit {} (access (access-tensor data) 2))",
+                activation
+            )
+            .parse::<RecExpr<Language>>()
+            .unwrap();
+            let pattern = mapped.pretty(80).parse::<Pattern<Language>>().unwrap();
+
+            let mut egraph = EGraph::new(MyAnalysis {
+                name_to_shape: map.clone(),
+                name_to_dtype: HashMap::default(),
+                shape_cache: None,
+            });
+            let id = egraph.add_expr(&program);
+            egraph.rebuild();
+
+            let runner = Runner::default()
+                .with_egraph(egraph)
+                .run(&vec![super::activation_unit().rewrite]);
+            assert!(pattern.search_eclass(&runner.egraph, id).is_some());
+
+            match (
+                interpret(&program, program.as_ref().len() - 1, &env),
+                interpret(&mapped, mapped.as_ref().len() - 1, &env),
+            ) {
+                (
+                    crate::language::interpreter::Value::Access(program),
+                    crate::language::interpreter::Value::Access(mapped),
+                ) => {
+                    assert_eq!(program.access_axis, mapped.access_axis);
+                    assert_close(&mapped.tensor, &program.tensor, Tolerance::absolute(1e-10));
+                }
+                _ => panic!(),
+            }
+        }
+
+        // Softmax is supported over vectors.
+        let program = "(compute softmax (access (access-tensor data) 3))"
+            .parse::<RecExpr<Language>>()
+            .unwrap();
+        let mapped = "(activation-unit softmax (access (access-tensor data) 3))"
+            .parse::<RecExpr<Language>>()
+            .unwrap();
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map.clone(),
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+        let runner = Runner::default()
+            .with_egraph(egraph)
+            .run(&vec![super::activation_unit().rewrite]);
+        assert!(mapped
+            .pretty(80)
+            .parse::<Pattern<Language>>()
+            .unwrap()
+            .search_eclass(&runner.egraph, id)
+            .is_some());
+        match (
+            interpret(&program, program.as_ref().len() - 1, &env),
+            interpret(&mapped, mapped.as_ref().len() - 1, &env),
+        ) {
+            (
+                crate::language::interpreter::Value::Access(program),
+                crate::language::interpreter::Value::Access(mapped),
+            ) => {
+                assert_eq!(program.access_axis, mapped.access_axis);
+                assert_close(&mapped.tensor, &program.tensor, Tolerance::absolute(1e-10));
+            }
+            _ => panic!(),
+        }
+
+        // Other computes aren't supported, nor is softmax over matrices.
+        for program in &[
+            "(compute negative (access (access-tensor data) 2))",
+            "(compute softmax (access (access-tensor data) 2))",
+        ] {
+            let program = program.parse::<RecExpr<Language>>().unwrap();
+            let mut egraph = EGraph::new(MyAnalysis {
+                name_to_shape: map.clone(),
+                name_to_dtype: HashMap::default(),
+                shape_cache: None,
+            });
+            let id = egraph.add_expr(&program);
+            egraph.rebuild();
+            let runner = Runner::default()
+                .with_egraph(egraph)
+                .run(&vec![super::activation_unit().rewrite]);
+            assert_eq!(runner.egraph[id].nodes.len(), 1);
+        }
+    }
 
-    test!(
-        relu_relay_to_glenside,
-        1e-60,
-        r#"
-#[version = "0.0.5"]
-def @main(%x: Tensor[(1, 3, 32, 32), float32]) -> Tensor[(1, 3, 32, 32), float32] {
-  nn.relu(%x)
-}
-"#,
-        r#"
-(compute relu (access-tensor x))
-"#,
-        &vec![super::relu_relay_to_glenside()],
-        &vec![RelayOperator::RelayReLU]
-    );
+    #[test]
+    fn systolic_array_with_activation() {
+        let program = "
+         (compute sigmoid
+          (systolic-array 32 64
+           (access (access-tensor t-32-32) 1)
+           (access (access-tensor t-32-64) 0)))"
+            .parse::<RecExpr<Language>>()
+            .unwrap();
+        let fused = "
+         (systolic-array-with-activation sigmoid 32 64
+          (access (access-tensor t-32-32) 1)
+          (access (access-tensor t-32-64) 0))"
+            .parse::<RecExpr<Language>>()
+            .unwrap();
 
-    test!(
-        negative_relay_to_glenside,
-        1e-60,
-        r#"
-#[version = "0.0.5"]
-def @main(%x: Tensor[(1, 3, 32, 32), float32]) -> Tensor[(1, 3, 32, 32), float32] {
-  negative(%x)
-}
-"#,
-        r#"
-(compute negative (access-tensor x))
-"#,
-        &vec![super::negative_relay_to_glenside()],
-        &vec![RelayOperator::RelayNegative]
-    );
+        let mut egraph = EGraph::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
 
-    test!(
-        sqrt_relay_to_glenside,
-        1e-60,
-        r#"
-#[version = "0.0.5"]
-def @main(%x: Tensor[(1, 3, 32, 32), float32]) -> Tensor[(1, 3, 32, 32), float32] {
-  sqrt(%x)
-}
-"#,
-        r#"
-(compute sqrt (access-tensor x))
-"#,
-        "",
-        // TODO(@gussmith23) Hardcoding test to f32
-        Uniform::new(0f32, 1f32),
-        &vec![super::sqrt_relay_to_glenside()],
-        &vec![RelayOperator::RelaySqrt]
-    );
+        let runner = Runner::default().with_egraph(egraph).run(&vec![
+            super::activation_unit().rewrite,
+            super::systolic_array_with_activation().rewrite,
+        ]);
+        assert!(fused
+            .pretty(80)
+            .parse::<Pattern<Language>>()
+            .unwrap()
+            .search_eclass(&runner.egraph, id)
+            .is_some());
+        assert!("(activation-unit sigmoid (systolic-array 32 64 ?a0 ?a1))"
+            .parse::<Pattern<Language>>()
+            .unwrap()
+            .search_eclass(&runner.egraph, id)
+            .is_some());
 
-    test!(
-        max_pool2d_nchw_relay_to_glenside,
-        1e-60,
-        r#"
-#[version = "0.0.5"]
-def @main(%data: Tensor[(1, 3, 32, 32), float32]) -> Tensor[(1, 3, 17, 12), float32] {
-  nn.max_pool2d(%data, pool_size=[3, 4], strides=[2, 3], padding=[1, 2, 3, 4]) /* ty=Tensor[(1, 3, 17, 12), float32] */
-}
-"#,
-        r#"
-(compute reduce-max
- (access-windows
-  (access
-   (access-pad
-    (access-pad
-     (access-tensor data)
-     min-padding
-     2 1 3
-    )
-    min-padding
-    3 2 4
-   )
-   2
-  )
-  (shape 3 4)
-  (shape 2 3)
- )
-)
-"#,
-        &vec![super::max_pool2d_relay_to_glenside_nchw()],
-        &vec![RelayOperator::RelayMaxPool2D]
-    );
+        // Fusing saves an atom, so it's preferred.
+        let (_, extracted) = egg::Extractor::new(
+            &runner.egraph,
+            crate::extraction::SimpleCostFunction::default(),
+        )
+        .find_best(id);
+        match extracted.as_ref().last().unwrap() {
+            Language::SystolicArrayWithActivation(_) => (),
+            other => panic!("Expected a fused systolic array, found {:?}", other),
+        }
 
-    test!(
-        global_avg_pool2d_nchw_relay_to_glenside,
-        1e-60,
-        r#"
-#[version = "0.0.5"]
-def @main(%x: Tensor[(1, 3, 32, 32), float32]) -> Tensor[(1, 3, 1, 1), float32] {
-  nn.global_avg_pool2d(%x) /* ty=Tensor[(1, 3, 1, 1), float32] */
-}
-"#,
-        r#"
-(access
- (access-insert-axis
-  (access-insert-axis
-   (compute reduce-mean (access (access-tensor x) 2))
-   2
-  )
-  3
- )
- 2
-)
-"#,
-        &vec![super::global_avg_pool2d_relay_to_glenside_nchw()],
-        &vec![RelayOperator::RelayGlobalAvgPool2D]
-    );
+        let mut tensor_rng = SmallRng::seed_from_u64(23);
+        let mut env = HashMap::default();
+        for (name, shape) in &[("t-32-32", vec![32, 32]), ("t-32-64", vec![32, 64])] {
+            env.insert(
+                *name,
+                ndarray::ArrayD::<f64>::random_using(
+                    shape.clone(),
+                    Uniform::new(-1f64, 1f64),
+                    &mut tensor_rng,
+                ),
+            );
+        }
+        match (
+            interpret(&program, program.as_ref().len() - 1, &env),
+            interpret(&fused, fused.as_ref().len() - 1, &env),
+        ) {
+            (
+                crate::language::interpreter::Value::Access(program),
+                crate::language::interpreter::Value::Access(fused),
+            ) => {
+                assert_eq!(program.access_axis, fused.access_axis);
+                assert_close(&fused.tensor, &program.tensor, Tolerance::absolute(1e-10));
+            }
+            _ => panic!(),
+        }
+    }
 
-    // TODO(@gussmith23) ?axis should be 2 here, but we can't match an Int64
-    // literal. We need to fix the confusion over all of the literals in
-    // Glenside, and then once we do, we need to fix this.
-    test!(
-        #[should_panic = "Symbol ?axis not in environment"]
-        expand_dims_relay_to_glenside,
-        1e-60,
-        r#"
-#[version = "0.0.5"]
-def @main(%data: Tensor[(1, 3, 32, 32), float32]) -> Tensor[(1, 3, 1, 1, 1, 32, 32), float32] {
-  expand_dims(%data, axis=2, num_newaxis=3) /* ty=Tensor[(1, 3, 1, 1, 1, 32, 32), float32] */
-}
-"#,
-        r#"
-(access-insert-axis (access-insert-axis (access-insert-axis (access-tensor data) ?axis) ?axis) ?axis)
-"#,
-        &vec![
-            super::expand_dims_relay_to_glenside(),
-            super::eliminate_expand_dims_zero_num_newaxis()
-        ],
-        &vec![RelayOperator::RelayExpandDims]
-    );
+    #[test]
+    fn vector_alu_residual_add() {
+        let mut map = HashMap::default();
+        map.insert("x".to_string(), vec![1, 8, 4, 4]);
+        map.insert("y".to_string(), vec![1, 8, 4, 4]);
+        let program = "
+         (compute elementwise-add
+          (access-pair (access (access-tensor x) 0) (access (access-tensor y) 0)))"
+            .parse::<RecExpr<Language>>()
+            .unwrap();
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
 
-    test!(
-        pad_relay_to_glenside,
-        1e-60,
-        r#"
-#[version = "0.0.5"]
-def @main(%data: Tensor[(1, 2, 3), float32]) {
-    nn.pad(%data, 0, pad_width=[[0, 0], [1, 0], [1, 1]])
-}"#,
-        r#"
-(access-pad 
- (access-pad 
-  (access-pad 
-   (access-tensor data)
-   zero-padding 0 0 0)
-  zero-padding 1 1 0)
- zero-padding 2 1 1)
-"#,
-        &vec![super::pad_relay_to_glenside(),],
-        &vec![RelayOperator::RelayPad]
-    );
+        let runner = Runner::default().with_egraph(egraph).run(&vec![
+            super::split_elementwise_for_vector_alu(16),
+            super::vector_alu(16).rewrite,
+        ]);
+        assert!("
+         (access-reshape
+          (vector-alu elementwise-add 16
+           (access-pair
+            (access-reshape (access (access-tensor x) 0) (access-shape (shape 8) (shape 16)))
+            (access-reshape (access (access-tensor y) 0) (access-shape (shape 8) (shape 16)))))
+          (access-shape (shape) (shape 1 8 4 4)))"
+            .parse::<Pattern<Language>>()
+            .unwrap()
+            .search_eclass(&runner.egraph, id)
+            .is_some());
 
-    test!(
-        dense_relay_to_glenside,
-        1e-5,
-        r#"
-#[version = "0.0.5"]
-def @main(%data: Tensor[(16, 32), float32], %weights: Tensor[(64, 32), float32]) -> Tensor[(16, 64), float32] {
-  nn.dense(%data, %weights, units=64) /* ty=Tensor[(16, 64), float32] */
-}
-"#,
-        r#"
-(compute dot-product
- (access-cartesian-product
-  (access (access-tensor data) 1)
-  (access (access-tensor weights) 1)
- )
-)
-"#,
-        &vec![super::dense_relay_to_glenside(),],
-        &vec![RelayOperator::RelayDense]
-    );
+        let (cost, extracted) = egg::Extractor::new(
+            &runner.egraph,
+            crate::extraction::SimpleCostFunction::default(),
+        )
+        .find_best(id);
+        assert!(cost < usize::MAX);
+
+        let mut tensor_rng = SmallRng::seed_from_u64(23);
+        let mut env = HashMap::default();
+        for name in &["x", "y"] {
+            env.insert(
+                *name,
+                ndarray::ArrayD::<f64>::random_using(
+                    vec![1, 8, 4, 4],
+                    Uniform::new(-1f64, 1f64),
+                    &mut tensor_rng,
+                ),
+            );
+        }
+        match (
+            interpret(&program, program.as_ref().len() - 1, &env),
+            interpret(&extracted, extracted.as_ref().len() - 1, &env),
+        ) {
+            (
+                crate::language::interpreter::Value::Access(program),
+                crate::language::interpreter::Value::Access(extracted),
+            ) => {
+                assert_eq!(program.access_axis, extracted.access_axis);
+                assert_close(
+                    &extracted.tensor,
+                    &program.tensor,
+                    Tolerance::absolute(1e-10),
+                );
+            }
+            _ => panic!(),
+        }
+    }
 
-    test!(
-        add_relay_to_glenside,
-        1e-60,
-        r#"
-#[version = "0.0.5"]
-def @main(%x: Tensor[(1024, 1, 1), float32], %y: Tensor[(1, 1024, 7, 7), float32]) -> Tensor[(1, 1024, 7, 7), float32] {
-  add(%x, %y) /* ty=Tensor[(1, 1024, 7, 7), float32] */
-}
-"#,
-        r#"
-(compute elementwise-add
- (access-pair
-  (access (access-broadcast (access-insert-axis (access-tensor x) 0) (access-shape (shape 1 1024 7 7) (shape))) 0)
-  (access (access-tensor y) 0)
- )
-)
-"#,
-        &vec![super::add_relay_to_glenside(),],
-        &vec![RelayOperator::RelayAdd]
-    );
+    #[test]
+    fn vector_alu_non_divisible() {
+        let mut map = HashMap::default();
+        map.insert("x".to_string(), vec![1, 8, 4, 4]);
+        map.insert("y".to_string(), vec![1, 8, 4, 4]);
+        let program = "
+         (compute elementwise-mul
+          (access-pair (access (access-tensor x) 0) (access (access-tensor y) 0)))"
+            .parse::<RecExpr<Language>>()
+            .unwrap();
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
 
-    test!(
-        multiply_relay_to_glenside,
-        1e-60,
-        r#"
-#[version = "0.0.5"]
-def @main(%x: Tensor[(1024, 1, 1), float32], %y: Tensor[(1, 1024, 7, 7), float32]) -> Tensor[(1, 1024, 7, 7), float32] {
-  multiply(%x, %y) /* ty=Tensor[(1, 1024, 7, 7), float32] */
-}
-"#,
-        r#"
-(compute elementwise-mul
- (access-pair
-  (access (access-broadcast (access-insert-axis (access-tensor x) 0) (access-shape (shape 1 1024 7 7) (shape))) 0)
-  (access (access-tensor y) 0)
- )
-)
-"#,
-        &vec![super::multiply_relay_to_glenside(),],
-        &vec![RelayOperator::RelayMultiply]
-    );
+        // 128 elements can't be split into vectors of 48.
+        let runner = Runner::default().with_egraph(egraph).run(&vec![
+            super::split_elementwise_for_vector_alu(48),
+            super::vector_alu(48).rewrite,
+        ]);
+        assert_eq!(runner.egraph[id].nodes.len(), 1);
+    }
+
+    #[test]
+    fn pooling_unit_unsupported_compute_type() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![1, 3, 8, 6]);
+        let program = "(compute reduce-sum
+                        (access-windows (access (access-tensor data) 2) (shape 2 2) (shape 2 2)))"
+            .parse::<RecExpr<Language>>()
+            .unwrap();
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
 
-    test!(
-        divide_relay_to_glenside,
-        1e-60,
-        r#"
-#[version = "0.0.5"]
-def @main(%x: Tensor[(1024, 1, 1), float32], %y: Tensor[(1, 1024, 7, 7), float32]) -> Tensor[(1, 1024, 7, 7), float32] {
-  divide(%x, %y) /* ty=Tensor[(1, 1024, 7, 7), float32] */
-}
-"#,
-        r#"
-(compute elementwise-div
- (access-pair
-  (access (access-broadcast (access-insert-axis (access-tensor x) 0) (access-shape (shape 1 1024 7 7) (shape))) 0)
-  (access (access-tensor y) 0)
- )
-)
-"#,
-        &vec![super::divide_relay_to_glenside(),],
-        &vec![RelayOperator::RelayDivide]
-    );
+        let runner = Runner::default()
+            .with_egraph(egraph)
+            .run(&vec![super::pooling_unit().rewrite]);
+        assert_eq!(runner.egraph[id].nodes.len(), 1);
+    }
 
-    test!(
-        batch_flatten_relay_to_glenside,
-        1e-60,
-        r#"
-#[version = "0.0.5"]
-def @main(%x: Tensor[(2, 3, 3, 4, 100), float32]) -> Tensor[(2, 3600), float32] {
-  nn.batch_flatten(%x)
-}
-"#,
-        r#"
-(access-flatten (access (access-tensor x) 1))
-"#,
-        &vec![super::batch_flatten_relay_to_glenside(),],
-        &vec![RelayOperator::RelayBatchFlatten]
-    );
+    #[test]
+    fn batch_matmul_to_access_pattern() {
+        let mut map = HashMap::default();
+        map.insert("a".to_string(), vec![3, 4, 5]);
+        map.insert("b".to_string(), vec![3, 5, 2]);
+        let program = "(batch-matmul (access-tensor a) (access-tensor b))"
+            .parse::<RecExpr<Language>>()
+            .unwrap();
 
-    test!(
-        bias_add_relay_to_glenside_axis_0,
-        1e-60,
-        r#"
-#[version = "0.0.5"]
-def @main(%x: Tensor[(3, 3), float32], %y: Tensor[(3), float32]) -> Tensor[(3, 3), float32] {
-  nn.bias_add(%x, %y, axis=0)
-}
-"#,
-        r#"
-(compute elementwise-add
- (access-pair
-  (access (access-tensor x) 0)
-  (access
-   (access-broadcast
-    (access-insert-axis (access-tensor y) 1)
-    (access-shape (shape 3 3) (shape))
-   )
-   0
-  )
- )
-)
-"#,
-        &vec![super::bias_add_relay_to_glenside(),],
-        &vec![RelayOperator::RelayBiasAdd]
-    );
+        let mut lowered = RecExpr::default();
+        let a_id = lowered.add(Language::Symbol("a".to_string()));
+        let a_id = lowered.add(Language::AccessTensor(a_id));
+        let b_id = lowered.add(Language::Symbol("b".to_string()));
+        let b_id = lowered.add(Language::AccessTensor(b_id));
+        from_relay::batch_matmul(&mut lowered, a_id, &[3, 4, 5], b_id, &[3, 5, 2]);
+        let pattern = lowered.pretty(80).parse::<Pattern<Language>>().unwrap();
 
-    test!(
-        bias_add_relay_to_glenside_axis_1,
-        1e-60,
-        r#"
-#[version = "0.0.5"]
-def @main(%x: Tensor[(3, 3), float32], %y: Tensor[(3), float32]) -> Tensor[(3, 3), float32] {
-  nn.bias_add(%x, %y, axis=1)
-}
-"#,
-        r#"
-(compute elementwise-add
- (access-pair
-  (access (access-tensor x) 0)
-  (access
-   (access-broadcast
-    (access-insert-axis (access-tensor y) 0)
-    (access-shape (shape 3 3) (shape))
-   )
-   0
-  )
- )
-)
-"#,
-        &vec![super::bias_add_relay_to_glenside(),],
-        &vec![RelayOperator::RelayBiasAdd]
-    );
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map.clone(),
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+        assert!(pattern.search_eclass(&egraph, id).is_none());
 
-    test!(
-        bias_add_relay_to_glenside_axis_neg_1,
-        1e-60,
-        r#"
-#[version = "0.0.5"]
-def @main(%x: Tensor[(3, 3), float32], %y: Tensor[(3), float32]) -> Tensor[(3, 3), float32] {
-  nn.bias_add(%x, %y, axis=-1)
-}
-"#,
-        r#"
-(compute elementwise-add
- (access-pair
-  (access (access-tensor x) 0)
-  (access
-   (access-broadcast
-    (access-insert-axis (access-tensor y) 0)
-    (access-shape (shape 3 3) (shape))
-   )
-   0
-  )
- )
-)
-"#,
-        &vec![super::bias_add_relay_to_glenside(),],
-        &vec![RelayOperator::RelayBiasAdd]
-    );
+        let runner = Runner::default()
+            .with_egraph(egraph)
+            .run(&vec![super::batch_matmul_to_access_pattern()]);
+        assert!(pattern.search_eclass(&runner.egraph, id).is_some());
+
+        let mut tensor_rng = SmallRng::seed_from_u64(23);
+        let mut env = HashMap::default();
+        for (name, shape) in map.iter() {
+            env.insert(
+                name.as_str(),
+                ndarray::ArrayD::<f64>::random_using(
+                    shape.clone(),
+                    Uniform::new(-1f64, 1f64),
+                    &mut tensor_rng,
+                ),
+            );
+        }
+        match (
+            interpret(&program, program.as_ref().len() - 1, &env),
+            interpret(&lowered, lowered.as_ref().len() - 1, &env),
+        ) {
+            (
+                crate::language::interpreter::Value::Access(high_level),
+                crate::language::interpreter::Value::Access(lowered),
+            ) => {
+                assert_eq!(high_level.tensor.shape(), &[3, 4, 2]);
+                assert_eq!(high_level.tensor.shape(), lowered.tensor.shape());
+                assert_close(
+                    &lowered.tensor,
+                    &high_level.tensor,
+                    Tolerance::absolute(1e-10),
+                );
+            }
+            _ => panic!(),
+        }
+    }
 
-    test!(
-        bias_add_relay_to_glenside_axis_neg_2,
-        1e-60,
-        r#"
-#[version = "0.0.5"]
-def @main(%x: Tensor[(3, 3), float32], %y: Tensor[(3), float32]) -> Tensor[(3, 3), float32] {
-  nn.bias_add(%x, %y, axis=-2)
-}
-"#,
-        r#"
-(compute elementwise-add
- (access-pair
-  (access (access-tensor x) 0)
-  (access
-   (access-broadcast
-    (access-insert-axis (access-tensor y) 1)
-    (access-shape (shape 3 3) (shape))
-   )
-   0
-  )
- )
-)
-"#,
-        &vec![super::bias_add_relay_to_glenside(),],
-        &vec![RelayOperator::RelayBiasAdd]
-    );
+    /// Checks that `rewrite` lowers `program` to `lowered`, and that both
+    /// interpret to the same values.
+    fn check_norm_lowering(
+        program: &str,
+        lowered: &RecExpr<Language>,
+        rewrite: RW,
+        map: HashMap<String, Vec<usize>>,
+    ) {
+        let program = program.parse::<RecExpr<Language>>().unwrap();
+        let pattern = lowered.pretty(80).parse::<Pattern<Language>>().unwrap();
 
-    test!(
-        concatenate_relay_to_glenside,
-        1e-60,
-        r#"
-#[version = "0.0.5"]
-def @main(%x: Tensor[(1, 5, 1, 1), float32], %y: Tensor[(1, 3, 1, 1), float32]) {
-    %0 = (%x, %y);
-    concatenate(%0, axis=1)
-}
-"#,
-        r#"
-(access-concatenate (access-tensor x) (access-tensor y) 1)
-"#,
-        &vec![
-            super::concatenate_relay_to_glenside(),
-            super::simplify_tuple_get_item()
-        ],
-        &vec![RelayOperator::RelayConcatenate]
-    );
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map.clone(),
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+        assert!(pattern.search_eclass(&egraph, id).is_none());
+
+        let runner = Runner::default().with_egraph(egraph).run(&vec![rewrite]);
+        assert!(pattern.search_eclass(&runner.egraph, id).is_some());
+
+        let mut tensor_rng = SmallRng::seed_from_u64(23);
+        let mut env = HashMap::default();
+        for (name, shape) in map.iter() {
+            env.insert(
+                name.as_str(),
+                ndarray::ArrayD::<f64>::random_using(
+                    shape.clone(),
+                    Uniform::new(-1f64, 1f64),
+                    &mut tensor_rng,
+                ),
+            );
+        }
+        match (
+            interpret(&program, program.as_ref().len() - 1, &env),
+            interpret(lowered, lowered.as_ref().len() - 1, &env),
+        ) {
+            (
+                crate::language::interpreter::Value::Access(high_level),
+                crate::language::interpreter::Value::Access(lowered),
+            ) => {
+                assert_eq!(high_level.tensor.shape(), lowered.tensor.shape());
+                assert_close(
+                    &lowered.tensor,
+                    &high_level.tensor,
+                    Tolerance::absolute(1e-10),
+                );
+            }
+            _ => panic!(),
+        }
+    }
 
-    test!(
-        transpose_relay_to_glenside,
-        1e-60,
-        r#"
-#[version = "0.0.5"]
-def @main(%x: Tensor[(3, 5), float32]) {
-    transpose(%x, axes=[1, 0])
-}
-"#,
-        r#"
-(access-transpose (access-tensor x) (list 1 0))
-        "#,
-        &vec![super::transpose_relay_to_glenside(),],
-        &vec![RelayOperator::RelayTranspose]
-    );
+    /// Adds the symbols `data`, `gamma`, and `beta` and the literal 1e-5 to
+    /// `expr`.
+    fn norm_arguments(expr: &mut RecExpr<Language>) -> (Id, Id, Id, Id) {
+        let data_id = expr.add(Language::Symbol("data".to_string()));
+        let data_id = expr.add(Language::AccessTensor(data_id));
+        let gamma_id = expr.add(Language::Symbol("gamma".to_string()));
+        let gamma_id = expr.add(Language::AccessTensor(gamma_id));
+        let beta_id = expr.add(Language::Symbol("beta".to_string()));
+        let beta_id = expr.add(Language::AccessTensor(beta_id));
+        let epsilon_id = expr.add(Language::NotNanFloat64(
+            ordered_float::NotNan::new(0.00001).unwrap(),
+        ));
+        let epsilon_id = expr.add(Language::Literal(epsilon_id));
+        (data_id, gamma_id, beta_id, epsilon_id)
+    }
 
-    test!(
-        reshape_relay_to_glenside,
-        1e-60,
-        r#"
-    #[version = "0.0.5"]
-    def @main(%x: Tensor[(1, 255, 52, 52), float32]) {
-        reshape(%x, newshape=[-1, 3, 85, 52, 52])
+    #[test]
+    fn layer_norm_to_mean_variance() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![2, 3, 8]);
+        map.insert("gamma".to_string(), vec![8]);
+        map.insert("beta".to_string(), vec![8]);
+
+        let mut lowered = RecExpr::default();
+        let (data_id, gamma_id, beta_id, epsilon_id) = norm_arguments(&mut lowered);
+        let data_id = from_relay::access(&mut lowered, data_id, 2);
+        from_relay::layer_norm(
+            &mut lowered,
+            data_id,
+            &[2, 3],
+            &[8],
+            gamma_id,
+            beta_id,
+            epsilon_id,
+        );
+
+        check_norm_lowering(
+            "(layer-norm (access (access-tensor data) 2) (access-tensor gamma)
+              (access-tensor beta) (literal 0.00001))",
+            &lowered,
+            super::layer_norm_to_mean_variance(),
+            map,
+        );
     }
-    "#,
-        r#"
-    (access-reshape (access-tensor x) (access-shape (shape) (shape 1 3 85 52 52)))
-    "#,
-        &vec![super::reshape_relay_to_glenside(),],
-        &vec![RelayOperator::RelayReshape]
-    );
 
-    test!(
-        squeeze_relay_to_glenside,
-        1e-60,
-        r#"
-#[version = "0.0.5"]
-def @main(%x: Tensor[(1, 100, 1, 1), float32]) {
-    squeeze(%x, axis=[2, 3])
-}
-"#,
-        r#"
-(access-squeeze (access-squeeze (access-tensor x) 3) 2)
-        "#,
-        &vec![super::squeeze_relay_to_glenside(),],
-        &vec![RelayOperator::RelaySqueeze]
-    );
+    #[test]
+    fn group_norm_to_mean_variance() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![2, 6, 3, 3]);
+        map.insert("gamma".to_string(), vec![6]);
+        map.insert("beta".to_string(), vec![6]);
+
+        let mut lowered = RecExpr::default();
+        let (data_id, gamma_id, beta_id, epsilon_id) = norm_arguments(&mut lowered);
+        from_relay::group_norm(
+            &mut lowered,
+            data_id,
+            &[2, 6, 3, 3],
+            gamma_id,
+            beta_id,
+            3,
+            epsilon_id,
+        );
+
+        check_norm_lowering(
+            "(group-norm (access-tensor data) (access-tensor gamma) (access-tensor beta)
+              3 (literal 0.00001))",
+            &lowered,
+            super::group_norm_to_mean_variance(),
+            map,
+        );
+    }
+
+    #[test]
+    fn batch_matmul_to_systolic_arrays() {
+        // Scaled attention scores, softmax(Q K^T / sqrt(d)), minus the
+        // softmax.
+        let mut map = HashMap::default();
+        map.insert("q".to_string(), vec![2, 8, 16]);
+        map.insert("k".to_string(), vec![2, 8, 16]);
+        let mut program = RecExpr::default();
+        let q_id = program.add(Language::Symbol("q".to_string()));
+        let q_id = program.add(Language::AccessTensor(q_id));
+        let k_id = program.add(Language::Symbol("k".to_string()));
+        let k_id = program.add(Language::AccessTensor(k_id));
+        let k_id = from_relay::access_transpose(&mut program, k_id, &[0, 2, 1]);
+        let scores_id = program.add(Language::BatchMatmul([q_id, k_id]));
+        from_relay::scale(&mut program, scores_id, &[2, 8, 8], 0.25);
+
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
 
-    test!(
-        conv2d,
-        1e-5,
-        r#"
-    #[version = "0.0.5"]
-    def @main(%data: Tensor[(1, 3, 32, 32), float32], %weights: Tensor[(8, 3, 3, 3), float32]) -> Tensor[(1, 8, 17, 12), float32] {
-      nn.conv2d(%data, %weights, strides=[2, 3], padding=[1, 2, 3, 4]) /* ty=Tensor[(1, 8, 17, 12), float32] */
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&vec![super::batch_matmul_to_systolic_arrays().rewrite]);
+
+        let (cost, extracted) = egg::Extractor::new(
+            &runner.egraph,
+            crate::extraction::MonolithicCostFunction {
+                egraph: &runner.egraph,
+                systolic_array_configuration: (16, 8),
+                prefer_systolic_arrays_with_blocking: false,
+            },
+        )
+        .find_best(id);
+        assert!(cost < crate::extraction::MonolithicCostFunction::INFINITY_VALUE);
+        assert_eq!(
+            extracted
+                .as_ref()
+                .iter()
+                .filter(|node| matches!(node, Language::SystolicArray(_)))
+                .count(),
+            2
+        );
+        assert!(crate::search::verify(&runner.egraph, &program, &extracted, 1e-10, 0).passed);
     }
-    "#,
-        r#"
-    (access-transpose
-     (compute dot-product
-      (access-cartesian-product
-       (access (access-tensor weights) 1)
-       (access
-        (access-squeeze
-         (access-windows
-          (access
-           (access-pad
-            (access-pad
-             (access-tensor data)
-             zero-padding
-             2 1 3
+
+    #[test]
+    fn bias_add_to_glenside() {
+        let mut map = HashMap::default();
+        map.insert("data".to_string(), vec![2, 3, 4]);
+        map.insert("bias".to_string(), vec![3]);
+        let program = "(bias-add (access (access-tensor data) 1) (access-tensor bias) 1)"
+            .parse::<RecExpr<Language>>()
+            .unwrap();
+        let lowered = "
+         (compute elementwise-add
+          (access-pair
+           (access (access (access-tensor data) 1) 0)
+           (access
+            (access-broadcast
+             (access-insert-axis (access-insert-axis (access-tensor bias) 0) 2)
+             (access-shape (shape 2 3 4) (shape))
             )
-            zero-padding
-            3 2 4
+            0
            )
-           1
           )
-          (shape 3 3 3)
-          (shape 1 2 3)
-         )
-         1
-        )
-        3
-       )
-      )
-     )
-     (list 1 0 2 3)
-    )
-    "#,
-        &vec![super::conv2d_relay_to_glenside(),],
-        &vec![RelayOperator::RelayConv2D]
-    );
+         )"
+        .parse::<RecExpr<Language>>()
+        .unwrap();
+        let pattern = lowered.pretty(80).parse::<Pattern<Language>>().unwrap();
 
-    test!(
-        conv2d_nhwc_hwio,
-        1e-5,
-        r#"
-    #[version = "0.0.5"]
-    def @main(%data: Tensor[(1, 32, 32, 3), float32], %weights: Tensor[(3, 3, 3, 8), float32]) -> Tensor[(1, 17, 12, 8), float32] {
-      nn.conv2d(%data, %weights, strides=[2, 3], padding=[1, 2, 3, 4], data_layout="NHWC", kernel_layout="HWIO")
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map.clone(),
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+        let runner = Runner::default()
+            .with_egraph(egraph)
+            .run(&vec![super::bias_add_to_glenside()]);
+        assert!(pattern.search_eclass(&runner.egraph, id).is_some());
+
+        let mut tensor_rng = SmallRng::seed_from_u64(23);
+        let mut env = HashMap::default();
+        for (name, shape) in map.iter() {
+            env.insert(
+                name.as_str(),
+                ndarray::ArrayD::<f64>::random_using(
+                    shape.clone(),
+                    Uniform::new(-1f64, 1f64),
+                    &mut tensor_rng,
+                ),
+            );
+        }
+        match (
+            interpret(&program, program.as_ref().len() - 1, &env),
+            interpret(&lowered, lowered.as_ref().len() - 1, &env),
+        ) {
+            (
+                crate::language::interpreter::Value::Access(high_level),
+                crate::language::interpreter::Value::Access(lowered),
+            ) => {
+                assert_eq!(high_level.tensor.shape(), lowered.tensor.shape());
+                assert_close(
+                    &lowered.tensor,
+                    &high_level.tensor,
+                    Tolerance::absolute(1e-10),
+                );
+            }
+            _ => panic!(),
+        }
     }
-    "#,
-        r#"
-    (access-transpose
-     (access-transpose
-      (compute dot-product
-       (access-cartesian-product
-        (access
-         (access-transpose (access-tensor weights) (list 3 2 0 1))
-         1
-        )
-        (access
-         (access-squeeze
-          (access-windows
-           (access
-            (access-pad
-             (access-pad
-              (access-transpose (access-tensor data) (list 0 3 1 2))
-              zero-padding
-              2 1 3
-             )
-             zero-padding
-             3 2 4
-            )
-            1
-           )
-           (shape 3 3 3)
-           (shape 1 2 3)
+
+    #[test]
+    fn fuse_bias_add_into_systolic_array() {
+        let mut map = HashMap::default();
+        map.insert("a".to_string(), vec![16, 64]);
+        map.insert("w".to_string(), vec![64, 32]);
+        map.insert("b".to_string(), vec![32]);
+        let program = "
+         (bias-add
+          (systolic-array 64 32
+           (access (access-tensor a) 1)
+           (access (access-tensor w) 0)
           )
+          (access-tensor b)
           1
-         )
-         3
+         )"
+        .parse::<RecExpr<Language>>()
+        .unwrap();
+
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+        let runner = Runner::default()
+            .with_egraph(egraph)
+            .run(&vec![super::fuse_bias_add_into_systolic_array().rewrite]);
+
+        let matches = "(systolic-array 65 32 ?a0 ?a1)"
+            .parse::<Pattern<Language>>()
+            .unwrap()
+            .search_eclass(&runner.egraph, id)
+            .unwrap();
+        assert_eq!(matches.substs.len(), 1);
+        match (
+            &runner.egraph[matches.substs[0]["?a0".parse().unwrap()]].data,
+            &runner.egraph[matches.substs[0]["?a1".parse().unwrap()]].data,
+        ) {
+            (MyAnalysisData::AccessPattern(a0), MyAnalysisData::AccessPattern(a1)) => {
+                assert_eq!(a0.shape, IxDyn(&[16]));
+                assert_eq!(a0.item_shape, IxDyn(&[65]));
+                assert_eq!(a1.shape, IxDyn(&[]));
+                assert_eq!(a1.item_shape, IxDyn(&[65, 32]));
+            }
+            _ => panic!(),
+        }
+
+        // The fused systolic array should compute bias-add(dense).
+        let extract = |var: &str| {
+            egg::Extractor::new(&runner.egraph, egg::AstSize)
+                .find_best(matches.substs[0][var.parse().unwrap()])
+                .1
+                .pretty(1000)
+        };
+        let fused = format!(
+            "(systolic-array 65 32 {} {})",
+            extract("?a0"),
+            extract("?a1")
         )
-       )
-      )
-      (list 1 0 2 3)
-     )
-     (list 0 2 3 1)
-    )
-    "#,
-        &vec![super::conv2d_relay_to_glenside(),],
-        &vec![RelayOperator::RelayConv2D]
-    );
+        .parse::<RecExpr<Language>>()
+        .unwrap();
 
-    // The first part of a separable convolution, as seen in Mobilenet.
-    test!(
-        conv2d_depthwise_separable_stage1,
-        1e-6,
-        r#"
-    #[version = "0.0.5"]
-    def @main(%data: Tensor[(1, 3, 32, 32), float32], %weight: Tensor[(3, 1, 3, 3), float32]) -> Tensor[(1, 3, 38, 20), float32] {
-     nn.conv2d(%data, %weight, strides=[1, 2], padding=[3, 4, 5, 6], groups=3)
+        let mut tensor_rng = SmallRng::seed_from_u64(23);
+        let mut env = HashMap::default();
+        for name in &["a", "w", "b"] {
+            env.insert(
+                *name,
+                ndarray::ArrayD::<f64>::random_using(
+                    runner.egraph.analysis.name_to_shape[*name].clone(),
+                    Uniform::new(-1f64, 1f64),
+                    &mut tensor_rng,
+                ),
+            );
+        }
+        match (
+            interpret(&program, program.as_ref().len() - 1, &env),
+            interpret(&fused, fused.as_ref().len() - 1, &env),
+        ) {
+            (
+                crate::language::interpreter::Value::Access(unfused),
+                crate::language::interpreter::Value::Access(fused),
+            ) => {
+                assert_eq!(unfused.tensor.shape(), fused.tensor.shape());
+                assert_close(&fused.tensor, &unfused.tensor, Tolerance::absolute(1e-10));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn sparse_systolic_array() {
+        let mut map = HashMap::default();
+        map.insert("a".to_string(), vec![4, 16]);
+        map.insert("w".to_string(), vec![8, 16]);
+        let program = "
+         (compute dot-product
+          (access-cartesian-product (access (access-tensor a) 1) (access (access-tensor w) 1)))"
+            .parse::<RecExpr<Language>>()
+            .unwrap();
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program);
+        egraph.rebuild();
+
+        let runner = Runner::default().with_egraph(egraph).run(&vec![
+            super::sparse_dot_product(vec!["w".to_string()].into_iter().collect()),
+            super::sparse_systolic_array().rewrite,
+        ]);
+        assert!(
+            "(sparse-systolic-array 16 8 (access (access-tensor a) 1) (access (access-tensor w) 1))"
+                .parse::<Pattern<Language>>()
+                .unwrap()
+                .search_eclass(&runner.egraph, id)
+                .is_some()
+        );
+
+        let (cost, extracted) = egg::Extractor::new(
+            &runner.egraph,
+            crate::extraction::SimpleCostFunction::default(),
+        )
+        .find_best(id);
+        assert!(cost < usize::MAX);
+
+        // Only the first of every four rows of `w` is nonzero.
+        let mut tensor_rng = SmallRng::seed_from_u64(23);
+        let a = ndarray::ArrayD::<f64>::random_using(
+            vec![4, 16],
+            Uniform::new(-1f64, 1f64),
+            &mut tensor_rng,
+        );
+        let mut w = ndarray::ArrayD::<f64>::random_using(
+            vec![8, 16],
+            Uniform::new(-1f64, 1f64),
+            &mut tensor_rng,
+        );
+        for (i, mut row) in w.outer_iter_mut().enumerate() {
+            if i % 4 != 0 {
+                row.fill(0.0);
+            }
+        }
+        let mut env = HashMap::default();
+        env.insert("a", a.clone());
+        env.insert("w", w.clone());
+        let mut sparse_env = crate::language::sparse::SparseEnvironment::default();
+        sparse_env.dense.insert("a", a);
+        // Each block spans two rows, so half of the blocks are stored.
+        sparse_env.insert_sparse("w", w, [2, 4]);
+        assert_eq!(sparse_env.sparse["w"].density(), 0.5);
+
+        match (
+            interpret(&program, program.as_ref().len() - 1, &env),
+            interpret(&extracted, extracted.as_ref().len() - 1, &sparse_env),
+        ) {
+            (
+                crate::language::interpreter::Value::Access(program),
+                crate::language::interpreter::Value::Access(extracted),
+            ) => {
+                assert_eq!(program.access_axis, extracted.access_axis);
+                assert_close(
+                    &extracted.tensor,
+                    &program.tensor,
+                    Tolerance::absolute(1e-10),
+                );
+            }
+            _ => panic!(),
+        }
     }
-    "#,
-        // TODO(@gussmith23) I'm being lazy here
-        r#"
-    (access-concatenate
-        (access-concatenate
-        (compute
-          dot-product
-          (access-cartesian-product
-            (access (access-slice (access-tensor weight) 0 0 1) 0)
-            (access
-              (access-windows
-                (access-slice
-                  (access
-                    (access-pad
-                      (access-pad (access-tensor data) zero-padding 2 3 5)
-                      zero-padding
-                      3
-                      4
-                      6)
-                    0)
-                  1
-                  0
-                  1)
-                (shape 1 1 3 3)
-                (shape 1 1 1 2))
-              4)))
-        (compute
-          dot-product
-          (access-cartesian-product
-            (access (access-slice (access-tensor weight) 0 1 2) 0)
-            (access
-              (access-windows
-                (access-slice
-                  (access
-                    (access-pad
-                      (access-pad (access-tensor data) zero-padding 2 3 5)
-                      zero-padding
-                      3
-                      4
-                      6)
-                    0)
-                  1
-                  1
-                  2)
-                (shape 1 1 3 3)
-                (shape 1 1 1 2))
-              4)))
-        1)
-        (compute
-        dot-product
-        (access-cartesian-product
-          (access (access-slice (access-tensor weight) 0 2 3) 0)
-          (access
-            (access-windows
-              (access-slice
-                (access
-                  (access-pad
-                    (access-pad (access-tensor data) zero-padding 2 3 5)
-                    zero-padding
-                    3
-                    4
-                    6)
-                  0)
-                1
-                2
-                3)
-              (shape 1 1 3 3)
-              (shape 1 1 1 2))
-            4)))
-        1)
-    "#,
-        &vec![super::conv2d_relay_to_glenside(),],
-        &vec![RelayOperator::RelayConv2D]
-    );
 }
@@ -0,0 +1,840 @@
+//! Layer-by-layer execution schedules.
+//!
+//! An extracted program is a DAG, but a backend runs it as a sequence of
+//! kernel invocations, each reading some buffers and writing one.
+//! [`schedule`] linearizes a program into such a sequence: each node which
+//! performs arithmetic (as counted by [`op_count`]), or which is a hardware
+//! atom, becomes one invocation, writing a buffer of its own. Access pattern
+//! manipulations (transposes, slices, windows, etc.) are not invocations;
+//! they're folded into the invocations reading through them, which address
+//! their input buffers accordingly. This is what both the C backend and the
+//! hardware design need: the order in which kernels run, and which buffers
+//! each one reads and writes. The schedule can be serialized to JSON.
+//!
+//! The schedule also describes how each buffer is laid out in memory (see
+//! [`Buffer`]), and how each invocation reads its inputs through the access
+//! pattern manipulations folded into it, as strided views of the buffers
+//! (see [`AccessDescriptor`]), so that RTL and driver generators don't have
+//! to re-derive either from the program. It records where the program's
+//! values are written to buffers, and where they're only ever read in place
+//! or streamed between kernels (see [`super::materialization`]).
+
+use super::materialization::{infer_materialization, Materialization};
+use super::op_count::op_count;
+use super::timing::{is_hardware_atom, timed_kind};
+use super::{AccessPatternData, DataType, Language, MyAnalysis, MyAnalysisData, PadType};
+use crate::legality::element_dtype;
+use egg::{EGraph, Id, Language as LanguageTrait, RecExpr};
+use serde::{Serialize, Serializer};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+/// What a buffer holds.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum BufferKind {
+    /// A tensor the program reads, e.g. an input or a weight.
+    Input { name: String },
+    /// The result of an invocation, consumed by later invocations.
+    Intermediate,
+    /// One of the program's results.
+    Output,
+}
+
+/// A buffer, stored in row-major order. Invocations which read a buffer
+/// padded (e.g. convolutions) read the padding in place, so the buffer is
+/// allocated with room for it around its elements.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Buffer {
+    pub id: usize,
+    pub shape: Vec<usize>,
+    /// The type of the buffer's elements, as [`element_dtype`] determines it.
+    /// Unknown if it can't be determined.
+    #[serde(serialize_with = "serialize_name")]
+    pub dtype: Option<DataType>,
+    /// The number of elements of padding allocated before and after each
+    /// axis.
+    pub padding: Vec<(usize, usize)>,
+    /// What the padding holds, if there is any.
+    #[serde(serialize_with = "serialize_name")]
+    pub pad_type: Option<PadType>,
+    /// The distance, in elements, between consecutive elements along each
+    /// axis, padding included.
+    pub strides: Vec<usize>,
+    pub kind: BufferKind,
+}
+
+/// A strided view of a buffer, as an invocation reads it: the element of the
+/// view at index `i` is the element `offset + i · strides` elements from the
+/// start of the buffer's storage, which may be in the buffer's padding.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct AccessDescriptor {
+    pub buffer: usize,
+    pub offset: usize,
+    /// The view's shape: its first `access_axis` axes are the shape of the
+    /// access pattern read, and the rest its item shape.
+    pub shape: Vec<usize>,
+    pub access_axis: usize,
+    /// Zero along broadcast axes, and negative along reversed ones.
+    pub strides: Vec<i64>,
+}
+
+/// One run of a kernel.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct KernelInvocation {
+    /// The index of the node computed, in the scheduled program.
+    pub node: usize,
+    /// The kernel run, named as in
+    /// [`Timings::by_operator`](super::timing::Timings::by_operator), e.g.
+    /// `systolic-array` or `compute relu`.
+    pub kernel: String,
+    /// The buffers read, in the order the node's arguments first read them.
+    pub inputs: Vec<usize>,
+    /// How each access pattern the node takes as an argument is read, with
+    /// cartesian products, pairs, and concatenations looked through to the
+    /// access patterns they combine. `None` where an access pattern isn't a
+    /// strided view of a single buffer (e.g. a flattened transpose, or a
+    /// padding which conflicts with that of another read of the buffer), so
+    /// that it must be materialized.
+    pub reads: Vec<Option<AccessDescriptor>>,
+    /// The buffer written.
+    pub output: usize,
+    /// The intermediate buffers which are no longer needed once this
+    /// invocation has run.
+    pub frees: Vec<usize>,
+}
+
+/// A program linearized into kernel invocations; see [`schedule`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ExecutionSchedule {
+    /// Every buffer, indexed by id: first the inputs, in the order the
+    /// program first reads them, then the invocations' outputs, in order.
+    pub buffers: Vec<Buffer>,
+    /// The invocations, in an order in which they can run.
+    pub invocations: Vec<KernelInvocation>,
+    /// The buffers holding the program's results: one per output of an
+    /// `outputs` root, and one otherwise. A result which is an access
+    /// pattern manipulation of a buffer is read from that buffer.
+    pub outputs: Vec<usize>,
+    /// How the value of each node of the scheduled program is stored, by
+    /// index, as [`infer_materialization`] decides.
+    pub materialization: Vec<Option<Materialization>>,
+}
+
+impl ExecutionSchedule {
+    /// The schedule as JSON, with fields named as [`ExecutionSchedule`]'s
+    /// are.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap()
+    }
+}
+
+/// Whether `node` is run as a kernel invocation.
+fn is_invocation(egraph: &EGraph<Language, MyAnalysis>, node: &Language) -> bool {
+    is_hardware_atom(node) || op_count(egraph, node) != Default::default()
+}
+
+/// Serializes an optional value by its name, e.g. `int8` or `zero-padding`.
+fn serialize_name<T: Display, S: Serializer>(
+    value: &Option<T>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => serializer.collect_str(value),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Adds an unpadded buffer to `buffers`, returning its id. Its strides are
+/// filled in once its padding is known.
+fn add_buffer(
+    buffers: &mut Vec<Buffer>,
+    shape: Vec<usize>,
+    dtype: Option<DataType>,
+    kind: BufferKind,
+) -> usize {
+    buffers.push(Buffer {
+        id: buffers.len(),
+        padding: vec![(0, 0); shape.len()],
+        shape,
+        dtype,
+        pad_type: None,
+        strides: Vec::default(),
+        kind,
+    });
+    buffers.len() - 1
+}
+
+/// A strided view of a buffer, in terms of the buffer's coordinates: the
+/// element of the view at index `i` is at coordinates `origin + Σ i[a] ·
+/// steps[a]` of the buffer. Coordinates outside of the buffer read padding.
+#[derive(Clone, Debug)]
+struct View {
+    buffer: usize,
+    origin: Vec<i64>,
+    /// For each axis of the view, the step it takes along each axis of the
+    /// buffer.
+    steps: Vec<Vec<i64>>,
+    shape: Vec<usize>,
+    access_axis: usize,
+    /// The padding read before and after each axis of the buffer.
+    padding: Vec<(usize, usize)>,
+    pad_type: Option<PadType>,
+}
+
+impl View {
+    /// The view of all of `buffer`, accessed at `access_axis`.
+    fn identity(buffer: &Buffer, access_axis: usize) -> View {
+        let ndim = buffer.shape.len();
+        View {
+            buffer: buffer.id,
+            origin: vec![0; ndim],
+            steps: (0..ndim)
+                .map(|axis| (0..ndim).map(|i| (i == axis) as i64).collect())
+                .collect(),
+            shape: buffer.shape.clone(),
+            access_axis,
+            padding: vec![(0, 0); ndim],
+            pad_type: None,
+        }
+    }
+
+    /// The view `node`, an access pattern manipulation whose result is
+    /// `result`, takes of the views of its arguments. `None` if it doesn't
+    /// take a strided view, or its argument isn't one. `enode` is `node` with
+    /// its children replaced by their eclasses in `egraph`.
+    fn of(
+        egraph: &EGraph<Language, MyAnalysis>,
+        buffers: &[Buffer],
+        node: &Language,
+        enode: &Language,
+        views: &[Option<View>],
+        result: &AccessPatternData,
+    ) -> Option<View> {
+        let mut view = views[usize::from(*node.children().first()?)].clone()?;
+        let ndim = view.shape.len();
+        match enode {
+            Language::Access(_) => (),
+            &Language::AccessTranspose([_, list_id]) => {
+                let list = MyAnalysis::get_axis_list(list_id, egraph, ndim);
+                view.steps = list.iter().map(|axis| view.steps[*axis].clone()).collect();
+            }
+            &Language::AccessSlice([_, axis_id, low_id, _]) => {
+                let axis = MyAnalysis::get_axis(axis_id, egraph, ndim);
+                let low = MyAnalysis::get_usize(low_id, egraph) as i64;
+                for (origin, step) in view.origin.iter_mut().zip(&view.steps[axis]) {
+                    *origin += low * step;
+                }
+            }
+            &Language::AccessReverse([_, axis_id]) => {
+                let axis = MyAnalysis::get_usize(axis_id, egraph);
+                let last = view.shape[axis] as i64 - 1;
+                for (origin, step) in view.origin.iter_mut().zip(view.steps[axis].iter_mut()) {
+                    *origin += last * *step;
+                    *step = -*step;
+                }
+            }
+            &Language::AccessSqueeze([_, axis_id]) => {
+                view.steps
+                    .remove(MyAnalysis::get_axis(axis_id, egraph, ndim));
+            }
+            &Language::AccessInsertAxis([_, axis_id]) => {
+                let zeros = vec![0; view.origin.len()];
+                view.steps
+                    .insert(MyAnalysis::get_usize(axis_id, egraph), zeros);
+            }
+            Language::AccessBroadcast(_) => {
+                for (axis, dim) in result.as_vec().iter().enumerate() {
+                    if view.shape[axis] != *dim {
+                        view.steps[axis].iter_mut().for_each(|step| *step = 0);
+                    }
+                }
+            }
+            &Language::AccessWindows([_, _, stride_shape_id]) => {
+                // Each item axis becomes an axis over the windows, stepping a
+                // stride at a time, and an axis within each window.
+                let strides = MyAnalysis::get_shape(stride_shape_id, egraph).slice();
+                let items = view.steps.split_off(view.access_axis);
+                view.steps
+                    .extend(items.iter().zip(strides).map(|(steps, stride)| {
+                        steps
+                            .iter()
+                            .map(|step| step * *stride as i64)
+                            .collect::<Vec<_>>()
+                    }));
+                view.steps.extend(items);
+            }
+            &Language::AccessPad([_, pad_type_id, axis_id, pad_before_id, pad_after_id]) => {
+                let pad_type = match &egraph[pad_type_id].data {
+                    MyAnalysisData::PadType(pad_type) => *pad_type,
+                    _ => panic!(),
+                };
+                let axis = MyAnalysis::get_axis(axis_id, egraph, ndim);
+                let before = MyAnalysis::get_usize(pad_before_id, egraph);
+                let after = MyAnalysis::get_usize(pad_after_id, egraph);
+                // The padding lies outside of the buffer only if the padded
+                // axis runs forwards along exactly one axis of the buffer,
+                // over all of it (and any padding already read), and no other
+                // axis of the view runs along that axis.
+                let buffer_axis = view.steps[axis].iter().position(|step| *step != 0)?;
+                let (padded_before, padded_after) = view.padding[buffer_axis];
+                if view.steps[axis][buffer_axis] != 1
+                    || view.steps[axis].iter().filter(|step| **step != 0).count() != 1
+                    || view
+                        .steps
+                        .iter()
+                        .enumerate()
+                        .any(|(other, steps)| other != axis && steps[buffer_axis] != 0)
+                    || view.origin[buffer_axis] != -(padded_before as i64)
+                    || view.shape[axis]
+                        != buffers[view.buffer].shape[buffer_axis] + padded_before + padded_after
+                    || view.pad_type.map_or(false, |t| t != pad_type)
+                {
+                    return None;
+                }
+                view.origin[buffer_axis] -= before as i64;
+                view.padding[buffer_axis] = (padded_before + before, padded_after + after);
+                view.pad_type = Some(pad_type);
+            }
+            _ => return None,
+        }
+        view.shape = result.as_vec();
+        view.access_axis = result.shape.ndim();
+        Some(view)
+    }
+
+    /// The view as a descriptor of a read of its buffer, once the buffer's
+    /// padding and strides are known.
+    fn descriptor(&self, buffer: &Buffer) -> AccessDescriptor {
+        AccessDescriptor {
+            buffer: self.buffer,
+            offset: self
+                .origin
+                .iter()
+                .zip(&buffer.padding)
+                .zip(&buffer.strides)
+                .map(|((origin, (before, _)), stride)| (origin + *before as i64) as usize * stride)
+                .sum(),
+            shape: self.shape.clone(),
+            access_axis: self.access_axis,
+            strides: self
+                .steps
+                .iter()
+                .map(|steps| {
+                    steps
+                        .iter()
+                        .zip(&buffer.strides)
+                        .map(|(step, stride)| step * *stride as i64)
+                        .sum()
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The views through which `node` reads each of its access pattern
+/// arguments, as [`KernelInvocation::reads`] describes.
+fn reads(
+    egraph: &EGraph<Language, MyAnalysis>,
+    ids: &[Id],
+    nodes: &[Language],
+    node: &Language,
+    views: &[Option<View>],
+) -> Vec<Option<View>> {
+    node.children()
+        .iter()
+        .map(|child| usize::from(*child))
+        .filter(|child| matches!(egraph[ids[*child]].data, MyAnalysisData::AccessPattern(_)))
+        .flat_map(|child| match (&views[child], &nodes[child]) {
+            (Some(view), _) => vec![Some(view.clone())],
+            (None, combined)
+                if matches!(
+                    combined,
+                    Language::AccessCartesianProduct(_)
+                        | Language::AccessPair(_)
+                        | Language::AccessConcatenate(_)
+                ) =>
+            {
+                reads(egraph, ids, nodes, combined, views)
+            }
+            (None, _) => vec![None],
+        })
+        .collect()
+}
+
+/// Linearizes the part of `expr` reachable from its root into kernel
+/// invocations. `analysis` must know the shapes of every tensor `expr` reads.
+/// Invocations are ordered as their nodes are in `expr`, which is a
+/// topological order.
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::schedule::{schedule, BufferKind};
+/// use glenside::language::{Language, MyAnalysis};
+///
+/// let expr: RecExpr<Language> = "
+///  (compute relu
+///   (systolic-array 32 64
+///    (access (access-tensor t-32-32) 1)
+///    (access (access-tensor t-32-64) 0)))"
+///     .parse()
+///     .unwrap();
+/// let schedule = schedule(&expr, MyAnalysis::default());
+///
+/// assert_eq!(schedule.invocations.len(), 2);
+/// assert_eq!(schedule.invocations[0].kernel, "systolic-array");
+/// assert_eq!(schedule.invocations[0].inputs, vec![0, 1]);
+/// assert_eq!(schedule.invocations[1].kernel, "compute relu");
+/// assert_eq!(schedule.invocations[1].inputs, vec![schedule.invocations[0].output]);
+/// assert_eq!(schedule.outputs, vec![schedule.invocations[1].output]);
+/// assert_eq!(schedule.buffers[schedule.outputs[0]].kind, BufferKind::Output);
+/// ```
+pub fn schedule(expr: &RecExpr<Language>, analysis: MyAnalysis) -> ExecutionSchedule {
+    let nodes = expr.as_ref();
+    assert!(!nodes.is_empty(), "Expected a non-empty expression");
+
+    let mut reachable = HashSet::new();
+    let mut stack = vec![nodes.len() - 1];
+    while let Some(index) = stack.pop() {
+        if reachable.insert(index) {
+            stack.extend(nodes[index].children().iter().map(|id| usize::from(*id)));
+        }
+    }
+
+    let mut egraph = EGraph::new(analysis);
+    let mut ids: Vec<Id> = Vec::with_capacity(nodes.len());
+    let mut schedule = ExecutionSchedule::default();
+    let mut inputs: HashMap<&str, usize> = HashMap::default();
+    // The buffers the value of each node is read from.
+    let mut sources: Vec<Vec<usize>> = Vec::with_capacity(nodes.len());
+    // The value of each node as a strided view of a buffer, where it is one.
+    let mut views: Vec<Option<View>> = Vec::with_capacity(nodes.len());
+    // The views each invocation reads through, which only become descriptors
+    // once every buffer's padding is known.
+    let mut invocation_reads: Vec<Vec<Option<View>>> = Vec::default();
+    for (index, node) in nodes.iter().enumerate() {
+        let enode = node.clone().map_children(|child| ids[usize::from(child)]);
+        let id = egraph.add(enode.clone());
+        ids.push(id);
+        if !reachable.contains(&index) {
+            sources.push(Vec::default());
+            views.push(None);
+            continue;
+        }
+
+        let access = || match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => a,
+            other => panic!("Expected an access pattern, found {:?}", other),
+        };
+        let read = node
+            .children()
+            .iter()
+            .flat_map(|child| sources[usize::from(*child)].iter().cloned())
+            .fold(Vec::default(), |mut read, buffer| {
+                if !read.contains(&buffer) {
+                    read.push(buffer);
+                }
+                read
+            });
+        let (source, view) = match node {
+            &Language::AccessTensor(symbol_id) => match &nodes[usize::from(symbol_id)] {
+                Language::Symbol(name) => {
+                    let buffers = &mut schedule.buffers;
+                    let buffer = *inputs.entry(name.as_str()).or_insert_with(|| {
+                        add_buffer(
+                            buffers,
+                            access().as_vec(),
+                            element_dtype(&egraph, id),
+                            BufferKind::Input { name: name.clone() },
+                        )
+                    });
+                    let view = View::identity(&schedule.buffers[buffer], access().shape.ndim());
+                    (vec![buffer], Some(view))
+                }
+                other => panic!("Expected a symbol, found {:?}", other),
+            },
+            _ if is_invocation(&egraph, &enode) => {
+                let output = add_buffer(
+                    &mut schedule.buffers,
+                    access().as_vec(),
+                    element_dtype(&egraph, id),
+                    BufferKind::Intermediate,
+                );
+                schedule.invocations.push(KernelInvocation {
+                    node: index,
+                    kernel: timed_kind(expr, index),
+                    inputs: read,
+                    reads: Vec::default(),
+                    output,
+                    frees: Vec::default(),
+                });
+                invocation_reads.push(reads(&egraph, &ids, nodes, node, &views));
+                let view = View::identity(&schedule.buffers[output], access().shape.ndim());
+                (vec![output], Some(view))
+            }
+            _ => {
+                let view = match &egraph[id].data {
+                    MyAnalysisData::AccessPattern(result) => {
+                        View::of(&egraph, &schedule.buffers, node, &enode, &views, result)
+                    }
+                    _ => None,
+                };
+                (read, view)
+            }
+        };
+        sources.push(source);
+        views.push(view);
+    }
+
+    let root = nodes.len() - 1;
+    schedule.outputs = match &nodes[root] {
+        Language::Outputs(outputs) => outputs
+            .iter()
+            .flat_map(|output| sources[usize::from(*output)].iter().cloned())
+            .collect(),
+        _ => sources[root].clone(),
+    };
+    for output in &schedule.outputs {
+        if schedule.buffers[*output].kind == BufferKind::Intermediate {
+            schedule.buffers[*output].kind = BufferKind::Output;
+        }
+    }
+
+    // Each intermediate buffer is freed by the last invocation reading it.
+    let mut last_reader = HashMap::new();
+    for (i, invocation) in schedule.invocations.iter().enumerate() {
+        for input in &invocation.inputs {
+            last_reader.insert(*input, i);
+        }
+    }
+    let mut freed = last_reader
+        .into_iter()
+        .filter(|(buffer, _)| schedule.buffers[*buffer].kind == BufferKind::Intermediate)
+        .collect::<Vec<_>>();
+    freed.sort();
+    for (buffer, i) in freed {
+        schedule.invocations[i].frees.push(buffer);
+    }
+
+    // Buffers read padded are allocated with the most padding any read needs
+    // along each axis. A read whose padding conflicts with that of an earlier
+    // read can't read it in place.
+    for read in invocation_reads.iter_mut().flatten() {
+        let conflicts = match read {
+            Some(view) if view.pad_type.is_some() => {
+                let buffer = &mut schedule.buffers[view.buffer];
+                if buffer.pad_type.is_none() || buffer.pad_type == view.pad_type {
+                    buffer.pad_type = view.pad_type;
+                    for (allocated, read) in buffer.padding.iter_mut().zip(&view.padding) {
+                        allocated.0 = allocated.0.max(read.0);
+                        allocated.1 = allocated.1.max(read.1);
+                    }
+                    false
+                } else {
+                    true
+                }
+            }
+            _ => false,
+        };
+        if conflicts {
+            *read = None;
+        }
+    }
+    for buffer in &mut schedule.buffers {
+        let mut stride = 1;
+        buffer.strides = vec![0; buffer.shape.len()];
+        for axis in (0..buffer.shape.len()).rev() {
+            buffer.strides[axis] = stride;
+            stride *= buffer.shape[axis] + buffer.padding[axis].0 + buffer.padding[axis].1;
+        }
+    }
+    let buffers = &schedule.buffers;
+    for (invocation, reads) in schedule.invocations.iter_mut().zip(invocation_reads) {
+        invocation.reads = reads
+            .iter()
+            .map(|view| {
+                view.as_ref()
+                    .map(|view| view.descriptor(&buffers[view.buffer]))
+            })
+            .collect();
+    }
+    schedule.materialization = infer_materialization(expr, &schedule);
+
+    schedule
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analysis() -> MyAnalysis {
+        MyAnalysis {
+            name_to_shape: vec![
+                ("x".to_string(), vec![4, 16]),
+                ("w1".to_string(), vec![8, 16]),
+                ("w2".to_string(), vec![8, 8]),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn two_layers() {
+        let expr: RecExpr<Language> = "
+         (systolic-array 8 8
+          (access
+           (activation-unit relu
+            (systolic-array 16 8
+             (access (access-tensor x) 1)
+             (access (access-transpose (access-tensor w1) (list 1 0)) 0)))
+           1)
+          (access (access-transpose (access-tensor w2) (list 1 0)) 0))"
+            .parse()
+            .unwrap();
+        let schedule = schedule(&expr, analysis());
+
+        assert_eq!(
+            schedule.buffers[..3],
+            [
+                Buffer {
+                    id: 0,
+                    shape: vec![4, 16],
+                    dtype: Some(DataType::Float(32)),
+                    padding: vec![(0, 0), (0, 0)],
+                    pad_type: None,
+                    strides: vec![16, 1],
+                    kind: BufferKind::Input {
+                        name: "x".to_string()
+                    }
+                },
+                Buffer {
+                    id: 1,
+                    shape: vec![8, 16],
+                    dtype: Some(DataType::Float(32)),
+                    padding: vec![(0, 0), (0, 0)],
+                    pad_type: None,
+                    strides: vec![16, 1],
+                    kind: BufferKind::Input {
+                        name: "w1".to_string()
+                    }
+                },
+                Buffer {
+                    id: 2,
+                    shape: vec![4, 8],
+                    dtype: Some(DataType::Float(32)),
+                    padding: vec![(0, 0), (0, 0)],
+                    pad_type: None,
+                    strides: vec![8, 1],
+                    kind: BufferKind::Intermediate
+                },
+            ]
+        );
+        assert_eq!(
+            schedule
+                .invocations
+                .iter()
+                .map(|invocation| (
+                    invocation.kernel.as_str(),
+                    invocation.inputs.clone(),
+                    invocation.output,
+                    invocation.frees.clone()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("systolic-array", vec![0, 1], 2, vec![]),
+                ("activation-unit", vec![2], 3, vec![2]),
+                // w2 is only read here, so its buffer comes after the others.
+                ("systolic-array", vec![3, 4], 5, vec![3]),
+            ]
+        );
+        // w1 is read transposed, in place.
+        assert_eq!(
+            schedule.invocations[0].reads,
+            vec![
+                Some(AccessDescriptor {
+                    buffer: 0,
+                    offset: 0,
+                    shape: vec![4, 16],
+                    access_axis: 1,
+                    strides: vec![16, 1],
+                }),
+                Some(AccessDescriptor {
+                    buffer: 1,
+                    offset: 0,
+                    shape: vec![16, 8],
+                    access_axis: 0,
+                    strides: vec![1, 16],
+                }),
+            ]
+        );
+        assert_eq!(schedule.outputs, vec![5]);
+        assert_eq!(schedule.buffers[5].kind, BufferKind::Output);
+        assert_eq!(schedule.buffers[5].shape, vec![4, 8]);
+        assert_eq!(
+            schedule.buffers[4].kind,
+            BufferKind::Input {
+                name: "w2".to_string()
+            }
+        );
+
+        let json = schedule.to_json();
+        assert_eq!(json["invocations"][1]["kernel"], "activation-unit");
+        assert_eq!(json["buffers"][0]["kind"], "input");
+        assert_eq!(json["buffers"][0]["name"], "x");
+        assert_eq!(json["buffers"][0]["dtype"], "float32");
+        assert_eq!(json["buffers"][0]["pad_type"], serde_json::Value::Null);
+        assert_eq!(json["outputs"][0], 5);
+    }
+
+    #[test]
+    fn multiple_outputs() {
+        // Both outputs read the first layer's result; the second one reads
+        // it through a transpose, which isn't an invocation.
+        let mut expr: RecExpr<Language> = "
+         (compute relu
+          (access
+           (systolic-array 16 8
+            (access (access-tensor x) 1)
+            (access (access-transpose (access-tensor w1) (list 1 0)) 0))
+           1))"
+        .parse()
+        .unwrap();
+        let relu = Id::from(expr.as_ref().len() - 1);
+        let layer = Id::from(
+            expr.as_ref()
+                .iter()
+                .position(|node| matches!(node, Language::SystolicArray(_)))
+                .unwrap(),
+        );
+        let one = expr.add(Language::Num(1));
+        let zero = expr.add(Language::Num(0));
+        let list = expr.add(Language::List(vec![one, zero].into_boxed_slice()));
+        let transposed = expr.add(Language::AccessTranspose([layer, list]));
+        expr.add(Language::Outputs(vec![relu, transposed].into_boxed_slice()));
+        let schedule = schedule(&expr, analysis());
+
+        assert_eq!(
+            schedule
+                .invocations
+                .iter()
+                .map(|invocation| invocation.kernel.as_str())
+                .collect::<Vec<_>>(),
+            vec!["systolic-array", "compute relu"]
+        );
+        assert_eq!(schedule.outputs, vec![3, 2]);
+        // The first layer's result is an output, so it's never freed.
+        assert_eq!(schedule.buffers[2].kind, BufferKind::Output);
+        assert!(schedule
+            .invocations
+            .iter()
+            .all(|invocation| invocation.frees.is_empty()));
+    }
+
+    #[test]
+    fn unreachable_nodes_are_not_scheduled() {
+        let mut expr: RecExpr<Language> = "(compute relu (access (access-tensor w2) 0))"
+            .parse()
+            .unwrap();
+        // A root reading only x.
+        let x = expr.add(Language::Symbol("x".to_string()));
+        let x = expr.add(Language::AccessTensor(x));
+        let axis = expr.add(Language::Num(1));
+        let x = expr.add(Language::Access([x, axis]));
+        let relu = expr.add(Language::ComputeType(crate::language::ComputeType::ReLU));
+        expr.add(Language::Compute([relu, x]));
+
+        let schedule = schedule(&expr, analysis());
+        assert_eq!(schedule.invocations.len(), 1);
+        assert_eq!(schedule.invocations[0].node, expr.as_ref().len() - 1);
+        assert_eq!(schedule.buffers.len(), 2);
+        assert_eq!(
+            schedule.buffers[0].kind,
+            BufferKind::Input {
+                name: "x".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn padded_windows() {
+        // 3x3 max pooling with stride 2, padding x by 1 on every side.
+        let expr: RecExpr<Language> = "
+         (compute reduce-max
+          (access-windows
+           (access-pad
+            (access-pad (access (access-tensor x) 0) zero-padding 0 1 1)
+            zero-padding 1 1 1)
+           (shape 3 3)
+           (shape 2 2)))"
+            .parse()
+            .unwrap();
+        let schedule = schedule(&expr, analysis());
+
+        assert_eq!(schedule.buffers[0].padding, vec![(1, 1), (1, 1)]);
+        assert_eq!(schedule.buffers[0].pad_type, Some(PadType::ZeroPadding));
+        assert_eq!(schedule.buffers[0].strides, vec![18, 1]);
+        // The windows start at the padding's top left corner, two rows or
+        // columns apart.
+        assert_eq!(
+            schedule.invocations[0].reads,
+            vec![Some(AccessDescriptor {
+                buffer: 0,
+                offset: 0,
+                shape: vec![2, 8, 3, 3],
+                access_axis: 2,
+                strides: vec![36, 2, 18, 1],
+            })]
+        );
+        assert_eq!(schedule.to_json()["buffers"][0]["pad_type"], "zero-padding");
+    }
+
+    #[test]
+    fn sliced_and_reversed() {
+        let expr: RecExpr<Language> = "
+         (compute relu
+          (access-reverse (access-slice (access (access-tensor w2) 1) 1 2 6) 0))"
+            .parse()
+            .unwrap();
+        let schedule = schedule(&expr, analysis());
+
+        // Rows are read from the last one up, starting at the third column.
+        assert_eq!(
+            schedule.invocations[0].reads,
+            vec![Some(AccessDescriptor {
+                buffer: 0,
+                offset: 7 * 8 + 2,
+                shape: vec![8, 4],
+                access_axis: 1,
+                strides: vec![-8, 1],
+            })]
+        );
+    }
+
+    #[test]
+    fn conflicting_padding() {
+        // w2 can't be allocated with both kinds of padding, so the second
+        // read must be materialized.
+        let expr: RecExpr<Language> = "
+         (outputs
+          (compute reduce-max (access-pad (access (access-tensor w2) 1) min-padding 1 0 1))
+          (compute relu (access-pad (access (access-tensor w2) 1) zero-padding 1 1 0)))"
+            .parse()
+            .unwrap();
+        let schedule = schedule(&expr, analysis());
+
+        assert_eq!(schedule.buffers[0].padding, vec![(0, 0), (0, 1)]);
+        assert_eq!(schedule.buffers[0].pad_type, Some(PadType::MinPadding));
+        assert_eq!(
+            schedule.invocations[0].reads,
+            vec![Some(AccessDescriptor {
+                buffer: 0,
+                offset: 0,
+                shape: vec![8, 9],
+                access_axis: 1,
+                strides: vec![9, 1],
+            })]
+        );
+        assert_eq!(schedule.invocations[1].reads, vec![None]);
+    }
+}
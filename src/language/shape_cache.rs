@@ -0,0 +1,373 @@
+//! A cache of analysis data, shared between egraphs.
+//!
+//! A design space sweep builds an egraph of the same model for each point it
+//! explores, and [`MyAnalysis`] computes the same shapes for the same subtrees
+//! in every one of them. When an analysis holds a [`ShapeCache`], it looks the
+//! data of each enode up by the enode's contents (its operator and its
+//! children's data), and only computes the data on a miss. Analyses holding
+//! clones of the same handle share the cache, across threads too; other
+//! analyses don't see it.
+//!
+//! Leaves aren't cached: they're cheap to analyze, and the data of some (e.g.
+//! symbols, whose shapes the analysis looks up) doesn't depend on their
+//! contents alone. Neither are enodes whose data depends on anything else
+//! about their children (see [`cacheable`]).
+//! ```
+//! use egg::{EGraph, RecExpr};
+//! use glenside::language::shape_cache::ShapeCache;
+//! use glenside::language::{Language, MyAnalysis};
+//!
+//! let expr: RecExpr<Language> = "(compute relu (access (access-tensor t-32-32) 1))"
+//!     .parse()
+//!     .unwrap();
+//! let cache = ShapeCache::default();
+//! for _ in 0..2 {
+//!     EGraph::new(MyAnalysis {
+//!         shape_cache: Some(cache.clone()),
+//!         ..Default::default()
+//!     })
+//!     .add_expr(&expr);
+//! }
+//! assert_eq!(cache.stats().hits, 3);
+//! ```
+
+use super::{Language, MyAnalysis, MyAnalysisData};
+use crate::error::Result;
+use egg::{EGraph, Id, Language as LanguageTrait};
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+/// How often a cache was hit, since it was created or last cleared.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    /// The number of enodes whose data is cached.
+    pub entries: usize,
+}
+
+/// The contents of an enode, which determine its data.
+#[derive(Clone, PartialEq)]
+struct Key {
+    /// The enode, with its children replaced by 0, so that it only compares
+    /// the operator (and the enode's value, for leaves).
+    op: Language,
+    children: Vec<MyAnalysisData>,
+}
+
+#[derive(Default)]
+struct Cache {
+    /// Entries, bucketed by the hash of their keys. [`MyAnalysisData`] can't
+    /// be hashed directly, so the hash is over its `Debug` output, and
+    /// entries whose keys collide share a bucket.
+    data: HashMap<u64, Vec<(Key, MyAnalysisData)>>,
+    hits: usize,
+    misses: usize,
+}
+
+/// A handle to a cache of analysis data. Clones of a handle share the same
+/// cache; give each analysis which should use it a clone, through
+/// [`MyAnalysis::shape_cache`].
+#[derive(Clone, Default)]
+pub struct ShapeCache(Arc<Mutex<Cache>>);
+
+impl ShapeCache {
+    /// Empties the cache, e.g. between sweeps over different models.
+    pub fn clear(&self) {
+        let mut cache = self.0.lock().unwrap();
+        cache.data.clear();
+        cache.hits = 0;
+        cache.misses = 0;
+    }
+
+    /// The cache's statistics.
+    pub fn stats(&self) -> CacheStats {
+        let cache = self.0.lock().unwrap();
+        CacheStats {
+            hits: cache.hits,
+            misses: cache.misses,
+            entries: cache.data.values().map(Vec::len).sum(),
+        }
+    }
+}
+
+/// Feeds formatted text to a hasher, so that data can be hashed through its
+/// `Debug` implementation without allocating a string.
+struct HashWriter<'a>(&'a mut DefaultHasher);
+
+impl<'a> Write for HashWriter<'a> {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        self.0.write(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// The contents of `enode`, and their hash. Data holding hash maps (e.g. zero
+/// regions) may print in a different order in different egraphs, which only
+/// costs a miss.
+fn key(egraph: &EGraph<Language, MyAnalysis>, enode: &Language) -> (u64, Key) {
+    let key = Key {
+        op: enode.clone().map_children(|_| Id::from(0)),
+        children: enode
+            .children()
+            .iter()
+            .map(|child| egraph[*child].data.clone())
+            .collect(),
+    };
+    let mut hasher = DefaultHasher::new();
+    key.op.hash(&mut hasher);
+    let mut writer = HashWriter(&mut hasher);
+    for data in &key.children {
+        write!(writer, ";{:?}", data).unwrap();
+    }
+    (hasher.finish(), key)
+}
+
+/// Whether the data of `enode` depends only on its [`Key`]. Leaves are
+/// excluded, as above. So is `access-concatenate`, whose data also depends on
+/// whether its second child's eclass holds only Relay operator calls: the
+/// analysis reads that eclass's nodes, which aren't part of the key.
+fn cacheable(enode: &Language) -> bool {
+    !enode.is_leaf()
+        && match enode {
+            Language::AccessConcatenate(_) => false,
+            _ => true,
+        }
+}
+
+/// The data of `enode`, from the analysis's cache if it has one holding it,
+/// or else from `make`. Errors aren't cached.
+pub(crate) fn cached(
+    egraph: &EGraph<Language, MyAnalysis>,
+    enode: &Language,
+    make: impl FnOnce() -> Result<MyAnalysisData>,
+) -> Result<MyAnalysisData> {
+    let cache = match &egraph.analysis.shape_cache {
+        Some(cache) if cacheable(enode) => cache,
+        _ => return make(),
+    };
+
+    let (hash, key) = key(egraph, enode);
+    {
+        let mut cache = cache.0.lock().unwrap();
+        let found = cache.data.get(&hash).and_then(|bucket| {
+            bucket
+                .iter()
+                .find(|(other, _)| *other == key)
+                .map(|(_, data)| data.clone())
+        });
+        if let Some(data) = found {
+            cache.hits += 1;
+            return Ok(data);
+        }
+    }
+
+    // The lock isn't held while computing the data, so that other threads
+    // can use the cache meanwhile.
+    let data = make()?;
+    let mut cache = cache.0.lock().unwrap();
+    cache.misses += 1;
+    let bucket = cache.data.entry(hash).or_default();
+    // Another thread may have added the same enode meanwhile.
+    if !bucket.iter().any(|(other, _)| *other == key) {
+        bucket.push((key, data.clone()));
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egg::RecExpr;
+
+    fn analysis(cache: &ShapeCache) -> MyAnalysis {
+        MyAnalysis {
+            name_to_shape: HashMap::default(),
+            name_to_dtype: HashMap::default(),
+            shape_cache: Some(cache.clone()),
+        }
+    }
+
+    fn sorted_data(egraph: &EGraph<Language, MyAnalysis>) -> Vec<(Id, MyAnalysisData)> {
+        let mut data = egraph
+            .classes()
+            .map(|eclass| (eclass.id, eclass.data.clone()))
+            .collect::<Vec<_>>();
+        data.sort_by_key(|(id, _)| *id);
+        data
+    }
+
+    #[test]
+    fn same_data_as_uncached() {
+        let expr: RecExpr<Language> = "
+         (compute relu
+          (access-windows
+           (access (access-tensor t-3-32-32) 1)
+           (shape 3 3)
+           (shape 1 1)))"
+            .parse()
+            .unwrap();
+
+        let mut uncached = EGraph::new(MyAnalysis::default());
+        uncached.add_expr(&expr);
+
+        let cache = ShapeCache::default();
+        let mut first = EGraph::new(analysis(&cache));
+        first.add_expr(&expr);
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 0,
+                misses: 6,
+                entries: 6
+            }
+        );
+        let mut second = EGraph::new(analysis(&cache));
+        second.add_expr(&expr);
+        assert_eq!(cache.stats().hits, 6);
+
+        assert_eq!(sorted_data(&first), sorted_data(&uncached));
+        assert_eq!(sorted_data(&second), sorted_data(&uncached));
+
+        cache.clear();
+        assert_eq!(cache.stats(), CacheStats::default());
+    }
+
+    #[test]
+    fn caches_are_separate() {
+        let expr: RecExpr<Language> = "(compute relu (access (access-tensor t-32-32) 1))"
+            .parse()
+            .unwrap();
+        let first = ShapeCache::default();
+        let second = ShapeCache::default();
+        EGraph::new(analysis(&first)).add_expr(&expr);
+        EGraph::new(analysis(&second)).add_expr(&expr);
+        EGraph::new(MyAnalysis::default()).add_expr(&expr);
+        assert_eq!(first.stats().hits, 0);
+        assert_eq!(second.stats().hits, 0);
+    }
+
+    #[test]
+    fn colliding_keys() {
+        // Two enodes whose keys land in the same bucket must still get their
+        // own data.
+        let expr: RecExpr<Language> = "
+         (access-pair
+          (access (access-tensor t-32-32) 0)
+          (access (access-tensor t-32-32) 1))"
+            .parse()
+            .unwrap();
+        let cache = ShapeCache::default();
+        let mut egraph = EGraph::new(analysis(&cache));
+        egraph.add_expr(&expr);
+        let access = |axis: usize| {
+            egraph
+                .classes()
+                .flat_map(|eclass| eclass.nodes.iter())
+                .find(|node| match node {
+                    Language::Access([_, axis_id]) => {
+                        MyAnalysis::get_usize(*axis_id, &egraph) == axis
+                    }
+                    _ => false,
+                })
+                .unwrap()
+                .clone()
+        };
+        let (_, first) = key(&egraph, &access(0));
+        let (hash, _) = key(&egraph, &access(1));
+        assert!(first != key(&egraph, &access(1)).1);
+
+        // Simulate a hash collision, by filing the first enode's key under
+        // the second's hash, along with bogus data, in place of the second's
+        // entry.
+        cache
+            .0
+            .lock()
+            .unwrap()
+            .data
+            .insert(hash, vec![(first, MyAnalysisData::Num(0))]);
+        let data = cached(&egraph, &access(1), || Ok(MyAnalysisData::Num(1)));
+        assert_eq!(data, Ok(MyAnalysisData::Num(1)));
+    }
+
+    #[test]
+    fn concatenate_not_cached() {
+        // The data of an access-concatenate depends on the nodes in its
+        // second child's eclass, which its key doesn't capture, so a cached
+        // entry under its key must never be used.
+        let mut name_to_shape = HashMap::default();
+        name_to_shape.insert("a".to_string(), vec![2, 3]);
+        name_to_shape.insert("b".to_string(), vec![4, 3]);
+        let cache = ShapeCache::default();
+        let build = |cache: Option<&ShapeCache>| {
+            let mut egraph = EGraph::new(MyAnalysis {
+                name_to_shape: name_to_shape.clone(),
+                name_to_dtype: HashMap::default(),
+                shape_cache: cache.cloned(),
+            });
+            let a = egraph.add_expr(&"(access (access-tensor a) 1)".parse().unwrap());
+            let b = egraph.add_expr(&"(access (access-tensor b) 1)".parse().unwrap());
+            let axis = egraph.add(Language::Num(0));
+            (egraph, Language::AccessConcatenate([a, b, axis]))
+        };
+
+        let (mut uncached, concatenate) = build(None);
+        let id = uncached.add(concatenate);
+        let expected = uncached[id].data.clone();
+
+        let (mut egraph, concatenate) = build(Some(&cache));
+        let (hash, key) = key(&egraph, &concatenate);
+        cache
+            .0
+            .lock()
+            .unwrap()
+            .data
+            .insert(hash, vec![(key, MyAnalysisData::Num(0))]);
+        let id = egraph.add(concatenate);
+        assert_eq!(egraph[id].data, expected);
+        assert_eq!(cache.stats().hits, 0);
+    }
+
+    /// Building the same egraph repeatedly, as a design space sweep does, is
+    /// faster with a cache. Timing-sensitive, so not run by default; run it
+    /// with `cargo test --release -- --ignored speedup`.
+    #[test]
+    #[ignore]
+    fn speedup() {
+        let mut program = "(access-tensor t-8-32-32)".to_string();
+        for _ in 0..16 {
+            program = format!(
+                "(access-squeeze
+                  (compute dot-product
+                   (access-cartesian-product
+                    (access (access-tensor t-8-8-3-3) 1)
+                    (access-windows
+                     (access (access-pad (access-pad {} zero-padding 1 1 1) zero-padding 2 1 1) 3)
+                     (shape 8 3 3)
+                     (shape 1 1 1))))
+                  1)",
+                program
+            );
+        }
+        let expr: RecExpr<Language> = program.parse().unwrap();
+        let time = |cache: Option<ShapeCache>| {
+            let start = std::time::Instant::now();
+            for _ in 0..50 {
+                EGraph::new(MyAnalysis {
+                    shape_cache: cache.clone(),
+                    ..Default::default()
+                })
+                .add_expr(&expr);
+            }
+            start.elapsed()
+        };
+
+        let uncached = time(None);
+        let cached = time(Some(ShapeCache::default()));
+        assert!(cached < uncached);
+    }
+}
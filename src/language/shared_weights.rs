@@ -0,0 +1,227 @@
+//! Weights shared between operators.
+//!
+//! Siamese networks, attention layers, and tied embeddings read the same
+//! weight at many sites. In an egraph, every read of a weight is the same
+//! `(access-tensor <name>)` eclass, so codegen already passes the weight's
+//! buffer once and every operator reads that one buffer. Extracted
+//! [`RecExpr`]s, however, are trees, in which the weight is read by a separate
+//! copy of the subexpression at each site. [`deduplicate`] restores the
+//! sharing, and [`SharedWeights`] finds the shared weights and where a schedule
+//! should load each of them, so that it is loaded once rather than per use.
+
+use super::{Language, MyAnalysis};
+use egg::{EGraph, Id, Language as LanguageTrait, RecExpr};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// The name of the weight in eclass `id`, if it's an `access-tensor` of a
+/// weight, or an `access` of one.
+fn weight_name(egraph: &EGraph<Language, MyAnalysis>, id: Id) -> Option<String> {
+    egraph[id].nodes.iter().find_map(|node| match node {
+        &Language::Access([id, _]) => weight_name(egraph, id),
+        &Language::AccessTensor(id) => egraph[id].nodes.iter().find_map(|node| match node {
+            Language::Symbol(name) => Some(name.clone()),
+            &Language::TensorDecl([name_id, _]) => {
+                egraph[name_id].nodes.iter().find_map(|node| match node {
+                    Language::Symbol(name) => Some(name.clone()),
+                    _ => None,
+                })
+            }
+            _ => None,
+        }),
+        _ => None,
+    })
+}
+
+/// The operators reading each weight of a program.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SharedWeights {
+    /// For each weight, the eclasses of the operators reading it, in
+    /// ascending order.
+    pub users: BTreeMap<String, Vec<Id>>,
+}
+
+impl SharedWeights {
+    /// Finds the operators reading each weight in the program rooted at `id`
+    /// in `egraph`. Like [`codegen`](crate::codegen::codegen), expects one
+    /// enode per eclass, i.e. an egraph built from an extracted program.
+    pub fn new(egraph: &EGraph<Language, MyAnalysis>, id: Id) -> Self {
+        let mut users: BTreeMap<String, HashSet<Id>> = BTreeMap::default();
+        let mut visited = HashSet::new();
+        let mut stack = vec![egraph.find(id)];
+        while let Some(id) = stack.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+            assert_eq!(
+                egraph[id].nodes.len(),
+                1,
+                "Expected one enode in eclass {}",
+                id
+            );
+            let node = &egraph[id].nodes[0];
+            // `access` and `access-tensor` nodes only name the weight; the
+            // operators they're passed to are what read it.
+            let is_view = matches!(node, Language::Access(_) | Language::AccessTensor(_));
+            for &child in node.children() {
+                let child = egraph.find(child);
+                if !is_view {
+                    if let Some(name) = weight_name(egraph, child) {
+                        users.entry(name).or_default().insert(id);
+                    }
+                }
+                stack.push(child);
+            }
+        }
+
+        SharedWeights {
+            users: users
+                .into_iter()
+                .map(|(name, ids)| {
+                    let mut ids = ids.into_iter().collect::<Vec<_>>();
+                    ids.sort();
+                    (name, ids)
+                })
+                .collect(),
+        }
+    }
+
+    /// The weights read by more than one operator.
+    pub fn shared(&self) -> Vec<&str> {
+        self.users
+            .iter()
+            .filter(|(_, users)| users.len() > 1)
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// For each shared weight, the first eclass in `worklist` which reads it.
+    /// A schedule should load the weight before that eclass runs, and keep it
+    /// loaded for the remaining users.
+    pub fn preload_points(&self, worklist: &[Id]) -> BTreeMap<String, Id> {
+        self.shared()
+            .into_iter()
+            .filter_map(|name| {
+                worklist
+                    .iter()
+                    .find(|id| self.users[name].contains(id))
+                    .map(|&id| (name.to_string(), id))
+            })
+            .collect()
+    }
+}
+
+/// Hash-conses `expr`, so that each distinct subexpression, and in particular
+/// each weight's `access-tensor`, appears in it once. Extracted programs
+/// repeat the subexpressions shared between operators; this turns them back
+/// into directed acyclic graphs.
+pub fn deduplicate(expr: &RecExpr<Language>) -> RecExpr<Language> {
+    let mut deduplicated = RecExpr::default();
+    let mut new_ids: Vec<Id> = Vec::with_capacity(expr.as_ref().len());
+    let mut memo: HashMap<Language, Id> = HashMap::default();
+    for node in expr.as_ref() {
+        let node = node.clone().map_children(|id| new_ids[usize::from(id)]);
+        let id = *memo
+            .entry(node.clone())
+            .or_insert_with(|| deduplicated.add(node));
+        new_ids.push(id);
+    }
+    deduplicated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::generate_worklist_for_codegen;
+    use crate::language::interpreter::{interpret, Value};
+    use ndarray::ArrayD;
+
+    /// Two branches of a siamese network, sharing the weight `w`.
+    const SIAMESE: &str = "
+     (access-concatenate
+      (compute dot-product
+       (access-cartesian-product
+        (access (access-tensor a) 1)
+        (access (access-tensor w) 1)))
+      (compute dot-product
+       (access-cartesian-product
+        (access (access-tensor b) 1)
+        (access (access-tensor w) 1)))
+      0)";
+
+    fn analysis() -> MyAnalysis {
+        MyAnalysis {
+            name_to_shape: vec![
+                ("a".to_string(), vec![2, 4]),
+                ("b".to_string(), vec![2, 4]),
+                ("w".to_string(), vec![3, 4]),
+            ]
+            .into_iter()
+            .collect(),
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        }
+    }
+
+    #[test]
+    fn find_shared_weights() {
+        let expr: RecExpr<Language> = SIAMESE.parse().unwrap();
+        let mut egraph = EGraph::new(analysis());
+        let id = egraph.add_expr(&expr);
+        egraph.rebuild();
+
+        let shared_weights = SharedWeights::new(&egraph, id);
+        assert_eq!(shared_weights.shared(), vec!["w"]);
+        assert_eq!(shared_weights.users["a"].len(), 1);
+        assert_eq!(shared_weights.users["w"].len(), 2);
+
+        // `w` is loaded once, for the first branch the worklist computes.
+        let worklist = generate_worklist_for_codegen(&egraph, id);
+        let preload_points = shared_weights.preload_points(&worklist);
+        assert_eq!(preload_points.len(), 1);
+        let first_user = worklist
+            .iter()
+            .find(|id| shared_weights.users["w"].contains(id))
+            .unwrap();
+        assert_eq!(preload_points["w"], *first_user);
+    }
+
+    #[test]
+    fn deduplicate_siamese() {
+        let expr: RecExpr<Language> = SIAMESE.parse().unwrap();
+        let deduplicated = deduplicate(&expr);
+
+        let access_tensors = |expr: &RecExpr<Language>| {
+            expr.as_ref()
+                .iter()
+                .filter(|node| matches!(node, Language::AccessTensor(_)))
+                .count()
+        };
+        assert_eq!(access_tensors(&expr), 4);
+        assert_eq!(access_tensors(&deduplicated), 3);
+        assert!(deduplicated.as_ref().len() < expr.as_ref().len());
+
+        let mut env = HashMap::default();
+        env.insert(
+            "a",
+            ArrayD::from_shape_fn(vec![2, 4], |i| (i[0] * 4 + i[1]) as i64),
+        );
+        env.insert(
+            "b",
+            ArrayD::from_shape_fn(vec![2, 4], |i| (i[0] + i[1]) as i64 - 3),
+        );
+        env.insert(
+            "w",
+            ArrayD::from_shape_fn(vec![3, 4], |i| (i[0] * i[1]) as i64),
+        );
+        match (
+            interpret(&expr, expr.as_ref().len() - 1, &env),
+            interpret(&deduplicated, deduplicated.as_ref().len() - 1, &env),
+        ) {
+            (Value::Access(expected), Value::Access(actual)) => {
+                assert_eq!(expected.access_axis, actual.access_axis);
+                assert_eq!(expected.tensor, actual.tensor);
+            }
+            _ => panic!(),
+        }
+    }
+}
@@ -0,0 +1,357 @@
+//! Positions of nodes in the text of a program.
+//!
+//! Errors about a node of a large program are hard to act on when they can
+//! only name the node by its index. [`parse_with_spans`] parses a program as
+//! egg does, but also records where in the text each node came from, so that
+//! parse errors and the errors of [`SpannedExpr::add_to_egraph`] can give the
+//! line, column, and text of the offending node.
+
+use super::{check_nodes, check_shape, check_tensor_shape, Language, MyAnalysis};
+use crate::error::{GlensideError, Result};
+use egg::{EGraph, Id, Language as LanguageTrait, RecExpr};
+
+/// The byte range of a node's text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A program, and the span of each of its nodes.
+#[derive(Clone, Debug)]
+pub struct SpannedExpr {
+    pub expr: RecExpr<Language>,
+    /// The span of each node of `expr`, by index.
+    pub spans: Vec<Span>,
+    text: String,
+}
+
+/// The longest snippet of a node's text included in an error.
+const MAX_SNIPPET_LEN: usize = 60;
+
+/// The beginning of the text of `span`, on one line.
+fn snippet(text: &str, span: Span) -> String {
+    let snippet = text[span.start..span.end]
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if snippet.chars().count() > MAX_SNIPPET_LEN {
+        format!(
+            "{}...",
+            snippet.chars().take(MAX_SNIPPET_LEN).collect::<String>()
+        )
+    } else {
+        snippet
+    }
+}
+
+/// Describes the position of `span` in `text` as its line and column (both
+/// starting at 1), followed by the beginning of its text.
+fn describe(text: &str, span: Span) -> String {
+    let line = text[..span.start].matches('\n').count() + 1;
+    let line_start = text[..span.start].rfind('\n').map_or(0, |i| i + 1);
+    let column = text[line_start..span.start].chars().count() + 1;
+    format!("line {}, column {}: {}", line, column, snippet(text, span))
+}
+
+enum Token {
+    Open,
+    Close,
+    Atom(String),
+}
+
+fn tokenize(text: &str) -> Vec<(Token, Span)> {
+    let mut tokens = Vec::default();
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '(' => tokens.push((
+                Token::Open,
+                Span {
+                    start,
+                    end: start + 1,
+                },
+            )),
+            ')' => tokens.push((
+                Token::Close,
+                Span {
+                    start,
+                    end: start + 1,
+                },
+            )),
+            c if c.is_whitespace() => (),
+            _ => {
+                let mut end = start + c.len_utf8();
+                while let Some(&(i, c)) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                tokens.push((
+                    Token::Atom(text[start..end].to_string()),
+                    Span { start, end },
+                ));
+            }
+        }
+    }
+    tokens
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    tokens: Vec<(Token, Span)>,
+    position: usize,
+    expr: RecExpr<Language>,
+    spans: Vec<Span>,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str, span: Span) -> GlensideError {
+        GlensideError::Parse(format!("{} at {}", message, describe(self.text, span)))
+    }
+
+    fn add(&mut self, op: &str, children: Vec<Id>, span: Span) -> Result<Id> {
+        let node = Language::from_op_str(op, children).map_err(|e| self.error(&e, span))?;
+        self.check(&node).map_err(|e| self.error(&e, span))?;
+        self.spans.push(span);
+        Ok(self.expr.add(node))
+    }
+
+    /// The values of `dims`, if they're all literals.
+    fn literal_dims(&self, dims: &[Id]) -> Option<Vec<i64>> {
+        dims.iter()
+            .map(|dim| match self.expr.as_ref()[usize::from(*dim)] {
+                Language::Num(n) => Some(n),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Checks the literal shapes in `node` (see [`check_shape`] and
+    /// [`check_tensor_shape`]), so that a bad shape is reported where it's
+    /// written rather than when it's first used.
+    fn check(&self, node: &Language) -> std::result::Result<(), String> {
+        match node {
+            Language::Shape(dims) => {
+                // Dimensions which aren't literals (e.g. `(+ 1 2)`) are
+                // checked by the analysis instead.
+                for dim in dims.iter() {
+                    if let Some(n) = self.literal_dims(&[*dim]) {
+                        check_shape(&n)?;
+                    }
+                }
+                match self.literal_dims(dims) {
+                    Some(dims) => check_shape(&dims).map(|_| ()),
+                    None => Ok(()),
+                }
+            }
+            &Language::TensorDecl([name_id, shape_id]) => {
+                match (
+                    &self.expr.as_ref()[usize::from(name_id)],
+                    &self.expr.as_ref()[usize::from(shape_id)],
+                ) {
+                    (Language::Symbol(name), Language::Shape(dims)) => {
+                        match self.literal_dims(dims) {
+                            Some(dims) => check_tensor_shape(name, &check_shape(&dims)?),
+                            None => Ok(()),
+                        }
+                    }
+                    _ => Ok(()),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Parses the s-expression starting at the current token, adding its
+    /// nodes in the same order as egg's parser.
+    fn parse_sexp(&mut self) -> Result<Id> {
+        let end_of_text = Span {
+            start: self.text.len(),
+            end: self.text.len(),
+        };
+        let (token, span) = match self.tokens.get(self.position) {
+            Some((token, span)) => (token, *span),
+            None => return Err(self.error("Unexpected end of program", end_of_text)),
+        };
+        self.position += 1;
+        match token {
+            Token::Close => Err(self.error("Unexpected )", span)),
+            Token::Atom(atom) => {
+                let atom = atom.clone();
+                self.add(&atom, vec![], span)
+            }
+            Token::Open => {
+                let op = match self.tokens.get(self.position) {
+                    Some((Token::Atom(op), _)) => op.clone(),
+                    Some((Token::Close, _)) => {
+                        return Err(self.error("Found empty s-expression", span))
+                    }
+                    Some((Token::Open, _)) => {
+                        return Err(self.error("Found a list in the head position", span))
+                    }
+                    None => return Err(self.error("Unclosed (", span)),
+                };
+                self.position += 1;
+                let mut children = Vec::default();
+                loop {
+                    match self.tokens.get(self.position) {
+                        Some((Token::Close, close)) => {
+                            let span = Span {
+                                start: span.start,
+                                end: close.end,
+                            };
+                            self.position += 1;
+                            return self.add(&op, children, span);
+                        }
+                        Some(_) => children.push(self.parse_sexp()?),
+                        None => return Err(self.error("Unclosed (", span)),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Parses a program, recording the span of each node. Produces the same
+/// [`RecExpr`] as egg's parser.
+/// ```
+/// use glenside::language::spans::parse_with_spans;
+///
+/// let error = parse_with_spans("(access\n (access-tensor t)\n 0))").unwrap_err();
+/// assert!(error.to_string().ends_with("line 3, column 4: )"));
+/// ```
+pub fn parse_with_spans(text: &str) -> Result<SpannedExpr> {
+    let mut parser = Parser {
+        text,
+        tokens: tokenize(text),
+        position: 0,
+        expr: RecExpr::default(),
+        spans: Vec::default(),
+    };
+    parser.parse_sexp()?;
+    if let Some((_, span)) = parser.tokens.get(parser.position) {
+        return Err(parser.error("Unexpected text after the program", *span));
+    }
+    Ok(SpannedExpr {
+        expr: parser.expr,
+        spans: parser.spans,
+        text: text.to_string(),
+    })
+}
+
+impl SpannedExpr {
+    /// The line, column, and text of node `index`.
+    pub fn describe(&self, index: usize) -> String {
+        describe(&self.text, self.spans[index])
+    }
+
+    /// The beginning of the text of node `index`, on one line.
+    pub fn snippet(&self, index: usize) -> String {
+        snippet(&self.text, self.spans[index])
+    }
+
+    /// Adds the program to `egraph`, like [`super::try_add_expr`], but
+    /// reports where in the text the node the analysis rejected is. As there,
+    /// `egraph` is unchanged after an error.
+    pub fn add_to_egraph(&self, egraph: &mut EGraph<Language, MyAnalysis>) -> Result<Id> {
+        self.add_nodes_to_egraph(egraph)
+            .map(|ids| *ids.last().unwrap())
+    }
+
+    /// Like [`SpannedExpr::add_to_egraph`], but returns the eclass of every
+    /// node, by index.
+    pub fn add_nodes_to_egraph(
+        &self,
+        egraph: &mut EGraph<Language, MyAnalysis>,
+    ) -> Result<Vec<Id>> {
+        check_nodes(&egraph.analysis, self.expr.as_ref()).map_err(|(index, e)| match e {
+            GlensideError::Analysis(message) => {
+                GlensideError::Analysis(format!("{} at {}", message, self.describe(index)))
+            }
+            e => e,
+        })?;
+        let mut ids: Vec<Id> = Vec::with_capacity(self.expr.as_ref().len());
+        for node in self.expr.as_ref() {
+            let node = node.clone().map_children(|id| ids[usize::from(id)]);
+            ids.push(egraph.add(node));
+        }
+        Ok(ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_as_egg() {
+        let text = "
+         (compute dot-product
+          (access-cartesian-product
+           (access (access-tensor t-32-32) 1)
+           (access (access-tensor t-32-32) 1)))";
+        let spanned = parse_with_spans(text).unwrap();
+        let expr: RecExpr<Language> = text.parse().unwrap();
+        assert_eq!(spanned.expr.as_ref(), expr.as_ref());
+        assert_eq!(spanned.spans.len(), expr.as_ref().len());
+        assert_eq!(
+            spanned.describe(spanned.expr.as_ref().len() - 1),
+            "line 2, column 10: (compute dot-product (access-cartesian-product (access (acce..."
+        );
+        assert_eq!(spanned.describe(0), "line 2, column 19: dot-product");
+    }
+
+    #[test]
+    fn parse_errors() {
+        let message = |text| match parse_with_spans(text) {
+            Err(GlensideError::Parse(message)) => message,
+            _ => panic!(),
+        };
+        assert_eq!(
+            message("(access (access-tensor t) 0"),
+            "Unclosed ( at line 1, column 1: (access (access-tensor t) 0"
+        );
+        assert!(message("(access\n  (access-tensor t))\n)").ends_with("line 3, column 1: )"));
+        assert!(message("(access (access-tensor t) 0)\n  ()").contains("line 2, column 3"));
+    }
+
+    #[test]
+    fn shape_errors() {
+        let message = |text| match parse_with_spans(text) {
+            Err(GlensideError::Parse(message)) => message,
+            _ => panic!(),
+        };
+        assert_eq!(
+            message("(access-windows (access (access-tensor t) 0) (shape 3 -3) (shape 1 1))"),
+            "Negative dimension -3 in shape at line 1, column 46: (shape 3 -3)"
+        );
+        assert!(message("(shape 4294967296 4294967296 4294967296)")
+            .starts_with("Shape [4294967296, 4294967296, 4294967296] has more than"));
+        assert!(message("(access-tensor (tensor-decl x (shape 2 0)))")
+            .starts_with("Tensor x has a dimension of size 0 in shape [2, 0]"));
+        // Zero-sized dimensions are fine elsewhere, e.g. as padding.
+        assert!(parse_with_spans("(shape 0 0 0 0)").is_ok());
+    }
+
+    #[test]
+    fn analysis_error() {
+        let spanned = parse_with_spans(
+            "(access-cartesian-product
+              (access (access-tensor t-32-32) 1)
+              (access (access-tensor t-32-64) 1))",
+        )
+        .unwrap();
+        let mut egraph = EGraph::new(MyAnalysis::default());
+        match spanned.add_to_egraph(&mut egraph) {
+            Err(GlensideError::Analysis(message)) => {
+                assert!(message.ends_with(
+                    "at line 1, column 1: (access-cartesian-product (access (access-tensor t-32-32) 1)..."
+                ))
+            }
+            _ => panic!(),
+        }
+    }
+}
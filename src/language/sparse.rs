@@ -0,0 +1,250 @@
+//! Sparse weights.
+//!
+//! Pruned models have weight matrices which are mostly zeros. A
+//! [`SparseMatrix`] stores such a matrix in blocked compressed sparse row
+//! (BSR) format: the matrix is divided into `block_shape` blocks, and only the
+//! blocks containing a nonzero are stored. With 1x1 blocks, this is plain CSR.
+//!
+//! Weights are made sparse by putting them in a [`SparseEnvironment`]. The
+//! `sparse-dot-product` compute and the `sparse-systolic-array` atom then
+//! multiply by the stored blocks only, when their second argument is
+//! `(access (access-tensor <weight>) 1)` for a sparse weight. Everything else
+//! sees the dense weight.
+
+use super::interpreter::{Environment, Lookup};
+use ndarray::{Array2, ArrayD, ArrayView2, ArrayViewD, Ix2};
+use num_traits::Zero;
+use std::collections::HashMap;
+
+/// A matrix in blocked compressed sparse row format.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SparseMatrix<DataType> {
+    /// The shape of the (dense) matrix.
+    pub shape: [usize; 2],
+    /// The shape of each block. Must evenly divide `shape`.
+    pub block_shape: [usize; 2],
+    /// For each row of blocks, the range of `block_cols` and `blocks` holding
+    /// its stored blocks is `row_offsets[row]..row_offsets[row + 1]`.
+    pub row_offsets: Vec<usize>,
+    /// The column (in blocks) of each stored block.
+    pub block_cols: Vec<usize>,
+    /// The stored blocks, each in row-major order.
+    pub blocks: Vec<DataType>,
+}
+
+impl<DataType> SparseMatrix<DataType>
+where
+    DataType: Copy + Zero + PartialEq,
+{
+    /// Stores the blocks of `dense` which contain a nonzero.
+    pub fn from_dense(dense: ArrayView2<DataType>, block_shape: [usize; 2]) -> Self {
+        let shape = [dense.nrows(), dense.ncols()];
+        assert!(
+            block_shape[0] > 0
+                && block_shape[1] > 0
+                && shape[0] % block_shape[0] == 0
+                && shape[1] % block_shape[1] == 0,
+            "Block shape {:?} doesn't divide matrix shape {:?}",
+            block_shape,
+            shape
+        );
+
+        let mut row_offsets = vec![0];
+        let mut block_cols = Vec::default();
+        let mut blocks = Vec::default();
+        let blocks_per_row = shape[1] / block_shape[1];
+        for (index, block) in dense.exact_chunks(block_shape).into_iter().enumerate() {
+            if block.iter().any(|v| !v.is_zero()) {
+                block_cols.push(index % blocks_per_row);
+                blocks.extend(block.iter().cloned());
+            }
+            if (index + 1) % blocks_per_row == 0 {
+                row_offsets.push(block_cols.len());
+            }
+        }
+
+        SparseMatrix {
+            shape,
+            block_shape,
+            row_offsets,
+            block_cols,
+            blocks,
+        }
+    }
+
+    pub fn to_dense(&self) -> Array2<DataType> {
+        let [block_rows, block_cols] = self.block_shape;
+        let mut dense = Array2::zeros(self.shape);
+        for (block_row, block_col, block) in self.stored_blocks() {
+            dense
+                .slice_mut(ndarray::s![
+                    block_row * block_rows..(block_row + 1) * block_rows,
+                    block_col * block_cols..(block_col + 1) * block_cols
+                ])
+                .assign(&block);
+        }
+        dense
+    }
+
+    /// The stored blocks, with their row and column (in blocks).
+    pub fn stored_blocks(&self) -> impl Iterator<Item = (usize, usize, ArrayView2<'_, DataType>)> {
+        let block_len = self.block_shape[0] * self.block_shape[1];
+        self.row_offsets
+            .windows(2)
+            .enumerate()
+            .flat_map(move |(block_row, range)| {
+                (range[0]..range[1]).map(move |i| {
+                    (
+                        block_row,
+                        self.block_cols[i],
+                        ArrayView2::from_shape(
+                            self.block_shape,
+                            &self.blocks[i * block_len..(i + 1) * block_len],
+                        )
+                        .unwrap(),
+                    )
+                })
+            })
+    }
+
+    /// The fraction of the matrix's elements which are stored.
+    pub fn density(&self) -> f64 {
+        self.blocks.len() as f64 / (self.shape[0] * self.shape[1]) as f64
+    }
+
+    /// Computes `a · selfᵀ`, the dot products of each row of `a` with each row
+    /// of this matrix, touching only the stored blocks. This is how a
+    /// `dot-product` over `(access-cartesian-product a w)` uses a weight `w`.
+    pub fn dot_rows(&self, a: ArrayView2<DataType>) -> Array2<DataType>
+    where
+        DataType: std::ops::Mul<Output = DataType>,
+    {
+        assert_eq!(
+            a.ncols(),
+            self.shape[1],
+            "Expected rows of length {}",
+            self.shape[1]
+        );
+        let [block_rows, block_cols] = self.block_shape;
+        let mut result = Array2::zeros([a.nrows(), self.shape[0]]);
+        for (block_row, block_col, block) in self.stored_blocks() {
+            for (a_row, mut result_row) in a.outer_iter().zip(result.outer_iter_mut()) {
+                let a_row = a_row.slice(ndarray::s![
+                    block_col * block_cols..(block_col + 1) * block_cols
+                ]);
+                for (i, weights) in block.outer_iter().enumerate() {
+                    let sum = result_row[block_row * block_rows + i];
+                    result_row[block_row * block_rows + i] = weights
+                        .iter()
+                        .zip(a_row.iter())
+                        .fold(sum, |acc, (w, a)| acc + *w * *a);
+                }
+            }
+        }
+        result
+    }
+}
+
+/// An environment in which some weights are sparse. The dense form of each
+/// sparse weight is kept alongside it, for the operators which don't exploit
+/// sparsity.
+#[derive(Default)]
+pub struct SparseEnvironment<'a, DataType> {
+    pub dense: Environment<'a, DataType>,
+    pub sparse: HashMap<&'a str, SparseMatrix<DataType>>,
+}
+
+impl<'a, DataType> SparseEnvironment<'a, DataType>
+where
+    DataType: Copy + Zero + PartialEq,
+{
+    /// Adds the 2D weight `name`, stored as `block_shape` blocks.
+    pub fn insert_sparse(
+        &mut self,
+        name: &'a str,
+        weight: ArrayD<DataType>,
+        block_shape: [usize; 2],
+    ) {
+        let weight = weight
+            .into_dimensionality::<Ix2>()
+            .unwrap_or_else(|_| panic!("Sparse weight {} must be a matrix", name));
+        self.sparse
+            .insert(name, SparseMatrix::from_dense(weight.view(), block_shape));
+        self.dense.insert(name, weight.into_dyn());
+    }
+}
+
+impl<'a, DataType> Lookup<DataType> for SparseEnvironment<'a, DataType> {
+    fn lookup(&self, name: &str) -> Option<ArrayViewD<'_, DataType>> {
+        self.dense.lookup(name)
+    }
+
+    fn lookup_sparse(&self, name: &str) -> Option<&SparseMatrix<DataType>> {
+        self.sparse.get(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::interpreter::{interpret, Value};
+    use crate::language::Language;
+    use egg::RecExpr;
+    use ndarray::array;
+
+    #[test]
+    fn csr() {
+        let dense = array![[0, 2, 0, 0], [0, 0, 0, 0], [3, 0, 0, 4]];
+        let sparse = SparseMatrix::from_dense(dense.view(), [1, 1]);
+        assert_eq!(sparse.row_offsets, vec![0, 1, 1, 3]);
+        assert_eq!(sparse.block_cols, vec![1, 0, 3]);
+        assert_eq!(sparse.blocks, vec![2, 3, 4]);
+        assert_eq!(sparse.density(), 0.25);
+        assert_eq!(sparse.to_dense(), dense);
+    }
+
+    #[test]
+    fn blocked() {
+        let dense = array![[1, 2, 0, 0], [3, 4, 0, 0], [0, 0, 0, 0], [0, 0, 0, 5]];
+        let sparse = SparseMatrix::from_dense(dense.view(), [2, 2]);
+        assert_eq!(sparse.row_offsets, vec![0, 1, 2]);
+        assert_eq!(sparse.block_cols, vec![0, 1]);
+        assert_eq!(sparse.blocks, vec![1, 2, 3, 4, 0, 0, 0, 5]);
+        assert_eq!(sparse.to_dense(), dense);
+
+        let a = array![[1, -1, 2, 3], [0, 1, 1, 0]];
+        assert_eq!(sparse.dot_rows(a.view()), a.dot(&dense.t()));
+    }
+
+    #[test]
+    fn interpret_sparse_dot_product() {
+        let a = array![[1, 2, 3, 4], [-1, 0, 2, 1]].into_dyn();
+        let w = array![[0, 0, 1, 0], [0, 0, 0, 0], [2, -1, 0, 0]].into_dyn();
+        let expected = array![[3, 0, 0], [2, 0, -2]].into_dyn();
+
+        let mut dense: Environment<i64> = HashMap::default();
+        dense.insert("a", a.clone());
+        dense.insert("w", w.clone());
+        let mut sparse = SparseEnvironment::default();
+        sparse.dense.insert("a", a);
+        sparse.insert_sparse("w", w, [1, 2]);
+
+        for program in &[
+            "(compute sparse-dot-product
+              (access-cartesian-product (access (access-tensor a) 1) (access (access-tensor w) 1)))",
+            "(sparse-systolic-array 4 3 (access (access-tensor a) 1) (access (access-tensor w) 1))",
+        ] {
+            let expr: RecExpr<Language> = program.parse().unwrap();
+            // Without a sparse weight, the weight is multiplied densely.
+            for env in &[&dense as &dyn Lookup<i64>, &sparse] {
+                match interpret(&expr, expr.as_ref().len() - 1, *env) {
+                    Value::Access(access) => {
+                        assert_eq!(access.access_axis, 2);
+                        assert_eq!(access.tensor, expected);
+                    }
+                    _ => panic!(),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,296 @@
+//! Size statistics for expressions and egraphs, for logging and tooling.
+//!
+//! [`expr_stats`] summarizes a single expression: how many nodes of each
+//! operator it has, how deep it is, how many distinct tensors it names, and
+//! roughly how much memory its intermediate results take up.
+//! [`egraph_stats`] summarizes an egraph, e.g. to track its growth during
+//! equality saturation. Both results can be printed or serialized to JSON.
+
+use super::op_count::{op_count, OpCount};
+use super::{Language, MyAnalysis, MyAnalysisData};
+use egg::{Analysis, EGraph, Id, Language as LanguageTrait, RecExpr};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+
+/// The size in bytes of each element of an intermediate result. The analysis
+/// doesn't track the types of computed values, so they're assumed to be
+/// float32s.
+const INTERMEDIATE_ELEMENT_BYTES: u64 = 4;
+
+/// The name under which `node` is counted. This is the node's operator, e.g.
+/// `access-windows`, except for nodes which carry a value, e.g. a number or a
+/// symbol, which are counted together under the kind of value they carry.
+pub fn operator_kind(node: &Language) -> String {
+    match node {
+        Language::Num(_) => "num".to_string(),
+        Language::DataType(_) => "data-type".to_string(),
+        Language::NotNanFloat64(_) => "float".to_string(),
+        Language::RelayOperator(_) => "relay-operator".to_string(),
+        Language::RelayActivationLayout(_) => "relay-activation-layout".to_string(),
+        Language::RelayKernelLayout(_) => "relay-kernel-layout".to_string(),
+        Language::PadType(_) => "pad-type".to_string(),
+        Language::WindowConvention(_) => "window-convention".to_string(),
+        Language::RoundingMode(_) => "rounding-mode".to_string(),
+        Language::ComputeType(_) => "compute-type".to_string(),
+        Language::AcceleratorFunc(_) => "accelerator-func".to_string(),
+        Language::Symbol(_) => "symbol".to_string(),
+        _ => node.display_op().to_string(),
+    }
+}
+
+/// The result of [`expr_stats`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ExprStats {
+    /// The number of nodes reachable from the root.
+    pub nodes: usize,
+    /// The number of reachable nodes of each kind; see [`operator_kind`].
+    pub operators: BTreeMap<String, usize>,
+    /// The number of nodes on the longest path from the root to a leaf,
+    /// including both.
+    pub depth: usize,
+    /// The number of distinct symbols reachable from the root.
+    pub symbols: usize,
+    /// The bytes needed to hold every intermediate result at once, where an
+    /// intermediate result is the value of a node which performs arithmetic
+    /// (as counted by [`op_count`]). Access pattern manipulations are assumed
+    /// not to copy their inputs.
+    pub intermediate_bytes: u64,
+}
+
+/// The result of [`egraph_stats`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EGraphStats {
+    pub eclasses: usize,
+    pub enodes: usize,
+    /// The number of enodes of each kind; see [`operator_kind`].
+    pub enodes_by_operator: BTreeMap<String, usize>,
+    /// The number of eclasses containing at least one enode of each kind.
+    pub eclasses_by_operator: BTreeMap<String, usize>,
+}
+
+impl ExprStats {
+    /// The statistics as JSON, with fields named as [`ExprStats`]'s are.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap()
+    }
+}
+
+impl EGraphStats {
+    /// The statistics as JSON, with fields named as [`EGraphStats`]'s are.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap()
+    }
+}
+
+impl std::fmt::Display for ExprStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} nodes, depth {}, {} symbols, {} intermediate bytes",
+            self.nodes, self.depth, self.symbols, self.intermediate_bytes
+        )?;
+        for (operator, count) in &self.operators {
+            writeln!(f, "{:>10}  {}", count, operator)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for EGraphStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} eclasses, {} enodes", self.eclasses, self.enodes)?;
+        writeln!(f, "{:>10}  {:>10}  operator", "eclasses", "enodes")?;
+        for (operator, enodes) in &self.enodes_by_operator {
+            writeln!(
+                f,
+                "{:>10}  {:>10}  {}",
+                self.eclasses_by_operator[operator], enodes, operator
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Computes statistics of the expression rooted at the last node of `expr`.
+/// `analysis` provides the shapes of the tensors `expr` refers to.
+///
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::stats::expr_stats;
+/// use glenside::language::MyAnalysis;
+///
+/// let expr: RecExpr<_> = "
+///     (compute relu
+///      (systolic-array 784 512 (access (access-tensor in) 1) (access (access-tensor w1) 0)))"
+///     .parse()
+///     .unwrap();
+/// let stats = expr_stats(&expr, MyAnalysis::default());
+/// assert_eq!(stats.nodes, 13);
+/// assert_eq!(stats.operators["access"], 2);
+/// assert_eq!(stats.operators["num"], 4);
+/// assert_eq!(stats.depth, 5);
+/// assert_eq!(stats.symbols, 2);
+/// // The results of the systolic array and the relu.
+/// assert_eq!(stats.intermediate_bytes, 2 * 512 * 4);
+/// ```
+pub fn expr_stats(expr: &RecExpr<Language>, analysis: MyAnalysis) -> ExprStats {
+    let nodes = expr.as_ref();
+    assert!(!nodes.is_empty(), "Expected a non-empty expression");
+
+    let mut reachable = vec![false; nodes.len()];
+    let mut stack = vec![nodes.len() - 1];
+    while let Some(index) = stack.pop() {
+        if !reachable[index] {
+            reachable[index] = true;
+            stack.extend(nodes[index].children().iter().map(|id| usize::from(*id)));
+        }
+    }
+
+    let mut egraph = EGraph::new(analysis);
+    let mut ids: Vec<Id> = Vec::with_capacity(nodes.len());
+    let mut depths = Vec::with_capacity(nodes.len());
+    let mut operators = BTreeMap::new();
+    let mut symbols = HashSet::new();
+    let mut intermediate_bytes = 0;
+    for (index, node) in nodes.iter().enumerate() {
+        let depth = 1 + node
+            .children()
+            .iter()
+            .map(|id| depths[usize::from(*id)])
+            .max()
+            .unwrap_or(0);
+        depths.push(depth);
+
+        let node = node.clone().map_children(|child| ids[usize::from(child)]);
+        if reachable[index] {
+            *operators.entry(operator_kind(&node)).or_insert(0) += 1;
+            if let Language::Symbol(name) = &node {
+                symbols.insert(name.clone());
+            }
+            if op_count(&egraph, &node) != OpCount::default() {
+                intermediate_bytes += match MyAnalysis::make(&egraph, &node) {
+                    MyAnalysisData::AccessPattern(a) => {
+                        a.as_vec().iter().map(|d| *d as u64).product::<u64>()
+                            * INTERMEDIATE_ELEMENT_BYTES
+                    }
+                    other => panic!("Expected an access pattern, found {:?}", other),
+                };
+            }
+        }
+        ids.push(egraph.add(node));
+    }
+
+    ExprStats {
+        nodes: reachable.iter().filter(|r| **r).count(),
+        operators,
+        depth: depths[nodes.len() - 1],
+        symbols: symbols.len(),
+        intermediate_bytes,
+    }
+}
+
+/// Computes statistics of `egraph`.
+///
+/// ```
+/// use egg::{EGraph, RecExpr};
+/// use glenside::language::stats::egraph_stats;
+/// use glenside::language::MyAnalysis;
+///
+/// let expr: RecExpr<_> = "(access (access-tensor t-32-32) 1)".parse().unwrap();
+/// let mut egraph = EGraph::new(MyAnalysis::default());
+/// egraph.add_expr(&expr);
+/// egraph.rebuild();
+/// let stats = egraph_stats(&egraph);
+/// assert_eq!(stats.eclasses, 4);
+/// assert_eq!(stats.enodes, 4);
+/// assert_eq!(stats.enodes_by_operator["symbol"], 1);
+/// ```
+pub fn egraph_stats<N: Analysis<Language>>(egraph: &EGraph<Language, N>) -> EGraphStats {
+    let mut enodes_by_operator = BTreeMap::new();
+    let mut eclasses_by_operator = BTreeMap::new();
+    for eclass in egraph.classes() {
+        let mut operators = HashSet::new();
+        for enode in &eclass.nodes {
+            let operator = operator_kind(enode);
+            *enodes_by_operator.entry(operator.clone()).or_insert(0) += 1;
+            operators.insert(operator);
+        }
+        for operator in operators {
+            *eclasses_by_operator.entry(operator).or_insert(0) += 1;
+        }
+    }
+
+    EGraphStats {
+        eclasses: egraph.number_of_classes(),
+        enodes: egraph.total_number_of_nodes(),
+        enodes_by_operator,
+        eclasses_by_operator,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egg::Runner;
+
+    #[test]
+    fn shared_and_unreachable_nodes() {
+        // Both operands of the pair are the same node, which is counted once.
+        // The unreachable symbol at the start isn't counted.
+        let mut expr = RecExpr::default();
+        expr.add(Language::Symbol("unused".to_string()));
+        let t = expr.add(Language::Symbol("t-32-32".to_string()));
+        let t = expr.add(Language::AccessTensor(t));
+        let zero = expr.add(Language::Num(0));
+        let a = expr.add(Language::Access([t, zero]));
+        let pair = expr.add(Language::AccessPair([a, a]));
+        let add = expr.add(Language::ComputeType(
+            crate::language::ComputeType::ElementwiseAdd,
+        ));
+        expr.add(Language::Compute([add, pair]));
+
+        let stats = expr_stats(&expr, MyAnalysis::default());
+        assert_eq!(stats.nodes, 7);
+        assert_eq!(stats.depth, 5);
+        assert_eq!(stats.symbols, 1);
+        assert_eq!(stats.operators["symbol"], 1);
+        assert_eq!(stats.operators["access"], 1);
+        assert_eq!(stats.operators["compute-type"], 1);
+        assert_eq!(stats.intermediate_bytes, 32 * 32 * 4);
+    }
+
+    #[test]
+    fn egraph_grows_during_saturation() {
+        let expr: RecExpr<Language> = "
+         (compute dot-product
+          (access-cartesian-product
+           (access (access-tensor t-32-32) 1)
+           (access (access-transpose (access (access-tensor t-32-64) 1) (list 1 0)) 1)))"
+            .parse()
+            .unwrap();
+        let mut egraph = EGraph::new(MyAnalysis::default());
+        egraph.add_expr(&expr);
+        egraph.rebuild();
+        let before = egraph_stats(&egraph);
+        assert!(!before.enodes_by_operator.contains_key("systolic-array"));
+
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&vec![crate::language::rewrites::systolic_array().rewrite]);
+        let after = egraph_stats(&runner.egraph);
+        assert_eq!(after.enodes_by_operator["systolic-array"], 1);
+        assert_eq!(after.eclasses_by_operator["systolic-array"], 1);
+        assert!(after.enodes > before.enodes);
+        assert_eq!(
+            after.enodes,
+            after.enodes_by_operator.values().sum::<usize>()
+        );
+
+        let json = after.to_json();
+        assert_eq!(json["eclasses"], after.eclasses);
+        assert_eq!(
+            after.to_string().lines().count(),
+            2 + after.enodes_by_operator.len()
+        );
+    }
+}
@@ -0,0 +1,303 @@
+//! Where interpretation time goes.
+//!
+//! The interpreter simulates a mapped program's hardware atoms by computing
+//! what they compute (e.g. a `systolic-array` as a matrix multiplication),
+//! while the unmapped program it was mapped from is interpreted as pure access
+//! patterns and `compute`s. [`interpret_timed`] interprets a program one
+//! operator at a time, as
+//! [`interpret_out_of_core`](super::out_of_core::interpret_out_of_core) does,
+//! and accumulates the wall-clock time spent in each kind of operator.
+//! [`TimingComparison`] puts the breakdowns of an unmapped program and of its
+//! mapped counterpart side by side, to show where interpretation cost goes,
+//! and how much of it is spent simulating hardware atoms.
+
+use super::interpreter::{Environment, GlensideScalar, Value};
+use super::out_of_core::{interpret_each_operator, OutOfCoreOptions};
+use super::stats::operator_kind;
+use super::Language;
+use egg::RecExpr;
+use ndarray_npy::{ReadableElement, WritableElement};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::time::Duration;
+
+/// The time spent interpreting the operators of one kind.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OperatorTime {
+    /// How many operators of this kind were interpreted.
+    pub count: usize,
+    pub time: Duration,
+    /// Whether operators of this kind are hardware atoms.
+    pub hardware_atom: bool,
+}
+
+/// The time spent interpreting a program, by operator kind.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Timings {
+    /// The time spent in each kind of operator. Kinds are as in
+    /// [`operator_kind`], except that `compute`s are broken down by compute
+    /// type, e.g. `compute dot-product`.
+    pub by_operator: BTreeMap<String, OperatorTime>,
+}
+
+impl Timings {
+    pub fn total(&self) -> Duration {
+        self.by_operator.values().map(|t| t.time).sum()
+    }
+
+    /// The time spent in hardware atoms.
+    pub fn in_hardware_atoms(&self) -> Duration {
+        self.by_operator
+            .values()
+            .filter(|t| t.hardware_atom)
+            .map(|t| t.time)
+            .sum()
+    }
+}
+
+/// Whether `node` is a hardware atom, as opposed to an access pattern or a
+/// `compute`.
+pub fn is_hardware_atom(node: &Language) -> bool {
+    matches!(
+        node,
+        Language::SystolicArray(_)
+            | Language::SystolicArrayWithBlocking(_)
+            | Language::SystolicArrayWithActivation(_)
+            | Language::SparseSystolicArray(_)
+            | Language::SystolicArrayConv2dNchwOihwWithBlocking(_)
+            | Language::SystolicArrayConv2dNhwcHwioWithBlocking(_)
+            | Language::SystolicArrayConv2dIm2colNchwOihwWithBlocking(_)
+            | Language::SystolicArrayConv2dIm2colNhwcHwioWithBlocking(_)
+            | Language::PoolingUnit(_)
+            | Language::ActivationUnit(_)
+            | Language::VectorAlu(_)
+            | Language::AcceleratorCall(_)
+    )
+}
+
+/// The kind node `index` of `expr` is timed under; see
+/// [`Timings::by_operator`].
+pub(crate) fn timed_kind(expr: &RecExpr<Language>, index: usize) -> String {
+    match &expr.as_ref()[index] {
+        &Language::Compute([compute_type_id, _]) => {
+            format!("compute {}", expr.as_ref()[usize::from(compute_type_id)])
+        }
+        node => operator_kind(node),
+    }
+}
+
+/// Interprets `expr` at `index`, returning its value and the time spent in
+/// each kind of operator. Like
+/// [`interpret_out_of_core`](super::out_of_core::interpret_out_of_core), and
+/// unlike [`interpret`](super::interpreter::interpret), evaluates shared
+/// subexpressions only once. Intermediate values are kept in memory.
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::timing::interpret_timed;
+/// use glenside::language::Language;
+/// use ndarray::array;
+/// use std::collections::HashMap;
+///
+/// let expr: RecExpr<Language> = "(compute relu (access (access-tensor t) 0))".parse().unwrap();
+/// let mut env = HashMap::default();
+/// env.insert("t", array![-1f64, 2.].into_dyn());
+/// let (_, timings) = interpret_timed(&expr, expr.as_ref().len() - 1, &env);
+/// assert_eq!(timings.by_operator["compute relu"].count, 1);
+/// assert_eq!(timings.by_operator["access-tensor"].count, 1);
+/// ```
+pub fn interpret_timed<DataType: 'static>(
+    expr: &RecExpr<Language>,
+    index: usize,
+    env: &Environment<DataType>,
+) -> (Value<'static, DataType>, Timings)
+where
+    DataType: GlensideScalar + WritableElement + ReadableElement,
+{
+    let options = OutOfCoreOptions {
+        spill_threshold_bytes: usize::MAX,
+        ..Default::default()
+    };
+    let mut timings = Timings::default();
+    let value = interpret_each_operator(expr, index, env, &options, &mut |i, time| {
+        let entry = timings.by_operator.entry(timed_kind(expr, i)).or_default();
+        entry.count += 1;
+        entry.time += time;
+        entry.hardware_atom = is_hardware_atom(&expr.as_ref()[i]);
+    });
+    (value, timings)
+}
+
+/// The timings of an unmapped program and of the mapped program it was
+/// mapped to, interpreted on the same inputs. Printing it gives a table of
+/// the time spent in each kind of operator in each program.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimingComparison {
+    pub unmapped: Timings,
+    pub mapped: Timings,
+}
+
+impl TimingComparison {
+    /// Interprets both programs (at their last nodes) on `env`.
+    pub fn new<DataType: 'static>(
+        unmapped: &RecExpr<Language>,
+        mapped: &RecExpr<Language>,
+        env: &Environment<DataType>,
+    ) -> Self
+    where
+        DataType: GlensideScalar + WritableElement + ReadableElement,
+    {
+        TimingComparison {
+            unmapped: interpret_timed(unmapped, unmapped.as_ref().len() - 1, env).1,
+            mapped: interpret_timed(mapped, mapped.as_ref().len() - 1, env).1,
+        }
+    }
+}
+
+impl Display for TimingComparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ms = |time: Duration| time.as_secs_f64() * 1e3;
+        writeln!(
+            f,
+            "unmapped: {:.3}ms; mapped: {:.3}ms, of which {:.3}ms in hardware atoms",
+            ms(self.unmapped.total()),
+            ms(self.mapped.total()),
+            ms(self.mapped.in_hardware_atoms())
+        )?;
+        writeln!(f, "{:>12}  {:>12}  operator", "unmapped ms", "mapped ms")?;
+
+        // Most expensive first.
+        let mut kinds = self
+            .unmapped
+            .by_operator
+            .keys()
+            .chain(self.mapped.by_operator.keys())
+            .collect::<Vec<_>>();
+        kinds.sort();
+        kinds.dedup();
+        let time = |timings: &Timings, kind: &str| {
+            timings
+                .by_operator
+                .get(kind)
+                .map_or(Duration::default(), |t| t.time)
+        };
+        kinds.sort_by_key(|kind| {
+            std::cmp::Reverse(time(&self.unmapped, kind).max(time(&self.mapped, kind)))
+        });
+
+        for kind in kinds {
+            let column = |timings: &Timings| match timings.by_operator.get(kind) {
+                Some(t) => format!("{:.3}", ms(t.time)),
+                None => "-".to_string(),
+            };
+            let hardware_atom = self
+                .mapped
+                .by_operator
+                .get(kind)
+                .map_or(false, |t| t.hardware_atom);
+            writeln!(
+                f,
+                "{:>12}  {:>12}  {}{}",
+                column(&self.unmapped),
+                column(&self.mapped),
+                kind,
+                if hardware_atom {
+                    " (hardware atom)"
+                } else {
+                    ""
+                }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::interpreter::interpret;
+    use ndarray::array;
+    use std::collections::HashMap;
+
+    fn env() -> Environment<'static, f64> {
+        let mut env = HashMap::default();
+        env.insert("x", array![[1., 2., 3.], [4., 5., 6.]].into_dyn());
+        env.insert(
+            "w",
+            array![[1., 0., 0.], [0., 1., 0.], [0., 0., 1.], [1., 1., 1.]].into_dyn(),
+        );
+        env
+    }
+
+    #[test]
+    fn timed_operators() {
+        let expr: RecExpr<Language> = "
+         (compute relu
+          (compute dot-product
+           (access-cartesian-product
+            (access (access-tensor x) 1)
+            (access (access-tensor w) 1))))
+         "
+        .parse()
+        .unwrap();
+        let env = env();
+        let (value, timings) = interpret_timed(&expr, expr.as_ref().len() - 1, &env);
+        match (value, interpret(&expr, expr.as_ref().len() - 1, &env)) {
+            (Value::Access(timed), Value::Access(expected)) => {
+                assert_eq!(timed.tensor, expected.tensor)
+            }
+            _ => panic!(),
+        }
+
+        assert_eq!(timings.by_operator["access-tensor"].count, 2);
+        assert_eq!(timings.by_operator["access"].count, 2);
+        assert_eq!(timings.by_operator["access-cartesian-product"].count, 1);
+        assert_eq!(timings.by_operator["compute dot-product"].count, 1);
+        assert_eq!(timings.by_operator["compute relu"].count, 1);
+        // Leaves aren't evaluated on their own.
+        assert!(!timings.by_operator.contains_key("symbol"));
+        assert_eq!(timings.in_hardware_atoms(), Duration::default());
+    }
+
+    #[test]
+    fn mapped_and_unmapped() {
+        let unmapped: RecExpr<Language> = "
+         (compute dot-product
+          (access-cartesian-product
+           (access (access-tensor x) 1)
+           (access (access-tensor w) 1)))
+         "
+        .parse()
+        .unwrap();
+        let mapped: RecExpr<Language> = "
+         (systolic-array 3 4
+          (access (access-tensor x) 1)
+          (access (access-transpose (access-tensor w) (list 1 0)) 0))
+         "
+        .parse()
+        .unwrap();
+        let comparison = TimingComparison::new(&unmapped, &mapped, &env());
+
+        assert!(comparison.mapped.by_operator["systolic-array"].hardware_atom);
+        assert!(!comparison
+            .unmapped
+            .by_operator
+            .contains_key("systolic-array"));
+        assert!(!comparison
+            .mapped
+            .by_operator
+            .contains_key("compute dot-product"));
+
+        let report = comparison.to_string();
+        let lines = report.lines().collect::<Vec<_>>();
+        assert!(lines[0].starts_with("unmapped: "));
+        assert_eq!(lines[1], " unmapped ms     mapped ms  operator");
+        assert!(lines
+            .iter()
+            .any(|line| line.ends_with("  systolic-array (hardware atom)")));
+        assert!(lines.iter().any(|line| line.trim_start().starts_with("-  ")
+            && line.ends_with("systolic-array (hardware atom)")));
+        assert!(lines
+            .iter()
+            .any(|line| line.ends_with("-  compute dot-product")));
+    }
+}
@@ -0,0 +1,182 @@
+//! Expansion of `unroll` nodes.
+//!
+//! `(unroll <i> <n> <body>)` stands for `n` copies of `body`, the `k`th of
+//! which reads the symbol `i` as `k`, concatenated along axis 0. Frontends
+//! use it to import fixed-length sequence models without unrolling them
+//! themselves. Neither the analysis nor the interpreter handle `unroll`:
+//! [`expand_unrolls`] replaces each with its copies before the egraph is built
+//! ([`lower`](super::lowering::lower) does so first thing).
+
+use super::hash_cons::HashConsBuilder;
+use super::Language;
+use egg::{Id, RecExpr};
+use std::collections::HashMap;
+
+/// The values bound to the iteration variables of the enclosing unrolls,
+/// innermost last.
+type Bindings = Vec<(String, usize)>;
+
+/// Expands every `unroll` in `expr`. The copies are hash-consed, so the
+/// parts of the body which don't depend on the iteration variable are only
+/// included once. Nodes unreachable from the root are dropped.
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::unroll::expand_unrolls;
+/// use glenside::language::Language;
+///
+/// let expr: RecExpr<Language> =
+///     "(unroll t 2 (access-slice (access (access-tensor x) 0) 0 t (+ t 1)))"
+///         .parse()
+///         .unwrap();
+/// let expected: RecExpr<Language> = "
+///  (access-concatenate
+///   (access-slice (access (access-tensor x) 0) 0 0 (+ 0 1))
+///   (access-slice (access (access-tensor x) 0) 0 1 (+ 1 1))
+///   0)"
+///     .parse()
+///     .unwrap();
+/// assert_eq!(expand_unrolls(&expr).pretty(80), expected.pretty(80));
+/// ```
+pub fn expand_unrolls(expr: &RecExpr<Language>) -> RecExpr<Language> {
+    let mut builder = HashConsBuilder::default();
+    expand(
+        expr,
+        Id::from(expr.as_ref().len() - 1),
+        &mut Bindings::default(),
+        &mut builder,
+        &mut HashMap::default(),
+    );
+    builder.build()
+}
+
+/// Adds node `id` of `expr` to `builder`, with unrolls expanded and bound
+/// iteration variables replaced by their values.
+fn expand(
+    expr: &RecExpr<Language>,
+    id: Id,
+    bindings: &mut Bindings,
+    builder: &mut HashConsBuilder,
+    memo: &mut HashMap<(Id, Bindings), Id>,
+) -> Id {
+    if let Some(new_id) = memo.get(&(id, bindings.clone())) {
+        return *new_id;
+    }
+
+    let new_id = match &expr.as_ref()[usize::from(id)] {
+        Language::Symbol(name) => match bindings.iter().rev().find(|(var, _)| var == name) {
+            Some((_, value)) => builder.add(Language::Num(*value as i64)),
+            None => builder.add(Language::Symbol(name.clone())),
+        },
+        &Language::Unroll([var_id, n_id, body_id]) => {
+            let var = match &expr.as_ref()[usize::from(var_id)] {
+                Language::Symbol(var) => var.clone(),
+                other => panic!(
+                    "Expected a symbol as the iteration variable of unroll, found {:?}",
+                    other
+                ),
+            };
+            let n_id = expand(expr, n_id, bindings, builder, memo);
+            let n = match builder.get(n_id) {
+                &Language::Num(n) if n > 0 => n as usize,
+                other => panic!(
+                    "Expected a positive number of iterations for unroll, found {:?}",
+                    other
+                ),
+            };
+
+            let mut result = None;
+            for i in 0..n {
+                bindings.push((var.clone(), i));
+                let copy = expand(expr, body_id, bindings, builder, memo);
+                bindings.pop();
+                result = Some(match result {
+                    None => copy,
+                    Some(previous) => {
+                        let axis_id = builder.add(Language::Num(0));
+                        builder.add(Language::AccessConcatenate([previous, copy, axis_id]))
+                    }
+                });
+            }
+            result.unwrap()
+        }
+        node => {
+            let node = node
+                .clone()
+                .map_children(|child| expand(expr, child, bindings, builder, memo));
+            builder.add(node)
+        }
+    };
+
+    memo.insert((id, bindings.clone()), new_id);
+    new_id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::interpreter::{interpret, Value};
+    use ndarray::{array, ArrayD};
+
+    fn run(expr: &RecExpr<Language>) -> ArrayD<f64> {
+        let mut env = HashMap::default();
+        env.insert("x", array![[1., -2., 3.], [-4., 5., -6.]].into_dyn());
+        env.insert("h", array![[0.5, -0.5, 1.]].into_dyn());
+        match interpret(expr, expr.as_ref().len() - 1, &env) {
+            Value::Access(a) => a.tensor,
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn per_step_slices() {
+        let expr: RecExpr<Language> = "
+         (unroll t 2
+          (compute elementwise-add
+           (access-pair
+            (access-slice (access (access-tensor x) 0) 0 t (+ t 1))
+            (access (access-tensor h) 0))))"
+            .parse()
+            .unwrap();
+        let expanded = expand_unrolls(&expr);
+        assert!(!expanded
+            .as_ref()
+            .iter()
+            .any(|node| matches!(node, Language::Unroll(_))));
+        // The access of h is shared by both copies.
+        assert_eq!(
+            expanded
+                .as_ref()
+                .iter()
+                .filter(|node| **node == Language::Symbol("h".to_string()))
+                .count(),
+            1
+        );
+        assert_eq!(
+            run(&expanded),
+            array![[1.5, -2.5, 4.], [-3.5, 4.5, -5.]].into_dyn()
+        );
+    }
+
+    #[test]
+    fn nested() {
+        let expr: RecExpr<Language> = "
+         (unroll i 2
+          (unroll j 3
+           (access-slice
+            (access-slice (access (access-tensor x) 0) 0 i (+ i 1))
+            1 j (+ j 1))))"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            run(&expand_unrolls(&expr)),
+            array![[1.], [-2.], [3.], [-4.], [5.], [-6.]].into_dyn()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected a positive number of iterations for unroll")]
+    fn zero_iterations() {
+        let expr: RecExpr<Language> = "(unroll t 0 (access (access-tensor x) 0))".parse().unwrap();
+        expand_unrolls(&expr);
+    }
+}
@@ -0,0 +1,217 @@
+//! Human-readable descriptions of access patterns.
+//!
+//! Most bugs in Glenside programs come down to access-axis bookkeeping: an
+//! access viewed as the wrong number of items, or items of the wrong shape.
+//! [`describe`] spells out how an access of a given shape is viewed, e.g.
+//! `access of shape (3, 32, 32) viewed as 3×32 vectors of length 32`, and
+//! [`iteration_structure`] renders the loops which iterate over its items.
+//! Both are also available for interpreted values ([`describe_value`]) and for
+//! eclasses, using the shape analysis ([`describe_eclass`]).
+
+use super::interpreter::Value;
+use super::{Language, MyAnalysis, MyAnalysisData};
+use egg::{EGraph, Id};
+
+fn tuple(dims: &[usize]) -> String {
+    format!(
+        "({})",
+        dims.iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+/// What the items of an access with item shape `item_shape` are, in the
+/// singular and the plural.
+fn item_kind(item_shape: &[usize]) -> (String, String) {
+    match item_shape {
+        [] => ("scalar".to_string(), "scalars".to_string()),
+        [n] => (
+            format!("vector of length {}", n),
+            format!("vectors of length {}", n),
+        ),
+        [_, _] => (
+            format!("matrix of shape {}", tuple(item_shape)),
+            format!("matrices of shape {}", tuple(item_shape)),
+        ),
+        _ => (
+            format!("tensor of shape {}", tuple(item_shape)),
+            format!("tensors of shape {}", tuple(item_shape)),
+        ),
+    }
+}
+
+/// Describes how an access with shape `shape` and item shape `item_shape` is
+/// viewed.
+///
+/// ```
+/// use glenside::language::visualize::describe;
+///
+/// assert_eq!(
+///     describe(&[3, 32], &[32]),
+///     "access of shape (3, 32, 32) viewed as 3×32 vectors of length 32"
+/// );
+/// assert_eq!(
+///     describe(&[], &[3, 32, 32]),
+///     "access of shape (3, 32, 32) viewed as a single tensor of shape (3, 32, 32)"
+/// );
+/// assert_eq!(describe(&[4], &[]), "access of shape (4) viewed as 4 scalars");
+/// ```
+pub fn describe(shape: &[usize], item_shape: &[usize]) -> String {
+    let full_shape = shape
+        .iter()
+        .chain(item_shape.iter())
+        .cloned()
+        .collect::<Vec<_>>();
+    let (singular, plural) = item_kind(item_shape);
+    let items = if shape.is_empty() {
+        format!("a single {}", singular)
+    } else {
+        format!(
+            "{} {}",
+            shape
+                .iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<_>>()
+                .join("×"),
+            plural
+        )
+    };
+    format!("access of shape {} viewed as {}", tuple(&full_shape), items)
+}
+
+/// Renders the loops which iterate over the items of an access with shape
+/// `shape` and item shape `item_shape`, one loop per line, indented by
+/// nesting.
+///
+/// ```
+/// use glenside::language::visualize::iteration_structure;
+///
+/// assert_eq!(
+///     iteration_structure(&[3, 32], &[32]),
+///     "for i0 in 0..3
+///   for i1 in 0..32
+///     vector of length 32 at [i0, i1, :]
+/// "
+/// );
+/// ```
+pub fn iteration_structure(shape: &[usize], item_shape: &[usize]) -> String {
+    let mut out = String::default();
+    for (i, d) in shape.iter().enumerate() {
+        out.push_str(&format!("{}for i{} in 0..{}\n", "  ".repeat(i), i, d));
+    }
+    let index = (0..shape.len())
+        .map(|i| format!("i{}", i))
+        .chain(std::iter::repeat(":".to_string()).take(item_shape.len()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    out.push_str(&format!(
+        "{}{} at [{}]\n",
+        "  ".repeat(shape.len()),
+        item_kind(item_shape).0,
+        index
+    ));
+    out
+}
+
+/// Describes an interpreted value: accesses as [`describe`] does, and other
+/// values by their kind.
+pub fn describe_value<DataType>(value: &Value<DataType>) -> String {
+    match value {
+        Value::Access(a) => describe(
+            &a.tensor.shape()[..a.access_axis],
+            &a.tensor.shape()[a.access_axis..],
+        ),
+        Value::Tensor(t) => format!("tensor of shape {}", tuple(t.shape())),
+        Value::Outputs(values) => format!(
+            "outputs [{}]",
+            values
+                .iter()
+                .map(describe_value)
+                .collect::<Vec<_>>()
+                .join("; ")
+        ),
+        Value::Tuple(values) => format!(
+            "tuple [{}]",
+            values
+                .iter()
+                .map(describe_value)
+                .collect::<Vec<_>>()
+                .join("; ")
+        ),
+        Value::Shape(s) => format!("shape {}", tuple(s.slice())),
+        Value::AccessShape(s, access_axis) => format!(
+            "access shape: {}",
+            describe(&s.slice()[..*access_axis], &s.slice()[*access_axis..])
+        ),
+        Value::List(l) => format!("list {}", tuple(l)),
+        Value::Num(n) => format!("number {}", n),
+        Value::Int32(n) => format!("number {}", n),
+        Value::Int64(n) => format!("number {}", n),
+        Value::Int8(n) => format!("number {}", n),
+        Value::Uint8(n) => format!("number {}", n),
+        Value::ComputeType(t) => format!("compute type {}", t),
+        Value::PadType(t) => format!("pad type {}", t),
+        Value::WindowConvention(c) => format!("window convention {}", c),
+        Value::RoundingMode(m) => format!("rounding mode {:?}", m),
+    }
+}
+
+/// Describes eclass `id` of `egraph` using the shape analysis: access patterns
+/// as [`describe`] does, and other analysis data with its debug formatting.
+pub fn describe_eclass(egraph: &EGraph<Language, MyAnalysis>, id: Id) -> String {
+    match &egraph[id].data {
+        MyAnalysisData::AccessPattern(a) => describe(a.shape.slice(), a.item_shape.slice()),
+        MyAnalysisData::Shape(s) => format!("tensor of shape {}", tuple(s.shape.slice())),
+        other => format!("{:?}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::interpreter::interpret;
+    use egg::RecExpr;
+    use ndarray::ArrayD;
+    use std::collections::HashMap;
+
+    #[test]
+    fn eclass_and_value_agree() {
+        let expr: RecExpr<Language> = "
+         (access-squeeze
+          (access-windows (access (access-tensor t-3-32-32) 0) (shape 3 3 3) (shape 1 1 1))
+          0)"
+        .parse()
+        .unwrap();
+        let mut egraph = EGraph::new(MyAnalysis::default());
+        let id = egraph.add_expr(&expr);
+        assert_eq!(
+            describe_eclass(&egraph, id),
+            "access of shape (30, 30, 3, 3, 3) viewed as 30×30 tensors of shape (3, 3, 3)"
+        );
+
+        let mut env = HashMap::default();
+        env.insert("t-3-32-32", ArrayD::<f64>::zeros(vec![3, 32, 32]));
+        assert_eq!(
+            describe_value(&interpret(&expr, expr.as_ref().len() - 1, &env)),
+            describe_eclass(&egraph, id)
+        );
+    }
+
+    #[test]
+    fn scalar_items() {
+        assert_eq!(
+            describe(&[2, 5], &[]),
+            "access of shape (2, 5) viewed as 2×5 scalars"
+        );
+        assert_eq!(
+            iteration_structure(&[2, 5], &[]),
+            "for i0 in 0..2\n  for i1 in 0..5\n    scalar at [i0, i1]\n"
+        );
+        assert_eq!(
+            iteration_structure(&[], &[4, 4]),
+            "matrix of shape (4, 4) at [:, :]\n"
+        );
+    }
+}
@@ -0,0 +1,476 @@
+//! Offline preprocessing of a mapped program's weights.
+//!
+//! Systolic-array mappings read their weights in a particular layout: a
+//! convolution lowered to a matrix multiplication reads its OIHW filters
+//! flattened to (O, I·H·W), possibly padded out to the array's size, and
+//! transposed. In the mapped program, these are access transformations of the
+//! `access-tensor`s of the weights, which would be recomputed on every
+//! inference even though they depend only on the weights.
+//! [`extract_weight_preprocessing`] splits them out: it replaces each maximal
+//! subexpression which reads only weights by a read of a new, preprocessed
+//! weight, and collects the subexpressions into a separate preprocessing
+//! program (rooted at an `outputs` node, and interpretable like any other), to
+//! be run once, offline.
+
+use super::constant_weights::is_interpretable;
+use super::interpreter::{interpret, Environment, Value};
+use super::multi_output::MultiOutputExpr;
+use super::{Language, MyAnalysis, MyAnalysisData};
+use egg::{EGraph, Id, Language as LanguageTrait, RecExpr};
+use ndarray::ArrayD;
+use std::collections::{HashMap, HashSet};
+
+/// A mapped program whose weight transformations have been split out.
+pub struct WeightPreprocessing {
+    /// The mapped program, reading the preprocessed weights in place of the
+    /// transformations computing them.
+    pub expr: RecExpr<Language>,
+    /// The program computing the preprocessed weights from the original
+    /// weights, with one output per preprocessed weight, named after it.
+    pub preprocessing: MultiOutputExpr,
+    /// The shape of each preprocessed weight.
+    pub shapes: HashMap<String, Vec<usize>>,
+}
+
+impl WeightPreprocessing {
+    /// Runs the preprocessing program on the original `weights`, returning
+    /// the values of the preprocessed weights, by name.
+    pub fn preprocess(&self, weights: &Environment<f64>) -> HashMap<String, ArrayD<f64>> {
+        match interpret(
+            &self.preprocessing.expr,
+            usize::from(self.preprocessing.root()),
+            weights,
+        ) {
+            Value::Outputs(values) => self
+                .preprocessing
+                .names
+                .iter()
+                .cloned()
+                .zip(values.into_iter().map(|value| match value {
+                    Value::Access(a) => a.tensor,
+                    _ => panic!("Expected preprocessed weights to be accesses"),
+                }))
+                .collect(),
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Splits the transformations of the weights named in `weights` out of
+/// `expr`, a mapped program. `analysis` must know the shapes of every tensor
+/// `expr` reads. A bare read of a weight (an `access-tensor`, or an `access`
+/// of one) is left in place, as there's nothing to precompute. A weight
+/// transformed in the same way at several sites is preprocessed once.
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::weight_preprocessing::extract_weight_preprocessing;
+/// use glenside::language::{Language, MyAnalysis};
+/// use std::collections::HashMap;
+///
+/// let expr: RecExpr<Language> = "
+///  (compute dot-product
+///   (access-cartesian-product
+///    (access (access-tensor x) 1)
+///    (access-transpose (access (access-tensor w) 1) (list 1 0))))"
+///     .parse()
+///     .unwrap();
+/// let mut name_to_shape = HashMap::default();
+/// name_to_shape.insert("x".to_string(), vec![2, 3]);
+/// name_to_shape.insert("w".to_string(), vec![3, 4]);
+/// let analysis = MyAnalysis { name_to_shape, name_to_dtype: HashMap::default(), shape_cache: None };
+///
+/// let preprocessed =
+///     extract_weight_preprocessing(&expr, analysis, &vec!["w".to_string()].into_iter().collect());
+/// assert_eq!(preprocessed.preprocessing.names, vec!["w-preprocessed"]);
+/// assert_eq!(preprocessed.shapes["w-preprocessed"], vec![4, 3]);
+/// assert!(preprocessed
+///     .expr
+///     .to_string()
+///     .contains("(access (access-tensor w-preprocessed) 1)"));
+/// ```
+pub fn extract_weight_preprocessing(
+    expr: &RecExpr<Language>,
+    analysis: MyAnalysis,
+    weights: &HashSet<String>,
+) -> WeightPreprocessing {
+    let nodes = expr.as_ref();
+
+    let mut egraph = EGraph::new(analysis);
+    let mut eclasses: Vec<Id> = Vec::with_capacity(nodes.len());
+    // Whether each node depends only on weights and literals, and whether it
+    // reads any weights at all.
+    let mut is_constant = Vec::with_capacity(nodes.len());
+    let mut reads_weights = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        let eclass = egraph.add(node.clone().map_children(|id| eclasses[usize::from(id)]));
+        eclasses.push(eclass);
+        let (constant, reads) = match node {
+            Language::Symbol(name) => (weights.contains(name), weights.contains(name)),
+            _ => (
+                is_interpretable(node)
+                    && node
+                        .children()
+                        .iter()
+                        .all(|child| is_constant[usize::from(*child)]),
+                node.children()
+                    .iter()
+                    .any(|child| reads_weights[usize::from(*child)]),
+            ),
+        };
+        is_constant.push(constant);
+        reads_weights.push(reads);
+    }
+
+    let mut builder = Builder {
+        nodes,
+        egraph: &egraph,
+        eclasses: &eclasses,
+        is_constant: &is_constant,
+        reads_weights: &reads_weights,
+        expr: RecExpr::default(),
+        added: HashMap::default(),
+        preprocessed: Vec::default(),
+        names: HashMap::default(),
+        shapes: HashMap::default(),
+    };
+    if !nodes.is_empty() {
+        builder.add(nodes.len() - 1);
+    }
+
+    let outputs = builder
+        .preprocessed
+        .iter()
+        .map(|(name, expr)| (name.as_str(), expr))
+        .collect::<Vec<_>>();
+    WeightPreprocessing {
+        preprocessing: if outputs.is_empty() {
+            let mut expr = RecExpr::default();
+            expr.add(Language::Outputs(Box::new([])));
+            MultiOutputExpr::from_parts(expr, Vec::default())
+        } else {
+            MultiOutputExpr::new(&outputs)
+        },
+        expr: builder.expr,
+        shapes: builder.shapes,
+    }
+}
+
+/// Rebuilds a mapped program top-down, replacing the first weight
+/// transformation on each path from the root, which is therefore maximal.
+struct Builder<'a> {
+    nodes: &'a [Language],
+    egraph: &'a EGraph<Language, MyAnalysis>,
+    eclasses: &'a [Id],
+    is_constant: &'a [bool],
+    reads_weights: &'a [bool],
+    /// The rebuilt program, and the id in it of each node added so far.
+    expr: RecExpr<Language>,
+    added: HashMap<usize, Id>,
+    /// The name and subexpression of each preprocessed weight, in the order
+    /// they were found.
+    preprocessed: Vec<(String, RecExpr<Language>)>,
+    /// The name of the preprocessed weight computed by each subexpression.
+    names: HashMap<RecExpr<Language>, String>,
+    shapes: HashMap<String, Vec<usize>>,
+}
+
+impl Builder<'_> {
+    /// Whether node `index` only reads a weight, with nothing to precompute.
+    fn is_bare_read(&self, index: usize) -> bool {
+        match &self.nodes[index] {
+            Language::AccessTensor(_) => true,
+            &Language::Access([access_id, _]) => {
+                matches!(
+                    self.nodes[usize::from(access_id)],
+                    Language::AccessTensor(_)
+                )
+            }
+            _ => false,
+        }
+    }
+
+    /// Adds the subexpression rooted at node `index` of the original program
+    /// to `expr`, recording the weights it reads in `weights`, in the order
+    /// they're read.
+    fn add_subexpression(
+        &self,
+        index: usize,
+        expr: &mut RecExpr<Language>,
+        added: &mut HashMap<usize, Id>,
+        weights: &mut Vec<String>,
+    ) -> Id {
+        if let Some(id) = added.get(&index) {
+            return *id;
+        }
+        let node = self.nodes[index].clone();
+        if let Language::Symbol(name) = &node {
+            if !weights.contains(name) {
+                weights.push(name.clone());
+            }
+        }
+        let children = node
+            .children()
+            .iter()
+            .map(|child| self.add_subexpression(usize::from(*child), expr, added, weights))
+            .collect::<Vec<_>>();
+        let mut children = children.into_iter();
+        let id = expr.add(node.map_children(|_| children.next().unwrap()));
+        added.insert(index, id);
+        id
+    }
+
+    /// Replaces node `index`, a weight transformation, with a read of its
+    /// preprocessed weight.
+    fn preprocess(&mut self, index: usize, shape: Vec<usize>, access_axis: usize) -> Id {
+        let mut subexpression = RecExpr::default();
+        let mut weights = Vec::default();
+        self.add_subexpression(
+            index,
+            &mut subexpression,
+            &mut HashMap::default(),
+            &mut weights,
+        );
+        let name = match self.names.get(&subexpression) {
+            Some(name) => name.clone(),
+            None => {
+                let prefix = format!("{}-preprocessed", weights.join("-"));
+                let mut name = prefix.clone();
+                let mut n = 1;
+                while self.shapes.contains_key(&name) {
+                    n += 1;
+                    name = format!("{}-{}", prefix, n);
+                }
+                self.names.insert(subexpression.clone(), name.clone());
+                self.preprocessed.push((name.clone(), subexpression));
+                self.shapes.insert(name.clone(), shape);
+                name
+            }
+        };
+
+        let symbol_id = self.expr.add(Language::Symbol(name));
+        let access_tensor_id = self.expr.add(Language::AccessTensor(symbol_id));
+        if access_axis == 0 {
+            access_tensor_id
+        } else {
+            let axis_id = self.expr.add(Language::Num(access_axis as i64));
+            self.expr.add(Language::Access([access_tensor_id, axis_id]))
+        }
+    }
+
+    /// Adds node `index` of the original program, and what it depends on, to
+    /// the rebuilt program.
+    fn add(&mut self, index: usize) -> Id {
+        if let Some(id) = self.added.get(&index) {
+            return *id;
+        }
+        let egraph = self.egraph;
+        let id = match &egraph[self.eclasses[index]].data {
+            MyAnalysisData::AccessPattern(access)
+                if self.is_constant[index]
+                    && self.reads_weights[index]
+                    && !self.is_bare_read(index) =>
+            {
+                self.preprocess(index, access.as_vec(), access.shape.ndim())
+            }
+            _ => {
+                let node = self.nodes[index].clone();
+                let children = node
+                    .children()
+                    .iter()
+                    .map(|child| self.add(usize::from(*child)))
+                    .collect::<Vec<_>>();
+                let mut children = children.into_iter();
+                self.expr
+                    .add(node.map_children(|_| children.next().unwrap()))
+            }
+        };
+        self.added.insert(index, id);
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{assert_close, Tolerance};
+    use ndarray_rand::{rand_distr::Uniform, RandomExt};
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+
+    fn analysis(shapes: &[(&str, Vec<usize>)]) -> MyAnalysis {
+        MyAnalysis {
+            name_to_shape: shapes
+                .iter()
+                .map(|(name, shape)| (name.to_string(), shape.clone()))
+                .collect(),
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        }
+    }
+
+    /// Checks that the preprocessed program, run on the preprocessed weights,
+    /// computes the same as the original.
+    fn check_equivalent(
+        expr: &RecExpr<Language>,
+        preprocessed: &WeightPreprocessing,
+        shapes: &[(&str, Vec<usize>)],
+    ) {
+        let mut rng = SmallRng::seed_from_u64(23);
+        let env: Environment<f64> = shapes
+            .iter()
+            .map(|(name, shape)| {
+                (
+                    *name,
+                    ArrayD::random_using(shape.clone(), Uniform::new(-1f64, 1f64), &mut rng),
+                )
+            })
+            .collect();
+        let values = preprocessed.preprocess(&env);
+        for (name, value) in values.iter() {
+            assert_eq!(value.shape(), &preprocessed.shapes[name][..]);
+        }
+        let mut preprocessed_env = env.clone();
+        preprocessed_env.extend(
+            values
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.clone())),
+        );
+
+        match (
+            interpret(expr, expr.as_ref().len() - 1, &env),
+            interpret(
+                &preprocessed.expr,
+                preprocessed.expr.as_ref().len() - 1,
+                &preprocessed_env,
+            ),
+        ) {
+            (Value::Access(expected), Value::Access(actual)) => {
+                assert_eq!(expected.access_axis, actual.access_axis);
+                assert_close(&expected.tensor, &actual.tensor, Tolerance::absolute(1e-10));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn conv2d_1x1_as_matmul() {
+        let shapes = vec![("data", vec![2, 8, 5, 5]), ("weights", vec![6, 8, 1, 1])];
+        let expr: RecExpr<Language> = "
+         (compute dot-product
+          (access-cartesian-product
+           (access-reshape
+            (access (access-transpose (access-tensor data) (list 0 2 3 1)) 3)
+            (access-shape (shape 50) (shape 8)))
+           (access-reshape
+            (access (access-tensor weights) 1)
+            (access-shape (shape 6) (shape 8)))))
+         "
+        .parse()
+        .unwrap();
+        let preprocessed = extract_weight_preprocessing(
+            &expr,
+            analysis(&shapes),
+            &vec!["weights".to_string()].into_iter().collect(),
+        );
+
+        assert_eq!(
+            preprocessed.preprocessing.names,
+            vec!["weights-preprocessed"]
+        );
+        assert_eq!(preprocessed.shapes["weights-preprocessed"], vec![6, 8]);
+        assert!(matches!(
+            preprocessed.preprocessing.expr.as_ref()
+                [usize::from(preprocessed.preprocessing.output_ids()[0])],
+            Language::AccessReshape(_)
+        ));
+        // The data's transformations still happen at inference time.
+        assert!(preprocessed
+            .expr
+            .as_ref()
+            .iter()
+            .any(|node| matches!(node, Language::AccessTranspose(_))));
+        assert!(!preprocessed
+            .expr
+            .as_ref()
+            .iter()
+            .any(|node| node == &Language::Symbol("weights".to_string())));
+        check_equivalent(&expr, &preprocessed, &shapes);
+    }
+
+    #[test]
+    fn shared_and_tiled_weights() {
+        let shapes = vec![
+            ("x", vec![4, 6]),
+            ("y", vec![4, 6]),
+            ("w", vec![5, 6]),
+            ("u", vec![8, 6]),
+        ];
+        // `w` is padded out to 8 rows in the same way for both products, and
+        // offset by `u` for one of them.
+        let expr: RecExpr<Language> = "
+         (access-concatenate
+          (compute dot-product
+           (access-cartesian-product
+            (access (access-tensor x) 1)
+            (access-pad (access (access-tensor w) 1) zero-padding 0 0 3)))
+          (compute dot-product
+           (access-cartesian-product
+            (access (access-tensor y) 1)
+            (compute elementwise-add
+             (access-pair
+              (access-pad (access (access-tensor w) 1) zero-padding 0 0 3)
+              (access (access-tensor u) 1)))))
+          0)
+         "
+        .parse()
+        .unwrap();
+        let preprocessed = extract_weight_preprocessing(
+            &expr,
+            analysis(&shapes),
+            &vec!["w".to_string(), "u".to_string()].into_iter().collect(),
+        );
+
+        assert_eq!(
+            preprocessed.preprocessing.names,
+            vec!["w-preprocessed", "w-u-preprocessed"]
+        );
+        assert_eq!(preprocessed.shapes["w-preprocessed"], vec![8, 6]);
+        assert_eq!(preprocessed.shapes["w-u-preprocessed"], vec![8, 6]);
+        // The padding of `w` is computed once, in the preprocessing program.
+        assert_eq!(
+            preprocessed
+                .preprocessing
+                .expr
+                .as_ref()
+                .iter()
+                .filter(|node| matches!(node, Language::AccessPad(_)))
+                .count(),
+            1
+        );
+        assert!(!preprocessed
+            .expr
+            .as_ref()
+            .iter()
+            .any(|node| matches!(node, Language::AccessPad(_))));
+        check_equivalent(&expr, &preprocessed, &shapes);
+    }
+
+    #[test]
+    fn nothing_to_preprocess() {
+        let shapes = vec![("x", vec![2, 3]), ("w", vec![4, 3])];
+        let expr: RecExpr<Language> = "
+         (compute dot-product
+          (access-cartesian-product (access (access-tensor x) 1) (access (access-tensor w) 1)))
+         "
+        .parse()
+        .unwrap();
+        let preprocessed = extract_weight_preprocessing(
+            &expr,
+            analysis(&shapes),
+            &vec!["w".to_string()].into_iter().collect(),
+        );
+        assert!(preprocessed.preprocessing.names.is_empty());
+        assert_eq!(preprocessed.expr.as_ref(), expr.as_ref());
+        check_equivalent(&expr, &preprocessed, &shapes);
+    }
+}
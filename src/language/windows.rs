@@ -0,0 +1,138 @@
+//! Windowing under the conventions of other frameworks.
+//!
+//! An `access-windows` node only places windows which fit within its input.
+//! An `access-windows-with-convention` node places as many windows as its
+//! [`WindowConvention`] calls for, padding its input where they don't fit.
+//! [`padding`] computes that padding for both the analysis and the
+//! interpreter, so the two agree on the node's shape; [`access_windows`]
+//! builds the node.
+
+use super::{Language, PadType, WindowConvention};
+use egg::{Id, RecExpr};
+use std::convert::TryInto;
+
+/// The padding before and after each item axis of an access with item shape
+/// `item_shape` which `access-windows-with-convention` adds before placing
+/// windows of shape `filters_shape`, spaced `strides` apart, under
+/// `convention`. Returns a description of the problem if the arguments don't
+/// describe any windows, e.g. if a window is empty or, under the conventions
+/// which don't pad before the first window, doesn't fit in the axis.
+///
+/// ```
+/// use glenside::language::windows::padding;
+/// use glenside::language::WindowConvention;
+///
+/// assert_eq!(
+///     padding(&[5, 5], &[3, 2], &[1, 2], WindowConvention::Same),
+///     Ok(vec![(1, 1), (0, 1)])
+/// );
+/// assert!(padding(&[2], &[3], &[1], WindowConvention::Ceil).is_err());
+/// ```
+pub fn padding(
+    item_shape: &[usize],
+    filters_shape: &[usize],
+    strides: &[usize],
+    convention: WindowConvention,
+) -> Result<Vec<(usize, usize)>, String> {
+    if item_shape.len() != strides.len() {
+        return Err(format!(
+            "access-windows-with-convention input has {} item axes, but strides have {}",
+            item_shape.len(),
+            strides.len()
+        ));
+    }
+    if filters_shape.len() != strides.len() {
+        return Err(format!(
+            "access-windows-with-convention filters have {} axes, but strides have {}",
+            filters_shape.len(),
+            strides.len()
+        ));
+    }
+
+    item_shape
+        .iter()
+        .zip(filters_shape.iter())
+        .zip(strides.iter())
+        .enumerate()
+        .map(|(axis, ((&dim_len, &kernel_dim_len), &stride))| {
+            let error = |problem: &str| {
+                Err(format!(
+                    "access-windows-with-convention axis {} (input size {}, filter size {}, \
+                     stride {}, {}): {}",
+                    axis, dim_len, kernel_dim_len, stride, convention, problem
+                ))
+            };
+            if kernel_dim_len == 0 {
+                return error("filter is empty");
+            }
+            if stride == 0 {
+                return error("stride must be positive");
+            }
+            if convention != WindowConvention::Same && kernel_dim_len > dim_len {
+                return error("filter is larger than input");
+            }
+            Ok(convention.padding(dim_len, kernel_dim_len, stride))
+        })
+        .collect()
+}
+
+/// Windows the item axes of an access, as `access-windows` does, but
+/// following `convention` when the windows don't fit a whole number of
+/// strides, padding with `pad_type` where needed. Builds an
+/// `access-windows-with-convention` node, which
+/// [`rewrites::access_windows_with_convention_to_access_windows`](super::rewrites::access_windows_with_convention_to_access_windows)
+/// lowers to `access-pad`s and an `access-windows`.
+///
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::windows::access_windows;
+/// use glenside::language::{PadType, WindowConvention};
+/// use std::str::FromStr;
+///
+/// let mut expr = RecExpr::from_str("(access (access-tensor a) 1)").unwrap();
+/// access_windows(
+///     &mut expr,
+///     3.into(),
+///     &[2, 2],
+///     &[2, 2],
+///     WindowConvention::Ceil,
+///     PadType::MinPadding,
+/// );
+/// assert_eq!(
+///     expr.pretty(1000),
+///     "(access-windows-with-convention \
+///       (access (access-tensor a) 1) \
+///       (shape 2 2) \
+///       (shape 2 2) \
+///       ceil-windows \
+///       min-padding)"
+/// );
+/// ```
+pub fn access_windows(
+    expr: &mut RecExpr<Language>,
+    access_id: Id,
+    filters_shape: &[usize],
+    strides: &[usize],
+    convention: WindowConvention,
+    pad_type: PadType,
+) -> Id {
+    let filters_shape_id = shape(expr, filters_shape);
+    let strides_id = shape(expr, strides);
+    let convention_id = expr.add(Language::WindowConvention(convention));
+    let pad_type_id = expr.add(Language::PadType(pad_type));
+    expr.add(Language::AccessWindowsWithConvention([
+        access_id,
+        filters_shape_id,
+        strides_id,
+        convention_id,
+        pad_type_id,
+    ]))
+}
+
+fn shape(expr: &mut RecExpr<Language>, values: &[usize]) -> Id {
+    let ids: Vec<Id> = values
+        .iter()
+        .map(|value| expr.add(Language::Num((*value).try_into().unwrap())))
+        .collect();
+    expr.add(Language::Shape(ids.into_boxed_slice()))
+}
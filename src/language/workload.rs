@@ -0,0 +1,363 @@
+//! Workload characterization.
+//!
+//! Before searching for a mapping of a model onto hardware, it helps to know
+//! what there is to map: which layers do the bulk of the arithmetic, how many
+//! bytes of parameters each needs, and how much arithmetic it does per byte
+//! it moves. [`workload_report`] combines the shape analysis with
+//! [`op_count`] to produce a [`WorkloadReport`], which can be printed as a
+//! table or serialized to JSON.
+//!
+//! A *layer* is any node which performs arithmetic, as counted by
+//! [`op_count`]. A layer's inputs are the layers and named tensors it reaches
+//! through access pattern manipulation alone. Named tensors are *parameters*,
+//! unless they're among the inputs to the model; everything else is an
+//! *activation*.
+
+use super::op_count::{op_count, OpCount};
+use super::{DataType, Language, MyAnalysis, MyAnalysisData};
+use egg::{EGraph, Id, Language as LanguageTrait, RecExpr};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// The size in bytes of the activations computed by layers. The analysis
+/// doesn't track the types of computed values, so they're assumed to be
+/// float32s.
+const ACTIVATION_ELEMENT_BYTES: u64 = 4;
+
+/// One layer of a [`WorkloadReport`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LayerReport {
+    /// The index of the layer's node in the expression.
+    pub node: usize,
+    /// The layer's operator, e.g. `systolic-array` or `compute dot-product`.
+    pub operator: String,
+    /// The shape of the layer's result.
+    pub shape: Vec<usize>,
+    pub ops: OpCount,
+    /// The bytes of parameters the layer reads.
+    pub parameter_bytes: u64,
+    /// The bytes of activations the layer reads and writes.
+    pub activation_bytes: u64,
+    /// Operations per byte of parameters and activations, where a
+    /// multiply-accumulate counts as two operations.
+    pub arithmetic_intensity: f64,
+}
+
+/// The result of [`workload_report`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct WorkloadReport {
+    /// The layers reachable from the root, in the order they appear in the
+    /// expression.
+    pub layers: Vec<LayerReport>,
+    pub total_ops: OpCount,
+    /// The bytes of all parameters, each counted once, even if more than one
+    /// layer reads it.
+    pub total_parameter_bytes: u64,
+}
+
+impl WorkloadReport {
+    /// The report as JSON: an object with `layers`, `total_ops`, and
+    /// `total_parameter_bytes` fields, named as [`WorkloadReport`]'s fields
+    /// are.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap()
+    }
+}
+
+impl std::fmt::Display for WorkloadReport {
+    /// Formats the report as a table, one layer per row, followed by totals.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:>6}  {:<28}  {:<20}  {:>14}  {:>14}  {:>12}  {:>12}  {:>9}",
+            "node",
+            "operator",
+            "shape",
+            "MACs",
+            "elementwise",
+            "param bytes",
+            "act bytes",
+            "ops/byte"
+        )?;
+        for layer in &self.layers {
+            writeln!(
+                f,
+                "{:>6}  {:<28}  {:<20}  {:>14}  {:>14}  {:>12}  {:>12}  {:>9.2}",
+                layer.node,
+                layer.operator,
+                format!("{:?}", layer.shape),
+                layer.ops.macs,
+                layer.ops.elementwise_ops,
+                layer.parameter_bytes,
+                layer.activation_bytes,
+                layer.arithmetic_intensity
+            )?;
+        }
+        writeln!(
+            f,
+            "{:>6}  {:<28}  {:<20}  {:>14}  {:>14}  {:>12}",
+            "",
+            "total",
+            "",
+            self.total_ops.macs,
+            self.total_ops.elementwise_ops,
+            self.total_parameter_bytes
+        )
+    }
+}
+
+fn dtype_bytes(dtype: &DataType) -> u64 {
+    match dtype {
+        DataType::Bool => 1,
+        DataType::Int(bits) | DataType::Float(bits) | DataType::Uint(bits) => {
+            ((*bits + 7) / 8) as u64
+        }
+    }
+}
+
+/// What a layer reads, found by walking down from one of its children.
+enum Input {
+    Layer(usize),
+    Tensor(String),
+}
+
+/// Finds the layers and named tensors reachable from node `index` through
+/// nodes which aren't layers.
+fn find_inputs(
+    nodes: &[Language],
+    is_layer: &[bool],
+    index: usize,
+    inputs: &mut Vec<Input>,
+    visited: &mut HashSet<usize>,
+) {
+    if !visited.insert(index) {
+        return;
+    }
+    match &nodes[index] {
+        _ if is_layer[index] => inputs.push(Input::Layer(index)),
+        Language::AccessTensor(id) => match &nodes[usize::from(*id)] {
+            Language::Symbol(name) => inputs.push(Input::Tensor(name.clone())),
+            _ => find_inputs(nodes, is_layer, usize::from(*id), inputs, visited),
+        },
+        node => {
+            for child in node.children() {
+                find_inputs(nodes, is_layer, usize::from(*child), inputs, visited);
+            }
+        }
+    }
+}
+
+/// Characterizes the workload of `expr`. `analysis` provides the shapes and
+/// dtypes of the tensors `expr` refers to, and `model_inputs` names those
+/// which are inputs to the model, rather than parameters.
+///
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::workload::workload_report;
+/// use glenside::language::{Language, MyAnalysis};
+///
+/// // A dense layer followed by a ReLU.
+/// let expr: RecExpr<Language> = "
+///     (compute relu
+///      (systolic-array 784 512 (access (access-tensor in) 1) (access (access-tensor w1) 0)))"
+///     .parse()
+///     .unwrap();
+/// let report = workload_report(&expr, MyAnalysis::default(), &["in"]);
+/// assert_eq!(report.layers.len(), 2);
+/// assert_eq!(report.layers[0].operator, "systolic-array");
+/// assert_eq!(report.layers[0].ops.macs, 784 * 512);
+/// assert_eq!(report.total_parameter_bytes, 784 * 512 * 4);
+/// assert_eq!(report.layers[1].operator, "compute relu");
+/// assert_eq!(report.layers[1].parameter_bytes, 0);
+/// ```
+pub fn workload_report(
+    expr: &RecExpr<Language>,
+    analysis: MyAnalysis,
+    model_inputs: &[&str],
+) -> WorkloadReport {
+    let nodes = expr.as_ref();
+    assert!(!nodes.is_empty(), "Expected a non-empty expression");
+
+    let mut egraph = EGraph::new(analysis);
+    let mut ids: Vec<Id> = Vec::with_capacity(nodes.len());
+    let mut ops = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        let node = node.clone().map_children(|child| ids[usize::from(child)]);
+        ops.push(op_count(&egraph, &node));
+        ids.push(egraph.add(node));
+    }
+    let is_layer = ops
+        .iter()
+        .map(|ops| *ops != OpCount::default())
+        .collect::<Vec<_>>();
+
+    let output_elements = |index: usize| match &egraph[ids[index]].data {
+        MyAnalysisData::AccessPattern(a) => a.as_vec().iter().map(|d| *d as u64).product::<u64>(),
+        other => panic!(
+            "Expected layer {} to produce an access, found {:?}",
+            index, other
+        ),
+    };
+    let mut tensor_bytes = HashMap::new();
+    for (index, node) in nodes.iter().enumerate() {
+        if let Language::Symbol(name) = node {
+            if let MyAnalysisData::Shape(s) = &egraph[ids[index]].data {
+                let elements: u64 = s.shape.slice().iter().map(|d| *d as u64).product();
+                tensor_bytes.insert(name.clone(), elements * dtype_bytes(&s.dtype));
+            }
+        }
+    }
+
+    let mut reachable = HashSet::new();
+    let mut stack = vec![nodes.len() - 1];
+    while let Some(index) = stack.pop() {
+        if reachable.insert(index) {
+            stack.extend(nodes[index].children().iter().map(|id| usize::from(*id)));
+        }
+    }
+
+    let mut layers = Vec::default();
+    let mut parameters = HashSet::new();
+    for index in (0..nodes.len()).filter(|i| is_layer[*i] && reachable.contains(i)) {
+        let mut inputs = Vec::default();
+        let mut visited = HashSet::new();
+        for child in nodes[index].children() {
+            find_inputs(
+                nodes,
+                &is_layer,
+                usize::from(*child),
+                &mut inputs,
+                &mut visited,
+            );
+        }
+
+        let mut parameter_bytes = 0;
+        let mut activation_bytes = output_elements(index) * ACTIVATION_ELEMENT_BYTES;
+        for input in inputs {
+            match input {
+                Input::Layer(i) => {
+                    activation_bytes += output_elements(i) * ACTIVATION_ELEMENT_BYTES
+                }
+                Input::Tensor(name) if model_inputs.contains(&name.as_str()) => {
+                    activation_bytes += tensor_bytes[&name]
+                }
+                Input::Tensor(name) => {
+                    parameter_bytes += tensor_bytes[&name];
+                    parameters.insert(name);
+                }
+            }
+        }
+
+        let operator = match &nodes[index] {
+            Language::Compute([compute_type_id, _])
+            | Language::ComputeWithAccumulator([compute_type_id, _, _]) => format!(
+                "{} {}",
+                nodes[index].display_op(),
+                nodes[usize::from(*compute_type_id)].display_op()
+            ),
+            node => node.display_op().to_string(),
+        };
+        let ops = ops[index];
+        layers.push(LayerReport {
+            node: index,
+            operator,
+            shape: match &egraph[ids[index]].data {
+                MyAnalysisData::AccessPattern(a) => a.as_vec(),
+                _ => unreachable!(),
+            },
+            ops,
+            parameter_bytes,
+            activation_bytes,
+            arithmetic_intensity: (2 * ops.macs + ops.elementwise_ops) as f64
+                / (parameter_bytes + activation_bytes) as f64,
+        });
+    }
+
+    WorkloadReport {
+        total_ops: layers.iter().map(|layer| layer.ops).sum(),
+        total_parameter_bytes: parameters.iter().map(|name| tensor_bytes[name]).sum(),
+        layers,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mlp() -> WorkloadReport {
+        let expr: RecExpr<Language> = "
+         (systolic-array 512 10
+          (access
+           (compute relu
+            (systolic-array 512 512
+             (access
+              (compute relu
+               (systolic-array 784 512 (access (access-tensor in) 1) (access (access-tensor w1) 0)))
+              1)
+             (access (access-tensor w2) 0)))
+           1)
+          (access (access-tensor w3) 0))"
+            .parse()
+            .unwrap();
+        workload_report(&expr, MyAnalysis::default(), &["in"])
+    }
+
+    #[test]
+    fn layers() {
+        let report = mlp();
+        assert_eq!(
+            report
+                .layers
+                .iter()
+                .map(|layer| (layer.operator.as_str(), layer.shape.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("systolic-array", vec![1, 512]),
+                ("compute relu", vec![1, 512]),
+                ("systolic-array", vec![1, 512]),
+                ("compute relu", vec![1, 512]),
+                ("systolic-array", vec![1, 10]),
+            ]
+        );
+        assert_eq!(
+            report.total_ops,
+            OpCount {
+                macs: 784 * 512 + 512 * 512 + 512 * 10,
+                elementwise_ops: 2 * 512
+            }
+        );
+        assert_eq!(
+            report.total_parameter_bytes,
+            (784 * 512 + 512 * 512 + 512 * 10) * 4
+        );
+
+        // The first layer reads the model's input and w1, and writes its
+        // result.
+        let first = &report.layers[0];
+        assert_eq!(first.parameter_bytes, 784 * 512 * 4);
+        assert_eq!(first.activation_bytes, (784 + 512) * 4);
+        assert_eq!(
+            first.arithmetic_intensity,
+            (2 * 784 * 512) as f64 / ((784 * 512 + 784 + 512) * 4) as f64
+        );
+        // The second layer reads the first's result.
+        assert_eq!(report.layers[1].activation_bytes, 2 * 512 * 4);
+    }
+
+    #[test]
+    fn table_and_json() {
+        let report = mlp();
+        let table = report.to_string();
+        assert_eq!(table.lines().count(), 1 + 5 + 1);
+        assert!(table.lines().last().unwrap().contains("total"));
+
+        let json = report.to_json();
+        assert_eq!(json["layers"].as_array().unwrap().len(), 5);
+        assert_eq!(json["layers"][4]["shape"], serde_json::json!([1, 10]));
+        assert_eq!(json["layers"][4]["ops"]["macs"], 512 * 10);
+        assert_eq!(
+            json["total_parameter_bytes"],
+            (784 * 512 + 512 * 512 + 512 * 10) * 4
+        );
+    }
+}
@@ -0,0 +1,352 @@
+//! Legality checks for mapping computations onto hardware atoms.
+//!
+//! The rewrites which introduce atoms (see [`HardwareSpec::rewrites`]) only
+//! check that a computation has the form the atom computes, e.g. that a dot
+//! product is a matrix multiplication. Real atoms have further limits: a
+//! systolic array's accumulators may only support reductions up to some
+//! depth, and an atom may only support some element types. These limits are
+//! given per atom in the hardware spec's `legality` section, e.g.
+//! ```json
+//! "legality": {
+//!   "systolic-array": { "max_depth": 256, "dtypes": ["int8"] },
+//!   "pooling-unit": { "max_depth": 9 }
+//! }
+//! ```
+//! and [`Legality::restrict`] makes an atom's rewrites refuse to fire where
+//! the atom doesn't support the computation. Each rewrite which introduces an
+//! atom is an [`AtomRewrite`], which declares the variables bound to the
+//! atom's inputs and how to find the depth of the reduction it matches.
+
+use crate::hardware::{Atom, HardwareSpec};
+use crate::language::rewrites::{AtomRewrite, Depth};
+use crate::language::{DataType, Language, MyAnalysis, MyAnalysisData};
+use egg::{Applier, EGraph, Id, PatternAst, Rewrite, Subst, Symbol, Var};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+type EG = EGraph<Language, MyAnalysis>;
+
+/// Limits on the computations an atom supports.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Legality {
+    /// The most elements reduced into each output: for systolic arrays, the K
+    /// of an MxK by KxN multiplication; for pooling units, the number of
+    /// elements in each window. Unlimited if unset.
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    /// The element types the atom's inputs may have, e.g. `["int8"]`. Any, if
+    /// empty.
+    #[serde(default, with = "dtype_names")]
+    pub dtypes: Vec<DataType>,
+}
+
+/// (De)serializes data types by name, e.g. `int8`.
+mod dtype_names {
+    use crate::language::DataType;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(dtypes: &[DataType], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(dtypes.iter().map(|dtype| dtype.to_string()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<DataType>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|name| name.parse().map_err(D::Error::custom))
+            .collect()
+    }
+}
+
+/// The element type of the tensor or access pattern in `eclass`, found by
+/// following access pattern manipulations back to the tensors they access,
+/// or to the casts which produce them. `None` if it can't be determined.
+pub fn element_dtype(egraph: &EG, eclass: Id) -> Option<DataType> {
+    fn find(egraph: &EG, eclass: Id, visited: &mut HashSet<Id>) -> Option<DataType> {
+        let eclass = egraph.find(eclass);
+        if !visited.insert(eclass) {
+            return None;
+        }
+        egraph[eclass].nodes.iter().find_map(|node| match node {
+            &Language::Cast([dtype, _]) | &Language::Requantize([dtype, ..]) => {
+                match &egraph[dtype].data {
+                    MyAnalysisData::DataType(dtype) => Some(*dtype),
+                    _ => None,
+                }
+            }
+            &Language::AccessTensor(tensor) => match &egraph[tensor].data {
+                MyAnalysisData::Shape(s) => Some(s.dtype),
+                _ => None,
+            },
+            _ => node
+                .children()
+                .iter()
+                .filter(|&&child| matches!(egraph[child].data, MyAnalysisData::AccessPattern(_)))
+                .find_map(|&child| find(egraph, child, visited)),
+        })
+    }
+    find(egraph, eclass, &mut HashSet::default())
+}
+
+impl Legality {
+    /// Whether an atom with these limits can reduce `depth` elements into
+    /// each output (if it reduces at all) of inputs in the eclasses `inputs`.
+    /// Inputs whose element type can't be determined are assumed to be
+    /// supported.
+    pub fn allows(&self, egraph: &EG, inputs: &[Id], depth: Option<usize>) -> bool {
+        if let (Some(max_depth), Some(depth)) = (self.max_depth, depth) {
+            if depth > max_depth {
+                return false;
+            }
+        }
+        self.dtypes.is_empty()
+            || inputs
+                .iter()
+                .filter_map(|&input| element_dtype(egraph, input))
+                .all(|dtype| self.dtypes.contains(&dtype))
+    }
+
+    /// Restricts `rewrite`, which maps computations onto an atom, so that it
+    /// only fires on computations these limits allow.
+    pub fn restrict(&self, rewrite: AtomRewrite) -> Rewrite<Language, MyAnalysis> {
+        let AtomRewrite {
+            mut rewrite,
+            inputs,
+            depth,
+        } = rewrite;
+        if *self == Legality::default() {
+            return rewrite;
+        }
+        rewrite.applier = Arc::new(LegalApplier {
+            inner: rewrite.applier.clone(),
+            inputs,
+            depth,
+            legality: self.clone(),
+        });
+        rewrite
+    }
+}
+
+/// Applies the wrapped applier only where `legality` allows.
+struct LegalApplier {
+    inner: Arc<dyn Applier<Language, MyAnalysis> + Send + Sync>,
+    inputs: Vec<Var>,
+    depth: Option<Depth>,
+    legality: Legality,
+}
+
+impl Applier<Language, MyAnalysis> for LegalApplier {
+    fn apply_one(
+        &self,
+        egraph: &mut EG,
+        eclass: Id,
+        subst: &Subst,
+        searcher_ast: Option<&PatternAst<Language>>,
+        rule_name: Symbol,
+    ) -> Vec<Id> {
+        let inputs: Vec<Id> = self
+            .inputs
+            .iter()
+            .filter_map(|&var| subst.get(var).copied())
+            .collect();
+        let depth = self.depth.as_ref().and_then(|depth| depth(egraph, subst));
+        if self.legality.allows(egraph, &inputs, depth) {
+            self.inner
+                .apply_one(egraph, eclass, subst, searcher_ast, rule_name)
+        } else {
+            vec![]
+        }
+    }
+
+    fn vars(&self) -> Vec<Var> {
+        self.inner.vars()
+    }
+}
+
+/// The limits `spec` gives for `atom`; none, if it doesn't give any.
+pub fn legality(spec: &HardwareSpec, atom: &Atom) -> Legality {
+    spec.legality.get(atom.name()).cloned().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::GlensideError;
+    use egg::{Pattern, RecExpr, Runner, Searcher};
+    use std::collections::HashMap;
+
+    fn spec_with(legality: &str) -> HardwareSpec {
+        HardwareSpec::from_json(&format!(
+            r#"{{
+              "atoms": [
+                {{ "atom": "systolic-array", "rows": 16, "cols": 16 }},
+                {{ "atom": "pooling-unit" }}
+              ],
+              "legality": {},
+              "sram_bytes": 262144,
+              "dram_bandwidth": 1e9,
+              "frequency": 5e8
+            }}"#,
+            legality
+        ))
+        .unwrap()
+    }
+
+    /// Runs `spec`'s rewrites over `program`, returning the egraph and the
+    /// eclass of its root.
+    fn run(spec: &HardwareSpec, program: &str, dtypes: &[(&str, DataType)]) -> (EG, Id) {
+        let mut name_to_shape = HashMap::default();
+        name_to_shape.insert("deep".to_string(), vec![16, 64]);
+        name_to_shape.insert("shallow".to_string(), vec![16, 16]);
+        name_to_shape.insert("image".to_string(), vec![8, 8]);
+        name_to_shape.insert("bias".to_string(), vec![16]);
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape,
+            name_to_dtype: dtypes
+                .iter()
+                .map(|(name, dtype)| (name.to_string(), *dtype))
+                .collect(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&program.parse::<RecExpr<Language>>().unwrap());
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&spec.rewrites());
+        let id = runner.egraph.find(id);
+        (runner.egraph, id)
+    }
+
+    /// Runs `spec`'s rewrites over `program`, returning whether its root was
+    /// mapped onto an atom.
+    fn mapped(spec: &HardwareSpec, program: &str, dtypes: &[(&str, DataType)]) -> bool {
+        let (egraph, id) = run(spec, program, dtypes);
+        egraph[id]
+            .nodes
+            .iter()
+            .any(|node| !matches!(node, Language::Compute(_)))
+    }
+
+    fn matmul(tensor: &str) -> String {
+        format!(
+            "(compute dot-product
+              (access-cartesian-product
+               (access (access-tensor {0}) 1)
+               (access (access-tensor {0}) 1)))",
+            tensor
+        )
+    }
+
+    #[test]
+    fn max_depth() {
+        let spec = spec_with(r#"{ "systolic-array": { "max_depth": 32 } }"#);
+        // K = 16.
+        assert!(mapped(&spec, &matmul("shallow"), &[]));
+        // K = 64.
+        assert!(!mapped(&spec, &matmul("deep"), &[]));
+        // Without limits, anything goes.
+        assert!(mapped(&spec_with("{}"), &matmul("deep"), &[]));
+
+        // 3x3 windows reduce 9 elements each.
+        let pool = "(compute reduce-max
+                     (access-windows (access (access-tensor image) 0)
+                      (shape 3 3) (shape 1 1)))";
+        assert!(mapped(
+            &spec_with(r#"{ "pooling-unit": { "max_depth": 9 } }"#),
+            pool,
+            &[]
+        ));
+        assert!(!mapped(
+            &spec_with(r#"{ "pooling-unit": { "max_depth": 4 } }"#),
+            pool,
+            &[]
+        ));
+    }
+
+    #[test]
+    fn fused_bias_add() {
+        // Fusing the bias into the weights adds a row, so the fused array
+        // reduces K + 1 = 17 elements into each output.
+        let program = "(bias-add
+                        (systolic-array 16 16
+                         (access (access-tensor shallow) 1)
+                         (access (access-tensor shallow) 0))
+                        (access-tensor bias) 1)";
+        let fused = |legality: &str| {
+            let (egraph, id) = run(&spec_with(legality), program, &[]);
+            "(systolic-array 17 16 ?a0 ?a1)"
+                .parse::<Pattern<Language>>()
+                .unwrap()
+                .search_eclass(&egraph, id)
+                .is_some()
+        };
+        assert!(fused(r#"{ "systolic-array": { "max_depth": 17 } }"#));
+        assert!(!fused(r#"{ "systolic-array": { "max_depth": 16 } }"#));
+    }
+
+    #[test]
+    fn dtypes() {
+        let spec = spec_with(r#"{ "systolic-array": { "dtypes": ["int8"] } }"#);
+        assert_eq!(
+            legality(&spec, &spec.atoms[0]).dtypes,
+            vec![DataType::Int(8)]
+        );
+        assert!(mapped(
+            &spec,
+            &matmul("shallow"),
+            &[("shallow", DataType::Int(8))]
+        ));
+        // Tensors are float32 by default.
+        assert!(!mapped(&spec, &matmul("shallow"), &[]));
+        // Casts determine the element type of what they produce.
+        assert!(mapped(
+            &spec,
+            "(compute dot-product
+              (access-cartesian-product
+               (cast int8 (access (access-tensor shallow) 1))
+               (cast int8 (access (access-tensor shallow) 1))))",
+            &[]
+        ));
+    }
+
+    #[test]
+    fn element_dtypes() {
+        let mut egraph = EGraph::new(MyAnalysis::default());
+        let transposed = egraph.add_expr(
+            &"(access-transpose (access (access-tensor t-32-32) 1) (list 1 0))"
+                .parse()
+                .unwrap(),
+        );
+        let cast = egraph.add_expr(
+            &"(cast uint8 (access (access-tensor t-32-32) 1))"
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(
+            element_dtype(&egraph, transposed),
+            Some(DataType::Float(32))
+        );
+        assert_eq!(element_dtype(&egraph, cast), Some(DataType::Uint(8)));
+    }
+
+    #[test]
+    fn unknown_atom() {
+        match HardwareSpec::from_json(
+            r#"{ "atoms": [], "legality": { "tpu": {} }, "sram_bytes": 1,
+                 "dram_bandwidth": 1, "frequency": 1 }"#,
+        ) {
+            Err(GlensideError::Parse(message)) => assert!(message.contains("tpu")),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn unknown_dtype() {
+        assert!(HardwareSpec::from_json(
+            r#"{ "atoms": [], "legality": { "vector-alu": { "dtypes": ["posit8"] } },
+                 "sram_bytes": 1, "dram_bandwidth": 1, "frequency": 1 }"#,
+        )
+        .is_err());
+    }
+}
@@ -8,8 +8,29 @@
 //! Parts of Glenside (namely extraction; see [`glenside::extraction`]) rely on
 //! IBM's CPLEX solver. You can disable these components by not enabling
 //! Glenside's `cplex` feature.
+//!
+//! The interpreter (see [`glenside::language::interpreter`]) computes matrix
+//! multiplications one element at a time, which is too slow to validate full
+//! networks. Glenside's `fast-matmul` feature instead multiplies `f32` and
+//! `f64` matrices using `ndarray`'s optimized routines; the `blas` feature
+//! further routes them through BLAS, which requires linking a BLAS
+//! implementation (e.g. via the `blas-src` crate). Other data types always use
+//! the generic path.
+//!
+//! Equality saturation with [`glenside::saturation::ParallelSaturation`]
+//! searches for rewrite matches on several threads when Glenside's `parallel`
+//! feature is enabled, which pulls in `rayon`. Without it, the same search
+//! runs on one thread.
 
 pub mod codegen;
+pub mod error;
 pub mod extraction;
+pub mod gantt;
+pub mod hardware;
 pub mod hw_design_language;
 pub mod language;
+pub mod legality;
+pub mod models;
+pub mod saturation;
+pub mod search;
+pub mod testing;
@@ -0,0 +1,302 @@
+//! Builders for common model components, written directly in Glenside.
+//!
+//! Models usually reach Glenside through the Relay importer
+//! ([`crate::language::from_relay`]). Recurrent cells are an exception worth
+//! building by hand: once a cell is unrolled, every step multiplies by the same
+//! weight matrices, and we want to check that the mapping rewrites find a
+//! systolic array for each of those matrix multiplications, rather than only
+//! the first. The builders here add a single step of a cell to a [`RecExpr`],
+//! so that steps can be chained by passing one step's outputs to the next.
+
+use crate::language::from_relay::{access, access_pair, access_slice, compute};
+use crate::language::{ComputeType, Language};
+use egg::{Id, RecExpr};
+
+/// Multiplies `data` (of shape `[batch, in]`) by the transpose of `weights`
+/// (of shape `[out, in]`), producing an access of shape `[batch, out]`.
+///
+/// ```
+/// use egg::RecExpr;
+/// use glenside::language::Language;
+/// use glenside::models::dense;
+///
+/// let mut expr = RecExpr::default();
+/// let x_id = expr.add(Language::Symbol("x".to_string()));
+/// let x_id = expr.add(Language::AccessTensor(x_id));
+/// let w_id = expr.add(Language::Symbol("w".to_string()));
+/// let w_id = expr.add(Language::AccessTensor(w_id));
+/// dense(&mut expr, x_id, w_id);
+/// assert_eq!(
+///     expr.to_string(),
+///     "(compute dot-product (access-cartesian-product (access (access-tensor x) 1) (access (access-tensor w) 1)))"
+/// );
+/// ```
+pub fn dense(expr: &mut RecExpr<Language>, data_id: Id, weights_id: Id) -> Id {
+    let data_id = access(expr, data_id, 1);
+    let weights_id = access(expr, weights_id, 1);
+    let product_id = expr.add(Language::AccessCartesianProduct([data_id, weights_id]));
+    compute(expr, ComputeType::DotProduct, product_id)
+}
+
+/// Applies an elementwise binary compute type (e.g.
+/// [`ComputeType::ElementwiseAdd`]) to two accesses of the same shape.
+fn elementwise(expr: &mut RecExpr<Language>, compute_type: ComputeType, a_id: Id, b_id: Id) -> Id {
+    let pair_id = access_pair(expr, a_id, b_id, 0);
+    compute(expr, compute_type, pair_id)
+}
+
+/// The `index`th of the gates stacked along axis 1 of `gates_id`, each of
+/// which is `hidden_size` wide.
+fn gate(expr: &mut RecExpr<Language>, gates_id: Id, index: usize, hidden_size: usize) -> Id {
+    access_slice(
+        expr,
+        gates_id,
+        1,
+        index * hidden_size,
+        (index + 1) * hidden_size,
+    )
+}
+
+/// Adds one step of an LSTM cell to `expr`, returning the ids of the new hidden
+/// state and the new cell state, in that order.
+///
+/// Follows PyTorch's `LSTMCell`, without biases: `x_id` is the input, of shape
+/// `[batch, input_size]`; `h_id` and `c_id` are the hidden and cell states, of
+/// shape `[batch, hidden_size]`; and `w_ih_id` and `w_hh_id` are the input and
+/// hidden weights, of shapes `[4 * hidden_size, input_size]` and
+/// `[4 * hidden_size, hidden_size]`, with the gates stacked in the order input,
+/// forget, cell, output.
+pub fn lstm_cell(
+    expr: &mut RecExpr<Language>,
+    x_id: Id,
+    h_id: Id,
+    c_id: Id,
+    w_ih_id: Id,
+    w_hh_id: Id,
+    hidden_size: usize,
+) -> (Id, Id) {
+    let x_gates_id = dense(expr, x_id, w_ih_id);
+    let h_gates_id = dense(expr, h_id, w_hh_id);
+    let gates_id = elementwise(expr, ComputeType::ElementwiseAdd, x_gates_id, h_gates_id);
+
+    let i_id = gate(expr, gates_id, 0, hidden_size);
+    let i_id = compute(expr, ComputeType::Sigmoid, i_id);
+    let f_id = gate(expr, gates_id, 1, hidden_size);
+    let f_id = compute(expr, ComputeType::Sigmoid, f_id);
+    let g_id = gate(expr, gates_id, 2, hidden_size);
+    let g_id = compute(expr, ComputeType::Tanh, g_id);
+    let o_id = gate(expr, gates_id, 3, hidden_size);
+    let o_id = compute(expr, ComputeType::Sigmoid, o_id);
+
+    // c' = f * c + i * g
+    let forget_id = elementwise(expr, ComputeType::ElementwiseMul, f_id, c_id);
+    let input_id = elementwise(expr, ComputeType::ElementwiseMul, i_id, g_id);
+    let new_c_id = elementwise(expr, ComputeType::ElementwiseAdd, forget_id, input_id);
+
+    // h' = o * tanh(c')
+    let tanh_c_id = compute(expr, ComputeType::Tanh, new_c_id);
+    let new_h_id = elementwise(expr, ComputeType::ElementwiseMul, o_id, tanh_c_id);
+
+    (new_h_id, new_c_id)
+}
+
+/// Adds one step of a GRU cell to `expr`, returning the id of the new hidden
+/// state.
+///
+/// Follows PyTorch's `GRUCell`, without biases: `x_id` is the input, of shape
+/// `[batch, input_size]`; `h_id` is the hidden state, of shape
+/// `[batch, hidden_size]`; and `w_ih_id` and `w_hh_id` are the input and hidden
+/// weights, of shapes `[3 * hidden_size, input_size]` and
+/// `[3 * hidden_size, hidden_size]`, with the gates stacked in the order reset,
+/// update, new.
+pub fn gru_cell(
+    expr: &mut RecExpr<Language>,
+    x_id: Id,
+    h_id: Id,
+    w_ih_id: Id,
+    w_hh_id: Id,
+    hidden_size: usize,
+) -> Id {
+    let x_gates_id = dense(expr, x_id, w_ih_id);
+    let h_gates_id = dense(expr, h_id, w_hh_id);
+
+    let x_r_id = gate(expr, x_gates_id, 0, hidden_size);
+    let h_r_id = gate(expr, h_gates_id, 0, hidden_size);
+    let r_id = elementwise(expr, ComputeType::ElementwiseAdd, x_r_id, h_r_id);
+    let r_id = compute(expr, ComputeType::Sigmoid, r_id);
+
+    let x_z_id = gate(expr, x_gates_id, 1, hidden_size);
+    let h_z_id = gate(expr, h_gates_id, 1, hidden_size);
+    let z_id = elementwise(expr, ComputeType::ElementwiseAdd, x_z_id, h_z_id);
+    let z_id = compute(expr, ComputeType::Sigmoid, z_id);
+
+    // n = tanh(x_n + r * h_n)
+    let x_n_id = gate(expr, x_gates_id, 2, hidden_size);
+    let h_n_id = gate(expr, h_gates_id, 2, hidden_size);
+    let h_n_id = elementwise(expr, ComputeType::ElementwiseMul, r_id, h_n_id);
+    let n_id = elementwise(expr, ComputeType::ElementwiseAdd, x_n_id, h_n_id);
+    let n_id = compute(expr, ComputeType::Tanh, n_id);
+
+    // h' = (1 - z) * n + z * h = n + z * (h - n)
+    let negative_n_id = compute(expr, ComputeType::Negative, n_id);
+    let difference_id = elementwise(expr, ComputeType::ElementwiseAdd, h_id, negative_n_id);
+    let update_id = elementwise(expr, ComputeType::ElementwiseMul, z_id, difference_id);
+    elementwise(expr, ComputeType::ElementwiseAdd, n_id, update_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extraction::MonolithicCostFunction;
+    use crate::language::interpreter::{interpret, Environment, Value};
+    use crate::language::{rewrites, MyAnalysis};
+    use crate::testing::{assert_close, Tolerance};
+    use egg::{EGraph, Extractor, Pattern, Runner, Searcher};
+    use ndarray::{array, ArrayD};
+    use std::collections::HashMap;
+
+    fn access_tensor(expr: &mut RecExpr<Language>, name: &str) -> Id {
+        let id = expr.add(Language::Symbol(name.to_string()));
+        expr.add(Language::AccessTensor(id))
+    }
+
+    /// Two steps of an LSTM cell, sharing weights, mapped to systolic arrays.
+    #[test]
+    fn lstm_cell_shared_weights_systolic_arrays() {
+        let mut expr = RecExpr::default();
+        let x0_id = access_tensor(&mut expr, "x0");
+        let x1_id = access_tensor(&mut expr, "x1");
+        let h_id = access_tensor(&mut expr, "h");
+        let c_id = access_tensor(&mut expr, "c");
+        let w_ih_id = access_tensor(&mut expr, "w_ih");
+        let w_hh_id = access_tensor(&mut expr, "w_hh");
+        let (h_id, c_id) = lstm_cell(&mut expr, x0_id, h_id, c_id, w_ih_id, w_hh_id, 8);
+        let (h_id, c_id) = lstm_cell(&mut expr, x1_id, h_id, c_id, w_ih_id, w_hh_id, 8);
+        expr.add(Language::Outputs(Box::new([h_id, c_id])));
+
+        let mut map = HashMap::default();
+        map.insert("x0".to_string(), vec![1, 8]);
+        map.insert("x1".to_string(), vec![1, 8]);
+        map.insert("h".to_string(), vec![1, 8]);
+        map.insert("c".to_string(), vec![1, 8]);
+        map.insert("w_ih".to_string(), vec![32, 8]);
+        map.insert("w_hh".to_string(), vec![32, 8]);
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&expr);
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&vec![rewrites::systolic_array().rewrite]);
+
+        // Both steps' multiplications by each of the shared weights are mapped:
+        // x0 and x1 by w_ih, and the initial and first hidden states by w_hh.
+        for weights in &["w_ih", "w_hh"] {
+            let matches = format!(
+                "(systolic-array 8 32
+                  ?a
+                  (access (access-transpose (access (access-tensor {}) 1) (list 1 0)) 0))",
+                weights
+            )
+            .parse::<Pattern<Language>>()
+            .unwrap()
+            .search(&runner.egraph);
+            assert_eq!(matches.len(), 2);
+        }
+
+        let (cost, extracted) = Extractor::new(
+            &runner.egraph,
+            MonolithicCostFunction {
+                egraph: &runner.egraph,
+                systolic_array_configuration: (8, 32),
+                prefer_systolic_arrays_with_blocking: false,
+            },
+        )
+        .find_best(id);
+        assert!(cost < MonolithicCostFunction::INFINITY_VALUE);
+        assert!(!extracted
+            .as_ref()
+            .iter()
+            .any(|node| matches!(node, Language::ComputeType(ComputeType::DotProduct))));
+        assert!(extracted
+            .as_ref()
+            .iter()
+            .any(|node| matches!(node, Language::SystolicArray(_))));
+        assert!(crate::search::verify(&runner.egraph, &expr, &extracted, 1e-9, 0).passed);
+    }
+
+    #[test]
+    fn gru_cell_systolic_arrays() {
+        let mut expr = RecExpr::default();
+        let x_id = access_tensor(&mut expr, "x");
+        let h_id = access_tensor(&mut expr, "h");
+        let w_ih_id = access_tensor(&mut expr, "w_ih");
+        let w_hh_id = access_tensor(&mut expr, "w_hh");
+        let h_id = gru_cell(&mut expr, x_id, h_id, w_ih_id, w_hh_id, 8);
+        let h_id = gru_cell(&mut expr, x_id, h_id, w_ih_id, w_hh_id, 8);
+        assert_eq!(usize::from(h_id), expr.as_ref().len() - 1);
+
+        let mut map = HashMap::default();
+        map.insert("x".to_string(), vec![2, 8]);
+        map.insert("h".to_string(), vec![2, 8]);
+        map.insert("w_ih".to_string(), vec![24, 8]);
+        map.insert("w_hh".to_string(), vec![24, 8]);
+        let mut egraph = EGraph::new(MyAnalysis {
+            name_to_shape: map,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        });
+        let id = egraph.add_expr(&expr);
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+            .with_egraph(egraph)
+            .run(&vec![rewrites::systolic_array().rewrite]);
+
+        let (cost, extracted) = Extractor::new(
+            &runner.egraph,
+            MonolithicCostFunction {
+                egraph: &runner.egraph,
+                systolic_array_configuration: (8, 24),
+                prefer_systolic_arrays_with_blocking: false,
+            },
+        )
+        .find_best(id);
+        assert!(cost < MonolithicCostFunction::INFINITY_VALUE);
+        assert!(!extracted
+            .as_ref()
+            .iter()
+            .any(|node| matches!(node, Language::ComputeType(ComputeType::DotProduct))));
+        assert!(extracted
+            .as_ref()
+            .iter()
+            .any(|node| matches!(node, Language::SystolicArray(_))));
+        assert!(crate::search::verify(&runner.egraph, &expr, &extracted, 1e-9, 0).passed);
+    }
+
+    #[test]
+    fn gru_cell_zero_weights() {
+        // With all weights zero, every gate is 0.5 and n is 0, so the hidden
+        // state is halved.
+        let mut expr = RecExpr::default();
+        let x_id = access_tensor(&mut expr, "x");
+        let h_id = access_tensor(&mut expr, "h");
+        let w_ih_id = access_tensor(&mut expr, "w_ih");
+        let w_hh_id = access_tensor(&mut expr, "w_hh");
+        gru_cell(&mut expr, x_id, h_id, w_ih_id, w_hh_id, 2);
+
+        let mut env: Environment<f64> = HashMap::default();
+        env.insert("x", array![[1., 2., 3.]].into_dyn());
+        env.insert("h", array![[4., -6.]].into_dyn());
+        env.insert("w_ih", ArrayD::zeros(vec![6, 3]));
+        env.insert("w_hh", ArrayD::zeros(vec![6, 2]));
+        match interpret(&expr, expr.as_ref().len() - 1, &env) {
+            Value::Access(a) => assert_close(
+                &a.tensor,
+                &array![[2., -3.]].into_dyn(),
+                Tolerance::absolute(1e-12),
+            ),
+            _ => panic!(),
+        }
+    }
+}
@@ -0,0 +1,274 @@
+//! Equality saturation with parallel rule matching.
+//!
+//! On ResNet-scale egraphs, most of the time [`egg::Runner`] spends is in
+//! searching for matches of the rewrites, one rewrite at a time, on one
+//! thread. [`ParallelSaturation`] shards the search instead: the eclasses are
+//! split into shards, and each (rewrite, shard) pair is searched as a separate
+//! task. The matches found are then merged and applied on one thread, as
+//! applying them mutates the egraph, and the egraph is rebuilt, as in egg.
+//!
+//! The tasks run in parallel when Glenside's `parallel` feature is enabled
+//! (which pulls in `rayon`), and one after the other otherwise. In
+//! deterministic mode (the default), the matches are merged in the order the
+//! tasks were created, so that every run builds the same egraph, with the same
+//! eclass ids, as a single-threaded run would. Otherwise, they're applied in
+//! whichever order the tasks finish in: the egraph is equivalent, but its ids
+//! may differ between runs.
+
+use crate::language::{Language, MyAnalysis};
+use egg::{EGraph, Id, Rewrite, SearchMatches, StopReason};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::time::{Duration, Instant};
+
+/// Options for running equality saturation with sharded rule matching.
+/// Every rewrite is searched for in every iteration, as with egg's
+/// [`SimpleScheduler`](egg::SimpleScheduler).
+#[derive(Clone, Debug)]
+pub struct ParallelSaturation {
+    pub iter_limit: usize,
+    pub node_limit: usize,
+    pub time_limit: Duration,
+    /// How many eclasses each search task covers.
+    pub shard_size: usize,
+    /// Whether matches are applied in a fixed order, rather than as they're
+    /// found.
+    pub deterministic: bool,
+}
+
+impl Default for ParallelSaturation {
+    /// Uses the same limits as [`egg::Runner`]'s defaults.
+    fn default() -> Self {
+        ParallelSaturation {
+            iter_limit: 30,
+            node_limit: 10_000,
+            time_limit: Duration::from_secs(5),
+            shard_size: 256,
+            deterministic: true,
+        }
+    }
+}
+
+/// What happened during a [`ParallelSaturation::run`].
+#[derive(Clone, Debug)]
+pub struct SaturationReport {
+    pub iterations: usize,
+    pub stop_reason: StopReason,
+}
+
+impl ParallelSaturation {
+    /// Runs `rules` over `egraph` until it saturates or a limit is reached.
+    /// ```
+    /// use egg::EGraph;
+    /// use glenside::language::{rewrites, MyAnalysis};
+    /// use glenside::saturation::ParallelSaturation;
+    ///
+    /// let mut egraph = EGraph::new(MyAnalysis::default());
+    /// let id = egraph.add_expr(
+    ///     &"(compute dot-product
+    ///        (access-cartesian-product
+    ///         (access (access-tensor t-32-32) 1)
+    ///         (access (access-tensor t-32-32) 1)))"
+    ///         .parse()
+    ///         .unwrap(),
+    /// );
+    /// egraph.rebuild();
+    /// let report = ParallelSaturation::default().run(&mut egraph, &[rewrites::systolic_array().rewrite]);
+    /// assert!(matches!(report.stop_reason, egg::StopReason::Saturated));
+    /// // The dot product was mapped to a systolic array.
+    /// assert!(egraph[id].nodes.len() > 1);
+    /// ```
+    pub fn run(
+        &self,
+        egraph: &mut EGraph<Language, MyAnalysis>,
+        rules: &[Rewrite<Language, MyAnalysis>],
+    ) -> SaturationReport {
+        assert!(self.shard_size > 0, "Shards must hold at least one eclass");
+        let start = Instant::now();
+        egraph.rebuild();
+
+        let mut iterations = 0;
+        let stop_reason = loop {
+            if iterations == self.iter_limit {
+                break StopReason::IterationLimit(iterations);
+            }
+            iterations += 1;
+
+            let size = (egraph.total_number_of_nodes(), egraph.number_of_classes());
+            let matches = self.search(egraph, rules);
+            for (rule, matches) in matches {
+                rules[rule].apply(egraph, std::slice::from_ref(&matches));
+            }
+            egraph.rebuild();
+
+            if (egraph.total_number_of_nodes(), egraph.number_of_classes()) == size {
+                break StopReason::Saturated;
+            }
+            if egraph.total_number_of_nodes() > self.node_limit {
+                break StopReason::NodeLimit(egraph.total_number_of_nodes());
+            }
+            if start.elapsed() > self.time_limit {
+                break StopReason::TimeLimit(start.elapsed().as_secs_f64());
+            }
+        };
+
+        SaturationReport {
+            iterations,
+            stop_reason,
+        }
+    }
+
+    /// Finds the matches of each of `rules` in `egraph`, paired with the
+    /// index of the rule they match.
+    fn search<'a>(
+        &self,
+        egraph: &EGraph<Language, MyAnalysis>,
+        rules: &'a [Rewrite<Language, MyAnalysis>],
+    ) -> Vec<(usize, SearchMatches<'a, Language>)> {
+        let mut eclasses = egraph.classes().map(|eclass| eclass.id).collect::<Vec<_>>();
+        eclasses.sort();
+        let tasks = (0..rules.len())
+            .flat_map(|rule| {
+                eclasses
+                    .chunks(self.shard_size)
+                    .map(move |shard| (rule, shard))
+            })
+            .collect::<Vec<(usize, &[Id])>>();
+        let search_shard = |&(rule, shard): &(usize, &[Id])| {
+            shard
+                .iter()
+                .filter_map(|&eclass| rules[rule].searcher.search_eclass(egraph, eclass))
+                .map(|matches| (rule, matches))
+                .collect::<Vec<_>>()
+        };
+
+        #[cfg(feature = "parallel")]
+        {
+            if self.deterministic {
+                tasks
+                    .par_iter()
+                    .map(search_shard)
+                    .collect::<Vec<_>>()
+                    .into_iter()
+                    .flatten()
+                    .collect()
+            } else {
+                let (sender, receiver) = std::sync::mpsc::channel();
+                tasks.par_iter().for_each_with(sender, |sender, task| {
+                    sender.send(search_shard(task)).unwrap()
+                });
+                receiver.into_iter().flatten().collect()
+            }
+        }
+        #[cfg(not(feature = "parallel"))]
+        {
+            tasks.iter().flat_map(search_shard).collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::rewrites;
+    use egg::{Extractor, RecExpr, Runner, SimpleScheduler};
+
+    fn program() -> RecExpr<Language> {
+        "(compute dot-product
+          (access-cartesian-product
+           (access (access-tensor t-32-32) 1)
+           (access (access-tensor t-32-32) 1)))"
+            .parse()
+            .unwrap()
+    }
+
+    fn rules() -> Vec<Rewrite<Language, MyAnalysis>> {
+        vec![
+            rewrites::systolic_array().rewrite,
+            rewrites::slice_concatenate_accesses(
+                0,
+                rewrites::SliceConcatenateStrategy::DivideInto { segment_size: 16 },
+            ),
+        ]
+    }
+
+    #[test]
+    fn same_as_runner() {
+        let options = ParallelSaturation {
+            iter_limit: 3,
+            // Small shards, so that there are many tasks.
+            shard_size: 2,
+            ..Default::default()
+        };
+        let mut egraph = EGraph::new(MyAnalysis::default());
+        let id = egraph.add_expr(&program());
+        let report = options.run(&mut egraph, &rules());
+        assert!(report.iterations <= 3);
+
+        let runner = Runner::default()
+            .with_scheduler(SimpleScheduler)
+            .with_iter_limit(3)
+            .with_expr(&program())
+            .run(&rules());
+        assert_eq!(
+            egraph.total_number_of_nodes(),
+            runner.egraph.total_number_of_nodes()
+        );
+        assert_eq!(
+            egraph.number_of_classes(),
+            runner.egraph.number_of_classes()
+        );
+        assert_eq!(
+            Extractor::new(&egraph, egg::AstSize).find_best(id).0,
+            Extractor::new(&runner.egraph, egg::AstSize)
+                .find_best(runner.roots[0])
+                .0
+        );
+    }
+
+    #[test]
+    fn deterministic() {
+        let run = |deterministic| {
+            let options = ParallelSaturation {
+                iter_limit: 3,
+                shard_size: 2,
+                deterministic,
+                ..Default::default()
+            };
+            let mut egraph = EGraph::new(MyAnalysis::default());
+            let id = egraph.add_expr(&program());
+            options.run(&mut egraph, &rules());
+            let extracted = Extractor::new(&egraph, egg::AstSize).find_best(id).1;
+            let mut nodes = egraph
+                .classes()
+                .flat_map(|eclass| {
+                    eclass
+                        .nodes
+                        .iter()
+                        .map(move |node| (eclass.id, node.clone()))
+                })
+                .collect::<Vec<_>>();
+            nodes.sort();
+            (nodes, extracted.to_string())
+        };
+
+        // Deterministic runs build identical egraphs.
+        assert_eq!(run(true), run(true));
+        // Otherwise, they build equivalent ones.
+        let (deterministic_nodes, _) = run(true);
+        let (nodes, _) = run(false);
+        assert_eq!(nodes.len(), deterministic_nodes.len());
+    }
+
+    #[test]
+    fn node_limit() {
+        let options = ParallelSaturation {
+            node_limit: 5,
+            ..Default::default()
+        };
+        let mut egraph = EGraph::new(MyAnalysis::default());
+        egraph.add_expr(&program());
+        let report = options.run(&mut egraph, &rules());
+        assert!(matches!(report.stop_reason, StopReason::NodeLimit(n) if n > 5));
+    }
+}
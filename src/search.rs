@@ -0,0 +1,960 @@
+//! The search driver: equality saturation, extraction, and verification.
+//!
+//! [`search`] runs rewrites over an expression, extracts the best program, and
+//! then checks the extracted program against the original by interpreting both
+//! on the same random inputs (see [`verify`]). A rewrite which changes the
+//! meaning of a program is thus caught in the first run which uses it, rather
+//! than after the program has been compiled and run on hardware.
+//!
+//! When a rewrite does change a program's meaning, or when rewrites interact
+//! unexpectedly, [`explain`] gives the chain of rewrites which made the
+//! extracted program equal to the original.
+//!
+//! Some rewrites (e.g. tiling, with its many choices of factors) can blow up
+//! the egraph; a [`RuleBudget`] turns rewrites on and off and caps how many
+//! times each is applied.
+//!
+//! Running every tiling and layout rewrite at once blows up the egraph
+//! regardless. [`staged_search`] instead runs the rewrites in phases, as
+//! described by a [`Schedule`]: cleanup phases run simplifying rewrites to
+//! saturation, and expansion phases apply the rewrites which grow the egraph
+//! a bounded number of times. Alternating the two (e.g. tile once, then clean
+//! up, then tile again) keeps the egraph small enough to extract from. A
+//! schedule names the rewrites of each phase, so users can define their own
+//! phases, in code or in JSON:
+//! ```json
+//! {
+//!   "phases": [
+//!     { "phase": "expand", "rules": ["slice-concatenate-access-axis-0-divide-into-16"],
+//!       "iterations": 2, "max_matches": 8, "node_limit": 50000 },
+//!     { "phase": "cleanup", "rules": ["systolic-array"] }
+//!   ],
+//!   "rounds": 2
+//! }
+//! ```
+
+use crate::error::{GlensideError, Result};
+use crate::language::interpreter::{interpret, Environment, Value};
+use crate::language::{Language, MyAnalysis, MyAnalysisData};
+use egg::{
+    BackoffScheduler, EGraph, FlatTerm, Id, RecExpr, Rewrite, RewriteScheduler, Runner,
+    SearchMatches, StopReason,
+};
+use ndarray::ArrayD;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// The result of checking an extracted program against the original.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Verification {
+    /// Whether both programs produced results of the same shapes, with every
+    /// element within the tolerance.
+    pub passed: bool,
+    /// The largest absolute difference between corresponding elements of the
+    /// two results, or infinity if the results have different shapes.
+    pub max_abs_diff: f64,
+    /// The seed the random inputs were generated from. Passing it to
+    /// [`verify`] again reproduces the same inputs, e.g. to debug a failure.
+    pub seed: u64,
+}
+
+/// What happened during one iteration of equality saturation.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct IterationStats {
+    /// The size of the egraph at the start of the iteration.
+    pub egraph_nodes: usize,
+    pub egraph_classes: usize,
+    /// The number of matches of each rewrite applied in the iteration.
+    pub applied: BTreeMap<String, usize>,
+    /// The time spent searching, applying, and rebuilding, and in the
+    /// iteration as a whole, in seconds.
+    pub search_time: f64,
+    pub apply_time: f64,
+    pub rebuild_time: f64,
+    pub total_time: f64,
+}
+
+/// What happened during a [`search`]. Use [`SearchReport::to_json`] to record
+/// it, e.g. for experiment tracking.
+#[derive(Clone, Debug)]
+pub struct SearchReport<Cost> {
+    pub extracted: RecExpr<Language>,
+    pub cost: Cost,
+    pub iterations: usize,
+    pub stop_reason: Option<StopReason>,
+    pub egraph_nodes: usize,
+    pub egraph_classes: usize,
+    pub verification: Verification,
+    /// The rewrites proving the extracted program equal to the original (see
+    /// [`explain`]), if the runner had explanations enabled.
+    pub explanation: Option<Vec<ExplanationStep>>,
+    /// The names of the rewrites which reached their caps in the
+    /// [`RuleBudget`].
+    pub capped_rules: BTreeSet<String>,
+    pub iteration_stats: Vec<IterationStats>,
+    /// The number of matches of each rewrite applied over the whole search.
+    pub rules_applied: BTreeMap<String, usize>,
+    /// The number of operators of each kind in the extracted program (see
+    /// [`operator_kind`](crate::language::stats::operator_kind)).
+    pub extracted_operators: BTreeMap<String, usize>,
+    /// The time spent in equality saturation, extraction, and verification,
+    /// in seconds.
+    pub saturation_time: f64,
+    pub extraction_time: f64,
+    pub verification_time: f64,
+}
+
+impl<Cost: Serialize> SearchReport<Cost> {
+    /// The report as JSON, with fields named as [`SearchReport`]'s are. The
+    /// extracted program is given as an s-expression, and the stop reason as
+    /// a string, e.g. `"Saturated"`.
+    /// ```
+    /// use egg::{AstSize, Extractor, Runner};
+    /// use glenside::language::{rewrites, MyAnalysis};
+    /// use glenside::search::{search, RuleBudget};
+    ///
+    /// let expr = "(compute relu (access (access-tensor t-32-32) 0))".parse().unwrap();
+    /// let report = search(
+    ///     &expr,
+    ///     Runner::new(MyAnalysis::default()),
+    ///     &[rewrites::activation_unit().rewrite],
+    ///     RuleBudget::default(),
+    ///     |egraph, id| Extractor::new(egraph, AstSize).find_best(id),
+    ///     1e-9,
+    ///     0,
+    /// );
+    /// let json = report.to_json();
+    /// assert_eq!(json["verification"]["passed"], true);
+    /// assert_eq!(json["rules_applied"]["activation-unit"], 1);
+    /// assert_eq!(json["stop_reason"], "Saturated");
+    /// ```
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "extracted": self.extracted.to_string(),
+            "cost": self.cost,
+            "iterations": self.iterations,
+            "stop_reason": self.stop_reason.as_ref().map(|reason| format!("{:?}", reason)),
+            "egraph_nodes": self.egraph_nodes,
+            "egraph_classes": self.egraph_classes,
+            "verification": self.verification,
+            "explanation": self.explanation,
+            "capped_rules": self.capped_rules,
+            "iteration_stats": self.iteration_stats,
+            "rules_applied": self.rules_applied,
+            "extracted_operators": self.extracted_operators,
+            "saturation_time": self.saturation_time,
+            "extraction_time": self.extraction_time,
+            "verification_time": self.verification_time,
+        })
+    }
+}
+
+/// Which rewrites a [`search`] may apply, and how many times.
+#[derive(Clone, Debug, Default)]
+pub struct RuleBudget {
+    /// If set, only the rewrites with these names are applied.
+    pub allow: Option<HashSet<String>>,
+    /// The rewrites with these names are never applied.
+    pub deny: HashSet<String>,
+    /// The maximum number of matches of each named rewrite applied over the
+    /// whole search. Rewrites not named here are unlimited.
+    pub caps: HashMap<String, usize>,
+}
+
+impl RuleBudget {
+    /// Whether the rewrite named `rule` may be applied at all.
+    pub fn enabled(&self, rule: &str) -> bool {
+        self.allow
+            .as_ref()
+            .map_or(true, |allow| allow.contains(rule))
+            && !self.deny.contains(rule)
+    }
+}
+
+/// A [`RewriteScheduler`] enforcing a [`RuleBudget`] on top of egg's default
+/// [`BackoffScheduler`]. The rewrites which reach their caps are recorded in
+/// `capped`, which is shared so that it can be read after the [`Runner`]
+/// (which takes ownership of its scheduler) has run.
+struct BudgetScheduler {
+    budget: RuleBudget,
+    inner: BackoffScheduler,
+    applied: HashMap<String, usize>,
+    capped: Rc<RefCell<BTreeSet<String>>>,
+}
+
+impl BudgetScheduler {
+    /// How many more matches of the rewrite named `rule` may be applied.
+    fn remaining(&self, rule: &str) -> Option<usize> {
+        self.budget
+            .caps
+            .get(rule)
+            .map(|cap| cap.saturating_sub(self.applied.get(rule).cloned().unwrap_or(0)))
+    }
+}
+
+impl RewriteScheduler<Language, MyAnalysis> for BudgetScheduler {
+    fn can_stop(&mut self, iteration: usize) -> bool {
+        RewriteScheduler::<Language, MyAnalysis>::can_stop(&mut self.inner, iteration)
+    }
+
+    fn search_rewrite<'a>(
+        &mut self,
+        iteration: usize,
+        egraph: &EGraph<Language, MyAnalysis>,
+        rewrite: &'a Rewrite<Language, MyAnalysis>,
+    ) -> Vec<SearchMatches<'a, Language>> {
+        let rule = rewrite.name.as_str();
+        if !self.budget.enabled(rule) || self.remaining(rule) == Some(0) {
+            return vec![];
+        }
+        self.inner.search_rewrite(iteration, egraph, rewrite)
+    }
+
+    fn apply_rewrite(
+        &mut self,
+        iteration: usize,
+        egraph: &mut EGraph<Language, MyAnalysis>,
+        rewrite: &Rewrite<Language, MyAnalysis>,
+        mut matches: Vec<SearchMatches<Language>>,
+    ) -> usize {
+        let rule = rewrite.name.as_str();
+        let total: usize = matches.iter().map(|m| m.substs.len()).sum();
+        let allowed = match self.remaining(rule) {
+            Some(remaining) if remaining <= total => {
+                self.capped.borrow_mut().insert(rule.to_string());
+                remaining
+            }
+            _ => total,
+        };
+        if allowed < total {
+            let mut left = allowed;
+            for m in matches.iter_mut() {
+                m.substs.truncate(left);
+                left -= m.substs.len();
+            }
+            matches.retain(|m| !m.substs.is_empty());
+        }
+        *self.applied.entry(rule.to_string()).or_insert(0) += allowed;
+        self.inner
+            .apply_rewrite(iteration, egraph, rewrite, matches)
+    }
+}
+
+/// One rewrite in an [`explain`]ed equivalence.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ExplanationStep {
+    /// The name of the rewrite.
+    pub rule: String,
+    /// Whether the rewrite was applied from right to left.
+    pub backward: bool,
+    /// The whole program after the rewrite.
+    pub program: String,
+}
+
+/// Runs `rules` over `expr` with `runner` (which determines the analysis and
+/// the iteration, node, and time limits), extracts a program from the root
+/// eclass of the saturated egraph with `extract` (e.g. with an
+/// [`egg::Extractor`], or with [ILP](crate::extraction::ilp)), and verifies
+/// the extracted program against `expr` with [`verify`], on inputs generated
+/// from `seed`. If `runner` has explanations enabled (with
+/// [`Runner::with_explanations_enabled`]), the report also includes the
+/// rewrites proving the extracted program equal to `expr`.
+///
+/// Rewrites are only applied as allowed by `budget`. This replaces the
+/// runner's scheduler with egg's default [`BackoffScheduler`], limited by the
+/// budget.
+///
+/// ```
+/// use egg::{AstSize, Extractor, Runner};
+/// use glenside::language::{rewrites, Language, MyAnalysis};
+/// use glenside::search::{search, RuleBudget};
+///
+/// let expr = "
+///  (compute dot-product
+///   (access-cartesian-product
+///    (access (access-tensor t-32-32) 1)
+///    (access (access-tensor t-32-32) 1)))"
+///     .parse()
+///     .unwrap();
+/// let report = search(
+///     &expr,
+///     Runner::new(MyAnalysis::default()),
+///     &[rewrites::systolic_array().rewrite],
+///     RuleBudget::default(),
+///     |egraph, id| Extractor::new(egraph, AstSize).find_best(id),
+///     1e-9,
+///     0,
+/// );
+/// assert!(report.verification.passed);
+/// ```
+pub fn search<Cost>(
+    expr: &RecExpr<Language>,
+    runner: Runner<Language, MyAnalysis, ()>,
+    rules: &[Rewrite<Language, MyAnalysis>],
+    budget: RuleBudget,
+    extract: impl FnOnce(&EGraph<Language, MyAnalysis>, Id) -> (Cost, RecExpr<Language>),
+    tolerance: f64,
+    seed: u64,
+) -> SearchReport<Cost> {
+    let capped = Rc::new(RefCell::new(BTreeSet::default()));
+    let mut runner = runner
+        .with_scheduler(BudgetScheduler {
+            budget,
+            inner: BackoffScheduler::default(),
+            applied: HashMap::default(),
+            capped: capped.clone(),
+        })
+        .with_expr(expr)
+        .run(rules);
+    let root = runner.roots[0];
+    let start = Instant::now();
+    let (cost, extracted) = extract(&runner.egraph, root);
+    let extraction_time = start.elapsed().as_secs_f64();
+    let start = Instant::now();
+    let verification = verify(&runner.egraph, expr, &extracted, tolerance, seed);
+    let verification_time = start.elapsed().as_secs_f64();
+    let explanation = if runner.egraph.are_explanations_enabled() {
+        Some(explain(&mut runner.egraph, expr, &extracted))
+    } else {
+        None
+    };
+
+    let iteration_stats = runner
+        .iterations
+        .iter()
+        .map(|iteration| IterationStats {
+            egraph_nodes: iteration.egraph_nodes,
+            egraph_classes: iteration.egraph_classes,
+            applied: iteration
+                .applied
+                .iter()
+                .map(|(rule, count)| (rule.to_string(), *count))
+                .collect(),
+            search_time: iteration.search_time,
+            apply_time: iteration.apply_time,
+            rebuild_time: iteration.rebuild_time,
+            total_time: iteration.total_time,
+        })
+        .collect::<Vec<_>>();
+    let mut rules_applied = BTreeMap::default();
+    for stats in &iteration_stats {
+        for (rule, count) in &stats.applied {
+            *rules_applied.entry(rule.clone()).or_insert(0) += count;
+        }
+    }
+    let mut extracted_operators = BTreeMap::default();
+    for node in extracted.as_ref() {
+        *extracted_operators
+            .entry(crate::language::stats::operator_kind(node))
+            .or_insert(0) += 1;
+    }
+
+    SearchReport {
+        extracted,
+        cost,
+        iterations: runner.iterations.len(),
+        stop_reason: runner.stop_reason.clone(),
+        egraph_nodes: runner.egraph.total_number_of_nodes(),
+        egraph_classes: runner.egraph.number_of_classes(),
+        verification,
+        explanation,
+        capped_rules: capped.borrow().clone(),
+        iteration_stats,
+        rules_applied,
+        extracted_operators,
+        saturation_time: runner.iterations.iter().map(|i| i.total_time).sum(),
+        extraction_time,
+        verification_time,
+    }
+}
+
+fn default_cleanup_iter_limit() -> usize {
+    30
+}
+
+fn default_rounds() -> usize {
+    1
+}
+
+/// One phase of a [`Schedule`]. Rewrites are named as in
+/// [`Rewrite::name`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "phase", rename_all = "kebab-case")]
+pub enum Phase {
+    /// Runs `rules` until they saturate, or for at most `iter_limit`
+    /// iterations (30 by default). For rewrites which simplify the egraph
+    /// rather than grow it, e.g. collapsing nested accesses, or mapping to
+    /// hardware atoms.
+    Cleanup {
+        rules: Vec<String>,
+        #[serde(default = "default_cleanup_iter_limit")]
+        iter_limit: usize,
+    },
+    /// Runs `rules` for at most `iterations` iterations, applying each to at
+    /// most `max_matches` matches over the whole phase (if set), and stopping
+    /// early once the egraph has more than `node_limit` nodes. For rewrites
+    /// which grow the egraph, e.g. tiling and layout changes.
+    Expand {
+        rules: Vec<String>,
+        iterations: usize,
+        #[serde(default)]
+        max_matches: Option<usize>,
+        node_limit: usize,
+    },
+}
+
+impl Phase {
+    pub fn rules(&self) -> &[String] {
+        match self {
+            Phase::Cleanup { rules, .. } | Phase::Expand { rules, .. } => rules,
+        }
+    }
+}
+
+/// The phases of a [`staged_search`], which are run in order, `rounds` times
+/// over (once by default).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Schedule {
+    pub phases: Vec<Phase>,
+    #[serde(default = "default_rounds")]
+    pub rounds: usize,
+}
+
+impl Schedule {
+    /// Parses a schedule from JSON, as in the [module](self) documentation.
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json)
+            .map_err(|e| GlensideError::Parse(format!("Invalid schedule: {}", e)))
+    }
+}
+
+/// What happened during one phase of a [`staged_search`].
+#[derive(Clone, Debug)]
+pub struct PhaseReport {
+    /// The round, and the index of the phase within the schedule.
+    pub round: usize,
+    pub phase: usize,
+    pub iterations: usize,
+    pub stop_reason: Option<StopReason>,
+    /// The size of the egraph after the phase.
+    pub egraph_nodes: usize,
+    pub egraph_classes: usize,
+    /// The names of the rewrites which reached `max_matches` in the phase.
+    pub capped_rules: BTreeSet<String>,
+}
+
+/// Runs the phases of `schedule` over `egraph`, looking up the rewrites each
+/// phase names in `rules`. Afterwards, extract from `egraph` as usual. Fails
+/// before running anything if a phase names a rewrite which isn't in `rules`.
+///
+/// ```
+/// use egg::EGraph;
+/// use glenside::language::{rewrites, MyAnalysis};
+/// use glenside::search::{staged_search, Schedule};
+///
+/// let mut egraph = EGraph::new(MyAnalysis::default());
+/// egraph.add_expr(
+///     &"(compute dot-product
+///        (access-cartesian-product
+///         (access (access-tensor t-32-32) 1)
+///         (access (access-tensor t-32-32) 1)))"
+///         .parse()
+///         .unwrap(),
+/// );
+/// let schedule = Schedule::from_json(
+///     r#"{ "phases": [
+///            { "phase": "expand", "rules": ["slice-concatenate-access-axis-0-divide-into-16"],
+///              "iterations": 1, "node_limit": 1000 },
+///            { "phase": "cleanup", "rules": ["systolic-array"] } ] }"#,
+/// )
+/// .unwrap();
+/// let rules = [
+///     rewrites::systolic_array().rewrite,
+///     rewrites::slice_concatenate_accesses(
+///         0,
+///         rewrites::SliceConcatenateStrategy::DivideInto { segment_size: 16 },
+///     ),
+/// ];
+/// let reports = staged_search(&mut egraph, &rules, &schedule).unwrap();
+/// assert_eq!(reports.len(), 2);
+/// ```
+pub fn staged_search(
+    egraph: &mut EGraph<Language, MyAnalysis>,
+    rules: &[Rewrite<Language, MyAnalysis>],
+    schedule: &Schedule,
+) -> Result<Vec<PhaseReport>> {
+    for (index, phase) in schedule.phases.iter().enumerate() {
+        if let Some(rule) = phase
+            .rules()
+            .iter()
+            .find(|rule| !rules.iter().any(|r| r.name.as_str() == rule.as_str()))
+        {
+            return Err(GlensideError::Parse(format!(
+                "Unknown rule {} in phase {}",
+                rule, index
+            )));
+        }
+    }
+
+    let mut reports = Vec::default();
+    for round in 0..schedule.rounds {
+        for (index, phase) in schedule.phases.iter().enumerate() {
+            let mut budget = RuleBudget {
+                allow: Some(phase.rules().iter().cloned().collect()),
+                ..Default::default()
+            };
+            let (iter_limit, node_limit) = match phase {
+                Phase::Cleanup { iter_limit, .. } => (*iter_limit, usize::MAX),
+                Phase::Expand {
+                    rules,
+                    iterations,
+                    max_matches,
+                    node_limit,
+                } => {
+                    if let Some(max_matches) = max_matches {
+                        budget.caps = rules
+                            .iter()
+                            .map(|rule| (rule.clone(), *max_matches))
+                            .collect();
+                    }
+                    (*iterations, *node_limit)
+                }
+            };
+
+            let capped = Rc::new(RefCell::new(BTreeSet::default()));
+            let runner = Runner::default()
+                .with_egraph(std::mem::take(egraph))
+                .with_iter_limit(iter_limit)
+                .with_node_limit(node_limit)
+                .with_time_limit(Duration::from_secs(u64::MAX))
+                .with_scheduler(BudgetScheduler {
+                    budget,
+                    inner: BackoffScheduler::default(),
+                    applied: HashMap::default(),
+                    capped: capped.clone(),
+                });
+            let runner = runner.run(rules);
+
+            reports.push(PhaseReport {
+                round,
+                phase: index,
+                iterations: runner.iterations.len(),
+                stop_reason: runner.stop_reason.clone(),
+                egraph_nodes: runner.egraph.total_number_of_nodes(),
+                egraph_classes: runner.egraph.number_of_classes(),
+                capped_rules: capped.borrow().clone(),
+            });
+            *egraph = runner.egraph;
+        }
+    }
+    Ok(reports)
+}
+
+/// Gives the chain of rewrites proving `extracted` equal to `original`, for
+/// debugging interactions between rewrites. `egraph` must have had
+/// explanations enabled before `original` was added, and must contain both
+/// programs; generally, it's the egraph `extracted` was extracted from.
+pub fn explain(
+    egraph: &mut EGraph<Language, MyAnalysis>,
+    original: &RecExpr<Language>,
+    extracted: &RecExpr<Language>,
+) -> Vec<ExplanationStep> {
+    /// The rewrite which produced `term`. Each term in a flattened explanation
+    /// is produced by exactly one rewrite, applied at the term itself or at one
+    /// of its subterms.
+    fn rewrite(term: &FlatTerm<Language>) -> Option<(String, bool)> {
+        if let Some(rule) = &term.forward_rule {
+            Some((rule.to_string(), false))
+        } else if let Some(rule) = &term.backward_rule {
+            Some((rule.to_string(), true))
+        } else {
+            term.children.iter().find_map(rewrite)
+        }
+    }
+
+    let mut explanation = egraph.explain_equivalence(original, extracted);
+    explanation
+        .make_flat_explanation()
+        .iter()
+        .filter_map(|term| {
+            rewrite(term).map(|(rule, backward)| ExplanationStep {
+                rule,
+                backward,
+                program: term.get_string(),
+            })
+        })
+        .collect()
+}
+
+/// Interprets `original` and `extracted` on the same environment, filled with
+/// random values in [-1, 1) generated from `seed`, and compares the results.
+///
+/// The shape of each tensor is taken from `egraph`, which must contain
+/// `original`; generally, it's the egraph `extracted` was extracted from. Both
+/// programs must be supported by the
+/// [interpreter](crate::language::interpreter), which panics otherwise.
+pub fn verify(
+    egraph: &EGraph<Language, MyAnalysis>,
+    original: &RecExpr<Language>,
+    extracted: &RecExpr<Language>,
+    tolerance: f64,
+    seed: u64,
+) -> Verification {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut env: Environment<f64> = HashMap::default();
+    for node in original.as_ref() {
+        if let Language::AccessTensor(symbol_id) = node {
+            let symbol = &original.as_ref()[usize::from(*symbol_id)];
+            let name = match symbol {
+                Language::Symbol(name) => name,
+                _ => continue,
+            };
+            let shape = match egraph.lookup(symbol.clone()).map(|id| &egraph[id].data) {
+                Some(MyAnalysisData::Shape(s)) => s.shape.slice().to_vec(),
+                _ => panic!("Could not find the shape of tensor {}", name),
+            };
+            env.entry(name.as_str())
+                .or_insert_with(|| ArrayD::from_shape_fn(shape, |_| rng.gen_range(-1.0, 1.0)));
+        }
+    }
+
+    /// The tensors making up an interpreted program's result.
+    fn tensors(value: Value<f64>) -> Vec<ArrayD<f64>> {
+        match value {
+            Value::Tensor(t) => vec![t.into_owned()],
+            Value::Access(a) => vec![a.tensor],
+            Value::Outputs(values) | Value::Tuple(values) => {
+                values.into_iter().flat_map(tensors).collect()
+            }
+            _ => panic!("Can only verify programs which produce tensors"),
+        }
+    }
+    let expected = tensors(interpret(original, original.as_ref().len() - 1, &env));
+    let actual = tensors(interpret(extracted, extracted.as_ref().len() - 1, &env));
+
+    if expected.len() != actual.len()
+        || expected
+            .iter()
+            .zip(actual.iter())
+            .any(|(e, a)| e.shape() != a.shape())
+    {
+        return Verification {
+            passed: false,
+            max_abs_diff: std::f64::INFINITY,
+            seed,
+        };
+    }
+
+    let diffs = expected
+        .iter()
+        .zip(actual.iter())
+        .flat_map(|(e, a)| e.iter().zip(a.iter()).map(|(e, a)| (e - a).abs()))
+        .collect::<Vec<_>>();
+    Verification {
+        // Written so that NaNs fail.
+        passed: diffs.iter().all(|diff| *diff <= tolerance),
+        max_abs_diff: diffs.iter().cloned().fold(0.0, f64::max),
+        seed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extraction::MonolithicCostFunction;
+    use crate::language::rewrites;
+    use egg::Extractor;
+
+    #[test]
+    fn systolic_array() {
+        let expr = "
+         (compute dot-product
+          (access-cartesian-product
+           (access (access-tensor t-32-32) 1)
+           (access (access-tensor t-32-32) 1)))"
+            .parse()
+            .unwrap();
+        let report = search(
+            &expr,
+            Runner::new(MyAnalysis::default()),
+            &[rewrites::systolic_array().rewrite],
+            RuleBudget::default(),
+            |egraph, id| {
+                Extractor::new(
+                    egraph,
+                    MonolithicCostFunction {
+                        egraph,
+                        systolic_array_configuration: (32, 32),
+                        prefer_systolic_arrays_with_blocking: false,
+                    },
+                )
+                .find_best(id)
+            },
+            1e-9,
+            0,
+        );
+
+        assert!(report.extracted.to_string().starts_with("(systolic-array"));
+        assert!(matches!(report.stop_reason, Some(StopReason::Saturated)));
+        assert!(report.verification.passed);
+        assert!(report.verification.max_abs_diff <= 1e-9);
+        assert_eq!(report.explanation, None);
+        assert!(report.capped_rules.is_empty());
+        assert_eq!(report.rules_applied["systolic-array"], 1);
+        assert_eq!(report.extracted_operators["systolic-array"], 1);
+        assert_eq!(report.iteration_stats.len(), report.iterations);
+
+        let json = report.to_json();
+        assert_eq!(json["extracted"], report.extracted.to_string());
+        assert_eq!(json["cost"], serde_json::json!(report.cost));
+        assert_eq!(json["verification"]["passed"], true);
+        assert_eq!(json["explanation"], serde_json::Value::Null);
+        assert_eq!(json["capped_rules"], serde_json::json!([]));
+        assert_eq!(json["iteration_stats"][0]["applied"]["systolic-array"], 1);
+        assert_eq!(json["extracted_operators"]["systolic-array"], 1);
+        assert!(json["saturation_time"].as_f64().unwrap() >= 0.0);
+    }
+
+    #[test]
+    fn rule_budget() {
+        let expr: RecExpr<Language> = "
+         (compute dot-product
+          (access-cartesian-product
+           (access (access-tensor t-32-32) 1)
+           (access (access-tensor t-32-32) 1)))"
+            .parse()
+            .unwrap();
+        let run = |budget| {
+            search(
+                &expr,
+                Runner::new(MyAnalysis::default()).with_iter_limit(3),
+                &[
+                    rewrites::systolic_array().rewrite,
+                    rewrites::slice_concatenate_accesses(
+                        0,
+                        rewrites::SliceConcatenateStrategy::DivideInto { segment_size: 16 },
+                    ),
+                ],
+                budget,
+                |egraph, id| Extractor::new(egraph, egg::AstSize).find_best(id),
+                1e-9,
+                0,
+            )
+        };
+
+        let unlimited = run(RuleBudget::default());
+        assert!(unlimited.capped_rules.is_empty());
+
+        // Denying the slicing rewrite is the same as allowing only the
+        // systolic array rewrite.
+        let mut budget = RuleBudget::default();
+        budget
+            .deny
+            .insert("slice-concatenate-access-axis-0-divide-into-16".to_string());
+        let denied = run(budget);
+        assert!(denied.egraph_nodes < unlimited.egraph_nodes);
+        let mut budget = RuleBudget::default();
+        budget.allow = Some(vec!["systolic-array".to_string()].into_iter().collect());
+        let allowed = run(budget);
+        assert_eq!(allowed.egraph_nodes, denied.egraph_nodes);
+        assert_eq!(allowed.egraph_classes, denied.egraph_classes);
+
+        let mut budget = RuleBudget::default();
+        budget.caps.insert(
+            "slice-concatenate-access-axis-0-divide-into-16".to_string(),
+            1,
+        );
+        budget.caps.insert("systolic-array".to_string(), 100);
+        let capped = run(budget);
+        assert_eq!(
+            capped.capped_rules,
+            vec!["slice-concatenate-access-axis-0-divide-into-16".to_string()]
+                .into_iter()
+                .collect()
+        );
+        assert!(capped.egraph_nodes < unlimited.egraph_nodes);
+        assert!(capped.verification.passed);
+    }
+
+    #[test]
+    fn explain_systolic_array() {
+        let expr = "
+         (compute dot-product
+          (access-cartesian-product
+           (access (access-tensor t-32-32) 1)
+           (access (access-tensor t-32-32) 1)))"
+            .parse()
+            .unwrap();
+        let report = search(
+            &expr,
+            Runner::new(MyAnalysis::default()).with_explanations_enabled(),
+            &[rewrites::systolic_array().rewrite],
+            RuleBudget::default(),
+            |egraph, id| {
+                Extractor::new(
+                    egraph,
+                    MonolithicCostFunction {
+                        egraph,
+                        systolic_array_configuration: (32, 32),
+                        prefer_systolic_arrays_with_blocking: false,
+                    },
+                )
+                .find_best(id)
+            },
+            1e-9,
+            0,
+        );
+
+        let explanation = report.explanation.unwrap();
+        assert_eq!(explanation.len(), 1);
+        assert_eq!(explanation[0].rule, "systolic-array");
+        assert!(!explanation[0].backward);
+        assert!(explanation[0].program.starts_with("(systolic-array"));
+    }
+
+    fn staged_rules() -> Vec<Rewrite<Language, MyAnalysis>> {
+        vec![
+            rewrites::systolic_array().rewrite,
+            rewrites::slice_concatenate_accesses(
+                0,
+                rewrites::SliceConcatenateStrategy::DivideInto { segment_size: 16 },
+            ),
+        ]
+    }
+
+    #[test]
+    fn staged_search_bounds_expansion() {
+        let expr: RecExpr<Language> = "
+         (compute dot-product
+          (access-cartesian-product
+           (access (access-tensor t-32-32) 1)
+           (access (access-tensor t-32-32) 1)))"
+            .parse()
+            .unwrap();
+        let schedule = Schedule {
+            phases: vec![
+                Phase::Expand {
+                    rules: vec!["slice-concatenate-access-axis-0-divide-into-16".to_string()],
+                    iterations: 2,
+                    max_matches: Some(1),
+                    node_limit: 10_000,
+                },
+                Phase::Cleanup {
+                    rules: vec!["systolic-array".to_string()],
+                    iter_limit: 30,
+                },
+            ],
+            rounds: 2,
+        };
+        let mut egraph = EGraph::new(MyAnalysis::default());
+        let id = egraph.add_expr(&expr);
+        let reports = staged_search(&mut egraph, &staged_rules(), &schedule).unwrap();
+
+        assert_eq!(
+            reports
+                .iter()
+                .map(|report| (report.round, report.phase))
+                .collect::<Vec<_>>(),
+            vec![(0, 0), (0, 1), (1, 0), (1, 1)]
+        );
+        assert!(reports[0]
+            .capped_rules
+            .contains("slice-concatenate-access-axis-0-divide-into-16"));
+        assert!(matches!(
+            reports[1].stop_reason,
+            Some(StopReason::Saturated)
+        ));
+
+        // Running every rewrite for as many iterations grows the egraph more.
+        let runner = Runner::default()
+            .with_iter_limit(4)
+            .with_expr(&expr)
+            .run(&staged_rules());
+        assert!(egraph.total_number_of_nodes() < runner.egraph.total_number_of_nodes());
+
+        // The result is still extractable and correct.
+        let (_, extracted) = Extractor::new(&egraph, egg::AstSize).find_best(id);
+        assert!(verify(&egraph, &expr, &extracted, 1e-9, 0).passed);
+    }
+
+    #[test]
+    fn schedule_from_json() {
+        let schedule = Schedule::from_json(
+            r#"{ "phases": [
+                   { "phase": "cleanup", "rules": ["systolic-array"] },
+                   { "phase": "expand", "rules": ["a", "b"], "iterations": 2,
+                     "max_matches": 4, "node_limit": 100 } ] }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            schedule,
+            Schedule {
+                phases: vec![
+                    Phase::Cleanup {
+                        rules: vec!["systolic-array".to_string()],
+                        iter_limit: 30,
+                    },
+                    Phase::Expand {
+                        rules: vec!["a".to_string(), "b".to_string()],
+                        iterations: 2,
+                        max_matches: Some(4),
+                        node_limit: 100,
+                    },
+                ],
+                rounds: 1,
+            }
+        );
+
+        assert!(matches!(
+            Schedule::from_json(r#"{ "phases": [{ "phase": "tile" }] }"#),
+            Err(GlensideError::Parse(_))
+        ));
+        let mut egraph = EGraph::new(MyAnalysis::default());
+        assert_eq!(
+            staged_search(&mut egraph, &staged_rules(), &schedule).err(),
+            Some(GlensideError::Parse(
+                "Unknown rule a in phase 1".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn wrong_program() {
+        let original: RecExpr<Language> = "(compute relu (access (access-tensor t-32-32) 0))"
+            .parse()
+            .unwrap();
+        let extracted: RecExpr<Language> = "(compute negative (access (access-tensor t-32-32) 0))"
+            .parse()
+            .unwrap();
+        let mut egraph = EGraph::new(MyAnalysis::default());
+        egraph.add_expr(&original);
+
+        let verification = verify(&egraph, &original, &extracted, 1e-9, 0);
+        assert!(!verification.passed);
+        assert!(verification.max_abs_diff > 0.0);
+        assert!(verification.max_abs_diff < 2.0);
+    }
+
+    #[test]
+    fn wrong_shape() {
+        let original: RecExpr<Language> = "(access (access-tensor t-32-32) 0)".parse().unwrap();
+        let extracted: RecExpr<Language> = "(access-flatten (access (access-tensor t-32-32) 0))"
+            .parse()
+            .unwrap();
+        let mut egraph = EGraph::new(MyAnalysis::default());
+        egraph.add_expr(&original);
+
+        assert_eq!(
+            verify(&egraph, &original, &extracted, 1e-9, 0),
+            Verification {
+                passed: false,
+                max_abs_diff: std::f64::INFINITY,
+                seed: 0,
+            }
+        );
+    }
+}
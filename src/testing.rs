@@ -0,0 +1,312 @@
+//! Utilities for testing: approximate comparison of tensors.
+//!
+//! Comparing results computed in different ways (by the interpreter, by Relay,
+//! by generated C) needs a tolerance, and the right kind of tolerance depends
+//! on the computation: absolute tolerances suit values near zero, relative
+//! tolerances suit large values, and ULP tolerances suit results which should
+//! differ only in rounding. [`Tolerance`] combines all three; an element
+//! passes if it's within any of them. When a comparison fails,
+//! [`assert_close`] reports how many elements differ, and where the worst
+//! difference is, rather than dumping both tensors.
+
+use ndarray::{ArrayBase, Data, Dimension, IxDyn};
+
+/// How close two elements must be to be considered equal. An element passes
+/// if it's within any of the tolerances. The default tolerance is exact
+/// equality.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Tolerance {
+    /// The largest allowed absolute difference.
+    pub absolute: f64,
+    /// The largest allowed difference, relative to the larger magnitude of
+    /// the two elements.
+    pub relative: f64,
+    /// The largest allowed number of representable values between the two
+    /// elements.
+    pub ulps: u64,
+}
+
+impl Tolerance {
+    pub fn absolute(absolute: f64) -> Self {
+        Tolerance {
+            absolute,
+            ..Default::default()
+        }
+    }
+
+    pub fn relative(relative: f64) -> Self {
+        Tolerance {
+            relative,
+            ..Default::default()
+        }
+    }
+
+    pub fn ulps(ulps: u64) -> Self {
+        Tolerance {
+            ulps,
+            ..Default::default()
+        }
+    }
+}
+
+/// Elements which can be compared approximately.
+pub trait ApproxElement: Copy + std::fmt::Debug {
+    fn to_f64(self) -> f64;
+    /// The number of representable values between `self` and `other`, or
+    /// `None` if either is NaN.
+    fn ulps_between(self, other: Self) -> Option<u64>;
+}
+
+impl ApproxElement for f64 {
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn ulps_between(self, other: Self) -> Option<u64> {
+        if self.is_nan() || other.is_nan() {
+            return None;
+        }
+        // Maps the bits of a float to integers which are ordered as the floats
+        // are, with -0 and +0 both mapping to 0.
+        fn ordered(f: f64) -> i128 {
+            let bits = f.to_bits() as i64;
+            if bits < 0 {
+                std::i64::MIN as i128 - bits as i128
+            } else {
+                bits as i128
+            }
+        }
+        Some((ordered(self) - ordered(other)).abs() as u64)
+    }
+}
+
+impl ApproxElement for f32 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn ulps_between(self, other: Self) -> Option<u64> {
+        if self.is_nan() || other.is_nan() {
+            return None;
+        }
+        fn ordered(f: f32) -> i64 {
+            let bits = f.to_bits() as i32;
+            if bits < 0 {
+                std::i32::MIN as i64 - bits as i64
+            } else {
+                bits as i64
+            }
+        }
+        Some((ordered(self) - ordered(other)).abs() as u64)
+    }
+}
+
+/// The element which differs the most between two tensors.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mismatch {
+    pub index: Vec<usize>,
+    pub actual: f64,
+    pub expected: f64,
+    /// `None` if either element is NaN.
+    pub ulps: Option<u64>,
+}
+
+/// The result of [`compare`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comparison {
+    pub num_elements: usize,
+    /// The number of elements not within the tolerance.
+    pub num_mismatches: usize,
+    /// The mismatched element with the largest absolute difference (NaNs
+    /// being the largest of all), if any.
+    pub worst: Option<Mismatch>,
+}
+
+impl Comparison {
+    pub fn passed(&self) -> bool {
+        self.num_mismatches == 0
+    }
+}
+
+impl std::fmt::Display for Comparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.worst {
+            None => write!(f, "all {} elements match", self.num_elements),
+            Some(worst) => write!(
+                f,
+                "{} of {} elements differ; worst at {:?}: actual {}, expected {} \
+                 (absolute difference {}, {} ulps)",
+                self.num_mismatches,
+                self.num_elements,
+                worst.index,
+                worst.actual,
+                worst.expected,
+                (worst.actual - worst.expected).abs(),
+                worst
+                    .ulps
+                    .map(|ulps| ulps.to_string())
+                    .unwrap_or_else(|| "NaN".to_string())
+            ),
+        }
+    }
+}
+
+/// Compares `actual` against `expected` elementwise. Panics if their shapes
+/// differ.
+pub fn compare<A, S1, S2>(
+    actual: &ArrayBase<S1, IxDyn>,
+    expected: &ArrayBase<S2, IxDyn>,
+    tolerance: Tolerance,
+) -> Comparison
+where
+    A: ApproxElement,
+    S1: Data<Elem = A>,
+    S2: Data<Elem = A>,
+{
+    assert_eq!(
+        actual.shape(),
+        expected.shape(),
+        "Shapes of actual and expected tensors differ"
+    );
+
+    let mut comparison = Comparison {
+        num_elements: actual.len(),
+        num_mismatches: 0,
+        worst: None,
+    };
+    // The absolute difference of the worst mismatch, with NaNs as infinite.
+    let mut worst_diff = std::f64::NEG_INFINITY;
+    for ((index, a), e) in actual.indexed_iter().zip(expected.iter()) {
+        let (a_f64, e_f64) = (a.to_f64(), e.to_f64());
+        let diff = (a_f64 - e_f64).abs();
+        let ulps = a.ulps_between(*e);
+        // Written so that NaNs fail.
+        let passed = diff <= tolerance.absolute
+            || diff <= tolerance.relative * a_f64.abs().max(e_f64.abs())
+            || ulps.map_or(false, |ulps| ulps <= tolerance.ulps);
+        if passed {
+            continue;
+        }
+
+        comparison.num_mismatches += 1;
+        let diff = if diff.is_nan() {
+            std::f64::INFINITY
+        } else {
+            diff
+        };
+        if diff > worst_diff || comparison.worst.is_none() {
+            worst_diff = diff;
+            comparison.worst = Some(Mismatch {
+                index: index.slice().to_vec(),
+                actual: a_f64,
+                expected: e_f64,
+                ulps,
+            });
+        }
+    }
+    comparison
+}
+
+/// Asserts that `actual` is within `tolerance` of `expected`, reporting the
+/// worst mismatch otherwise.
+///
+/// ```
+/// use glenside::testing::{assert_close, Tolerance};
+/// use ndarray::array;
+///
+/// assert_close(
+///     &array![1.0, 100.0].into_dyn(),
+///     &array![1.0 + 1e-9, 100.0 + 1e-5].into_dyn(),
+///     Tolerance {
+///         absolute: 1e-8,
+///         relative: 1e-6,
+///         ..Default::default()
+///     },
+/// );
+/// ```
+///
+/// ```should_panic
+/// use glenside::testing::{assert_close, Tolerance};
+/// use ndarray::array;
+///
+/// // Panics with "1 of 3 elements differ; worst at [2]: actual 3, expected
+/// // 3.5 (absolute difference 0.5, 2097152 ulps)".
+/// assert_close(
+///     &array![1f32, 2., 3.].into_dyn(),
+///     &array![1f32, 2., 3.5].into_dyn(),
+///     Tolerance::absolute(1e-3),
+/// );
+/// ```
+pub fn assert_close<A, S1, S2>(
+    actual: &ArrayBase<S1, IxDyn>,
+    expected: &ArrayBase<S2, IxDyn>,
+    tolerance: Tolerance,
+) where
+    A: ApproxElement,
+    S1: Data<Elem = A>,
+    S2: Data<Elem = A>,
+{
+    let comparison = compare(actual, expected, tolerance);
+    assert!(comparison.passed(), "{}", comparison);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    fn ulps() {
+        assert_eq!(1f32.ulps_between(1f32), Some(0));
+        assert_eq!(
+            1f64.ulps_between(f64::from_bits(1f64.to_bits() + 3)),
+            Some(3)
+        );
+        // Across zero.
+        assert_eq!(f32::from_bits(1).ulps_between(-f32::from_bits(1)), Some(2));
+        assert_eq!(0f64.ulps_between(-0f64), Some(0));
+        assert_eq!(std::f32::NAN.ulps_between(1.), None);
+    }
+
+    #[test]
+    fn worst_mismatch() {
+        let comparison = compare(
+            &array![[1., 2.], [3., 4.]].into_dyn(),
+            &array![[1.5, 2.], [3., 6.]].into_dyn(),
+            Tolerance::relative(0.1),
+        );
+        assert_eq!(comparison.num_elements, 4);
+        assert_eq!(comparison.num_mismatches, 2);
+        let worst = comparison.worst.unwrap();
+        assert_eq!(worst.index, vec![1, 1]);
+        assert_eq!((worst.actual, worst.expected), (4., 6.));
+    }
+
+    #[test]
+    fn nans_fail() {
+        let comparison = compare(
+            &array![1., std::f64::NAN].into_dyn(),
+            &array![100., 1.].into_dyn(),
+            Tolerance::absolute(1.),
+        );
+        assert_eq!(comparison.num_mismatches, 2);
+        assert_eq!(comparison.worst.unwrap().index, vec![1]);
+    }
+
+    #[test]
+    fn ulps_tolerance() {
+        let one_ulp_off = f32::from_bits(1f32.to_bits() + 1);
+        assert!(compare(
+            &array![1f32].into_dyn(),
+            &array![one_ulp_off].into_dyn(),
+            Tolerance::ulps(1)
+        )
+        .passed());
+        assert!(!compare(
+            &array![1f32].into_dyn(),
+            &array![one_ulp_off].into_dyn(),
+            Tolerance::default()
+        )
+        .passed());
+    }
+}
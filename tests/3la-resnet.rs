@@ -25,6 +25,7 @@ fn test_resnet_flexmatch() {
     let mut egraph = EGraph::new(MyAnalysis {
         name_to_shape: env.clone(),
         name_to_dtype: dtypes_info.iter().cloned().collect(),
+        shape_cache: None,
     });
     let mut rws = vec![
         // glenside::language::rewrites::bubble_reshape_through_linear_generalized(),
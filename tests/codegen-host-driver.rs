@@ -0,0 +1,113 @@
+use egg::RecExpr;
+use glenside::codegen::{codegen_host_driver, HOST_RUNTIME_API};
+use glenside::language::schedule::schedule;
+use glenside::language::MyAnalysis;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::process::Command;
+use std::str::FromStr;
+
+/// Runs a driver against a simulated runtime, which reads a padded,
+/// transposed input in place.
+#[test]
+fn codegen_host_driver_relu() {
+    let dir = std::env::temp_dir();
+    let header_filepath = dir.join("glenside_runtime.h");
+    let driver_filepath = dir.join("host-driver.c");
+    let main_filepath = dir.join("host-driver-main.c");
+    let out_filepath = dir.join("codegen-host-driver");
+
+    let program = "
+     (compute relu
+      (access-transpose
+       (access-pad (access (access-tensor x) 1) zero-padding 1 1 1)
+       (list 1 0)))";
+    let mut name_to_shape = HashMap::default();
+    name_to_shape.insert("x".to_string(), vec![2, 3]);
+    let schedule = schedule(
+        &RecExpr::from_str(program).unwrap(),
+        MyAnalysis {
+            name_to_shape,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        },
+    );
+
+    let driver = codegen_host_driver(&schedule, "model");
+    File::create(&header_filepath)
+        .unwrap()
+        .write_all(HOST_RUNTIME_API.as_bytes())
+        .unwrap();
+    File::create(&driver_filepath)
+        .unwrap()
+        .write_all(driver.as_bytes())
+        .unwrap();
+    File::create(&main_filepath)
+        .unwrap()
+        .write_all(
+            format!(
+                "
+#include <assert.h>
+#include <stdio.h>
+#include \"{}\"
+
+float x[2][3] = {{ {{ -1, 2, -3 }}, {{ 4, -5, 6 }} }};
+float expected_out[5][2] = {{ {{ 0, 0 }}, {{ 0, 4 }}, {{ 2, 0 }}, {{ 0, 6 }}, {{ 0, 0 }} }};
+float out[5][2];
+
+int main() {{
+  model_init();
+  model_load_x(&x[0][0]);
+  model_run();
+  model_read_output_0(&out[0][0]);
+  model_release();
+
+  int i, j;
+  for (i = 0; i < 5; ++i) {{
+    for (j = 0; j < 2; ++j) {{
+      fprintf(stderr, \"%f ?= %f\\n\", out[i][j], expected_out[i][j]);
+      assert(out[i][j] == expected_out[i][j]);
+    }}
+  }}
+
+  return 0;
+}}
+",
+                driver_filepath.display()
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+    let output = Command::new("gcc")
+        .arg("-Werror")
+        .arg(&main_filepath)
+        .arg(format!(
+            "{}/data/codegen-host-driver/runtime.c",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .arg("-I")
+        .arg(&dir)
+        .arg("-o")
+        .arg(&out_filepath)
+        .current_dir(&dir)
+        .output()
+        .expect("Failed to compile main file with gcc");
+    assert!(
+        output.status.success(),
+        "Compilation failed. stderr:\n{}",
+        std::str::from_utf8(output.stderr.as_slice()).expect("Could not convert stderr to UTF8")
+    );
+
+    let output = Command::new(&out_filepath)
+        .current_dir(&dir)
+        .output()
+        .expect("Failed to run result");
+    assert!(
+        output.status.success(),
+        "Main binary failed with code {:?}. stderr:\n{}",
+        output.status.code(),
+        std::str::from_utf8(output.stderr.as_slice()).expect("Could not convert stderr to UTF8")
+    );
+}
@@ -53,6 +53,7 @@ fn codegen_mlp() {
     let mut egraph = EGraph::new(MyAnalysis {
         name_to_shape: map,
         name_to_dtype: HashMap::default(),
+        shape_cache: None,
     });
     let id = egraph.add_expr(&expr);
 
@@ -31,6 +31,7 @@ fn test_conv1d_flexmatch() {
     let mut egraph = EGraph::new(MyAnalysis {
         name_to_shape: env.clone(),
         name_to_dtype: dtype_info.iter().cloned().collect(),
+        shape_cache: None,
     });
     let mut rws = vec![
         glenside::language::rewrites::flatten_unflatten_any_access(),
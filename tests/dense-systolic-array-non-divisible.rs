@@ -0,0 +1,227 @@
+use egg::{EGraph, Pattern, RecExpr, Runner, Searcher};
+use glenside::language::interpreter::*;
+use glenside::language::*;
+use glenside::testing::{assert_close, Tolerance};
+use ndarray_rand::{rand_distr::Uniform, RandomExt};
+use rand::{rngs::SmallRng, SeedableRng};
+use std::collections::HashMap;
+
+/// Maps the dense multiplication `(access (access-tensor x) ...)` by
+/// `(access-tensor w)` onto a `rows`x`cols` systolic array, padding as needed.
+/// Checks that `padded` (the padded-and-sliced version of the computation) and
+/// `tensorized` (the same, with the padded computation mapped to a systolic
+/// array) are both found, and that both compute the same thing as the
+/// original program.
+fn test_dense(
+    x_shape: &[usize],
+    w_shape: &[usize],
+    rows: usize,
+    cols: usize,
+    padded: &str,
+    tensorized: &str,
+) {
+    let program = format!(
+        "
+         (compute dot-product
+          (access-cartesian-product
+           (access (access-tensor x) {})
+           (access-transpose (access (access-tensor w) 1) (list 1 0))
+          )
+         )
+        ",
+        x_shape.len() - 1
+    )
+    .parse::<RecExpr<Language>>()
+    .unwrap();
+
+    let mut map = HashMap::default();
+    map.insert("x".to_string(), x_shape.to_vec());
+    map.insert("w".to_string(), w_shape.to_vec());
+
+    let mut egraph = EGraph::new(MyAnalysis {
+        name_to_shape: map,
+        name_to_dtype: HashMap::default(),
+        shape_cache: None,
+    });
+    let id = egraph.add_expr(&program);
+
+    let rws = vec![
+        rewrites::pad_dense_for_systolic_array_with_blocking(rows, cols),
+        rewrites::systolic_array_with_blocking(rows, cols).rewrite,
+    ];
+
+    let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+        .with_egraph(egraph)
+        .run(&rws);
+    match runner.stop_reason.unwrap() {
+        egg::StopReason::Saturated => (),
+        _ => panic!(),
+    };
+
+    assert!(padded
+        .parse::<Pattern<_>>()
+        .unwrap()
+        .search_eclass(&runner.egraph, id)
+        .is_some());
+    assert!(tensorized
+        .parse::<Pattern<_>>()
+        .unwrap()
+        .search_eclass(&runner.egraph, id)
+        .is_some());
+
+    // The padded and tensorized programs should compute the same result as
+    // the original.
+    let mut tensor_rng = SmallRng::seed_from_u64(23);
+    let mut env = Environment::new();
+    env.insert(
+        "x",
+        ndarray::ArrayD::<f64>::random_using(
+            x_shape.to_vec(),
+            Uniform::new(-1f64, 1f64),
+            &mut tensor_rng,
+        ),
+    );
+    env.insert(
+        "w",
+        ndarray::ArrayD::<f64>::random_using(
+            w_shape.to_vec(),
+            Uniform::new(-1f64, 1f64),
+            &mut tensor_rng,
+        ),
+    );
+    let original = match interpret(&program, program.as_ref().len() - 1, &env) {
+        Value::Access(a) => a.tensor,
+        _ => panic!(),
+    };
+    for rewritten in &[padded, tensorized] {
+        let rewritten = rewritten.parse::<RecExpr<Language>>().unwrap();
+        match interpret(&rewritten, rewritten.as_ref().len() - 1, &env) {
+            Value::Access(rewritten) => {
+                assert_eq!(original.shape(), rewritten.tensor.shape());
+                assert_close(&original, &rewritten.tensor, Tolerance::absolute(1e-10));
+            }
+            _ => panic!(),
+        }
+    }
+}
+
+#[test]
+fn dense_pad_reduction_and_output_axes() {
+    // K=30 and N=20 are padded up to 32 for a 16x16 array.
+    test_dense(
+        &[10, 30],
+        &[30, 20],
+        16,
+        16,
+        "
+        (access-slice
+         (compute dot-product
+          (access-cartesian-product
+           (access-pad (access (access-tensor x) 1) zero-padding 1 0 2)
+           (access-pad
+            (access-pad
+             (access-transpose (access (access-tensor w) 1) (list 1 0))
+             zero-padding 1 0 2)
+            zero-padding 0 0 12)
+          )
+         )
+         1 0 20
+        )
+        ",
+        "
+        (access-slice
+         (systolic-array-with-blocking 16 16
+          (access-pad (access (access-tensor x) 1) zero-padding 1 0 2)
+          (access
+           (access-transpose
+            (access-pad
+             (access-pad
+              (access-transpose (access (access-tensor w) 1) (list 1 0))
+              zero-padding 1 0 2)
+             zero-padding 0 0 12)
+            (list 1 0))
+           0)
+         )
+         1 0 20
+        )
+        ",
+    );
+}
+
+#[test]
+fn dense_pad_output_axis_only() {
+    // K=32 already divides evenly into 16 rows; only N=10 needs padding.
+    test_dense(
+        &[3, 32],
+        &[32, 10],
+        16,
+        8,
+        "
+        (access-slice
+         (compute dot-product
+          (access-cartesian-product
+           (access (access-tensor x) 1)
+           (access-pad
+            (access-transpose (access (access-tensor w) 1) (list 1 0))
+            zero-padding 0 0 6)
+          )
+         )
+         1 0 10
+        )
+        ",
+        "
+        (access-slice
+         (systolic-array-with-blocking 16 8
+          (access (access-tensor x) 1)
+          (access
+           (access-transpose
+            (access-pad
+             (access-transpose (access (access-tensor w) 1) (list 1 0))
+             zero-padding 0 0 6)
+            (list 1 0))
+           0)
+         )
+         1 0 10
+        )
+        ",
+    );
+}
+
+#[test]
+fn dense_vector_pad_reduction_axis_only() {
+    // A vector-matrix multiplication, where only K=20 needs padding.
+    test_dense(
+        &[20],
+        &[20, 16],
+        8,
+        16,
+        "
+        (access-slice
+         (compute dot-product
+          (access-cartesian-product
+           (access-pad (access (access-tensor x) 0) zero-padding 0 0 4)
+           (access-pad
+            (access-transpose (access (access-tensor w) 1) (list 1 0))
+            zero-padding 1 0 4)
+          )
+         )
+         0 0 16
+        )
+        ",
+        "
+        (access-slice
+         (systolic-array-with-blocking 8 16
+          (access-pad (access (access-tensor x) 0) zero-padding 0 0 4)
+          (access
+           (access-transpose
+            (access-pad
+             (access-transpose (access (access-tensor w) 1) (list 1 0))
+             zero-padding 1 0 4)
+            (list 1 0))
+           0)
+         )
+         0 0 16
+        )
+        ",
+    );
+}
@@ -0,0 +1,145 @@
+use std::{
+    fs::{read_dir, read_to_string, write},
+    path::Path,
+    str::FromStr,
+};
+
+use egg::{AstSize, EGraph, Extractor, RecExpr, Rewrite, Runner};
+use glenside::extraction::{MonolithicCostFunction, SimpleCostFunction};
+use glenside::language::canonical::canonicalize;
+use glenside::language::{rewrites, Language, MyAnalysis};
+
+/// The rewrite named `name`. Parameterized rewrites are named with their
+/// parameters, as in the rewrites' own names.
+fn rule(name: &str) -> Rewrite<Language, MyAnalysis> {
+    let parameters = |prefix: &str| -> Vec<usize> {
+        name[prefix.len()..]
+            .split('-')
+            .map(|p| p.parse().unwrap())
+            .collect()
+    };
+    match name {
+        "systolic-array" => rewrites::systolic_array().rewrite,
+        "systolic-array-with-activation" => rewrites::systolic_array_with_activation().rewrite,
+        "collapse-nested-transposes" => rewrites::collapse_nested_transposes(),
+        "remove-trivial-transpose" => rewrites::remove_trivial_transpose(),
+        "collapse-nested-accesses" => rewrites::collapse_nested_accesses(),
+        "pooling-unit" => rewrites::pooling_unit().rewrite,
+        "activation-unit" => rewrites::activation_unit().rewrite,
+        _ if name.starts_with("systolic-array-with-blocking-") => {
+            match parameters("systolic-array-with-blocking-")[..] {
+                [rows, cols] => rewrites::systolic_array_with_blocking(rows, cols).rewrite,
+                _ => panic!("Expected rows and columns in {}", name),
+            }
+        }
+        _ if name.starts_with("vector-alu-") => match parameters("vector-alu-")[..] {
+            [lanes] => rewrites::vector_alu(lanes).rewrite,
+            _ => panic!("Expected a number of lanes in {}", name),
+        },
+        _ => panic!("Unsupported rule {} in extraction corpus", name),
+    }
+}
+
+/// Extracts the best program from `egraph` with the cost function named
+/// `cost_function`.
+fn extract(
+    egraph: &EGraph<Language, MyAnalysis>,
+    id: egg::Id,
+    cost_function: &str,
+) -> (usize, RecExpr<Language>) {
+    match cost_function {
+        "ast-size" => Extractor::new(egraph, AstSize).find_best(id),
+        "simple" => Extractor::new(egraph, SimpleCostFunction::default()).find_best(id),
+        _ if cost_function.starts_with("monolithic-") => {
+            let dims = cost_function["monolithic-".len()..]
+                .split('-')
+                .map(|d| d.parse().unwrap())
+                .collect::<Vec<usize>>();
+            assert_eq!(dims.len(), 2, "Expected monolithic-<rows>-<cols>");
+            Extractor::new(
+                egraph,
+                MonolithicCostFunction {
+                    systolic_array_configuration: (dims[0], dims[1]),
+                    egraph,
+                    prefer_systolic_arrays_with_blocking: false,
+                },
+            )
+            .find_best(id)
+        }
+        _ => panic!("Unsupported cost function {}", cost_function),
+    }
+}
+
+/// This test replays all of the cases in the /extraction-corpus directory,
+/// and lists every case whose extracted program or cost has changed.
+#[test]
+fn extraction_corpus() {
+    let update = std::env::var("GLENSIDE_UPDATE_EXTRACTION_CORPUS").is_ok();
+    let mut failures = Vec::default();
+    let mut num_cases = 0;
+
+    for entry in read_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("extraction-corpus"))
+        .unwrap()
+        .map(|e| e.unwrap())
+        .filter(|e| e.path().is_dir())
+    {
+        num_cases += 1;
+        let dir = entry.path();
+        let name = dir.file_name().unwrap().to_str().unwrap().to_string();
+
+        let program =
+            RecExpr::<Language>::from_str(&read_to_string(dir.join("program.glenside")).unwrap())
+                .unwrap();
+        let expected =
+            RecExpr::<Language>::from_str(&read_to_string(dir.join("expected.glenside")).unwrap())
+                .unwrap();
+        let mut case: serde_json::Value =
+            serde_json::from_str(&read_to_string(dir.join("case.json")).unwrap()).unwrap();
+        let rules = case["rules"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| rule(r.as_str().unwrap()))
+            .collect::<Vec<_>>();
+        let cost_function = case["cost_function"].as_str().unwrap().to_string();
+        let expected_cost = case["expected_cost"].as_u64().unwrap() as usize;
+
+        let runner = Runner::<_, _, ()>::new(MyAnalysis::from_tensor_decls(&program))
+            .with_expr(&program)
+            .run(&rules);
+        let (cost, extracted) = extract(&runner.egraph, runner.roots[0], &cost_function);
+        // Programs are compared in canonical form, so that extraction picking
+        // an equivalent program (e.g. with an addition's operands swapped)
+        // doesn't count as a change.
+        let (extracted, expected) = (canonicalize(&extracted), canonicalize(&expected));
+
+        if extracted != expected || cost != expected_cost {
+            failures.push(format!(
+                "{}: expected cost {}, got {}\nexpected:\n{}\ngot:\n{}",
+                name,
+                expected_cost,
+                cost,
+                expected.pretty(80),
+                extracted.pretty(80)
+            ));
+            if update {
+                write(dir.join("expected.glenside"), extracted.pretty(80) + "\n").unwrap();
+                case["expected_cost"] = cost.into();
+                write(
+                    dir.join("case.json"),
+                    serde_json::to_string_pretty(&case).unwrap() + "\n",
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    assert!(num_cases > 0, "Found no cases in the extraction corpus");
+    assert!(
+        failures.is_empty() || update,
+        "{} of {} extraction corpus cases changed:\n\n{}",
+        failures.len(),
+        num_cases,
+        failures.join("\n\n")
+    );
+}
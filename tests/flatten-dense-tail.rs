@@ -0,0 +1,89 @@
+use egg::{EGraph, Extractor, RecExpr, Runner};
+use glenside::extraction::SimpleCostFunction;
+use glenside::language::*;
+use glenside::search::verify;
+use std::collections::HashMap;
+
+/// Maps the classifier at the end of a CNN: the feature maps are flattened,
+/// then go through a dense layer, a ReLU, another dense layer, and a softmax.
+/// Every computation should map onto an atom: the dense layers onto systolic
+/// arrays (the first with the ReLU fused in), and the softmax onto the
+/// activation unit.
+#[test]
+fn flatten_dense_tail() {
+    let program = "
+     (compute softmax
+      (access
+       (compute dot-product
+        (access-cartesian-product
+         (access
+          (compute relu
+           (compute dot-product
+            (access-cartesian-product
+             (access (access-flatten (access (access-tensor features) 1)) 1)
+             (access (access-tensor fc1) 1))))
+          1)
+         (access (access-tensor fc2) 1)))
+       1))
+    "
+    .parse::<RecExpr<Language>>()
+    .unwrap();
+
+    let mut map = HashMap::default();
+    map.insert("features".to_string(), vec![2, 8, 3, 3]);
+    map.insert("fc1".to_string(), vec![32, 72]);
+    map.insert("fc2".to_string(), vec![10, 32]);
+    let mut egraph = EGraph::new(MyAnalysis {
+        name_to_shape: map,
+        name_to_dtype: HashMap::default(),
+        shape_cache: None,
+    });
+    let id = egraph.add_expr(&program);
+
+    let rws = vec![
+        rewrites::systolic_array().rewrite,
+        rewrites::activation_unit().rewrite,
+        rewrites::systolic_array_with_activation().rewrite,
+    ];
+    let runner = Runner::<_, _, ()>::new(MyAnalysis::default())
+        .with_egraph(egraph)
+        .run(&rws);
+    match runner.stop_reason.as_ref().unwrap() {
+        egg::StopReason::Saturated => (),
+        _ => panic!(),
+    };
+
+    // Computes can't be extracted, so a finite cost means everything mapped.
+    let (cost, extracted) = Extractor::new(&runner.egraph, SimpleCostFunction::default())
+        .find_best(runner.egraph.find(id));
+    assert!(cost < usize::MAX);
+    assert!(!extracted
+        .as_ref()
+        .iter()
+        .any(|node| matches!(node, Language::Compute(_))));
+    assert_eq!(
+        extracted
+            .as_ref()
+            .iter()
+            .filter(|node| matches!(node, Language::SystolicArrayWithActivation(_)))
+            .count(),
+        1
+    );
+    assert_eq!(
+        extracted
+            .as_ref()
+            .iter()
+            .filter(|node| matches!(node, Language::SystolicArray(_)))
+            .count(),
+        1
+    );
+    match extracted.as_ref().last().unwrap() {
+        Language::ActivationUnit(_) => (),
+        other => panic!("Expected an activation unit, found {:?}", other),
+    }
+    assert!(extracted
+        .to_string()
+        .contains("(systolic-array-with-activation relu 72 32"));
+
+    assert!(verify(&runner.egraph, &program, &extracted, 1e-9, 0).passed);
+}
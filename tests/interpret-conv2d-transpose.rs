@@ -0,0 +1,90 @@
+mod common;
+
+use common::load_npy;
+use egg::RecExpr;
+use glenside::language::from_relay;
+use glenside::language::interpreter::*;
+use glenside::language::Language;
+use glenside::testing::{assert_close, Tolerance};
+use std::str::FromStr;
+
+/// Checks both the high-level transposed convolution and its lowering against
+/// the reference data generated by data/conv2d_transpose.py.
+#[test]
+fn interpret_conv2d_transpose() {
+    let expr = RecExpr::<Language>::from_str(
+        "
+         (conv2d-transpose
+          (access-tensor activations)
+          (access-tensor filters)
+          (shape 2 2)
+          (shape 1 1 1 1)
+          (shape 1 1)
+         )
+        ",
+    )
+    .unwrap();
+
+    let mut lowered = RecExpr::default();
+    let activations_id = lowered.add(Language::Symbol("activations".to_string()));
+    let activations_id = lowered.add(Language::AccessTensor(activations_id));
+    let filters_id = lowered.add(Language::Symbol("filters".to_string()));
+    let filters_id = lowered.add(Language::AccessTensor(filters_id));
+    from_relay::conv2d_transpose(
+        &mut lowered,
+        activations_id,
+        &[1, 4, 8, 8],
+        filters_id,
+        &[4, 3, 3, 3],
+        &[2, 2],
+        &[1, 1, 1, 1],
+        &[1, 1],
+        &[1, 1],
+        1,
+        "NCHW",
+        "IOHW",
+        "",
+    );
+
+    let filters = load_npy::<f32>(
+        format!(
+            "{}/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            "data/conv2d_transpose_filters.npy"
+        )
+        .as_str(),
+    );
+    assert_eq!(filters.shape(), &[4, 3, 3, 3]);
+    let activations = load_npy::<f32>(
+        format!(
+            "{}/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            "data/conv2d_transpose_activations.npy"
+        )
+        .as_str(),
+    );
+    assert_eq!(activations.shape(), &[1, 4, 8, 8]);
+    let result = load_npy::<f32>(
+        format!(
+            "{}/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            "data/conv2d_transpose_result.npy"
+        )
+        .as_str(),
+    );
+    assert_eq!(result.shape(), &[1, 3, 16, 16]);
+
+    let mut env = Environment::new();
+    env.insert("filters", filters);
+    env.insert("activations", activations);
+
+    for expr in &[expr, lowered] {
+        match interpret(expr, expr.as_ref().len() - 1, &env) {
+            Value::Access(a) => {
+                assert_eq!(a.tensor.shape(), result.shape());
+                assert_close(&a.tensor, &result, Tolerance::absolute(1e-5));
+            }
+            _ => panic!(),
+        };
+    }
+}
@@ -4,8 +4,10 @@ use common::load_npy;
 use egg::RecExpr;
 use glenside::language::interpreter::*;
 use glenside::language::Language;
+use glenside::testing::{assert_close, Tolerance};
 use std::str::FromStr;
 
+/// Fixtures generated by `cargo run --bin gen_test_data -- conv2d`.
 #[test]
 fn interpret_conv2d() {
     // TODO(@gussmith) Support batch dimension
@@ -60,12 +62,11 @@ fn interpret_conv2d() {
     env.insert("filters", filters);
     env.insert("activations", activations);
 
-    use approx::AbsDiffEq;
     match interpret(&expr, expr.as_ref().len() - 1, &env) {
         Value::Access(a) => {
             assert_eq!(a.tensor.shape(), result.shape());
             // TODO(@gussmith) Is this tolerance too big?
-            assert!(a.tensor.abs_diff_eq(&result, 5e-6));
+            assert_close(&a.tensor, &result, Tolerance::absolute(5e-6));
         }
         _ => panic!(),
     };
@@ -0,0 +1,46 @@
+mod common;
+
+use common::load_npy;
+use egg::RecExpr;
+use glenside::language::interpreter::*;
+use glenside::language::Language;
+use glenside::models::lstm_cell;
+use glenside::testing::{assert_close, Tolerance};
+
+fn load(name: &str) -> ndarray::ArrayD<f32> {
+    load_npy::<f32>(format!("{}/data/lstm_cell_{}.npy", env!("CARGO_MANIFEST_DIR"), name).as_str())
+}
+
+/// Checks an LSTM cell step built by [`lstm_cell`] against the reference data
+/// generated by data/lstm_cell.py.
+#[test]
+fn interpret_lstm_cell() {
+    let mut expr = RecExpr::default();
+    let mut ids = Vec::default();
+    for name in &["x", "h", "c", "w_ih", "w_hh"] {
+        let id = expr.add(Language::Symbol(name.to_string()));
+        ids.push(expr.add(Language::AccessTensor(id)));
+    }
+    let (h_next_id, c_next_id) = lstm_cell(&mut expr, ids[0], ids[1], ids[2], ids[3], ids[4], 3);
+
+    let mut env = Environment::new();
+    env.insert("x", load("x"));
+    env.insert("h", load("h"));
+    env.insert("c", load("c"));
+    env.insert("w_ih", load("w_ih"));
+    env.insert("w_hh", load("w_hh"));
+    assert_eq!(env["w_ih"].shape(), &[12, 4]);
+    assert_eq!(env["w_hh"].shape(), &[12, 3]);
+
+    for (id, name) in &[(h_next_id, "h_next"), (c_next_id, "c_next")] {
+        let result = load(name);
+        assert_eq!(result.shape(), &[2, 3]);
+        match interpret(&expr, usize::from(*id), &env) {
+            Value::Access(a) => {
+                assert_eq!(a.tensor.shape(), result.shape());
+                assert_close(&a.tensor, &result, Tolerance::absolute(1e-5));
+            }
+            _ => panic!(),
+        }
+    }
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use common::load_npy;
+use egg::RecExpr;
+use glenside::language::interpreter::*;
+use glenside::language::Language;
+use std::str::FromStr;
+
+/// Fixtures generated by `cargo run --bin gen_test_data -- max_pool2d`.
+#[test]
+fn interpret_max_pool2d() {
+    let expr = RecExpr::<Language>::from_str(
+        "
+         (compute reduce-max
+          (access-windows
+           (access (access-tensor activations) 1)
+           (shape 2 2)
+           (shape 2 2)
+          )
+         )
+        ",
+    )
+    .unwrap();
+
+    let activations = load_npy::<f32>(
+        format!(
+            "{}/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            "data/max_pool2d_activations.npy"
+        )
+        .as_str(),
+    );
+    assert_eq!(activations.shape(), &[4, 32, 32]);
+    let result = load_npy::<f32>(
+        format!(
+            "{}/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            "data/max_pool2d_result.npy"
+        )
+        .as_str(),
+    );
+    assert_eq!(result.shape(), &[4, 16, 16]);
+
+    let mut env = Environment::new();
+    env.insert("activations", activations);
+
+    match interpret(&expr, expr.as_ref().len() - 1, &env) {
+        Value::Access(a) => assert_eq!(a.tensor, result),
+        _ => panic!(),
+    };
+}
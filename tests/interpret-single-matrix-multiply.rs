@@ -0,0 +1,64 @@
+mod common;
+
+use common::load_npy;
+use egg::RecExpr;
+use glenside::language::interpreter::*;
+use glenside::language::Language;
+use glenside::testing::{assert_close, Tolerance};
+use std::str::FromStr;
+
+/// Fixtures generated by `cargo run --bin gen_test_data -- single_matrix_multiply`.
+#[test]
+fn interpret_single_matrix_multiply() {
+    let expr = RecExpr::<Language>::from_str(
+        "
+         (compute dot-product
+          (access-cartesian-product
+           (access (access-tensor a) 1)
+           (access (access-transpose (access (access-tensor b) 1) (list 1 0)) 1)
+          )
+         )
+        ",
+    )
+    .unwrap();
+
+    let a = load_npy::<f64>(
+        format!(
+            "{}/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            "data/single_matrix_multiply_input_a.npy"
+        )
+        .as_str(),
+    );
+    assert_eq!(a.shape(), &[64, 64]);
+    let b = load_npy::<f64>(
+        format!(
+            "{}/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            "data/single_matrix_multiply_input_b.npy"
+        )
+        .as_str(),
+    );
+    assert_eq!(b.shape(), &[64, 64]);
+    let result = load_npy::<f64>(
+        format!(
+            "{}/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            "data/single_matrix_multiply_output.npy"
+        )
+        .as_str(),
+    );
+    assert_eq!(result.shape(), &[64, 64]);
+
+    let mut env = Environment::new();
+    env.insert("a", a);
+    env.insert("b", b);
+
+    match interpret(&expr, expr.as_ref().len() - 1, &env) {
+        Value::Access(a) => {
+            assert_eq!(a.tensor.shape(), result.shape());
+            assert_close(&a.tensor, &result, Tolerance::absolute(1e-10));
+        }
+        _ => panic!(),
+    };
+}
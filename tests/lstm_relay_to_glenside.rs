@@ -35,6 +35,7 @@ fn lstm_relay_to_glenside() {
     let mut egraph = EGraph::new(MyAnalysis {
         name_to_shape: shapes_vec.iter().cloned().collect(),
         name_to_dtype: dtypes_vec.iter().cloned().collect(),
+        shape_cache: None,
     });
 
     let id = egraph.add_expr(&expr);
@@ -43,6 +43,7 @@ fn parse_mobilenet_simplified_for_inference() {
     let mut egraph = EGraph::new(MyAnalysis {
         name_to_shape: env.clone(),
         name_to_dtype: dtypes_vec.into_iter().collect(),
+        shape_cache: None,
     });
     egraph.add_expr(&expr);
 }
@@ -87,6 +88,7 @@ fn parse_mobilenet() {
     let mut egraph = EGraph::new(MyAnalysis {
         name_to_shape: env.clone(),
         name_to_dtype: dtypes_vec.into_iter().collect(),
+        shape_cache: None,
     });
     egraph.add_expr(&expr);
 }
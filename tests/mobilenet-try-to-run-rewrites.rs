@@ -53,6 +53,7 @@ fn mobilenet_try_to_run_rewrites() {
     let mut egraph = EGraph::new(MyAnalysis {
         name_to_shape: env.clone(),
         name_to_dtype: dtypes_vec.into_iter().collect(),
+        shape_cache: None,
     });
     let _id = egraph.add_expr(&expr);
 
@@ -66,7 +67,7 @@ fn mobilenet_try_to_run_rewrites() {
         glenside::language::rewrites::bubble_access_concatenate_through_compute_dot_product_item_axis(),
         glenside::language::rewrites::bubble_access_concatenate_through_compute_dot_product_not_item_axis(),
         glenside::language::rewrites::bubble_access_slice_through_access_pad_inequal_axes(),
-        glenside::language::rewrites::systolic_array(),
+        glenside::language::rewrites::systolic_array().rewrite,
         glenside::language::rewrites::pad_slice_accesses(
             0,
             PadSliceStrategy::PadToClosestMultipleOf {
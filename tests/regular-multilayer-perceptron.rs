@@ -80,7 +80,7 @@ fn regular_multilayer_perceptron() {
         // )
         // and rewrites it to:
         // (bsg-systolic-array <rows> <cols> < 1xrows vector> <a rowsxcols tensor>)
-        rewrites::systolic_array(),
+        rewrites::systolic_array().rewrite,
     ];
 
     // Run the rewrites over the egraph.
@@ -46,7 +46,7 @@ fn regular_small_multilayer_perceptron() {
         rewrites::bubble_access_concatenate_through_access_cartesian_product_same_item_axis(),
         rewrites::bubble_access_concatenate_through_compute_dot_product_item_axis(),
         rewrites::bubble_access_concatenate_through_compute_dot_product_not_item_axis(),
-        rewrites::systolic_array(),
+        rewrites::systolic_array().rewrite,
     ];
 
     // Run the rewrites over the egraph.
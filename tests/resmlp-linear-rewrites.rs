@@ -26,6 +26,7 @@ fn test_resmlp() {
     let mut egraph = EGraph::new(MyAnalysis {
         name_to_shape: env.clone(),
         name_to_dtype: dtype_info.iter().cloned().collect(),
+        shape_cache: None,
     });
     let mut rws = vec![
         //    glenside::language::rewrites::bubble_reshape_through_compute_dot_product(),
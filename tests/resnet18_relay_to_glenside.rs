@@ -306,6 +306,7 @@ def @main(%data: Tensor[(1, 3, 224, 224), float32], %bn_data_gamma: Tensor[(3),
     let mut egraph = EGraph::new(MyAnalysis {
         name_to_shape: env.clone(),
         name_to_dtype: dtypes_vec.into_iter().collect(),
+        shape_cache: None,
     });
 
     egraph.add_expr(&expr);
@@ -5,9 +5,9 @@ use std::{
     str::FromStr,
 };
 
-use approx::AbsDiffEq;
 use egg::RecExpr;
 use glenside::language::{interpreter::interpret, Language};
+use glenside::testing::{assert_close, Tolerance};
 use ndarray::ArrayD;
 use ndarray_npy::read_npy;
 
@@ -48,12 +48,13 @@ fn run_glenside_examples() {
                 .unwrap();
         match interpret(&expr, expr.as_ref().len() - 1, &env) {
             glenside::language::interpreter::Value::Access(a) => {
-                assert!(a.tensor.abs_diff_eq(
+                assert_close(
+                    &a.tensor,
                     &read_npy::<_, ArrayD<f64>>(output_file.unwrap())
                         .unwrap()
                         .into_dyn(),
-                    1e-7
-                ));
+                    Tolerance::absolute(1e-7),
+                );
             }
             _ => panic!(),
         }
@@ -51,6 +51,7 @@ fn conv2d_im2col_tensorize_to_smaller_array_with_padding_and_slicing() {
     let mut egraph = egg::EGraph::<Language, MyAnalysis>::new(MyAnalysis {
         name_to_shape: map,
         name_to_dtype: HashMap::default(),
+        shape_cache: None,
     });
     let id = egraph.add_expr(&expr);
 
@@ -64,7 +65,7 @@ fn conv2d_im2col_tensorize_to_smaller_array_with_padding_and_slicing() {
         glenside::language::rewrites::bubble_access_concatenate_through_compute_dot_product_item_axis(),
         glenside::language::rewrites::bubble_access_concatenate_through_compute_dot_product_not_item_axis(),
         glenside::language::rewrites::bubble_access_slice_through_access_pad_inequal_axes(),
-        glenside::language::rewrites::systolic_array(),
+        glenside::language::rewrites::systolic_array().rewrite,
         glenside::language::rewrites::pad_slice_accesses(
             0,
             PadSliceStrategy::PadToClosestMultipleOf {
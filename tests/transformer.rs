@@ -34,6 +34,7 @@ fn transformer() {
     let mut egraph = EGraph::new(MyAnalysis {
         name_to_shape: env.clone(),
         name_to_dtype: dtypes_vec.into_iter().collect(),
+        shape_cache: None,
     });
     let id = egraph.add_expr(&expr);
 
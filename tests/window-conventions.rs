@@ -0,0 +1,196 @@
+mod common;
+
+use common::load_npy;
+use egg::{EGraph, RecExpr};
+use glenside::language::interpreter::{interpret, Environment, Value};
+use glenside::language::lowering::lower;
+use glenside::language::windows::access_windows;
+use glenside::language::*;
+use ndarray::{ArrayD, IxDyn};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Max pools a 1x`n`x`n` tensor holding 0 through `n`² - 1, windowing with
+/// `convention`, and checks the result's shape in the analysis against its
+/// shape in the interpreter, returning the result.
+fn max_pool_n(n: usize, kernel: usize, stride: usize, convention: WindowConvention) -> ArrayD<f64> {
+    let mut expr = RecExpr::from_str("(access (access-tensor t) 1)").unwrap();
+    let windows = access_windows(
+        &mut expr,
+        3.into(),
+        &[kernel, kernel],
+        &[stride, stride],
+        convention,
+        PadType::MinPadding,
+    );
+    let reduce_max = expr.add(Language::ComputeType(ComputeType::ReduceMax));
+    expr.add(Language::Compute([reduce_max, windows]));
+
+    let mut env = Environment::new();
+    env.insert("t", input(n));
+    let result = match interpret(&expr, expr.as_ref().len() - 1, &env) {
+        Value::Access(a) => a.tensor,
+        _ => panic!(),
+    };
+
+    let mut name_to_shape = HashMap::default();
+    name_to_shape.insert("t".to_string(), vec![1, n, n]);
+    let mut egraph = EGraph::new(MyAnalysis {
+        name_to_shape,
+        name_to_dtype: HashMap::default(),
+        shape_cache: None,
+    });
+    let id = egraph.add_expr(&expr);
+    match &egraph[id].data {
+        MyAnalysisData::AccessPattern(a) => assert_eq!(a.as_vec(), result.shape()),
+        _ => panic!(),
+    }
+
+    result
+}
+
+fn input(n: usize) -> ArrayD<f64> {
+    ArrayD::from_shape_fn(IxDyn(&[1, n, n]), |index| (index[1] * n + index[2]) as f64)
+}
+
+/// The windows along an axis of length `n`, as ranges of the unpadded axis,
+/// following the definitions of PyTorch's
+/// `torch.nn.functional.max_pool2d(t, k, s, ceil_mode=...)` and TensorFlow's
+/// `tf.nn.max_pool2d(t, k, s, padding="SAME")`. Written independently of
+/// [`WindowConvention`], as a reference for it.
+fn reference_windows(
+    n: usize,
+    k: usize,
+    s: usize,
+    convention: WindowConvention,
+) -> Vec<std::ops::Range<usize>> {
+    let (num_windows, pad_before) = match convention {
+        WindowConvention::Floor => ((n - k) / s + 1, 0),
+        WindowConvention::Ceil => {
+            // PyTorch drops the last window if it starts in the padding.
+            let num_windows = ((n - k) as f64 / s as f64).ceil() as usize + 1;
+            if (num_windows - 1) * s >= n {
+                (num_windows - 1, 0)
+            } else {
+                (num_windows, 0)
+            }
+        }
+        WindowConvention::Same => {
+            let num_windows = (n as f64 / s as f64).ceil() as usize;
+            let padding = ((num_windows - 1) * s + k).saturating_sub(n);
+            (num_windows, padding / 2)
+        }
+    };
+    (0..num_windows)
+        .map(|i| {
+            let start = (i * s) as isize - pad_before as isize;
+            let end = start + k as isize;
+            (start.max(0) as usize)..(end.min(n as isize) as usize)
+        })
+        .collect()
+}
+
+/// Max pools [`input`]`(n)` by the reference windows. Pooling ignores
+/// padding.
+fn reference_max_pool(n: usize, k: usize, s: usize, convention: WindowConvention) -> ArrayD<f64> {
+    let t = input(n);
+    let windows = reference_windows(n, k, s, convention);
+    ArrayD::from_shape_fn(IxDyn(&[1, windows.len(), windows.len()]), |index| {
+        let mut max = std::f64::NEG_INFINITY;
+        for y in windows[index[1]].clone() {
+            for x in windows[index[2]].clone() {
+                max = max.max(t[[0, y, x].as_ref()]);
+            }
+        }
+        max
+    })
+}
+
+#[test]
+fn matches_reference() {
+    for &convention in &[
+        WindowConvention::Floor,
+        WindowConvention::Ceil,
+        WindowConvention::Same,
+    ] {
+        for n in 3..8 {
+            for kernel in 1..=3 {
+                for stride in 1..=3 {
+                    assert_eq!(
+                        max_pool_n(n, kernel, stride, convention),
+                        reference_max_pool(n, kernel, stride, convention),
+                        "{:?} windows of length {} spaced {} apart over {} elements",
+                        convention,
+                        kernel,
+                        stride,
+                        n
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Max pools the input generated by data/window_conventions.py under each
+/// convention, and checks the result against PyTorch's, both as is and
+/// lowered to `access-pad`s and an `access-windows`.
+#[test]
+fn matches_pytorch() {
+    let load = |name: &str| {
+        load_npy::<f32>(&format!(
+            "{}/data/window_conventions_{}.npy",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        ))
+    };
+    let mut env = Environment::new();
+    env.insert("t", load("input"));
+    let analysis = || {
+        let mut name_to_shape = HashMap::default();
+        name_to_shape.insert("t".to_string(), vec![1, 2, 9, 7]);
+        MyAnalysis {
+            name_to_shape,
+            name_to_dtype: HashMap::default(),
+            shape_cache: None,
+        }
+    };
+
+    for &(name, convention) in &[
+        ("floor", WindowConvention::Floor),
+        ("ceil", WindowConvention::Ceil),
+        ("same", WindowConvention::Same),
+    ] {
+        let expected = load(name);
+        let expr = RecExpr::<Language>::from_str(&format!(
+            "(compute reduce-max
+              (access-windows-with-convention
+               (access (access-tensor t) 2)
+               (shape 4 3)
+               (shape 2 3)
+               {}
+               min-padding))",
+            convention
+        ))
+        .unwrap();
+
+        let mut egraph = EGraph::new(analysis());
+        let id = egraph.add_expr(&expr);
+        match &egraph[id].data {
+            MyAnalysisData::AccessPattern(a) => assert_eq!(a.as_vec(), expected.shape()),
+            _ => panic!(),
+        }
+
+        let lowered = lower(&expr, analysis(), false);
+        assert!(lowered
+            .as_ref()
+            .iter()
+            .all(|node| !matches!(node, Language::AccessWindowsWithConvention(_))));
+
+        for expr in &[expr, lowered] {
+            match interpret(expr, expr.as_ref().len() - 1, &env) {
+                Value::Access(a) => assert_eq!(a.tensor, expected, "{}", convention),
+                _ => panic!(),
+            }
+        }
+    }
+}